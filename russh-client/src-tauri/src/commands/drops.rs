@@ -0,0 +1,178 @@
+//! P2P file drop Tauri commands
+//!
+//! Sends files and folders straight to another device. Peers may drop files
+//! here once they have been connected to, and each drop still has to be
+//! accepted: a `p2p-transfer-request` event asks about it, answered with
+//! `p2p_receive_accept`. Accepted files go to the download folder. Both
+//! directions report `p2p-transfer-progress` events per file.
+
+use russh_ssh::events::{EventBus, EventKind, EventSubscription, RusshEvent};
+use russh_ssh::p2p::{FileTransfer, P2PConnectionManager, PeerCapability, PeerTicket};
+use russh_ssh::NodeId;
+use serde::Serialize;
+use std::path::Path;
+use tauri::{AppHandle, Emitter, State, Window};
+use tokio::sync::broadcast::error::RecvError;
+
+use super::p2p::ensure_p2p_initialized;
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// Transfer progress event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DropProgress {
+    pub transfer_id: String,
+    /// `send` or `receive`
+    pub direction: String,
+    /// Path of the file within the drop
+    pub path: String,
+    pub bytes: u64,
+    pub total: u64,
+    /// Whether this file is complete
+    pub done: bool,
+}
+
+/// Outcome of a drop sent to a peer
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DropReport {
+    pub transfer_id: String,
+    pub peer_id: String,
+    pub file_count: usize,
+    pub bytes_transferred: u64,
+    /// Bytes the peer already had, from an earlier interrupted drop
+    pub resumed_bytes: u64,
+}
+
+/// Send a file or folder to a peer, once it accepts
+///
+/// `peer_id` may be a connection ticket, a `russh://` URI or a bare NodeId.
+#[tauri::command]
+pub async fn p2p_send_file(
+    state: State<'_, AppState>,
+    window: Window,
+    peer_id: String,
+    path: String,
+) -> Result<DropReport, AppError> {
+    let ticket: PeerTicket = peer_id
+        .parse()
+        .map_err(|e| AppError::P2PConnectionFailed(format!("Invalid peer ID: {}", e)))?;
+    let peer = ticket.node_id();
+    tracing::info!("Sending {} to {}", path, peer);
+
+    let (_, manager) = ensure_p2p_initialized(&state).await?;
+    manager
+        .connect_ticket(&ticket)
+        .await
+        .map_err(|e| AppError::P2PConnectionFailed(e.to_string()))?;
+    // Lets the peer drop files back
+    allow_drops(&manager, &peer);
+
+    let bus = EventBus::new();
+    let progress = tokio::spawn(forward_progress(
+        bus.subscribe_to(&[EventKind::Transfer]),
+        window.app_handle().clone(),
+        "send",
+    ));
+    let transfer = FileTransfer::new(manager, state.drops().download_dir()).with_event_bus(bus);
+    let result = transfer.send(peer, Path::new(&path)).await;
+    progress.abort();
+
+    let report = result.map_err(|e| {
+        tracing::error!("Sending {} failed: {}", path, e);
+        AppError::TransferFailed(e.to_string())
+    })?;
+    tracing::info!(
+        "Sent {} files ({} bytes) to {}",
+        report.files.len(),
+        report.bytes_transferred,
+        peer
+    );
+    Ok(DropReport {
+        transfer_id: report.id.to_string(),
+        peer_id: peer.to_string(),
+        file_count: report.files.len(),
+        bytes_transferred: report.bytes_transferred,
+        resumed_bytes: report.resumed_bytes,
+    })
+}
+
+/// Accept or decline a drop a peer wants to send
+#[tauri::command]
+pub async fn p2p_receive_accept(
+    state: State<'_, AppState>,
+    transfer_id: String,
+    accept: Option<bool>,
+) -> Result<(), AppError> {
+    let accept = accept.unwrap_or(true);
+    tracing::info!(
+        "{} drop {}",
+        if accept { "Accepting" } else { "Declining" },
+        transfer_id
+    );
+
+    if !state.drops().answer(&transfer_id, accept).await {
+        return Err(AppError::TransferNotFound(transfer_id));
+    }
+    Ok(())
+}
+
+/// Let a peer drop files here; each drop still needs accepting
+pub fn allow_drops(manager: &P2PConnectionManager, peer: &NodeId) {
+    if let Some(acl) = manager.acl() {
+        if let Err(e) = acl.grant(peer, [PeerCapability::FileDrop]) {
+            tracing::warn!("Failed to allow drops from {}: {}", peer, e);
+        }
+    }
+}
+
+/// Send incoming drop requests and their progress to the frontend
+pub fn forward_drops(state: &AppState, app: AppHandle) {
+    let drops = state.drops();
+    let mut requests = drops.subscribe();
+    tauri::async_runtime::spawn(forward_progress(
+        drops.events().subscribe_to(&[EventKind::Transfer]),
+        app.clone(),
+        "receive",
+    ));
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match requests.recv().await {
+                Ok(request) => {
+                    tracing::info!(
+                        "{} wants to send {} files",
+                        request.peer_id,
+                        request.file_count
+                    );
+                    app.emit("p2p-transfer-request", &request).ok();
+                }
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+async fn forward_progress(mut events: EventSubscription, app: AppHandle, direction: &'static str) {
+    while let Some(event) = events.recv().await {
+        if let RusshEvent::Transfer {
+            transfer_id,
+            path,
+            bytes,
+            total,
+            done,
+        } = event
+        {
+            let progress = DropProgress {
+                transfer_id: transfer_id.to_string(),
+                direction: direction.to_string(),
+                path,
+                bytes,
+                total: total.unwrap_or(bytes),
+                done,
+            };
+            app.emit("p2p-transfer-progress", &progress).ok();
+        }
+    }
+}
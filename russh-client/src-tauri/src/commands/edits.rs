@@ -0,0 +1,319 @@
+//! Quick-edit Tauri commands
+//!
+//! `file_edit_open` downloads a remote file to a temporary folder and opens
+//! it in the local editor. Whenever the editor saves it, the file goes back
+//! to the server, unless the remote file changed since it was opened: that
+//! is a conflict, and the local copy waits for `file_edit_save` with `force`.
+//! Each attempt is reported in a `file-edit-status` event.
+
+use russh_ssh::encryption::hash::{hash_data, ContentHash};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tauri::{Emitter, State, Window};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::state::{edit_dir, AppState, EditInfo, EditStatus, RemoteVersion};
+
+/// Largest file that can be opened for editing
+const MAX_EDIT_SIZE: u64 = 10 * 1024 * 1024;
+
+/// How often the local copy is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Download a remote file and open it in the local editor
+///
+/// With `launch` set to false the file is only downloaded, for the frontend
+/// to open itself.
+#[tauri::command]
+pub async fn file_edit_open(
+    state: State<'_, AppState>,
+    window: Window,
+    session_id: String,
+    remote_path: String,
+    launch: Option<bool>,
+) -> Result<EditInfo, AppError> {
+    tracing::info!(
+        "Opening {} for editing on session {}",
+        remote_path,
+        session_id
+    );
+
+    let client = state
+        .get_session_client(&session_id)
+        .await
+        .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
+    let (data, modified) = {
+        let client = client.lock().await;
+        let entry = client
+            .stat_path(&remote_path)
+            .await
+            .map_err(|e| AppError::FileOperationFailed(e.to_string()))?;
+        if entry.is_dir {
+            return Err(AppError::FileOperationFailed(format!(
+                "{} is a directory",
+                remote_path
+            )));
+        }
+        if entry.size > MAX_EDIT_SIZE {
+            return Err(AppError::FileOperationFailed(format!(
+                "{} is too large to edit ({} bytes)",
+                remote_path, entry.size
+            )));
+        }
+        let data = client
+            .read_file(&remote_path)
+            .await
+            .map_err(|e| AppError::FileOperationFailed(e.to_string()))?;
+        (data, entry.modified)
+    };
+
+    let edit_id = Uuid::new_v4().to_string();
+    let dir = edit_dir(&edit_id);
+    tokio::fs::create_dir_all(&dir).await?;
+    // The copy may hold anything the server does, so only the user gets in
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).await?;
+    }
+    let name = Path::new(&remote_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "file".to_string());
+    let local_path = dir.join(name);
+    tokio::fs::write(&local_path, &data).await?;
+
+    let hash = hash_data(&data);
+    let info = EditInfo {
+        edit_id: edit_id.clone(),
+        session_id,
+        remote_path,
+        local_path: local_path.to_string_lossy().into_owned(),
+        status: EditStatus::Synced,
+        error: None,
+        opened_at: chrono::Utc::now(),
+        saved_at: None,
+    };
+    let edits = state.edits();
+    edits
+        .add(info.clone(), RemoteVersion { modified, hash }, hash)
+        .await;
+    let watcher = tokio::spawn(watch(state.inner().clone(), window, edit_id.clone()));
+    edits.set_watcher(&edit_id, watcher).await;
+
+    if launch.unwrap_or(true) {
+        open_in_editor(&local_path).await?;
+    }
+    Ok(info)
+}
+
+/// Upload the local copy of an edit now
+///
+/// Does not overwrite a remote file changed by someone else unless `force`
+/// is set; the returned status is then `conflict`.
+#[tauri::command]
+pub async fn file_edit_save(
+    state: State<'_, AppState>,
+    window: Window,
+    edit_id: String,
+    force: Option<bool>,
+) -> Result<EditInfo, AppError> {
+    tracing::info!("Saving edit {}", edit_id);
+    let info = upload(&state, &window, &edit_id, force.unwrap_or(false)).await?;
+    match (info.status, &info.error) {
+        (EditStatus::Failed, Some(error)) => Err(AppError::FileOperationFailed(error.clone())),
+        _ => Ok(info),
+    }
+}
+
+/// Stop editing a file and delete the local copy
+///
+/// Changes not uploaded yet are lost.
+#[tauri::command]
+pub async fn file_edit_close(state: State<'_, AppState>, edit_id: String) -> Result<(), AppError> {
+    tracing::info!("Closing edit {}", edit_id);
+    state
+        .edits()
+        .remove(&edit_id)
+        .await
+        .ok_or_else(|| AppError::EditNotFound(edit_id.clone()))?;
+    match tokio::fs::remove_dir_all(edit_dir(&edit_id)).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// List files open for editing
+#[tauri::command]
+pub async fn file_edit_list(state: State<'_, AppState>) -> Result<Vec<EditInfo>, AppError> {
+    Ok(state.edits().list().await)
+}
+
+/// Upload the local copy whenever the editor saves it
+///
+/// Waits for the file to stay the same for one check, so a save still being
+/// written is not sent half-done. Stops once the edit is closed.
+async fn watch(state: AppState, window: Window, edit_id: String) {
+    let Some(info) = state.edits().get(&edit_id).await else {
+        return;
+    };
+    let local_path = info.local_path;
+    let mut seen = stamp(&local_path).await;
+    let mut handled = seen;
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        let current = stamp(&local_path).await;
+        if current != seen {
+            seen = current;
+            continue;
+        }
+        if current == handled || current.is_none() {
+            continue;
+        }
+        handled = current;
+
+        // Editors often touch a file without changing it
+        let Ok(data) = tokio::fs::read(&local_path).await else {
+            continue;
+        };
+        match state.edits().local_hash(&edit_id).await {
+            Some(hash) if hash == hash_data(&data) => continue,
+            Some(_) => {}
+            None => break,
+        }
+        if let Err(e) = upload(&state, &window, &edit_id, false).await {
+            tracing::warn!("Saving edit {} failed: {}", edit_id, e);
+        }
+    }
+}
+
+/// Modification time and size of the local copy
+async fn stamp(path: &str) -> Option<(SystemTime, u64)> {
+    let metadata = tokio::fs::metadata(path).await.ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Result of sending the local copy
+enum Outcome {
+    Saved(RemoteVersion),
+    Conflict(String),
+}
+
+/// Upload the local copy, recording and reporting how it went
+async fn upload(
+    state: &AppState,
+    window: &Window,
+    edit_id: &str,
+    force: bool,
+) -> Result<EditInfo, AppError> {
+    let edits = state.edits();
+    let info = edits
+        .get(edit_id)
+        .await
+        .ok_or_else(|| AppError::EditNotFound(edit_id.to_string()))?;
+    let data = tokio::fs::read(&info.local_path).await?;
+    let hash = hash_data(&data);
+
+    let (info, remote) = edits.begin_upload(edit_id).await?;
+    window.emit("file-edit-status", &info).ok();
+    let updated = match push(state, &info, &remote, &data, hash, force).await {
+        Ok(Outcome::Saved(remote)) => {
+            tracing::info!("Saved {} ({} bytes)", info.remote_path, data.len());
+            edits.finish_upload(edit_id, remote, hash).await
+        }
+        Ok(Outcome::Conflict(reason)) => {
+            tracing::warn!("Not saving {}: {}", info.remote_path, reason);
+            edits
+                .fail_upload(edit_id, EditStatus::Conflict, reason, hash)
+                .await
+        }
+        Err(e) => {
+            edits
+                .fail_upload(edit_id, EditStatus::Failed, e.to_string(), hash)
+                .await
+        }
+    }
+    .ok_or_else(|| AppError::EditNotFound(edit_id.to_string()))?;
+
+    window.emit("file-edit-status", &updated).ok();
+    Ok(updated)
+}
+
+async fn push(
+    state: &AppState,
+    info: &EditInfo,
+    remote: &RemoteVersion,
+    data: &[u8],
+    hash: ContentHash,
+    force: bool,
+) -> Result<Outcome, AppError> {
+    let client = state
+        .get_session_client(&info.session_id)
+        .await
+        .ok_or_else(|| AppError::SessionNotFound(info.session_id.clone()))?;
+    let client = client.lock().await;
+
+    if !force {
+        if !client.path_exists(&info.remote_path).await.unwrap_or(true) {
+            return Ok(Outcome::Conflict("The remote file was removed".to_string()));
+        }
+        let current = client
+            .stat_path(&info.remote_path)
+            .await
+            .map_err(|e| AppError::FileOperationFailed(e.to_string()))?;
+        // A new modification time alone may just be a touch
+        if current.modified != remote.modified {
+            let current = client
+                .read_file(&info.remote_path)
+                .await
+                .map_err(|e| AppError::FileOperationFailed(e.to_string()))?;
+            if hash_data(&current) != remote.hash {
+                return Ok(Outcome::Conflict(
+                    "The remote file changed since it was opened".to_string(),
+                ));
+            }
+        }
+    }
+
+    client
+        .write_file(&info.remote_path, data)
+        .await
+        .map_err(|e| AppError::FileOperationFailed(e.to_string()))?;
+    let modified = client
+        .stat_path(&info.remote_path)
+        .await
+        .map(|entry| entry.modified)
+        .unwrap_or_default();
+    Ok(Outcome::Saved(RemoteVersion { modified, hash }))
+}
+
+/// Open a file with the desktop's default app for it
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+async fn open_in_editor(path: &Path) -> Result<(), AppError> {
+    #[cfg(target_os = "macos")]
+    let mut command = tokio::process::Command::new("open");
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = tokio::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = tokio::process::Command::new("xdg-open");
+
+    command
+        .arg(path)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| AppError::FileOperationFailed(format!("Failed to open an editor: {}", e)))
+}
+
+#[cfg(any(target_os = "android", target_os = "ios"))]
+async fn open_in_editor(_path: &Path) -> Result<(), AppError> {
+    Err(AppError::FileOperationFailed(
+        "Opening files in a local editor is not supported on this device".to_string(),
+    ))
+}
@@ -1,12 +1,18 @@
 //! File transfer Tauri commands
 
+use russh_ssh::ssh::RemoteFileEntry;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
 use tauri::{Emitter, State, Window};
 use uuid::Uuid;
 
+use super::transfers;
 use crate::error::AppError;
-use crate::state::AppState;
+use crate::state::{AppState, TransferDirection};
+
+/// Entries per `file-list-batch` event unless the caller asks otherwise
+const DEFAULT_BATCH_SIZE: usize = 500;
+/// Largest batch a caller may ask for
+const MAX_BATCH_SIZE: usize = 5000;
 
 /// File entry information
 #[derive(Debug, Serialize, Deserialize)]
@@ -21,17 +27,36 @@ pub struct FileEntry {
     pub owner: String,
 }
 
-/// Transfer progress information
-#[derive(Debug, Clone, Serialize)]
+impl From<RemoteFileEntry> for FileEntry {
+    fn from(entry: RemoteFileEntry) -> Self {
+        Self {
+            name: entry.name,
+            path: entry.path,
+            is_dir: entry.is_dir,
+            size: entry.size,
+            permissions: entry.permissions,
+            modified: entry.modified,
+            owner: entry.owner,
+        }
+    }
+}
+
+/// Part of a streamed directory listing
+#[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct TransferProgress {
-    pub transfer_id: String,
-    pub filename: String,
-    pub bytes_transferred: u64,
-    pub total_bytes: u64,
-    pub speed_bps: u64,
-    pub eta_seconds: u64,
-    pub status: String,
+pub struct FileListBatch {
+    pub listing_id: String,
+    pub session_id: String,
+    pub path: String,
+    pub entries: Vec<FileEntry>,
+    /// Entries sent so far, this batch included
+    pub loaded: usize,
+    /// Entries in the directory, once known
+    pub total: Option<usize>,
+    /// Set on the last event of a listing
+    pub done: bool,
+    pub cancelled: bool,
+    pub error: Option<String>,
 }
 
 /// List directory contents
@@ -58,21 +83,109 @@ pub async fn file_list(
     })?;
 
     // Convert to frontend format
-    Ok(entries
-        .into_iter()
-        .map(|e| FileEntry {
-            name: e.name,
-            path: e.path,
-            is_dir: e.is_dir,
-            size: e.size,
-            permissions: e.permissions,
-            modified: e.modified,
-            owner: e.owner,
-        })
-        .collect())
+    Ok(entries.into_iter().map(FileEntry::from).collect())
+}
+
+/// List directory contents in batches
+///
+/// Returns the listing ID at once and sends the entries as `file-list-batch`
+/// events, so a directory with many thousands of files never arrives in one
+/// piece. The caller may pick the ID to be ready for the first batch before
+/// this returns. The last event has `done` set, also after an error or
+/// `file_list_cancel`.
+#[tauri::command]
+pub async fn file_list_stream(
+    state: State<'_, AppState>,
+    window: Window,
+    session_id: String,
+    path: String,
+    listing_id: Option<String>,
+    batch_size: Option<usize>,
+) -> Result<String, AppError> {
+    tracing::info!("Streaming directory {} for session {}", path, session_id);
+
+    let client = state
+        .get_session_client(&session_id)
+        .await
+        .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
+
+    let listing_id = listing_id.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let listings = state.listings();
+    let token = listings.start(&listing_id).await.ok_or_else(|| {
+        AppError::FileOperationFailed(format!("Listing {} is already running", listing_id))
+    })?;
+    let batch_size = batch_size
+        .unwrap_or(DEFAULT_BATCH_SIZE)
+        .clamp(1, MAX_BATCH_SIZE);
+
+    let id = listing_id.clone();
+    tokio::spawn(async move {
+        let mut batch = FileListBatch {
+            listing_id: id.clone(),
+            session_id,
+            path,
+            entries: Vec::new(),
+            loaded: 0,
+            total: None,
+            done: false,
+            cancelled: false,
+            error: None,
+        };
+
+        let listed = tokio::select! {
+            result = async { client.lock().await.list_directory(&batch.path).await } => Some(result),
+            _ = token.cancelled() => None,
+        };
+        match listed {
+            Some(Ok(entries)) => {
+                batch.total = Some(entries.len());
+                let mut entries = entries.into_iter().map(FileEntry::from).peekable();
+                while entries.peek().is_some() && !token.is_cancelled() {
+                    batch.entries = entries.by_ref().take(batch_size).collect();
+                    batch.loaded += batch.entries.len();
+                    batch.done = entries.peek().is_none();
+                    if window.emit("file-list-batch", &batch).is_err() {
+                        break;
+                    }
+                    // Let other work in between the batches of a huge listing
+                    tokio::task::yield_now().await;
+                }
+                batch.entries.clear();
+            }
+            Some(Err(e)) => {
+                tracing::error!("Failed to list directory: {}", e);
+                batch.error = Some(e.to_string());
+            }
+            None => {}
+        }
+
+        if !batch.done {
+            batch.done = true;
+            batch.cancelled = token.is_cancelled();
+            window.emit("file-list-batch", &batch).ok();
+        }
+        listings.finish(&id).await;
+    });
+
+    Ok(listing_id)
+}
+
+/// Stop a streamed directory listing
+#[tauri::command]
+pub async fn file_list_cancel(
+    state: State<'_, AppState>,
+    listing_id: String,
+) -> Result<(), AppError> {
+    if !state.listings().cancel(&listing_id).await {
+        tracing::debug!("Listing {} already finished", listing_id);
+    }
+    Ok(())
 }
 
-/// Upload file to remote server
+/// Queue a file upload to the remote server
+///
+/// Returns the transfer ID at once; progress arrives as
+/// `transfer://progress` events.
 #[tauri::command]
 pub async fn file_upload(
     state: State<'_, AppState>,
@@ -88,72 +201,32 @@ pub async fn file_upload(
         session_id
     );
 
-    // Get SSH client
-    let client = state
-        .get_session_client(&session_id)
-        .await
-        .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
-
-    let transfer_id = Uuid::new_v4().to_string();
-    let filename = Path::new(&local_path)
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_else(|| local_path.clone());
-
-    // Read local file
-    let data = tokio::fs::read(&local_path)
+    let metadata = tokio::fs::metadata(&local_path)
         .await
         .map_err(|e| AppError::FileOperationFailed(format!("Failed to read local file: {}", e)))?;
-
-    let total_bytes = data.len() as u64;
-    let tid = transfer_id.clone();
-    let fname = filename.clone();
-    let win = window.clone();
-
-    // Emit initial progress
-    win.emit(
-        "transfer-progress",
-        TransferProgress {
-            transfer_id: tid.clone(),
-            filename: fname.clone(),
-            bytes_transferred: 0,
-            total_bytes,
-            speed_bps: 0,
-            eta_seconds: 0,
-            status: "active".to_string(),
-        },
-    )
-    .ok();
-
-    // Upload file
-    {
-        let client = client.lock().await;
-        client.write_file(&remote_path, &data).await.map_err(|e| {
-            tracing::error!("Failed to upload file: {}", e);
-            AppError::TransferFailed(e.to_string())
-        })?;
+    if !metadata.is_file() {
+        return Err(AppError::FileOperationFailed(format!(
+            "{} is not a file",
+            local_path
+        )));
     }
 
-    // Emit completion
-    window
-        .emit(
-            "transfer-progress",
-            TransferProgress {
-                transfer_id: tid,
-                filename: fname,
-                bytes_transferred: total_bytes,
-                total_bytes,
-                speed_bps: 0,
-                eta_seconds: 0,
-                status: "completed".to_string(),
-            },
-        )
-        .ok();
-
-    Ok(transfer_id)
+    transfers::enqueue(
+        &state,
+        window,
+        session_id,
+        TransferDirection::Upload,
+        local_path,
+        remote_path,
+        metadata.len(),
+    )
+    .await
 }
 
-/// Download file from remote server
+/// Queue a file download from the remote server
+///
+/// Returns the transfer ID at once; progress arrives as
+/// `transfer://progress` events.
 #[tauri::command]
 pub async fn file_download(
     state: State<'_, AppState>,
@@ -175,68 +248,22 @@ pub async fn file_download(
         .await
         .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
 
-    let transfer_id = Uuid::new_v4().to_string();
-    let filename = Path::new(&remote_path)
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_else(|| remote_path.clone());
-
     // Get file size first
     let total_bytes = {
         let client = client.lock().await;
         client.file_size(&remote_path).await.unwrap_or(0)
     };
 
-    let tid = transfer_id.clone();
-    let fname = filename.clone();
-    let win = window.clone();
-
-    // Emit initial progress
-    win.emit(
-        "transfer-progress",
-        TransferProgress {
-            transfer_id: tid.clone(),
-            filename: fname.clone(),
-            bytes_transferred: 0,
-            total_bytes,
-            speed_bps: 0,
-            eta_seconds: 0,
-            status: "active".to_string(),
-        },
+    transfers::enqueue(
+        &state,
+        window,
+        session_id,
+        TransferDirection::Download,
+        local_path,
+        remote_path,
+        total_bytes,
     )
-    .ok();
-
-    // Download file
-    let data = {
-        let client = client.lock().await;
-        client.read_file(&remote_path).await.map_err(|e| {
-            tracing::error!("Failed to download file: {}", e);
-            AppError::TransferFailed(e.to_string())
-        })?
-    };
-
-    // Write to local file
-    tokio::fs::write(&local_path, &data)
-        .await
-        .map_err(|e| AppError::FileOperationFailed(format!("Failed to write local file: {}", e)))?;
-
-    // Emit completion
-    window
-        .emit(
-            "transfer-progress",
-            TransferProgress {
-                transfer_id: tid,
-                filename: fname,
-                bytes_transferred: data.len() as u64,
-                total_bytes: data.len() as u64,
-                speed_bps: 0,
-                eta_seconds: 0,
-                status: "completed".to_string(),
-            },
-        )
-        .ok();
-
-    Ok(transfer_id)
+    .await
 }
 
 /// Delete file or directory
@@ -0,0 +1,280 @@
+//! Port forwarding Tauri commands
+//!
+//! Forwards belong to an SSH session and stop with it. While a forward runs,
+//! `forward-stats` events report its traffic about once a second, and
+//! `forward-status` events announce when it starts, stops or fails.
+
+use russh_ssh::ssh::{ForwardHandle, PortForward, PortForwarder};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{Emitter, State, Window};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// How often `forward-stats` events are sent
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Forward specification shared with the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ForwardSpec {
+    /// Listen locally and connect out from the server
+    #[serde(rename_all = "camelCase")]
+    Local {
+        local_port: u16,
+        remote_host: String,
+        remote_port: u16,
+    },
+    /// Listen on the server and connect out from this machine
+    #[serde(rename_all = "camelCase")]
+    Remote {
+        remote_port: u16,
+        local_host: String,
+        local_port: u16,
+    },
+    /// SOCKS5 proxy on a local port
+    #[serde(rename_all = "camelCase")]
+    Dynamic { local_port: u16 },
+}
+
+impl From<ForwardSpec> for PortForward {
+    fn from(spec: ForwardSpec) -> Self {
+        match spec {
+            ForwardSpec::Local {
+                local_port,
+                remote_host,
+                remote_port,
+            } => PortForward::Local {
+                local_port,
+                remote_host,
+                remote_port,
+            },
+            ForwardSpec::Remote {
+                remote_port,
+                local_host,
+                local_port,
+            } => PortForward::Remote {
+                remote_port,
+                local_host,
+                local_port,
+            },
+            ForwardSpec::Dynamic { local_port } => PortForward::Dynamic { local_port },
+        }
+    }
+}
+
+impl From<PortForward> for ForwardSpec {
+    fn from(forward: PortForward) -> Self {
+        match forward {
+            PortForward::Local {
+                local_port,
+                remote_host,
+                remote_port,
+            } => ForwardSpec::Local {
+                local_port,
+                remote_host,
+                remote_port,
+            },
+            PortForward::Remote {
+                remote_port,
+                local_host,
+                local_port,
+            } => ForwardSpec::Remote {
+                remote_port,
+                local_host,
+                local_port,
+            },
+            PortForward::Dynamic { local_port } => ForwardSpec::Dynamic { local_port },
+        }
+    }
+}
+
+/// Active forward response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardResponse {
+    pub forward_id: String,
+    pub session_id: String,
+    pub forward: ForwardSpec,
+    pub bytes_transferred: u64,
+}
+
+impl ForwardResponse {
+    fn new(session_id: &str, handle: &ForwardHandle) -> Self {
+        Self {
+            forward_id: handle.id.to_string(),
+            session_id: session_id.to_string(),
+            forward: handle.config.clone().into(),
+            bytes_transferred: handle.bytes(),
+        }
+    }
+}
+
+/// Start a port forward on a session
+#[tauri::command]
+pub async fn forward_start(
+    state: State<'_, AppState>,
+    window: Window,
+    session_id: String,
+    forward: ForwardSpec,
+) -> Result<ForwardResponse, AppError> {
+    tracing::info!("Starting {:?} forward on session {}", forward, session_id);
+
+    let client = state
+        .get_session_client(&session_id)
+        .await
+        .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
+
+    let result = {
+        let client = client.lock().await;
+        client.start_forward(forward.clone().into()).await
+    };
+    let handle = match result {
+        Ok(handle) => handle,
+        Err(e) => {
+            tracing::error!("Failed to start forward: {}", e);
+            window
+                .emit(
+                    "forward-status",
+                    serde_json::json!({
+                        "sessionId": session_id,
+                        "status": "failed",
+                        "forward": forward,
+                        "error": e.to_string(),
+                    }),
+                )
+                .ok();
+            return Err(e.into());
+        }
+    };
+
+    let monitor = spawn_stats_monitor(window.clone(), session_id.clone(), handle.clone());
+    state
+        .get_session_mut(&session_id, |s| {
+            s.forward_monitors.insert(handle.id, monitor);
+        })
+        .await;
+
+    let response = ForwardResponse::new(&session_id, &handle);
+    window
+        .emit(
+            "forward-status",
+            serde_json::json!({
+                "sessionId": session_id,
+                "forwardId": response.forward_id,
+                "status": "active",
+                "forward": response.forward,
+            }),
+        )
+        .ok();
+
+    Ok(response)
+}
+
+/// Stop a port forward
+#[tauri::command]
+pub async fn forward_stop(
+    state: State<'_, AppState>,
+    window: Window,
+    session_id: String,
+    forward_id: String,
+) -> Result<(), AppError> {
+    tracing::info!("Stopping forward {} on session {}", forward_id, session_id);
+
+    let id =
+        Uuid::parse_str(&forward_id).map_err(|_| AppError::ForwardNotFound(forward_id.clone()))?;
+    let client = state
+        .get_session_client(&session_id)
+        .await
+        .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
+
+    {
+        let client = client.lock().await;
+        client.stop_forward(id).await?;
+    }
+    state
+        .get_session_mut(&session_id, |s| s.stop_forward_monitor(&id))
+        .await;
+
+    window
+        .emit(
+            "forward-status",
+            serde_json::json!({
+                "sessionId": session_id,
+                "forwardId": forward_id,
+                "status": "stopped",
+            }),
+        )
+        .ok();
+
+    Ok(())
+}
+
+/// List active forwards, of one session or of all of them
+#[tauri::command]
+pub async fn forward_list(
+    state: State<'_, AppState>,
+    session_id: Option<String>,
+) -> Result<Vec<ForwardResponse>, AppError> {
+    let session_ids = match session_id {
+        Some(session_id) => vec![session_id],
+        None => state
+            .list_sessions()
+            .await
+            .into_iter()
+            .map(|s| s.session_id)
+            .collect(),
+    };
+
+    let mut forwards = Vec::new();
+    for session_id in session_ids {
+        let client = state
+            .get_session_client(&session_id)
+            .await
+            .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
+        let handles = client.lock().await.list_forwards().await;
+        forwards.extend(
+            handles
+                .iter()
+                .map(|handle| ForwardResponse::new(&session_id, handle)),
+        );
+    }
+
+    Ok(forwards)
+}
+
+/// Report a forward's traffic until the task is aborted
+fn spawn_stats_monitor(
+    window: Window,
+    session_id: String,
+    handle: Arc<ForwardHandle>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(STATS_INTERVAL);
+        let mut last = 0;
+        loop {
+            interval.tick().await;
+            let bytes = handle.bytes();
+            if bytes == last {
+                continue;
+            }
+            let rate = (bytes - last) as f64 / STATS_INTERVAL.as_secs_f64();
+            last = bytes;
+            let sent = window.emit(
+                "forward-stats",
+                serde_json::json!({
+                    "sessionId": session_id,
+                    "forwardId": handle.id.to_string(),
+                    "bytesTransferred": bytes,
+                    "bytesPerSecond": rate,
+                }),
+            );
+            if sent.is_err() {
+                break;
+            }
+        }
+    })
+}
@@ -0,0 +1,289 @@
+//! SSH key management Tauri commands
+//!
+//! Keys live in the `keys` folder of the app data directory as OpenSSH
+//! files, `<name>` for the private key and `<name>.pub` next to it, so they
+//! can be used by their path like any other key. Passphrases can be kept in
+//! the OS keystore, or the vault once one is set up, and connecting with a
+//! stored key then needs no prompt.
+
+use russh_ssh::ssh::keygen::public_key_fingerprint;
+use russh_ssh::ssh::{KeyType, SshKeyPair};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::State;
+
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// Keystore account prefix for key passphrases
+const PASSPHRASE_ACCOUNT_PREFIX: &str = "key:";
+
+/// Key generation request from frontend
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyGenerateRequest {
+    pub name: String,
+    pub key_type: String,
+    pub bits: Option<usize>,
+    pub comment: Option<String>,
+    pub passphrase: Option<String>,
+    #[serde(default)]
+    pub remember_passphrase: bool,
+}
+
+/// Key import request from frontend, with either a file or the pasted key
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyImportRequest {
+    pub name: String,
+    pub path: Option<String>,
+    pub private_key: Option<String>,
+    pub passphrase: Option<String>,
+    #[serde(default)]
+    pub remember_passphrase: bool,
+}
+
+/// Stored key info
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyInfo {
+    pub name: String,
+    pub algorithm: String,
+    pub fingerprint: String,
+    pub comment: String,
+    pub encrypted: bool,
+    pub passphrase_saved: bool,
+    pub path: String,
+    pub public_key: String,
+}
+
+/// Generate a new key pair
+#[tauri::command]
+pub async fn key_generate(
+    state: State<'_, AppState>,
+    request: KeyGenerateRequest,
+) -> Result<KeyInfo, AppError> {
+    let dir = state.keys_dir();
+    check_new_key(&dir, &request.name)?;
+    let key_type = KeyType::parse(&request.key_type, request.bits)
+        .map_err(|e| AppError::KeyError(e.to_string()))?;
+    tracing::info!("Generating {} key: {}", key_type, request.name);
+
+    let comment = request.comment.unwrap_or_else(|| request.name.clone());
+    // Large RSA keys take a while
+    let key = tokio::task::spawn_blocking(move || SshKeyPair::generate(key_type, &comment))
+        .await
+        .map_err(|e| AppError::InternalError(e.to_string()))?
+        .map_err(|e| AppError::KeyError(e.to_string()))?;
+
+    store_key(
+        &state,
+        &request.name,
+        &key,
+        request.passphrase.as_deref(),
+        request.remember_passphrase,
+    )
+    .await
+}
+
+/// Import an existing private key
+#[tauri::command]
+pub async fn key_import(
+    state: State<'_, AppState>,
+    request: KeyImportRequest,
+) -> Result<KeyInfo, AppError> {
+    let dir = state.keys_dir();
+    check_new_key(&dir, &request.name)?;
+    tracing::info!("Importing key: {}", request.name);
+
+    let passphrase = request.passphrase.as_deref();
+    let key = match (&request.path, &request.private_key) {
+        (Some(source), _) => SshKeyPair::load(Path::new(source), passphrase),
+        (None, Some(pem)) => SshKeyPair::from_openssh(pem, passphrase),
+        (None, None) => {
+            return Err(AppError::KeyError(
+                "Give a key file or paste the private key".to_string(),
+            ))
+        }
+    }
+    .map_err(|e| AppError::KeyError(e.to_string()))?;
+
+    // Stays protected by the passphrase it came with
+    store_key(
+        &state,
+        &request.name,
+        &key,
+        passphrase,
+        request.remember_passphrase,
+    )
+    .await
+}
+
+/// List stored keys
+#[tauri::command]
+pub async fn key_list(state: State<'_, AppState>) -> Result<Vec<KeyInfo>, AppError> {
+    let dir = state.keys_dir();
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut keys = Vec::new();
+    for entry in entries {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if valid_key_name(&name) && dir.join(format!("{}.pub", name)).exists() {
+            match key_info(&state, &name).await {
+                Ok(info) => keys.push(info),
+                Err(e) => tracing::warn!("Skipping key {}: {}", name, e),
+            }
+        }
+    }
+    keys.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(keys)
+}
+
+/// Delete a key pair and its saved passphrase
+#[tauri::command]
+pub async fn key_delete(state: State<'_, AppState>, name: String) -> Result<(), AppError> {
+    let path = existing_key_path(&state.keys_dir(), &name)?;
+    tracing::info!("Deleting key: {}", name);
+
+    std::fs::remove_file(&path)?;
+    match std::fs::remove_file(public_key_path(&path)) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    state.delete_secret(&passphrase_account(&name)).await
+}
+
+/// Get a key's public half as an `authorized_keys` line
+#[tauri::command]
+pub async fn key_export_public(
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<String, AppError> {
+    existing_key_path(&state.keys_dir(), &name)?;
+    Ok(key_info(&state, &name).await?.public_key)
+}
+
+/// Passphrase saved for an encrypted key in the keys folder, used when
+/// connecting
+///
+/// Fails if the passphrase is in the vault and the vault is locked.
+pub async fn stored_passphrase(
+    state: &AppState,
+    key_path: &Path,
+) -> Result<Option<String>, AppError> {
+    if key_path.parent() != Some(state.keys_dir().as_path()) {
+        return Ok(None);
+    }
+    let name = match key_path.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+    if !SshKeyPair::is_encrypted_file(key_path).unwrap_or(false) {
+        return Ok(None);
+    }
+    state.get_secret(&passphrase_account(name)).await
+}
+
+/// Keystore accounts of the passphrases of the stored keys
+pub fn passphrase_accounts(keys_dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(keys_dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| valid_key_name(name))
+        .map(|name| passphrase_account(&name))
+        .collect()
+}
+
+async fn store_key(
+    state: &AppState,
+    name: &str,
+    key: &SshKeyPair,
+    passphrase: Option<&str>,
+    remember_passphrase: bool,
+) -> Result<KeyInfo, AppError> {
+    key.save(&state.keys_dir().join(name), passphrase)
+        .map_err(|e| AppError::KeyError(e.to_string()))?;
+    let account = passphrase_account(name);
+    match passphrase.filter(|p| !p.is_empty()) {
+        Some(passphrase) if remember_passphrase => {
+            state.store_secret(&account, passphrase).await?;
+        }
+        // A key stored again under this name must not get the old passphrase
+        _ => state.delete_secret(&account).await?,
+    }
+    key_info(state, name).await
+}
+
+async fn key_info(state: &AppState, name: &str) -> Result<KeyInfo, AppError> {
+    let path = state.keys_dir().join(name);
+    let public_key = std::fs::read_to_string(public_key_path(&path))?
+        .trim()
+        .to_string();
+    let fingerprint =
+        public_key_fingerprint(&public_key).map_err(|e| AppError::KeyError(e.to_string()))?;
+    let mut fields = public_key.splitn(3, ' ');
+    let algorithm = fields.next().unwrap_or_default().to_string();
+    let comment = fields.nth(1).unwrap_or_default().to_string();
+    let encrypted =
+        SshKeyPair::is_encrypted_file(&path).map_err(|e| AppError::KeyError(e.to_string()))?;
+    // Shown as not saved while the vault is locked
+    let passphrase_saved = matches!(stored_passphrase(state, &path).await, Ok(Some(_)));
+
+    Ok(KeyInfo {
+        name: name.to_string(),
+        algorithm,
+        fingerprint,
+        comment,
+        encrypted,
+        passphrase_saved,
+        path: path.to_string_lossy().into_owned(),
+        public_key,
+    })
+}
+
+/// Make sure a new key can be stored under `name` without replacing one
+fn check_new_key(dir: &Path, name: &str) -> Result<(), AppError> {
+    if !valid_key_name(name) {
+        return Err(AppError::KeyError(format!("Invalid key name: {}", name)));
+    }
+    if dir.join(name).exists() {
+        return Err(AppError::KeyError(format!("Key already exists: {}", name)));
+    }
+    Ok(())
+}
+
+fn existing_key_path(dir: &Path, name: &str) -> Result<PathBuf, AppError> {
+    let path = dir.join(name);
+    if !valid_key_name(name) || !path.is_file() {
+        return Err(AppError::KeyNotFound(name.to_string()));
+    }
+    Ok(path)
+}
+
+/// Plain file names only, so a name cannot point outside the keys folder
+fn valid_key_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && !name.ends_with(".pub")
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '@'))
+}
+
+fn public_key_path(path: &Path) -> PathBuf {
+    let mut public = path.as_os_str().to_owned();
+    public.push(".pub");
+    PathBuf::from(public)
+}
+
+fn passphrase_account(name: &str) -> String {
+    format!("{}{}", PASSPHRASE_ACCOUNT_PREFIX, name)
+}
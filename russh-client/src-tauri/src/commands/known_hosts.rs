@@ -0,0 +1,181 @@
+//! Known hosts Tauri commands
+//!
+//! Works on the user's `~/.ssh/known_hosts`, the file connections check host
+//! keys against. When a connection fails and the server turns out to present
+//! a key other than the one recorded, a `host-key-mismatch` event carries both
+//! fingerprints so the frontend can ask whether to trust the new key.
+
+use russh_ssh::ssh::keygen::public_key_fingerprint;
+use russh_ssh::ssh::known_hosts::host_pattern;
+use russh_ssh::ssh::{scan_host_keys, HostMarker, KnownHost, KnownHosts};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+use crate::error::AppError;
+
+/// How long fetching a server's host keys may take
+const SCAN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Known host entry response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownHostEntry {
+    pub hosts: Vec<String>,
+    pub hashed: bool,
+    pub marker: Option<String>,
+    pub key_type: String,
+    pub fingerprint: String,
+    pub comment: Option<String>,
+}
+
+impl From<&KnownHost> for KnownHostEntry {
+    fn from(entry: &KnownHost) -> Self {
+        Self {
+            hosts: entry.hosts.clone(),
+            hashed: entry.is_hashed(),
+            marker: entry.marker.map(|marker| {
+                match marker {
+                    HostMarker::CertAuthority => "cert-authority",
+                    HostMarker::Revoked => "revoked",
+                }
+                .to_string()
+            }),
+            key_type: entry.key_type.clone(),
+            fingerprint: entry.fingerprint().unwrap_or_default(),
+            comment: entry.comment.clone(),
+        }
+    }
+}
+
+/// Host key mismatch event payload
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostKeyMismatch {
+    pub host: String,
+    pub port: u16,
+    pub key_type: String,
+    pub known_fingerprint: String,
+    pub presented_fingerprint: String,
+}
+
+/// Path of the known_hosts file connections use
+pub fn known_hosts_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| h.join(".ssh").join("known_hosts"))
+}
+
+/// List known hosts, optionally only those matching `query`
+#[tauri::command]
+pub async fn known_hosts_list(query: Option<String>) -> Result<Vec<KnownHostEntry>, AppError> {
+    let known = open()?;
+    let entries = match query.as_deref().map(str::trim) {
+        Some(query) if !query.is_empty() => known.search(query),
+        _ => known.entries().collect(),
+    };
+    Ok(entries.into_iter().map(KnownHostEntry::from).collect())
+}
+
+/// Forget the keys recorded for a host; returns how many lines changed
+#[tauri::command]
+pub async fn known_hosts_remove(host: String, port: u16) -> Result<usize, AppError> {
+    tracing::info!("Removing {} from known hosts", host_pattern(&host, port));
+
+    let mut known = open()?;
+    let changed = known.remove(&host, port);
+    if changed > 0 {
+        known.save().map_err(|e| AppError::IoError(e.to_string()))?;
+    }
+    Ok(changed)
+}
+
+/// Trust the key a host presents, if it is still the one the user was shown
+///
+/// The key is fetched again rather than taken from the frontend, and replaces
+/// the recorded key of the same type.
+#[tauri::command]
+pub async fn known_hosts_trust(
+    host: String,
+    port: u16,
+    fingerprint: String,
+) -> Result<KnownHostEntry, AppError> {
+    tracing::info!("Trusting {} for {}", fingerprint, host_pattern(&host, port));
+
+    let keys = scan_host_keys(&host, port, SCAN_TIMEOUT)
+        .await
+        .map_err(|e| AppError::ConnectionFailed(e.to_string()))?;
+    let key = keys
+        .iter()
+        .find(|key| public_key_fingerprint(key).is_ok_and(|f| f == fingerprint))
+        .ok_or_else(|| {
+            AppError::HostKeyMismatch(format!(
+                "{} no longer presents the key {}",
+                host, fingerprint
+            ))
+        })?;
+
+    let mut known = open()?;
+    let entry = known
+        .add(&host, port, key, false)
+        .map_err(|e| AppError::InternalError(e.to_string()))?;
+    known.save().map_err(|e| AppError::IoError(e.to_string()))?;
+    Ok(KnownHostEntry::from(&entry))
+}
+
+/// After a failed connection, check whether the host key changed
+///
+/// Emits `host-key-mismatch` and returns the error to report if the server
+/// presents none of the keys recorded for it.
+pub async fn check_host_key(app: &AppHandle, host: &str, port: u16) -> Option<AppError> {
+    let known = open().ok()?;
+    let recorded: Vec<&KnownHost> = known
+        .lookup(host, port)
+        .into_iter()
+        .filter(|entry| entry.marker.is_none())
+        .collect();
+    let first = recorded.first()?;
+    let keys = scan_host_keys(host, port, SCAN_TIMEOUT).await.ok()?;
+    if keys
+        .iter()
+        .any(|key| recorded.iter().any(|entry| entry.public_key() == *key))
+    {
+        return None;
+    }
+
+    // Compare keys of the same type where the server still has one
+    let (entry, presented) = recorded
+        .iter()
+        .find_map(|entry| {
+            keys.iter()
+                .find(|key| key.split_whitespace().next() == Some(entry.key_type.as_str()))
+                .map(|key| (*entry, key))
+        })
+        .or_else(|| keys.first().map(|key| (*first, key)))?;
+    let mismatch = HostKeyMismatch {
+        host: host.to_string(),
+        port,
+        key_type: entry.key_type.clone(),
+        known_fingerprint: entry.fingerprint().ok()?,
+        presented_fingerprint: public_key_fingerprint(presented).ok()?,
+    };
+    tracing::warn!(
+        "Host key for {} changed: recorded {}, presented {}",
+        host_pattern(host, port),
+        mismatch.known_fingerprint,
+        mismatch.presented_fingerprint
+    );
+    app.emit("host-key-mismatch", mismatch.clone()).ok();
+
+    Some(AppError::HostKeyMismatch(format!(
+        "{} presented {} instead of the recorded {}",
+        host_pattern(host, port),
+        mismatch.presented_fingerprint,
+        mismatch.known_fingerprint
+    )))
+}
+
+fn open() -> Result<KnownHosts, AppError> {
+    let path = known_hosts_path()
+        .ok_or_else(|| AppError::InternalError("No home directory".to_string()))?;
+    KnownHosts::open(path).map_err(|e| AppError::IoError(e.to_string()))
+}
@@ -0,0 +1,199 @@
+//! Local terminal Tauri commands
+//!
+//! A local session runs the user's shell in a PTY on this machine instead of
+//! on an SSH host. Once opened with `local_session_open` it is driven by the
+//! same `terminal_start`, `terminal_input` and `terminal_resize` commands and
+//! closed with `ssh_disconnect`, so local and SSH tabs share one code path.
+//! Commands that need an SSH connection fail on it with `SESSION_NOT_FOUND`.
+
+use tauri::{AppHandle, Emitter, State, Window};
+use uuid::Uuid;
+
+use super::ssh::ConnectionResponse;
+use crate::error::AppError;
+use crate::state::{AppState, SessionState};
+
+/// Open a session for a shell on this machine
+#[tauri::command]
+pub async fn local_session_open(
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<ConnectionResponse, AppError> {
+    if cfg!(any(target_os = "android", target_os = "ios")) {
+        return Err(unsupported());
+    }
+
+    let session_id = Uuid::new_v4().to_string();
+    let username = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default();
+    tracing::info!("Opening local session: {}", session_id);
+
+    let mut session = SessionState::local(session_id.clone(), username.clone());
+    session.set_connected();
+    let host = session.info.host.clone();
+    state.add_session(session_id.clone(), session).await;
+
+    window
+        .emit(
+            "connection-state-changed",
+            serde_json::json!({
+                "sessionId": session_id,
+                "status": "connected",
+                "host": host,
+                "username": username,
+            }),
+        )
+        .ok();
+
+    Ok(ConnectionResponse {
+        session_id,
+        connected: true,
+        host,
+        username,
+    })
+}
+
+/// Run the user's shell in a PTY and stream it to the frontend
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub(super) async fn start_terminal(
+    state: &AppState,
+    app: &AppHandle,
+    session_id: &str,
+) -> Result<(), AppError> {
+    use portable_pty::{native_pty_system, CommandBuilder};
+    use std::io::{Read, Write};
+    use tokio::sync::mpsc;
+
+    use super::ssh::{terminal_ended, terminal_output};
+
+    tracing::info!("Starting local terminal for session: {}", session_id);
+
+    let pair = native_pty_system()
+        .openpty(pty_size(80, 24))
+        .map_err(terminal_error)?;
+    // The user's login shell
+    let mut shell = CommandBuilder::new_default_prog();
+    shell.env("TERM", "xterm-256color");
+    if let Some(home) = dirs::home_dir() {
+        shell.cwd(home);
+    }
+    let mut child = pair.slave.spawn_command(shell).map_err(terminal_error)?;
+    drop(pair.slave);
+    let hangup = Hangup(child.clone_killer());
+    std::thread::spawn(move || child.wait());
+
+    let master = pair.master;
+    let mut reader = master.try_clone_reader().map_err(terminal_error)?;
+    let mut writer = master.take_writer().map_err(terminal_error)?;
+
+    // The PTY blocks, so it is read and written on threads of its own
+    let (output_tx, mut output_rx) = mpsc::channel::<Vec<u8>>(32);
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 8192];
+        loop {
+            match reader.read(&mut buf) {
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if output_tx.blocking_send(buf[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+    let (input_tx, mut input_rx) = mpsc::channel::<Vec<u8>>(32);
+    std::thread::spawn(move || {
+        while let Some(data) = input_rx.blocking_recv() {
+            if writer
+                .write_all(&data)
+                .and_then(|_| writer.flush())
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+    let (resize_tx, mut resize_rx) = mpsc::channel::<(u16, u16)>(4);
+
+    let win = app.clone();
+    let sid = session_id.to_string();
+    let watches = state.command_watches();
+    let terminal_task = tokio::spawn(async move {
+        // Ending the task, or aborting it, ends the shell
+        let _hangup = hangup;
+        loop {
+            tokio::select! {
+                Some((cols, rows)) = resize_rx.recv() => {
+                    if let Err(e) = master.resize(pty_size(cols, rows)) {
+                        tracing::warn!("Failed to resize terminal {}: {}", sid, e);
+                    }
+                }
+                output = output_rx.recv() => match output {
+                    Some(bytes) => {
+                        if !terminal_output(&win, &watches, &sid, &bytes).await {
+                            break;
+                        }
+                    }
+                    None => {
+                        tracing::info!("Shell exited for session: {}", sid);
+                        break;
+                    }
+                }
+            }
+        }
+        terminal_ended(&win, &watches, &sid).await;
+    });
+
+    state
+        .get_session_mut(session_id, |s| {
+            s.terminal_task = Some(terminal_task);
+            s.terminal_input_tx = Some(input_tx);
+            s.terminal_resize_tx = Some(resize_tx);
+        })
+        .await;
+
+    Ok(())
+}
+
+#[cfg(any(target_os = "android", target_os = "ios"))]
+pub(super) async fn start_terminal(
+    _state: &AppState,
+    _app: &AppHandle,
+    _session_id: &str,
+) -> Result<(), AppError> {
+    Err(unsupported())
+}
+
+/// Kills the shell when dropped
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+struct Hangup(Box<dyn portable_pty::ChildKiller + Send + Sync>);
+
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+impl Drop for Hangup {
+    fn drop(&mut self) {
+        // Fails once the shell has exited by itself
+        self.0.kill().ok();
+    }
+}
+
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn pty_size(cols: u16, rows: u16) -> portable_pty::PtySize {
+    portable_pty::PtySize {
+        rows,
+        cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+fn terminal_error(e: anyhow::Error) -> AppError {
+    tracing::error!("Failed to open local terminal: {}", e);
+    AppError::TerminalError(e.to_string())
+}
+
+fn unsupported() -> AppError {
+    AppError::TerminalError("Local terminals are not supported on this device".to_string())
+}
@@ -1,8 +1,20 @@
 //! Tauri command modules
 
+pub mod drops;
+pub mod edits;
 pub mod files;
+pub mod forwards;
+pub mod keys;
+pub mod known_hosts;
+pub mod local;
+pub mod notifications;
 pub mod p2p;
+pub mod processes;
 pub mod profiles;
 pub mod settings;
+pub mod snippets;
 pub mod ssh;
 pub mod streaming;
+pub mod transfers;
+pub mod vault;
+pub mod vdfs;
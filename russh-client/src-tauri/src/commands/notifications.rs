@@ -0,0 +1,143 @@
+//! Command completion notification Tauri commands
+//!
+//! A command run with `ssh_execute` can ask to be notified about when it
+//! exits; one typed into a terminal is marked with `terminal_notify`, and
+//! counts as finished once the shell shows its prompt again. Either way a
+//! `command-finished` event is sent, along with a system notification
+//! unless notifications are turned off in the settings.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::error::AppError;
+use crate::state::{AppState, CommandWatch};
+
+/// Command finished event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandFinished {
+    pub session_id: String,
+    pub command: Option<String>,
+    /// `exec` or `terminal`
+    pub source: String,
+    /// Not known for terminal commands
+    pub exit_code: Option<i32>,
+    /// Why an exec command could not be run
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+}
+
+impl CommandFinished {
+    pub fn new(
+        watch: CommandWatch,
+        source: &str,
+        exit_code: Option<i32>,
+        error: Option<String>,
+    ) -> Self {
+        let finished_at = Utc::now();
+        let duration = (finished_at - watch.started_at)
+            .to_std()
+            .unwrap_or_default();
+        Self {
+            session_id: watch.session_id,
+            command: watch.command,
+            source: source.to_string(),
+            exit_code,
+            error,
+            duration_ms: duration.as_millis() as u64,
+            started_at: watch.started_at,
+            finished_at,
+        }
+    }
+
+    fn failed(&self) -> bool {
+        self.error.is_some() || self.exit_code.is_some_and(|code| code != 0)
+    }
+}
+
+/// Notify when the command running in a session's terminal finishes
+///
+/// `command` is only used to describe it. Marking another command replaces
+/// the earlier one.
+#[tauri::command]
+pub async fn terminal_notify(
+    state: State<'_, AppState>,
+    session_id: String,
+    command: Option<String>,
+) -> Result<CommandWatch, AppError> {
+    state
+        .get_terminal_input_tx(&session_id)
+        .await
+        .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
+    tracing::info!("Waiting for the terminal command on session {}", session_id);
+
+    let watch = CommandWatch::new(session_id, command);
+    state.command_watches().watch_terminal(watch.clone()).await;
+    Ok(watch)
+}
+
+/// Stop waiting for a session's terminal command
+#[tauri::command]
+pub async fn terminal_notify_cancel(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<(), AppError> {
+    state.command_watches().unwatch_terminal(&session_id).await;
+    Ok(())
+}
+
+/// Report a command that finished
+pub async fn command_finished(app: &AppHandle, finished: CommandFinished) {
+    tracing::info!(
+        "Command on session {} finished after {} ms",
+        finished.session_id,
+        finished.duration_ms
+    );
+    app.emit("command-finished", &finished).ok();
+
+    let state = app.state::<AppState>();
+    if !state.get_settings().await.notifications.enabled {
+        return;
+    }
+
+    let title = if finished.failed() {
+        "Command failed"
+    } else {
+        "Command finished"
+    };
+    let mut body = finished
+        .command
+        .clone()
+        .unwrap_or_else(|| "Command".to_string());
+    if let Some(session) = state.get_session(&finished.session_id).await {
+        body.push_str(&format!(" on {}", session.host));
+    }
+    body.push_str(&format!(
+        " took {}",
+        format_duration(Duration::from_millis(finished.duration_ms))
+    ));
+    match (&finished.error, finished.exit_code) {
+        (Some(error), _) => body.push_str(&format!(": {}", error)),
+        (None, Some(code)) if code != 0 => body.push_str(&format!(", exit code {}", code)),
+        _ => {}
+    }
+
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        tracing::warn!("Failed to show notification: {}", e);
+    }
+}
+
+/// Duration in its two largest units, e.g. `4m 12s`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+    }
+}
@@ -2,17 +2,24 @@
 
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use chrono::Utc;
-use image::ImageEncoder;
-use russh_ssh::p2p::{P2PConfig, P2PConnectionManager, P2PEndpoint};
+use russh_ssh::p2p::{
+    render_qr_svg, FileTransfer, NetworkDiagnostics, P2PConfig, P2PConnectionManager, P2PEndpoint,
+    PeerAcl, PeerCapability, PeerDiagnostics, PeerTicket, TunnelAgent, DEFAULT_STATS_INTERVAL,
+};
 use russh_ssh::NodeId;
 use std::sync::Arc;
+use std::time::Duration;
 use tauri::State;
 
+use super::drops::allow_drops;
 use crate::error::AppError;
-use crate::state::{AppState, P2PNodeInfo, P2PPeerInfo};
+use crate::state::{AppState, P2PDiagnostics, P2PNodeInfo, P2PPeerInfo, P2PPeerStats};
 
 /// Initialize P2P endpoint if not already initialized
-async fn ensure_p2p_initialized(
+///
+/// Peers may only dial in to pull the sync namespaces shared with them,
+/// and to drop files once connected to.
+pub async fn ensure_p2p_initialized(
     state: &AppState,
 ) -> Result<(Arc<P2PEndpoint>, Arc<P2PConnectionManager>), AppError> {
     // Check if already initialized
@@ -22,7 +29,9 @@ async fn ensure_p2p_initialized(
 
     // Initialize P2P endpoint
     tracing::info!("Initializing P2P endpoint");
-    let config = P2PConfig::default();
+    let config = P2PConfig::default()
+        .with_key_file(&state.node_key_path())
+        .map_err(|e| AppError::P2PConnectionFailed(e.to_string()))?;
     let endpoint = P2PEndpoint::bind(config).await.map_err(|e| {
         tracing::error!("Failed to initialize P2P: {}", e);
         AppError::P2PConnectionFailed(e.to_string())
@@ -32,7 +41,22 @@ async fn ensure_p2p_initialized(
     endpoint.wait_online().await;
 
     let endpoint = Arc::new(endpoint);
-    let manager = Arc::new(P2PConnectionManager::new(endpoint.clone()));
+    let manager = Arc::new(
+        P2PConnectionManager::new(endpoint.clone()).with_acl(Arc::new(PeerAcl::default_deny())),
+    );
+    // Both stop by themselves once the manager is dropped
+    manager.start_stats_sampling(DEFAULT_STATS_INTERVAL);
+    manager.start_idle_reaper();
+    let drops = state.drops();
+    let transfer = FileTransfer::new(manager.clone(), drops.download_dir())
+        .with_event_bus(drops.events())
+        .with_approval(drops.clone())
+        .with_capability(PeerCapability::FileDrop);
+    state
+        .namespaces()
+        .server()
+        .register(transfer.register(TunnelAgent::new(manager.clone())))
+        .serve();
 
     // Store in state
     state.set_p2p_state(endpoint.clone(), manager.clone()).await;
@@ -67,6 +91,8 @@ pub async fn p2p_get_node_info(state: State<'_, AppState>) -> Result<P2PNodeInfo
 }
 
 /// Connect to a P2P peer
+///
+/// `peer_id` may be a connection ticket, a `russh://` URI or a bare NodeId.
 #[tauri::command]
 pub async fn p2p_connect(
     state: State<'_, AppState>,
@@ -76,17 +102,20 @@ pub async fn p2p_connect(
 
     let (_, manager) = ensure_p2p_initialized(&state).await?;
 
-    // Parse peer ID
-    let node_id: NodeId = peer_id
+    // Parse ticket (falls back to a bare peer ID)
+    let ticket: PeerTicket = peer_id
         .parse()
         .map_err(|e| AppError::P2PConnectionFailed(format!("Invalid peer ID: {}", e)))?;
+    let peer_id = ticket.node_id().to_string();
 
     // Connect to peer
-    let connection = manager.connect(node_id).await.map_err(|e| {
+    let connection = manager.connect_ticket(&ticket).await.map_err(|e| {
         tracing::error!("Failed to connect to peer: {}", e);
         AppError::P2PConnectionFailed(e.to_string())
     })?;
 
+    allow_drops(&manager, &ticket.node_id());
+
     // Get connection info
     let info = connection.info().await;
 
@@ -147,48 +176,81 @@ pub async fn p2p_list_peers(state: State<'_, AppState>) -> Result<Vec<P2PPeerInf
     Ok(state.list_p2p_peers().await)
 }
 
-/// Generate QR code for node ID sharing
+/// Get the latest link statistics for connected peers
 #[tauri::command]
-pub async fn p2p_generate_qr(state: State<'_, AppState>) -> Result<String, AppError> {
+pub async fn p2p_peer_stats(state: State<'_, AppState>) -> Result<Vec<P2PPeerStats>, AppError> {
+    let Some((_, manager)) = state.get_p2p_state().await else {
+        return Ok(Vec::new());
+    };
+
+    Ok(manager
+        .all_peer_stats()
+        .await
+        .into_iter()
+        .map(|stats| P2PPeerStats {
+            peer_id: stats.peer_id.to_string(),
+            connection_type: stats.connection_type.to_string(),
+            rtt_ms: stats.rtt.as_millis() as u64,
+            avg_rtt_ms: stats.avg_rtt.as_millis() as u64,
+            min_rtt_ms: stats.min_rtt.as_millis() as u64,
+            bytes_sent: stats.bytes_sent,
+            bytes_received: stats.bytes_received,
+            send_rate: stats.send_rate,
+            recv_rate: stats.recv_rate,
+            lost_packets: stats.lost_packets,
+        })
+        .collect())
+}
+
+/// Run NAT traversal diagnostics, optionally for the path to one peer
+#[tauri::command]
+pub async fn p2p_diagnostics(
+    state: State<'_, AppState>,
+    peer_id: Option<String>,
+) -> Result<P2PDiagnostics, AppError> {
     let (endpoint, _) = ensure_p2p_initialized(&state).await?;
 
-    // Get node address for sharing
-    let node_addr = endpoint
-        .node_addr()
+    let network = NetworkDiagnostics::collect(&endpoint, Duration::from_secs(5)).await;
+    let peer = match peer_id {
+        Some(peer_id) => {
+            let ticket: PeerTicket = peer_id
+                .parse()
+                .map_err(|e| AppError::P2PConnectionFailed(format!("Invalid peer ID: {}", e)))?;
+            PeerDiagnostics::collect(&endpoint, ticket.node_id())
+        }
+        None => None,
+    };
+
+    Ok(P2PDiagnostics {
+        issues: network.issues(),
+        network,
+        peer,
+    })
+}
+
+/// Create a shareable connection ticket for this node
+#[tauri::command]
+pub async fn p2p_create_ticket(state: State<'_, AppState>) -> Result<String, AppError> {
+    let (endpoint, _) = ensure_p2p_initialized(&state).await?;
+
+    let ticket = PeerTicket::from_endpoint(&endpoint)
         .await
-        .map_err(|e| AppError::InternalError(format!("Failed to get node address: {}", e)))?;
+        .map_err(|e| AppError::InternalError(format!("Failed to create ticket: {}", e)))?;
 
-    // Create shareable connection string
-    // Format: russh://node_id?relay=url&addr=ip:port
-    let mut qr_data = format!("russh://{}", node_addr.node_id);
+    Ok(ticket.to_string())
+}
 
-    if let Some(relay) = node_addr.relay_url {
-        qr_data.push_str(&format!("?relay={}", relay));
-    }
+/// Generate QR code for connection ticket sharing
+#[tauri::command]
+pub async fn p2p_generate_qr(state: State<'_, AppState>) -> Result<String, AppError> {
+    let (endpoint, _) = ensure_p2p_initialized(&state).await?;
 
-    // Generate QR code
-    let qr = qrcode::QrCode::new(qr_data.as_bytes())
+    let ticket = PeerTicket::from_endpoint(&endpoint)
+        .await
+        .map_err(|e| AppError::InternalError(format!("Failed to get node address: {}", e)))?;
+    let svg = render_qr_svg(&ticket.to_string())
         .map_err(|e| AppError::InternalError(format!("Failed to generate QR code: {}", e)))?;
 
-    // Render to image
-    let image = qr
-        .render::<image::Luma<u8>>()
-        .min_dimensions(200, 200)
-        .build();
-
-    // Convert to PNG bytes
-    let mut png_bytes = Vec::new();
-    let encoder = image::codecs::png::PngEncoder::new(&mut png_bytes);
-    encoder
-        .write_image(
-            image.as_raw(),
-            image.width(),
-            image.height(),
-            image::ExtendedColorType::L8,
-        )
-        .map_err(|e| AppError::InternalError(format!("Failed to encode QR code: {}", e)))?;
-
     // Return as base64 data URL
-    let base64_data = BASE64.encode(&png_bytes);
-    Ok(format!("data:image/png;base64,{}", base64_data))
+    Ok(format!("data:image/svg+xml;base64,{}", BASE64.encode(svg)))
 }
@@ -0,0 +1,160 @@
+//! Remote process manager Tauri commands
+//!
+//! Lists and signals processes on the host of a session with `ps` and
+//! `kill`, as found on Linux, the BSDs and macOS.
+
+use serde::Serialize;
+use tauri::State;
+
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// `ps` invocation; the `=` after each column drops the header line, and the
+/// C locale keeps decimal points in the CPU and memory columns
+const PS_COMMAND: &str = "LC_ALL=C ps -eo pid=,ppid=,user=,pcpu=,pmem=,rss=,etime=,stat=,args=";
+
+/// Signals that can be sent, by name without the `SIG` prefix
+const SIGNALS: &[&str] = &[
+    "HUP", "INT", "QUIT", "KILL", "USR1", "USR2", "TERM", "CONT", "STOP", "TSTP",
+];
+
+/// Remote process information (serializable for frontend)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub ppid: u32,
+    pub user: String,
+    /// CPU usage in percent of one core
+    pub cpu: f32,
+    /// Share of physical memory in percent
+    pub mem: f32,
+    /// Resident memory in bytes
+    pub rss_bytes: u64,
+    /// Time since the process started, as `ps` gives it
+    pub elapsed: String,
+    pub state: String,
+    pub command: String,
+}
+
+/// List the processes running on a session's host, busiest first
+#[tauri::command]
+pub async fn proc_list(
+    state: State<'_, AppState>,
+    session_id: String,
+) -> Result<Vec<ProcessInfo>, AppError> {
+    let stdout = run(&state, &session_id, PS_COMMAND).await?;
+    let mut processes: Vec<ProcessInfo> = stdout.lines().filter_map(parse_line).collect();
+    processes.sort_by(|a, b| b.cpu.total_cmp(&a.cpu));
+    Ok(processes)
+}
+
+/// Ask a process to terminate, or kill it outright with `force`
+#[tauri::command]
+pub async fn proc_kill(
+    state: State<'_, AppState>,
+    session_id: String,
+    pid: u32,
+    force: Option<bool>,
+) -> Result<(), AppError> {
+    let signal = if force.unwrap_or(false) {
+        "KILL"
+    } else {
+        "TERM"
+    };
+    send_signal(&state, &session_id, pid, signal).await
+}
+
+/// Send a signal to a process, e.g. `HUP` to reload or `STOP` to pause it
+///
+/// The signal is given by name, with or without the `SIG` prefix.
+#[tauri::command]
+pub async fn proc_signal(
+    state: State<'_, AppState>,
+    session_id: String,
+    pid: u32,
+    signal: String,
+) -> Result<(), AppError> {
+    let name = signal.trim().to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    let signal = SIGNALS
+        .iter()
+        .find(|s| **s == name)
+        .ok_or_else(|| AppError::ProcessError(format!("Unsupported signal: {}", signal)))?;
+    send_signal(&state, &session_id, pid, signal).await
+}
+
+async fn send_signal(
+    state: &AppState,
+    session_id: &str,
+    pid: u32,
+    signal: &str,
+) -> Result<(), AppError> {
+    // `kill` takes 0 as the caller's whole process group
+    if pid == 0 {
+        return Err(AppError::ProcessError("Invalid process ID: 0".to_string()));
+    }
+    tracing::info!(
+        "Sending SIG{} to process {} on session {}",
+        signal,
+        pid,
+        session_id
+    );
+    run(state, session_id, &format!("kill -s {} {}", signal, pid)).await?;
+    Ok(())
+}
+
+/// Run a command on a session, failing unless it exits with 0
+async fn run(state: &AppState, session_id: &str, command: &str) -> Result<String, AppError> {
+    let client = state
+        .get_session_client(session_id)
+        .await
+        .ok_or_else(|| AppError::SessionNotFound(session_id.to_string()))?;
+    let result = client
+        .lock()
+        .await
+        .execute(command)
+        .await
+        .map_err(|e| AppError::ProcessError(e.to_string()))?;
+
+    state
+        .get_session_mut(session_id, |s| {
+            s.increment_commands();
+            s.add_bytes_received(result.stdout.len() as u64 + result.stderr.len() as u64);
+        })
+        .await;
+
+    if !result.success() {
+        let stderr = result.stderr_string();
+        let message = match stderr.trim() {
+            "" => format!("`{}` exited with {}", command, result.exit_code),
+            stderr => stderr.to_string(),
+        };
+        return Err(AppError::ProcessError(message));
+    }
+    Ok(result.stdout_string())
+}
+
+/// Parse a line of [`PS_COMMAND`] output
+fn parse_line(line: &str) -> Option<ProcessInfo> {
+    let mut rest = line.trim_start();
+    let mut fields = [""; 8];
+    for field in &mut fields {
+        let end = rest.find(char::is_whitespace)?;
+        *field = &rest[..end];
+        rest = rest[end..].trim_start();
+    }
+    let [pid, ppid, user, cpu, mem, rss, elapsed, stat] = fields;
+
+    Some(ProcessInfo {
+        pid: pid.parse().ok()?,
+        ppid: ppid.parse().ok()?,
+        user: user.to_string(),
+        cpu: cpu.parse().unwrap_or_default(),
+        mem: mem.parse().unwrap_or_default(),
+        rss_bytes: rss.parse::<u64>().unwrap_or_default() * 1024,
+        elapsed: elapsed.to_string(),
+        state: stat.to_string(),
+        command: rest.trim_end().to_string(),
+    })
+}
@@ -20,7 +20,7 @@ pub async fn profile_create(
 
     // Store password securely if provided
     if let Some(pwd) = password {
-        profile.store_password(&pwd)?;
+        state.store_secret(&id, &pwd).await?;
     }
 
     state.save_profile(id.clone(), profile).await?;
@@ -40,7 +40,7 @@ pub async fn profile_update(
 
     // Update password if provided
     if let Some(pwd) = password {
-        profile.store_password(&pwd)?;
+        state.store_secret(&id, &pwd).await?;
     }
 
     state.update_profile(id, profile).await
@@ -54,10 +54,9 @@ pub async fn profile_delete(
 ) -> Result<(), AppError> {
     tracing::info!("Deleting profile: {}", profile_id);
 
-    // Get profile to delete password from keyring
-    let profiles = state.list_profiles().await;
-    if let Some(profile) = profiles.iter().find(|p| p.id.as_ref() == Some(&profile_id)) {
-        profile.delete_password().ok(); // Ignore errors
+    // Delete the stored password along with the profile
+    if state.get_profile(&profile_id).await.is_some() {
+        state.delete_secret(&profile_id).await.ok(); // Ignore errors
     }
 
     state.delete_profile(&profile_id).await
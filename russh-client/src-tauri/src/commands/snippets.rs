@@ -0,0 +1,273 @@
+//! Command snippet Tauri commands
+//!
+//! Snippets may use `{name}` placeholders. Running one on a session fills in
+//! `{host}`, `{port}` and `{user}` from that session; other values come from
+//! the caller, and those given override the session's.
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use tauri::State;
+use uuid::Uuid;
+
+use super::ssh::CommandResponse;
+use crate::error::AppError;
+use crate::state::{AppState, Snippet};
+
+/// Snippet create/update request from frontend
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetRequest {
+    pub name: String,
+    pub command: String,
+    pub description: Option<String>,
+    pub folder: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub shared: bool,
+}
+
+/// Snippet response, with the placeholders it needs filled
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetResponse {
+    #[serde(flatten)]
+    pub snippet: Snippet,
+    pub variables: Vec<String>,
+}
+
+impl From<Snippet> for SnippetResponse {
+    fn from(snippet: Snippet) -> Self {
+        Self {
+            variables: snippet.variables(),
+            snippet,
+        }
+    }
+}
+
+/// Save a new snippet
+#[tauri::command]
+pub async fn snippet_create(
+    state: State<'_, AppState>,
+    request: SnippetRequest,
+) -> Result<SnippetResponse, AppError> {
+    check_request(&request)?;
+    tracing::info!("Creating snippet: {}", request.name);
+
+    let now = Utc::now();
+    let snippet = Snippet {
+        id: Uuid::new_v4().to_string(),
+        name: request.name,
+        command: request.command,
+        description: request.description,
+        folder: request.folder,
+        tags: request.tags,
+        shared: request.shared,
+        use_count: 0,
+        created_at: now,
+        updated_at: now,
+    };
+    state.snippets().save(snippet.clone()).await?;
+    Ok(snippet.into())
+}
+
+/// Change a snippet
+#[tauri::command]
+pub async fn snippet_update(
+    state: State<'_, AppState>,
+    snippet_id: String,
+    request: SnippetRequest,
+) -> Result<SnippetResponse, AppError> {
+    check_request(&request)?;
+    tracing::info!("Updating snippet: {} ({})", request.name, snippet_id);
+
+    let snippet = state
+        .snippets()
+        .update(&snippet_id, |s| {
+            s.name = request.name;
+            s.command = request.command;
+            s.description = request.description;
+            s.folder = request.folder;
+            s.tags = request.tags;
+            s.shared = request.shared;
+            s.updated_at = Utc::now();
+        })
+        .await?;
+    Ok(snippet.into())
+}
+
+/// Delete a snippet
+#[tauri::command]
+pub async fn snippet_delete(
+    state: State<'_, AppState>,
+    snippet_id: String,
+) -> Result<(), AppError> {
+    tracing::info!("Deleting snippet: {}", snippet_id);
+    state.snippets().delete(&snippet_id).await
+}
+
+/// List snippets, optionally of one folder or matching `query`
+///
+/// The query is looked for in names, commands, descriptions and tags.
+/// Most used snippets come first.
+#[tauri::command]
+pub async fn snippet_list(
+    state: State<'_, AppState>,
+    folder: Option<String>,
+    query: Option<String>,
+) -> Result<Vec<SnippetResponse>, AppError> {
+    let query = query
+        .map(|q| q.trim().to_lowercase())
+        .filter(|q| !q.is_empty());
+
+    let mut snippets: Vec<Snippet> = state
+        .snippets()
+        .list()
+        .await
+        .into_iter()
+        .filter(|s| folder.is_none() || s.folder == folder)
+        .filter(|s| match query.as_deref() {
+            Some(q) => matches_query(s, q),
+            None => true,
+        })
+        .collect();
+    snippets.sort_by(|a, b| {
+        b.use_count
+            .cmp(&a.use_count)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+
+    Ok(snippets.into_iter().map(SnippetResponse::from).collect())
+}
+
+/// Get a snippet's command with its placeholders filled in, e.g. to paste it
+/// into a terminal
+#[tauri::command]
+pub async fn snippet_render(
+    state: State<'_, AppState>,
+    snippet_id: String,
+    session_id: Option<String>,
+    variables: Option<HashMap<String, String>>,
+) -> Result<String, AppError> {
+    let snippet = get_snippet(&state, &snippet_id).await?;
+    let values = values_for(&state, session_id.as_deref(), variables).await?;
+    snippet.render(&values)
+}
+
+/// Run a snippet on a session
+#[tauri::command]
+pub async fn snippet_run(
+    state: State<'_, AppState>,
+    snippet_id: String,
+    session_id: String,
+    variables: Option<HashMap<String, String>>,
+    timeout_secs: Option<u64>,
+) -> Result<CommandResponse, AppError> {
+    let snippet = get_snippet(&state, &snippet_id).await?;
+    let values = values_for(&state, Some(&session_id), variables).await?;
+    let command = snippet.render(&values)?;
+    tracing::info!(
+        "Running snippet {} on session {}: {}",
+        snippet.name,
+        session_id,
+        command
+    );
+
+    let client = state
+        .get_session_client(&session_id)
+        .await
+        .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
+    let result = {
+        let client = client.lock().await;
+        match timeout_secs {
+            Some(secs) => {
+                client
+                    .execute_with_timeout(&command, Duration::from_secs(secs))
+                    .await
+            }
+            None => client.execute(&command).await,
+        }
+    }
+    .map_err(|e| {
+        tracing::error!("Snippet failed: {}", e);
+        AppError::InternalError(e.to_string())
+    })?;
+
+    state
+        .get_session_mut(&session_id, |s| {
+            s.increment_commands();
+            s.add_bytes_received(result.stdout.len() as u64 + result.stderr.len() as u64);
+        })
+        .await;
+    state
+        .snippets()
+        .update(&snippet_id, |s| s.use_count += 1)
+        .await?;
+
+    Ok(CommandResponse {
+        stdout: result.stdout_string(),
+        stderr: result.stderr_string(),
+        exit_code: result.exit_code,
+    })
+}
+
+fn check_request(request: &SnippetRequest) -> Result<(), AppError> {
+    if request.name.trim().is_empty() {
+        return Err(AppError::SnippetError("Name is required".to_string()));
+    }
+    if request.command.trim().is_empty() {
+        return Err(AppError::SnippetError("Command is required".to_string()));
+    }
+    Ok(())
+}
+
+async fn get_snippet(state: &AppState, snippet_id: &str) -> Result<Snippet, AppError> {
+    state
+        .snippets()
+        .get(snippet_id)
+        .await
+        .ok_or_else(|| AppError::SnippetNotFound(snippet_id.to_string()))
+}
+
+/// Placeholder values: the session's, then the caller's on top
+async fn values_for(
+    state: &AppState,
+    session_id: Option<&str>,
+    variables: Option<HashMap<String, String>>,
+) -> Result<HashMap<String, String>, AppError> {
+    let mut values = HashMap::new();
+    if let Some(session_id) = session_id {
+        let (host, username, port) = state
+            .get_session_mut(session_id, |s| {
+                (
+                    s.info.host.clone(),
+                    s.info.username.clone(),
+                    s.origin.as_ref().map(|o| o.port),
+                )
+            })
+            .await
+            .ok_or_else(|| AppError::SessionNotFound(session_id.to_string()))?;
+        values.insert("host".to_string(), host);
+        values.insert("user".to_string(), username);
+        if let Some(port) = port {
+            values.insert("port".to_string(), port.to_string());
+        }
+    }
+    values.extend(variables.unwrap_or_default());
+    Ok(values)
+}
+
+fn matches_query(snippet: &Snippet, query: &str) -> bool {
+    snippet.name.to_lowercase().contains(query)
+        || snippet.command.to_lowercase().contains(query)
+        || snippet
+            .description
+            .as_ref()
+            .is_some_and(|d| d.to_lowercase().contains(query))
+        || snippet
+            .tags
+            .iter()
+            .any(|t| t.to_lowercase().contains(query))
+}
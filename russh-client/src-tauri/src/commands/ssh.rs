@@ -1,14 +1,25 @@
 //! SSH-related Tauri commands
 
-use russh_ssh::ssh::{AuthMethod, HostKeyCheck, SshClient, SshConfig};
+use russh_ssh::connection::HealthProbe;
+use russh_ssh::session::IdleAction;
+use russh_ssh::ssh::{AuthMethod, HostKeyCheck, SshClient, SshConfig, Transport};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::time::Duration;
-use tauri::{Emitter, State, Window};
+use tauri::{AppHandle, Emitter, Manager, State, Window};
+use tokio::sync::broadcast::error::RecvError;
 use uuid::Uuid;
 
+use super::notifications::{self, CommandFinished};
+use super::{keys, known_hosts, local};
 use crate::error::AppError;
-use crate::state::{AppState, SessionState};
+use crate::state::{
+    AppState, CommandWatch, CommandWatches, SessionKind, SessionOrigin, SessionSnapshot,
+    SessionState,
+};
+
+/// How often the idle policies of open sessions are applied
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
 
 /// Connection request from frontend
 #[derive(Debug, Deserialize)]
@@ -21,6 +32,9 @@ pub struct ConnectionRequest {
     pub password: Option<String>,
     pub key_path: Option<String>,
     pub key_passphrase: Option<String>,
+    /// Saved profile the connection was made from, whose stored password
+    /// is used when none is given
+    pub profile_id: Option<String>,
 }
 
 /// Connection response to frontend
@@ -40,6 +54,9 @@ pub struct CommandRequest {
     pub session_id: String,
     pub command: String,
     pub timeout_secs: Option<u64>,
+    /// Send a notification when the command exits
+    #[serde(default)]
+    pub notify: bool,
 }
 
 /// Command response to frontend
@@ -58,10 +75,27 @@ pub struct SessionResponse {
     pub session_id: String,
     pub host: String,
     pub username: String,
+    pub kind: SessionKind,
     pub status: String,
     pub connected_at: String,
 }
 
+/// Session restore progress event payload
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreProgress {
+    pub session_id: String,
+    pub profile_id: Option<String>,
+    pub host: String,
+    pub username: String,
+    /// `connecting`, `connected` or `failed`
+    pub status: String,
+    pub error: Option<String>,
+    /// Position of this session among those being restored, from 1
+    pub current: usize,
+    pub total: usize,
+}
+
 /// Connect to SSH server
 #[tauri::command]
 pub async fn ssh_connect(
@@ -69,90 +103,64 @@ pub async fn ssh_connect(
     window: Window,
     request: ConnectionRequest,
 ) -> Result<ConnectionResponse, AppError> {
-    tracing::info!(
-        "Connecting to {}@{}:{}",
-        request.username,
-        request.host,
-        request.port
-    );
+    let session_id = Uuid::new_v4().to_string();
+    let response = connect(&state, window.app_handle(), session_id, request).await?;
+    save_sessions(&state).await;
+    Ok(response)
+}
 
-    // Build auth method
-    let auth = match request.auth_type.as_str() {
-        "password" => {
-            let password = request
-                .password
-                .ok_or_else(|| AppError::AuthenticationFailed("Password required".to_string()))?;
-            AuthMethod::Password(password)
-        }
-        "key" => {
-            let key_path = request
-                .key_path
-                .ok_or_else(|| AppError::AuthenticationFailed("Key path required".to_string()))?;
-            AuthMethod::PublicKey {
-                key_path: PathBuf::from(key_path),
-                passphrase: request.key_passphrase,
+/// Reconnect the sessions that were open when the app last closed
+///
+/// Meant to be called once the frontend listens for
+/// `session-restore-progress`, which is sent before and after each session.
+/// Sessions keep their previous IDs; those that fail are dropped. Returns the
+/// IDs of the sessions that are back.
+#[tauri::command]
+pub async fn ssh_restore_sessions(
+    state: State<'_, AppState>,
+    window: Window,
+) -> Result<Vec<String>, AppError> {
+    let saved = state.restore_sessions().await?;
+    if !saved.is_empty() && state.list_profiles().await.is_empty() {
+        // Profiles may still be loading this early after startup
+        state.load_profiles().await?;
+    }
+    let app = window.app_handle();
+    let total = saved.len();
+    let mut restored = Vec::new();
+
+    for (index, snapshot) in saved.into_iter().enumerate() {
+        let mut progress = RestoreProgress {
+            session_id: snapshot.session_id.clone(),
+            profile_id: snapshot.profile_id.clone(),
+            host: snapshot.host.clone(),
+            username: snapshot.username.clone(),
+            status: "connecting".to_string(),
+            error: None,
+            current: index + 1,
+            total,
+        };
+        app.emit("session-restore-progress", progress.clone()).ok();
+
+        match restore_session(&state, app, &snapshot).await {
+            Ok(()) => {
+                progress.status = "connected".to_string();
+                restored.push(snapshot.session_id);
+            }
+            Err(e) => {
+                tracing::warn!("Failed to restore session {}: {}", snapshot.session_id, e);
+                progress.status = "failed".to_string();
+                progress.error = Some(e.to_string());
             }
         }
-        "agent" => AuthMethod::Agent,
-        _ => return Err(AppError::InvalidAuthMethod),
-    };
-
-    // Build SSH config
-    let known_hosts = dirs::home_dir()
-        .map(|h| h.join(".ssh").join("known_hosts"))
-        .filter(|p| p.exists());
-
-    let config = SshConfig {
-        host: request.host.clone(),
-        port: request.port,
-        username: request.username.clone(),
-        auth,
-        timeout: Duration::from_secs(30),
-        known_hosts_path: known_hosts,
-        host_key_check: HostKeyCheck::Strict,
-    };
-
-    // Create and connect SSH client
-    let mut client = SshClient::new();
-    client.connect(&config).await.map_err(|e| {
-        tracing::error!("SSH connection failed: {}", e);
-        AppError::ConnectionFailed(e.to_string())
-    })?;
-
-    // Create session
-    let session_id = Uuid::new_v4().to_string();
-    let mut session = SessionState::new(
-        session_id.clone(),
-        request.host.clone(),
-        request.username.clone(),
-        client,
-    );
-    session.set_connected();
-
-    // Store session
-    state.add_session(session_id.clone(), session).await;
-
-    // Emit connection event
-    window
-        .emit(
-            "connection-state-changed",
-            serde_json::json!({
-                "sessionId": session_id,
-                "status": "connected",
-                "host": request.host,
-                "username": request.username,
-            }),
-        )
-        .ok();
-
-    tracing::info!("SSH connection established: {}", session_id);
+        app.emit("session-restore-progress", progress).ok();
+    }
 
-    Ok(ConnectionResponse {
-        session_id,
-        connected: true,
-        host: request.host,
-        username: request.username,
-    })
+    if total > 0 {
+        tracing::info!("Restored {} of {} session(s)", restored.len(), total);
+        save_sessions(&state).await;
+    }
+    Ok(restored)
 }
 
 /// Disconnect from SSH server
@@ -161,17 +169,71 @@ pub async fn ssh_disconnect(
     state: State<'_, AppState>,
     window: Window,
     session_id: String,
+) -> Result<(), AppError> {
+    disconnect(&state, window.app_handle(), &session_id, None).await?;
+    save_sessions(&state).await;
+    Ok(())
+}
+
+/// Apply the idle policies of open sessions for as long as the app runs
+///
+/// A session idle past a `Disconnect` policy is closed as by
+/// `ssh_disconnect`, its `connection-state-changed` event giving `idle` as
+/// the reason. A `KeepAlive` session gets a round trip over its connection.
+pub fn watch_idle_sessions(state: &AppState, app: AppHandle) {
+    let state = state.clone();
+    tauri::async_runtime::spawn(async move {
+        let tracker = state.idle_tracker();
+        let mut events = tracker.subscribe_idle_events();
+        tracker.spawn_idle_monitor(IDLE_CHECK_INTERVAL);
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            let session_id = event.session_id.to_string();
+            match event.action {
+                IdleAction::Disconnect => {
+                    tracing::info!(
+                        "Disconnecting session {} after {:?} idle",
+                        session_id,
+                        event.idle_for
+                    );
+                    match disconnect(&state, &app, &session_id, Some("idle")).await {
+                        Ok(()) => save_sessions(&state).await,
+                        Err(e) => tracing::warn!("Idle disconnect of {} failed: {}", session_id, e),
+                    }
+                }
+                IdleAction::KeepAlive => {
+                    if let Some(client) = state.get_session_client(&session_id).await {
+                        if let Err(e) = client.lock().await.probe().await {
+                            tracing::warn!("Keep-alive on session {} failed: {}", session_id, e);
+                        }
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Close a session's connection, drop the session and tell the frontend
+async fn disconnect(
+    state: &AppState,
+    app: &AppHandle,
+    session_id: &str,
+    reason: Option<&str>,
 ) -> Result<(), AppError> {
     tracing::info!("Disconnecting session: {}", session_id);
 
     // Get session info before removing
     let session = state
-        .get_session(&session_id)
+        .get_session(session_id)
         .await
-        .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
+        .ok_or_else(|| AppError::SessionNotFound(session_id.to_string()))?;
 
     // Get client and disconnect
-    if let Some(client) = state.get_session_client(&session_id).await {
+    if let Some(client) = state.get_session_client(session_id).await {
         let mut client = client.lock().await;
         if let Err(e) = client.disconnect().await {
             tracing::warn!("Error during disconnect: {}", e);
@@ -179,20 +241,20 @@ pub async fn ssh_disconnect(
     }
 
     // Remove session
-    state.remove_session(&session_id).await?;
+    state.remove_session(session_id).await?;
 
     // Emit disconnection event
-    window
-        .emit(
-            "connection-state-changed",
-            serde_json::json!({
-                "sessionId": session_id,
-                "status": "disconnected",
-                "host": session.host,
-                "username": session.username,
-            }),
-        )
-        .ok();
+    app.emit(
+        "connection-state-changed",
+        serde_json::json!({
+            "sessionId": session_id,
+            "status": "disconnected",
+            "host": session.host,
+            "username": session.username,
+            "reason": reason,
+        }),
+    )
+    .ok();
 
     Ok(())
 }
@@ -201,6 +263,7 @@ pub async fn ssh_disconnect(
 #[tauri::command]
 pub async fn ssh_execute(
     state: State<'_, AppState>,
+    window: Window,
     request: CommandRequest,
 ) -> Result<CommandResponse, AppError> {
     tracing::info!(
@@ -215,6 +278,10 @@ pub async fn ssh_execute(
         .await
         .ok_or_else(|| AppError::SessionNotFound(request.session_id.clone()))?;
 
+    state.record_activity(&request.session_id).await;
+    let watch = request
+        .notify
+        .then(|| CommandWatch::new(request.session_id.clone(), Some(request.command.clone())));
     let client = client.lock().await;
 
     // Execute command with optional timeout
@@ -225,6 +292,15 @@ pub async fn ssh_execute(
     } else {
         client.execute(&request.command).await
     };
+    drop(client);
+
+    if let Some(watch) = watch {
+        let finished = match &result {
+            Ok(result) => CommandFinished::new(watch, "exec", Some(result.exit_code), None),
+            Err(e) => CommandFinished::new(watch, "exec", None, Some(e.to_string())),
+        };
+        notifications::command_finished(window.app_handle(), finished).await;
+    }
 
     let result = result.map_err(|e| {
         tracing::error!("Command execution failed: {}", e);
@@ -259,6 +335,7 @@ pub async fn ssh_list_sessions(
             session_id: s.session_id,
             host: s.host,
             username: s.username,
+            kind: s.kind,
             status: format!("{:?}", s.status).to_lowercase(),
             connected_at: s.connected_at.to_rfc3339(),
         })
@@ -272,13 +349,84 @@ pub async fn terminal_start(
     window: Window,
     session_id: String,
 ) -> Result<(), AppError> {
+    start_terminal(&state, window.app_handle(), &session_id).await?;
+    save_sessions(&state).await;
+    Ok(())
+}
+
+/// Send input to terminal
+#[tauri::command]
+pub async fn terminal_input(
+    state: State<'_, AppState>,
+    session_id: String,
+    data: String,
+) -> Result<(), AppError> {
+    // Get input sender from session
+    let tx = state
+        .get_terminal_input_tx(&session_id)
+        .await
+        .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
+    state.record_activity(&session_id).await;
+
+    // Send input to terminal task
+    tx.send(data.into_bytes())
+        .await
+        .map_err(|e| AppError::InternalError(format!("Failed to send input: {}", e)))?;
+
+    Ok(())
+}
+
+/// Resize terminal
+#[tauri::command]
+pub async fn terminal_resize(
+    state: State<'_, AppState>,
+    session_id: String,
+    cols: u16,
+    rows: u16,
+) -> Result<(), AppError> {
+    tracing::debug!("Resizing terminal {} to {}x{}", session_id, cols, rows);
+
+    // Verify session exists
+    let _session = state
+        .get_session(&session_id)
+        .await
+        .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
+
+    // Local terminals resize their PTY
+    if let Some(tx) = state.get_terminal_resize_tx(&session_id).await {
+        tx.send((cols, rows))
+            .await
+            .map_err(|e| AppError::InternalError(format!("Failed to resize terminal: {}", e)))?;
+        return Ok(());
+    }
+
+    // TODO: Implement PTY resize - requires extending Shell with resize capability
+    // The async-ssh2-tokio library would need to support window-change requests
+
+    Ok(())
+}
+
+/// Open a shell on a session and stream it to the frontend
+async fn start_terminal(
+    state: &AppState,
+    app: &AppHandle,
+    session_id: &str,
+) -> Result<(), AppError> {
+    let session = state
+        .get_session(session_id)
+        .await
+        .ok_or_else(|| AppError::SessionNotFound(session_id.to_string()))?;
+    if session.kind == SessionKind::Local {
+        return local::start_terminal(state, app, session_id).await;
+    }
+
     tracing::info!("Starting terminal for session: {}", session_id);
 
     // Get client
     let client = state
-        .get_session_client(&session_id)
+        .get_session_client(session_id)
         .await
-        .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
+        .ok_or_else(|| AppError::SessionNotFound(session_id.to_string()))?;
 
     // Open shell with PTY
     let mut shell = {
@@ -296,8 +444,9 @@ pub async fn terminal_start(
     let (input_tx, mut input_rx) = tokio::sync::mpsc::channel::<Vec<u8>>(32);
 
     // Spawn task to handle shell I/O with timeout
-    let win = window.clone();
-    let sid = session_id.clone();
+    let win = app.clone();
+    let sid = session_id.to_string();
+    let watches = state.command_watches();
     let terminal_task = tokio::spawn(async move {
         use std::time::{Duration, Instant};
 
@@ -333,8 +482,7 @@ pub async fn terminal_start(
                     last_activity = Instant::now();
                     match output {
                         Some(bytes) if !bytes.is_empty() => {
-                            let text = String::from_utf8_lossy(&bytes).to_string();
-                            if win.emit(&format!("terminal-output-{}", sid), &text).is_err() {
+                            if !terminal_output(&win, &watches, &sid, &bytes).await {
                                 break;
                             }
                         }
@@ -347,12 +495,12 @@ pub async fn terminal_start(
                 }
             }
         }
-        tracing::info!("Terminal task ended for session: {}", sid);
+        terminal_ended(&win, &watches, &sid).await;
     });
 
     // Store terminal handles in session
     state
-        .get_session_mut(&session_id, |s| {
+        .get_session_mut(session_id, |s| {
             s.terminal_task = Some(terminal_task);
             s.terminal_input_tx = Some(input_tx);
         })
@@ -361,45 +509,207 @@ pub async fn terminal_start(
     Ok(())
 }
 
-/// Send input to terminal
-#[tauri::command]
-pub async fn terminal_input(
-    state: State<'_, AppState>,
-    session_id: String,
-    data: String,
-) -> Result<(), AppError> {
-    // Get input sender from session
-    let tx = state
-        .get_terminal_input_tx(&session_id)
-        .await
-        .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
-
-    // Send input to terminal task
-    tx.send(data.into_bytes())
-        .await
-        .map_err(|e| AppError::InternalError(format!("Failed to send input: {}", e)))?;
+/// Send a session's terminal output to the frontend; false once it is gone
+pub(super) async fn terminal_output(
+    app: &AppHandle,
+    watches: &CommandWatches,
+    session_id: &str,
+    bytes: &[u8],
+) -> bool {
+    let text = String::from_utf8_lossy(bytes).to_string();
+    if app
+        .emit(&format!("terminal-output-{}", session_id), &text)
+        .is_err()
+    {
+        return false;
+    }
+    if let Some(watch) = watches.terminal_output(session_id, &text).await {
+        let finished = CommandFinished::new(watch, "terminal", None, None);
+        notifications::command_finished(app, finished).await;
+    }
+    true
+}
 
-    Ok(())
+/// Wrap up after a session's shell is gone
+pub(super) async fn terminal_ended(app: &AppHandle, watches: &CommandWatches, session_id: &str) {
+    // A command still waited on ended with its shell
+    if let Some(watch) = watches.unwatch_terminal(session_id).await {
+        let finished = CommandFinished::new(watch, "terminal", None, None);
+        notifications::command_finished(app, finished).await;
+    }
+    tracing::info!("Terminal task ended for session: {}", session_id);
 }
 
-/// Resize terminal
-#[tauri::command]
-pub async fn terminal_resize(
-    state: State<'_, AppState>,
+/// Connect and register a session under `session_id`
+async fn connect(
+    state: &AppState,
+    app: &AppHandle,
     session_id: String,
-    cols: u16,
-    rows: u16,
-) -> Result<(), AppError> {
-    tracing::debug!("Resizing terminal {} to {}x{}", session_id, cols, rows);
+    request: ConnectionRequest,
+) -> Result<ConnectionResponse, AppError> {
+    tracing::info!(
+        "Connecting to {}@{}:{}",
+        request.username,
+        request.host,
+        request.port
+    );
 
-    // Verify session exists
-    let _session = state
-        .get_session(&session_id)
-        .await
-        .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
+    let auth = auth_method(state, &request).await?;
+    let idle_policy = match &request.profile_id {
+        Some(id) => state.get_profile(id).await.and_then(|p| p.idle_policy),
+        None => None,
+    };
 
-    // TODO: Implement PTY resize - requires extending Shell with resize capability
-    // The async-ssh2-tokio library would need to support window-change requests
+    // Build SSH config
+    let known_hosts = known_hosts::known_hosts_path().filter(|p| p.exists());
+
+    let config = SshConfig {
+        host: request.host.clone(),
+        port: request.port,
+        username: request.username.clone(),
+        auth,
+        timeout: Duration::from_secs(30),
+        known_hosts_path: known_hosts,
+        host_key_check: HostKeyCheck::Strict,
+        transport: Transport::Tcp,
+    };
+
+    // Create and connect SSH client
+    let mut client = SshClient::new();
+    if let Err(e) = client.connect(&config).await {
+        tracing::error!("SSH connection failed: {}", e);
+        if config.known_hosts_path.is_some() {
+            if let Some(mismatch) =
+                known_hosts::check_host_key(app, &request.host, request.port).await
+            {
+                return Err(mismatch);
+            }
+        }
+        return Err(AppError::ConnectionFailed(e.to_string()));
+    }
+
+    // Create session
+    let mut session = SessionState::new(
+        session_id.clone(),
+        request.host.clone(),
+        request.username.clone(),
+        client,
+    )
+    .with_profile(request.profile_id)
+    .with_origin(SessionOrigin {
+        port: request.port,
+        auth_type: request.auth_type,
+        key_path: request.key_path,
+    });
+    session.set_connected();
+
+    // Store session
+    state.add_session(session_id.clone(), session).await;
+    if let Ok(id) = Uuid::parse_str(&session_id) {
+        state.idle_tracker().track_session(id, idle_policy).await;
+    }
 
+    // Emit connection event
+    app.emit(
+        "connection-state-changed",
+        serde_json::json!({
+            "sessionId": session_id,
+            "status": "connected",
+            "host": request.host,
+            "username": request.username,
+        }),
+    )
+    .ok();
+
+    tracing::info!("SSH connection established: {}", session_id);
+
+    Ok(ConnectionResponse {
+        session_id,
+        connected: true,
+        host: request.host,
+        username: request.username,
+    })
+}
+
+/// Build the auth method, filling in credentials kept in the keyring
+async fn auth_method(
+    state: &AppState,
+    request: &ConnectionRequest,
+) -> Result<AuthMethod, AppError> {
+    match request.auth_type.as_str() {
+        "password" => {
+            let password = match (&request.password, &request.profile_id) {
+                (Some(password), _) => Some(password.clone()),
+                (None, Some(id)) => state.get_secret(id).await?,
+                (None, None) => None,
+            };
+            let password = password
+                .ok_or_else(|| AppError::AuthenticationFailed("Password required".to_string()))?;
+            Ok(AuthMethod::Password(password))
+        }
+        "key" => {
+            let key_path = request
+                .key_path
+                .as_ref()
+                .ok_or_else(|| AppError::AuthenticationFailed("Key path required".to_string()))?;
+            let key_path = PathBuf::from(key_path);
+            let passphrase = match &request.key_passphrase {
+                Some(passphrase) => Some(passphrase.clone()),
+                None => keys::stored_passphrase(state, &key_path).await?,
+            };
+            Ok(AuthMethod::PublicKey {
+                key_path,
+                passphrase,
+            })
+        }
+        "agent" => Ok(AuthMethod::Agent),
+        _ => Err(AppError::InvalidAuthMethod),
+    }
+}
+
+/// Reconnect a saved session, from its profile when it still exists
+async fn restore_session(
+    state: &AppState,
+    app: &AppHandle,
+    snapshot: &SessionSnapshot,
+) -> Result<(), AppError> {
+    let profile = match &snapshot.profile_id {
+        Some(id) => state.get_profile(id).await,
+        None => None,
+    };
+    let request = match profile {
+        Some(profile) => ConnectionRequest {
+            host: profile.host,
+            port: profile.port,
+            username: profile.username,
+            auth_type: profile.auth_type,
+            password: None,
+            key_path: profile.key_path,
+            key_passphrase: None,
+            profile_id: profile.id,
+        },
+        None => ConnectionRequest {
+            host: snapshot.host.clone(),
+            port: snapshot.port,
+            username: snapshot.username.clone(),
+            auth_type: snapshot.auth_type.clone(),
+            password: None,
+            key_path: snapshot.key_path.clone(),
+            key_passphrase: None,
+            profile_id: None,
+        },
+    };
+
+    connect(state, app, snapshot.session_id.clone(), request).await?;
+    if snapshot.terminal {
+        start_terminal(state, app, &snapshot.session_id).await?;
+    }
     Ok(())
 }
+
+/// Keep the saved session list in step with the open sessions
+async fn save_sessions(state: &AppState) {
+    if let Err(e) = state.save_active_sessions().await {
+        tracing::warn!("Failed to save active sessions: {}", e);
+    }
+}
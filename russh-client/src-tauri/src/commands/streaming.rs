@@ -1,7 +1,11 @@
-//! Video streaming Tauri commands
+//! Video and audio streaming Tauri commands
 
-use russh_ssh::streaming::{PlaybackState, StreamRoom, StreamSession, StreamSource};
+use russh_ssh::streaming::{
+    PlaybackState, Rendition, StreamMetrics, StreamRoom, StreamSession, StreamSource, Track,
+    TrackMetadata,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tauri::{Emitter, State, Window};
 
@@ -37,6 +41,68 @@ pub enum StreamSourceResponse {
         file_id: String,
         size: u64,
     },
+    Audio {
+        tracks: Vec<TrackResponse>,
+        current: usize,
+    },
+    Renditions {
+        renditions: Vec<RenditionResponse>,
+    },
+    Hls {
+        playlist_url: String,
+    },
+    Dash {
+        manifest_url: String,
+    },
+    Terminal {
+        term: String,
+        cols: u32,
+        rows: u32,
+    },
+}
+
+/// Rendition response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenditionResponse {
+    pub label: String,
+    pub bitrate: u64,
+    pub source: Box<StreamSourceResponse>,
+}
+
+impl From<Rendition> for RenditionResponse {
+    fn from(rendition: Rendition) -> Self {
+        Self {
+            label: rendition.label,
+            bitrate: rendition.bitrate,
+            source: Box::new(rendition.source.into()),
+        }
+    }
+}
+
+/// Audio track response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackResponse {
+    pub source: Box<StreamSourceResponse>,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<f64>,
+    pub artwork_url: Option<String>,
+}
+
+impl From<Track> for TrackResponse {
+    fn from(track: Track) -> Self {
+        Self {
+            source: Box::new(track.source.into()),
+            title: track.metadata.title,
+            artist: track.metadata.artist,
+            album: track.metadata.album,
+            duration: track.metadata.duration,
+            artwork_url: track.metadata.artwork_url,
+        }
+    }
 }
 
 /// Playback state response
@@ -76,6 +142,51 @@ impl From<StreamSource> for StreamSourceResponse {
                 file_id,
                 size,
             },
+            StreamSource::Audio { tracks, current } => StreamSourceResponse::Audio {
+                tracks: tracks.into_iter().map(Into::into).collect(),
+                current,
+            },
+            StreamSource::Renditions { renditions } => StreamSourceResponse::Renditions {
+                renditions: renditions.into_iter().map(Into::into).collect(),
+            },
+            StreamSource::Hls { playlist_url } => StreamSourceResponse::Hls { playlist_url },
+            StreamSource::Dash { manifest_url } => StreamSourceResponse::Dash { manifest_url },
+            StreamSource::Terminal { term, cols, rows } => {
+                StreamSourceResponse::Terminal { term, cols, rows }
+            }
+        }
+    }
+}
+
+/// Stream metrics response
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamMetricsResponse {
+    pub rebuffer_count: u64,
+    pub rebuffer_secs: f64,
+    pub rebuffering: bool,
+    pub buffer_level: u64,
+    pub avg_buffer_level: f64,
+    pub sync_drift: Option<f64>,
+    pub avg_sync_drift: Option<f64>,
+    pub max_sync_drift: f64,
+    pub bytes_fetched: HashMap<String, u64>,
+    pub elapsed_secs: f64,
+}
+
+impl From<StreamMetrics> for StreamMetricsResponse {
+    fn from(metrics: StreamMetrics) -> Self {
+        Self {
+            rebuffer_count: metrics.rebuffer_count,
+            rebuffer_secs: metrics.rebuffer_secs,
+            rebuffering: metrics.rebuffering,
+            buffer_level: metrics.buffer_level,
+            avg_buffer_level: metrics.avg_buffer_level,
+            sync_drift: metrics.sync_drift,
+            avg_sync_drift: metrics.avg_sync_drift,
+            max_sync_drift: metrics.max_sync_drift,
+            bytes_fetched: metrics.bytes_fetched,
+            elapsed_secs: metrics.elapsed_secs,
         }
     }
 }
@@ -88,6 +199,9 @@ pub struct CreateStreamRequest {
     pub source_type: String,
     pub url: Option<String>,
     pub file_path: Option<String>,
+    /// Track title for audio sources; defaults to the file name
+    pub title: Option<String>,
+    pub artist: Option<String>,
 }
 
 /// Sync event request
@@ -122,7 +236,8 @@ pub async fn stream_create_room(
             let url = request.url.ok_or_else(|| {
                 AppError::InternalError("URL required for URL source".to_string())
             })?;
-            StreamSource::Url { url }
+            // HLS and DASH manifests are recognized by their extension
+            StreamSource::from_url(url)
         }
         "file" => {
             let path = request.file_path.ok_or_else(|| {
@@ -136,6 +251,38 @@ pub async fn stream_create_room(
                 size: metadata.len(),
             }
         }
+        "audio" => {
+            let location = request.url.or(request.file_path).ok_or_else(|| {
+                AppError::InternalError("URL or file path required for audio source".to_string())
+            })?;
+            let title = request.title.unwrap_or_else(|| {
+                std::path::Path::new(&location)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| location.clone())
+            });
+            let mut metadata = TrackMetadata::new(title);
+            if let Some(artist) = request.artist {
+                metadata = metadata.with_artist(artist);
+            }
+            let source = if location.contains("://") {
+                StreamSource::Url { url: location }
+            } else {
+                let size = std::fs::metadata(&location)
+                    .map_err(|e| {
+                        AppError::FileOperationFailed(format!("Failed to read file: {}", e))
+                    })?
+                    .len();
+                StreamSource::LocalFile {
+                    path: location,
+                    size,
+                }
+            };
+            StreamSource::Audio {
+                tracks: vec![Track::new(source, metadata)],
+                current: 0,
+            }
+        }
         _ => return Err(AppError::InternalError("Invalid source type".to_string())),
     };
 
@@ -318,6 +465,12 @@ pub async fn stream_sync(
                 .await
                 .map_err(|e| AppError::InternalError(e.to_string()))?;
         }
+        "next_track" => {
+            session
+                .advance_track()
+                .await
+                .map_err(|e| AppError::InternalError(e.to_string()))?;
+        }
         _ => return Err(AppError::InternalError("Invalid event type".to_string())),
     }
 
@@ -350,3 +503,35 @@ pub async fn stream_get_expected_position(
 
     Ok(session.expected_position().await)
 }
+
+/// Report that the player stalled or resumed (e.g. the video element's
+/// `waiting` and `playing` events)
+#[tauri::command]
+pub async fn stream_report_stall(
+    state: State<'_, AppState>,
+    room_id: String,
+    stalled: bool,
+) -> Result<(), AppError> {
+    if let Some(session) = state.get_stream_session(&room_id).await {
+        if stalled {
+            session.metrics().rebuffer_started();
+        } else {
+            session.metrics().rebuffer_ended();
+        }
+    }
+    Ok(())
+}
+
+/// Get playback quality metrics for a stats overlay
+#[tauri::command]
+pub async fn stream_get_metrics(
+    state: State<'_, AppState>,
+    room_id: String,
+) -> Result<StreamMetricsResponse, AppError> {
+    let session = state
+        .get_stream_session(&room_id)
+        .await
+        .ok_or_else(|| AppError::InternalError("Room not found".to_string()))?;
+
+    Ok(session.metrics().snapshot().into())
+}
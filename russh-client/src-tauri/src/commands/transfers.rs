@@ -0,0 +1,490 @@
+//! File transfer queue Tauri commands
+//!
+//! Uploads and downloads are queued and run a few at a time, moving the file
+//! in chunks so they can be paused, resumed from where they stopped, or
+//! cancelled. Every change is pushed to the frontend as a
+//! `transfer://progress` event carrying the transfer's current state.
+
+use russh_ssh::ssh::SshClient;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, State, Window};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::state::{AppState, TransferDirection, TransferInfo, TransferQueue, TransferStatus};
+
+/// Event carrying a transfer's state whenever it changes
+pub const PROGRESS_EVENT: &str = "transfer://progress";
+
+/// Bytes read per request when downloading
+const DOWNLOAD_CHUNK: u64 = 1024 * 1024;
+
+/// Bytes read from the local file per write when uploading
+const UPLOAD_CHUNK: u64 = 256 * 1024;
+
+/// Least time between two progress events of a running transfer
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Add a transfer to the queue; it starts when a slot is free
+pub async fn enqueue(
+    state: &AppState,
+    window: Window,
+    session_id: String,
+    direction: TransferDirection,
+    local_path: String,
+    remote_path: String,
+    total_bytes: u64,
+) -> Result<String, AppError> {
+    let client = state
+        .get_session_client(&session_id)
+        .await
+        .ok_or_else(|| AppError::SessionNotFound(session_id.clone()))?;
+
+    let source = match direction {
+        TransferDirection::Upload => &local_path,
+        TransferDirection::Download => &remote_path,
+    };
+    let filename = Path::new(source)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| source.clone());
+    let transfer = TransferInfo {
+        transfer_id: Uuid::new_v4().to_string(),
+        session_id,
+        direction,
+        filename,
+        local_path,
+        remote_path,
+        status: TransferStatus::Queued,
+        bytes_transferred: 0,
+        total_bytes,
+        speed_bps: 0,
+        eta_seconds: 0,
+        error: None,
+        created_at: chrono::Utc::now(),
+        run: 0,
+    };
+    let transfer_id = transfer.transfer_id.clone();
+
+    let queue = state.transfers();
+    queue.add(transfer.clone()).await;
+    emit(&window, &transfer);
+    spawn(queue, client, window, transfer_id.clone(), 0);
+    Ok(transfer_id)
+}
+
+/// List queued, running and finished transfers
+#[tauri::command]
+pub async fn transfer_list(state: State<'_, AppState>) -> Result<Vec<TransferInfo>, AppError> {
+    Ok(state.transfers().list().await)
+}
+
+/// Pause a queued or running transfer
+#[tauri::command]
+pub async fn transfer_pause(
+    state: State<'_, AppState>,
+    window: Window,
+    transfer_id: String,
+) -> Result<TransferInfo, AppError> {
+    tracing::info!("Pausing transfer {}", transfer_id);
+
+    let transfer = state
+        .transfers()
+        .update(&transfer_id, |t| {
+            if matches!(t.status, TransferStatus::Queued | TransferStatus::Active) {
+                t.status = TransferStatus::Paused;
+                t.speed_bps = 0;
+                t.eta_seconds = 0;
+            }
+        })
+        .await
+        .ok_or_else(|| AppError::TransferNotFound(transfer_id.clone()))?;
+    if transfer.status != TransferStatus::Paused {
+        return Err(AppError::TransferFailed(format!(
+            "A {:?} transfer cannot be paused",
+            transfer.status
+        )));
+    }
+
+    emit(&window, &transfer);
+    Ok(transfer)
+}
+
+/// Resume a paused transfer, or retry a failed one, from where it stopped
+#[tauri::command]
+pub async fn transfer_resume(
+    state: State<'_, AppState>,
+    window: Window,
+    transfer_id: String,
+) -> Result<TransferInfo, AppError> {
+    tracing::info!("Resuming transfer {}", transfer_id);
+
+    let queue = state.transfers();
+    let transfer = queue
+        .get(&transfer_id)
+        .await
+        .ok_or_else(|| AppError::TransferNotFound(transfer_id.clone()))?;
+    let client = state
+        .get_session_client(&transfer.session_id)
+        .await
+        .ok_or_else(|| AppError::SessionNotFound(transfer.session_id.clone()))?;
+
+    let mut resumed = false;
+    let transfer = queue
+        .update(&transfer_id, |t| {
+            if matches!(t.status, TransferStatus::Paused | TransferStatus::Failed) {
+                t.status = TransferStatus::Queued;
+                t.error = None;
+                t.run += 1;
+                resumed = true;
+            }
+        })
+        .await
+        .ok_or_else(|| AppError::TransferNotFound(transfer_id.clone()))?;
+    if !resumed {
+        return Err(AppError::TransferFailed(format!(
+            "A {:?} transfer cannot be resumed",
+            transfer.status
+        )));
+    }
+
+    emit(&window, &transfer);
+    spawn(queue, client, window, transfer_id, transfer.run);
+    Ok(transfer)
+}
+
+/// Cancel a transfer and remove the part already copied
+#[tauri::command]
+pub async fn transfer_cancel(
+    state: State<'_, AppState>,
+    window: Window,
+    transfer_id: String,
+) -> Result<(), AppError> {
+    tracing::info!("Cancelling transfer {}", transfer_id);
+
+    let mut previous = None;
+    let transfer = state
+        .transfers()
+        .update(&transfer_id, |t| {
+            if !t.status.is_finished() {
+                previous = Some(t.status);
+                t.status = TransferStatus::Cancelled;
+                t.speed_bps = 0;
+                t.eta_seconds = 0;
+            }
+        })
+        .await
+        .ok_or_else(|| AppError::TransferNotFound(transfer_id.clone()))?;
+    emit(&window, &transfer);
+
+    // A running transfer cleans up after itself once it sees the change
+    if previous.is_some_and(|status| status != TransferStatus::Active)
+        && transfer.bytes_transferred > 0
+    {
+        if let Some(client) = state.get_session_client(&transfer.session_id).await {
+            discard_partial(&client, &transfer).await;
+        }
+    }
+    Ok(())
+}
+
+/// Remove completed, failed and cancelled transfers from the list
+#[tauri::command]
+pub async fn transfer_clear_finished(state: State<'_, AppState>) -> Result<usize, AppError> {
+    Ok(state.transfers().clear_finished().await)
+}
+
+/// Run a transfer in the background once a slot is free
+///
+/// `run` tells this worker apart from one started before a pause; a worker
+/// stops as soon as the transfer is no longer active under its run.
+fn spawn(
+    queue: Arc<TransferQueue>,
+    client: Arc<Mutex<SshClient>>,
+    window: Window,
+    transfer_id: String,
+    run: u64,
+) {
+    tokio::spawn(async move {
+        let Some(_slot) = queue.acquire_slot().await else {
+            return;
+        };
+        let started = queue
+            .update(&transfer_id, |t| {
+                if t.run == run && t.status == TransferStatus::Queued {
+                    t.status = TransferStatus::Active;
+                }
+            })
+            .await;
+        // Paused, cancelled or started again while waiting
+        let Some(transfer) = started.filter(|t| t.run == run && t.status == TransferStatus::Active)
+        else {
+            return;
+        };
+        emit(&window, &transfer);
+        tracing::info!("Starting transfer {} ({})", transfer_id, transfer.filename);
+
+        let result = copy(&queue, &client, &window, &transfer).await;
+        let finished = queue
+            .update(&transfer_id, |t| {
+                if t.run != run || t.status != TransferStatus::Active {
+                    return;
+                }
+                t.speed_bps = 0;
+                t.eta_seconds = 0;
+                match &result {
+                    Ok(()) => {
+                        t.status = TransferStatus::Completed;
+                        t.total_bytes = t.bytes_transferred;
+                    }
+                    Err(e) => {
+                        t.status = TransferStatus::Failed;
+                        t.error = Some(e.to_string());
+                    }
+                }
+            })
+            .await;
+        let Some(transfer) = finished.filter(|t| t.run == run) else {
+            return;
+        };
+
+        match transfer.status {
+            TransferStatus::Completed => tracing::info!("Transfer {} completed", transfer_id),
+            TransferStatus::Failed => tracing::error!(
+                "Transfer {} failed: {}",
+                transfer_id,
+                transfer.error.as_deref().unwrap_or_default()
+            ),
+            TransferStatus::Cancelled => discard_partial(&client, &transfer).await,
+            _ => {}
+        }
+        emit(&window, &transfer);
+    });
+}
+
+/// Move the file chunk by chunk, starting after the bytes already copied
+///
+/// Returns early, without an error, once the transfer is paused or cancelled.
+async fn copy(
+    queue: &TransferQueue,
+    client: &Mutex<SshClient>,
+    window: &Window,
+    transfer: &TransferInfo,
+) -> Result<(), AppError> {
+    match transfer.direction {
+        TransferDirection::Upload => upload(queue, client, window, transfer).await,
+        TransferDirection::Download => download(queue, client, window, transfer).await,
+    }
+}
+
+/// Stream the local file to the remote one through a single command
+///
+/// A resumed upload continues from what actually reached the remote file,
+/// which can be less than was sent before a connection dropped.
+async fn upload(
+    queue: &TransferQueue,
+    client: &Mutex<SshClient>,
+    window: &Window,
+    transfer: &TransferInfo,
+) -> Result<(), AppError> {
+    let local = transfer.local_path.as_str();
+    let remote = transfer.remote_path.as_str();
+    let local_error =
+        |e: std::io::Error| AppError::FileOperationFailed(format!("{}: {}", local, e));
+    let remote_error = |e: russh_ssh::error::SshError| AppError::TransferFailed(e.to_string());
+
+    let mut offset = 0;
+    if transfer.bytes_transferred > 0 {
+        // Start over when the remote file is gone or larger than the source
+        let landed = client.lock().await.file_size(remote).await.ok();
+        offset = landed
+            .filter(|&size| size <= transfer.total_bytes)
+            .unwrap_or(0);
+    }
+
+    let mut file = tokio::fs::File::open(local).await.map_err(local_error)?;
+    file.seek(SeekFrom::Start(offset))
+        .await
+        .map_err(local_error)?;
+    let mut writer = client
+        .lock()
+        .await
+        .open_file_writer(remote, offset > 0)
+        .await
+        .map_err(remote_error)?;
+
+    let mut meter = Meter::new(offset);
+    let mut data = vec![0; UPLOAD_CHUNK as usize];
+    while offset < transfer.total_bytes && is_running(queue, transfer).await {
+        let len = UPLOAD_CHUNK.min(transfer.total_bytes - offset) as usize;
+        let read = file.read(&mut data[..len]).await.map_err(local_error)?;
+        if read == 0 {
+            return Err(AppError::TransferFailed(
+                "File shrank while uploading".to_string(),
+            ));
+        }
+        writer.write(&data[..read]).await.map_err(remote_error)?;
+        offset += read as u64;
+        report(queue, window, &mut meter, transfer, offset).await;
+    }
+
+    // Done or paused, everything sent has to land before this run ends
+    writer.finish().await.map_err(remote_error)?;
+    Ok(())
+}
+
+/// Fetch the remote file range by range until a read comes back empty
+async fn download(
+    queue: &TransferQueue,
+    client: &Mutex<SshClient>,
+    window: &Window,
+    transfer: &TransferInfo,
+) -> Result<(), AppError> {
+    let local = transfer.local_path.as_str();
+    let remote = transfer.remote_path.as_str();
+    let local_error =
+        |e: std::io::Error| AppError::FileOperationFailed(format!("{}: {}", local, e));
+    let remote_error = |e: russh_ssh::error::SshError| AppError::TransferFailed(e.to_string());
+
+    // Whatever lies past the bytes counted as copied is dropped
+    let mut offset = transfer.bytes_transferred;
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(offset == 0)
+        .open(local)
+        .await
+        .map_err(local_error)?;
+    file.set_len(offset).await.map_err(local_error)?;
+    file.seek(SeekFrom::Start(offset))
+        .await
+        .map_err(local_error)?;
+
+    let mut meter = Meter::new(offset);
+    while is_running(queue, transfer).await {
+        let data = client
+            .lock()
+            .await
+            .read_file_range(remote, offset, DOWNLOAD_CHUNK)
+            .await
+            .map_err(remote_error)?;
+        if data.is_empty() {
+            break;
+        }
+        file.write_all(&data).await.map_err(local_error)?;
+        offset += data.len() as u64;
+        report(queue, window, &mut meter, transfer, offset).await;
+    }
+
+    file.flush().await.map_err(local_error)?;
+    Ok(())
+}
+
+/// Whether this run of the transfer is still meant to go on
+async fn is_running(queue: &TransferQueue, transfer: &TransferInfo) -> bool {
+    queue
+        .get(&transfer.transfer_id)
+        .await
+        .is_some_and(|t| t.run == transfer.run && t.status == TransferStatus::Active)
+}
+
+/// Record the bytes copied so far, emitting progress now and then
+async fn report(
+    queue: &TransferQueue,
+    window: &Window,
+    meter: &mut Meter,
+    transfer: &TransferInfo,
+    offset: u64,
+) {
+    let (speed, eta) = meter.measure(offset, transfer.total_bytes);
+    let updated = queue
+        .update(&transfer.transfer_id, |t| {
+            if t.run == transfer.run {
+                t.bytes_transferred = offset;
+                t.total_bytes = t.total_bytes.max(offset);
+                t.speed_bps = speed;
+                t.eta_seconds = eta;
+            }
+        })
+        .await;
+    if let Some(updated) = updated {
+        if meter.should_report() {
+            emit(window, &updated);
+        }
+    }
+}
+
+/// Remove what a cancelled transfer left at its destination
+async fn discard_partial(client: &Mutex<SshClient>, transfer: &TransferInfo) {
+    let removed = match transfer.direction {
+        TransferDirection::Upload => client
+            .lock()
+            .await
+            .delete_path(&transfer.remote_path, false)
+            .await
+            .map_err(|e| e.to_string()),
+        TransferDirection::Download => tokio::fs::remove_file(&transfer.local_path)
+            .await
+            .map_err(|e| e.to_string()),
+    };
+    if let Err(e) = removed {
+        tracing::warn!(
+            "Could not remove the partial copy of {}: {}",
+            transfer.filename,
+            e
+        );
+    }
+}
+
+fn emit(window: &Window, transfer: &TransferInfo) {
+    window.emit(PROGRESS_EVENT, transfer).ok();
+}
+
+/// Speed and remaining time of a transfer run, and progress event pacing
+struct Meter {
+    started: Instant,
+    resumed_at: u64,
+    last_report: Option<Instant>,
+}
+
+impl Meter {
+    fn new(resumed_at: u64) -> Self {
+        Self {
+            started: Instant::now(),
+            resumed_at,
+            last_report: None,
+        }
+    }
+
+    /// Bytes per second so far and seconds left
+    fn measure(&self, done: u64, total: u64) -> (u64, u64) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if elapsed <= 0.0 {
+            return (0, 0);
+        }
+        let speed = (done - self.resumed_at) as f64 / elapsed;
+        let eta = if speed > 0.0 {
+            (total.saturating_sub(done) as f64 / speed).ceil() as u64
+        } else {
+            0
+        };
+        (speed as u64, eta)
+    }
+
+    fn should_report(&mut self) -> bool {
+        let now = Instant::now();
+        if self
+            .last_report
+            .is_some_and(|last| now.duration_since(last) < PROGRESS_INTERVAL)
+        {
+            return false;
+        }
+        self.last_report = Some(now);
+        true
+    }
+}
@@ -0,0 +1,117 @@
+//! Credential vault Tauri commands
+//!
+//! Setting up the vault moves saved profile passwords and key passphrases
+//! out of the OS keyring; from then on they are only available while the
+//! vault is unlocked. A `vault-locked` event is sent whenever it locks, so
+//! the frontend can ask for the master password again.
+
+use serde::Serialize;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State, Window};
+
+use super::keys;
+use crate::error::AppError;
+use crate::state::{AppState, IdleCheck, VaultStatus, DEFAULT_AUTO_LOCK};
+
+/// Shortest auto-lock time accepted
+const MIN_AUTO_LOCK: Duration = Duration::from_secs(60);
+
+/// Vault locked event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultLocked {
+    /// `manual` or `idle`
+    pub reason: String,
+}
+
+/// Get whether the vault is set up and unlocked
+#[tauri::command]
+pub async fn vault_status(state: State<'_, AppState>) -> Result<VaultStatus, AppError> {
+    Ok(state.vault().status().await)
+}
+
+/// Set up the vault, moving stored passwords and passphrases into it
+#[tauri::command]
+pub async fn vault_create(
+    state: State<'_, AppState>,
+    window: Window,
+    password: String,
+    auto_lock_secs: Option<u64>,
+) -> Result<VaultStatus, AppError> {
+    let auto_lock = auto_lock_secs
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_AUTO_LOCK)
+        .max(MIN_AUTO_LOCK);
+
+    let mut accounts: Vec<String> = state
+        .list_profiles()
+        .await
+        .into_iter()
+        .filter_map(|p| p.id)
+        .collect();
+    accounts.extend(keys::passphrase_accounts(&state.keys_dir()));
+
+    let moved = state.create_vault(&password, auto_lock, accounts).await?;
+    tracing::info!("Vault set up with {} secrets from the keyring", moved);
+
+    watch_idle(window.app_handle().clone());
+    Ok(state.vault().status().await)
+}
+
+/// Unlock the vault with the master password
+#[tauri::command]
+pub async fn vault_unlock(
+    state: State<'_, AppState>,
+    window: Window,
+    password: String,
+) -> Result<VaultStatus, AppError> {
+    state.vault().unlock(&password).await?;
+    tracing::info!("Vault unlocked");
+
+    watch_idle(window.app_handle().clone());
+    Ok(state.vault().status().await)
+}
+
+/// Lock the vault right away
+#[tauri::command]
+pub async fn vault_lock(state: State<'_, AppState>, window: Window) -> Result<(), AppError> {
+    state.vault().lock().await;
+    tracing::info!("Vault locked");
+
+    window
+        .emit(
+            "vault-locked",
+            VaultLocked {
+                reason: "manual".to_string(),
+            },
+        )
+        .ok();
+    Ok(())
+}
+
+/// Lock the vault once it has gone unused for its auto-lock time
+///
+/// Runs until the vault is locked. An unlock while an earlier watcher is
+/// still waiting starts another; only the one that locks sends the event.
+fn watch_idle(app: AppHandle) {
+    let vault = app.state::<AppState>().vault();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match vault.lock_if_idle().await {
+                IdleCheck::Due(wait) => tokio::time::sleep(wait).await,
+                IdleCheck::Locked => {
+                    tracing::info!("Vault locked after being idle");
+                    app.emit(
+                        "vault-locked",
+                        VaultLocked {
+                            reason: "idle".to_string(),
+                        },
+                    )
+                    .ok();
+                    break;
+                }
+                IdleCheck::AlreadyLocked => break,
+            }
+        }
+    });
+}
@@ -0,0 +1,286 @@
+//! VDFS sync Tauri commands
+//!
+//! Syncs local folders with other devices, Syncthing-style. A namespace is
+//! created on one device and joined on another under the same ID; each side
+//! lists the other as a peer, since peers only pull what is shared with them.
+//! While a namespace runs, local edits are picked up as they happen and its
+//! peers are pulled from every [`PULL_INTERVAL`]; a `vdfs-status` event
+//! carries its status after each round.
+
+use russh_ssh::p2p::{P2PConnectionManager, PeerTicket};
+use russh_ssh::vdfs::{PullReport, SyncedFolder, WatchChange};
+use russh_ssh::NodeId;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State, Window};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use super::p2p::ensure_p2p_initialized;
+use crate::error::AppError;
+use crate::state::{AppState, NamespaceConflict, NamespaceInfo};
+
+/// How often the peers of a running namespace are pulled from
+const PULL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// List sync namespaces
+#[tauri::command]
+pub async fn vdfs_list(state: State<'_, AppState>) -> Result<Vec<NamespaceInfo>, AppError> {
+    Ok(state.namespaces().list().await)
+}
+
+/// Get the sync status of a namespace
+#[tauri::command]
+pub async fn vdfs_status(
+    state: State<'_, AppState>,
+    namespace_id: String,
+) -> Result<NamespaceInfo, AppError> {
+    state.namespaces().info(&namespace_id).await
+}
+
+/// Create a namespace for a local folder and start syncing it
+///
+/// The ID defaults to the folder's name. Other devices join it under the
+/// same ID, and are then added with `vdfs_share`.
+#[tauri::command]
+pub async fn vdfs_create(
+    state: State<'_, AppState>,
+    window: Window,
+    local_path: String,
+    namespace_id: Option<String>,
+) -> Result<NamespaceInfo, AppError> {
+    let path = PathBuf::from(local_path);
+    let id = namespace_id.unwrap_or_else(|| default_id(&path));
+    tracing::info!("Creating sync namespace {} at {}", id, path.display());
+
+    state.namespaces().add(id.clone(), path, Vec::new()).await?;
+    start(&state, window.app_handle(), &id).await?;
+    state.namespaces().info(&id).await
+}
+
+/// Join a namespace another device created, syncing it into a local folder
+///
+/// `peer_id` may be a connection ticket, a `russh://` URI or a bare NodeId.
+#[tauri::command]
+pub async fn vdfs_join(
+    state: State<'_, AppState>,
+    window: Window,
+    namespace_id: String,
+    peer_id: String,
+    local_path: String,
+) -> Result<NamespaceInfo, AppError> {
+    let ticket = parse_peer(&peer_id)?;
+    tracing::info!(
+        "Joining sync namespace {} from {}",
+        namespace_id,
+        ticket.node_id()
+    );
+
+    let (_, manager) = ensure_p2p_initialized(&state).await?;
+    // Remembers the peer's addresses for the pulls; it may be offline for now
+    if let Err(e) = manager.connect_ticket(&ticket).await {
+        tracing::warn!("Could not reach {} yet: {}", ticket.node_id(), e);
+    }
+
+    state
+        .namespaces()
+        .add(
+            namespace_id.clone(),
+            PathBuf::from(local_path),
+            vec![ticket.node_id()],
+        )
+        .await?;
+    start(&state, window.app_handle(), &namespace_id).await?;
+    state.namespaces().info(&namespace_id).await
+}
+
+/// Sync a namespace with another device as well
+#[tauri::command]
+pub async fn vdfs_share(
+    state: State<'_, AppState>,
+    window: Window,
+    namespace_id: String,
+    peer_id: String,
+) -> Result<NamespaceInfo, AppError> {
+    let ticket = parse_peer(&peer_id)?;
+    tracing::info!(
+        "Sharing sync namespace {} with {}",
+        namespace_id,
+        ticket.node_id()
+    );
+
+    let manager = state.get_p2p_state().await.map(|(_, manager)| manager);
+    let info = state
+        .namespaces()
+        .add_peer(&namespace_id, ticket.node_id(), manager.as_deref())
+        .await?;
+    window.emit("vdfs-status", &info).ok();
+    Ok(info)
+}
+
+/// List the unresolved conflicts of a namespace
+#[tauri::command]
+pub async fn vdfs_conflicts(
+    state: State<'_, AppState>,
+    namespace_id: String,
+) -> Result<Vec<NamespaceConflict>, AppError> {
+    state.namespaces().conflicts(&namespace_id).await
+}
+
+/// Stop syncing a namespace until it is resumed, across restarts too
+#[tauri::command]
+pub async fn vdfs_pause(
+    state: State<'_, AppState>,
+    window: Window,
+    namespace_id: String,
+) -> Result<NamespaceInfo, AppError> {
+    tracing::info!("Pausing sync namespace {}", namespace_id);
+
+    let namespaces = state.namespaces();
+    namespaces.set_paused(&namespace_id, true).await?;
+    namespaces.stop(&namespace_id).await?;
+    let info = namespaces.info(&namespace_id).await?;
+    window.emit("vdfs-status", &info).ok();
+    Ok(info)
+}
+
+/// Sync a paused namespace again
+#[tauri::command]
+pub async fn vdfs_resume(
+    state: State<'_, AppState>,
+    window: Window,
+    namespace_id: String,
+) -> Result<NamespaceInfo, AppError> {
+    tracing::info!("Resuming sync namespace {}", namespace_id);
+
+    state.namespaces().set_paused(&namespace_id, false).await?;
+    start(&state, window.app_handle(), &namespace_id).await?;
+    let info = state.namespaces().info(&namespace_id).await?;
+    window.emit("vdfs-status", &info).ok();
+    Ok(info)
+}
+
+/// Stop syncing a namespace and forget it; the local folder is kept
+#[tauri::command]
+pub async fn vdfs_remove(state: State<'_, AppState>, namespace_id: String) -> Result<(), AppError> {
+    tracing::info!("Removing sync namespace {}", namespace_id);
+    state.namespaces().remove(&namespace_id).await
+}
+
+/// Start syncing every namespace that is not paused, e.g. on startup
+pub async fn start_all(state: &AppState, app: &AppHandle) {
+    for id in state.namespaces().active_ids().await {
+        if let Err(e) = start(state, app, &id).await {
+            tracing::error!("Failed to start sync namespace {}: {}", id, e);
+        }
+    }
+}
+
+/// Start syncing a namespace unless it is paused or running
+async fn start(state: &AppState, app: &AppHandle, id: &str) -> Result<(), AppError> {
+    let (endpoint, manager) = ensure_p2p_initialized(state).await?;
+    let node_id = endpoint.node_id().to_string();
+    let namespaces = state.namespaces();
+    let Some((folder, changes)) = namespaces.start(id, &node_id, &manager).await? else {
+        return Ok(());
+    };
+
+    let task = tokio::spawn(sync_namespace(
+        state.clone(),
+        manager,
+        app.clone(),
+        folder,
+        changes,
+    ));
+    namespaces.set_task(id, task).await;
+    Ok(())
+}
+
+/// Pull a namespace from its peers at an interval and save what changed,
+/// locally or from them, until the namespace is stopped
+async fn sync_namespace(
+    state: AppState,
+    manager: Arc<P2PConnectionManager>,
+    app: AppHandle,
+    folder: Arc<SyncedFolder>,
+    mut changes: broadcast::Receiver<Vec<WatchChange>>,
+) {
+    let namespaces = state.namespaces();
+    let id = folder.id().to_string();
+    let mut ticker = tokio::time::interval(PULL_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                for (peer, since) in namespaces.pull_targets(&id).await {
+                    let result = pull(&manager, &folder, peer, since).await;
+                    match &result {
+                        Ok(report) if !report.is_empty() => tracing::info!(
+                            "{}: {} updated, {} removed, {} conflicts from {}",
+                            id,
+                            report.updated.len(),
+                            report.removed.len(),
+                            report.conflicts.len(),
+                            peer
+                        ),
+                        Ok(_) => {}
+                        Err(e) => tracing::warn!("{}: could not pull from {}: {}", id, peer, e),
+                    }
+                    namespaces.record_pull(&id, &peer, &result).await;
+                }
+            }
+            batch = changes.recv() => match batch {
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
+        }
+
+        if let Err(e) = namespaces.save(&id).await {
+            tracing::warn!("{}: could not save sync state: {}", id, e);
+        }
+        if let Ok(info) = namespaces.info(&id).await {
+            app.emit("vdfs-status", &info).ok();
+        }
+    }
+}
+
+async fn pull(
+    manager: &P2PConnectionManager,
+    folder: &SyncedFolder,
+    peer: NodeId,
+    since: u64,
+) -> Result<PullReport, String> {
+    manager.connect(peer).await.map_err(|e| e.to_string())?;
+    folder
+        .pull(manager, peer, since)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+fn parse_peer(peer_id: &str) -> Result<PeerTicket, AppError> {
+    peer_id
+        .parse()
+        .map_err(|e| AppError::VdfsError(format!("Invalid peer ID: {}", e)))
+}
+
+/// Namespace ID from a folder name
+fn default_id(path: &Path) -> String {
+    let id: String = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    if id.is_empty() {
+        "folder".to_string()
+    } else {
+        id
+    }
+}
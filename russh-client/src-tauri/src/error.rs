@@ -11,6 +11,9 @@ pub enum AppError {
     #[error("Authentication failed: {0}")]
     AuthenticationFailed(String),
 
+    #[error("Host key mismatch: {0}")]
+    HostKeyMismatch(String),
+
     #[error("Session not found: {0}")]
     SessionNotFound(String),
 
@@ -31,6 +34,12 @@ pub enum AppError {
     #[allow(dead_code)]
     TransferFailed(String),
 
+    #[error("Transfer not found: {0}")]
+    TransferNotFound(String),
+
+    #[error("Edit not found: {0}")]
+    EditNotFound(String),
+
     #[error("P2P connection failed: {0}")]
     #[allow(dead_code)]
     P2PConnectionFailed(String),
@@ -38,6 +47,42 @@ pub enum AppError {
     #[error("Peer not found: {0}")]
     PeerNotFound(String),
 
+    #[error("Port forward failed: {0}")]
+    ForwardFailed(String),
+
+    #[error("Port forward not found: {0}")]
+    ForwardNotFound(String),
+
+    #[error("Key error: {0}")]
+    KeyError(String),
+
+    #[error("Key not found: {0}")]
+    KeyNotFound(String),
+
+    #[error("Snippet error: {0}")]
+    SnippetError(String),
+
+    #[error("Snippet not found: {0}")]
+    SnippetNotFound(String),
+
+    #[error("Vault is locked")]
+    VaultLocked,
+
+    #[error("Vault error: {0}")]
+    VaultError(String),
+
+    #[error("Sync error: {0}")]
+    VdfsError(String),
+
+    #[error("Sync namespace not found: {0}")]
+    NamespaceNotFound(String),
+
+    #[error("Process error: {0}")]
+    ProcessError(String),
+
+    #[error("Terminal error: {0}")]
+    TerminalError(String),
+
     #[error("Settings error: {0}")]
     #[allow(dead_code)]
     SettingsError(String),
@@ -70,6 +115,21 @@ impl From<russh_ssh::ConnectionError> for AppError {
     }
 }
 
+impl From<russh_ssh::error::ForwardError> for AppError {
+    fn from(err: russh_ssh::error::ForwardError) -> Self {
+        match err {
+            russh_ssh::error::ForwardError::NotFound(id) => AppError::ForwardNotFound(id),
+            err => AppError::ForwardFailed(err.to_string()),
+        }
+    }
+}
+
+impl From<russh_ssh::error::VdfsError> for AppError {
+    fn from(err: russh_ssh::error::VdfsError) -> Self {
+        AppError::VdfsError(err.to_string())
+    }
+}
+
 // Make AppError compatible with Tauri's error handling
 impl serde::Serialize for AppError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -90,14 +150,29 @@ impl AppError {
         match self {
             AppError::ConnectionFailed(_) => "CONNECTION_FAILED",
             AppError::AuthenticationFailed(_) => "AUTH_FAILED",
+            AppError::HostKeyMismatch(_) => "HOST_KEY_MISMATCH",
             AppError::SessionNotFound(_) => "SESSION_NOT_FOUND",
             AppError::InvalidAuthMethod => "INVALID_AUTH_METHOD",
             AppError::MissingProfileId => "MISSING_PROFILE_ID",
             AppError::ProfileNotFound(_) => "PROFILE_NOT_FOUND",
             AppError::FileOperationFailed(_) => "FILE_OPERATION_FAILED",
             AppError::TransferFailed(_) => "TRANSFER_FAILED",
+            AppError::TransferNotFound(_) => "TRANSFER_NOT_FOUND",
+            AppError::EditNotFound(_) => "EDIT_NOT_FOUND",
             AppError::P2PConnectionFailed(_) => "P2P_CONNECTION_FAILED",
             AppError::PeerNotFound(_) => "PEER_NOT_FOUND",
+            AppError::ForwardFailed(_) => "FORWARD_FAILED",
+            AppError::ForwardNotFound(_) => "FORWARD_NOT_FOUND",
+            AppError::KeyError(_) => "KEY_ERROR",
+            AppError::KeyNotFound(_) => "KEY_NOT_FOUND",
+            AppError::SnippetError(_) => "SNIPPET_ERROR",
+            AppError::SnippetNotFound(_) => "SNIPPET_NOT_FOUND",
+            AppError::VaultLocked => "VAULT_LOCKED",
+            AppError::VaultError(_) => "VAULT_ERROR",
+            AppError::VdfsError(_) => "VDFS_ERROR",
+            AppError::NamespaceNotFound(_) => "NAMESPACE_NOT_FOUND",
+            AppError::ProcessError(_) => "PROCESS_ERROR",
+            AppError::TerminalError(_) => "TERMINAL_ERROR",
             AppError::SettingsError(_) => "SETTINGS_ERROR",
             AppError::SerializationError(_) => "SERIALIZATION_ERROR",
             AppError::IoError(_) => "IO_ERROR",
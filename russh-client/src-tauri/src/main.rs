@@ -56,9 +56,13 @@ fn main() {
             commands::ssh::ssh_disconnect,
             commands::ssh::ssh_execute,
             commands::ssh::ssh_list_sessions,
+            commands::ssh::ssh_restore_sessions,
             commands::ssh::terminal_start,
             commands::ssh::terminal_input,
             commands::ssh::terminal_resize,
+            commands::local::local_session_open,
+            commands::notifications::terminal_notify,
+            commands::notifications::terminal_notify_cancel,
             // Profile commands
             commands::profiles::profile_create,
             commands::profiles::profile_update,
@@ -68,20 +72,62 @@ fn main() {
             commands::profiles::profile_import,
             // File commands
             commands::files::file_list,
+            commands::files::file_list_stream,
+            commands::files::file_list_cancel,
             commands::files::file_upload,
             commands::files::file_download,
             commands::files::file_delete,
             commands::files::file_rename,
             commands::files::file_mkdir,
+            commands::edits::file_edit_open,
+            commands::edits::file_edit_save,
+            commands::edits::file_edit_close,
+            commands::edits::file_edit_list,
+            // Transfer queue commands
+            commands::transfers::transfer_list,
+            commands::transfers::transfer_pause,
+            commands::transfers::transfer_resume,
+            commands::transfers::transfer_cancel,
+            commands::transfers::transfer_clear_finished,
+            // Port forward commands
+            commands::forwards::forward_start,
+            commands::forwards::forward_stop,
+            commands::forwards::forward_list,
+            // Key commands
+            commands::keys::key_generate,
+            commands::keys::key_import,
+            commands::keys::key_list,
+            commands::keys::key_delete,
+            commands::keys::key_export_public,
+            // Known hosts commands
+            commands::known_hosts::known_hosts_list,
+            commands::known_hosts::known_hosts_remove,
+            commands::known_hosts::known_hosts_trust,
             // P2P commands
             commands::p2p::p2p_get_node_info,
             commands::p2p::p2p_connect,
             commands::p2p::p2p_disconnect,
             commands::p2p::p2p_list_peers,
+            commands::p2p::p2p_create_ticket,
+            commands::p2p::p2p_diagnostics,
+            commands::p2p::p2p_peer_stats,
             commands::p2p::p2p_generate_qr,
+            commands::drops::p2p_send_file,
+            commands::drops::p2p_receive_accept,
+            // Process commands
+            commands::processes::proc_list,
+            commands::processes::proc_kill,
+            commands::processes::proc_signal,
             // Settings commands
             commands::settings::settings_load,
             commands::settings::settings_save,
+            // Snippet commands
+            commands::snippets::snippet_create,
+            commands::snippets::snippet_update,
+            commands::snippets::snippet_delete,
+            commands::snippets::snippet_list,
+            commands::snippets::snippet_render,
+            commands::snippets::snippet_run,
             // Streaming commands
             commands::streaming::stream_create_room,
             commands::streaming::stream_join_room,
@@ -90,8 +136,28 @@ fn main() {
             commands::streaming::stream_sync,
             commands::streaming::stream_update_position,
             commands::streaming::stream_get_expected_position,
+            commands::streaming::stream_report_stall,
+            commands::streaming::stream_get_metrics,
+            // Vault commands
+            commands::vault::vault_status,
+            commands::vault::vault_create,
+            commands::vault::vault_unlock,
+            commands::vault::vault_lock,
+            // Sync commands
+            commands::vdfs::vdfs_list,
+            commands::vdfs::vdfs_status,
+            commands::vdfs::vdfs_create,
+            commands::vdfs::vdfs_join,
+            commands::vdfs::vdfs_share,
+            commands::vdfs::vdfs_conflicts,
+            commands::vdfs::vdfs_pause,
+            commands::vdfs::vdfs_resume,
+            commands::vdfs::vdfs_remove,
         ])
-        .setup(move |_app| {
+        .setup(move |app| {
+            let app = app.handle().clone();
+            commands::drops::forward_drops(&state_clone, app.clone());
+            commands::ssh::watch_idle_sessions(&state_clone, app.clone());
             tauri::async_runtime::spawn(async move {
                 if let Err(e) = state_clone.load_profiles().await {
                     tracing::error!("Failed to load profiles: {}", e);
@@ -99,13 +165,7 @@ fn main() {
                 if let Err(e) = state_clone.load_settings().await {
                     tracing::error!("Failed to load settings: {}", e);
                 }
-                match state_clone.restore_sessions().await {
-                    Ok(sessions) if !sessions.is_empty() => {
-                        tracing::info!("Restored {} session(s) for reconnection", sessions.len());
-                    }
-                    Err(e) => tracing::error!("Failed to restore sessions: {}", e),
-                    _ => {}
-                }
+                commands::vdfs::start_all(&state_clone, &app).await;
             });
             Ok(())
         })
@@ -2,6 +2,7 @@
 
 use chrono::{DateTime, Utc};
 use russh_ssh::p2p::{P2PConnectionManager, P2PEndpoint};
+use russh_ssh::session::{IdlePolicy, SessionManager};
 use russh_ssh::ssh::SshClient;
 use russh_ssh::streaming::StreamSession;
 use serde::{Deserialize, Serialize};
@@ -11,18 +12,49 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
-use super::session_state::{SessionInfo, SessionState};
+use super::command_watches::CommandWatches;
+use super::drops::FileDrops;
+use super::edits::Edits;
+use super::listings::Listings;
+use super::namespaces::SyncNamespaces;
+use super::session_state::{SessionInfo, SessionState, SessionStatus};
+use super::snippets::{Snippet, SnippetLibrary};
+use super::transfers::TransferQueue;
+use super::vault::Vault;
 use crate::error::AppError;
 
 /// Session snapshot for persistence
+///
+/// Holds no secrets: passwords and passphrases come from the keyring again
+/// when the session is restored.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct SessionSnapshot {
-    session_id: String,
-    profile_id: Option<String>,
-    host: String,
-    username: String,
-    port: u16,
-    connected_at: DateTime<Utc>,
+pub struct SessionSnapshot {
+    pub session_id: String,
+    pub profile_id: Option<String>,
+    pub host: String,
+    pub username: String,
+    pub port: u16,
+    pub auth_type: String,
+    pub key_path: Option<String>,
+    /// Whether a terminal was open, so it is started again too
+    pub terminal: bool,
+    pub connected_at: DateTime<Utc>,
+}
+
+/// Profile export file; shared snippets travel along
+#[derive(Debug, Serialize, Deserialize)]
+struct ProfileExport {
+    profiles: Vec<ProfileData>,
+    #[serde(default)]
+    snippets: Vec<Snippet>,
+}
+
+/// Profile import file, also in the older form of just the profiles
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ProfileImport {
+    Export(ProfileExport),
+    Profiles(Vec<ProfileData>),
 }
 
 /// Profile data for saved connections
@@ -39,58 +71,46 @@ pub struct ProfileData {
     pub folder: Option<String>,
     pub color: Option<String>,
     pub auto_reconnect: bool,
+    /// What happens to sessions from this profile once they sit idle
+    #[serde(default)]
+    pub idle_policy: Option<IdlePolicy>,
     #[serde(default)]
     pub use_count: u32,
     pub last_connected: Option<String>,
 }
 
-impl ProfileData {
-    /// Store password securely in system keyring
-    pub fn store_password(&self, password: &str) -> Result<(), AppError> {
-        if let Some(id) = &self.id {
-            let entry = keyring::Entry::new("russh", id)
-                .map_err(|e| AppError::InternalError(format!("Keyring error: {}", e)))?;
-            entry
-                .set_password(password)
-                .map_err(|e| AppError::InternalError(format!("Failed to store password: {}", e)))?;
-        }
-        Ok(())
-    }
+/// Keyring service the app's secrets are stored under
+const KEYRING_SERVICE: &str = "russh";
 
-    /// Retrieve password from system keyring
-    pub fn get_password(&self) -> Result<Option<String>, AppError> {
-        if let Some(id) = &self.id {
-            let entry = keyring::Entry::new("russh", id)
-                .map_err(|e| AppError::InternalError(format!("Keyring error: {}", e)))?;
-            match entry.get_password() {
-                Ok(pwd) => Ok(Some(pwd)),
-                Err(keyring::Error::NoEntry) => Ok(None),
-                Err(e) => Err(AppError::InternalError(format!(
-                    "Failed to retrieve password: {}",
-                    e
-                ))),
-            }
-        } else {
-            Ok(None)
-        }
+fn keyring_entry(account: &str) -> Result<keyring::Entry, AppError> {
+    keyring::Entry::new(KEYRING_SERVICE, account)
+        .map_err(|e| AppError::InternalError(format!("Keyring error: {}", e)))
+}
+
+fn keyring_get(account: &str) -> Result<Option<String>, AppError> {
+    match keyring_entry(account)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(AppError::InternalError(format!(
+            "Failed to retrieve secret: {}",
+            e
+        ))),
     }
+}
 
-    /// Delete password from system keyring
-    pub fn delete_password(&self) -> Result<(), AppError> {
-        if let Some(id) = &self.id {
-            let entry = keyring::Entry::new("russh", id)
-                .map_err(|e| AppError::InternalError(format!("Keyring error: {}", e)))?;
-            match entry.delete_password() {
-                Ok(()) => Ok(()),
-                Err(keyring::Error::NoEntry) => Ok(()), // Already deleted
-                Err(e) => Err(AppError::InternalError(format!(
-                    "Failed to delete password: {}",
-                    e
-                ))),
-            }
-        } else {
-            Ok(())
-        }
+fn keyring_set(account: &str, secret: &str) -> Result<(), AppError> {
+    keyring_entry(account)?
+        .set_password(secret)
+        .map_err(|e| AppError::InternalError(format!("Failed to store secret: {}", e)))
+}
+
+fn keyring_delete(account: &str) -> Result<(), AppError> {
+    match keyring_entry(account)?.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(AppError::InternalError(format!(
+            "Failed to delete secret: {}",
+            e
+        ))),
     }
 }
 
@@ -237,10 +257,36 @@ pub struct P2PPeerInfo {
     pub connected_at: String,
 }
 
+/// P2P link statistics for one peer
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct P2PPeerStats {
+    pub peer_id: String,
+    pub connection_type: String,
+    pub rtt_ms: u64,
+    pub avg_rtt_ms: u64,
+    pub min_rtt_ms: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub send_rate: f64,
+    pub recv_rate: f64,
+    pub lost_packets: u64,
+}
+
+/// P2P NAT traversal diagnostics
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct P2PDiagnostics {
+    pub network: russh_ssh::p2p::NetworkDiagnostics,
+    pub issues: Vec<String>,
+    pub peer: Option<russh_ssh::p2p::PeerDiagnostics>,
+}
+
 /// Main application state
+#[derive(Clone)]
 pub struct AppState {
     /// Active SSH sessions
     sessions: Arc<RwLock<HashMap<String, SessionState>>>,
+    /// Activity of the SSH sessions, for their idle policies
+    idle_tracker: Arc<SessionManager>,
     /// Saved connection profiles
     profiles: Arc<RwLock<HashMap<String, ProfileData>>>,
     /// Application settings
@@ -254,6 +300,23 @@ pub struct AppState {
     /// Stream sessions
     stream_sessions:
         Arc<RwLock<HashMap<String, std::sync::Arc<russh_ssh::streaming::StreamSession>>>>,
+    /// File transfers, queued and finished
+    transfers: Arc<TransferQueue>,
+    /// Saved command snippets
+    snippets: Arc<SnippetLibrary>,
+    /// Directory listings being streamed
+    listings: Arc<Listings>,
+    /// Remote files open in a local editor
+    edits: Arc<Edits>,
+    /// Commands to notify about once they finish
+    command_watches: Arc<CommandWatches>,
+    /// Master-password vault, used for secrets instead of the keyring once
+    /// it is set up
+    vault: Arc<Vault>,
+    /// VDFS namespaces synced with other devices
+    namespaces: Arc<SyncNamespaces>,
+    /// Files peers drop onto this device
+    drops: Arc<FileDrops>,
     /// Data directory path
     data_dir: PathBuf,
 }
@@ -269,12 +332,23 @@ impl AppState {
 
         Self {
             sessions: Arc::new(RwLock::new(HashMap::new())),
+            idle_tracker: Arc::new(SessionManager::new()),
             profiles: Arc::new(RwLock::new(HashMap::new())),
             settings: Arc::new(RwLock::new(AppSettings::default())),
             p2p_endpoint: Arc::new(RwLock::new(None)),
             p2p_manager: Arc::new(RwLock::new(None)),
             p2p_peers: Arc::new(RwLock::new(HashMap::new())),
             stream_sessions: Arc::new(RwLock::new(HashMap::new())),
+            transfers: Arc::new(TransferQueue::default()),
+            snippets: Arc::new(SnippetLibrary::open(data_dir.join("snippets.json"))),
+            listings: Arc::new(Listings::default()),
+            edits: Arc::new(Edits::default()),
+            command_watches: Arc::new(CommandWatches::default()),
+            vault: Arc::new(Vault::new(data_dir.join("vault.json"))),
+            namespaces: Arc::new(SyncNamespaces::open(data_dir.join("sync"))),
+            drops: Arc::new(FileDrops::new(
+                dirs::download_dir().unwrap_or_else(|| data_dir.join("downloads")),
+            )),
             data_dir,
         }
     }
@@ -290,12 +364,13 @@ impl AppState {
         sessions.get(session_id).map(|s| s.info.clone())
     }
 
+    /// SSH client of a session; `None` for local sessions as well
     pub async fn get_session_client(
         &self,
         session_id: &str,
     ) -> Option<std::sync::Arc<tokio::sync::Mutex<russh_ssh::ssh::SshClient>>> {
         let sessions = self.sessions.read().await;
-        sessions.get(session_id).map(|s| s.client.clone())
+        sessions.get(session_id).and_then(|s| s.client.clone())
     }
 
     pub async fn get_session_mut<F, R>(&self, session_id: &str, f: F) -> Option<R>
@@ -320,12 +395,25 @@ impl AppState {
         let mut sessions = self.sessions.write().await;
         if let Some(mut session) = sessions.remove(session_id) {
             session.stop_terminal();
+            session.stop_forward_monitors();
+            if let Ok(id) = Uuid::parse_str(session_id) {
+                // Sessions the idle policy closed are gone already
+                self.idle_tracker.close_session(&id).await.ok();
+            }
             Ok(())
         } else {
             Err(AppError::SessionNotFound(session_id.to_string()))
         }
     }
 
+    /// Reset the idle timer of a session after user input
+    pub async fn record_activity(&self, session_id: &str) {
+        if let Ok(id) = Uuid::parse_str(session_id) {
+            // Untracked sessions have no idle timer to reset
+            self.idle_tracker.record_activity(&id).await.ok();
+        }
+    }
+
     pub async fn get_terminal_input_tx(
         &self,
         session_id: &str,
@@ -336,6 +424,16 @@ impl AppState {
             .and_then(|s| s.terminal_input_tx.clone())
     }
 
+    pub async fn get_terminal_resize_tx(
+        &self,
+        session_id: &str,
+    ) -> Option<tokio::sync::mpsc::Sender<(u16, u16)>> {
+        let sessions = self.sessions.read().await;
+        sessions
+            .get(session_id)
+            .and_then(|s| s.terminal_resize_tx.clone())
+    }
+
     pub async fn list_sessions(&self) -> Vec<SessionInfo> {
         let sessions = self.sessions.read().await;
         sessions.values().map(|s| s.info.clone()).collect()
@@ -373,6 +471,11 @@ impl AppState {
         Ok(())
     }
 
+    pub async fn get_profile(&self, id: &str) -> Option<ProfileData> {
+        let profiles = self.profiles.read().await;
+        profiles.get(id).cloned()
+    }
+
     pub async fn list_profiles(&self) -> Vec<ProfileData> {
         let profiles = self.profiles.read().await;
         profiles.values().cloned().collect()
@@ -423,12 +526,20 @@ impl AppState {
             })
             .collect();
 
-        Ok(serde_json::to_string_pretty(&export_profiles)?)
+        let export = ProfileExport {
+            profiles: export_profiles,
+            snippets: self.snippets.shared().await,
+        };
+        Ok(serde_json::to_string_pretty(&export)?)
     }
 
     pub async fn import_profiles(&self, json_data: &str) -> Result<usize, AppError> {
-        let import_profiles: Vec<ProfileData> = serde_json::from_str(json_data)?;
+        let (import_profiles, import_snippets) = match serde_json::from_str(json_data)? {
+            ProfileImport::Export(export) => (export.profiles, export.snippets),
+            ProfileImport::Profiles(profiles) => (profiles, Vec::new()),
+        };
         let count = import_profiles.len();
+        self.snippets.merge(import_snippets).await?;
 
         let mut profiles = self.profiles.write().await;
         for mut profile in import_profiles {
@@ -445,23 +556,36 @@ impl AppState {
     }
 
     // Session persistence
+    /// Record the connected sessions so the next start can restore them
     pub async fn save_active_sessions(&self) -> Result<(), AppError> {
         let sessions = self.sessions.read().await;
         let snapshots: Vec<SessionSnapshot> = sessions
             .iter()
-            .filter(|(_, s)| matches!(s.status, super::session_state::SessionStatus::Connected))
-            .map(|(id, s)| SessionSnapshot {
-                session_id: id.clone(),
-                profile_id: None,
-                host: s.info.host.clone(),
-                username: s.info.username.clone(),
-                port: 22,
-                connected_at: s.info.connected_at,
+            .filter(|(_, s)| s.info.status == SessionStatus::Connected)
+            .filter_map(|(id, s)| {
+                let origin = s.origin.as_ref()?;
+                Some(SessionSnapshot {
+                    session_id: id.clone(),
+                    profile_id: s.info.profile_id.clone(),
+                    host: s.info.host.clone(),
+                    username: s.info.username.clone(),
+                    port: origin.port,
+                    auth_type: origin.auth_type.clone(),
+                    key_path: origin.key_path.clone(),
+                    terminal: s.has_terminal(),
+                    connected_at: s.info.connected_at,
+                })
             })
             .collect();
 
-        if !snapshots.is_empty() {
-            let path = self.data_dir.join("active_sessions.json");
+        let path = self.data_dir.join("active_sessions.json");
+        if snapshots.is_empty() {
+            match std::fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        } else {
             let content = serde_json::to_string_pretty(&snapshots)?;
             std::fs::write(&path, content)?;
 
@@ -478,7 +602,9 @@ impl AppState {
         Ok(())
     }
 
-    pub async fn restore_sessions(&self) -> Result<Vec<String>, AppError> {
+    /// Take the sessions saved by the last run; reconnecting them is up to
+    /// the caller
+    pub async fn restore_sessions(&self) -> Result<Vec<SessionSnapshot>, AppError> {
         let path = self.data_dir.join("active_sessions.json");
         if !path.exists() {
             return Ok(vec![]);
@@ -492,8 +618,108 @@ impl AppState {
         // Clean up the file after reading
         std::fs::remove_file(&path).ok();
 
-        // Return session IDs for manual reconnection
-        Ok(snapshots.iter().map(|s| s.session_id.clone()).collect())
+        Ok(snapshots)
+    }
+
+    pub fn transfers(&self) -> Arc<TransferQueue> {
+        self.transfers.clone()
+    }
+
+    pub fn snippets(&self) -> Arc<SnippetLibrary> {
+        self.snippets.clone()
+    }
+
+    pub fn listings(&self) -> Arc<Listings> {
+        self.listings.clone()
+    }
+
+    pub fn edits(&self) -> Arc<Edits> {
+        self.edits.clone()
+    }
+
+    pub fn idle_tracker(&self) -> Arc<SessionManager> {
+        self.idle_tracker.clone()
+    }
+
+    pub fn command_watches(&self) -> Arc<CommandWatches> {
+        self.command_watches.clone()
+    }
+
+    pub fn vault(&self) -> Arc<Vault> {
+        self.vault.clone()
+    }
+
+    pub fn drops(&self) -> Arc<FileDrops> {
+        self.drops.clone()
+    }
+
+    pub fn namespaces(&self) -> Arc<SyncNamespaces> {
+        self.namespaces.clone()
+    }
+
+    // Credentials
+    /// Store a secret: profile passwords under the profile ID, key
+    /// passphrases under `key:<name>`
+    ///
+    /// Goes to the vault once one is set up, which must then be unlocked.
+    pub async fn store_secret(&self, account: &str, secret: &str) -> Result<(), AppError> {
+        if self.vault.is_enabled() {
+            self.vault.set(account, secret).await
+        } else {
+            keyring_set(account, secret)
+        }
+    }
+
+    pub async fn get_secret(&self, account: &str) -> Result<Option<String>, AppError> {
+        if self.vault.is_enabled() {
+            self.vault.get(account).await
+        } else {
+            keyring_get(account)
+        }
+    }
+
+    pub async fn delete_secret(&self, account: &str) -> Result<(), AppError> {
+        if self.vault.is_enabled() {
+            self.vault.remove(account).await
+        } else {
+            keyring_delete(account)
+        }
+    }
+
+    /// Set up the vault, moving the secrets of `accounts` that are in the
+    /// keyring into it; returns how many moved
+    pub async fn create_vault(
+        &self,
+        password: &str,
+        auto_lock: std::time::Duration,
+        accounts: Vec<String>,
+    ) -> Result<usize, AppError> {
+        let mut secrets = HashMap::new();
+        for account in accounts {
+            if let Some(secret) = keyring_get(&account)? {
+                secrets.insert(account, secret);
+            }
+        }
+        let moved: Vec<String> = secrets.keys().cloned().collect();
+
+        self.vault.create(password, auto_lock, secrets).await?;
+        for account in &moved {
+            if let Err(e) = keyring_delete(account) {
+                tracing::warn!("Secret {} stays in the keyring: {}", account, e);
+            }
+        }
+        Ok(moved.len())
+    }
+
+    /// Folder the SSH keys managed by the app are stored in
+    pub fn keys_dir(&self) -> PathBuf {
+        self.data_dir.join("keys")
+    }
+
+    /// File holding the P2P node key, which keeps this device's NodeId the
+    /// same across runs
+    pub fn node_key_path(&self) -> PathBuf {
+        self.data_dir.join(russh_ssh::p2p::NODE_KEY_FILE_NAME)
     }
 
     // Settings management
@@ -510,6 +736,10 @@ impl AppState {
         }
     }
 
+    pub async fn get_settings(&self) -> AppSettings {
+        self.settings.read().await.clone()
+    }
+
     pub async fn save_settings(&self, settings: AppSettings) -> Result<(), AppError> {
         let path = self.data_dir.join("settings.json");
         let content = serde_json::to_string_pretty(&settings)?;
@@ -0,0 +1,134 @@
+//! Commands waited on to notify when they finish
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+/// Longest stretch of the current output line kept to look for a prompt
+const MAX_TAIL: usize = 256;
+
+/// A command to notify about once it finishes
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommandWatch {
+    pub session_id: String,
+    /// Command line, if the frontend knows it
+    pub command: Option<String>,
+    pub started_at: DateTime<Utc>,
+}
+
+impl CommandWatch {
+    pub fn new(session_id: String, command: Option<String>) -> Self {
+        Self {
+            session_id,
+            command,
+            started_at: Utc::now(),
+        }
+    }
+}
+
+struct TerminalWatch {
+    watch: CommandWatch,
+    /// Output since the last line break, without escape sequences
+    tail: String,
+}
+
+/// Terminal commands being waited on, by session ID
+///
+/// A terminal gives no exit status, so a command counts as finished once
+/// the shell prints what looks like its prompt again.
+#[derive(Default)]
+pub struct CommandWatches {
+    terminals: RwLock<HashMap<String, TerminalWatch>>,
+}
+
+impl CommandWatches {
+    /// Wait for the command running in a session's terminal, replacing any
+    /// earlier watch of it
+    pub async fn watch_terminal(&self, watch: CommandWatch) {
+        let session_id = watch.session_id.clone();
+        let watch = TerminalWatch {
+            watch,
+            tail: String::new(),
+        };
+        self.terminals.write().await.insert(session_id, watch);
+    }
+
+    pub async fn unwatch_terminal(&self, session_id: &str) -> Option<CommandWatch> {
+        let watch = self.terminals.write().await.remove(session_id)?;
+        Some(watch.watch)
+    }
+
+    /// Look at a session's terminal output; gives back the watch once the
+    /// output ends in a prompt
+    pub async fn terminal_output(&self, session_id: &str, output: &str) -> Option<CommandWatch> {
+        if !self.terminals.read().await.contains_key(session_id) {
+            return None;
+        }
+
+        let mut terminals = self.terminals.write().await;
+        let watch = terminals.get_mut(session_id)?;
+        watch.tail.push_str(&strip_escapes(output));
+        if let Some(end) = watch.tail.rfind(['\n', '\r']) {
+            watch.tail.drain(..=end);
+        }
+        if watch.tail.len() > MAX_TAIL {
+            let mut start = watch.tail.len() - MAX_TAIL;
+            while !watch.tail.is_char_boundary(start) {
+                start += 1;
+            }
+            watch.tail.drain(..start);
+        }
+
+        if !looks_like_prompt(&watch.tail) {
+            return None;
+        }
+        terminals.remove(session_id).map(|w| w.watch)
+    }
+}
+
+/// Whether a line waiting for input looks like a shell prompt, e.g.
+/// `user@host:~$ ` or `root@host:/# `
+///
+/// Prompts end in a space, unlike progress output such as `45%`.
+fn looks_like_prompt(line: &str) -> bool {
+    line.strip_suffix(' ')
+        .is_some_and(|line| line.ends_with(['$', '#', '%', '>', '❯']))
+}
+
+/// Drop ANSI escape sequences (colours, titles, cursor moves) from output
+fn strip_escapes(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters up to a final byte in @..~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: up to BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    out
+}
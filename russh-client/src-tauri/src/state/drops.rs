@@ -0,0 +1,119 @@
+//! Files dropped onto this device by P2P peers
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use russh_ssh::events::EventBus;
+use russh_ssh::p2p::{TransferApproval, TransferManifest};
+use russh_ssh::NodeId;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot, Mutex};
+
+/// How long an incoming drop waits to be accepted
+const APPROVAL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Most files listed in a drop request
+const MAX_LISTED_FILES: usize = 100;
+
+/// One file of an incoming drop
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DropFile {
+    pub path: String,
+    pub size: u64,
+}
+
+/// Incoming drop waiting for the user to accept it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DropRequest {
+    pub transfer_id: String,
+    pub peer_id: String,
+    /// The first files of the drop; `file_count` has them all
+    pub files: Vec<DropFile>,
+    pub file_count: usize,
+    pub total_bytes: u64,
+    pub requested_at: DateTime<Utc>,
+}
+
+/// Incoming drops: asks the user about each one and reports their progress
+pub struct FileDrops {
+    download_dir: PathBuf,
+    pending: Mutex<HashMap<String, oneshot::Sender<bool>>>,
+    requests: broadcast::Sender<DropRequest>,
+    /// Progress of drops being received
+    events: EventBus,
+}
+
+impl FileDrops {
+    pub fn new(download_dir: PathBuf) -> Self {
+        Self {
+            download_dir,
+            pending: Mutex::new(HashMap::new()),
+            requests: broadcast::channel(16).0,
+            events: EventBus::new(),
+        }
+    }
+
+    /// Directory accepted drops are saved to
+    pub fn download_dir(&self) -> &Path {
+        &self.download_dir
+    }
+
+    pub fn events(&self) -> EventBus {
+        self.events.clone()
+    }
+
+    /// Drops waiting for an answer, as they come in
+    pub fn subscribe(&self) -> broadcast::Receiver<DropRequest> {
+        self.requests.subscribe()
+    }
+
+    /// Answer a drop request; false if it is no longer waiting
+    pub async fn answer(&self, transfer_id: &str, accept: bool) -> bool {
+        match self.pending.lock().await.remove(transfer_id) {
+            Some(reply) => reply.send(accept).is_ok(),
+            None => false,
+        }
+    }
+}
+
+#[async_trait]
+impl TransferApproval for FileDrops {
+    async fn approve(&self, peer_id: NodeId, manifest: &TransferManifest) -> Result<(), String> {
+        let transfer_id = manifest.id.to_string();
+        let request = DropRequest {
+            transfer_id: transfer_id.clone(),
+            peer_id: peer_id.to_string(),
+            files: manifest
+                .files
+                .iter()
+                .take(MAX_LISTED_FILES)
+                .map(|file| DropFile {
+                    path: file.path.clone(),
+                    size: file.size,
+                })
+                .collect(),
+            file_count: manifest.files.len(),
+            total_bytes: manifest.total_bytes(),
+            requested_at: Utc::now(),
+        };
+
+        let (reply, answer) = oneshot::channel();
+        self.pending.lock().await.insert(transfer_id.clone(), reply);
+        if self.requests.send(request).is_err() {
+            self.pending.lock().await.remove(&transfer_id);
+            return Err("no one is there to accept it".to_string());
+        }
+
+        let answer = tokio::time::timeout(APPROVAL_TIMEOUT, answer).await;
+        self.pending.lock().await.remove(&transfer_id);
+        match answer {
+            Ok(Ok(true)) => Ok(()),
+            Ok(Ok(false)) => Err("declined".to_string()),
+            Ok(Err(_)) | Err(_) => Err("not accepted in time".to_string()),
+        }
+    }
+}
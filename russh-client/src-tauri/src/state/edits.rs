@@ -0,0 +1,168 @@
+//! Remote files open in a local editor
+
+use chrono::{DateTime, Utc};
+use russh_ssh::encryption::hash::ContentHash;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+
+use crate::error::AppError;
+
+/// Where an edited file stands against the remote copy
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EditStatus {
+    /// The local copy matches what was last downloaded or uploaded
+    Synced,
+    Uploading,
+    /// The remote file changed since it was opened; the local copy was kept
+    Conflict,
+    Failed,
+}
+
+/// Quick-edit information (serializable for frontend)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditInfo {
+    pub edit_id: String,
+    pub session_id: String,
+    pub remote_path: String,
+    /// Temporary copy the editor works on
+    pub local_path: String,
+    pub status: EditStatus,
+    pub error: Option<String>,
+    pub opened_at: DateTime<Utc>,
+    pub saved_at: Option<DateTime<Utc>>,
+}
+
+/// Remote version an edit started from, to tell whether someone else
+/// changed the file meanwhile
+#[derive(Debug, Clone)]
+pub struct RemoteVersion {
+    /// Modification time as `stat` gives it
+    pub modified: String,
+    pub hash: ContentHash,
+}
+
+struct Edit {
+    info: EditInfo,
+    remote: RemoteVersion,
+    /// Hash of the local copy as last downloaded or uploaded
+    local_hash: ContentHash,
+    watcher: Option<JoinHandle<()>>,
+}
+
+/// Files open for editing, by edit ID
+#[derive(Default)]
+pub struct Edits {
+    edits: RwLock<HashMap<String, Edit>>,
+}
+
+impl Edits {
+    pub async fn add(&self, info: EditInfo, remote: RemoteVersion, local_hash: ContentHash) {
+        let edit = Edit {
+            info,
+            remote,
+            local_hash,
+            watcher: None,
+        };
+        self.edits
+            .write()
+            .await
+            .insert(edit.info.edit_id.clone(), edit);
+    }
+
+    /// Keep the task watching an edit's local copy, to stop it on close
+    pub async fn set_watcher(&self, edit_id: &str, watcher: JoinHandle<()>) {
+        match self.edits.write().await.get_mut(edit_id) {
+            Some(edit) => edit.watcher = Some(watcher),
+            None => watcher.abort(),
+        }
+    }
+
+    pub async fn get(&self, edit_id: &str) -> Option<EditInfo> {
+        let edits = self.edits.read().await;
+        edits.get(edit_id).map(|e| e.info.clone())
+    }
+
+    pub async fn list(&self) -> Vec<EditInfo> {
+        let edits = self.edits.read().await;
+        let mut list: Vec<EditInfo> = edits.values().map(|e| e.info.clone()).collect();
+        list.sort_by_key(|e| e.opened_at);
+        list
+    }
+
+    /// Hash of the local copy as last synced
+    pub async fn local_hash(&self, edit_id: &str) -> Option<ContentHash> {
+        let edits = self.edits.read().await;
+        edits.get(edit_id).map(|e| e.local_hash)
+    }
+
+    /// Mark an edit as uploading and get the remote version it is based on
+    ///
+    /// Fails if an upload of the edit is already running.
+    pub async fn begin_upload(&self, edit_id: &str) -> Result<(EditInfo, RemoteVersion), AppError> {
+        let mut edits = self.edits.write().await;
+        let edit = edits
+            .get_mut(edit_id)
+            .ok_or_else(|| AppError::EditNotFound(edit_id.to_string()))?;
+        if edit.info.status == EditStatus::Uploading {
+            return Err(AppError::FileOperationFailed(format!(
+                "{} is already being saved",
+                edit.info.remote_path
+            )));
+        }
+        edit.info.status = EditStatus::Uploading;
+        edit.info.error = None;
+        Ok((edit.info.clone(), edit.remote.clone()))
+    }
+
+    /// Record an upload that went through
+    pub async fn finish_upload(
+        &self,
+        edit_id: &str,
+        remote: RemoteVersion,
+        local_hash: ContentHash,
+    ) -> Option<EditInfo> {
+        let mut edits = self.edits.write().await;
+        let edit = edits.get_mut(edit_id)?;
+        edit.remote = remote;
+        edit.local_hash = local_hash;
+        edit.info.status = EditStatus::Synced;
+        edit.info.saved_at = Some(Utc::now());
+        Some(edit.info.clone())
+    }
+
+    /// Record an upload that did not happen
+    pub async fn fail_upload(
+        &self,
+        edit_id: &str,
+        status: EditStatus,
+        error: String,
+        local_hash: ContentHash,
+    ) -> Option<EditInfo> {
+        let mut edits = self.edits.write().await;
+        let edit = edits.get_mut(edit_id)?;
+        edit.info.status = status;
+        edit.info.error = Some(error);
+        // Not tried again until the file changes once more
+        edit.local_hash = local_hash;
+        Some(edit.info.clone())
+    }
+
+    /// Forget an edit and stop watching it
+    pub async fn remove(&self, edit_id: &str) -> Option<EditInfo> {
+        let edit = self.edits.write().await.remove(edit_id)?;
+        if let Some(watcher) = edit.watcher {
+            watcher.abort();
+        }
+        Some(edit.info)
+    }
+}
+
+/// Folder the local copies of an edit live in
+pub fn edit_dir(edit_id: &str) -> PathBuf {
+    std::env::temp_dir().join("russh-edit").join(edit_id)
+}
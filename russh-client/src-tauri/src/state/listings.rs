@@ -0,0 +1,68 @@
+//! Running directory listings
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::{Notify, RwLock};
+
+/// Cancellation token for one directory listing
+#[derive(Debug, Default)]
+pub struct ListingToken {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl ListingToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the listing is cancelled
+    pub async fn cancelled(&self) {
+        // Registered before the check, so a cancel in between still wakes it
+        let notified = self.notify.notified();
+        if self.is_cancelled() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// Listings in progress, by listing ID
+#[derive(Default)]
+pub struct Listings {
+    tokens: RwLock<HashMap<String, Arc<ListingToken>>>,
+}
+
+impl Listings {
+    /// Register a listing; `None` if the ID is already in use
+    pub async fn start(&self, listing_id: &str) -> Option<Arc<ListingToken>> {
+        let mut tokens = self.tokens.write().await;
+        if tokens.contains_key(listing_id) {
+            return None;
+        }
+        let token = Arc::new(ListingToken::default());
+        tokens.insert(listing_id.to_string(), token.clone());
+        Some(token)
+    }
+
+    /// Cancel a listing; false if it is not running
+    pub async fn cancel(&self, listing_id: &str) -> bool {
+        match self.tokens.read().await.get(listing_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn finish(&self, listing_id: &str) {
+        self.tokens.write().await.remove(listing_id);
+    }
+}
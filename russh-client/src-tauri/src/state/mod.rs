@@ -1,7 +1,23 @@
 //! Application state management
 
 mod app_state;
+mod command_watches;
+mod drops;
+mod edits;
+mod listings;
+mod namespaces;
 mod session_state;
+mod snippets;
+mod transfers;
+mod vault;
 
-pub use app_state::{AppSettings, AppState, P2PNodeInfo, P2PPeerInfo, ProfileData};
-pub use session_state::SessionState;
+pub use app_state::{
+    AppSettings, AppState, P2PDiagnostics, P2PNodeInfo, P2PPeerInfo, ProfileData, SessionSnapshot,
+};
+pub use command_watches::{CommandWatch, CommandWatches};
+pub use edits::{edit_dir, EditInfo, EditStatus, RemoteVersion};
+pub use namespaces::{NamespaceConflict, NamespaceInfo};
+pub use session_state::{SessionKind, SessionOrigin, SessionState};
+pub use snippets::Snippet;
+pub use transfers::{TransferDirection, TransferInfo, TransferQueue, TransferStatus};
+pub use vault::{IdleCheck, VaultStatus, DEFAULT_AUTO_LOCK};
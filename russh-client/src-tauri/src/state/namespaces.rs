@@ -0,0 +1,586 @@
+//! VDFS sync namespaces
+//!
+//! A namespace is a local folder kept in step, through the VDFS, with the
+//! namespace of the same ID on other devices. Namespaces are listed in
+//! `sync/namespaces.json` in the app data directory; what each last synced
+//! is kept next to it in `sync/<id>.json`, so a restart only exchanges what
+//! changed since.
+
+use russh_ssh::p2p::{P2PConnectionManager, PeerCapability};
+use russh_ssh::vdfs::{
+    is_valid_folder_id, FileMetadata, FolderSync, PullReport, SyncedFolder, WatchChange,
+    WatchHandle,
+};
+use russh_ssh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{broadcast, RwLock};
+use tokio::task::JoinHandle;
+
+use crate::error::AppError;
+
+/// File in the sync directory listing the namespaces
+const NAMESPACES_FILE_NAME: &str = "namespaces.json";
+
+/// A namespace as saved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NamespaceConfig {
+    id: String,
+    path: PathBuf,
+    /// NodeIds of the devices the namespace syncs with
+    #[serde(default)]
+    peers: Vec<String>,
+    #[serde(default)]
+    paused: bool,
+}
+
+/// What a namespace last synced, kept between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SavedState {
+    entries: Vec<FileMetadata>,
+    tombstones: Vec<FileMetadata>,
+    /// Each peer's operation clock at the last pull
+    since: HashMap<String, u64>,
+    /// Unix time of the last pull from each peer, in seconds
+    synced_at: HashMap<String, u64>,
+    /// Paths with an unresolved conflict
+    conflicts: Vec<PathBuf>,
+    /// Unix time the state was saved, in seconds
+    updated: u64,
+}
+
+/// A namespace being synced
+struct Running {
+    folder: Arc<SyncedFolder>,
+    /// Dropping it stops the watcher
+    _watcher: WatchHandle,
+    task: Option<JoinHandle<()>>,
+}
+
+struct Namespace {
+    config: NamespaceConfig,
+    saved: SavedState,
+    running: Option<Running>,
+    /// Last pull error from each peer, cleared by a pull that works
+    errors: HashMap<String, String>,
+}
+
+/// Sync status of a peer of a namespace
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NamespacePeer {
+    pub peer_id: String,
+    /// Unix time of the last pull, in seconds
+    pub last_synced_at: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Namespace status for the frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NamespaceInfo {
+    pub id: String,
+    pub path: String,
+    pub peers: Vec<NamespacePeer>,
+    pub paused: bool,
+    /// Whether local changes are being watched and peers pulled from
+    pub syncing: bool,
+    pub file_count: usize,
+    pub total_bytes: u64,
+    pub conflict_count: usize,
+    /// Unix time the sync state was last saved, in seconds
+    pub updated_at: Option<u64>,
+}
+
+/// One side of a conflict
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictVersion {
+    /// NodeId of the device that made the edit
+    pub modified_by: Option<String>,
+    pub size: u64,
+    pub modified: String,
+}
+
+impl From<&FileMetadata> for ConflictVersion {
+    fn from(metadata: &FileMetadata) -> Self {
+        Self {
+            modified_by: metadata.modified_by.clone(),
+            size: metadata.size,
+            modified: metadata.modified.to_rfc3339(),
+        }
+    }
+}
+
+/// Concurrent edits of a file in a namespace
+///
+/// The versions are only known while the namespace is syncing.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NamespaceConflict {
+    /// Path relative to the namespace folder
+    pub path: String,
+    /// Version kept in the folder
+    pub kept: Option<ConflictVersion>,
+    /// Concurrent version that lost
+    pub lost: Option<ConflictVersion>,
+    pub detected_at: Option<String>,
+}
+
+/// Sync namespaces of this device
+pub struct SyncNamespaces {
+    dir: PathBuf,
+    server: FolderSync,
+    namespaces: RwLock<BTreeMap<String, Namespace>>,
+}
+
+impl SyncNamespaces {
+    /// Open the namespaces saved in `dir`, none of them running yet
+    pub fn open(dir: PathBuf) -> Self {
+        let configs: Vec<NamespaceConfig> = read_json(&dir.join(NAMESPACES_FILE_NAME));
+        let namespaces = configs
+            .into_iter()
+            .map(|config| {
+                let saved = read_json(&state_path(&dir, &config.id));
+                let namespace = Namespace {
+                    config,
+                    saved,
+                    running: None,
+                    errors: HashMap::new(),
+                };
+                (namespace.config.id.clone(), namespace)
+            })
+            .collect();
+        Self {
+            dir,
+            server: FolderSync::new(),
+            namespaces: RwLock::new(namespaces),
+        }
+    }
+
+    /// The service peers pull the namespaces from
+    pub fn server(&self) -> &FolderSync {
+        &self.server
+    }
+
+    pub async fn list(&self) -> Vec<NamespaceInfo> {
+        let namespaces = self.namespaces.read().await;
+        namespaces.values().map(Namespace::info).collect()
+    }
+
+    pub async fn info(&self, id: &str) -> Result<NamespaceInfo, AppError> {
+        let namespaces = self.namespaces.read().await;
+        namespaces
+            .get(id)
+            .map(Namespace::info)
+            .ok_or_else(|| AppError::NamespaceNotFound(id.to_string()))
+    }
+
+    /// IDs of the namespaces that are not paused
+    pub async fn active_ids(&self) -> Vec<String> {
+        let namespaces = self.namespaces.read().await;
+        namespaces
+            .values()
+            .filter(|n| !n.config.paused)
+            .map(|n| n.config.id.clone())
+            .collect()
+    }
+
+    /// Add a namespace for the folder at `path`
+    pub async fn add(
+        &self,
+        id: String,
+        path: PathBuf,
+        peers: Vec<NodeId>,
+    ) -> Result<NamespaceInfo, AppError> {
+        if !is_valid_folder_id(&id) {
+            return Err(AppError::VdfsError(format!(
+                "Invalid namespace ID '{}': use letters, digits, '-' and '_'",
+                id
+            )));
+        }
+        if !path.is_dir() {
+            return Err(AppError::VdfsError(format!(
+                "{} is not a folder",
+                path.display()
+            )));
+        }
+        let path = std::fs::canonicalize(&path)?;
+
+        let mut namespaces = self.namespaces.write().await;
+        if let Some(existing) = namespaces
+            .values()
+            .find(|n| n.config.id == id || n.config.path == path)
+        {
+            return Err(AppError::VdfsError(format!(
+                "{} is already synced as '{}'",
+                existing.config.path.display(),
+                existing.config.id
+            )));
+        }
+        let namespace = Namespace {
+            config: NamespaceConfig {
+                id: id.clone(),
+                path,
+                peers: peers.iter().map(|p| p.to_string()).collect(),
+                paused: false,
+            },
+            saved: SavedState::default(),
+            running: None,
+            errors: HashMap::new(),
+        };
+        let info = namespace.info();
+        namespaces.insert(id, namespace);
+        self.persist(&namespaces)?;
+        Ok(info)
+    }
+
+    /// Stop syncing a namespace and forget it; the folder is left as it is
+    pub async fn remove(&self, id: &str) -> Result<(), AppError> {
+        self.stop(id).await?;
+        let mut namespaces = self.namespaces.write().await;
+        namespaces
+            .remove(id)
+            .ok_or_else(|| AppError::NamespaceNotFound(id.to_string()))?;
+        self.persist(&namespaces)?;
+        match std::fs::remove_file(state_path(&self.dir, id)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Sync a namespace with another device as well
+    pub async fn add_peer(
+        &self,
+        id: &str,
+        peer: NodeId,
+        manager: Option<&P2PConnectionManager>,
+    ) -> Result<NamespaceInfo, AppError> {
+        let mut namespaces = self.namespaces.write().await;
+        let namespace = namespaces
+            .get_mut(id)
+            .ok_or_else(|| AppError::NamespaceNotFound(id.to_string()))?;
+        let key = peer.to_string();
+        if !namespace.config.peers.contains(&key) {
+            namespace.config.peers.push(key);
+        }
+        if let (Some(running), Some(manager)) = (&namespace.running, manager) {
+            let peers = namespace.config.node_ids();
+            allow_peers(manager, &peers)?;
+            self.server.share(running.folder.clone(), peers).await;
+        }
+        let info = namespace.info();
+        self.persist(&namespaces)?;
+        Ok(info)
+    }
+
+    pub async fn set_paused(&self, id: &str, paused: bool) -> Result<NamespaceInfo, AppError> {
+        let mut namespaces = self.namespaces.write().await;
+        let namespace = namespaces
+            .get_mut(id)
+            .ok_or_else(|| AppError::NamespaceNotFound(id.to_string()))?;
+        namespace.config.paused = paused;
+        let info = namespace.info();
+        self.persist(&namespaces)?;
+        Ok(info)
+    }
+
+    /// Start syncing a namespace: restore its last state, share it with its
+    /// peers and watch its folder
+    ///
+    /// Returns the folder and its local changes, or `None` if the namespace
+    /// is paused or already running. The caller drives the pulls and hands
+    /// the task doing so to [`SyncNamespaces::set_task`].
+    pub async fn start(
+        &self,
+        id: &str,
+        node_id: &str,
+        manager: &P2PConnectionManager,
+    ) -> Result<Option<(Arc<SyncedFolder>, broadcast::Receiver<Vec<WatchChange>>)>, AppError> {
+        let (config, entries, tombstones) = {
+            let namespaces = self.namespaces.read().await;
+            let namespace = namespaces
+                .get(id)
+                .ok_or_else(|| AppError::NamespaceNotFound(id.to_string()))?;
+            if namespace.config.paused || namespace.running.is_some() {
+                return Ok(None);
+            }
+            (
+                namespace.config.clone(),
+                namespace.saved.entries.clone(),
+                namespace.saved.tombstones.clone(),
+            )
+        };
+
+        // Restoring and scanning a large folder takes a while, so it is done
+        // without holding up the other namespaces
+        let folder = Arc::new(SyncedFolder::new(&config.id, &config.path, node_id)?);
+        folder.restore(entries, tombstones).await?;
+        let watcher = folder.watcher().start().await?;
+        let changes = watcher.subscribe();
+
+        let mut namespaces = self.namespaces.write().await;
+        let namespace = namespaces
+            .get_mut(id)
+            .ok_or_else(|| AppError::NamespaceNotFound(id.to_string()))?;
+        if namespace.config.paused || namespace.running.is_some() {
+            // Paused or started again meanwhile
+            return Ok(None);
+        }
+        let peers = namespace.config.node_ids();
+        allow_peers(manager, &peers)?;
+        self.server.share(folder.clone(), peers).await;
+
+        namespace.running = Some(Running {
+            folder: folder.clone(),
+            _watcher: watcher,
+            task: None,
+        });
+        Ok(Some((folder, changes)))
+    }
+
+    /// Keep the task syncing a running namespace, to stop it with the
+    /// namespace
+    pub async fn set_task(&self, id: &str, task: JoinHandle<()>) {
+        let mut namespaces = self.namespaces.write().await;
+        match namespaces.get_mut(id).and_then(|n| n.running.as_mut()) {
+            Some(running) => running.task = Some(task),
+            None => task.abort(),
+        }
+    }
+
+    /// Stop syncing a namespace, saving what it synced
+    pub async fn stop(&self, id: &str) -> Result<(), AppError> {
+        let running = {
+            let mut namespaces = self.namespaces.write().await;
+            let namespace = namespaces
+                .get_mut(id)
+                .ok_or_else(|| AppError::NamespaceNotFound(id.to_string()))?;
+            namespace.running.take()
+        };
+        if let Some(running) = running {
+            if let Some(task) = &running.task {
+                task.abort();
+            }
+            self.server.unshare(id).await;
+            self.save_folder(id, &running.folder).await?;
+        }
+        Ok(())
+    }
+
+    /// Peers to pull a namespace from, with the clock of the last pull
+    pub async fn pull_targets(&self, id: &str) -> Vec<(NodeId, u64)> {
+        let namespaces = self.namespaces.read().await;
+        let Some(namespace) = namespaces.get(id) else {
+            return Vec::new();
+        };
+        namespace
+            .config
+            .node_ids()
+            .into_iter()
+            .map(|peer| {
+                let since = namespace
+                    .saved
+                    .since
+                    .get(&peer.to_string())
+                    .copied()
+                    .unwrap_or(0);
+                (peer, since)
+            })
+            .collect()
+    }
+
+    /// Record how a pull from a peer went
+    pub async fn record_pull(&self, id: &str, peer: &NodeId, result: &Result<PullReport, String>) {
+        let mut namespaces = self.namespaces.write().await;
+        let Some(namespace) = namespaces.get_mut(id) else {
+            return;
+        };
+        let key = peer.to_string();
+        match result {
+            Ok(report) => {
+                namespace.saved.since.insert(key.clone(), report.clock);
+                namespace.saved.synced_at.insert(key.clone(), unix_now());
+                namespace.errors.remove(&key);
+            }
+            Err(e) => {
+                namespace.errors.insert(key, e.clone());
+            }
+        }
+    }
+
+    /// Save what a running namespace has synced so far
+    pub async fn save(&self, id: &str) -> Result<(), AppError> {
+        let folder = {
+            let namespaces = self.namespaces.read().await;
+            namespaces
+                .get(id)
+                .and_then(|n| n.running.as_ref())
+                .map(|r| r.folder.clone())
+        };
+        match folder {
+            Some(folder) => self.save_folder(id, &folder).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Unresolved conflicts of a namespace
+    ///
+    /// Only known while the namespace runs; otherwise just the paths saved
+    /// at its last run are given.
+    pub async fn conflicts(&self, id: &str) -> Result<Vec<NamespaceConflict>, AppError> {
+        let namespaces = self.namespaces.read().await;
+        let namespace = namespaces
+            .get(id)
+            .ok_or_else(|| AppError::NamespaceNotFound(id.to_string()))?;
+        let Some(running) = &namespace.running else {
+            return Ok(namespace
+                .saved
+                .conflicts
+                .iter()
+                .map(|path| NamespaceConflict {
+                    path: path.to_string_lossy().into_owned(),
+                    kept: None,
+                    lost: None,
+                    detected_at: None,
+                })
+                .collect());
+        };
+
+        let root = running.folder.fs().mount_point();
+        let mut conflicts: Vec<NamespaceConflict> = running
+            .folder
+            .fs()
+            .conflicts()
+            .await
+            .into_iter()
+            .map(|c| NamespaceConflict {
+                path: relative(root, &c.path),
+                kept: Some((&c.winner).into()),
+                lost: Some((&c.loser).into()),
+                detected_at: Some(c.detected_at.to_rfc3339()),
+            })
+            .collect();
+        conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(conflicts)
+    }
+
+    async fn save_folder(&self, id: &str, folder: &SyncedFolder) -> Result<(), AppError> {
+        let entries = folder.entries().await;
+        let tombstones = folder.tombstones(0).await;
+        let root = folder.fs().mount_point();
+        let mut conflicts: Vec<PathBuf> = folder
+            .fs()
+            .conflicts()
+            .await
+            .into_iter()
+            .map(|c| c.path.strip_prefix(root).unwrap_or(&c.path).to_path_buf())
+            .collect();
+        conflicts.sort();
+
+        let mut namespaces = self.namespaces.write().await;
+        let Some(namespace) = namespaces.get_mut(id) else {
+            return Ok(());
+        };
+        let saved = &mut namespace.saved;
+        saved.entries = entries;
+        saved.tombstones = tombstones;
+        saved.conflicts = conflicts;
+        saved.updated = unix_now();
+        write_json(&state_path(&self.dir, id), saved)
+    }
+
+    fn persist(&self, namespaces: &BTreeMap<String, Namespace>) -> Result<(), AppError> {
+        let configs: Vec<&NamespaceConfig> = namespaces.values().map(|n| &n.config).collect();
+        write_json(&self.dir.join(NAMESPACES_FILE_NAME), &configs)
+    }
+}
+
+impl NamespaceConfig {
+    fn node_ids(&self) -> Vec<NodeId> {
+        self.peers.iter().filter_map(|p| p.parse().ok()).collect()
+    }
+}
+
+impl Namespace {
+    fn info(&self) -> NamespaceInfo {
+        let files: Vec<&FileMetadata> = self.saved.entries.iter().filter(|e| e.is_file()).collect();
+        NamespaceInfo {
+            id: self.config.id.clone(),
+            path: self.config.path.to_string_lossy().into_owned(),
+            peers: self
+                .config
+                .peers
+                .iter()
+                .map(|peer| NamespacePeer {
+                    peer_id: peer.clone(),
+                    last_synced_at: self.saved.synced_at.get(peer).copied(),
+                    error: self.errors.get(peer).cloned(),
+                })
+                .collect(),
+            paused: self.config.paused,
+            syncing: self.running.is_some(),
+            file_count: files.len(),
+            total_bytes: files.iter().map(|f| f.size).sum(),
+            conflict_count: self.saved.conflicts.len(),
+            updated_at: Some(self.saved.updated).filter(|&t| t > 0),
+        }
+    }
+}
+
+/// Let the peers of a namespace pull from this device
+fn allow_peers(manager: &P2PConnectionManager, peers: &[NodeId]) -> Result<(), AppError> {
+    if let Some(acl) = manager.acl() {
+        for peer in peers {
+            acl.grant(peer, [PeerCapability::FileSync])
+                .map_err(|e| AppError::VdfsError(e.to_string()))?;
+        }
+    }
+    Ok(())
+}
+
+fn state_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{}.json", id))
+}
+
+/// A virtual path relative to its namespace
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Read a JSON file, falling back to the default if it is missing or
+/// unreadable
+fn read_json<T: serde::de::DeserializeOwned + Default>(path: &Path) -> T {
+    match std::fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring unreadable {}: {}", path.display(), e);
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+/// Write JSON next to the target and move it into place
+fn write_json(path: &Path, value: &impl Serialize) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let partial = path.with_extension("json.tmp");
+    std::fs::write(&partial, serde_json::to_vec_pretty(value)?)?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
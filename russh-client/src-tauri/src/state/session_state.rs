@@ -3,8 +3,10 @@
 use chrono::{DateTime, Utc};
 use russh_ssh::ssh::SshClient;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use uuid::Uuid;
 
 /// Session information (serializable for frontend)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,11 +15,24 @@ pub struct SessionInfo {
     pub profile_id: Option<String>,
     pub host: String,
     pub username: String,
+    #[serde(default)]
+    pub kind: SessionKind,
     pub connected_at: DateTime<Utc>,
     pub status: SessionStatus,
     pub stats: SessionStats,
 }
 
+/// What a session is connected to
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionKind {
+    /// A shell on an SSH host
+    #[default]
+    Ssh,
+    /// A shell in a PTY on this machine
+    Local,
+}
+
 /// Session status
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -37,15 +52,29 @@ pub struct SessionStats {
     pub commands_executed: u64,
 }
 
+/// How a session was opened, kept so it can be opened again
+#[derive(Debug, Clone)]
+pub struct SessionOrigin {
+    pub port: u16,
+    pub auth_type: String,
+    pub key_path: Option<String>,
+}
+
 /// Internal session state (not serialized)
 pub struct SessionState {
     pub info: SessionInfo,
-    /// SSH client handle
-    pub client: Arc<Mutex<SshClient>>,
+    /// SSH client handle; local sessions have none
+    pub client: Option<Arc<Mutex<SshClient>>>,
     /// Terminal output task handle
     pub terminal_task: Option<tokio::task::JoinHandle<()>>,
     /// Terminal input sender
     pub terminal_input_tx: Option<tokio::sync::mpsc::Sender<Vec<u8>>>,
+    /// Terminal size sender, as columns and rows, for terminals that resize
+    pub terminal_resize_tx: Option<tokio::sync::mpsc::Sender<(u16, u16)>>,
+    /// Tasks reporting the traffic of each port forward
+    pub forward_monitors: HashMap<Uuid, tokio::task::JoinHandle<()>>,
+    /// Connection details for reconnecting after a restart
+    pub origin: Option<SessionOrigin>,
 }
 
 impl SessionState {
@@ -56,16 +85,59 @@ impl SessionState {
                 profile_id: None,
                 host,
                 username,
+                kind: SessionKind::Ssh,
                 connected_at: Utc::now(),
                 status: SessionStatus::Connecting,
                 stats: SessionStats::default(),
             },
-            client: Arc::new(Mutex::new(client)),
+            client: Some(Arc::new(Mutex::new(client))),
             terminal_task: None,
             terminal_input_tx: None,
+            terminal_resize_tx: None,
+            forward_monitors: HashMap::new(),
+            origin: None,
         }
     }
 
+    /// Session for a shell on this machine, run by `username`
+    pub fn local(session_id: String, username: String) -> Self {
+        Self {
+            info: SessionInfo {
+                session_id,
+                profile_id: None,
+                host: "localhost".to_string(),
+                username,
+                kind: SessionKind::Local,
+                connected_at: Utc::now(),
+                status: SessionStatus::Connecting,
+                stats: SessionStats::default(),
+            },
+            client: None,
+            terminal_task: None,
+            terminal_input_tx: None,
+            terminal_resize_tx: None,
+            forward_monitors: HashMap::new(),
+            origin: None,
+        }
+    }
+
+    pub fn with_profile(mut self, profile_id: Option<String>) -> Self {
+        self.info.profile_id = profile_id;
+        self
+    }
+
+    pub fn with_origin(mut self, origin: SessionOrigin) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Whether a terminal is open on the session
+    pub fn has_terminal(&self) -> bool {
+        self.terminal_task
+            .as_ref()
+            .is_some_and(|task| !task.is_finished())
+    }
+
     pub fn set_connected(&mut self) {
         self.info.status = SessionStatus::Connected;
     }
@@ -98,5 +170,18 @@ impl SessionState {
             task.abort();
         }
         self.terminal_input_tx = None;
+        self.terminal_resize_tx = None;
+    }
+
+    pub fn stop_forward_monitor(&mut self, forward_id: &Uuid) {
+        if let Some(task) = self.forward_monitors.remove(forward_id) {
+            task.abort();
+        }
+    }
+
+    pub fn stop_forward_monitors(&mut self) {
+        for (_, task) in self.forward_monitors.drain() {
+            task.abort();
+        }
     }
 }
@@ -0,0 +1,222 @@
+//! Saved command snippets
+//!
+//! Snippets are commands kept for reuse, with `{name}` placeholders filled in
+//! when they run. They are stored in `snippets.json` in the app data
+//! directory; the ones marked shared travel with exported profiles.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use tokio::sync::RwLock;
+
+use crate::error::AppError;
+
+/// A saved command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snippet {
+    pub id: String,
+    pub name: String,
+    pub command: String,
+    pub description: Option<String>,
+    pub folder: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Whether the snippet is exported along with profiles
+    #[serde(default)]
+    pub shared: bool,
+    #[serde(default)]
+    pub use_count: u32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Snippet {
+    /// Placeholder names used in the command, each once
+    pub fn variables(&self) -> Vec<String> {
+        let mut names = BTreeSet::new();
+        for_each_placeholder(&self.command, |name| {
+            names.insert(name.to_string());
+            None
+        });
+        names.into_iter().collect()
+    }
+
+    /// The command with every placeholder replaced
+    ///
+    /// Fails naming the placeholders `values` has nothing for.
+    pub fn render(&self, values: &HashMap<String, String>) -> Result<String, AppError> {
+        let mut missing = BTreeSet::new();
+        let rendered = for_each_placeholder(&self.command, |name| {
+            let value = values.get(name).cloned();
+            if value.is_none() {
+                missing.insert(name.to_string());
+            }
+            value
+        });
+        if !missing.is_empty() {
+            let names: Vec<String> = missing.into_iter().map(|n| format!("{{{}}}", n)).collect();
+            return Err(AppError::SnippetError(format!(
+                "No value for {}",
+                names.join(", ")
+            )));
+        }
+        Ok(rendered)
+    }
+}
+
+/// Walk the `{name}` placeholders of a command, replacing each with what
+/// `f` returns, or leaving it as it was
+///
+/// Only word characters count as a name, and `${...}` is left to the shell,
+/// so brace expansion, `awk '{print $1}'` and `find -exec {} ;` pass through.
+fn for_each_placeholder<F>(command: &str, mut f: F) -> String
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut out = String::with_capacity(command.len());
+    let mut rest = command;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let name_len = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(after.len());
+        let is_placeholder =
+            name_len > 0 && after[name_len..].starts_with('}') && !out.ends_with('$');
+        if is_placeholder {
+            let name = &after[..name_len];
+            match f(name) {
+                Some(value) => out.push_str(&value),
+                None => {
+                    out.push('{');
+                    out.push_str(name);
+                    out.push('}');
+                }
+            }
+            rest = &after[name_len + 1..];
+        } else {
+            out.push('{');
+            rest = after;
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Snippets of the current user, kept in a JSON file
+pub struct SnippetLibrary {
+    path: PathBuf,
+    snippets: RwLock<Vec<Snippet>>,
+}
+
+impl SnippetLibrary {
+    /// Open the library at `path`, starting empty if the file is missing or
+    /// unreadable
+    pub fn open(path: PathBuf) -> Self {
+        let snippets = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                tracing::warn!("Ignoring unreadable {}: {}", path.display(), e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self {
+            path,
+            snippets: RwLock::new(snippets),
+        }
+    }
+
+    pub async fn list(&self) -> Vec<Snippet> {
+        self.snippets.read().await.clone()
+    }
+
+    pub async fn get(&self, id: &str) -> Option<Snippet> {
+        let snippets = self.snippets.read().await;
+        snippets.iter().find(|s| s.id == id).cloned()
+    }
+
+    /// Add a snippet, or replace the one with the same ID
+    pub async fn save(&self, snippet: Snippet) -> Result<(), AppError> {
+        let mut snippets = self.snippets.write().await;
+        match snippets.iter_mut().find(|s| s.id == snippet.id) {
+            Some(existing) => *existing = snippet,
+            None => snippets.push(snippet),
+        }
+        self.persist(&snippets)
+    }
+
+    /// Change a snippet and get it back as it is now
+    pub async fn update<F>(&self, id: &str, f: F) -> Result<Snippet, AppError>
+    where
+        F: FnOnce(&mut Snippet),
+    {
+        let mut snippets = self.snippets.write().await;
+        let snippet = snippets
+            .iter_mut()
+            .find(|s| s.id == id)
+            .ok_or_else(|| AppError::SnippetNotFound(id.to_string()))?;
+        f(snippet);
+        let snippet = snippet.clone();
+        self.persist(&snippets)?;
+        Ok(snippet)
+    }
+
+    pub async fn delete(&self, id: &str) -> Result<(), AppError> {
+        let mut snippets = self.snippets.write().await;
+        let before = snippets.len();
+        snippets.retain(|s| s.id != id);
+        if snippets.len() == before {
+            return Err(AppError::SnippetNotFound(id.to_string()));
+        }
+        self.persist(&snippets)
+    }
+
+    /// Snippets marked to be shared with other devices
+    pub async fn shared(&self) -> Vec<Snippet> {
+        let snippets = self.snippets.read().await;
+        snippets.iter().filter(|s| s.shared).cloned().collect()
+    }
+
+    /// Take in snippets from another device, keeping whichever copy of a
+    /// snippet was changed last; returns how many were added or updated
+    pub async fn merge(&self, incoming: Vec<Snippet>) -> Result<usize, AppError> {
+        let mut snippets = self.snippets.write().await;
+        let mut changed = 0;
+        for snippet in incoming {
+            match snippets.iter_mut().find(|s| s.id == snippet.id) {
+                Some(existing) if existing.updated_at >= snippet.updated_at => {}
+                Some(existing) => {
+                    *existing = snippet;
+                    changed += 1;
+                }
+                None => {
+                    snippets.push(snippet);
+                    changed += 1;
+                }
+            }
+        }
+        if changed > 0 {
+            self.persist(&snippets)?;
+        }
+        Ok(changed)
+    }
+
+    fn persist(&self, snippets: &[Snippet]) -> Result<(), AppError> {
+        let content = serde_json::to_string_pretty(snippets)?;
+        std::fs::write(&self.path, content)?;
+
+        // Commands can hold hostnames and paths worth keeping private
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = std::fs::metadata(&self.path)?;
+            let mut permissions = metadata.permissions();
+            permissions.set_mode(0o600);
+            std::fs::set_permissions(&self.path, permissions)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+//! File transfer queue
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, RwLock, Semaphore};
+
+/// Transfers that run at the same time; the rest wait their turn
+pub const MAX_ACTIVE_TRANSFERS: usize = 3;
+
+/// Which way a file goes
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferDirection {
+    Upload,
+    Download,
+}
+
+/// Transfer status
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferStatus {
+    Queued,
+    Active,
+    Paused,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl TransferStatus {
+    /// Whether the transfer will not run again
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Completed | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// Transfer information (serializable for frontend)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferInfo {
+    pub transfer_id: String,
+    pub session_id: String,
+    pub direction: TransferDirection,
+    pub filename: String,
+    pub local_path: String,
+    pub remote_path: String,
+    pub status: TransferStatus,
+    pub bytes_transferred: u64,
+    pub total_bytes: u64,
+    pub speed_bps: u64,
+    pub eta_seconds: u64,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Bumped each time the transfer is (re)started, so a worker left over
+    /// from before a pause knows to stop
+    #[serde(skip)]
+    pub run: u64,
+}
+
+/// Queue of file transfers, in the order they were added
+pub struct TransferQueue {
+    transfers: RwLock<Vec<TransferInfo>>,
+    slots: Arc<Semaphore>,
+}
+
+impl TransferQueue {
+    pub fn new(max_active: usize) -> Self {
+        Self {
+            transfers: RwLock::new(Vec::new()),
+            slots: Arc::new(Semaphore::new(max_active)),
+        }
+    }
+
+    pub async fn add(&self, transfer: TransferInfo) {
+        self.transfers.write().await.push(transfer);
+    }
+
+    pub async fn get(&self, transfer_id: &str) -> Option<TransferInfo> {
+        let transfers = self.transfers.read().await;
+        transfers
+            .iter()
+            .find(|t| t.transfer_id == transfer_id)
+            .cloned()
+    }
+
+    pub async fn list(&self) -> Vec<TransferInfo> {
+        self.transfers.read().await.clone()
+    }
+
+    /// Change a transfer and get it back as it is now
+    pub async fn update<F>(&self, transfer_id: &str, f: F) -> Option<TransferInfo>
+    where
+        F: FnOnce(&mut TransferInfo),
+    {
+        let mut transfers = self.transfers.write().await;
+        let transfer = transfers
+            .iter_mut()
+            .find(|t| t.transfer_id == transfer_id)?;
+        f(transfer);
+        Some(transfer.clone())
+    }
+
+    /// Drop finished transfers from the list; returns how many went
+    pub async fn clear_finished(&self) -> usize {
+        let mut transfers = self.transfers.write().await;
+        let before = transfers.len();
+        transfers.retain(|t| !t.status.is_finished());
+        before - transfers.len()
+    }
+
+    /// Wait for a free slot; it is held until the permit is dropped
+    pub async fn acquire_slot(&self) -> Option<OwnedSemaphorePermit> {
+        self.slots.clone().acquire_owned().await.ok()
+    }
+}
+
+impl Default for TransferQueue {
+    fn default() -> Self {
+        Self::new(MAX_ACTIVE_TRANSFERS)
+    }
+}
@@ -0,0 +1,263 @@
+//! Master-password credential vault
+//!
+//! An optional alternative to the OS keyring for machines shared with others.
+//! Secrets are kept in `vault.json`, sealed with a key derived from the
+//! master password, and only held in memory while the vault is unlocked. The
+//! vault locks itself once it has gone unused for its auto-lock time.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use russh_ssh::encryption::{
+    decrypt_with_aad, encrypt_with_aad, CipherSuite, EncryptionKey, SealedMessage,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use crate::error::AppError;
+
+/// Auto-lock time for new vaults
+pub const DEFAULT_AUTO_LOCK: Duration = Duration::from_secs(15 * 60);
+
+/// Bound into every seal, so a vault file cannot pass for anything else
+const VAULT_AAD: &[u8] = b"russh-client vault v1";
+
+/// The vault file; only the sealed part is secret
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultFile {
+    version: u32,
+    salt: String,
+    auto_lock_secs: u64,
+    secrets: SealedMessage,
+}
+
+struct Unlocked {
+    key: EncryptionKey,
+    salt: String,
+    secrets: HashMap<String, String>,
+    last_used: Instant,
+}
+
+/// Vault status for the frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultStatus {
+    /// Whether a vault has been set up; secrets go to the keyring otherwise
+    pub enabled: bool,
+    pub unlocked: bool,
+    pub auto_lock_secs: u64,
+    /// Seconds until the vault locks unless used again
+    pub locks_in_secs: Option<u64>,
+    pub secret_count: usize,
+}
+
+/// Outcome of [`Vault::lock_if_idle`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleCheck {
+    /// Still in use; locks after this long unless used again
+    Due(Duration),
+    /// Locked by this check
+    Locked,
+    /// Was locked before the check
+    AlreadyLocked,
+}
+
+/// Credential vault backed by one encrypted file
+pub struct Vault {
+    path: PathBuf,
+    unlocked: RwLock<Option<Unlocked>>,
+}
+
+impl Vault {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            unlocked: RwLock::new(None),
+        }
+    }
+
+    /// Whether secrets are kept here rather than in the keyring
+    pub fn is_enabled(&self) -> bool {
+        self.path.exists()
+    }
+
+    pub async fn status(&self) -> VaultStatus {
+        let auto_lock = self.auto_lock();
+        let unlocked = self.unlocked.read().await;
+        VaultStatus {
+            enabled: self.is_enabled(),
+            unlocked: unlocked.is_some(),
+            auto_lock_secs: auto_lock.as_secs(),
+            locks_in_secs: unlocked
+                .as_ref()
+                .map(|u| auto_lock.saturating_sub(u.last_used.elapsed()).as_secs()),
+            secret_count: unlocked.as_ref().map_or(0, |u| u.secrets.len()),
+        }
+    }
+
+    /// Set up the vault with `secrets`, leaving it unlocked
+    pub async fn create(
+        &self,
+        password: &str,
+        auto_lock: Duration,
+        secrets: HashMap<String, String>,
+    ) -> Result<(), AppError> {
+        if self.is_enabled() {
+            return Err(AppError::VaultError(
+                "The vault is already set up".to_string(),
+            ));
+        }
+        check_password(password)?;
+
+        let salt =
+            EncryptionKey::generate_salt().map_err(|e| AppError::VaultError(e.to_string()))?;
+        let salt = STANDARD.encode(salt);
+        let key = derive_key(password, &salt).await?;
+        let unlocked = Unlocked {
+            key,
+            salt,
+            secrets,
+            last_used: Instant::now(),
+        };
+        self.persist(&unlocked, auto_lock)?;
+        *self.unlocked.write().await = Some(unlocked);
+        Ok(())
+    }
+
+    /// Open the vault with the master password
+    pub async fn unlock(&self, password: &str) -> Result<(), AppError> {
+        let file = self.read_file()?;
+        let key = derive_key(password, &file.salt).await?;
+        let plaintext = decrypt_with_aad(CipherSuite::Aes256Gcm, &key, &file.secrets, VAULT_AAD)
+            .map_err(|_| AppError::VaultError("Wrong master password".to_string()))?;
+        let secrets = serde_json::from_slice(&plaintext)?;
+
+        *self.unlocked.write().await = Some(Unlocked {
+            key,
+            salt: file.salt,
+            secrets,
+            last_used: Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// Forget the key and the secrets until the next unlock
+    pub async fn lock(&self) {
+        self.unlocked.write().await.take();
+    }
+
+    /// Lock the vault if it has been idle for its auto-lock time
+    pub async fn lock_if_idle(&self) -> IdleCheck {
+        let auto_lock = self.auto_lock();
+        let mut unlocked = self.unlocked.write().await;
+        let idle = match unlocked.as_ref() {
+            Some(u) => u.last_used.elapsed(),
+            None => return IdleCheck::AlreadyLocked,
+        };
+        if idle >= auto_lock {
+            unlocked.take();
+            return IdleCheck::Locked;
+        }
+        IdleCheck::Due(auto_lock - idle)
+    }
+
+    pub async fn get(&self, name: &str) -> Result<Option<String>, AppError> {
+        let mut unlocked = self.unlocked.write().await;
+        let unlocked = unlocked.as_mut().ok_or(AppError::VaultLocked)?;
+        unlocked.last_used = Instant::now();
+        Ok(unlocked.secrets.get(name).cloned())
+    }
+
+    pub async fn set(&self, name: &str, secret: &str) -> Result<(), AppError> {
+        self.change(|secrets| {
+            secrets.insert(name.to_string(), secret.to_string());
+        })
+        .await
+    }
+
+    pub async fn remove(&self, name: &str) -> Result<(), AppError> {
+        self.change(|secrets| {
+            secrets.remove(name);
+        })
+        .await
+    }
+
+    async fn change<F>(&self, f: F) -> Result<(), AppError>
+    where
+        F: FnOnce(&mut HashMap<String, String>),
+    {
+        let auto_lock = self.auto_lock();
+        let mut unlocked = self.unlocked.write().await;
+        let unlocked = unlocked.as_mut().ok_or(AppError::VaultLocked)?;
+        f(&mut unlocked.secrets);
+        unlocked.last_used = Instant::now();
+        self.persist(unlocked, auto_lock)
+    }
+
+    /// Auto-lock time recorded in the vault file
+    fn auto_lock(&self) -> Duration {
+        self.read_file()
+            .map(|file| Duration::from_secs(file.auto_lock_secs))
+            .unwrap_or(DEFAULT_AUTO_LOCK)
+    }
+
+    fn read_file(&self) -> Result<VaultFile, AppError> {
+        if !self.is_enabled() {
+            return Err(AppError::VaultError("No vault has been set up".to_string()));
+        }
+        let content = std::fs::read_to_string(&self.path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    fn persist(&self, unlocked: &Unlocked, auto_lock: Duration) -> Result<(), AppError> {
+        let plaintext = serde_json::to_vec(&unlocked.secrets)?;
+        let secrets =
+            encrypt_with_aad(CipherSuite::Aes256Gcm, &unlocked.key, &plaintext, VAULT_AAD)
+                .map_err(|e| AppError::VaultError(e.to_string()))?;
+        let file = VaultFile {
+            version: 1,
+            salt: unlocked.salt.clone(),
+            auto_lock_secs: auto_lock.as_secs(),
+            secrets,
+        };
+
+        // Written beside the vault and renamed over it, so a crash midway
+        // cannot leave a vault that no password opens
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&file)?)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+fn check_password(password: &str) -> Result<(), AppError> {
+    if password.chars().count() < 8 {
+        return Err(AppError::VaultError(
+            "The master password needs at least 8 characters".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Derive the vault key off the async runtime; PBKDF2 takes a while by design
+async fn derive_key(password: &str, salt: &str) -> Result<EncryptionKey, AppError> {
+    let salt = STANDARD
+        .decode(salt)
+        .map_err(|e| AppError::VaultError(format!("Damaged vault file: {}", e)))?;
+    if salt.len() < 16 {
+        return Err(AppError::VaultError(
+            "Damaged vault file: short salt".to_string(),
+        ));
+    }
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || EncryptionKey::from_password(password.as_bytes(), &salt))
+        .await
+        .map_err(|e| AppError::InternalError(e.to_string()))
+}
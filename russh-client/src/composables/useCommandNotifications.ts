@@ -0,0 +1,88 @@
+/**
+ * Command notification composable - reports when long-running commands finish
+ */
+
+import { ref, onUnmounted } from 'vue';
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { useNotificationStore } from '@/stores/notifications';
+import type { CommandFinished, CommandWatch } from '@/types/ssh';
+import { parseBackendError } from '@/types/errors';
+
+export function useCommandNotifications() {
+  const notificationStore = useNotificationStore();
+
+  /** Terminal commands waited on, by session ID */
+  const watches = ref<Record<string, CommandWatch>>({});
+  const lastFinished = ref<CommandFinished | null>(null);
+
+  let unlistenFinished: UnlistenFn | null = null;
+
+  async function initialize() {
+    unlistenFinished = await listen<CommandFinished>('command-finished', (event) => {
+      const finished = event.payload;
+      lastFinished.value = finished;
+      if (finished.source === 'terminal') {
+        delete watches.value[finished.sessionId];
+      }
+
+      const label = finished.command ?? 'Command';
+      const took = formatDuration(finished.durationMs);
+      if (finished.error) {
+        notificationStore.error('Command Failed', `${label}: ${finished.error}`);
+      } else if (finished.exitCode !== undefined && finished.exitCode !== 0) {
+        notificationStore.warning('Command Failed', `${label} exited with ${finished.exitCode} after ${took}`);
+      } else {
+        notificationStore.success('Command Finished', `${label} took ${took}`);
+      }
+    });
+  }
+
+  /** Notify when the command running in a session's terminal finishes */
+  async function notifyWhenDone(sessionId: string, command?: string) {
+    try {
+      watches.value[sessionId] = await invoke<CommandWatch>('terminal_notify', { sessionId, command });
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Cannot Watch Command', appError.message);
+      throw e;
+    }
+  }
+
+  async function cancel(sessionId: string) {
+    try {
+      await invoke('terminal_notify_cancel', { sessionId });
+      delete watches.value[sessionId];
+    } catch (e) {
+      console.error('Failed to stop watching command:', e);
+    }
+  }
+
+  function isWatching(sessionId: string): boolean {
+    return sessionId in watches.value;
+  }
+
+  function formatDuration(ms: number): string {
+    const secs = Math.floor(ms / 1000);
+    if (secs < 60) return `${secs}s`;
+    if (secs < 3600) return `${Math.floor(secs / 60)}m ${secs % 60}s`;
+    return `${Math.floor(secs / 3600)}h ${Math.floor((secs % 3600) / 60)}m`;
+  }
+
+  function dispose() {
+    unlistenFinished?.();
+  }
+
+  onUnmounted(dispose);
+
+  return {
+    // State
+    watches,
+    lastFinished,
+    // Actions
+    initialize,
+    notifyWhenDone,
+    cancel,
+    isWatching,
+  };
+}
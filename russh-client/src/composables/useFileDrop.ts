@@ -0,0 +1,135 @@
+/**
+ * File drop composable - sends files straight to other devices over P2P
+ */
+
+import { ref, computed, onUnmounted } from 'vue';
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { getCurrentWebview } from '@tauri-apps/api/webview';
+import { useNotificationStore } from '@/stores/notifications';
+import type { DropProgress, DropReport, DropRequest } from '@/types/p2p';
+import { parseBackendError } from '@/types/errors';
+
+export interface DropTransfer {
+  transferId: string;
+  direction: 'send' | 'receive';
+  /** Progress of each file, by path */
+  files: Record<string, DropProgress>;
+}
+
+export function useFileDrop() {
+  const notificationStore = useNotificationStore();
+
+  const requests = ref<DropRequest[]>([]);
+  const transfers = ref<Record<string, DropTransfer>>({});
+  const isSending = ref(false);
+
+  const activeTransfers = computed(() =>
+    Object.values(transfers.value).filter(t => Object.values(t.files).some(f => !f.done))
+  );
+
+  let unlistenRequest: UnlistenFn | null = null;
+  let unlistenProgress: UnlistenFn | null = null;
+  let unlistenDragDrop: UnlistenFn | null = null;
+
+  async function initialize() {
+    unlistenRequest = await listen<DropRequest>('p2p-transfer-request', (event) => {
+      requests.value.push(event.payload);
+      notificationStore.info(
+        'Incoming Files',
+        `A device wants to send ${event.payload.fileCount} file(s), ${formatBytes(event.payload.totalBytes)}`
+      );
+    });
+    unlistenProgress = await listen<DropProgress>('p2p-transfer-progress', (event) => {
+      const progress = event.payload;
+      if (!transfers.value[progress.transferId]) {
+        transfers.value[progress.transferId] = {
+          transferId: progress.transferId,
+          direction: progress.direction,
+          files: {},
+        };
+      }
+      transfers.value[progress.transferId].files[progress.path] = progress;
+    });
+  }
+
+  /** Send a file or folder to a peer; resolves once it is delivered */
+  async function sendFile(peerId: string, path: string): Promise<DropReport> {
+    isSending.value = true;
+    try {
+      const report = await invoke<DropReport>('p2p_send_file', { peerId, path });
+      notificationStore.success('Files Sent', `${report.fileCount} file(s) delivered`);
+      return report;
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Sending Failed', appError.message);
+      throw e;
+    } finally {
+      isSending.value = false;
+    }
+  }
+
+  /** Send whatever is dragged onto the window to `peerId()`, if it gives one */
+  async function enableDragDrop(peerId: () => string | null) {
+    unlistenDragDrop = await getCurrentWebview().onDragDropEvent(async (event) => {
+      if (event.payload.type !== 'drop') return;
+      const peer = peerId();
+      if (!peer) return;
+      for (const path of event.payload.paths) {
+        await sendFile(peer, path).catch(() => {});
+      }
+    });
+  }
+
+  async function answer(transferId: string, accept: boolean) {
+    requests.value = requests.value.filter(r => r.transferId !== transferId);
+    try {
+      await invoke('p2p_receive_accept', { transferId, accept });
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Transfer Expired', appError.message);
+      throw e;
+    }
+  }
+
+  function accept(transferId: string) {
+    return answer(transferId, true);
+  }
+
+  function decline(transferId: string) {
+    return answer(transferId, false);
+  }
+
+  function formatBytes(bytes: number): string {
+    const units = ['B', 'KB', 'MB', 'GB', 'TB'];
+    let value = bytes;
+    let unit = 0;
+    while (value >= 1024 && unit < units.length - 1) {
+      value /= 1024;
+      unit++;
+    }
+    return `${value.toFixed(unit === 0 ? 0 : 1)} ${units[unit]}`;
+  }
+
+  function dispose() {
+    unlistenRequest?.();
+    unlistenProgress?.();
+    unlistenDragDrop?.();
+  }
+
+  onUnmounted(dispose);
+
+  return {
+    // State
+    requests,
+    transfers,
+    activeTransfers,
+    isSending,
+    // Actions
+    initialize,
+    sendFile,
+    enableDragDrop,
+    accept,
+    decline,
+  };
+}
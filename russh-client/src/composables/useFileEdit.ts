@@ -0,0 +1,111 @@
+/**
+ * Quick-edit composable - edits remote files in a local editor
+ */
+
+import { ref, computed, onUnmounted } from 'vue';
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { useNotificationStore } from '@/stores/notifications';
+import type { EditInfo } from '@/types/edits';
+import { parseBackendError } from '@/types/errors';
+
+export function useFileEdit() {
+  const notificationStore = useNotificationStore();
+
+  const edits = ref<EditInfo[]>([]);
+
+  const conflicts = computed(() => edits.value.filter(e => e.status === 'conflict'));
+
+  let unlistenStatus: UnlistenFn | null = null;
+
+  function applyUpdate(info: EditInfo) {
+    const index = edits.value.findIndex(e => e.editId === info.editId);
+    const previous = index === -1 ? undefined : edits.value[index];
+    if (index === -1) {
+      edits.value.push(info);
+    } else {
+      edits.value[index] = info;
+    }
+
+    if (previous?.status === info.status) return;
+    if (info.status === 'conflict') {
+      notificationStore.warning(
+        'Remote File Changed',
+        `${info.remotePath} was not saved: ${info.error ?? 'it changed on the server'}`
+      );
+    } else if (info.status === 'failed') {
+      notificationStore.error('Save Failed', info.error ?? info.remotePath);
+    }
+  }
+
+  async function initialize() {
+    unlistenStatus = await listen<EditInfo>('file-edit-status', (event) => {
+      applyUpdate(event.payload);
+    });
+    await refresh();
+  }
+
+  async function refresh() {
+    try {
+      edits.value = await invoke<EditInfo[]>('file_edit_list');
+    } catch (e) {
+      console.error('Failed to list edited files:', e);
+    }
+  }
+
+  /** Download a remote file and open it in the local editor */
+  async function openEdit(sessionId: string, remotePath: string): Promise<EditInfo> {
+    try {
+      const info = await invoke<EditInfo>('file_edit_open', { sessionId, remotePath });
+      applyUpdate(info);
+      return info;
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Cannot Edit File', appError.message);
+      throw e;
+    }
+  }
+
+  /** Upload the local copy now; `force` overwrites remote changes */
+  async function saveEdit(editId: string, force = false): Promise<EditInfo> {
+    try {
+      const info = await invoke<EditInfo>('file_edit_save', { editId, force });
+      applyUpdate(info);
+      return info;
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Save Failed', appError.message);
+      throw e;
+    }
+  }
+
+  /** Stop editing a file; changes not uploaded yet are lost */
+  async function closeEdit(editId: string) {
+    try {
+      await invoke('file_edit_close', { editId });
+      edits.value = edits.value.filter(e => e.editId !== editId);
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Closing Failed', appError.message);
+      throw e;
+    }
+  }
+
+  function dispose() {
+    unlistenStatus?.();
+  }
+
+  onUnmounted(dispose);
+
+  return {
+    // State
+    edits,
+    conflicts,
+    // Actions
+    initialize,
+    refresh,
+    openEdit,
+    saveEdit,
+    closeEdit,
+  };
+}
@@ -1,58 +1,92 @@
 /**
- * File transfer composable - handles file listing, upload, download
+ * File transfer composable - handles file listing and the transfer queue
+ *
+ * Uploads and downloads are queued in the backend, which runs a few at a time
+ * and reports every change on `transfer://progress`.
  */
 
 import { ref, computed, onUnmounted } from 'vue';
 import { invoke } from '@tauri-apps/api/core';
 import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { open, save } from '@tauri-apps/plugin-dialog';
 import { useNotificationStore } from '@/stores/notifications';
-import type { TransferItem, FileEntry, TransferProgress } from '@/types/files';
+import type { TransferItem, TransferInfo, FileEntry, FileListBatch } from '@/types/files';
 import { parseBackendError } from '@/types/errors';
 
+function toItem(info: TransferInfo): TransferItem {
+  const total = info.totalBytes || 1;
+  return {
+    id: info.transferId,
+    direction: info.direction,
+    name: info.filename,
+    localPath: info.localPath,
+    remotePath: info.remotePath,
+    status: info.status,
+    transferred: info.bytesTransferred,
+    size: info.totalBytes,
+    progress: Math.min(100, Math.round((info.bytesTransferred / total) * 100)),
+    speed: info.speedBps,
+    error: info.error,
+  };
+}
+
 export function useFileTransfer() {
   const notificationStore = useNotificationStore();
-  
+
   const transfers = ref<TransferItem[]>([]);
   const isLoading = ref(false);
   const error = ref<string | null>(null);
-  
+
   let unlistenProgress: UnlistenFn | null = null;
 
-  const activeTransfers = computed(() => 
-    transfers.value.filter(t => t.status === 'transferring' || t.status === 'pending')
+  const activeTransfers = computed(() =>
+    transfers.value.filter(t => t.status === 'active' || t.status === 'queued')
   );
 
   const completedTransfers = computed(() =>
     transfers.value.filter(t => t.status === 'completed')
   );
 
+  function applyUpdate(info: TransferInfo) {
+    const item = toItem(info);
+    const index = transfers.value.findIndex(t => t.id === item.id);
+    const previous = index !== -1 ? transfers.value[index].status : undefined;
+
+    if (index !== -1) {
+      transfers.value[index] = item;
+    } else {
+      transfers.value.push(item);
+    }
+
+    if (previous === item.status) return;
+    if (item.status === 'completed') {
+      notificationStore.success('Transfer Complete', `${item.name} transferred successfully`);
+    } else if (item.status === 'failed') {
+      notificationStore.error('Transfer Failed', item.error || `${item.name} could not be transferred`);
+    }
+  }
+
   async function initialize() {
-    unlistenProgress = await listen<TransferProgress>('transfer-progress', (event) => {
-      const progress = event.payload;
-      const index = transfers.value.findIndex(t => t.id === progress.transferId);
-      
-      if (index !== -1) {
-        const total = progress.totalBytes || 1;
-        transfers.value[index] = {
-          ...transfers.value[index],
-          transferred: progress.bytesTransferred,
-          size: progress.totalBytes,
-          progress: Math.round((progress.bytesTransferred / total) * 100),
-          speed: progress.speedBps,
-          status: progress.bytesTransferred >= progress.totalBytes ? 'completed' : 'transferring',
-        };
-
-        if (progress.bytesTransferred >= progress.totalBytes) {
-          notificationStore.success('Transfer Complete', `${progress.filename} transferred successfully`);
-        }
-      }
+    unlistenProgress = await listen<TransferInfo>('transfer://progress', (event) => {
+      applyUpdate(event.payload);
     });
+    await refreshTransfers();
+  }
+
+  async function refreshTransfers() {
+    try {
+      const list = await invoke<TransferInfo[]>('transfer_list');
+      transfers.value = list.map(toItem);
+    } catch (e) {
+      const appError = parseBackendError(e);
+      error.value = appError.message;
+    }
   }
 
   async function listFiles(sessionId: string, path: string): Promise<FileEntry[]> {
     isLoading.value = true;
     error.value = null;
-    
+
     try {
       const files = await invoke<FileEntry[]>('file_list', { sessionId, path });
       return files.map(f => ({ ...f, isDirectory: f.isDir ?? f.isDirectory }));
@@ -65,75 +99,100 @@ export function useFileTransfer() {
     }
   }
 
-  async function uploadFile(sessionId: string, remotePath: string, file: File): Promise<string> {
-    const transferId = crypto.randomUUID();
-    
-    transfers.value.push({
-      id: transferId,
-      direction: 'upload',
-      name: file.name,
-      remotePath: `${remotePath}/${file.name}`,
-      status: 'pending',
-      transferred: 0,
-      size: file.size,
-      progress: 0,
-    });
-    
-    try {
-      // Read file as array buffer and send to backend
-      const buffer = await file.arrayBuffer();
-      await invoke('file_upload', {
-        sessionId,
-        remotePath: `${remotePath}/${file.name}`,
-        data: Array.from(new Uint8Array(buffer)),
+  /**
+   * List a directory in batches, calling `onBatch` as entries arrive.
+   * Resolves once the listing is done; `cancel` stops it early.
+   */
+  function streamFiles(
+    sessionId: string,
+    path: string,
+    onBatch: (entries: FileEntry[], batch: FileListBatch) => void,
+    batchSize?: number,
+  ): { done: Promise<void>; cancel: () => Promise<void> } {
+    const listingId = crypto.randomUUID();
+    isLoading.value = true;
+    error.value = null;
+
+    const done = (async () => {
+      let finish!: () => void;
+      let fail!: (reason: unknown) => void;
+      const finished = new Promise<void>((resolve, reject) => {
+        finish = resolve;
+        fail = reject;
+      });
+
+      // Listen first, so the earliest batches are not missed
+      const unlisten = await listen<FileListBatch>('file-list-batch', (event) => {
+        const batch = event.payload;
+        if (batch.listingId !== listingId) return;
+        if (batch.entries.length) {
+          onBatch(batch.entries.map(f => ({ ...f, isDirectory: f.isDir ?? f.isDirectory })), batch);
+        }
+        if (!batch.done) return;
+        if (batch.error) {
+          error.value = batch.error;
+          fail(new Error(batch.error));
+        } else {
+          finish();
+        }
       });
-      
-      const idx = transfers.value.findIndex(t => t.id === transferId);
-      if (idx !== -1) {
-        transfers.value[idx].status = 'completed';
-        transfers.value[idx].progress = 100;
-        transfers.value[idx].transferred = file.size;
+
+      try {
+        await invoke<string>('file_list_stream', { sessionId, path, listingId, batchSize });
+        await finished;
+      } catch (e) {
+        const appError = parseBackendError(e);
+        error.value = appError.message;
+        throw e;
+      } finally {
+        unlisten();
+        isLoading.value = false;
       }
-      
-      return transferId;
+    })();
+
+    async function cancel() {
+      await invoke('file_list_cancel', { listingId });
+    }
+
+    return { done, cancel };
+  }
+
+  async function uploadFile(sessionId: string, remoteDir: string, localPath: string): Promise<string> {
+    const name = localPath.split(/[\\/]/).pop() || localPath;
+    const remotePath = `${remoteDir.replace(/\/$/, '')}/${name}`;
+
+    try {
+      return await invoke<string>('file_upload', { sessionId, localPath, remotePath });
     } catch (e) {
-      const idx = transfers.value.findIndex(t => t.id === transferId);
-      if (idx !== -1) {
-        transfers.value[idx].status = 'failed';
-        transfers.value[idx].error = String(e);
-      }
+      const appError = parseBackendError(e);
+      notificationStore.error('Upload Failed', appError.message);
       throw e;
     }
   }
 
-  async function downloadFile(sessionId: string, remotePath: string, filename: string): Promise<void> {
-    const transferId = crypto.randomUUID();
-    
-    transfers.value.push({
-      id: transferId,
-      direction: 'download',
-      name: filename,
-      remotePath,
-      status: 'pending',
-      transferred: 0,
-      size: 0,
-      progress: 0,
-    });
-    
+  /** Pick local files and queue them for upload into `remoteDir` */
+  async function uploadFiles(sessionId: string, remoteDir: string): Promise<string[]> {
+    const selected = await open({ multiple: true, directory: false });
+    if (!selected) return [];
+
+    const paths = Array.isArray(selected) ? selected : [selected];
+    const ids: string[] = [];
+    for (const localPath of paths) {
+      ids.push(await uploadFile(sessionId, remoteDir, localPath));
+    }
+    return ids;
+  }
+
+  /** Ask where to save a remote file and queue its download */
+  async function downloadFile(sessionId: string, remotePath: string, filename: string): Promise<string | null> {
+    const localPath = await save({ defaultPath: filename });
+    if (!localPath) return null;
+
     try {
-      await invoke('file_download', { sessionId, remotePath, filename });
-      
-      const idx = transfers.value.findIndex(t => t.id === transferId);
-      if (idx !== -1) {
-        transfers.value[idx].status = 'completed';
-        transfers.value[idx].progress = 100;
-      }
+      return await invoke<string>('file_download', { sessionId, remotePath, localPath });
     } catch (e) {
-      const idx = transfers.value.findIndex(t => t.id === transferId);
-      if (idx !== -1) {
-        transfers.value[idx].status = 'failed';
-        transfers.value[idx].error = String(e);
-      }
+      const appError = parseBackendError(e);
+      notificationStore.error('Download Failed', appError.message);
       throw e;
     }
   }
@@ -160,17 +219,44 @@ export function useFileTransfer() {
     }
   }
 
-  function cancelTransfer(id: string) {
-    const idx = transfers.value.findIndex(t => t.id === id);
-    if (idx !== -1) {
-      transfers.value[idx].status = 'cancelled';
+  async function runTransferCommand(command: string, transferId: string) {
+    try {
+      applyUpdate(await invoke<TransferInfo>(command, { transferId }));
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Transfer Error', appError.message);
+    }
+  }
+
+  function pauseTransfer(id: string) {
+    return runTransferCommand('transfer_pause', id);
+  }
+
+  /** Resume a paused transfer, or retry a failed one where it stopped */
+  function resumeTransfer(id: string) {
+    return runTransferCommand('transfer_resume', id);
+  }
+
+  /** Cancel a transfer; the backend removes whatever was already copied */
+  async function cancelTransfer(id: string) {
+    try {
+      await invoke('transfer_cancel', { transferId: id });
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Transfer Error', appError.message);
     }
   }
 
-  function clearCompleted() {
-    transfers.value = transfers.value.filter(t => 
-      t.status !== 'completed' && t.status !== 'cancelled'
-    );
+  async function clearCompleted() {
+    try {
+      await invoke<number>('transfer_clear_finished');
+      transfers.value = transfers.value.filter(t =>
+        t.status !== 'completed' && t.status !== 'failed' && t.status !== 'cancelled'
+      );
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Transfer Error', appError.message);
+    }
   }
 
   function dispose() {
@@ -186,11 +272,16 @@ export function useFileTransfer() {
     activeTransfers,
     completedTransfers,
     initialize,
+    refreshTransfers,
     listFiles,
+    streamFiles,
     uploadFile,
+    uploadFiles,
     downloadFile,
     deleteFile,
     createDirectory,
+    pauseTransfer,
+    resumeTransfer,
     cancelTransfer,
     clearCompleted,
     dispose,
@@ -0,0 +1,89 @@
+/**
+ * Port forward composable - starts, stops and tracks a session's forwards
+ */
+
+import { ref, onUnmounted } from 'vue';
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { useNotificationStore } from '@/stores/notifications';
+import type {
+  ActiveForward,
+  ForwardSpec,
+  ForwardStatsEvent,
+  ForwardStatusEvent,
+} from '@/types/forwards';
+import { parseBackendError } from '@/types/errors';
+
+export function useForwards() {
+  const notificationStore = useNotificationStore();
+
+  const forwards = ref<ActiveForward[]>([]);
+
+  let unlistenStatus: UnlistenFn | null = null;
+  let unlistenStats: UnlistenFn | null = null;
+
+  async function initialize(sessionId?: string) {
+    unlistenStatus = await listen<ForwardStatusEvent>('forward-status', (event) => {
+      const { forwardId, status } = event.payload;
+      if (status === 'stopped') {
+        forwards.value = forwards.value.filter(f => f.forwardId !== forwardId);
+      }
+    });
+    unlistenStats = await listen<ForwardStatsEvent>('forward-stats', (event) => {
+      const forward = forwards.value.find(f => f.forwardId === event.payload.forwardId);
+      if (forward) {
+        forward.bytesTransferred = event.payload.bytesTransferred;
+        forward.bytesPerSecond = event.payload.bytesPerSecond;
+      }
+    });
+    await refresh(sessionId);
+  }
+
+  async function refresh(sessionId?: string) {
+    try {
+      forwards.value = await invoke<ActiveForward[]>('forward_list', { sessionId });
+    } catch (e) {
+      console.error('Failed to list forwards:', e);
+    }
+  }
+
+  async function startForward(sessionId: string, forward: ForwardSpec): Promise<ActiveForward> {
+    try {
+      const active = await invoke<ActiveForward>('forward_start', { sessionId, forward });
+      forwards.value.push(active);
+      return active;
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Port Forward Failed', appError.message);
+      throw e;
+    }
+  }
+
+  async function stopForward(sessionId: string, forwardId: string) {
+    try {
+      await invoke('forward_stop', { sessionId, forwardId });
+      forwards.value = forwards.value.filter(f => f.forwardId !== forwardId);
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Stopping Forward Failed', appError.message);
+      throw e;
+    }
+  }
+
+  function dispose() {
+    unlistenStatus?.();
+    unlistenStats?.();
+  }
+
+  onUnmounted(dispose);
+
+  return {
+    // State
+    forwards,
+    // Actions
+    initialize,
+    refresh,
+    startForward,
+    stopForward,
+  };
+}
@@ -0,0 +1,88 @@
+/**
+ * SSH key composable - manages the keys stored by the app
+ */
+
+import { ref } from 'vue';
+import { invoke } from '@tauri-apps/api/core';
+import { useNotificationStore } from '@/stores/notifications';
+import type { KeyGenerateRequest, KeyImportRequest, KeyInfo } from '@/types/keys';
+import { parseBackendError } from '@/types/errors';
+
+export function useKeys() {
+  const notificationStore = useNotificationStore();
+
+  const keys = ref<KeyInfo[]>([]);
+  const isLoading = ref(false);
+
+  async function refresh() {
+    isLoading.value = true;
+    try {
+      keys.value = await invoke<KeyInfo[]>('key_list');
+    } catch (e) {
+      console.error('Failed to list keys:', e);
+    } finally {
+      isLoading.value = false;
+    }
+  }
+
+  async function generateKey(request: KeyGenerateRequest): Promise<KeyInfo> {
+    try {
+      const key = await invoke<KeyInfo>('key_generate', { request });
+      await refresh();
+      notificationStore.success('Key Generated', `${key.name} (${key.fingerprint})`);
+      return key;
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Key Generation Failed', appError.message);
+      throw e;
+    }
+  }
+
+  async function importKey(request: KeyImportRequest): Promise<KeyInfo> {
+    try {
+      const key = await invoke<KeyInfo>('key_import', { request });
+      await refresh();
+      notificationStore.success('Key Imported', `${key.name} (${key.fingerprint})`);
+      return key;
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Key Import Failed', appError.message);
+      throw e;
+    }
+  }
+
+  async function deleteKey(name: string) {
+    try {
+      await invoke('key_delete', { name });
+      keys.value = keys.value.filter(k => k.name !== name);
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Key Deletion Failed', appError.message);
+      throw e;
+    }
+  }
+
+  async function copyPublicKey(name: string) {
+    try {
+      const publicKey = await invoke<string>('key_export_public', { name });
+      await navigator.clipboard.writeText(publicKey);
+      notificationStore.success('Copied', 'Public key copied to clipboard');
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Copy Failed', appError.message);
+      throw e;
+    }
+  }
+
+  return {
+    // State
+    keys,
+    isLoading,
+    // Actions
+    refresh,
+    generateKey,
+    importKey,
+    deleteKey,
+    copyPublicKey,
+  };
+}
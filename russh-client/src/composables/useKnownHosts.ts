@@ -0,0 +1,86 @@
+/**
+ * Known hosts composable - lists recorded host keys and handles changed ones
+ */
+
+import { ref, onUnmounted } from 'vue';
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { useNotificationStore } from '@/stores/notifications';
+import type { HostKeyMismatch, KnownHostEntry } from '@/types/knownHosts';
+import { parseBackendError } from '@/types/errors';
+
+export function useKnownHosts() {
+  const notificationStore = useNotificationStore();
+
+  const entries = ref<KnownHostEntry[]>([]);
+  /** Changed host key waiting for the user to decide */
+  const pendingMismatch = ref<HostKeyMismatch | null>(null);
+
+  let unlistenMismatch: UnlistenFn | null = null;
+
+  async function initialize() {
+    unlistenMismatch = await listen<HostKeyMismatch>('host-key-mismatch', (event) => {
+      pendingMismatch.value = event.payload;
+    });
+  }
+
+  async function refresh(query?: string) {
+    try {
+      entries.value = await invoke<KnownHostEntry[]>('known_hosts_list', { query });
+    } catch (e) {
+      console.error('Failed to list known hosts:', e);
+    }
+  }
+
+  async function removeHost(host: string, port = 22) {
+    try {
+      await invoke<number>('known_hosts_remove', { host, port });
+      await refresh();
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Removing Host Failed', appError.message);
+      throw e;
+    }
+  }
+
+  /** Trust the key from the pending mismatch; connect again afterwards */
+  async function trustPresentedKey() {
+    const mismatch = pendingMismatch.value;
+    if (!mismatch) return;
+    try {
+      await invoke<KnownHostEntry>('known_hosts_trust', {
+        host: mismatch.host,
+        port: mismatch.port,
+        fingerprint: mismatch.presentedFingerprint,
+      });
+      pendingMismatch.value = null;
+      notificationStore.success('Host Key Trusted', `${mismatch.host} now uses the new key`);
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Trusting Key Failed', appError.message);
+      throw e;
+    }
+  }
+
+  function dismissMismatch() {
+    pendingMismatch.value = null;
+  }
+
+  function dispose() {
+    unlistenMismatch?.();
+  }
+
+  onUnmounted(dispose);
+
+  return {
+    // State
+    entries,
+    pendingMismatch,
+    // Actions
+    initialize,
+    refresh,
+    removeHost,
+    trustPresentedKey,
+    dismissMismatch,
+  };
+}
@@ -6,7 +6,7 @@ import { ref, onUnmounted } from 'vue';
 import { invoke } from '@tauri-apps/api/core';
 import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { useNotificationStore } from '@/stores/notifications';
-import type { P2PNodeInfo, P2PPeer } from '@/types/p2p';
+import type { P2PDiagnostics, P2PNodeInfo, P2PPeer, P2PPeerStats } from '@/types/p2p';
 import { parseBackendError } from '@/types/errors';
 
 export function useP2P() {
@@ -16,6 +16,7 @@ export function useP2P() {
   const peers = ref<P2PPeer[]>([]);
   const isOnline = ref(false);
   const qrCodeData = ref<string | null>(null);
+  const peerStats = ref<P2PPeerStats[]>([]);
   const isLoading = ref(false);
   
   let unlistenStatus: UnlistenFn | null = null;
@@ -69,6 +70,14 @@ export function useP2P() {
     }
   }
 
+  async function refreshPeerStats() {
+    try {
+      peerStats.value = await invoke<P2PPeerStats[]>('p2p_peer_stats');
+    } catch (e) {
+      console.error('Failed to refresh peer stats:', e);
+    }
+  }
+
   async function generateQRCode(): Promise<string> {
     try {
       qrCodeData.value = await invoke<string>('p2p_generate_qr');
@@ -80,6 +89,32 @@ export function useP2P() {
     }
   }
 
+  async function createTicket(): Promise<string> {
+    try {
+      return await invoke<string>('p2p_create_ticket');
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Ticket Creation Failed', appError.message);
+      throw e;
+    }
+  }
+
+  async function copyTicket() {
+    const ticket = await createTicket();
+    await navigator.clipboard.writeText(ticket);
+    notificationStore.success('Copied', 'Connection ticket copied to clipboard');
+  }
+
+  async function runDiagnostics(peerId?: string): Promise<P2PDiagnostics> {
+    try {
+      return await invoke<P2PDiagnostics>('p2p_diagnostics', { peerId });
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Diagnostics Failed', appError.message);
+      throw e;
+    }
+  }
+
   async function copyNodeId() {
     if (nodeInfo.value) {
       await navigator.clipboard.writeText(nodeInfo.value.nodeId);
@@ -107,13 +142,18 @@ export function useP2P() {
     peers,
     isOnline,
     qrCodeData,
+    peerStats,
     isLoading,
     // Actions
     initialize,
     connectToPeer,
     disconnectPeer,
     refreshPeers,
+    refreshPeerStats,
     generateQRCode,
+    createTicket,
+    copyTicket,
+    runDiagnostics,
     copyNodeId,
     getConnectionQuality,
   };
@@ -0,0 +1,93 @@
+/**
+ * Process manager composable - lists and signals processes on a remote host
+ */
+
+import { ref, computed, onUnmounted } from 'vue';
+import { invoke } from '@tauri-apps/api/core';
+import { useNotificationStore } from '@/stores/notifications';
+import type { ProcessInfo, ProcessSignal } from '@/types/processes';
+import { parseBackendError } from '@/types/errors';
+
+export function useProcesses(sessionId: string) {
+  const notificationStore = useNotificationStore();
+
+  const processes = ref<ProcessInfo[]>([]);
+  const filter = ref('');
+  const isLoading = ref(false);
+
+  const filtered = computed(() => {
+    const query = filter.value.trim().toLowerCase();
+    if (!query) return processes.value;
+    return processes.value.filter(p =>
+      p.command.toLowerCase().includes(query) ||
+      p.user.toLowerCase().includes(query) ||
+      String(p.pid) === query
+    );
+  });
+
+  let refreshTimer: ReturnType<typeof setInterval> | null = null;
+
+  async function refresh() {
+    isLoading.value = true;
+    try {
+      processes.value = await invoke<ProcessInfo[]>('proc_list', { sessionId });
+    } catch (e) {
+      console.error('Failed to list processes:', e);
+    } finally {
+      isLoading.value = false;
+    }
+  }
+
+  /** Refresh the list every `intervalMs` until stopped */
+  function startAutoRefresh(intervalMs = 3000) {
+    stopAutoRefresh();
+    refresh();
+    refreshTimer = setInterval(refresh, intervalMs);
+  }
+
+  function stopAutoRefresh() {
+    if (refreshTimer) {
+      clearInterval(refreshTimer);
+      refreshTimer = null;
+    }
+  }
+
+  /** Terminate a process, or kill it outright with `force` */
+  async function killProcess(pid: number, force = false) {
+    try {
+      await invoke('proc_kill', { sessionId, pid, force });
+      await refresh();
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Kill Failed', appError.message);
+      throw e;
+    }
+  }
+
+  async function signalProcess(pid: number, signal: ProcessSignal) {
+    try {
+      await invoke('proc_signal', { sessionId, pid, signal });
+      await refresh();
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Signal Failed', appError.message);
+      throw e;
+    }
+  }
+
+  onUnmounted(stopAutoRefresh);
+
+  return {
+    // State
+    processes,
+    filter,
+    filtered,
+    isLoading,
+    // Actions
+    refresh,
+    startAutoRefresh,
+    stopAutoRefresh,
+    killProcess,
+    signalProcess,
+  };
+}
@@ -5,7 +5,7 @@
 import { ref } from 'vue';
 import { invoke } from '@tauri-apps/api/core';
 import { useNotificationStore } from '@/stores/notifications';
-import type { CommandResult, ConnectionRequest } from '@/types/ssh';
+import type { CommandResult, ConnectionRequest, ConnectionResponse } from '@/types/ssh';
 import { parseBackendError } from '@/types/errors';
 
 export function useSSH(sessionId?: string) {
@@ -36,7 +36,30 @@ export function useSSH(sessionId?: string) {
     }
   }
 
-  async function execute(command: string, timeout?: number): Promise<CommandResult> {
+  /**
+   * Open a session for a shell on this machine; its terminal is started,
+   * fed and closed just like an SSH session's
+   */
+  async function openLocal(): Promise<string> {
+    isConnecting.value = true;
+    error.value = null;
+    
+    try {
+      const response = await invoke<ConnectionResponse>('local_session_open');
+      currentSessionId.value = response.sessionId;
+      return response.sessionId;
+    } catch (e) {
+      const appError = parseBackendError(e);
+      error.value = appError.message;
+      notificationStore.error('Local Terminal Failed', appError.message);
+      throw e;
+    } finally {
+      isConnecting.value = false;
+    }
+  }
+
+  /** Run a command; with `notify` a notification is sent once it exits */
+  async function execute(command: string, timeout?: number, notify = false): Promise<CommandResult> {
     isExecuting.value = true;
     error.value = null;
     
@@ -46,6 +69,7 @@ export function useSSH(sessionId?: string) {
           sessionId: currentSessionId.value,
           command,
           timeoutSecs: timeout,
+          notify,
         }
       });
       lastResult.value = result;
@@ -78,6 +102,7 @@ export function useSSH(sessionId?: string) {
     error,
     sessionId: currentSessionId,
     connect,
+    openLocal,
     execute,
     disconnect,
   };
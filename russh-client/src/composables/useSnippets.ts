@@ -0,0 +1,118 @@
+/**
+ * Snippet composable - manages saved commands and runs them on sessions
+ */
+
+import { ref, computed } from 'vue';
+import { invoke } from '@tauri-apps/api/core';
+import { useNotificationStore } from '@/stores/notifications';
+import type { CommandResult } from '@/types/ssh';
+import type { Snippet, SnippetRequest } from '@/types/snippets';
+import { parseBackendError } from '@/types/errors';
+
+export function useSnippets() {
+  const notificationStore = useNotificationStore();
+
+  const snippets = ref<Snippet[]>([]);
+  const isLoading = ref(false);
+
+  const folders = computed(() => {
+    const folderSet = new Set<string>();
+    snippets.value.forEach(s => {
+      if (s.folder) folderSet.add(s.folder);
+    });
+    return Array.from(folderSet).sort();
+  });
+
+  async function refresh(folder?: string, query?: string) {
+    isLoading.value = true;
+    try {
+      snippets.value = await invoke<Snippet[]>('snippet_list', { folder, query });
+    } catch (e) {
+      console.error('Failed to list snippets:', e);
+    } finally {
+      isLoading.value = false;
+    }
+  }
+
+  async function createSnippet(request: SnippetRequest): Promise<Snippet> {
+    try {
+      const snippet = await invoke<Snippet>('snippet_create', { request });
+      snippets.value.push(snippet);
+      return snippet;
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Snippet Not Saved', appError.message);
+      throw e;
+    }
+  }
+
+  async function updateSnippet(snippetId: string, request: SnippetRequest): Promise<Snippet> {
+    try {
+      const snippet = await invoke<Snippet>('snippet_update', { snippetId, request });
+      const index = snippets.value.findIndex(s => s.id === snippetId);
+      if (index !== -1) snippets.value[index] = snippet;
+      return snippet;
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Snippet Not Saved', appError.message);
+      throw e;
+    }
+  }
+
+  async function deleteSnippet(snippetId: string) {
+    try {
+      await invoke('snippet_delete', { snippetId });
+      snippets.value = snippets.value.filter(s => s.id !== snippetId);
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Snippet Not Deleted', appError.message);
+      throw e;
+    }
+  }
+
+  /** The command with placeholders filled in, e.g. to send to a terminal */
+  async function renderSnippet(
+    snippetId: string,
+    sessionId?: string,
+    variables?: Record<string, string>,
+  ): Promise<string> {
+    return invoke<string>('snippet_render', { snippetId, sessionId, variables });
+  }
+
+  async function runSnippet(
+    snippetId: string,
+    sessionId: string,
+    variables?: Record<string, string>,
+    timeoutSecs?: number,
+  ): Promise<CommandResult> {
+    try {
+      const result = await invoke<CommandResult>('snippet_run', {
+        snippetId,
+        sessionId,
+        variables,
+        timeoutSecs,
+      });
+      const snippet = snippets.value.find(s => s.id === snippetId);
+      if (snippet) snippet.useCount += 1;
+      return result;
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Snippet Failed', appError.message);
+      throw e;
+    }
+  }
+
+  return {
+    // State
+    snippets,
+    isLoading,
+    folders,
+    // Actions
+    refresh,
+    createSnippet,
+    updateSnippet,
+    deleteSnippet,
+    renderSnippet,
+    runSnippet,
+  };
+}
@@ -5,7 +5,7 @@
 import { ref, computed, onUnmounted } from 'vue';
 import { invoke } from '@tauri-apps/api/core';
 import { listen, type UnlistenFn } from '@tauri-apps/api/event';
-import type { StreamRoom, CreateStreamRequest, SyncEvent, SyncEventRequest } from '@/types/streaming';
+import type { StreamRoom, CreateStreamRequest, StreamMetrics, SyncEvent, SyncEventRequest } from '@/types/streaming';
 
 export function useStreaming() {
   const room = ref<StreamRoom | null>(null);
@@ -140,6 +140,23 @@ export function useStreaming() {
     });
   }
 
+  async function reportStall(stalled: boolean): Promise<void> {
+    if (!room.value) return;
+    
+    await invoke('stream_report_stall', {
+      roomId: room.value.roomId,
+      stalled,
+    });
+  }
+
+  async function getMetrics(): Promise<StreamMetrics | null> {
+    if (!room.value) return null;
+    
+    return await invoke<StreamMetrics>('stream_get_metrics', {
+      roomId: room.value.roomId,
+    });
+  }
+
   async function requestSync(): Promise<void> {
     // This would send a sync request to the host
     // For now, just refresh room state
@@ -243,6 +260,8 @@ export function useStreaming() {
     setSpeed,
     updatePosition,
     getExpectedPosition,
+    reportStall,
+    getMetrics,
     requestSync,
   };
 }
@@ -0,0 +1,99 @@
+/**
+ * Vault composable - sets up, unlocks and locks the master-password vault
+ */
+
+import { ref, computed, onUnmounted } from 'vue';
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { useNotificationStore } from '@/stores/notifications';
+import type { VaultLockedEvent, VaultStatus } from '@/types/vault';
+import { parseBackendError } from '@/types/errors';
+
+export function useVault() {
+  const notificationStore = useNotificationStore();
+
+  const status = ref<VaultStatus | null>(null);
+  const isBusy = ref(false);
+
+  /** A vault is set up but its secrets are not available */
+  const needsUnlock = computed(() => !!status.value?.enabled && !status.value.unlocked);
+
+  let unlistenLocked: UnlistenFn | null = null;
+
+  async function initialize() {
+    unlistenLocked = await listen<VaultLockedEvent>('vault-locked', (event) => {
+      if (status.value) {
+        status.value = { ...status.value, unlocked: false, locksInSecs: undefined, secretCount: 0 };
+      }
+      if (event.payload.reason === 'idle') {
+        notificationStore.info('Vault Locked', 'The vault locked after being unused');
+      }
+    });
+    await refresh();
+  }
+
+  async function refresh() {
+    try {
+      status.value = await invoke<VaultStatus>('vault_status');
+    } catch (e) {
+      console.error('Failed to get vault status:', e);
+    }
+  }
+
+  /** Set up the vault; saved passwords and passphrases move into it */
+  async function createVault(password: string, autoLockSecs?: number) {
+    isBusy.value = true;
+    try {
+      status.value = await invoke<VaultStatus>('vault_create', { password, autoLockSecs });
+      notificationStore.success('Vault Created', `${status.value.secretCount} saved credentials moved into the vault`);
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Vault Not Created', appError.message);
+      throw e;
+    } finally {
+      isBusy.value = false;
+    }
+  }
+
+  async function unlock(password: string) {
+    isBusy.value = true;
+    try {
+      status.value = await invoke<VaultStatus>('vault_unlock', { password });
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Unlock Failed', appError.message);
+      throw e;
+    } finally {
+      isBusy.value = false;
+    }
+  }
+
+  async function lock() {
+    try {
+      await invoke('vault_lock');
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Lock Failed', appError.message);
+      throw e;
+    }
+  }
+
+  function dispose() {
+    unlistenLocked?.();
+  }
+
+  onUnmounted(dispose);
+
+  return {
+    // State
+    status,
+    isBusy,
+    needsUnlock,
+    // Actions
+    initialize,
+    refresh,
+    createVault,
+    unlock,
+    lock,
+  };
+}
@@ -0,0 +1,170 @@
+/**
+ * VDFS composable - syncs local folders with other devices
+ */
+
+import { ref, computed, onUnmounted } from 'vue';
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { open } from '@tauri-apps/plugin-dialog';
+import { useNotificationStore } from '@/stores/notifications';
+import type { NamespaceConflict, NamespaceInfo } from '@/types/vdfs';
+import { parseBackendError } from '@/types/errors';
+
+export function useVdfs() {
+  const notificationStore = useNotificationStore();
+
+  const namespaces = ref<NamespaceInfo[]>([]);
+  const conflicts = ref<Record<string, NamespaceConflict[]>>({});
+  const isLoading = ref(false);
+
+  const conflictCount = computed(() =>
+    namespaces.value.reduce((sum, n) => sum + n.conflictCount, 0)
+  );
+
+  let unlistenStatus: UnlistenFn | null = null;
+
+  function applyUpdate(info: NamespaceInfo) {
+    const index = namespaces.value.findIndex(n => n.id === info.id);
+    if (index === -1) {
+      namespaces.value.push(info);
+    } else {
+      namespaces.value[index] = info;
+    }
+  }
+
+  async function initialize() {
+    unlistenStatus = await listen<NamespaceInfo>('vdfs-status', (event) => {
+      applyUpdate(event.payload);
+    });
+    await refresh();
+  }
+
+  async function refresh() {
+    isLoading.value = true;
+    try {
+      namespaces.value = await invoke<NamespaceInfo[]>('vdfs_list');
+    } catch (e) {
+      console.error('Failed to list sync namespaces:', e);
+    } finally {
+      isLoading.value = false;
+    }
+  }
+
+  /** Ask for a local folder to sync; null if the user cancels */
+  async function pickFolder(): Promise<string | null> {
+    const selected = await open({ directory: true, multiple: false });
+    return typeof selected === 'string' ? selected : null;
+  }
+
+  async function createNamespace(localPath: string, namespaceId?: string): Promise<NamespaceInfo> {
+    try {
+      const info = await invoke<NamespaceInfo>('vdfs_create', { localPath, namespaceId });
+      applyUpdate(info);
+      notificationStore.success('Sync Started', `Join it on other devices as "${info.id}"`);
+      return info;
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Sync Not Started', appError.message);
+      throw e;
+    }
+  }
+
+  /** Join a namespace created on another device, identified by `peerId` */
+  async function joinNamespace(
+    namespaceId: string,
+    peerId: string,
+    localPath: string,
+  ): Promise<NamespaceInfo> {
+    try {
+      const info = await invoke<NamespaceInfo>('vdfs_join', { namespaceId, peerId, localPath });
+      applyUpdate(info);
+      return info;
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Joining Failed', appError.message);
+      throw e;
+    }
+  }
+
+  /** Sync a namespace with another device as well */
+  async function shareNamespace(namespaceId: string, peerId: string): Promise<NamespaceInfo> {
+    try {
+      const info = await invoke<NamespaceInfo>('vdfs_share', { namespaceId, peerId });
+      applyUpdate(info);
+      return info;
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Sharing Failed', appError.message);
+      throw e;
+    }
+  }
+
+  async function loadConflicts(namespaceId: string): Promise<NamespaceConflict[]> {
+    try {
+      const list = await invoke<NamespaceConflict[]>('vdfs_conflicts', { namespaceId });
+      conflicts.value[namespaceId] = list;
+      return list;
+    } catch (e) {
+      console.error('Failed to list sync conflicts:', e);
+      return [];
+    }
+  }
+
+  async function pauseNamespace(namespaceId: string) {
+    try {
+      applyUpdate(await invoke<NamespaceInfo>('vdfs_pause', { namespaceId }));
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Pausing Failed', appError.message);
+      throw e;
+    }
+  }
+
+  async function resumeNamespace(namespaceId: string) {
+    try {
+      applyUpdate(await invoke<NamespaceInfo>('vdfs_resume', { namespaceId }));
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Resuming Failed', appError.message);
+      throw e;
+    }
+  }
+
+  /** Stop syncing a namespace; the local folder is kept */
+  async function removeNamespace(namespaceId: string) {
+    try {
+      await invoke('vdfs_remove', { namespaceId });
+      namespaces.value = namespaces.value.filter(n => n.id !== namespaceId);
+      delete conflicts.value[namespaceId];
+    } catch (e) {
+      const appError = parseBackendError(e);
+      notificationStore.error('Removing Failed', appError.message);
+      throw e;
+    }
+  }
+
+  function dispose() {
+    unlistenStatus?.();
+  }
+
+  onUnmounted(dispose);
+
+  return {
+    // State
+    namespaces,
+    conflicts,
+    isLoading,
+    conflictCount,
+    // Actions
+    initialize,
+    refresh,
+    pickFolder,
+    createNamespace,
+    joinNamespace,
+    shareNamespace,
+    loadConflicts,
+    pauseNamespace,
+    resumeNamespace,
+    removeNamespace,
+  };
+}
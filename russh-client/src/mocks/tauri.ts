@@ -99,6 +99,10 @@ const handlers: Record<string, (args?: unknown) => unknown> = {
     return { sessionId: `session-${sessionCounter}` };
   },
   ssh_disconnect: () => null,
+  local_session_open: () => {
+    sessionCounter++;
+    return { sessionId: `session-${sessionCounter}`, connected: true, host: 'localhost', username: 'user' };
+  },
   ssh_execute: () => ({ stdout: 'mock output\n', stderr: '', exitCode: 0 }),
   ssh_keep_alive: () => null,
   ssh_check_connection: () => true,
@@ -132,6 +136,24 @@ const handlers: Record<string, (args?: unknown) => unknown> = {
   p2p_connect: () => mockPeers[0],
   p2p_disconnect: () => null,
   p2p_list_peers: () => mockPeers,
+  p2p_create_ticket: () => 'russh1mock-ticket',
+  p2p_peer_stats: () => [],
+  p2p_diagnostics: () => ({
+    network: {
+      node_id: 'mock-node-id-12345',
+      nat_type: 'endpoint_independent',
+      report_available: true,
+      udp: true,
+      ipv4: true,
+      ipv6: false,
+      global_v4: '203.0.113.7:61000',
+      global_v6: null,
+      home_relay: 'wss://relay.example.com',
+      candidates: [{ addr: '192.168.1.10:9000', kind: 'local' }],
+    },
+    issues: [],
+    peer: null,
+  }),
   p2p_generate_qr: () => 'russh://connect?node=mock-node-id-12345',
   p2p_send_message: () => null,
   p2p_send_typing: () => null,
@@ -5,7 +5,8 @@
 import { defineStore } from 'pinia';
 import { ref, computed } from 'vue';
 import { invoke } from '@tauri-apps/api/core';
-import type { ConnectionProfile, ConnectionState, ConnectionStats } from '@/types/ssh';
+import { listen } from '@tauri-apps/api/event';
+import type { ConnectionProfile, ConnectionState, ConnectionStats, RestoreProgress } from '@/types/ssh';
 
 export const useConnectionStore = defineStore('connections', () => {
   // State
@@ -107,6 +108,7 @@ export const useConnectionStore = defineStore('connections', () => {
           authType: profile.authType,
           password,
           keyPath: profile.keyPath,
+          profileId,
         }
       });
 
@@ -137,6 +139,34 @@ export const useConnectionStore = defineStore('connections', () => {
     }
   }
 
+  /**
+   * Reconnect the sessions left open when the app last closed.
+   * Sessions opened without a profile are tracked under their session ID.
+   */
+  async function restoreSessions(): Promise<string[]> {
+    const unlisten = await listen<RestoreProgress>('session-restore-progress', (event) => {
+      const progress = event.payload;
+      const key = progress.profileId ?? progress.sessionId;
+      activeConnections.value.set(key, {
+        sessionId: progress.status === 'failed' ? '' : progress.sessionId,
+        profileId: key,
+        status: progress.status === 'failed' ? 'error' : progress.status,
+        connectedAt: progress.status === 'connected' ? new Date() : undefined,
+        error: progress.error,
+        stats: createEmptyStats(),
+      });
+    });
+
+    try {
+      return await invoke<string[]>('ssh_restore_sessions');
+    } catch (e) {
+      console.error('Failed to restore sessions:', e);
+      return [];
+    } finally {
+      unlisten();
+    }
+  }
+
   async function disconnect(profileId: string) {
     const connection = activeConnections.value.get(profileId);
     if (!connection) return;
@@ -201,6 +231,7 @@ export const useConnectionStore = defineStore('connections', () => {
     updateProfile,
     deleteProfile,
     connect,
+    restoreSessions,
     disconnect,
     getConnection,
     getConnectionBySessionId,
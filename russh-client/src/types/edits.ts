@@ -0,0 +1,18 @@
+/**
+ * Quick-edit type definitions
+ */
+
+export type EditStatus = 'synced' | 'uploading' | 'conflict' | 'failed';
+
+export interface EditInfo {
+  editId: string;
+  sessionId: string;
+  remotePath: string;
+  /** Temporary copy the editor works on */
+  localPath: string;
+  status: EditStatus;
+  /** Why the last upload did not happen */
+  error?: string;
+  openedAt: string;
+  savedAt?: string;
+}
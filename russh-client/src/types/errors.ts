@@ -24,6 +24,34 @@ export const ErrorCodes = {
   FILE_NOT_FOUND: 'FILE_NOT_FOUND',
   PERMISSION_DENIED: 'PERMISSION_DENIED',
   TRANSFER_FAILED: 'TRANSFER_FAILED',
+  TRANSFER_NOT_FOUND: 'TRANSFER_NOT_FOUND',
+  EDIT_NOT_FOUND: 'EDIT_NOT_FOUND',
+  
+  // Port forward errors
+  FORWARD_FAILED: 'FORWARD_FAILED',
+  FORWARD_NOT_FOUND: 'FORWARD_NOT_FOUND',
+  
+  // Key errors
+  KEY_ERROR: 'KEY_ERROR',
+  KEY_NOT_FOUND: 'KEY_NOT_FOUND',
+  
+  // Snippet errors
+  SNIPPET_ERROR: 'SNIPPET_ERROR',
+  SNIPPET_NOT_FOUND: 'SNIPPET_NOT_FOUND',
+  
+  // Vault errors
+  VAULT_LOCKED: 'VAULT_LOCKED',
+  VAULT_ERROR: 'VAULT_ERROR',
+  
+  // Sync errors
+  VDFS_ERROR: 'VDFS_ERROR',
+  NAMESPACE_NOT_FOUND: 'NAMESPACE_NOT_FOUND',
+  
+  // Process errors
+  PROCESS_ERROR: 'PROCESS_ERROR',
+  
+  // Terminal errors
+  TERMINAL_ERROR: 'TERMINAL_ERROR',
   
   // P2P errors
   PEER_NOT_FOUND: 'PEER_NOT_FOUND',
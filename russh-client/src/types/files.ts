@@ -28,20 +28,41 @@ export interface TransferItem {
 }
 
 export type TransferStatus = 
-  | 'pending' 
-  | 'transferring'
+  | 'queued' 
+  | 'active'
   | 'paused'
   | 'completed' 
   | 'failed' 
   | 'cancelled';
 
-export interface TransferProgress {
+/** Transfer as kept in the backend queue, sent with every `transfer://progress` event */
+export interface TransferInfo {
   transferId: string;
+  sessionId: string;
+  direction: 'upload' | 'download';
   filename: string;
+  localPath: string;
+  remotePath: string;
+  status: TransferStatus;
   bytesTransferred: number;
   totalBytes: number;
   speedBps: number;
   etaSeconds: number;
+  error?: string;
+  createdAt: string;
+}
+
+/** Part of a listing from `file_list_stream`, sent as `file-list-batch` */
+export interface FileListBatch {
+  listingId: string;
+  sessionId: string;
+  path: string;
+  entries: FileEntry[];
+  loaded: number;
+  total?: number;
+  done: boolean;
+  cancelled: boolean;
+  error?: string;
 }
 
 export interface FileOperation {
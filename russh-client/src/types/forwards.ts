@@ -0,0 +1,31 @@
+/**
+ * Port forward type definitions
+ */
+
+export type ForwardSpec =
+  | { type: 'local'; localPort: number; remoteHost: string; remotePort: number }
+  | { type: 'remote'; remotePort: number; localHost: string; localPort: number }
+  | { type: 'dynamic'; localPort: number };
+
+export interface ActiveForward {
+  forwardId: string;
+  sessionId: string;
+  forward: ForwardSpec;
+  bytesTransferred: number;
+  bytesPerSecond?: number;
+}
+
+export interface ForwardStatusEvent {
+  sessionId: string;
+  forwardId?: string;
+  status: 'active' | 'stopped' | 'failed';
+  forward?: ForwardSpec;
+  error?: string;
+}
+
+export interface ForwardStatsEvent {
+  sessionId: string;
+  forwardId: string;
+  bytesTransferred: number;
+  bytesPerSecond: number;
+}
@@ -1,7 +1,15 @@
 // Re-export all types
 export * from './ssh';
 export * from './files';
+export * from './edits';
+export * from './forwards';
+export * from './keys';
+export * from './knownHosts';
 export * from './p2p';
+export * from './processes';
 export * from './settings';
+export * from './snippets';
+export * from './vault';
+export * from './vdfs';
 export * from './errors';
 export * from './blocks';
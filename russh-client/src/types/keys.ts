@@ -0,0 +1,35 @@
+/**
+ * SSH key type definitions
+ */
+
+export type KeyType = 'ed25519' | 'rsa' | 'ecdsa';
+
+export interface KeyInfo {
+  name: string;
+  algorithm: string;
+  fingerprint: string;
+  comment: string;
+  encrypted: boolean;
+  passphraseSaved: boolean;
+  path: string;
+  publicKey: string;
+}
+
+export interface KeyGenerateRequest {
+  name: string;
+  keyType: KeyType;
+  bits?: number;
+  comment?: string;
+  passphrase?: string;
+  rememberPassphrase?: boolean;
+}
+
+export interface KeyImportRequest {
+  name: string;
+  /** Private key file to copy */
+  path?: string;
+  /** Pasted private key, used when no path is given */
+  privateKey?: string;
+  passphrase?: string;
+  rememberPassphrase?: boolean;
+}
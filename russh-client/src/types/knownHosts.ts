@@ -0,0 +1,21 @@
+/**
+ * Known hosts type definitions
+ */
+
+export interface KnownHostEntry {
+  hosts: string[];
+  hashed: boolean;
+  marker: 'cert-authority' | 'revoked' | null;
+  keyType: string;
+  fingerprint: string;
+  comment: string | null;
+}
+
+/** Payload of the `host-key-mismatch` event */
+export interface HostKeyMismatch {
+  host: string;
+  port: number;
+  keyType: string;
+  knownFingerprint: string;
+  presentedFingerprint: string;
+}
@@ -28,3 +28,96 @@ export interface P2PConnectionQuality {
   bandwidth: number;
   stability: 'excellent' | 'good' | 'fair' | 'poor';
 }
+
+/** Link statistics for one peer (field names match the backend) */
+export interface P2PPeerStats {
+  peer_id: string;
+  connection_type: string;
+  rtt_ms: number;
+  avg_rtt_ms: number;
+  min_rtt_ms: number;
+  bytes_sent: number;
+  bytes_received: number;
+  send_rate: number;
+  recv_rate: number;
+  lost_packets: number;
+}
+
+/** NAT traversal diagnostics (field names match the backend report) */
+export type NatType =
+  | 'no_udp'
+  | 'open'
+  | 'endpoint_independent'
+  | 'endpoint_dependent'
+  | 'unknown';
+
+export interface P2PNetworkDiagnostics {
+  node_id: string;
+  nat_type: NatType;
+  report_available: boolean;
+  udp: boolean;
+  ipv4: boolean;
+  ipv6: boolean;
+  global_v4: string | null;
+  global_v6: string | null;
+  home_relay: string | null;
+  candidates: { addr: string; kind: 'local' | 'stun' | 'portmapped' | 'stun4_local_port' | 'unknown' }[];
+}
+
+export interface P2PPeerDiagnostics {
+  peer_id: string;
+  path: 'direct' | 'relay' | 'mixed' | 'none';
+  direct_addr: string | null;
+  relay_url: string | null;
+  relay_latency_ms: number | null;
+  latency_ms: number | null;
+  candidates: {
+    addr: string;
+    outcome: 'active' | 'reachable' | 'no_response' | 'untried';
+    latency_ms: number | null;
+    last_alive_ms: number | null;
+  }[];
+  hole_punch: 'succeeded' | 'in_progress' | 'failed' | 'not_attempted';
+}
+
+export interface P2PDiagnostics {
+  network: P2PNetworkDiagnostics;
+  issues: string[];
+  peer: P2PPeerDiagnostics | null;
+}
+
+export interface DropFile {
+  path: string;
+  size: number;
+}
+
+/** Incoming file drop waiting to be accepted */
+export interface DropRequest {
+  transferId: string;
+  peerId: string;
+  /** The first files of the drop; `fileCount` has them all */
+  files: DropFile[];
+  fileCount: number;
+  totalBytes: number;
+  requestedAt: string;
+}
+
+export interface DropProgress {
+  transferId: string;
+  direction: 'send' | 'receive';
+  /** Path of the file within the drop */
+  path: string;
+  bytes: number;
+  total: number;
+  /** Whether this file is complete */
+  done: boolean;
+}
+
+export interface DropReport {
+  transferId: string;
+  peerId: string;
+  fileCount: number;
+  bytesTransferred: number;
+  /** Bytes the peer already had, from an earlier interrupted drop */
+  resumedBytes: number;
+}
@@ -0,0 +1,22 @@
+/**
+ * Remote process type definitions
+ */
+
+export interface ProcessInfo {
+  pid: number;
+  ppid: number;
+  user: string;
+  /** CPU usage in percent of one core */
+  cpu: number;
+  /** Share of physical memory in percent */
+  mem: number;
+  /** Resident memory in bytes */
+  rssBytes: number;
+  /** Time since the process started, as `ps` gives it */
+  elapsed: string;
+  state: string;
+  command: string;
+}
+
+export type ProcessSignal =
+  | 'HUP' | 'INT' | 'QUIT' | 'KILL' | 'USR1' | 'USR2' | 'TERM' | 'CONT' | 'STOP' | 'TSTP';
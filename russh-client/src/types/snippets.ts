@@ -0,0 +1,32 @@
+/**
+ * Command snippet type definitions
+ */
+
+export interface Snippet {
+  id: string;
+  name: string;
+  /** Command with `{name}` placeholders */
+  command: string;
+  description?: string;
+  folder?: string;
+  tags: string[];
+  /** Exported along with profiles, to reach other devices */
+  shared: boolean;
+  useCount: number;
+  createdAt: string;
+  updatedAt: string;
+  /** Placeholders the command uses */
+  variables: string[];
+}
+
+export interface SnippetRequest {
+  name: string;
+  command: string;
+  description?: string;
+  folder?: string;
+  tags?: string[];
+  shared?: boolean;
+}
+
+/** Placeholders a session fills in by itself */
+export const SESSION_VARIABLES = ['host', 'port', 'user'] as const;
@@ -14,10 +14,18 @@ export interface ConnectionProfile {
   folder?: string;
   color?: string;
   autoReconnect: boolean;
+  /** What happens to sessions from this profile once they sit idle */
+  idlePolicy?: IdlePolicy;
   lastConnected?: string;
   useCount: number;
 }
 
+export interface IdlePolicy {
+  /** Milliseconds without input before the policy applies */
+  timeout: number;
+  action: 'Disconnect' | 'KeepAlive';
+}
+
 export interface ConnectionState {
   sessionId: string;
   profileId: string;
@@ -27,6 +35,9 @@ export interface ConnectionState {
   stats: ConnectionStats;
 }
 
+/** `local` sessions run a shell on this machine rather than an SSH host */
+export type SessionKind = 'ssh' | 'local';
+
 export type ConnectionStatus = 
   | 'connecting' 
   | 'connected' 
@@ -56,6 +67,19 @@ export interface ConnectionRequest {
   password?: string;
   keyPath?: string;
   keyPassphrase?: string;
+  profileId?: string;
+}
+
+/** Sent before and after each session reconnected at startup */
+export interface RestoreProgress {
+  sessionId: string;
+  profileId?: string;
+  host: string;
+  username: string;
+  status: 'connecting' | 'connected' | 'failed';
+  error?: string;
+  current: number;
+  total: number;
 }
 
 export interface ConnectionResponse {
@@ -69,4 +93,26 @@ export interface CommandRequest {
   sessionId: string;
   command: string;
   timeoutSecs?: number;
+  /** Send a notification when the command exits */
+  notify?: boolean;
+}
+
+/** Command waited on to notify when it finishes */
+export interface CommandWatch {
+  sessionId: string;
+  command?: string;
+  startedAt: string;
+}
+
+export interface CommandFinished {
+  sessionId: string;
+  command?: string;
+  source: 'exec' | 'terminal';
+  /** Not known for terminal commands */
+  exitCode?: number;
+  /** Why an exec command could not be run */
+  error?: string;
+  durationMs: number;
+  startedAt: string;
+  finishedAt: string;
 }
@@ -1,5 +1,5 @@
 /**
- * Streaming types for synchronized video playback
+ * Streaming types for synchronized video and audio playback
  */
 
 export interface StreamRoom {
@@ -15,7 +15,27 @@ export interface StreamRoom {
 export type StreamSource =
   | { type: 'url'; url: string }
   | { type: 'localFile'; path: string; size: number }
-  | { type: 'p2pFile'; hostId: string; fileId: string; size: number };
+  | { type: 'p2pFile'; hostId: string; fileId: string; size: number }
+  | { type: 'audio'; tracks: Track[]; current: number }
+  | { type: 'renditions'; renditions: Rendition[] }
+  | { type: 'hls'; playlistUrl: string }
+  | { type: 'dash'; manifestUrl: string }
+  | { type: 'terminal'; term: string; cols: number; rows: number };
+
+export interface Rendition {
+  label: string;
+  bitrate: number;
+  source: StreamSource;
+}
+
+export interface Track {
+  source: StreamSource;
+  title: string;
+  artist?: string;
+  album?: string;
+  duration?: number;
+  artworkUrl?: string;
+}
 
 export interface PlaybackState {
   playing: boolean;
@@ -25,24 +45,43 @@ export interface PlaybackState {
 }
 
 export interface SyncEvent {
-  type: 'play' | 'pause' | 'seek' | 'speed' | 'peerJoined' | 'peerLeft' | 'sourceChanged' | 'requestSync' | 'stateSync';
+  type: 'play' | 'pause' | 'seek' | 'speed' | 'peerJoined' | 'peerLeft' | 'sourceChanged' | 'requestSync' | 'stateSync' | 'trackChanged' | 'hostChanged' | 'sourceUnavailable';
   position?: number;
   speed?: number;
   peerId?: string;
   source?: StreamSource;
   state?: PlaybackState;
+  index?: number;
+  startTime?: number;
+  hostId?: string;
+  reason?: string;
+}
+
+export interface StreamMetrics {
+  rebufferCount: number;
+  rebufferSecs: number;
+  rebuffering: boolean;
+  bufferLevel: number;
+  avgBufferLevel: number;
+  syncDrift?: number;
+  avgSyncDrift?: number;
+  maxSyncDrift: number;
+  bytesFetched: Record<string, number>;
+  elapsedSecs: number;
 }
 
 export interface CreateStreamRequest {
   name: string;
-  sourceType: 'url' | 'file';
+  sourceType: 'url' | 'file' | 'audio';
   url?: string;
   filePath?: string;
+  title?: string;
+  artist?: string;
 }
 
 export interface SyncEventRequest {
   roomId: string;
-  eventType: 'play' | 'pause' | 'seek' | 'speed';
+  eventType: 'play' | 'pause' | 'seek' | 'speed' | 'next_track';
   position?: number;
   speed?: number;
 }
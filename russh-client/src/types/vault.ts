@@ -0,0 +1,17 @@
+/**
+ * Credential vault type definitions
+ */
+
+export interface VaultStatus {
+  /** Whether a vault is set up; secrets go to the OS keyring otherwise */
+  enabled: boolean;
+  unlocked: boolean;
+  autoLockSecs: number;
+  /** Seconds until the vault locks unless used again */
+  locksInSecs?: number;
+  secretCount: number;
+}
+
+export interface VaultLockedEvent {
+  reason: 'manual' | 'idle';
+}
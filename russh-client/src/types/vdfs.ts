@@ -0,0 +1,43 @@
+/**
+ * VDFS sync type definitions
+ */
+
+export interface NamespacePeer {
+  peerId: string;
+  /** Unix time of the last pull, in seconds */
+  lastSyncedAt?: number;
+  /** Why the last pull failed */
+  error?: string;
+}
+
+export interface NamespaceInfo {
+  id: string;
+  /** Local folder synced */
+  path: string;
+  peers: NamespacePeer[];
+  paused: boolean;
+  /** Whether local changes are watched and peers pulled from */
+  syncing: boolean;
+  fileCount: number;
+  totalBytes: number;
+  conflictCount: number;
+  /** Unix time the sync state was last saved, in seconds */
+  updatedAt?: number;
+}
+
+export interface ConflictVersion {
+  /** NodeId of the device that made the edit */
+  modifiedBy?: string;
+  size: number;
+  modified: string;
+}
+
+export interface NamespaceConflict {
+  /** Path relative to the namespace folder */
+  path: string;
+  /** Version kept in the folder; only known while syncing */
+  kept?: ConflictVersion;
+  /** Concurrent version that lost */
+  lost?: ConflictVersion;
+  detectedAt?: string;
+}
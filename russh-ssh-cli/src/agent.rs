@@ -0,0 +1,174 @@
+//! `russh agent`
+//!
+//! Manages keys in the running ssh-agent, or runs a built-in agent that
+//! serves the keys russh manages under `<config dir>/keys`. Passphrases of
+//! those keys can be kept in the OS keyring (Keychain, Secret Service,
+//! Windows Credential Manager), so the built-in agent can start without
+//! asking for them.
+
+use crate::AgentAction;
+use russh_ssh::ssh::{AgentClient, AgentIdentity, KeyAgent, SshKeyPair};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+/// Keyring service under which key passphrases are stored
+const KEYRING_SERVICE: &str = "russh-ssh";
+
+/// Run an agent command
+pub async fn run(action: AgentAction, keys_dir: &Path, config_dir: &Path) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        run_unix(action, keys_dir, config_dir).await
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (action, keys_dir, config_dir);
+        anyhow::bail!("SSH agent sockets are only supported on Unix")
+    }
+}
+
+#[cfg(unix)]
+async fn run_unix(action: AgentAction, keys_dir: &Path, config_dir: &Path) -> anyhow::Result<()> {
+    match action {
+        AgentAction::List => {
+            let identities = AgentClient::connect_env().await?.identities().await?;
+            if identities.is_empty() {
+                println!("The agent has no keys.");
+            }
+            for identity in identities {
+                println!(
+                    "{} {} ({})",
+                    identity.fingerprint,
+                    identity.comment,
+                    key_algorithm(&identity)
+                );
+            }
+        }
+        AgentAction::Add { files, remember } => {
+            let mut agent = AgentClient::connect_env().await?;
+            for path in key_files(files, keys_dir)? {
+                let key = load_key(&path, remember)?;
+                agent.add(&key).await?;
+                println!("Identity added: {} ({})", path.display(), key.comment());
+            }
+        }
+        AgentAction::Remove { files, all } => {
+            let mut agent = AgentClient::connect_env().await?;
+            if all {
+                agent.remove_all().await?;
+                println!("All identities removed.");
+            }
+            for path in files {
+                agent.remove(&identity_of(&path)?).await?;
+                println!("Identity removed: {}", path.display());
+            }
+        }
+        AgentAction::Serve { socket } => {
+            let socket = socket.unwrap_or_else(|| config_dir.join("agent.sock"));
+            let agent = KeyAgent::new();
+            // Keys can still be added with ssh-add when there are none
+            for path in key_files(Vec::new(), keys_dir).unwrap_or_default() {
+                match load_key(&path, false) {
+                    Ok(key) => agent.add(key),
+                    Err(e) => eprintln!("Skipping {}: {:#}", path.display(), e),
+                }
+            }
+            println!("SSH_AUTH_SOCK={}; export SSH_AUTH_SOCK;", socket.display());
+            eprintln!(
+                "Serving {} key(s); press Ctrl-C to stop",
+                agent.identities().len()
+            );
+
+            let result: anyhow::Result<()> = tokio::select! {
+                result = agent.serve(&socket) => result.map_err(Into::into),
+                result = tokio::signal::ctrl_c() => result.map_err(Into::into),
+            };
+            let _ = std::fs::remove_file(&socket);
+            result?;
+        }
+    }
+    Ok(())
+}
+
+/// The given key files, or every private key under the keys directory
+fn key_files(files: Vec<PathBuf>, keys_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !files.is_empty() {
+        return Ok(files);
+    }
+    let mut files: Vec<PathBuf> = match std::fs::read_dir(keys_dir) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && !path.extension().is_some_and(|ext| ext == "pub"))
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    files.sort();
+    if files.is_empty() {
+        anyhow::bail!(
+            "No keys in {} (create one with 'russh keygen')",
+            keys_dir.display()
+        );
+    }
+    Ok(files)
+}
+
+/// Load a private key, taking its passphrase from the keyring or the user
+///
+/// With `remember`, a passphrase typed in is saved to the keyring.
+fn load_key(path: &Path, remember: bool) -> anyhow::Result<SshKeyPair> {
+    if !SshKeyPair::is_encrypted_file(path)? {
+        return Ok(SshKeyPair::load(path, None)?);
+    }
+    let entry = keyring_entry(path)?;
+    if let Ok(passphrase) = entry.get_password() {
+        if let Ok(key) = SshKeyPair::load(path, Some(&passphrase)) {
+            return Ok(key);
+        }
+        tracing::warn!(path = %path.display(), "Passphrase in the keyring no longer works");
+    }
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!("needs a passphrase; add it with 'russh agent add --remember'");
+    }
+
+    let passphrase = rpassword::prompt_password(format!("Passphrase for {}: ", path.display()))?;
+    let key = SshKeyPair::load(path, Some(&passphrase))?;
+    if remember {
+        entry.set_password(&passphrase)?;
+        println!("Passphrase saved to the keyring.");
+    }
+    Ok(key)
+}
+
+fn keyring_entry(path: &Path) -> anyhow::Result<keyring::Entry> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    Ok(keyring::Entry::new(
+        KEYRING_SERVICE,
+        &path.to_string_lossy(),
+    )?)
+}
+
+/// Identify a key by its public key file, or its private key
+fn identity_of(path: &Path) -> anyhow::Result<AgentIdentity> {
+    let public_path = if path.extension().is_some_and(|ext| ext == "pub") {
+        path.to_path_buf()
+    } else {
+        let mut public_path = path.as_os_str().to_owned();
+        public_path.push(".pub");
+        PathBuf::from(public_path)
+    };
+    if let Ok(line) = std::fs::read_to_string(&public_path) {
+        return Ok(AgentIdentity::from_public_key(&line)?);
+    }
+    let key = load_key(path, false)?;
+    Ok(AgentIdentity::from_public_key(&key.public_key()?)?)
+}
+
+fn key_algorithm(identity: &AgentIdentity) -> &str {
+    identity
+        .public_key
+        .split_whitespace()
+        .next()
+        .unwrap_or("unknown")
+}
@@ -0,0 +1,92 @@
+//! `russh completions`
+//!
+//! Prints a completion script for bash, zsh or fish. The clap-generated
+//! script completes commands and flags; a snippet after it completes
+//! profile names by asking `russh complete-profiles`, so profiles added
+//! later show up without regenerating the script.
+
+use clap::{CommandFactory, ValueEnum};
+use std::io::Write;
+
+/// Shells completions are generated for
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Commands whose target can be a profile name
+const PROFILE_COMMANDS: &str = "connect exec sftp tunnel";
+
+/// Print the completion script for `shell`
+pub fn print(shell: CompletionShell) -> anyhow::Result<()> {
+    let mut command = crate::Cli::command();
+    let mut out = std::io::stdout().lock();
+    let generator = match shell {
+        CompletionShell::Bash => clap_complete::Shell::Bash,
+        CompletionShell::Zsh => clap_complete::Shell::Zsh,
+        CompletionShell::Fish => clap_complete::Shell::Fish,
+    };
+    clap_complete::generate(generator, &mut command, "russh", &mut out);
+    writeln!(out)?;
+    writeln!(out, "{}", profile_snippet(shell))?;
+    Ok(())
+}
+
+/// Print every profile name, one per line
+pub async fn print_profiles(manager: &russh_ssh::session::SessionManager) {
+    let mut names: Vec<String> = manager
+        .list_profiles()
+        .await
+        .into_iter()
+        .map(|profile| profile.name)
+        .collect();
+    names.sort();
+    for name in names {
+        println!("{}", name);
+    }
+}
+
+fn profile_snippet(shell: CompletionShell) -> String {
+    match shell {
+        CompletionShell::Bash => format!(
+            r#"# Profile names, read from the profile store on each completion
+_russh_with_profiles() {{
+    _russh "$@"
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    [[ "$cur" == -* ]] && return
+    local word
+    for word in "${{COMP_WORDS[@]:1:COMP_CWORD-1}}"; do
+        [[ "$word" == -* ]] && continue
+        [[ " {commands} " == *" $word "* ]] || return
+        break
+    done
+    COMPREPLY+=($(compgen -W "$(russh complete-profiles 2>/dev/null)" -- "$cur"))
+}}
+complete -F _russh_with_profiles -o bashdefault -o default russh"#,
+            commands = PROFILE_COMMANDS
+        ),
+        CompletionShell::Zsh => format!(
+            r#"# Profile names, read from the profile store on each completion
+# (use with: source <(russh completions zsh))
+_russh_with_profiles() {{
+    _russh "$@"
+    [[ $PREFIX == -* ]] && return
+    if (( CURRENT == 2 )) || [[ " {commands} " == *" ${{words[2]}} "* ]]; then
+        local -a profiles
+        profiles=(${{(f)"$(russh complete-profiles 2>/dev/null)"}})
+        compadd -a profiles
+    fi
+}}
+compdef _russh_with_profiles russh"#,
+            commands = PROFILE_COMMANDS
+        ),
+        CompletionShell::Fish => format!(
+            r#"# Profile names, read from the profile store on each completion
+complete -c russh -n "__fish_use_subcommand" -f -a "(russh complete-profiles 2>/dev/null)" -d Profile
+complete -c russh -n "__fish_seen_subcommand_from {commands}" -f -a "(russh complete-profiles 2>/dev/null)" -d Profile"#,
+            commands = PROFILE_COMMANDS
+        ),
+    }
+}
@@ -0,0 +1,494 @@
+//! `russh cp`
+//!
+//! Copies files between this machine and SSH hosts, scp style. Either side
+//! is a local path, `user@host:path` or `profile:path`; both sides may be
+//! remote. Files are moved in chunks through the SFTP helpers, which makes
+//! progress reporting and resuming possible.
+
+use russh_ssh::session::SessionManager;
+use russh_ssh::ssh::SshClient;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+/// Some files could not be copied
+pub const EXIT_FAILED: i32 = 1;
+/// The arguments are invalid
+pub const EXIT_USAGE: i32 = 2;
+/// A host could not be reached or refused the login
+pub const EXIT_CONNECT: i32 = 3;
+/// The source does not exist
+pub const EXIT_NOT_FOUND: i32 = 4;
+
+/// Bytes read per request
+const READ_CHUNK: u64 = 1024 * 1024;
+
+/// Bytes appended per request to a remote file; they travel base64 encoded
+/// in a command line, which is limited to 128 KiB on Linux
+const REMOTE_WRITE_CHUNK: u64 = 48 * 1024;
+
+/// Options of a copy
+pub struct CopyOptions {
+    pub recursive: bool,
+    pub resume: bool,
+    pub quiet: bool,
+    pub port: Option<u16>,
+    pub password: bool,
+    pub identity: Option<PathBuf>,
+}
+
+/// A failed copy and the exit code to report it with
+#[derive(Debug)]
+pub struct CopyError {
+    pub code: i32,
+    pub error: anyhow::Error,
+}
+
+impl CopyError {
+    fn new(code: i32, error: impl Into<anyhow::Error>) -> Self {
+        Self {
+            code,
+            error: error.into(),
+        }
+    }
+}
+
+/// A side of the copy as given on the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Spec {
+    Local(String),
+    Remote { target: String, path: String },
+}
+
+/// Split `target:path` from a local path
+///
+/// Like scp, anything with a colon before the first slash is remote.
+fn parse_spec(arg: &str) -> Spec {
+    match arg.split_once(':') {
+        Some((target, path)) if !target.is_empty() && !target.contains('/') => {
+            // Remote commands run in the home directory
+            let path = path.strip_prefix("~/").unwrap_or(path);
+            let path = if path.is_empty() || path == "~" {
+                "."
+            } else {
+                path
+            };
+            Spec::Remote {
+                target: target.to_string(),
+                path: path.to_string(),
+            }
+        }
+        _ => Spec::Local(arg.to_string()),
+    }
+}
+
+/// What a path points to
+struct Entry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+}
+
+/// Where files are read from or written to
+pub enum FileHost<'a> {
+    Local,
+    Remote(&'a SshClient),
+}
+
+impl FileHost<'_> {
+    async fn stat(&self, path: &str) -> anyhow::Result<Option<Entry>> {
+        let name = base_name(path);
+        match self {
+            Self::Local => match tokio::fs::metadata(path).await {
+                Ok(metadata) => Ok(Some(Entry {
+                    name,
+                    is_dir: metadata.is_dir(),
+                    size: metadata.len(),
+                })),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            },
+            Self::Remote(client) => {
+                if !client.path_exists(path).await? {
+                    return Ok(None);
+                }
+                let entry = client.stat_path(path).await?;
+                Ok(Some(Entry {
+                    name,
+                    is_dir: entry.is_dir,
+                    size: entry.size,
+                }))
+            }
+        }
+    }
+
+    async fn list(&self, path: &str) -> anyhow::Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        match self {
+            Self::Local => {
+                let mut dir = tokio::fs::read_dir(path).await?;
+                while let Some(item) = dir.next_entry().await? {
+                    let kind = item.file_type().await?;
+                    if kind.is_symlink() {
+                        eprintln!("Skipping link {}", item.path().display());
+                        continue;
+                    }
+                    entries.push(Entry {
+                        name: item.file_name().to_string_lossy().to_string(),
+                        is_dir: kind.is_dir(),
+                        size: item.metadata().await?.len(),
+                    });
+                }
+            }
+            Self::Remote(client) => {
+                for item in client.list_directory(path).await? {
+                    if item.name == "." || item.name == ".." {
+                        continue;
+                    }
+                    if item.permissions.starts_with('l') {
+                        eprintln!("Skipping link {}", item.path);
+                        continue;
+                    }
+                    entries.push(Entry {
+                        name: item.name,
+                        is_dir: item.is_dir,
+                        size: item.size,
+                    });
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    async fn read(&self, path: &str, offset: u64, len: u64) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Local => {
+                let mut file = tokio::fs::File::open(path).await?;
+                file.seek(std::io::SeekFrom::Start(offset)).await?;
+                let mut data = Vec::new();
+                file.take(len).read_to_end(&mut data).await?;
+                Ok(data)
+            }
+            Self::Remote(client) => Ok(client.read_file_range(path, offset, len).await?),
+        }
+    }
+
+    async fn append(&self, path: &str, data: &[u8]) -> anyhow::Result<()> {
+        match self {
+            Self::Local => {
+                let mut file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await?;
+                file.write_all(data).await?;
+                file.flush().await?;
+            }
+            Self::Remote(client) => client.append_file(path, data).await?,
+        }
+        Ok(())
+    }
+
+    async fn truncate(&self, path: &str) -> anyhow::Result<()> {
+        match self {
+            Self::Local => {
+                tokio::fs::File::create(path).await?;
+            }
+            Self::Remote(client) => client.write_file(path, &[]).await?,
+        }
+        Ok(())
+    }
+
+    async fn mkdir(&self, path: &str) -> anyhow::Result<()> {
+        match self {
+            Self::Local => tokio::fs::create_dir_all(path).await?,
+            Self::Remote(client) => client.create_directory(path).await?,
+        }
+        Ok(())
+    }
+
+    fn join(&self, dir: &str, name: &str) -> String {
+        match self {
+            Self::Local => Path::new(dir).join(name).to_string_lossy().to_string(),
+            Self::Remote(_) if dir.is_empty() || dir == "." => name.to_string(),
+            Self::Remote(_) => format!("{}/{}", dir.trim_end_matches('/'), name),
+        }
+    }
+
+    /// Largest piece to append in one request
+    fn write_chunk(&self) -> u64 {
+        match self {
+            Self::Local => READ_CHUNK,
+            Self::Remote(_) => REMOTE_WRITE_CHUNK,
+        }
+    }
+}
+
+/// Last component of a path, empty for `.` and `..`
+fn base_name(path: &str) -> String {
+    let name = path
+        .trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default();
+    match name {
+        "." | ".." => String::new(),
+        name => name.to_string(),
+    }
+}
+
+/// Copy `src` to `dest`
+pub async fn copy(
+    manager: &SessionManager,
+    config_dir: &Path,
+    src: &str,
+    dest: &str,
+    options: &CopyOptions,
+) -> Result<(), CopyError> {
+    let (src_client, src_path) = open(manager, config_dir, parse_spec(src), options).await?;
+    let (dest_client, dest_path) = match open(manager, config_dir, parse_spec(dest), options).await
+    {
+        Ok(opened) => opened,
+        Err(e) => {
+            disconnect(src_client).await;
+            return Err(e);
+        }
+    };
+
+    let src_host = src_client
+        .as_ref()
+        .map_or(FileHost::Local, FileHost::Remote);
+    let dest_host = dest_client
+        .as_ref()
+        .map_or(FileHost::Local, FileHost::Remote);
+    let result = transfer(&src_host, &src_path, &dest_host, &dest_path, options).await;
+    disconnect(src_client).await;
+    disconnect(dest_client).await;
+    result
+}
+
+async fn disconnect(client: Option<SshClient>) {
+    if let Some(mut client) = client {
+        let _ = client.disconnect().await;
+    }
+}
+
+async fn open(
+    manager: &SessionManager,
+    config_dir: &Path,
+    spec: Spec,
+    options: &CopyOptions,
+) -> Result<(Option<SshClient>, String), CopyError> {
+    let (target, path) = match spec {
+        Spec::Local(path) => return Ok((None, path)),
+        Spec::Remote { target, path } => (target, path),
+    };
+    let target = crate::resolve_target(manager, &target)
+        .await
+        .map_err(|e| CopyError::new(EXIT_USAGE, e))?;
+    let port = options.port.unwrap_or(target.port);
+
+    let identity = options.identity.clone().or(target.identity);
+    let client = crate::mux::open_session(
+        config_dir,
+        target.host,
+        port,
+        target.username,
+        &crate::options::SshOptions::default(),
+        || crate::resolve_auth(options.password, identity),
+    )
+    .await
+    .map_err(|e| CopyError::new(EXIT_CONNECT, e))?;
+    Ok((Some(client), path))
+}
+
+/// Copy a file, or a directory with `recursive`, between two hosts
+///
+/// An existing directory at `dest_path` receives the source under its name.
+pub async fn transfer(
+    src: &FileHost<'_>,
+    src_path: &str,
+    dest: &FileHost<'_>,
+    dest_path: &str,
+    options: &CopyOptions,
+) -> Result<(), CopyError> {
+    let failed = |e: anyhow::Error| CopyError::new(EXIT_FAILED, e);
+    let entry = src.stat(src_path).await.map_err(failed)?.ok_or_else(|| {
+        CopyError::new(
+            EXIT_NOT_FOUND,
+            anyhow::anyhow!("{}: No such file or directory", src_path),
+        )
+    })?;
+    if entry.is_dir && !options.recursive {
+        return Err(CopyError::new(
+            EXIT_USAGE,
+            anyhow::anyhow!("{} is a directory (use -r)", src_path),
+        ));
+    }
+
+    // Like cp, copy into an existing directory under the source's name
+    let into_dir = dest_path.ends_with('/')
+        || dest
+            .stat(dest_path)
+            .await
+            .map_err(failed)?
+            .is_some_and(|existing| existing.is_dir);
+    let dest_path = if into_dir && !entry.name.is_empty() {
+        dest.join(dest_path, &entry.name)
+    } else {
+        dest_path.to_string()
+    };
+
+    if !entry.is_dir {
+        return copy_file(src, src_path, entry.size, dest, &dest_path, options)
+            .await
+            .map_err(failed);
+    }
+
+    let mut errors = 0;
+    let mut dirs = vec![(src_path.to_string(), dest_path)];
+    while let Some((src_dir, dest_dir)) = dirs.pop() {
+        let listed = match dest.mkdir(&dest_dir).await {
+            Ok(()) => src.list(&src_dir).await,
+            Err(e) => Err(e),
+        };
+        let entries = match listed {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("russh cp: {}: {:#}", src_dir, e);
+                errors += 1;
+                continue;
+            }
+        };
+        for entry in entries {
+            let from = src.join(&src_dir, &entry.name);
+            let to = dest.join(&dest_dir, &entry.name);
+            if entry.is_dir {
+                dirs.push((from, to));
+            } else if let Err(e) = copy_file(src, &from, entry.size, dest, &to, options).await {
+                eprintln!("russh cp: {}: {:#}", from, e);
+                errors += 1;
+            }
+        }
+    }
+
+    if errors > 0 {
+        return Err(CopyError::new(
+            EXIT_FAILED,
+            anyhow::anyhow!("{} item(s) could not be copied", errors),
+        ));
+    }
+    Ok(())
+}
+
+/// Copy one file, picking up where an earlier copy stopped when resuming
+///
+/// Resuming trusts that the part already at the destination matches the
+/// source; a destination larger than the source is copied again.
+async fn copy_file(
+    src: &FileHost<'_>,
+    src_path: &str,
+    size: u64,
+    dest: &FileHost<'_>,
+    dest_path: &str,
+    options: &CopyOptions,
+) -> anyhow::Result<()> {
+    let mut offset = 0;
+    if options.resume {
+        if let Some(existing) = dest.stat(dest_path).await? {
+            if !existing.is_dir && existing.size <= size {
+                offset = existing.size;
+            }
+        }
+    }
+    if offset == 0 {
+        dest.truncate(dest_path).await?;
+    }
+
+    let progress = Progress::new(base_name(src_path), size, offset, options.quiet);
+    let chunk = dest.write_chunk();
+    while offset < size {
+        let data = src.read(src_path, offset, chunk.min(size - offset)).await?;
+        if data.is_empty() {
+            anyhow::bail!("file shrank while copying");
+        }
+        dest.append(dest_path, &data).await?;
+        offset += data.len() as u64;
+        progress.update(offset);
+    }
+    progress.finish();
+    Ok(())
+}
+
+/// Progress bar on stderr, shown only on a terminal
+pub struct Progress {
+    name: String,
+    total: u64,
+    resumed_at: u64,
+    started: Instant,
+    enabled: bool,
+}
+
+impl Progress {
+    pub fn new(name: String, total: u64, resumed_at: u64, quiet: bool) -> Self {
+        let progress = Self {
+            name,
+            total,
+            resumed_at,
+            started: Instant::now(),
+            enabled: !quiet && std::io::stderr().is_terminal(),
+        };
+        progress.update(resumed_at);
+        progress
+    }
+
+    pub fn update(&self, done: u64) {
+        if !self.enabled {
+            return;
+        }
+        const WIDTH: usize = 30;
+        let fraction = if self.total == 0 {
+            1.0
+        } else {
+            done as f64 / self.total as f64
+        };
+        let filled = (fraction * WIDTH as f64) as usize;
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            (done - self.resumed_at) as f64 / elapsed
+        } else {
+            0.0
+        };
+        eprint!(
+            "\r{:<24.24} [{}{}] {:>3}% {:>10} {:>10}/s",
+            self.name,
+            "=".repeat(filled),
+            " ".repeat(WIDTH - filled),
+            (fraction * 100.0) as u32,
+            format_bytes(done),
+            format_bytes(rate as u64)
+        );
+    }
+
+    pub fn finish(&self) {
+        if self.enabled {
+            eprintln!();
+        }
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
@@ -0,0 +1,227 @@
+//! `russh exec`
+//!
+//! Runs one command on many hosts at once: profiles carrying a tag and
+//! any targets named on the command line. Each host's output is printed
+//! with its name in front as soon as it finishes, followed by a summary
+//! table. Credentials are asked for once and reused for every host. While
+//! `russh mux` runs, hosts share its sessions, and hosts it already holds
+//! need no credentials at all.
+
+use russh_ssh::session::SessionManager;
+use russh_ssh::ssh::{AuthMethod, FleetExecutor, FleetHost, HostResult, MuxClient, PoolKey};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Options of `russh exec`
+pub struct ExecOptions {
+    pub tags: Vec<String>,
+    pub targets: Vec<String>,
+    pub parallel: usize,
+    pub timeout: Option<u64>,
+    pub password: bool,
+    pub identity: Option<PathBuf>,
+    pub command: Vec<String>,
+}
+
+/// Run the command everywhere; returns whether every host succeeded
+pub async fn run(
+    manager: &SessionManager,
+    config_dir: &Path,
+    options: ExecOptions,
+) -> anyhow::Result<bool> {
+    let command = options.command.join(" ");
+    let hosts = select_hosts(manager, &options).await?;
+
+    let mux = MuxClient::new(crate::mux::socket_path(config_dir));
+    let shared: Option<HashSet<PoolKey>> = match mux.status().await {
+        Ok(sessions) => Some(sessions.into_iter().map(|session| session.key).collect()),
+        Err(_) => None,
+    };
+    let hosts = authenticate(
+        hosts,
+        options.password,
+        options.identity,
+        shared.as_ref().unwrap_or(&HashSet::new()),
+    )?;
+
+    let mut executor = FleetExecutor::new(options.parallel);
+    if let Some(timeout) = options.timeout {
+        executor = executor.with_timeout(Duration::from_secs(timeout));
+    }
+    if shared.is_some() {
+        executor = executor.with_mux(mux);
+    }
+    eprintln!(
+        "Running on {} host(s), {} at a time: {}",
+        hosts.len(),
+        executor.parallelism(),
+        command
+    );
+
+    let width = hosts.iter().map(|host| host.name.len()).max().unwrap_or(0);
+    let mut rx = executor.spawn(hosts, &command);
+    let mut results = Vec::new();
+    while let Some(result) = rx.recv().await {
+        print_output(&result);
+        results.push(result);
+    }
+    results.sort_by_key(|result| result.index);
+
+    print_summary(&results, width);
+    Ok(results.iter().all(HostResult::success))
+}
+
+/// A host before credentials are attached
+struct Selected {
+    name: String,
+    host: String,
+    port: u16,
+    username: String,
+    identity: Option<PathBuf>,
+}
+
+/// Profiles with any of the tags, then the explicit targets
+async fn select_hosts(
+    manager: &SessionManager,
+    options: &ExecOptions,
+) -> anyhow::Result<Vec<Selected>> {
+    let mut profiles = manager.list_profiles().await;
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut hosts: Vec<Selected> = Vec::new();
+    for tag in &options.tags {
+        // Exact matches only: `prod` must not pick up `preprod`
+        let tagged = profiles.iter().filter(|p| p.tags.iter().any(|t| t == tag));
+        for profile in tagged {
+            if hosts.iter().any(|host| host.name == profile.name) {
+                continue;
+            }
+            let target = crate::resolve_target(manager, &profile.name).await?;
+            hosts.push(Selected {
+                name: profile.name.clone(),
+                host: target.host,
+                port: target.port,
+                username: target.username,
+                identity: target.identity,
+            });
+        }
+    }
+    for name in &options.targets {
+        if hosts.iter().any(|host| &host.name == name) {
+            continue;
+        }
+        let target = crate::resolve_target(manager, name).await?;
+        hosts.push(Selected {
+            name: name.clone(),
+            host: target.host,
+            port: target.port,
+            username: target.username,
+            identity: target.identity,
+        });
+    }
+    if hosts.is_empty() {
+        anyhow::bail!("No hosts selected: give --tag or targets");
+    }
+    Ok(hosts)
+}
+
+/// Attach credentials, asking for each password or passphrase only once
+///
+/// Hosts in `shared` are held by the multiplexer and need none.
+fn authenticate(
+    hosts: Vec<Selected>,
+    use_password: bool,
+    identity: Option<PathBuf>,
+    shared: &HashSet<PoolKey>,
+) -> anyhow::Result<Vec<FleetHost>> {
+    let is_shared = |host: &Selected| {
+        shared.contains(&PoolKey {
+            host: host.host.clone(),
+            port: host.port,
+            username: host.username.clone(),
+        })
+    };
+    let password = if use_password && !hosts.iter().all(is_shared) {
+        Some(rpassword::prompt_password("Password: ")?)
+    } else {
+        None
+    };
+    let default_key = {
+        let home = dirs::home_dir().unwrap_or_default();
+        [home.join(".ssh/id_ed25519"), home.join(".ssh/id_rsa")]
+            .into_iter()
+            .find(|path| path.exists())
+    };
+
+    let mut keys: HashMap<PathBuf, AuthMethod> = HashMap::new();
+    let mut fleet = Vec::with_capacity(hosts.len());
+    for host in hosts {
+        let auth = if is_shared(&host) {
+            // Never used: the multiplexer runs commands over the session it
+            // holds, and reconnects it with the credentials it was opened with
+            AuthMethod::Agent
+        } else if let Some(password) = &password {
+            AuthMethod::Password(password.clone())
+        } else {
+            let key = identity
+                .clone()
+                .or(host.identity)
+                .or_else(|| default_key.clone())
+                .ok_or_else(|| anyhow::anyhow!("No key for {}; use -i or --password", host.name))?;
+            match keys.get(&key) {
+                Some(auth) => auth.clone(),
+                None => {
+                    let auth = crate::key_auth(key.clone())?;
+                    keys.insert(key, auth.clone());
+                    auth
+                }
+            }
+        };
+        let config = crate::ssh_config(host.host, host.port, host.username, auth);
+        fleet.push(FleetHost::new(host.name, config));
+    }
+    Ok(fleet)
+}
+
+/// Print a host's output with its name in front of every line
+fn print_output(result: &HostResult) {
+    match &result.result {
+        Ok(output) => {
+            for line in output.stdout_string().lines() {
+                println!("[{}] {}", result.name, line);
+            }
+            for line in output.stderr_string().lines() {
+                eprintln!("[{}] {}", result.name, line);
+            }
+        }
+        Err(e) => eprintln!("[{}] error: {}", result.name, e),
+    }
+}
+
+fn print_summary(results: &[HostResult], width: usize) {
+    let width = width.max("HOST".len());
+    println!();
+    println!(
+        "{:<width$}  {:<6}  {:>4}  {:>7}",
+        "HOST", "STATUS", "EXIT", "TIME"
+    );
+    for result in results {
+        let (status, exit, error) = match &result.result {
+            Ok(output) if output.success() => ("ok", output.exit_code.to_string(), None),
+            Ok(output) => ("failed", output.exit_code.to_string(), None),
+            Err(e) => ("error", "-".to_string(), Some(e.to_string())),
+        };
+        println!(
+            "{:<width$}  {:<6}  {:>4}  {:>6.1}s{}",
+            result.name,
+            status,
+            exit,
+            result.elapsed.as_secs_f64(),
+            error.map(|e| format!("  {}", e)).unwrap_or_default()
+        );
+    }
+    let failed = results.iter().filter(|result| !result.success()).count();
+    println!();
+    println!("{} succeeded, {} failed", results.len() - failed, failed);
+}
@@ -0,0 +1,134 @@
+//! `russh keygen`
+//!
+//! Generates SSH keys under `<config dir>/keys` by default, and shows the
+//! fingerprint or public key of existing ones, much like `ssh-keygen`.
+
+use russh_ssh::session::profile::AuthConfig;
+use russh_ssh::session::SessionManager;
+use russh_ssh::ssh::keygen::public_key_fingerprint;
+use russh_ssh::ssh::{KeyType, SshKeyPair};
+use std::path::{Path, PathBuf};
+
+/// Options of `russh keygen`
+pub struct KeygenOptions {
+    pub key_type: String,
+    pub bits: Option<usize>,
+    pub file: Option<PathBuf>,
+    pub comment: Option<String>,
+    pub passphrase: Option<String>,
+    pub force: bool,
+    pub profile: Option<String>,
+    pub fingerprint: Option<PathBuf>,
+    pub public: Option<PathBuf>,
+}
+
+/// Generate a key, or inspect one when `fingerprint` or `public` is set
+pub async fn run(
+    manager: &SessionManager,
+    keys_dir: &Path,
+    options: KeygenOptions,
+) -> anyhow::Result<()> {
+    if let Some(path) = options.fingerprint {
+        return show_fingerprint(&path);
+    }
+    if let Some(path) = options.public {
+        println!("{}", load_private_key(&path)?.public_key()?);
+        return Ok(());
+    }
+
+    let key_type = KeyType::parse(&options.key_type, options.bits)?;
+    let path = options
+        .file
+        .unwrap_or_else(|| keys_dir.join(key_type.default_file_name()));
+    if path.exists() && !options.force {
+        anyhow::bail!(
+            "{} already exists (use --force to replace it)",
+            path.display()
+        );
+    }
+    // Check the profile before asking for anything
+    let profile = match &options.profile {
+        Some(name) => Some(
+            manager
+                .get_profile_by_name(name)
+                .await
+                .ok_or_else(|| anyhow::anyhow!("Profile '{}' not found", name))?,
+        ),
+        None => None,
+    };
+
+    let passphrase = match options.passphrase {
+        Some(passphrase) => passphrase,
+        None => prompt_new_passphrase()?,
+    };
+    let comment = options.comment.unwrap_or_else(default_comment);
+
+    println!("Generating {} key...", key_type);
+    let key =
+        tokio::task::spawn_blocking(move || SshKeyPair::generate(key_type, &comment)).await??;
+    let public_path = key.save(&path, Some(&passphrase))?;
+
+    println!("Private key: {}", path.display());
+    println!("Public key:  {}", public_path.display());
+    println!("Fingerprint: {} {}", key.fingerprint(), key.comment());
+    println!();
+    println!("{}", key.public_key()?);
+
+    if let Some(mut profile) = profile {
+        profile.auth = AuthConfig::public_key(&path, !passphrase.is_empty());
+        let name = profile.name.clone();
+        manager.update_profile(profile).await?;
+        println!();
+        println!("Profile '{}' now logs in with this key.", name);
+    }
+    Ok(())
+}
+
+/// Print the fingerprint of a public or private key file
+fn show_fingerprint(path: &Path) -> anyhow::Result<()> {
+    let contents = std::fs::read_to_string(path)?;
+    if let Ok(fingerprint) = public_key_fingerprint(&contents) {
+        let comment = contents.split_whitespace().nth(2).unwrap_or_default();
+        println!("{} {}", fingerprint, comment);
+        return Ok(());
+    }
+    let key = load_private_key(path)?;
+    println!("{} {}", key.fingerprint(), key.comment());
+    Ok(())
+}
+
+fn load_private_key(path: &Path) -> anyhow::Result<SshKeyPair> {
+    let passphrase = if SshKeyPair::is_encrypted_file(path)? {
+        Some(rpassword::prompt_password(format!(
+            "Passphrase for {}: ",
+            path.display()
+        ))?)
+    } else {
+        None
+    };
+    Ok(SshKeyPair::load(path, passphrase.as_deref())?)
+}
+
+fn prompt_new_passphrase() -> anyhow::Result<String> {
+    let passphrase = rpassword::prompt_password("Passphrase (empty for none): ")?;
+    let again = rpassword::prompt_password("Same passphrase again: ")?;
+    if passphrase != again {
+        anyhow::bail!("Passphrases do not match");
+    }
+    Ok(passphrase)
+}
+
+/// `user@host`, as ssh-keygen uses for comments
+fn default_comment() -> String {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "russh".to_string());
+    let host = std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "localhost".to_string());
+    format!("{}@{}", user, host)
+}
@@ -0,0 +1,170 @@
+//! `russh known-hosts`
+//!
+//! Lists, searches and edits the known_hosts file russh checks host keys
+//! against (`~/.russh/known_hosts` unless `-f` names another), and pins a
+//! host's keys before the first connection by fetching them the way
+//! `ssh-keyscan` does. Keys are pinned under the host name and, like
+//! OpenSSH's `CheckHostIP`, under each address it resolves to.
+
+use crate::KnownHostsAction;
+use russh_ssh::ssh::keygen::public_key_fingerprint;
+use russh_ssh::ssh::known_hosts::{hash_host, host_pattern};
+use russh_ssh::ssh::{scan_host_keys, HostMarker, KnownHost, KnownHosts};
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Duration;
+
+/// Run a known-hosts command on the file at `path`
+pub async fn run(action: KnownHostsAction, path: &Path) -> anyhow::Result<()> {
+    let mut known = KnownHosts::open(path)?;
+    match action {
+        KnownHostsAction::List => {
+            let entries: Vec<&KnownHost> = known.entries().collect();
+            if entries.is_empty() {
+                println!("No known hosts in {}.", path.display());
+            }
+            for entry in entries {
+                print_entry(entry);
+            }
+        }
+        KnownHostsAction::Search { query } => {
+            let entries = known.search(&query);
+            if entries.is_empty() {
+                println!("No known host matches '{}'.", query);
+            }
+            for entry in entries {
+                print_entry(entry);
+            }
+        }
+        KnownHostsAction::Remove { host, port } => {
+            let name = host_pattern(&host, port);
+            match known.remove(&host, port) {
+                0 => println!("{} is not in {}.", name, path.display()),
+                lines => {
+                    known.save()?;
+                    println!("Removed {} from {} line(s).", name, lines);
+                }
+            }
+        }
+        KnownHostsAction::Hash => {
+            let hashed = known.hash_hosts();
+            if hashed > 0 {
+                known.save()?;
+            }
+            println!("Hashed {} host name(s) in {}.", hashed, path.display());
+        }
+        KnownHostsAction::Scan {
+            host,
+            port,
+            hash,
+            print,
+            force,
+            timeout,
+        } => {
+            let keys = scan_host_keys(&host, port, Duration::from_secs(timeout)).await?;
+            if print {
+                let name = host_pattern(&host, port);
+                let name = if hash { hash_host(&name) } else { name };
+                for key in &keys {
+                    println!("{} {}", name, key);
+                }
+                return Ok(());
+            }
+
+            let names = pinned_names(&host, port).await;
+            if !force {
+                check_unchanged(&known, &names, port, &keys)?;
+            }
+            for key in &keys {
+                for name in &names {
+                    known.add(name, port, key, hash)?;
+                }
+                println!(
+                    "{} {}",
+                    public_key_fingerprint(key)?,
+                    key.split_whitespace().next().unwrap_or_default()
+                );
+            }
+            known.save()?;
+            println!(
+                "Pinned {} key(s) for {} in {}.",
+                keys.len(),
+                names.join(", "),
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Fail if a key of the same type as a scanned one is pinned but differs
+fn check_unchanged(
+    known: &KnownHosts,
+    names: &[String],
+    port: u16,
+    keys: &[String],
+) -> anyhow::Result<()> {
+    for key in keys {
+        let key_type = key.split_whitespace().next().unwrap_or_default();
+        for name in names {
+            let changed = known.lookup(name, port).into_iter().find(|entry| {
+                entry.marker.is_none() && entry.key_type == key_type && entry.public_key() != *key
+            });
+            if let Some(pinned) = changed {
+                anyhow::bail!(
+                    "The {} key of {} changed (pinned: {}); use --force to replace it",
+                    key_type,
+                    host_pattern(name, port),
+                    pinned.fingerprint()?
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Host name and the addresses it resolves to, each once
+async fn pinned_names(host: &str, port: u16) -> Vec<String> {
+    let mut names = vec![host.to_string()];
+    if host.parse::<IpAddr>().is_ok() {
+        return names;
+    }
+    if let Ok(addrs) = tokio::net::lookup_host((host, port)).await {
+        for addr in addrs {
+            let ip = addr.ip().to_string();
+            if !names.contains(&ip) {
+                names.push(ip);
+            }
+        }
+    }
+    names
+}
+
+fn print_entry(entry: &KnownHost) {
+    let hosts: Vec<&str> = entry
+        .hosts
+        .iter()
+        .map(|host| {
+            if host.starts_with("|1|") {
+                "(hashed)"
+            } else {
+                host.as_str()
+            }
+        })
+        .collect();
+    let marker = match entry.marker {
+        Some(HostMarker::CertAuthority) => " [cert-authority]",
+        Some(HostMarker::Revoked) => " [revoked]",
+        None => "",
+    };
+    let fingerprint = entry
+        .fingerprint()
+        .unwrap_or_else(|_| "(invalid key)".to_string());
+    println!(
+        "{:<40} {:<20} {}{}",
+        hosts.join(","),
+        entry.key_type,
+        fingerprint,
+        marker
+    );
+}
@@ -5,10 +5,35 @@
 //! # Requirements Coverage
 //! - Requirement 7.1: CLI interface
 
-use clap::{Parser, Subcommand};
+mod agent;
+mod completions;
+mod cp;
+mod exec;
+mod keygen;
+mod known_hosts;
+mod mux;
+mod options;
+mod p2p;
+mod picker;
+mod roam;
+mod sftp;
+mod stream;
+mod sync;
+mod tunnel;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use options::SshOptions;
+use russh_ssh::connection::HealthConfig;
+use russh_ssh::p2p::{
+    NetworkDiagnostics, P2PConfig, P2PConnectionManager, P2PEndpoint, PathType, PeerDiagnostics,
+    PeerTicket,
+};
 use russh_ssh::session::profile::AuthConfig;
-use russh_ssh::session::{SessionManager, SessionProfile};
-use russh_ssh::ssh::{AuthMethod, HostKeyCheck, PortForward, PortForwarder, SshClient, SshConfig};
+use russh_ssh::session::{ImportMode, NameConflict, SessionManager, SessionProfile};
+use russh_ssh::ssh::{
+    AuthMethod, HostKeyCheck, PortForward, PortForwarder, SshClient, SshConfig, SshKeyPair,
+    Transport, KNOWN_HOSTS_FILE_NAME,
+};
 use std::path::PathBuf;
 use std::time::Duration;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -16,6 +41,7 @@ use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 #[derive(Parser)]
 #[command(name = "russh")]
 #[command(author, version, about = "russh SSH - Secure P2P SSH connections", long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
     /// Enable verbose output
     #[arg(short, long)]
@@ -25,6 +51,10 @@ struct Cli {
     #[arg(short, long, default_value = "~/.russh")]
     config_dir: String,
 
+    /// Profile to connect to, as a shorthand for 'russh connect PROFILE'
+    #[arg(value_name = "PROFILE")]
+    profile: Option<String>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -32,10 +62,18 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Connect to a remote host
+    #[command(after_help = "Examples:\n  russh connect -c uptime user@host\n  \
+russh connect -L 5432:db.internal:5432 -R 8080:localhost:3000 -D 1080 myprofile\n  \
+russh connect -o ProxyJump=admin@bastion -o ConnectTimeout=10 -c uptime user@db\n  \
+russh connect --roam --via laptop-peer --tmux main user@host\n  \
+russh connect    (pick a saved profile)\n\n\
+Options for -o: StrictHostKeyChecking, ConnectTimeout, ServerAliveInterval, ProxyJump, \
+IdentityFile, User")]
     Connect {
-        /// Host to connect to (user@host:port or profile name)
+        /// Host to connect to (user@host:port or profile name); without it,
+        /// pick one of the saved profiles
         #[arg(value_name = "TARGET")]
-        target: String,
+        target: Option<String>,
 
         /// Use password authentication
         #[arg(short, long)]
@@ -45,23 +83,510 @@ enum Commands {
         #[arg(short, long)]
         identity: Option<PathBuf>,
 
-        /// Local port forward (local:remote_host:remote_port)
-        #[arg(short = 'L', long)]
+        /// Local port forward (port:host:hostport)
+        #[arg(short = 'L', long, value_name = "SPEC")]
         local_forward: Vec<String>,
 
+        /// Remote port forward (port:host:hostport); not supported yet
+        #[arg(short = 'R', long, value_name = "SPEC")]
+        remote_forward: Vec<String>,
+
+        /// SOCKS5 proxy on a local port
+        #[arg(short = 'D', long, value_name = "PORT")]
+        dynamic_forward: Vec<String>,
+
+        /// OpenSSH option, e.g. -o ConnectTimeout=10 (repeatable)
+        #[arg(short = 'o', value_name = "OPTION=VALUE")]
+        option: Vec<String>,
+
         /// Execute command instead of shell
         #[arg(short, long)]
         command: Option<String>,
+
+        /// Tunnel through this peer's 'russh p2p serve' (name, ticket or Node ID)
+        #[arg(long, value_name = "PEER")]
+        via: Option<String>,
+
+        /// Keep the shell across network changes and suspend, with local echo
+        #[arg(long, requires = "via", conflicts_with = "command")]
+        roam: bool,
+
+        /// Reattach this tmux session after reconnecting a roaming shell
+        #[arg(long, value_name = "SESSION", requires = "roam")]
+        tmux: Option<String>,
+    },
+    /// Run a command on many hosts in parallel
+    #[command(
+        after_help = "Example:\n  russh exec --tag prod --parallel 20 -- 'systemctl restart app'\n\n\
+Exit status: 0 if the command succeeded everywhere, 1 otherwise"
+    )]
+    Exec {
+        /// Run on every profile with this tag (repeatable)
+        #[arg(short, long)]
+        tag: Vec<String>,
+
+        /// Hosts to run on, besides tagged profiles (user@host:port or profile name)
+        #[arg(value_name = "TARGET")]
+        targets: Vec<String>,
+
+        /// Maximum number of hosts running at once
+        #[arg(long, default_value = "10")]
+        parallel: usize,
+
+        /// Seconds a host's command may run before it counts as failed
+        #[arg(long)]
+        timeout: Option<u64>,
+
+        /// Use password authentication (asked for once)
+        #[arg(short, long)]
+        password: bool,
+
+        /// Path to private key, overriding the profiles'
+        #[arg(short, long)]
+        identity: Option<PathBuf>,
+
+        /// Command to run, after --
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+    /// Copy files to, from or between hosts (scp style)
+    #[command(
+        after_help = "Either side is a local path, user@host:path or profile:path.\n\n\
+Exit status: 0 copied, 1 some files failed, 2 invalid arguments, \
+3 connection or login failed, 4 source not found"
+    )]
+    Cp {
+        /// Copy directories recursively
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// Continue partial files at the destination instead of starting over
+        #[arg(long)]
+        resume: bool,
+
+        /// Do not show progress
+        #[arg(short, long)]
+        quiet: bool,
+
+        /// Port of the remote hosts
+        #[arg(short = 'P', long)]
+        port: Option<u16>,
+
+        /// Use password authentication
+        #[arg(short, long)]
+        password: bool,
+
+        /// Path to private key
+        #[arg(short, long)]
+        identity: Option<PathBuf>,
+
+        /// File or directory to copy
+        #[arg(value_name = "SRC")]
+        src: String,
+
+        /// Where to copy it
+        #[arg(value_name = "DEST")]
+        dest: String,
     },
+    /// Browse and transfer files interactively (like OpenSSH sftp)
+    Sftp {
+        /// Host to connect to (user@host:port or profile name)
+        #[arg(value_name = "TARGET")]
+        target: String,
+
+        /// Port, overriding the target's
+        #[arg(short = 'P', long)]
+        port: Option<u16>,
+
+        /// Use password authentication
+        #[arg(short, long)]
+        password: bool,
+
+        /// Path to private key
+        #[arg(short, long)]
+        identity: Option<PathBuf>,
+    },
+    /// Hold port forwards open without a shell, reconnecting when dropped
+    #[command(after_help = "Forwards saved in the profile are started as well.\n\n\
+Examples:\n  russh tunnel -L 5432:db.internal:5432 user@bastion\n  \
+russh tunnel -D 1080 -R 8080:localhost:3000 myprofile")]
+    Tunnel {
+        /// Host to connect to (user@host:port or profile name)
+        #[arg(value_name = "TARGET")]
+        target: String,
+
+        /// Port, overriding the target's
+        #[arg(short = 'P', long)]
+        port: Option<u16>,
+
+        /// Use password authentication
+        #[arg(short, long)]
+        password: bool,
+
+        /// Path to private key
+        #[arg(short, long)]
+        identity: Option<PathBuf>,
+
+        /// Local port forward (port:host:hostport)
+        #[arg(short = 'L', long, value_name = "SPEC")]
+        local_forward: Vec<String>,
+
+        /// Remote port forward (port:host:hostport); not supported yet
+        #[arg(short = 'R', long, value_name = "SPEC")]
+        remote_forward: Vec<String>,
+
+        /// SOCKS5 proxy on a local port
+        #[arg(short = 'D', long, value_name = "PORT")]
+        dynamic_forward: Vec<String>,
+    },
+    /// Generate an SSH key pair, or inspect an existing one
+    Keygen {
+        /// Key type: ed25519, rsa or ecdsa
+        #[arg(short = 't', long = "type", default_value = "ed25519")]
+        key_type: String,
+
+        /// Key size in bits (RSA: 2048 to 16384, ECDSA: 256, 384 or 521)
+        #[arg(short, long)]
+        bits: Option<usize>,
+
+        /// Private key file [default: <config dir>/keys/id_<type>]
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// Comment stored with the key [default: user@host]
+        #[arg(short = 'C', long)]
+        comment: Option<String>,
+
+        /// Passphrase for the new key, empty for none; asked for if omitted
+        #[arg(short = 'N', long)]
+        passphrase: Option<String>,
+
+        /// Replace an existing key file
+        #[arg(long)]
+        force: bool,
+
+        /// Make this profile log in with the new key
+        #[arg(long, value_name = "NAME")]
+        profile: Option<String>,
+
+        /// Show the fingerprint of a key file instead
+        #[arg(short = 'l', long, value_name = "FILE", conflicts_with = "public")]
+        fingerprint: Option<PathBuf>,
+
+        /// Print the public key of a private key file instead
+        #[arg(short = 'y', long, value_name = "FILE")]
+        public: Option<PathBuf>,
+    },
+    /// Manage keys in the SSH agent, or run the built-in agent
+    Agent {
+        #[command(subcommand)]
+        action: AgentAction,
+    },
+    /// List, edit and pre-fetch the host keys russh trusts
+    #[command(after_help = "Examples:\n  russh known-hosts scan example.com\n  \
+russh known-hosts search example.com\n  russh known-hosts remove -p 2222 example.com\n  \
+russh known-hosts hash")]
+    KnownHosts {
+        /// known_hosts file [default: ~/.russh/known_hosts]
+        #[arg(short, long, global = true)]
+        file: Option<PathBuf>,
+
+        #[command(subcommand)]
+        action: KnownHostsAction,
+    },
+    /// Keep SSH sessions open in the background for other commands to reuse
+    #[command(
+        after_help = "While the multiplexer runs, 'connect -c', 'cp', 'sftp' and 'exec' share its \
+sessions instead of connecting and logging in each time.\n\n\
+Example:\n  russh mux start\n  russh exec --tag prod -- uptime\n  russh mux status\n  russh mux stop"
+    )]
+    Mux {
+        #[command(subcommand)]
+        action: MuxAction,
+    },
+    /// Pair with devices, connect to peers and send them files over P2P
+    P2p {
+        #[command(subcommand)]
+        action: P2pAction,
+    },
+    /// Host or join synchronized playback rooms
+    #[command(
+        after_help = "Examples:\n  russh stream create --name 'Movie night' ~/Videos/film.mkv\n  \
+russh stream join 'russh://stream/<room>?host=<node-id>'\n  \
+russh stream status"
+    )]
+    Stream {
+        #[command(subcommand)]
+        action: StreamAction,
+    },
+    /// Keep directories in sync with paired devices
+    #[command(after_help = "Examples:\n  russh sync init ~/Documents --id docs\n  \
+russh sync pair laptop\n  \
+russh sync run --watch\n  \
+russh sync status")]
+    Sync {
+        #[command(subcommand)]
+        action: SyncAction,
+    },
+    /// Print a shell completion script
+    #[command(after_help = "Examples:\n  \
+russh completions bash > ~/.local/share/bash-completion/completions/russh\n  \
+source <(russh completions zsh)\n  \
+russh completions fish > ~/.config/fish/completions/russh.fish")]
+    Completions {
+        /// Shell to complete for
+        #[arg(value_enum)]
+        shell: completions::CompletionShell,
+    },
+    /// Print profile names for shell completion
+    #[command(hide = true)]
+    CompleteProfiles,
     /// Manage session profiles
+    #[command(
+        after_help = "Examples:\n  russh profile export --strip-secrets profiles.json\n  \
+russh profile import --on-conflict rename profiles.json\n  \
+russh profile import --replace profiles.json"
+    )]
     Profile {
         #[command(subcommand)]
         action: ProfileAction,
     },
+    /// Diagnose P2P connectivity (NAT type, relay, hole punching)
+    Doctor {
+        /// Also diagnose the path to this peer (ticket or Node ID)
+        #[arg(long, value_name = "TICKET")]
+        peer: Option<String>,
+
+        /// Seconds to wait for network probes and hole punching
+        #[arg(short, long, default_value = "10")]
+        timeout: u64,
+    },
     /// Show version and system information
     Version,
 }
 
+#[derive(Subcommand)]
+enum AgentAction {
+    /// List the keys the agent holds
+    List,
+    /// Add keys to the agent [default: every key russh manages]
+    Add {
+        /// Private key files
+        files: Vec<PathBuf>,
+
+        /// Save passphrases typed in to the OS keyring
+        #[arg(long)]
+        remember: bool,
+    },
+    /// Remove keys from the agent
+    Remove {
+        /// Private or public key files
+        #[arg(required_unless_present = "all")]
+        files: Vec<PathBuf>,
+
+        /// Remove every key
+        #[arg(short, long)]
+        all: bool,
+    },
+    /// Serve russh-managed keys on an agent socket until stopped
+    Serve {
+        /// Socket path [default: <config dir>/agent.sock]
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum KnownHostsAction {
+    /// List every pinned host key
+    List,
+    /// Show keys for a host, or whose host names or comment contain a word
+    Search {
+        /// Host name, [host]:port or part of a name
+        query: String,
+    },
+    /// Remove a host's keys
+    Remove {
+        /// Host name or address
+        host: String,
+
+        /// Port the host's SSH server listens on
+        #[arg(short, long, default_value = "22")]
+        port: u16,
+    },
+    /// Hash plain host names so the file does not reveal them
+    Hash,
+    /// Fetch a host's keys and pin them before connecting
+    Scan {
+        /// Host name or address
+        host: String,
+
+        /// Port the host's SSH server listens on
+        #[arg(short, long, default_value = "22")]
+        port: u16,
+
+        /// Record the host names hashed
+        #[arg(short = 'H', long)]
+        hash: bool,
+
+        /// Print the keys as known_hosts lines instead of pinning them
+        #[arg(long)]
+        print: bool,
+
+        /// Replace pinned keys that no longer match
+        #[arg(long)]
+        force: bool,
+
+        /// Seconds to wait for each connection
+        #[arg(short, long, default_value = "10")]
+        timeout: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum MuxAction {
+    /// Start the multiplexer in the background
+    Start {
+        /// Minutes an unused session stays open
+        #[arg(long, default_value = "30")]
+        idle: u64,
+
+        /// Stay in the foreground until Ctrl-C
+        #[arg(long)]
+        foreground: bool,
+    },
+    /// Show the shared sessions
+    Status,
+    /// Close every shared session and stop the multiplexer
+    Stop,
+}
+
+#[derive(Subcommand)]
+enum StreamAction {
+    /// Host a room playing a local file or URL until stopped
+    Create {
+        /// Media file or URL (HLS and DASH manifests are recognized)
+        source: String,
+
+        /// Room name [default: the file name]
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Ask for a password guests must give
+        #[arg(short, long)]
+        password: bool,
+
+        /// Hold each guest until approved
+        #[arg(long)]
+        approve: bool,
+
+        /// Only admit guests with an invite, and print one
+        #[arg(long)]
+        invite_only: bool,
+
+        /// Minutes the invite is valid
+        #[arg(long, default_value = "60")]
+        ttl: u64,
+    },
+    /// Join a room by its link or an invite
+    Join {
+        /// Room link (russh://stream/...) or invite
+        link: String,
+
+        /// Ask for the room password
+        #[arg(short, long)]
+        password: bool,
+    },
+    /// Show the rooms hosted or joined on this machine
+    Status,
+}
+
+#[derive(Subcommand)]
+enum SyncAction {
+    /// Add a directory to sync
+    Init {
+        /// Directory to sync
+        dir: PathBuf,
+
+        /// Folder ID, the same on every device [default: the directory name]
+        #[arg(long)]
+        id: Option<String>,
+    },
+    /// Share a folder with a paired device
+    Pair {
+        /// Device name or NodeId, as paired with 'russh p2p pair'
+        peer: String,
+
+        /// Folder to share [default: the only folder]
+        #[arg(short, long)]
+        folder: Option<String>,
+    },
+    /// Show folders, their devices and when they last synced
+    Status,
+    /// Pull changes from every folder's devices
+    Run {
+        /// Keep running, syncing local edits as they happen
+        #[arg(short, long)]
+        watch: bool,
+
+        /// Seconds between pulls while watching
+        #[arg(long, default_value = "30")]
+        interval: u64,
+    },
+}
+
+#[derive(Subcommand)]
+enum P2pAction {
+    /// Show this device's NodeId and connection ticket
+    Id {
+        /// Also show the ticket as a QR code
+        #[arg(long)]
+        qr: bool,
+    },
+    /// Show a one-time pairing code, or redeem one from another device
+    Pair {
+        /// Pairing code shown by the other device
+        ticket: Option<String>,
+
+        /// Name announced to the other device
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Minutes a new pairing code stays valid
+        #[arg(long, default_value = "10")]
+        ttl: u64,
+    },
+    /// Connect to a peer and show the path to it
+    Connect {
+        /// Peer name, ticket or NodeId
+        peer: String,
+
+        /// Save the peer under this name
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// List known peers
+    Peers,
+    /// Send a file or directory to a peer running 'russh p2p serve'
+    Send {
+        /// File or directory to send
+        file: PathBuf,
+
+        /// Peer name, ticket or NodeId
+        peer: String,
+    },
+    /// Accept files and SSH tunnels from paired peers until stopped
+    Serve {
+        /// Directory received files are saved to [default: current directory]
+        #[arg(short, long)]
+        dir: Option<PathBuf>,
+
+        /// SSH server that peers reach with 'russh connect --via'
+        #[arg(long, default_value = "127.0.0.1:22")]
+        ssh: std::net::SocketAddr,
+    },
+}
+
 #[derive(Subcommand)]
 enum ProfileAction {
     /// List all profiles
@@ -78,6 +603,9 @@ enum ProfileAction {
         /// Port
         #[arg(short, long, default_value = "22")]
         port: u16,
+        /// Tag, e.g. for 'russh exec --tag' (repeatable)
+        #[arg(short, long)]
+        tag: Vec<String>,
     },
     /// Remove a profile
     Remove {
@@ -89,6 +617,53 @@ enum ProfileAction {
         /// Profile name
         name: String,
     },
+    /// Write all profiles to a JSON file
+    Export {
+        /// File to write
+        file: PathBuf,
+
+        /// Leave stored passwords out
+        #[arg(long)]
+        strip_secrets: bool,
+    },
+    /// Read profiles from a JSON file made by 'russh profile export'
+    Import {
+        /// File to read
+        file: PathBuf,
+
+        /// Keep the saved profiles (default)
+        #[arg(long, conflicts_with = "replace")]
+        merge: bool,
+
+        /// Remove the saved profiles first
+        #[arg(long)]
+        replace: bool,
+
+        /// What to do with a profile whose name is taken
+        #[arg(long, value_enum, default_value = "skip")]
+        on_conflict: OnConflict,
+    },
+}
+
+/// `--on-conflict` of `russh profile import`
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OnConflict {
+    /// Keep the saved profile
+    Skip,
+    /// Replace the saved profile
+    Overwrite,
+    /// Import under a free name, e.g. web-2
+    Rename,
+}
+
+impl From<OnConflict> for NameConflict {
+    fn from(on_conflict: OnConflict) -> Self {
+        match on_conflict {
+            OnConflict::Skip => NameConflict::Skip,
+            OnConflict::Overwrite => NameConflict::Overwrite,
+            OnConflict::Rename => NameConflict::Rename,
+        }
+    }
 }
 
 #[tokio::main]
@@ -130,28 +705,219 @@ async fn main() -> anyhow::Result<()> {
         }
     }
 
-    match cli.command {
+    // `russh PROFILE` is `russh connect PROFILE`
+    let command = match (cli.command, cli.profile) {
+        (None, Some(profile)) => Some(Commands::Connect {
+            target: Some(profile),
+            password: false,
+            identity: None,
+            local_forward: Vec::new(),
+            remote_forward: Vec::new(),
+            dynamic_forward: Vec::new(),
+            option: Vec::new(),
+            command: None,
+            via: None,
+            roam: false,
+            tmux: None,
+        }),
+        (command, _) => command,
+    };
+
+    match command {
         Some(Commands::Connect {
             target,
             password,
             identity,
             local_forward,
+            remote_forward,
+            dynamic_forward,
+            option,
+            command,
+            via,
+            roam,
+            tmux,
+        }) => {
+            let target = match target {
+                Some(target) => target,
+                None => match picker::pick(manager.list_profiles().await)? {
+                    Some(profile) => profile.name,
+                    None => return Ok(()),
+                },
+            };
+            // Checked before connecting, so a typo does not cost a login
+            let options = ConnectOptions {
+                target,
+                password,
+                identity,
+                forwards: tunnel::forwards(
+                    &local_forward,
+                    &remote_forward,
+                    &dynamic_forward,
+                    Vec::new(),
+                )?,
+                ssh: SshOptions::parse(&option)?,
+                command,
+                via,
+                roam,
+                tmux,
+            };
+            connect(&manager, &config_path, options).await?;
+        }
+        Some(Commands::Exec {
+            tag,
+            targets,
+            parallel,
+            timeout,
+            password,
+            identity,
             command,
         }) => {
-            connect(
-                &manager,
-                &target,
+            let options = exec::ExecOptions {
+                tags: tag,
+                targets,
+                parallel,
+                timeout,
                 password,
                 identity,
-                local_forward,
                 command,
+            };
+            if !exec::run(&manager, &config_path, options).await? {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Cp {
+            recursive,
+            resume,
+            quiet,
+            port,
+            password,
+            identity,
+            src,
+            dest,
+        }) => {
+            let options = cp::CopyOptions {
+                recursive,
+                resume,
+                quiet,
+                port,
+                password,
+                identity,
+            };
+            if let Err(e) = cp::copy(&manager, &config_path, &src, &dest, &options).await {
+                eprintln!("russh cp: {:#}", e.error);
+                std::process::exit(e.code);
+            }
+        }
+        Some(Commands::Sftp {
+            target,
+            port,
+            password,
+            identity,
+        }) => {
+            let target = resolve_target(&manager, &target).await?;
+            let port = port.unwrap_or(target.port);
+            println!(
+                "Connecting to {}@{}:{}...",
+                target.username, target.host, port
+            );
+            let mut client = mux::open_session(
+                &config_path,
+                target.host,
+                port,
+                target.username,
+                &SshOptions::default(),
+                || resolve_auth(password, identity.or(target.identity)),
+            )
+            .await?;
+            let result = sftp::run(&client).await;
+            client.disconnect().await?;
+            result?;
+        }
+        Some(Commands::Tunnel {
+            target,
+            port,
+            password,
+            identity,
+            local_forward,
+            remote_forward,
+            dynamic_forward,
+        }) => {
+            let target = resolve_target(&manager, &target).await?;
+            let forwards = tunnel::forwards(
+                &local_forward,
+                &remote_forward,
+                &dynamic_forward,
+                target.forwards,
+            )?;
+            let port = port.unwrap_or(target.port);
+            println!(
+                "Connecting to {}@{}:{}...",
+                target.username, target.host, port
+            );
+            let auth = resolve_auth(password, identity.or(target.identity))?;
+            tunnel::run(
+                ssh_config(target.host, port, target.username, auth),
+                forwards,
             )
             .await?;
         }
+        Some(Commands::Keygen {
+            key_type,
+            bits,
+            file,
+            comment,
+            passphrase,
+            force,
+            profile,
+            fingerprint,
+            public,
+        }) => {
+            let options = keygen::KeygenOptions {
+                key_type,
+                bits,
+                file,
+                comment,
+                passphrase,
+                force,
+                profile,
+                fingerprint,
+                public,
+            };
+            keygen::run(&manager, &config_path.join("keys"), options).await?;
+            manager.save().await?;
+        }
+        Some(Commands::Agent { action }) => {
+            agent::run(action, &config_path.join("keys"), &config_path).await?;
+        }
+        Some(Commands::KnownHosts { file, action }) => {
+            let path = file.unwrap_or_else(known_hosts_path);
+            known_hosts::run(action, &path).await?;
+        }
+        Some(Commands::Mux { action }) => {
+            mux::run(action, &config_path).await?;
+        }
+        Some(Commands::P2p { action }) => {
+            p2p::run(action, &config_path).await?;
+        }
+        Some(Commands::Stream { action }) => {
+            stream::run(action, &config_path).await?;
+        }
+        Some(Commands::Sync { action }) => {
+            sync::run(action, &config_path).await?;
+        }
+        Some(Commands::Completions { shell }) => {
+            completions::print(shell)?;
+        }
+        Some(Commands::CompleteProfiles) => {
+            completions::print_profiles(&manager).await;
+        }
         Some(Commands::Profile { action }) => {
             handle_profile_action(&manager, action).await?;
             manager.save().await?;
         }
+        Some(Commands::Doctor { peer, timeout }) => {
+            doctor(peer, Duration::from_secs(timeout)).await?;
+        }
         Some(Commands::Version) => {
             println!("russh SSH version {}", env!("CARGO_PKG_VERSION"));
             println!("Built with Rust");
@@ -170,46 +936,281 @@ async fn main() -> anyhow::Result<()> {
             println!();
             println!("Quick start:");
             println!("  russh connect user@host       Connect to a host");
+            println!("  russh PROFILE                 Connect to a saved profile");
+            println!("  russh cp FILE user@host:DIR   Copy a file to a host");
+            println!("  russh sftp user@host          Browse files on a host");
+            println!("  russh keygen                  Generate an SSH key");
+            println!("  russh mux start               Reuse connections across commands");
+            println!("  russh p2p pair                Pair with another device");
             println!("  russh profile list            List saved profiles");
             println!("  russh profile add NAME HOST   Add a new profile");
+            println!("  russh doctor                  Diagnose P2P connectivity");
+            println!("  russh completions bash        Print shell completions");
         }
     }
 
     Ok(())
 }
 
-async fn connect(
-    manager: &SessionManager,
-    target: &str,
-    use_password: bool,
+/// Options of `russh connect`
+struct ConnectOptions {
+    target: String,
+    password: bool,
     identity: Option<PathBuf>,
-    local_forwards: Vec<String>,
+    forwards: Vec<PortForward>,
+    /// Settings given with `-o`
+    ssh: SshOptions,
     command: Option<String>,
+    via: Option<String>,
+    /// Run a roaming shell, see [`roam`]
+    roam: bool,
+    tmux: Option<String>,
+}
+
+async fn connect(
+    manager: &SessionManager,
+    config_dir: &std::path::Path,
+    options: ConnectOptions,
 ) -> anyhow::Result<()> {
-    // Parse target: could be profile name or user@host:port
-    let (host, port, username) = if target.contains('@') {
-        parse_target(target)?
+    let ConnectOptions {
+        target,
+        password: use_password,
+        identity,
+        forwards,
+        ssh,
+        command,
+        via,
+        roam,
+        tmux,
+    } = options;
+    if via.is_some() && ssh.proxy_jump.is_some() {
+        anyhow::bail!("--via and -o ProxyJump cannot be combined");
+    }
+    let target = qualify_target(manager, target, ssh.user.as_deref()).await;
+    let profile = if target.contains('@') {
+        None
+    } else {
+        manager.get_profile_by_name(&target).await
+    };
+    let mut target = resolve_target(manager, &target).await?;
+    if let Some(user) = &ssh.user {
+        target.username = user.clone();
+    }
+    let identity = identity.or(ssh.identity_file.clone());
+
+    println!(
+        "Connecting to {}@{}:{}...",
+        target.username, target.host, target.port
+    );
+
+    let mut jump = None;
+    let mut endpoint = None;
+    let mut client = if via.is_none() && ssh.proxy_jump.is_none() && forwards.is_empty() {
+        // Forwards need a session of their own, commands can share one
+        mux::open_session(
+            config_dir,
+            target.host,
+            target.port,
+            target.username,
+            &ssh,
+            || resolve_auth(use_password, identity.or(target.identity)),
+        )
+        .await?
     } else {
-        // Try to find profile by name
-        if let Some(profile) = manager.get_profile_by_name(target).await {
-            (profile.host, profile.port, profile.username)
-        } else {
-            anyhow::bail!("Unknown profile or invalid target: {}", target);
+        let identity = identity.or(target.identity);
+        let auth = resolve_auth(use_password, identity.clone())?;
+        let mut config = ssh_config(target.host, target.port, target.username, auth);
+        ssh.apply(&mut config);
+
+        let mut client = SshClient::new();
+        if let Some(via) = via {
+            let node = p2p::Node::open(config_dir).await?;
+            let ticket = node.connect(&via).await?;
+            println!(
+                "Tunneling through peer {}...",
+                node.registry.display_name(&ticket.node_id())
+            );
+            config.transport = Transport::P2P(ticket.node_id());
+            client = client.with_p2p(node.manager);
+            endpoint = Some(node.endpoint);
+        }
+        if let Some(hop) = &ssh.proxy_jump {
+            jump = Some(proxy_jump(hop, &mut config, &ssh, use_password, identity).await?);
+        }
+        client.connect(&config).await?;
+        client
+    };
+
+    println!("Connected!");
+
+    // Keeps recently used profiles at the top of the picker
+    if let Some(mut profile) = profile {
+        profile.record_use();
+        manager.update_profile(profile).await?;
+        if let Err(e) = manager.save().await {
+            tracing::warn!("Could not save profiles: {}", e);
+        }
+    }
+
+    // Set up port forwards; each is up once its listener is bound
+    let mut started = 0;
+    for forward in &forwards {
+        match client.start_forward(forward.clone()).await {
+            Ok(_handle) => {
+                started += 1;
+                println!("  up      {}", tunnel::describe(forward));
+            }
+            Err(e) => eprintln!("  failed  {}: {}", tunnel::describe(forward), e),
         }
+    }
+    if !forwards.is_empty() {
+        println!("{} of {} forward(s) up", started, forwards.len());
+    }
+
+    // Execute command or start shell
+    if let Some(cmd) = command {
+        let result = client.execute(&cmd).await?;
+        print!("{}", result.stdout_string());
+        eprint!("{}", result.stderr_string());
+        std::process::exit(result.exit_code);
+    } else if let (true, Some(endpoint)) = (roam, endpoint) {
+        roam::run(&mut client, endpoint, tmux).await?;
+    } else if started > 0 {
+        println!("Forwarding; press Ctrl-C to stop");
+        hold(&client, ssh.server_alive_interval).await?;
+    } else {
+        println!("Interactive shell not yet implemented in CLI");
+        println!("Use -c 'command' to execute commands");
+    }
+
+    client.disconnect().await?;
+    if let Some(mut jump) = jump {
+        jump.disconnect().await?;
+    }
+    Ok(())
+}
+
+/// Reach `config`'s host through a jump host, like ssh's ProxyJump
+///
+/// The jump session forwards a loopback port to the host, and `config` is
+/// pointed at that port; the session must stay open while `config` is in
+/// use. As with `--via`, the host key is recorded for the loopback address.
+async fn proxy_jump(
+    hop: &str,
+    config: &mut SshConfig,
+    ssh: &SshOptions,
+    use_password: bool,
+    identity: Option<PathBuf>,
+) -> anyhow::Result<SshClient> {
+    let hop = if hop.contains('@') {
+        hop.to_string()
+    } else {
+        format!("{}@{}", config.username, hop)
+    };
+    let (host, port, username) = parse_target(&hop)?;
+    println!("Jumping through {}@{}:{}...", username, host, port);
+
+    let mut jump_config = ssh_config(host, port, username, resolve_auth(use_password, identity)?);
+    ssh.apply(&mut jump_config);
+    let mut jump = SshClient::new();
+    jump.connect(&jump_config).await?;
+
+    let local_port = std::net::TcpListener::bind("127.0.0.1:0")?
+        .local_addr()?
+        .port();
+    jump.start_forward(PortForward::Local {
+        local_port,
+        remote_host: config.host.clone(),
+        remote_port: config.port,
+    })
+    .await?;
+    config.host = "127.0.0.1".to_string();
+    config.port = local_port;
+    Ok(jump)
+}
+
+/// Wait for Ctrl-C, probing the session every `interval` like ssh's
+/// ServerAliveInterval and giving up once it stops answering
+async fn hold(client: &SshClient, interval: Option<Duration>) -> anyhow::Result<()> {
+    let Some(interval) = interval else {
+        tokio::signal::ctrl_c().await?;
+        return Ok(());
     };
+    let monitor = client.health_monitor(HealthConfig::new().with_interval(interval));
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            result = tokio::signal::ctrl_c() => return Ok(result?),
+        }
+        monitor.check(client).await;
+        if client.state().is_failed() {
+            anyhow::bail!("Connection lost: the server stopped answering");
+        }
+    }
+}
+
+/// Host to connect to, from a profile or a user@host:port target
+struct Target {
+    host: String,
+    port: u16,
+    username: String,
+    /// Key the profile logs in with
+    identity: Option<PathBuf>,
+    /// Forwards saved in the profile
+    forwards: Vec<PortForward>,
+}
 
-    println!("Connecting to {}@{}:{}...", username, host, port);
+/// Put `-o User=...` in front of a bare host, making it a valid target as
+/// with ssh; profile names and targets naming a user stay as they are
+async fn qualify_target(manager: &SessionManager, target: String, user: Option<&str>) -> String {
+    match user {
+        Some(user)
+            if !target.contains('@') && manager.get_profile_by_name(&target).await.is_none() =>
+        {
+            format!("{}@{}", user, target)
+        }
+        _ => target,
+    }
+}
+
+/// Resolve a profile name or user@host:port
+async fn resolve_target(manager: &SessionManager, target: &str) -> anyhow::Result<Target> {
+    if target.contains('@') {
+        let (host, port, username) = parse_target(target)?;
+        Ok(Target {
+            host,
+            port,
+            username,
+            identity: None,
+            forwards: Vec::new(),
+        })
+    } else if let Some(profile) = manager.get_profile_by_name(target).await {
+        let identity = match &profile.auth {
+            AuthConfig::PublicKey { key_path, .. } => Some(key_path.clone()),
+            _ => None,
+        };
+        Ok(Target {
+            host: profile.host,
+            port: profile.port,
+            username: profile.username,
+            identity,
+            forwards: profile.port_forwards,
+        })
+    } else {
+        anyhow::bail!("Unknown profile or invalid target: {}", target);
+    }
+}
 
-    // Determine auth method
+/// Pick the authentication method from the flags, falling back to the
+/// default keys and then a password prompt
+fn resolve_auth(use_password: bool, identity: Option<PathBuf>) -> anyhow::Result<AuthMethod> {
     let auth = if use_password {
         println!("Password: ");
         let password = rpassword::read_password()?;
         AuthMethod::Password(password)
     } else if let Some(key_path) = identity {
-        AuthMethod::PublicKey {
-            key_path,
-            passphrase: None,
-        }
+        key_auth(key_path)?
     } else {
         // Try default key locations
         let home = dirs::home_dir().unwrap_or_default();
@@ -220,10 +1221,7 @@ async fn connect(
         match key_path {
             Some(path) => {
                 println!("Using key: {}", path.display());
-                AuthMethod::PublicKey {
-                    key_path: path,
-                    passphrase: None,
-                }
+                key_auth(path)?
             }
             None => {
                 println!("No key found, using password authentication");
@@ -233,65 +1231,140 @@ async fn connect(
             }
         }
     };
+    Ok(auth)
+}
+
+/// Log in with a key file, asking for its passphrase if it has one
+fn key_auth(key_path: PathBuf) -> anyhow::Result<AuthMethod> {
+    let passphrase = if SshKeyPair::is_encrypted_file(&key_path).unwrap_or(false) {
+        Some(rpassword::prompt_password(format!(
+            "Passphrase for {}: ",
+            key_path.display()
+        ))?)
+    } else {
+        None
+    };
+    Ok(AuthMethod::PublicKey {
+        key_path,
+        passphrase,
+    })
+}
 
-    let config = SshConfig {
-        host: host.clone(),
+/// known_hosts file host keys are checked against
+fn known_hosts_path() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_default()
+        .join(".russh")
+        .join(KNOWN_HOSTS_FILE_NAME)
+}
+
+/// Connection settings shared by every command that opens an SSH session
+fn ssh_config(host: String, port: u16, username: String, auth: AuthMethod) -> SshConfig {
+    SshConfig {
+        host,
         port,
-        username: username.clone(),
+        username,
         auth,
         timeout: Duration::from_secs(30),
-        known_hosts_path: Some(
-            dirs::home_dir()
-                .unwrap_or_default()
-                .join(".russh/known_hosts"),
-        ),
+        known_hosts_path: Some(known_hosts_path()),
         host_key_check: HostKeyCheck::AcceptNew,
-    };
+        transport: Transport::Tcp,
+    }
+}
 
-    let mut client = SshClient::new();
-    client.connect(&config).await?;
+/// Report NAT traversal diagnostics for this machine and optionally a peer
+async fn doctor(peer: Option<String>, timeout: Duration) -> anyhow::Result<()> {
+    let ticket = peer.map(|p| p.parse::<PeerTicket>()).transpose()?;
 
-    println!("Connected!");
+    println!("Probing network...");
+    let endpoint = std::sync::Arc::new(P2PEndpoint::bind(P2PConfig::default()).await?);
+    endpoint.wait_online().await;
 
-    // Set up port forwards
-    for forward_spec in local_forwards {
-        if let Some(forward) = parse_local_forward(&forward_spec) {
-            match client.start_forward(forward.clone()).await {
-                Ok(_handle) => {
-                    if let PortForward::Local {
-                        local_port,
-                        remote_host,
-                        remote_port,
-                    } = &forward
-                    {
-                        println!(
-                            "Forwarding localhost:{} -> {}:{}",
-                            local_port, remote_host, remote_port
-                        );
-                    }
+    let network = NetworkDiagnostics::collect(&endpoint, timeout).await;
+    println!();
+    println!("Node ID: {}", network.node_id);
+    println!("  NAT type: {}", network.nat_type);
+    println!(
+        "  UDP: {}  IPv4: {}  IPv6: {}",
+        yes_no(network.udp),
+        yes_no(network.ipv4),
+        yes_no(network.ipv6)
+    );
+    if let Some(addr) = network.global_v4 {
+        println!("  Public IPv4: {}", addr);
+    }
+    if let Some(addr) = network.global_v6 {
+        println!("  Public IPv6: {}", addr);
+    }
+    println!(
+        "  Home relay: {}",
+        network.home_relay.as_deref().unwrap_or("none")
+    );
+    println!("  Candidate addresses:");
+    for candidate in &network.candidates {
+        println!("    {} ({:?})", candidate.addr, candidate.kind);
+    }
+
+    if let Some(ticket) = ticket {
+        let manager = P2PConnectionManager::new(endpoint.clone());
+        println!();
+        println!("Connecting to {}...", ticket.node_id().fmt_short());
+        manager.connect_ticket(&ticket).await?;
+
+        // Give hole punching a chance to upgrade the path from the relay
+        let deadline = std::time::Instant::now() + timeout;
+        let mut report = PeerDiagnostics::collect(&endpoint, ticket.node_id());
+        while report.as_ref().is_some_and(|r| r.path != PathType::Direct)
+            && std::time::Instant::now() < deadline
+        {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            report = PeerDiagnostics::collect(&endpoint, ticket.node_id());
+        }
+
+        match report {
+            Some(report) => {
+                println!("  Path: {:?}", report.path);
+                if let Some(addr) = report.direct_addr {
+                    println!("  Direct address: {}", addr);
+                }
+                if let Some(relay) = &report.relay_url {
+                    println!("  Relay: {}", relay);
                 }
-                Err(e) => {
-                    eprintln!("Failed to set up forward: {}", e);
+                if let Some(latency) = report.latency_ms {
+                    println!("  Latency: {} ms", latency);
                 }
+                println!("  Hole punching: {:?}", report.hole_punch);
+                for candidate in &report.candidates {
+                    println!("    {} {:?}", candidate.addr, candidate.outcome);
+                }
+                manager.disconnect(&ticket.node_id()).await;
             }
+            None => println!("  No path information for this peer"),
         }
     }
 
-    // Execute command or start shell
-    if let Some(cmd) = command {
-        let result = client.execute(&cmd).await?;
-        print!("{}", result.stdout_string());
-        eprint!("{}", result.stderr_string());
-        std::process::exit(result.exit_code);
+    let issues = network.issues();
+    println!();
+    if issues.is_empty() {
+        println!("No problems found.");
     } else {
-        println!("Interactive shell not yet implemented in CLI");
-        println!("Use -c 'command' to execute commands");
+        println!("Problems:");
+        for issue in issues {
+            println!("  - {}", issue);
+        }
     }
 
-    client.disconnect().await?;
     Ok(())
 }
 
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
 fn parse_target(target: &str) -> anyhow::Result<(String, u16, String)> {
     // Format: user@host:port or user@host
     let parts: Vec<&str> = target.split('@').collect();
@@ -303,6 +1376,9 @@ fn parse_target(target: &str) -> anyhow::Result<(String, u16, String)> {
     let host_port: Vec<&str> = parts[1].split(':').collect();
 
     let host = host_port[0].to_string();
+    if username.is_empty() || host.is_empty() {
+        anyhow::bail!("Invalid target format. Use: user@host[:port]");
+    }
     let port = if host_port.len() > 1 {
         host_port[1].parse()?
     } else {
@@ -312,24 +1388,6 @@ fn parse_target(target: &str) -> anyhow::Result<(String, u16, String)> {
     Ok((host, port, username))
 }
 
-fn parse_local_forward(spec: &str) -> Option<PortForward> {
-    // Format: local_port:remote_host:remote_port
-    let parts: Vec<&str> = spec.split(':').collect();
-    if parts.len() != 3 {
-        return None;
-    }
-
-    let local_port: u16 = parts[0].parse().ok()?;
-    let remote_host = parts[1].to_string();
-    let remote_port: u16 = parts[2].parse().ok()?;
-
-    Some(PortForward::Local {
-        local_port,
-        remote_host,
-        remote_port,
-    })
-}
-
 async fn handle_profile_action(
     manager: &SessionManager,
     action: ProfileAction,
@@ -359,10 +1417,14 @@ async fn handle_profile_action(
             host,
             user,
             port,
+            tag,
         } => {
-            let profile = SessionProfile::new(name.clone(), host.clone(), user.clone())
+            let mut profile = SessionProfile::new(name.clone(), host.clone(), user.clone())
                 .with_port(port)
                 .with_auth(AuthConfig::Agent);
+            for tag in tag {
+                profile = profile.with_tag(tag);
+            }
 
             manager.add_profile(profile).await;
             println!("Profile '{}' added: {}@{}:{}", name, user, host, port);
@@ -380,6 +1442,9 @@ async fn handle_profile_action(
                 println!("Profile: {}", profile.name);
                 println!("  Host: {}:{}", profile.host, profile.port);
                 println!("  User: {}", profile.username);
+                if !profile.tags.is_empty() {
+                    println!("  Tags: {}", profile.tags.join(", "));
+                }
                 if let Some(desc) = &profile.description {
                     println!("  Description: {}", desc);
                 }
@@ -392,6 +1457,129 @@ async fn handle_profile_action(
                 println!("Profile '{}' not found.", name);
             }
         }
+        ProfileAction::Export {
+            file,
+            strip_secrets,
+        } => {
+            let count = manager.export_with(&file, strip_secrets).await?;
+            println!("Exported {} profile(s) to {}.", count, file.display());
+        }
+        ProfileAction::Import {
+            file,
+            merge: _,
+            replace,
+            on_conflict,
+        } => {
+            let mode = if replace {
+                ImportMode::Replace
+            } else {
+                ImportMode::Merge
+            };
+            let report = manager.import_with(&file, mode, on_conflict.into()).await?;
+            if report.removed > 0 {
+                println!("Removed {} saved profile(s).", report.removed);
+            }
+            for name in &report.added {
+                println!("  added        {}", name);
+            }
+            for name in &report.overwritten {
+                println!("  overwritten  {}", name);
+            }
+            for (name, new_name) in &report.renamed {
+                println!("  renamed      {} -> {}", name, new_name);
+            }
+            for name in &report.skipped {
+                println!("  skipped      {} (name taken)", name);
+            }
+            println!(
+                "Imported {} profile(s) from {}.",
+                report.added.len() + report.overwritten.len() + report.renamed.len(),
+                file.display()
+            );
+            if !report.skipped.is_empty() {
+                println!("Use --on-conflict overwrite or rename to import skipped profiles.");
+            }
+        }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn manager_with_profile() -> SessionManager {
+        let manager = SessionManager::new();
+        let profile = SessionProfile::new(
+            "web".to_string(),
+            "web.example.com".to_string(),
+            "deploy".to_string(),
+        )
+        .with_port(2222)
+        .with_auth(AuthConfig::public_key("/keys/deploy", false))
+        .with_port_forward(PortForward::Dynamic { local_port: 1080 });
+        manager.add_profile(profile).await;
+        manager
+    }
+
+    #[test]
+    fn targets_name_a_user_and_host_and_maybe_a_port() {
+        assert_eq!(
+            parse_target("alice@example.com").unwrap(),
+            ("example.com".to_string(), 22, "alice".to_string())
+        );
+        assert_eq!(
+            parse_target("alice@10.0.0.5:2222").unwrap(),
+            ("10.0.0.5".to_string(), 2222, "alice".to_string())
+        );
+        for target in [
+            "example.com",
+            "a@b@example.com",
+            "@example.com",
+            "alice@",
+            "alice@:22",
+            "alice@example.com:ssh",
+            "alice@example.com:70000",
+        ] {
+            assert!(parse_target(target).is_err(), "{} was accepted", target);
+        }
+    }
+
+    #[tokio::test]
+    async fn option_user_qualifies_bare_hosts_only() {
+        let manager = manager_with_profile().await;
+        let qualify = |target: &str, user| qualify_target(&manager, target.to_string(), user);
+        assert_eq!(
+            qualify("example.com", Some("alice")).await,
+            "alice@example.com"
+        );
+        assert_eq!(
+            qualify("bob@example.com", Some("alice")).await,
+            "bob@example.com"
+        );
+        // A profile name is resolved as a profile
+        assert_eq!(qualify("web", Some("alice")).await, "web");
+        assert_eq!(qualify("example.com", None).await, "example.com");
+    }
+
+    #[tokio::test]
+    async fn targets_resolve_from_profiles_or_addresses() {
+        let manager = manager_with_profile().await;
+        let target = resolve_target(&manager, "web").await.unwrap();
+        assert_eq!(
+            (target.host.as_str(), target.port, target.username.as_str()),
+            ("web.example.com", 2222, "deploy")
+        );
+        assert_eq!(target.identity, Some(PathBuf::from("/keys/deploy")));
+        assert_eq!(target.forwards, [PortForward::Dynamic { local_port: 1080 }]);
+
+        let target = resolve_target(&manager, "alice@example.com:2200")
+            .await
+            .unwrap();
+        assert_eq!(
+            (target.host.as_str(), target.port, target.identity),
+            ("example.com", 2200, None)
+        );
+        assert!(resolve_target(&manager, "db").await.is_err());
+    }
+}
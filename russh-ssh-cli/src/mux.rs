@@ -0,0 +1,167 @@
+//! `russh mux`
+//!
+//! Runs the session multiplexer: a background process holding SSH sessions
+//! open on `<config dir>/mux.sock`, so `russh connect -c`, `cp`, `sftp` and
+//! `exec` run over a session that is already up instead of connecting
+//! again. Sessions are opened in it the first time a command needs them
+//! and closed after sitting unused for the idle timeout.
+
+use crate::options::SshOptions;
+use crate::MuxAction;
+use russh_ssh::ssh::{AuthMethod, MuxClient, PoolKey, SshClient, MUX_SOCKET_FILE_NAME};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long `start` waits for the daemon to come up
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Path of the control socket
+pub fn socket_path(config_dir: &Path) -> PathBuf {
+    config_dir.join(MUX_SOCKET_FILE_NAME)
+}
+
+/// Open a session, through the multiplexer when it is running
+///
+/// `auth` is only called when a new session has to be authenticated, so
+/// reusing a shared session asks for no password or passphrase.
+pub async fn open_session(
+    config_dir: &Path,
+    host: String,
+    port: u16,
+    username: String,
+    options: &SshOptions,
+    auth: impl FnOnce() -> anyhow::Result<AuthMethod>,
+) -> anyhow::Result<SshClient> {
+    let mux = MuxClient::new(socket_path(config_dir));
+    let key = PoolKey {
+        host: host.clone(),
+        port,
+        username: username.clone(),
+    };
+    let running = match mux.status().await {
+        Ok(sessions) if sessions.iter().any(|session| session.key == key) => {
+            return Ok(SshClient::new().with_mux(mux, key));
+        }
+        Ok(_) => true,
+        Err(_) => false,
+    };
+
+    let mut config = crate::ssh_config(host, port, username, auth()?);
+    options.apply(&mut config);
+    if running {
+        return Ok(mux.attach(&config).await?);
+    }
+    let mut client = SshClient::new();
+    client.connect(&config).await?;
+    Ok(client)
+}
+
+/// Run a mux command
+pub async fn run(action: MuxAction, config_dir: &Path) -> anyhow::Result<()> {
+    let socket = socket_path(config_dir);
+    let mux = MuxClient::new(&socket);
+    match action {
+        MuxAction::Start { idle, foreground } => {
+            let idle = Duration::from_secs(idle.saturating_mul(60));
+            if mux.is_running().await {
+                anyhow::bail!("The multiplexer is already running on {}", socket.display());
+            }
+            if foreground {
+                serve(&socket, idle).await?;
+            } else {
+                start(config_dir, &mux, idle).await?;
+            }
+        }
+        MuxAction::Status => {
+            let Ok(sessions) = mux.status().await else {
+                println!("The multiplexer is not running.");
+                println!("Start it with 'russh mux start'.");
+                return Ok(());
+            };
+            println!("Multiplexer running on {}", socket.display());
+            if sessions.is_empty() {
+                println!("No shared sessions.");
+            }
+            for session in sessions {
+                println!(
+                    "  {:<40} {:<12} {} command(s), idle {}s",
+                    session.key.to_string(),
+                    if session.connected {
+                        "connected"
+                    } else {
+                        "disconnected"
+                    },
+                    session.commands,
+                    session.idle.as_secs()
+                );
+            }
+        }
+        MuxAction::Stop => {
+            if !mux.is_running().await {
+                println!("The multiplexer is not running.");
+                return Ok(());
+            }
+            mux.stop().await?;
+            println!("Multiplexer stopped; shared sessions closed.");
+        }
+    }
+    Ok(())
+}
+
+/// Start the daemon in the background and wait for its socket
+async fn start(config_dir: &Path, mux: &MuxClient, idle: Duration) -> anyhow::Result<()> {
+    let mut command = std::process::Command::new(std::env::current_exe()?);
+    command
+        .arg("--config-dir")
+        .arg(config_dir)
+        .args(["mux", "start", "--foreground", "--idle"])
+        .arg((idle.as_secs() / 60).to_string())
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    #[cfg(unix)]
+    {
+        // Out of the terminal's process group, so Ctrl-C there leaves it running
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    let child = command.spawn()?;
+
+    let deadline = tokio::time::Instant::now() + START_TIMEOUT;
+    while !mux.is_running().await {
+        if tokio::time::Instant::now() >= deadline {
+            anyhow::bail!(
+                "The multiplexer did not start; run 'russh mux start --foreground' to see why"
+            );
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    println!(
+        "Multiplexer started (pid {}) on {}",
+        child.id(),
+        mux.path().display()
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn serve(socket: &Path, idle: Duration) -> anyhow::Result<()> {
+    let server = russh_ssh::ssh::MuxServer::new().with_idle_timeout(idle);
+    eprintln!(
+        "Sharing sessions on {}; press Ctrl-C to stop",
+        socket.display()
+    );
+    tokio::select! {
+        result = server.serve(socket) => result?,
+        result = tokio::signal::ctrl_c() => {
+            result?;
+            let _ = std::fs::remove_file(socket);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn serve(_socket: &Path, _idle: Duration) -> anyhow::Result<()> {
+    anyhow::bail!("The multiplexer is only supported on Unix")
+}
@@ -0,0 +1,205 @@
+//! OpenSSH `-o Option=value` flags
+//!
+//! Translates the ssh_config options scripts most often pass on the
+//! command line into russh settings, so `ssh -o ...` invocations carry
+//! over. Names are case-insensitive and, as with ssh, the value may follow
+//! `=` or a space, and the first value given for an option wins. Options
+//! russh does not understand are an error rather than silently ignored.
+
+use russh_ssh::ssh::{HostKeyCheck, SshConfig};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Options supported by `-o`
+const SUPPORTED: &str = "StrictHostKeyChecking, ConnectTimeout, ServerAliveInterval, \
+                         ProxyJump, IdentityFile, User";
+
+/// Settings given with `-o`
+#[derive(Debug, Default)]
+pub struct SshOptions {
+    /// `User`
+    pub user: Option<String>,
+    /// `IdentityFile`
+    pub identity_file: Option<PathBuf>,
+    /// `StrictHostKeyChecking`
+    pub host_key_check: Option<HostKeyCheck>,
+    /// `ConnectTimeout`
+    pub connect_timeout: Option<Duration>,
+    /// `ServerAliveInterval`; `None` when 0 turns it off
+    pub server_alive_interval: Option<Duration>,
+    /// `ProxyJump` as `[user@]host[:port]`
+    pub proxy_jump: Option<String>,
+}
+
+impl SshOptions {
+    /// Parse the arguments of every `-o`
+    pub fn parse(options: &[String]) -> anyhow::Result<Self> {
+        let mut parsed = Self::default();
+        let mut seen = Vec::new();
+        for option in options {
+            let (name, value) = split(option)?;
+            let name = name.to_ascii_lowercase();
+            if seen.contains(&name) {
+                continue;
+            }
+            parsed.set(&name, value, option)?;
+            seen.push(name);
+        }
+        Ok(parsed)
+    }
+
+    fn set(&mut self, name: &str, value: &str, option: &str) -> anyhow::Result<()> {
+        let invalid = |expected: &str| {
+            anyhow::anyhow!("Invalid option '-o {}': expected {}", option, expected)
+        };
+        let seconds = |expected: &str| -> anyhow::Result<u64> {
+            value.parse().map_err(|_| invalid(expected))
+        };
+        match name {
+            "user" => self.user = Some(value.to_string()),
+            "identityfile" => {
+                self.identity_file = Some(PathBuf::from(shellexpand::tilde(value).as_ref()));
+            }
+            "stricthostkeychecking" => {
+                self.host_key_check = Some(match value.to_ascii_lowercase().as_str() {
+                    "yes" | "ask" => HostKeyCheck::Strict,
+                    "accept-new" => HostKeyCheck::AcceptNew,
+                    "no" | "off" => HostKeyCheck::None,
+                    _ => return Err(invalid("yes, accept-new or no")),
+                });
+            }
+            "connecttimeout" => match seconds("a number of seconds above 0")? {
+                0 => return Err(invalid("a number of seconds above 0")),
+                secs => self.connect_timeout = Some(Duration::from_secs(secs)),
+            },
+            "serveraliveinterval" => match seconds("a number of seconds")? {
+                0 => self.server_alive_interval = None,
+                secs => self.server_alive_interval = Some(Duration::from_secs(secs)),
+            },
+            "proxyjump" => {
+                if value.contains(',') {
+                    return Err(invalid("a single jump host"));
+                }
+                if !value.eq_ignore_ascii_case("none") {
+                    self.proxy_jump = Some(value.to_string());
+                }
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Unsupported option '-o {}' (supported: {})",
+                    option,
+                    SUPPORTED
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the connection settings to `config`
+    pub fn apply(&self, config: &mut SshConfig) {
+        if let Some(check) = &self.host_key_check {
+            config.host_key_check = check.clone();
+        }
+        if let Some(timeout) = self.connect_timeout {
+            config.timeout = timeout;
+        }
+    }
+}
+
+/// Split `Name=value` or `Name value`
+fn split(option: &str) -> anyhow::Result<(&str, &str)> {
+    let option = option.trim();
+    let (name, value) = option
+        .split_once('=')
+        .or_else(|| option.split_once(char::is_whitespace))
+        .ok_or_else(|| anyhow::anyhow!("Invalid option '-o {}': expected Name=value", option))?;
+    let (name, value) = (name.trim(), value.trim());
+    if name.is_empty() || value.is_empty() {
+        anyhow::bail!("Invalid option '-o {}': expected Name=value", option);
+    }
+    Ok((name, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(options: &[&str]) -> anyhow::Result<SshOptions> {
+        let options: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+        SshOptions::parse(&options)
+    }
+
+    #[test]
+    fn values_follow_equals_or_space_and_names_ignore_case() {
+        let options = parse(&[
+            "User=alice",
+            "connecttimeout 10",
+            "STRICTHOSTKEYCHECKING = accept-new",
+            "IdentityFile=/keys/id_ed25519",
+        ])
+        .unwrap();
+        assert_eq!(options.user.as_deref(), Some("alice"));
+        assert_eq!(options.connect_timeout, Some(Duration::from_secs(10)));
+        assert!(matches!(
+            options.host_key_check,
+            Some(HostKeyCheck::AcceptNew)
+        ));
+        assert_eq!(
+            options.identity_file,
+            Some(PathBuf::from("/keys/id_ed25519"))
+        );
+
+        let check = |value: &str| {
+            parse(&[&format!("StrictHostKeyChecking={}", value)])
+                .unwrap()
+                .host_key_check
+        };
+        assert!(matches!(check("yes"), Some(HostKeyCheck::Strict)));
+        assert!(matches!(check("ask"), Some(HostKeyCheck::Strict)));
+        assert!(matches!(check("Off"), Some(HostKeyCheck::None)));
+    }
+
+    #[test]
+    fn first_value_wins() {
+        let options = parse(&["User=alice", "user=bob"]).unwrap();
+        assert_eq!(options.user.as_deref(), Some("alice"));
+        // Later values are not even checked, as with ssh
+        let options = parse(&["ConnectTimeout=5", "ConnectTimeout=soon"]).unwrap();
+        assert_eq!(options.connect_timeout, Some(Duration::from_secs(5)));
+        let options = parse(&["ServerAliveInterval=0", "ServerAliveInterval=30"]).unwrap();
+        assert_eq!(options.server_alive_interval, None);
+    }
+
+    #[test]
+    fn server_alive_interval_zero_turns_it_off() {
+        let options = parse(&["ServerAliveInterval=30"]).unwrap();
+        assert_eq!(options.server_alive_interval, Some(Duration::from_secs(30)));
+        let options = parse(&["ServerAliveInterval=0"]).unwrap();
+        assert_eq!(options.server_alive_interval, None);
+        assert!(parse(&["ServerAliveInterval=-1"]).is_err());
+        assert!(parse(&["ConnectTimeout=0"]).is_err());
+    }
+
+    #[test]
+    fn proxy_jump_takes_one_host_or_none() {
+        let options = parse(&["ProxyJump=bob@jump.example.com:2222"]).unwrap();
+        assert_eq!(
+            options.proxy_jump.as_deref(),
+            Some("bob@jump.example.com:2222")
+        );
+        assert_eq!(parse(&["ProxyJump=none"]).unwrap().proxy_jump, None);
+        assert_eq!(parse(&["ProxyJump NONE"]).unwrap().proxy_jump, None);
+        assert!(parse(&["ProxyJump=a.example.com,b.example.com"]).is_err());
+    }
+
+    #[test]
+    fn malformed_and_unsupported_options_are_errors() {
+        for option in ["User", "=alice", "User=", "  "] {
+            assert!(parse(&[option]).is_err(), "{:?} was accepted", option);
+        }
+        assert!(parse(&["StrictHostKeyChecking=maybe"]).is_err());
+        let error = parse(&["Compression=yes"]).unwrap_err().to_string();
+        assert!(error.contains("Unsupported"), "{}", error);
+        assert!(error.contains("ProxyJump"), "{}", error);
+    }
+}
@@ -0,0 +1,333 @@
+//! `russh p2p`
+//!
+//! Runs a P2P node from the command line. Its NodeId is kept in
+//! `<config dir>/node.key`, so tickets and pairings survive restarts, and
+//! its access control list refuses peers that have not been paired. Known
+//! peers are kept in the peer registry, so commands take a name like
+//! `laptop` as well as a ticket or NodeId.
+
+use crate::cp::{format_bytes, Progress};
+use crate::P2pAction;
+use russh_ssh::events::{EventBus, EventKind, RusshEvent};
+use russh_ssh::p2p::{
+    AclDefault, FileTransfer, P2PConfig, P2PConnectionManager, P2PEndpoint, Pairing, PairingTicket,
+    PeerAcl, PeerRegistry, PeerTicket, PeerTrust, TunnelAgent, NODE_KEY_FILE_NAME,
+    PEER_ACL_FILE_NAME, PEER_REGISTRY_FILE_NAME,
+};
+use russh_ssh::NodeId;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// This device's P2P endpoint and what it knows about peers
+pub struct Node {
+    pub endpoint: Arc<P2PEndpoint>,
+    pub manager: Arc<P2PConnectionManager>,
+    pub registry: PeerRegistry,
+}
+
+impl Node {
+    /// Bring the node online with the identity and lists in `config_dir`
+    pub async fn open(config_dir: &Path) -> anyhow::Result<Self> {
+        let acl_path = config_dir.join(PEER_ACL_FILE_NAME);
+        let first_run = !acl_path.exists();
+        let acl = PeerAcl::open(&acl_path)?;
+        if first_run {
+            // Only paired peers may dial in
+            acl.set_default_rule(AclDefault::Deny)?;
+        }
+
+        let config = P2PConfig::default().with_key_file(&config_dir.join(NODE_KEY_FILE_NAME))?;
+        let endpoint = Arc::new(P2PEndpoint::bind(config).await?);
+        let manager = Arc::new(P2PConnectionManager::new(endpoint.clone()).with_acl(Arc::new(acl)));
+        let registry = PeerRegistry::open(&config_dir.join(PEER_REGISTRY_FILE_NAME))?;
+        Ok(Self {
+            endpoint,
+            manager,
+            registry,
+        })
+    }
+
+    /// Connect to a peer given by name, ticket or NodeId
+    pub async fn connect(&self, peer: &str) -> anyhow::Result<PeerTicket> {
+        let ticket = self.registry.resolve(peer)?;
+        self.manager.connect_ticket(&ticket).await?;
+        self.registry.touch(&ticket.node_id(), Some(&ticket))?;
+        Ok(ticket)
+    }
+
+    /// Name of a peer for messages
+    fn name(&self, peer: &NodeId) -> String {
+        self.registry.display_name(peer)
+    }
+}
+
+/// Run a p2p command
+pub async fn run(action: P2pAction, config_dir: &Path) -> anyhow::Result<()> {
+    let node = Node::open(config_dir).await?;
+    let result = match action {
+        P2pAction::Id { qr } => show_id(&node, qr).await,
+        P2pAction::Pair { ticket, name, ttl } => {
+            let ttl = Duration::from_secs(ttl.saturating_mul(60));
+            match ticket {
+                Some(ticket) => join(&node, &ticket, name).await,
+                None => invite(&node, name, ttl).await,
+            }
+        }
+        P2pAction::Connect { peer, name } => connect(&node, &peer, name).await,
+        P2pAction::Peers => {
+            list_peers(&node);
+            Ok(())
+        }
+        P2pAction::Send { file, peer } => send(&node, &file, &peer).await,
+        P2pAction::Serve { dir, ssh } => {
+            let dir = match dir {
+                Some(dir) => dir,
+                None => std::env::current_dir()?,
+            };
+            serve(&node, &dir, ssh).await
+        }
+    };
+    node.manager.disconnect_all().await;
+    result
+}
+
+async fn show_id(node: &Node, qr: bool) -> anyhow::Result<()> {
+    node.endpoint.wait_online().await;
+    let ticket = PeerTicket::from_endpoint(&node.endpoint).await?;
+    println!("Node ID: {}", node.endpoint.node_id());
+    if let Some(relay) = node.endpoint.relay_url() {
+        println!("Relay:   {}", relay);
+    }
+    for addr in ticket.direct_addresses() {
+        println!("Address: {}", addr);
+    }
+    println!();
+    if qr {
+        println!(
+            "{}",
+            russh_ssh::p2p::render_qr_terminal(&ticket.to_string())?
+        );
+    }
+    println!("{}", ticket);
+    Ok(())
+}
+
+/// Show a one-time pairing code and wait for a device to redeem it
+async fn invite(node: &Node, name: Option<String>, ttl: Duration) -> anyhow::Result<()> {
+    let mut pairing = Pairing::new(node.manager.clone());
+    if let Some(name) = name {
+        pairing = pairing.with_name(name);
+    }
+    let server = pairing
+        .register(TunnelAgent::new(node.manager.clone()))
+        .serve();
+    let mut paired = pairing.subscribe();
+
+    node.endpoint.wait_online().await;
+    let ticket = pairing.create_ticket(ttl).await?;
+    println!("{}", ticket.to_qr_terminal()?);
+    println!("{}", ticket);
+    println!();
+    println!(
+        "On the other device, scan the code or run 'russh p2p pair <TICKET>'. \
+         Waiting up to {} minute(s); press Ctrl-C to cancel.",
+        ttl.as_secs().div_ceil(60)
+    );
+
+    let result = tokio::select! {
+        peer = tokio::time::timeout(ttl, paired.recv()) => match peer {
+            Ok(Ok(peer)) => {
+                let name = remember(node, &PeerTicket::new(peer.node_id), peer.name.as_deref())?;
+                println!("Paired with {} ({})", name, peer.node_id.fmt_short());
+                Ok(())
+            }
+            Ok(Err(e)) => Err(e.into()),
+            Err(_) => Err(anyhow::anyhow!("The pairing code expired")),
+        },
+        result = tokio::signal::ctrl_c() => result.map_err(Into::into),
+    };
+    pairing.cancel();
+    server.abort();
+    result
+}
+
+/// Redeem a pairing code shown by another device
+async fn join(node: &Node, ticket: &str, name: Option<String>) -> anyhow::Result<()> {
+    let ticket: PairingTicket = ticket.trim().parse()?;
+    let mut pairing = Pairing::new(node.manager.clone());
+    if let Some(name) = name {
+        pairing = pairing.with_name(name);
+    }
+    println!("Pairing with {}...", ticket.node_id().fmt_short());
+    let peer = pairing.pair(&ticket).await?;
+    let name = remember(node, ticket.peer(), peer.name.as_deref())?;
+    println!("Paired with {} ({})", name, peer.node_id.fmt_short());
+    Ok(())
+}
+
+/// Add a paired peer to the registry as trusted, returning its name
+fn remember(node: &Node, ticket: &PeerTicket, announced: Option<&str>) -> anyhow::Result<String> {
+    let node_id = ticket.node_id();
+    let name = match node.registry.get(&node_id) {
+        Some(record) => {
+            node.registry.touch(&node_id, Some(ticket))?;
+            record.name
+        }
+        None => {
+            let fallback = format!("peer-{}", node_id.fmt_short());
+            let record = match announced.map(|name| node.registry.add(ticket, &peer_name(name))) {
+                Some(Ok(record)) => record,
+                _ => node.registry.add(ticket, &fallback)?,
+            };
+            record.name
+        }
+    };
+    node.registry.set_trust(&node_id, PeerTrust::Trusted)?;
+    Ok(name)
+}
+
+/// Turn a device name into one the registry accepts
+fn peer_name(announced: &str) -> String {
+    announced
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+async fn connect(node: &Node, peer: &str, name: Option<String>) -> anyhow::Result<()> {
+    let ticket = node.registry.resolve(peer)?;
+    println!("Connecting to {}...", node.name(&ticket.node_id()));
+    let connection = node.manager.connect_ticket(&ticket).await?;
+    if let Some(name) = name {
+        node.registry.add(&ticket, &name)?;
+        println!("Saved as {}", name);
+    } else {
+        node.registry.touch(&ticket.node_id(), Some(&ticket))?;
+    }
+
+    // Give hole punching a moment to find a direct path
+    tokio::time::sleep(Duration::from_secs(2)).await;
+    connection.update_connection_type().await;
+    connection.measure_latency().await;
+    let info = connection.info().await;
+    println!("Connected to {}", ticket.node_id());
+    println!("  Path: {}", info.connection_type);
+    if let Some(addr) = info.remote_addr {
+        println!("  Address: {}", addr);
+    }
+    if let Some(relay) = info.relay_url {
+        println!("  Relay: {}", relay);
+    }
+    if let Some(latency) = info.latency {
+        println!("  Latency: {} ms", latency.as_millis());
+    }
+    Ok(())
+}
+
+fn list_peers(node: &Node) {
+    let peers = node.registry.list();
+    if peers.is_empty() {
+        println!("No known peers.");
+        println!("Use 'russh p2p pair' to pair with a device.");
+        return;
+    }
+    for peer in peers {
+        let trust = match peer.trust {
+            PeerTrust::Trusted => "trusted",
+            PeerTrust::Unverified => "unverified",
+            PeerTrust::Blocked => "blocked",
+        };
+        println!("  {:<20} {} {}", peer.name, peer.node_id.fmt_short(), trust);
+        if !peer.tags.is_empty() {
+            let tags: Vec<&str> = peer.tags.iter().map(String::as_str).collect();
+            println!("    tags: {}", tags.join(", "));
+        }
+        if let Some(last_seen) = peer.last_seen {
+            println!("    last seen: {}", last_seen.format("%Y-%m-%d %H:%M UTC"));
+        }
+    }
+}
+
+async fn send(node: &Node, path: &Path, peer: &str) -> anyhow::Result<()> {
+    let ticket = node.connect(peer).await?;
+    let bus = EventBus::new();
+    let mut events = bus.subscribe_to(&[EventKind::Transfer]);
+    let transfer = FileTransfer::new(node.manager.clone(), ".").with_event_bus(bus);
+
+    // One progress bar per file, as the transfer reports them
+    let progress = tokio::spawn(async move {
+        let mut bars: HashMap<String, Progress> = HashMap::new();
+        while let Some(RusshEvent::Transfer {
+            path,
+            bytes,
+            total,
+            done,
+            ..
+        }) = events.recv().await
+        {
+            let bar = bars.entry(path.clone()).or_insert_with(|| {
+                Progress::new(path.clone(), total.unwrap_or(bytes), bytes, false)
+            });
+            bar.update(bytes);
+            if done {
+                bar.finish();
+            }
+        }
+    });
+
+    println!(
+        "Sending {} to {}...",
+        path.display(),
+        node.name(&ticket.node_id())
+    );
+    let result = transfer.send(ticket.node_id(), path).await;
+    progress.abort();
+    let report = result?;
+    println!(
+        "Sent {} file(s), {} ({} already there)",
+        report.files.len(),
+        format_bytes(report.bytes_transferred),
+        format_bytes(report.resumed_bytes)
+    );
+    Ok(())
+}
+
+/// Accept files and SSH tunnels from paired peers until Ctrl-C
+async fn serve(node: &Node, dir: &Path, ssh: std::net::SocketAddr) -> anyhow::Result<()> {
+    let bus = EventBus::new();
+    let mut events = bus.subscribe_to(&[EventKind::Transfer]);
+    let transfer = FileTransfer::new(node.manager.clone(), dir).with_event_bus(bus);
+    let server = transfer
+        .register(TunnelAgent::new(node.manager.clone()).with_ssh_target(ssh))
+        .serve();
+
+    node.endpoint.wait_online().await;
+    println!("Node ID: {}", node.endpoint.node_id());
+    println!("Saving received files to {}", dir.display());
+    println!("Tunneling SSH to {}", ssh);
+    println!("Press Ctrl-C to stop.");
+
+    let result = loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(RusshEvent::Transfer { path, total, done: true, .. }) => {
+                    println!("Received {} ({})", path, format_bytes(total.unwrap_or(0)));
+                }
+                Some(_) => {}
+                None => break Ok(()),
+            },
+            result = tokio::signal::ctrl_c() => break result.map_err(Into::into),
+        }
+    };
+    server.abort();
+    result
+}
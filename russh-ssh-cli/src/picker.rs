@@ -0,0 +1,290 @@
+//! Profile picker for `russh connect` without a target
+//!
+//! Lists the saved profiles full-screen, most recently used first, and
+//! narrows the list as a query is typed. Matching is fuzzy: the query's
+//! characters must appear in order in a profile's name, `user@host` or
+//! tags, and runs of consecutive characters or ones starting a word rank
+//! higher.
+
+use chrono::{DateTime, Utc};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use russh_ssh::session::SessionProfile;
+use std::io::{IsTerminal, Write};
+
+/// Rows above the list: the query and the key help
+const HEADER_ROWS: u16 = 2;
+
+/// Widest the name column gets
+const MAX_NAME_WIDTH: usize = 24;
+
+/// Let the user pick a profile; `None` if they cancelled
+pub fn pick(mut profiles: Vec<SessionProfile>) -> anyhow::Result<Option<SessionProfile>> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        anyhow::bail!("No target given; name a host or profile to connect to");
+    }
+    if profiles.is_empty() {
+        anyhow::bail!("No profiles saved; give a target or add one with 'russh profile add'");
+    }
+    profiles.sort_by(|a, b| {
+        b.last_used
+            .cmp(&a.last_used)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    let mut stdout = std::io::stdout();
+    terminal::enable_raw_mode()?;
+    let result = execute!(stdout, EnterAlternateScreen, Hide)
+        .map_err(anyhow::Error::from)
+        .and_then(|()| Picker::new(&profiles).run(&mut stdout));
+    let _ = execute!(stdout, Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+    Ok(result?.map(|index| profiles.swap_remove(index)))
+}
+
+/// What a key press asks for
+enum Outcome {
+    Pick(usize),
+    Cancel,
+}
+
+struct Picker<'a> {
+    profiles: &'a [SessionProfile],
+    /// Lowercased text each profile is matched against
+    haystacks: Vec<Vec<char>>,
+    query: String,
+    /// Indices of the matching profiles, best first
+    matches: Vec<usize>,
+    /// Position in `matches` of the highlighted profile
+    selected: usize,
+    /// Position in `matches` of the first row shown
+    scroll: usize,
+    name_width: usize,
+}
+
+impl<'a> Picker<'a> {
+    fn new(profiles: &'a [SessionProfile]) -> Self {
+        let haystacks = profiles
+            .iter()
+            .map(|p| {
+                format!("{} {}@{} {}", p.name, p.username, p.host, p.tags.join(" "))
+                    .to_lowercase()
+                    .chars()
+                    .collect()
+            })
+            .collect();
+        let name_width = profiles
+            .iter()
+            .map(|p| p.name.chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(MAX_NAME_WIDTH);
+        let mut picker = Self {
+            profiles,
+            haystacks,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            scroll: 0,
+            name_width,
+        };
+        picker.filter();
+        picker
+    }
+
+    fn run(&mut self, out: &mut impl Write) -> anyhow::Result<Option<usize>> {
+        loop {
+            self.draw(out)?;
+            // Resizes just redraw
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            match self.handle(key) {
+                Some(Outcome::Pick(index)) => return Ok(Some(index)),
+                Some(Outcome::Cancel) => return Ok(None),
+                None => {}
+            }
+        }
+    }
+
+    fn handle(&mut self, key: KeyEvent) -> Option<Outcome> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let page = list_rows().max(1);
+        match key.code {
+            KeyCode::Esc => return Some(Outcome::Cancel),
+            KeyCode::Char('c') | KeyCode::Char('d') if ctrl => return Some(Outcome::Cancel),
+            KeyCode::Enter => {
+                return self.matches.get(self.selected).map(|&i| Outcome::Pick(i));
+            }
+            KeyCode::Up => self.move_by(-1),
+            KeyCode::Char('p') | KeyCode::Char('k') if ctrl => self.move_by(-1),
+            KeyCode::Down | KeyCode::Tab => self.move_by(1),
+            KeyCode::Char('n') | KeyCode::Char('j') if ctrl => self.move_by(1),
+            KeyCode::PageUp => self.move_by(-(page as isize)),
+            KeyCode::PageDown => self.move_by(page as isize),
+            KeyCode::Char('u') if ctrl => {
+                self.query.clear();
+                self.filter();
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.filter();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.filter();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let last = self.matches.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    /// Rank the profiles against the query
+    fn filter(&mut self) {
+        let query: Vec<char> = self
+            .query
+            .to_lowercase()
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        let mut scored: Vec<(i64, usize)> = self
+            .haystacks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, haystack)| score(&query, haystack).map(|s| (s, i)))
+            .collect();
+        // Stable, so equal scores keep the most recently used first
+        scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+        self.matches = scored.into_iter().map(|(_, i)| i).collect();
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> anyhow::Result<()> {
+        let (width, _) = terminal::size().unwrap_or((80, 24));
+        let width = usize::from(width);
+        let rows = list_rows();
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + rows {
+            self.scroll = self.selected + 1 - rows;
+        }
+
+        queue!(
+            out,
+            Clear(ClearType::All),
+            MoveTo(0, 0),
+            Print(truncate(&format!("Connect to: {}", self.query), width)),
+            MoveTo(0, 1),
+            SetAttribute(Attribute::Dim),
+            Print(truncate(
+                &format!(
+                    "{}/{} profiles  Up/Down select  Enter connect  Esc cancel",
+                    self.matches.len(),
+                    self.profiles.len()
+                ),
+                width
+            )),
+            SetAttribute(Attribute::Reset),
+        )?;
+
+        let now = Utc::now();
+        let shown = self.matches.iter().enumerate().skip(self.scroll).take(rows);
+        for (row, (position, &index)) in (HEADER_ROWS..).zip(shown) {
+            let line = self.line(&self.profiles[index], now);
+            queue!(out, MoveTo(0, row))?;
+            if position == self.selected {
+                queue!(
+                    out,
+                    SetAttribute(Attribute::Reverse),
+                    Print(truncate(&format!("> {}", line), width)),
+                    SetAttribute(Attribute::Reset),
+                )?;
+            } else {
+                queue!(out, Print(truncate(&format!("  {}", line), width)))?;
+            }
+        }
+        if self.matches.is_empty() {
+            queue!(out, MoveTo(0, HEADER_ROWS), Print("  No matching profiles"))?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// One profile's row: name, address, tags and when it was last used
+    fn line(&self, profile: &SessionProfile, now: DateTime<Utc>) -> String {
+        let mut address = format!("{}@{}", profile.username, profile.host);
+        if profile.port != 22 {
+            address.push_str(&format!(":{}", profile.port));
+        }
+        let tags = if profile.tags.is_empty() {
+            String::new()
+        } else {
+            format!("[{}]", profile.tags.join(", "))
+        };
+        let used = match profile.last_used {
+            Some(at) => ago(now, at),
+            None => "never used".to_string(),
+        };
+        format!(
+            "{:name$}  {:30}  {:12}  {}",
+            truncate(&profile.name, self.name_width),
+            address,
+            used,
+            tags,
+            name = self.name_width
+        )
+    }
+}
+
+/// Rows available for the list
+fn list_rows() -> usize {
+    let (_, height) = terminal::size().unwrap_or((80, 24));
+    usize::from(height.saturating_sub(HEADER_ROWS)).max(1)
+}
+
+/// Score a lowercased query against a haystack; `None` unless every query
+/// character appears in order
+fn score(query: &[char], haystack: &[char]) -> Option<i64> {
+    let mut score = 0;
+    let mut from = 0;
+    let mut previous: Option<usize> = None;
+    for &c in query {
+        let found = from + haystack[from..].iter().position(|&h| h == c)?;
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 4;
+        }
+        if found == 0 || !haystack[found - 1].is_alphanumeric() {
+            score += 2;
+        }
+        previous = Some(found);
+        from = found + 1;
+    }
+    Some(score)
+}
+
+fn ago(now: DateTime<Utc>, then: DateTime<Utc>) -> String {
+    match (now - then).num_seconds().max(0) {
+        secs if secs < 60 => "just now".to_string(),
+        secs if secs < 3600 => format!("{} min ago", secs / 60),
+        secs if secs < 86400 => format!("{} h ago", secs / 3600),
+        secs => format!("{} days ago", secs / 86400),
+    }
+}
+
+/// Cut text to at most `width` characters
+fn truncate(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
@@ -0,0 +1,167 @@
+//! `russh connect --roam`
+//!
+//! An interactive shell that survives a laptop's travels, in the spirit of
+//! mosh. The SSH session runs through a peer's `russh p2p serve` over QUIC,
+//! which keeps the connection when this machine's address changes; the
+//! network is watched so the connection moves to the new path as soon as
+//! it changes. If the session is lost anyway, e.g. after a long suspend, it
+//! is re-established and the shell reopened, reattaching a tmux session
+//! when `--tmux` names one. On slow links typing is echoed locally ahead of
+//! the server.
+//!
+//! `~.` typed at the start of a line ends a session that has hung.
+
+use russh_ssh::connection::{NetworkChange, NetworkChangeDetector};
+use russh_ssh::p2p::P2PEndpoint;
+use russh_ssh::ssh::{AutoReconnectConfig, EchoPredictor, Shell, SshClient};
+use std::io::Write;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::sync::broadcast;
+
+/// How long the session gets to answer once the shell has closed
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often predictions are checked for a missing echo
+const EXPIRE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Run a roaming shell on a connected client until it exits
+pub async fn run(
+    client: &mut SshClient,
+    endpoint: Arc<P2PEndpoint>,
+    tmux: Option<String>,
+) -> anyhow::Result<()> {
+    let mut policy = AutoReconnectConfig::new();
+    if let Some(session) = tmux {
+        policy = policy.with_tmux_session(session);
+    }
+    let command = policy.shell_command();
+    client.set_auto_reconnect(Some(policy));
+
+    let term = std::env::var("TERM").unwrap_or_else(|_| "xterm-256color".to_string());
+    let (cols, rows) = crossterm::terminal::size().unwrap_or((80, 24));
+    let shell = client
+        .open_shell_with_command(&command, &term, cols.into(), rows.into())
+        .await?;
+
+    let detector = Arc::new(NetworkChangeDetector::new());
+    let changes = detector.subscribe();
+    let watcher = detector.clone().spawn();
+
+    println!("Roaming session; type ~. at the start of a line to end it");
+    let raw = RawMode::enable()?;
+    let result = session(client, shell, &endpoint, changes).await;
+    drop(raw);
+    watcher.abort();
+    result
+}
+
+async fn session(
+    client: &mut SshClient,
+    mut shell: Shell,
+    endpoint: &P2PEndpoint,
+    mut changes: broadcast::Receiver<NetworkChange>,
+) -> anyhow::Result<()> {
+    let mut stdin = tokio::io::stdin();
+    let mut stdout = std::io::stdout();
+    let mut predictor = EchoPredictor::new();
+    let mut escape = Escape::default();
+    let mut expiry = tokio::time::interval(EXPIRE_INTERVAL);
+    let mut buf = [0u8; 4096];
+    loop {
+        tokio::select! {
+            read = stdin.read(&mut buf) => {
+                let data = &buf[..read?];
+                if data.is_empty() || escape.quits(data) {
+                    return Ok(());
+                }
+                draw(&mut stdout, &predictor.input(data))?;
+                // A dead session shows up on the read side
+                let _ = shell.write(data).await;
+            }
+            output = shell.read() => match output {
+                Some(data) => draw(&mut stdout, &predictor.output(&data))?,
+                None => {
+                    if still_connected(client).await {
+                        // The shell exited on its own
+                        return Ok(());
+                    }
+                    shell = reconnect(client, &mut stdout).await?;
+                    predictor.reset();
+                }
+            },
+            _ = changes.recv() => endpoint.network_change().await,
+            _ = expiry.tick() => draw(&mut stdout, &predictor.expire(Instant::now()))?,
+        }
+    }
+}
+
+/// Whether the session still answers after the shell closed
+async fn still_connected(client: &SshClient) -> bool {
+    tokio::time::timeout(PROBE_TIMEOUT, client.execute("true"))
+        .await
+        .is_ok_and(|result| result.is_ok())
+}
+
+async fn reconnect(client: &mut SshClient, stdout: &mut std::io::Stdout) -> anyhow::Result<Shell> {
+    draw(stdout, b"\r\n[russh] Connection lost; reconnecting...\r\n")?;
+    let restored = client.reconnect().await?;
+    let shell = restored
+        .shell
+        .ok_or_else(|| anyhow::anyhow!("The shell could not be reopened"))?;
+    draw(stdout, b"[russh] Reconnected.\r\n")?;
+    Ok(shell)
+}
+
+fn draw(stdout: &mut std::io::Stdout, data: &[u8]) -> std::io::Result<()> {
+    if data.is_empty() {
+        return Ok(());
+    }
+    stdout.write_all(data)?;
+    stdout.flush()
+}
+
+/// Spots `~.` typed at the start of a line, as ssh's escape character
+struct Escape {
+    line_start: bool,
+    tilde: bool,
+}
+
+impl Default for Escape {
+    fn default() -> Self {
+        Self {
+            line_start: true,
+            tilde: false,
+        }
+    }
+}
+
+impl Escape {
+    fn quits(&mut self, data: &[u8]) -> bool {
+        for &byte in data {
+            if self.tilde && byte == b'.' {
+                return true;
+            }
+            self.tilde = self.line_start && byte == b'~';
+            self.line_start = byte == b'\r' || byte == b'\n';
+        }
+        false
+    }
+}
+
+/// Keeps the terminal in raw mode until dropped
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> anyhow::Result<Self> {
+        crossterm::terminal::enable_raw_mode()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+}
@@ -0,0 +1,555 @@
+//! `russh sftp`
+//!
+//! An interactive file manager in the style of OpenSSH's `sftp`. Remote
+//! paths are resolved against a working directory kept by the shell, local
+//! paths against the process's own, which `lcd` changes. Transfers go
+//! through [`cp::transfer`], so they show progress and `-a` resumes them.
+
+use crate::cp::{self, CopyOptions, FileHost};
+use russh_ssh::ssh::SshClient;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::path::Path;
+
+const COMMANDS: &[&str] = &[
+    "bye", "cd", "chmod", "exit", "get", "help", "lcd", "lls", "lpwd", "ls", "mget", "mkdir",
+    "put", "pwd", "quit", "rename", "rm", "rmdir",
+];
+
+const HELP: &str = "\
+Available commands:
+  cd [PATH]                    Change remote directory
+  pwd                          Show remote directory
+  ls [PATH]                    List remote directory
+  lcd [PATH]                   Change local directory
+  lpwd                         Show local directory
+  lls [PATH]                   List local directory
+  get [-r] [-a] REMOTE [LOCAL] Download a file; -r for directories, -a to resume
+  mget [-r] [-a] PATTERN...    Download every match into the local directory
+  put [-r] [-a] LOCAL [REMOTE] Upload a file; -r for directories, -a to resume
+  rm PATH...                   Delete remote files
+  mkdir PATH                   Create a remote directory
+  rmdir PATH                   Remove an empty remote directory
+  chmod MODE PATH...           Change permissions, MODE in octal
+  rename OLD NEW               Rename or move a remote file
+  help                         Show this help
+  exit, quit, bye              Leave
+
+Remote paths in get, mget and rm may use * and ? wildcards.";
+
+/// Whether the shell keeps running after a command
+enum Flow {
+    Continue,
+    Exit,
+}
+
+/// Run the shell until the user leaves
+pub async fn run(client: &SshClient) -> anyhow::Result<()> {
+    let home = client
+        .execute("pwd")
+        .await?
+        .stdout_string()
+        .trim()
+        .to_string();
+    let mut shell = SftpShell {
+        client,
+        cwd: home.clone(),
+        home,
+    };
+
+    let mut editor = Editor::<SftpHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(SftpHelper {
+        client,
+        cwd: shell.cwd.clone(),
+    }));
+
+    loop {
+        // Completion asks the remote for listings, which blocks on the runtime
+        let line = match tokio::task::block_in_place(|| editor.readline("sftp> ")) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let args = match split_args(&line) {
+            Ok(args) if args.is_empty() => continue,
+            Ok(args) => args,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+        let _ = editor.add_history_entry(line.as_str());
+
+        match shell.execute(&args).await {
+            Ok(Flow::Continue) => {}
+            Ok(Flow::Exit) => break,
+            Err(e) => eprintln!("{:#}", e),
+        }
+        if let Some(helper) = editor.helper_mut() {
+            helper.cwd = shell.cwd.clone();
+        }
+    }
+    Ok(())
+}
+
+struct SftpShell<'a> {
+    client: &'a SshClient,
+    /// Remote working directory, always absolute
+    cwd: String,
+    home: String,
+}
+
+impl SftpShell<'_> {
+    async fn execute(&mut self, args: &[String]) -> anyhow::Result<Flow> {
+        let operands: Vec<&str> = args[1..].iter().map(String::as_str).collect();
+        match args[0].as_str() {
+            "cd" => {
+                let path = match operands.first() {
+                    Some(path) => resolve(&self.cwd, path),
+                    None => self.home.clone(),
+                };
+                if !self.client.stat_path(&path).await?.is_dir {
+                    anyhow::bail!("{}: not a directory", path);
+                }
+                self.cwd = path;
+            }
+            "pwd" => println!("Remote working directory: {}", self.cwd),
+            "ls" | "dir" => {
+                let path = resolve(&self.cwd, operands.first().copied().unwrap_or("."));
+                for entry in self.client.list_directory(&path).await? {
+                    if entry.name == "." || entry.name == ".." {
+                        continue;
+                    }
+                    println!(
+                        "{} {:<8} {:>10} {} {}{}",
+                        entry.permissions,
+                        entry.owner,
+                        entry.size,
+                        entry.modified,
+                        entry.name,
+                        if entry.is_dir { "/" } else { "" }
+                    );
+                }
+            }
+            "lcd" => {
+                let path = match operands.first() {
+                    Some(path) => shellexpand::tilde(path).to_string(),
+                    None => dirs::home_dir()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string(),
+                };
+                std::env::set_current_dir(&path).map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+            }
+            "lpwd" => println!(
+                "Local working directory: {}",
+                std::env::current_dir()?.display()
+            ),
+            "lls" => {
+                let path = operands.first().copied().unwrap_or(".");
+                let mut names: Vec<String> = std::fs::read_dir(path)?
+                    .filter_map(Result::ok)
+                    .map(|entry| {
+                        let mut name = entry.file_name().to_string_lossy().to_string();
+                        if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                            name.push('/');
+                        }
+                        name
+                    })
+                    .collect();
+                names.sort();
+                for name in names {
+                    println!("{}", name);
+                }
+            }
+            "get" => {
+                let (options, operands) = parse_flags(&operands)?;
+                let (remote, local) = match operands.as_slice() {
+                    [remote] => (*remote, "."),
+                    [remote, local] => (*remote, *local),
+                    _ => anyhow::bail!("usage: get [-r] [-a] REMOTE [LOCAL]"),
+                };
+                let sources = self.expand(remote).await?;
+                if sources.len() > 1 && !Path::new(local).is_dir() {
+                    anyhow::bail!(
+                        "{} matches several files; LOCAL must be a directory",
+                        remote
+                    );
+                }
+                for source in sources {
+                    self.download(&source, local, &options).await?;
+                }
+            }
+            "mget" => {
+                let (options, patterns) = parse_flags(&operands)?;
+                if patterns.is_empty() {
+                    anyhow::bail!("usage: mget [-r] [-a] PATTERN...");
+                }
+                for pattern in patterns {
+                    for source in self.expand(pattern).await? {
+                        self.download(&source, ".", &options).await?;
+                    }
+                }
+            }
+            "put" => {
+                let (options, operands) = parse_flags(&operands)?;
+                let (local, remote) = match operands.as_slice() {
+                    [local] => (*local, self.cwd.clone()),
+                    [local, remote] => (*local, resolve(&self.cwd, remote)),
+                    _ => anyhow::bail!("usage: put [-r] [-a] LOCAL [REMOTE]"),
+                };
+                println!("Uploading {} to {}", local, remote);
+                cp::transfer(
+                    &FileHost::Local,
+                    local,
+                    &FileHost::Remote(self.client),
+                    &remote,
+                    &options,
+                )
+                .await
+                .map_err(|e| e.error)?;
+            }
+            "rm" => {
+                if operands.is_empty() {
+                    anyhow::bail!("usage: rm PATH...");
+                }
+                for pattern in operands {
+                    for path in self.expand(pattern).await? {
+                        println!("Removing {}", path);
+                        self.client.delete_path(&path, false).await?;
+                    }
+                }
+            }
+            "mkdir" => match operands.as_slice() {
+                [path] => {
+                    self.client
+                        .create_directory(&resolve(&self.cwd, path))
+                        .await?
+                }
+                _ => anyhow::bail!("usage: mkdir PATH"),
+            },
+            "rmdir" => match operands.as_slice() {
+                [path] => {
+                    self.client
+                        .remove_directory(&resolve(&self.cwd, path))
+                        .await?
+                }
+                _ => anyhow::bail!("usage: rmdir PATH"),
+            },
+            "chmod" => {
+                let [mode, paths @ ..] = operands.as_slice() else {
+                    anyhow::bail!("usage: chmod MODE PATH...");
+                };
+                let mode = u32::from_str_radix(mode, 8)
+                    .map_err(|_| anyhow::anyhow!("{}: mode must be octal", mode))?;
+                if paths.is_empty() {
+                    anyhow::bail!("usage: chmod MODE PATH...");
+                }
+                for pattern in paths {
+                    for path in self.expand(pattern).await? {
+                        self.client.chmod(&path, mode).await?;
+                    }
+                }
+            }
+            "rename" => match operands.as_slice() {
+                [old, new] => {
+                    self.client
+                        .rename_path(&resolve(&self.cwd, old), &resolve(&self.cwd, new))
+                        .await?
+                }
+                _ => anyhow::bail!("usage: rename OLD NEW"),
+            },
+            "help" | "?" => println!("{}", HELP),
+            "exit" | "quit" | "bye" => return Ok(Flow::Exit),
+            other => anyhow::bail!("Invalid command: {} (try help)", other),
+        }
+        Ok(Flow::Continue)
+    }
+
+    async fn download(
+        &self,
+        remote: &str,
+        local: &str,
+        options: &CopyOptions,
+    ) -> anyhow::Result<()> {
+        println!("Fetching {} to {}", remote, local);
+        cp::transfer(
+            &FileHost::Remote(self.client),
+            remote,
+            &FileHost::Local,
+            local,
+            options,
+        )
+        .await
+        .map_err(|e| e.error)
+    }
+
+    /// Resolve a remote path, expanding wildcards in its last component
+    async fn expand(&self, pattern: &str) -> anyhow::Result<Vec<String>> {
+        let path = resolve(&self.cwd, pattern);
+        let (dir, name) = path.rsplit_once('/').unwrap_or(("", path.as_str()));
+        if !name.contains(['*', '?']) {
+            return Ok(vec![path]);
+        }
+        let dir = if dir.is_empty() { "/" } else { dir };
+
+        let mut matches: Vec<String> = self
+            .client
+            .list_directory(dir)
+            .await?
+            .into_iter()
+            .filter(|entry| entry.name != "." && entry.name != "..")
+            .filter(|entry| glob_match(name, &entry.name))
+            .map(|entry| entry.path)
+            .collect();
+        if matches.is_empty() {
+            anyhow::bail!("{}: no matches", pattern);
+        }
+        matches.sort();
+        Ok(matches)
+    }
+}
+
+/// Read `-r` and `-a` off the front of a transfer's operands
+fn parse_flags<'a>(operands: &[&'a str]) -> anyhow::Result<(CopyOptions, Vec<&'a str>)> {
+    let mut options = CopyOptions {
+        recursive: false,
+        resume: false,
+        quiet: false,
+        port: None,
+        password: false,
+        identity: None,
+    };
+    let mut rest = Vec::new();
+    for operand in operands {
+        match operand.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() && rest.is_empty() => {
+                for flag in flags.chars() {
+                    match flag {
+                        'r' | 'R' => options.recursive = true,
+                        'a' => options.resume = true,
+                        other => anyhow::bail!("Unknown option -{}", other),
+                    }
+                }
+            }
+            _ => rest.push(*operand),
+        }
+    }
+    Ok((options, rest))
+}
+
+/// Make a remote path absolute and drop `.` and `..` components
+fn resolve(cwd: &str, path: &str) -> String {
+    let joined = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/{}", cwd, path)
+    };
+    let mut parts = Vec::new();
+    for part in joined.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// Match a file name against a pattern with `*` and `?`
+///
+/// Like a shell, wildcards don't match a leading dot.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') {
+        return false;
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position after the last `*` and the name position it matched up to
+    let mut backtrack = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            p += 1;
+            backtrack = Some((p, n));
+        } else if let Some((star_p, star_n)) = backtrack {
+            // Let the `*` swallow one more character
+            p = star_p;
+            n = star_n + 1;
+            backtrack = Some((star_p, n));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Split a command line into words, honouring quotes and backslashes
+fn split_args(line: &str) -> anyhow::Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, '\\') => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    args.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        anyhow::bail!("Unterminated quote");
+    }
+    if in_word {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+/// Tab completion of commands and paths
+struct SftpHelper<'a> {
+    client: &'a SshClient,
+    cwd: String,
+}
+
+impl SftpHelper<'_> {
+    fn complete_remote(&self, dir: &str, prefix: &str) -> Vec<(String, bool)> {
+        let path = resolve(&self.cwd, if dir.is_empty() { "." } else { dir });
+        // Runs inside `block_in_place`, so blocking on the runtime is allowed
+        tokio::runtime::Handle::current()
+            .block_on(self.client.list_directory(&path))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|entry| entry.name != "." && entry.name != "..")
+            .filter(|entry| entry.name.starts_with(prefix))
+            .map(|entry| (entry.name, entry.is_dir))
+            .collect()
+    }
+}
+
+fn complete_local(dir: &str, prefix: &str) -> Vec<(String, bool)> {
+    let path = if dir.is_empty() {
+        ".".to_string()
+    } else {
+        shellexpand::tilde(dir).to_string()
+    };
+    std::fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| {
+                    (
+                        entry.file_name().to_string_lossy().to_string(),
+                        entry.file_type().is_ok_and(|kind| kind.is_dir()),
+                    )
+                })
+                .filter(|(name, _)| name.starts_with(prefix))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl Completer for SftpHelper<'_> {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &before[start..];
+        let mut words = before[..start].split_whitespace();
+
+        let Some(command) = words.next() else {
+            let commands = COMMANDS
+                .iter()
+                .filter(|command| command.starts_with(word))
+                .map(|command| Pair {
+                    display: command.to_string(),
+                    replacement: format!("{} ", command),
+                })
+                .collect();
+            return Ok((start, commands));
+        };
+
+        // Which operand is being completed decides the side for transfers
+        let operand = words.filter(|w| !w.starts_with('-')).count() + 1;
+        let local = match command {
+            "lcd" | "lls" => true,
+            "put" => operand == 1,
+            "get" => operand == 2,
+            _ => false,
+        };
+        let (dir, prefix) = match word.rfind('/') {
+            Some(i) => (&word[..=i], &word[i + 1..]),
+            None => ("", word),
+        };
+        let mut names = if local {
+            complete_local(dir, prefix)
+        } else {
+            self.complete_remote(dir, prefix)
+        };
+        if !prefix.starts_with('.') {
+            names.retain(|(name, _)| !name.starts_with('.'));
+        }
+        names.sort();
+
+        let candidates = names
+            .into_iter()
+            .map(|(name, is_dir)| Pair {
+                replacement: format!(
+                    "{}{}{}",
+                    dir,
+                    name.replace(' ', "\\ "),
+                    if is_dir { "/" } else { " " }
+                ),
+                display: if is_dir { format!("{}/", name) } else { name },
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for SftpHelper<'_> {
+    type Hint = String;
+}
+
+impl Highlighter for SftpHelper<'_> {}
+
+impl Validator for SftpHelper<'_> {}
+
+impl Helper for SftpHelper<'_> {}
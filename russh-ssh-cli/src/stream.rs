@@ -0,0 +1,423 @@
+//! `russh stream`
+//!
+//! Hosts or joins a watch-together room without the GUI client, e.g. on a
+//! media PC. The command stays in the foreground and prints what happens in
+//! the room; typing `play`, `pause`, `seek SECONDS` or `speed RATE` changes
+//! playback for every member. Local files are served to members over P2P,
+//! so only paired peers can join, as with `russh p2p serve`.
+//!
+//! A running room keeps its state in `<config dir>/stream_rooms/`, which
+//! `russh stream status` reads.
+
+use crate::p2p::Node;
+use crate::StreamAction;
+use russh_ssh::p2p::TunnelAgent;
+use russh_ssh::streaming::{
+    parse_share_link, JoinCredentials, MediaServer, RoomAccess, RoomInvite, RoomLink, RoomServer,
+    StreamRoom, StreamSession, StreamSource, SyncEvent, ROOM_INVITE_PREFIX,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncBufReadExt;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Directory in the config dir with the state of running rooms
+const STATUS_DIR_NAME: &str = "stream_rooms";
+
+/// How often a running room refreshes its state file
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// State files not refreshed for this long belong to a command that is gone
+const STATUS_STALE_AFTER: Duration = Duration::from_secs(15);
+
+/// What `russh stream status` shows of a running room
+#[derive(Serialize, Deserialize)]
+struct RoomStatus {
+    /// Whether this machine hosts the room
+    host: bool,
+    /// How to join the room
+    link: String,
+    /// Unix time the file was written, in seconds
+    updated: u64,
+    room: StreamRoom,
+}
+
+/// Run a stream command
+pub async fn run(action: StreamAction, config_dir: &Path) -> anyhow::Result<()> {
+    let (node, result) = match action {
+        StreamAction::Status => return status(config_dir),
+        StreamAction::Create {
+            source,
+            name,
+            password,
+            approve,
+            invite_only,
+            ttl,
+        } => {
+            let node = Node::open(config_dir).await?;
+            let access = RoomOptions {
+                password,
+                approve,
+                invite_only,
+                ttl: Duration::from_secs(ttl.saturating_mul(60)),
+            };
+            let result = create(&node, &source, name, access, config_dir).await;
+            (node, result)
+        }
+        StreamAction::Join { link, password } => {
+            let node = Node::open(config_dir).await?;
+            let result = join(&node, &link, password, config_dir).await;
+            (node, result)
+        }
+    };
+    node.manager.disconnect_all().await;
+    result
+}
+
+/// Who `russh stream create` lets in
+struct RoomOptions {
+    password: bool,
+    approve: bool,
+    invite_only: bool,
+    ttl: Duration,
+}
+
+async fn create(
+    node: &Node,
+    source: &str,
+    name: Option<String>,
+    options: RoomOptions,
+    config_dir: &Path,
+) -> anyhow::Result<()> {
+    node.endpoint.wait_online().await;
+    let host_id = node.endpoint.node_id().to_string();
+    let media = MediaServer::new();
+    let path = Path::new(source);
+    let source = if path.is_file() {
+        media.share(path, &host_id).await?
+    } else if source.contains("://") {
+        StreamSource::from_url(source)
+    } else {
+        anyhow::bail!("{} is neither a file nor a URL", source);
+    };
+    let name = name.unwrap_or_else(|| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "russh room".to_string())
+    });
+
+    let mut access = RoomAccess::open();
+    if options.password {
+        let password = rpassword::prompt_password("Room password: ")?;
+        access = access.with_password(&password);
+    }
+    if options.invite_only {
+        access = access.invite_only();
+    }
+    if options.approve {
+        access = access.with_approval();
+    }
+
+    let session = Arc::new(
+        StreamSession::create_room(name.clone(), source, host_id).with_p2p(node.manager.clone()),
+    );
+    let rooms = RoomServer::new();
+    rooms.host_with_access(session.clone(), access)?;
+    let server = media
+        .register(rooms.register(TunnelAgent::new(node.manager.clone())))
+        .serve();
+
+    println!("Hosting '{}'", name);
+    if options.invite_only {
+        let invite = rooms
+            .create_invite(&session.session_id, options.ttl, false)
+            .await?;
+        println!(
+            "Invite (valid {} min): {}",
+            options.ttl.as_secs() / 60,
+            invite
+        );
+    } else {
+        println!("Link: {}", session.share_link().await);
+    }
+    let result = follow(node, &session, &rooms, None, config_dir).await;
+    rooms.close(&session.session_id);
+    server.abort();
+    result
+}
+
+async fn join(node: &Node, link: &str, password: bool, config_dir: &Path) -> anyhow::Result<()> {
+    let link = link.trim();
+    let room = if link.starts_with(ROOM_INVITE_PREFIX) {
+        let invite: RoomInvite = link.parse()?;
+        node.connect(invite.host_id()).await?;
+        println!("Joining room...");
+        RoomLink::join_invite(node.manager.clone(), &invite).await?
+    } else {
+        let (room_id, host) = parse_share_link(link)
+            .ok_or_else(|| anyhow::anyhow!("Not a room link or invite: {}", link))?;
+        let ticket = node.connect(&host).await?;
+        let mut credentials = JoinCredentials::default();
+        if password {
+            credentials = credentials.with_password(rpassword::prompt_password("Room password: ")?);
+        }
+        println!("Joining room...");
+        RoomLink::join_with(
+            node.manager.clone(),
+            ticket.node_id(),
+            &room_id,
+            credentials,
+        )
+        .await?
+    };
+
+    // Take the room over if the host goes away and this node is next
+    let rooms = RoomServer::new();
+    let server = rooms
+        .register(TunnelAgent::new(node.manager.clone()))
+        .serve();
+    let room = room.with_server(rooms.clone());
+    let session = room.session().clone();
+    println!("Joined '{}'", session.room().await.name);
+
+    let result = follow(node, &session, &rooms, Some(&room), config_dir).await;
+    server.abort();
+    result
+}
+
+/// Print the room's events and apply typed commands until Ctrl-C
+async fn follow(
+    node: &Node,
+    session: &StreamSession,
+    rooms: &RoomServer,
+    link: Option<&RoomLink>,
+    config_dir: &Path,
+) -> anyhow::Result<()> {
+    let status_path = config_dir
+        .join(STATUS_DIR_NAME)
+        .join(format!("{}.json", session.session_id));
+    let mut events = session.subscribe();
+    let mut requests = rooms.subscribe_join_requests();
+    let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+    // Without a terminal, e.g. as a service, the room just runs
+    let mut stdin_open = true;
+    let mut refresh = tokio::time::interval(STATUS_INTERVAL);
+
+    println!("Commands: play, pause, seek SECONDS, speed RATE, approve PEER, deny PEER, quit");
+    println!("Press Ctrl-C to leave.");
+    let result = loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => print_event(node, &event),
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break Ok(()),
+            },
+            request = requests.recv() => {
+                if let Ok(request) = request {
+                    println!(
+                        "{} asks to join; type 'approve {}' or 'deny {}'",
+                        peer_name(node, &request.peer_id),
+                        request.peer_id,
+                        request.peer_id
+                    );
+                }
+            }
+            line = lines.next_line(), if stdin_open => match line? {
+                Some(line) => match command(session, rooms, line.trim()).await {
+                    Ok(true) => {}
+                    Ok(false) => break Ok(()),
+                    Err(e) => eprintln!("{}", e),
+                },
+                None => stdin_open = false,
+            },
+            _ = refresh.tick() => {
+                if link.is_some_and(|link| !link.is_connected()) && !session.is_host() {
+                    break Err(anyhow::anyhow!("Lost the connection to the room"));
+                }
+                if let Err(e) = write_status(&status_path, session).await {
+                    tracing::warn!(path = %status_path.display(), "Could not save room status: {}", e);
+                }
+            }
+            result = tokio::signal::ctrl_c() => break result.map_err(Into::into),
+        }
+    };
+    let _ = std::fs::remove_file(&status_path);
+    result
+}
+
+/// Apply a typed command; `false` to leave the room
+async fn command(session: &StreamSession, rooms: &RoomServer, line: &str) -> anyhow::Result<bool> {
+    let mut words = line.split_whitespace();
+    let Some(verb) = words.next() else {
+        return Ok(true);
+    };
+    let arg = words.next();
+    let number = |what: &str| -> anyhow::Result<f64> {
+        arg.and_then(|arg| arg.parse().ok())
+            .ok_or_else(|| anyhow::anyhow!("Usage: {} {}", verb, what))
+    };
+    match verb {
+        "play" => session.play().await?,
+        "pause" => session.pause().await?,
+        "seek" => session.seek(number("SECONDS")?).await?,
+        "speed" => session.set_speed(number("RATE")?).await?,
+        "approve" | "deny" => {
+            let peer = arg.ok_or_else(|| anyhow::anyhow!("Usage: {} PEER", verb))?;
+            let decided = if verb == "approve" {
+                rooms.approve(&session.session_id, peer)
+            } else {
+                rooms.deny(&session.session_id, peer)
+            };
+            if !decided {
+                anyhow::bail!("No join request from {}", peer);
+            }
+        }
+        "quit" | "exit" => return Ok(false),
+        _ => anyhow::bail!("Unknown command '{}'", verb),
+    }
+    Ok(true)
+}
+
+fn print_event(node: &Node, event: &SyncEvent) {
+    match event {
+        SyncEvent::Play { position } => println!("Playing from {}", format_position(*position)),
+        SyncEvent::Pause { position } => println!("Paused at {}", format_position(*position)),
+        SyncEvent::Seek { position } => println!("Seeked to {}", format_position(*position)),
+        SyncEvent::Speed { speed } => println!("Speed {}x", speed),
+        SyncEvent::PeerJoined { peer_id } => println!("{} joined", peer_name(node, peer_id)),
+        SyncEvent::PeerLeft { peer_id } => println!("{} left", peer_name(node, peer_id)),
+        SyncEvent::SourceChanged { source } => println!("Now playing {}", describe(source)),
+        SyncEvent::TrackChanged { index, .. } => println!("Track {}", index + 1),
+        SyncEvent::HostChanged { host_id } => {
+            println!("{} now hosts the room", peer_name(node, host_id))
+        }
+        SyncEvent::SourceUnavailable { reason } => println!("Paused: {}", reason),
+        SyncEvent::RequestSync | SyncEvent::StateSync { .. } => {}
+    }
+}
+
+/// Show the rooms hosted or joined on this machine
+fn status(config_dir: &Path) -> anyhow::Result<()> {
+    let mut rooms = Vec::new();
+    let dir = config_dir.join(STATUS_DIR_NAME);
+    if let Ok(entries) = std::fs::read_dir(&dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let status = std::fs::read_to_string(&path)
+                .ok()
+                .and_then(|json| serde_json::from_str::<RoomStatus>(&json).ok());
+            match status {
+                Some(status)
+                    if unix_now().saturating_sub(status.updated)
+                        <= STATUS_STALE_AFTER.as_secs() =>
+                {
+                    rooms.push(status)
+                }
+                // Left behind by a command that did not exit cleanly
+                _ => {
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+        }
+    }
+    if rooms.is_empty() {
+        println!("No stream rooms running.");
+        println!("Use 'russh stream create' or 'russh stream join' to start one.");
+        return Ok(());
+    }
+
+    let now = (unix_now() * 1000) as i64;
+    for status in rooms {
+        let room = &status.room;
+        let state = if room.playback.playing {
+            "playing"
+        } else {
+            "paused"
+        };
+        println!(
+            "{} ({})  {} at {}, {}x",
+            room.name,
+            if status.host { "hosting" } else { "joined" },
+            state,
+            format_position(room.playback.position_at(now)),
+            room.playback.speed
+        );
+        println!("  Source:  {}", describe(&room.source));
+        println!("  Members: {}", room.peers.len());
+        println!("  Link:    {}", status.link);
+    }
+    Ok(())
+}
+
+async fn write_status(path: &Path, session: &StreamSession) -> anyhow::Result<()> {
+    let status = RoomStatus {
+        host: session.is_host(),
+        link: session.share_link().await,
+        updated: unix_now(),
+        room: session.room().await,
+    };
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec_pretty(&status)?).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+/// What a source plays, in a few words
+fn describe(source: &StreamSource) -> String {
+    match source {
+        StreamSource::Url { url } => url.clone(),
+        StreamSource::LocalFile { path, .. } => path.clone(),
+        // File IDs end with the file name
+        StreamSource::P2PFile { file_id, .. } => file_id
+            .rsplit_once('/')
+            .map_or(file_id.as_str(), |(_, name)| name)
+            .to_string(),
+        StreamSource::Audio { tracks, current } => match tracks.get(*current) {
+            Some(track) => format!(
+                "track {}/{}: {}",
+                current + 1,
+                tracks.len(),
+                track.metadata.title
+            ),
+            None => format!("{} track(s)", tracks.len()),
+        },
+        StreamSource::Renditions { renditions } => {
+            let labels: Vec<&str> = renditions.iter().map(|r| r.label.as_str()).collect();
+            format!("renditions {}", labels.join(", "))
+        }
+        StreamSource::Hls { playlist_url } => playlist_url.clone(),
+        StreamSource::Dash { manifest_url } => manifest_url.clone(),
+        StreamSource::Terminal { term, cols, rows } => {
+            format!("terminal ({}, {}x{})", term, cols, rows)
+        }
+    }
+}
+
+/// Position as `h:mm:ss` or `m:ss`
+fn format_position(position: f64) -> String {
+    let secs = position.max(0.0) as u64;
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
+fn peer_name(node: &Node, peer_id: &str) -> String {
+    match peer_id.parse() {
+        Ok(node_id) => node.registry.display_name(&node_id),
+        Err(_) => peer_id.to_string(),
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
@@ -0,0 +1,470 @@
+//! `russh sync`
+//!
+//! Keeps directories in step with paired devices, Syncthing-style, through
+//! the VDFS. `russh sync init` adds a folder, `russh sync pair` shares it
+//! with a device paired through `russh p2p pair`, and `russh sync run`
+//! pulls changes from every peer of every folder. With `--watch` it keeps
+//! running: local edits are picked up as they happen and peers are pulled
+//! from at an interval. A device only gets changes from peers pulling from
+//! it while its own `russh sync run` is running.
+//!
+//! Each device adds the folder under the same ID and pairs it with the
+//! other. Folders are listed in `<config dir>/sync/folders.json`; what each
+//! last synced is kept next to it, so later runs only exchange changes.
+
+use crate::cp::format_bytes;
+use crate::p2p::Node;
+use crate::SyncAction;
+use russh_ssh::p2p::{PeerRegistry, TunnelAgent, PEER_REGISTRY_FILE_NAME};
+use russh_ssh::vdfs::{
+    is_valid_folder_id, FileMetadata, FolderSync, PullReport, SyncedFolder, WatchChange,
+    WatchConfig,
+};
+use russh_ssh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+/// Directory in the config dir with the folder list and sync state
+const SYNC_DIR_NAME: &str = "sync";
+
+/// File in the sync dir listing the folders
+const FOLDERS_FILE_NAME: &str = "folders.json";
+
+/// A folder added with `russh sync init`
+#[derive(Serialize, Deserialize)]
+struct FolderConfig {
+    id: String,
+    path: PathBuf,
+    /// NodeIds of the peers the folder is shared with
+    #[serde(default)]
+    peers: Vec<String>,
+}
+
+/// What a folder last synced, kept between runs
+#[derive(Default, Serialize, Deserialize)]
+struct FolderState {
+    entries: Vec<FileMetadata>,
+    tombstones: Vec<FileMetadata>,
+    /// Each peer's operation clock at the last pull
+    #[serde(default)]
+    since: HashMap<String, u64>,
+    /// Unix time of the last pull from each peer, in seconds
+    #[serde(default)]
+    synced_at: HashMap<String, u64>,
+    /// Paths with an unresolved conflict
+    #[serde(default)]
+    conflicts: Vec<PathBuf>,
+    /// Unix time the state was saved, in seconds
+    #[serde(default)]
+    updated: u64,
+}
+
+/// A folder being synced by `russh sync run`
+struct Running {
+    folder: Arc<SyncedFolder>,
+    peers: Vec<NodeId>,
+    state: FolderState,
+}
+
+/// Run a sync command
+pub async fn run(action: SyncAction, config_dir: &Path) -> anyhow::Result<()> {
+    let sync_dir = config_dir.join(SYNC_DIR_NAME);
+    match action {
+        SyncAction::Init { dir, id } => init(&sync_dir, &dir, id),
+        SyncAction::Pair { peer, folder } => pair(config_dir, &sync_dir, &peer, folder),
+        SyncAction::Status => status(config_dir, &sync_dir),
+        SyncAction::Run { watch, interval } => {
+            let node = Node::open(config_dir).await?;
+            let interval = Duration::from_secs(interval.max(1));
+            let result = sync(&node, &sync_dir, watch, interval).await;
+            node.manager.disconnect_all().await;
+            result
+        }
+    }
+}
+
+fn init(sync_dir: &Path, dir: &Path, id: Option<String>) -> anyhow::Result<()> {
+    if !dir.is_dir() {
+        anyhow::bail!("{} is not a directory", dir.display());
+    }
+    let path = std::fs::canonicalize(dir)?;
+    let id = id.unwrap_or_else(|| default_id(&path));
+    if !is_valid_folder_id(&id) {
+        anyhow::bail!(
+            "Invalid folder ID '{}': use letters, digits, '-' and '_'",
+            id
+        );
+    }
+
+    let mut folders = load_folders(sync_dir)?;
+    if let Some(existing) = folders.iter().find(|f| f.id == id || f.path == path) {
+        anyhow::bail!(
+            "{} is already synced as '{}'",
+            existing.path.display(),
+            existing.id
+        );
+    }
+    folders.push(FolderConfig {
+        id: id.clone(),
+        path: path.clone(),
+        peers: Vec::new(),
+    });
+    save_json(&sync_dir.join(FOLDERS_FILE_NAME), &folders)?;
+
+    println!("Added folder '{}' at {}", id, path.display());
+    println!(
+        "Add it on the other device with 'russh sync init <DIR> --id {}', \
+         then run 'russh sync pair <DEVICE>' on both.",
+        id
+    );
+    Ok(())
+}
+
+/// Folder ID from a directory name
+fn default_id(path: &Path) -> String {
+    let id: String = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    if id.is_empty() {
+        "folder".to_string()
+    } else {
+        id
+    }
+}
+
+fn pair(
+    config_dir: &Path,
+    sync_dir: &Path,
+    peer: &str,
+    folder: Option<String>,
+) -> anyhow::Result<()> {
+    let registry = PeerRegistry::open(&config_dir.join(PEER_REGISTRY_FILE_NAME))?;
+    let node_id = registry.resolve(peer)?.node_id();
+    if registry.get(&node_id).is_none() {
+        anyhow::bail!(
+            "{} is not a known device; pair with it first using 'russh p2p pair'",
+            peer
+        );
+    }
+
+    let mut folders = load_folders(sync_dir)?;
+    let index = match folder {
+        Some(id) => folders
+            .iter()
+            .position(|f| f.id == id)
+            .ok_or_else(|| anyhow::anyhow!("No folder named '{}'", id))?,
+        None if folders.len() == 1 => 0,
+        None if folders.is_empty() => {
+            anyhow::bail!("No folders yet; add one with 'russh sync init <DIR>'")
+        }
+        None => {
+            let ids: Vec<&str> = folders.iter().map(|f| f.id.as_str()).collect();
+            anyhow::bail!("Choose a folder with --folder: {}", ids.join(", "));
+        }
+    };
+
+    let name = registry.display_name(&node_id);
+    let folder = &mut folders[index];
+    let node_id = node_id.to_string();
+    if folder.peers.contains(&node_id) {
+        println!("'{}' is already shared with {}", folder.id, name);
+        return Ok(());
+    }
+    folder.peers.push(node_id);
+    println!("Sharing '{}' with {}", folder.id, name);
+    save_json(&sync_dir.join(FOLDERS_FILE_NAME), &folders)?;
+    Ok(())
+}
+
+fn status(config_dir: &Path, sync_dir: &Path) -> anyhow::Result<()> {
+    let folders = load_folders(sync_dir)?;
+    if folders.is_empty() {
+        println!("No synced folders.");
+        println!("Use 'russh sync init <DIR>' to add one.");
+        return Ok(());
+    }
+    let registry = PeerRegistry::open(&config_dir.join(PEER_REGISTRY_FILE_NAME))?;
+    let now = unix_now();
+
+    for (i, folder) in folders.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        let state = load_state(sync_dir, &folder.id)?;
+        println!("{}  {}", folder.id, folder.path.display());
+        let files: Vec<&FileMetadata> = state.entries.iter().filter(|e| e.is_file()).collect();
+        if state.updated == 0 {
+            println!("  Not synced yet");
+        } else {
+            println!(
+                "  {} files, {}; saved {}",
+                files.len(),
+                format_bytes(files.iter().map(|f| f.size).sum()),
+                ago(now, state.updated)
+            );
+        }
+        if folder.peers.is_empty() {
+            println!("  Not shared; use 'russh sync pair <DEVICE>'");
+        }
+        for peer in &folder.peers {
+            let name = match peer.parse::<NodeId>() {
+                Ok(node_id) => registry.display_name(&node_id),
+                Err(_) => peer.clone(),
+            };
+            match state.synced_at.get(peer) {
+                Some(&at) => println!("  {}: pulled {}", name, ago(now, at)),
+                None => println!("  {}: never pulled", name),
+            }
+        }
+        for path in &state.conflicts {
+            println!("  Conflict: {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+async fn sync(node: &Node, sync_dir: &Path, watch: bool, interval: Duration) -> anyhow::Result<()> {
+    let configs = load_folders(sync_dir)?;
+    if configs.is_empty() {
+        anyhow::bail!("No folders yet; add one with 'russh sync init <DIR>'");
+    }
+
+    let node_id = node.endpoint.node_id().to_string();
+    let server = FolderSync::new();
+    let mut folders = Vec::with_capacity(configs.len());
+    for config in configs {
+        let folder = Arc::new(SyncedFolder::new(&config.id, &config.path, &node_id)?);
+        let state = load_state(sync_dir, &config.id)?;
+        folder
+            .restore(state.entries.clone(), state.tombstones.clone())
+            .await?;
+        let peers: Vec<NodeId> = config.peers.iter().filter_map(|p| p.parse().ok()).collect();
+        server.share(folder.clone(), peers.iter().copied()).await;
+        folders.push(Running {
+            folder,
+            peers,
+            state,
+        });
+    }
+    let agent = server
+        .register(TunnelAgent::new(node.manager.clone()))
+        .serve();
+
+    // Watch before scanning, so nothing changed in between is missed
+    let (changes_tx, mut changes) = mpsc::unbounded_channel();
+    let mut watchers = Vec::new();
+    if watch {
+        let config = WatchConfig {
+            initial_scan: false,
+            ..WatchConfig::default()
+        };
+        for (index, running) in folders.iter().enumerate() {
+            let handle = running
+                .folder
+                .watcher()
+                .with_config(config.clone())
+                .start()
+                .await?;
+            let mut batches = handle.subscribe();
+            let changes_tx = changes_tx.clone();
+            tokio::spawn(async move {
+                while let Ok(batch) = batches.recv().await {
+                    if changes_tx.send((index, batch)).is_err() {
+                        break;
+                    }
+                }
+            });
+            watchers.push(handle);
+        }
+    }
+    drop(changes_tx);
+    for running in &mut folders {
+        let changes = running.folder.watcher().scan().await?;
+        print_local(running.folder.id(), &changes);
+        save_state(sync_dir, running).await?;
+    }
+
+    node.endpoint.wait_online().await;
+    if !watch {
+        pull_all(node, sync_dir, &mut folders).await;
+        agent.abort();
+        return Ok(());
+    }
+
+    println!(
+        "Syncing {} folder(s); pulling every {}s. Press Ctrl-C to stop.",
+        folders.len(),
+        interval.as_secs()
+    );
+    let mut ticker = tokio::time::interval(interval);
+    let result = loop {
+        tokio::select! {
+            _ = ticker.tick() => pull_all(node, sync_dir, &mut folders).await,
+            Some((index, batch)) = changes.recv() => {
+                let running = &mut folders[index];
+                print_local(running.folder.id(), &batch);
+                if let Err(e) = save_state(sync_dir, running).await {
+                    eprintln!("{}: could not save sync state: {}", running.folder.id(), e);
+                }
+            }
+            result = tokio::signal::ctrl_c() => break result.map_err(Into::into),
+        }
+    };
+    drop(watchers);
+    agent.abort();
+    for running in &mut folders {
+        save_state(sync_dir, running).await?;
+    }
+    result
+}
+
+/// Pull every folder from each of its peers
+async fn pull_all(node: &Node, sync_dir: &Path, folders: &mut [Running]) {
+    for running in folders.iter_mut() {
+        for peer in running.peers.clone() {
+            let key = peer.to_string();
+            let name = node.registry.display_name(&peer);
+            let since = running.state.since.get(&key).copied().unwrap_or(0);
+            match pull(node, &running.folder, peer, since).await {
+                Ok(report) => {
+                    print_pull(running.folder.id(), &name, &report);
+                    running.state.since.insert(key.clone(), report.clock);
+                    running.state.synced_at.insert(key, unix_now());
+                }
+                Err(e) => eprintln!(
+                    "{}: could not pull from {}: {}",
+                    running.folder.id(),
+                    name,
+                    e
+                ),
+            }
+        }
+        if let Err(e) = save_state(sync_dir, running).await {
+            eprintln!("{}: could not save sync state: {}", running.folder.id(), e);
+        }
+    }
+}
+
+async fn pull(
+    node: &Node,
+    folder: &SyncedFolder,
+    peer: NodeId,
+    since: u64,
+) -> anyhow::Result<PullReport> {
+    node.connect(&peer.to_string()).await?;
+    Ok(folder.pull(&node.manager, peer, since).await?)
+}
+
+fn print_local(id: &str, changes: &[WatchChange]) {
+    for change in changes {
+        let (mark, path) = match change {
+            WatchChange::Written(path) | WatchChange::DirectoryCreated(path) => ('+', path),
+            WatchChange::Removed(path) => ('-', path),
+        };
+        println!("{}: {} {} (local)", id, mark, display_path(id, path));
+    }
+}
+
+fn print_pull(id: &str, peer: &str, report: &PullReport) {
+    for path in &report.updated {
+        println!("{}: + {} (from {})", id, path.display(), peer);
+    }
+    for path in &report.removed {
+        println!("{}: - {} (from {})", id, path.display(), peer);
+    }
+    for path in &report.conflicts {
+        println!("{}: ! {} conflicts with {}", id, path.display(), peer);
+    }
+    if report.bytes > 0 {
+        println!(
+            "{}: received {} from {}",
+            id,
+            format_bytes(report.bytes),
+            peer
+        );
+    }
+}
+
+/// A virtual path relative to its folder
+fn display_path(id: &str, path: &Path) -> String {
+    let root = Path::new("/").join(id);
+    path.strip_prefix(&root)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+fn load_folders(sync_dir: &Path) -> anyhow::Result<Vec<FolderConfig>> {
+    let path = sync_dir.join(FOLDERS_FILE_NAME);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_slice(&std::fs::read(&path)?)?)
+}
+
+fn load_state(sync_dir: &Path, id: &str) -> anyhow::Result<FolderState> {
+    let path = sync_dir.join(format!("{}.json", id));
+    if !path.exists() {
+        return Ok(FolderState::default());
+    }
+    Ok(serde_json::from_slice(&std::fs::read(&path)?)?)
+}
+
+async fn save_state(sync_dir: &Path, running: &mut Running) -> anyhow::Result<()> {
+    let folder = &running.folder;
+    let state = &mut running.state;
+    state.entries = folder.entries().await;
+    state.tombstones = folder.tombstones(0).await;
+    let root = folder.fs().mount_point();
+    state.conflicts = folder
+        .fs()
+        .conflicts()
+        .await
+        .into_iter()
+        .map(|c| c.path.strip_prefix(root).unwrap_or(&c.path).to_path_buf())
+        .collect();
+    state.conflicts.sort();
+    state.updated = unix_now();
+    save_json(&sync_dir.join(format!("{}.json", folder.id())), state)
+}
+
+/// Write JSON next to the target and move it into place
+fn save_json(path: &Path, value: &impl Serialize) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let partial = path.with_extension("json.tmp");
+    std::fs::write(&partial, serde_json::to_vec_pretty(value)?)?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+fn ago(now: u64, then: u64) -> String {
+    match now.saturating_sub(then) {
+        secs if secs < 60 => "just now".to_string(),
+        secs if secs < 3600 => format!("{} min ago", secs / 60),
+        secs if secs < 86400 => format!("{} h ago", secs / 3600),
+        secs => format!("{} days ago", secs / 86400),
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,277 @@
+//! `russh tunnel`
+//!
+//! Holds port forwards open without a shell, like `ssh -N`, and keeps them
+//! up: the session is probed periodically, and when it drops it is
+//! reconnected with backoff and every forward is bound again. Forwards come
+//! from `-L` and `-D` and from the profile being connected to; `-R` is
+//! rejected, as remote forwarding is not supported.
+
+use russh_ssh::connection::{ExponentialBackoff, HealthConfig, Unlimited, WithVeto};
+use russh_ssh::ssh::{AutoReconnectConfig, PortForward, PortForwarder, SshClient, SshConfig};
+use std::time::Duration;
+
+/// Time between liveness probes
+const PROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Longest wait between reconnection attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Collect the forwards given on the command line, then the profile's
+pub fn forwards(
+    local: &[String],
+    remote: &[String],
+    dynamic: &[String],
+    profile: Vec<PortForward>,
+) -> anyhow::Result<Vec<PortForward>> {
+    let mut forwards = Vec::new();
+    for spec in local {
+        forwards.push(parse_forward('L', spec)?);
+    }
+    for spec in remote {
+        forwards.push(parse_forward('R', spec)?);
+    }
+    for spec in dynamic {
+        forwards.push(parse_forward('D', spec)?);
+    }
+    for forward in profile {
+        if !forwards.contains(&forward) {
+            forwards.push(forward);
+        }
+    }
+    Ok(forwards)
+}
+
+/// Parse the argument of `-L`, `-R` or `-D`
+///
+/// `-L PORT:HOST:HOSTPORT` listens locally and `-D PORT` runs a local
+/// SOCKS5 proxy. `-R` fails: remote forwarding is not supported.
+pub fn parse_forward(flag: char, spec: &str) -> anyhow::Result<PortForward> {
+    if flag == 'R' {
+        anyhow::bail!(
+            "Remote forwarding ('-R {}') is not supported; forward with -L from the other side instead",
+            spec
+        );
+    }
+    let invalid = |expected: &str| {
+        anyhow::anyhow!(
+            "Invalid forward '-{} {}': expected {}",
+            flag,
+            spec,
+            expected
+        )
+    };
+    let port = |part: &str, expected: &str| -> anyhow::Result<u16> {
+        match part.parse() {
+            Ok(0) | Err(_) => Err(invalid(expected)),
+            Ok(port) => Ok(port),
+        }
+    };
+
+    if flag == 'D' {
+        return Ok(PortForward::Dynamic {
+            local_port: port(spec, "PORT")?,
+        });
+    }
+    let expected = "PORT:HOST:HOSTPORT";
+    let [listen, host, host_port] = spec.split(':').collect::<Vec<_>>()[..] else {
+        return Err(invalid(expected));
+    };
+    if host.is_empty() {
+        return Err(invalid(expected));
+    }
+    let (listen, host, host_port) = (
+        port(listen, expected)?,
+        host.to_string(),
+        port(host_port, expected)?,
+    );
+    match flag {
+        'L' => Ok(PortForward::Local {
+            local_port: listen,
+            remote_host: host,
+            remote_port: host_port,
+        }),
+        _ => Err(anyhow::anyhow!("Unknown forward type -{}", flag)),
+    }
+}
+
+/// Describe a forward for status output
+pub fn describe(forward: &PortForward) -> String {
+    match forward {
+        PortForward::Local {
+            local_port,
+            remote_host,
+            remote_port,
+        } => format!(
+            "-L localhost:{} -> {}:{}",
+            local_port, remote_host, remote_port
+        ),
+        PortForward::Remote {
+            remote_port,
+            local_host,
+            local_port,
+        } => format!("-R remote:{} -> {}:{}", remote_port, local_host, local_port),
+        PortForward::Dynamic { local_port } => format!("-D localhost:{} (SOCKS5)", local_port),
+    }
+}
+
+/// Connect, start the forwards and keep them up until Ctrl-C
+pub async fn run(config: SshConfig, forwards: Vec<PortForward>) -> anyhow::Result<()> {
+    if forwards.is_empty() {
+        anyhow::bail!("Nothing to forward: give -L or -D, or add forwards to the profile");
+    }
+    let destination = format!("{}@{}:{}", config.username, config.host, config.port);
+
+    // Retry forever, except for failures another attempt cannot fix
+    let policy = WithVeto::new(
+        Unlimited::new(
+            ExponentialBackoff::new(u32::MAX, Duration::from_secs(1), MAX_BACKOFF),
+            MAX_BACKOFF,
+        ),
+        |_, error: &str| {
+            !error.contains("Authentication failed") && !error.contains("Host key verification")
+        },
+    );
+    let mut client = SshClient::new().with_auto_reconnect(
+        AutoReconnectConfig::new()
+            .with_strategy(policy)
+            .with_restore_shell(false),
+    );
+    client.connect(&config).await?;
+    println!("Connected to {}", destination);
+
+    let mut started = 0;
+    for forward in &forwards {
+        match client.start_forward(forward.clone()).await {
+            Ok(_) => {
+                started += 1;
+                println!("  up      {}", describe(forward));
+            }
+            Err(e) => println!("  failed  {}: {}", describe(forward), e),
+        }
+    }
+    if started == 0 {
+        client.disconnect().await?;
+        anyhow::bail!("No forward could be started");
+    }
+    println!(
+        "{} of {} forward(s) up; press Ctrl-C to stop",
+        started,
+        forwards.len()
+    );
+
+    let monitor = client.health_monitor(HealthConfig::new().with_interval(PROBE_INTERVAL));
+    let mut ticker = tokio::time::interval(PROBE_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            result = tokio::signal::ctrl_c() => {
+                result?;
+                break;
+            }
+        }
+        // Marks the session failed after enough unanswered probes
+        if client.is_connected() {
+            monitor.check(&client).await;
+        }
+        if client.is_connected() && !client.state().is_failed() {
+            continue;
+        }
+
+        println!("Connection to {} lost; reconnecting...", destination);
+        let result = tokio::select! {
+            result = client.ensure_connected() => result,
+            result = tokio::signal::ctrl_c() => {
+                result?;
+                break;
+            }
+        };
+        match result {
+            Ok(Some(restored)) => {
+                println!("Reconnected to {}", destination);
+                for handle in &restored.forwards {
+                    println!("  up      {}", describe(&handle.config));
+                }
+                for forward in &restored.failed_forwards {
+                    println!("  failed  {}", describe(forward));
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                anyhow::bail!("Giving up on {}: {}", destination, e);
+            }
+        }
+    }
+
+    println!("Closing forwards");
+    client.disconnect().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_and_dynamic_forwards_parse() {
+        assert_eq!(
+            parse_forward('L', "8080:db.internal:5432").unwrap(),
+            PortForward::Local {
+                local_port: 8080,
+                remote_host: "db.internal".to_string(),
+                remote_port: 5432,
+            }
+        );
+        assert_eq!(
+            parse_forward('D', "1080").unwrap(),
+            PortForward::Dynamic { local_port: 1080 }
+        );
+    }
+
+    #[test]
+    fn malformed_forwards_are_rejected() {
+        for spec in [
+            "0:host:80",
+            "8080:host:0",
+            "8080::80",
+            "8080:host",
+            "8080:host:80:1",
+            "8080:host:70000",
+            "port:host:80",
+        ] {
+            assert!(parse_forward('L', spec).is_err(), "{} was accepted", spec);
+        }
+        assert!(parse_forward('D', "0").is_err());
+        assert!(parse_forward('D', "1080:x").is_err());
+        assert!(parse_forward('X', "8080:host:80").is_err());
+    }
+
+    #[test]
+    fn remote_forwards_are_not_supported() {
+        let error = parse_forward('R', "8080:localhost:3000")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("not supported"), "{}", error);
+        assert!(forwards(&[], &["8080:localhost:3000".to_string()], &[], Vec::new()).is_err());
+    }
+
+    #[test]
+    fn profile_forwards_follow_the_command_line_without_duplicates() {
+        let saved = PortForward::Local {
+            local_port: 8080,
+            remote_host: "web".to_string(),
+            remote_port: 80,
+        };
+        let extra = PortForward::Dynamic { local_port: 1080 };
+        let forwards = forwards(
+            &["8080:web:80".to_string()],
+            &[],
+            &["9090".to_string()],
+            vec![saved.clone(), extra.clone()],
+        )
+        .unwrap();
+        assert_eq!(
+            forwards,
+            [saved, PortForward::Dynamic { local_port: 9090 }, extra]
+        );
+    }
+}
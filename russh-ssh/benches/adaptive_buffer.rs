@@ -0,0 +1,136 @@
+//! AdaptiveBuffer throughput on a 4K stream
+//!
+//! Feeds ten seconds of a 40 Mbit/s stream through the buffer in 64 KiB
+//! network chunks and reads it back in 16 KiB pieces, as a player would.
+//! The reader either keeps up or trails 8 MiB behind, which leaves a few
+//! hundred chunks buffered and makes the buffer evict. `copying` is the
+//! earlier design for comparison: chunks copied in and out, and every
+//! lookup and eviction scanning all of them.
+//!
+//! Run with `cargo bench -p russh-ssh --bench adaptive_buffer`.
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use russh_ssh::streaming::{AdaptiveBuffer, BufferConfig};
+use std::collections::BTreeMap;
+use std::hint::black_box;
+
+/// A 4K stream's bitrate in bits per second
+const BITRATE: u64 = 40_000_000;
+const SECONDS: u64 = 10;
+/// Size of each chunk coming off the network
+const CHUNK: usize = 64 * 1024;
+/// Size of each read by the player
+const READ: usize = 16 * 1024;
+/// How far the reader trails the download, in bytes
+const LEADS: [u64; 2] = [0, 8 * 1024 * 1024];
+
+fn stream() -> Bytes {
+    let len = BITRATE / 8 * SECONDS;
+    (0..len).map(|i| i as u8).collect::<Vec<u8>>().into()
+}
+
+fn zero_copy(stream: &Bytes, lead: u64) -> usize {
+    let mut buffer = AdaptiveBuffer::new(BufferConfig::default())
+        .with_bitrate(BITRATE)
+        .with_stream_size(stream.len() as u64);
+    let mut read = 0;
+    for start in (0..stream.len()).step_by(CHUNK) {
+        let end = (start + CHUNK).min(stream.len());
+        buffer.add_data(start as u64, stream.slice(start..end));
+        while buffer.position() + lead < end as u64 {
+            match buffer.read(READ) {
+                Some(data) => read += black_box(data).len(),
+                None => break,
+            }
+        }
+    }
+    read
+}
+
+fn copying(stream: &Bytes, lead: u64) -> usize {
+    let mut buffer = CopyingBuffer::new(BufferConfig::default().max_buffer_size);
+    let mut read = 0;
+    for start in (0..stream.len()).step_by(CHUNK) {
+        let end = (start + CHUNK).min(stream.len());
+        buffer.add_data(start as u64, stream[start..end].to_vec());
+        while buffer.position + lead < end as u64 {
+            match buffer.read(READ) {
+                Some(data) => read += black_box(data).len(),
+                None => break,
+            }
+        }
+    }
+    read
+}
+
+/// The buffer as it was before the switch to `Bytes` segments
+struct CopyingBuffer {
+    ranges: BTreeMap<u64, Vec<u8>>,
+    total: usize,
+    max: usize,
+    position: u64,
+}
+
+impl CopyingBuffer {
+    fn new(max: usize) -> Self {
+        Self {
+            ranges: BTreeMap::new(),
+            total: 0,
+            max,
+            position: 0,
+        }
+    }
+
+    fn add_data(&mut self, position: u64, data: Vec<u8>) {
+        let target = self.max.saturating_sub(data.len());
+        while self.total > target {
+            let position = self.position;
+            let oldest = self
+                .ranges
+                .iter()
+                .find(|(start, data)| **start + data.len() as u64 <= position)
+                .or_else(|| self.ranges.iter().next())
+                .map(|(start, _)| *start);
+            let Some(start) = oldest else {
+                break;
+            };
+            if let Some(data) = self.ranges.remove(&start) {
+                self.total -= data.len();
+            }
+        }
+        self.total += data.len();
+        self.ranges.insert(position, data);
+    }
+
+    fn read(&mut self, len: usize) -> Option<Vec<u8>> {
+        let pos = self.position;
+        let (start, data) = self
+            .ranges
+            .iter()
+            .find(|(start, data)| pos >= **start && pos < **start + data.len() as u64)?;
+        let offset = (pos - start) as usize;
+        let data = data[offset..(offset + len).min(data.len())].to_vec();
+        self.position += data.len() as u64;
+        Some(data)
+    }
+}
+
+fn bench_4k_stream(c: &mut Criterion) {
+    let stream = stream();
+    let mut group = c.benchmark_group("4k_stream");
+    group.throughput(Throughput::Bytes(stream.len() as u64));
+    group.sample_size(20);
+    for lead in LEADS {
+        group.bench_with_input(BenchmarkId::new("zero_copy", lead), &lead, |b, &lead| {
+            b.iter(|| zero_copy(&stream, lead))
+        });
+        group.bench_with_input(BenchmarkId::new("copying", lead), &lead, |b, &lead| {
+            b.iter(|| copying(&stream, lead))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_4k_stream);
+criterion_main!(benches);
@@ -3,10 +3,14 @@
 //! This module defines configuration structs for connections,
 //! reconnection strategies, and other configurable behaviors.
 
+use crate::connection::{ProxyConfig, ResolverConfig};
+use crate::error::ConnectionError;
 use crate::ssh::HostKeyCheck;
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 use std::path::PathBuf;
 use std::time::Duration;
+use zeroize::Zeroize;
 
 /// Configuration for connection behavior
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +30,29 @@ pub struct ConnectionConfig {
     /// Base delay for reconnection backoff
     #[serde(with = "duration_serde")]
     pub reconnect_base_delay: Duration,
+    /// Delay before racing the next address family (RFC 8305 "Connection Attempt Delay")
+    #[serde(with = "duration_serde", default = "default_happy_eyeballs_delay")]
+    pub happy_eyeballs_delay: Duration,
+    /// Outbound proxy to tunnel the connection through
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+    /// DNS resolver used to look up the host
+    #[serde(default)]
+    pub resolver: ResolverConfig,
+    /// Low-level TCP socket tuning
+    #[serde(default)]
+    pub socket: SocketOptions,
+    /// Alternative endpoints for the same host, in priority order
+    #[serde(default)]
+    pub fallback_endpoints: Vec<Endpoint>,
+    /// How the primary and fallback endpoints are tried
+    #[serde(default)]
+    pub failover: FailoverMode,
+}
+
+/// Default Happy Eyeballs connection attempt delay (RFC 8305 recommends 250ms)
+fn default_happy_eyeballs_delay() -> Duration {
+    Duration::from_millis(250)
 }
 
 impl Default for ConnectionConfig {
@@ -37,6 +64,12 @@ impl Default for ConnectionConfig {
             keepalive_interval: Duration::from_secs(60),
             max_reconnect_attempts: 5,
             reconnect_base_delay: Duration::from_secs(1),
+            happy_eyeballs_delay: default_happy_eyeballs_delay(),
+            proxy: None,
+            resolver: ResolverConfig::default(),
+            socket: SocketOptions::default(),
+            fallback_endpoints: Vec::new(),
+            failover: FailoverMode::default(),
         }
     }
 }
@@ -68,6 +101,207 @@ impl ConnectionConfig {
         self.max_reconnect_attempts = attempts;
         self
     }
+
+    /// Set the delay between staggered Happy Eyeballs connection attempts
+    pub fn with_happy_eyeballs_delay(mut self, delay: Duration) -> Self {
+        self.happy_eyeballs_delay = delay;
+        self
+    }
+
+    /// Route the connection through an outbound proxy
+    pub fn with_proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Set the DNS resolver configuration
+    pub fn with_resolver(mut self, resolver: ResolverConfig) -> Self {
+        self.resolver = resolver;
+        self
+    }
+
+    /// Set low-level TCP socket options
+    pub fn with_socket_options(mut self, socket: SocketOptions) -> Self {
+        self.socket = socket;
+        self
+    }
+
+    /// Add a fallback endpoint (tried after the primary host and earlier fallbacks)
+    pub fn with_fallback_endpoint(mut self, endpoint: Endpoint) -> Self {
+        self.fallback_endpoints.push(endpoint);
+        self
+    }
+
+    /// Set how endpoints are tried
+    pub fn with_failover(mut self, failover: FailoverMode) -> Self {
+        self.failover = failover;
+        self
+    }
+
+    /// All endpoints in priority order, starting with the primary host
+    pub fn endpoints(&self) -> Vec<Endpoint> {
+        std::iter::once(Endpoint::new(self.host.clone(), self.port))
+            .chain(self.fallback_endpoints.iter().cloned())
+            .collect()
+    }
+}
+
+/// A network address for a logical host (e.g. public IP, VPN IP)
+///
+/// Endpoints are TCP addresses only, since a
+/// [`ManagedConnection`](crate::connection::ManagedConnection) carries a TCP
+/// stream. A P2P node id cannot be listed directly. To fail over onto a
+/// peer, bind a [`LocalTunnel`](crate::p2p::tunnel::LocalTunnel) to its
+/// `ssh` service and add the tunnel's loopback address as an endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Endpoint {
+    /// Host name or IP address
+    pub host: String,
+    /// Port number
+    pub port: u16,
+    /// Optional human-readable label (e.g. "vpn")
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+impl Endpoint {
+    /// Create a new endpoint
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+            label: None,
+        }
+    }
+
+    /// Attach a label
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Check whether this endpoint has the same address as another
+    pub fn same_address(&self, other: &Endpoint) -> bool {
+        self.host == other.host && self.port == other.port
+    }
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "[{}]:{}", self.host, self.port)?;
+        } else {
+            write!(f, "{}:{}", self.host, self.port)?;
+        }
+        if let Some(label) = &self.label {
+            write!(f, " ({})", label)?;
+        }
+        Ok(())
+    }
+}
+
+/// How multiple endpoints of a host are tried
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailoverMode {
+    /// Try endpoints one after another in priority order
+    #[default]
+    Sequential,
+    /// Race all endpoints, staggered like Happy Eyeballs
+    Race,
+}
+
+/// Low-level TCP socket options
+///
+/// Unset options keep the operating system defaults. Useful for tuning
+/// high-latency links (satellite, long-haul VPNs).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SocketOptions {
+    /// Interval between keepalive probes once the keepalive idle time elapsed
+    #[serde(default)]
+    pub keepalive_probe_interval: Option<Duration>,
+    /// Unanswered keepalive probes before the connection is dropped (not on Windows)
+    #[serde(default)]
+    pub keepalive_retries: Option<u32>,
+    /// How long sent data may stay unacknowledged before the connection is
+    /// dropped (`TCP_USER_TIMEOUT`, Linux only)
+    #[serde(default)]
+    pub user_timeout: Option<Duration>,
+    /// Send buffer size in bytes (`SO_SNDBUF`)
+    #[serde(default)]
+    pub send_buffer_size: Option<u32>,
+    /// Receive buffer size in bytes (`SO_RCVBUF`)
+    #[serde(default)]
+    pub recv_buffer_size: Option<u32>,
+    /// Network interface to bind to (`SO_BINDTODEVICE`, Linux only)
+    #[serde(default)]
+    pub bind_interface: Option<String>,
+    /// Local source address for outgoing connections
+    #[serde(default)]
+    pub source_address: Option<IpAddr>,
+}
+
+impl SocketOptions {
+    /// Create socket options using OS defaults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the keepalive probe interval
+    pub fn with_keepalive_probe_interval(mut self, interval: Duration) -> Self {
+        self.keepalive_probe_interval = Some(interval);
+        self
+    }
+
+    /// Set the keepalive probe count
+    pub fn with_keepalive_retries(mut self, retries: u32) -> Self {
+        self.keepalive_retries = Some(retries);
+        self
+    }
+
+    /// Set `TCP_USER_TIMEOUT`
+    pub fn with_user_timeout(mut self, timeout: Duration) -> Self {
+        self.user_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the send buffer size
+    pub fn with_send_buffer_size(mut self, size: u32) -> Self {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    /// Set the receive buffer size
+    pub fn with_recv_buffer_size(mut self, size: u32) -> Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// Bind outgoing connections to a network interface
+    pub fn with_bind_interface(mut self, interface: impl Into<String>) -> Self {
+        self.bind_interface = Some(interface.into());
+        self
+    }
+
+    /// Bind outgoing connections to a local source address
+    pub fn with_source_address(mut self, address: IpAddr) -> Self {
+        self.source_address = Some(address);
+        self
+    }
+
+    /// Check that the options are supported on this platform
+    pub fn validate(&self) -> Result<(), ConnectionError> {
+        if self.bind_interface.is_some() && !cfg!(any(target_os = "linux", target_os = "android")) {
+            return Err(ConnectionError::InvalidConfig(
+                "Binding to an interface is only supported on Linux".to_string(),
+            ));
+        }
+        if self.keepalive_retries.is_some() && cfg!(windows) {
+            return Err(ConnectionError::InvalidConfig(
+                "Keepalive retry count is not configurable on Windows".to_string(),
+            ));
+        }
+        Ok(())
+    }
 }
 
 /// Reconnection strategy configuration with exponential backoff
@@ -159,7 +393,9 @@ impl ReconnectionStrategy {
 }
 
 /// SSH authentication method
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Secrets are zeroed on drop and never shown by `Debug`.
+#[derive(Clone, Serialize, Deserialize)]
 pub enum AuthMethod {
     /// Password-based authentication
     Password(String),
@@ -174,6 +410,33 @@ pub enum AuthMethod {
     Agent,
 }
 
+impl std::fmt::Debug for AuthMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Password(_) => f.debug_tuple("Password").field(&"[REDACTED]").finish(),
+            Self::PublicKey {
+                key_path,
+                passphrase,
+            } => f
+                .debug_struct("PublicKey")
+                .field("key_path", key_path)
+                .field("passphrase", &passphrase.as_ref().map(|_| "[REDACTED]"))
+                .finish(),
+            Self::Agent => f.write_str("Agent"),
+        }
+    }
+}
+
+impl Drop for AuthMethod {
+    fn drop(&mut self) {
+        match self {
+            Self::Password(password) => password.zeroize(),
+            Self::PublicKey { passphrase, .. } => passphrase.zeroize(),
+            Self::Agent => {}
+        }
+    }
+}
+
 /// SSH session configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SshConfig {
@@ -332,6 +595,22 @@ mod tests {
         assert!(matches!(config.auth, AuthMethod::Password(_)));
     }
 
+    #[test]
+    fn auth_method_debug_hides_secrets() {
+        let password = format!("{:?}", AuthMethod::Password("hunter2".into()));
+        let key = format!(
+            "{:?}",
+            AuthMethod::PublicKey {
+                key_path: PathBuf::from("/home/user/.ssh/id_ed25519"),
+                passphrase: Some("hunter2".into()),
+            }
+        );
+
+        assert!(!password.contains("hunter2"));
+        assert!(!key.contains("hunter2"));
+        assert!(key.contains("id_ed25519"));
+    }
+
     #[test]
     fn ssh_config_serialization_roundtrip() {
         let config = SshConfig::with_password("host.com", 22, "user", "pass")
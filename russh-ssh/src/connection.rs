@@ -2,8 +2,20 @@
 //!
 //! This module provides connection state tracking and management.
 
+pub mod health;
+pub mod manager;
+pub mod network;
+pub mod policy;
+pub mod proxy;
 pub mod reconnection;
+pub mod resolver;
 pub mod state;
 
+pub use health::*;
+pub use manager::*;
+pub use network::*;
+pub use policy::*;
+pub use proxy::*;
 pub use reconnection::*;
+pub use resolver::*;
 pub use state::*;
@@ -0,0 +1,343 @@
+//! Connection health monitoring
+//!
+//! A background monitor periodically sends a cheap no-op request over the
+//! connection, measures its round-trip time and publishes the resulting
+//! [`ConnectionHealth`] through the [`StateManager`]. Unanswered probes mark
+//! the connection degraded and, after several in a row, failed, so
+//! half-open connections are detected long before TCP keepalive would.
+//!
+//! # Requirements Coverage
+//! - Requirement 1.5: Keepalive for idle connections
+//! - Requirement 2.4: User notification of connection status
+
+use crate::connection::state::{ConnectionHealth, ConnectionState, StateManager};
+use crate::error::ConnectionError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Instant, MissedTickBehavior};
+
+/// A cheap request used to measure round-trip time
+#[async_trait]
+pub trait HealthProbe: Send + Sync {
+    /// Send one probe and wait for the answer
+    async fn probe(&self) -> Result<(), ConnectionError>;
+}
+
+/// Health monitor configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthConfig {
+    /// Time between probes
+    pub interval: Duration,
+    /// How long to wait for a probe answer
+    pub probe_timeout: Duration,
+    /// Round-trip time above which the connection counts as degraded
+    pub degraded_rtt: Duration,
+    /// Consecutive missed probes before the connection is declared dead
+    pub max_missed_probes: u32,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(15),
+            probe_timeout: Duration::from_secs(5),
+            degraded_rtt: Duration::from_millis(500),
+            max_missed_probes: 3,
+        }
+    }
+}
+
+impl HealthConfig {
+    /// Create a new health configuration with default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the probe interval
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the probe timeout
+    pub fn with_probe_timeout(mut self, probe_timeout: Duration) -> Self {
+        self.probe_timeout = probe_timeout;
+        self
+    }
+
+    /// Set the degraded latency threshold
+    pub fn with_degraded_rtt(mut self, degraded_rtt: Duration) -> Self {
+        self.degraded_rtt = degraded_rtt;
+        self
+    }
+
+    /// Set the number of missed probes tolerated
+    pub fn with_max_missed_probes(mut self, max_missed_probes: u32) -> Self {
+        self.max_missed_probes = max_missed_probes.max(1);
+        self
+    }
+}
+
+/// Probe statistics
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HealthStats {
+    /// Round-trip time of the last answered probe
+    pub last_rtt: Option<Duration>,
+    /// Smoothed round-trip time (RFC 6298 style, alpha = 1/8)
+    pub smoothed_rtt: Option<Duration>,
+    /// Consecutive unanswered probes
+    pub consecutive_failures: u32,
+    /// Total probes sent
+    pub probes_sent: u64,
+    /// Total probes that failed or timed out
+    pub probes_failed: u64,
+}
+
+impl HealthStats {
+    fn record_rtt(&mut self, rtt: Duration) {
+        self.last_rtt = Some(rtt);
+        self.smoothed_rtt = Some(match self.smoothed_rtt {
+            Some(srtt) => (srtt * 7 + rtt) / 8,
+            None => rtt,
+        });
+        self.consecutive_failures = 0;
+    }
+}
+
+/// Background connection health monitor
+pub struct HealthMonitor {
+    /// Monitor configuration
+    config: HealthConfig,
+    /// State manager that receives health updates
+    state_manager: Arc<StateManager>,
+    /// Probe statistics
+    stats: Mutex<HealthStats>,
+}
+
+impl HealthMonitor {
+    /// Create a monitor publishing to the given state manager
+    pub fn new(config: HealthConfig, state_manager: Arc<StateManager>) -> Self {
+        Self {
+            config,
+            state_manager,
+            stats: Mutex::new(HealthStats::default()),
+        }
+    }
+
+    /// Get the monitor configuration
+    pub fn config(&self) -> &HealthConfig {
+        &self.config
+    }
+
+    /// Get a snapshot of the probe statistics
+    pub fn stats(&self) -> HealthStats {
+        self.lock_stats().clone()
+    }
+
+    /// Run a single probe and publish the resulting health
+    ///
+    /// When the connection becomes unresponsive, the connection state is
+    /// set to `Failed` so reconnection logic can take over.
+    pub async fn check<P: HealthProbe + ?Sized>(&self, probe: &P) -> ConnectionHealth {
+        let started = Instant::now();
+        let result = timeout(self.config.probe_timeout, probe.probe()).await;
+        let rtt = started.elapsed();
+
+        let health = {
+            let mut stats = self.lock_stats();
+            stats.probes_sent += 1;
+            match result {
+                Ok(Ok(())) => {
+                    stats.record_rtt(rtt);
+                    let rtt_ms = rtt.as_millis() as u64;
+                    if rtt > self.config.degraded_rtt {
+                        ConnectionHealth::Degraded {
+                            rtt_ms: Some(rtt_ms),
+                            missed_probes: 0,
+                        }
+                    } else {
+                        ConnectionHealth::Healthy { rtt_ms }
+                    }
+                }
+                Ok(Err(e)) => {
+                    tracing::debug!(error = %e, "Health probe failed");
+                    self.classify_failure(&mut stats)
+                }
+                Err(_) => {
+                    tracing::debug!(timeout = ?self.config.probe_timeout, "Health probe timed out");
+                    self.classify_failure(&mut stats)
+                }
+            }
+        };
+
+        if self.state_manager.set_health(health.clone()) {
+            tracing::info!(health = %health, "Connection health changed");
+        }
+
+        if let ConnectionHealth::Unresponsive { missed_probes } = health {
+            tracing::warn!(missed_probes, "Connection unresponsive, marking as failed");
+            self.state_manager.set_state(ConnectionState::Failed {
+                reason: format!(
+                    "Connection unresponsive: {} health probes unanswered",
+                    missed_probes
+                ),
+            });
+        }
+
+        health
+    }
+
+    /// Spawn the periodic probe loop
+    ///
+    /// Probes are only sent while the connection state is `Connected`, so
+    /// the loop pauses during reconnection and resumes afterwards. Abort
+    /// the returned handle to stop monitoring.
+    pub fn spawn<P>(self: Arc<Self>, probe: Arc<P>) -> JoinHandle<()>
+    where
+        P: HealthProbe + ?Sized + 'static,
+    {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.config.interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                if self.state_manager.state().is_connected() {
+                    self.check(probe.as_ref()).await;
+                }
+            }
+        })
+    }
+
+    fn classify_failure(&self, stats: &mut HealthStats) -> ConnectionHealth {
+        stats.probes_failed += 1;
+        stats.consecutive_failures += 1;
+        if stats.consecutive_failures >= self.config.max_missed_probes {
+            ConnectionHealth::Unresponsive {
+                missed_probes: stats.consecutive_failures,
+            }
+        } else {
+            ConnectionHealth::Degraded {
+                rtt_ms: stats.last_rtt.map(|rtt| rtt.as_millis() as u64),
+                missed_probes: stats.consecutive_failures,
+            }
+        }
+    }
+
+    fn lock_stats(&self) -> std::sync::MutexGuard<'_, HealthStats> {
+        self.stats.lock().unwrap_or_else(|poisoned| {
+            tracing::warn!("Health stats lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Probe answering after a configurable delay; `u64::MAX` never answers
+    struct DelayProbe {
+        delay_ms: AtomicU64,
+    }
+
+    impl DelayProbe {
+        fn new(delay_ms: u64) -> Self {
+            Self {
+                delay_ms: AtomicU64::new(delay_ms),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl HealthProbe for DelayProbe {
+        async fn probe(&self) -> Result<(), ConnectionError> {
+            match self.delay_ms.load(Ordering::SeqCst) {
+                u64::MAX => std::future::pending().await,
+                ms => {
+                    tokio::time::sleep(Duration::from_millis(ms)).await;
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    fn monitor() -> HealthMonitor {
+        let state = Arc::new(StateManager::with_state(ConnectionState::Connected));
+        HealthMonitor::new(
+            HealthConfig::new()
+                .with_probe_timeout(Duration::from_secs(1))
+                .with_degraded_rtt(Duration::from_millis(200))
+                .with_max_missed_probes(2),
+            state,
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fast_probe_is_healthy() {
+        let monitor = monitor();
+        let health = monitor.check(&DelayProbe::new(50)).await;
+        assert_eq!(health, ConnectionHealth::Healthy { rtt_ms: 50 });
+        assert_eq!(monitor.stats().last_rtt, Some(Duration::from_millis(50)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_probe_is_degraded() {
+        let monitor = monitor();
+        let health = monitor.check(&DelayProbe::new(400)).await;
+        assert!(matches!(
+            health,
+            ConnectionHealth::Degraded {
+                rtt_ms: Some(400),
+                missed_probes: 0
+            }
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn missed_probes_fail_connection() {
+        let monitor = monitor();
+        let probe = DelayProbe::new(u64::MAX);
+
+        let first = monitor.check(&probe).await;
+        assert!(matches!(
+            first,
+            ConnectionHealth::Degraded {
+                missed_probes: 1,
+                ..
+            }
+        ));
+        assert!(monitor.state_manager.state().is_connected());
+
+        let second = monitor.check(&probe).await;
+        assert_eq!(second, ConnectionHealth::Unresponsive { missed_probes: 2 });
+        assert!(monitor.state_manager.state().is_failed());
+        assert_eq!(monitor.stats().probes_failed, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn answered_probe_resets_failures() {
+        let monitor = monitor();
+        let probe = DelayProbe::new(u64::MAX);
+        monitor.check(&probe).await;
+
+        probe.delay_ms.store(10, Ordering::SeqCst);
+        let health = monitor.check(&probe).await;
+        assert_eq!(health, ConnectionHealth::Healthy { rtt_ms: 10 });
+        assert_eq!(monitor.stats().consecutive_failures, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn spawned_monitor_publishes_health() {
+        let monitor = Arc::new(monitor());
+        let mut health_rx = monitor.state_manager.subscribe_health();
+        let handle = monitor.clone().spawn(Arc::new(DelayProbe::new(5)));
+
+        let event = health_rx.recv().await.unwrap();
+        assert_eq!(event.new_health, ConnectionHealth::Healthy { rtt_ms: 5 });
+        handle.abort();
+    }
+}
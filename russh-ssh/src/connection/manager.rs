@@ -6,6 +6,7 @@
 //! # Features
 //! - Async connection establishment using Tokio runtime
 //! - TCP keepalive configuration to prevent timeout disconnections
+//! - Happy Eyeballs (RFC 8305) racing of IPv6 and IPv4 candidates
 //! - Automatic reconnection with exponential backoff
 //! - Connection state tracking and broadcasting
 //!
@@ -13,17 +14,121 @@
 //! - Requirement 1.1: Async SSH connection establishment
 //! - Requirement 1.5: TCP keepalive for idle connections
 
-use crate::config::{ConnectionConfig, ReconnectionStrategy};
+use crate::config::{
+    ConnectionConfig, Endpoint, FailoverMode, ReconnectionStrategy, SocketOptions,
+};
 use crate::connection::state::{ConnectionState, StateManager, StateChangeEvent};
+use crate::connection::policy::ReconnectionPolicy;
+use crate::connection::proxy::ProxyConfig;
 use crate::connection::reconnection::ReconnectionController;
+use crate::connection::resolver::{Resolver, ResolverConfig};
 use crate::error::{ConnectionError, ReconnectionError};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::net::TcpStream;
+use tokio::net::{TcpSocket, TcpStream};
 use tokio::sync::broadcast;
+use tokio::task::JoinSet;
 use tokio::time::timeout;
 
+/// IP address family used by a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFamily {
+    /// IPv4
+    V4,
+    /// IPv6
+    V6,
+}
+
+impl AddressFamily {
+    /// Get the family of a socket address
+    pub fn of(addr: &SocketAddr) -> Self {
+        if addr.is_ipv6() {
+            AddressFamily::V6
+        } else {
+            AddressFamily::V4
+        }
+    }
+}
+
+impl std::fmt::Display for AddressFamily {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AddressFamily::V4 => write!(f, "IPv4"),
+            AddressFamily::V6 => write!(f, "IPv6"),
+        }
+    }
+}
+
+/// Outcome of a single connection attempt
+type Attempt = (SocketAddr, std::io::Result<TcpStream>);
+
+/// Start a connection attempt to the next candidate, if any
+fn spawn_attempt(
+    attempts: &mut JoinSet<Attempt>,
+    candidates: &mut std::vec::IntoIter<SocketAddr>,
+    options: &SocketOptions,
+) {
+    if let Some(addr) = candidates.next() {
+        tracing::debug!(address = %addr, "Starting connection attempt");
+        let options = options.clone();
+        attempts.spawn(async move { (addr, connect_socket(addr, &options).await) });
+    }
+}
+
+/// Connect to `addr`, applying options that must be set before connecting
+///
+/// Buffer sizes are set before the handshake so TCP window scaling can
+/// take them into account.
+async fn connect_socket(addr: SocketAddr, options: &SocketOptions) -> std::io::Result<TcpStream> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+
+    if let Some(size) = options.send_buffer_size {
+        socket.set_send_buffer_size(size)?;
+    }
+    if let Some(size) = options.recv_buffer_size {
+        socket.set_recv_buffer_size(size)?;
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if let Some(interface) = &options.bind_interface {
+        socket2::SockRef::from(&socket).bind_device(Some(interface.as_bytes()))?;
+    }
+    if let Some(source) = options.source_address {
+        socket.bind(SocketAddr::new(source, 0))?;
+    }
+
+    socket.connect(addr).await
+}
+
+/// Order resolved addresses for Happy Eyeballs
+///
+/// Alternates address families, starting with the family of the first
+/// resolved address (RFC 8305 section 4).
+pub fn interleave_families(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+    let preferred = AddressFamily::of(first);
+    let (mut primary, mut secondary): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| AddressFamily::of(addr) == preferred);
+
+    let mut ordered = Vec::with_capacity(primary.len() + secondary.len());
+    primary.reverse();
+    secondary.reverse();
+    loop {
+        match (primary.pop(), secondary.pop()) {
+            (None, None) => break,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+    ordered
+}
+
 /// A managed TCP connection with state tracking
 ///
 /// This struct wraps a TCP stream with additional metadata and utilities
@@ -32,30 +137,44 @@ use tokio::time::timeout;
 ///
 /// # Requirements Coverage
 /// - Requirement 1.1: Async SSH connection establishment
+#[derive(Debug)]
 pub struct ManagedConnection {
     /// The underlying TCP stream
     stream: TcpStream,
     /// Remote address
     remote_addr: SocketAddr,
+    /// Endpoint this connection was made to
+    endpoint: Endpoint,
     /// Connection established timestamp
     connected_at: std::time::Instant,
 }
 
 impl ManagedConnection {
     /// Create a new managed connection
-    fn new(stream: TcpStream, remote_addr: SocketAddr) -> Self {
+    fn new(stream: TcpStream, remote_addr: SocketAddr, endpoint: Endpoint) -> Self {
         Self {
             stream,
             remote_addr,
+            endpoint,
             connected_at: std::time::Instant::now(),
         }
     }
 
-    /// Get the remote address
+    /// Get the remote address (the proxy's address when proxied)
     pub fn remote_addr(&self) -> SocketAddr {
         self.remote_addr
     }
 
+    /// Get the endpoint this connection was made to
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// Get the address family the connection was established over
+    pub fn address_family(&self) -> AddressFamily {
+        AddressFamily::of(&self.remote_addr)
+    }
+
     /// Get a reference to the underlying stream
     pub fn stream(&self) -> &TcpStream {
         &self.stream
@@ -104,18 +223,40 @@ pub struct ConnectionManager {
     state_manager: Arc<StateManager>,
     /// Reconnection controller
     reconnection_controller: Arc<ReconnectionController>,
+    /// DNS resolver for the host name
+    resolver: Arc<dyn Resolver>,
+    /// Custom reconnection policy (overrides the config-derived strategy)
+    reconnection_policy: Option<Arc<dyn ReconnectionPolicy>>,
+    /// Endpoint of the last successful connection, tried first on reconnect
+    preferred_endpoint: std::sync::RwLock<Option<Endpoint>>,
 }
 
 impl ConnectionManager {
     /// Create a new connection manager with the given configuration
     pub fn new(config: ConnectionConfig) -> Self {
+        let resolver = config.resolver.build();
         Self {
             config,
             state_manager: Arc::new(StateManager::new()),
             reconnection_controller: Arc::new(ReconnectionController::new()),
+            resolver,
+            reconnection_policy: None,
+            preferred_endpoint: std::sync::RwLock::new(None),
         }
     }
 
+    /// Use a custom resolver instead of the one built from the configuration
+    pub fn with_resolver(mut self, resolver: Arc<dyn Resolver>) -> Self {
+        self.resolver = resolver;
+        self
+    }
+
+    /// Use a custom reconnection policy for [`reconnect`](Self::reconnect)
+    pub fn with_reconnection_policy(mut self, policy: Arc<dyn ReconnectionPolicy>) -> Self {
+        self.reconnection_policy = Some(policy);
+        self
+    }
+
     /// Get the current connection state
     pub fn state(&self) -> ConnectionState {
         self.state_manager.state()
@@ -185,14 +326,162 @@ impl ConnectionManager {
 
     /// Internal connection logic
     ///
-    /// Performs DNS resolution, TCP connection with timeout, and keepalive setup.
+    /// Tries the primary and fallback endpoints according to the failover
+    /// mode, starting with the endpoint that last succeeded.
     async fn connect_internal(&self) -> Result<ManagedConnection, ConnectionError> {
-        // Resolve the address
-        let addr = self.resolve_address().await?;
-        tracing::debug!(address = %addr, "Resolved address");
+        self.config.socket.validate()?;
+        let endpoints = self.ordered_endpoints();
+
+        let connection = if self.config.failover == FailoverMode::Race
+            && self.config.proxy.is_none()
+            && endpoints.len() > 1
+        {
+            self.race_endpoints(endpoints).await?
+        } else {
+            self.connect_sequential(endpoints).await?
+        };
 
-        // Connect with timeout
-        let stream = timeout(self.config.timeout, TcpStream::connect(addr))
+        self.record_endpoint(connection.endpoint());
+        Ok(connection)
+    }
+
+    /// Endpoints in priority order, with the last successful one first
+    fn ordered_endpoints(&self) -> Vec<Endpoint> {
+        let mut endpoints = self.config.endpoints();
+        if let Some(preferred) = self.active_endpoint() {
+            if let Some(index) = endpoints.iter().position(|e| e.same_address(&preferred)) {
+                let endpoint = endpoints.remove(index);
+                endpoints.insert(0, endpoint);
+            }
+        }
+        endpoints
+    }
+
+    /// Remember the endpoint that succeeded so reconnects prefer it
+    fn record_endpoint(&self, endpoint: &Endpoint) {
+        let mut preferred = self.preferred_endpoint.write().unwrap_or_else(|poisoned| {
+            tracing::warn!("Endpoint lock was poisoned, recovering");
+            poisoned.into_inner()
+        });
+        if preferred.as_ref() != Some(endpoint) {
+            tracing::info!(endpoint = %endpoint, "Connected via endpoint");
+            *preferred = Some(endpoint.clone());
+        }
+    }
+
+    /// Get the endpoint of the last successful connection
+    pub fn active_endpoint(&self) -> Option<Endpoint> {
+        self.preferred_endpoint
+            .read()
+            .unwrap_or_else(|poisoned| {
+                tracing::warn!("Endpoint lock was poisoned, recovering");
+                poisoned.into_inner()
+            })
+            .clone()
+    }
+
+    /// Try endpoints one after another
+    async fn connect_sequential(
+        &self,
+        endpoints: Vec<Endpoint>,
+    ) -> Result<ManagedConnection, ConnectionError> {
+        let mut last_error = None;
+        for endpoint in endpoints {
+            let result = match &self.config.proxy {
+                Some(proxy) => self.connect_via_proxy(proxy, &endpoint).await,
+                None => self.connect_endpoint(&endpoint).await,
+            };
+            match result {
+                Ok(connection) => return Ok(connection),
+                Err(e) => {
+                    tracing::warn!(endpoint = %endpoint, error = %e, "Endpoint failed");
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error
+            .unwrap_or_else(|| ConnectionError::InvalidConfig("No endpoints".to_string())))
+    }
+
+    /// Race all endpoints' addresses against each other
+    ///
+    /// Addresses are resolved per endpoint and raced with Happy Eyeballs
+    /// staggering; the winning address is mapped back to its endpoint.
+    async fn race_endpoints(
+        &self,
+        endpoints: Vec<Endpoint>,
+    ) -> Result<ManagedConnection, ConnectionError> {
+        let mut owners: Vec<(SocketAddr, Endpoint)> = Vec::new();
+        let mut last_error = None;
+        for endpoint in endpoints {
+            match self.candidate_addresses(&endpoint).await {
+                Ok(addrs) => owners.extend(addrs.into_iter().map(|addr| (addr, endpoint.clone()))),
+                Err(e) => {
+                    tracing::warn!(endpoint = %endpoint, error = %e, "Endpoint unresolvable");
+                    last_error = Some(e);
+                }
+            }
+        }
+        if owners.is_empty() {
+            return Err(last_error
+                .unwrap_or_else(|| ConnectionError::InvalidConfig("No endpoints".to_string())));
+        }
+
+        let addrs = owners.iter().map(|(addr, _)| *addr).collect();
+        let (stream, addr) = self.timed_race(addrs).await?;
+        let endpoint = owners
+            .into_iter()
+            .find(|(candidate, _)| *candidate == addr)
+            .map(|(_, endpoint)| endpoint)
+            .ok_or_else(|| {
+                ConnectionError::ConnectionClosed("Unknown winning address".to_string())
+            })?;
+
+        self.finish_connection(stream, addr, endpoint)
+    }
+
+    /// Connect directly to a single endpoint
+    ///
+    /// Performs DNS resolution, Happy Eyeballs connection racing with timeout,
+    /// and keepalive setup.
+    async fn connect_endpoint(
+        &self,
+        endpoint: &Endpoint,
+    ) -> Result<ManagedConnection, ConnectionError> {
+        let addrs = self.candidate_addresses(endpoint).await?;
+        let (stream, addr) = self.timed_race(addrs).await?;
+        self.finish_connection(stream, addr, endpoint.clone())
+    }
+
+    /// Resolve an endpoint, keeping only addresses usable with the socket options
+    async fn candidate_addresses(
+        &self,
+        endpoint: &Endpoint,
+    ) -> Result<Vec<SocketAddr>, ConnectionError> {
+        let mut addrs = self.resolve_addresses(endpoint).await?;
+        tracing::debug!(endpoint = %endpoint, addresses = ?addrs, "Resolved addresses");
+
+        // A source address pins the connection to its family
+        if let Some(source) = self.config.socket.source_address {
+            addrs.retain(|addr| addr.is_ipv4() == source.is_ipv4());
+            if addrs.is_empty() {
+                return Err(ConnectionError::InvalidConfig(format!(
+                    "No {} address for {} matches source address {}",
+                    if source.is_ipv4() { "IPv4" } else { "IPv6" },
+                    endpoint,
+                    source
+                )));
+            }
+        }
+        Ok(addrs)
+    }
+
+    /// Race candidates with the overall connection timeout
+    async fn timed_race(
+        &self,
+        addrs: Vec<SocketAddr>,
+    ) -> Result<(TcpStream, SocketAddr), ConnectionError> {
+        let (stream, addr) = timeout(self.config.timeout, self.race_connect(addrs))
             .await
             .map_err(|_| {
                 tracing::warn!(
@@ -200,9 +489,22 @@ impl ConnectionManager {
                     "Connection timed out"
                 );
                 ConnectionError::Timeout(self.config.timeout)
-            })?
-            .map_err(|e| self.map_io_error(e, &addr))?;
+            })??;
+        tracing::debug!(
+            address = %addr,
+            family = %AddressFamily::of(&addr),
+            "Connection attempt won"
+        );
+        Ok((stream, addr))
+    }
 
+    /// Apply post-connect socket options and wrap the stream
+    fn finish_connection(
+        &self,
+        stream: TcpStream,
+        addr: SocketAddr,
+        endpoint: Endpoint,
+    ) -> Result<ManagedConnection, ConnectionError> {
         // Configure TCP keepalive to prevent idle disconnections
         self.configure_keepalive(&stream)?;
         tracing::debug!(
@@ -213,64 +515,142 @@ impl ConnectionManager {
         // Configure TCP nodelay for lower latency
         stream.set_nodelay(true).map_err(ConnectionError::Io)?;
 
-        Ok(ManagedConnection::new(stream, addr))
+        Ok(ManagedConnection::new(stream, addr, endpoint))
+    }
+
+    /// Connect to an endpoint through the configured outbound proxy
+    ///
+    /// The target host is resolved by the proxy, not locally.
+    async fn connect_via_proxy(
+        &self,
+        proxy: &ProxyConfig,
+        endpoint: &Endpoint,
+    ) -> Result<ManagedConnection, ConnectionError> {
+        let stream = timeout(
+            self.config.timeout,
+            proxy.connect(&endpoint.host, endpoint.port),
+        )
+        .await
+        .map_err(|_| ConnectionError::Timeout(self.config.timeout))??;
+        let proxy_addr = stream.peer_addr()?;
+        tracing::debug!(proxy = %proxy_addr, "Connected through proxy");
+
+        self.finish_connection(stream, proxy_addr, endpoint.clone())
     }
 
-    /// Resolve the host address
+    /// Race connection attempts to the candidate addresses (RFC 8305)
     ///
-    /// Attempts to parse the address directly first, then falls back to DNS resolution.
-    async fn resolve_address(&self) -> Result<SocketAddr, ConnectionError> {
-        let addr_str = format!("{}:{}", self.config.host, self.config.port);
+    /// Attempts start in interleaved family order, staggered by the
+    /// configured connection attempt delay. A failed attempt starts the next
+    /// candidate immediately. The first successful connection wins and the
+    /// remaining attempts are cancelled.
+    async fn race_connect(
+        &self,
+        addrs: Vec<SocketAddr>,
+    ) -> Result<(TcpStream, SocketAddr), ConnectionError> {
+        let mut candidates = interleave_families(addrs).into_iter();
+        let mut attempts = JoinSet::new();
+        let mut last_error = None;
+
+        spawn_attempt(&mut attempts, &mut candidates, &self.config.socket);
+
+        loop {
+            if attempts.is_empty() && candidates.as_slice().is_empty() {
+                return Err(
+                    last_error.unwrap_or_else(|| ConnectionError::DnsResolution {
+                        host: self.config.host.clone(),
+                        reason: "No addresses found".to_string(),
+                    }),
+                );
+            }
+
+            tokio::select! {
+                Some(joined) = attempts.join_next() => match joined {
+                    Ok((addr, Ok(stream))) => {
+                        attempts.abort_all();
+                        return Ok((stream, addr));
+                    }
+                    Ok((addr, Err(e))) => {
+                        tracing::debug!(address = %addr, error = %e, "Connection attempt failed");
+                        last_error = Some(self.map_io_error(e, &addr));
+                        spawn_attempt(&mut attempts, &mut candidates, &self.config.socket);
+                    }
+                    Err(e) => {
+                        last_error = Some(ConnectionError::ConnectionClosed(e.to_string()));
+                        spawn_attempt(&mut attempts, &mut candidates, &self.config.socket);
+                    }
+                },
+                _ = tokio::time::sleep(self.config.happy_eyeballs_delay), if !candidates.as_slice().is_empty() => {
+                    spawn_attempt(&mut attempts, &mut candidates, &self.config.socket);
+                }
+            }
+        }
+    }
 
-        // Try to parse as socket address first (for IP addresses)
-        if let Ok(addr) = addr_str.parse::<SocketAddr>() {
-            return Ok(addr);
+    /// Resolve an endpoint to all candidate addresses
+    ///
+    /// Attempts to parse the host as an IP address first, then falls back to
+    /// the configured resolver.
+    async fn resolve_addresses(
+        &self,
+        endpoint: &Endpoint,
+    ) -> Result<Vec<SocketAddr>, ConnectionError> {
+        // Try to parse as IP address first
+        if let Ok(ip) = endpoint.host.parse::<IpAddr>() {
+            return Ok(vec![SocketAddr::new(ip, endpoint.port)]);
         }
 
         // Otherwise, perform DNS resolution
-        let addrs: Vec<SocketAddr> = tokio::net::lookup_host(&addr_str)
+        self.resolver
+            .resolve(&endpoint.host, endpoint.port)
             .await
             .map_err(|e| {
                 tracing::warn!(
-                    host = %self.config.host,
+                    host = %endpoint.host,
                     error = %e,
                     "DNS resolution failed"
                 );
-                ConnectionError::DnsResolution {
-                    host: self.config.host.clone(),
-                    reason: e.to_string(),
-                }
-            })?
-            .collect();
-
-        addrs.into_iter().next().ok_or_else(|| {
-            tracing::warn!(host = %self.config.host, "No addresses found for host");
-            ConnectionError::DnsResolution {
-                host: self.config.host.clone(),
-                reason: "No addresses found".to_string(),
-            }
-        })
+                e
+            })
     }
 
     /// Configure TCP keepalive on the stream
     ///
     /// This prevents idle connections from being dropped by intermediate
-    /// network devices (firewalls, NAT, etc.).
+    /// network devices (firewalls, NAT, etc.). Keepalive probe tuning and
+    /// `TCP_USER_TIMEOUT` from the socket options are applied here too.
     ///
     /// # Requirements Coverage
     /// - Requirement 1.5: TCP keepalive for idle connections
     fn configure_keepalive(&self, stream: &TcpStream) -> Result<(), ConnectionError> {
         let socket = socket2::SockRef::from(stream);
+        let options = &self.config.socket;
 
-        let keepalive = socket2::TcpKeepalive::new()
-            .with_time(self.config.keepalive_interval);
+        let mut keepalive = socket2::TcpKeepalive::new().with_time(self.config.keepalive_interval);
+        if let Some(interval) = options.keepalive_probe_interval {
+            keepalive = keepalive.with_interval(interval);
+        }
+        #[cfg(not(windows))]
+        if let Some(retries) = options.keepalive_retries {
+            keepalive = keepalive.with_retries(retries);
+        }
 
         socket.set_tcp_keepalive(&keepalive)
             .map_err(|e| {
                 tracing::warn!(error = %e, "Failed to configure TCP keepalive");
-                ConnectionError::Io(e.into())
+                ConnectionError::Io(e)
             })?;
 
+        if let Some(user_timeout) = options.user_timeout {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            socket.set_tcp_user_timeout(Some(user_timeout))?;
+            #[cfg(not(any(target_os = "linux", target_os = "android")))]
+            tracing::warn!(
+                ?user_timeout,
+                "TCP_USER_TIMEOUT is not supported on this platform"
+            );
+        }
+
         Ok(())
     }
 
@@ -314,12 +694,17 @@ impl ConnectionManager {
 
     /// Attempt to reconnect with the configured strategy
     ///
-    /// Uses the default reconnection strategy based on the connection config.
+    /// Uses the custom policy if one was set, otherwise the default
+    /// reconnection strategy based on the connection config.
     ///
     /// # Requirements Coverage
     /// - Requirement 2.1: Automatic reconnection with exponential backoff
     /// - Requirement 2.2: Configurable maximum attempts
     pub async fn reconnect(&self) -> Result<ManagedConnection, ReconnectionError> {
+        if let Some(policy) = &self.reconnection_policy {
+            return self.reconnect_with_policy(policy.as_ref()).await;
+        }
+
         let strategy = ReconnectionStrategy::new(
             self.config.max_reconnect_attempts,
             self.config.reconnect_base_delay,
@@ -341,10 +726,21 @@ impl ConnectionManager {
         &self,
         strategy: &ReconnectionStrategy,
     ) -> Result<ManagedConnection, ReconnectionError> {
+        self.reconnect_with_policy(strategy).await
+    }
+
+    /// Attempt to reconnect with any [`ReconnectionPolicy`]
+    pub async fn reconnect_with_policy<P>(
+        &self,
+        policy: &P,
+    ) -> Result<ManagedConnection, ReconnectionError>
+    where
+        P: ReconnectionPolicy + ?Sized,
+    {
         tracing::info!(
             host = %self.config.host,
             port = %self.config.port,
-            max_attempts = %strategy.max_attempts,
+            max_attempts = ?policy.max_attempts(),
             "Starting reconnection"
         );
 
@@ -352,7 +748,7 @@ impl ConnectionManager {
         let config = self.config.clone();
 
         self.reconnection_controller
-            .reconnect(strategy, || {
+            .reconnect(policy, || {
                 let sm = state_manager.clone();
                 let attempt = self.reconnection_controller.current_attempt();
                 let cfg = config.clone();
@@ -416,6 +812,7 @@ impl ConnectionManager {
 /// Builder for creating ConnectionManager instances
 pub struct ConnectionManagerBuilder {
     config: ConnectionConfig,
+    resolver: Option<Arc<dyn Resolver>>,
 }
 
 impl ConnectionManagerBuilder {
@@ -423,6 +820,7 @@ impl ConnectionManagerBuilder {
     pub fn new(host: impl Into<String>, port: u16) -> Self {
         Self {
             config: ConnectionConfig::new(host, port),
+            resolver: None,
         }
     }
 
@@ -450,9 +848,55 @@ impl ConnectionManagerBuilder {
         self
     }
 
+    /// Set the delay between staggered Happy Eyeballs attempts
+    pub fn happy_eyeballs_delay(mut self, delay: Duration) -> Self {
+        self.config.happy_eyeballs_delay = delay;
+        self
+    }
+
+    /// Set low-level TCP socket options
+    pub fn socket_options(mut self, options: SocketOptions) -> Self {
+        self.config.socket = options;
+        self
+    }
+
+    /// Add a fallback endpoint for the same host
+    pub fn fallback_endpoint(mut self, endpoint: Endpoint) -> Self {
+        self.config.fallback_endpoints.push(endpoint);
+        self
+    }
+
+    /// Set how endpoints are tried
+    pub fn failover(mut self, failover: FailoverMode) -> Self {
+        self.config.failover = failover;
+        self
+    }
+
+    /// Route connections through an outbound proxy
+    pub fn proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.config.proxy = Some(proxy);
+        self
+    }
+
+    /// Set the DNS resolver configuration
+    pub fn resolver_config(mut self, resolver: ResolverConfig) -> Self {
+        self.config.resolver = resolver;
+        self
+    }
+
+    /// Use a custom resolver implementation
+    pub fn resolver(mut self, resolver: Arc<dyn Resolver>) -> Self {
+        self.resolver = Some(resolver);
+        self
+    }
+
     /// Build the ConnectionManager
     pub fn build(self) -> ConnectionManager {
-        ConnectionManager::new(self.config)
+        let manager = ConnectionManager::new(self.config);
+        match self.resolver {
+            Some(resolver) => manager.with_resolver(resolver),
+            None => manager,
+        }
     }
 }
 
@@ -542,6 +986,120 @@ mod tests {
         // The actual uptime test would require a real connection
     }
 
+    #[test]
+    fn interleave_alternates_families() {
+        let v6a: SocketAddr = "[2001:db8::1]:22".parse().unwrap();
+        let v6b: SocketAddr = "[2001:db8::2]:22".parse().unwrap();
+        let v4a: SocketAddr = "192.0.2.1:22".parse().unwrap();
+        let v4b: SocketAddr = "192.0.2.2:22".parse().unwrap();
+
+        let ordered = interleave_families(vec![v6a, v6b, v4a, v4b]);
+        assert_eq!(ordered, vec![v6a, v4a, v6b, v4b]);
+
+        // Preference follows the resolver's first answer
+        let ordered = interleave_families(vec![v4a, v6a, v6b]);
+        assert_eq!(ordered, vec![v4a, v6a, v6b]);
+
+        assert!(interleave_families(Vec::new()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn happy_eyeballs_falls_back_to_working_address() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let good = listener.local_addr().unwrap();
+        let bad: SocketAddr = "127.0.0.1:1".parse().unwrap();
+
+        let manager = ConnectionManagerBuilder::new("127.0.0.1", good.port())
+            .happy_eyeballs_delay(Duration::from_millis(10))
+            .build();
+        let (_stream, addr) = manager.race_connect(vec![bad, good]).await.unwrap();
+        assert_eq!(addr, good);
+        assert_eq!(AddressFamily::of(&addr), AddressFamily::V4);
+    }
+
+    #[derive(Debug)]
+    struct StaticResolver(SocketAddr);
+
+    #[async_trait::async_trait]
+    impl Resolver for StaticResolver {
+        async fn resolve(
+            &self,
+            _host: &str,
+            _port: u16,
+        ) -> Result<Vec<SocketAddr>, ConnectionError> {
+            Ok(vec![self.0])
+        }
+    }
+
+    #[tokio::test]
+    async fn failover_prefers_last_successful_endpoint() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let vpn = Endpoint::new("127.0.0.1", port).with_label("vpn");
+
+        let manager = ConnectionManagerBuilder::new("127.0.0.1", 1)
+            .fallback_endpoint(vpn.clone())
+            .build();
+        let connection = manager.connect().await.unwrap();
+        assert_eq!(connection.endpoint(), &vpn);
+        assert_eq!(manager.active_endpoint(), Some(vpn.clone()));
+        assert_eq!(manager.ordered_endpoints()[0], vpn);
+    }
+
+    #[tokio::test]
+    async fn race_failover_picks_reachable_endpoint() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let manager = ConnectionManagerBuilder::new("127.0.0.1", 1)
+            .fallback_endpoint(Endpoint::new("127.0.0.1", port))
+            .failover(FailoverMode::Race)
+            .happy_eyeballs_delay(Duration::from_millis(10))
+            .build();
+        let connection = manager.connect().await.unwrap();
+        assert_eq!(connection.endpoint().port, port);
+    }
+
+    #[tokio::test]
+    async fn socket_options_are_applied() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let options = SocketOptions::new()
+            .with_recv_buffer_size(256 * 1024)
+            .with_source_address("127.0.0.1".parse().unwrap());
+        let manager = ConnectionManagerBuilder::new("127.0.0.1", addr.port())
+            .socket_options(options)
+            .build();
+        let connection = manager.connect().await.unwrap();
+
+        let local = connection.stream().local_addr().unwrap();
+        assert!(local.ip().is_loopback());
+        let socket = socket2::SockRef::from(connection.stream());
+        assert!(socket.keepalive().unwrap());
+    }
+
+    #[tokio::test]
+    async fn source_address_family_mismatch_is_rejected() {
+        let manager = ConnectionManagerBuilder::new("127.0.0.1", 22)
+            .socket_options(SocketOptions::new().with_source_address("::1".parse().unwrap()))
+            .build();
+        let result = manager.connect().await;
+        assert!(matches!(result, Err(ConnectionError::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn custom_resolver_is_used() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let manager = ConnectionManagerBuilder::new("ssh.internal.corp", addr.port())
+            .resolver(Arc::new(StaticResolver(addr)))
+            .build();
+        let connection = manager.connect().await.unwrap();
+        assert_eq!(connection.remote_addr(), addr);
+    }
+
     #[test]
     fn disconnect_sets_state() {
         let manager = ConnectionManager::new(ConnectionConfig::new("localhost", 22));
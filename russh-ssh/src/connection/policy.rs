@@ -0,0 +1,328 @@
+//! Pluggable reconnection policies
+//!
+//! [`ReconnectionPolicy`] decides how many times and how long to wait
+//! between reconnection attempts. The serializable
+//! [`ReconnectionStrategy`](crate::config::ReconnectionStrategy) is one
+//! implementation; this module adds full-jitter exponential, Fibonacci,
+//! fixed-interval and unlimited policies, plus a per-attempt veto hook.
+//!
+//! # Requirements Coverage
+//! - Requirement 2.1: Automatic reconnection with backoff
+//! - Requirement 2.2: Configurable maximum attempts
+
+use crate::config::ReconnectionStrategy;
+use rand::Rng;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Decides the retry schedule for a reconnection loop
+///
+/// Attempts are numbered from 0 (the immediate first attempt); `delay(n)`
+/// is the wait before retry `n + 1`.
+pub trait ReconnectionPolicy: Send + Sync + std::fmt::Debug {
+    /// Maximum number of attempts, or `None` for unlimited
+    fn max_attempts(&self) -> Option<u32>;
+
+    /// Delay before the retry following failed attempt `attempt`
+    fn delay(&self, attempt: u32) -> Duration;
+
+    /// Called before every retry; returning `false` stops reconnecting
+    fn allow_attempt(&self, _attempt: u32, _last_error: &str) -> bool {
+        true
+    }
+}
+
+impl ReconnectionPolicy for ReconnectionStrategy {
+    fn max_attempts(&self) -> Option<u32> {
+        Some(self.max_attempts)
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        self.delay_for_attempt(attempt)
+    }
+}
+
+impl<P: ReconnectionPolicy + ?Sized> ReconnectionPolicy for Arc<P> {
+    fn max_attempts(&self) -> Option<u32> {
+        (**self).max_attempts()
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        (**self).delay(attempt)
+    }
+
+    fn allow_attempt(&self, attempt: u32, last_error: &str) -> bool {
+        (**self).allow_attempt(attempt, last_error)
+    }
+}
+
+/// Exponential backoff with full jitter
+///
+/// Each delay is drawn uniformly from `[0, min(max_delay, base × 2^n)]`,
+/// which spreads out reconnect storms better than additive jitter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExponentialBackoff {
+    /// Base delay
+    pub base_delay: Duration,
+    /// Upper bound for any single delay
+    pub max_delay: Duration,
+    /// Maximum number of attempts
+    pub max_attempts: u32,
+}
+
+impl ExponentialBackoff {
+    /// Create a new full-jitter exponential policy
+    pub fn new(max_attempts: u32, base_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            base_delay,
+            max_delay,
+            max_attempts,
+        }
+    }
+
+    /// Upper bound of the jitter window for the given attempt
+    pub fn ceiling(&self, attempt: u32) -> Duration {
+        let multiplier = 2u32.saturating_pow(attempt.min(31));
+        self.base_delay
+            .saturating_mul(multiplier)
+            .min(self.max_delay)
+    }
+}
+
+impl ReconnectionPolicy for ExponentialBackoff {
+    fn max_attempts(&self) -> Option<u32> {
+        Some(self.max_attempts)
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        let ceiling = self.ceiling(attempt).as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(0..=ceiling))
+    }
+}
+
+/// Fibonacci backoff (base × 1, 1, 2, 3, 5, 8, …), capped at `max_delay`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FibonacciBackoff {
+    /// Base delay
+    pub base_delay: Duration,
+    /// Upper bound for any single delay
+    pub max_delay: Duration,
+    /// Maximum number of attempts
+    pub max_attempts: u32,
+}
+
+impl FibonacciBackoff {
+    /// Create a new Fibonacci policy
+    pub fn new(max_attempts: u32, base_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            base_delay,
+            max_delay,
+            max_attempts,
+        }
+    }
+}
+
+impl ReconnectionPolicy for FibonacciBackoff {
+    fn max_attempts(&self) -> Option<u32> {
+        Some(self.max_attempts)
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        let (mut prev, mut current) = (0u32, 1u32);
+        for _ in 0..attempt {
+            (prev, current) = (current, prev.saturating_add(current));
+            if self.base_delay.saturating_mul(current) >= self.max_delay {
+                break;
+            }
+        }
+        self.base_delay.saturating_mul(current).min(self.max_delay)
+    }
+}
+
+/// Constant delay between attempts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedInterval {
+    /// Delay between attempts
+    pub interval: Duration,
+    /// Maximum number of attempts
+    pub max_attempts: u32,
+}
+
+impl FixedInterval {
+    /// Create a new fixed-interval policy
+    pub fn new(max_attempts: u32, interval: Duration) -> Self {
+        Self {
+            interval,
+            max_attempts,
+        }
+    }
+}
+
+impl ReconnectionPolicy for FixedInterval {
+    fn max_attempts(&self) -> Option<u32> {
+        Some(self.max_attempts)
+    }
+
+    fn delay(&self, _attempt: u32) -> Duration {
+        self.interval
+    }
+}
+
+/// Retry forever using another policy's delays, capped at `max_delay`
+///
+/// Only cancellation or a veto stops the loop.
+#[derive(Debug, Clone)]
+pub struct Unlimited<P> {
+    /// Policy providing the delay schedule
+    pub inner: P,
+    /// Upper bound for any single delay
+    pub max_delay: Duration,
+}
+
+impl<P: ReconnectionPolicy> Unlimited<P> {
+    /// Retry forever with `inner`'s delays, never waiting longer than `max_delay`
+    pub fn new(inner: P, max_delay: Duration) -> Self {
+        Self { inner, max_delay }
+    }
+}
+
+impl<P: ReconnectionPolicy> ReconnectionPolicy for Unlimited<P> {
+    fn max_attempts(&self) -> Option<u32> {
+        None
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        self.inner.delay(attempt).min(self.max_delay)
+    }
+
+    fn allow_attempt(&self, attempt: u32, last_error: &str) -> bool {
+        self.inner.allow_attempt(attempt, last_error)
+    }
+}
+
+/// Callback deciding whether a retry may proceed
+pub type VetoFn = dyn Fn(u32, &str) -> bool + Send + Sync;
+
+/// Wraps a policy with a user-supplied per-attempt veto
+///
+/// The callback receives the upcoming attempt number and the last error;
+/// returning `false` aborts reconnection (e.g. on authentication failures
+/// that retrying cannot fix).
+#[derive(Clone)]
+pub struct WithVeto<P> {
+    /// Policy providing the schedule
+    pub inner: P,
+    veto: Arc<VetoFn>,
+}
+
+impl<P: ReconnectionPolicy> WithVeto<P> {
+    /// Wrap `inner` with the given veto callback
+    pub fn new<F>(inner: P, veto: F) -> Self
+    where
+        F: Fn(u32, &str) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            inner,
+            veto: Arc::new(veto),
+        }
+    }
+}
+
+impl<P: std::fmt::Debug> std::fmt::Debug for WithVeto<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WithVeto")
+            .field("inner", &self.inner)
+            .field("veto", &"<callback>")
+            .finish()
+    }
+}
+
+impl<P: ReconnectionPolicy> ReconnectionPolicy for WithVeto<P> {
+    fn max_attempts(&self) -> Option<u32> {
+        self.inner.max_attempts()
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        self.inner.delay(attempt)
+    }
+
+    fn allow_attempt(&self, attempt: u32, last_error: &str) -> bool {
+        self.inner.allow_attempt(attempt, last_error) && (self.veto)(attempt, last_error)
+    }
+}
+
+/// Extension methods for composing policies
+pub trait ReconnectionPolicyExt: ReconnectionPolicy + Sized {
+    /// Add a per-attempt veto callback
+    fn with_veto<F>(self, veto: F) -> WithVeto<Self>
+    where
+        F: Fn(u32, &str) -> bool + Send + Sync + 'static,
+    {
+        WithVeto::new(self, veto)
+    }
+
+    /// Retry forever, capping each delay at `max_delay`
+    fn unlimited(self, max_delay: Duration) -> Unlimited<Self> {
+        Unlimited::new(self, max_delay)
+    }
+}
+
+impl<P: ReconnectionPolicy> ReconnectionPolicyExt for P {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_jitter_stays_within_ceiling() {
+        let policy =
+            ExponentialBackoff::new(10, Duration::from_millis(100), Duration::from_secs(2));
+        for attempt in 0..10 {
+            let ceiling = policy.ceiling(attempt);
+            assert!(policy.delay(attempt) <= ceiling);
+        }
+        assert_eq!(policy.ceiling(0), Duration::from_millis(100));
+        assert_eq!(policy.ceiling(3), Duration::from_millis(800));
+        assert_eq!(policy.ceiling(20), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn fibonacci_sequence_is_capped() {
+        let policy = FibonacciBackoff::new(10, Duration::from_secs(1), Duration::from_secs(10));
+        let delays: Vec<u64> = (0..8).map(|n| policy.delay(n).as_secs()).collect();
+        assert_eq!(delays, vec![1, 1, 2, 3, 5, 8, 10, 10]);
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn fixed_interval_is_constant() {
+        let policy = FixedInterval::new(3, Duration::from_millis(500));
+        assert_eq!(policy.delay(0), policy.delay(7));
+        assert_eq!(policy.max_attempts(), Some(3));
+    }
+
+    #[test]
+    fn unlimited_caps_delay() {
+        let policy = FibonacciBackoff::new(3, Duration::from_secs(1), Duration::from_secs(600))
+            .unlimited(Duration::from_secs(30));
+        assert_eq!(policy.max_attempts(), None);
+        assert_eq!(policy.delay(20), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn veto_sees_attempt_and_error() {
+        let policy = FixedInterval::new(5, Duration::from_millis(1))
+            .with_veto(|attempt, error| attempt < 3 && !error.contains("auth"));
+        assert!(policy.allow_attempt(1, "timeout"));
+        assert!(!policy.allow_attempt(3, "timeout"));
+        assert!(!policy.allow_attempt(1, "auth rejected"));
+    }
+
+    #[test]
+    fn strategy_implements_policy() {
+        let strategy = ReconnectionStrategy::new(4, Duration::from_secs(1), Duration::from_secs(8))
+            .without_jitter();
+        assert_eq!(ReconnectionPolicy::max_attempts(&strategy), Some(4));
+        assert_eq!(strategy.delay(2), Duration::from_secs(4));
+    }
+}
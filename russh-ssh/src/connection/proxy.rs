@@ -0,0 +1,407 @@
+//! Outbound proxy support
+//!
+//! Tunnels the TCP connection through an HTTP CONNECT or SOCKS5 proxy,
+//! with optional username/password authentication.
+//!
+//! # Requirements Coverage
+//! - Requirement 1.1: Async SSH connection establishment
+//! - Requirement 1.4: Descriptive error on failure
+
+use crate::error::ConnectionError;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Maximum size of an HTTP CONNECT response header
+const MAX_HTTP_RESPONSE_SIZE: usize = 8192;
+
+/// Proxy protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProxyKind {
+    /// HTTP proxy using the CONNECT method
+    Http,
+    /// SOCKS5 proxy (RFC 1928)
+    Socks5,
+}
+
+/// Proxy credentials
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyAuth {
+    /// Proxy username
+    pub username: String,
+    /// Proxy password
+    pub password: String,
+}
+
+impl std::fmt::Debug for ProxyAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProxyAuth")
+            .field("username", &self.username)
+            .field("password", &"[REDACTED]")
+            .finish()
+    }
+}
+
+/// Outbound proxy configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// Proxy protocol
+    pub kind: ProxyKind,
+    /// Proxy host
+    pub host: String,
+    /// Proxy port
+    pub port: u16,
+    /// Optional proxy credentials
+    pub auth: Option<ProxyAuth>,
+}
+
+impl ProxyConfig {
+    /// Create an HTTP CONNECT proxy configuration
+    pub fn http(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            kind: ProxyKind::Http,
+            host: host.into(),
+            port,
+            auth: None,
+        }
+    }
+
+    /// Create a SOCKS5 proxy configuration
+    pub fn socks5(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            kind: ProxyKind::Socks5,
+            host: host.into(),
+            port,
+            auth: None,
+        }
+    }
+
+    /// Set proxy credentials
+    pub fn with_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.auth = Some(ProxyAuth {
+            username: username.into(),
+            password: password.into(),
+        });
+        self
+    }
+
+    /// Connect to the target through the proxy
+    ///
+    /// Returns a stream that is tunnelled to `target_host:target_port`.
+    pub async fn connect(
+        &self,
+        target_host: &str,
+        target_port: u16,
+    ) -> Result<TcpStream, ConnectionError> {
+        let mut stream = TcpStream::connect((self.host.as_str(), self.port))
+            .await
+            .map_err(|e| {
+                ConnectionError::Proxy(format!(
+                    "Failed to reach proxy {}:{}: {}",
+                    self.host, self.port, e
+                ))
+            })?;
+
+        tracing::debug!(
+            proxy = %format!("{}:{}", self.host, self.port),
+            kind = ?self.kind,
+            target = %format!("{}:{}", target_host, target_port),
+            "Connecting through proxy"
+        );
+
+        match self.kind {
+            ProxyKind::Http => {
+                self.http_connect(&mut stream, target_host, target_port)
+                    .await?
+            }
+            ProxyKind::Socks5 => {
+                self.socks5_connect(&mut stream, target_host, target_port)
+                    .await?
+            }
+        }
+        Ok(stream)
+    }
+
+    /// Perform an HTTP CONNECT handshake
+    async fn http_connect(
+        &self,
+        stream: &mut TcpStream,
+        target_host: &str,
+        target_port: u16,
+    ) -> Result<(), ConnectionError> {
+        let authority = format_authority(target_host, target_port);
+        let mut request = format!(
+            "CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\nProxy-Connection: keep-alive\r\n"
+        );
+        if let Some(auth) = &self.auth {
+            let credentials = base64::Engine::encode(
+                &base64::engine::general_purpose::STANDARD,
+                format!("{}:{}", auth.username, auth.password),
+            );
+            request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", credentials));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        // Read the response header byte by byte so no tunnel data is consumed
+        let mut response = Vec::new();
+        let mut byte = [0u8; 1];
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() >= MAX_HTTP_RESPONSE_SIZE {
+                return Err(ConnectionError::Proxy(
+                    "Proxy response header too large".to_string(),
+                ));
+            }
+            if stream.read(&mut byte).await? == 0 {
+                return Err(ConnectionError::Proxy(
+                    "Proxy closed connection during CONNECT".to_string(),
+                ));
+            }
+            response.push(byte[0]);
+        }
+
+        let response = String::from_utf8_lossy(&response);
+        let status_line = response.lines().next().unwrap_or_default();
+        let status = status_line.split_whitespace().nth(1).unwrap_or_default();
+        if status == "200" {
+            Ok(())
+        } else {
+            Err(ConnectionError::Proxy(format!(
+                "HTTP CONNECT rejected: {}",
+                status_line
+            )))
+        }
+    }
+
+    /// Perform a SOCKS5 handshake (RFC 1928, RFC 1929 for auth)
+    async fn socks5_connect(
+        &self,
+        stream: &mut TcpStream,
+        target_host: &str,
+        target_port: u16,
+    ) -> Result<(), ConnectionError> {
+        // Greeting: offer no-auth, plus username/password when configured
+        let greeting: &[u8] = if self.auth.is_some() {
+            &[0x05, 0x02, 0x00, 0x02]
+        } else {
+            &[0x05, 0x01, 0x00]
+        };
+        stream.write_all(greeting).await?;
+
+        let mut choice = [0u8; 2];
+        stream.read_exact(&mut choice).await?;
+        if choice[0] != 0x05 {
+            return Err(ConnectionError::Proxy("Invalid SOCKS version".to_string()));
+        }
+        match (choice[1], &self.auth) {
+            (0x00, _) => {}
+            (0x02, Some(auth)) => {
+                let username = auth.username.as_bytes();
+                let password = auth.password.as_bytes();
+                if username.len() > 255 || password.len() > 255 {
+                    return Err(ConnectionError::Proxy(
+                        "SOCKS5 credentials too long".to_string(),
+                    ));
+                }
+                let mut request = vec![0x01, username.len() as u8];
+                request.extend_from_slice(username);
+                request.push(password.len() as u8);
+                request.extend_from_slice(password);
+                stream.write_all(&request).await?;
+
+                let mut status = [0u8; 2];
+                stream.read_exact(&mut status).await?;
+                if status[1] != 0x00 {
+                    return Err(ConnectionError::Proxy(
+                        "SOCKS5 authentication failed".to_string(),
+                    ));
+                }
+            }
+            _ => {
+                return Err(ConnectionError::Proxy(
+                    "SOCKS5 proxy offered no acceptable authentication method".to_string(),
+                ))
+            }
+        }
+
+        // CONNECT request; let the proxy resolve host names
+        let mut request = vec![0x05, 0x01, 0x00];
+        match target_host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(0x01);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(0x04);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                let host = target_host.as_bytes();
+                if host.len() > 255 {
+                    return Err(ConnectionError::Proxy(
+                        "Target host name too long for SOCKS5".to_string(),
+                    ));
+                }
+                request.push(0x03);
+                request.push(host.len() as u8);
+                request.extend_from_slice(host);
+            }
+        }
+        request.extend_from_slice(&target_port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).await?;
+        if reply[1] != 0x00 {
+            return Err(ConnectionError::Proxy(format!(
+                "SOCKS5 CONNECT failed: {}",
+                socks5_reply_message(reply[1])
+            )));
+        }
+
+        // Skip the bound address in the reply
+        let addr_len = match reply[3] {
+            0x01 => 4,
+            0x04 => 16,
+            0x03 => {
+                let mut len = [0u8; 1];
+                stream.read_exact(&mut len).await?;
+                len[0] as usize
+            }
+            other => {
+                return Err(ConnectionError::Proxy(format!(
+                    "Invalid SOCKS5 address type {}",
+                    other
+                )))
+            }
+        };
+        let mut bound = vec![0u8; addr_len + 2];
+        stream.read_exact(&mut bound).await?;
+
+        Ok(())
+    }
+}
+
+/// Format `host:port`, bracketing IPv6 literals
+fn format_authority(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// Describe a SOCKS5 reply code
+fn socks5_reply_message(code: u8) -> &'static str {
+    match code {
+        0x01 => "general SOCKS server failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn http_connect_sends_credentials() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 1024];
+            let n = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+
+        let proxy = ProxyConfig::http("127.0.0.1", port).with_auth("alice", "secret");
+        proxy.connect("example.com", 22).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("CONNECT example.com:22 HTTP/1.1\r\n"));
+        assert!(request.contains("Proxy-Authorization: Basic YWxpY2U6c2VjcmV0\r\n"));
+    }
+
+    #[tokio::test]
+    async fn http_connect_rejection_is_reported() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let result = ProxyConfig::http("127.0.0.1", port)
+            .connect("example.com", 22)
+            .await;
+        assert!(matches!(result, Err(ConnectionError::Proxy(msg)) if msg.contains("407")));
+    }
+
+    #[tokio::test]
+    async fn socks5_connect_with_auth() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut greeting = [0u8; 4];
+            socket.read_exact(&mut greeting).await.unwrap();
+            assert_eq!(greeting, [0x05, 0x02, 0x00, 0x02]);
+            socket.write_all(&[0x05, 0x02]).await.unwrap();
+
+            let mut auth = [0u8; 2 + 3 + 1 + 4];
+            socket.read_exact(&mut auth).await.unwrap();
+            assert_eq!(&auth[2..5], b"bob");
+            assert_eq!(&auth[6..], b"hunt");
+            socket.write_all(&[0x01, 0x00]).await.unwrap();
+
+            let mut request = [0u8; 5];
+            socket.read_exact(&mut request).await.unwrap();
+            assert_eq!(&request[..4], &[0x05, 0x01, 0x00, 0x03]);
+            let mut host = vec![0u8; request[4] as usize + 2];
+            socket.read_exact(&mut host).await.unwrap();
+            socket
+                .write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&host[..host.len() - 2]).to_string()
+        });
+
+        let proxy = ProxyConfig::socks5("127.0.0.1", port).with_auth("bob", "hunt");
+        proxy.connect("internal.host", 2222).await.unwrap();
+        assert_eq!(server.await.unwrap(), "internal.host");
+    }
+
+    #[test]
+    fn proxy_auth_debug_redacts_password() {
+        let proxy = ProxyConfig::http("proxy", 3128).with_auth("alice", "secret");
+        let debug = format!("{:?}", proxy);
+        assert!(!debug.contains("secret"));
+    }
+
+    #[test]
+    fn authority_brackets_ipv6() {
+        assert_eq!(format_authority("::1", 22), "[::1]:22");
+        assert_eq!(format_authority("host", 22), "host:22");
+    }
+}
@@ -1,6 +1,6 @@
 //! Reconnection controller implementation
 
-use crate::config::ReconnectionStrategy;
+use crate::connection::policy::ReconnectionPolicy;
 use crate::error::ReconnectionError;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
@@ -20,6 +20,10 @@ impl std::fmt::Display for ReconnectionStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ReconnectionStatus::Idle => write!(f, "Idle"),
+            ReconnectionStatus::InProgress {
+                attempt,
+                max_attempts: 0,
+            } => write!(f, "Reconnecting ({})", attempt),
             ReconnectionStatus::InProgress {
                 attempt,
                 max_attempts,
@@ -90,27 +94,41 @@ impl ReconnectionController {
         self.cancelled.store(false, Ordering::SeqCst);
     }
 
-    pub async fn reconnect<F, Fut, T, E>(
+    /// Run the reconnection loop with the given policy
+    ///
+    /// A `max_attempts` of 0 in [`ReconnectionStatus::InProgress`] means the
+    /// policy is unlimited.
+    pub async fn reconnect<P, F, Fut, T, E>(
         &self,
-        strategy: &ReconnectionStrategy,
+        policy: &P,
         mut connect: F,
     ) -> Result<T, ReconnectionError>
     where
+        P: ReconnectionPolicy + ?Sized,
         F: FnMut() -> Fut,
         Fut: std::future::Future<Output = Result<T, E>>,
         E: std::fmt::Display,
     {
         self.reset();
+        let max_attempts = policy.max_attempts();
         self.max_attempts
-            .store(strategy.max_attempts, Ordering::SeqCst);
+            .store(max_attempts.unwrap_or(0), Ordering::SeqCst);
         let mut last_error = String::new();
-        for attempt in 0..strategy.max_attempts {
+        let mut attempt: u32 = 0;
+        while max_attempts.map_or(true, |max| attempt < max) {
             if self.cancelled.load(Ordering::SeqCst) {
                 return Err(ReconnectionError::Cancelled);
             }
+            if attempt > 0 && !policy.allow_attempt(attempt, &last_error) {
+                self.current_attempt.store(0, Ordering::SeqCst);
+                return Err(ReconnectionError::Vetoed {
+                    attempts: attempt,
+                    last_error,
+                });
+            }
             self.current_attempt.store(attempt + 1, Ordering::SeqCst);
             if attempt > 0 {
-                let delay = strategy.delay_for_attempt(attempt - 1);
+                let delay = policy.delay(attempt - 1);
                 tokio::select! {
                     _ = sleep(delay) => {}
                     _ = self.cancel_notify.notified() => { return Err(ReconnectionError::Cancelled); }
@@ -125,10 +143,11 @@ impl ReconnectionController {
                     last_error = e.to_string();
                 }
             }
+            attempt = attempt.saturating_add(1);
         }
         self.current_attempt.store(0, Ordering::SeqCst);
         Err(ReconnectionError::AttemptsExhausted {
-            attempts: strategy.max_attempts,
+            attempts: attempt,
             last_error,
         })
     }
@@ -161,6 +180,8 @@ impl ReconnectionController {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::ReconnectionStrategy;
+    use crate::connection::policy::{FixedInterval, ReconnectionPolicyExt};
     use std::time::Duration;
 
     #[tokio::test]
@@ -241,6 +262,52 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn veto_stops_reconnection() {
+        let controller = ReconnectionController::new();
+        let policy = FixedInterval::new(10, Duration::from_millis(1))
+            .with_veto(|_, error| !error.contains("denied"));
+        let attempt_count = Arc::new(AtomicU32::new(0));
+        let ac = attempt_count.clone();
+        let result: Result<i32, ReconnectionError> = controller
+            .reconnect(&policy, || {
+                let c = ac.clone();
+                async move {
+                    c.fetch_add(1, Ordering::SeqCst);
+                    Err::<i32, _>("permission denied")
+                }
+            })
+            .await;
+        assert!(matches!(
+            result,
+            Err(ReconnectionError::Vetoed { attempts: 1, .. })
+        ));
+        assert_eq!(attempt_count.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unlimited_policy_keeps_retrying() {
+        let controller = ReconnectionController::new();
+        let policy =
+            FixedInterval::new(1, Duration::from_secs(5)).unlimited(Duration::from_secs(5));
+        let attempt_count = Arc::new(AtomicU32::new(0));
+        let ac = attempt_count.clone();
+        let result: Result<i32, ReconnectionError> = controller
+            .reconnect(&policy, || {
+                let c = ac.clone();
+                async move {
+                    if c.fetch_add(1, Ordering::SeqCst) < 20 {
+                        Err("unreachable")
+                    } else {
+                        Ok(7)
+                    }
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 7);
+        assert_eq!(attempt_count.load(Ordering::SeqCst), 21);
+    }
+
     #[tokio::test]
     async fn try_once_succeeds() {
         let controller = ReconnectionController::new();
@@ -0,0 +1,397 @@
+//! Pluggable DNS resolution
+//!
+//! Host names are resolved through a [`Resolver`] trait object so the
+//! connection manager can use system DNS, a caching layer, or
+//! DNS-over-HTTPS for privacy and split-horizon setups.
+//!
+//! # Requirements Coverage
+//! - Requirement 1.1: Async SSH connection establishment
+//! - Requirement 1.4: Descriptive error on failure
+
+use crate::error::ConnectionError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Default DNS-over-HTTPS endpoint (JSON API)
+pub const DEFAULT_DOH_URL: &str = "https://cloudflare-dns.com/dns-query";
+
+/// DNS record type for IPv4 addresses
+const RECORD_TYPE_A: u16 = 1;
+
+/// DNS record type for IPv6 addresses
+const RECORD_TYPE_AAAA: u16 = 28;
+
+/// Resolves a host name to candidate socket addresses
+#[async_trait]
+pub trait Resolver: Send + Sync + std::fmt::Debug {
+    /// Resolve `host` to all addresses reachable on `port`
+    async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, ConnectionError>;
+}
+
+/// Resolver backed by the operating system (`getaddrinfo`)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemResolver;
+
+impl SystemResolver {
+    /// Create a new system resolver
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Resolver for SystemResolver {
+    async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, ConnectionError> {
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| ConnectionError::DnsResolution {
+                host: host.to_string(),
+                reason: e.to_string(),
+            })?
+            .collect();
+        non_empty(host, addrs)
+    }
+}
+
+/// Cached addresses keyed by (lowercased host, port), with the time they expire
+type ResolverCache = HashMap<(String, u16), (Instant, Vec<SocketAddr>)>;
+
+/// Resolver that caches results of another resolver for a fixed TTL
+#[derive(Debug)]
+pub struct CachingResolver {
+    /// Resolver consulted on cache misses
+    inner: Arc<dyn Resolver>,
+    /// How long entries stay valid
+    ttl: Duration,
+    /// Cached addresses keyed by host and port
+    cache: Mutex<ResolverCache>,
+}
+
+impl CachingResolver {
+    /// Wrap `inner` with a cache of the given TTL
+    pub fn new(inner: Arc<dyn Resolver>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Drop all cached entries
+    pub fn clear(&self) {
+        self.lock_cache().clear();
+    }
+
+    /// Number of cached entries (including expired ones not yet evicted)
+    pub fn len(&self) -> usize {
+        self.lock_cache().len()
+    }
+
+    /// Check whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock_cache(&self) -> std::sync::MutexGuard<'_, ResolverCache> {
+        self.cache.lock().unwrap_or_else(|poisoned| {
+            tracing::warn!("DNS cache lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+}
+
+#[async_trait]
+impl Resolver for CachingResolver {
+    async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, ConnectionError> {
+        let key = (host.to_ascii_lowercase(), port);
+        {
+            let mut cache = self.lock_cache();
+            match cache.get(&key) {
+                Some((expires, addrs)) if *expires > Instant::now() => {
+                    tracing::trace!(host = %host, "DNS cache hit");
+                    return Ok(addrs.clone());
+                }
+                Some(_) => {
+                    cache.remove(&key);
+                }
+                None => {}
+            }
+        }
+
+        let addrs = self.inner.resolve(host, port).await?;
+        self.lock_cache()
+            .insert(key, (Instant::now() + self.ttl, addrs.clone()));
+        Ok(addrs)
+    }
+}
+
+/// DNS-over-HTTPS resolver using the JSON API (`application/dns-json`)
+///
+/// Queries A and AAAA records concurrently.
+#[derive(Debug, Clone)]
+pub struct DohResolver {
+    /// DoH endpoint URL
+    url: String,
+    /// HTTP client
+    client: reqwest::Client,
+}
+
+impl DohResolver {
+    /// Create a resolver for the given DoH endpoint
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Get the DoH endpoint URL
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    async fn query(&self, host: &str, record_type: u16) -> Result<Vec<IpAddr>, ConnectionError> {
+        let error = |reason: String| ConnectionError::DnsResolution {
+            host: host.to_string(),
+            reason,
+        };
+
+        let body = self
+            .client
+            .get(&self.url)
+            .query(&[("name", host), ("type", &record_type.to_string())])
+            .header(reqwest::header::ACCEPT, "application/dns-json")
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| error(format!("DoH request failed: {}", e)))?
+            .text()
+            .await
+            .map_err(|e| error(format!("DoH response unreadable: {}", e)))?;
+
+        parse_doh_response(&body, record_type).map_err(error)
+    }
+}
+
+impl Default for DohResolver {
+    fn default() -> Self {
+        Self::new(DEFAULT_DOH_URL)
+    }
+}
+
+#[async_trait]
+impl Resolver for DohResolver {
+    async fn resolve(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, ConnectionError> {
+        let (v6, v4) = tokio::join!(
+            self.query(host, RECORD_TYPE_AAAA),
+            self.query(host, RECORD_TYPE_A)
+        );
+
+        // One family failing is fine as long as the other produced results
+        let (v6, v4) = match (v6, v4) {
+            (Err(e), Err(_)) => return Err(e),
+            (v6, v4) => (v6.unwrap_or_default(), v4.unwrap_or_default()),
+        };
+
+        let addrs = v6
+            .into_iter()
+            .chain(v4)
+            .map(|ip| SocketAddr::new(ip, port))
+            .collect();
+        non_empty(host, addrs)
+    }
+}
+
+/// JSON body returned by DoH providers
+#[derive(Debug, Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Status")]
+    status: u32,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DohAnswer>,
+}
+
+/// A single answer record
+#[derive(Debug, Deserialize)]
+struct DohAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    data: String,
+}
+
+/// Extract addresses of `record_type` from a DoH JSON response
+///
+/// CNAME and other records in the answer section are skipped.
+fn parse_doh_response(body: &str, record_type: u16) -> Result<Vec<IpAddr>, String> {
+    let response: DohResponse =
+        serde_json::from_str(body).map_err(|e| format!("Invalid DoH response: {}", e))?;
+    if response.status != 0 {
+        return Err(format!("DNS error status {}", response.status));
+    }
+
+    Ok(response
+        .answer
+        .into_iter()
+        .filter(|answer| answer.record_type == record_type)
+        .filter_map(|answer| answer.data.parse().ok())
+        .collect())
+}
+
+/// Fail with a descriptive error when no addresses were found
+fn non_empty(host: &str, addrs: Vec<SocketAddr>) -> Result<Vec<SocketAddr>, ConnectionError> {
+    if addrs.is_empty() {
+        return Err(ConnectionError::DnsResolution {
+            host: host.to_string(),
+            reason: "No addresses found".to_string(),
+        });
+    }
+    Ok(addrs)
+}
+
+/// Which resolver backend to use
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResolverBackend {
+    /// Operating system resolver
+    #[default]
+    System,
+    /// DNS-over-HTTPS against the given endpoint
+    DnsOverHttps {
+        /// DoH endpoint URL
+        url: String,
+    },
+}
+
+/// Serializable resolver configuration for `ConnectionConfig`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolverConfig {
+    /// Resolver backend
+    #[serde(default)]
+    pub backend: ResolverBackend,
+    /// Cache results for this long (no caching if `None`)
+    #[serde(default)]
+    pub cache_ttl: Option<Duration>,
+}
+
+impl ResolverConfig {
+    /// Use the system resolver
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// Use DNS-over-HTTPS against the given endpoint
+    pub fn dns_over_https(url: impl Into<String>) -> Self {
+        Self {
+            backend: ResolverBackend::DnsOverHttps { url: url.into() },
+            cache_ttl: None,
+        }
+    }
+
+    /// Cache results for the given TTL
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Build the configured resolver
+    pub fn build(&self) -> Arc<dyn Resolver> {
+        let resolver: Arc<dyn Resolver> = match &self.backend {
+            ResolverBackend::System => Arc::new(SystemResolver::new()),
+            ResolverBackend::DnsOverHttps { url } => Arc::new(DohResolver::new(url.clone())),
+        };
+
+        match self.cache_ttl {
+            Some(ttl) => Arc::new(CachingResolver::new(resolver, ttl)),
+            None => resolver,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Default)]
+    struct CountingResolver {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Resolver for CountingResolver {
+        async fn resolve(
+            &self,
+            _host: &str,
+            port: u16,
+        ) -> Result<Vec<SocketAddr>, ConnectionError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![SocketAddr::from(([10, 0, 0, 1], port))])
+        }
+    }
+
+    #[tokio::test]
+    async fn system_resolver_resolves_localhost() {
+        let addrs = SystemResolver::new()
+            .resolve("localhost", 22)
+            .await
+            .unwrap();
+        assert!(addrs.iter().all(|addr| addr.port() == 22));
+        assert!(addrs.iter().any(|addr| addr.ip().is_loopback()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn caching_resolver_expires_entries() {
+        let inner = Arc::new(CountingResolver::default());
+        let cache = CachingResolver::new(inner.clone(), Duration::from_secs(60));
+
+        cache.resolve("example.com", 22).await.unwrap();
+        cache.resolve("EXAMPLE.com", 22).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 1);
+
+        // Different port is a separate entry
+        cache.resolve("example.com", 2222).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+
+        tokio::time::advance(Duration::from_secs(61)).await;
+        cache.resolve("example.com", 22).await.unwrap();
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn parse_doh_response_filters_record_type() {
+        let body = r#"{
+            "Status": 0,
+            "Answer": [
+                {"name": "www.example.com", "type": 5, "TTL": 300, "data": "example.com."},
+                {"name": "example.com", "type": 1, "TTL": 300, "data": "93.184.216.34"},
+                {"name": "example.com", "type": 28, "TTL": 300, "data": "2606:2800:220:1::1"}
+            ]
+        }"#;
+
+        let v4 = parse_doh_response(body, RECORD_TYPE_A).unwrap();
+        assert_eq!(v4, vec!["93.184.216.34".parse::<IpAddr>().unwrap()]);
+        let v6 = parse_doh_response(body, RECORD_TYPE_AAAA).unwrap();
+        assert_eq!(v6, vec!["2606:2800:220:1::1".parse::<IpAddr>().unwrap()]);
+    }
+
+    #[test]
+    fn parse_doh_response_reports_nxdomain() {
+        let body = r#"{"Status": 3}"#;
+        assert!(parse_doh_response(body, RECORD_TYPE_A)
+            .unwrap_err()
+            .contains("status 3"));
+    }
+
+    #[test]
+    fn resolver_config_serde_roundtrip() {
+        let config =
+            ResolverConfig::dns_over_https(DEFAULT_DOH_URL).with_cache_ttl(Duration::from_secs(30));
+        let json = serde_json::to_string(&config).unwrap();
+        let parsed: ResolverConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, config);
+    }
+}
@@ -130,6 +130,69 @@ pub struct StateChangeEvent {
     pub new_state: ConnectionState,
 }
 
+/// Connection health as measured by latency probes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum ConnectionHealth {
+    /// No probe has completed yet
+    #[default]
+    Unknown,
+    /// Probes are answered within the latency threshold
+    Healthy {
+        /// Round-trip time of the last probe in milliseconds
+        rtt_ms: u64,
+    },
+    /// Probes are slow or some were missed
+    Degraded {
+        /// Round-trip time of the last answered probe in milliseconds
+        rtt_ms: Option<u64>,
+        /// Consecutive unanswered probes
+        missed_probes: u32,
+    },
+    /// Too many consecutive probes were missed (likely half-open)
+    Unresponsive {
+        /// Consecutive unanswered probes
+        missed_probes: u32,
+    },
+}
+
+impl ConnectionHealth {
+    /// Check if the connection is degraded or unresponsive
+    pub fn is_impaired(&self) -> bool {
+        matches!(
+            self,
+            ConnectionHealth::Degraded { .. } | ConnectionHealth::Unresponsive { .. }
+        )
+    }
+}
+
+impl std::fmt::Display for ConnectionHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectionHealth::Unknown => write!(f, "Unknown"),
+            ConnectionHealth::Healthy { rtt_ms } => write!(f, "Healthy ({} ms)", rtt_ms),
+            ConnectionHealth::Degraded {
+                missed_probes: 0,
+                rtt_ms: Some(rtt_ms),
+            } => write!(f, "Degraded ({} ms)", rtt_ms),
+            ConnectionHealth::Degraded { missed_probes, .. } => {
+                write!(f, "Degraded ({} probes missed)", missed_probes)
+            }
+            ConnectionHealth::Unresponsive { missed_probes } => {
+                write!(f, "Unresponsive ({} probes missed)", missed_probes)
+            }
+        }
+    }
+}
+
+/// Health change event containing old and new health
+#[derive(Debug, Clone)]
+pub struct HealthChangeEvent {
+    /// The previous health
+    pub old_health: ConnectionHealth,
+    /// The new health
+    pub new_health: ConnectionHealth,
+}
+
 /// Manager for connection state with broadcasting capabilities
 ///
 /// Thread-safe state management with proper handling of poisoned locks.
@@ -138,24 +201,27 @@ pub struct StateManager {
     state: std::sync::RwLock<ConnectionState>,
     /// Broadcast sender for state changes
     sender: broadcast::Sender<StateChangeEvent>,
+    /// Current health
+    health: std::sync::RwLock<ConnectionHealth>,
+    /// Broadcast sender for health changes
+    health_sender: broadcast::Sender<HealthChangeEvent>,
 }
 
 impl StateManager {
     /// Create a new state manager starting in Disconnected state
     pub fn new() -> Self {
-        let (sender, _) = broadcast::channel(STATE_CHANNEL_CAPACITY);
-        Self {
-            state: std::sync::RwLock::new(ConnectionState::Disconnected),
-            sender,
-        }
+        Self::with_state(ConnectionState::Disconnected)
     }
 
     /// Create a new state manager with a specific initial state
     pub fn with_state(initial_state: ConnectionState) -> Self {
         let (sender, _) = broadcast::channel(STATE_CHANNEL_CAPACITY);
+        let (health_sender, _) = broadcast::channel(STATE_CHANNEL_CAPACITY);
         Self {
             state: std::sync::RwLock::new(initial_state),
             sender,
+            health: std::sync::RwLock::new(ConnectionHealth::Unknown),
+            health_sender,
         }
     }
 
@@ -238,6 +304,45 @@ impl StateManager {
     pub fn subscriber_count(&self) -> usize {
         self.sender.receiver_count()
     }
+
+    /// Get the current health
+    pub fn health(&self) -> ConnectionHealth {
+        self.health
+            .read()
+            .unwrap_or_else(|poisoned| {
+                tracing::warn!("Health lock was poisoned, recovering");
+                poisoned.into_inner()
+            })
+            .clone()
+    }
+
+    /// Set the health, broadcasting the change to all subscribers
+    ///
+    /// Returns true if the health was changed, false if it was the same.
+    pub fn set_health(&self, new_health: ConnectionHealth) -> bool {
+        let mut health = self.health.write().unwrap_or_else(|poisoned| {
+            tracing::warn!("Health lock was poisoned, recovering");
+            poisoned.into_inner()
+        });
+        if *health == new_health {
+            return false;
+        }
+
+        let old_health = std::mem::replace(&mut *health, new_health.clone());
+        drop(health);
+
+        let _ = self.health_sender.send(HealthChangeEvent {
+            old_health,
+            new_health,
+        });
+
+        true
+    }
+
+    /// Subscribe to health changes
+    pub fn subscribe_health(&self) -> broadcast::Receiver<HealthChangeEvent> {
+        self.health_sender.subscribe()
+    }
 }
 
 impl Default for StateManager {
@@ -401,4 +506,18 @@ mod tests {
         // Setting same state should return false
         assert!(!manager.set_state(ConnectionState::Connecting));
     }
+
+    #[tokio::test]
+    async fn state_manager_health_broadcasting() {
+        let manager = StateManager::new();
+        let mut receiver = manager.subscribe_health();
+        assert_eq!(manager.health(), ConnectionHealth::Unknown);
+
+        assert!(manager.set_health(ConnectionHealth::Healthy { rtt_ms: 20 }));
+        assert!(!manager.set_health(ConnectionHealth::Healthy { rtt_ms: 20 }));
+
+        let event = receiver.recv().await.unwrap();
+        assert_eq!(event.old_health, ConnectionHealth::Unknown);
+        assert_eq!(event.new_health, ConnectionHealth::Healthy { rtt_ms: 20 });
+    }
 }
@@ -3,14 +3,33 @@
 //! This module provides encryption utilities using BLAKE3 and secure encryption.
 //! The encryption layer follows OCKAM's secure channel principles:
 //! - End-to-end encryption using AES-256-GCM
-//! - Mutual authentication between peers
+//! - Mutual authentication between peers using long-term identities, held in
+//!   software or in hardware key stores
+//! - Trust-on-first-use records of peer identities, like `known_hosts`
+//! - Identity expiry and revocation lists for compromised devices
+//! - Single-message channel resumption with one-time tickets
+//! - Optional double-ratchet mode for long-lived channels
 //! - BLAKE3 for high-performance cryptographic hashing
 //! - Zero-knowledge key storage principles
 
 pub mod cipher;
 pub mod hash;
+pub mod identity;
+pub mod key_provider;
+pub mod ratchet;
+pub mod resumption;
+pub mod revocation;
 pub mod secure_channel;
+pub mod stream;
+pub mod trust;
 
 pub use cipher::*;
 pub use hash::*;
+pub use identity::*;
+pub use key_provider::*;
+pub use ratchet::*;
+pub use resumption::*;
+pub use revocation::*;
 pub use secure_channel::*;
+pub use stream::*;
+pub use trust::*;
@@ -1,23 +1,99 @@
 //! Encryption and decryption utilities
 //!
-//! This module provides symmetric encryption using AES-256-GCM via ring.
-//! While the design mentions OCKAM, we use ring for the core encryption
-//! primitives as it provides the same security guarantees.
+//! This module provides symmetric encryption using AES-256-GCM or
+//! ChaCha20-Poly1305 via ring. While the design mentions OCKAM, we use ring
+//! for the core encryption primitives as it provides the same security
+//! guarantees. Key bytes are zeroed when an [`EncryptionKey`] is dropped.
 
 use crate::encryption::hash::{hash_data, ContentHash};
 use crate::error::EncryptionError;
 use ring::aead::{self, Aad, BoundKey, Nonce, NonceSequence, UnboundKey, NONCE_LEN};
 use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
 
 /// Size of the encryption key in bytes (256 bits)
 pub const KEY_SIZE: usize = 32;
 
-/// Size of the nonce in bytes (96 bits for AES-GCM)
+/// Size of the nonce in bytes (96 bits for both supported ciphers)
 pub const NONCE_SIZE: usize = NONCE_LEN;
 
 /// Size of the authentication tag in bytes
 pub const TAG_SIZE: usize = 16;
 
+/// AEAD cipher used to protect messages
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CipherSuite {
+    /// AES-256-GCM, fastest on CPUs with AES instructions
+    #[default]
+    Aes256Gcm,
+    /// ChaCha20-Poly1305, fastest on CPUs without AES instructions
+    ChaCha20Poly1305,
+}
+
+impl CipherSuite {
+    /// All supported cipher suites
+    pub const ALL: [CipherSuite; 2] = [CipherSuite::Aes256Gcm, CipherSuite::ChaCha20Poly1305];
+
+    /// Get the ring AEAD algorithm for this suite
+    pub fn algorithm(self) -> &'static aead::Algorithm {
+        match self {
+            CipherSuite::Aes256Gcm => &aead::AES_256_GCM,
+            CipherSuite::ChaCha20Poly1305 => &aead::CHACHA20_POLY1305,
+        }
+    }
+
+    /// Get the suite name
+    pub fn name(self) -> &'static str {
+        match self {
+            CipherSuite::Aes256Gcm => "aes-256-gcm",
+            CipherSuite::ChaCha20Poly1305 => "chacha20-poly1305",
+        }
+    }
+
+    /// Fastest suite for the current CPU
+    ///
+    /// Picks AES-256-GCM when hardware AES is available and
+    /// ChaCha20-Poly1305 otherwise (e.g. many ARM phones and SBCs).
+    pub fn preferred() -> Self {
+        if has_hardware_aes() {
+            CipherSuite::Aes256Gcm
+        } else {
+            CipherSuite::ChaCha20Poly1305
+        }
+    }
+
+    /// All suites ordered by preference for the current CPU
+    pub fn preference_order() -> Vec<CipherSuite> {
+        let preferred = Self::preferred();
+        let mut suites = vec![preferred];
+        suites.extend(Self::ALL.iter().copied().filter(|s| *s != preferred));
+        suites
+    }
+}
+
+impl std::fmt::Display for CipherSuite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn has_hardware_aes() -> bool {
+    std::is_x86_feature_detected!("aes") && std::is_x86_feature_detected!("pclmulqdq")
+}
+
+#[cfg(target_arch = "aarch64")]
+fn has_hardware_aes() -> bool {
+    std::arch::is_aarch64_feature_detected!("aes")
+        && std::arch::is_aarch64_feature_detected!("pmull")
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+fn has_hardware_aes() -> bool {
+    false
+}
+
 /// Encrypted message wrapper containing ciphertext, nonce, and content hash
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EncryptedMessage {
@@ -88,7 +164,74 @@ impl<'de> serde::Deserialize<'de> for EncryptedMessage {
     }
 }
 
+/// AEAD output whose integrity also covers caller-supplied associated data
+///
+/// Unlike [`EncryptedMessage`] no plaintext hash is carried: the tag already
+/// authenticates the plaintext, and a hash would reveal repeated payloads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealedMessage {
+    /// The encrypted data including authentication tag
+    pub ciphertext: Vec<u8>,
+    /// The nonce used for encryption
+    pub nonce: [u8; NONCE_SIZE],
+}
+
+impl SealedMessage {
+    /// Get the size of the sealed message
+    pub fn size(&self) -> usize {
+        self.ciphertext.len()
+    }
+}
+
+impl serde::Serialize for SealedMessage {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use base64::{engine::general_purpose::STANDARD, Engine as _};
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("SealedMessage", 2)?;
+        state.serialize_field("ciphertext", &STANDARD.encode(&self.ciphertext))?;
+        state.serialize_field("nonce", &STANDARD.encode(self.nonce))?;
+        state.end()
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for SealedMessage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+        #[derive(serde::Deserialize)]
+        struct Helper {
+            ciphertext: String,
+            nonce: String,
+        }
+
+        let helper = Helper::deserialize(deserializer)?;
+        let ciphertext = STANDARD
+            .decode(&helper.ciphertext)
+            .map_err(serde::de::Error::custom)?;
+        let nonce_bytes = STANDARD
+            .decode(&helper.nonce)
+            .map_err(serde::de::Error::custom)?;
+
+        if nonce_bytes.len() != NONCE_SIZE {
+            return Err(serde::de::Error::custom("Invalid nonce length"));
+        }
+
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce.copy_from_slice(&nonce_bytes);
+
+        Ok(SealedMessage { ciphertext, nonce })
+    }
+}
+
 /// Encryption key wrapper
+///
+/// The key bytes are zeroed on drop and never shown by `Debug`.
 #[derive(Clone)]
 pub struct EncryptionKey {
     key_bytes: [u8; KEY_SIZE],
@@ -98,10 +241,10 @@ impl EncryptionKey {
     /// Generate a new random encryption key
     pub fn generate() -> Result<Self, EncryptionError> {
         let rng = SystemRandom::new();
-        let mut key_bytes = [0u8; KEY_SIZE];
-        rng.fill(&mut key_bytes)
+        let mut key = Self::zeroed();
+        rng.fill(&mut key.key_bytes)
             .map_err(|_| EncryptionError::KeyGeneration("Failed to generate random key".into()))?;
-        Ok(Self { key_bytes })
+        Ok(key)
     }
 
     /// Create a key from raw bytes
@@ -109,6 +252,13 @@ impl EncryptionKey {
         Self { key_bytes: bytes }
     }
 
+    /// Key buffer to be filled in place, so no unprotected copy is left behind
+    fn zeroed() -> Self {
+        Self {
+            key_bytes: [0u8; KEY_SIZE],
+        }
+    }
+
     /// Get the raw key bytes
     pub fn as_bytes(&self) -> &[u8; KEY_SIZE] {
         &self.key_bytes
@@ -140,16 +290,16 @@ impl EncryptionKey {
         // SAFETY: ITERATIONS is a non-zero constant
         let iterations = unsafe { std::num::NonZeroU32::new_unchecked(ITERATIONS) };
 
-        let mut key_bytes = [0u8; KEY_SIZE];
+        let mut key = Self::zeroed();
         pbkdf2::derive(
             pbkdf2::PBKDF2_HMAC_SHA256,
             iterations,
             salt,
             password,
-            &mut key_bytes,
+            &mut key.key_bytes,
         );
 
-        Self { key_bytes }
+        key
     }
 
     /// Generate a cryptographically secure random salt for password-based key derivation
@@ -172,9 +322,15 @@ impl EncryptionKey {
         let mut hasher = blake3::Hasher::new_derive_key("russh-ssh encryption key");
         hasher.update(secret);
         hasher.update(context);
-        let mut key_bytes = [0u8; KEY_SIZE];
-        hasher.finalize_xof().fill(&mut key_bytes);
-        Self { key_bytes }
+        let mut key = Self::zeroed();
+        hasher.finalize_xof().fill(&mut key.key_bytes);
+        key
+    }
+}
+
+impl Drop for EncryptionKey {
+    fn drop(&mut self) {
+        self.key_bytes.zeroize();
     }
 }
 
@@ -195,32 +351,80 @@ impl NonceSequence for SingleNonce {
     }
 }
 
+/// Encrypt `data` in place with the given suite and nonce, appending the tag
+pub(crate) fn seal(
+    suite: CipherSuite,
+    key: &EncryptionKey,
+    nonce: [u8; NONCE_SIZE],
+    aad: &[u8],
+    data: &mut Vec<u8>,
+) -> Result<(), EncryptionError> {
+    let unbound_key = UnboundKey::new(suite.algorithm(), key.as_bytes())
+        .map_err(|_| EncryptionError::Encryption("Failed to create encryption key".into()))?;
+
+    let nonce = Nonce::assume_unique_for_key(nonce);
+    let mut sealing_key = aead::SealingKey::new(unbound_key, SingleNonce(Some(nonce)));
+
+    sealing_key
+        .seal_in_place_append_tag(Aad::from(aad), data)
+        .map_err(|_| EncryptionError::Encryption("Encryption failed".into()))
+}
+
+/// Decrypt and authenticate ciphertext with the given suite and nonce
+pub(crate) fn open(
+    suite: CipherSuite,
+    key: &EncryptionKey,
+    nonce: [u8; NONCE_SIZE],
+    aad: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    let unbound_key = UnboundKey::new(suite.algorithm(), key.as_bytes())
+        .map_err(|_| EncryptionError::Decryption)?;
+
+    let nonce = Nonce::assume_unique_for_key(nonce);
+    let mut opening_key = aead::OpeningKey::new(unbound_key, SingleNonce(Some(nonce)));
+
+    let mut plaintext = ciphertext.to_vec();
+    let decrypted_len = opening_key
+        .open_in_place(Aad::from(aad), &mut plaintext)
+        .map_err(|_| EncryptionError::Decryption)?
+        .len();
+    plaintext.truncate(decrypted_len);
+
+    Ok(plaintext)
+}
+
+/// Generate a random nonce
+fn random_nonce() -> Result<[u8; NONCE_SIZE], EncryptionError> {
+    let rng = SystemRandom::new();
+    let mut nonce_bytes = [0u8; NONCE_SIZE];
+    rng.fill(&mut nonce_bytes)
+        .map_err(|_| EncryptionError::Encryption("Failed to generate nonce".into()))?;
+    Ok(nonce_bytes)
+}
+
 /// Encrypt plaintext using AES-256-GCM
 ///
 /// Returns an EncryptedMessage containing the ciphertext, nonce, and plaintext hash.
 pub fn encrypt(key: &EncryptionKey, plaintext: &[u8]) -> Result<EncryptedMessage, EncryptionError> {
-    let rng = SystemRandom::new();
+    encrypt_with(CipherSuite::Aes256Gcm, key, plaintext)
+}
 
+/// Encrypt plaintext using the given cipher suite
+pub fn encrypt_with(
+    suite: CipherSuite,
+    key: &EncryptionKey,
+    plaintext: &[u8],
+) -> Result<EncryptedMessage, EncryptionError> {
     // Generate random nonce
-    let mut nonce_bytes = [0u8; NONCE_SIZE];
-    rng.fill(&mut nonce_bytes)
-        .map_err(|_| EncryptionError::Encryption("Failed to generate nonce".into()))?;
+    let nonce_bytes = random_nonce()?;
 
     // Compute plaintext hash before encryption
     let plaintext_hash = hash_data(plaintext);
 
-    // Create sealing key
-    let unbound_key = UnboundKey::new(&aead::AES_256_GCM, key.as_bytes())
-        .map_err(|_| EncryptionError::Encryption("Failed to create encryption key".into()))?;
-
-    let nonce = Nonce::assume_unique_for_key(nonce_bytes);
-    let mut sealing_key = aead::SealingKey::new(unbound_key, SingleNonce(Some(nonce)));
-
     // Encrypt in place
     let mut ciphertext = plaintext.to_vec();
-    sealing_key
-        .seal_in_place_append_tag(Aad::empty(), &mut ciphertext)
-        .map_err(|_| EncryptionError::Encryption("Encryption failed".into()))?;
+    seal(suite, key, nonce_bytes, &[], &mut ciphertext)?;
 
     Ok(EncryptedMessage {
         ciphertext,
@@ -236,26 +440,67 @@ pub fn decrypt(
     key: &EncryptionKey,
     message: &EncryptedMessage,
 ) -> Result<Vec<u8>, EncryptionError> {
-    // Create opening key
-    let unbound_key = UnboundKey::new(&aead::AES_256_GCM, key.as_bytes())
-        .map_err(|_| EncryptionError::Decryption)?;
-
-    let nonce = Nonce::assume_unique_for_key(message.nonce);
-    let mut opening_key = aead::OpeningKey::new(unbound_key, SingleNonce(Some(nonce)));
+    decrypt_with(CipherSuite::Aes256Gcm, key, message)
+}
 
-    // Decrypt in place
-    let mut plaintext = message.ciphertext.clone();
-    let decrypted = opening_key
-        .open_in_place(Aad::empty(), &mut plaintext)
-        .map_err(|_| EncryptionError::Decryption)?;
+/// Decrypt ciphertext using the given cipher suite
+pub fn decrypt_with(
+    suite: CipherSuite,
+    key: &EncryptionKey,
+    message: &EncryptedMessage,
+) -> Result<Vec<u8>, EncryptionError> {
+    let decrypted = open(suite, key, message.nonce, &[], &message.ciphertext)?;
 
     // Verify plaintext hash
-    let computed_hash = hash_data(decrypted);
+    let computed_hash = hash_data(&decrypted);
     if computed_hash != message.plaintext_hash {
         return Err(EncryptionError::AuthenticationFailed);
     }
 
-    Ok(decrypted.to_vec())
+    Ok(decrypted)
+}
+
+/// Encrypt plaintext, authenticating `aad` alongside it
+///
+/// The associated data is not encrypted or transmitted; the receiver must
+/// supply the same bytes to [`decrypt_with_aad`] or decryption fails.
+pub fn encrypt_with_aad(
+    suite: CipherSuite,
+    key: &EncryptionKey,
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<SealedMessage, EncryptionError> {
+    let nonce = random_nonce()?;
+    let mut ciphertext = plaintext.to_vec();
+    seal(suite, key, nonce, aad, &mut ciphertext)?;
+    Ok(SealedMessage { ciphertext, nonce })
+}
+
+/// Encrypt plaintext with a caller-chosen nonce, authenticating `aad`
+///
+/// Only for keys that are used for a single plaintext (e.g. keys derived
+/// from the content itself); reusing a nonce with the same key for
+/// different plaintexts breaks confidentiality.
+pub fn encrypt_with_nonce(
+    suite: CipherSuite,
+    key: &EncryptionKey,
+    nonce: [u8; NONCE_SIZE],
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<SealedMessage, EncryptionError> {
+    let mut ciphertext = plaintext.to_vec();
+    seal(suite, key, nonce, aad, &mut ciphertext)?;
+    Ok(SealedMessage { ciphertext, nonce })
+}
+
+/// Decrypt a sealed message, verifying the associated data
+pub fn decrypt_with_aad(
+    suite: CipherSuite,
+    key: &EncryptionKey,
+    message: &SealedMessage,
+    aad: &[u8],
+) -> Result<Vec<u8>, EncryptionError> {
+    open(suite, key, message.nonce, aad, &message.ciphertext)
 }
 
 /// Encrypt plaintext and return only the ciphertext bytes (without metadata)
@@ -264,17 +509,8 @@ pub fn encrypt_raw(
     nonce: &[u8; NONCE_SIZE],
     plaintext: &[u8],
 ) -> Result<Vec<u8>, EncryptionError> {
-    let unbound_key = UnboundKey::new(&aead::AES_256_GCM, key.as_bytes())
-        .map_err(|_| EncryptionError::Encryption("Failed to create encryption key".into()))?;
-
-    let nonce = Nonce::assume_unique_for_key(*nonce);
-    let mut sealing_key = aead::SealingKey::new(unbound_key, SingleNonce(Some(nonce)));
-
     let mut ciphertext = plaintext.to_vec();
-    sealing_key
-        .seal_in_place_append_tag(Aad::empty(), &mut ciphertext)
-        .map_err(|_| EncryptionError::Encryption("Encryption failed".into()))?;
-
+    seal(CipherSuite::Aes256Gcm, key, *nonce, &[], &mut ciphertext)?;
     Ok(ciphertext)
 }
 
@@ -284,18 +520,7 @@ pub fn decrypt_raw(
     nonce: &[u8; NONCE_SIZE],
     ciphertext: &[u8],
 ) -> Result<Vec<u8>, EncryptionError> {
-    let unbound_key = UnboundKey::new(&aead::AES_256_GCM, key.as_bytes())
-        .map_err(|_| EncryptionError::Decryption)?;
-
-    let nonce = Nonce::assume_unique_for_key(*nonce);
-    let mut opening_key = aead::OpeningKey::new(unbound_key, SingleNonce(Some(nonce)));
-
-    let mut plaintext = ciphertext.to_vec();
-    let decrypted = opening_key
-        .open_in_place(Aad::empty(), &mut plaintext)
-        .map_err(|_| EncryptionError::Decryption)?;
-
-    Ok(decrypted.to_vec())
+    open(CipherSuite::Aes256Gcm, key, *nonce, &[], ciphertext)
 }
 
 #[cfg(test)]
@@ -324,6 +549,42 @@ mod tests {
         assert_eq!(plaintext.as_slice(), decrypted.as_slice());
     }
 
+    #[test]
+    fn chacha20_poly1305_roundtrip() {
+        let key = EncryptionKey::generate().unwrap();
+        let plaintext = b"Hello from ARM";
+
+        let encrypted = encrypt_with(CipherSuite::ChaCha20Poly1305, &key, plaintext).unwrap();
+        let decrypted = decrypt_with(CipherSuite::ChaCha20Poly1305, &key, &encrypted).unwrap();
+        assert_eq!(plaintext.as_slice(), decrypted.as_slice());
+
+        // The suites are not interchangeable
+        assert!(decrypt_with(CipherSuite::Aes256Gcm, &key, &encrypted).is_err());
+    }
+
+    #[test]
+    fn aad_must_match() {
+        let key = EncryptionKey::generate().unwrap();
+        let sealed =
+            encrypt_with_aad(CipherSuite::Aes256Gcm, &key, b"payload", b"header v1").unwrap();
+
+        let opened = decrypt_with_aad(CipherSuite::Aes256Gcm, &key, &sealed, b"header v1").unwrap();
+        assert_eq!(opened, b"payload".to_vec());
+
+        let result = decrypt_with_aad(CipherSuite::Aes256Gcm, &key, &sealed, b"header v2");
+        assert!(matches!(result, Err(EncryptionError::Decryption)));
+    }
+
+    #[test]
+    fn preference_order_lists_every_suite_once() {
+        let order = CipherSuite::preference_order();
+        assert_eq!(order[0], CipherSuite::preferred());
+        assert_eq!(order.len(), CipherSuite::ALL.len());
+        for suite in CipherSuite::ALL {
+            assert!(order.contains(&suite));
+        }
+    }
+
     #[test]
     fn wrong_key_fails() {
         let key1 = EncryptionKey::generate().unwrap();
@@ -378,6 +639,23 @@ mod tests {
         let _ = EncryptionKey::from_password(password, short_salt);
     }
 
+    #[test]
+    fn key_debug_is_redacted() {
+        let key = EncryptionKey::from_bytes([0xAB; KEY_SIZE]);
+        let debug = format!("{:?}", key);
+        assert!(debug.contains("REDACTED"));
+        assert!(!debug.contains("171"));
+        assert!(!debug.to_lowercase().contains("abab"));
+    }
+
+    #[test]
+    fn key_bytes_are_zeroed_on_drop() {
+        let mut key = std::mem::ManuallyDrop::new(EncryptionKey::from_bytes([0xAB; KEY_SIZE]));
+        // SAFETY: the key is not used as a value again, only its bytes are read
+        unsafe { std::ptr::drop_in_place(&mut *key) };
+        assert_eq!(key.key_bytes, [0u8; KEY_SIZE]);
+    }
+
     #[test]
     fn generate_salt_produces_unique_values() {
         let salt1 = EncryptionKey::generate_salt().unwrap();
@@ -0,0 +1,316 @@
+//! Long-term identity keys
+//!
+//! Secure channel peers are identified by a persistent Ed25519 key instead
+//! of their per-session X25519 key. During the handshake each side signs its
+//! ephemeral key with its identity key, so a peer's identifier stays the
+//! same across sessions and cannot be claimed without the private key.
+//!
+//! Identity keys are stored as PKCS#8 PEM files, readable only by the owner
+//! on Unix. An optional expiry is kept as an `Expires:` header line. Keys held by hardware stores use ECDSA P-256 instead; see
+//! [`KeyProvider`](crate::encryption::KeyProvider).
+//!
+//! # Requirements Coverage
+//! - Requirement 4.1: End-to-end encryption between peers
+//! - Requirement 4.2: Mutual authentication between peers
+
+use crate::encryption::secure_channel::{Identity, PUBLIC_KEY_SIZE};
+use crate::error::EncryptionError;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::{DateTime, Utc};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair as _};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Size of an Ed25519 signature in bytes
+pub const SIGNATURE_SIZE: usize = 64;
+
+/// File name of the local identity inside the configuration directory
+pub const IDENTITY_FILE_NAME: &str = "identity.pem";
+
+/// PEM label used for identity key files
+const PEM_LABEL: &str = "PRIVATE KEY";
+
+/// Header line prefix recording the identity expiry
+const EXPIRES_HEADER: &str = "Expires:";
+
+/// Signature algorithm of an identity key
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SignatureAlgorithm {
+    /// Ed25519, used for software identities
+    #[default]
+    Ed25519,
+    /// ECDSA over P-256 with SHA-256 (ASN.1 DER signatures), used by TPMs,
+    /// the Secure Enclave and most OS keystores
+    EcdsaP256,
+}
+
+impl SignatureAlgorithm {
+    /// Length of an encoded public key in bytes
+    ///
+    /// P-256 keys use the uncompressed SEC1 encoding.
+    pub fn public_key_len(&self) -> usize {
+        match self {
+            Self::Ed25519 => PUBLIC_KEY_SIZE,
+            Self::EcdsaP256 => 65,
+        }
+    }
+
+    /// Get the algorithm name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ed25519 => "ed25519",
+            Self::EcdsaP256 => "ecdsa-p256",
+        }
+    }
+}
+
+impl std::fmt::Display for SignatureAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A long-term Ed25519 identity key pair
+pub struct IdentityKeyPair {
+    /// The signing key
+    key_pair: Ed25519KeyPair,
+    /// PKCS#8 encoding of the key, kept for persistence
+    pkcs8: Vec<u8>,
+    /// Public identity derived from the signing key
+    identity: Identity,
+}
+
+impl IdentityKeyPair {
+    /// Generate a new random identity key pair
+    pub fn generate() -> Result<Self, EncryptionError> {
+        let rng = SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).map_err(|_| {
+            EncryptionError::KeyGeneration("Failed to generate Ed25519 identity key".into())
+        })?;
+        Self::from_pkcs8(pkcs8.as_ref())
+    }
+
+    /// Load an identity key pair from a PKCS#8 document
+    pub fn from_pkcs8(pkcs8: &[u8]) -> Result<Self, EncryptionError> {
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8).map_err(|e| {
+            EncryptionError::InvalidKeyFormat(format!("Invalid Ed25519 identity key: {}", e))
+        })?;
+
+        let mut public_key = [0u8; PUBLIC_KEY_SIZE];
+        public_key.copy_from_slice(key_pair.public_key().as_ref());
+
+        Ok(Self {
+            key_pair,
+            pkcs8: pkcs8.to_vec(),
+            identity: Identity::from_public_key(public_key),
+        })
+    }
+
+    /// Get the public identity
+    pub fn identity(&self) -> &Identity {
+        &self.identity
+    }
+
+    /// Set a time after which peers refuse this identity
+    ///
+    /// This changes the identity's fingerprint, so peers that pinned the
+    /// previous one will see it as a new identity.
+    pub fn with_expiry(mut self, expires_at: DateTime<Utc>) -> Self {
+        self.identity = self.identity.with_expiry(expires_at);
+        self
+    }
+
+    /// Sign a message with the identity key
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        self.key_pair.sign(message).as_ref().to_vec()
+    }
+
+    /// Default location of the local identity file
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("russh").join(IDENTITY_FILE_NAME))
+    }
+
+    /// Load an identity key pair from a PEM file
+    pub fn load(path: &Path) -> Result<Self, EncryptionError> {
+        let pem = std::fs::read_to_string(path).map_err(|e| {
+            EncryptionError::KeyStorage(format!("Failed to read {}: {}", path.display(), e))
+        })?;
+        let key_pair = Self::from_pkcs8(&decode_pem(&pem)?)?;
+        Ok(match decode_expiry(&pem)? {
+            Some(expires_at) => key_pair.with_expiry(expires_at),
+            None => key_pair,
+        })
+    }
+
+    /// Save the identity key pair as a PEM file, creating parent directories
+    pub fn save(&self, path: &Path) -> Result<(), EncryptionError> {
+        let storage_error =
+            |e: std::io::Error| EncryptionError::KeyStorage(format!("{}: {}", path.display(), e));
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(storage_error)?;
+        }
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600); // rw-------
+        }
+
+        let mut contents = String::new();
+        if let Some(expires_at) = self.identity.expires_at {
+            contents.push_str(&format!("{} {}\n", EXPIRES_HEADER, expires_at.to_rfc3339()));
+        }
+        contents.push_str(&encode_pem(&self.pkcs8));
+
+        let mut file = options.open(path).map_err(storage_error)?;
+        file.write_all(contents.as_bytes()).map_err(storage_error)?;
+        file.sync_all().map_err(storage_error)
+    }
+
+    /// Load the identity at `path`, generating and saving a new one if absent
+    pub fn load_or_create(path: &Path) -> Result<Self, EncryptionError> {
+        if path.exists() {
+            return Self::load(path);
+        }
+
+        let key_pair = Self::generate()?;
+        key_pair.save(path)?;
+        tracing::info!(
+            path = %path.display(),
+            identity = %key_pair.identity.identifier_hex(),
+            "Created new local identity"
+        );
+        Ok(key_pair)
+    }
+}
+
+impl std::fmt::Debug for IdentityKeyPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IdentityKeyPair")
+            .field("identity", &self.identity)
+            .finish()
+    }
+}
+
+fn encode_pem(der: &[u8]) -> String {
+    let encoded = STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", PEM_LABEL);
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(&String::from_utf8_lossy(line));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", PEM_LABEL));
+    pem
+}
+
+fn decode_pem(pem: &str) -> Result<Vec<u8>, EncryptionError> {
+    let begin = format!("-----BEGIN {}-----", PEM_LABEL);
+    let end = format!("-----END {}-----", PEM_LABEL);
+
+    let body = pem
+        .split_once(begin.as_str())
+        .and_then(|(_, rest)| rest.split_once(end.as_str()))
+        .map(|(body, _)| body)
+        .ok_or_else(|| EncryptionError::InvalidKeyFormat("Missing PEM private key block".into()))?;
+
+    let encoded: String = body.split_whitespace().collect();
+    STANDARD
+        .decode(encoded)
+        .map_err(|e| EncryptionError::InvalidKeyFormat(format!("Invalid PEM encoding: {}", e)))
+}
+
+fn decode_expiry(pem: &str) -> Result<Option<DateTime<Utc>>, EncryptionError> {
+    let header = pem
+        .lines()
+        .take_while(|line| !line.starts_with("-----BEGIN"))
+        .find_map(|line| line.strip_prefix(EXPIRES_HEADER));
+
+    header
+        .map(|value| {
+            DateTime::parse_from_rfc3339(value.trim())
+                .map(|expires_at| expires_at.with_timezone(&Utc))
+                .map_err(|e| EncryptionError::InvalidKeyFormat(format!("Invalid expiry: {}", e)))
+        })
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_verify_against_identity() {
+        let key_pair = IdentityKeyPair::generate().unwrap();
+        let signature = key_pair.sign(b"ephemeral key");
+
+        assert_eq!(signature.len(), SIGNATURE_SIZE);
+        assert!(key_pair
+            .identity()
+            .verify(b"ephemeral key", &signature)
+            .is_ok());
+        assert!(key_pair
+            .identity()
+            .verify(b"other key", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn load_or_create_persists_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join(IDENTITY_FILE_NAME);
+
+        let created = IdentityKeyPair::load_or_create(&path).unwrap();
+        let loaded = IdentityKeyPair::load_or_create(&path).unwrap();
+        assert_eq!(created.identity().identifier, loaded.identity().identifier);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn expiry_is_persisted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(IDENTITY_FILE_NAME);
+        let expires_at = DateTime::parse_from_rfc3339("2031-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        let key_pair = IdentityKeyPair::generate().unwrap().with_expiry(expires_at);
+        key_pair.save(&path).unwrap();
+
+        let loaded = IdentityKeyPair::load(&path).unwrap();
+        assert_eq!(loaded.identity().expires_at, Some(expires_at));
+        assert_eq!(loaded.identity().identifier, key_pair.identity().identifier);
+        assert!(!loaded.identity().is_expired());
+    }
+
+    #[test]
+    fn rejects_corrupt_identity_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(IDENTITY_FILE_NAME);
+        std::fs::write(&path, "not a key").unwrap();
+
+        assert!(matches!(
+            IdentityKeyPair::load(&path),
+            Err(EncryptionError::InvalidKeyFormat(_))
+        ));
+    }
+
+    #[test]
+    fn debug_does_not_leak_private_key() {
+        let key_pair = IdentityKeyPair::generate().unwrap();
+        let debug = format!("{:?}", key_pair);
+        assert!(!debug.contains(&STANDARD.encode(&key_pair.pkcs8)));
+        assert!(debug.contains(&key_pair.identity().identifier_hex()));
+    }
+}
@@ -0,0 +1,302 @@
+//! Pluggable identity key storage
+//!
+//! Handshake signing goes through the [`KeyProvider`] trait so the private
+//! identity key can live outside process memory: in a TPM, the macOS Secure
+//! Enclave, Windows CNG or another OS keystore. Platform backends implement
+//! [`HardwareKeyStore`] and are tried in order by [`KeyProviderSelector`],
+//! which falls back to a software [`IdentityKeyPair`] on disk unless hardware
+//! is required by policy.
+//!
+//! # Requirements Coverage
+//! - Requirement 4.2: Mutual authentication between peers
+
+use crate::encryption::identity::IdentityKeyPair;
+use crate::encryption::secure_channel::Identity;
+use crate::error::EncryptionError;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Label under which hardware stores keep the local identity key
+pub const DEFAULT_KEY_LABEL: &str = "russh-identity";
+
+/// Where an identity's private key is held
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyStorageKind {
+    /// In process memory, persisted as a PEM file
+    #[default]
+    Software,
+    /// A TPM 2.0 device
+    Tpm,
+    /// The macOS / iOS Secure Enclave
+    SecureEnclave,
+    /// Windows CNG (Platform Crypto Provider)
+    WindowsCng,
+    /// Another OS keystore (e.g. Android Keystore)
+    OsKeystore,
+}
+
+impl KeyStorageKind {
+    /// Check whether the private key never enters process memory
+    pub fn is_hardware_backed(&self) -> bool {
+        !matches!(self, Self::Software)
+    }
+}
+
+impl std::fmt::Display for KeyStorageKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Software => "software",
+            Self::Tpm => "tpm",
+            Self::SecureEnclave => "secure-enclave",
+            Self::WindowsCng => "windows-cng",
+            Self::OsKeystore => "os-keystore",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A signing key for the local identity
+///
+/// Signatures must verify with [`Identity::verify`] for the provider's
+/// identity, i.e. use the identity's [`SignatureAlgorithm`](crate::encryption::SignatureAlgorithm).
+pub trait KeyProvider: Send + Sync + std::fmt::Debug {
+    /// Get the public identity of the key
+    fn identity(&self) -> &Identity;
+
+    /// Sign a message with the identity key
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, EncryptionError>;
+
+    /// Where the private key is held
+    fn storage(&self) -> KeyStorageKind {
+        KeyStorageKind::Software
+    }
+}
+
+impl KeyProvider for IdentityKeyPair {
+    fn identity(&self) -> &Identity {
+        IdentityKeyPair::identity(self)
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        Ok(IdentityKeyPair::sign(self, message))
+    }
+}
+
+/// A platform key store able to hold identity keys
+pub trait HardwareKeyStore: Send + Sync {
+    /// The kind of storage this store provides
+    fn kind(&self) -> KeyStorageKind;
+
+    /// Check whether the store is usable on this machine
+    fn is_available(&self) -> bool;
+
+    /// Open the key stored under `label`, creating it if absent
+    fn load_or_create(&self, label: &str) -> Result<Arc<dyn KeyProvider>, EncryptionError>;
+}
+
+/// Chooses where the local identity key lives
+pub struct KeyProviderSelector {
+    /// Label used for hardware-held keys
+    label: String,
+    /// Hardware stores, in order of preference
+    stores: Vec<Arc<dyn HardwareKeyStore>>,
+    /// Location of the software fallback key
+    fallback_path: Option<PathBuf>,
+    /// Refuse to fall back to a software key
+    require_hardware: bool,
+}
+
+impl KeyProviderSelector {
+    /// Create a selector with no hardware stores
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            stores: Vec::new(),
+            fallback_path: None,
+            require_hardware: false,
+        }
+    }
+
+    /// Add a hardware store; earlier stores are preferred
+    pub fn with_store(mut self, store: Arc<dyn HardwareKeyStore>) -> Self {
+        self.stores.push(store);
+        self
+    }
+
+    /// Set the software fallback key file (defaults to [`IdentityKeyPair::default_path`])
+    pub fn with_fallback_path(mut self, path: PathBuf) -> Self {
+        self.fallback_path = Some(path);
+        self
+    }
+
+    /// Fail instead of falling back to a software key
+    pub fn require_hardware(mut self, required: bool) -> Self {
+        self.require_hardware = required;
+        self
+    }
+
+    /// Open the identity key from the first usable store
+    pub fn select(&self) -> Result<Arc<dyn KeyProvider>, EncryptionError> {
+        for store in self.stores.iter().filter(|store| store.is_available()) {
+            match store.load_or_create(&self.label) {
+                Ok(provider) => {
+                    tracing::info!(
+                        storage = %store.kind(),
+                        identity = %provider.identity().identifier_hex(),
+                        "Using hardware-backed identity"
+                    );
+                    return Ok(provider);
+                }
+                Err(e) => {
+                    tracing::warn!(storage = %store.kind(), error = %e, "Key store unusable");
+                }
+            }
+        }
+
+        if self.require_hardware {
+            return Err(EncryptionError::KeyStorage(
+                "No hardware key store available".into(),
+            ));
+        }
+
+        let path = self
+            .fallback_path
+            .clone()
+            .or_else(IdentityKeyPair::default_path)
+            .ok_or_else(|| {
+                EncryptionError::KeyStorage("No location for software identity key".into())
+            })?;
+        Ok(Arc::new(IdentityKeyPair::load_or_create(&path)?))
+    }
+}
+
+impl Default for KeyProviderSelector {
+    fn default() -> Self {
+        Self::new(DEFAULT_KEY_LABEL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::identity::SignatureAlgorithm;
+    use crate::encryption::secure_channel::SecureChannelBuilder;
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair as _, ECDSA_P256_SHA256_ASN1_SIGNING};
+
+    /// P-256 key standing in for a key held by a TPM
+    #[derive(Debug)]
+    struct FakeTpmKey {
+        key_pair: EcdsaKeyPair,
+        identity: Identity,
+    }
+
+    impl KeyProvider for FakeTpmKey {
+        fn identity(&self) -> &Identity {
+            &self.identity
+        }
+
+        fn sign(&self, message: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+            self.key_pair
+                .sign(&SystemRandom::new(), message)
+                .map(|signature| signature.as_ref().to_vec())
+                .map_err(|_| EncryptionError::Encryption("TPM signing failed".into()))
+        }
+
+        fn storage(&self) -> KeyStorageKind {
+            KeyStorageKind::Tpm
+        }
+    }
+
+    struct FakeTpm {
+        available: bool,
+    }
+
+    impl HardwareKeyStore for FakeTpm {
+        fn kind(&self) -> KeyStorageKind {
+            KeyStorageKind::Tpm
+        }
+
+        fn is_available(&self) -> bool {
+            self.available
+        }
+
+        fn load_or_create(&self, _label: &str) -> Result<Arc<dyn KeyProvider>, EncryptionError> {
+            let rng = SystemRandom::new();
+            let pkcs8 =
+                EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+            let key_pair =
+                EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng)
+                    .unwrap();
+            let identity = Identity::new(
+                SignatureAlgorithm::EcdsaP256,
+                key_pair.public_key().as_ref().to_vec(),
+            )?;
+            Ok(Arc::new(FakeTpmKey { key_pair, identity }))
+        }
+    }
+
+    #[test]
+    fn falls_back_to_software_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("identity.pem");
+        let selector = KeyProviderSelector::default()
+            .with_store(Arc::new(FakeTpm { available: false }))
+            .with_fallback_path(path.clone());
+
+        let provider = selector.select().unwrap();
+        assert_eq!(provider.storage(), KeyStorageKind::Software);
+        assert!(path.exists());
+        assert_eq!(
+            selector.select().unwrap().identity().identifier,
+            provider.identity().identifier
+        );
+    }
+
+    #[test]
+    fn prefers_available_hardware() {
+        let provider = KeyProviderSelector::default()
+            .with_store(Arc::new(FakeTpm { available: true }))
+            .select()
+            .unwrap();
+
+        assert!(provider.storage().is_hardware_backed());
+        assert_eq!(provider.identity().algorithm, SignatureAlgorithm::EcdsaP256);
+    }
+
+    #[test]
+    fn required_hardware_does_not_fall_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = KeyProviderSelector::default()
+            .with_store(Arc::new(FakeTpm { available: false }))
+            .with_fallback_path(dir.path().join("identity.pem"))
+            .require_hardware(true)
+            .select();
+
+        assert!(matches!(result, Err(EncryptionError::KeyStorage(_))));
+    }
+
+    #[test]
+    fn hardware_identity_establishes_channel() {
+        let tpm_key = FakeTpm { available: true }.load_or_create("test").unwrap();
+        let initiator = SecureChannelBuilder::with_identity(tpm_key.clone()).unwrap();
+        let responder = SecureChannelBuilder::new().unwrap();
+
+        let (responder_channel, response) = responder
+            .process_init(initiator.create_init_message())
+            .unwrap();
+        let initiator_channel = initiator.process_response(response).unwrap();
+
+        assert_eq!(
+            responder_channel.peer_identity().identifier,
+            tpm_key.identity().identifier
+        );
+        let encrypted = initiator_channel.encrypt(b"from the tpm").unwrap();
+        assert_eq!(
+            responder_channel.decrypt(&encrypted).unwrap(),
+            b"from the tpm".to_vec()
+        );
+    }
+}
@@ -0,0 +1,631 @@
+//! Double-ratchet mode for long-lived secure channels
+//!
+//! A [`RatchetChannel`] takes over an established [`SecureChannel`] and
+//! layers two ratchets on its derived keys:
+//!
+//! - A symmetric ratchet: every message is sealed with a fresh key taken
+//!   from a per-direction KDF chain, and the chain moves forward, so old
+//!   message keys cannot be recomputed from the current state.
+//! - A Diffie-Hellman ratchet: every [`DEFAULT_DH_INTERVAL`] messages the
+//!   initiator proposes a new X25519 key, the responder answers with its
+//!   own, and both mix the new shared secret into the root key. A leaked
+//!   chain key therefore stops being useful after the next step.
+//!
+//! Only the initiator proposes steps, which avoids crossed proposals. The
+//! responder's answer carries a proof keyed by the current root, so an
+//! injected answer cannot make the initiator discard its pending key.
+//! Messages may arrive out of order; keys for skipped messages are kept
+//! (up to [`MAX_SKIPPED_KEYS`]) and deleted once used. Root and chain keys
+//! are zeroed when the channel is dropped.
+//!
+//! # Requirements Coverage
+//! - Requirement 4.1: End-to-end encryption between peers
+
+use crate::encryption::cipher::{
+    decrypt_with_aad, encrypt_with_aad, CipherSuite, EncryptionKey, SealedMessage,
+};
+use crate::encryption::hash::ContentHash;
+use crate::encryption::secure_channel::{
+    ChannelRole, Identity, KeyPair, SecureChannel, PUBLIC_KEY_SIZE,
+};
+use crate::error::EncryptionError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Messages (in either direction) between Diffie-Hellman ratchet steps
+pub const DEFAULT_DH_INTERVAL: u64 = 100;
+
+/// Maximum number of stored keys for skipped messages
+pub const MAX_SKIPPED_KEYS: usize = 1000;
+
+/// Key derivation context for the initial root and chain keys
+const INIT_CONTEXT: &str = "russh-ssh ratchet init v1";
+
+/// Key derivation context for Diffie-Hellman ratchet steps
+const ROOT_CONTEXT: &str = "russh-ssh ratchet root v1";
+
+/// Domain separator for ratchet message associated data
+const MESSAGE_AAD_CONTEXT: &[u8] = b"russh-ssh ratchet message v1";
+
+/// Domain separator for the responder's step proof
+const PROOF_CONTEXT: &[u8] = b"russh-ssh ratchet proof v1";
+
+/// A ratchet public key carried in a message header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RatchetKey {
+    /// X25519 public key for the next Diffie-Hellman step
+    pub public_key: [u8; PUBLIC_KEY_SIZE],
+    /// Responder's proof binding its key to the initiator's proposal
+    pub proof: Option<[u8; 32]>,
+}
+
+/// Unencrypted header of a ratchet message, authenticated as associated data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RatchetHeader {
+    /// Diffie-Hellman epoch of the sending chain
+    pub epoch: u32,
+    /// Position in the sending chain
+    pub counter: u64,
+    /// Proposal (from the initiator) or answer (from the responder)
+    pub ratchet_key: Option<RatchetKey>,
+}
+
+impl RatchetHeader {
+    fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4 + 8 + 1 + PUBLIC_KEY_SIZE + 33);
+        bytes.extend_from_slice(&self.epoch.to_be_bytes());
+        bytes.extend_from_slice(&self.counter.to_be_bytes());
+        match self.ratchet_key {
+            Some(key) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&key.public_key);
+                if let Some(proof) = key.proof {
+                    bytes.push(1);
+                    bytes.extend_from_slice(&proof);
+                } else {
+                    bytes.push(0);
+                }
+            }
+            None => bytes.push(0),
+        }
+        bytes
+    }
+}
+
+/// A message sent through a ratchet channel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RatchetMessage {
+    /// Message header
+    pub header: RatchetHeader,
+    /// Payload sealed with a single-use message key
+    pub encrypted: SealedMessage,
+}
+
+/// One direction's KDF chain
+#[derive(Clone, Copy)]
+struct Chain {
+    epoch: u32,
+    key: [u8; 32],
+    next: u64,
+}
+
+impl Chain {
+    fn new(epoch: u32, key: [u8; 32]) -> Self {
+        Self {
+            epoch,
+            key,
+            next: 0,
+        }
+    }
+
+    /// Take the key for the next message and advance the chain
+    fn advance(&mut self) -> [u8; 32] {
+        let message_key = *blake3::keyed_hash(&self.key, &[0x01]).as_bytes();
+        self.key = *blake3::keyed_hash(&self.key, &[0x02]).as_bytes();
+        self.next += 1;
+        message_key
+    }
+}
+
+/// Responder's answer, repeated until the initiator moves to the new epoch
+#[derive(Clone, Copy)]
+struct Answer {
+    ratchet_key: RatchetKey,
+    recv: Chain,
+}
+
+/// Mutable ratchet state
+struct RatchetState {
+    root: [u8; 32],
+    send: Chain,
+    recv: Chain,
+    previous_recv: Option<Chain>,
+    skipped: HashMap<(u32, u64), EncryptionKey>,
+    /// Initiator's outstanding proposal
+    proposal: Option<KeyPair>,
+    /// Responder's outstanding answer
+    answer: Option<Answer>,
+    messages_since_step: u64,
+}
+
+impl Drop for RatchetState {
+    fn drop(&mut self) {
+        self.root.zeroize();
+        self.send.key.zeroize();
+        self.recv.key.zeroize();
+        if let Some(previous) = &mut self.previous_recv {
+            previous.key.zeroize();
+        }
+        if let Some(answer) = &mut self.answer {
+            answer.recv.key.zeroize();
+        }
+    }
+}
+
+/// A secure channel whose keys advance with every message
+pub struct RatchetChannel {
+    role: ChannelRole,
+    cipher_suite: CipherSuite,
+    channel_id: ContentHash,
+    local_identity: Identity,
+    peer_identity: Identity,
+    dh_interval: u64,
+    state: Mutex<RatchetState>,
+}
+
+impl RatchetChannel {
+    /// Switch an established channel to ratchet mode
+    ///
+    /// Both ends must switch at the same point in the conversation; the
+    /// original channel's keys are only used to seed the ratchet.
+    pub fn new(channel: &SecureChannel) -> Self {
+        let (initiator_key, responder_key) = channel.directional_keys();
+
+        let mut hasher = blake3::Hasher::new_derive_key(INIT_CONTEXT);
+        hasher.update(initiator_key.as_bytes());
+        hasher.update(responder_key.as_bytes());
+        hasher.update(channel.channel_id().as_bytes());
+        let (root, initiator_chain, responder_chain) = split_keys(hasher);
+
+        let (send, recv) = match channel.role() {
+            ChannelRole::Initiator => (initiator_chain, responder_chain),
+            ChannelRole::Responder => (responder_chain, initiator_chain),
+        };
+
+        Self {
+            role: channel.role(),
+            cipher_suite: channel.cipher_suite(),
+            channel_id: *channel.channel_id(),
+            local_identity: channel.local_identity().clone(),
+            peer_identity: channel.peer_identity().clone(),
+            dh_interval: DEFAULT_DH_INTERVAL,
+            state: Mutex::new(RatchetState {
+                root,
+                send: Chain::new(0, send),
+                recv: Chain::new(0, recv),
+                previous_recv: None,
+                skipped: HashMap::new(),
+                proposal: None,
+                answer: None,
+                messages_since_step: 0,
+            }),
+        }
+    }
+
+    /// Set how many messages pass between Diffie-Hellman steps
+    ///
+    /// Only the initiator's setting matters, since it proposes the steps.
+    pub fn with_dh_interval(mut self, interval: u64) -> Self {
+        self.dh_interval = interval.max(1);
+        self
+    }
+
+    /// Get our role in the channel
+    pub fn role(&self) -> ChannelRole {
+        self.role
+    }
+
+    /// Get the channel identifier
+    pub fn channel_id(&self) -> &ContentHash {
+        &self.channel_id
+    }
+
+    /// Get our identity
+    pub fn local_identity(&self) -> &Identity {
+        &self.local_identity
+    }
+
+    /// Get the peer's identity
+    pub fn peer_identity(&self) -> &Identity {
+        &self.peer_identity
+    }
+
+    /// Get the Diffie-Hellman epoch of the sending chain
+    pub fn epoch(&self) -> u32 {
+        self.lock_state().send.epoch
+    }
+
+    /// Propose a Diffie-Hellman step with the next message (initiator only)
+    pub fn request_dh_step(&self) {
+        self.lock_state().messages_since_step = self.dh_interval;
+    }
+
+    /// Encrypt a message, advancing the sending chain
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<RatchetMessage, EncryptionError> {
+        let mut state = self.lock_state();
+
+        let ratchet_key = match self.role {
+            ChannelRole::Initiator => {
+                if state.proposal.is_none()
+                    && state.send.epoch == state.recv.epoch
+                    && state.messages_since_step >= self.dh_interval
+                {
+                    state.proposal = Some(KeyPair::generate()?);
+                }
+                state.proposal.as_ref().map(|proposal| RatchetKey {
+                    public_key: *proposal.public_key(),
+                    proof: None,
+                })
+            }
+            ChannelRole::Responder => state.answer.map(|answer| answer.ratchet_key),
+        };
+
+        let header = RatchetHeader {
+            epoch: state.send.epoch,
+            counter: state.send.next,
+            ratchet_key,
+        };
+        let message_key = EncryptionKey::from_bytes(state.send.advance());
+        let encrypted = encrypt_with_aad(
+            self.cipher_suite,
+            &message_key,
+            plaintext,
+            &self.message_aad(&header),
+        )?;
+        state.messages_since_step += 1;
+
+        Ok(RatchetMessage { header, encrypted })
+    }
+
+    /// Decrypt a message, advancing the receiving chain
+    ///
+    /// Replayed messages and messages too far ahead are rejected.
+    pub fn decrypt(&self, message: &RatchetMessage) -> Result<Vec<u8>, EncryptionError> {
+        let mut state = self.lock_state();
+        let header = message.header;
+
+        if self.role == ChannelRole::Initiator && header.epoch == state.recv.epoch + 1 {
+            self.complete_step(&mut state, &header)?;
+        }
+
+        let plaintext = self.open(&mut state, message)?;
+        state.messages_since_step += 1;
+
+        if self.role == ChannelRole::Responder
+            && header.epoch == state.recv.epoch
+            && state.answer.is_none()
+        {
+            if let Some(proposal) = header.ratchet_key {
+                self.answer_step(&mut state, &proposal.public_key)?;
+            }
+        }
+
+        Ok(plaintext)
+    }
+
+    /// Decrypt with the matching chain, committing state only on success
+    fn open(
+        &self,
+        state: &mut RatchetState,
+        message: &RatchetMessage,
+    ) -> Result<Vec<u8>, EncryptionError> {
+        let header = message.header;
+        let aad = self.message_aad(&header);
+
+        if let Some(message_key) = state.skipped.get(&(header.epoch, header.counter)) {
+            let plaintext =
+                decrypt_with_aad(self.cipher_suite, message_key, &message.encrypted, &aad)
+                    .map_err(|_| EncryptionError::AuthenticationFailed)?;
+            state.skipped.remove(&(header.epoch, header.counter));
+            return Ok(plaintext);
+        }
+
+        let answered = state
+            .answer
+            .map(|answer| answer.recv)
+            .filter(|next| next.epoch == header.epoch);
+        let is_current = header.epoch == state.recv.epoch;
+        let mut chain = if is_current {
+            state.recv
+        } else if let Some(next) = answered {
+            next
+        } else {
+            state
+                .previous_recv
+                .filter(|previous| previous.epoch == header.epoch)
+                .ok_or(EncryptionError::AuthenticationFailed)?
+        };
+
+        // Already used, or too far ahead to be worth buffering keys for
+        if header.counter < chain.next || header.counter - chain.next > MAX_SKIPPED_KEYS as u64 {
+            return Err(EncryptionError::AuthenticationFailed);
+        }
+
+        let mut skipped = Vec::new();
+        while chain.next < header.counter {
+            let counter = chain.next;
+            skipped.push((
+                (chain.epoch, counter),
+                EncryptionKey::from_bytes(chain.advance()),
+            ));
+        }
+        let message_key = EncryptionKey::from_bytes(chain.advance());
+
+        let plaintext = decrypt_with_aad(self.cipher_suite, &message_key, &message.encrypted, &aad)
+            .map_err(|_| EncryptionError::AuthenticationFailed)?;
+
+        if is_current {
+            state.recv = chain;
+        } else if answered.is_some() {
+            // The initiator has moved to the epoch we answered
+            state.previous_recv = Some(state.recv);
+            state.recv = chain;
+            state.answer = None;
+        } else {
+            state.previous_recv = Some(chain);
+        }
+        state.skipped.extend(skipped);
+        if state.skipped.len() > MAX_SKIPPED_KEYS {
+            // Drop the oldest keys; those messages are treated as lost
+            let mut keys: Vec<_> = state.skipped.keys().copied().collect();
+            keys.sort_unstable();
+            for key in &keys[..state.skipped.len() - MAX_SKIPPED_KEYS] {
+                state.skipped.remove(key);
+            }
+        }
+
+        Ok(plaintext)
+    }
+
+    /// Responder: answer an authenticated proposal and start a new sending epoch
+    fn answer_step(
+        &self,
+        state: &mut RatchetState,
+        proposal: &[u8; PUBLIC_KEY_SIZE],
+    ) -> Result<(), EncryptionError> {
+        let key_pair = KeyPair::generate()?;
+        let public_key = *key_pair.public_key();
+        let proof = step_proof(&state.root, proposal, &public_key);
+        let shared = key_pair.agree(proposal)?;
+
+        let epoch = state.send.epoch + 1;
+        let (root, initiator_chain, responder_chain) = step_keys(&state.root, shared.as_bytes());
+        state.root = root;
+        state.send = Chain::new(epoch, responder_chain);
+        state.answer = Some(Answer {
+            ratchet_key: RatchetKey {
+                public_key,
+                proof: Some(proof),
+            },
+            recv: Chain::new(epoch, initiator_chain),
+        });
+        state.messages_since_step = 0;
+
+        tracing::debug!(epoch, "Ratchet step answered");
+        Ok(())
+    }
+
+    /// Initiator: complete a step once the responder's answer arrives
+    fn complete_step(
+        &self,
+        state: &mut RatchetState,
+        header: &RatchetHeader,
+    ) -> Result<(), EncryptionError> {
+        let (answer, proposal) = match (header.ratchet_key, state.proposal.as_ref()) {
+            (Some(answer), Some(proposal)) => (answer, proposal),
+            _ => return Err(EncryptionError::AuthenticationFailed),
+        };
+
+        let expected = step_proof(&state.root, proposal.public_key(), &answer.public_key);
+        if answer.proof != Some(expected) {
+            return Err(EncryptionError::AuthenticationFailed);
+        }
+
+        let proposal = state
+            .proposal
+            .take()
+            .ok_or(EncryptionError::AuthenticationFailed)?;
+        let shared = proposal.agree(&answer.public_key)?;
+
+        let epoch = state.recv.epoch + 1;
+        let (root, initiator_chain, responder_chain) = step_keys(&state.root, shared.as_bytes());
+        state.root = root;
+        state.previous_recv = Some(state.recv);
+        state.recv = Chain::new(epoch, responder_chain);
+        state.send = Chain::new(epoch, initiator_chain);
+        state.messages_since_step = 0;
+
+        tracing::debug!(epoch, "Ratchet step completed");
+        Ok(())
+    }
+
+    /// Associated data binding a message to this channel and its header
+    fn message_aad(&self, header: &RatchetHeader) -> Vec<u8> {
+        let mut aad = MESSAGE_AAD_CONTEXT.to_vec();
+        aad.extend_from_slice(self.channel_id.as_bytes());
+        aad.extend_from_slice(&header.to_bytes());
+        aad
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, RatchetState> {
+        self.state.lock().unwrap_or_else(|poisoned| {
+            tracing::warn!("Ratchet state lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+}
+
+impl std::fmt::Debug for RatchetChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RatchetChannel")
+            .field("role", &self.role)
+            .field("channel_id", &self.channel_id.to_hex())
+            .field("epoch", &self.epoch())
+            .finish()
+    }
+}
+
+/// Split a derivation into root, initiator chain and responder chain keys
+fn split_keys(hasher: blake3::Hasher) -> ([u8; 32], [u8; 32], [u8; 32]) {
+    let mut output = Zeroizing::new([0u8; 96]);
+    hasher.finalize_xof().fill(&mut output[..]);
+
+    let mut root = [0u8; 32];
+    let mut initiator_chain = [0u8; 32];
+    let mut responder_chain = [0u8; 32];
+    root.copy_from_slice(&output[..32]);
+    initiator_chain.copy_from_slice(&output[32..64]);
+    responder_chain.copy_from_slice(&output[64..]);
+    (root, initiator_chain, responder_chain)
+}
+
+/// Mix a Diffie-Hellman output into the root key
+fn step_keys(root: &[u8; 32], shared: &[u8; 32]) -> ([u8; 32], [u8; 32], [u8; 32]) {
+    let mut hasher = blake3::Hasher::new_derive_key(ROOT_CONTEXT);
+    hasher.update(root);
+    hasher.update(shared);
+    split_keys(hasher)
+}
+
+/// Proof that an answer comes from the holder of the current root key
+fn step_proof(
+    root: &[u8; 32],
+    proposal: &[u8; PUBLIC_KEY_SIZE],
+    answer: &[u8; PUBLIC_KEY_SIZE],
+) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new_keyed(root);
+    hasher.update(PROOF_CONTEXT);
+    hasher.update(proposal);
+    hasher.update(answer);
+    *hasher.finalize().as_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::secure_channel::SecureChannelBuilder;
+
+    fn establish(interval: u64) -> (RatchetChannel, RatchetChannel) {
+        let initiator = SecureChannelBuilder::new().unwrap();
+        let responder = SecureChannelBuilder::new().unwrap();
+        let (responder_channel, response) = responder
+            .process_init(initiator.create_init_message())
+            .unwrap();
+        let initiator_channel = initiator.process_response(response).unwrap();
+        (
+            RatchetChannel::new(&initiator_channel).with_dh_interval(interval),
+            RatchetChannel::new(&responder_channel),
+        )
+    }
+
+    #[test]
+    fn every_message_uses_a_new_key() {
+        let (alice, bob) = establish(DEFAULT_DH_INTERVAL);
+
+        let first = alice.encrypt(b"same").unwrap();
+        let second = alice.encrypt(b"same").unwrap();
+        assert_ne!(first.encrypted.ciphertext, second.encrypted.ciphertext);
+
+        assert_eq!(bob.decrypt(&first).unwrap(), b"same".to_vec());
+        assert_eq!(bob.decrypt(&second).unwrap(), b"same".to_vec());
+        assert!(bob.decrypt(&first).is_err(), "replay must be rejected");
+    }
+
+    #[test]
+    fn debug_output_never_contains_chain_keys() {
+        let (alice, _bob) = establish(DEFAULT_DH_INTERVAL);
+        let (root, send) = {
+            let state = alice.lock_state();
+            (hex::encode(state.root), hex::encode(state.send.key))
+        };
+
+        let debug = format!("{:?}", alice);
+        assert!(!debug.contains(&root[..16]));
+        assert!(!debug.contains(&send[..16]));
+    }
+
+    #[test]
+    fn out_of_order_messages_decrypt() {
+        let (alice, bob) = establish(DEFAULT_DH_INTERVAL);
+        let messages: Vec<_> = (0..5u8).map(|i| alice.encrypt(&[i]).unwrap()).collect();
+
+        for i in [3usize, 0, 4, 1, 2] {
+            assert_eq!(bob.decrypt(&messages[i]).unwrap(), vec![i as u8]);
+        }
+    }
+
+    #[test]
+    fn dh_steps_advance_epochs_in_both_directions() {
+        let (alice, bob) = establish(2);
+
+        for round in 0..10u8 {
+            let to_bob = alice.encrypt(&[round]).unwrap();
+            assert_eq!(bob.decrypt(&to_bob).unwrap(), vec![round]);
+            let to_alice = bob.encrypt(&[round]).unwrap();
+            assert_eq!(alice.decrypt(&to_alice).unwrap(), vec![round]);
+        }
+
+        assert!(alice.epoch() >= 3);
+        assert_eq!(alice.epoch(), bob.epoch());
+    }
+
+    #[test]
+    fn in_flight_messages_survive_a_step() {
+        let (alice, bob) = establish(DEFAULT_DH_INTERVAL);
+        alice.request_dh_step();
+
+        // Alice proposes; a second message in the old epoch is delayed
+        let proposal = alice.encrypt(b"proposal").unwrap();
+        let delayed = alice.encrypt(b"delayed").unwrap();
+        bob.decrypt(&proposal).unwrap();
+
+        let answer = bob.encrypt(b"answer").unwrap();
+        assert_eq!(answer.header.epoch, 1);
+        alice.decrypt(&answer).unwrap();
+        assert_eq!(alice.epoch(), 1);
+
+        let new_epoch = alice.encrypt(b"new epoch").unwrap();
+        assert_eq!(bob.decrypt(&new_epoch).unwrap(), b"new epoch".to_vec());
+        assert_eq!(bob.decrypt(&delayed).unwrap(), b"delayed".to_vec());
+    }
+
+    #[test]
+    fn forged_answer_keeps_pending_proposal() {
+        let (alice, bob) = establish(DEFAULT_DH_INTERVAL);
+        alice.request_dh_step();
+        bob.decrypt(&alice.encrypt(b"proposal").unwrap()).unwrap();
+        let mut answer = bob.encrypt(b"answer").unwrap();
+
+        let genuine = answer.clone();
+        if let Some(key) = answer.header.ratchet_key.as_mut() {
+            key.public_key = *KeyPair::generate().unwrap().public_key();
+        }
+        assert!(alice.decrypt(&answer).is_err());
+
+        assert_eq!(alice.decrypt(&genuine).unwrap(), b"answer".to_vec());
+        assert_eq!(alice.epoch(), 1);
+    }
+
+    #[test]
+    fn tampered_header_is_rejected() {
+        let (alice, bob) = establish(DEFAULT_DH_INTERVAL);
+        let mut message = alice.encrypt(b"hello").unwrap();
+        message.header.ratchet_key = Some(RatchetKey {
+            public_key: [7u8; PUBLIC_KEY_SIZE],
+            proof: None,
+        });
+        assert!(bob.decrypt(&message).is_err());
+    }
+}
@@ -0,0 +1,442 @@
+//! Secure channel session resumption
+//!
+//! After a full handshake the responder can hand the initiator a
+//! [`ResumptionTicket`]: the channel's resumption secret and both identities,
+//! sealed under a key only the responder knows. To reconnect, the initiator
+//! sends the ticket with a fresh nonce in a single [`HandshakeMessage::Resume`]
+//! and can start sending data right away, skipping the X25519 exchange and
+//! identity signatures.
+//!
+//! Tickets are single use: the responder remembers ticket ids until they
+//! expire, so a captured resume message cannot be replayed. Resumed channels
+//! reuse the original key agreement and therefore do not add forward secrecy;
+//! the ticket lifetime bounds that exposure. An issuer given a revocation
+//! list refuses to resume channels of identities revoked since the ticket
+//! was issued.
+//!
+//! # Requirements Coverage
+//! - Requirement 2.1: Automatic reconnection on connection loss
+//! - Requirement 4.1: End-to-end encryption between peers
+
+use crate::encryption::cipher::{
+    decrypt_with_aad, encrypt_with_aad, CipherSuite, EncryptionKey, SealedMessage,
+};
+use crate::encryption::revocation::RevocationList;
+use crate::encryption::secure_channel::{
+    ChannelRole, HandshakeMessage, Identity, SecureChannel, SharedSecret, RESUMPTION_NONCE_SIZE,
+};
+use crate::error::EncryptionError;
+use chrono::{DateTime, Utc};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Default time a resumption ticket stays valid
+pub const DEFAULT_TICKET_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Size of a ticket identifier in bytes
+const TICKET_ID_SIZE: usize = 16;
+
+/// Associated data binding sealed tickets to their purpose
+const TICKET_AAD: &[u8] = b"russh-ssh resumption ticket v1";
+
+/// Context for deriving resumed channel keys
+const RESUME_CONTEXT: &[u8] = b"russh-ssh resume v1";
+
+/// A resumption ticket handed from responder to initiator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumptionTicket {
+    /// Ticket contents, readable only by the issuer
+    pub sealed: SealedMessage,
+    /// When the issuer stops accepting the ticket
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Channel state sealed inside a ticket
+#[derive(Serialize, Deserialize)]
+struct TicketContents {
+    id: [u8; TICKET_ID_SIZE],
+    secret: [u8; 32],
+    initiator: Identity,
+    responder: Identity,
+    cipher_suite: CipherSuite,
+    expires_at: DateTime<Utc>,
+}
+
+impl Drop for TicketContents {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+/// Issues and redeems resumption tickets on the responder side
+pub struct TicketIssuer {
+    /// Key sealing ticket contents
+    key: EncryptionKey,
+    /// How long issued tickets stay valid
+    lifetime: Duration,
+    /// Redeemed ticket ids and their expiry, for replay protection
+    redeemed: Mutex<HashMap<[u8; TICKET_ID_SIZE], DateTime<Utc>>>,
+    /// Identities that may no longer resume
+    revocations: Option<Arc<RevocationList>>,
+}
+
+impl TicketIssuer {
+    /// Create an issuer with a fresh random ticket key
+    ///
+    /// Tickets issued by another issuer (or before a restart) are rejected,
+    /// which makes the initiator fall back to a full handshake.
+    pub fn new() -> Result<Self, EncryptionError> {
+        Ok(Self {
+            key: EncryptionKey::generate()?,
+            lifetime: DEFAULT_TICKET_LIFETIME,
+            redeemed: Mutex::new(HashMap::new()),
+            revocations: None,
+        })
+    }
+
+    /// Set how long issued tickets stay valid
+    pub fn with_lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// Refuse to resume channels of identities on a revocation list
+    pub fn with_revocation_list(mut self, revocations: Arc<RevocationList>) -> Self {
+        self.revocations = Some(revocations);
+        self
+    }
+
+    /// Issue a ticket for a channel this side responded to
+    pub fn issue(&self, channel: &SecureChannel) -> Result<ResumptionTicket, EncryptionError> {
+        if channel.role() != ChannelRole::Responder {
+            return Err(EncryptionError::ChannelEstablishment(
+                "Only the responder can issue resumption tickets".into(),
+            ));
+        }
+
+        let lifetime = chrono::Duration::from_std(self.lifetime).map_err(|e| {
+            EncryptionError::ChannelEstablishment(format!("Invalid ticket lifetime: {}", e))
+        })?;
+        let contents = TicketContents {
+            id: random_bytes()?,
+            secret: *channel.resumption_secret(),
+            initiator: channel.peer_identity().clone(),
+            responder: channel.local_identity().clone(),
+            cipher_suite: channel.cipher_suite(),
+            expires_at: Utc::now() + lifetime,
+        };
+        let plaintext = serde_json::to_vec(&contents)
+            .map(Zeroizing::new)
+            .map_err(|e| EncryptionError::Encryption(format!("Failed to encode ticket: {}", e)))?;
+
+        Ok(ResumptionTicket {
+            sealed: encrypt_with_aad(CipherSuite::Aes256Gcm, &self.key, &plaintext, TICKET_AAD)?,
+            expires_at: contents.expires_at,
+        })
+    }
+
+    /// Redeem a resume message, re-establishing the channel
+    pub fn resume(&self, message: &HandshakeMessage) -> Result<SecureChannel, EncryptionError> {
+        let (ticket, nonce) = match message {
+            HandshakeMessage::Resume { ticket, nonce } => (ticket, nonce),
+            _ => {
+                return Err(EncryptionError::ChannelEstablishment(
+                    "Expected Resume message".into(),
+                ))
+            }
+        };
+
+        let plaintext = decrypt_with_aad(CipherSuite::Aes256Gcm, &self.key, ticket, TICKET_AAD)
+            .map(Zeroizing::new)
+            .map_err(|_| {
+                EncryptionError::ChannelEstablishment("Unknown resumption ticket".into())
+            })?;
+        let contents: TicketContents = serde_json::from_slice(&plaintext).map_err(|e| {
+            EncryptionError::ChannelEstablishment(format!("Invalid resumption ticket: {}", e))
+        })?;
+
+        let now = Utc::now();
+        if contents.expires_at <= now {
+            return Err(EncryptionError::ChannelEstablishment(
+                "Resumption ticket expired".into(),
+            ));
+        }
+        match &self.revocations {
+            Some(revocations) => revocations.check_identity(&contents.initiator)?,
+            None => contents.initiator.check_expiry()?,
+        }
+
+        {
+            let mut redeemed = self.lock_redeemed();
+            redeemed.retain(|_, expires_at| *expires_at > now);
+            if redeemed.insert(contents.id, contents.expires_at).is_some() {
+                return Err(EncryptionError::ChannelEstablishment(
+                    "Resumption ticket already used".into(),
+                ));
+            }
+        }
+
+        let keys =
+            SharedSecret::from_bytes(contents.secret).derive_keys(&resume_context(ticket, nonce));
+        Ok(SecureChannel::new(
+            ChannelRole::Responder,
+            keys,
+            contents.responder.clone(),
+            contents.initiator.clone(),
+        )
+        .with_cipher_suite(contents.cipher_suite))
+    }
+
+    fn lock_redeemed(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<[u8; TICKET_ID_SIZE], DateTime<Utc>>> {
+        self.redeemed.lock().unwrap_or_else(|poisoned| {
+            tracing::warn!("Redeemed ticket lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+}
+
+impl std::fmt::Debug for TicketIssuer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TicketIssuer")
+            .field("key", &self.key)
+            .field("lifetime", &self.lifetime)
+            .finish()
+    }
+}
+
+/// What the initiator keeps to resume a channel later
+pub struct ResumptionState {
+    /// Ticket from the responder
+    ticket: ResumptionTicket,
+    /// Resumption secret of the original channel
+    secret: Zeroizing<[u8; 32]>,
+    /// Our identity
+    local_identity: Identity,
+    /// Responder's identity
+    peer_identity: Identity,
+    /// Cipher suite of the original channel
+    cipher_suite: CipherSuite,
+}
+
+impl ResumptionState {
+    /// Pair a received ticket with the channel it was issued for
+    pub fn new(channel: &SecureChannel, ticket: ResumptionTicket) -> Result<Self, EncryptionError> {
+        if channel.role() != ChannelRole::Initiator {
+            return Err(EncryptionError::ChannelEstablishment(
+                "Only the initiator can resume a channel".into(),
+            ));
+        }
+
+        Ok(Self {
+            ticket,
+            secret: Zeroizing::new(*channel.resumption_secret()),
+            local_identity: channel.local_identity().clone(),
+            peer_identity: channel.peer_identity().clone(),
+            cipher_suite: channel.cipher_suite(),
+        })
+    }
+
+    /// Get the identity of the peer this state resumes to
+    pub fn peer_identity(&self) -> &Identity {
+        &self.peer_identity
+    }
+
+    /// Check whether the ticket has expired
+    pub fn is_expired(&self) -> bool {
+        self.ticket.expires_at <= Utc::now()
+    }
+
+    /// Resume the channel, returning it with the message to send
+    ///
+    /// Consumes the state since the responder accepts each ticket only once.
+    pub fn resume(self) -> Result<(SecureChannel, HandshakeMessage), EncryptionError> {
+        if self.is_expired() {
+            return Err(EncryptionError::ChannelEstablishment(
+                "Resumption ticket expired".into(),
+            ));
+        }
+
+        let nonce: [u8; RESUMPTION_NONCE_SIZE] = random_bytes()?;
+        let keys = SharedSecret::from_bytes(*self.secret)
+            .derive_keys(&resume_context(&self.ticket.sealed, &nonce));
+
+        let channel = SecureChannel::new(
+            ChannelRole::Initiator,
+            keys,
+            self.local_identity,
+            self.peer_identity,
+        )
+        .with_cipher_suite(self.cipher_suite);
+        let message = HandshakeMessage::Resume {
+            ticket: self.ticket.sealed,
+            nonce,
+        };
+
+        Ok((channel, message))
+    }
+}
+
+impl std::fmt::Debug for ResumptionState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResumptionState")
+            .field("peer_identity", &self.peer_identity)
+            .field("expires_at", &self.ticket.expires_at)
+            .finish()
+    }
+}
+
+/// Key derivation context binding resumed keys to the ticket and nonce
+fn resume_context(ticket: &SealedMessage, nonce: &[u8; RESUMPTION_NONCE_SIZE]) -> Vec<u8> {
+    let mut context = RESUME_CONTEXT.to_vec();
+    context.extend_from_slice(&ticket.nonce);
+    context.extend_from_slice(&ticket.ciphertext);
+    context.extend_from_slice(nonce);
+    context
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], EncryptionError> {
+    let mut bytes = [0u8; N];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| EncryptionError::KeyGeneration("Failed to generate random bytes".into()))?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::secure_channel::SecureChannelBuilder;
+
+    fn establish() -> (SecureChannel, SecureChannel) {
+        let initiator = SecureChannelBuilder::new().unwrap();
+        let responder = SecureChannelBuilder::new().unwrap();
+        let (responder_channel, response) = responder
+            .process_init(initiator.create_init_message())
+            .unwrap();
+        let initiator_channel = initiator.process_response(response).unwrap();
+        (initiator_channel, responder_channel)
+    }
+
+    #[test]
+    fn resumed_channel_communicates() {
+        let issuer = TicketIssuer::new().unwrap();
+        let (initiator_channel, responder_channel) = establish();
+
+        let ticket = issuer.issue(&responder_channel).unwrap();
+        let state = ResumptionState::new(&initiator_channel, ticket).unwrap();
+
+        let (resumed_initiator, message) = state.resume().unwrap();
+        let resumed_responder = issuer.resume(&message).unwrap();
+
+        assert_eq!(
+            resumed_initiator.channel_id(),
+            resumed_responder.channel_id()
+        );
+        assert_ne!(
+            resumed_initiator.channel_id(),
+            initiator_channel.channel_id()
+        );
+        assert_eq!(
+            resumed_responder.peer_identity().identifier,
+            initiator_channel.local_identity().identifier
+        );
+
+        let encrypted = resumed_initiator.encrypt(b"resumed").unwrap();
+        assert_eq!(
+            resumed_responder.decrypt(&encrypted).unwrap(),
+            b"resumed".to_vec()
+        );
+    }
+
+    #[test]
+    fn replayed_resume_is_rejected() {
+        let issuer = TicketIssuer::new().unwrap();
+        let (initiator_channel, responder_channel) = establish();
+        let ticket = issuer.issue(&responder_channel).unwrap();
+
+        let (_, message) = ResumptionState::new(&initiator_channel, ticket)
+            .unwrap()
+            .resume()
+            .unwrap();
+
+        assert!(issuer.resume(&message).is_ok());
+        assert!(matches!(
+            issuer.resume(&message),
+            Err(EncryptionError::ChannelEstablishment(_))
+        ));
+    }
+
+    #[test]
+    fn foreign_or_expired_tickets_are_rejected() {
+        let (initiator_channel, responder_channel) = establish();
+
+        let issuer = TicketIssuer::new().unwrap();
+        let ticket = issuer.issue(&responder_channel).unwrap();
+        let (_, message) = ResumptionState::new(&initiator_channel, ticket)
+            .unwrap()
+            .resume()
+            .unwrap();
+        assert!(TicketIssuer::new().unwrap().resume(&message).is_err());
+
+        let expired = TicketIssuer::new()
+            .unwrap()
+            .with_lifetime(Duration::ZERO)
+            .issue(&responder_channel)
+            .unwrap();
+        let state = ResumptionState::new(&initiator_channel, expired).unwrap();
+        assert!(state.is_expired());
+        assert!(state.resume().is_err());
+    }
+
+    #[test]
+    fn revoked_identity_cannot_resume() {
+        let revocations = Arc::new(RevocationList::new());
+        let issuer = TicketIssuer::new()
+            .unwrap()
+            .with_revocation_list(revocations.clone());
+        let (initiator_channel, responder_channel) = establish();
+        let ticket = issuer.issue(&responder_channel).unwrap();
+
+        revocations
+            .revoke_identity(initiator_channel.local_identity(), "device lost")
+            .unwrap();
+        let (_, message) = ResumptionState::new(&initiator_channel, ticket)
+            .unwrap()
+            .resume()
+            .unwrap();
+
+        assert!(matches!(
+            issuer.resume(&message),
+            Err(EncryptionError::IdentityRevoked { .. })
+        ));
+    }
+
+    #[test]
+    fn debug_output_never_contains_resumption_secret() {
+        let (initiator_channel, responder_channel) = establish();
+        let secret = hex::encode(initiator_channel.resumption_secret());
+
+        let issuer = TicketIssuer::new().unwrap();
+        let ticket = issuer.issue(&responder_channel).unwrap();
+        let state = ResumptionState::new(&initiator_channel, ticket).unwrap();
+        let debug = format!("{:?} {:?}", issuer, state);
+
+        assert!(!debug.contains(&secret[..16]));
+        assert!(!debug.contains(&hex::encode(issuer.key.as_bytes())[..16]));
+    }
+
+    #[test]
+    fn only_responder_issues_tickets() {
+        let (initiator_channel, _) = establish();
+        assert!(TicketIssuer::new()
+            .unwrap()
+            .issue(&initiator_channel)
+            .is_err());
+    }
+}
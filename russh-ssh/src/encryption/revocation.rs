@@ -0,0 +1,236 @@
+//! Revocation list for compromised identities and devices
+//!
+//! Entries are keyed by identity fingerprint (see
+//! [`Identity::identifier_hex`]) or by iroh NodeId, so a lost device can be
+//! cut off both at the secure channel and at the P2P connection layer.
+//! Unlike a [`TrustStore`](crate::encryption::TrustStore) revocation, which is
+//! tied to one peer record, the list can be shared between devices.
+//!
+//! # Requirements Coverage
+//! - Requirement 4.2: Mutual authentication between peers
+
+use crate::encryption::secure_channel::Identity;
+use crate::error::EncryptionError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// File name of the revocation list inside the configuration directory
+pub const REVOCATION_LIST_FILE_NAME: &str = "revoked.json";
+
+/// A single revocation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Revocation {
+    /// When the identity was revoked
+    pub revoked_at: DateTime<Utc>,
+    /// Why it was revoked (e.g. "device lost")
+    pub reason: String,
+}
+
+/// Persisted set of revoked identity fingerprints and NodeIds
+#[derive(Debug, Default)]
+pub struct RevocationList {
+    /// File the list is saved to, if any
+    path: Option<PathBuf>,
+    /// Revocations keyed by fingerprint or NodeId
+    entries: RwLock<HashMap<String, Revocation>>,
+}
+
+impl RevocationList {
+    /// Create an empty in-memory list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Default location of the revocation list file
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("russh").join(REVOCATION_LIST_FILE_NAME))
+    }
+
+    /// Open the list at `path`, starting empty if the file does not exist
+    pub fn open(path: &Path) -> Result<Self, EncryptionError> {
+        let entries = if path.exists() {
+            let json = std::fs::read_to_string(path).map_err(|e| {
+                EncryptionError::KeyStorage(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+            serde_json::from_str(&json).map_err(|e| {
+                EncryptionError::KeyStorage(format!(
+                    "Invalid revocation list {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            entries: RwLock::new(entries),
+        })
+    }
+
+    /// Revoke a fingerprint or NodeId
+    pub fn revoke(&self, id: &str, reason: impl Into<String>) -> Result<(), EncryptionError> {
+        let revocation = Revocation {
+            revoked_at: Utc::now(),
+            reason: reason.into(),
+        };
+        tracing::info!(id, reason = %revocation.reason, "Revoking identity");
+        self.write_entries().insert(id.to_string(), revocation);
+        self.save()
+    }
+
+    /// Revoke an identity by its fingerprint
+    pub fn revoke_identity(
+        &self,
+        identity: &Identity,
+        reason: impl Into<String>,
+    ) -> Result<(), EncryptionError> {
+        self.revoke(&identity.identifier_hex(), reason)
+    }
+
+    /// Lift a revocation, returning it if there was one
+    pub fn unrevoke(&self, id: &str) -> Result<Option<Revocation>, EncryptionError> {
+        let removed = self.write_entries().remove(id);
+        self.save()?;
+        Ok(removed)
+    }
+
+    /// Look up the revocation for a fingerprint or NodeId
+    pub fn get(&self, id: &str) -> Option<Revocation> {
+        self.read_entries().get(id).cloned()
+    }
+
+    /// Check whether a fingerprint or NodeId is revoked
+    pub fn is_revoked(&self, id: &str) -> bool {
+        self.read_entries().contains_key(id)
+    }
+
+    /// Fail if the identity is expired or revoked
+    pub fn check_identity(&self, identity: &Identity) -> Result<(), EncryptionError> {
+        identity.check_expiry()?;
+        let fingerprint = identity.identifier_hex();
+        match self.get(&fingerprint) {
+            Some(revocation) => Err(EncryptionError::IdentityRevoked {
+                identity: fingerprint,
+                reason: revocation.reason,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// List all revocations
+    pub fn entries(&self) -> Vec<(String, Revocation)> {
+        let mut entries: Vec<_> = self
+            .read_entries()
+            .iter()
+            .map(|(id, revocation)| (id.clone(), revocation.clone()))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    /// Write the list to its file, if it has one
+    pub fn save(&self) -> Result<(), EncryptionError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let storage_error =
+            |e: std::io::Error| EncryptionError::KeyStorage(format!("{}: {}", path.display(), e));
+
+        let json = serde_json::to_string_pretty(&*self.read_entries()).map_err(|e| {
+            EncryptionError::KeyStorage(format!("Failed to encode revocation list: {}", e))
+        })?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(storage_error)?;
+        }
+
+        let tmp_path = path.with_extension("json.tmp");
+        let mut file = std::fs::File::create(&tmp_path).map_err(storage_error)?;
+        file.write_all(json.as_bytes()).map_err(storage_error)?;
+        file.sync_all().map_err(storage_error)?;
+        std::fs::rename(&tmp_path, path).map_err(storage_error)
+    }
+
+    fn read_entries(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Revocation>> {
+        self.entries.read().unwrap_or_else(|poisoned| {
+            tracing::warn!("Revocation list lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+
+    fn write_entries(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, Revocation>> {
+        self.entries.write().unwrap_or_else(|poisoned| {
+            tracing::warn!("Revocation list lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::identity::IdentityKeyPair;
+    use crate::encryption::secure_channel::SecureChannelBuilder;
+    use std::sync::Arc;
+
+    #[test]
+    fn revoked_identity_is_refused_in_handshake() {
+        let revocations = Arc::new(RevocationList::new());
+        let laptop = Arc::new(IdentityKeyPair::generate().unwrap());
+        revocations
+            .revoke_identity(laptop.identity(), "device lost")
+            .unwrap();
+
+        let initiator = SecureChannelBuilder::with_identity(laptop).unwrap();
+        let responder = SecureChannelBuilder::new()
+            .unwrap()
+            .with_revocation_list(revocations);
+
+        match responder.process_init(initiator.create_init_message()) {
+            Err(EncryptionError::IdentityRevoked { reason, .. }) => {
+                assert_eq!(reason, "device lost")
+            }
+            other => panic!("expected revocation error, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn expired_identity_is_refused() {
+        let identity = IdentityKeyPair::generate()
+            .unwrap()
+            .identity()
+            .clone()
+            .with_expiry(Utc::now() - chrono::Duration::hours(1));
+
+        assert!(identity.is_expired());
+        assert!(matches!(
+            RevocationList::new().check_identity(&identity),
+            Err(EncryptionError::IdentityExpired { .. })
+        ));
+    }
+
+    #[test]
+    fn list_persists_and_unrevokes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(REVOCATION_LIST_FILE_NAME);
+
+        RevocationList::open(&path)
+            .unwrap()
+            .revoke("node-a", "compromised")
+            .unwrap();
+
+        let reopened = RevocationList::open(&path).unwrap();
+        assert!(reopened.is_revoked("node-a"));
+        assert_eq!(
+            reopened.unrevoke("node-a").unwrap().unwrap().reason,
+            "compromised"
+        );
+        assert!(!RevocationList::open(&path).unwrap().is_revoked("node-a"));
+    }
+}
@@ -1,43 +1,127 @@
 //! Secure Channel implementation following OCKAM principles
 //!
 //! This module provides end-to-end encrypted secure channels with:
-//! - Mutual authentication between peers
+//! - Mutual authentication between peers via Ed25519-signed ephemeral keys
 //! - Key agreement using X25519 Diffie-Hellman
-//! - Message encryption using AES-256-GCM
+//! - Message encryption using AES-256-GCM or ChaCha20-Poly1305, negotiated
+//!   during the handshake
 //! - BLAKE3 for key derivation and integrity
+//! - Message headers (channel id, sender, counter) bound as associated data
 //! - Replay protection with sliding window
+//! - Refusal of expired and revoked peer identities
 
-use crate::encryption::cipher::{decrypt, encrypt, EncryptedMessage, EncryptionKey, KEY_SIZE};
+use crate::encryption::cipher::{
+    decrypt_with_aad, encrypt_with_aad, CipherSuite, EncryptionKey, SealedMessage, KEY_SIZE,
+};
 use crate::encryption::hash::{hash_data, ContentHash};
+use crate::encryption::identity::{IdentityKeyPair, SignatureAlgorithm};
+use crate::encryption::key_provider::KeyProvider;
+use crate::encryption::revocation::RevocationList;
+use crate::encryption::trust::TrustStore;
 use crate::error::EncryptionError;
+use chrono::{DateTime, Utc};
 use ring::agreement::{self, EphemeralPrivateKey, UnparsedPublicKey, X25519};
 use ring::rand::SystemRandom;
+use ring::signature::{self, ECDSA_P256_SHA256_ASN1, ED25519};
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
+use zeroize::{Zeroize, Zeroizing};
 
-/// Size of X25519 public key in bytes
+/// Size of X25519 and Ed25519 public keys in bytes
 pub const PUBLIC_KEY_SIZE: usize = 32;
 
+/// Domain separator for the initiator's handshake signature
+const INIT_SIGNATURE_CONTEXT: &[u8] = b"russh-ssh secure channel init v1";
+
+/// Domain separator for the responder's handshake signature
+const RESPONSE_SIGNATURE_CONTEXT: &[u8] = b"russh-ssh secure channel response v1";
+
+/// Domain separator for secure message associated data
+const MESSAGE_AAD_CONTEXT: &[u8] = b"russh-ssh secure message v1";
+
+/// Size of the nonce sent when resuming a channel
+pub const RESUMPTION_NONCE_SIZE: usize = 32;
+
 /// Size of the replay protection window
 const REPLAY_WINDOW_SIZE: u64 = 64;
 
 /// A cryptographic identity for secure channel establishment
+///
+/// For authenticated channels the public key is a long-term signing key
+/// (see [`KeyProvider`]), so the identifier is stable across sessions.
+/// An expiry time, if set, is part of the identifier, so a pinned identity
+/// cannot be extended without changing its fingerprint.
 #[derive(Clone)]
 pub struct Identity {
+    /// Signature algorithm of the public key
+    pub algorithm: SignatureAlgorithm,
     /// The public key for this identity
-    pub public_key: [u8; PUBLIC_KEY_SIZE],
-    /// Unique identifier derived from public key
+    pub public_key: Vec<u8>,
+    /// Time after which peers refuse this identity
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Unique identifier derived from public key and expiry
     pub identifier: ContentHash,
 }
 
 impl Identity {
-    /// Create an identity from a public key
+    /// Create an identity from an Ed25519 public key
     pub fn from_public_key(public_key: [u8; PUBLIC_KEY_SIZE]) -> Self {
         let identifier = hash_data(&public_key);
         Self {
+            algorithm: SignatureAlgorithm::Ed25519,
+            public_key: public_key.to_vec(),
+            expires_at: None,
+            identifier,
+        }
+    }
+
+    /// Create an identity from a public key of the given algorithm
+    pub fn new(
+        algorithm: SignatureAlgorithm,
+        public_key: Vec<u8>,
+    ) -> Result<Self, EncryptionError> {
+        if public_key.len() != algorithm.public_key_len() {
+            return Err(EncryptionError::InvalidKeyFormat(format!(
+                "{} public keys are {} bytes, got {}",
+                algorithm,
+                algorithm.public_key_len(),
+                public_key.len()
+            )));
+        }
+
+        let identifier = hash_data(&public_key);
+        Ok(Self {
+            algorithm,
             public_key,
+            expires_at: None,
             identifier,
+        })
+    }
+
+    /// Set the expiry time, recomputing the identifier
+    pub fn with_expiry(mut self, expires_at: DateTime<Utc>) -> Self {
+        let mut data = self.public_key.clone();
+        data.extend_from_slice(&expires_at.timestamp().to_be_bytes());
+        self.identifier = hash_data(&data);
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Check whether the identity has expired
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+
+    /// Fail with [`EncryptionError::IdentityExpired`] if the identity has expired
+    pub fn check_expiry(&self) -> Result<(), EncryptionError> {
+        match self.expires_at {
+            Some(expires_at) if self.is_expired() => Err(EncryptionError::IdentityExpired {
+                identity: self.identifier_hex(),
+                expired_at: expires_at,
+            }),
+            _ => Ok(()),
         }
     }
 
@@ -45,6 +129,23 @@ impl Identity {
     pub fn identifier_hex(&self) -> String {
         self.identifier.to_hex()
     }
+
+    /// Verify a signature made by this identity
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), EncryptionError> {
+        let algorithm: &dyn signature::VerificationAlgorithm = match self.algorithm {
+            SignatureAlgorithm::Ed25519 => &ED25519,
+            SignatureAlgorithm::EcdsaP256 => &ECDSA_P256_SHA256_ASN1,
+        };
+
+        signature::UnparsedPublicKey::new(algorithm, &self.public_key)
+            .verify(message, signature)
+            .map_err(|_| {
+                EncryptionError::InvalidSignature(format!(
+                    "Signature does not match identity {}",
+                    self.identifier_hex()
+                ))
+            })
+    }
 }
 
 impl std::fmt::Debug for Identity {
@@ -61,8 +162,10 @@ impl Serialize for Identity {
         S: serde::Serializer,
     {
         use serde::ser::SerializeStruct;
-        let mut state = serializer.serialize_struct("Identity", 2)?;
-        state.serialize_field("public_key", &hex::encode(self.public_key))?;
+        let mut state = serializer.serialize_struct("Identity", 4)?;
+        state.serialize_field("algorithm", &self.algorithm)?;
+        state.serialize_field("public_key", &hex::encode(&self.public_key))?;
+        state.serialize_field("expires_at", &self.expires_at)?;
         state.serialize_field("identifier", &self.identifier)?;
         state.end()
     }
@@ -75,24 +178,29 @@ impl<'de> Deserialize<'de> for Identity {
     {
         #[derive(Deserialize)]
         struct Helper {
+            #[serde(default)]
+            algorithm: SignatureAlgorithm,
             public_key: String,
+            #[serde(default)]
+            expires_at: Option<DateTime<Utc>>,
             identifier: ContentHash,
         }
 
         let helper = Helper::deserialize(deserializer)?;
         let public_key_bytes = hex::decode(&helper.public_key).map_err(serde::de::Error::custom)?;
 
-        if public_key_bytes.len() != PUBLIC_KEY_SIZE {
-            return Err(serde::de::Error::custom("Invalid public key length"));
+        let mut identity =
+            Self::new(helper.algorithm, public_key_bytes).map_err(serde::de::Error::custom)?;
+        if let Some(expires_at) = helper.expires_at {
+            identity = identity.with_expiry(expires_at);
+        }
+        if identity.identifier != helper.identifier {
+            return Err(serde::de::Error::custom(
+                "Identifier does not match public key",
+            ));
         }
 
-        let mut public_key = [0u8; PUBLIC_KEY_SIZE];
-        public_key.copy_from_slice(&public_key_bytes);
-
-        Ok(Self {
-            public_key,
-            identifier: helper.identifier,
-        })
+        Ok(identity)
     }
 }
 
@@ -130,7 +238,7 @@ impl KeyPair {
         &self.public_key_bytes
     }
 
-    /// Create an Identity from this key pair
+    /// Create an unauthenticated, single-session Identity from this key pair
     pub fn identity(&self) -> Identity {
         Identity::from_public_key(self.public_key_bytes)
     }
@@ -143,18 +251,30 @@ impl KeyPair {
         let peer_public = UnparsedPublicKey::new(&X25519, peer_public_key);
 
         agreement::agree_ephemeral(self.private_key, &peer_public, |shared_secret| {
-            let mut secret_bytes = [0u8; 32];
-            secret_bytes.copy_from_slice(shared_secret);
-            SharedSecret(secret_bytes)
+            let mut secret = SharedSecret([0u8; 32]);
+            secret.0.copy_from_slice(shared_secret);
+            secret
         })
         .map_err(|_| EncryptionError::ChannelEstablishment("Key agreement failed".into()))
     }
 }
 
 /// Shared secret from key agreement
+///
+/// Zeroed on drop.
 pub struct SharedSecret([u8; 32]);
 
 impl SharedSecret {
+    /// Wrap secret bytes obtained other than by key agreement (e.g. resumption)
+    pub(crate) fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Get the raw secret bytes
+    pub(crate) fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
     /// Derive encryption keys from the shared secret
     pub fn derive_keys(&self, context: &[u8]) -> DerivedKeys {
         // Use BLAKE3 key derivation
@@ -162,27 +282,62 @@ impl SharedSecret {
         hasher.update(&self.0);
         hasher.update(context);
 
-        let mut output = [0u8; 64]; // 32 bytes for each direction
-        hasher.finalize_xof().fill(&mut output);
+        // 32 bytes for each direction, 32 bytes of channel id, then 32 bytes
+        // of resumption secret
+        let mut output = Zeroizing::new([0u8; 128]);
+        hasher.finalize_xof().fill(&mut output[..]);
 
-        let mut initiator_key = [0u8; KEY_SIZE];
-        let mut responder_key = [0u8; KEY_SIZE];
+        let mut initiator_key = Zeroizing::new([0u8; KEY_SIZE]);
+        let mut responder_key = Zeroizing::new([0u8; KEY_SIZE]);
+        let mut channel_id = [0u8; 32];
+        let mut resumption_secret = Zeroizing::new([0u8; 32]);
         initiator_key.copy_from_slice(&output[..32]);
-        responder_key.copy_from_slice(&output[32..]);
+        responder_key.copy_from_slice(&output[32..64]);
+        channel_id.copy_from_slice(&output[64..96]);
+        resumption_secret.copy_from_slice(&output[96..]);
 
         DerivedKeys {
-            initiator_key: EncryptionKey::from_bytes(initiator_key),
-            responder_key: EncryptionKey::from_bytes(responder_key),
+            initiator_key: EncryptionKey::from_bytes(*initiator_key),
+            responder_key: EncryptionKey::from_bytes(*responder_key),
+            channel_id: ContentHash::from_bytes(channel_id),
+            resumption_secret,
         }
     }
 }
 
+impl Drop for SharedSecret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl std::fmt::Debug for SharedSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedSecret([REDACTED])")
+    }
+}
+
 /// Keys derived from shared secret for bidirectional communication
 pub struct DerivedKeys {
     /// Key for messages from initiator to responder
     pub initiator_key: EncryptionKey,
     /// Key for messages from responder to initiator
     pub responder_key: EncryptionKey,
+    /// Identifier shared by both ends of the channel
+    pub channel_id: ContentHash,
+    /// Secret for resuming the channel later without a new key agreement
+    pub resumption_secret: Zeroizing<[u8; 32]>,
+}
+
+impl std::fmt::Debug for DerivedKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DerivedKeys")
+            .field("initiator_key", &self.initiator_key)
+            .field("responder_key", &self.responder_key)
+            .field("channel_id", &self.channel_id.to_hex())
+            .field("resumption_secret", &"[REDACTED]")
+            .finish()
+    }
 }
 
 /// Role in the secure channel
@@ -256,6 +411,12 @@ pub struct SecureChannel {
     encrypt_key: EncryptionKey,
     /// Key for decrypting incoming messages
     decrypt_key: EncryptionKey,
+    /// Negotiated AEAD cipher
+    cipher_suite: CipherSuite,
+    /// Identifier shared by both ends, bound into every message
+    channel_id: ContentHash,
+    /// Secret carried into resumption tickets
+    resumption_secret: Zeroizing<[u8; 32]>,
     /// Our identity
     local_identity: Identity,
     /// Peer's identity
@@ -283,6 +444,9 @@ impl SecureChannel {
             role,
             encrypt_key,
             decrypt_key,
+            cipher_suite: CipherSuite::default(),
+            channel_id: keys.channel_id,
+            resumption_secret: keys.resumption_secret,
             local_identity,
             peer_identity,
             send_counter: AtomicU64::new(0),
@@ -290,11 +454,40 @@ impl SecureChannel {
         }
     }
 
+    /// Use the given cipher suite for this channel
+    pub fn with_cipher_suite(mut self, cipher_suite: CipherSuite) -> Self {
+        self.cipher_suite = cipher_suite;
+        self
+    }
+
     /// Get our role in the channel
     pub fn role(&self) -> ChannelRole {
         self.role
     }
 
+    /// Get the negotiated cipher suite
+    pub fn cipher_suite(&self) -> CipherSuite {
+        self.cipher_suite
+    }
+
+    /// Get the channel identifier
+    pub fn channel_id(&self) -> &ContentHash {
+        &self.channel_id
+    }
+
+    /// Get the secret shared for session resumption
+    pub(crate) fn resumption_secret(&self) -> &[u8; 32] {
+        &self.resumption_secret
+    }
+
+    /// Get the initiator-to-responder and responder-to-initiator keys
+    pub(crate) fn directional_keys(&self) -> (&EncryptionKey, &EncryptionKey) {
+        match self.role {
+            ChannelRole::Initiator => (&self.encrypt_key, &self.decrypt_key),
+            ChannelRole::Responder => (&self.decrypt_key, &self.encrypt_key),
+        }
+    }
+
     /// Get our identity
     pub fn local_identity(&self) -> &Identity {
         &self.local_identity
@@ -308,42 +501,74 @@ impl SecureChannel {
     /// Encrypt a message for sending through the channel
     pub fn encrypt(&self, plaintext: &[u8]) -> Result<SecureMessage, EncryptionError> {
         let counter = self.send_counter.fetch_add(1, Ordering::SeqCst);
-        let encrypted = encrypt(&self.encrypt_key, plaintext)?;
+        let sender = self.local_identity.identifier;
+        let aad = self.message_aad(&sender, counter);
+        let encrypted = encrypt_with_aad(self.cipher_suite, &self.encrypt_key, plaintext, &aad)?;
 
         Ok(SecureMessage {
             encrypted,
             counter,
-            sender: self.local_identity.identifier,
+            sender,
         })
     }
 
     /// Decrypt a message received through the channel
+    ///
+    /// The header is authenticated together with the payload, so the
+    /// counter is only recorded once the message is known to be genuine.
     pub fn decrypt(&self, message: &SecureMessage) -> Result<Vec<u8>, EncryptionError> {
-        // Verify sender
+        // Cheap pre-check; the sender is authenticated as AAD below
         if message.sender != self.peer_identity.identifier {
             return Err(EncryptionError::AuthenticationFailed);
         }
 
+        let aad = self.message_aad(&message.sender, message.counter);
+        let plaintext = decrypt_with_aad(
+            self.cipher_suite,
+            &self.decrypt_key,
+            &message.encrypted,
+            &aad,
+        )
+        .map_err(|_| EncryptionError::AuthenticationFailed)?;
+
         // Check counter for replay protection using sliding window
-        {
-            let mut window = self
-                .replay_window
-                .write()
-                .map_err(|_| EncryptionError::ChannelEstablishment("Lock poisoned".into()))?;
-            if !window.check_and_mark(message.counter) {
-                return Err(EncryptionError::AuthenticationFailed);
-            }
+        let mut window = self
+            .replay_window
+            .write()
+            .map_err(|_| EncryptionError::ChannelEstablishment("Lock poisoned".into()))?;
+        if !window.check_and_mark(message.counter) {
+            return Err(EncryptionError::AuthenticationFailed);
         }
 
-        decrypt(&self.decrypt_key, &message.encrypted)
+        Ok(plaintext)
+    }
+
+    /// Associated data binding a message to this channel, its sender and counter
+    fn message_aad(&self, sender: &ContentHash, counter: u64) -> Vec<u8> {
+        let mut aad = MESSAGE_AAD_CONTEXT.to_vec();
+        aad.extend_from_slice(self.channel_id.as_bytes());
+        aad.extend_from_slice(sender.as_bytes());
+        aad.extend_from_slice(&counter.to_be_bytes());
+        aad
+    }
+}
+
+impl std::fmt::Debug for SecureChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecureChannel")
+            .field("role", &self.role)
+            .field("cipher_suite", &self.cipher_suite)
+            .field("channel_id", &self.channel_id.to_hex())
+            .field("peer_identity", &self.peer_identity)
+            .finish()
     }
 }
 
 /// A message sent through a secure channel
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecureMessage {
-    /// The encrypted payload
-    pub encrypted: EncryptedMessage,
+    /// The encrypted payload, authenticated together with the header
+    pub encrypted: SealedMessage,
     /// Message counter for ordering and replay protection
     pub counter: u64,
     /// Sender's identifier
@@ -359,6 +584,10 @@ pub enum HandshakeMessage {
         public_key: [u8; PUBLIC_KEY_SIZE],
         /// Initiator's identity
         identity: Identity,
+        /// Supported cipher suites in order of preference
+        cipher_suites: Vec<CipherSuite>,
+        /// Identity signature over the ephemeral public key and cipher suites
+        signature: Vec<u8>,
     },
     /// Response from responder containing their public key
     Response {
@@ -366,37 +595,141 @@ pub enum HandshakeMessage {
         public_key: [u8; PUBLIC_KEY_SIZE],
         /// Responder's identity
         identity: Identity,
+        /// Cipher suite selected from the initiator's list
+        cipher_suite: CipherSuite,
+        /// Identity signature over both ephemeral public keys and the cipher suite
+        signature: Vec<u8>,
     },
+    /// Single-message resumption of an earlier channel (for initiator)
+    Resume {
+        /// Opaque ticket issued by the responder
+        ticket: SealedMessage,
+        /// Fresh random value making the resumed keys unique
+        nonce: [u8; RESUMPTION_NONCE_SIZE],
+    },
+}
+
+/// Bytes signed by the initiator
+///
+/// Covering the offered suites prevents a downgrade by stripping entries,
+/// and covering the identifier prevents stripping the expiry.
+fn init_transcript(
+    initiator_key: &[u8; PUBLIC_KEY_SIZE],
+    initiator: &Identity,
+    cipher_suites: &[CipherSuite],
+) -> Vec<u8> {
+    let mut transcript = INIT_SIGNATURE_CONTEXT.to_vec();
+    transcript.extend_from_slice(initiator_key);
+    transcript.extend_from_slice(initiator.identifier.as_bytes());
+    transcript.extend(cipher_suites.iter().map(|suite| *suite as u8));
+    transcript
+}
+
+/// Bytes signed by the responder, binding the response to the init
+fn response_transcript(
+    responder_key: &[u8; PUBLIC_KEY_SIZE],
+    responder: &Identity,
+    initiator_key: &[u8; PUBLIC_KEY_SIZE],
+    cipher_suite: CipherSuite,
+) -> Vec<u8> {
+    let mut transcript = RESPONSE_SIGNATURE_CONTEXT.to_vec();
+    transcript.extend_from_slice(responder_key);
+    transcript.extend_from_slice(responder.identifier.as_bytes());
+    transcript.extend_from_slice(initiator_key);
+    transcript.push(cipher_suite as u8);
+    transcript
 }
 
 /// Builder for establishing secure channels
 pub struct SecureChannelBuilder {
     local_keypair: KeyPair,
-    local_identity: Identity,
+    identity_key: Arc<dyn KeyProvider>,
+    cipher_suites: Vec<CipherSuite>,
+    init_signature: Vec<u8>,
+    trust: Option<(Arc<TrustStore>, String)>,
+    revocations: Option<Arc<RevocationList>>,
 }
 
 impl SecureChannelBuilder {
-    /// Create a new secure channel builder
+    /// Create a new secure channel builder with a throwaway identity
+    ///
+    /// Use [`with_identity`](Self::with_identity) so peers can recognise
+    /// this side across sessions.
     pub fn new() -> Result<Self, EncryptionError> {
+        Self::with_identity(Arc::new(IdentityKeyPair::generate()?))
+    }
+
+    /// Create a new secure channel builder using a long-term identity
+    ///
+    /// The key may live in software or in a hardware store; the init
+    /// signature is made here so a slow or unavailable device fails early.
+    /// An expired identity is rejected, since peers would refuse it anyway.
+    pub fn with_identity(identity_key: Arc<dyn KeyProvider>) -> Result<Self, EncryptionError> {
+        identity_key.identity().check_expiry()?;
+
         let local_keypair = KeyPair::generate()?;
-        let local_identity = local_keypair.identity();
+        let cipher_suites = CipherSuite::preference_order();
+        let init_signature = identity_key.sign(&init_transcript(
+            local_keypair.public_key(),
+            identity_key.identity(),
+            &cipher_suites,
+        ))?;
 
         Ok(Self {
             local_keypair,
-            local_identity,
+            identity_key,
+            cipher_suites,
+            init_signature,
+            trust: None,
+            revocations: None,
         })
     }
 
+    /// Restrict and order the cipher suites this side accepts
+    ///
+    /// Defaults to every suite, fastest for the current CPU first. The
+    /// initiator's order decides which common suite is used.
+    pub fn with_cipher_suites(
+        mut self,
+        cipher_suites: Vec<CipherSuite>,
+    ) -> Result<Self, EncryptionError> {
+        self.init_signature = self.identity_key.sign(&init_transcript(
+            self.local_keypair.public_key(),
+            self.identity_key.identity(),
+            &cipher_suites,
+        ))?;
+        self.cipher_suites = cipher_suites;
+        Ok(self)
+    }
+
+    /// Check the peer's identity against a trust store during the handshake
+    ///
+    /// `peer` is the key the store uses for the remote side, usually its
+    /// NodeId. Without a trust store any correctly signed identity is accepted.
+    pub fn with_trust_store(mut self, store: Arc<TrustStore>, peer: impl Into<String>) -> Self {
+        self.trust = Some((store, peer.into()));
+        self
+    }
+
+    /// Refuse peers whose identity is on a revocation list
+    pub fn with_revocation_list(mut self, revocations: Arc<RevocationList>) -> Self {
+        self.revocations = Some(revocations);
+        self
+    }
+
     /// Get the local identity
     pub fn local_identity(&self) -> &Identity {
-        &self.local_identity
+        self.identity_key.identity()
     }
 
     /// Create the initial handshake message (for initiator)
     pub fn create_init_message(&self) -> HandshakeMessage {
+        let public_key = *self.local_keypair.public_key();
         HandshakeMessage::Init {
-            public_key: *self.local_keypair.public_key(),
-            identity: self.local_identity.clone(),
+            public_key,
+            identity: self.local_identity().clone(),
+            cipher_suites: self.cipher_suites.clone(),
+            signature: self.init_signature.clone(),
         }
     }
 
@@ -405,11 +738,13 @@ impl SecureChannelBuilder {
         self,
         init: HandshakeMessage,
     ) -> Result<(SecureChannel, HandshakeMessage), EncryptionError> {
-        let (peer_public_key, peer_identity) = match init {
+        let (peer_public_key, peer_identity, peer_suites, peer_signature) = match init {
             HandshakeMessage::Init {
                 public_key,
                 identity,
-            } => (public_key, identity),
+                cipher_suites,
+                signature,
+            } => (public_key, identity, cipher_suites, signature),
             _ => {
                 return Err(EncryptionError::ChannelEstablishment(
                     "Expected Init message".into(),
@@ -417,10 +752,35 @@ impl SecureChannelBuilder {
             }
         };
 
+        // Authenticate the initiator's ephemeral key
+        peer_identity.verify(
+            &init_transcript(&peer_public_key, &peer_identity, &peer_suites),
+            &peer_signature,
+        )?;
+        self.check_peer_identity(&peer_identity)?;
+
+        // Pick the initiator's most preferred suite that we also accept
+        let cipher_suite = peer_suites
+            .iter()
+            .copied()
+            .find(|suite| self.cipher_suites.contains(suite))
+            .ok_or_else(|| {
+                EncryptionError::ChannelEstablishment("No common cipher suite".into())
+            })?;
+
         // Create response message
+        let public_key = *self.local_keypair.public_key();
+        let local_identity = self.local_identity().clone();
         let response = HandshakeMessage::Response {
-            public_key: *self.local_keypair.public_key(),
-            identity: self.local_identity.clone(),
+            public_key,
+            identity: local_identity.clone(),
+            cipher_suite,
+            signature: self.identity_key.sign(&response_transcript(
+                &public_key,
+                &local_identity,
+                &peer_public_key,
+                cipher_suite,
+            ))?,
         };
 
         // Perform key agreement
@@ -429,16 +789,13 @@ impl SecureChannelBuilder {
         // Derive keys with context including both identities
         let mut context = Vec::new();
         context.extend_from_slice(&peer_identity.public_key);
-        context.extend_from_slice(&self.local_identity.public_key);
+        context.extend_from_slice(&local_identity.public_key);
         let keys = shared_secret.derive_keys(&context);
 
         // Create secure channel as responder
-        let channel = SecureChannel::new(
-            ChannelRole::Responder,
-            keys,
-            self.local_identity,
-            peer_identity,
-        );
+        let channel =
+            SecureChannel::new(ChannelRole::Responder, keys, local_identity, peer_identity)
+                .with_cipher_suite(cipher_suite);
 
         Ok((channel, response))
     }
@@ -448,11 +805,13 @@ impl SecureChannelBuilder {
         self,
         response: HandshakeMessage,
     ) -> Result<SecureChannel, EncryptionError> {
-        let (peer_public_key, peer_identity) = match response {
+        let (peer_public_key, peer_identity, cipher_suite, peer_signature) = match response {
             HandshakeMessage::Response {
                 public_key,
                 identity,
-            } => (public_key, identity),
+                cipher_suite,
+                signature,
+            } => (public_key, identity, cipher_suite, signature),
             _ => {
                 return Err(EncryptionError::ChannelEstablishment(
                     "Expected Response message".into(),
@@ -460,25 +819,58 @@ impl SecureChannelBuilder {
             }
         };
 
+        // Authenticate the responder's ephemeral key and its binding to our init
+        peer_identity.verify(
+            &response_transcript(
+                &peer_public_key,
+                &peer_identity,
+                self.local_keypair.public_key(),
+                cipher_suite,
+            ),
+            &peer_signature,
+        )?;
+        self.check_peer_identity(&peer_identity)?;
+
+        if !self.cipher_suites.contains(&cipher_suite) {
+            return Err(EncryptionError::ChannelEstablishment(format!(
+                "Peer selected unoffered cipher suite {}",
+                cipher_suite
+            )));
+        }
+
+        let local_identity = self.local_identity().clone();
+
         // Perform key agreement
         let shared_secret = self.local_keypair.agree(&peer_public_key)?;
 
         // Derive keys with context including both identities
         let mut context = Vec::new();
-        context.extend_from_slice(&self.local_identity.public_key);
+        context.extend_from_slice(&local_identity.public_key);
         context.extend_from_slice(&peer_identity.public_key);
         let keys = shared_secret.derive_keys(&context);
 
         // Create secure channel as initiator
-        let channel = SecureChannel::new(
-            ChannelRole::Initiator,
-            keys,
-            self.local_identity,
-            peer_identity,
-        );
+        let channel =
+            SecureChannel::new(ChannelRole::Initiator, keys, local_identity, peer_identity)
+                .with_cipher_suite(cipher_suite);
 
         Ok(channel)
     }
+
+    /// Refuse expired, revoked or untrusted peer identities
+    pub(crate) fn check_peer_identity(
+        &self,
+        peer_identity: &Identity,
+    ) -> Result<(), EncryptionError> {
+        peer_identity.check_expiry()?;
+        if let Some(revocations) = &self.revocations {
+            revocations.check_identity(peer_identity)?;
+        }
+        if let Some((store, peer)) = &self.trust {
+            store.check(peer, peer_identity)?;
+        }
+        Ok(())
+    }
 }
 
 impl Default for SecureChannelBuilder {
@@ -494,6 +886,35 @@ impl Default for SecureChannelBuilder {
 mod tests {
     use super::*;
 
+    #[test]
+    fn debug_output_never_contains_key_material() {
+        let secret = SharedSecret::from_bytes([0x5A; 32]);
+        let keys = secret.derive_keys(b"debug test");
+        let secrets = [
+            hex::encode(secret.as_bytes()),
+            hex::encode(keys.initiator_key.as_bytes()),
+            hex::encode(keys.responder_key.as_bytes()),
+            hex::encode(*keys.resumption_secret),
+        ];
+        let keypair = KeyPair::generate().unwrap();
+        let debug = format!("{:?} {:?}", secret, keys);
+
+        let channel = SecureChannel::new(
+            ChannelRole::Initiator,
+            keys,
+            keypair.identity(),
+            keypair.identity(),
+        );
+        let debug = format!("{} {:?}", debug, channel);
+
+        assert!(debug.contains("REDACTED"));
+        assert!(!debug.contains("90, 90"));
+        for secret in &secrets {
+            assert!(!debug.contains(secret.as_str()));
+            assert!(!debug.contains(&secret[..16]));
+        }
+    }
+
     #[test]
     fn keypair_generation() {
         let keypair = KeyPair::generate().unwrap();
@@ -615,10 +1036,12 @@ mod tests {
                 HandshakeMessage::Init {
                     public_key: pk1,
                     identity: id1,
+                    ..
                 },
                 HandshakeMessage::Init {
                     public_key: pk2,
                     identity: id2,
+                    ..
                 },
             ) => {
                 assert_eq!(pk1, pk2);
@@ -628,6 +1051,211 @@ mod tests {
         }
     }
 
+    #[test]
+    fn persistent_identity_is_stable_across_sessions() {
+        let identity_key = Arc::new(IdentityKeyPair::generate().unwrap());
+
+        let mut peer_ids = Vec::new();
+        for _ in 0..2 {
+            let initiator = SecureChannelBuilder::with_identity(identity_key.clone()).unwrap();
+            let (responder_channel, response) = SecureChannelBuilder::new()
+                .unwrap()
+                .process_init(initiator.create_init_message())
+                .unwrap();
+            initiator.process_response(response).unwrap();
+            peer_ids.push(responder_channel.peer_identity().identifier);
+        }
+
+        assert_eq!(peer_ids[0], peer_ids[1]);
+        assert_eq!(peer_ids[0], identity_key.identity().identifier);
+    }
+
+    #[test]
+    fn forged_init_is_rejected() {
+        let victim = IdentityKeyPair::generate().unwrap();
+        let attacker = SecureChannelBuilder::new().unwrap();
+
+        // Claim the victim's identity while signing with another key
+        let forged = match attacker.create_init_message() {
+            HandshakeMessage::Init {
+                public_key,
+                cipher_suites,
+                signature,
+                ..
+            } => HandshakeMessage::Init {
+                public_key,
+                identity: victim.identity().clone(),
+                cipher_suites,
+                signature,
+            },
+            _ => unreachable!(),
+        };
+
+        let result = SecureChannelBuilder::new().unwrap().process_init(forged);
+        assert!(matches!(result, Err(EncryptionError::InvalidSignature(_))));
+    }
+
+    #[test]
+    fn response_for_other_init_is_rejected() {
+        let initiator = SecureChannelBuilder::new().unwrap();
+        let other = SecureChannelBuilder::new().unwrap();
+
+        // A response bound to a different init must not be accepted
+        let (_, response) = SecureChannelBuilder::new()
+            .unwrap()
+            .process_init(other.create_init_message())
+            .unwrap();
+
+        let result = initiator.process_response(response);
+        assert!(matches!(result, Err(EncryptionError::InvalidSignature(_))));
+    }
+
+    #[test]
+    fn cipher_suite_follows_initiator_preference() {
+        let initiator = SecureChannelBuilder::new()
+            .unwrap()
+            .with_cipher_suites(vec![CipherSuite::ChaCha20Poly1305, CipherSuite::Aes256Gcm])
+            .unwrap();
+        let responder = SecureChannelBuilder::new()
+            .unwrap()
+            .with_cipher_suites(vec![CipherSuite::Aes256Gcm, CipherSuite::ChaCha20Poly1305])
+            .unwrap();
+
+        let (responder_channel, response) = responder
+            .process_init(initiator.create_init_message())
+            .unwrap();
+        let initiator_channel = initiator.process_response(response).unwrap();
+
+        assert_eq!(
+            initiator_channel.cipher_suite(),
+            CipherSuite::ChaCha20Poly1305
+        );
+        assert_eq!(
+            responder_channel.cipher_suite(),
+            CipherSuite::ChaCha20Poly1305
+        );
+
+        let encrypted = initiator_channel.encrypt(b"over chacha").unwrap();
+        assert_eq!(
+            responder_channel.decrypt(&encrypted).unwrap(),
+            b"over chacha".to_vec()
+        );
+    }
+
+    #[test]
+    fn no_common_cipher_suite_fails() {
+        let initiator = SecureChannelBuilder::new()
+            .unwrap()
+            .with_cipher_suites(vec![CipherSuite::ChaCha20Poly1305])
+            .unwrap();
+        let responder = SecureChannelBuilder::new()
+            .unwrap()
+            .with_cipher_suites(vec![CipherSuite::Aes256Gcm])
+            .unwrap();
+
+        let result = responder.process_init(initiator.create_init_message());
+        assert!(matches!(
+            result,
+            Err(EncryptionError::ChannelEstablishment(_))
+        ));
+    }
+
+    #[test]
+    fn stripped_cipher_suites_break_signature() {
+        let initiator = SecureChannelBuilder::new().unwrap();
+        let downgraded = match initiator.create_init_message() {
+            HandshakeMessage::Init {
+                public_key,
+                identity,
+                signature,
+                ..
+            } => HandshakeMessage::Init {
+                public_key,
+                identity,
+                cipher_suites: vec![CipherSuite::Aes256Gcm],
+                signature,
+            },
+            _ => unreachable!(),
+        };
+
+        let result = SecureChannelBuilder::new()
+            .unwrap()
+            .process_init(downgraded);
+        assert!(matches!(result, Err(EncryptionError::InvalidSignature(_))));
+    }
+
+    #[test]
+    fn trust_store_rejects_changed_peer_identity() {
+        let store = Arc::new(TrustStore::new());
+        let server_identity = Arc::new(IdentityKeyPair::generate().unwrap());
+
+        let handshake = |server_identity: Arc<IdentityKeyPair>| {
+            let initiator = SecureChannelBuilder::new()
+                .unwrap()
+                .with_trust_store(store.clone(), "server");
+            let responder = SecureChannelBuilder::with_identity(server_identity).unwrap();
+            let (_, response) = responder
+                .process_init(initiator.create_init_message())
+                .unwrap();
+            initiator.process_response(response)
+        };
+
+        assert!(handshake(server_identity.clone()).is_ok());
+        assert!(handshake(server_identity).is_ok());
+        assert!(matches!(
+            handshake(Arc::new(IdentityKeyPair::generate().unwrap())),
+            Err(EncryptionError::IdentityChanged { .. })
+        ));
+    }
+
+    #[test]
+    fn mismatched_identifier_fails_deserialization() {
+        let identity = IdentityKeyPair::generate().unwrap().identity().clone();
+        let mut json: serde_json::Value = serde_json::to_value(&identity).unwrap();
+        json["public_key"] = serde_json::Value::String(hex::encode(
+            &IdentityKeyPair::generate().unwrap().identity().public_key,
+        ));
+
+        assert!(serde_json::from_value::<Identity>(json).is_err());
+    }
+
+    #[test]
+    fn tampered_counter_is_rejected() {
+        let initiator_builder = SecureChannelBuilder::new().unwrap();
+        let (responder_channel, response_msg) = SecureChannelBuilder::new()
+            .unwrap()
+            .process_init(initiator_builder.create_init_message())
+            .unwrap();
+        let initiator_channel = initiator_builder.process_response(response_msg).unwrap();
+
+        // Rewriting the header must invalidate the message
+        let mut message = initiator_channel.encrypt(b"counter bound").unwrap();
+        message.counter += 100;
+        assert!(responder_channel.decrypt(&message).is_err());
+
+        // The forged counter must not have consumed a replay window slot
+        message.counter -= 100;
+        assert_eq!(
+            responder_channel.decrypt(&message).unwrap(),
+            b"counter bound".to_vec()
+        );
+    }
+
+    #[test]
+    fn both_ends_share_channel_id() {
+        let initiator_builder = SecureChannelBuilder::new().unwrap();
+        let (responder_channel, response_msg) = SecureChannelBuilder::new()
+            .unwrap()
+            .process_init(initiator_builder.create_init_message())
+            .unwrap();
+        let initiator_channel = initiator_builder.process_response(response_msg).unwrap();
+
+        assert_eq!(
+            initiator_channel.channel_id(),
+            responder_channel.channel_id()
+        );
+    }
+
     #[test]
     fn replay_attack_prevention() {
         // Establish channel
@@ -0,0 +1,537 @@
+//! Streaming chunked AEAD
+//!
+//! Encrypts arbitrarily large payloads with constant memory using the STREAM
+//! construction: the payload is split into fixed-size chunks, each sealed
+//! with a nonce made of a random per-stream prefix, the chunk index and a
+//! final-chunk flag. Reordering, dropping or truncating chunks makes
+//! decryption fail.
+//!
+//! Wire format: a [`STREAM_HEADER_SIZE`]-byte header (version, cipher suite,
+//! chunk size, nonce prefix) followed by the sealed chunks. The header is
+//! authenticated as associated data of every chunk.
+//!
+//! # Requirements Coverage
+//! - Requirement 4.1: End-to-end encryption
+//! - Requirement 4.5: Integrity verification of transferred data
+
+use crate::encryption::cipher::{open, seal, CipherSuite, EncryptionKey, NONCE_SIZE, TAG_SIZE};
+use crate::error::EncryptionError;
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Default plaintext chunk size (64 KiB)
+pub const DEFAULT_STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Maximum plaintext chunk size (16 MiB)
+pub const MAX_STREAM_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Size of the stream header in bytes
+pub const STREAM_HEADER_SIZE: usize = 1 + 1 + 4 + NONCE_PREFIX_SIZE;
+
+/// Current stream format version
+const STREAM_VERSION: u8 = 1;
+
+/// Random per-stream part of each chunk nonce
+const NONCE_PREFIX_SIZE: usize = NONCE_SIZE - 4 - 1;
+
+/// Parsed stream header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamHeader {
+    /// Cipher used for every chunk
+    pub cipher_suite: CipherSuite,
+    /// Plaintext bytes per chunk (the last chunk may be shorter)
+    pub chunk_size: usize,
+    /// Random nonce prefix unique to this stream
+    nonce_prefix: [u8; NONCE_PREFIX_SIZE],
+}
+
+impl StreamHeader {
+    /// Encode the header for transmission
+    pub fn to_bytes(&self) -> [u8; STREAM_HEADER_SIZE] {
+        let mut bytes = [0u8; STREAM_HEADER_SIZE];
+        bytes[0] = STREAM_VERSION;
+        bytes[1] = self.cipher_suite as u8;
+        bytes[2..6].copy_from_slice(&(self.chunk_size as u32).to_be_bytes());
+        bytes[6..].copy_from_slice(&self.nonce_prefix);
+        bytes
+    }
+
+    /// Decode a received header
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EncryptionError> {
+        if bytes.len() != STREAM_HEADER_SIZE {
+            return Err(EncryptionError::Stream(format!(
+                "Invalid header length {}",
+                bytes.len()
+            )));
+        }
+        if bytes[0] != STREAM_VERSION {
+            return Err(EncryptionError::Stream(format!(
+                "Unsupported stream version {}",
+                bytes[0]
+            )));
+        }
+
+        let cipher_suite = CipherSuite::ALL
+            .iter()
+            .copied()
+            .find(|suite| *suite as u8 == bytes[1])
+            .ok_or_else(|| EncryptionError::Stream(format!("Unknown cipher suite {}", bytes[1])))?;
+
+        let mut size_bytes = [0u8; 4];
+        size_bytes.copy_from_slice(&bytes[2..6]);
+        let chunk_size = u32::from_be_bytes(size_bytes) as usize;
+        validate_chunk_size(chunk_size)?;
+
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_SIZE];
+        nonce_prefix.copy_from_slice(&bytes[6..]);
+
+        Ok(Self {
+            cipher_suite,
+            chunk_size,
+            nonce_prefix,
+        })
+    }
+
+    /// Nonce for the chunk at `index`
+    fn nonce(&self, index: u32, last: bool) -> [u8; NONCE_SIZE] {
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce[..NONCE_PREFIX_SIZE].copy_from_slice(&self.nonce_prefix);
+        nonce[NONCE_PREFIX_SIZE..NONCE_SIZE - 1].copy_from_slice(&index.to_be_bytes());
+        nonce[NONCE_SIZE - 1] = u8::from(last);
+        nonce
+    }
+}
+
+fn validate_chunk_size(chunk_size: usize) -> Result<(), EncryptionError> {
+    if chunk_size == 0 || chunk_size > MAX_STREAM_CHUNK_SIZE {
+        return Err(EncryptionError::Stream(format!(
+            "Chunk size must be between 1 and {} bytes, got {}",
+            MAX_STREAM_CHUNK_SIZE, chunk_size
+        )));
+    }
+    Ok(())
+}
+
+/// Chunk position tracking shared by both directions
+struct ChunkCounter {
+    next: u32,
+    finished: bool,
+}
+
+impl ChunkCounter {
+    fn advance(&mut self, last: bool) -> Result<u32, EncryptionError> {
+        if self.finished {
+            return Err(EncryptionError::Stream("Stream already finished".into()));
+        }
+        let index = self.next;
+        self.next = self
+            .next
+            .checked_add(1)
+            .ok_or_else(|| EncryptionError::Stream("Too many chunks in stream".into()))?;
+        self.finished = last;
+        Ok(index)
+    }
+}
+
+/// Encrypts a payload chunk by chunk
+pub struct StreamEncryptor {
+    key: EncryptionKey,
+    header: StreamHeader,
+    header_bytes: [u8; STREAM_HEADER_SIZE],
+    counter: ChunkCounter,
+}
+
+impl StreamEncryptor {
+    /// Start a new stream with a random nonce prefix
+    pub fn new(
+        cipher_suite: CipherSuite,
+        key: EncryptionKey,
+        chunk_size: usize,
+    ) -> Result<Self, EncryptionError> {
+        validate_chunk_size(chunk_size)?;
+
+        let mut nonce_prefix = [0u8; NONCE_PREFIX_SIZE];
+        SystemRandom::new()
+            .fill(&mut nonce_prefix)
+            .map_err(|_| EncryptionError::Encryption("Failed to generate nonce".into()))?;
+
+        let header = StreamHeader {
+            cipher_suite,
+            chunk_size,
+            nonce_prefix,
+        };
+        Ok(Self {
+            key,
+            header,
+            header_bytes: header.to_bytes(),
+            counter: ChunkCounter {
+                next: 0,
+                finished: false,
+            },
+        })
+    }
+
+    /// Header to send before the first chunk
+    pub fn header(&self) -> &[u8; STREAM_HEADER_SIZE] {
+        &self.header_bytes
+    }
+
+    /// Plaintext bytes per chunk
+    pub fn chunk_size(&self) -> usize {
+        self.header.chunk_size
+    }
+
+    /// Encrypt a full intermediate chunk
+    pub fn encrypt_chunk(&mut self, chunk: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        if chunk.len() != self.header.chunk_size {
+            return Err(EncryptionError::Stream(format!(
+                "Intermediate chunks must be exactly {} bytes, got {}",
+                self.header.chunk_size,
+                chunk.len()
+            )));
+        }
+        self.seal_chunk(chunk, false)
+    }
+
+    /// Encrypt the final chunk, which may be shorter or empty
+    pub fn encrypt_last(mut self, chunk: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        if chunk.len() > self.header.chunk_size {
+            return Err(EncryptionError::Stream(format!(
+                "Final chunk exceeds {} bytes",
+                self.header.chunk_size
+            )));
+        }
+        self.seal_chunk(chunk, true)
+    }
+
+    fn seal_chunk(&mut self, chunk: &[u8], last: bool) -> Result<Vec<u8>, EncryptionError> {
+        let index = self.counter.advance(last)?;
+        let mut data = Vec::with_capacity(chunk.len() + TAG_SIZE);
+        data.extend_from_slice(chunk);
+        seal(
+            self.header.cipher_suite,
+            &self.key,
+            self.header.nonce(index, last),
+            &self.header_bytes,
+            &mut data,
+        )?;
+        Ok(data)
+    }
+}
+
+/// Decrypts a payload produced by [`StreamEncryptor`]
+pub struct StreamDecryptor {
+    key: EncryptionKey,
+    header: StreamHeader,
+    header_bytes: [u8; STREAM_HEADER_SIZE],
+    counter: ChunkCounter,
+}
+
+impl StreamDecryptor {
+    /// Start decrypting a stream from its header
+    pub fn new(key: EncryptionKey, header: &[u8]) -> Result<Self, EncryptionError> {
+        let parsed = StreamHeader::from_bytes(header)?;
+        Ok(Self {
+            key,
+            header: parsed,
+            header_bytes: parsed.to_bytes(),
+            counter: ChunkCounter {
+                next: 0,
+                finished: false,
+            },
+        })
+    }
+
+    /// Size of each sealed intermediate chunk
+    pub fn sealed_chunk_size(&self) -> usize {
+        self.header.chunk_size + TAG_SIZE
+    }
+
+    /// Decrypt an intermediate chunk
+    pub fn decrypt_chunk(&mut self, sealed: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        if sealed.len() != self.sealed_chunk_size() {
+            return Err(EncryptionError::Decryption);
+        }
+        self.open_chunk(sealed, false)
+    }
+
+    /// Decrypt the final chunk
+    pub fn decrypt_last(mut self, sealed: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        if sealed.len() < TAG_SIZE || sealed.len() > self.sealed_chunk_size() {
+            return Err(EncryptionError::Decryption);
+        }
+        self.open_chunk(sealed, true)
+    }
+
+    fn open_chunk(&mut self, sealed: &[u8], last: bool) -> Result<Vec<u8>, EncryptionError> {
+        let index = self.counter.advance(last)?;
+        open(
+            self.header.cipher_suite,
+            &self.key,
+            self.header.nonce(index, last),
+            &self.header_bytes,
+            sealed,
+        )
+    }
+}
+
+/// Fill `buf` from `reader`, returning fewer bytes only at end of input
+async fn read_full<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buf: &mut [u8],
+) -> Result<usize, EncryptionError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let n = reader
+            .read(&mut buf[filled..])
+            .await
+            .map_err(|e| EncryptionError::Stream(format!("Read failed: {}", e)))?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    Ok(filled)
+}
+
+/// Reads fixed-size blocks, looking one block ahead to flag the last one
+///
+/// Holds at most two blocks in memory regardless of input length.
+struct BlockReader<'a, R> {
+    reader: &'a mut R,
+    block_size: usize,
+    current: Vec<u8>,
+    current_len: usize,
+    next: Vec<u8>,
+    next_len: usize,
+    started: bool,
+    done: bool,
+}
+
+impl<'a, R: AsyncRead + Unpin> BlockReader<'a, R> {
+    fn new(reader: &'a mut R, block_size: usize) -> Self {
+        Self {
+            reader,
+            block_size,
+            current: vec![0u8; block_size],
+            current_len: 0,
+            next: vec![0u8; block_size],
+            next_len: 0,
+            started: false,
+            done: false,
+        }
+    }
+
+    /// Move to the next block; returns whether it is the last, or `None` when done
+    async fn advance(&mut self) -> Result<Option<bool>, EncryptionError> {
+        if self.done {
+            return Ok(None);
+        }
+
+        if self.started {
+            std::mem::swap(&mut self.current, &mut self.next);
+            self.current_len = self.next_len;
+        } else {
+            self.started = true;
+            self.current_len = read_full(self.reader, &mut self.current).await?;
+        }
+
+        self.next_len = if self.current_len == self.block_size {
+            read_full(self.reader, &mut self.next).await?
+        } else {
+            0
+        };
+        self.done = self.next_len == 0;
+        Ok(Some(self.done))
+    }
+
+    /// The current block
+    fn block(&self) -> &[u8] {
+        &self.current[..self.current_len]
+    }
+}
+
+/// Encrypt everything from `reader` into `writer` with constant memory
+///
+/// Returns the number of plaintext bytes encrypted.
+pub async fn encrypt_stream<R, W>(
+    cipher_suite: CipherSuite,
+    key: EncryptionKey,
+    chunk_size: usize,
+    reader: &mut R,
+    writer: &mut W,
+) -> Result<u64, EncryptionError>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut encryptor = StreamEncryptor::new(cipher_suite, key, chunk_size)?;
+    write_all(writer, encryptor.header()).await?;
+
+    let mut blocks = BlockReader::new(reader, chunk_size);
+    let mut total = 0u64;
+    while let Some(last) = blocks.advance().await? {
+        let chunk = blocks.block();
+        total += chunk.len() as u64;
+        if last {
+            let sealed = encryptor.encrypt_last(chunk)?;
+            write_all(writer, &sealed).await?;
+            break;
+        }
+        let sealed = encryptor.encrypt_chunk(chunk)?;
+        write_all(writer, &sealed).await?;
+    }
+
+    writer
+        .flush()
+        .await
+        .map_err(|e| EncryptionError::Stream(format!("Write failed: {}", e)))?;
+    Ok(total)
+}
+
+/// Decrypt a stream produced by [`encrypt_stream`] with constant memory
+///
+/// Fails if the input is truncated, reordered or tampered with. Plaintext of
+/// earlier chunks may already have been written when a later chunk fails,
+/// so callers should discard the output on error.
+pub async fn decrypt_stream<R, W>(
+    key: EncryptionKey,
+    reader: &mut R,
+    writer: &mut W,
+) -> Result<u64, EncryptionError>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut header = [0u8; STREAM_HEADER_SIZE];
+    if read_full(reader, &mut header).await? != STREAM_HEADER_SIZE {
+        return Err(EncryptionError::Stream("Truncated stream header".into()));
+    }
+    let mut decryptor = StreamDecryptor::new(key, &header)?;
+
+    let mut blocks = BlockReader::new(reader, decryptor.sealed_chunk_size());
+    let mut total = 0u64;
+    while let Some(last) = blocks.advance().await? {
+        if last {
+            let plaintext = decryptor.decrypt_last(blocks.block())?;
+            total += plaintext.len() as u64;
+            write_all(writer, &plaintext).await?;
+            break;
+        }
+        let plaintext = decryptor.decrypt_chunk(blocks.block())?;
+        total += plaintext.len() as u64;
+        write_all(writer, &plaintext).await?;
+    }
+
+    writer
+        .flush()
+        .await
+        .map_err(|e| EncryptionError::Stream(format!("Write failed: {}", e)))?;
+    Ok(total)
+}
+
+async fn write_all<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    data: &[u8],
+) -> Result<(), EncryptionError> {
+    writer
+        .write_all(data)
+        .await
+        .map_err(|e| EncryptionError::Stream(format!("Write failed: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn roundtrip(suite: CipherSuite, chunk_size: usize, len: usize) {
+        let key = EncryptionKey::generate().unwrap();
+        let plaintext: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+
+        let mut sealed = Vec::new();
+        let written = encrypt_stream(
+            suite,
+            key.clone(),
+            chunk_size,
+            &mut &plaintext[..],
+            &mut sealed,
+        )
+        .await
+        .unwrap();
+        assert_eq!(written, len as u64);
+
+        let chunks = len.div_ceil(chunk_size).max(1);
+        assert_eq!(sealed.len(), STREAM_HEADER_SIZE + len + chunks * TAG_SIZE);
+
+        let mut opened = Vec::new();
+        decrypt_stream(key, &mut &sealed[..], &mut opened)
+            .await
+            .unwrap();
+        assert_eq!(opened, plaintext);
+    }
+
+    #[tokio::test]
+    async fn roundtrip_various_lengths() {
+        for len in [0, 1, 15, 16, 17, 64, 1000] {
+            roundtrip(CipherSuite::Aes256Gcm, 16, len).await;
+            roundtrip(CipherSuite::ChaCha20Poly1305, 16, len).await;
+        }
+    }
+
+    async fn seal_fixture() -> (EncryptionKey, Vec<u8>) {
+        let key = EncryptionKey::generate().unwrap();
+        let mut sealed = Vec::new();
+        encrypt_stream(
+            CipherSuite::Aes256Gcm,
+            key.clone(),
+            8,
+            &mut &[7u8; 40][..],
+            &mut sealed,
+        )
+        .await
+        .unwrap();
+        (key, sealed)
+    }
+
+    #[tokio::test]
+    async fn truncation_is_detected() {
+        let (key, sealed) = seal_fixture().await;
+        // Drop the final chunk so the stream ends on a full intermediate chunk
+        let truncated = &sealed[..STREAM_HEADER_SIZE + 2 * (8 + TAG_SIZE)];
+        let result = decrypt_stream(key, &mut &truncated[..], &mut Vec::new()).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn reordered_chunks_are_detected() {
+        let (key, mut sealed) = seal_fixture().await;
+        let sealed_chunk = 8 + TAG_SIZE;
+        let (first, second) = sealed[STREAM_HEADER_SIZE..].split_at_mut(sealed_chunk);
+        first.swap_with_slice(&mut second[..sealed_chunk]);
+
+        let result = decrypt_stream(key, &mut &sealed[..], &mut Vec::new()).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn tampered_header_is_detected() {
+        let (key, mut sealed) = seal_fixture().await;
+        sealed[STREAM_HEADER_SIZE - 1] ^= 0x01;
+
+        let result = decrypt_stream(key, &mut &sealed[..], &mut Vec::new()).await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn encryptor_rejects_short_intermediate_chunk() {
+        let key = EncryptionKey::generate().unwrap();
+        let mut encryptor = StreamEncryptor::new(CipherSuite::Aes256Gcm, key, 8).unwrap();
+        assert!(encryptor.encrypt_chunk(&[0u8; 4]).is_err());
+    }
+
+    #[test]
+    fn invalid_chunk_size_is_rejected() {
+        let key = EncryptionKey::generate().unwrap();
+        assert!(StreamEncryptor::new(CipherSuite::Aes256Gcm, key, 0).is_err());
+    }
+}
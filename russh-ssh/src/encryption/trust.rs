@@ -0,0 +1,385 @@
+//! Trust-on-first-use store for peer identities
+//!
+//! The P2P counterpart of `known_hosts`: each peer (usually an iroh NodeId)
+//! is mapped to the identity it presented on first contact. Later handshakes
+//! must present the same identity, and peers can be pinned ahead of time,
+//! marked verified after an out-of-band fingerprint check, or revoked.
+//!
+//! # Requirements Coverage
+//! - Requirement 4.2: Mutual authentication between peers
+
+use crate::encryption::secure_channel::Identity;
+use crate::error::EncryptionError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// File name of the trust store inside the configuration directory
+pub const TRUST_STORE_FILE_NAME: &str = "trusted_peers.json";
+
+/// How far a peer's identity has been confirmed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrustLevel {
+    /// Recorded automatically on first contact
+    FirstSeen,
+    /// Added explicitly before first contact
+    Pinned,
+    /// Fingerprint confirmed out of band
+    Verified,
+    /// Never accept this identity again
+    Revoked,
+}
+
+/// Policy for peers that are not in the store yet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrustPolicy {
+    /// Reject unknown peers; only pinned or verified peers connect
+    Strict,
+    /// Record unknown peers on first use, reject changed identities
+    #[default]
+    AcceptNew,
+    /// Skip checks entirely (insecure)
+    None,
+}
+
+/// Trust record for a single peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustEntry {
+    /// Identity the peer is expected to present
+    pub identity: Identity,
+    /// Current trust level
+    pub level: TrustLevel,
+    /// When the identity was first recorded
+    pub first_seen: DateTime<Utc>,
+    /// When the identity was last presented in a handshake
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+impl TrustEntry {
+    fn new(identity: Identity, level: TrustLevel) -> Self {
+        Self {
+            identity,
+            level,
+            first_seen: Utc::now(),
+            last_seen: None,
+        }
+    }
+
+    /// Fingerprint of the expected identity
+    pub fn fingerprint(&self) -> String {
+        self.identity.identifier_hex()
+    }
+}
+
+/// Outcome of a successful trust check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustDecision {
+    /// Peer was unknown and has now been recorded
+    FirstUse,
+    /// Peer matched its stored identity at the given level
+    Known(TrustLevel),
+    /// Checking is disabled by policy
+    Unchecked,
+}
+
+/// Persisted mapping of peers to their trusted identities
+#[derive(Debug, Default)]
+pub struct TrustStore {
+    /// File the store is saved to, if any
+    path: Option<PathBuf>,
+    /// Policy for unknown peers
+    policy: TrustPolicy,
+    /// Entries keyed by peer id
+    entries: RwLock<HashMap<String, TrustEntry>>,
+}
+
+impl TrustStore {
+    /// Create an empty in-memory store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Default location of the trust store file
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("russh").join(TRUST_STORE_FILE_NAME))
+    }
+
+    /// Open the store at `path`, starting empty if the file does not exist
+    ///
+    /// Changes are written back to the same file.
+    pub fn open(path: &Path) -> Result<Self, EncryptionError> {
+        let entries = if path.exists() {
+            let json = std::fs::read_to_string(path).map_err(|e| {
+                EncryptionError::KeyStorage(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+            serde_json::from_str(&json).map_err(|e| {
+                EncryptionError::KeyStorage(format!(
+                    "Invalid trust store {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            policy: TrustPolicy::default(),
+            entries: RwLock::new(entries),
+        })
+    }
+
+    /// Set the policy for unknown peers
+    pub fn with_policy(mut self, policy: TrustPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Get the policy for unknown peers
+    pub fn policy(&self) -> TrustPolicy {
+        self.policy
+    }
+
+    /// Check a peer's identity, recording it if this is the first contact
+    pub fn check(&self, peer: &str, identity: &Identity) -> Result<TrustDecision, EncryptionError> {
+        if self.policy == TrustPolicy::None {
+            tracing::warn!(peer, "Peer identity check disabled - INSECURE");
+            return Ok(TrustDecision::Unchecked);
+        }
+
+        let decision = {
+            let mut entries = self.write_entries();
+
+            if entries.values().any(|entry| {
+                entry.level == TrustLevel::Revoked
+                    && entry.identity.identifier == identity.identifier
+            }) {
+                return Err(EncryptionError::UntrustedPeer(format!(
+                    "identity {} for {} has been revoked",
+                    identity.identifier_hex(),
+                    peer
+                )));
+            }
+
+            match entries.get_mut(peer) {
+                Some(entry) if entry.level == TrustLevel::Revoked => {
+                    return Err(EncryptionError::UntrustedPeer(format!(
+                        "{} has been revoked",
+                        peer
+                    )));
+                }
+                Some(entry) if entry.identity.identifier != identity.identifier => {
+                    return Err(EncryptionError::IdentityChanged {
+                        peer: peer.to_string(),
+                        expected: entry.fingerprint(),
+                        actual: identity.identifier_hex(),
+                    });
+                }
+                Some(entry) => {
+                    entry.last_seen = Some(Utc::now());
+                    TrustDecision::Known(entry.level)
+                }
+                None if self.policy == TrustPolicy::Strict => {
+                    return Err(EncryptionError::UntrustedPeer(format!(
+                        "{} is not pinned or verified",
+                        peer
+                    )));
+                }
+                None => {
+                    let mut entry = TrustEntry::new(identity.clone(), TrustLevel::FirstSeen);
+                    entry.last_seen = Some(entry.first_seen);
+                    entries.insert(peer.to_string(), entry);
+                    tracing::info!(
+                        peer,
+                        fingerprint = %identity.identifier_hex(),
+                        "Trusting new peer on first use"
+                    );
+                    TrustDecision::FirstUse
+                }
+            }
+        };
+
+        self.save()?;
+        Ok(decision)
+    }
+
+    /// Pin the identity a peer must present, replacing any previous record
+    pub fn pin(&self, peer: &str, identity: Identity) -> Result<(), EncryptionError> {
+        self.write_entries().insert(
+            peer.to_string(),
+            TrustEntry::new(identity, TrustLevel::Pinned),
+        );
+        self.save()
+    }
+
+    /// Mark a known peer as verified out of band
+    pub fn mark_verified(&self, peer: &str) -> Result<(), EncryptionError> {
+        self.set_level(peer, TrustLevel::Verified)
+    }
+
+    /// Revoke a peer's identity so it is rejected from now on
+    pub fn revoke(&self, peer: &str) -> Result<(), EncryptionError> {
+        self.set_level(peer, TrustLevel::Revoked)
+    }
+
+    /// Forget a peer entirely, so its next identity is trusted on first use
+    pub fn remove(&self, peer: &str) -> Result<Option<TrustEntry>, EncryptionError> {
+        let removed = self.write_entries().remove(peer);
+        self.save()?;
+        Ok(removed)
+    }
+
+    /// Get the trust record for a peer
+    pub fn get(&self, peer: &str) -> Option<TrustEntry> {
+        self.read_entries().get(peer).cloned()
+    }
+
+    /// List all trust records
+    pub fn entries(&self) -> Vec<(String, TrustEntry)> {
+        let mut entries: Vec<_> = self
+            .read_entries()
+            .iter()
+            .map(|(peer, entry)| (peer.clone(), entry.clone()))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    /// Write the store to its file, if it has one
+    pub fn save(&self) -> Result<(), EncryptionError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let storage_error =
+            |e: std::io::Error| EncryptionError::KeyStorage(format!("{}: {}", path.display(), e));
+
+        let json = serde_json::to_string_pretty(&*self.read_entries()).map_err(|e| {
+            EncryptionError::KeyStorage(format!("Failed to encode trust store: {}", e))
+        })?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(storage_error)?;
+        }
+
+        // Write to a temporary file and rename so a crash never leaves a truncated store
+        let tmp_path = path.with_extension("json.tmp");
+        let mut file = std::fs::File::create(&tmp_path).map_err(storage_error)?;
+        file.write_all(json.as_bytes()).map_err(storage_error)?;
+        file.sync_all().map_err(storage_error)?;
+        std::fs::rename(&tmp_path, path).map_err(storage_error)
+    }
+
+    fn set_level(&self, peer: &str, level: TrustLevel) -> Result<(), EncryptionError> {
+        match self.write_entries().get_mut(peer) {
+            Some(entry) => entry.level = level,
+            None => {
+                return Err(EncryptionError::UntrustedPeer(format!(
+                    "{} is not in the trust store",
+                    peer
+                )))
+            }
+        }
+        self.save()
+    }
+
+    fn read_entries(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, TrustEntry>> {
+        self.entries.read().unwrap_or_else(|poisoned| {
+            tracing::warn!("Trust store lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+
+    fn write_entries(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, TrustEntry>> {
+        self.entries.write().unwrap_or_else(|poisoned| {
+            tracing::warn!("Trust store lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::identity::IdentityKeyPair;
+
+    fn identity() -> Identity {
+        IdentityKeyPair::generate().unwrap().identity().clone()
+    }
+
+    #[test]
+    fn first_use_is_recorded_and_changes_rejected() {
+        let store = TrustStore::new();
+        let alice = identity();
+
+        assert_eq!(
+            store.check("alice", &alice).unwrap(),
+            TrustDecision::FirstUse
+        );
+        assert_eq!(
+            store.check("alice", &alice).unwrap(),
+            TrustDecision::Known(TrustLevel::FirstSeen)
+        );
+        assert!(matches!(
+            store.check("alice", &identity()),
+            Err(EncryptionError::IdentityChanged { .. })
+        ));
+    }
+
+    #[test]
+    fn strict_policy_requires_pinning() {
+        let store = TrustStore::new().with_policy(TrustPolicy::Strict);
+        let bob = identity();
+
+        assert!(matches!(
+            store.check("bob", &bob),
+            Err(EncryptionError::UntrustedPeer(_))
+        ));
+
+        store.pin("bob", bob.clone()).unwrap();
+        assert_eq!(
+            store.check("bob", &bob).unwrap(),
+            TrustDecision::Known(TrustLevel::Pinned)
+        );
+
+        store.mark_verified("bob").unwrap();
+        assert_eq!(store.get("bob").unwrap().level, TrustLevel::Verified);
+    }
+
+    #[test]
+    fn revoked_identity_is_rejected_under_any_peer_id() {
+        let store = TrustStore::new();
+        let mallory = identity();
+        store.check("mallory", &mallory).unwrap();
+        store.revoke("mallory").unwrap();
+
+        assert!(matches!(
+            store.check("mallory", &mallory),
+            Err(EncryptionError::UntrustedPeer(_))
+        ));
+        assert!(matches!(
+            store.check("new-node-id", &mallory),
+            Err(EncryptionError::UntrustedPeer(_))
+        ));
+    }
+
+    #[test]
+    fn store_persists_across_opens() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join(TRUST_STORE_FILE_NAME);
+        let carol = identity();
+
+        let store = TrustStore::open(&path).unwrap();
+        store.check("carol", &carol).unwrap();
+        store.mark_verified("carol").unwrap();
+
+        let reopened = TrustStore::open(&path).unwrap();
+        let entry = reopened.get("carol").unwrap();
+        assert_eq!(entry.level, TrustLevel::Verified);
+        assert_eq!(entry.identity.identifier, carol.identifier);
+    }
+}
@@ -41,6 +41,10 @@ pub enum ConnectionError {
     /// Invalid configuration
     #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
+
+    /// Proxy handshake or tunnel setup failed
+    #[error("Proxy error: {0}")]
+    Proxy(String),
 }
 
 /// Errors that can occur during SSH operations
@@ -73,6 +77,30 @@ pub enum SshError {
     /// Connection error
     #[error("Connection error: {0}")]
     Connection(#[from] ConnectionError),
+
+    /// Automatic reconnection failed
+    #[error("Reconnection failed: {0}")]
+    Reconnection(#[from] ReconnectionError),
+
+    /// Per-host connection limit reached
+    #[error("Connection pool exhausted for {host} (limit {limit})")]
+    PoolExhausted { host: String, limit: usize },
+
+    /// SSH key could not be generated, read or written
+    #[error("Key error: {0}")]
+    Key(String),
+
+    /// An SSH agent failed or refused a request
+    #[error("Agent error: {0}")]
+    Agent(String),
+
+    /// The session multiplexer failed or refused a request
+    #[error("Multiplexer error: {0}")]
+    Mux(String),
+
+    /// A host key could not be fetched, or known_hosts read or written
+    #[error("Host key error: {0}")]
+    HostKey(String),
 }
 
 /// Errors that can occur during encryption operations
@@ -105,6 +133,41 @@ pub enum EncryptionError {
     /// Invalid key format
     #[error("Invalid key format: {0}")]
     InvalidKeyFormat(String),
+
+    /// Signature did not verify against the claimed identity
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+
+    /// Reading or writing stored key material failed
+    #[error("Key storage error: {0}")]
+    KeyStorage(String),
+
+    /// Streaming encryption failed (I/O or framing)
+    #[error("Encrypted stream error: {0}")]
+    Stream(String),
+
+    /// Peer is not trusted by the local trust store
+    #[error("Untrusted peer: {0}")]
+    UntrustedPeer(String),
+
+    /// Peer presented a different identity than the one on record
+    #[error("Identity for {peer} changed: expected {expected}, got {actual}")]
+    IdentityChanged {
+        peer: String,
+        expected: String,
+        actual: String,
+    },
+
+    /// Identity is past its expiry time
+    #[error("Identity {identity} expired at {expired_at}")]
+    IdentityExpired {
+        identity: String,
+        expired_at: chrono::DateTime<chrono::Utc>,
+    },
+
+    /// Identity is on the revocation list
+    #[error("Identity {identity} has been revoked: {reason}")]
+    IdentityRevoked { identity: String, reason: String },
 }
 
 /// Errors that can occur during VDFS operations
@@ -145,6 +208,54 @@ pub enum VdfsError {
     /// Chunk not found
     #[error("Chunk not found: {0}")]
     ChunkNotFound(String),
+
+    /// Requested file version is not in the history
+    #[error("Version {version} of {} not found", path.display())]
+    VersionNotFound { path: PathBuf, version: u64 },
+
+    /// Snapshot not found
+    #[error("Snapshot not found: {0}")]
+    SnapshotNotFound(String),
+
+    /// Filesystem watcher failed
+    #[error("Watch error: {0}")]
+    Watch(String),
+
+    /// Chunk encryption or decryption failed
+    #[error("Chunk encryption error: {0}")]
+    Encryption(#[from] EncryptionError),
+
+    /// Extended attribute name or value rejected
+    #[error("Invalid attribute: {0}")]
+    InvalidAttribute(String),
+
+    /// Path is locked by another peer
+    #[error("{} is locked by {owner} until {expires_at}", path.display())]
+    Locked {
+        path: PathBuf,
+        owner: String,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    },
+
+    /// Storage quota would be exceeded
+    #[error("Quota exceeded: {resource} limit is {limit}, operation needs {requested}")]
+    QuotaExceeded {
+        resource: &'static str,
+        limit: u64,
+        requested: u64,
+    },
+
+    /// Namespace archive is malformed
+    #[error("Invalid archive: {0}")]
+    InvalidArchive(String),
+
+    /// Synced folder ID is malformed
+    #[error("Invalid folder ID: {0}")]
+    InvalidFolder(String),
+
+    /// Exchange with a peer failed
+    #[error("Peer error: {0}")]
+    Peer(#[from] P2PError),
 }
 
 /// Errors that can occur during reconnection
@@ -158,6 +269,10 @@ pub enum ReconnectionError {
     #[error("Reconnection cancelled by user")]
     Cancelled,
 
+    /// The reconnection policy vetoed a further attempt
+    #[error("Reconnection stopped by policy after {attempts} attempts. Last error: {last_error}")]
+    Vetoed { attempts: u32, last_error: String },
+
     /// Connection error during reconnection
     #[error("Connection error during reconnection: {0}")]
     Connection(#[from] ConnectionError),
@@ -210,6 +325,10 @@ pub enum ForwardError {
     #[error("Forward not found: {0}")]
     NotFound(String),
 
+    /// Kind of forward this client cannot set up
+    #[error("Forward not supported: {0}")]
+    Unsupported(String),
+
     /// SSH error
     #[error("SSH error: {0}")]
     Ssh(#[from] SshError),
@@ -241,6 +360,34 @@ pub enum P2PError {
     /// NAT traversal failed
     #[error("NAT traversal failed: {0}")]
     NatTraversalFailed(String),
+
+    /// Secure channel error on a P2P stream
+    #[error("Secure channel error: {0}")]
+    Encryption(#[from] EncryptionError),
+
+    /// Peer is not authorized for the requested operation
+    #[error("Peer not authorized: {0}")]
+    Unauthorized(String),
+
+    /// Peer's NodeId is on the revocation list
+    #[error("Peer {peer_id} has been revoked: {reason}")]
+    PeerRevoked { peer_id: String, reason: String },
+
+    /// Failed to load or save persisted P2P state
+    #[error("P2P storage error: {0}")]
+    Storage(String),
+
+    /// Connection ticket could not be parsed
+    #[error("Invalid ticket: {0}")]
+    InvalidTicket(String),
+
+    /// File transfer failed or was rejected
+    #[error("File transfer failed: {0}")]
+    Transfer(String),
+
+    /// Peer name is malformed or already taken
+    #[error("Invalid peer name: {0}")]
+    InvalidAlias(String),
 }
 
 /// Errors that can occur during streaming operations
@@ -265,6 +412,22 @@ pub enum StreamError {
     /// Connection error
     #[error("Connection error: {0}")]
     Connection(#[from] ConnectionError),
+
+    /// P2P transport error while syncing a room
+    #[error("P2P error: {0}")]
+    P2P(#[from] P2PError),
+
+    /// Room refused to admit the peer
+    #[error("Access denied: {0}")]
+    AccessDenied(String),
+
+    /// HLS or DASH manifest that cannot be played
+    #[error("Invalid manifest: {0}")]
+    Manifest(String),
+
+    /// Writing a recording to VDFS failed
+    #[error("Storage error: {0}")]
+    Storage(#[from] VdfsError),
 }
 
 impl ConnectionError {
@@ -299,6 +462,7 @@ mod tests {
             ConnectionError::TlsHandshake("Certificate expired".to_string()),
             ConnectionError::ConnectionClosed("Remote closed".to_string()),
             ConnectionError::InvalidConfig("Missing host".to_string()),
+            ConnectionError::Proxy("407 Proxy Authentication Required".to_string()),
         ];
 
         for error in errors {
@@ -0,0 +1,298 @@
+//! Unified Event Bus
+//!
+//! A crate-wide typed broadcast channel that surfaces connection, auth,
+//! port forward, transfer and P2P events. Components publish to a shared
+//! `EventBus`; frontends subscribe once instead of wiring up one channel
+//! per subsystem.
+
+use crate::connection::{ConnectionState, StateManager};
+use crate::ssh::PortForward;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Default channel capacity for the event bus
+pub const EVENT_BUS_CAPACITY: usize = 256;
+
+/// Event categories, used to filter subscriptions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EventKind {
+    /// Connection state changes
+    Connection,
+    /// Authentication results
+    Auth,
+    /// Port forward lifecycle
+    Forward,
+    /// File transfer progress
+    Transfer,
+    /// P2P peer lifecycle
+    P2P,
+}
+
+/// An event published on the bus
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RusshEvent {
+    /// Connection state changed
+    Connection {
+        /// Host the connection belongs to
+        host: String,
+        /// Previous state
+        old_state: ConnectionState,
+        /// New state
+        new_state: ConnectionState,
+    },
+    /// Authentication attempt finished
+    Auth {
+        /// Remote host
+        host: String,
+        /// Username
+        user: String,
+        /// Whether authentication succeeded
+        success: bool,
+        /// Failure reason, if any
+        reason: Option<String>,
+    },
+    /// Port forward started
+    ForwardStarted {
+        /// Forward identifier
+        id: Uuid,
+        /// Forward configuration
+        forward: PortForward,
+    },
+    /// Port forward stopped
+    ForwardStopped {
+        /// Forward identifier
+        id: Uuid,
+    },
+    /// Port forward failed to start
+    ForwardFailed {
+        /// Forward configuration
+        forward: PortForward,
+        /// Failure reason
+        reason: String,
+    },
+    /// File transfer progress
+    Transfer {
+        /// Transfer identifier
+        transfer_id: Uuid,
+        /// Remote path
+        path: String,
+        /// Bytes transferred so far
+        bytes: u64,
+        /// Total bytes, if known
+        total: Option<u64>,
+        /// Whether the transfer finished
+        done: bool,
+    },
+    /// P2P peer connected
+    PeerConnected {
+        /// Peer node ID
+        peer_id: String,
+    },
+    /// P2P peer disconnected
+    PeerDisconnected {
+        /// Peer node ID
+        peer_id: String,
+    },
+    /// P2P peer found on the local network
+    PeerDiscovered {
+        /// Peer node ID
+        peer_id: String,
+        /// Name the peer advertises
+        name: String,
+        /// Direct addresses of the peer
+        addrs: Vec<String>,
+    },
+    /// Local network peer went away
+    PeerLost {
+        /// Peer node ID
+        peer_id: String,
+    },
+}
+
+impl RusshEvent {
+    /// Get the category of this event
+    pub fn kind(&self) -> EventKind {
+        match self {
+            RusshEvent::Connection { .. } => EventKind::Connection,
+            RusshEvent::Auth { .. } => EventKind::Auth,
+            RusshEvent::ForwardStarted { .. }
+            | RusshEvent::ForwardStopped { .. }
+            | RusshEvent::ForwardFailed { .. } => EventKind::Forward,
+            RusshEvent::Transfer { .. } => EventKind::Transfer,
+            RusshEvent::PeerConnected { .. }
+            | RusshEvent::PeerDisconnected { .. }
+            | RusshEvent::PeerDiscovered { .. }
+            | RusshEvent::PeerLost { .. } => EventKind::P2P,
+        }
+    }
+}
+
+/// Crate-wide event bus
+///
+/// Cheap to clone; all clones publish to the same subscribers.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<RusshEvent>,
+}
+
+impl EventBus {
+    /// Create an event bus with the default capacity
+    pub fn new() -> Self {
+        Self::with_capacity(EVENT_BUS_CAPACITY)
+    }
+
+    /// Create an event bus with a specific capacity
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Publish an event, returning the number of subscribers that received it
+    pub fn publish(&self, event: RusshEvent) -> usize {
+        self.sender.send(event).unwrap_or(0)
+    }
+
+    /// Subscribe to all events
+    pub fn subscribe(&self) -> broadcast::Receiver<RusshEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Subscribe to events of the given kinds only
+    pub fn subscribe_to(&self, kinds: &[EventKind]) -> EventSubscription {
+        EventSubscription {
+            receiver: self.sender.subscribe(),
+            kinds: kinds.to_vec(),
+        }
+    }
+
+    /// Get the number of active subscribers
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Forward state changes from a state manager onto the bus
+    ///
+    /// The task ends when the state manager is dropped.
+    pub fn bridge_state_changes(
+        &self,
+        host: impl Into<String>,
+        state_manager: &StateManager,
+    ) -> tokio::task::JoinHandle<()> {
+        let host = host.into();
+        let bus = self.clone();
+        let mut receiver = state_manager.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(change) => {
+                        bus.publish(RusshEvent::Connection {
+                            host: host.clone(),
+                            old_state: change.old_state,
+                            new_state: change.new_state,
+                        });
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "Event bus bridge lagged");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A filtered subscription to the event bus
+pub struct EventSubscription {
+    receiver: broadcast::Receiver<RusshEvent>,
+    kinds: Vec<EventKind>,
+}
+
+impl EventSubscription {
+    /// Receive the next matching event
+    ///
+    /// Returns `None` once the bus is closed. Lagged events are skipped.
+    pub async fn recv(&mut self) -> Option<RusshEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.kinds.contains(&event.kind()) => return Some(event),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "Event subscription lagged");
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn publish_reaches_all_subscribers() {
+        let bus = EventBus::new();
+        let mut a = bus.subscribe();
+        let mut b = bus.clone().subscribe();
+
+        let event = RusshEvent::PeerConnected {
+            peer_id: "peer".to_string(),
+        };
+        assert_eq!(bus.publish(event.clone()), 2);
+        assert_eq!(a.recv().await.unwrap(), event);
+        assert_eq!(b.recv().await.unwrap(), event);
+    }
+
+    #[tokio::test]
+    async fn filtered_subscription_skips_other_kinds() {
+        let bus = EventBus::new();
+        let mut forwards = bus.subscribe_to(&[EventKind::Forward]);
+
+        bus.publish(RusshEvent::PeerConnected {
+            peer_id: "peer".to_string(),
+        });
+        let id = Uuid::new_v4();
+        bus.publish(RusshEvent::ForwardStopped { id });
+
+        assert_eq!(
+            forwards.recv().await,
+            Some(RusshEvent::ForwardStopped { id })
+        );
+    }
+
+    #[tokio::test]
+    async fn state_changes_are_bridged() {
+        let bus = EventBus::new();
+        let mut events = bus.subscribe();
+        let state_manager = StateManager::new();
+        let _bridge = bus.bridge_state_changes("host.com", &state_manager);
+
+        state_manager.set_state(ConnectionState::Connecting);
+
+        assert_eq!(
+            events.recv().await.unwrap(),
+            RusshEvent::Connection {
+                host: "host.com".to_string(),
+                old_state: ConnectionState::Disconnected,
+                new_state: ConnectionState::Connecting,
+            }
+        );
+    }
+
+    #[test]
+    fn publish_without_subscribers_is_noop() {
+        let bus = EventBus::new();
+        assert_eq!(
+            bus.publish(RusshEvent::ForwardStopped { id: Uuid::new_v4() }),
+            0
+        );
+    }
+}
@@ -6,12 +6,16 @@
 //! - End-to-end encryption
 //! - Virtual distributed filesystem
 //! - Media streaming capabilities
+//! - Unified event bus for lifecycle events
+//! - Shared bandwidth scheduling across transfers
 
 pub mod config;
 pub mod connection;
 pub mod encryption;
 pub mod error;
+pub mod events;
 pub mod p2p;
+pub mod scheduler;
 pub mod session;
 pub mod streaming;
 pub mod vdfs;
@@ -7,7 +7,23 @@
 //! - QUIC transport for reliable, multiplexed connections
 //! - NAT traversal via hole-punching
 //! - Relay server fallback when direct connection fails
+//! - Self-hosted relays with health checks and failover ordering
 //! - Bidirectional stream support
+//! - Topic-based publish/subscribe between groups of peers
+//! - Encrypted group channels with per-member sender keys
+//! - Per-peer RTT and bandwidth statistics
+//! - Peer presence tracking with heartbeats and change events
+//! - Encrypted offline queue flushed when peers come online
+//! - End-to-end encrypted streams over secure channels
+//! - Peer allowlist with per-peer capabilities
+//! - Persistent peer registry with names, tags and trust state
+//! - Local network peer discovery over mDNS
+//! - NAT traversal diagnostics
+//! - Shareable connection tickets with optional DHT lookup
+//! - QR pairing with one-time secrets and mutual proof
+//! - TCP tunneling to services on a peer (SSH over P2P)
+//! - Local and remote port forwarding through a peer
+//! - Resumable, BLAKE3-verified file transfer between peers
 //!
 //! # Requirements Coverage
 //! - Requirement 3.1: Iroh QUIC implementation for transport
@@ -16,10 +32,42 @@
 //! - Requirement 3.4: Multiplexed bidirectional streams
 //! - Requirement 3.5: Connection metadata (latency, type)
 
+pub mod acl;
 pub mod connection;
+pub mod diagnostics;
+pub mod discovery;
 pub mod endpoint;
+pub mod forward;
+pub mod gossip;
+pub mod group;
+pub mod outbox;
+pub mod pairing;
+pub mod presence;
+pub mod registry;
+pub mod relay;
+pub mod secure;
+pub mod stats;
 pub mod stream;
+pub mod ticket;
+pub mod transfer;
+pub mod tunnel;
 
+pub use acl::*;
 pub use connection::*;
+pub use diagnostics::*;
+pub use discovery::*;
 pub use endpoint::*;
+pub use forward::*;
+pub use gossip::*;
+pub use group::*;
+pub use outbox::*;
+pub use pairing::*;
+pub use presence::*;
+pub use registry::*;
+pub use relay::*;
+pub use secure::*;
+pub use stats::*;
 pub use stream::*;
+pub use ticket::*;
+pub use transfer::*;
+pub use tunnel::*;
@@ -0,0 +1,425 @@
+//! Peer allowlist and per-peer capabilities
+//!
+//! The access control list decides which NodeIds may connect and which
+//! protocols each of them may use once connected. Explicitly denied peers
+//! are refused in both directions; in default-deny mode unknown peers are
+//! refused when they dial in and get no capabilities on connections we
+//! open ourselves.
+//!
+//! # Requirements Coverage
+//! - Requirement 4.2: Mutual authentication between peers
+
+use crate::error::P2PError;
+use chrono::{DateTime, Utc};
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// File name of the access control list inside the configuration directory
+pub const PEER_ACL_FILE_NAME: &str = "peer_acl.json";
+
+/// Protocol a peer may use over its connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PeerCapability {
+    /// Tunnel SSH sessions over the P2P connection
+    SshTunnel,
+    /// Synchronize files through the VDFS
+    FileSync,
+    /// Stream media
+    Streaming,
+    /// Exchange pub/sub messages on gossip topics
+    Messaging,
+    /// Forward TCP ports through the peer
+    PortForward,
+    /// Send files straight to this device
+    FileDrop,
+}
+
+impl PeerCapability {
+    /// Every capability
+    pub const ALL: [PeerCapability; 6] = [
+        Self::SshTunnel,
+        Self::FileSync,
+        Self::Streaming,
+        Self::Messaging,
+        Self::PortForward,
+        Self::FileDrop,
+    ];
+}
+
+impl fmt::Display for PeerCapability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SshTunnel => write!(f, "ssh-tunnel"),
+            Self::FileSync => write!(f, "file-sync"),
+            Self::Streaming => write!(f, "streaming"),
+            Self::Messaging => write!(f, "messaging"),
+            Self::PortForward => write!(f, "port-forward"),
+            Self::FileDrop => write!(f, "file-drop"),
+        }
+    }
+}
+
+/// Treatment of peers that have no entry in the list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AclDefault {
+    /// Unknown peers may connect and use every capability
+    #[default]
+    Allow,
+    /// Unknown peers may not connect in and get no capabilities
+    Deny,
+}
+
+/// Access rule for a single peer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AclEntry {
+    /// Whether the peer may connect at all
+    pub allowed: bool,
+    /// Protocols the peer may use
+    pub capabilities: BTreeSet<PeerCapability>,
+    /// Free-form note (e.g. device name)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// When the rule was last changed
+    pub updated_at: DateTime<Utc>,
+}
+
+/// On-disk layout of the list
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AclFile {
+    #[serde(default)]
+    default: AclDefault,
+    #[serde(default)]
+    peers: HashMap<String, AclEntry>,
+}
+
+/// Persisted allow/deny rules and capabilities keyed by NodeId
+#[derive(Debug, Default)]
+pub struct PeerAcl {
+    /// File the list is saved to, if any
+    path: Option<PathBuf>,
+    /// Default treatment and per-peer rules
+    state: RwLock<AclFile>,
+}
+
+impl PeerAcl {
+    /// Create an empty in-memory list that allows unknown peers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty in-memory list that refuses unknown peers
+    pub fn default_deny() -> Self {
+        Self {
+            path: None,
+            state: RwLock::new(AclFile {
+                default: AclDefault::Deny,
+                peers: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Default location of the access control list file
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("russh").join(PEER_ACL_FILE_NAME))
+    }
+
+    /// Open the list at `path`, starting empty if the file does not exist
+    pub fn open(path: &Path) -> Result<Self, P2PError> {
+        let state = if path.exists() {
+            let json = std::fs::read_to_string(path).map_err(|e| {
+                P2PError::Storage(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+            serde_json::from_str(&json).map_err(|e| {
+                P2PError::Storage(format!("Invalid peer ACL {}: {}", path.display(), e))
+            })?
+        } else {
+            AclFile::default()
+        };
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            state: RwLock::new(state),
+        })
+    }
+
+    /// Get the treatment of unknown peers
+    pub fn default_rule(&self) -> AclDefault {
+        self.read_state().default
+    }
+
+    /// Set the treatment of unknown peers
+    pub fn set_default_rule(&self, default: AclDefault) -> Result<(), P2PError> {
+        self.write_state().default = default;
+        self.save()
+    }
+
+    /// Allow a peer to connect with the given capabilities
+    pub fn allow(
+        &self,
+        peer: &NodeId,
+        capabilities: impl IntoIterator<Item = PeerCapability>,
+    ) -> Result<(), P2PError> {
+        self.set_entry(peer, true, capabilities.into_iter().collect())
+    }
+
+    /// Allow a peer to connect, adding capabilities to those it already has
+    pub fn grant(
+        &self,
+        peer: &NodeId,
+        capabilities: impl IntoIterator<Item = PeerCapability>,
+    ) -> Result<(), P2PError> {
+        let mut granted = match self.get(peer) {
+            Some(entry) if entry.allowed => entry.capabilities,
+            _ => BTreeSet::new(),
+        };
+        granted.extend(capabilities);
+        self.set_entry(peer, true, granted)
+    }
+
+    /// Refuse all connections to and from a peer
+    pub fn deny(&self, peer: &NodeId) -> Result<(), P2PError> {
+        self.set_entry(peer, false, BTreeSet::new())
+    }
+
+    /// Attach a note to an existing rule
+    pub fn set_note(&self, peer: &NodeId, note: impl Into<String>) -> Result<(), P2PError> {
+        match self.write_state().peers.get_mut(&peer.to_string()) {
+            Some(entry) => entry.note = Some(note.into()),
+            None => return Err(P2PError::PeerNotFound(peer.to_string())),
+        }
+        self.save()
+    }
+
+    /// Remove a peer's rule so the default applies again
+    pub fn remove(&self, peer: &NodeId) -> Result<Option<AclEntry>, P2PError> {
+        let removed = self.write_state().peers.remove(&peer.to_string());
+        self.save()?;
+        Ok(removed)
+    }
+
+    /// Get the rule for a peer
+    pub fn get(&self, peer: &NodeId) -> Option<AclEntry> {
+        self.read_state().peers.get(&peer.to_string()).cloned()
+    }
+
+    /// Capabilities a peer currently has
+    pub fn capabilities(&self, peer: &NodeId) -> BTreeSet<PeerCapability> {
+        let state = self.read_state();
+        match state.peers.get(&peer.to_string()) {
+            Some(entry) if entry.allowed => entry.capabilities.clone(),
+            Some(_) => BTreeSet::new(),
+            None if state.default == AclDefault::Allow => PeerCapability::ALL.into(),
+            None => BTreeSet::new(),
+        }
+    }
+
+    /// Fail unless the peer may dial in
+    pub fn check_incoming(&self, peer: &NodeId) -> Result<(), P2PError> {
+        let state = self.read_state();
+        match state.peers.get(&peer.to_string()) {
+            Some(entry) if entry.allowed => Ok(()),
+            Some(_) => Err(unauthorized(peer, "peer is denied")),
+            None if state.default == AclDefault::Allow => Ok(()),
+            None => Err(unauthorized(peer, "peer is not on the allowlist")),
+        }
+    }
+
+    /// Fail if the peer is explicitly denied
+    pub fn check_outgoing(&self, peer: &NodeId) -> Result<(), P2PError> {
+        match self.read_state().peers.get(&peer.to_string()) {
+            Some(entry) if !entry.allowed => Err(unauthorized(peer, "peer is denied")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fail unless the peer may use the given capability
+    pub fn check_capability(
+        &self,
+        peer: &NodeId,
+        capability: PeerCapability,
+    ) -> Result<(), P2PError> {
+        if self.capabilities(peer).contains(&capability) {
+            Ok(())
+        } else {
+            Err(unauthorized(
+                peer,
+                &format!("{} is not permitted", capability),
+            ))
+        }
+    }
+
+    /// List all rules
+    pub fn entries(&self) -> Vec<(String, AclEntry)> {
+        let mut entries: Vec<_> = self
+            .read_state()
+            .peers
+            .iter()
+            .map(|(peer, entry)| (peer.clone(), entry.clone()))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    /// Write the list to its file, if it has one
+    pub fn save(&self) -> Result<(), P2PError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let storage_error =
+            |e: std::io::Error| P2PError::Storage(format!("{}: {}", path.display(), e));
+
+        let json = serde_json::to_string_pretty(&*self.read_state())
+            .map_err(|e| P2PError::Storage(format!("Failed to encode peer ACL: {}", e)))?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(storage_error)?;
+        }
+
+        let tmp_path = path.with_extension("json.tmp");
+        let mut file = std::fs::File::create(&tmp_path).map_err(storage_error)?;
+        file.write_all(json.as_bytes()).map_err(storage_error)?;
+        file.sync_all().map_err(storage_error)?;
+        std::fs::rename(&tmp_path, path).map_err(storage_error)
+    }
+
+    fn set_entry(
+        &self,
+        peer: &NodeId,
+        allowed: bool,
+        capabilities: BTreeSet<PeerCapability>,
+    ) -> Result<(), P2PError> {
+        {
+            let mut state = self.write_state();
+            let note = state
+                .peers
+                .get(&peer.to_string())
+                .and_then(|entry| entry.note.clone());
+            state.peers.insert(
+                peer.to_string(),
+                AclEntry {
+                    allowed,
+                    capabilities,
+                    note,
+                    updated_at: Utc::now(),
+                },
+            );
+        }
+        self.save()
+    }
+
+    fn read_state(&self) -> std::sync::RwLockReadGuard<'_, AclFile> {
+        self.state.read().unwrap_or_else(|poisoned| {
+            tracing::warn!("Peer ACL lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+
+    fn write_state(&self) -> std::sync::RwLockWriteGuard<'_, AclFile> {
+        self.state.write().unwrap_or_else(|poisoned| {
+            tracing::warn!("Peer ACL lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+}
+
+fn unauthorized(peer: &NodeId, reason: &str) -> P2PError {
+    tracing::warn!(peer_id = %peer, reason, "Refusing peer");
+    P2PError::Unauthorized(format!("{}: {}", peer.fmt_short(), reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::SecretKey;
+
+    fn node_id() -> NodeId {
+        SecretKey::generate(rand::rngs::OsRng).public()
+    }
+
+    #[test]
+    fn default_allow_grants_everything_until_denied() {
+        let acl = PeerAcl::new();
+        let peer = node_id();
+
+        assert!(acl.check_incoming(&peer).is_ok());
+        assert!(acl
+            .check_capability(&peer, PeerCapability::Streaming)
+            .is_ok());
+
+        acl.deny(&peer).unwrap();
+        assert!(matches!(
+            acl.check_incoming(&peer),
+            Err(P2PError::Unauthorized(_))
+        ));
+        assert!(acl.check_outgoing(&peer).is_err());
+        assert!(acl.capabilities(&peer).is_empty());
+    }
+
+    #[test]
+    fn default_deny_requires_allowlisting() {
+        let acl = PeerAcl::default_deny();
+        let friend = node_id();
+        let stranger = node_id();
+
+        acl.allow(&friend, [PeerCapability::SshTunnel]).unwrap();
+
+        assert!(acl.check_incoming(&friend).is_ok());
+        assert!(acl
+            .check_capability(&friend, PeerCapability::SshTunnel)
+            .is_ok());
+        assert!(acl
+            .check_capability(&friend, PeerCapability::FileSync)
+            .is_err());
+
+        assert!(acl.check_incoming(&stranger).is_err());
+        assert!(acl.check_outgoing(&stranger).is_ok());
+        assert!(acl.capabilities(&stranger).is_empty());
+    }
+
+    #[test]
+    fn grant_adds_to_existing_capabilities() {
+        let acl = PeerAcl::default_deny();
+        let peer = node_id();
+
+        acl.grant(&peer, [PeerCapability::FileSync]).unwrap();
+        acl.grant(&peer, [PeerCapability::FileDrop]).unwrap();
+        assert_eq!(
+            acl.capabilities(&peer),
+            [PeerCapability::FileSync, PeerCapability::FileDrop].into()
+        );
+
+        // A denied peer starts over rather than getting its old grants back
+        acl.deny(&peer).unwrap();
+        acl.grant(&peer, [PeerCapability::FileDrop]).unwrap();
+        assert_eq!(acl.capabilities(&peer), [PeerCapability::FileDrop].into());
+    }
+
+    #[test]
+    fn list_persists_across_opens() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PEER_ACL_FILE_NAME);
+        let peer = node_id();
+
+        let acl = PeerAcl::open(&path).unwrap();
+        acl.set_default_rule(AclDefault::Deny).unwrap();
+        acl.allow(&peer, [PeerCapability::FileSync, PeerCapability::Streaming])
+            .unwrap();
+        acl.set_note(&peer, "laptop").unwrap();
+
+        let reopened = PeerAcl::open(&path).unwrap();
+        assert_eq!(reopened.default_rule(), AclDefault::Deny);
+        let entry = reopened.get(&peer).unwrap();
+        assert_eq!(entry.note.as_deref(), Some("laptop"));
+        assert_eq!(
+            entry.capabilities,
+            [PeerCapability::FileSync, PeerCapability::Streaming].into()
+        );
+    }
+}
@@ -1,22 +1,40 @@
 //! P2P Connection management
 //!
 //! This module handles peer connections with NAT traversal and relay fallback.
+//! The manager keeps one connection per peer and hands it to every
+//! subsystem (sync, streaming, presence), dialing each peer at most once at
+//! a time and closing connections whose streams have been idle too long.
 //!
 //! # Requirements Coverage
 //! - Requirement 3.2: NAT hole-punching for direct connections
 //! - Requirement 3.3: Relay server fallback
 //! - Requirement 3.5: Connection metadata (latency, type)
 
+use crate::encryption::RevocationList;
 use crate::error::P2PError;
+use crate::events::{EventBus, RusshEvent};
+use crate::p2p::acl::{PeerAcl, PeerCapability};
 use crate::p2p::endpoint::{P2PEndpoint, RUSSH_ALPN};
+use crate::p2p::stats::{PeerStats, PeerStatsTracker, StatsSample};
+use crate::p2p::stream::{BiStream, StreamManager};
+use crate::p2p::ticket::PeerTicket;
 use iroh::{
-    endpoint::{Connection, ConnectionType as IrohConnectionType},
+    endpoint::{Connection, ConnectionType as IrohConnectionType, SendStream},
     NodeAddr, NodeId,
 };
+use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
+use tokio::task::JoinHandle;
+
+/// Capacity of the peer statistics broadcast channel
+const STATS_CAPACITY: usize = 64;
+
+/// Default time a connection may carry no stream data before it is closed
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
 
 /// Type of P2P connection
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -85,6 +103,8 @@ pub struct P2PConnection {
     info: Arc<RwLock<P2PConnectionInfo>>,
     /// Reference to the endpoint for connection type queries
     endpoint: Arc<P2PEndpoint>,
+    /// Stream activity, for idle detection
+    activity: std::sync::Mutex<StreamActivity>,
 }
 
 impl P2PConnection {
@@ -95,6 +115,7 @@ impl P2PConnection {
             peer_id,
             info: Arc::new(RwLock::new(P2PConnectionInfo::new(peer_id))),
             endpoint,
+            activity: std::sync::Mutex::new(StreamActivity::new(Instant::now())),
         }
     }
 
@@ -151,6 +172,18 @@ impl P2PConnection {
         Some(rtt)
     }
 
+    /// Read the connection's current QUIC counters
+    pub fn stats_sample(&self) -> StatsSample {
+        let stats = self.connection.stats();
+        StatsSample {
+            rtt: stats.path.rtt,
+            bytes_sent: stats.udp_tx.bytes,
+            bytes_received: stats.udp_rx.bytes,
+            lost_packets: stats.path.lost_packets,
+            at: Instant::now(),
+        }
+    }
+
     /// Close the connection gracefully
     pub fn close(&self, code: u32, reason: &[u8]) {
         self.connection.close(code.into(), reason);
@@ -160,6 +193,95 @@ impl P2PConnection {
     pub fn is_alive(&self) -> bool {
         self.connection.close_reason().is_none()
     }
+
+    /// Time since any stream last carried data on this connection
+    ///
+    /// Keep-alive pings do not count as activity.
+    pub fn idle_time(&self) -> Duration {
+        let stats = self.connection.stats();
+        let frames = stats.frame_tx.stream + stats.frame_rx.stream;
+        self.activity().observe(frames, Instant::now())
+    }
+
+    /// Mark the connection as in use, resetting its idle time
+    pub fn touch(&self) {
+        self.activity().changed_at = Instant::now();
+    }
+
+    fn activity(&self) -> std::sync::MutexGuard<'_, StreamActivity> {
+        self.activity
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Tracks when a connection's stream frame count last changed
+#[derive(Debug, Clone, Copy)]
+struct StreamActivity {
+    stream_frames: u64,
+    changed_at: Instant,
+}
+
+impl StreamActivity {
+    fn new(now: Instant) -> Self {
+        Self {
+            stream_frames: 0,
+            changed_at: now,
+        }
+    }
+
+    /// Record the current frame count and return how long it has been unchanged
+    fn observe(&mut self, stream_frames: u64, now: Instant) -> Duration {
+        if stream_frames != self.stream_frames {
+            self.stream_frames = stream_frames;
+            self.changed_at = now;
+        }
+        now.saturating_duration_since(self.changed_at)
+    }
+}
+
+/// Connection reuse counters of a [`P2PConnectionManager`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct PoolMetrics {
+    /// Live connections
+    pub active: usize,
+    /// Connections dialed
+    pub dials: u64,
+    /// Connect calls answered with an existing connection
+    pub reused: u64,
+    /// Connections accepted from peers
+    pub accepted: u64,
+    /// Connections closed for being idle
+    pub idle_closed: u64,
+    /// Streams opened through the manager
+    pub streams_opened: u64,
+}
+
+/// Shared counters behind [`PoolMetrics`]
+#[derive(Debug, Default)]
+struct PoolCounters {
+    dials: AtomicU64,
+    reused: AtomicU64,
+    accepted: AtomicU64,
+    idle_closed: AtomicU64,
+    streams_opened: AtomicU64,
+}
+
+impl PoolCounters {
+    fn bump(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self, active: usize) -> PoolMetrics {
+        PoolMetrics {
+            active,
+            dials: self.dials.load(Ordering::Relaxed),
+            reused: self.reused.load(Ordering::Relaxed),
+            accepted: self.accepted.load(Ordering::Relaxed),
+            idle_closed: self.idle_closed.load(Ordering::Relaxed),
+            streams_opened: self.streams_opened.load(Ordering::Relaxed),
+        }
+    }
 }
 
 /// P2P Connection Manager
@@ -170,7 +292,25 @@ pub struct P2PConnectionManager {
     /// The P2P endpoint
     endpoint: Arc<P2PEndpoint>,
     /// Active connections
-    connections: Arc<RwLock<std::collections::HashMap<NodeId, Arc<P2PConnection>>>>,
+    connections: Arc<RwLock<HashMap<NodeId, Arc<P2PConnection>>>>,
+    /// Link statistics per connected peer
+    stats: Arc<RwLock<HashMap<NodeId, PeerStatsTracker>>>,
+    /// Broadcasts every new statistics sample
+    stats_events: broadcast::Sender<PeerStats>,
+    /// Event bus for peer lifecycle events
+    event_bus: Option<EventBus>,
+    /// NodeIds that must not be connected to or accepted
+    revocations: Option<Arc<RevocationList>>,
+    /// Allow/deny rules and per-peer capabilities
+    acl: Option<Arc<PeerAcl>>,
+    /// Let peers without an ACL entry dial in so they can pair
+    pairing_mode: AtomicBool,
+    /// Close connections whose streams were idle this long
+    idle_timeout: Duration,
+    /// Connection reuse counters
+    pool: Arc<PoolCounters>,
+    /// Per-peer locks so concurrent connects share one dial
+    dialing: tokio::sync::Mutex<HashMap<NodeId, Arc<tokio::sync::Mutex<()>>>>,
 }
 
 impl Drop for P2PConnectionManager {
@@ -191,7 +331,125 @@ impl P2PConnectionManager {
     pub fn new(endpoint: Arc<P2PEndpoint>) -> Self {
         Self {
             endpoint,
-            connections: Arc::new(RwLock::new(std::collections::HashMap::new())),
+            connections: Arc::new(RwLock::new(HashMap::new())),
+            stats: Arc::new(RwLock::new(HashMap::new())),
+            stats_events: broadcast::channel(STATS_CAPACITY).0,
+            event_bus: None,
+            revocations: None,
+            acl: None,
+            pairing_mode: AtomicBool::new(false),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            pool: Arc::new(PoolCounters::default()),
+            dialing: tokio::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Close connections once their streams have been idle this long
+    ///
+    /// Only enforced while [`start_idle_reaper`](Self::start_idle_reaper)
+    /// runs or [`close_idle`](Self::close_idle) is called.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Get the endpoint connections are made through
+    pub fn endpoint(&self) -> &Arc<P2PEndpoint> {
+        &self.endpoint
+    }
+
+    /// Refuse connections to and from revoked NodeIds
+    pub fn with_revocation_list(mut self, revocations: Arc<RevocationList>) -> Self {
+        self.revocations = Some(revocations);
+        self
+    }
+
+    /// Fail with [`P2PError::PeerRevoked`] if the peer is revoked
+    fn check_revoked(&self, peer_id: &NodeId) -> Result<(), P2PError> {
+        let revocation = self
+            .revocations
+            .as_ref()
+            .and_then(|revocations| revocations.get(&peer_id.to_string()));
+        match revocation {
+            Some(revocation) => {
+                tracing::warn!(
+                    peer_id = %peer_id,
+                    reason = %revocation.reason,
+                    "Refusing revoked peer"
+                );
+                Err(P2PError::PeerRevoked {
+                    peer_id: peer_id.to_string(),
+                    reason: revocation.reason,
+                })
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Enforce an access control list on incoming and outgoing connections
+    pub fn with_acl(mut self, acl: Arc<PeerAcl>) -> Self {
+        self.acl = Some(acl);
+        self
+    }
+
+    /// Get the access control list, if any
+    pub fn acl(&self) -> Option<&Arc<PeerAcl>> {
+        self.acl.as_ref()
+    }
+
+    /// Fail unless the peer may use a capability
+    ///
+    /// Protocol handlers call this before serving a peer's request. Without
+    /// an access control list every connected peer is authorized.
+    pub fn authorize(&self, peer_id: &NodeId, capability: PeerCapability) -> Result<(), P2PError> {
+        match &self.acl {
+            Some(acl) => acl.check_capability(peer_id, capability),
+            None => Ok(()),
+        }
+    }
+
+    /// Fail if the peer may not be dialed
+    fn check_outgoing(&self, peer_id: &NodeId) -> Result<(), P2PError> {
+        self.check_revoked(peer_id)?;
+        match &self.acl {
+            Some(acl) => acl.check_outgoing(peer_id),
+            None => Ok(()),
+        }
+    }
+
+    /// Fail if the peer may not dial in
+    ///
+    /// In pairing mode unknown peers are let in; they still get no
+    /// capabilities under a default-deny list until pairing adds them.
+    fn check_incoming(&self, peer_id: &NodeId) -> Result<(), P2PError> {
+        self.check_revoked(peer_id)?;
+        match &self.acl {
+            Some(acl) if self.pairing_mode() && acl.get(peer_id).is_none() => Ok(()),
+            Some(acl) => acl.check_incoming(peer_id),
+            None => Ok(()),
+        }
+    }
+
+    /// Let peers without an ACL entry dial in while pairing is in progress
+    pub fn set_pairing_mode(&self, enabled: bool) {
+        self.pairing_mode.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Whether unknown peers are currently let in for pairing
+    pub fn pairing_mode(&self) -> bool {
+        self.pairing_mode.load(Ordering::Relaxed)
+    }
+
+    /// Publish peer lifecycle events to the given event bus
+    pub fn with_event_bus(mut self, bus: EventBus) -> Self {
+        self.event_bus = Some(bus);
+        self
+    }
+
+    /// Publish an event if an event bus is attached
+    fn emit(&self, event: RusshEvent) {
+        if let Some(bus) = &self.event_bus {
+            bus.publish(event);
         }
     }
 
@@ -203,23 +461,73 @@ impl P2PConnectionManager {
     /// - Requirement 3.2: NAT hole-punching
     /// - Requirement 3.3: Relay fallback
     pub async fn connect(&self, peer_id: NodeId) -> Result<Arc<P2PConnection>, P2PError> {
-        // Check if already connected
-        {
-            let connections = self.connections.read().await;
-            if let Some(conn) = connections.get(&peer_id) {
-                if conn.is_alive() {
-                    return Ok(conn.clone());
+        self.connect_to(NodeAddr::new(peer_id)).await
+    }
+
+    /// Connect to a peer with explicit address information
+    pub async fn connect_with_addr(&self, addr: NodeAddr) -> Result<Arc<P2PConnection>, P2PError> {
+        self.connect_to(addr).await
+    }
+
+    /// Reuse a live connection to the peer, or dial it once
+    ///
+    /// Concurrent calls for the same peer wait for a single dial instead of
+    /// racing to open several connections.
+    async fn connect_to(&self, addr: NodeAddr) -> Result<Arc<P2PConnection>, P2PError> {
+        let peer_id = addr.node_id;
+        self.check_outgoing(&peer_id)?;
+
+        if let Some(conn) = self.live_connection(&peer_id).await {
+            PoolCounters::bump(&self.pool.reused);
+            return Ok(conn);
+        }
+
+        let lock = self
+            .dialing
+            .lock()
+            .await
+            .entry(peer_id)
+            .or_default()
+            .clone();
+        let result = {
+            let _guard = lock.lock().await;
+            // Another task may have connected while we waited
+            match self.live_connection(&peer_id).await {
+                Some(conn) => {
+                    PoolCounters::bump(&self.pool.reused);
+                    Ok(conn)
                 }
+                None => self.dial(addr).await,
             }
+        };
+
+        // Keep the entry while other callers still wait on it, so that a
+        // caller arriving now queues behind them instead of dialing again
+        let mut dialing = self.dialing.lock().await;
+        let last_waiter = dialing
+            .get(&peer_id)
+            .is_some_and(|current| Arc::ptr_eq(current, &lock))
+            && Arc::strong_count(&lock) == 2;
+        if last_waiter {
+            dialing.remove(&peer_id);
         }
+        result
+    }
 
-        tracing::info!(peer_id = %peer_id, "Connecting to peer");
+    async fn dial(&self, addr: NodeAddr) -> Result<Arc<P2PConnection>, P2PError> {
+        let peer_id = addr.node_id;
+        tracing::info!(
+            peer_id = %peer_id,
+            relay = ?addr.relay_url,
+            direct_addrs = ?addr.direct_addresses,
+            "Connecting to peer"
+        );
 
-        // Connect using discovery
+        // Without addresses the endpoint resolves the peer through discovery
         let connection = self
             .endpoint
             .endpoint()
-            .connect(peer_id, RUSSH_ALPN)
+            .connect(addr, RUSSH_ALPN)
             .await
             .map_err(|e| P2PError::ConnectionFailed {
                 peer_id: peer_id.to_string(),
@@ -249,40 +557,69 @@ impl P2PConnectionManager {
             let mut connections = self.connections.write().await;
             connections.insert(peer_id, p2p_conn.clone());
         }
+        PoolCounters::bump(&self.pool.dials);
+        self.emit(RusshEvent::PeerConnected {
+            peer_id: peer_id.to_string(),
+        });
 
         Ok(p2p_conn)
     }
 
-    /// Connect to a peer with explicit address information
-    pub async fn connect_with_addr(&self, addr: NodeAddr) -> Result<Arc<P2PConnection>, P2PError> {
-        let peer_id = addr.node_id;
+    async fn live_connection(&self, peer_id: &NodeId) -> Option<Arc<P2PConnection>> {
+        self.connections
+            .read()
+            .await
+            .get(peer_id)
+            .filter(|conn| conn.is_alive())
+            .cloned()
+    }
 
-        // Check if already connected
-        {
-            let connections = self.connections.read().await;
-            if let Some(conn) = connections.get(&peer_id) {
-                if conn.is_alive() {
-                    return Ok(conn.clone());
-                }
-            }
+    /// Connect to the peer described by a ticket
+    ///
+    /// Tickets without addressing information fall back to discovery.
+    pub async fn connect_ticket(
+        &self,
+        ticket: &PeerTicket,
+    ) -> Result<Arc<P2PConnection>, P2PError> {
+        if ticket.needs_lookup() {
+            self.connect(ticket.node_id()).await
+        } else {
+            self.connect_with_addr(ticket.node_addr()).await
         }
+    }
 
-        tracing::info!(
-            peer_id = %peer_id,
-            relay = ?addr.relay_url,
-            direct_addrs = ?addr.direct_addresses,
-            "Connecting to peer with address"
-        );
-
-        let connection = self
+    /// Accept an incoming connection from a peer
+    ///
+    /// The connection is registered like an outgoing one, so it can be
+    /// looked up with `get_connection` afterwards.
+    pub async fn accept(&self) -> Result<Arc<P2PConnection>, P2PError> {
+        let incoming = self
             .endpoint
             .endpoint()
-            .connect(addr, RUSSH_ALPN)
+            .accept()
             .await
-            .map_err(|e| P2PError::ConnectionFailed {
-                peer_id: peer_id.to_string(),
-                reason: e.to_string(),
-            })?;
+            .ok_or_else(|| P2PError::Stream("Endpoint closed".to_string()))?;
+
+        let connection = incoming.await.map_err(|e| P2PError::ConnectionFailed {
+            peer_id: "unknown".to_string(),
+            reason: e.to_string(),
+        })?;
+
+        let peer_id = iroh::endpoint::get_remote_node_id(&connection).map_err(|e| {
+            P2PError::ConnectionFailed {
+                peer_id: "unknown".to_string(),
+                reason: format!("Failed to identify peer: {}", e),
+            }
+        })?;
+
+        if let Err(e) = self.check_incoming(&peer_id) {
+            let reason: &[u8] = match e {
+                P2PError::PeerRevoked { .. } => b"revoked",
+                _ => b"unauthorized",
+            };
+            connection.close(0u32.into(), reason);
+            return Err(e);
+        }
 
         let p2p_conn = Arc::new(P2PConnection::new(
             connection,
@@ -292,14 +629,83 @@ impl P2PConnectionManager {
         p2p_conn.update_connection_type().await;
         p2p_conn.measure_latency().await;
 
+        tracing::info!(peer_id = %peer_id, "Accepted connection from peer");
+
         {
             let mut connections = self.connections.write().await;
             connections.insert(peer_id, p2p_conn.clone());
         }
+        PoolCounters::bump(&self.pool.accepted);
+        self.emit(RusshEvent::PeerConnected {
+            peer_id: peer_id.to_string(),
+        });
 
         Ok(p2p_conn)
     }
 
+    /// Open a bidirectional stream to a peer on its shared connection
+    pub async fn open_bi(&self, peer_id: NodeId) -> Result<BiStream, P2PError> {
+        let connection = self.connect(peer_id).await?;
+        let stream = StreamManager::new(connection.clone()).open_bi().await?;
+        connection.touch();
+        PoolCounters::bump(&self.pool.streams_opened);
+        Ok(stream)
+    }
+
+    /// Open a unidirectional stream to a peer on its shared connection
+    pub async fn open_uni(&self, peer_id: NodeId) -> Result<SendStream, P2PError> {
+        let connection = self.connect(peer_id).await?;
+        let stream = StreamManager::new(connection.clone()).open_uni().await?;
+        connection.touch();
+        PoolCounters::bump(&self.pool.streams_opened);
+        Ok(stream)
+    }
+
+    /// Connection reuse counters
+    pub async fn pool_metrics(&self) -> PoolMetrics {
+        let active = self
+            .connections
+            .read()
+            .await
+            .values()
+            .filter(|conn| conn.is_alive())
+            .count();
+        self.pool.snapshot(active)
+    }
+
+    /// Drop closed connections and close those idle past the idle timeout
+    ///
+    /// Returns the peers that were disconnected.
+    pub async fn close_idle(&self) -> Vec<NodeId> {
+        close_idle_connections(
+            &self.connections,
+            self.idle_timeout,
+            &self.pool,
+            self.event_bus.as_ref(),
+        )
+        .await
+    }
+
+    /// Close idle connections periodically until the manager is dropped
+    pub fn start_idle_reaper(&self) -> JoinHandle<()> {
+        let connections = Arc::downgrade(&self.connections);
+        let idle_timeout = self.idle_timeout;
+        let pool = self.pool.clone();
+        let event_bus = self.event_bus.clone();
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval((idle_timeout / 4).max(Duration::from_secs(1)));
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                ticker.tick().await;
+                let Some(connections) = Weak::upgrade(&connections) else {
+                    break;
+                };
+                close_idle_connections(&connections, idle_timeout, &pool, event_bus.as_ref()).await;
+            }
+        })
+    }
+
     /// Get an existing connection to a peer
     pub async fn get_connection(&self, peer_id: &NodeId) -> Option<Arc<P2PConnection>> {
         let connections = self.connections.read().await;
@@ -328,6 +734,9 @@ impl P2PConnectionManager {
         if let Some(conn) = connections.remove(peer_id) {
             conn.close(0, b"disconnect");
             tracing::info!(peer_id = %peer_id, "Disconnected from peer");
+            self.emit(RusshEvent::PeerDisconnected {
+                peer_id: peer_id.to_string(),
+            });
         }
     }
 
@@ -337,6 +746,9 @@ impl P2PConnectionManager {
         for (peer_id, conn) in connections.drain() {
             conn.close(0, b"shutdown");
             tracing::info!(peer_id = %peer_id, "Disconnected from peer");
+            self.emit(RusshEvent::PeerDisconnected {
+                peer_id: peer_id.to_string(),
+            });
         }
     }
 
@@ -344,6 +756,124 @@ impl P2PConnectionManager {
     pub fn local_node_id(&self) -> NodeId {
         self.endpoint.node_id()
     }
+
+    /// Subscribe to link statistics as they are sampled
+    pub fn subscribe_stats(&self) -> broadcast::Receiver<PeerStats> {
+        self.stats_events.subscribe()
+    }
+
+    /// Latest link statistics for a peer
+    pub async fn peer_stats(&self, peer_id: &NodeId) -> Option<PeerStats> {
+        self.stats.read().await.get(peer_id)?.latest().cloned()
+    }
+
+    /// Latest link statistics for every connected peer
+    pub async fn all_peer_stats(&self) -> Vec<PeerStats> {
+        self.stats
+            .read()
+            .await
+            .values()
+            .filter_map(|tracker| tracker.latest().cloned())
+            .collect()
+    }
+
+    /// Sample every live connection once and broadcast the results
+    pub async fn sample_stats(&self) -> Vec<PeerStats> {
+        sample_peer_stats(&self.connections, &self.stats, &self.stats_events).await
+    }
+
+    /// Sample statistics every `interval` until the manager is dropped
+    pub fn start_stats_sampling(&self, interval: Duration) -> JoinHandle<()> {
+        let connections = Arc::downgrade(&self.connections);
+        let stats = Arc::downgrade(&self.stats);
+        let events = self.stats_events.clone();
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                ticker.tick().await;
+                let (Some(connections), Some(stats)) =
+                    (Weak::upgrade(&connections), Weak::upgrade(&stats))
+                else {
+                    break;
+                };
+                sample_peer_stats(&connections, &stats, &events).await;
+            }
+        })
+    }
+}
+
+/// Remove closed connections and close those idle for at least `idle_timeout`
+async fn close_idle_connections(
+    connections: &RwLock<HashMap<NodeId, Arc<P2PConnection>>>,
+    idle_timeout: Duration,
+    pool: &PoolCounters,
+    event_bus: Option<&EventBus>,
+) -> Vec<NodeId> {
+    let mut closed = Vec::new();
+    connections.write().await.retain(|peer_id, conn| {
+        if !conn.is_alive() {
+            closed.push(*peer_id);
+            return false;
+        }
+        if conn.idle_time() >= idle_timeout {
+            conn.close(0, b"idle");
+            tracing::info!(peer_id = %peer_id, "Closed idle connection");
+            PoolCounters::bump(&pool.idle_closed);
+            closed.push(*peer_id);
+            return false;
+        }
+        true
+    });
+
+    if let Some(bus) = event_bus {
+        for peer_id in &closed {
+            bus.publish(RusshEvent::PeerDisconnected {
+                peer_id: peer_id.to_string(),
+            });
+        }
+    }
+    closed
+}
+
+/// Record a statistics sample for each live connection
+async fn sample_peer_stats(
+    connections: &RwLock<HashMap<NodeId, Arc<P2PConnection>>>,
+    trackers: &RwLock<HashMap<NodeId, PeerStatsTracker>>,
+    events: &broadcast::Sender<PeerStats>,
+) -> Vec<PeerStats> {
+    let live: Vec<Arc<P2PConnection>> = connections
+        .read()
+        .await
+        .values()
+        .filter(|conn| conn.is_alive())
+        .cloned()
+        .collect();
+
+    let mut samples = Vec::with_capacity(live.len());
+    for conn in &live {
+        conn.update_connection_type().await;
+        conn.measure_latency().await;
+        let connection_type = conn.info().await.connection_type;
+        samples.push((conn.peer_id(), conn.stats_sample(), connection_type));
+    }
+
+    let mut trackers = trackers.write().await;
+    trackers.retain(|peer_id, _| samples.iter().any(|(id, _, _)| id == peer_id));
+
+    samples
+        .into_iter()
+        .map(|(peer_id, sample, connection_type)| {
+            let stats = trackers
+                .entry(peer_id)
+                .or_insert_with(|| PeerStatsTracker::new(peer_id))
+                .record(sample, connection_type);
+            // No subscribers is fine
+            let _ = events.send(stats.clone());
+            stats
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -369,6 +899,35 @@ mod tests {
         assert!(info.relay_url.is_none());
     }
 
+    #[test]
+    fn stream_activity_ignores_unchanged_frame_counts() {
+        let start = Instant::now();
+        let mut activity = StreamActivity::new(start);
+
+        let later = start + Duration::from_secs(30);
+        assert_eq!(activity.observe(0, later), Duration::from_secs(30));
+        // New stream frames reset the idle time
+        assert_eq!(activity.observe(12, later), Duration::ZERO);
+        let much_later = later + Duration::from_secs(90);
+        assert_eq!(activity.observe(12, much_later), Duration::from_secs(90));
+    }
+
+    #[test]
+    fn pool_counters_snapshot() {
+        let pool = PoolCounters::default();
+        PoolCounters::bump(&pool.dials);
+        PoolCounters::bump(&pool.reused);
+        PoolCounters::bump(&pool.reused);
+        PoolCounters::bump(&pool.streams_opened);
+
+        let metrics = pool.snapshot(1);
+        assert_eq!(metrics.active, 1);
+        assert_eq!(metrics.dials, 1);
+        assert_eq!(metrics.reused, 2);
+        assert_eq!(metrics.streams_opened, 1);
+        assert_eq!(metrics.idle_closed, 0);
+    }
+
     #[test]
     fn connection_info_uptime() {
         let node_id = iroh::SecretKey::generate(rand::rngs::OsRng).public();
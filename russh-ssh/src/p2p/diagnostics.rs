@@ -0,0 +1,547 @@
+//! NAT traversal diagnostics
+//!
+//! Collects what the endpoint knows about the local network (NAT type, UDP
+//! reachability, candidate addresses) and about the path to a given peer
+//! (direct or relayed, which candidates were probed and whether hole
+//! punching succeeded). Everything is serializable so the CLI and the
+//! desktop client can render the same report.
+//!
+//! # Requirements Coverage
+//! - Requirement 3.2: NAT hole-punching for direct connections
+//! - Requirement 3.3: Relay server fallback
+//! - Requirement 3.5: Connection metadata (latency, type)
+
+use crate::p2p::endpoint::P2PEndpoint;
+use iroh::endpoint::{ConnectionType as IrohConnectionType, ControlMsg, DirectAddrType};
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// How often the direct addresses are polled while waiting for them
+const ADDRESS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// NAT behaviour observed by the endpoint's STUN probes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NatType {
+    /// UDP is blocked; only relayed connections are possible
+    NoUdp,
+    /// Public address equals a local address (no NAT)
+    Open,
+    /// Same public mapping for every destination; hole punching usually works
+    EndpointIndependent,
+    /// Mapping changes per destination (symmetric NAT); hole punching rarely works
+    EndpointDependent,
+    /// Not enough probe results yet
+    Unknown,
+}
+
+impl NatType {
+    /// Whether direct connections are likely to be established
+    pub fn hole_punching_likely(&self) -> bool {
+        matches!(self, Self::Open | Self::EndpointIndependent)
+    }
+}
+
+impl fmt::Display for NatType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoUdp => write!(f, "UDP blocked"),
+            Self::Open => write!(f, "no NAT"),
+            Self::EndpointIndependent => write!(f, "endpoint-independent NAT"),
+            Self::EndpointDependent => write!(f, "endpoint-dependent (symmetric) NAT"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Classify the NAT from net report results and our local addresses
+pub fn classify_nat(
+    udp: bool,
+    mapping_varies_by_dest_ip: Option<bool>,
+    global_v4: Option<SocketAddr>,
+    local_addrs: &[SocketAddr],
+) -> NatType {
+    if !udp {
+        return NatType::NoUdp;
+    }
+    if global_v4.is_some_and(|global| local_addrs.contains(&global)) {
+        return NatType::Open;
+    }
+    match mapping_varies_by_dest_ip {
+        Some(false) => NatType::EndpointIndependent,
+        Some(true) => NatType::EndpointDependent,
+        None => NatType::Unknown,
+    }
+}
+
+/// How a local candidate address was discovered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CandidateKind {
+    /// Address of a local interface
+    Local,
+    /// Public address reported by STUN
+    Stun,
+    /// Public address mapped through UPnP, PCP or NAT-PMP
+    Portmapped,
+    /// STUN address assumed to keep the local port
+    Stun4LocalPort,
+    /// Unknown origin
+    Unknown,
+}
+
+impl From<DirectAddrType> for CandidateKind {
+    fn from(typ: DirectAddrType) -> Self {
+        match typ {
+            DirectAddrType::Local => Self::Local,
+            DirectAddrType::Stun => Self::Stun,
+            DirectAddrType::Portmapped => Self::Portmapped,
+            DirectAddrType::Stun4LocalPort => Self::Stun4LocalPort,
+            DirectAddrType::Unknown => Self::Unknown,
+        }
+    }
+}
+
+/// An address we advertise to peers for hole punching
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocalCandidate {
+    /// The address
+    pub addr: SocketAddr,
+    /// How it was discovered
+    pub kind: CandidateKind,
+}
+
+/// Snapshot of the local network as seen by the endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkDiagnostics {
+    /// Our NodeId
+    pub node_id: String,
+    /// Classified NAT behaviour
+    pub nat_type: NatType,
+    /// Whether the endpoint has finished its first net report
+    pub report_available: bool,
+    /// UDP works at all
+    pub udp: bool,
+    /// IPv4 connectivity
+    pub ipv4: bool,
+    /// IPv6 connectivity
+    pub ipv6: bool,
+    /// Public IPv4 address reported by STUN
+    pub global_v4: Option<SocketAddr>,
+    /// Public IPv6 address reported by STUN
+    pub global_v6: Option<SocketAddr>,
+    /// Relay the endpoint is homed on
+    pub home_relay: Option<String>,
+    /// Addresses advertised for hole punching
+    pub candidates: Vec<LocalCandidate>,
+}
+
+impl NetworkDiagnostics {
+    /// Collect diagnostics, waiting up to `timeout` for the first net report
+    ///
+    /// The endpoint publishes its direct addresses once the first net report
+    /// completes, so UDP reachability, the public addresses and the NAT type
+    /// are derived from those.
+    pub async fn collect(endpoint: &P2PEndpoint, timeout: Duration) -> Self {
+        let iroh_endpoint = endpoint.endpoint();
+        let deadline = Instant::now() + timeout;
+        let direct_addresses = loop {
+            let addrs = iroh_endpoint.direct_addresses().get().ok().flatten();
+            if addrs.is_some() || Instant::now() >= deadline {
+                break addrs;
+            }
+            tokio::time::sleep(ADDRESS_POLL_INTERVAL).await;
+        };
+
+        let candidates = direct_addresses.map(|addrs| {
+            addrs
+                .into_iter()
+                .map(|direct| LocalCandidate {
+                    addr: direct.addr,
+                    kind: direct.typ.into(),
+                })
+                .collect()
+        });
+        let home_relay = iroh_endpoint
+            .home_relay()
+            .get()
+            .ok()
+            .flatten()
+            .map(|url| url.to_string());
+
+        Self::from_candidates(endpoint.node_id().to_string(), home_relay, candidates)
+    }
+
+    /// Build diagnostics from the endpoint's direct addresses
+    ///
+    /// `candidates` is `None` until the first net report has completed. A
+    /// STUN address means a STUN round trip worked over that address family.
+    /// The endpoint only adds a [`CandidateKind::Stun4LocalPort`] address when
+    /// the public mapping varies by destination, so its absence next to a
+    /// STUN IPv4 address is taken as an endpoint-independent mapping.
+    pub fn from_candidates(
+        node_id: String,
+        home_relay: Option<String>,
+        candidates: Option<Vec<LocalCandidate>>,
+    ) -> Self {
+        let report_available = candidates.is_some();
+        let candidates = candidates.unwrap_or_default();
+        let stun = |ipv4: bool| {
+            candidates
+                .iter()
+                .find(|c| c.kind == CandidateKind::Stun && c.addr.is_ipv4() == ipv4)
+                .map(|c| c.addr)
+        };
+        let global_v4 = stun(true);
+        let global_v6 = stun(false);
+        let udp = global_v4.is_some() || global_v6.is_some();
+
+        let mut nat_type = NatType::Unknown;
+        if report_available {
+            let mapping_varies_by_dest_ip = if candidates
+                .iter()
+                .any(|c| c.kind == CandidateKind::Stun4LocalPort)
+            {
+                Some(true)
+            } else {
+                global_v4.map(|_| false)
+            };
+            let local_addrs: Vec<SocketAddr> = candidates
+                .iter()
+                .filter(|c| c.kind == CandidateKind::Local)
+                .map(|c| c.addr)
+                .collect();
+            nat_type = classify_nat(udp, mapping_varies_by_dest_ip, global_v4, &local_addrs);
+        }
+
+        Self {
+            node_id,
+            nat_type,
+            report_available,
+            udp,
+            ipv4: global_v4.is_some(),
+            ipv6: global_v6.is_some(),
+            global_v4,
+            global_v6,
+            home_relay,
+            candidates,
+        }
+    }
+
+    /// Human-readable problems that explain poor connectivity
+    pub fn issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        if !self.report_available {
+            issues.push("No network report yet; STUN probes have not completed".to_string());
+            return issues;
+        }
+        if !self.udp {
+            issues.push("UDP is blocked; all traffic will go through a relay".to_string());
+        }
+        if self.nat_type == NatType::EndpointDependent {
+            issues.push(
+                "Symmetric NAT detected; direct connections will usually fall back to a relay"
+                    .to_string(),
+            );
+        }
+        if self.home_relay.is_none() {
+            issues.push("Not connected to a relay server".to_string());
+        }
+        if !self
+            .candidates
+            .iter()
+            .any(|c| c.kind != CandidateKind::Local)
+        {
+            issues.push("No public address discovered for hole punching".to_string());
+        }
+        issues
+    }
+}
+
+/// Path currently used to reach a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathType {
+    /// Direct UDP path
+    Direct,
+    /// Through a relay server
+    Relay,
+    /// Relay while a direct path is being validated
+    Mixed,
+    /// No path
+    None,
+}
+
+/// Result of probing one of the peer's candidate addresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CandidateOutcome {
+    /// Currently carrying traffic
+    Active,
+    /// Answered a ping
+    Reachable,
+    /// Pinged without answer so far
+    NoResponse,
+    /// Never probed
+    Untried,
+}
+
+/// One of the peer's candidate addresses and what happened when we probed it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CandidateProbe {
+    /// The candidate address
+    pub addr: SocketAddr,
+    /// Probe result
+    pub outcome: CandidateOutcome,
+    /// Latency measured on this address
+    pub latency_ms: Option<u64>,
+    /// Time since the address last answered
+    pub last_alive_ms: Option<u64>,
+}
+
+/// Overall hole-punching result for a peer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HolePunchOutcome {
+    /// A direct path is in use
+    Succeeded,
+    /// A direct path is being validated
+    InProgress,
+    /// Candidates were probed but none became direct
+    Failed,
+    /// The peer advertised no candidates
+    NotAttempted,
+}
+
+/// Summarize hole punching from the path type and candidate probes
+pub fn hole_punch_outcome(path: PathType, candidates: &[CandidateProbe]) -> HolePunchOutcome {
+    match path {
+        PathType::Direct => HolePunchOutcome::Succeeded,
+        PathType::Mixed => HolePunchOutcome::InProgress,
+        PathType::Relay | PathType::None if candidates.is_empty() => HolePunchOutcome::NotAttempted,
+        PathType::Relay | PathType::None => {
+            if candidates
+                .iter()
+                .all(|c| c.outcome == CandidateOutcome::Untried)
+            {
+                HolePunchOutcome::NotAttempted
+            } else {
+                HolePunchOutcome::Failed
+            }
+        }
+    }
+}
+
+/// Diagnostics for the path to a single peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerDiagnostics {
+    /// The peer's NodeId
+    pub peer_id: String,
+    /// Path currently in use
+    pub path: PathType,
+    /// Address of the direct path, if any
+    pub direct_addr: Option<SocketAddr>,
+    /// Relay used for the peer, if any
+    pub relay_url: Option<String>,
+    /// Latency to the relay
+    pub relay_latency_ms: Option<u64>,
+    /// Latency of the path in use
+    pub latency_ms: Option<u64>,
+    /// Candidate addresses and probe results
+    pub candidates: Vec<CandidateProbe>,
+    /// Overall hole-punching result
+    pub hole_punch: HolePunchOutcome,
+}
+
+impl PeerDiagnostics {
+    /// Collect diagnostics for a peer the endpoint knows about
+    pub fn collect(endpoint: &P2PEndpoint, peer_id: NodeId) -> Option<Self> {
+        let info = endpoint.endpoint().remote_info(peer_id)?;
+
+        let (path, direct_addr, active_relay) = match info.conn_type {
+            IrohConnectionType::Direct(addr) => (PathType::Direct, Some(addr), None),
+            IrohConnectionType::Relay(url) => (PathType::Relay, None, Some(url)),
+            IrohConnectionType::Mixed(addr, url) => (PathType::Mixed, Some(addr), Some(url)),
+            IrohConnectionType::None => (PathType::None, None, None),
+        };
+
+        let candidates: Vec<CandidateProbe> = info
+            .addrs
+            .iter()
+            .map(|candidate| {
+                let outcome = if direct_addr == Some(candidate.addr) {
+                    CandidateOutcome::Active
+                } else {
+                    match &candidate.last_control {
+                        Some((_, ControlMsg::Pong)) => CandidateOutcome::Reachable,
+                        Some(_) if candidate.last_alive.is_some() => CandidateOutcome::Reachable,
+                        Some(_) => CandidateOutcome::NoResponse,
+                        None if candidate.last_alive.is_some() => CandidateOutcome::Reachable,
+                        None => CandidateOutcome::Untried,
+                    }
+                };
+                CandidateProbe {
+                    addr: candidate.addr,
+                    outcome,
+                    latency_ms: candidate.latency.map(as_millis),
+                    last_alive_ms: candidate.last_alive.map(as_millis),
+                }
+            })
+            .collect();
+
+        let relay_url = active_relay
+            .map(|url| url.to_string())
+            .or_else(|| info.relay_url.as_ref().map(|r| r.relay_url.to_string()));
+
+        Some(Self {
+            peer_id: peer_id.to_string(),
+            path,
+            direct_addr,
+            relay_url,
+            relay_latency_ms: info
+                .relay_url
+                .as_ref()
+                .and_then(|r| r.latency)
+                .map(as_millis),
+            latency_ms: info.latency.map(as_millis),
+            hole_punch: hole_punch_outcome(path, &candidates),
+            candidates,
+        })
+    }
+}
+
+fn as_millis(duration: Duration) -> u64 {
+    duration.as_millis().min(u64::MAX as u128) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(outcome: CandidateOutcome) -> CandidateProbe {
+        CandidateProbe {
+            addr: "203.0.113.7:4433".parse().unwrap(),
+            outcome,
+            latency_ms: None,
+            last_alive_ms: None,
+        }
+    }
+
+    #[test]
+    fn nat_classification() {
+        let local: SocketAddr = "192.168.1.20:4433".parse().unwrap();
+        let public: SocketAddr = "203.0.113.7:61000".parse().unwrap();
+
+        assert_eq!(classify_nat(false, Some(false), None, &[]), NatType::NoUdp);
+        assert_eq!(
+            classify_nat(true, Some(true), Some(local), &[local]),
+            NatType::Open
+        );
+        assert_eq!(
+            classify_nat(true, Some(false), Some(public), &[local]),
+            NatType::EndpointIndependent
+        );
+        assert_eq!(
+            classify_nat(true, Some(true), Some(public), &[local]),
+            NatType::EndpointDependent
+        );
+        assert_eq!(
+            classify_nat(true, None, Some(public), &[local]),
+            NatType::Unknown
+        );
+        assert!(!NatType::EndpointDependent.hole_punching_likely());
+    }
+
+    #[test]
+    fn hole_punch_summary() {
+        assert_eq!(
+            hole_punch_outcome(PathType::Direct, &[probe(CandidateOutcome::Active)]),
+            HolePunchOutcome::Succeeded
+        );
+        assert_eq!(
+            hole_punch_outcome(PathType::Mixed, &[probe(CandidateOutcome::Reachable)]),
+            HolePunchOutcome::InProgress
+        );
+        assert_eq!(
+            hole_punch_outcome(PathType::Relay, &[probe(CandidateOutcome::NoResponse)]),
+            HolePunchOutcome::Failed
+        );
+        assert_eq!(
+            hole_punch_outcome(PathType::Relay, &[probe(CandidateOutcome::Untried)]),
+            HolePunchOutcome::NotAttempted
+        );
+        assert_eq!(
+            hole_punch_outcome(PathType::Relay, &[]),
+            HolePunchOutcome::NotAttempted
+        );
+    }
+
+    #[test]
+    fn diagnostics_from_direct_addresses() {
+        let candidate = |addr: &str, kind| LocalCandidate {
+            addr: addr.parse().unwrap(),
+            kind,
+        };
+        let local = candidate("192.168.1.20:4433", CandidateKind::Local);
+        let stun = candidate("203.0.113.7:61000", CandidateKind::Stun);
+
+        let pending = NetworkDiagnostics::from_candidates("node".to_string(), None, None);
+        assert!(!pending.report_available);
+        assert_eq!(pending.nat_type, NatType::Unknown);
+
+        let blocked = NetworkDiagnostics::from_candidates(
+            "node".to_string(),
+            None,
+            Some(vec![local.clone()]),
+        );
+        assert!(!blocked.udp);
+        assert_eq!(blocked.nat_type, NatType::NoUdp);
+
+        let easy = NetworkDiagnostics::from_candidates(
+            "node".to_string(),
+            None,
+            Some(vec![local.clone(), stun.clone()]),
+        );
+        assert!(easy.udp && easy.ipv4 && !easy.ipv6);
+        assert_eq!(easy.global_v4, Some(stun.addr));
+        assert_eq!(easy.nat_type, NatType::EndpointIndependent);
+
+        let symmetric = NetworkDiagnostics::from_candidates(
+            "node".to_string(),
+            None,
+            Some(vec![
+                local,
+                stun,
+                candidate("203.0.113.7:4433", CandidateKind::Stun4LocalPort),
+            ]),
+        );
+        assert_eq!(symmetric.nat_type, NatType::EndpointDependent);
+    }
+
+    #[test]
+    fn issues_explain_relay_only_networks() {
+        let diagnostics = NetworkDiagnostics {
+            node_id: "node".to_string(),
+            nat_type: NatType::EndpointDependent,
+            report_available: true,
+            udp: true,
+            ipv4: true,
+            ipv6: false,
+            global_v4: None,
+            global_v6: None,
+            home_relay: Some("https://relay.example.com".to_string()),
+            candidates: vec![LocalCandidate {
+                addr: "192.168.1.20:4433".parse().unwrap(),
+                kind: CandidateKind::Local,
+            }],
+        };
+
+        let issues = diagnostics.issues();
+        assert_eq!(issues.len(), 2);
+        assert!(issues[0].contains("Symmetric NAT"));
+        assert!(issues[1].contains("No public address"));
+    }
+}
@@ -0,0 +1,909 @@
+//! Local network peer discovery over mDNS / DNS-SD
+//!
+//! Nodes advertise a [`MDNS_SERVICE_TYPE`] service whose TXT record carries
+//! their NodeId, and browse for the same service to find other russh nodes
+//! on the LAN. Discovered peers come with their direct addresses, so two
+//! laptops on the same Wi-Fi can connect without exchanging tickets.
+//!
+//! # Requirements Coverage
+//! - Requirement 3.2: NAT hole-punching for direct connections
+
+use crate::error::P2PError;
+use crate::events::{EventBus, RusshEvent};
+use crate::p2p::endpoint::P2PEndpoint;
+use iroh::{NodeAddr, NodeId};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::sync::{broadcast, RwLock};
+use tokio::task::JoinHandle;
+
+/// DNS-SD service type advertised by russh nodes
+pub const MDNS_SERVICE_TYPE: &str = "_russh._udp.local";
+
+/// mDNS port
+pub const MDNS_PORT: u16 = 5353;
+
+/// mDNS IPv4 multicast group
+pub const MDNS_IPV4_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+
+/// Default interval between announcements and browse queries
+pub const DEFAULT_ANNOUNCE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Default lifetime of advertised records
+pub const DEFAULT_RECORD_TTL: Duration = Duration::from_secs(120);
+
+/// TXT key holding the advertising node's NodeId
+const TXT_NODE_ID: &str = "node";
+
+/// TXT entry identifying the announcement format
+const TXT_VERSION: &str = "v=1";
+
+/// Largest mDNS packet we accept
+const MAX_PACKET_SIZE: usize = 9000;
+
+/// Capacity of the discovery event channel
+const EVENT_CAPACITY: usize = 64;
+
+/// Configuration for local discovery
+#[derive(Debug, Clone)]
+pub struct DiscoveryConfig {
+    /// Name shown to other peers (defaults to a short form of the NodeId)
+    pub instance_name: Option<String>,
+    /// Advertise this node; when false the node only browses
+    pub advertise: bool,
+    /// Interval between announcements and browse queries
+    pub announce_interval: Duration,
+    /// Lifetime of our records; peers not heard from within theirs are dropped
+    pub record_ttl: Duration,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            instance_name: None,
+            advertise: true,
+            announce_interval: DEFAULT_ANNOUNCE_INTERVAL,
+            record_ttl: DEFAULT_RECORD_TTL,
+        }
+    }
+}
+
+impl DiscoveryConfig {
+    /// Create a config with default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the name shown to other peers
+    pub fn with_instance_name(mut self, name: impl Into<String>) -> Self {
+        self.instance_name = Some(name.into());
+        self
+    }
+
+    /// Enable or disable advertising this node
+    pub fn with_advertise(mut self, advertise: bool) -> Self {
+        self.advertise = advertise;
+        self
+    }
+
+    /// Set the interval between announcements
+    pub fn with_announce_interval(mut self, interval: Duration) -> Self {
+        self.announce_interval = interval;
+        self
+    }
+
+    /// Set the lifetime of advertised records
+    pub fn with_record_ttl(mut self, ttl: Duration) -> Self {
+        self.record_ttl = ttl;
+        self
+    }
+}
+
+/// A russh node found on the local network
+#[derive(Debug, Clone)]
+pub struct DiscoveredPeer {
+    /// The peer's node ID
+    pub node_id: NodeId,
+    /// Name the peer advertises
+    pub name: String,
+    /// Direct addresses the peer can be reached at
+    pub addrs: Vec<SocketAddr>,
+    /// When the peer was last heard from
+    pub last_seen: Instant,
+    /// How long the peer's records stay valid
+    pub ttl: Duration,
+}
+
+impl DiscoveredPeer {
+    /// Address to pass to `P2PConnectionManager::connect_with_addr`
+    pub fn node_addr(&self) -> NodeAddr {
+        NodeAddr::new(self.node_id).with_direct_addresses(self.addrs.iter().copied())
+    }
+
+    /// Check whether the peer's records have expired
+    pub fn is_expired(&self) -> bool {
+        self.last_seen.elapsed() > self.ttl
+    }
+}
+
+/// Change in the set of discovered peers
+#[derive(Debug, Clone)]
+pub enum DiscoveryEvent {
+    /// A peer appeared or changed its addresses
+    Discovered(DiscoveredPeer),
+    /// A peer said goodbye or its records expired
+    Lost(NodeId),
+}
+
+/// State shared with the background task
+struct Shared {
+    node_id: NodeId,
+    socket: UdpSocket,
+    announcement: Option<Vec<u8>>,
+    goodbye: Option<Vec<u8>>,
+    query: Vec<u8>,
+    peers: RwLock<HashMap<NodeId, DiscoveredPeer>>,
+    events: broadcast::Sender<DiscoveryEvent>,
+}
+
+/// Advertises this node and browses for others on the local network
+pub struct LocalDiscovery {
+    shared: Arc<Shared>,
+    task: JoinHandle<()>,
+}
+
+impl LocalDiscovery {
+    /// Start discovery for a node reachable on `port`
+    ///
+    /// The advertised addresses are the machine's LAN address with `port`.
+    pub async fn start(
+        node_id: NodeId,
+        port: u16,
+        config: DiscoveryConfig,
+    ) -> Result<Self, P2PError> {
+        let socket = bind_multicast()
+            .and_then(UdpSocket::from_std)
+            .map_err(|e| P2PError::Stream(format!("Failed to bind mDNS socket: {}", e)))?;
+
+        let ttl = u32::try_from(config.record_ttl.as_secs()).unwrap_or(u32::MAX);
+        let (announcement, goodbye) = if config.advertise {
+            let instance = config
+                .instance_name
+                .clone()
+                .unwrap_or_else(|| default_instance_name(&node_id));
+            let ips: Vec<IpAddr> = local_ip().into_iter().collect();
+            let records = announcement_records(&node_id, &instance, port, &ips);
+            (
+                Some(encode_response(&records, ttl)),
+                Some(encode_response(&records, 0)),
+            )
+        } else {
+            (None, None)
+        };
+
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let shared = Arc::new(Shared {
+            node_id,
+            socket,
+            announcement,
+            goodbye,
+            query: encode_query(MDNS_SERVICE_TYPE),
+            peers: RwLock::new(HashMap::new()),
+            events,
+        });
+
+        tracing::info!(node_id = %node_id, port, "Local discovery started");
+        let task = tokio::spawn(run(shared.clone(), config.announce_interval));
+        Ok(Self { shared, task })
+    }
+
+    /// Start discovery for a bound endpoint
+    pub async fn for_endpoint(
+        endpoint: &P2PEndpoint,
+        config: DiscoveryConfig,
+    ) -> Result<Self, P2PError> {
+        let (addr, _) = endpoint.endpoint().bound_sockets();
+        Self::start(endpoint.node_id(), addr.port(), config).await
+    }
+
+    /// Get our node ID
+    pub fn node_id(&self) -> NodeId {
+        self.shared.node_id
+    }
+
+    /// List peers whose records have not expired
+    pub async fn peers(&self) -> Vec<DiscoveredPeer> {
+        let peers = self.shared.peers.read().await;
+        let mut peers: Vec<_> = peers
+            .values()
+            .filter(|peer| !peer.is_expired())
+            .cloned()
+            .collect();
+        peers.sort_by(|a, b| a.name.cmp(&b.name));
+        peers
+    }
+
+    /// Look up a discovered peer
+    pub async fn get(&self, node_id: &NodeId) -> Option<DiscoveredPeer> {
+        let peers = self.shared.peers.read().await;
+        peers
+            .get(node_id)
+            .filter(|peer| !peer.is_expired())
+            .cloned()
+    }
+
+    /// Subscribe to discovery events
+    pub fn subscribe(&self) -> broadcast::Receiver<DiscoveryEvent> {
+        self.shared.events.subscribe()
+    }
+
+    /// Ask peers on the network to announce themselves now
+    pub async fn browse(&self) -> Result<(), P2PError> {
+        self.shared.send(&self.shared.query).await
+    }
+
+    /// Forward discovery events onto the event bus
+    ///
+    /// The task ends when discovery stops.
+    pub fn bridge_events(&self, bus: &EventBus) -> JoinHandle<()> {
+        let bus = bus.clone();
+        let mut receiver = self.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(DiscoveryEvent::Discovered(peer)) => {
+                        bus.publish(RusshEvent::PeerDiscovered {
+                            peer_id: peer.node_id.to_string(),
+                            name: peer.name,
+                            addrs: peer.addrs.iter().map(ToString::to_string).collect(),
+                        });
+                    }
+                    Ok(DiscoveryEvent::Lost(node_id)) => {
+                        bus.publish(RusshEvent::PeerLost {
+                            peer_id: node_id.to_string(),
+                        });
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "Discovery event bridge lagged");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    /// Withdraw our announcement and stop discovery
+    pub async fn stop(self) {
+        if let Some(goodbye) = &self.shared.goodbye {
+            if let Err(e) = self.shared.send(goodbye).await {
+                tracing::debug!(error = %e, "Failed to send mDNS goodbye");
+            }
+        }
+        tracing::info!(node_id = %self.shared.node_id, "Local discovery stopped");
+    }
+}
+
+impl Drop for LocalDiscovery {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Shared {
+    async fn send(&self, packet: &[u8]) -> Result<(), P2PError> {
+        self.socket
+            .send_to(packet, (MDNS_IPV4_ADDR, MDNS_PORT))
+            .await
+            .map(|_| ())
+            .map_err(|e| P2PError::Stream(format!("Failed to send mDNS packet: {}", e)))
+    }
+
+    async fn handle_packet(&self, packet: &[u8], from: SocketAddr) {
+        let Some(message) = Message::parse(packet) else {
+            return;
+        };
+
+        if !message.response {
+            let asked = message
+                .questions
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(MDNS_SERVICE_TYPE));
+            if let (true, Some(announcement)) = (asked, &self.announcement) {
+                if let Err(e) = self.send(announcement).await {
+                    tracing::debug!(error = %e, "Failed to answer mDNS query");
+                }
+            }
+            return;
+        }
+
+        let now = Instant::now();
+        let mut peers = self.peers.write().await;
+        for announced in announced_peers(&message.records, from.ip()) {
+            if announced.node_id == self.node_id {
+                continue;
+            }
+            if let Some(event) = apply_announcement(&mut peers, announced, now) {
+                let _ = self.events.send(event);
+            }
+        }
+    }
+
+    async fn expire_peers(&self) {
+        let mut peers = self.peers.write().await;
+        let expired: Vec<NodeId> = peers
+            .values()
+            .filter(|peer| peer.is_expired())
+            .map(|peer| peer.node_id)
+            .collect();
+        for node_id in expired {
+            peers.remove(&node_id);
+            tracing::debug!(peer_id = %node_id, "Discovered peer expired");
+            let _ = self.events.send(DiscoveryEvent::Lost(node_id));
+        }
+    }
+}
+
+/// Background loop: announce, browse, and process incoming packets
+async fn run(shared: Arc<Shared>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    let mut buf = vec![0u8; MAX_PACKET_SIZE];
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                shared.expire_peers().await;
+                if let Some(announcement) = &shared.announcement {
+                    if let Err(e) = shared.send(announcement).await {
+                        tracing::debug!(error = %e, "Failed to send mDNS announcement");
+                    }
+                }
+                if let Err(e) = shared.send(&shared.query).await {
+                    tracing::debug!(error = %e, "Failed to send mDNS query");
+                }
+            }
+            received = shared.socket.recv_from(&mut buf) => match received {
+                Ok((len, from)) => shared.handle_packet(&buf[..len], from).await,
+                Err(e) => tracing::warn!(error = %e, "mDNS receive failed"),
+            },
+        }
+    }
+}
+
+/// Bind a UDP socket to the mDNS port, shared with other responders
+fn bind_multicast() -> std::io::Result<std::net::UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    socket.set_reuse_port(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, MDNS_PORT)).into())?;
+    socket.join_multicast_v4(&MDNS_IPV4_ADDR, &Ipv4Addr::UNSPECIFIED)?;
+    socket.set_multicast_loop_v4(true)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket.into())
+}
+
+/// Address of the interface used to reach the multicast group
+fn local_ip() -> Option<IpAddr> {
+    // Connecting a UDP socket only selects a route; nothing is sent
+    let socket = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((MDNS_IPV4_ADDR, MDNS_PORT)).ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_unspecified()).then_some(ip)
+}
+
+/// Instance name used when none is configured
+fn default_instance_name(node_id: &NodeId) -> String {
+    let id = node_id.to_string();
+    format!("russh-{}", &id[..id.len().min(10)])
+}
+
+/// Make a string usable as a single DNS label
+fn to_label(name: &str) -> String {
+    let mut label: String = name
+        .chars()
+        .map(|c| if c == '.' { '-' } else { c })
+        .collect();
+    while label.len() > 63 {
+        label.pop();
+    }
+    label
+}
+
+/// A peer as described by one announcement
+#[derive(Debug)]
+struct Announced {
+    node_id: NodeId,
+    name: String,
+    addrs: Vec<SocketAddr>,
+    ttl: Duration,
+}
+
+/// Record an announcement, returning the event it causes, if any
+fn apply_announcement(
+    peers: &mut HashMap<NodeId, DiscoveredPeer>,
+    announced: Announced,
+    now: Instant,
+) -> Option<DiscoveryEvent> {
+    if announced.ttl.is_zero() {
+        return peers
+            .remove(&announced.node_id)
+            .map(|peer| DiscoveryEvent::Lost(peer.node_id));
+    }
+
+    let changed = peers.get(&announced.node_id).map_or(true, |peer| {
+        peer.addrs != announced.addrs || peer.name != announced.name || peer.is_expired()
+    });
+    let peer = DiscoveredPeer {
+        node_id: announced.node_id,
+        name: announced.name,
+        addrs: announced.addrs,
+        last_seen: now,
+        ttl: announced.ttl,
+    };
+    peers.insert(peer.node_id, peer.clone());
+
+    if changed {
+        tracing::info!(peer_id = %peer.node_id, name = %peer.name, "Discovered local peer");
+        Some(DiscoveryEvent::Discovered(peer))
+    } else {
+        None
+    }
+}
+
+/// Records advertising a node
+fn announcement_records(
+    node_id: &NodeId,
+    instance: &str,
+    port: u16,
+    ips: &[IpAddr],
+) -> Vec<Record> {
+    let instance_name = format!("{}.{}", to_label(instance), MDNS_SERVICE_TYPE);
+    let host = format!("{}.local", default_instance_name(node_id));
+
+    let mut records = vec![
+        Record {
+            name: MDNS_SERVICE_TYPE.to_string(),
+            data: RecordData::Ptr(instance_name.clone()),
+        },
+        Record {
+            name: instance_name.clone(),
+            data: RecordData::Srv {
+                port,
+                target: host.clone(),
+            },
+        },
+        Record {
+            name: instance_name,
+            data: RecordData::Txt(vec![
+                TXT_VERSION.to_string(),
+                format!("{}={}", TXT_NODE_ID, node_id),
+            ]),
+        },
+    ];
+    records.extend(ips.iter().map(|ip| Record {
+        name: host.clone(),
+        data: match ip {
+            IpAddr::V4(ip) => RecordData::A(*ip),
+            IpAddr::V6(ip) => RecordData::Aaaa(*ip),
+        },
+    }));
+    records
+}
+
+/// Records with the given owner name
+fn named<'a>(
+    records: &'a [(Record, u32)],
+    name: &'a str,
+) -> impl Iterator<Item = &'a (Record, u32)> + 'a {
+    records
+        .iter()
+        .filter(move |(record, _)| record.name.eq_ignore_ascii_case(name))
+}
+
+/// Extract the peers announced in a response
+fn announced_peers(records: &[(Record, u32)], sender: IpAddr) -> Vec<Announced> {
+    named(records, MDNS_SERVICE_TYPE)
+        .filter_map(|(record, ttl)| {
+            let RecordData::Ptr(instance) = &record.data else {
+                return None;
+            };
+
+            let node_id = named(records, instance).find_map(|(record, _)| match &record.data {
+                RecordData::Txt(entries) => entries.iter().find_map(|entry| {
+                    entry
+                        .strip_prefix(TXT_NODE_ID)
+                        .and_then(|rest| rest.strip_prefix('='))
+                        .and_then(|id| id.parse::<NodeId>().ok())
+                }),
+                _ => None,
+            })?;
+
+            let (port, target) =
+                named(records, instance).find_map(|(record, _)| match &record.data {
+                    RecordData::Srv { port, target } => Some((*port, target.clone())),
+                    _ => None,
+                })?;
+
+            let mut addrs: Vec<SocketAddr> = named(records, &target)
+                .filter_map(|(record, _)| match record.data {
+                    RecordData::A(ip) => Some(SocketAddr::new(IpAddr::V4(ip), port)),
+                    RecordData::Aaaa(ip) => Some(SocketAddr::new(IpAddr::V6(ip), port)),
+                    _ => None,
+                })
+                .collect();
+            if addrs.is_empty() {
+                addrs.push(SocketAddr::new(sender, port));
+            }
+
+            let suffix = format!(".{}", MDNS_SERVICE_TYPE);
+            let name = instance
+                .strip_suffix(suffix.as_str())
+                .unwrap_or(instance)
+                .to_string();
+
+            Some(Announced {
+                node_id,
+                name,
+                addrs,
+                ttl: Duration::from_secs(u64::from(*ttl)),
+            })
+        })
+        .collect()
+}
+
+// Minimal DNS message encoding and parsing (RFC 1035, RFC 6762)
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+/// Cache-flush bit for records only we own
+const CLASS_CACHE_FLUSH: u16 = 0x8000;
+/// Response flags: QR and AA
+const FLAGS_RESPONSE: u16 = 0x8400;
+
+/// A resource record
+#[derive(Debug, Clone, PartialEq)]
+struct Record {
+    name: String,
+    data: RecordData,
+}
+
+/// Resource record data
+#[derive(Debug, Clone, PartialEq)]
+enum RecordData {
+    Ptr(String),
+    Srv { port: u16, target: String },
+    Txt(Vec<String>),
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Other,
+}
+
+/// A parsed mDNS message
+#[derive(Debug)]
+struct Message {
+    response: bool,
+    questions: Vec<String>,
+    /// Answer, authority and additional records with their TTLs
+    records: Vec<(Record, u32)>,
+}
+
+fn encode_query(name: &str) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(64);
+    for field in [0u16, 0, 1, 0, 0, 0] {
+        packet.extend_from_slice(&field.to_be_bytes());
+    }
+    encode_name(&mut packet, name);
+    packet.extend_from_slice(&TYPE_PTR.to_be_bytes());
+    packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+    packet
+}
+
+fn encode_response(records: &[Record], ttl: u32) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(512);
+    let count = u16::try_from(records.len()).unwrap_or(u16::MAX);
+    for field in [0u16, FLAGS_RESPONSE, 0, count, 0, 0] {
+        packet.extend_from_slice(&field.to_be_bytes());
+    }
+
+    for record in records.iter().take(usize::from(count)) {
+        encode_name(&mut packet, &record.name);
+
+        let mut rdata = Vec::new();
+        let (kind, class) = match &record.data {
+            RecordData::Ptr(target) => {
+                encode_name(&mut rdata, target);
+                (TYPE_PTR, CLASS_IN)
+            }
+            RecordData::Srv { port, target } => {
+                rdata.extend_from_slice(&[0, 0, 0, 0]); // priority, weight
+                rdata.extend_from_slice(&port.to_be_bytes());
+                encode_name(&mut rdata, target);
+                (TYPE_SRV, CLASS_IN | CLASS_CACHE_FLUSH)
+            }
+            RecordData::Txt(entries) => {
+                for entry in entries {
+                    let bytes = &entry.as_bytes()[..entry.len().min(255)];
+                    rdata.push(bytes.len() as u8);
+                    rdata.extend_from_slice(bytes);
+                }
+                (TYPE_TXT, CLASS_IN | CLASS_CACHE_FLUSH)
+            }
+            RecordData::A(ip) => {
+                rdata.extend_from_slice(&ip.octets());
+                (TYPE_A, CLASS_IN | CLASS_CACHE_FLUSH)
+            }
+            RecordData::Aaaa(ip) => {
+                rdata.extend_from_slice(&ip.octets());
+                (TYPE_AAAA, CLASS_IN | CLASS_CACHE_FLUSH)
+            }
+            RecordData::Other => continue,
+        };
+
+        packet.extend_from_slice(&kind.to_be_bytes());
+        packet.extend_from_slice(&class.to_be_bytes());
+        packet.extend_from_slice(&ttl.to_be_bytes());
+        packet.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&rdata);
+    }
+    packet
+}
+
+fn encode_name(out: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        let bytes = &label.as_bytes()[..label.len().min(63)];
+        out.push(bytes.len() as u8);
+        out.extend_from_slice(bytes);
+    }
+    out.push(0);
+}
+
+impl Message {
+    /// Parse a message, returning `None` if it is malformed
+    fn parse(packet: &[u8]) -> Option<Self> {
+        let mut reader = Reader { packet, pos: 0 };
+        let _id = reader.u16()?;
+        let flags = reader.u16()?;
+        let questions = reader.u16()?;
+        let record_count = [reader.u16()?, reader.u16()?, reader.u16()?]
+            .iter()
+            .map(|count| usize::from(*count))
+            .sum::<usize>();
+
+        let mut message = Message {
+            response: flags & 0x8000 != 0,
+            questions: Vec::new(),
+            records: Vec::new(),
+        };
+
+        for _ in 0..questions {
+            let name = reader.name()?;
+            let _kind = reader.u16()?;
+            let _class = reader.u16()?;
+            message.questions.push(name);
+        }
+
+        for _ in 0..record_count {
+            let name = reader.name()?;
+            let kind = reader.u16()?;
+            let _class = reader.u16()?;
+            let ttl = reader.u32()?;
+            let len = usize::from(reader.u16()?);
+            let end = reader.pos.checked_add(len)?;
+            if end > packet.len() {
+                return None;
+            }
+
+            let data = match kind {
+                TYPE_PTR => RecordData::Ptr(reader.name()?),
+                TYPE_SRV => {
+                    let _priority = reader.u16()?;
+                    let _weight = reader.u16()?;
+                    let port = reader.u16()?;
+                    RecordData::Srv {
+                        port,
+                        target: reader.name()?,
+                    }
+                }
+                TYPE_TXT => {
+                    let mut entries = Vec::new();
+                    while reader.pos < end {
+                        let len = usize::from(reader.u8()?);
+                        let entry = reader.bytes(len)?;
+                        entries.push(String::from_utf8_lossy(entry).into_owned());
+                    }
+                    RecordData::Txt(entries)
+                }
+                TYPE_A if len == 4 => {
+                    let octets: [u8; 4] = reader.bytes(4)?.try_into().ok()?;
+                    RecordData::A(Ipv4Addr::from(octets))
+                }
+                TYPE_AAAA if len == 16 => {
+                    let octets: [u8; 16] = reader.bytes(16)?.try_into().ok()?;
+                    RecordData::Aaaa(Ipv6Addr::from(octets))
+                }
+                _ => RecordData::Other,
+            };
+            reader.pos = end;
+            message.records.push((Record { name, data }, ttl));
+        }
+
+        Some(message)
+    }
+}
+
+/// Cursor over a DNS message
+struct Reader<'a> {
+    packet: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.packet.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Read a possibly compressed domain name
+    fn name(&mut self) -> Option<String> {
+        let mut labels: Vec<String> = Vec::new();
+        let mut pos = self.pos;
+        let mut resume = None;
+        let mut jumps = 0;
+
+        loop {
+            let len = *self.packet.get(pos)?;
+            match len {
+                0 => {
+                    pos += 1;
+                    break;
+                }
+                len if len & 0xC0 == 0xC0 => {
+                    let low = *self.packet.get(pos + 1)?;
+                    let target = usize::from(len & 0x3F) << 8 | usize::from(low);
+                    // Pointers must go backwards, which also rules out loops
+                    if target >= pos || jumps > 32 {
+                        return None;
+                    }
+                    resume.get_or_insert(pos + 2);
+                    jumps += 1;
+                    pos = target;
+                }
+                len if len & 0xC0 == 0 => {
+                    let start = pos + 1;
+                    let label = self.packet.get(start..start + usize::from(len))?;
+                    labels.push(String::from_utf8_lossy(label).into_owned());
+                    pos = start + usize::from(len);
+                }
+                _ => return None,
+            }
+        }
+
+        self.pos = resume.unwrap_or(pos);
+        Some(labels.join("."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_id() -> NodeId {
+        iroh::SecretKey::generate(rand::rngs::OsRng).public()
+    }
+
+    #[test]
+    fn announcement_roundtrips() {
+        let node = node_id();
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20));
+        let records = announcement_records(&node, "alice.laptop", 4433, &[ip]);
+
+        let message = Message::parse(&encode_response(&records, 120)).unwrap();
+        assert!(message.response);
+
+        let peers = announced_peers(&message.records, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].node_id, node);
+        assert_eq!(peers[0].name, "alice-laptop");
+        assert_eq!(peers[0].addrs, vec![SocketAddr::new(ip, 4433)]);
+        assert_eq!(peers[0].ttl, Duration::from_secs(120));
+    }
+
+    #[test]
+    fn sender_address_used_without_address_records() {
+        let node = node_id();
+        let records = announcement_records(&node, "bob", 7000, &[]);
+        let message = Message::parse(&encode_response(&records, 60)).unwrap();
+
+        let sender = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 5));
+        let peers = announced_peers(&message.records, sender);
+        assert_eq!(peers[0].addrs, vec![SocketAddr::new(sender, 7000)]);
+    }
+
+    #[test]
+    fn query_names_service() {
+        let message = Message::parse(&encode_query(MDNS_SERVICE_TYPE)).unwrap();
+        assert!(!message.response);
+        assert_eq!(message.questions, vec![MDNS_SERVICE_TYPE.to_string()]);
+    }
+
+    #[test]
+    fn compressed_names_are_followed() {
+        let mut packet = vec![0, 0, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+        let service_offset = packet.len() as u8;
+        encode_name(&mut packet, MDNS_SERVICE_TYPE);
+        packet.extend_from_slice(&TYPE_PTR.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+        packet.extend_from_slice(&120u32.to_be_bytes());
+        // "carol" followed by a pointer back to the service name
+        packet.extend_from_slice(&8u16.to_be_bytes());
+        packet.extend_from_slice(&[5, b'c', b'a', b'r', b'o', b'l', 0xC0, service_offset]);
+
+        let message = Message::parse(&packet).unwrap();
+        assert_eq!(
+            message.records[0].0.data,
+            RecordData::Ptr(format!("carol.{}", MDNS_SERVICE_TYPE))
+        );
+    }
+
+    #[test]
+    fn malformed_packets_are_rejected() {
+        assert!(Message::parse(&[0, 0, 0x84]).is_none());
+        // Pointer to itself
+        let packet = [0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0xC0, 12, 0, 12, 0, 1];
+        assert!(Message::parse(&packet).is_none());
+    }
+
+    #[test]
+    fn goodbye_removes_peer() {
+        let node = node_id();
+        let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 30));
+        let records = announcement_records(&node, "dave", 4433, &[ip]);
+        let mut peers = HashMap::new();
+        let now = Instant::now();
+
+        let hello = Message::parse(&encode_response(&records, 120)).unwrap();
+        for announced in announced_peers(&hello.records, ip) {
+            assert!(matches!(
+                apply_announcement(&mut peers, announced, now),
+                Some(DiscoveryEvent::Discovered(_))
+            ));
+        }
+        // Repeated announcements are not reported again
+        for announced in announced_peers(&hello.records, ip) {
+            assert!(apply_announcement(&mut peers, announced, now).is_none());
+        }
+        assert_eq!(peers[&node].node_addr().node_id, node);
+
+        let goodbye = Message::parse(&encode_response(&records, 0)).unwrap();
+        for announced in announced_peers(&goodbye.records, ip) {
+            assert!(matches!(
+                apply_announcement(&mut peers, announced, now),
+                Some(DiscoveryEvent::Lost(id)) if id == node
+            ));
+        }
+        assert!(peers.is_empty());
+    }
+}
@@ -7,13 +7,20 @@
 //! - Requirement 3.3: Relay server configuration for fallback
 
 use crate::error::P2PError;
-use iroh::{Endpoint, NodeId, RelayMode, SecretKey};
+use crate::p2p::relay::{self, RelayFailover, RelayHealth, DEFAULT_RELAY_CHECK_TIMEOUT};
+use iroh::{Endpoint, NodeId, RelayMode, RelayUrl, SecretKey};
+use std::io::Write;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
 /// Application-Level Protocol Negotiation identifier for russh
 pub const RUSSH_ALPN: &[u8] = b"russh/1";
 
+/// File name of the node key inside the configuration directory
+pub const NODE_KEY_FILE_NAME: &str = "node.key";
+
 /// Configuration for the P2P endpoint
 #[derive(Debug, Clone)]
 pub struct P2PConfig {
@@ -21,8 +28,16 @@ pub struct P2PConfig {
     pub secret_key: Option<SecretKey>,
     /// Relay mode configuration
     pub relay_mode: P2PRelayMode,
+    /// Failover strategy for custom relays
+    pub relay_failover: RelayFailover,
+    /// Probe custom relays before binding and skip unreachable ones
+    pub relay_health_check: bool,
     /// Enable n0 DNS discovery
     pub enable_discovery: bool,
+    /// Publish and resolve node addresses on the mainline DHT
+    ///
+    /// Requires the `dht` feature; ignored with a warning otherwise.
+    pub enable_dht_discovery: bool,
     /// Custom ALPN protocols (russh/1 is always included)
     pub alpns: Vec<Vec<u8>>,
 }
@@ -35,7 +50,7 @@ pub enum P2PRelayMode {
     Default,
     /// Disable relay (direct connections only)
     Disabled,
-    /// Custom relay configuration (URLs)
+    /// Self-hosted relays only, in priority order (public relays are never used)
     Custom(Vec<String>),
 }
 
@@ -44,7 +59,10 @@ impl Default for P2PConfig {
         Self {
             secret_key: None,
             relay_mode: P2PRelayMode::Default,
+            relay_failover: RelayFailover::default(),
+            relay_health_check: true,
             enable_discovery: true,
+            enable_dht_discovery: false,
             alpns: vec![RUSSH_ALPN.to_vec()],
         }
     }
@@ -62,18 +80,50 @@ impl P2PConfig {
         self
     }
 
+    /// Use the node key stored at `path`, creating it on first use
+    ///
+    /// Keeps the NodeId stable across runs, so tickets handed out and
+    /// pairings made earlier stay valid.
+    pub fn with_key_file(self, path: &Path) -> Result<Self, P2PError> {
+        Ok(self.with_secret_key(load_or_create_secret_key(path)?))
+    }
+
     /// Set relay mode
     pub fn with_relay_mode(mut self, mode: P2PRelayMode) -> Self {
         self.relay_mode = mode;
         self
     }
 
+    /// Use only the given self-hosted relays, in priority order
+    pub fn with_custom_relays(mut self, urls: Vec<String>) -> Self {
+        self.relay_mode = P2PRelayMode::Custom(urls);
+        self
+    }
+
+    /// Set the failover strategy for custom relays
+    pub fn with_relay_failover(mut self, failover: RelayFailover) -> Self {
+        self.relay_failover = failover;
+        self
+    }
+
+    /// Enable or disable relay health checks before binding
+    pub fn with_relay_health_check(mut self, enabled: bool) -> Self {
+        self.relay_health_check = enabled;
+        self
+    }
+
     /// Enable or disable discovery
     pub fn with_discovery(mut self, enabled: bool) -> Self {
         self.enable_discovery = enabled;
         self
     }
 
+    /// Enable or disable DHT discovery
+    pub fn with_dht_discovery(mut self, enabled: bool) -> Self {
+        self.enable_dht_discovery = enabled;
+        self
+    }
+
     /// Add additional ALPN protocols
     pub fn with_alpn(mut self, alpn: Vec<u8>) -> Self {
         if !self.alpns.contains(&alpn) {
@@ -93,7 +143,6 @@ pub struct P2PEndpoint {
     /// Our node ID
     node_id: NodeId,
     /// Configuration used to create this endpoint
-    #[allow(dead_code)]
     config: P2PConfig,
     /// Whether the endpoint is online (connected to relay)
     online: Arc<RwLock<bool>>,
@@ -121,47 +170,26 @@ impl P2PEndpoint {
             P2PRelayMode::Default => builder.relay_mode(RelayMode::Default),
             P2PRelayMode::Disabled => builder.relay_mode(RelayMode::Disabled),
             P2PRelayMode::Custom(urls) => {
-                if urls.is_empty() {
-                    tracing::warn!("Empty custom relay list, using default");
-                    builder.relay_mode(RelayMode::Default)
-                } else {
-                    // Parse URLs and create relay map
-                    let relay_urls: Vec<iroh::RelayUrl> = urls
-                        .iter()
-                        .filter_map(|url_str| {
-                            url_str
-                                .parse::<iroh::RelayUrl>()
-                                .map_err(|e| {
-                                    tracing::warn!("Invalid relay URL '{}': {}", url_str, e);
-                                    e
-                                })
-                                .ok()
-                        })
-                        .collect();
-
-                    if relay_urls.is_empty() {
-                        tracing::warn!("No valid relay URLs, using default");
-                        builder.relay_mode(RelayMode::Default)
-                    } else {
-                        tracing::info!("Using {} custom relay server(s)", relay_urls.len());
-                        let relay_map =
-                            iroh::RelayMap::from_nodes(relay_urls.into_iter().map(|url| {
-                                iroh::RelayNode {
-                                    url: url.clone(),
-                                    stun_only: false,
-                                    stun_port: 3478,
-                                    quic: None,
-                                }
-                            }))
-                            .map_err(|e| {
-                                P2PError::ConnectionFailed {
-                                    peer_id: node_id.to_string(),
-                                    reason: format!("Failed to create relay map: {}", e),
-                                }
-                            })?;
-                        builder.relay_mode(RelayMode::Custom(relay_map))
+                let relay_urls = relay::parse_relay_urls(urls)?;
+                let selected = if config.relay_health_check {
+                    let health =
+                        relay::check_relays(&relay_urls, DEFAULT_RELAY_CHECK_TIMEOUT).await;
+                    for check in health.iter().filter(|check| !check.healthy) {
+                        tracing::warn!(
+                            relay = %check.url,
+                            error = ?check.error,
+                            "Custom relay failed health check"
+                        );
                     }
-                }
+                    relay::select_relays(&health, config.relay_failover)
+                } else if config.relay_failover == RelayFailover::Ordered {
+                    relay_urls.into_iter().take(1).collect()
+                } else {
+                    relay_urls
+                };
+
+                tracing::info!("Using {} custom relay server(s)", selected.len());
+                builder.relay_mode(RelayMode::Custom(relay::relay_map(&selected)?))
             }
         };
 
@@ -169,6 +197,16 @@ impl P2PEndpoint {
         if config.enable_discovery {
             builder = builder.discovery_n0();
         }
+        if config.enable_dht_discovery {
+            #[cfg(feature = "dht")]
+            {
+                builder = builder.discovery_dht();
+            }
+            #[cfg(not(feature = "dht"))]
+            tracing::warn!(
+                "DHT discovery requested but russh-ssh was built without the `dht` feature"
+            );
+        }
 
         let endpoint = builder
             .bind()
@@ -229,11 +267,13 @@ impl P2PEndpoint {
 
     /// Get direct addresses (if any)
     pub fn direct_addresses(&self) -> Vec<std::net::SocketAddr> {
-        // let watcher = self.endpoint.direct_addresses();
-        // watcher.get()
-        //     .map(|addrs| addrs.into_iter().map(|da| da.clone().into()).collect())
-        //     .unwrap_or_default()
-        vec![]
+        self.endpoint
+            .direct_addresses()
+            .get()
+            .ok()
+            .flatten()
+            .map(|addrs| addrs.into_iter().map(|direct| direct.addr).collect())
+            .unwrap_or_default()
     }
 
     /// Get the relay URL (if connected to a relay)
@@ -241,6 +281,33 @@ impl P2PEndpoint {
         self.endpoint.home_relay().get().ok().flatten()
     }
 
+    /// Self-hosted relays this endpoint was configured with, in priority order
+    pub fn configured_relays(&self) -> Vec<RelayUrl> {
+        match &self.config.relay_mode {
+            P2PRelayMode::Custom(urls) => relay::parse_relay_urls(urls).unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Probe the configured self-hosted relays, or the home relay otherwise
+    pub async fn relay_health(&self, timeout: Duration) -> Vec<RelayHealth> {
+        let mut urls = self.configured_relays();
+        if urls.is_empty() {
+            urls.extend(self.relay_url());
+        }
+        relay::check_relays(&urls, timeout).await
+    }
+
+    /// Tell the endpoint the local network changed
+    ///
+    /// It rebinds and re-probes its addresses right away, so open
+    /// connections move to the new path instead of waiting for their
+    /// current one to time out.
+    pub async fn network_change(&self) {
+        tracing::info!(node_id = %self.node_id, "Network changed, migrating P2P connections");
+        self.endpoint.network_change().await;
+    }
+
     /// Close the endpoint gracefully
     pub async fn close(self) {
         tracing::info!(node_id = %self.node_id, "Closing P2P endpoint");
@@ -279,12 +346,30 @@ impl P2PEndpointBuilder {
         self
     }
 
+    /// Use only the given self-hosted relays, in priority order
+    pub fn relay_custom(mut self, urls: Vec<String>) -> Self {
+        self.config.relay_mode = P2PRelayMode::Custom(urls);
+        self
+    }
+
+    /// Set the failover strategy for custom relays
+    pub fn relay_failover(mut self, failover: RelayFailover) -> Self {
+        self.config.relay_failover = failover;
+        self
+    }
+
     /// Enable n0 DNS discovery
     pub fn discovery(mut self, enabled: bool) -> Self {
         self.config.enable_discovery = enabled;
         self
     }
 
+    /// Enable DHT discovery
+    pub fn dht_discovery(mut self, enabled: bool) -> Self {
+        self.config.enable_dht_discovery = enabled;
+        self
+    }
+
     /// Add an ALPN protocol
     pub fn alpn(mut self, alpn: Vec<u8>) -> Self {
         if !self.config.alpns.contains(&alpn) {
@@ -305,16 +390,66 @@ impl Default for P2PEndpointBuilder {
     }
 }
 
+/// Read a node key file, generating and saving a new key if it is missing
+///
+/// The file holds the raw 32-byte key and is readable only by the owner
+/// on Unix.
+pub fn load_or_create_secret_key(path: &Path) -> Result<SecretKey, P2PError> {
+    let storage_error = |e: std::io::Error| P2PError::Storage(format!("{}: {}", path.display(), e));
+
+    match std::fs::read(path) {
+        Ok(bytes) => {
+            let bytes: [u8; 32] = bytes
+                .try_into()
+                .map_err(|_| P2PError::Storage(format!("Invalid node key {}", path.display())))?;
+            return Ok(SecretKey::from_bytes(&bytes));
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(storage_error(e)),
+    }
+
+    let key = SecretKey::generate(rand::rngs::OsRng);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(storage_error)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600); // rw-------
+    }
+    let mut file = options.open(path).map_err(storage_error)?;
+    file.write_all(&key.to_bytes()).map_err(storage_error)?;
+    file.sync_all().map_err(storage_error)?;
+    tracing::info!(path = %path.display(), node_id = %key.public(), "Created node key");
+    Ok(key)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn node_key_file_keeps_the_node_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("p2p").join(NODE_KEY_FILE_NAME);
+
+        let created = load_or_create_secret_key(&path).unwrap();
+        let loaded = load_or_create_secret_key(&path).unwrap();
+        assert_eq!(created.public(), loaded.public());
+
+        std::fs::write(&path, b"too short").unwrap();
+        assert!(load_or_create_secret_key(&path).is_err());
+    }
+
     #[test]
     fn p2p_config_default() {
         let config = P2PConfig::default();
         assert!(config.secret_key.is_none());
         assert!(config.enable_discovery);
         assert!(config.alpns.contains(&RUSSH_ALPN.to_vec()));
+        assert!(config.relay_health_check);
     }
 
     #[test]
@@ -341,4 +476,15 @@ mod tests {
         assert!(builder.config.enable_discovery);
         assert!(builder.config.alpns.contains(&b"test/1".to_vec()));
     }
+
+    #[tokio::test]
+    async fn empty_custom_relay_list_does_not_fall_back_to_public_relays() {
+        let result = P2PEndpointBuilder::new()
+            .relay_custom(Vec::new())
+            .discovery(false)
+            .bind()
+            .await;
+
+        assert!(matches!(result, Err(P2PError::RelayFailed(_))));
+    }
 }
@@ -0,0 +1,499 @@
+//! Port forwarding over P2P connections
+//!
+//! Mirrors SSH local and remote forwarding, with the peer's tunnel agent in
+//! place of the SSH server:
+//!
+//! - A local forward listens on a loopback port here and connects each
+//!   client to a `host:port` reached from the peer.
+//! - A remote forward asks the peer to listen on one of its loopback ports
+//!   and connects each client back to a `host:port` reached from here, so a
+//!   dev server can be shared without any public endpoint.
+//!
+//! Both directions use the `forward` service. Dialing and binding on a
+//! peer require the [`PeerCapability::PortForward`] capability; connections
+//! back to a remote forward are only accepted from the peer it was bound on.
+//!
+//! # Requirements Coverage
+//! - Requirement 3.4: Multiplexed bidirectional streams
+
+use crate::error::P2PError;
+use crate::p2p::acl::PeerCapability;
+use crate::p2p::connection::P2PConnectionManager;
+use crate::p2p::stream::{BiStream, StreamExt};
+use crate::p2p::tunnel::{open_tunnel, pipe, StreamHandler, TunnelAgent};
+use async_trait::async_trait;
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::AbortHandle;
+use uuid::Uuid;
+
+/// Service name of the port forwarding protocol
+pub const FORWARD_SERVICE: &str = "forward";
+
+/// Largest forwarding control frame
+const MAX_FORWARD_FRAME_SIZE: usize = 4096;
+
+/// Port forward configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum P2PForward {
+    /// Local port forwarding (local port -> host:port reached from the peer)
+    Local {
+        local_port: u16,
+        remote_host: String,
+        remote_port: u16,
+    },
+    /// Remote port forwarding (port on the peer -> host:port reached from here)
+    Remote {
+        remote_port: u16,
+        local_host: String,
+        local_port: u16,
+    },
+}
+
+/// Active P2P forward
+#[derive(Debug)]
+pub struct P2PForwardHandle {
+    /// Forward identifier
+    pub id: Uuid,
+    /// Peer the forward goes through
+    pub peer_id: NodeId,
+    /// Forward configuration
+    pub config: P2PForward,
+    /// Port actually listening, here for local forwards and on the peer
+    /// for remote forwards
+    pub bound_port: u16,
+}
+
+/// First frame on a forwarding stream
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ForwardRequest {
+    /// Connect to a target and pipe the stream to it
+    Dial { host: String, port: u16 },
+    /// Listen on a loopback port for as long as this stream stays open
+    Bind { id: Uuid, port: u16 },
+    /// A client arrived on a port bound for the requester
+    Connect { id: Uuid },
+}
+
+/// Answer to a [`ForwardRequest`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ForwardReply {
+    Connected,
+    Bound { port: u16 },
+    Refused { reason: String },
+}
+
+/// Target of a remote forward, as seen from this side
+struct ExposedTarget {
+    peer_id: NodeId,
+    host: String,
+    port: u16,
+}
+
+struct ForwarderInner {
+    manager: Arc<P2PConnectionManager>,
+    forwards: Mutex<HashMap<Uuid, (Arc<P2PForwardHandle>, AbortHandle)>>,
+    exposed: Mutex<HashMap<Uuid, ExposedTarget>>,
+}
+
+/// Starts, stops and serves P2P port forwards
+///
+/// Cheap to clone; all clones share the same forwards. Register it with a
+/// [`TunnelAgent`] (see [`P2PForwarder::register`]) on both ends: remote
+/// forwards connect back through the requester's agent.
+#[derive(Clone)]
+pub struct P2PForwarder {
+    inner: Arc<ForwarderInner>,
+}
+
+impl P2PForwarder {
+    /// Create a forwarder using the given connection manager
+    pub fn new(manager: Arc<P2PConnectionManager>) -> Self {
+        Self {
+            inner: Arc::new(ForwarderInner {
+                manager,
+                forwards: Mutex::new(HashMap::new()),
+                exposed: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Serve forwarding requests from peers
+    ///
+    /// The service is open so remote forwards can connect back; dialing and
+    /// binding still check [`PeerCapability::PortForward`].
+    pub fn register(&self, agent: TunnelAgent) -> TunnelAgent {
+        agent.with_open_handler(FORWARD_SERVICE, Arc::new(self.clone()))
+    }
+
+    /// Start a port forward through a peer
+    pub async fn start(
+        &self,
+        peer_id: NodeId,
+        forward: P2PForward,
+    ) -> Result<Arc<P2PForwardHandle>, P2PError> {
+        let id = Uuid::new_v4();
+        let (bound_port, task) = match &forward {
+            P2PForward::Local {
+                local_port,
+                remote_host,
+                remote_port,
+            } => {
+                self.start_local(peer_id, *local_port, remote_host.clone(), *remote_port)
+                    .await?
+            }
+            P2PForward::Remote {
+                remote_port,
+                local_host,
+                local_port,
+            } => {
+                self.start_remote(id, peer_id, *remote_port, local_host.clone(), *local_port)
+                    .await?
+            }
+        };
+
+        let handle = Arc::new(P2PForwardHandle {
+            id,
+            peer_id,
+            config: forward,
+            bound_port,
+        });
+        tracing::info!(peer_id = %peer_id, forward = ?handle.config, bound_port, "P2P forward started");
+        self.forwards().insert(id, (handle.clone(), task));
+        Ok(handle)
+    }
+
+    /// Stop a port forward
+    ///
+    /// Connections already piped keep running until they close.
+    pub fn stop(&self, id: Uuid) -> Result<(), P2PError> {
+        let (_, task) = self
+            .forwards()
+            .remove(&id)
+            .ok_or_else(|| P2PError::Stream(format!("Forward not found: {}", id)))?;
+        task.abort();
+        self.exposed().remove(&id);
+        Ok(())
+    }
+
+    /// List active forwards
+    pub fn list(&self) -> Vec<Arc<P2PForwardHandle>> {
+        self.forwards()
+            .values()
+            .map(|(handle, _)| handle.clone())
+            .collect()
+    }
+
+    async fn start_local(
+        &self,
+        peer_id: NodeId,
+        local_port: u16,
+        host: String,
+        port: u16,
+    ) -> Result<(u16, AbortHandle), P2PError> {
+        let listener = bind_loopback(local_port).await?;
+        let bound_port = local_addr_port(&listener)?;
+        let manager = self.inner.manager.clone();
+
+        let task = tokio::spawn(async move {
+            loop {
+                let tcp = match listener.accept().await {
+                    Ok((tcp, _)) => tcp,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Forward listener failed");
+                        break;
+                    }
+                };
+
+                let manager = manager.clone();
+                let request = ForwardRequest::Dial {
+                    host: host.clone(),
+                    port,
+                };
+                tokio::spawn(async move {
+                    let result = async {
+                        let mut stream = open_tunnel(&manager, peer_id, FORWARD_SERVICE).await?;
+                        expect_connected(request_reply(&mut stream, &request).await?)?;
+                        pipe(tcp, stream).await
+                    }
+                    .await;
+                    if let Err(e) = result {
+                        tracing::debug!(peer_id = %peer_id, error = %e, "Forwarded connection failed");
+                    }
+                });
+            }
+        });
+
+        Ok((bound_port, task.abort_handle()))
+    }
+
+    async fn start_remote(
+        &self,
+        id: Uuid,
+        peer_id: NodeId,
+        remote_port: u16,
+        host: String,
+        port: u16,
+    ) -> Result<(u16, AbortHandle), P2PError> {
+        // Register the target first: the peer may connect back as soon as
+        // it has bound the port
+        self.exposed().insert(
+            id,
+            ExposedTarget {
+                peer_id,
+                host,
+                port,
+            },
+        );
+
+        let bound = async {
+            let mut control = open_tunnel(&self.inner.manager, peer_id, FORWARD_SERVICE).await?;
+            let request = ForwardRequest::Bind {
+                id,
+                port: remote_port,
+            };
+            match request_reply(&mut control, &request).await? {
+                ForwardReply::Bound { port } => Ok((port, control)),
+                ForwardReply::Refused { reason } => Err(P2PError::Unauthorized(reason)),
+                ForwardReply::Connected => Err(unexpected_reply()),
+            }
+        }
+        .await;
+        let (bound_port, mut control) = match bound {
+            Ok(bound) => bound,
+            Err(e) => {
+                self.exposed().remove(&id);
+                return Err(e);
+            }
+        };
+
+        // The peer listens until the control stream closes
+        let forwarder = self.clone();
+        let task = tokio::spawn(async move {
+            let mut buf = [0u8; 1];
+            while matches!(control.read(&mut buf).await, Ok(n) if n > 0) {}
+            tracing::info!(peer_id = %peer_id, remote_port = bound_port, "Remote forward closed by peer");
+            forwarder.exposed().remove(&id);
+            forwarder.forwards().remove(&id);
+        });
+
+        Ok((bound_port, task.abort_handle()))
+    }
+
+    /// Listen on a loopback port for a peer until its control stream closes
+    async fn serve_bind(
+        &self,
+        peer_id: NodeId,
+        mut control: BiStream,
+        id: Uuid,
+        port: u16,
+    ) -> Result<(), P2PError> {
+        let listener = match bind_loopback(port).await {
+            Ok(listener) => listener,
+            Err(e) => return refuse(&mut control, e.to_string()).await,
+        };
+        let bound_port = local_addr_port(&listener)?;
+        send_json(&mut control, &ForwardReply::Bound { port: bound_port }).await?;
+        tracing::info!(peer_id = %peer_id, port = bound_port, "Listening for remote forward");
+
+        let mut buf = [0u8; 1];
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let tcp = match accepted {
+                        Ok((tcp, _)) => tcp,
+                        Err(e) => {
+                            tracing::warn!(error = %e, "Remote forward listener failed");
+                            break;
+                        }
+                    };
+                    let manager = self.inner.manager.clone();
+                    tokio::spawn(async move {
+                        let result = async {
+                            let mut stream = open_tunnel(&manager, peer_id, FORWARD_SERVICE).await?;
+                            let request = ForwardRequest::Connect { id };
+                            expect_connected(request_reply(&mut stream, &request).await?)?;
+                            pipe(tcp, stream).await
+                        }
+                        .await;
+                        if let Err(e) = result {
+                            tracing::debug!(peer_id = %peer_id, error = %e, "Remote forward connection failed");
+                        }
+                    });
+                }
+                read = control.read(&mut buf) => {
+                    if !matches!(read, Ok(n) if n > 0) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        tracing::info!(peer_id = %peer_id, port = bound_port, "Remote forward stopped");
+        Ok(())
+    }
+
+    fn forwards(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<Uuid, (Arc<P2PForwardHandle>, AbortHandle)>> {
+        self.inner
+            .forwards
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn exposed(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, ExposedTarget>> {
+        self.inner
+            .exposed
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl StreamHandler for P2PForwarder {
+    async fn handle(&self, peer_id: NodeId, mut stream: BiStream) -> Result<(), P2PError> {
+        let request: ForwardRequest = recv_json(&mut stream).await?;
+
+        match request {
+            ForwardRequest::Dial { host, port } => {
+                if let Err(e) = self
+                    .inner
+                    .manager
+                    .authorize(&peer_id, PeerCapability::PortForward)
+                {
+                    refuse(&mut stream, e.to_string()).await?;
+                    return Err(e);
+                }
+                connect_and_pipe(stream, &host, port).await
+            }
+            ForwardRequest::Bind { id, port } => {
+                if let Err(e) = self
+                    .inner
+                    .manager
+                    .authorize(&peer_id, PeerCapability::PortForward)
+                {
+                    refuse(&mut stream, e.to_string()).await?;
+                    return Err(e);
+                }
+                self.serve_bind(peer_id, stream, id, port).await
+            }
+            ForwardRequest::Connect { id } => {
+                let target = self
+                    .exposed()
+                    .get(&id)
+                    .filter(|target| target.peer_id == peer_id)
+                    .map(|target| (target.host.clone(), target.port));
+                match target {
+                    Some((host, port)) => connect_and_pipe(stream, &host, port).await,
+                    None => refuse(&mut stream, format!("no remote forward {}", id)).await,
+                }
+            }
+        }
+    }
+}
+
+/// Connect to a target, tell the requester, and pipe the stream to it
+async fn connect_and_pipe(mut stream: BiStream, host: &str, port: u16) -> Result<(), P2PError> {
+    let tcp = match TcpStream::connect((host, port)).await {
+        Ok(tcp) => tcp,
+        Err(e) => {
+            let reason = format!("failed to reach {}:{}: {}", host, port, e);
+            refuse(&mut stream, reason.clone()).await?;
+            return Err(P2PError::Stream(reason));
+        }
+    };
+    send_json(&mut stream, &ForwardReply::Connected).await?;
+    pipe(tcp, stream).await
+}
+
+async fn request_reply(
+    stream: &mut BiStream,
+    request: &ForwardRequest,
+) -> Result<ForwardReply, P2PError> {
+    send_json(stream, request).await?;
+    recv_json(stream).await
+}
+
+fn expect_connected(reply: ForwardReply) -> Result<(), P2PError> {
+    match reply {
+        ForwardReply::Connected => Ok(()),
+        ForwardReply::Refused { reason } => Err(P2PError::Stream(reason)),
+        ForwardReply::Bound { .. } => Err(unexpected_reply()),
+    }
+}
+
+fn unexpected_reply() -> P2PError {
+    P2PError::Stream("unexpected forward reply".to_string())
+}
+
+async fn refuse(stream: &mut BiStream, reason: String) -> Result<(), P2PError> {
+    send_json(stream, &ForwardReply::Refused { reason }).await?;
+    stream.finish().await
+}
+
+async fn send_json<T: Serialize>(stream: &mut BiStream, value: &T) -> Result<(), P2PError> {
+    let data = serde_json::to_vec(value)
+        .map_err(|e| P2PError::Stream(format!("Failed to encode forward frame: {}", e)))?;
+    stream.send_message(&data).await
+}
+
+async fn recv_json<T: serde::de::DeserializeOwned>(stream: &mut BiStream) -> Result<T, P2PError> {
+    let data = stream.recv_message(MAX_FORWARD_FRAME_SIZE).await?;
+    serde_json::from_slice(&data)
+        .map_err(|e| P2PError::Stream(format!("Invalid forward frame: {}", e)))
+}
+
+async fn bind_loopback(port: u16) -> Result<TcpListener, P2PError> {
+    TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+        .await
+        .map_err(|e| P2PError::Stream(format!("Failed to bind local port {}: {}", port, e)))
+}
+
+fn local_addr_port(listener: &TcpListener) -> Result<u16, P2PError> {
+    listener
+        .local_addr()
+        .map(|addr| addr.port())
+        .map_err(|e| P2PError::Stream(format!("Failed to read bound port: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forward_frames_are_tagged() {
+        let id = Uuid::new_v4();
+        let json = serde_json::to_value(ForwardRequest::Bind { id, port: 3000 }).unwrap();
+        assert_eq!(json["type"], "bind");
+        assert_eq!(json["port"], 3000);
+
+        let reply: ForwardReply =
+            serde_json::from_str(r#"{"type":"refused","reason":"nope"}"#).unwrap();
+        assert_eq!(
+            reply,
+            ForwardReply::Refused {
+                reason: "nope".to_string()
+            }
+        );
+        assert!(expect_connected(reply).is_err());
+        assert!(expect_connected(ForwardReply::Connected).is_ok());
+    }
+
+    #[test]
+    fn forward_config_roundtrips() {
+        let forward = P2PForward::Remote {
+            remote_port: 8080,
+            local_host: "localhost".to_string(),
+            local_port: 5173,
+        };
+        let json = serde_json::to_string(&forward).unwrap();
+        assert_eq!(serde_json::from_str::<P2PForward>(&json).unwrap(), forward);
+    }
+}
@@ -0,0 +1,498 @@
+//! Topic-based publish/subscribe over P2P
+//!
+//! Peers that join the same topic form a mesh: every message is sent to the
+//! topic's neighbors, which deliver it locally and forward it to their own
+//! neighbors. Message ids are remembered for a while so each peer delivers
+//! and forwards a message at most once. Streaming sync events, presence and
+//! profile sync can broadcast to a group this way instead of managing N
+//! point-to-point channels.
+//!
+//! Frames travel over one long-lived tunnel stream per neighbor to the
+//! peer's `gossip` service.
+//!
+//! # Requirements Coverage
+//! - Requirement 3.4: Multiplexed bidirectional streams
+
+use crate::error::P2PError;
+use crate::p2p::acl::PeerCapability;
+use crate::p2p::connection::P2PConnectionManager;
+use crate::p2p::stream::{BiStream, StreamExt};
+use crate::p2p::tunnel::{open_tunnel, StreamHandler, TunnelAgent};
+use async_trait::async_trait;
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Service name of the gossip protocol
+pub const GOSSIP_SERVICE: &str = "gossip";
+
+/// Largest message payload
+pub const MAX_GOSSIP_PAYLOAD: usize = 1024 * 1024;
+
+/// How many times a message is forwarded before it is dropped
+pub const MAX_GOSSIP_HOPS: u8 = 8;
+
+/// Capacity of each topic's local subscriber channel
+const TOPIC_CAPACITY: usize = 256;
+
+/// How many message ids are remembered for deduplication
+const SEEN_CAPACITY: usize = 4096;
+
+/// Largest frame on the wire (payload plus encoding overhead)
+const MAX_FRAME_SIZE: usize = 3 * MAX_GOSSIP_PAYLOAD;
+
+/// Identifier of a gossip topic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TopicId([u8; 32]);
+
+impl TopicId {
+    /// Derive a topic id from a human-readable name
+    pub fn from_name(name: &str) -> Self {
+        Self(*blake3::hash(name.as_bytes()).as_bytes())
+    }
+
+    /// Create a topic id from raw bytes
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Get the raw bytes
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for TopicId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl FromStr for TopicId {
+    type Err = P2PError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = [0u8; 32];
+        hex::decode_to_slice(s, &mut bytes)
+            .map_err(|e| P2PError::Stream(format!("invalid topic id '{}': {}", s, e)))?;
+        Ok(Self(bytes))
+    }
+}
+
+impl Serialize for TopicId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for TopicId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A message published on a topic
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GossipMessage {
+    /// Unique message id, used for deduplication
+    pub id: Uuid,
+    /// Topic the message was published on
+    pub topic: TopicId,
+    /// Peer that published the message
+    pub origin: NodeId,
+    /// Neighbor the message was received from (`None` for local publishes)
+    #[serde(skip)]
+    pub delivered_by: Option<NodeId>,
+    /// Application payload
+    pub payload: Vec<u8>,
+    /// Number of times the message has been forwarded
+    pub hops: u8,
+}
+
+/// Frames exchanged between neighbors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum GossipFrame {
+    /// The sender joined a topic and wants to be a neighbor
+    Join { topic: TopicId },
+    /// The sender left a topic
+    Leave { topic: TopicId },
+    /// A published message
+    Message(GossipMessage),
+}
+
+/// Local state of a joined topic
+struct TopicState {
+    neighbors: HashSet<NodeId>,
+    sender: broadcast::Sender<GossipMessage>,
+}
+
+/// Bounded set of recently seen message ids
+#[derive(Debug, Default)]
+struct SeenMessages {
+    order: VecDeque<Uuid>,
+    ids: HashSet<Uuid>,
+}
+
+impl SeenMessages {
+    /// Record an id, returning false if it was already seen
+    fn insert(&mut self, id: Uuid) -> bool {
+        if !self.ids.insert(id) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > SEEN_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+struct GossipInner {
+    manager: Arc<P2PConnectionManager>,
+    topics: Mutex<HashMap<TopicId, TopicState>>,
+    seen: Mutex<SeenMessages>,
+    outgoing: tokio::sync::Mutex<HashMap<NodeId, Arc<tokio::sync::Mutex<BiStream>>>>,
+}
+
+/// Publish/subscribe message bus over P2P connections
+///
+/// Cheap to clone; all clones share the same topics. Register it with a
+/// [`TunnelAgent`] (see [`Gossip::register`]) to receive messages.
+#[derive(Clone)]
+pub struct Gossip {
+    inner: Arc<GossipInner>,
+}
+
+impl Gossip {
+    /// Create a gossip bus using the given connection manager
+    pub fn new(manager: Arc<P2PConnectionManager>) -> Self {
+        Self {
+            inner: Arc::new(GossipInner {
+                manager,
+                topics: Mutex::new(HashMap::new()),
+                seen: Mutex::new(SeenMessages::default()),
+                outgoing: tokio::sync::Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Accept gossip from peers with the [`PeerCapability::Messaging`]
+    /// capability
+    pub fn register(&self, agent: TunnelAgent) -> TunnelAgent {
+        agent.with_handler(
+            GOSSIP_SERVICE,
+            PeerCapability::Messaging,
+            Arc::new(self.clone()),
+        )
+    }
+
+    /// Join a topic with an initial set of neighbors
+    ///
+    /// Joining a topic twice adds the new neighbors and returns another
+    /// subscription. Neighbors that cannot be reached are kept; sends to
+    /// them are retried with every publish.
+    pub async fn join(
+        &self,
+        topic: TopicId,
+        peers: impl IntoIterator<Item = NodeId>,
+    ) -> broadcast::Receiver<GossipMessage> {
+        let local = self.inner.manager.local_node_id();
+        let peers: Vec<NodeId> = peers.into_iter().filter(|p| *p != local).collect();
+
+        let receiver = {
+            let mut topics = self.topics();
+            let state = topics.entry(topic).or_insert_with(|| TopicState {
+                neighbors: HashSet::new(),
+                sender: broadcast::channel(TOPIC_CAPACITY).0,
+            });
+            state.neighbors.extend(peers.iter().copied());
+            state.sender.subscribe()
+        };
+
+        for peer in peers {
+            if let Err(e) = self.send_frame(peer, &GossipFrame::Join { topic }).await {
+                tracing::debug!(peer_id = %peer, topic = %topic, error = %e, "Failed to announce join");
+            }
+        }
+        receiver
+    }
+
+    /// Add a neighbor to a joined topic
+    pub async fn add_peer(&self, topic: TopicId, peer: NodeId) -> Result<(), P2PError> {
+        match self.topics().get_mut(&topic) {
+            Some(state) => state.neighbors.insert(peer),
+            None => return Err(not_joined(topic)),
+        };
+        self.send_frame(peer, &GossipFrame::Join { topic }).await
+    }
+
+    /// Drop a neighbor from a joined topic, telling it to stop sending
+    pub async fn remove_peer(&self, topic: TopicId, peer: NodeId) {
+        let removed = self
+            .topics()
+            .get_mut(&topic)
+            .is_some_and(|state| state.neighbors.remove(&peer));
+        if removed {
+            let _ = self.send_frame(peer, &GossipFrame::Leave { topic }).await;
+        }
+    }
+
+    /// Leave a topic, telling its neighbors
+    pub async fn leave(&self, topic: TopicId) {
+        let Some(state) = self.topics().remove(&topic) else {
+            return;
+        };
+        for peer in state.neighbors {
+            let _ = self.send_frame(peer, &GossipFrame::Leave { topic }).await;
+        }
+    }
+
+    /// Topics joined locally
+    pub fn topics_joined(&self) -> Vec<TopicId> {
+        self.topics().keys().copied().collect()
+    }
+
+    /// Current neighbors of a topic
+    pub fn neighbors(&self, topic: &TopicId) -> Vec<NodeId> {
+        self.topics()
+            .get(topic)
+            .map(|state| state.neighbors.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Publish a payload on a joined topic
+    ///
+    /// Returns the number of neighbors the message was handed to.
+    pub async fn publish(&self, topic: TopicId, payload: Vec<u8>) -> Result<usize, P2PError> {
+        if payload.len() > MAX_GOSSIP_PAYLOAD {
+            return Err(P2PError::Stream(format!(
+                "gossip payload too large: {} > {}",
+                payload.len(),
+                MAX_GOSSIP_PAYLOAD
+            )));
+        }
+
+        let neighbors = {
+            let topics = self.topics();
+            let state = topics.get(&topic).ok_or_else(|| not_joined(topic))?;
+            state.neighbors.iter().copied().collect::<Vec<_>>()
+        };
+
+        let message = GossipMessage {
+            id: Uuid::new_v4(),
+            topic,
+            origin: self.inner.manager.local_node_id(),
+            delivered_by: None,
+            payload,
+            hops: 0,
+        };
+        self.seen().insert(message.id);
+
+        Ok(self.fan_out(neighbors, message).await)
+    }
+
+    /// Send a message to each neighbor concurrently, returning how many accepted it
+    async fn fan_out(&self, neighbors: Vec<NodeId>, message: GossipMessage) -> usize {
+        let frame = GossipFrame::Message(message);
+        let handles: Vec<_> = neighbors
+            .into_iter()
+            .map(|peer| {
+                let gossip = self.clone();
+                let frame = frame.clone();
+                tokio::spawn(async move {
+                    let result = gossip.send_frame(peer, &frame).await;
+                    if let Err(e) = &result {
+                        tracing::debug!(peer_id = %peer, error = %e, "Failed to send gossip");
+                    }
+                    result.is_ok()
+                })
+            })
+            .collect();
+
+        let mut delivered = 0;
+        for handle in handles {
+            if matches!(handle.await, Ok(true)) {
+                delivered += 1;
+            }
+        }
+        delivered
+    }
+
+    /// Handle a frame received from a neighbor
+    async fn handle_frame(&self, from: NodeId, frame: GossipFrame) {
+        match frame {
+            GossipFrame::Join { topic } => {
+                if let Some(state) = self.topics().get_mut(&topic) {
+                    state.neighbors.insert(from);
+                }
+            }
+            GossipFrame::Leave { topic } => {
+                if let Some(state) = self.topics().get_mut(&topic) {
+                    state.neighbors.remove(&from);
+                }
+            }
+            GossipFrame::Message(mut message) => {
+                if !self.seen().insert(message.id) {
+                    return;
+                }
+
+                let forward_to = {
+                    let topics = self.topics();
+                    let Some(state) = topics.get(&message.topic) else {
+                        return;
+                    };
+                    message.delivered_by = Some(from);
+                    let _ = state.sender.send(message.clone());
+                    state
+                        .neighbors
+                        .iter()
+                        .copied()
+                        .filter(|peer| *peer != from && *peer != message.origin)
+                        .collect::<Vec<_>>()
+                };
+
+                if message.hops < MAX_GOSSIP_HOPS && !forward_to.is_empty() {
+                    message.hops += 1;
+                    message.delivered_by = None;
+                    let gossip = self.clone();
+                    tokio::spawn(async move {
+                        gossip.fan_out(forward_to, message).await;
+                    });
+                }
+            }
+        }
+    }
+
+    /// Send a frame on the neighbor's stream, reopening it once if it broke
+    async fn send_frame(&self, peer: NodeId, frame: &GossipFrame) -> Result<(), P2PError> {
+        let data = serde_json::to_vec(frame)
+            .map_err(|e| P2PError::Stream(format!("Failed to encode gossip frame: {}", e)))?;
+
+        if self.write_frame(peer, &data).await.is_ok() {
+            return Ok(());
+        }
+        // The neighbor may have restarted; retry once on a fresh stream
+        self.write_frame(peer, &data).await
+    }
+
+    async fn write_frame(&self, peer: NodeId, data: &[u8]) -> Result<(), P2PError> {
+        let stream = self.outgoing_stream(peer).await?;
+        let result = stream.lock().await.send_message(data).await;
+        if result.is_err() {
+            self.inner.outgoing.lock().await.remove(&peer);
+        }
+        result
+    }
+
+    async fn outgoing_stream(
+        &self,
+        peer: NodeId,
+    ) -> Result<Arc<tokio::sync::Mutex<BiStream>>, P2PError> {
+        let mut outgoing = self.inner.outgoing.lock().await;
+        if let Some(stream) = outgoing.get(&peer) {
+            return Ok(stream.clone());
+        }
+        let stream = open_tunnel(&self.inner.manager, peer, GOSSIP_SERVICE).await?;
+        let stream = Arc::new(tokio::sync::Mutex::new(stream));
+        outgoing.insert(peer, stream.clone());
+        Ok(stream)
+    }
+
+    fn topics(&self) -> std::sync::MutexGuard<'_, HashMap<TopicId, TopicState>> {
+        self.inner
+            .topics
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn seen(&self) -> std::sync::MutexGuard<'_, SeenMessages> {
+        self.inner
+            .seen
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl StreamHandler for Gossip {
+    async fn handle(&self, peer_id: NodeId, mut stream: BiStream) -> Result<(), P2PError> {
+        loop {
+            let data = match stream.recv_message(MAX_FRAME_SIZE).await {
+                Ok(data) => data,
+                Err(e) => {
+                    tracing::debug!(peer_id = %peer_id, error = %e, "Gossip stream closed");
+                    return Ok(());
+                }
+            };
+            match serde_json::from_slice(&data) {
+                Ok(frame) => self.handle_frame(peer_id, frame).await,
+                Err(e) => {
+                    return Err(P2PError::Stream(format!("Invalid gossip frame: {}", e)));
+                }
+            }
+        }
+    }
+}
+
+fn not_joined(topic: TopicId) -> P2PError {
+    P2PError::Stream(format!("gossip topic {} is not joined", topic))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::SecretKey;
+
+    #[test]
+    fn topic_ids_are_stable_and_roundtrip() {
+        let topic = TopicId::from_name("presence");
+        assert_eq!(topic, TopicId::from_name("presence"));
+        assert_ne!(topic, TopicId::from_name("profiles"));
+        assert_eq!(topic.to_string().parse::<TopicId>().unwrap(), topic);
+        assert!("not-hex".parse::<TopicId>().is_err());
+    }
+
+    #[test]
+    fn frames_roundtrip_without_local_fields() {
+        let origin = SecretKey::generate(rand::rngs::OsRng).public();
+        let message = GossipMessage {
+            id: Uuid::new_v4(),
+            topic: TopicId::from_name("sync"),
+            origin,
+            delivered_by: Some(origin),
+            payload: b"hello".to_vec(),
+            hops: 2,
+        };
+
+        let json = serde_json::to_vec(&GossipFrame::Message(message.clone())).unwrap();
+        let GossipFrame::Message(decoded) = serde_json::from_slice(&json).unwrap() else {
+            panic!("wrong frame type");
+        };
+        assert_eq!(decoded.delivered_by, None);
+        assert_eq!(decoded.payload, message.payload);
+        assert_eq!(decoded.topic, message.topic);
+    }
+
+    #[test]
+    fn seen_messages_are_deduplicated_and_bounded() {
+        let mut seen = SeenMessages::default();
+        let first = Uuid::new_v4();
+        assert!(seen.insert(first));
+        assert!(!seen.insert(first));
+
+        for _ in 0..SEEN_CAPACITY {
+            seen.insert(Uuid::new_v4());
+        }
+        assert_eq!(seen.ids.len(), SEEN_CAPACITY);
+        assert!(seen.insert(first));
+    }
+}
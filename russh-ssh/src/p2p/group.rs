@@ -0,0 +1,546 @@
+//! Encrypted group channels
+//!
+//! A group channel gives a set of devices one encrypted conversation
+//! instead of N×N pairwise [`SecureStream`]s. Each member holds a random
+//! sender key per group and hands it to every other member over a pairwise
+//! secure stream; a message is then encrypted once with the sender's key
+//! and spread over a gossip topic derived from the group id.
+//!
+//! Removing a member rotates the local sender key and redistributes it to
+//! the remaining members, so the removed device cannot read later messages.
+//! Every member decides membership locally and must remove the peer too.
+//! Sender keys are shared by all members, so they authenticate a message
+//! as coming from the group, not from one particular member.
+//!
+//! # Requirements Coverage
+//! - Requirement 3.4: Multiplexed bidirectional streams
+//! - Requirement 4.1: End-to-end encryption between peers
+
+use crate::encryption::{decrypt_with_aad, encrypt_with_aad, CipherSuite, EncryptionKey};
+use crate::encryption::{SealedMessage, KEY_SIZE};
+use crate::error::P2PError;
+use crate::p2p::acl::PeerCapability;
+use crate::p2p::connection::P2PConnectionManager;
+use crate::p2p::gossip::{Gossip, GossipMessage, TopicId};
+use crate::p2p::secure::SecureStream;
+use crate::p2p::stream::BiStream;
+use crate::p2p::tunnel::{open_tunnel, StreamHandler, TunnelAgent};
+use async_trait::async_trait;
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use zeroize::Zeroize;
+
+/// Service name of the sender key exchange
+pub const GROUP_SERVICE: &str = "group";
+
+/// Largest sender key frame
+const MAX_KEY_FRAME_SIZE: usize = 4096;
+
+/// How many generations of a member's sender key are kept for late messages
+const KEPT_GENERATIONS: usize = 2;
+
+/// Cipher used for group messages
+const GROUP_CIPHER: CipherSuite = CipherSuite::ChaCha20Poly1305;
+
+/// A decrypted message from a group member
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupMessage {
+    /// The group the message was sent to
+    pub group: TopicId,
+    /// The member that sent it
+    pub sender: NodeId,
+    /// Decrypted payload
+    pub payload: Vec<u8>,
+}
+
+/// A member's sender key, sent over a pairwise secure stream
+#[derive(Serialize, Deserialize)]
+struct KeyFrame {
+    group: TopicId,
+    generation: u32,
+    /// Hex-encoded key bytes
+    key: String,
+}
+
+impl Drop for KeyFrame {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+/// A group message as published on the gossip topic
+#[derive(Debug, Serialize, Deserialize)]
+struct GroupEnvelope {
+    generation: u32,
+    sealed: SealedMessage,
+}
+
+/// Keys and membership of one joined group
+struct GroupState {
+    members: HashSet<NodeId>,
+    generation: u32,
+    key: EncryptionKey,
+    /// Sender keys of other members, by generation
+    peer_keys: HashMap<NodeId, BTreeMap<u32, EncryptionKey>>,
+}
+
+impl GroupState {
+    fn new(members: HashSet<NodeId>) -> Result<Self, P2PError> {
+        Ok(Self {
+            members,
+            generation: 0,
+            key: EncryptionKey::generate()?,
+            peer_keys: HashMap::new(),
+        })
+    }
+
+    /// Replace the local sender key with a fresh one
+    fn rotate(&mut self) -> Result<(), P2PError> {
+        self.key = EncryptionKey::generate()?;
+        self.generation = self.generation.wrapping_add(1);
+        Ok(())
+    }
+
+    fn key_frame(&self, group: TopicId) -> KeyFrame {
+        KeyFrame {
+            group,
+            generation: self.generation,
+            key: hex::encode(self.key.as_bytes()),
+        }
+    }
+
+    /// Store a member's sender key; returns false if the peer is not a member
+    fn accept_key(&mut self, peer: NodeId, frame: &KeyFrame) -> Result<bool, P2PError> {
+        if !self.members.contains(&peer) {
+            return Ok(false);
+        }
+        let mut bytes = [0u8; KEY_SIZE];
+        hex::decode_to_slice(&frame.key, &mut bytes)
+            .map_err(|e| P2PError::Stream(format!("Invalid sender key: {}", e)))?;
+        let key = EncryptionKey::from_bytes(bytes);
+        bytes.zeroize();
+
+        let keys = self.peer_keys.entry(peer).or_default();
+        keys.insert(frame.generation, key);
+        while keys.len() > KEPT_GENERATIONS {
+            keys.pop_first();
+        }
+        Ok(true)
+    }
+
+    fn remove_member(&mut self, peer: &NodeId) -> bool {
+        self.peer_keys.remove(peer);
+        self.members.remove(peer)
+    }
+
+    fn seal(
+        &self,
+        group: TopicId,
+        local: NodeId,
+        payload: &[u8],
+    ) -> Result<GroupEnvelope, P2PError> {
+        let aad = envelope_aad(group, local, self.generation);
+        Ok(GroupEnvelope {
+            generation: self.generation,
+            sealed: encrypt_with_aad(GROUP_CIPHER, &self.key, payload, &aad)?,
+        })
+    }
+
+    fn open(
+        &self,
+        group: TopicId,
+        sender: NodeId,
+        envelope: &GroupEnvelope,
+    ) -> Result<Vec<u8>, P2PError> {
+        let key = self
+            .peer_keys
+            .get(&sender)
+            .and_then(|keys| keys.get(&envelope.generation))
+            .ok_or_else(|| {
+                P2PError::Stream(format!(
+                    "no sender key generation {} for {}",
+                    envelope.generation, sender
+                ))
+            })?;
+        let aad = envelope_aad(group, sender, envelope.generation);
+        Ok(decrypt_with_aad(GROUP_CIPHER, key, &envelope.sealed, &aad)?)
+    }
+}
+
+/// Associated data binding a message to its group, sender and key generation
+fn envelope_aad(group: TopicId, sender: NodeId, generation: u32) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(68);
+    aad.extend_from_slice(group.as_bytes());
+    aad.extend_from_slice(sender.as_bytes());
+    aad.extend_from_slice(&generation.to_be_bytes());
+    aad
+}
+
+/// Gossip topic that carries a group's encrypted messages
+fn gossip_topic(group: TopicId) -> TopicId {
+    TopicId::from_bytes(blake3::derive_key(
+        "russh group channel v1",
+        group.as_bytes(),
+    ))
+}
+
+struct GroupsInner {
+    manager: Arc<P2PConnectionManager>,
+    gossip: Gossip,
+    groups: Mutex<HashMap<TopicId, GroupState>>,
+}
+
+/// Encrypted channels shared by groups of peers
+///
+/// Cheap to clone; all clones share the same groups. Register it with a
+/// [`TunnelAgent`] (see [`GroupChannels::register`]) together with the
+/// [`Gossip`] bus it publishes on.
+#[derive(Clone)]
+pub struct GroupChannels {
+    inner: Arc<GroupsInner>,
+}
+
+impl GroupChannels {
+    /// Create group channels on top of a gossip bus
+    pub fn new(manager: Arc<P2PConnectionManager>, gossip: Gossip) -> Self {
+        Self {
+            inner: Arc::new(GroupsInner {
+                manager,
+                gossip,
+                groups: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Exchange sender keys with peers with the
+    /// [`PeerCapability::Messaging`] capability
+    pub fn register(&self, agent: TunnelAgent) -> TunnelAgent {
+        agent.with_handler(
+            GROUP_SERVICE,
+            PeerCapability::Messaging,
+            Arc::new(self.clone()),
+        )
+    }
+
+    /// Join a group with its other members
+    ///
+    /// Sends the local sender key to every reachable member and collects
+    /// theirs. Members that are offline receive it when they join.
+    pub async fn join(
+        &self,
+        group: TopicId,
+        members: impl IntoIterator<Item = NodeId>,
+    ) -> Result<GroupChannel, P2PError> {
+        let local = self.inner.manager.local_node_id();
+        let members: HashSet<NodeId> = members.into_iter().filter(|m| *m != local).collect();
+
+        {
+            let mut groups = self.groups();
+            match groups.get_mut(&group) {
+                Some(state) => state.members.extend(members.iter().copied()),
+                None => {
+                    groups.insert(group, GroupState::new(members.clone())?);
+                }
+            }
+        }
+
+        for member in &members {
+            if let Err(e) = self.exchange_key(group, *member).await {
+                tracing::debug!(peer_id = %member, group = %group, error = %e, "Sender key exchange failed");
+            }
+        }
+
+        let receiver = self
+            .inner
+            .gossip
+            .join(gossip_topic(group), members.iter().copied())
+            .await;
+        Ok(GroupChannel {
+            group,
+            channels: self.clone(),
+            receiver,
+        })
+    }
+
+    /// Add a member to a joined group and exchange sender keys with it
+    pub async fn add_member(&self, group: TopicId, peer: NodeId) -> Result<(), P2PError> {
+        self.groups()
+            .get_mut(&group)
+            .ok_or_else(|| not_joined(group))?
+            .members
+            .insert(peer);
+
+        self.exchange_key(group, peer).await?;
+        self.inner.gossip.add_peer(gossip_topic(group), peer).await
+    }
+
+    /// Remove a member and rotate the local sender key
+    ///
+    /// The new key is sent to the remaining members only. Returns the
+    /// number of members that received it.
+    pub async fn remove_member(&self, group: TopicId, peer: NodeId) -> Result<usize, P2PError> {
+        let remaining = {
+            let mut groups = self.groups();
+            let state = groups.get_mut(&group).ok_or_else(|| not_joined(group))?;
+            if !state.remove_member(&peer) {
+                return Ok(0);
+            }
+            state.rotate()?;
+            state.members.iter().copied().collect::<Vec<_>>()
+        };
+        self.inner
+            .gossip
+            .remove_peer(gossip_topic(group), peer)
+            .await;
+
+        let mut delivered = 0;
+        for member in remaining {
+            match self.exchange_key(group, member).await {
+                Ok(()) => delivered += 1,
+                Err(e) => {
+                    tracing::debug!(peer_id = %member, group = %group, error = %e, "Failed to send rotated sender key");
+                }
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// Leave a group and forget its keys
+    pub async fn leave(&self, group: TopicId) {
+        if self.groups().remove(&group).is_some() {
+            self.inner.gossip.leave(gossip_topic(group)).await;
+        }
+    }
+
+    /// Groups joined locally
+    pub fn groups_joined(&self) -> Vec<TopicId> {
+        self.groups().keys().copied().collect()
+    }
+
+    /// Members of a joined group, excluding the local node
+    pub fn members(&self, group: &TopicId) -> Vec<NodeId> {
+        self.groups()
+            .get(group)
+            .map(|state| state.members.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Encrypt a payload once and publish it to the group
+    ///
+    /// Returns the number of neighbors the message was handed to.
+    pub async fn send(&self, group: TopicId, payload: &[u8]) -> Result<usize, P2PError> {
+        let local = self.inner.manager.local_node_id();
+        let envelope = self
+            .groups()
+            .get(&group)
+            .ok_or_else(|| not_joined(group))?
+            .seal(group, local, payload)?;
+        let data = serde_json::to_vec(&envelope)
+            .map_err(|e| P2PError::Stream(format!("Failed to encode group message: {}", e)))?;
+        self.inner.gossip.publish(gossip_topic(group), data).await
+    }
+
+    /// Decrypt a gossip message received on a group's topic
+    fn open(&self, group: TopicId, message: &GossipMessage) -> Result<GroupMessage, P2PError> {
+        let envelope: GroupEnvelope = serde_json::from_slice(&message.payload)
+            .map_err(|e| P2PError::Stream(format!("Invalid group message: {}", e)))?;
+        let payload = self
+            .groups()
+            .get(&group)
+            .ok_or_else(|| not_joined(group))?
+            .open(group, message.origin, &envelope)?;
+        Ok(GroupMessage {
+            group,
+            sender: message.origin,
+            payload,
+        })
+    }
+
+    /// Send our sender key to a member and store the one it sends back
+    async fn exchange_key(&self, group: TopicId, peer: NodeId) -> Result<(), P2PError> {
+        let frame = self
+            .groups()
+            .get(&group)
+            .ok_or_else(|| not_joined(group))?
+            .key_frame(group);
+
+        let stream = open_tunnel(&self.inner.manager, peer, GROUP_SERVICE).await?;
+        let mut secure = SecureStream::initiate(stream).await?;
+        secure.send_json(&frame).await?;
+        let reply: Option<KeyFrame> = secure.recv_json(MAX_KEY_FRAME_SIZE).await?;
+        let _ = secure.finish().await;
+
+        let reply = reply
+            .ok_or_else(|| P2PError::Unauthorized(format!("{} is not in group {}", peer, group)))?;
+        if reply.group != group {
+            return Err(P2PError::Stream(format!(
+                "sender key for wrong group {}",
+                reply.group
+            )));
+        }
+        if let Some(state) = self.groups().get_mut(&group) {
+            state.accept_key(peer, &reply)?;
+        }
+        Ok(())
+    }
+
+    fn groups(&self) -> std::sync::MutexGuard<'_, HashMap<TopicId, GroupState>> {
+        self.inner
+            .groups
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl StreamHandler for GroupChannels {
+    async fn handle(&self, peer_id: NodeId, stream: BiStream) -> Result<(), P2PError> {
+        let mut secure = SecureStream::accept(stream).await?;
+        let frame: KeyFrame = secure.recv_json(MAX_KEY_FRAME_SIZE).await?;
+
+        let reply = {
+            let mut groups = self.groups();
+            match groups.get_mut(&frame.group) {
+                Some(state) => state
+                    .accept_key(peer_id, &frame)?
+                    .then(|| state.key_frame(frame.group)),
+                None => None,
+            }
+        };
+        if reply.is_none() {
+            tracing::debug!(peer_id = %peer_id, group = %frame.group, "Rejected sender key from non-member");
+        }
+
+        secure.send_json(&reply).await?;
+        secure.finish().await
+    }
+}
+
+/// Handle to a joined group
+pub struct GroupChannel {
+    group: TopicId,
+    channels: GroupChannels,
+    receiver: broadcast::Receiver<GossipMessage>,
+}
+
+impl GroupChannel {
+    /// The group's id
+    pub fn group(&self) -> TopicId {
+        self.group
+    }
+
+    /// Members of the group, excluding the local node
+    pub fn members(&self) -> Vec<NodeId> {
+        self.channels.members(&self.group)
+    }
+
+    /// Encrypt a payload once and publish it to the group
+    pub async fn send(&self, payload: &[u8]) -> Result<usize, P2PError> {
+        self.channels.send(self.group, payload).await
+    }
+
+    /// Receive the next message from a member
+    ///
+    /// Messages that cannot be decrypted, such as those from removed members
+    /// or under an unknown key, are skipped.
+    pub async fn recv(&mut self) -> Result<GroupMessage, P2PError> {
+        loop {
+            let message = match self.receiver.recv().await {
+                Ok(message) => message,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!(group = %self.group, skipped, "Group channel lagged");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    return Err(not_joined(self.group));
+                }
+            };
+            match self.channels.open(self.group, &message) {
+                Ok(message) => return Ok(message),
+                Err(e) => {
+                    tracing::debug!(group = %self.group, sender = %message.origin, error = %e, "Dropped group message");
+                }
+            }
+        }
+    }
+}
+
+fn not_joined(group: TopicId) -> P2PError {
+    P2PError::Stream(format!("group {} is not joined", group))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::SecretKey;
+
+    fn node_id() -> NodeId {
+        SecretKey::generate(rand::rngs::OsRng).public()
+    }
+
+    #[test]
+    fn members_decrypt_with_exchanged_sender_keys() {
+        let group = TopicId::from_name("living-room");
+        let (alice, bob, carol) = (node_id(), node_id(), node_id());
+        let alice_state = GroupState::new([bob, carol].into()).unwrap();
+        let mut bob_state = GroupState::new([alice, carol].into()).unwrap();
+
+        assert!(bob_state
+            .accept_key(alice, &alice_state.key_frame(group))
+            .unwrap());
+        let envelope = alice_state.seal(group, alice, b"play").unwrap();
+        assert_eq!(bob_state.open(group, alice, &envelope).unwrap(), b"play");
+
+        // A claimed sender or group other than the real one fails
+        assert!(bob_state.open(group, carol, &envelope).is_err());
+        assert!(bob_state
+            .open(TopicId::from_name("kitchen"), alice, &envelope)
+            .is_err());
+    }
+
+    #[test]
+    fn non_members_cannot_hand_out_keys() {
+        let group = TopicId::from_name("sync");
+        let (alice, stranger) = (node_id(), node_id());
+        let mut state = GroupState::new([alice].into()).unwrap();
+        let other = GroupState::new(HashSet::new()).unwrap();
+
+        assert!(!state.accept_key(stranger, &other.key_frame(group)).unwrap());
+        assert!(state.peer_keys.is_empty());
+    }
+
+    #[test]
+    fn rotation_locks_out_removed_members() {
+        let group = TopicId::from_name("room");
+        let (alice, bob, mallory) = (node_id(), node_id(), node_id());
+        let mut alice_state = GroupState::new([bob, mallory].into()).unwrap();
+        let mut bob_state = GroupState::new([alice].into()).unwrap();
+        let mut mallory_state = GroupState::new([alice].into()).unwrap();
+        let frame = alice_state.key_frame(group);
+        bob_state.accept_key(alice, &frame).unwrap();
+        mallory_state.accept_key(alice, &frame).unwrap();
+
+        assert!(alice_state.remove_member(&mallory));
+        alice_state.rotate().unwrap();
+        bob_state
+            .accept_key(alice, &alice_state.key_frame(group))
+            .unwrap();
+
+        let envelope = alice_state.seal(group, alice, b"secret").unwrap();
+        assert_eq!(envelope.generation, 1);
+        assert_eq!(bob_state.open(group, alice, &envelope).unwrap(), b"secret");
+        assert!(mallory_state.open(group, alice, &envelope).is_err());
+
+        // Old generations are pruned as new keys arrive
+        for _ in 0..3 {
+            alice_state.rotate().unwrap();
+            bob_state
+                .accept_key(alice, &alice_state.key_frame(group))
+                .unwrap();
+        }
+        assert_eq!(bob_state.peer_keys[&alice].len(), KEPT_GENERATIONS);
+    }
+}
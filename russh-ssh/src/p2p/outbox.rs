@@ -0,0 +1,724 @@
+//! Offline message queue
+//!
+//! The outbox holds messages for peers that cannot be reached right now and
+//! delivers them, in order, once [`Presence`] reports the peer online. The
+//! queue is bounded per peer and in total size, expires old messages, and
+//! is encrypted as a whole before it is written to disk.
+//!
+//! Messages are delivered to the peer's `outbox` service, which
+//! acknowledges each one and publishes it to local subscribers by topic.
+//! Redelivery after a lost acknowledgement is filtered by message id.
+//!
+//! # Requirements Coverage
+//! - Requirement 3.4: Multiplexed bidirectional streams
+//! - Requirement 4.1: End-to-end encryption between peers
+
+use crate::encryption::{decrypt_with_aad, encrypt_with_aad, CipherSuite, EncryptionKey};
+use crate::encryption::{SealedMessage, KEY_SIZE};
+use crate::error::P2PError;
+use crate::p2p::acl::PeerCapability;
+use crate::p2p::connection::P2PConnectionManager;
+use crate::p2p::presence::{Presence, PresenceState};
+use crate::p2p::stream::{BiStream, StreamExt};
+use crate::p2p::tunnel::{open_tunnel, StreamHandler, TunnelAgent};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+use zeroize::Zeroize;
+
+/// Service name of the delivery protocol
+pub const OUTBOX_SERVICE: &str = "outbox";
+
+/// File name of the queue inside the configuration directory
+pub const OUTBOX_FILE_NAME: &str = "outbox.sealed";
+
+/// File name of the queue's encryption key inside the configuration directory
+pub const OUTBOX_KEY_FILE_NAME: &str = "outbox.key";
+
+/// Largest message payload
+pub const MAX_OUTBOX_PAYLOAD: usize = 1024 * 1024;
+
+/// Default number of queued messages per peer
+pub const DEFAULT_MAX_QUEUED_PER_PEER: usize = 256;
+
+/// Default total size of queued payloads
+pub const DEFAULT_MAX_QUEUED_BYTES: usize = 32 * 1024 * 1024;
+
+/// Default age after which undelivered messages are dropped
+pub const DEFAULT_MAX_MESSAGE_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Largest delivery header frame
+const MAX_HEADER_SIZE: usize = 4096;
+
+/// Capacity of the inbox subscriber channel
+const INBOX_CAPACITY: usize = 256;
+
+/// How many delivered message ids are remembered for deduplication
+const SEEN_CAPACITY: usize = 1024;
+
+/// Associated data binding the sealed file to its purpose
+const OUTBOX_AAD: &[u8] = b"russh outbox v1";
+
+/// A message waiting for its peer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    /// Message identifier, used to drop duplicate deliveries
+    pub id: Uuid,
+    /// Destination peer
+    pub peer_id: NodeId,
+    /// Topic receivers filter on (e.g. "vdfs-sync")
+    pub topic: String,
+    /// Message body
+    #[serde(with = "base64_bytes")]
+    pub payload: Vec<u8>,
+    /// When the message was queued
+    pub queued_at: DateTime<Utc>,
+}
+
+/// A message delivered to us
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InboxMessage {
+    /// Message identifier
+    pub id: Uuid,
+    /// Peer that sent it
+    pub from: NodeId,
+    /// Topic it was sent on
+    pub topic: String,
+    /// Message body
+    pub payload: Vec<u8>,
+    /// When the sender queued it
+    pub queued_at: DateTime<Utc>,
+}
+
+/// Outcome of [`Outbox::send`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// The peer acknowledged the message
+    Delivered,
+    /// The peer was unreachable; the message waits in the outbox
+    Queued(Uuid),
+}
+
+/// Delivery header sent ahead of the payload
+#[derive(Debug, Serialize, Deserialize)]
+struct DeliveryHeader {
+    id: Uuid,
+    topic: String,
+    queued_at: DateTime<Utc>,
+}
+
+/// Queue bounds
+#[derive(Debug, Clone, Copy)]
+struct OutboxLimits {
+    per_peer: usize,
+    total_bytes: usize,
+    max_age: Duration,
+}
+
+/// Pending messages in queue order
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OutboxQueue {
+    #[serde(default)]
+    messages: Vec<QueuedMessage>,
+}
+
+impl OutboxQueue {
+    fn total_bytes(&self) -> usize {
+        self.messages.iter().map(|m| m.payload.len()).sum()
+    }
+
+    /// Append a message unless it would exceed the limits
+    fn push(&mut self, message: QueuedMessage, limits: &OutboxLimits) -> Result<(), P2PError> {
+        let queued_for_peer = self
+            .messages
+            .iter()
+            .filter(|m| m.peer_id == message.peer_id)
+            .count();
+        if queued_for_peer >= limits.per_peer {
+            return Err(P2PError::Storage(format!(
+                "outbox for {} is full ({} messages)",
+                message.peer_id.fmt_short(),
+                limits.per_peer
+            )));
+        }
+        if self.total_bytes() + message.payload.len() > limits.total_bytes {
+            return Err(P2PError::Storage(format!(
+                "outbox is full ({} bytes)",
+                limits.total_bytes
+            )));
+        }
+        self.messages.push(message);
+        Ok(())
+    }
+
+    /// Drop messages older than `max_age`; returns how many were dropped
+    fn expire(&mut self, max_age: Duration, now: DateTime<Utc>) -> usize {
+        let max_age = chrono::Duration::from_std(max_age).unwrap_or(chrono::Duration::MAX);
+        let before = self.messages.len();
+        self.messages.retain(|m| now - m.queued_at < max_age);
+        before - self.messages.len()
+    }
+
+    fn for_peer(&self, peer_id: &NodeId) -> Vec<QueuedMessage> {
+        self.messages
+            .iter()
+            .filter(|m| m.peer_id == *peer_id)
+            .cloned()
+            .collect()
+    }
+
+    fn remove(&mut self, id: &Uuid) -> bool {
+        let before = self.messages.len();
+        self.messages.retain(|m| m.id != *id);
+        before != self.messages.len()
+    }
+}
+
+struct OutboxInner {
+    manager: Arc<P2PConnectionManager>,
+    path: Option<PathBuf>,
+    key: EncryptionKey,
+    limits: OutboxLimits,
+    queue: Mutex<OutboxQueue>,
+    inbox: broadcast::Sender<InboxMessage>,
+    seen: Mutex<VecDeque<Uuid>>,
+    presence: Mutex<Option<Presence>>,
+    /// Serializes flushes so messages to a peer stay in order
+    flushing: tokio::sync::Mutex<()>,
+}
+
+/// Queues messages for unreachable peers and receives queued messages
+///
+/// Cheap to clone; all clones share the same queue. Register it with a
+/// [`TunnelAgent`] (see [`Outbox::register`]) to receive messages.
+#[derive(Clone)]
+pub struct Outbox {
+    inner: Arc<OutboxInner>,
+}
+
+impl Outbox {
+    /// Create an in-memory outbox with default limits
+    pub fn new(manager: Arc<P2PConnectionManager>) -> Result<Self, P2PError> {
+        Ok(Self::build(
+            manager,
+            None,
+            EncryptionKey::generate()?,
+            OutboxQueue::default(),
+            default_limits(),
+        ))
+    }
+
+    /// Open the sealed queue at `path`, starting empty if it does not exist
+    pub fn open(
+        manager: Arc<P2PConnectionManager>,
+        path: &Path,
+        key: EncryptionKey,
+    ) -> Result<Self, P2PError> {
+        Self::open_with_limits(
+            manager,
+            path,
+            key,
+            DEFAULT_MAX_QUEUED_PER_PEER,
+            DEFAULT_MAX_QUEUED_BYTES,
+            DEFAULT_MAX_MESSAGE_AGE,
+        )
+    }
+
+    /// Open the sealed queue at `path` with custom bounds
+    pub fn open_with_limits(
+        manager: Arc<P2PConnectionManager>,
+        path: &Path,
+        key: EncryptionKey,
+        per_peer: usize,
+        total_bytes: usize,
+        max_age: Duration,
+    ) -> Result<Self, P2PError> {
+        let mut queue = if path.exists() {
+            let sealed = std::fs::read(path).map_err(|e| {
+                P2PError::Storage(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+            unseal_queue(&key, &sealed).map_err(|e| {
+                P2PError::Storage(format!("Invalid outbox {}: {}", path.display(), e))
+            })?
+        } else {
+            OutboxQueue::default()
+        };
+        let limits = OutboxLimits {
+            per_peer,
+            total_bytes,
+            max_age,
+        };
+        let expired = queue.expire(max_age, Utc::now());
+        if expired > 0 {
+            tracing::info!(expired, "Dropped expired outbox messages");
+        }
+
+        Ok(Self::build(
+            manager,
+            Some(path.to_path_buf()),
+            key,
+            queue,
+            limits,
+        ))
+    }
+
+    fn build(
+        manager: Arc<P2PConnectionManager>,
+        path: Option<PathBuf>,
+        key: EncryptionKey,
+        queue: OutboxQueue,
+        limits: OutboxLimits,
+    ) -> Self {
+        Self {
+            inner: Arc::new(OutboxInner {
+                manager,
+                path,
+                key,
+                limits,
+                queue: Mutex::new(queue),
+                inbox: broadcast::channel(INBOX_CAPACITY).0,
+                seen: Mutex::new(VecDeque::new()),
+                presence: Mutex::new(None),
+                flushing: tokio::sync::Mutex::new(()),
+            }),
+        }
+    }
+
+    /// Default location of the sealed queue
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("russh").join(OUTBOX_FILE_NAME))
+    }
+
+    /// Default location of the queue's encryption key
+    pub fn default_key_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("russh").join(OUTBOX_KEY_FILE_NAME))
+    }
+
+    /// Load the queue key at `path`, generating and saving one if absent
+    ///
+    /// The key file is readable by the owner only.
+    pub fn load_or_create_key(path: &Path) -> Result<EncryptionKey, P2PError> {
+        let storage_error =
+            |e: std::io::Error| P2PError::Storage(format!("{}: {}", path.display(), e));
+
+        if path.exists() {
+            let mut bytes = std::fs::read(path).map_err(storage_error)?;
+            let key = <[u8; KEY_SIZE]>::try_from(bytes.as_slice())
+                .map(EncryptionKey::from_bytes)
+                .map_err(|_| {
+                    P2PError::Storage(format!("{}: not a {}-byte key", path.display(), KEY_SIZE))
+                });
+            bytes.zeroize();
+            return key;
+        }
+
+        let key = EncryptionKey::generate()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(storage_error)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600); // rw-------
+        }
+        let mut file = options.open(path).map_err(storage_error)?;
+        file.write_all(key.as_bytes()).map_err(storage_error)?;
+        file.sync_all().map_err(storage_error)?;
+        Ok(key)
+    }
+
+    /// Accept messages from peers with the [`PeerCapability::Messaging`]
+    /// capability
+    pub fn register(&self, agent: TunnelAgent) -> TunnelAgent {
+        agent.with_handler(
+            OUTBOX_SERVICE,
+            PeerCapability::Messaging,
+            Arc::new(self.clone()),
+        )
+    }
+
+    /// Subscribe to messages delivered to us
+    pub fn subscribe(&self) -> broadcast::Receiver<InboxMessage> {
+        self.inner.inbox.subscribe()
+    }
+
+    /// Deliver a message now, or queue it if the peer is unreachable
+    ///
+    /// Messages already waiting for the peer are flushed first so delivery
+    /// order is preserved.
+    pub async fn send(
+        &self,
+        peer_id: NodeId,
+        topic: &str,
+        payload: Vec<u8>,
+    ) -> Result<Delivery, P2PError> {
+        let id = self.enqueue(peer_id, topic, payload)?;
+        self.flush(peer_id).await?;
+
+        if self.pending(&peer_id).iter().any(|m| m.id == id) {
+            Ok(Delivery::Queued(id))
+        } else {
+            Ok(Delivery::Delivered)
+        }
+    }
+
+    /// Queue a message without trying to deliver it
+    pub fn enqueue(
+        &self,
+        peer_id: NodeId,
+        topic: &str,
+        payload: Vec<u8>,
+    ) -> Result<Uuid, P2PError> {
+        if payload.len() > MAX_OUTBOX_PAYLOAD {
+            return Err(P2PError::Stream(format!(
+                "outbox payload too large: {} > {}",
+                payload.len(),
+                MAX_OUTBOX_PAYLOAD
+            )));
+        }
+        let message = QueuedMessage {
+            id: Uuid::new_v4(),
+            peer_id,
+            topic: topic.to_string(),
+            payload,
+            queued_at: Utc::now(),
+        };
+        let id = message.id;
+        self.queue().push(message, &self.inner.limits)?;
+        self.save()?;
+
+        if let Some(presence) = self.presence() {
+            presence.watch(peer_id);
+        }
+        Ok(id)
+    }
+
+    /// Messages waiting for a peer, oldest first
+    pub fn pending(&self, peer_id: &NodeId) -> Vec<QueuedMessage> {
+        self.queue().for_peer(peer_id)
+    }
+
+    /// Number of messages waiting for any peer
+    pub fn len(&self) -> usize {
+        self.queue().messages.len()
+    }
+
+    /// Whether no messages are waiting
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove a queued message without delivering it
+    pub fn discard(&self, id: &Uuid) -> Result<bool, P2PError> {
+        let removed = self.queue().remove(id);
+        if removed {
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// Deliver queued messages to a peer in order
+    ///
+    /// Stops at the first message the peer does not acknowledge, leaving it
+    /// and later messages queued. Returns the number delivered.
+    pub async fn flush(&self, peer_id: NodeId) -> Result<usize, P2PError> {
+        let _flushing = self.inner.flushing.lock().await;
+        let expired = self.queue().expire(self.inner.limits.max_age, Utc::now());
+        if expired > 0 {
+            tracing::info!(expired, "Dropped expired outbox messages");
+            self.save()?;
+        }
+
+        let mut delivered = 0;
+        for message in self.pending(&peer_id) {
+            if let Err(e) = self.deliver(&message).await {
+                tracing::debug!(peer_id = %peer_id, error = %e, "Outbox delivery failed, keeping message queued");
+                break;
+            }
+            self.queue().remove(&message.id);
+            self.save()?;
+            delivered += 1;
+        }
+        if delivered > 0 {
+            tracing::info!(peer_id = %peer_id, delivered, "Flushed outbox");
+        }
+        Ok(delivered)
+    }
+
+    /// Flush a peer's messages whenever presence reports it online
+    ///
+    /// Also watches every peer that already has queued messages, and every
+    /// peer a message is queued for later. The task runs until aborted.
+    pub fn watch_presence(&self, presence: Presence) -> JoinHandle<()> {
+        let mut events = presence.subscribe();
+        let peers: Vec<NodeId> = {
+            let queue = self.queue();
+            queue.messages.iter().map(|m| m.peer_id).collect()
+        };
+        for peer_id in peers {
+            presence.watch(peer_id);
+        }
+        *self
+            .inner
+            .presence
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(presence);
+
+        let outbox = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) if event.current == PresenceState::Online => {
+                        if let Err(e) = outbox.flush(event.node_id).await {
+                            tracing::warn!(peer_id = %event.node_id, error = %e, "Failed to flush outbox");
+                        }
+                    }
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+
+    /// Send one message and wait for the peer's acknowledgement
+    async fn deliver(&self, message: &QueuedMessage) -> Result<(), P2PError> {
+        let header = DeliveryHeader {
+            id: message.id,
+            topic: message.topic.clone(),
+            queued_at: message.queued_at,
+        };
+        let header = serde_json::to_vec(&header)
+            .map_err(|e| P2PError::Stream(format!("Failed to encode outbox header: {}", e)))?;
+
+        let mut stream = open_tunnel(&self.inner.manager, message.peer_id, OUTBOX_SERVICE).await?;
+        stream.send_message(&header).await?;
+        stream.send_message(&message.payload).await?;
+        stream.finish().await?;
+
+        let mut ack = [0u8; 1];
+        stream.read_exact(&mut ack).await
+    }
+
+    /// Write the sealed queue to its file, if it has one
+    fn save(&self) -> Result<(), P2PError> {
+        let Some(path) = &self.inner.path else {
+            return Ok(());
+        };
+        let storage_error =
+            |e: std::io::Error| P2PError::Storage(format!("{}: {}", path.display(), e));
+
+        let sealed = seal_queue(&self.inner.key, &self.queue())?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(storage_error)?;
+        }
+
+        let tmp_path = path.with_extension("sealed.tmp");
+        let mut file = std::fs::File::create(&tmp_path).map_err(storage_error)?;
+        file.write_all(&sealed).map_err(storage_error)?;
+        file.sync_all().map_err(storage_error)?;
+        std::fs::rename(&tmp_path, path).map_err(storage_error)
+    }
+
+    /// Remember a delivered id; returns false if it was seen before
+    fn first_delivery(&self, id: Uuid) -> bool {
+        let mut seen = self
+            .inner
+            .seen
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if seen.contains(&id) {
+            return false;
+        }
+        if seen.len() == SEEN_CAPACITY {
+            seen.pop_front();
+        }
+        seen.push_back(id);
+        true
+    }
+
+    fn queue(&self) -> std::sync::MutexGuard<'_, OutboxQueue> {
+        self.inner
+            .queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn presence(&self) -> Option<Presence> {
+        self.inner
+            .presence
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+#[async_trait]
+impl StreamHandler for Outbox {
+    async fn handle(&self, peer_id: NodeId, mut stream: BiStream) -> Result<(), P2PError> {
+        let header = stream.recv_message(MAX_HEADER_SIZE).await?;
+        let header: DeliveryHeader = serde_json::from_slice(&header)
+            .map_err(|e| P2PError::Stream(format!("Invalid outbox header: {}", e)))?;
+        let payload = stream.recv_message(MAX_OUTBOX_PAYLOAD).await?;
+
+        if self.first_delivery(header.id) {
+            let _ = self.inner.inbox.send(InboxMessage {
+                id: header.id,
+                from: peer_id,
+                topic: header.topic,
+                payload,
+                queued_at: header.queued_at,
+            });
+        }
+        stream.write_and_finish(&[0]).await
+    }
+}
+
+fn default_limits() -> OutboxLimits {
+    OutboxLimits {
+        per_peer: DEFAULT_MAX_QUEUED_PER_PEER,
+        total_bytes: DEFAULT_MAX_QUEUED_BYTES,
+        max_age: DEFAULT_MAX_MESSAGE_AGE,
+    }
+}
+
+fn seal_queue(key: &EncryptionKey, queue: &OutboxQueue) -> Result<Vec<u8>, P2PError> {
+    let mut json = serde_json::to_vec(queue)
+        .map_err(|e| P2PError::Storage(format!("Failed to encode outbox: {}", e)))?;
+    let sealed = encrypt_with_aad(CipherSuite::preferred(), key, &json, OUTBOX_AAD);
+    json.zeroize();
+    let sealed = SealedQueue {
+        cipher_suite: CipherSuite::preferred(),
+        sealed: sealed?,
+    };
+    serde_json::to_vec(&sealed)
+        .map_err(|e| P2PError::Storage(format!("Failed to encode outbox: {}", e)))
+}
+
+fn unseal_queue(key: &EncryptionKey, bytes: &[u8]) -> Result<OutboxQueue, P2PError> {
+    let sealed: SealedQueue = serde_json::from_slice(bytes)
+        .map_err(|e| P2PError::Storage(format!("not a sealed outbox: {}", e)))?;
+    let mut json = decrypt_with_aad(sealed.cipher_suite, key, &sealed.sealed, OUTBOX_AAD)?;
+    let queue = serde_json::from_slice(&json)
+        .map_err(|e| P2PError::Storage(format!("corrupt outbox: {}", e)));
+    json.zeroize();
+    queue
+}
+
+/// On-disk form of the queue
+#[derive(Serialize, Deserialize)]
+struct SealedQueue {
+    cipher_suite: CipherSuite,
+    sealed: SealedMessage,
+}
+
+/// Serialize payload bytes as base64 rather than a JSON number array
+mod base64_bytes {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::SecretKey;
+
+    fn node_id() -> NodeId {
+        SecretKey::generate(rand::rngs::OsRng).public()
+    }
+
+    fn message(peer_id: NodeId, payload: &[u8]) -> QueuedMessage {
+        QueuedMessage {
+            id: Uuid::new_v4(),
+            peer_id,
+            topic: "vdfs-sync".to_string(),
+            payload: payload.to_vec(),
+            queued_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn queue_is_bounded_per_peer_and_in_size() {
+        let limits = OutboxLimits {
+            per_peer: 2,
+            total_bytes: 10,
+            max_age: DEFAULT_MAX_MESSAGE_AGE,
+        };
+        let (a, b) = (node_id(), node_id());
+        let mut queue = OutboxQueue::default();
+
+        queue.push(message(a, b"1234"), &limits).unwrap();
+        queue.push(message(a, b"1234"), &limits).unwrap();
+        assert!(queue.push(message(a, b"1"), &limits).is_err());
+        assert!(queue.push(message(b, b"123"), &limits).is_err());
+        queue.push(message(b, b"12"), &limits).unwrap();
+
+        assert_eq!(queue.for_peer(&a).len(), 2);
+        assert_eq!(queue.total_bytes(), 10);
+    }
+
+    #[test]
+    fn old_messages_expire() {
+        let peer = node_id();
+        let mut queue = OutboxQueue::default();
+        let mut old = message(peer, b"stale");
+        old.queued_at = Utc::now() - chrono::Duration::days(8);
+        queue.messages.push(old);
+        queue.messages.push(message(peer, b"fresh"));
+
+        assert_eq!(queue.expire(DEFAULT_MAX_MESSAGE_AGE, Utc::now()), 1);
+        assert_eq!(queue.messages[0].payload, b"fresh");
+    }
+
+    #[test]
+    fn queue_is_sealed_at_rest() {
+        let key = EncryptionKey::generate().unwrap();
+        let mut queue = OutboxQueue::default();
+        queue.messages.push(message(node_id(), b"meet at noon"));
+
+        let sealed = seal_queue(&key, &queue).unwrap();
+        assert!(!String::from_utf8_lossy(&sealed).contains("vdfs-sync"));
+
+        let opened = unseal_queue(&key, &sealed).unwrap();
+        assert_eq!(opened.messages, queue.messages);
+        let other = EncryptionKey::generate().unwrap();
+        assert!(unseal_queue(&other, &sealed).is_err());
+    }
+
+    #[test]
+    fn queue_key_is_created_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(OUTBOX_KEY_FILE_NAME);
+
+        let key = Outbox::load_or_create_key(&path).unwrap();
+        let again = Outbox::load_or_create_key(&path).unwrap();
+        assert_eq!(key.as_bytes(), again.as_bytes());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
@@ -0,0 +1,636 @@
+//! Device pairing with one-time tickets
+//!
+//! The inviting device creates a [`PairingTicket`] holding its address and a
+//! random one-time secret, and shows it as a QR code or string. The joining
+//! device dials the inviter and both sides prove knowledge of the secret,
+//! bound to both NodeIds and fresh nonces, before either one adds the other
+//! to its access control list. A ticket can be redeemed once and expires.
+//!
+//! While tickets are outstanding the connection manager is put in pairing
+//! mode so that peers unknown to a default-deny ACL can dial in; they get
+//! no capabilities until pairing succeeds.
+//!
+//! # Requirements Coverage
+//! - Requirement 4.2: Mutual authentication between peers
+
+use crate::error::P2PError;
+use crate::p2p::acl::PeerCapability;
+use crate::p2p::connection::P2PConnectionManager;
+use crate::p2p::stream::{BiStream, StreamExt};
+use crate::p2p::ticket::PeerTicket;
+use crate::p2p::tunnel::{open_tunnel, StreamHandler, TunnelAgent};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use iroh::NodeId;
+use rand::RngCore;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use zeroize::Zeroize;
+
+/// Service name of the pairing handshake
+pub const PAIRING_SERVICE: &str = "pair";
+
+/// Prefix of encoded pairing tickets (includes the format version)
+pub const PAIRING_TICKET_PREFIX: &str = "russhpair1";
+
+/// Default lifetime of a pairing ticket
+pub const DEFAULT_PAIRING_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Largest handshake message
+const MAX_PAIRING_MESSAGE: usize = 4096;
+
+/// Capacity of the paired-peer event channel
+const PAIRING_EVENTS_CAPACITY: usize = 16;
+
+const INVITER_LABEL: &[u8] = b"russh-pair-inviter";
+const JOINER_LABEL: &[u8] = b"russh-pair-joiner";
+
+/// A one-time invitation to pair with a device
+#[derive(Clone, PartialEq, Eq)]
+pub struct PairingTicket {
+    /// Address of the inviting device
+    peer: PeerTicket,
+    /// One-time secret shared through the QR code
+    secret: [u8; 32],
+    /// When the ticket stops being accepted
+    expires_at: DateTime<Utc>,
+}
+
+impl PairingTicket {
+    /// Get the inviting device's address
+    pub fn peer(&self) -> &PeerTicket {
+        &self.peer
+    }
+
+    /// Get the inviting device's NodeId
+    pub fn node_id(&self) -> NodeId {
+        self.peer.node_id()
+    }
+
+    /// When the ticket expires
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.expires_at
+    }
+
+    /// Whether the ticket has expired
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.expires_at
+    }
+
+    /// Identifier of the ticket that does not reveal its secret
+    fn id(&self) -> String {
+        ticket_id(&self.secret)
+    }
+
+    /// Render as a QR code made of Unicode half blocks for terminals
+    pub fn to_qr_terminal(&self) -> Result<String, P2PError> {
+        render_qr_terminal(&self.to_string())
+    }
+
+    /// Render as an SVG QR code
+    pub fn to_qr_svg(&self) -> Result<String, P2PError> {
+        render_qr_svg(&self.to_string())
+    }
+}
+
+impl Drop for PairingTicket {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+impl fmt::Debug for PairingTicket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PairingTicket")
+            .field("peer", &self.peer)
+            .field("secret", &"[REDACTED]")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+impl fmt::Display for PairingTicket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bytes = Vec::with_capacity(64);
+        bytes.extend_from_slice(&self.secret);
+        bytes.extend_from_slice(&self.expires_at.timestamp().to_be_bytes());
+        bytes.extend_from_slice(&self.peer.to_bytes());
+        write!(
+            f,
+            "{}{}",
+            PAIRING_TICKET_PREFIX,
+            URL_SAFE_NO_PAD.encode(&bytes)
+        )?;
+        bytes.zeroize();
+        Ok(())
+    }
+}
+
+impl FromStr for PairingTicket {
+    type Err = P2PError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let encoded = s
+            .trim()
+            .strip_prefix(PAIRING_TICKET_PREFIX)
+            .ok_or_else(|| P2PError::InvalidTicket("not a pairing ticket".to_string()))?;
+        let mut bytes = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|e| P2PError::InvalidTicket(format!("bad encoding: {}", e)))?;
+        if bytes.len() < 40 {
+            bytes.zeroize();
+            return Err(P2PError::InvalidTicket(
+                "pairing ticket is truncated".to_string(),
+            ));
+        }
+
+        let mut secret = [0u8; 32];
+        secret.copy_from_slice(&bytes[..32]);
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(&bytes[32..40]);
+        let peer = PeerTicket::from_bytes(&bytes[40..]);
+        bytes.zeroize();
+
+        let expires_at = DateTime::from_timestamp(i64::from_be_bytes(timestamp), 0)
+            .ok_or_else(|| P2PError::InvalidTicket("bad expiry".to_string()))?;
+        Ok(Self {
+            peer: peer?,
+            secret,
+            expires_at,
+        })
+    }
+}
+
+/// A device that completed pairing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PairedPeer {
+    /// The device's NodeId
+    pub node_id: NodeId,
+    /// Name the device announced
+    pub name: Option<String>,
+    /// When pairing completed
+    pub paired_at: DateTime<Utc>,
+}
+
+/// Handshake messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum PairingMessage {
+    /// Joiner → inviter: which ticket is being redeemed
+    Hello {
+        ticket_id: String,
+        nonce: [u8; 32],
+        name: Option<String>,
+    },
+    /// Inviter → joiner: proof of the secret
+    Challenge {
+        nonce: [u8; 32],
+        proof: [u8; 32],
+        name: Option<String>,
+    },
+    /// Joiner → inviter: proof of the secret
+    Confirm { proof: [u8; 32] },
+    /// Inviter → joiner: pairing finished
+    Accepted,
+    /// Either side: pairing refused
+    Rejected { reason: String },
+}
+
+/// Creates pairing tickets and runs the pairing handshake
+///
+/// Register it with a [`TunnelAgent`] (see [`Pairing::register`]) to let
+/// devices redeem tickets created here. Paired devices are added to the
+/// connection manager's access control list, if it has one, with the
+/// configured capabilities.
+#[derive(Clone)]
+pub struct Pairing {
+    manager: Arc<P2PConnectionManager>,
+    capabilities: Vec<PeerCapability>,
+    name: Option<String>,
+    pending: Arc<Mutex<HashMap<String, PairingTicket>>>,
+    events: broadcast::Sender<PairedPeer>,
+}
+
+impl Pairing {
+    /// Create a pairing service
+    pub fn new(manager: Arc<P2PConnectionManager>) -> Self {
+        Self {
+            manager,
+            capabilities: PeerCapability::ALL.to_vec(),
+            name: None,
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            events: broadcast::channel(PAIRING_EVENTS_CAPACITY).0,
+        }
+    }
+
+    /// Capabilities granted to paired devices (default: all)
+    pub fn with_capabilities(
+        mut self,
+        capabilities: impl IntoIterator<Item = PeerCapability>,
+    ) -> Self {
+        self.capabilities = capabilities.into_iter().collect();
+        self
+    }
+
+    /// Name announced to the other device
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Accept pairing handshakes from any connected peer
+    pub fn register(&self, agent: TunnelAgent) -> TunnelAgent {
+        agent.with_open_handler(PAIRING_SERVICE, Arc::new(self.clone()))
+    }
+
+    /// Subscribe to devices that paired with us
+    pub fn subscribe(&self) -> broadcast::Receiver<PairedPeer> {
+        self.events.subscribe()
+    }
+
+    /// Create a one-time ticket for this device
+    ///
+    /// Puts the connection manager in pairing mode until the ticket is
+    /// redeemed, cancelled or expires.
+    pub async fn create_ticket(&self, ttl: Duration) -> Result<PairingTicket, P2PError> {
+        let peer = PeerTicket::from_endpoint(self.manager.endpoint()).await?;
+        let mut secret = [0u8; 32];
+        rand::rngs::OsRng.fill_bytes(&mut secret);
+        // Whole seconds, as that is what the encoded ticket carries
+        let expires_at = i64::try_from(ttl.as_secs())
+            .ok()
+            .and_then(|secs| DateTime::from_timestamp(Utc::now().timestamp().checked_add(secs)?, 0))
+            .ok_or_else(|| P2PError::InvalidTicket("bad lifetime".to_string()))?;
+
+        let ticket = PairingTicket {
+            peer,
+            secret,
+            expires_at,
+        };
+        self.pending_tickets().insert(ticket.id(), ticket.clone());
+        self.manager.set_pairing_mode(true);
+
+        let pairing = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(ttl).await;
+            pairing.prune();
+        });
+
+        Ok(ticket)
+    }
+
+    /// Withdraw all outstanding tickets
+    pub fn cancel(&self) {
+        self.pending_tickets().clear();
+        self.manager.set_pairing_mode(false);
+    }
+
+    /// Number of tickets that can still be redeemed
+    pub fn pending(&self) -> usize {
+        self.prune();
+        self.pending_tickets().len()
+    }
+
+    /// Redeem a ticket created by another device
+    pub async fn pair(&self, ticket: &PairingTicket) -> Result<PairedPeer, P2PError> {
+        if ticket.is_expired() {
+            return Err(P2PError::InvalidTicket(
+                "pairing ticket expired".to_string(),
+            ));
+        }
+
+        let inviter = ticket.node_id();
+        let joiner = self.manager.local_node_id();
+        self.manager.connect_ticket(ticket.peer()).await?;
+        let mut stream = open_tunnel(&self.manager, inviter, PAIRING_SERVICE).await?;
+
+        let joiner_nonce = random_nonce();
+        send(
+            &mut stream,
+            &PairingMessage::Hello {
+                ticket_id: ticket.id(),
+                nonce: joiner_nonce,
+                name: self.name.clone(),
+            },
+        )
+        .await?;
+
+        let (inviter_nonce, name) = match recv(&mut stream).await? {
+            PairingMessage::Challenge { nonce, proof, name } => {
+                let expected = prove(
+                    &ticket.secret,
+                    INVITER_LABEL,
+                    &inviter,
+                    &joiner,
+                    &nonce,
+                    &joiner_nonce,
+                );
+                if blake3::Hash::from(proof) != expected {
+                    let _ = reject(&mut stream, "inviter proof did not verify").await;
+                    return Err(P2PError::Unauthorized(format!(
+                        "{} does not hold the pairing secret",
+                        inviter
+                    )));
+                }
+                (nonce, name)
+            }
+            PairingMessage::Rejected { reason } => return Err(P2PError::Unauthorized(reason)),
+            other => return Err(unexpected(&other)),
+        };
+
+        let proof = prove(
+            &ticket.secret,
+            JOINER_LABEL,
+            &inviter,
+            &joiner,
+            &inviter_nonce,
+            &joiner_nonce,
+        );
+        send(
+            &mut stream,
+            &PairingMessage::Confirm {
+                proof: *proof.as_bytes(),
+            },
+        )
+        .await?;
+
+        match recv(&mut stream).await? {
+            PairingMessage::Accepted => {}
+            PairingMessage::Rejected { reason } => return Err(P2PError::Unauthorized(reason)),
+            other => return Err(unexpected(&other)),
+        }
+        let _ = stream.finish().await;
+
+        self.complete(inviter, name)
+    }
+
+    /// Run the inviter side of the handshake
+    async fn accept(&self, joiner: NodeId, stream: &mut BiStream) -> Result<PairedPeer, P2PError> {
+        let (ticket_id, joiner_nonce, name) = match recv(stream).await? {
+            PairingMessage::Hello {
+                ticket_id,
+                nonce,
+                name,
+            } => (ticket_id, nonce, name),
+            other => return Err(unexpected(&other)),
+        };
+
+        // Redeeming is attempted once, whether or not it succeeds
+        self.prune();
+        let ticket = self.pending_tickets().remove(&ticket_id);
+        self.update_pairing_mode();
+        let Some(ticket) = ticket else {
+            reject(stream, "unknown or expired pairing ticket").await?;
+            return Err(P2PError::InvalidTicket(
+                "unknown or expired pairing ticket".to_string(),
+            ));
+        };
+
+        let inviter = self.manager.local_node_id();
+        let inviter_nonce = random_nonce();
+        let proof = prove(
+            &ticket.secret,
+            INVITER_LABEL,
+            &inviter,
+            &joiner,
+            &inviter_nonce,
+            &joiner_nonce,
+        );
+        send(
+            stream,
+            &PairingMessage::Challenge {
+                nonce: inviter_nonce,
+                proof: *proof.as_bytes(),
+                name: self.name.clone(),
+            },
+        )
+        .await?;
+
+        let expected = prove(
+            &ticket.secret,
+            JOINER_LABEL,
+            &inviter,
+            &joiner,
+            &inviter_nonce,
+            &joiner_nonce,
+        );
+        match recv(stream).await? {
+            PairingMessage::Confirm { proof } if blake3::Hash::from(proof) == expected => {}
+            PairingMessage::Confirm { .. } => {
+                reject(stream, "pairing proof did not verify").await?;
+                return Err(P2PError::Unauthorized(format!(
+                    "{} does not hold the pairing secret",
+                    joiner
+                )));
+            }
+            PairingMessage::Rejected { reason } => return Err(P2PError::Unauthorized(reason)),
+            other => return Err(unexpected(&other)),
+        }
+
+        let paired = self.complete(joiner, name)?;
+        send(stream, &PairingMessage::Accepted).await?;
+        stream.finish().await?;
+        let _ = self.events.send(paired.clone());
+        Ok(paired)
+    }
+
+    /// Record a paired device in the access control list
+    fn complete(&self, node_id: NodeId, name: Option<String>) -> Result<PairedPeer, P2PError> {
+        if let Some(acl) = self.manager.acl() {
+            acl.allow(&node_id, self.capabilities.iter().copied())?;
+            if let Some(name) = &name {
+                acl.set_note(&node_id, name.clone())?;
+            }
+        }
+
+        tracing::info!(peer_id = %node_id, name = ?name, "Paired with device");
+        Ok(PairedPeer {
+            node_id,
+            name,
+            paired_at: Utc::now(),
+        })
+    }
+
+    fn prune(&self) {
+        self.pending_tickets()
+            .retain(|_, ticket| !ticket.is_expired());
+        self.update_pairing_mode();
+    }
+
+    fn update_pairing_mode(&self) {
+        let open = !self.pending_tickets().is_empty();
+        self.manager.set_pairing_mode(open);
+    }
+
+    fn pending_tickets(&self) -> std::sync::MutexGuard<'_, HashMap<String, PairingTicket>> {
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl StreamHandler for Pairing {
+    async fn handle(&self, peer_id: NodeId, mut stream: BiStream) -> Result<(), P2PError> {
+        self.accept(peer_id, &mut stream).await.map(|_| ())
+    }
+}
+
+/// Render data as a QR code made of Unicode half blocks for terminals
+pub fn render_qr_terminal(data: &str) -> Result<String, P2PError> {
+    let code = qr_code(data)?;
+    Ok(code
+        .render::<qrcode::render::unicode::Dense1x2>()
+        .dark_color(qrcode::render::unicode::Dense1x2::Light)
+        .light_color(qrcode::render::unicode::Dense1x2::Dark)
+        .build())
+}
+
+/// Render data as an SVG QR code
+pub fn render_qr_svg(data: &str) -> Result<String, P2PError> {
+    let code = qr_code(data)?;
+    Ok(code
+        .render::<qrcode::render::svg::Color<'_>>()
+        .min_dimensions(200, 200)
+        .build())
+}
+
+fn qr_code(data: &str) -> Result<qrcode::QrCode, P2PError> {
+    qrcode::QrCode::new(data.as_bytes())
+        .map_err(|e| P2PError::InvalidTicket(format!("cannot encode QR code: {}", e)))
+}
+
+fn ticket_id(secret: &[u8; 32]) -> String {
+    let hash = blake3::derive_key("russh pairing ticket id", secret);
+    hex::encode(&hash[..16])
+}
+
+/// Keyed hash binding the secret to both devices and both nonces
+fn prove(
+    secret: &[u8; 32],
+    label: &[u8],
+    inviter: &NodeId,
+    joiner: &NodeId,
+    inviter_nonce: &[u8; 32],
+    joiner_nonce: &[u8; 32],
+) -> blake3::Hash {
+    let mut hasher = blake3::Hasher::new_keyed(secret);
+    hasher.update(label);
+    hasher.update(inviter.as_bytes());
+    hasher.update(joiner.as_bytes());
+    hasher.update(inviter_nonce);
+    hasher.update(joiner_nonce);
+    hasher.finalize()
+}
+
+fn random_nonce() -> [u8; 32] {
+    let mut nonce = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    nonce
+}
+
+async fn send(stream: &mut BiStream, message: &PairingMessage) -> Result<(), P2PError> {
+    let data = serde_json::to_vec(message)
+        .map_err(|e| P2PError::Stream(format!("Failed to encode pairing message: {}", e)))?;
+    stream.send_message(&data).await
+}
+
+async fn recv<T: DeserializeOwned>(stream: &mut BiStream) -> Result<T, P2PError> {
+    let data = stream.recv_message(MAX_PAIRING_MESSAGE).await?;
+    serde_json::from_slice(&data)
+        .map_err(|e| P2PError::Stream(format!("Invalid pairing message: {}", e)))
+}
+
+async fn reject(stream: &mut BiStream, reason: &str) -> Result<(), P2PError> {
+    send(
+        stream,
+        &PairingMessage::Rejected {
+            reason: reason.to_string(),
+        },
+    )
+    .await?;
+    stream.finish().await
+}
+
+fn unexpected(message: &PairingMessage) -> P2PError {
+    P2PError::Stream(format!("unexpected pairing message {:?}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::SecretKey;
+
+    fn node_id() -> NodeId {
+        SecretKey::generate(rand::rngs::OsRng).public()
+    }
+
+    fn ticket() -> PairingTicket {
+        PairingTicket {
+            peer: PeerTicket::new(node_id())
+                .with_direct_addresses(["192.168.1.20:4433".parse().unwrap()]),
+            secret: random_nonce(),
+            expires_at: DateTime::from_timestamp(Utc::now().timestamp() + 600, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn pairing_ticket_roundtrips() {
+        let ticket = ticket();
+        let encoded = ticket.to_string();
+
+        assert!(encoded.starts_with(PAIRING_TICKET_PREFIX));
+        assert_eq!(encoded.parse::<PairingTicket>().unwrap(), ticket);
+        assert!(!ticket.is_expired());
+        assert!(!format!("{:?}", ticket).contains(&hex::encode(ticket.secret)));
+
+        assert!(ticket.peer().to_string().parse::<PairingTicket>().is_err());
+        assert!(encoded[..encoded.len() - 8]
+            .parse::<PairingTicket>()
+            .is_err());
+    }
+
+    #[test]
+    fn proofs_bind_role_devices_and_nonces() {
+        let secret = random_nonce();
+        let (inviter, joiner) = (node_id(), node_id());
+        let (n1, n2) = (random_nonce(), random_nonce());
+
+        let proof = prove(&secret, INVITER_LABEL, &inviter, &joiner, &n1, &n2);
+        assert_eq!(
+            proof,
+            prove(&secret, INVITER_LABEL, &inviter, &joiner, &n1, &n2)
+        );
+        assert_ne!(
+            proof,
+            prove(&secret, JOINER_LABEL, &inviter, &joiner, &n1, &n2)
+        );
+        assert_ne!(
+            proof,
+            prove(&secret, INVITER_LABEL, &joiner, &inviter, &n1, &n2)
+        );
+        assert_ne!(
+            proof,
+            prove(&secret, INVITER_LABEL, &inviter, &joiner, &n2, &n1)
+        );
+        assert_ne!(
+            proof,
+            prove(&random_nonce(), INVITER_LABEL, &inviter, &joiner, &n1, &n2)
+        );
+    }
+
+    #[test]
+    fn qr_codes_render() {
+        let encoded = ticket().to_string();
+        let terminal = render_qr_terminal(&encoded).unwrap();
+        assert!(terminal.lines().count() > 10);
+        assert!(render_qr_svg(&encoded).unwrap().starts_with("<?xml"));
+    }
+}
@@ -0,0 +1,404 @@
+//! Peer presence and heartbeats
+//!
+//! The presence service periodically sends a heartbeat to every watched
+//! peer and tracks which of them are reachable, when each was last seen and
+//! how long the heartbeat took. A peer is marked offline after a number of
+//! consecutive missed heartbeats, and every online/offline transition is
+//! broadcast so the device list and sync triggers can react.
+//!
+//! A heartbeat opens a stream to the peer's `presence` service; the agent's
+//! acknowledgement is the reply. Heartbeats received from a watched peer
+//! count as a sign of life too.
+//!
+//! # Requirements Coverage
+//! - Requirement 3.5: Connection metadata (latency, type)
+
+use crate::error::P2PError;
+use crate::p2p::connection::P2PConnectionManager;
+use crate::p2p::stream::BiStream;
+use crate::p2p::tunnel::{open_tunnel, StreamHandler, TunnelAgent};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// Service name of the heartbeat protocol
+pub const PRESENCE_SERVICE: &str = "presence";
+
+/// Default interval between heartbeats
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Default time to wait for a heartbeat acknowledgement
+pub const DEFAULT_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default number of missed heartbeats before a peer is offline
+pub const DEFAULT_OFFLINE_AFTER: u32 = 2;
+
+/// Capacity of the presence event channel
+const PRESENCE_EVENTS_CAPACITY: usize = 64;
+
+/// Whether a peer is reachable
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceState {
+    /// No heartbeat has completed yet
+    #[default]
+    Unknown,
+    /// The last heartbeat succeeded
+    Online,
+    /// Too many heartbeats in a row failed
+    Offline,
+}
+
+/// Presence information for one peer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerPresence {
+    /// The peer's node ID
+    pub node_id: NodeId,
+    /// Current state
+    pub state: PresenceState,
+    /// When the peer was last heard from
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Round-trip time of the last successful heartbeat
+    pub rtt: Option<Duration>,
+    /// Consecutive failed heartbeats
+    pub missed: u32,
+}
+
+impl PeerPresence {
+    fn new(node_id: NodeId) -> Self {
+        Self {
+            node_id,
+            state: PresenceState::Unknown,
+            last_seen: None,
+            rtt: None,
+            missed: 0,
+        }
+    }
+}
+
+/// A peer changed between online and offline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresenceEvent {
+    /// The peer's node ID
+    pub node_id: NodeId,
+    /// State before the change
+    pub previous: PresenceState,
+    /// State after the change
+    pub current: PresenceState,
+    /// When the peer was last heard from
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+/// Presence bookkeeping for watched peers, independent of the network
+#[derive(Debug, Clone)]
+pub struct PresenceTracker {
+    peers: HashMap<NodeId, PeerPresence>,
+    offline_after: u32,
+}
+
+impl PresenceTracker {
+    /// Create a tracker marking peers offline after `offline_after` misses
+    pub fn new(offline_after: u32) -> Self {
+        Self {
+            peers: HashMap::new(),
+            offline_after: offline_after.max(1),
+        }
+    }
+
+    /// Start tracking a peer
+    pub fn watch(&mut self, node_id: NodeId) {
+        self.peers
+            .entry(node_id)
+            .or_insert_with(|| PeerPresence::new(node_id));
+    }
+
+    /// Stop tracking a peer
+    pub fn unwatch(&mut self, node_id: &NodeId) -> Option<PeerPresence> {
+        self.peers.remove(node_id)
+    }
+
+    /// Get a watched peer's presence
+    pub fn get(&self, node_id: &NodeId) -> Option<&PeerPresence> {
+        self.peers.get(node_id)
+    }
+
+    /// All watched peers
+    pub fn peers(&self) -> Vec<PeerPresence> {
+        self.peers.values().cloned().collect()
+    }
+
+    /// Watched peer ids
+    pub fn watched(&self) -> Vec<NodeId> {
+        self.peers.keys().copied().collect()
+    }
+
+    /// Record a sign of life; returns an event if the peer came online
+    pub fn record_alive(
+        &mut self,
+        node_id: &NodeId,
+        rtt: Option<Duration>,
+        at: DateTime<Utc>,
+    ) -> Option<PresenceEvent> {
+        let peer = self.peers.get_mut(node_id)?;
+        peer.last_seen = Some(at);
+        peer.missed = 0;
+        if rtt.is_some() {
+            peer.rtt = rtt;
+        }
+        Self::transition(peer, PresenceState::Online)
+    }
+
+    /// Record a missed heartbeat; returns an event if the peer went offline
+    pub fn record_missed(&mut self, node_id: &NodeId) -> Option<PresenceEvent> {
+        let offline_after = self.offline_after;
+        let peer = self.peers.get_mut(node_id)?;
+        peer.missed = peer.missed.saturating_add(1);
+        if peer.missed >= offline_after {
+            Self::transition(peer, PresenceState::Offline)
+        } else {
+            None
+        }
+    }
+
+    fn transition(peer: &mut PeerPresence, state: PresenceState) -> Option<PresenceEvent> {
+        if peer.state == state {
+            return None;
+        }
+        let previous = std::mem::replace(&mut peer.state, state);
+        Some(PresenceEvent {
+            node_id: peer.node_id,
+            previous,
+            current: state,
+            last_seen: peer.last_seen,
+        })
+    }
+}
+
+struct PresenceInner {
+    manager: Arc<P2PConnectionManager>,
+    tracker: Mutex<PresenceTracker>,
+    events: broadcast::Sender<PresenceEvent>,
+    interval: Duration,
+    timeout: Duration,
+}
+
+/// Tracks which known peers are online
+///
+/// Cheap to clone; all clones share the same state. Register it with a
+/// [`TunnelAgent`] (see [`Presence::register`]) so peers can heartbeat us.
+#[derive(Clone)]
+pub struct Presence {
+    inner: Arc<PresenceInner>,
+}
+
+impl Presence {
+    /// Create a presence service with default timing
+    pub fn new(manager: Arc<P2PConnectionManager>) -> Self {
+        Self::with_timing(
+            manager,
+            DEFAULT_HEARTBEAT_INTERVAL,
+            DEFAULT_HEARTBEAT_TIMEOUT,
+            DEFAULT_OFFLINE_AFTER,
+        )
+    }
+
+    /// Create a presence service with custom heartbeat timing
+    pub fn with_timing(
+        manager: Arc<P2PConnectionManager>,
+        interval: Duration,
+        timeout: Duration,
+        offline_after: u32,
+    ) -> Self {
+        Self {
+            inner: Arc::new(PresenceInner {
+                manager,
+                tracker: Mutex::new(PresenceTracker::new(offline_after)),
+                events: broadcast::channel(PRESENCE_EVENTS_CAPACITY).0,
+                interval,
+                timeout,
+            }),
+        }
+    }
+
+    /// Answer heartbeats from any connected peer
+    pub fn register(&self, agent: TunnelAgent) -> TunnelAgent {
+        agent.with_open_handler(PRESENCE_SERVICE, Arc::new(self.clone()))
+    }
+
+    /// Subscribe to online/offline transitions
+    pub fn subscribe(&self) -> broadcast::Receiver<PresenceEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// Start tracking a peer
+    pub fn watch(&self, node_id: NodeId) {
+        self.tracker().watch(node_id);
+    }
+
+    /// Stop tracking a peer
+    pub fn unwatch(&self, node_id: &NodeId) {
+        self.tracker().unwatch(node_id);
+    }
+
+    /// Get a watched peer's presence
+    pub fn presence(&self, node_id: &NodeId) -> Option<PeerPresence> {
+        self.tracker().get(node_id).cloned()
+    }
+
+    /// Whether a watched peer is currently online
+    pub fn is_online(&self, node_id: &NodeId) -> bool {
+        self.presence(node_id)
+            .is_some_and(|p| p.state == PresenceState::Online)
+    }
+
+    /// All watched peers
+    pub fn peers(&self) -> Vec<PeerPresence> {
+        self.tracker().peers()
+    }
+
+    /// Heartbeat one peer now and update its presence
+    pub async fn check(&self, node_id: NodeId) -> PresenceState {
+        let started = Instant::now();
+        let result = tokio::time::timeout(
+            self.inner.timeout,
+            open_tunnel(&self.inner.manager, node_id, PRESENCE_SERVICE),
+        )
+        .await;
+
+        let event = match result {
+            Ok(Ok(mut stream)) => {
+                let _ = stream.finish().await;
+                self.tracker()
+                    .record_alive(&node_id, Some(started.elapsed()), Utc::now())
+            }
+            Ok(Err(e)) => {
+                tracing::debug!(peer_id = %node_id, error = %e, "Heartbeat failed");
+                self.tracker().record_missed(&node_id)
+            }
+            Err(_) => {
+                tracing::debug!(peer_id = %node_id, "Heartbeat timed out");
+                self.tracker().record_missed(&node_id)
+            }
+        };
+        self.publish(event);
+
+        self.presence(&node_id).map(|p| p.state).unwrap_or_default()
+    }
+
+    /// Heartbeat every watched peer concurrently
+    pub async fn check_all(&self) {
+        let handles: Vec<_> = self
+            .tracker()
+            .watched()
+            .into_iter()
+            .map(|node_id| {
+                let presence = self.clone();
+                tokio::spawn(async move { presence.check(node_id).await })
+            })
+            .collect();
+        for handle in handles {
+            let _ = handle.await;
+        }
+    }
+
+    /// Heartbeat watched peers in the background
+    ///
+    /// The task stops once every clone of this service has been dropped.
+    pub fn start(&self) -> JoinHandle<()> {
+        let inner: Weak<PresenceInner> = Arc::downgrade(&self.inner);
+        let interval = self.inner.interval;
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let Some(inner) = inner.upgrade() else {
+                    break;
+                };
+                Presence { inner }.check_all().await;
+            }
+        })
+    }
+
+    fn publish(&self, event: Option<PresenceEvent>) {
+        if let Some(event) = event {
+            tracing::info!(peer_id = %event.node_id, state = ?event.current, "Peer presence changed");
+            let _ = self.inner.events.send(event);
+        }
+    }
+
+    fn tracker(&self) -> std::sync::MutexGuard<'_, PresenceTracker> {
+        self.inner
+            .tracker
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl StreamHandler for Presence {
+    async fn handle(&self, peer_id: NodeId, _stream: BiStream) -> Result<(), P2PError> {
+        let event = self.tracker().record_alive(&peer_id, None, Utc::now());
+        self.publish(event);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::SecretKey;
+
+    fn node_id() -> NodeId {
+        SecretKey::generate(rand::rngs::OsRng).public()
+    }
+
+    #[test]
+    fn peers_come_online_and_go_offline_after_missed_heartbeats() {
+        let mut tracker = PresenceTracker::new(2);
+        let peer = node_id();
+        tracker.watch(peer);
+        assert_eq!(tracker.get(&peer).unwrap().state, PresenceState::Unknown);
+
+        let online = tracker
+            .record_alive(&peer, Some(Duration::from_millis(30)), Utc::now())
+            .unwrap();
+        assert_eq!(online.previous, PresenceState::Unknown);
+        assert_eq!(online.current, PresenceState::Online);
+        assert!(tracker.record_alive(&peer, None, Utc::now()).is_none());
+        assert_eq!(
+            tracker.get(&peer).unwrap().rtt,
+            Some(Duration::from_millis(30))
+        );
+
+        assert!(tracker.record_missed(&peer).is_none());
+        let offline = tracker.record_missed(&peer).unwrap();
+        assert_eq!(offline.current, PresenceState::Offline);
+        assert!(offline.last_seen.is_some());
+        assert!(tracker.record_missed(&peer).is_none());
+
+        let back = tracker.record_alive(&peer, None, Utc::now()).unwrap();
+        assert_eq!(back.previous, PresenceState::Offline);
+        assert_eq!(tracker.get(&peer).unwrap().missed, 0);
+    }
+
+    #[test]
+    fn unwatched_peers_are_ignored() {
+        let mut tracker = PresenceTracker::new(1);
+        let peer = node_id();
+
+        assert!(tracker.record_alive(&peer, None, Utc::now()).is_none());
+        assert!(tracker.record_missed(&peer).is_none());
+        assert!(tracker.peers().is_empty());
+
+        tracker.watch(peer);
+        assert!(tracker.unwatch(&peer).is_some());
+        assert!(tracker.get(&peer).is_none());
+    }
+}
@@ -0,0 +1,405 @@
+//! Persistent peer registry with aliases
+//!
+//! The registry maps NodeIds to human-friendly names, tags and a trust
+//! state, and remembers the last ticket each peer was reached with, so
+//! users can write `laptop` instead of a 52-character NodeId. Names are
+//! unique ignoring case and can never be mistaken for a ticket or NodeId.
+//!
+//! The trust state is descriptive; connection policy is enforced by the
+//! [`PeerAcl`](crate::p2p::acl::PeerAcl).
+//!
+//! # Requirements Coverage
+//! - Requirement 3.5: Connection metadata (latency, type)
+
+use crate::error::P2PError;
+use crate::p2p::ticket::PeerTicket;
+use chrono::{DateTime, Utc};
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// File name of the peer registry inside the configuration directory
+pub const PEER_REGISTRY_FILE_NAME: &str = "peers.json";
+
+/// Longest accepted peer name
+pub const MAX_PEER_NAME_LEN: usize = 64;
+
+/// How much a peer is trusted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerTrust {
+    /// Added without verifying the peer's identity
+    #[default]
+    Unverified,
+    /// Identity confirmed, e.g. by pairing
+    Trusted,
+    /// Should not be connected to
+    Blocked,
+}
+
+/// A known peer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerRecord {
+    /// The peer's NodeId
+    pub node_id: NodeId,
+    /// Unique human-friendly name
+    pub name: String,
+    /// Free-form labels (e.g. "home", "work")
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    /// Trust state
+    #[serde(default)]
+    pub trust: PeerTrust,
+    /// Last ticket the peer was reached with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ticket: Option<String>,
+    /// When the peer was added
+    pub added_at: DateTime<Utc>,
+    /// When the peer was last connected to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+impl PeerRecord {
+    /// Ticket to dial the peer with, falling back to its bare NodeId
+    pub fn dial_ticket(&self) -> PeerTicket {
+        self.ticket
+            .as_deref()
+            .and_then(|ticket| ticket.parse().ok())
+            .filter(|ticket: &PeerTicket| ticket.node_id() == self.node_id)
+            .unwrap_or_else(|| PeerTicket::new(self.node_id))
+    }
+}
+
+/// On-disk layout of the registry
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RegistryFile {
+    #[serde(default)]
+    peers: HashMap<String, PeerRecord>,
+}
+
+/// Persisted names, tags and trust state of known peers
+#[derive(Debug, Default)]
+pub struct PeerRegistry {
+    /// File the registry is saved to, if any
+    path: Option<PathBuf>,
+    /// Known peers keyed by NodeId
+    state: RwLock<RegistryFile>,
+}
+
+impl PeerRegistry {
+    /// Create an empty in-memory registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Default location of the registry file
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("russh").join(PEER_REGISTRY_FILE_NAME))
+    }
+
+    /// Open the registry at `path`, starting empty if the file does not exist
+    pub fn open(path: &Path) -> Result<Self, P2PError> {
+        let state = if path.exists() {
+            let json = std::fs::read_to_string(path).map_err(|e| {
+                P2PError::Storage(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+            serde_json::from_str(&json).map_err(|e| {
+                P2PError::Storage(format!("Invalid peer registry {}: {}", path.display(), e))
+            })?
+        } else {
+            RegistryFile::default()
+        };
+
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            state: RwLock::new(state),
+        })
+    }
+
+    /// Add a peer under a name, or rename it and update its ticket if known
+    pub fn add(&self, ticket: &PeerTicket, name: &str) -> Result<PeerRecord, P2PError> {
+        let node_id = ticket.node_id();
+        let record = {
+            let mut state = self.write_state();
+            check_name(&state, name, &node_id)?;
+            let record = state
+                .peers
+                .entry(node_id.to_string())
+                .or_insert_with(|| PeerRecord {
+                    node_id,
+                    name: String::new(),
+                    tags: BTreeSet::new(),
+                    trust: PeerTrust::default(),
+                    ticket: None,
+                    added_at: Utc::now(),
+                    last_seen: None,
+                });
+            record.name = name.to_string();
+            record.ticket = Some(ticket.to_string());
+            record.clone()
+        };
+        self.save()?;
+        Ok(record)
+    }
+
+    /// Give a known peer a new name
+    pub fn rename(&self, peer: &NodeId, name: &str) -> Result<(), P2PError> {
+        {
+            let mut state = self.write_state();
+            check_name(&state, name, peer)?;
+            record_mut(&mut state, peer)?.name = name.to_string();
+        }
+        self.save()
+    }
+
+    /// Forget a peer
+    pub fn remove(&self, peer: &NodeId) -> Result<Option<PeerRecord>, P2PError> {
+        let removed = self.write_state().peers.remove(&peer.to_string());
+        self.save()?;
+        Ok(removed)
+    }
+
+    /// Set a known peer's trust state
+    pub fn set_trust(&self, peer: &NodeId, trust: PeerTrust) -> Result<(), P2PError> {
+        record_mut(&mut self.write_state(), peer)?.trust = trust;
+        self.save()
+    }
+
+    /// Add a tag to a known peer
+    pub fn add_tag(&self, peer: &NodeId, tag: &str) -> Result<(), P2PError> {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return Err(P2PError::InvalidAlias("tags cannot be empty".to_string()));
+        }
+        record_mut(&mut self.write_state(), peer)?
+            .tags
+            .insert(tag.to_string());
+        self.save()
+    }
+
+    /// Remove a tag from a known peer
+    pub fn remove_tag(&self, peer: &NodeId, tag: &str) -> Result<(), P2PError> {
+        record_mut(&mut self.write_state(), peer)?.tags.remove(tag);
+        self.save()
+    }
+
+    /// Record a successful connection, optionally with a fresher ticket
+    ///
+    /// Unknown peers are ignored, as are tickets without addressing.
+    pub fn touch(&self, peer: &NodeId, ticket: Option<&PeerTicket>) -> Result<(), P2PError> {
+        {
+            let mut state = self.write_state();
+            let Some(record) = state.peers.get_mut(&peer.to_string()) else {
+                return Ok(());
+            };
+            record.last_seen = Some(Utc::now());
+            if let Some(ticket) = ticket.filter(|t| t.node_id() == *peer && !t.needs_lookup()) {
+                record.ticket = Some(ticket.to_string());
+            }
+        }
+        self.save()
+    }
+
+    /// Get a peer by NodeId
+    pub fn get(&self, peer: &NodeId) -> Option<PeerRecord> {
+        self.read_state().peers.get(&peer.to_string()).cloned()
+    }
+
+    /// Find a peer by name, ignoring case
+    pub fn find(&self, name: &str) -> Option<PeerRecord> {
+        self.read_state()
+            .peers
+            .values()
+            .find(|record| record.name.eq_ignore_ascii_case(name.trim()))
+            .cloned()
+    }
+
+    /// Turn a name, ticket or NodeId into a ticket to dial
+    ///
+    /// Names resolve to the peer's last known ticket; bare NodeIds of known
+    /// peers do too, since they carry no addressing of their own.
+    pub fn resolve(&self, name_or_ticket: &str) -> Result<PeerTicket, P2PError> {
+        if let Some(record) = self.find(name_or_ticket) {
+            return Ok(record.dial_ticket());
+        }
+        let ticket: PeerTicket = name_or_ticket.parse()?;
+        match self.get(&ticket.node_id()) {
+            Some(record) if ticket.needs_lookup() => Ok(record.dial_ticket()),
+            _ => Ok(ticket),
+        }
+    }
+
+    /// Display name of a peer, falling back to its short NodeId
+    pub fn display_name(&self, peer: &NodeId) -> String {
+        self.get(peer)
+            .map(|record| record.name)
+            .unwrap_or_else(|| peer.fmt_short())
+    }
+
+    /// Peers carrying a tag
+    pub fn with_tag(&self, tag: &str) -> Vec<PeerRecord> {
+        self.list()
+            .into_iter()
+            .filter(|record| record.tags.contains(tag))
+            .collect()
+    }
+
+    /// All peers, sorted by name
+    pub fn list(&self) -> Vec<PeerRecord> {
+        let mut peers: Vec<_> = self.read_state().peers.values().cloned().collect();
+        peers.sort_by_key(|record| record.name.to_lowercase());
+        peers
+    }
+
+    /// Write the registry to its file, if it has one
+    pub fn save(&self) -> Result<(), P2PError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let storage_error =
+            |e: std::io::Error| P2PError::Storage(format!("{}: {}", path.display(), e));
+
+        let json = serde_json::to_string_pretty(&*self.read_state())
+            .map_err(|e| P2PError::Storage(format!("Failed to encode peer registry: {}", e)))?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(storage_error)?;
+        }
+
+        let tmp_path = path.with_extension("json.tmp");
+        let mut file = std::fs::File::create(&tmp_path).map_err(storage_error)?;
+        file.write_all(json.as_bytes()).map_err(storage_error)?;
+        file.sync_all().map_err(storage_error)?;
+        std::fs::rename(&tmp_path, path).map_err(storage_error)
+    }
+
+    fn read_state(&self) -> std::sync::RwLockReadGuard<'_, RegistryFile> {
+        self.state.read().unwrap_or_else(|poisoned| {
+            tracing::warn!("Peer registry lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+
+    fn write_state(&self) -> std::sync::RwLockWriteGuard<'_, RegistryFile> {
+        self.state.write().unwrap_or_else(|poisoned| {
+            tracing::warn!("Peer registry lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+}
+
+fn record_mut<'a>(
+    state: &'a mut RegistryFile,
+    peer: &NodeId,
+) -> Result<&'a mut PeerRecord, P2PError> {
+    state
+        .peers
+        .get_mut(&peer.to_string())
+        .ok_or_else(|| P2PError::PeerNotFound(peer.to_string()))
+}
+
+/// Fail unless `name` is a valid name that no other peer uses
+fn check_name(state: &RegistryFile, name: &str, owner: &NodeId) -> Result<(), P2PError> {
+    let invalid = |reason: &str| P2PError::InvalidAlias(format!("'{}' {}", name, reason));
+
+    if name.is_empty() || name.len() > MAX_PEER_NAME_LEN {
+        return Err(invalid(&format!(
+            "must be 1 to {} characters",
+            MAX_PEER_NAME_LEN
+        )));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(invalid(
+            "may only contain letters, digits, '-', '_' and '.'",
+        ));
+    }
+    if name.parse::<PeerTicket>().is_ok() {
+        return Err(invalid("looks like a ticket or node id"));
+    }
+    if let Some(other) = state
+        .peers
+        .values()
+        .find(|record| record.node_id != *owner && record.name.eq_ignore_ascii_case(name))
+    {
+        return Err(invalid(&format!(
+            "is already used by {}",
+            other.node_id.fmt_short()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::SecretKey;
+
+    fn node_id() -> NodeId {
+        SecretKey::generate(rand::rngs::OsRng).public()
+    }
+
+    #[test]
+    fn names_resolve_to_stored_tickets() {
+        let registry = PeerRegistry::new();
+        let laptop = node_id();
+        let ticket =
+            PeerTicket::new(laptop).with_direct_addresses(["10.0.0.2:4433".parse().unwrap()]);
+        registry.add(&ticket, "laptop").unwrap();
+
+        assert_eq!(registry.resolve("Laptop").unwrap(), ticket);
+        // A bare NodeId of a known peer picks up its stored addresses
+        assert_eq!(registry.resolve(&laptop.to_string()).unwrap(), ticket);
+
+        let stranger = node_id();
+        assert_eq!(
+            registry.resolve(&stranger.to_string()).unwrap(),
+            PeerTicket::new(stranger)
+        );
+        assert!(registry.resolve("desktop").is_err());
+        assert_eq!(registry.display_name(&laptop), "laptop");
+    }
+
+    #[test]
+    fn invalid_and_duplicate_names_are_rejected() {
+        let registry = PeerRegistry::new();
+        let (a, b) = (node_id(), node_id());
+        registry.add(&PeerTicket::new(a), "laptop").unwrap();
+
+        assert!(registry.add(&PeerTicket::new(b), "LAPTOP").is_err());
+        assert!(registry.add(&PeerTicket::new(b), "my laptop").is_err());
+        assert!(registry.add(&PeerTicket::new(b), "").is_err());
+        assert!(registry.add(&PeerTicket::new(b), &a.to_string()).is_err());
+        assert!(registry.rename(&b, "desktop").is_err());
+
+        // Renaming a peer to its own name in another case is fine
+        registry.rename(&a, "Laptop").unwrap();
+        assert_eq!(registry.get(&a).unwrap().name, "Laptop");
+    }
+
+    #[test]
+    fn registry_persists_across_opens() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PEER_REGISTRY_FILE_NAME);
+        let peer = node_id();
+
+        let registry = PeerRegistry::open(&path).unwrap();
+        registry.add(&PeerTicket::new(peer), "nas").unwrap();
+        registry.add_tag(&peer, "home").unwrap();
+        registry.set_trust(&peer, PeerTrust::Trusted).unwrap();
+        registry.touch(&peer, None).unwrap();
+
+        let reopened = PeerRegistry::open(&path).unwrap();
+        let record = reopened.get(&peer).unwrap();
+        assert_eq!(record.name, "nas");
+        assert_eq!(record.trust, PeerTrust::Trusted);
+        assert!(record.last_seen.is_some());
+        assert_eq!(reopened.with_tag("home").len(), 1);
+    }
+}
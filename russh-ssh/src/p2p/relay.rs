@@ -0,0 +1,265 @@
+//! Self-hosted relay configuration and health checks
+//!
+//! Custom relays replace the public n0 relays entirely, so traffic never
+//! touches third-party infrastructure. Relays are listed in priority order
+//! and probed over HTTPS before the endpoint binds; unreachable relays are
+//! left out of the relay map.
+//!
+//! # Requirements Coverage
+//! - Requirement 3.3: Relay server fallback
+
+use crate::error::P2PError;
+use iroh::{RelayMap, RelayNode, RelayUrl};
+use std::time::{Duration, Instant};
+
+/// HTTP path iroh relay servers answer for liveness probes
+pub const RELAY_PROBE_PATH: &str = "/ping";
+
+/// Default timeout for a single relay probe
+pub const DEFAULT_RELAY_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default STUN port advertised for custom relays
+pub const DEFAULT_STUN_PORT: u16 = 3478;
+
+/// How the endpoint fails over between custom relays
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RelayFailover {
+    /// Register every healthy relay; iroh homes on the fastest and fails
+    /// over to the others automatically
+    #[default]
+    Fastest,
+    /// Register only the highest-priority healthy relay
+    Ordered,
+}
+
+/// Result of probing a relay server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayHealth {
+    /// Relay that was probed
+    pub url: RelayUrl,
+    /// Whether the relay answered the probe
+    pub healthy: bool,
+    /// Round-trip time of the probe
+    pub latency: Option<Duration>,
+    /// Why the probe failed
+    pub error: Option<String>,
+}
+
+/// Parse a list of relay URLs, rejecting empty lists and invalid entries
+///
+/// A misconfigured self-hosted relay list is an error rather than a silent
+/// fallback to the public relays.
+pub fn parse_relay_urls(urls: &[String]) -> Result<Vec<RelayUrl>, P2PError> {
+    if urls.is_empty() {
+        return Err(P2PError::RelayFailed(
+            "custom relay list is empty".to_string(),
+        ));
+    }
+
+    let mut parsed: Vec<RelayUrl> = Vec::with_capacity(urls.len());
+    for url in urls {
+        let relay = url
+            .parse::<RelayUrl>()
+            .map_err(|e| P2PError::RelayFailed(format!("invalid relay URL '{}': {}", url, e)))?;
+        if !parsed.contains(&relay) {
+            parsed.push(relay);
+        }
+    }
+    Ok(parsed)
+}
+
+/// Probe a relay's liveness endpoint
+pub async fn check_relay(url: &RelayUrl, timeout: Duration) -> RelayHealth {
+    let client = match reqwest::Client::builder().timeout(timeout).build() {
+        Ok(client) => client,
+        Err(e) => return RelayHealth::failed(url, e.to_string()),
+    };
+    probe(&client, url).await
+}
+
+/// Probe several relays concurrently, returning results in the given order
+pub async fn check_relays(urls: &[RelayUrl], timeout: Duration) -> Vec<RelayHealth> {
+    let client = match reqwest::Client::builder().timeout(timeout).build() {
+        Ok(client) => client,
+        Err(e) => {
+            return urls
+                .iter()
+                .map(|url| RelayHealth::failed(url, e.to_string()))
+                .collect()
+        }
+    };
+
+    let handles: Vec<_> = urls
+        .iter()
+        .map(|url| {
+            let client = client.clone();
+            let url = url.clone();
+            tokio::spawn(async move { probe(&client, &url).await })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(urls.len());
+    for (url, handle) in urls.iter().zip(handles) {
+        results.push(
+            handle
+                .await
+                .unwrap_or_else(|e| RelayHealth::failed(url, e.to_string())),
+        );
+    }
+    results
+}
+
+/// Choose which relays to register, in priority order
+///
+/// If no relay is healthy all of them are kept, since they may come up
+/// after the endpoint binds and iroh keeps retrying.
+pub fn select_relays(health: &[RelayHealth], failover: RelayFailover) -> Vec<RelayUrl> {
+    let healthy: Vec<RelayUrl> = health
+        .iter()
+        .filter(|h| h.healthy)
+        .map(|h| h.url.clone())
+        .collect();
+
+    if healthy.is_empty() {
+        tracing::warn!("No custom relay passed its health check, keeping all of them");
+        return health.iter().map(|h| h.url.clone()).collect();
+    }
+
+    match failover {
+        RelayFailover::Fastest => healthy,
+        RelayFailover::Ordered => healthy.into_iter().take(1).collect(),
+    }
+}
+
+/// Build an iroh relay map from relay URLs
+pub fn relay_map(urls: &[RelayUrl]) -> Result<RelayMap, P2PError> {
+    RelayMap::from_nodes(urls.iter().map(|url| RelayNode {
+        url: url.clone(),
+        stun_only: false,
+        stun_port: DEFAULT_STUN_PORT,
+        quic: None,
+    }))
+    .map_err(|e| P2PError::RelayFailed(format!("Failed to create relay map: {}", e)))
+}
+
+impl RelayHealth {
+    fn failed(url: &RelayUrl, error: String) -> Self {
+        Self {
+            url: url.clone(),
+            healthy: false,
+            latency: None,
+            error: Some(error),
+        }
+    }
+}
+
+async fn probe(client: &reqwest::Client, url: &RelayUrl) -> RelayHealth {
+    let probe_url = format!(
+        "{}{}",
+        url.to_string().trim_end_matches('/'),
+        RELAY_PROBE_PATH
+    );
+    let started = Instant::now();
+
+    match client.get(&probe_url).send().await {
+        Ok(response) if response.status().is_success() => RelayHealth {
+            url: url.clone(),
+            healthy: true,
+            latency: Some(started.elapsed()),
+            error: None,
+        },
+        Ok(response) => RelayHealth::failed(url, format!("HTTP {}", response.status())),
+        Err(e) => RelayHealth::failed(url, e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn health(url: &str, healthy: bool) -> RelayHealth {
+        RelayHealth {
+            url: url.parse().unwrap(),
+            healthy,
+            latency: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn empty_or_invalid_relay_lists_are_rejected() {
+        assert!(matches!(
+            parse_relay_urls(&[]),
+            Err(P2PError::RelayFailed(_))
+        ));
+        assert!(matches!(
+            parse_relay_urls(&["https://relay.corp.example".into(), "not a url".into()]),
+            Err(P2PError::RelayFailed(_))
+        ));
+
+        let urls = parse_relay_urls(&[
+            "https://b.corp.example".into(),
+            "https://a.corp.example".into(),
+            "https://b.corp.example".into(),
+        ])
+        .unwrap();
+        assert_eq!(urls.len(), 2);
+        assert_eq!(urls[0], "https://b.corp.example".parse().unwrap());
+    }
+
+    #[test]
+    fn selection_prefers_healthy_relays_in_order() {
+        let checks = [
+            health("https://primary.corp.example", false),
+            health("https://secondary.corp.example", true),
+            health("https://tertiary.corp.example", true),
+        ];
+
+        let fastest = select_relays(&checks, RelayFailover::Fastest);
+        assert_eq!(fastest, vec![checks[1].url.clone(), checks[2].url.clone()]);
+
+        let ordered = select_relays(&checks, RelayFailover::Ordered);
+        assert_eq!(ordered, vec![checks[1].url.clone()]);
+    }
+
+    #[test]
+    fn all_relays_kept_when_none_are_healthy() {
+        let checks = [
+            health("https://primary.corp.example", false),
+            health("https://secondary.corp.example", false),
+        ];
+        assert_eq!(select_relays(&checks, RelayFailover::Ordered).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn probe_reports_reachable_and_unreachable_relays() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let n = socket.read(&mut buf).await.unwrap();
+            assert!(String::from_utf8_lossy(&buf[..n]).starts_with("GET /ping "));
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed_addr = closed.local_addr().unwrap();
+        drop(closed);
+
+        let urls: Vec<RelayUrl> = [addr, closed_addr]
+            .iter()
+            .map(|addr| format!("http://{}", addr).parse().unwrap())
+            .collect();
+        let results = check_relays(&urls, Duration::from_secs(5)).await;
+
+        assert!(results[0].healthy, "{:?}", results[0].error);
+        assert!(results[0].latency.is_some());
+        assert!(!results[1].healthy);
+        assert!(results[1].error.is_some());
+    }
+}
@@ -0,0 +1,347 @@
+//! Encrypted P2P streams
+//!
+//! Wraps a bidirectional stream in a `SecureChannel`. The handshake runs
+//! over length-prefixed frames, and every frame sent afterwards is
+//! encrypted end-to-end independently of the QUIC transport security.
+//!
+//! # Requirements Coverage
+//! - Requirement 3.4: Multiplexed bidirectional streams
+//! - Requirement 4.1: End-to-end encryption between peers
+
+use crate::encryption::{
+    HandshakeMessage, Identity, KeyProvider, RatchetChannel, RatchetMessage, ResumptionState,
+    SecureChannel, SecureChannelBuilder, SecureMessage, TicketIssuer,
+};
+use crate::error::P2PError;
+use crate::p2p::stream::{BiStream, StreamExt};
+use iroh::endpoint::{RecvStream, SendStream};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Maximum size of a handshake frame
+pub const MAX_HANDSHAKE_SIZE: usize = 4096;
+
+/// A bidirectional stream protected by a secure channel
+pub struct SecureStream {
+    /// The underlying stream
+    stream: BiStream,
+    /// Frame encryption state
+    codec: FrameCodec,
+}
+
+/// Encrypts and decrypts frames; shared by both halves of a split stream
+#[derive(Clone)]
+struct FrameCodec {
+    /// Established secure channel
+    channel: Arc<SecureChannel>,
+    /// Ratchet layered on the channel, if enabled
+    ratchet: Option<Arc<RatchetChannel>>,
+}
+
+impl FrameCodec {
+    fn seal(&self, data: &[u8]) -> Result<Vec<u8>, P2PError> {
+        match &self.ratchet {
+            Some(ratchet) => serde_json::to_vec(&ratchet.encrypt(data)?),
+            None => serde_json::to_vec(&self.channel.encrypt(data)?),
+        }
+        .map_err(|e| P2PError::Stream(format!("Failed to encode frame: {}", e)))
+    }
+
+    fn open(&self, bytes: &[u8]) -> Result<Vec<u8>, P2PError> {
+        let invalid_frame =
+            |e: serde_json::Error| P2PError::Stream(format!("Invalid frame: {}", e));
+
+        match &self.ratchet {
+            Some(ratchet) => {
+                let message: RatchetMessage =
+                    serde_json::from_slice(bytes).map_err(invalid_frame)?;
+                Ok(ratchet.decrypt(&message)?)
+            }
+            None => {
+                let message: SecureMessage =
+                    serde_json::from_slice(bytes).map_err(invalid_frame)?;
+                Ok(self.channel.decrypt(&message)?)
+            }
+        }
+    }
+}
+
+impl SecureStream {
+    fn established(stream: BiStream, channel: SecureChannel) -> Self {
+        Self {
+            stream,
+            codec: FrameCodec {
+                channel: Arc::new(channel),
+                ratchet: None,
+            },
+        }
+    }
+
+    /// Switch to double-ratchet mode for the rest of the stream
+    ///
+    /// Both ends must call this after the same frame. Recommended for
+    /// streams that stay open for a long time, such as sync or presence.
+    pub fn with_ratchet(mut self) -> Self {
+        self.codec.ratchet = Some(Arc::new(RatchetChannel::new(&self.codec.channel)));
+        self
+    }
+
+    /// Run the handshake as initiator with a throwaway identity
+    pub async fn initiate(stream: BiStream) -> Result<Self, P2PError> {
+        Self::initiate_with(stream, SecureChannelBuilder::new()?).await
+    }
+
+    /// Run the handshake as initiator, authenticating with a long-term identity
+    pub async fn initiate_with_identity(
+        stream: BiStream,
+        identity: Arc<dyn KeyProvider>,
+    ) -> Result<Self, P2PError> {
+        Self::initiate_with(stream, SecureChannelBuilder::with_identity(identity)?).await
+    }
+
+    /// Run the handshake as initiator with a preconfigured builder
+    ///
+    /// Use this to consult a [`TrustStore`](crate::encryption::TrustStore)
+    /// via [`SecureChannelBuilder::with_trust_store`].
+    pub async fn initiate_with(
+        mut stream: BiStream,
+        builder: SecureChannelBuilder,
+    ) -> Result<Self, P2PError> {
+        let init = serde_json::to_vec(&builder.create_init_message())
+            .map_err(|e| P2PError::Stream(format!("Failed to encode handshake: {}", e)))?;
+        stream.send_message(&init).await?;
+
+        let response = stream.recv_message(MAX_HANDSHAKE_SIZE).await?;
+        let response: HandshakeMessage = serde_json::from_slice(&response)
+            .map_err(|e| P2PError::Stream(format!("Invalid handshake response: {}", e)))?;
+        let channel = builder.process_response(response)?;
+
+        Ok(Self::established(stream, channel))
+    }
+
+    /// Run the handshake as responder with a throwaway identity
+    pub async fn accept(stream: BiStream) -> Result<Self, P2PError> {
+        Self::accept_with(stream, SecureChannelBuilder::new()?).await
+    }
+
+    /// Run the handshake as responder, authenticating with a long-term identity
+    pub async fn accept_with_identity(
+        stream: BiStream,
+        identity: Arc<dyn KeyProvider>,
+    ) -> Result<Self, P2PError> {
+        Self::accept_with(stream, SecureChannelBuilder::with_identity(identity)?).await
+    }
+
+    /// Run the handshake as responder with a preconfigured builder
+    ///
+    /// Use this to consult a [`TrustStore`](crate::encryption::TrustStore)
+    /// via [`SecureChannelBuilder::with_trust_store`].
+    pub async fn accept_with(
+        mut stream: BiStream,
+        builder: SecureChannelBuilder,
+    ) -> Result<Self, P2PError> {
+        let init = Self::recv_init(&mut stream).await?;
+        Self::respond(stream, builder, init).await
+    }
+
+    /// Resume an earlier channel with a single message
+    ///
+    /// The responder closes the stream if it no longer accepts the ticket;
+    /// fall back to a full handshake in that case.
+    pub async fn resume(mut stream: BiStream, state: ResumptionState) -> Result<Self, P2PError> {
+        let (channel, message) = state.resume()?;
+        let message = serde_json::to_vec(&message)
+            .map_err(|e| P2PError::Stream(format!("Failed to encode handshake: {}", e)))?;
+        stream.send_message(&message).await?;
+
+        Ok(Self::established(stream, channel))
+    }
+
+    /// Run the handshake as responder, also accepting resumption tickets
+    ///
+    /// A resumed peer is checked against the builder's revocation list and
+    /// trust store just like one completing a full handshake.
+    pub async fn accept_resumable(
+        mut stream: BiStream,
+        builder: SecureChannelBuilder,
+        issuer: &TicketIssuer,
+    ) -> Result<Self, P2PError> {
+        let init = Self::recv_init(&mut stream).await?;
+
+        if let HandshakeMessage::Resume { .. } = init {
+            let resumed = issuer.resume(&init).and_then(|channel| {
+                builder.check_peer_identity(channel.peer_identity())?;
+                Ok(channel)
+            });
+            return match resumed {
+                Ok(channel) => Ok(Self::established(stream, channel)),
+                Err(e) => {
+                    let _ = stream.finish().await;
+                    Err(e.into())
+                }
+            };
+        }
+
+        Self::respond(stream, builder, init).await
+    }
+
+    async fn recv_init(stream: &mut BiStream) -> Result<HandshakeMessage, P2PError> {
+        let init = stream.recv_message(MAX_HANDSHAKE_SIZE).await?;
+        serde_json::from_slice(&init)
+            .map_err(|e| P2PError::Stream(format!("Invalid handshake init: {}", e)))
+    }
+
+    async fn respond(
+        mut stream: BiStream,
+        builder: SecureChannelBuilder,
+        init: HandshakeMessage,
+    ) -> Result<Self, P2PError> {
+        let (channel, response) = builder.process_init(init)?;
+        let response = serde_json::to_vec(&response)
+            .map_err(|e| P2PError::Stream(format!("Failed to encode handshake: {}", e)))?;
+        stream.send_message(&response).await?;
+
+        Ok(Self::established(stream, channel))
+    }
+
+    /// Encrypt and send a frame
+    pub async fn send(&mut self, data: &[u8]) -> Result<(), P2PError> {
+        let bytes = self.codec.seal(data)?;
+        self.stream.send_message(&bytes).await
+    }
+
+    /// Receive and decrypt a frame
+    pub async fn recv(&mut self, max_size: usize) -> Result<Vec<u8>, P2PError> {
+        let bytes = self.stream.recv_message(max_size).await?;
+        self.codec.open(&bytes)
+    }
+
+    /// Serialize a value as JSON and send it as an encrypted frame
+    pub async fn send_json<T: Serialize>(&mut self, value: &T) -> Result<(), P2PError> {
+        let data = serde_json::to_vec(value)
+            .map_err(|e| P2PError::Stream(format!("Failed to encode message: {}", e)))?;
+        self.send(&data).await
+    }
+
+    /// Receive an encrypted frame and deserialize it from JSON
+    pub async fn recv_json<T: DeserializeOwned>(&mut self, max_size: usize) -> Result<T, P2PError> {
+        let data = self.recv(max_size).await?;
+        serde_json::from_slice(&data)
+            .map_err(|e| P2PError::Stream(format!("Invalid message: {}", e)))
+    }
+
+    /// Finish the send side of the stream
+    pub async fn finish(&mut self) -> Result<(), P2PError> {
+        self.stream.finish().await
+    }
+
+    /// Get the peer's secure channel identity
+    pub fn peer_identity(&self) -> &Identity {
+        self.codec.channel.peer_identity()
+    }
+
+    /// Get the secure channel
+    pub fn channel(&self) -> &SecureChannel {
+        &self.codec.channel
+    }
+
+    /// Get the underlying stream
+    pub fn stream(&self) -> &BiStream {
+        &self.stream
+    }
+
+    /// Split into halves that can send and receive concurrently
+    ///
+    /// Frames are only ever partially read inside [`SecureReceiver::recv`],
+    /// so a long-lived stream can be read in one task while another writes,
+    /// instead of racing reads and writes in a `select!`.
+    pub fn split(self) -> (SecureSender, SecureReceiver) {
+        let (send, recv) = self.stream.split();
+        (
+            SecureSender {
+                send,
+                codec: self.codec.clone(),
+            },
+            SecureReceiver {
+                recv,
+                codec: self.codec,
+            },
+        )
+    }
+}
+
+/// Sending half of a [`SecureStream`]
+pub struct SecureSender {
+    send: SendStream,
+    codec: FrameCodec,
+}
+
+impl SecureSender {
+    /// Encrypt and send a frame
+    pub async fn send(&mut self, data: &[u8]) -> Result<(), P2PError> {
+        let bytes = self.codec.seal(data)?;
+        let len = u32::try_from(bytes.len())
+            .map_err(|_| P2PError::Stream("Frame too large".to_string()))?;
+        for part in [&len.to_be_bytes()[..], &bytes] {
+            self.send
+                .write_all(part)
+                .await
+                .map_err(|e| P2PError::Stream(format!("Write failed: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// Serialize a value as JSON and send it as an encrypted frame
+    pub async fn send_json<T: Serialize>(&mut self, value: &T) -> Result<(), P2PError> {
+        let data = serde_json::to_vec(value)
+            .map_err(|e| P2PError::Stream(format!("Failed to encode message: {}", e)))?;
+        self.send(&data).await
+    }
+
+    /// Finish the send side of the stream
+    pub fn finish(&mut self) -> Result<(), P2PError> {
+        self.send
+            .finish()
+            .map_err(|e| P2PError::Stream(format!("Finish failed: {}", e)))
+    }
+}
+
+/// Receiving half of a [`SecureStream`]
+pub struct SecureReceiver {
+    recv: RecvStream,
+    codec: FrameCodec,
+}
+
+impl SecureReceiver {
+    /// Receive and decrypt a frame
+    pub async fn recv(&mut self, max_size: usize) -> Result<Vec<u8>, P2PError> {
+        let read_failed = |e| P2PError::Stream(format!("Read exact failed: {}", e));
+        let mut len_buf = [0u8; 4];
+        self.recv
+            .read_exact(&mut len_buf)
+            .await
+            .map_err(read_failed)?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        if len > max_size {
+            return Err(P2PError::Stream(format!(
+                "Message too large: {} > {}",
+                len, max_size
+            )));
+        }
+
+        let mut bytes = vec![0u8; len];
+        self.recv
+            .read_exact(&mut bytes)
+            .await
+            .map_err(read_failed)?;
+        self.codec.open(&bytes)
+    }
+
+    /// Receive an encrypted frame and deserialize it from JSON
+    pub async fn recv_json<T: DeserializeOwned>(&mut self, max_size: usize) -> Result<T, P2PError> {
+        let data = self.recv(max_size).await?;
+        serde_json::from_slice(&data)
+            .map_err(|e| P2PError::Stream(format!("Invalid message: {}", e)))
+    }
+}
@@ -0,0 +1,366 @@
+//! Shareable connection tickets
+//!
+//! A ticket bundles a peer's NodeId with its home relay and direct addresses
+//! into one short string that can be pasted, scanned from a QR code or sent
+//! over chat. Tickets without addressing information are still valid: the
+//! endpoint then resolves the NodeId through discovery (n0 DNS and, with the
+//! `dht` feature, the mainline DHT).
+//!
+//! Besides the compact `russh1...` form, parsing also accepts the older
+//! `russh://<node_id>?relay=<url>` URIs and bare NodeIds.
+//!
+//! # Requirements Coverage
+//! - Requirement 3.1: Iroh QUIC implementation for transport
+//! - Requirement 3.3: Relay server fallback
+
+use crate::error::P2PError;
+use crate::p2p::endpoint::P2PEndpoint;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use iroh::{NodeAddr, NodeId, RelayUrl};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+
+/// Prefix of the compact ticket encoding (includes the format version)
+pub const TICKET_PREFIX: &str = "russh1";
+
+/// URI scheme accepted for legacy tickets
+pub const TICKET_URI_SCHEME: &str = "russh://";
+
+/// Maximum number of direct addresses carried in a ticket
+pub const MAX_TICKET_ADDRESSES: usize = 16;
+
+const ADDR_V4: u8 = 4;
+const ADDR_V6: u8 = 6;
+
+/// Length of a NodeId written in base32 or hex
+const NODE_ID_BASE32_LEN: usize = 52;
+const NODE_ID_HEX_LEN: usize = 64;
+
+/// Everything needed to dial a peer, encoded as a pasteable string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerTicket {
+    /// The peer's NodeId
+    node_id: NodeId,
+    /// The peer's home relay, if known
+    relay_url: Option<RelayUrl>,
+    /// Direct addresses the peer was reachable on
+    direct_addresses: Vec<SocketAddr>,
+}
+
+impl PeerTicket {
+    /// Create a ticket carrying only a NodeId
+    pub fn new(node_id: NodeId) -> Self {
+        Self {
+            node_id,
+            relay_url: None,
+            direct_addresses: Vec::new(),
+        }
+    }
+
+    /// Set the home relay
+    pub fn with_relay_url(mut self, relay_url: RelayUrl) -> Self {
+        self.relay_url = Some(relay_url);
+        self
+    }
+
+    /// Set the direct addresses, keeping at most [`MAX_TICKET_ADDRESSES`]
+    pub fn with_direct_addresses(mut self, addrs: impl IntoIterator<Item = SocketAddr>) -> Self {
+        self.direct_addresses = addrs.into_iter().take(MAX_TICKET_ADDRESSES).collect();
+        self
+    }
+
+    /// Create a ticket for our own endpoint
+    pub async fn from_endpoint(endpoint: &P2PEndpoint) -> Result<Self, P2PError> {
+        Ok(endpoint.node_addr().await?.into())
+    }
+
+    /// Get the peer's NodeId
+    pub fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    /// Get the peer's home relay
+    pub fn relay_url(&self) -> Option<&RelayUrl> {
+        self.relay_url.as_ref()
+    }
+
+    /// Get the peer's direct addresses
+    pub fn direct_addresses(&self) -> &[SocketAddr] {
+        &self.direct_addresses
+    }
+
+    /// Whether the NodeId must be resolved through discovery before dialing
+    pub fn needs_lookup(&self) -> bool {
+        self.relay_url.is_none() && self.direct_addresses.is_empty()
+    }
+
+    /// Convert to an iroh node address
+    pub fn node_addr(&self) -> NodeAddr {
+        NodeAddr::from_parts(
+            self.node_id,
+            self.relay_url.clone(),
+            self.direct_addresses.iter().copied(),
+        )
+    }
+
+    /// Format as a legacy `russh://` URI
+    pub fn to_uri(&self) -> String {
+        let mut uri = format!("{}{}", TICKET_URI_SCHEME, self.node_id);
+        let mut separator = '?';
+        if let Some(relay) = &self.relay_url {
+            uri.push_str(&format!("{}relay={}", separator, relay));
+            separator = '&';
+        }
+        for addr in &self.direct_addresses {
+            uri.push_str(&format!("{}addr={}", separator, addr));
+            separator = '&';
+        }
+        uri
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(64);
+        out.extend_from_slice(self.node_id.as_bytes());
+
+        let relay = self
+            .relay_url
+            .as_ref()
+            .map(|url| url.to_string())
+            .filter(|url| url.len() <= u8::MAX as usize)
+            .unwrap_or_default();
+        out.push(relay.len() as u8);
+        out.extend_from_slice(relay.as_bytes());
+
+        out.push(self.direct_addresses.len() as u8);
+        for addr in &self.direct_addresses {
+            match addr.ip() {
+                IpAddr::V4(ip) => {
+                    out.push(ADDR_V4);
+                    out.extend_from_slice(&ip.octets());
+                }
+                IpAddr::V6(ip) => {
+                    out.push(ADDR_V6);
+                    out.extend_from_slice(&ip.octets());
+                }
+            }
+            out.extend_from_slice(&addr.port().to_be_bytes());
+        }
+        out
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self, P2PError> {
+        let mut reader = Reader(bytes);
+
+        let node_id = NodeId::from_bytes(&reader.array::<32>()?)
+            .map_err(|e| invalid(format!("bad node id: {}", e)))?;
+
+        let relay_len = reader.u8()? as usize;
+        let relay_url = if relay_len == 0 {
+            None
+        } else {
+            let raw = std::str::from_utf8(reader.take(relay_len)?)
+                .map_err(|_| invalid("relay URL is not UTF-8"))?;
+            Some(parse_relay(raw)?)
+        };
+
+        let count = reader.u8()? as usize;
+        if count > MAX_TICKET_ADDRESSES {
+            return Err(invalid(format!("too many addresses ({})", count)));
+        }
+        let mut direct_addresses = Vec::with_capacity(count);
+        for _ in 0..count {
+            let ip = match reader.u8()? {
+                ADDR_V4 => IpAddr::V4(Ipv4Addr::from(reader.array::<4>()?)),
+                ADDR_V6 => IpAddr::V6(Ipv6Addr::from(reader.array::<16>()?)),
+                other => return Err(invalid(format!("unknown address family {}", other))),
+            };
+            let port = u16::from_be_bytes(reader.array::<2>()?);
+            direct_addresses.push(SocketAddr::new(ip, port));
+        }
+
+        if !reader.0.is_empty() {
+            return Err(invalid("trailing data"));
+        }
+
+        Ok(Self {
+            node_id,
+            relay_url,
+            direct_addresses,
+        })
+    }
+
+    fn from_uri(rest: &str) -> Result<Self, P2PError> {
+        let (node, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let mut ticket = Self::new(parse_node_id(node.trim_end_matches('/'))?);
+
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match key {
+                "relay" => ticket.relay_url = Some(parse_relay(value)?),
+                "addr" if ticket.direct_addresses.len() < MAX_TICKET_ADDRESSES => {
+                    let addr = value
+                        .parse()
+                        .map_err(|_| invalid(format!("bad address '{}'", value)))?;
+                    ticket.direct_addresses.push(addr);
+                }
+                _ => {}
+            }
+        }
+        Ok(ticket)
+    }
+}
+
+impl From<NodeAddr> for PeerTicket {
+    fn from(addr: NodeAddr) -> Self {
+        let mut ticket = Self::new(addr.node_id).with_direct_addresses(addr.direct_addresses);
+        ticket.relay_url = addr.relay_url;
+        ticket
+    }
+}
+
+impl From<NodeId> for PeerTicket {
+    fn from(node_id: NodeId) -> Self {
+        Self::new(node_id)
+    }
+}
+
+impl fmt::Display for PeerTicket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            TICKET_PREFIX,
+            URL_SAFE_NO_PAD.encode(self.to_bytes())
+        )
+    }
+}
+
+impl FromStr for PeerTicket {
+    type Err = P2PError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(rest) = s.strip_prefix(TICKET_URI_SCHEME) {
+            return Self::from_uri(rest);
+        }
+        if let Some(encoded) = s.strip_prefix(TICKET_PREFIX) {
+            let bytes = URL_SAFE_NO_PAD
+                .decode(encoded)
+                .map_err(|e| invalid(format!("bad encoding: {}", e)))?;
+            return Self::from_bytes(&bytes);
+        }
+        parse_node_id(s).map(Self::new)
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> P2PError {
+    P2PError::InvalidTicket(reason.into())
+}
+
+fn parse_node_id(s: &str) -> Result<NodeId, P2PError> {
+    // iroh panics on ids that are neither 52 base32 nor 64 hex characters
+    if !matches!(s.len(), NODE_ID_BASE32_LEN | NODE_ID_HEX_LEN) {
+        return Err(invalid(format!("'{}' is not a ticket or node id", s)));
+    }
+    s.parse()
+        .map_err(|_| invalid(format!("'{}' is not a ticket or node id", s)))
+}
+
+fn parse_relay(s: &str) -> Result<RelayUrl, P2PError> {
+    s.parse()
+        .map_err(|e| invalid(format!("bad relay URL '{}': {}", s, e)))
+}
+
+/// Cursor over the binary ticket payload
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], P2PError> {
+        if self.0.len() < len {
+            return Err(invalid("ticket is truncated"));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, P2PError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], P2PError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::SecretKey;
+
+    fn node_id() -> NodeId {
+        SecretKey::generate(rand::rngs::OsRng).public()
+    }
+
+    fn full_ticket() -> PeerTicket {
+        PeerTicket::new(node_id())
+            .with_relay_url("https://relay.example.com./".parse().unwrap())
+            .with_direct_addresses([
+                "192.168.1.20:4433".parse().unwrap(),
+                "[2001:db8::1]:4433".parse().unwrap(),
+            ])
+    }
+
+    #[test]
+    fn compact_ticket_roundtrips() {
+        let ticket = full_ticket();
+        let encoded = ticket.to_string();
+
+        assert!(encoded.starts_with(TICKET_PREFIX));
+        assert!(!encoded.contains(['+', '/', '=', ' ']));
+        assert_eq!(encoded.parse::<PeerTicket>().unwrap(), ticket);
+    }
+
+    #[test]
+    fn bare_node_id_and_uri_are_accepted() {
+        let ticket = full_ticket();
+        assert_eq!(ticket.to_uri().parse::<PeerTicket>().unwrap(), ticket);
+
+        let bare: PeerTicket = ticket.node_id().to_string().parse().unwrap();
+        assert_eq!(bare.node_id(), ticket.node_id());
+        assert!(bare.needs_lookup());
+        assert!(!ticket.needs_lookup());
+    }
+
+    #[test]
+    fn node_addr_conversion_keeps_addressing() {
+        let ticket = full_ticket();
+        let addr = ticket.node_addr();
+
+        assert_eq!(addr.node_id, ticket.node_id());
+        assert_eq!(addr.relay_url.as_ref(), ticket.relay_url());
+        assert_eq!(PeerTicket::from(addr), ticket);
+    }
+
+    #[test]
+    fn malformed_tickets_are_rejected() {
+        let encoded = full_ticket().to_string();
+
+        for input in [
+            "",
+            "not-a-ticket",
+            "russh1!!!",
+            &encoded[..encoded.len() - 4],
+            &format!("{}AAAA", encoded),
+        ] {
+            assert!(
+                matches!(input.parse::<PeerTicket>(), Err(P2PError::InvalidTicket(_))),
+                "accepted {:?}",
+                input
+            );
+        }
+    }
+}
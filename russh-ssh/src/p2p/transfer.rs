@@ -0,0 +1,852 @@
+//! Direct file transfer between peers
+//!
+//! Files and directories are sent over a tunnel stream to the peer's
+//! `transfer` service. The sender first ships a manifest listing every file
+//! with its size, BLAKE3 hash and per-chunk hashes. The receiver checks any
+//! partial download it already has against the chunk hashes and answers
+//! with the offset to resume each file from, so an interrupted transfer
+//! only re-sends what is missing. Every chunk is verified on arrival and
+//! every file is re-hashed before it is moved into place.
+//!
+//! Progress is published as [`RusshEvent::Transfer`] on the event bus.
+//! With a [`TransferScheduler`] attached, every chunk sent or received is
+//! paced as [`TransferClass::Bulk`] traffic. A [`TransferApproval`] gets to
+//! refuse each incoming transfer once its manifest is known.
+//!
+//! # Requirements Coverage
+//! - Requirement 3.4: Multiplexed bidirectional streams
+
+use crate::error::P2PError;
+use crate::events::{EventBus, RusshEvent};
+use crate::p2p::acl::PeerCapability;
+use crate::p2p::connection::P2PConnectionManager;
+use crate::p2p::stream::{BiStream, StreamExt};
+use crate::p2p::tunnel::{open_tunnel, StreamHandler, TunnelAgent};
+use crate::scheduler::{TransferClass, TransferScheduler};
+use async_trait::async_trait;
+use iroh::NodeId;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use uuid::Uuid;
+
+/// Service name of the file transfer protocol
+pub const TRANSFER_SERVICE: &str = "transfer";
+
+/// Default size of a transfer chunk
+pub const DEFAULT_TRANSFER_CHUNK_SIZE: u32 = 1024 * 1024;
+
+/// Largest chunk size a receiver accepts
+pub const MAX_TRANSFER_CHUNK_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Suffix of partially received files
+pub const PARTIAL_SUFFIX: &str = ".russh-part";
+
+/// Largest manifest a receiver accepts
+const MAX_MANIFEST_SIZE: usize = 16 * 1024 * 1024;
+
+/// Largest control reply
+const MAX_REPLY_SIZE: usize = 64 * 1024;
+
+/// One file in a transfer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferFile {
+    /// Path relative to the destination, `/`-separated
+    pub path: String,
+    /// Size in bytes
+    pub size: u64,
+    /// BLAKE3 hash of the whole file (hex)
+    pub hash: String,
+    /// BLAKE3 hash of each chunk (hex)
+    pub chunks: Vec<String>,
+}
+
+/// Everything the receiver needs to know before data flows
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferManifest {
+    /// Transfer identifier
+    pub id: Uuid,
+    /// Size of every chunk except the last of each file
+    pub chunk_size: u32,
+    /// Files in the order they are sent
+    pub files: Vec<TransferFile>,
+}
+
+impl TransferManifest {
+    /// Hash a file or directory tree into a manifest
+    ///
+    /// Paths in the manifest start with the file or directory name.
+    /// Symlinks and special files are skipped. This reads every file, so
+    /// call it from a blocking context.
+    pub fn from_path(path: &Path, chunk_size: u32) -> Result<Self, P2PError> {
+        if chunk_size == 0 || chunk_size > MAX_TRANSFER_CHUNK_SIZE {
+            return Err(P2PError::Transfer(format!(
+                "invalid chunk size {}",
+                chunk_size
+            )));
+        }
+
+        let path = path.canonicalize().map_err(io_error)?;
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| P2PError::Transfer(format!("cannot send '{}'", path.display())))?
+            .to_string();
+
+        let mut files = Vec::new();
+        collect_files(&path, name, chunk_size, &mut files)?;
+        Ok(Self {
+            id: Uuid::new_v4(),
+            chunk_size,
+            files,
+        })
+    }
+
+    /// Total size of all files
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+
+    /// Check a manifest received from a peer
+    pub fn validate(&self) -> Result<(), P2PError> {
+        if self.chunk_size == 0 || self.chunk_size > MAX_TRANSFER_CHUNK_SIZE {
+            return Err(P2PError::Transfer(format!(
+                "invalid chunk size {}",
+                self.chunk_size
+            )));
+        }
+
+        let mut seen = HashSet::new();
+        for file in &self.files {
+            safe_relative_path(&file.path)?;
+            if !seen.insert(file.path.as_str()) {
+                return Err(P2PError::Transfer(format!(
+                    "duplicate path '{}'",
+                    file.path
+                )));
+            }
+            if file.chunks.len() as u64 != chunk_count(file.size, self.chunk_size) {
+                return Err(P2PError::Transfer(format!(
+                    "chunk list of '{}' does not match its size",
+                    file.path
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of a finished transfer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferReport {
+    /// Transfer identifier
+    pub id: Uuid,
+    /// The other peer
+    pub peer_id: NodeId,
+    /// Local paths that were sent or written
+    pub files: Vec<PathBuf>,
+    /// Bytes that crossed the network
+    pub bytes_transferred: u64,
+    /// Bytes skipped because the receiver already had them
+    pub resumed_bytes: u64,
+}
+
+/// Decides whether an incoming transfer is accepted
+///
+/// Asked once the manifest has been received and validated, before any
+/// data is sent; the sender waits for the answer.
+#[async_trait]
+pub trait TransferApproval: Send + Sync {
+    /// Accept the transfer, or refuse it with a reason for the sender
+    async fn approve(&self, peer_id: NodeId, manifest: &TransferManifest) -> Result<(), String>;
+}
+
+/// Control messages sent by the receiver
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum TransferReply {
+    /// Send each file starting at the given byte offset
+    Accept { resume_from: Vec<u64> },
+    /// The transfer was refused
+    Reject { reason: String },
+    /// Every file was received and verified
+    Complete,
+    /// Receiving failed
+    Failed { reason: String },
+}
+
+/// Sends files to peers and receives files from them
+///
+/// Register it with a [`TunnelAgent`] (see [`FileTransfer::register`]) to
+/// accept incoming transfers into the download directory.
+#[derive(Clone)]
+pub struct FileTransfer {
+    manager: Arc<P2PConnectionManager>,
+    download_dir: PathBuf,
+    chunk_size: u32,
+    event_bus: Option<EventBus>,
+    scheduler: Option<TransferScheduler>,
+    approval: Option<Arc<dyn TransferApproval>>,
+    capability: PeerCapability,
+}
+
+impl FileTransfer {
+    /// Create a transfer service saving incoming files to `download_dir`
+    pub fn new(manager: Arc<P2PConnectionManager>, download_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            manager,
+            download_dir: download_dir.into(),
+            chunk_size: DEFAULT_TRANSFER_CHUNK_SIZE,
+            event_bus: None,
+            scheduler: None,
+            approval: None,
+            capability: PeerCapability::FileSync,
+        }
+    }
+
+    /// Set the chunk size used when sending
+    pub fn with_chunk_size(mut self, chunk_size: u32) -> Self {
+        self.chunk_size = chunk_size.clamp(1, MAX_TRANSFER_CHUNK_SIZE);
+        self
+    }
+
+    /// Publish progress events to the given event bus
+    pub fn with_event_bus(mut self, bus: EventBus) -> Self {
+        self.event_bus = Some(bus);
+        self
+    }
+
+    /// Pace chunks through a shared transfer scheduler
+    pub fn with_scheduler(mut self, scheduler: TransferScheduler) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    /// Ask `approval` before accepting each incoming transfer
+    pub fn with_approval(mut self, approval: Arc<dyn TransferApproval>) -> Self {
+        self.approval = Some(approval);
+        self
+    }
+
+    /// Capability senders need, [`PeerCapability::FileSync`] by default
+    pub fn with_capability(mut self, capability: PeerCapability) -> Self {
+        self.capability = capability;
+        self
+    }
+
+    /// Get the directory incoming files are saved to
+    pub fn download_dir(&self) -> &Path {
+        &self.download_dir
+    }
+
+    /// Serve incoming transfers from peers with the required capability
+    pub fn register(self, agent: TunnelAgent) -> TunnelAgent {
+        let capability = self.capability;
+        agent.with_handler(TRANSFER_SERVICE, capability, Arc::new(self))
+    }
+
+    /// Send a file or directory to a peer
+    pub async fn send(&self, peer_id: NodeId, path: &Path) -> Result<TransferReport, P2PError> {
+        let chunk_size = self.chunk_size;
+        let source = path.to_path_buf();
+        let (manifest, base) = blocking(move || {
+            let manifest = TransferManifest::from_path(&source, chunk_size)?;
+            let base = source
+                .canonicalize()
+                .map_err(io_error)?
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            Ok((manifest, base))
+        })
+        .await?;
+
+        let mut stream = open_tunnel(&self.manager, peer_id, TRANSFER_SERVICE).await?;
+        send_json(&mut stream, &manifest).await?;
+
+        let resume_from = match recv_json(&mut stream, MAX_REPLY_SIZE).await? {
+            TransferReply::Accept { resume_from } if resume_from.len() == manifest.files.len() => {
+                resume_from
+            }
+            TransferReply::Reject { reason } => {
+                return Err(P2PError::Transfer(format!(
+                    "peer rejected transfer: {}",
+                    reason
+                )))
+            }
+            other => return Err(P2PError::Transfer(format!("unexpected reply {:?}", other))),
+        };
+
+        tracing::info!(
+            peer_id = %peer_id,
+            transfer_id = %manifest.id,
+            files = manifest.files.len(),
+            bytes = manifest.total_bytes(),
+            "Sending files"
+        );
+
+        let mut report = TransferReport {
+            id: manifest.id,
+            peer_id,
+            files: Vec::with_capacity(manifest.files.len()),
+            bytes_transferred: 0,
+            resumed_bytes: 0,
+        };
+        let mut buf = vec![0u8; manifest.chunk_size as usize];
+
+        for (file, &offset) in manifest.files.iter().zip(&resume_from) {
+            let chunk_size = manifest.chunk_size as u64;
+            if offset > file.size || (offset % chunk_size != 0 && offset != file.size) {
+                return Err(P2PError::Transfer(format!(
+                    "invalid resume offset {} for '{}'",
+                    offset, file.path
+                )));
+            }
+
+            let local = base.join(safe_relative_path(&file.path)?);
+            let mut source = tokio::fs::File::open(&local).await.map_err(io_error)?;
+            source
+                .seek(SeekFrom::Start(offset))
+                .await
+                .map_err(io_error)?;
+
+            let mut done = offset;
+            while done < file.size {
+                let len = (file.size - done).min(chunk_size) as usize;
+                source.read_exact(&mut buf[..len]).await.map_err(io_error)?;
+                self.throttle(len).await;
+                stream.write(&buf[..len]).await?;
+                done += len as u64;
+                self.emit_progress(manifest.id, &file.path, done, file.size);
+            }
+            if offset == file.size {
+                self.emit_progress(manifest.id, &file.path, done, file.size);
+            }
+
+            report.bytes_transferred += file.size - offset;
+            report.resumed_bytes += offset;
+            report.files.push(local);
+        }
+
+        match recv_json(&mut stream, MAX_REPLY_SIZE).await? {
+            TransferReply::Complete => {
+                stream.finish().await?;
+                Ok(report)
+            }
+            TransferReply::Failed { reason } => Err(P2PError::Transfer(reason)),
+            other => Err(P2PError::Transfer(format!("unexpected reply {:?}", other))),
+        }
+    }
+
+    /// Receive a transfer on a stream accepted from a peer
+    pub async fn receive(
+        &self,
+        peer_id: NodeId,
+        mut stream: BiStream,
+    ) -> Result<TransferReport, P2PError> {
+        let manifest: TransferManifest = recv_json(&mut stream, MAX_MANIFEST_SIZE).await?;
+        if let Err(e) = manifest.validate() {
+            send_json(
+                &mut stream,
+                &TransferReply::Reject {
+                    reason: e.to_string(),
+                },
+            )
+            .await?;
+            stream.finish().await?;
+            return Err(e);
+        }
+        if let Some(approval) = &self.approval {
+            if let Err(reason) = approval.approve(peer_id, &manifest).await {
+                send_json(
+                    &mut stream,
+                    &TransferReply::Reject {
+                        reason: reason.clone(),
+                    },
+                )
+                .await?;
+                stream.finish().await?;
+                return Err(P2PError::Transfer(format!("transfer refused: {}", reason)));
+            }
+        }
+
+        let plans = {
+            let manifest = manifest.clone();
+            let download_dir = self.download_dir.clone();
+            blocking(move || plan_receive(&download_dir, &manifest)).await?
+        };
+        let resume_from: Vec<u64> = plans.iter().map(|plan| plan.offset).collect();
+        send_json(
+            &mut stream,
+            &TransferReply::Accept {
+                resume_from: resume_from.clone(),
+            },
+        )
+        .await?;
+
+        tracing::info!(
+            peer_id = %peer_id,
+            transfer_id = %manifest.id,
+            files = manifest.files.len(),
+            resumed = resume_from.iter().sum::<u64>(),
+            "Receiving files"
+        );
+
+        match self
+            .receive_files(peer_id, &manifest, plans, &mut stream)
+            .await
+        {
+            Ok(report) => {
+                send_json(&mut stream, &TransferReply::Complete).await?;
+                stream.finish().await?;
+                Ok(report)
+            }
+            Err(e) => {
+                // Best effort: the stream may already be broken
+                let _ = send_json(
+                    &mut stream,
+                    &TransferReply::Failed {
+                        reason: e.to_string(),
+                    },
+                )
+                .await;
+                let _ = stream.finish().await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn receive_files(
+        &self,
+        peer_id: NodeId,
+        manifest: &TransferManifest,
+        plans: Vec<ReceivePlan>,
+        stream: &mut BiStream,
+    ) -> Result<TransferReport, P2PError> {
+        let chunk_size = manifest.chunk_size as u64;
+        let mut report = TransferReport {
+            id: manifest.id,
+            peer_id,
+            files: Vec::with_capacity(plans.len()),
+            bytes_transferred: 0,
+            resumed_bytes: 0,
+        };
+        let mut buf = vec![0u8; manifest.chunk_size as usize];
+
+        for (file, plan) in manifest.files.iter().zip(plans) {
+            if plan.complete {
+                self.emit_progress(manifest.id, &file.path, file.size, file.size);
+                report.resumed_bytes += file.size;
+                report.files.push(plan.target);
+                continue;
+            }
+
+            if let Some(parent) = plan.partial.parent() {
+                tokio::fs::create_dir_all(parent).await.map_err(io_error)?;
+            }
+            let mut partial = tokio::fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&plan.partial)
+                .await
+                .map_err(io_error)?;
+            partial.set_len(plan.offset).await.map_err(io_error)?;
+            partial
+                .seek(SeekFrom::Start(plan.offset))
+                .await
+                .map_err(io_error)?;
+
+            let mut done = plan.offset;
+            while done < file.size {
+                let index = (done / chunk_size) as usize;
+                let len = (file.size - done).min(chunk_size) as usize;
+                self.throttle(len).await;
+                stream.read_exact(&mut buf[..len]).await?;
+                if blake3::hash(&buf[..len]).to_hex().as_str() != file.chunks[index] {
+                    return Err(P2PError::Transfer(format!(
+                        "chunk {} of '{}' failed verification",
+                        index, file.path
+                    )));
+                }
+                partial.write_all(&buf[..len]).await.map_err(io_error)?;
+                done += len as u64;
+                self.emit_progress(manifest.id, &file.path, done, file.size);
+            }
+            partial.sync_all().await.map_err(io_error)?;
+            drop(partial);
+
+            let target = {
+                let partial = plan.partial.clone();
+                let target = plan.target.clone();
+                let expected = file.hash.clone();
+                blocking(move || finish_file(&partial, &target, &expected)).await?
+            };
+
+            report.bytes_transferred += file.size - plan.offset;
+            report.resumed_bytes += plan.offset;
+            report.files.push(target);
+        }
+
+        Ok(report)
+    }
+
+    async fn throttle(&self, len: usize) {
+        if let Some(scheduler) = &self.scheduler {
+            scheduler.acquire(TransferClass::Bulk, len as u64).await;
+        }
+    }
+
+    fn emit_progress(&self, transfer_id: Uuid, path: &str, bytes: u64, total: u64) {
+        if let Some(bus) = &self.event_bus {
+            bus.publish(RusshEvent::Transfer {
+                transfer_id,
+                path: path.to_string(),
+                bytes,
+                total: Some(total),
+                done: bytes == total,
+            });
+        }
+    }
+}
+
+#[async_trait]
+impl StreamHandler for FileTransfer {
+    async fn handle(&self, peer_id: NodeId, stream: BiStream) -> Result<(), P2PError> {
+        self.receive(peer_id, stream).await.map(|_| ())
+    }
+}
+
+/// Where a received file goes and how much of it is already there
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ReceivePlan {
+    target: PathBuf,
+    partial: PathBuf,
+    offset: u64,
+    /// The target already holds the exact file
+    complete: bool,
+}
+
+fn plan_receive(
+    download_dir: &Path,
+    manifest: &TransferManifest,
+) -> Result<Vec<ReceivePlan>, P2PError> {
+    manifest
+        .files
+        .iter()
+        .map(|file| {
+            let target = download_dir.join(safe_relative_path(&file.path)?);
+            let partial = partial_path(&target);
+
+            if target.is_file() && hash_file(&target).ok().as_deref() == Some(&file.hash) {
+                return Ok(ReceivePlan {
+                    target,
+                    partial,
+                    offset: file.size,
+                    complete: true,
+                });
+            }
+
+            let offset = verified_prefix(&partial, file, manifest.chunk_size);
+            Ok(ReceivePlan {
+                target,
+                partial,
+                offset,
+                complete: false,
+            })
+        })
+        .collect()
+}
+
+/// Number of leading bytes of a partial file that match the manifest,
+/// rounded down to whole chunks
+fn verified_prefix(partial: &Path, file: &TransferFile, chunk_size: u32) -> u64 {
+    let Ok(mut reader) = File::open(partial) else {
+        return 0;
+    };
+
+    let chunk_size = chunk_size as u64;
+    let mut buf = vec![0u8; chunk_size as usize];
+    let mut offset = 0u64;
+    for expected in &file.chunks {
+        let len = (file.size - offset).min(chunk_size) as usize;
+        if reader.read_exact(&mut buf[..len]).is_err()
+            || blake3::hash(&buf[..len]).to_hex().as_str() != expected
+        {
+            break;
+        }
+        offset += len as u64;
+    }
+    offset
+}
+
+/// Verify a completed partial file and move it into place
+fn finish_file(partial: &Path, target: &Path, expected: &str) -> Result<PathBuf, P2PError> {
+    let actual = hash_file(partial).map_err(io_error)?;
+    if actual != expected {
+        let _ = std::fs::remove_file(partial);
+        return Err(P2PError::Transfer(format!(
+            "'{}' failed verification",
+            target.display()
+        )));
+    }
+
+    let target = unique_path(target);
+    std::fs::rename(partial, &target).map_err(io_error)?;
+    Ok(target)
+}
+
+fn collect_files(
+    path: &Path,
+    relative: String,
+    chunk_size: u32,
+    files: &mut Vec<TransferFile>,
+) -> Result<(), P2PError> {
+    let metadata = std::fs::symlink_metadata(path).map_err(io_error)?;
+
+    if metadata.is_file() {
+        let (hash, chunks) = hash_chunks(path, chunk_size).map_err(io_error)?;
+        files.push(TransferFile {
+            path: relative,
+            size: metadata.len(),
+            hash,
+            chunks,
+        });
+    } else if metadata.is_dir() {
+        let mut entries: Vec<_> = std::fs::read_dir(path)
+            .map_err(io_error)?
+            .collect::<Result<_, _>>()
+            .map_err(io_error)?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+                tracing::warn!(path = %entry.path().display(), "Skipping non-UTF-8 file name");
+                continue;
+            };
+            collect_files(
+                &entry.path(),
+                format!("{}/{}", relative, name),
+                chunk_size,
+                files,
+            )?;
+        }
+    } else {
+        tracing::debug!(path = %path.display(), "Skipping special file");
+    }
+    Ok(())
+}
+
+/// Hash a file and each of its chunks
+fn hash_chunks(path: &Path, chunk_size: u32) -> std::io::Result<(String, Vec<String>)> {
+    let mut reader = File::open(path)?;
+    let mut whole = blake3::Hasher::new();
+    let mut chunks = Vec::new();
+    let mut buf = vec![0u8; chunk_size as usize];
+
+    loop {
+        let len = read_full(&mut reader, &mut buf)?;
+        if len == 0 {
+            break;
+        }
+        whole.update(&buf[..len]);
+        chunks.push(blake3::hash(&buf[..len]).to_hex().to_string());
+        if len < buf.len() {
+            break;
+        }
+    }
+    Ok((whole.finalize().to_hex().to_string(), chunks))
+}
+
+fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Read until the buffer is full or the file ends
+fn read_full(reader: &mut File, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+fn chunk_count(size: u64, chunk_size: u32) -> u64 {
+    size.div_ceil(chunk_size as u64)
+}
+
+/// Turn a manifest path into a relative path that cannot escape the
+/// destination directory
+fn safe_relative_path(path: &str) -> Result<PathBuf, P2PError> {
+    let mut out = PathBuf::new();
+    for part in path.split('/') {
+        if part.is_empty() || part == "." || part == ".." || part.contains(['\\', ':', '\0']) {
+            return Err(P2PError::Transfer(format!("unsafe path '{}'", path)));
+        }
+        out.push(part);
+    }
+    Ok(out)
+}
+
+fn partial_path(target: &Path) -> PathBuf {
+    let mut name = target.as_os_str().to_os_string();
+    name.push(PARTIAL_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// First of `name`, `name (1)`, `name (2)`, ... that does not exist yet
+fn unique_path(target: &Path) -> PathBuf {
+    if !target.exists() {
+        return target.to_path_buf();
+    }
+
+    let stem = target
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = target
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (1u32..)
+        .map(|n| target.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_else(|| target.to_path_buf())
+}
+
+async fn send_json<T: Serialize>(stream: &mut BiStream, value: &T) -> Result<(), P2PError> {
+    let data = serde_json::to_vec(value)
+        .map_err(|e| P2PError::Transfer(format!("Failed to encode message: {}", e)))?;
+    stream.send_message(&data).await
+}
+
+async fn recv_json<T: DeserializeOwned>(
+    stream: &mut BiStream,
+    max_size: usize,
+) -> Result<T, P2PError> {
+    let data = stream.recv_message(max_size).await?;
+    serde_json::from_slice(&data).map_err(|e| P2PError::Transfer(format!("Invalid message: {}", e)))
+}
+
+async fn blocking<T, F>(f: F) -> Result<T, P2PError>
+where
+    F: FnOnce() -> Result<T, P2PError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| P2PError::Transfer(e.to_string()))?
+}
+
+fn io_error(e: std::io::Error) -> P2PError {
+    P2PError::Transfer(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, data: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn manifest_lists_directory_with_chunk_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("photos");
+        write(&root, "b.jpg", &[7u8; 10]);
+        write(&root, "a/x.raw", b"");
+        write(&root, "a/y.raw", &[1u8; 4]);
+
+        let manifest = TransferManifest::from_path(&root, 4).unwrap();
+        let paths: Vec<_> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["photos/a/x.raw", "photos/a/y.raw", "photos/b.jpg"]);
+        assert_eq!(manifest.total_bytes(), 14);
+
+        let big = &manifest.files[2];
+        assert_eq!(big.chunks.len(), 3);
+        assert_eq!(big.chunks[2], blake3::hash(&[7u8; 2]).to_hex().to_string());
+        assert_eq!(big.hash, blake3::hash(&[7u8; 10]).to_hex().to_string());
+        assert!(manifest.files[0].chunks.is_empty());
+        manifest.validate().unwrap();
+    }
+
+    #[test]
+    fn unsafe_manifests_are_rejected() {
+        for path in ["../etc/passwd", "/abs", "a//b", "a/./b", "C:\\x", ""] {
+            assert!(safe_relative_path(path).is_err(), "accepted {:?}", path);
+        }
+
+        let mut manifest = TransferManifest {
+            id: Uuid::new_v4(),
+            chunk_size: 4,
+            files: vec![TransferFile {
+                path: "file".into(),
+                size: 9,
+                hash: String::new(),
+                chunks: vec![String::new(); 2],
+            }],
+        };
+        assert!(manifest.validate().is_err());
+
+        manifest.files[0].chunks.push(String::new());
+        manifest.validate().unwrap();
+        manifest.files.push(manifest.files[0].clone());
+        assert!(manifest.validate().is_err());
+    }
+
+    #[test]
+    fn partial_files_resume_after_last_verified_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = write(dir.path(), "src/video.bin", b"aaaabbbbcc");
+        let manifest = TransferManifest::from_path(&source, 4).unwrap();
+        let file = &manifest.files[0];
+
+        let partial = write(dir.path(), "partial", b"aaaabb");
+        assert_eq!(verified_prefix(&partial, file, 4), 4);
+
+        let corrupt = write(dir.path(), "corrupt", b"aaaXbbbb");
+        assert_eq!(verified_prefix(&corrupt, file, 4), 0);
+        assert_eq!(verified_prefix(&dir.path().join("missing"), file, 4), 0);
+
+        let download = dir.path().join("download");
+        write(&download, "video.bin.russh-part", b"aaaabbbbc");
+        let plans = plan_receive(&download, &manifest).unwrap();
+        assert_eq!(plans[0].offset, 8);
+        assert!(!plans[0].complete);
+
+        write(&download, "video.bin", b"aaaabbbbcc");
+        let plans = plan_receive(&download, &manifest).unwrap();
+        assert!(plans[0].complete);
+    }
+
+    #[test]
+    fn finished_files_are_verified_and_not_clobbered() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = write(dir.path(), "notes.txt", b"old");
+        let partial = write(dir.path(), "notes.txt.russh-part", b"new");
+
+        let expected = blake3::hash(b"new").to_hex().to_string();
+        let saved = finish_file(&partial, &target, &expected).unwrap();
+        assert_eq!(saved, dir.path().join("notes (1).txt"));
+        assert_eq!(std::fs::read(&saved).unwrap(), b"new");
+        assert_eq!(std::fs::read(&target).unwrap(), b"old");
+
+        let bad = write(dir.path(), "bad.russh-part", b"tampered");
+        assert!(finish_file(&bad, &dir.path().join("bad"), &expected).is_err());
+        assert!(!bad.exists());
+    }
+}
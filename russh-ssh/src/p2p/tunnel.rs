@@ -0,0 +1,540 @@
+//! TCP tunneling over P2P connections
+//!
+//! A tunnel carries one TCP connection inside a QUIC stream. The dialing
+//! side opens a stream, names the service it wants (for example `ssh`) and
+//! the agent on the remote machine connects to the matching local TCP
+//! address and pipes bytes both ways. This lets `SshClient` reach an sshd
+//! behind NAT without exposing port 22.
+//!
+//! Each stream starts with [`TUNNEL_MAGIC`], a one-byte service name length
+//! and the service name. The agent answers with a single [`TunnelStatus`]
+//! byte before any payload flows. Besides TCP targets, services can be
+//! handled in-process by a [`StreamHandler`] (for example file transfers).
+//!
+//! # Requirements Coverage
+//! - Requirement 3.4: Multiplexed bidirectional streams
+//! - Requirement 4.2: Mutual authentication
+
+use crate::error::P2PError;
+use crate::p2p::acl::PeerCapability;
+use crate::p2p::connection::{P2PConnection, P2PConnectionManager};
+use crate::p2p::stream::{BiStream, StreamManager};
+use async_trait::async_trait;
+use iroh::endpoint::{RecvStream, SendStream};
+use iroh::NodeId;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// Magic bytes (with version) that open every tunnel stream
+pub const TUNNEL_MAGIC: &[u8; 8] = b"RSSHTUN1";
+
+/// Service name of the SSH tunnel
+pub const SSH_SERVICE: &str = "ssh";
+
+/// Default sshd address the agent forwards to
+pub const DEFAULT_SSH_TARGET: SocketAddr =
+    SocketAddr::new(std::net::IpAddr::V4(Ipv4Addr::LOCALHOST), 22);
+
+/// How long the agent waits for a stream's header
+const HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Buffer size used when piping bytes
+const PIPE_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Loopback ports used for local tunnel listeners start here
+const LOCAL_PORT_BASE: u16 = 49152;
+
+/// Number of loopback ports tunnel listeners are spread over
+const LOCAL_PORT_RANGE: u16 = 16384;
+
+/// Agent's answer to a tunnel request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TunnelStatus {
+    /// The tunnel is open and payload follows
+    Ok,
+    /// The peer may not use this service
+    Unauthorized,
+    /// The service's TCP target could not be reached
+    Unreachable,
+    /// The agent does not offer this service
+    UnknownService,
+}
+
+impl TunnelStatus {
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Ok => 0,
+            Self::Unauthorized => 1,
+            Self::Unreachable => 2,
+            Self::UnknownService => 3,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, P2PError> {
+        match byte {
+            0 => Ok(Self::Ok),
+            1 => Ok(Self::Unauthorized),
+            2 => Ok(Self::Unreachable),
+            3 => Ok(Self::UnknownService),
+            other => Err(P2PError::Stream(format!("invalid tunnel status {}", other))),
+        }
+    }
+}
+
+/// Encode the header that opens a tunnel stream
+pub fn encode_tunnel_header(service: &str) -> Result<Vec<u8>, P2PError> {
+    if service.is_empty() || service.len() > u8::MAX as usize {
+        return Err(P2PError::Stream(format!(
+            "invalid tunnel service name '{}'",
+            service
+        )));
+    }
+
+    let mut header = Vec::with_capacity(TUNNEL_MAGIC.len() + 1 + service.len());
+    header.extend_from_slice(TUNNEL_MAGIC);
+    header.push(service.len() as u8);
+    header.extend_from_slice(service.as_bytes());
+    Ok(header)
+}
+
+/// Read a tunnel header and return the requested service name
+async fn read_tunnel_header(stream: &mut BiStream) -> Result<String, P2PError> {
+    let mut magic = [0u8; 8];
+    stream.read_exact(&mut magic).await?;
+    if &magic != TUNNEL_MAGIC {
+        return Err(P2PError::Stream("not a tunnel stream".to_string()));
+    }
+
+    let mut len = [0u8; 1];
+    stream.read_exact(&mut len).await?;
+    let mut name = vec![0u8; len[0] as usize];
+    stream.read_exact(&mut name).await?;
+    String::from_utf8(name)
+        .map_err(|_| P2PError::Stream("tunnel service name is not UTF-8".to_string()))
+}
+
+/// Open a tunnel to a service on a peer
+///
+/// Reuses an existing connection to the peer if there is one. Fails if the
+/// agent refuses the request.
+pub async fn open_tunnel(
+    manager: &P2PConnectionManager,
+    peer_id: NodeId,
+    service: &str,
+) -> Result<BiStream, P2PError> {
+    let header = encode_tunnel_header(service)?;
+    let mut stream = manager.open_bi(peer_id).await?;
+    stream.write(&header).await?;
+
+    let mut status = [0u8; 1];
+    stream.read_exact(&mut status).await?;
+    match TunnelStatus::from_byte(status[0])? {
+        TunnelStatus::Ok => Ok(stream),
+        TunnelStatus::Unauthorized => Err(P2PError::Unauthorized(format!(
+            "peer {} refused the '{}' tunnel",
+            peer_id, service
+        ))),
+        TunnelStatus::Unreachable => Err(P2PError::Stream(format!(
+            "peer {} could not reach the '{}' service",
+            peer_id, service
+        ))),
+        TunnelStatus::UnknownService => Err(P2PError::Stream(format!(
+            "peer {} does not offer the '{}' service",
+            peer_id, service
+        ))),
+    }
+}
+
+/// Loopback port a peer's tunnel listener prefers
+///
+/// Derived from the peer and service so the port is the same across runs,
+/// which keeps `known_hosts` entries for `127.0.0.1:<port>` stable.
+pub fn tunnel_port(peer_id: &NodeId, service: &str) -> u16 {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(peer_id.as_bytes());
+    hasher.update(service.as_bytes());
+    let hash = hasher.finalize();
+    let value = u16::from_be_bytes([hash.as_bytes()[0], hash.as_bytes()[1]]);
+    LOCAL_PORT_BASE + value % LOCAL_PORT_RANGE
+}
+
+/// A TCP service an agent exposes through tunnels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TunnelService {
+    /// Local address the agent connects to
+    pub target: SocketAddr,
+    /// Capability a peer needs to use the service
+    pub capability: PeerCapability,
+}
+
+/// Serves a tunnel service in-process instead of forwarding it to TCP
+#[async_trait]
+pub trait StreamHandler: Send + Sync {
+    /// Handle an accepted stream; the agent has already answered
+    /// [`TunnelStatus::Ok`]
+    async fn handle(&self, peer_id: NodeId, stream: BiStream) -> Result<(), P2PError>;
+}
+
+/// A service handled by a [`StreamHandler`]
+#[derive(Clone)]
+struct HandlerService {
+    /// Capability required to use the service; `None` if any connected
+    /// peer may use it
+    capability: Option<PeerCapability>,
+    handler: Arc<dyn StreamHandler>,
+}
+
+/// Everything an agent offers, shared between its connection tasks
+#[derive(Default)]
+struct Services {
+    tcp: HashMap<String, TunnelService>,
+    handlers: HashMap<String, HandlerService>,
+}
+
+/// Serves tunnel requests from connected peers
+///
+/// Runs on the machine whose services are being reached. By default it
+/// forwards the `ssh` service to `127.0.0.1:22` for peers with the
+/// [`PeerCapability::SshTunnel`] capability.
+pub struct TunnelAgent {
+    manager: Arc<P2PConnectionManager>,
+    services: Services,
+}
+
+impl TunnelAgent {
+    /// Create an agent offering the SSH service
+    pub fn new(manager: Arc<P2PConnectionManager>) -> Self {
+        let mut services = Services::default();
+        services.tcp.insert(
+            SSH_SERVICE.to_string(),
+            TunnelService {
+                target: DEFAULT_SSH_TARGET,
+                capability: PeerCapability::SshTunnel,
+            },
+        );
+        Self { manager, services }
+    }
+
+    /// Forward the SSH service to a different local address
+    pub fn with_ssh_target(self, target: SocketAddr) -> Self {
+        self.with_service(SSH_SERVICE, target, PeerCapability::SshTunnel)
+    }
+
+    /// Offer an additional service
+    pub fn with_service(
+        mut self,
+        name: impl Into<String>,
+        target: SocketAddr,
+        capability: PeerCapability,
+    ) -> Self {
+        let name = name.into();
+        self.services.handlers.remove(&name);
+        self.services
+            .tcp
+            .insert(name, TunnelService { target, capability });
+        self
+    }
+
+    /// Offer a service handled in-process
+    pub fn with_handler(
+        self,
+        name: impl Into<String>,
+        capability: PeerCapability,
+        handler: Arc<dyn StreamHandler>,
+    ) -> Self {
+        self.insert_handler(name.into(), Some(capability), handler)
+    }
+
+    /// Offer a service any connected peer may use
+    ///
+    /// The handler is responsible for authenticating the peer, as the
+    /// pairing handshake does.
+    pub fn with_open_handler(
+        self,
+        name: impl Into<String>,
+        handler: Arc<dyn StreamHandler>,
+    ) -> Self {
+        self.insert_handler(name.into(), None, handler)
+    }
+
+    fn insert_handler(
+        mut self,
+        name: String,
+        capability: Option<PeerCapability>,
+        handler: Arc<dyn StreamHandler>,
+    ) -> Self {
+        self.services.tcp.remove(&name);
+        self.services.handlers.insert(
+            name,
+            HandlerService {
+                capability,
+                handler,
+            },
+        );
+        self
+    }
+
+    /// Get the services forwarded to TCP targets
+    pub fn services(&self) -> &HashMap<String, TunnelService> {
+        &self.services.tcp
+    }
+
+    /// Accept connections and serve tunnel requests until the endpoint closes
+    pub fn serve(self) -> JoinHandle<()> {
+        let services = Arc::new(self.services);
+        let manager = self.manager;
+
+        tokio::spawn(async move {
+            loop {
+                match manager.accept().await {
+                    Ok(connection) => {
+                        tokio::spawn(serve_connection(
+                            manager.clone(),
+                            connection,
+                            services.clone(),
+                        ));
+                    }
+                    Err(P2PError::Stream(reason)) => {
+                        tracing::debug!(%reason, "Tunnel agent stopped");
+                        break;
+                    }
+                    Err(e) => tracing::warn!(error = %e, "Rejected incoming connection"),
+                }
+            }
+        })
+    }
+}
+
+async fn serve_connection(
+    manager: Arc<P2PConnectionManager>,
+    connection: Arc<P2PConnection>,
+    services: Arc<Services>,
+) {
+    let peer_id = connection.peer_id();
+    let streams = StreamManager::new(connection);
+
+    while let Ok(stream) = streams.accept_bi().await {
+        let manager = manager.clone();
+        let services = services.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_stream(&manager, peer_id, stream, &services).await {
+                tracing::warn!(peer_id = %peer_id, error = %e, "Tunnel request failed");
+            }
+        });
+    }
+}
+
+async fn serve_stream(
+    manager: &P2PConnectionManager,
+    peer_id: NodeId,
+    mut stream: BiStream,
+    services: &Services,
+) -> Result<(), P2PError> {
+    let name = tokio::time::timeout(HEADER_TIMEOUT, read_tunnel_header(&mut stream))
+        .await
+        .map_err(|_| P2PError::Stream("timed out waiting for tunnel header".to_string()))??;
+
+    if let Some(service) = services.handlers.get(&name) {
+        if let Some(capability) = service.capability {
+            if let Err(e) = manager.authorize(&peer_id, capability) {
+                refuse(stream, TunnelStatus::Unauthorized).await?;
+                return Err(e);
+            }
+        }
+        stream.write(&[TunnelStatus::Ok.to_byte()]).await?;
+        return service.handler.handle(peer_id, stream).await;
+    }
+
+    let Some(service) = services.tcp.get(&name) else {
+        return refuse(stream, TunnelStatus::UnknownService).await;
+    };
+    if let Err(e) = manager.authorize(&peer_id, service.capability) {
+        refuse(stream, TunnelStatus::Unauthorized).await?;
+        return Err(e);
+    }
+
+    let tcp = match TcpStream::connect(service.target).await {
+        Ok(tcp) => tcp,
+        Err(e) => {
+            refuse(stream, TunnelStatus::Unreachable).await?;
+            return Err(P2PError::Stream(format!(
+                "failed to reach {}: {}",
+                service.target, e
+            )));
+        }
+    };
+
+    stream.write(&[TunnelStatus::Ok.to_byte()]).await?;
+    tracing::info!(peer_id = %peer_id, service = %name, "Tunnel opened");
+    pipe(tcp, stream).await
+}
+
+async fn refuse(mut stream: BiStream, status: TunnelStatus) -> Result<(), P2PError> {
+    stream.write_and_finish(&[status.to_byte()]).await
+}
+
+/// Copy bytes between a TCP connection and a tunnel stream until both close
+pub async fn pipe(tcp: TcpStream, stream: BiStream) -> Result<(), P2PError> {
+    let (mut tcp_read, mut tcp_write) = tcp.into_split();
+    let (mut send, mut recv): (SendStream, RecvStream) = stream.split();
+
+    let upstream = async {
+        let mut buf = vec![0u8; PIPE_BUFFER_SIZE];
+        loop {
+            let n = tcp_read.read(&mut buf).await.map_err(io_error)?;
+            if n == 0 {
+                break;
+            }
+            send.write_all(&buf[..n])
+                .await
+                .map_err(|e| P2PError::Stream(format!("Write failed: {}", e)))?;
+        }
+        send.finish()
+            .map_err(|e| P2PError::Stream(format!("Finish failed: {}", e)))
+    };
+
+    let downstream = async {
+        let mut buf = vec![0u8; PIPE_BUFFER_SIZE];
+        while let Some(n) = recv
+            .read(&mut buf)
+            .await
+            .map_err(|e| P2PError::Stream(format!("Read failed: {}", e)))?
+        {
+            tcp_write.write_all(&buf[..n]).await.map_err(io_error)?;
+        }
+        tcp_write.shutdown().await.map_err(io_error)
+    };
+
+    tokio::try_join!(upstream, downstream).map(|_| ())
+}
+
+fn io_error(e: std::io::Error) -> P2PError {
+    P2PError::Stream(format!("Tunnel I/O failed: {}", e))
+}
+
+/// A loopback TCP listener that forwards each connection to a peer's service
+///
+/// Connecting to [`LocalTunnel::local_addr`] is equivalent to connecting to
+/// the service on the peer. Dropping the tunnel stops accepting new
+/// connections; connections already piped keep running until they close.
+pub struct LocalTunnel {
+    local_addr: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl LocalTunnel {
+    /// Listen on loopback and tunnel connections to a peer's service
+    ///
+    /// The first tunnel stream is opened before returning, so connection
+    /// and authorization failures surface here rather than as a closed
+    /// socket later.
+    pub async fn bind(
+        manager: Arc<P2PConnectionManager>,
+        peer_id: NodeId,
+        service: &str,
+    ) -> Result<Self, P2PError> {
+        let listener = bind_loopback(tunnel_port(&peer_id, service)).await?;
+        let local_addr = listener.local_addr().map_err(io_error)?;
+        let first = open_tunnel(&manager, peer_id, service).await?;
+
+        let service = service.to_string();
+        let task = tokio::spawn(async move {
+            let mut pending = Some(first);
+            loop {
+                let tcp = match listener.accept().await {
+                    Ok((tcp, _)) => tcp,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Tunnel listener failed");
+                        break;
+                    }
+                };
+
+                let stream = match pending.take() {
+                    Some(stream) => stream,
+                    None => match open_tunnel(&manager, peer_id, &service).await {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            tracing::warn!(peer_id = %peer_id, error = %e, "Failed to open tunnel");
+                            continue;
+                        }
+                    },
+                };
+
+                tokio::spawn(async move {
+                    if let Err(e) = pipe(tcp, stream).await {
+                        tracing::debug!(error = %e, "Tunnel closed with error");
+                    }
+                });
+            }
+        });
+
+        Ok(Self { local_addr, task })
+    }
+
+    /// Loopback address that reaches the peer's service
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for LocalTunnel {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn bind_loopback(port: u16) -> Result<TcpListener, P2PError> {
+    match TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await {
+        Ok(listener) => Ok(listener),
+        Err(e) => {
+            tracing::warn!(port, error = %e, "Preferred tunnel port unavailable, using an ephemeral port");
+            TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+                .await
+                .map_err(io_error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iroh::SecretKey;
+
+    #[test]
+    fn header_encodes_magic_and_service() {
+        let header = encode_tunnel_header(SSH_SERVICE).unwrap();
+        assert_eq!(&header[..8], TUNNEL_MAGIC);
+        assert_eq!(header[8] as usize, SSH_SERVICE.len());
+        assert_eq!(&header[9..], SSH_SERVICE.as_bytes());
+
+        assert!(encode_tunnel_header("").is_err());
+        assert!(encode_tunnel_header(&"x".repeat(256)).is_err());
+    }
+
+    #[test]
+    fn status_bytes_roundtrip() {
+        for status in [
+            TunnelStatus::Ok,
+            TunnelStatus::Unauthorized,
+            TunnelStatus::Unreachable,
+            TunnelStatus::UnknownService,
+        ] {
+            assert_eq!(TunnelStatus::from_byte(status.to_byte()).unwrap(), status);
+        }
+        assert!(TunnelStatus::from_byte(9).is_err());
+    }
+
+    #[test]
+    fn tunnel_port_is_stable_and_in_range() {
+        let peer = SecretKey::generate(rand::rngs::OsRng).public();
+        let port = tunnel_port(&peer, SSH_SERVICE);
+
+        assert_eq!(port, tunnel_port(&peer, SSH_SERVICE));
+        assert!(port >= LOCAL_PORT_BASE);
+    }
+}
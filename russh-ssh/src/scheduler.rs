@@ -0,0 +1,456 @@
+//! Transfer Scheduler
+//!
+//! Coordinates everything that moves bulk data over the network, so
+//! interactive SFTP, streaming prefetch, VDFS sync and peer file transfers
+//! share the link instead of competing for it.
+//!
+//! Every transfer belongs to a [`TransferClass`]. Before sending or
+//! receiving a block of data, a transfer calls
+//! [`TransferScheduler::acquire`] with its class and the block size. The
+//! scheduler holds the call back while:
+//!
+//! - the scheduler or the class is paused,
+//! - a class with a higher priority is waiting for bandwidth, or
+//! - the global bandwidth budget is used up.
+//!
+//! The budget is a token bucket refilled at the configured rate and holding
+//! at most one second of traffic. A block larger than the bucket is let
+//! through once the bucket is not in debt, and the debt delays whoever comes
+//! next, so the long-run rate still matches the limit.
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// Kind of traffic a transfer carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferClass {
+    /// User-facing operations such as SFTP browsing and edits
+    Interactive,
+    /// Media streaming and its prefetch
+    Streaming,
+    /// VDFS chunk sync
+    Sync,
+    /// Large background copies such as peer file transfers
+    Bulk,
+}
+
+impl TransferClass {
+    /// Every class, highest default priority first
+    pub const ALL: [TransferClass; 4] = [
+        TransferClass::Interactive,
+        TransferClass::Streaming,
+        TransferClass::Sync,
+        TransferClass::Bulk,
+    ];
+
+    /// Priority used until one is configured; higher goes first
+    pub fn default_priority(self) -> u8 {
+        match self {
+            TransferClass::Interactive => 3,
+            TransferClass::Streaming => 2,
+            TransferClass::Sync => 1,
+            TransferClass::Bulk => 0,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            TransferClass::Interactive => 0,
+            TransferClass::Streaming => 1,
+            TransferClass::Sync => 2,
+            TransferClass::Bulk => 3,
+        }
+    }
+}
+
+/// Scheduler state for one class
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClassStats {
+    /// The class
+    pub class: TransferClass,
+    /// Configured priority; higher goes first
+    pub priority: u8,
+    /// Whether the class is paused
+    pub paused: bool,
+    /// Bytes granted to the class so far
+    pub bytes: u64,
+    /// Transfers currently waiting for bandwidth
+    pub waiting: usize,
+}
+
+/// Snapshot of the scheduler
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchedulerStats {
+    /// Global budget in bytes per second, `None` when unlimited
+    pub rate_limit: Option<u64>,
+    /// Whether all transfers are paused
+    pub paused: bool,
+    /// Per-class state, in [`TransferClass::ALL`] order
+    pub classes: Vec<ClassStats>,
+}
+
+impl SchedulerStats {
+    /// Get the state of one class
+    pub fn class(&self, class: TransferClass) -> Option<&ClassStats> {
+        self.classes.iter().find(|stats| stats.class == class)
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    rate_limit: Option<u64>,
+    /// Available budget in bytes; negative while in debt
+    tokens: f64,
+    last_refill: Instant,
+    paused: bool,
+    classes: [ClassStats; 4],
+}
+
+impl State {
+    fn new() -> Self {
+        Self {
+            rate_limit: None,
+            tokens: 0.0,
+            last_refill: Instant::now(),
+            paused: false,
+            classes: TransferClass::ALL.map(|class| ClassStats {
+                class,
+                priority: class.default_priority(),
+                paused: false,
+                bytes: 0,
+                waiting: 0,
+            }),
+        }
+    }
+
+    fn is_paused(&self, class: TransferClass) -> bool {
+        self.paused || self.classes[class.index()].paused
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let Some(rate) = self.rate_limit {
+            let elapsed = now.saturating_duration_since(self.last_refill);
+            let rate = rate as f64;
+            self.tokens = (self.tokens + elapsed.as_secs_f64() * rate).min(rate);
+        }
+        self.last_refill = now;
+    }
+
+    /// Take `bytes` for `class`, or say how long to wait
+    ///
+    /// `Err(None)` means wait until the scheduler changes state.
+    fn try_take(
+        &mut self,
+        class: TransferClass,
+        bytes: u64,
+        now: Instant,
+    ) -> Result<(), Option<Duration>> {
+        if self.is_paused(class) {
+            return Err(None);
+        }
+        let priority = self.classes[class.index()].priority;
+        let outranked = self.classes.iter().any(|other| {
+            other.priority > priority && other.waiting > 0 && !self.is_paused(other.class)
+        });
+        if outranked {
+            return Err(None);
+        }
+
+        self.refill(now);
+        if let Some(rate) = self.rate_limit {
+            if self.tokens < 0.0 {
+                return Err(Some(Duration::from_secs_f64(-self.tokens / rate as f64)));
+            }
+            self.tokens -= bytes as f64;
+        }
+        let stats = &mut self.classes[class.index()];
+        stats.bytes = stats.bytes.saturating_add(bytes);
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: Mutex<State>,
+    changed: Notify,
+}
+
+impl Inner {
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|poisoned| {
+            tracing::warn!("Transfer scheduler lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+
+    fn update(&self, f: impl FnOnce(&mut State)) {
+        f(&mut self.lock());
+        self.changed.notify_waiters();
+    }
+}
+
+/// Marks a transfer as waiting until it is granted or cancelled
+struct Waiter<'a> {
+    inner: &'a Inner,
+    class: TransferClass,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        let class = self.class;
+        self.inner.update(|state| {
+            let stats = &mut state.classes[class.index()];
+            stats.waiting = stats.waiting.saturating_sub(1);
+        });
+    }
+}
+
+/// Shares bandwidth between transfer classes
+///
+/// Cheap to clone; clones share the same budget.
+#[derive(Debug, Clone)]
+pub struct TransferScheduler {
+    inner: Arc<Inner>,
+}
+
+impl Default for TransferScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransferScheduler {
+    /// Create a scheduler without a bandwidth limit
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State::new()),
+                changed: Notify::new(),
+            }),
+        }
+    }
+
+    /// Limit all classes together to `bytes_per_sec`
+    pub fn with_rate_limit(self, bytes_per_sec: u64) -> Self {
+        self.set_rate_limit(Some(bytes_per_sec));
+        self
+    }
+
+    /// Set the priority of a class
+    pub fn with_priority(self, class: TransferClass, priority: u8) -> Self {
+        self.set_priority(class, priority);
+        self
+    }
+
+    /// Change the global budget; `None` removes the limit
+    ///
+    /// A limit of zero is treated as one byte per second.
+    pub fn set_rate_limit(&self, bytes_per_sec: Option<u64>) {
+        self.inner.update(|state| {
+            state.refill(Instant::now());
+            state.rate_limit = bytes_per_sec.map(|rate| rate.max(1));
+            state.tokens = match state.rate_limit {
+                Some(rate) => state.tokens.min(rate as f64),
+                None => 0.0,
+            };
+        });
+    }
+
+    /// Get the global budget in bytes per second
+    pub fn rate_limit(&self) -> Option<u64> {
+        self.inner.lock().rate_limit
+    }
+
+    /// Change the priority of a class; higher goes first
+    pub fn set_priority(&self, class: TransferClass, priority: u8) {
+        self.inner
+            .update(|state| state.classes[class.index()].priority = priority);
+    }
+
+    /// Get the priority of a class
+    pub fn priority(&self, class: TransferClass) -> u8 {
+        self.inner.lock().classes[class.index()].priority
+    }
+
+    /// Hold back every transfer until [`resume`](Self::resume)
+    pub fn pause(&self) {
+        self.inner.update(|state| state.paused = true);
+    }
+
+    /// Let transfers continue after [`pause`](Self::pause)
+    pub fn resume(&self) {
+        self.inner.update(|state| state.paused = false);
+    }
+
+    /// Check whether all transfers are paused
+    pub fn is_paused(&self) -> bool {
+        self.inner.lock().paused
+    }
+
+    /// Hold back transfers of one class
+    ///
+    /// A paused class does not block lower-priority classes.
+    pub fn pause_class(&self, class: TransferClass) {
+        self.inner
+            .update(|state| state.classes[class.index()].paused = true);
+    }
+
+    /// Let transfers of one class continue
+    pub fn resume_class(&self, class: TransferClass) {
+        self.inner
+            .update(|state| state.classes[class.index()].paused = false);
+    }
+
+    /// Check whether a class is paused, directly or by a global pause
+    pub fn is_class_paused(&self, class: TransferClass) -> bool {
+        self.inner.lock().is_paused(class)
+    }
+
+    /// Wait until `class` may move `bytes`
+    ///
+    /// Call it before each block of a transfer. With `bytes` set to zero it
+    /// only waits for pauses and higher-priority traffic. Cancelling the
+    /// returned future gives up the place in line without using budget.
+    pub async fn acquire(&self, class: TransferClass, bytes: u64) {
+        let mut waiter = None;
+        loop {
+            let changed = self.inner.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+
+            let wait = {
+                let mut state = self.inner.lock();
+                match state.try_take(class, bytes, Instant::now()) {
+                    Ok(()) => return,
+                    Err(wait) => {
+                        if waiter.is_none() {
+                            state.classes[class.index()].waiting += 1;
+                        }
+                        wait
+                    }
+                }
+            };
+            if waiter.is_none() {
+                waiter = Some(Waiter {
+                    inner: &self.inner,
+                    class,
+                });
+            }
+
+            match wait {
+                Some(delay) => {
+                    tokio::select! {
+                        _ = &mut changed => {}
+                        _ = tokio::time::sleep(delay) => {}
+                    }
+                }
+                None => changed.await,
+            }
+        }
+    }
+
+    /// Snapshot the scheduler state
+    pub fn stats(&self) -> SchedulerStats {
+        let state = self.inner.lock();
+        SchedulerStats {
+            rate_limit: state.rate_limit,
+            paused: state.paused,
+            classes: state.classes.to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::timeout;
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limit_spreads_blocks_over_time() {
+        let scheduler = TransferScheduler::new().with_rate_limit(1000);
+        let start = Instant::now();
+
+        // The bucket starts empty and fills at 1000 bytes per second
+        for _ in 0..4 {
+            scheduler.acquire(TransferClass::Sync, 500).await;
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(1500), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(2100), "{:?}", elapsed);
+
+        let stats = scheduler.stats();
+        assert_eq!(stats.class(TransferClass::Sync).unwrap().bytes, 2000);
+        assert_eq!(stats.class(TransferClass::Bulk).unwrap().bytes, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pause_holds_transfers_until_resumed() {
+        let scheduler = TransferScheduler::new();
+        scheduler.pause_class(TransferClass::Bulk);
+
+        // Other classes are unaffected
+        scheduler.acquire(TransferClass::Sync, 10).await;
+        assert!(timeout(
+            Duration::from_secs(1),
+            scheduler.acquire(TransferClass::Bulk, 10)
+        )
+        .await
+        .is_err());
+        // The cancelled acquire no longer counts as waiting
+        assert_eq!(
+            scheduler
+                .stats()
+                .class(TransferClass::Bulk)
+                .unwrap()
+                .waiting,
+            0
+        );
+
+        let task = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.acquire(TransferClass::Bulk, 10).await }
+        });
+        scheduler.pause();
+        scheduler.resume_class(TransferClass::Bulk);
+        tokio::task::yield_now().await;
+        assert!(!task.is_finished());
+
+        scheduler.resume();
+        task.await.unwrap();
+        assert_eq!(
+            scheduler.stats().class(TransferClass::Bulk).unwrap().bytes,
+            10
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn higher_priority_waiters_go_first() {
+        let scheduler = TransferScheduler::new().with_rate_limit(100);
+        // Drain the budget so both transfers have to wait
+        scheduler.acquire(TransferClass::Bulk, 100).await;
+
+        let bulk = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move {
+                scheduler.acquire(TransferClass::Bulk, 100).await;
+                Instant::now()
+            }
+        });
+        tokio::task::yield_now().await;
+        let interactive = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move {
+                scheduler.acquire(TransferClass::Interactive, 100).await;
+                Instant::now()
+            }
+        });
+
+        let bulk = bulk.await.unwrap();
+        let interactive = interactive.await.unwrap();
+        assert!(interactive < bulk);
+    }
+}
@@ -1,6 +1,8 @@
 //! Session Management
 //!
-//! Provides session profiles, persistence, and management.
+//! Provides session profiles, persistence, and management, along with idle
+//! timeout policies, read-only sharing of a session with P2P observers,
+//! handoff of a session between devices, and profile import and export.
 //!
 //! # Requirements Coverage
 //! - Requirement 8.1: Session parameter completeness
@@ -9,8 +11,14 @@
 //! - Requirement 8.4: Session persistence
 //! - Requirement 8.7: Session serialization round-trip
 
+pub mod handoff;
+pub mod idle;
 pub mod manager;
 pub mod profile;
+pub mod share;
 
-pub use manager::SessionManager;
+pub use handoff::{send_handoff, HandoffOutcome, HandoffPayload, IncomingHandoff};
+pub use idle::{IdleAction, IdleEvent, IdlePolicy};
+pub use manager::{ImportMode, ImportReport, NameConflict, SessionManager};
 pub use profile::SessionProfile;
+pub use share::{ShareFrame, ShareViewer, SharedSession};
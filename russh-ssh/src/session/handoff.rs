@@ -0,0 +1,278 @@
+//! Session Handoff
+//!
+//! Moves an active session to another device over P2P. The sending device
+//! packages the profile, working directory, scrollback and tmux target;
+//! the receiving device reconnects with that state instead of starting
+//! from scratch. The transfer runs over an end-to-end encrypted stream.
+//!
+//! # Requirements Coverage
+//! - Requirement 2.3: Session state restoration
+//! - Requirement 4.1: End-to-end encryption between peers
+
+use super::profile::SessionProfile;
+use crate::error::P2PError;
+use crate::p2p::{BiStream, P2PConnectionManager, SecureStream, StreamManager};
+use crate::ssh::AutoReconnectConfig;
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Maximum size of a handoff message on the wire
+pub const MAX_HANDOFF_SIZE: usize = 8 * 1024 * 1024;
+
+/// Session state transferred to another device
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandoffPayload {
+    /// Handoff identifier
+    pub handoff_id: Uuid,
+    /// Profile used by the session (stored passwords are stripped)
+    pub profile: SessionProfile,
+    /// Current working directory on the remote host
+    pub working_directory: Option<String>,
+    /// Terminal scrollback to display before reattaching
+    pub scrollback: Vec<u8>,
+    /// tmux session to reattach on the receiving device
+    pub tmux_session: Option<String>,
+    /// Terminal dimensions (cols, rows) at handoff time
+    pub dimensions: (u32, u32),
+    /// When the handoff was created
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl HandoffPayload {
+    /// Create a payload for the given profile
+    ///
+    /// Any password stored in the profile is removed; the receiving device
+    /// uses its own keyring or prompts for credentials.
+    pub fn new(mut profile: SessionProfile) -> Self {
+        profile.auth.clear_secrets();
+        let working_directory = profile.working_directory.clone();
+        Self {
+            handoff_id: Uuid::new_v4(),
+            profile,
+            working_directory,
+            scrollback: Vec::new(),
+            tmux_session: None,
+            dimensions: (80, 24),
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    /// Set the remote working directory
+    pub fn with_working_directory(mut self, cwd: impl Into<String>) -> Self {
+        self.working_directory = Some(cwd.into());
+        self
+    }
+
+    /// Set the scrollback to carry over
+    pub fn with_scrollback(mut self, scrollback: Vec<u8>) -> Self {
+        self.scrollback = scrollback;
+        self
+    }
+
+    /// Set the tmux session to reattach
+    pub fn with_tmux_session(mut self, session: impl Into<String>) -> Self {
+        self.tmux_session = Some(session.into());
+        self
+    }
+
+    /// Set the terminal dimensions
+    pub fn with_dimensions(mut self, cols: u32, rows: u32) -> Self {
+        self.dimensions = (cols, rows);
+        self
+    }
+
+    /// Profile to connect with on the receiving device
+    ///
+    /// The working directory from the handoff replaces the profile's default.
+    pub fn resume_profile(&self) -> SessionProfile {
+        let mut profile = self.profile.clone();
+        if self.working_directory.is_some() {
+            profile.working_directory = self.working_directory.clone();
+        }
+        profile
+    }
+
+    /// Auto-reconnect policy that reattaches the handed-off shell
+    pub fn auto_reconnect_config(&self) -> AutoReconnectConfig {
+        let config = AutoReconnectConfig::new();
+        match &self.tmux_session {
+            Some(session) => config.with_tmux_session(session.clone()),
+            None => config,
+        }
+    }
+}
+
+/// Message exchanged during a handoff
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum HandoffMessage {
+    /// Session state offered to the receiving device
+    Offer { payload: Box<HandoffPayload> },
+    /// The receiving device took over the session
+    Accepted { handoff_id: Uuid },
+    /// The receiving device declined the session
+    Rejected { handoff_id: Uuid, reason: String },
+}
+
+/// Result of offering a session to another device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandoffOutcome {
+    /// The other device took over the session
+    Accepted,
+    /// The other device declined
+    Rejected(String),
+}
+
+/// Offer a session to another device and wait for its answer
+pub async fn send_handoff(
+    manager: &P2PConnectionManager,
+    peer_id: NodeId,
+    payload: HandoffPayload,
+) -> Result<HandoffOutcome, P2PError> {
+    let connection = manager.connect(peer_id).await?;
+    let stream = StreamManager::new(connection).open_bi().await?;
+    let mut secure = SecureStream::initiate(stream).await?;
+
+    let handoff_id = payload.handoff_id;
+    tracing::info!(peer_id = %peer_id, handoff_id = %handoff_id, "Offering session handoff");
+    secure
+        .send_json(&HandoffMessage::Offer {
+            payload: Box::new(payload),
+        })
+        .await?;
+
+    let outcome = match secure.recv_json(MAX_HANDOFF_SIZE).await? {
+        HandoffMessage::Accepted { handoff_id: id } if id == handoff_id => HandoffOutcome::Accepted,
+        HandoffMessage::Rejected {
+            handoff_id: id,
+            reason,
+        } if id == handoff_id => HandoffOutcome::Rejected(reason),
+        other => {
+            return Err(P2PError::Stream(format!(
+                "Unexpected handoff reply: {:?}",
+                other
+            )))
+        }
+    };
+    let _ = secure.finish().await;
+
+    tracing::info!(peer_id = %peer_id, outcome = ?outcome, "Session handoff answered");
+    Ok(outcome)
+}
+
+/// A handoff offer received from another device
+pub struct IncomingHandoff {
+    /// Encrypted stream to the sending device
+    stream: SecureStream,
+    /// Device that sent the offer
+    from: NodeId,
+    /// Offered session state
+    payload: HandoffPayload,
+}
+
+impl IncomingHandoff {
+    /// Receive a handoff offer on an accepted stream
+    pub async fn receive(stream: BiStream, from: NodeId) -> Result<Self, P2PError> {
+        let mut stream = SecureStream::accept(stream).await?;
+        match stream.recv_json(MAX_HANDOFF_SIZE).await? {
+            HandoffMessage::Offer { payload } => Ok(Self {
+                stream,
+                from,
+                payload: *payload,
+            }),
+            other => Err(P2PError::Stream(format!(
+                "Expected handoff offer, got {:?}",
+                other
+            ))),
+        }
+    }
+
+    /// Device that sent the offer
+    pub fn sender(&self) -> NodeId {
+        self.from
+    }
+
+    /// Offered session state
+    pub fn payload(&self) -> &HandoffPayload {
+        &self.payload
+    }
+
+    /// Take over the session
+    pub async fn accept(mut self) -> Result<HandoffPayload, P2PError> {
+        self.stream
+            .send_json(&HandoffMessage::Accepted {
+                handoff_id: self.payload.handoff_id,
+            })
+            .await?;
+        let _ = self.stream.finish().await;
+        Ok(self.payload)
+    }
+
+    /// Decline the session
+    pub async fn reject(mut self, reason: impl Into<String>) -> Result<(), P2PError> {
+        self.stream
+            .send_json(&HandoffMessage::Rejected {
+                handoff_id: self.payload.handoff_id,
+                reason: reason.into(),
+            })
+            .await?;
+        self.stream.finish().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::profile::AuthConfig;
+
+    fn profile() -> SessionProfile {
+        SessionProfile::new(
+            "Laptop".to_string(),
+            "host.com".to_string(),
+            "user".to_string(),
+        )
+        .with_auth(AuthConfig::Password {
+            password: Some("secret".to_string()),
+        })
+    }
+
+    #[test]
+    fn payload_strips_stored_password() {
+        let payload = HandoffPayload::new(profile());
+        assert!(!payload.profile.auth.stores_sensitive_data());
+    }
+
+    #[test]
+    fn payload_resume_profile_uses_working_directory() {
+        let payload = HandoffPayload::new(profile())
+            .with_working_directory("/srv/app")
+            .with_tmux_session("main");
+
+        let resumed = payload.resume_profile();
+        assert_eq!(resumed.working_directory.as_deref(), Some("/srv/app"));
+        assert_eq!(
+            payload.auto_reconnect_config().tmux_session.as_deref(),
+            Some("main")
+        );
+    }
+
+    #[test]
+    fn handoff_message_serialization_roundtrip() {
+        let payload = HandoffPayload::new(profile())
+            .with_scrollback(b"$ make\r\n".to_vec())
+            .with_dimensions(120, 40);
+        let json = serde_json::to_string(&HandoffMessage::Offer {
+            payload: Box::new(payload),
+        })
+        .unwrap();
+
+        match serde_json::from_str(&json).unwrap() {
+            HandoffMessage::Offer { payload } => {
+                assert_eq!(payload.scrollback, b"$ make\r\n".to_vec());
+                assert_eq!(payload.dimensions, (120, 40));
+            }
+            other => panic!("Unexpected message: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! Idle Session Policy
+//!
+//! Tracks per-session inactivity and applies a configurable policy
+//! (disconnect, or renew with a keep-alive) once a session has been idle
+//! for longer than the configured timeout.
+//!
+//! # Requirements Coverage
+//! - Requirement 8.3: Session management
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Action taken when a session exceeds its idle timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum IdleAction {
+    /// Close the session
+    #[default]
+    Disconnect,
+    /// Send a keep-alive and reset the idle timer
+    KeepAlive,
+}
+
+/// Idle timeout policy for a session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdlePolicy {
+    /// Inactivity period after which the policy triggers
+    #[serde(with = "duration_serde")]
+    pub timeout: Duration,
+    /// Action to take when the policy triggers
+    pub action: IdleAction,
+}
+
+impl IdlePolicy {
+    /// Disconnect sessions idle for longer than `timeout`
+    pub fn disconnect_after(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            action: IdleAction::Disconnect,
+        }
+    }
+
+    /// Renew sessions with a keep-alive after `timeout` of inactivity
+    pub fn keep_alive_after(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            action: IdleAction::KeepAlive,
+        }
+    }
+
+    /// Check whether the policy triggers for the given idle time
+    pub fn is_triggered(&self, idle_for: Duration) -> bool {
+        idle_for >= self.timeout
+    }
+}
+
+/// Event emitted when an idle policy triggers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdleEvent {
+    /// Session the policy applied to
+    pub session_id: Uuid,
+    /// How long the session had been idle
+    pub idle_for: Duration,
+    /// Action that was taken
+    pub action: IdleAction,
+}
+
+/// Serde helper for Duration
+mod duration_serde {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        (duration.as_millis() as u64).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let millis = u64::deserialize(deserializer)?;
+        Ok(Duration::from_millis(millis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy_triggers_at_timeout() {
+        let policy = IdlePolicy::disconnect_after(Duration::from_secs(300));
+        assert!(!policy.is_triggered(Duration::from_secs(299)));
+        assert!(policy.is_triggered(Duration::from_secs(300)));
+        assert_eq!(policy.action, IdleAction::Disconnect);
+    }
+
+    #[test]
+    fn policy_serialization_roundtrip() {
+        let policy = IdlePolicy::keep_alive_after(Duration::from_secs(600));
+        let json = serde_json::to_string(&policy).unwrap();
+        let restored: IdlePolicy = serde_json::from_str(&json).unwrap();
+        assert_eq!(policy, restored);
+    }
+}
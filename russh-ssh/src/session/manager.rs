@@ -6,13 +6,57 @@
 //! - Requirement 8.3: Session management
 //! - Requirement 8.4: Session persistence
 
+use super::idle::{IdleAction, IdleEvent, IdlePolicy};
 use super::profile::SessionProfile;
 use crate::error::SessionError;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use tokio::sync::RwLock;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+use tokio::time::Instant;
 use uuid::Uuid;
 
+/// Channel capacity for idle policy events
+const IDLE_EVENT_CHANNEL_CAPACITY: usize = 16;
+
+/// What an import does with the profiles already saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImportMode {
+    /// Keep them, adding the imported profiles
+    #[default]
+    Merge,
+    /// Remove them first
+    Replace,
+}
+
+/// What an import does with a profile whose name is already taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameConflict {
+    /// Keep the saved profile and drop the imported one
+    #[default]
+    Skip,
+    /// Replace the saved profile, which keeps its ID
+    Overwrite,
+    /// Import the profile under a free name, e.g. `web-2`
+    Rename,
+}
+
+/// Outcome of [`SessionManager::import_with`], by profile name
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    /// Profiles added under their own name
+    pub added: Vec<String>,
+    /// Saved profiles replaced by an imported one
+    pub overwritten: Vec<String>,
+    /// Profiles added under a new name, as (original, new)
+    pub renamed: Vec<(String, String)>,
+    /// Imported profiles dropped because the name was taken
+    pub skipped: Vec<String>,
+    /// Saved profiles removed by [`ImportMode::Replace`]
+    pub removed: usize,
+}
+
 /// Session statistics
 #[derive(Debug, Clone, Default)]
 pub struct SessionStats {
@@ -31,7 +75,7 @@ pub struct SessionStats {
 pub struct ActiveSession {
     /// Session ID
     pub id: Uuid,
-    /// Profile used
+    /// Profile used; nil for sessions added with [`SessionManager::track_session`]
     pub profile_id: Uuid,
     /// Start time
     pub started_at: chrono::DateTime<chrono::Utc>,
@@ -41,17 +85,23 @@ pub struct ActiveSession {
     pub bytes_received: u64,
     /// Commands executed
     pub commands_executed: u64,
+    /// Last time user activity was recorded
+    pub last_activity: Instant,
+    /// Idle timeout policy for this session
+    pub idle_policy: Option<IdlePolicy>,
 }
 
 impl ActiveSession {
-    fn new(profile_id: Uuid) -> Self {
+    fn new(id: Uuid, profile_id: Uuid, idle_policy: Option<IdlePolicy>) -> Self {
         Self {
-            id: Uuid::new_v4(),
+            id,
             profile_id,
             started_at: chrono::Utc::now(),
             bytes_sent: 0,
             bytes_received: 0,
             commands_executed: 0,
+            last_activity: Instant::now(),
+            idle_policy,
         }
     }
 
@@ -59,6 +109,11 @@ impl ActiveSession {
     pub fn duration(&self) -> chrono::Duration {
         chrono::Utc::now() - self.started_at
     }
+
+    /// Get time since the last recorded activity
+    pub fn idle_time(&self) -> Duration {
+        self.last_activity.elapsed()
+    }
 }
 
 /// Session manager for profiles and active sessions
@@ -71,6 +126,8 @@ pub struct SessionManager {
     storage_path: Option<PathBuf>,
     /// Statistics
     stats: RwLock<SessionStats>,
+    /// Broadcast sender for idle policy events
+    idle_events: broadcast::Sender<IdleEvent>,
 }
 
 impl SessionManager {
@@ -81,6 +138,7 @@ impl SessionManager {
             active_sessions: RwLock::new(HashMap::new()),
             storage_path: None,
             stats: RwLock::new(SessionStats::default()),
+            idle_events: broadcast::channel(IDLE_EVENT_CHANNEL_CAPACITY).0,
         }
     }
 
@@ -91,6 +149,7 @@ impl SessionManager {
             active_sessions: RwLock::new(HashMap::new()),
             storage_path: Some(path),
             stats: RwLock::new(SessionStats::default()),
+            idle_events: broadcast::channel(IDLE_EVENT_CHANNEL_CAPACITY).0,
         }
     }
 
@@ -152,28 +211,18 @@ impl SessionManager {
     /// Create a new session from a profile
     pub async fn create_session(&self, profile_id: &Uuid) -> Result<Uuid, SessionError> {
         // Verify profile exists
-        {
+        let idle_policy = {
             let profiles = self.profiles.read().await;
-            if !profiles.contains_key(profile_id) {
-                return Err(SessionError::ProfileNotFound(profile_id.to_string()));
+            match profiles.get(profile_id) {
+                Some(profile) => profile.idle_policy.clone(),
+                None => return Err(SessionError::ProfileNotFound(profile_id.to_string())),
             }
-        }
+        };
 
         // Create active session
-        let session = ActiveSession::new(*profile_id);
-        let session_id = session.id;
-
-        {
-            let mut active = self.active_sessions.write().await;
-            active.insert(session_id, session);
-        }
-
-        // Update stats
-        {
-            let mut stats = self.stats.write().await;
-            stats.total_created += 1;
-            stats.active_count += 1;
-        }
+        let session_id = Uuid::new_v4();
+        self.insert_session(ActiveSession::new(session_id, *profile_id, idle_policy))
+            .await;
 
         // Update profile usage
         {
@@ -186,6 +235,29 @@ impl SessionManager {
         Ok(session_id)
     }
 
+    /// Track a session whose profile lives outside this manager
+    ///
+    /// For frontends that keep their own profiles: the session is tracked
+    /// under the caller's `session_id`, with a nil profile ID, so its idle
+    /// policy applies and it shows up in the statistics.
+    pub async fn track_session(&self, session_id: Uuid, idle_policy: Option<IdlePolicy>) {
+        self.insert_session(ActiveSession::new(session_id, Uuid::nil(), idle_policy))
+            .await;
+    }
+
+    async fn insert_session(&self, session: ActiveSession) {
+        let replaced = {
+            let mut active = self.active_sessions.write().await;
+            active.insert(session.id, session)
+        };
+
+        let mut stats = self.stats.write().await;
+        stats.total_created += 1;
+        if replaced.is_none() {
+            stats.active_count += 1;
+        }
+    }
+
     /// Close a session
     pub async fn close_session(&self, session_id: &Uuid) -> Result<(), SessionError> {
         let session = {
@@ -222,6 +294,101 @@ impl SessionManager {
         active.keys().cloned().collect()
     }
 
+    /// Record user activity on a session, resetting its idle timer
+    pub async fn record_activity(&self, session_id: &Uuid) -> Result<(), SessionError> {
+        let mut active = self.active_sessions.write().await;
+        let session = active
+            .get_mut(session_id)
+            .ok_or_else(|| SessionError::NotFound(session_id.to_string()))?;
+        session.last_activity = Instant::now();
+        Ok(())
+    }
+
+    /// Override the idle policy of an active session
+    pub async fn set_idle_policy(
+        &self,
+        session_id: &Uuid,
+        policy: Option<IdlePolicy>,
+    ) -> Result<(), SessionError> {
+        let mut active = self.active_sessions.write().await;
+        let session = active
+            .get_mut(session_id)
+            .ok_or_else(|| SessionError::NotFound(session_id.to_string()))?;
+        session.idle_policy = policy;
+        Ok(())
+    }
+
+    /// Get how long a session has been idle
+    pub async fn idle_time(&self, session_id: &Uuid) -> Option<Duration> {
+        let active = self.active_sessions.read().await;
+        active.get(session_id).map(|s| s.idle_time())
+    }
+
+    /// Subscribe to idle policy events
+    ///
+    /// The manager only keeps the books: whoever owns the connection must
+    /// drop it on `IdleAction::Disconnect` and send a keep-alive on
+    /// `IdleAction::KeepAlive`.
+    pub fn subscribe_idle_events(&self) -> broadcast::Receiver<IdleEvent> {
+        self.idle_events.subscribe()
+    }
+
+    /// Apply idle policies to all active sessions
+    ///
+    /// Sessions whose policy is `Disconnect` are closed; sessions whose
+    /// policy is `KeepAlive` have their idle timer renewed. An event is
+    /// broadcast for every session the policy triggered on.
+    pub async fn check_idle(&self) -> Vec<IdleEvent> {
+        let mut events = Vec::new();
+        {
+            let mut active = self.active_sessions.write().await;
+            for session in active.values_mut() {
+                let Some(policy) = &session.idle_policy else {
+                    continue;
+                };
+                let idle_for = session.idle_time();
+                if !policy.is_triggered(idle_for) {
+                    continue;
+                }
+                if policy.action == IdleAction::KeepAlive {
+                    session.last_activity = Instant::now();
+                }
+                events.push(IdleEvent {
+                    session_id: session.id,
+                    idle_for,
+                    action: policy.action,
+                });
+            }
+        }
+
+        for event in &events {
+            tracing::info!(
+                session_id = %event.session_id,
+                idle_for = ?event.idle_for,
+                action = ?event.action,
+                "Idle policy triggered"
+            );
+            if event.action == IdleAction::Disconnect {
+                // Already removed sessions are fine to ignore
+                let _ = self.close_session(&event.session_id).await;
+            }
+            let _ = self.idle_events.send(event.clone());
+        }
+
+        events
+    }
+
+    /// Spawn a background task that applies idle policies periodically
+    pub fn spawn_idle_monitor(self: Arc<Self>, interval: Duration) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                self.check_idle().await;
+            }
+        })
+    }
+
     /// Get statistics
     pub async fn stats(&self) -> SessionStats {
         let stats = self.stats.read().await;
@@ -296,6 +463,61 @@ impl SessionManager {
         Ok(count)
     }
 
+    /// Import profiles from a file, handling name collisions
+    ///
+    /// Unlike [`import`](Self::import), which matches profiles by ID, names
+    /// decide what collides, so profiles exported from another machine merge
+    /// sensibly. Collisions between profiles of the file itself are handled
+    /// the same way.
+    pub async fn import_with(
+        &self,
+        path: &Path,
+        mode: ImportMode,
+        on_conflict: NameConflict,
+    ) -> Result<ImportReport, SessionError> {
+        let json = tokio::fs::read_to_string(path).await?;
+        let profiles_vec: Vec<SessionProfile> =
+            serde_json::from_str(&json).map_err(|e| SessionError::Serialization(e.to_string()))?;
+
+        let mut report = ImportReport::default();
+        let mut profiles = self.profiles.write().await;
+        if mode == ImportMode::Replace {
+            report.removed = profiles.len();
+            profiles.clear();
+        }
+        for mut profile in profiles_vec {
+            let taken = profiles
+                .values()
+                .find(|p| p.name == profile.name)
+                .map(|p| p.id);
+            match (taken, on_conflict) {
+                (None, _) => report.added.push(profile.name.clone()),
+                (Some(_), NameConflict::Skip) => {
+                    report.skipped.push(profile.name.clone());
+                    continue;
+                }
+                (Some(id), NameConflict::Overwrite) => {
+                    profile.id = id;
+                    report.overwritten.push(profile.name.clone());
+                }
+                (Some(_), NameConflict::Rename) => {
+                    let name = free_name(&profiles, &profile.name);
+                    report.renamed.push((profile.name.clone(), name.clone()));
+                    profile.name = name;
+                }
+            }
+            let id_taken = profiles
+                .get(&profile.id)
+                .is_some_and(|p| p.name != profile.name);
+            if id_taken {
+                profile.id = Uuid::new_v4();
+            }
+            profiles.insert(profile.id, profile);
+        }
+
+        Ok(report)
+    }
+
     /// Export profiles to a file
     pub async fn export(&self, path: &Path) -> Result<usize, SessionError> {
         let profiles = self.profiles.read().await;
@@ -308,6 +530,34 @@ impl SessionManager {
         tokio::fs::write(path, json).await?;
         Ok(count)
     }
+
+    /// Export profiles to a file, optionally without stored passwords
+    pub async fn export_with(
+        &self,
+        path: &Path,
+        strip_secrets: bool,
+    ) -> Result<usize, SessionError> {
+        let mut profiles_vec = self.list_profiles().await;
+        if strip_secrets {
+            for profile in &mut profiles_vec {
+                profile.auth.clear_secrets();
+            }
+        }
+
+        let json = serde_json::to_string_pretty(&profiles_vec)
+            .map_err(|e| SessionError::Serialization(e.to_string()))?;
+
+        tokio::fs::write(path, json).await?;
+        Ok(profiles_vec.len())
+    }
+}
+
+/// First of `name-2`, `name-3`, ... no profile uses
+fn free_name(profiles: &HashMap<Uuid, SessionProfile>, name: &str) -> String {
+    (2..)
+        .map(|n| format!("{}-{}", name, n))
+        .find(|candidate| !profiles.values().any(|p| &p.name == candidate))
+        .unwrap_or_else(|| name.to_string())
 }
 
 impl Default for SessionManager {
@@ -319,6 +569,7 @@ impl Default for SessionManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::session::profile::AuthConfig;
 
     #[tokio::test]
     async fn session_manager_profile_crud() {
@@ -374,6 +625,76 @@ mod tests {
         assert_eq!(stats.active_count, 0);
     }
 
+    #[tokio::test(start_paused = true)]
+    async fn idle_policy_disconnects_session() {
+        let manager = SessionManager::new();
+        let profile = SessionProfile::new(
+            "Idle".to_string(),
+            "host.com".to_string(),
+            "user".to_string(),
+        )
+        .with_idle_policy(IdlePolicy::disconnect_after(Duration::from_secs(60)));
+        let profile_id = manager.add_profile(profile).await;
+        let session_id = manager.create_session(&profile_id).await.unwrap();
+        let mut events = manager.subscribe_idle_events();
+
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert!(manager.check_idle().await.is_empty());
+
+        tokio::time::advance(Duration::from_secs(31)).await;
+        let triggered = manager.check_idle().await;
+        assert_eq!(triggered.len(), 1);
+        assert_eq!(triggered[0].action, IdleAction::Disconnect);
+        assert!(manager.get_session(&session_id).await.is_none());
+        assert_eq!(events.recv().await.unwrap().session_id, session_id);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_policy_keep_alive_renews_session() {
+        let manager = SessionManager::new();
+        let profile = SessionProfile::new(
+            "Idle".to_string(),
+            "host.com".to_string(),
+            "user".to_string(),
+        )
+        .with_idle_policy(IdlePolicy::keep_alive_after(Duration::from_secs(60)));
+        let profile_id = manager.add_profile(profile).await;
+        let session_id = manager.create_session(&profile_id).await.unwrap();
+
+        tokio::time::advance(Duration::from_secs(45)).await;
+        manager.record_activity(&session_id).await.unwrap();
+        tokio::time::advance(Duration::from_secs(45)).await;
+        assert!(manager.check_idle().await.is_empty());
+
+        tokio::time::advance(Duration::from_secs(20)).await;
+        let triggered = manager.check_idle().await;
+        assert_eq!(triggered.len(), 1);
+        assert_eq!(triggered[0].action, IdleAction::KeepAlive);
+        assert!(manager.get_session(&session_id).await.is_some());
+        assert!(manager.idle_time(&session_id).await.unwrap() < Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tracked_sessions_follow_their_idle_policy() {
+        let manager = SessionManager::new();
+        let session_id = Uuid::new_v4();
+        manager
+            .track_session(
+                session_id,
+                Some(IdlePolicy::disconnect_after(Duration::from_secs(60))),
+            )
+            .await;
+        manager.track_session(Uuid::new_v4(), None).await;
+        assert_eq!(manager.stats().await.active_count, 2);
+
+        tokio::time::advance(Duration::from_secs(61)).await;
+        let triggered = manager.check_idle().await;
+        assert_eq!(triggered.len(), 1);
+        assert_eq!(triggered[0].session_id, session_id);
+        assert!(manager.get_session(&session_id).await.is_none());
+        assert_eq!(manager.stats().await.active_count, 1);
+    }
+
     #[tokio::test]
     async fn session_manager_search() {
         let manager = SessionManager::new();
@@ -418,4 +739,71 @@ mod tests {
         let not_found = manager.get_profile_by_name("Unknown").await;
         assert!(not_found.is_none());
     }
+
+    fn profile(name: &str, host: &str) -> SessionProfile {
+        SessionProfile::new(name.to_string(), host.to_string(), "user".to_string())
+    }
+
+    #[tokio::test]
+    async fn import_merges_by_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("profiles.json");
+        let other = SessionManager::new();
+        other.add_profile(profile("web", "web.new.com")).await;
+        other.add_profile(profile("db", "db.com")).await;
+        other.export(&path).await.unwrap();
+
+        let manager = SessionManager::new();
+        let web = manager.add_profile(profile("web", "web.com")).await;
+        let report = manager
+            .import_with(&path, ImportMode::Merge, NameConflict::Skip)
+            .await
+            .unwrap();
+        assert_eq!(report.added, vec!["db".to_string()]);
+        assert_eq!(report.skipped, vec!["web".to_string()]);
+        assert_eq!(manager.get_profile(&web).await.unwrap().host, "web.com");
+
+        let report = manager
+            .import_with(&path, ImportMode::Merge, NameConflict::Rename)
+            .await
+            .unwrap();
+        assert_eq!(report.renamed.len(), 2);
+        let renamed = manager.get_profile_by_name("web-2").await.unwrap();
+        assert_eq!(renamed.host, "web.new.com");
+        assert_eq!(manager.list_profiles().await.len(), 4);
+
+        let report = manager
+            .import_with(&path, ImportMode::Merge, NameConflict::Overwrite)
+            .await
+            .unwrap();
+        assert_eq!(report.overwritten.len(), 2);
+        assert_eq!(manager.get_profile(&web).await.unwrap().host, "web.new.com");
+        assert_eq!(manager.list_profiles().await.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn export_strips_secrets_and_import_replaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("profiles.json");
+        let manager = SessionManager::new();
+        manager
+            .add_profile(profile("web", "web.com").with_auth(AuthConfig::Password {
+                password: Some("hunter2".to_string()),
+            }))
+            .await;
+        assert_eq!(manager.export_with(&path, true).await.unwrap(), 1);
+        let json = std::fs::read_to_string(&path).unwrap();
+        assert!(!json.contains("hunter2"));
+
+        let other = SessionManager::new();
+        other.add_profile(profile("old", "old.com")).await;
+        let report = other
+            .import_with(&path, ImportMode::Replace, NameConflict::Skip)
+            .await
+            .unwrap();
+        assert_eq!(report.removed, 1);
+        let profiles = other.list_profiles().await;
+        assert_eq!(profiles.len(), 1);
+        assert!(!profiles[0].auth.stores_sensitive_data());
+    }
 }
@@ -6,11 +6,13 @@
 //! - Requirement 8.1: Session parameter completeness
 //! - Requirement 8.2: Session profile serialization
 
+use super::idle::IdlePolicy;
 use crate::ssh::{AuthMethod, PortForward};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::time::Duration;
 use uuid::Uuid;
+use zeroize::Zeroize;
 
 /// Session profile containing all connection parameters
 ///
@@ -39,6 +41,9 @@ pub struct SessionProfile {
     /// Keep-alive interval
     #[serde(with = "option_duration_serde")]
     pub keepalive_interval: Option<Duration>,
+    /// Idle timeout policy (none means sessions never idle out)
+    #[serde(default)]
+    pub idle_policy: Option<IdlePolicy>,
     /// Port forwards to establish
     pub port_forwards: Vec<PortForward>,
     /// Environment variables to set
@@ -65,7 +70,9 @@ pub struct SessionProfile {
 /// - Using `PublicKey` or `Agent` authentication instead
 /// - Storing passwords in a secure keyring/credential manager
 /// - Prompting for passwords at runtime rather than storing them
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Stored passwords are zeroed on drop and never shown by `Debug`.
+#[derive(Clone, Serialize, Deserialize)]
 pub enum AuthConfig {
     /// Password authentication
     ///
@@ -113,13 +120,20 @@ impl AuthConfig {
         matches!(self, AuthConfig::Password { password: Some(_) })
     }
 
+    /// Forget a stored password, keeping the auth method
+    pub fn clear_secrets(&mut self) {
+        if let AuthConfig::Password { password } = self {
+            password.zeroize();
+        }
+    }
+
     /// Convert to AuthMethod for connection
     pub fn to_auth_method(&self, password_prompt: Option<&str>) -> Option<AuthMethod> {
         match self {
             AuthConfig::Password { password } => password
-                .as_ref()
-                .or(password_prompt.map(|s| s.to_string()).as_ref())
-                .map(|p| AuthMethod::Password(p.clone())),
+                .as_deref()
+                .or(password_prompt)
+                .map(|p| AuthMethod::Password(p.to_string())),
             AuthConfig::PublicKey {
                 key_path,
                 encrypted,
@@ -136,6 +150,34 @@ impl AuthConfig {
     }
 }
 
+impl std::fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthConfig::Password { password } => f
+                .debug_struct("Password")
+                .field("password", &password.as_ref().map(|_| "[REDACTED]"))
+                .finish(),
+            AuthConfig::PublicKey {
+                key_path,
+                encrypted,
+            } => f
+                .debug_struct("PublicKey")
+                .field("key_path", key_path)
+                .field("encrypted", encrypted)
+                .finish(),
+            AuthConfig::Agent => f.write_str("Agent"),
+        }
+    }
+}
+
+impl Drop for AuthConfig {
+    fn drop(&mut self) {
+        if let AuthConfig::Password { password } = self {
+            password.zeroize();
+        }
+    }
+}
+
 impl SessionProfile {
     /// Create a new session profile
     pub fn new(name: String, host: String, username: String) -> Self {
@@ -149,6 +191,7 @@ impl SessionProfile {
             auth: AuthConfig::Agent,
             timeout: Duration::from_secs(30),
             keepalive_interval: Some(Duration::from_secs(60)),
+            idle_policy: None,
             port_forwards: Vec::new(),
             environment: Vec::new(),
             startup_command: None,
@@ -184,6 +227,12 @@ impl SessionProfile {
         self
     }
 
+    /// Set idle timeout policy
+    pub fn with_idle_policy(mut self, policy: IdlePolicy) -> Self {
+        self.idle_policy = Some(policy);
+        self
+    }
+
     /// Add port forward
     pub fn with_port_forward(mut self, forward: PortForward) -> Self {
         self.port_forwards.push(forward);
@@ -292,6 +341,21 @@ mod tests {
         assert!(profile.is_complete());
     }
 
+    #[test]
+    fn stored_password_is_not_in_debug_output() {
+        let profile = SessionProfile::new(
+            "Prod".to_string(),
+            "example.com".to_string(),
+            "user".to_string(),
+        )
+        .with_auth(AuthConfig::Password {
+            password: Some("hunter2".to_string()),
+        });
+
+        assert!(profile.auth.stores_sensitive_data());
+        assert!(!format!("{:?}", profile).contains("hunter2"));
+    }
+
     #[test]
     fn session_profile_builder() {
         let profile = SessionProfile::new(
@@ -0,0 +1,367 @@
+//! Read-only Session Sharing
+//!
+//! Shares a live terminal session with selected peers over P2P. Observers
+//! receive the terminal output as an end-to-end encrypted stream and never
+//! send input back. Late joiners are caught up with the scrollback buffer
+//! before live output starts.
+//!
+//! # Requirements Coverage
+//! - Requirement 3.4: Multiplexed bidirectional streams
+//! - Requirement 4.1: End-to-end encryption between peers
+
+use crate::error::P2PError;
+use crate::p2p::{BiStream, P2PConnectionManager, SecureStream, StreamManager};
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::{broadcast, RwLock};
+use tokio::task::AbortHandle;
+use uuid::Uuid;
+
+/// Default scrollback kept for late joiners (256 KB)
+pub const DEFAULT_SCROLLBACK_SIZE: usize = 256 * 1024;
+
+/// Maximum size of a share frame on the wire
+pub const MAX_SHARE_FRAME_SIZE: usize = 4 * 1024 * 1024;
+
+/// Channel capacity for live output frames
+const SHARE_CHANNEL_CAPACITY: usize = 256;
+
+/// Frame sent from the host to observers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ShareFrame {
+    /// First frame on a share stream
+    Hello {
+        /// Shared session identifier
+        share_id: Uuid,
+        /// Terminal width in columns
+        cols: u32,
+        /// Terminal height in rows
+        rows: u32,
+    },
+    /// Scrollback snapshot; replaces whatever the observer has displayed
+    Scrollback { data: Vec<u8> },
+    /// Live terminal output
+    Output { data: Vec<u8> },
+    /// Terminal was resized
+    Resize { cols: u32, rows: u32 },
+    /// The shared session ended
+    Ended,
+}
+
+/// Bounded byte buffer holding the most recent terminal output
+#[derive(Debug, Clone)]
+pub struct Scrollback {
+    data: VecDeque<u8>,
+    capacity: usize,
+}
+
+impl Scrollback {
+    /// Create a scrollback buffer holding at most `capacity` bytes
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            data: VecDeque::with_capacity(capacity.min(DEFAULT_SCROLLBACK_SIZE)),
+            capacity,
+        }
+    }
+
+    /// Append output, discarding the oldest bytes beyond capacity
+    pub fn push(&mut self, bytes: &[u8]) {
+        let bytes = if bytes.len() > self.capacity {
+            &bytes[bytes.len() - self.capacity..]
+        } else {
+            bytes
+        };
+        let overflow = (self.data.len() + bytes.len()).saturating_sub(self.capacity);
+        self.data.drain(..overflow);
+        self.data.extend(bytes);
+    }
+
+    /// Copy the buffered output
+    pub fn snapshot(&self) -> Vec<u8> {
+        self.data.iter().copied().collect()
+    }
+
+    /// Number of buffered bytes
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Check if the buffer is empty
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// Terminal state shared under one lock so snapshots and live output never
+/// overlap or leave gaps
+struct ShareState {
+    scrollback: Scrollback,
+    cols: u32,
+    rows: u32,
+}
+
+/// A terminal session shared read-only with observers
+pub struct SharedSession {
+    /// Shared session identifier
+    share_id: Uuid,
+    /// Scrollback and terminal dimensions
+    state: Arc<RwLock<ShareState>>,
+    /// Live frames for observer tasks
+    frame_tx: broadcast::Sender<ShareFrame>,
+    /// Observer tasks by peer
+    observers: Arc<RwLock<HashMap<NodeId, AbortHandle>>>,
+}
+
+impl SharedSession {
+    /// Create a shared session with the default scrollback size
+    pub fn new(cols: u32, rows: u32) -> Self {
+        Self::with_scrollback(cols, rows, DEFAULT_SCROLLBACK_SIZE)
+    }
+
+    /// Create a shared session with a custom scrollback size
+    pub fn with_scrollback(cols: u32, rows: u32, scrollback_size: usize) -> Self {
+        let (frame_tx, _) = broadcast::channel(SHARE_CHANNEL_CAPACITY);
+        Self {
+            share_id: Uuid::new_v4(),
+            state: Arc::new(RwLock::new(ShareState {
+                scrollback: Scrollback::new(scrollback_size),
+                cols,
+                rows,
+            })),
+            frame_tx,
+            observers: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Get the shared session identifier
+    pub fn share_id(&self) -> Uuid {
+        self.share_id
+    }
+
+    /// Publish terminal output to the scrollback and all observers
+    pub async fn publish(&self, data: &[u8]) {
+        let mut state = self.state.write().await;
+        state.scrollback.push(data);
+        let _ = self.frame_tx.send(ShareFrame::Output {
+            data: data.to_vec(),
+        });
+    }
+
+    /// Publish a terminal resize
+    pub async fn resize(&self, cols: u32, rows: u32) {
+        let mut state = self.state.write().await;
+        state.cols = cols;
+        state.rows = rows;
+        let _ = self.frame_tx.send(ShareFrame::Resize { cols, rows });
+    }
+
+    /// Get the current scrollback contents
+    pub async fn scrollback(&self) -> Vec<u8> {
+        self.state.read().await.scrollback.snapshot()
+    }
+
+    /// Start streaming this session to a peer
+    ///
+    /// Opens an encrypted stream to the peer, sends the scrollback and then
+    /// relays live output until the peer is removed or the stream fails.
+    pub async fn add_observer(
+        &self,
+        manager: &P2PConnectionManager,
+        peer_id: NodeId,
+    ) -> Result<(), P2PError> {
+        let connection = manager.connect(peer_id).await?;
+        let stream = StreamManager::new(connection).open_bi().await?;
+        let mut secure = SecureStream::initiate(stream).await?;
+
+        // Subscribe and snapshot under the same lock so no output is lost or repeated
+        let (mut frame_rx, hello, snapshot) = {
+            let state = self.state.read().await;
+            (
+                self.frame_tx.subscribe(),
+                ShareFrame::Hello {
+                    share_id: self.share_id,
+                    cols: state.cols,
+                    rows: state.rows,
+                },
+                state.scrollback.snapshot(),
+            )
+        };
+        secure.send_json(&hello).await?;
+        secure
+            .send_json(&ShareFrame::Scrollback { data: snapshot })
+            .await?;
+
+        let state = self.state.clone();
+        let observers = self.observers.clone();
+        let task = tokio::spawn(async move {
+            loop {
+                let frame = match frame_rx.recv().await {
+                    Ok(frame) => frame,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(peer_id = %peer_id, skipped, "Observer lagged, resending scrollback");
+                        ShareFrame::Scrollback {
+                            data: state.read().await.scrollback.snapshot(),
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => ShareFrame::Ended,
+                };
+                let ended = frame == ShareFrame::Ended;
+                if let Err(e) = secure.send_json(&frame).await {
+                    tracing::info!(peer_id = %peer_id, error = %e, "Observer stream closed");
+                    break;
+                }
+                if ended {
+                    let _ = secure.finish().await;
+                    break;
+                }
+            }
+            observers.write().await.remove(&peer_id);
+        });
+
+        let previous = self
+            .observers
+            .write()
+            .await
+            .insert(peer_id, task.abort_handle());
+        if let Some(previous) = previous {
+            previous.abort();
+        }
+
+        tracing::info!(peer_id = %peer_id, share_id = %self.share_id, "Observer added");
+        Ok(())
+    }
+
+    /// Stop streaming to a peer
+    pub async fn remove_observer(&self, peer_id: &NodeId) -> bool {
+        match self.observers.write().await.remove(peer_id) {
+            Some(handle) => {
+                handle.abort();
+                tracing::info!(peer_id = %peer_id, "Observer removed");
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// List peers currently observing the session
+    pub async fn observers(&self) -> Vec<NodeId> {
+        self.observers.read().await.keys().copied().collect()
+    }
+
+    /// End the share, notifying all observers
+    pub async fn end(&self) {
+        let _ = self.frame_tx.send(ShareFrame::Ended);
+    }
+}
+
+/// Observer side of a shared session
+pub struct ShareViewer {
+    /// Encrypted stream from the host
+    stream: SecureStream,
+    /// Shared session identifier
+    share_id: Uuid,
+    /// Terminal dimensions announced by the host
+    dimensions: (u32, u32),
+}
+
+impl ShareViewer {
+    /// Accept a share stream opened by the host
+    pub async fn accept(stream: BiStream) -> Result<Self, P2PError> {
+        let mut stream = SecureStream::accept(stream).await?;
+        match stream.recv_json(MAX_SHARE_FRAME_SIZE).await? {
+            ShareFrame::Hello {
+                share_id,
+                cols,
+                rows,
+            } => Ok(Self {
+                stream,
+                share_id,
+                dimensions: (cols, rows),
+            }),
+            other => Err(P2PError::Stream(format!(
+                "Expected share hello, got {:?}",
+                other
+            ))),
+        }
+    }
+
+    /// Get the shared session identifier
+    pub fn share_id(&self) -> Uuid {
+        self.share_id
+    }
+
+    /// Get the last known terminal dimensions
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.dimensions
+    }
+
+    /// Receive the next frame; returns `None` once the share has ended
+    pub async fn next_frame(&mut self) -> Result<Option<ShareFrame>, P2PError> {
+        let frame: ShareFrame = self.stream.recv_json(MAX_SHARE_FRAME_SIZE).await?;
+        match frame {
+            ShareFrame::Ended => Ok(None),
+            ShareFrame::Resize { cols, rows } => {
+                self.dimensions = (cols, rows);
+                Ok(Some(frame))
+            }
+            frame => Ok(Some(frame)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrollback_keeps_most_recent_bytes() {
+        let mut scrollback = Scrollback::new(8);
+        scrollback.push(b"hello");
+        scrollback.push(b" world");
+        assert_eq!(scrollback.len(), 8);
+        assert_eq!(scrollback.snapshot(), b"lo world".to_vec());
+
+        scrollback.push(b"0123456789");
+        assert_eq!(scrollback.snapshot(), b"23456789".to_vec());
+    }
+
+    #[tokio::test]
+    async fn shared_session_records_scrollback() {
+        let session = SharedSession::with_scrollback(80, 24, 1024);
+        let mut frames = session.frame_tx.subscribe();
+
+        session.publish(b"$ ls\r\n").await;
+        session.resize(120, 40).await;
+
+        assert_eq!(session.scrollback().await, b"$ ls\r\n".to_vec());
+        assert_eq!(
+            frames.recv().await.unwrap(),
+            ShareFrame::Output {
+                data: b"$ ls\r\n".to_vec()
+            }
+        );
+        assert_eq!(
+            frames.recv().await.unwrap(),
+            ShareFrame::Resize {
+                cols: 120,
+                rows: 40
+            }
+        );
+        assert!(session.observers().await.is_empty());
+    }
+
+    #[test]
+    fn share_frame_serialization_roundtrip() {
+        let frame = ShareFrame::Hello {
+            share_id: Uuid::new_v4(),
+            cols: 80,
+            rows: 24,
+        };
+        let json = serde_json::to_string(&frame).unwrap();
+        let restored: ShareFrame = serde_json::from_str(&json).unwrap();
+        assert_eq!(frame, restored);
+    }
+}
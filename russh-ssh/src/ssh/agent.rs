@@ -0,0 +1,483 @@
+//! SSH Agent Protocol
+//!
+//! Speaks the ssh-agent protocol in both directions. [`AgentClient`] lists,
+//! adds and removes keys in a running agent such as OpenSSH's `ssh-agent`;
+//! [`KeyAgent`] is an agent of its own that holds [`SshKeyPair`]s in memory
+//! and signs with them on behalf of other tools. Agents are reached over the
+//! Unix socket named by `SSH_AUTH_SOCK`; both sides also work over any
+//! stream for testing.
+//!
+//! The built-in agent does not support key constraints (lifetimes,
+//! confirmation) and refuses keys added with them. RSA keys only sign with
+//! `rsa-sha2-512`.
+//!
+//! # Requirements Coverage
+//! - Requirement 1: Async SSH Connection Management (agent authentication)
+
+use super::keygen::{key_error, SshKeyPair};
+use crate::error::SshError;
+use signature::Signer;
+use ssh_encoding::{Decode, Encode};
+use ssh_key::private::KeypairData;
+use ssh_key::public::KeyData;
+use ssh_key::{Algorithm, HashAlg, PrivateKey, PublicKey};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Environment variable naming the agent socket
+pub const AUTH_SOCK_ENV: &str = "SSH_AUTH_SOCK";
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENT_SUCCESS: u8 = 6;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+const SSH_AGENTC_ADD_IDENTITY: u8 = 17;
+const SSH_AGENTC_REMOVE_IDENTITY: u8 = 18;
+const SSH_AGENTC_REMOVE_ALL_IDENTITIES: u8 = 19;
+const SSH_AGENTC_ADD_ID_CONSTRAINED: u8 = 25;
+
+/// Sign request flag asking for an `rsa-sha2-512` signature
+const SSH_AGENT_RSA_SHA2_512: u32 = 4;
+
+/// Largest message accepted from a peer
+const MAX_MESSAGE: usize = 256 * 1024;
+
+/// A key held by an agent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgentIdentity {
+    /// Public key as an `authorized_keys` line
+    pub public_key: String,
+    /// SHA-256 fingerprint
+    pub fingerprint: String,
+    /// Comment the key was added with
+    pub comment: String,
+    /// Wire encoding of the public key
+    blob: Vec<u8>,
+}
+
+impl AgentIdentity {
+    /// Identify a key by its `authorized_keys` line
+    pub fn from_public_key(line: &str) -> Result<Self, SshError> {
+        let key = PublicKey::from_openssh(line.trim()).map_err(key_error)?;
+        let blob = key.to_bytes().map_err(key_error)?;
+        Ok(Self::new(key, blob))
+    }
+
+    fn from_blob(blob: &[u8], comment: String) -> Result<Self, SshError> {
+        let key_data = KeyData::decode(&mut &blob[..]).map_err(key_error)?;
+        Ok(Self::new(PublicKey::new(key_data, comment), blob.to_vec()))
+    }
+
+    fn from_key(key: &PrivateKey) -> Result<Self, SshError> {
+        let blob = key.public_key().to_bytes().map_err(key_error)?;
+        Ok(Self::new(key.public_key().clone(), blob))
+    }
+
+    fn new(key: PublicKey, blob: Vec<u8>) -> Self {
+        Self {
+            public_key: key.to_openssh().unwrap_or_default(),
+            fingerprint: key.fingerprint(HashAlg::Sha256).to_string(),
+            comment: key.comment().to_string(),
+            blob,
+        }
+    }
+}
+
+/// Talks to a running ssh-agent
+#[derive(Debug)]
+pub struct AgentClient<S> {
+    stream: S,
+}
+
+#[cfg(unix)]
+impl AgentClient<tokio::net::UnixStream> {
+    /// Connect to the agent listening on `path`
+    pub async fn connect(path: &std::path::Path) -> Result<Self, SshError> {
+        let stream = tokio::net::UnixStream::connect(path)
+            .await
+            .map_err(|e| SshError::Agent(format!("{}: {}", path.display(), e)))?;
+        Ok(Self::new(stream))
+    }
+
+    /// Connect to the agent named by `SSH_AUTH_SOCK`
+    pub async fn connect_env() -> Result<Self, SshError> {
+        let path = std::env::var_os(AUTH_SOCK_ENV)
+            .ok_or_else(|| SshError::Agent(format!("{} is not set", AUTH_SOCK_ENV)))?;
+        Self::connect(std::path::Path::new(&path)).await
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AgentClient<S> {
+    /// Use an already connected stream
+    pub fn new(stream: S) -> Self {
+        Self { stream }
+    }
+
+    /// List the keys the agent holds
+    pub async fn identities(&mut self) -> Result<Vec<AgentIdentity>, SshError> {
+        let reply = self.request(&[SSH_AGENTC_REQUEST_IDENTITIES]).await?;
+        let mut wire = Wire(&reply);
+        if wire.byte()? != SSH_AGENT_IDENTITIES_ANSWER {
+            return Err(SshError::Agent("Agent refused to list keys".to_string()));
+        }
+        let count = wire.u32()?;
+        let mut identities = Vec::new();
+        for _ in 0..count {
+            let blob = wire.string()?;
+            let comment = String::from_utf8_lossy(wire.string()?).to_string();
+            identities.push(AgentIdentity::from_blob(blob, comment)?);
+        }
+        Ok(identities)
+    }
+
+    /// Hand a private key to the agent
+    pub async fn add(&mut self, key: &SshKeyPair) -> Result<(), SshError> {
+        let mut message = vec![SSH_AGENTC_ADD_IDENTITY];
+        key.private_key()
+            .key_data()
+            .encode(&mut message)
+            .map_err(encoding_error)?;
+        put_string(&mut message, key.comment().as_bytes());
+        self.expect_success(&message, "Agent refused the key").await
+    }
+
+    /// Remove a key from the agent
+    pub async fn remove(&mut self, identity: &AgentIdentity) -> Result<(), SshError> {
+        let mut message = vec![SSH_AGENTC_REMOVE_IDENTITY];
+        put_string(&mut message, &identity.blob);
+        self.expect_success(&message, "Agent does not hold the key")
+            .await
+    }
+
+    /// Remove every key from the agent
+    pub async fn remove_all(&mut self) -> Result<(), SshError> {
+        self.expect_success(
+            &[SSH_AGENTC_REMOVE_ALL_IDENTITIES],
+            "Agent refused to remove keys",
+        )
+        .await
+    }
+
+    /// Have the agent sign `data`, returning the signature in wire format
+    pub async fn sign(
+        &mut self,
+        identity: &AgentIdentity,
+        data: &[u8],
+    ) -> Result<Vec<u8>, SshError> {
+        let flags = if identity.public_key.starts_with("ssh-rsa ") {
+            SSH_AGENT_RSA_SHA2_512
+        } else {
+            0
+        };
+        let mut message = vec![SSH_AGENTC_SIGN_REQUEST];
+        put_string(&mut message, &identity.blob);
+        put_string(&mut message, data);
+        message.extend_from_slice(&flags.to_be_bytes());
+
+        let reply = self.request(&message).await?;
+        let mut wire = Wire(&reply);
+        if wire.byte()? != SSH_AGENT_SIGN_RESPONSE {
+            return Err(SshError::Agent("Agent refused to sign".to_string()));
+        }
+        Ok(wire.string()?.to_vec())
+    }
+
+    async fn expect_success(&mut self, message: &[u8], refused: &str) -> Result<(), SshError> {
+        match self.request(message).await?.first() {
+            Some(&SSH_AGENT_SUCCESS) => Ok(()),
+            _ => Err(SshError::Agent(refused.to_string())),
+        }
+    }
+
+    async fn request(&mut self, message: &[u8]) -> Result<Vec<u8>, SshError> {
+        write_message(&mut self.stream, message).await?;
+        read_message(&mut self.stream)
+            .await?
+            .ok_or_else(|| SshError::Agent("Agent closed the connection".to_string()))
+    }
+}
+
+/// An ssh-agent serving keys held in memory
+#[derive(Clone, Default)]
+pub struct KeyAgent {
+    keys: Arc<Mutex<Vec<SshKeyPair>>>,
+}
+
+impl KeyAgent {
+    /// Create an agent with no keys
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a key, replacing one with the same public key
+    pub fn add(&self, key: SshKeyPair) {
+        let mut keys = self.lock();
+        keys.retain(|held| held.fingerprint() != key.fingerprint());
+        keys.push(key);
+    }
+
+    /// List the keys held
+    pub fn identities(&self) -> Vec<AgentIdentity> {
+        self.lock()
+            .iter()
+            .filter_map(|key| AgentIdentity::from_key(key.private_key()).ok())
+            .collect()
+    }
+
+    /// Remove a key; returns whether it was held
+    pub fn remove(&self, identity: &AgentIdentity) -> bool {
+        let mut keys = self.lock();
+        let before = keys.len();
+        keys.retain(|key| key.fingerprint() != identity.fingerprint);
+        keys.len() != before
+    }
+
+    /// Listen on a Unix socket until the task is dropped
+    ///
+    /// The socket is created readable only by the owner and replaces a
+    /// stale one at the same path.
+    #[cfg(unix)]
+    pub async fn serve(&self, path: &std::path::Path) -> Result<(), SshError> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let socket_error =
+            |e: std::io::Error| SshError::Agent(format!("{}: {}", path.display(), e));
+        if path.exists() {
+            std::fs::remove_file(path).map_err(socket_error)?;
+        }
+        let listener = tokio::net::UnixListener::bind(path).map_err(socket_error)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .map_err(socket_error)?;
+        tracing::info!(path = %path.display(), "SSH agent listening");
+
+        loop {
+            let (stream, _) = listener.accept().await.map_err(socket_error)?;
+            let agent = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = agent.handle(stream).await {
+                    tracing::debug!(error = %e, "Agent client failed");
+                }
+            });
+        }
+    }
+
+    /// Answer requests on one connection until the client hangs up
+    pub async fn handle<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        mut stream: S,
+    ) -> Result<(), SshError> {
+        while let Some(request) = read_message(&mut stream).await? {
+            let reply = self
+                .respond(&request)
+                .unwrap_or_else(|_| vec![SSH_AGENT_FAILURE]);
+            write_message(&mut stream, &reply).await?;
+        }
+        Ok(())
+    }
+
+    fn respond(&self, request: &[u8]) -> Result<Vec<u8>, SshError> {
+        let mut wire = Wire(request);
+        match wire.byte()? {
+            SSH_AGENTC_REQUEST_IDENTITIES => {
+                let identities = self.identities();
+                let mut reply = vec![SSH_AGENT_IDENTITIES_ANSWER];
+                reply.extend_from_slice(&(identities.len() as u32).to_be_bytes());
+                for identity in identities {
+                    put_string(&mut reply, &identity.blob);
+                    put_string(&mut reply, identity.comment.as_bytes());
+                }
+                Ok(reply)
+            }
+            SSH_AGENTC_SIGN_REQUEST => {
+                let blob = wire.string()?;
+                let data = wire.string()?;
+                let flags = wire.u32()?;
+                let key = self
+                    .lock()
+                    .iter()
+                    .find(|key| {
+                        AgentIdentity::from_key(key.private_key())
+                            .is_ok_and(|identity| identity.blob == blob)
+                    })
+                    .cloned()
+                    .ok_or_else(|| SshError::Agent("Unknown key".to_string()))?;
+                let key = key.private_key();
+                if matches!(key.algorithm(), Algorithm::Rsa { .. })
+                    && flags & SSH_AGENT_RSA_SHA2_512 == 0
+                {
+                    return Err(SshError::Agent(
+                        "Only rsa-sha2-512 signatures are supported".to_string(),
+                    ));
+                }
+                let signature = key
+                    .try_sign(data)
+                    .map_err(|e| SshError::Agent(format!("Signing failed: {}", e)))?;
+                let mut reply = vec![SSH_AGENT_SIGN_RESPONSE];
+                put_string(&mut reply, &Vec::try_from(signature).map_err(key_error)?);
+                Ok(reply)
+            }
+            SSH_AGENTC_ADD_IDENTITY => {
+                let mut rest = wire.0;
+                let key_data = KeypairData::decode(&mut rest).map_err(key_error)?;
+                let comment = String::from_utf8_lossy(Wire(rest).string()?).to_string();
+                let key = PrivateKey::new(key_data, comment).map_err(key_error)?;
+                self.add(SshKeyPair::from_private_key(key));
+                Ok(vec![SSH_AGENT_SUCCESS])
+            }
+            SSH_AGENTC_REMOVE_IDENTITY => {
+                let identity = AgentIdentity::from_blob(wire.string()?, String::new())?;
+                Ok(vec![if self.remove(&identity) {
+                    SSH_AGENT_SUCCESS
+                } else {
+                    SSH_AGENT_FAILURE
+                }])
+            }
+            SSH_AGENTC_REMOVE_ALL_IDENTITIES => {
+                self.lock().clear();
+                Ok(vec![SSH_AGENT_SUCCESS])
+            }
+            // Constraints can't be honoured, so constrained keys are refused
+            SSH_AGENTC_ADD_ID_CONSTRAINED => Ok(vec![SSH_AGENT_FAILURE]),
+            _ => Ok(vec![SSH_AGENT_FAILURE]),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<SshKeyPair>> {
+        self.keys.lock().unwrap_or_else(|poisoned| {
+            tracing::warn!("Agent key lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+}
+
+impl std::fmt::Debug for KeyAgent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyAgent")
+            .field("keys", &self.lock().len())
+            .finish()
+    }
+}
+
+/// Reader for the agent's length-prefixed wire format
+struct Wire<'a>(&'a [u8]);
+
+impl<'a> Wire<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SshError> {
+        if self.0.len() < len {
+            return Err(SshError::Agent("Truncated agent message".to_string()));
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> Result<u8, SshError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, SshError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn string(&mut self) -> Result<&'a [u8], SshError> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+}
+
+fn put_string(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(data);
+}
+
+async fn read_message<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Option<Vec<u8>>, SshError> {
+    let mut len = [0u8; 4];
+    match stream.read_exact(&mut len).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(SshError::Agent(e.to_string())),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len == 0 || len > MAX_MESSAGE {
+        return Err(SshError::Agent(format!("Bad agent message length {}", len)));
+    }
+    let mut message = vec![0; len];
+    stream
+        .read_exact(&mut message)
+        .await
+        .map_err(|e| SshError::Agent(e.to_string()))?;
+    Ok(Some(message))
+}
+
+async fn write_message<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    message: &[u8],
+) -> Result<(), SshError> {
+    let mut frame = Vec::with_capacity(4 + message.len());
+    put_string(&mut frame, message);
+    stream
+        .write_all(&frame)
+        .await
+        .map_err(|e| SshError::Agent(e.to_string()))?;
+    stream
+        .flush()
+        .await
+        .map_err(|e| SshError::Agent(e.to_string()))
+}
+
+fn encoding_error(e: ssh_encoding::Error) -> SshError {
+    SshError::Key(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh::KeyType;
+
+    #[tokio::test]
+    async fn client_manages_and_uses_keys_in_the_built_in_agent() {
+        let agent = KeyAgent::new();
+        let (client_end, agent_end) = tokio::io::duplex(64 * 1024);
+        let server = agent.clone();
+        tokio::spawn(async move { server.handle(agent_end).await });
+        let mut client = AgentClient::new(client_end);
+
+        let key = SshKeyPair::generate(KeyType::Ed25519, "me@laptop").unwrap();
+        client.add(&key).await.unwrap();
+        // Adding the same key again replaces it
+        client.add(&key).await.unwrap();
+
+        let identities = client.identities().await.unwrap();
+        assert_eq!(identities.len(), 1);
+        assert_eq!(identities[0].fingerprint, key.fingerprint());
+        assert_eq!(identities[0].comment, "me@laptop");
+        assert_eq!(
+            identities[0],
+            AgentIdentity::from_public_key(&key.public_key().unwrap()).unwrap()
+        );
+
+        let signature = client.sign(&identities[0], b"challenge").await.unwrap();
+        // string "ssh-ed25519" followed by the 64 byte signature
+        assert_eq!(&signature[4..15], b"ssh-ed25519");
+        assert_eq!(signature.len(), 4 + 11 + 4 + 64);
+
+        client.remove(&identities[0]).await.unwrap();
+        assert!(client.remove(&identities[0]).await.is_err());
+        assert!(client.identities().await.unwrap().is_empty());
+        assert!(agent.identities().is_empty());
+    }
+
+    #[test]
+    fn malformed_requests_get_a_failure() {
+        let agent = KeyAgent::new();
+        assert!(agent.respond(&[SSH_AGENTC_SIGN_REQUEST, 0, 0]).is_err());
+        assert_eq!(agent.respond(&[99]).unwrap(), vec![SSH_AGENT_FAILURE]);
+        assert_eq!(
+            agent.respond(&[SSH_AGENTC_REQUEST_IDENTITIES]).unwrap(),
+            vec![SSH_AGENT_IDENTITIES_ANSWER, 0, 0, 0, 0]
+        );
+    }
+}
@@ -5,14 +5,25 @@
 //! # Requirements Coverage
 //! - Requirement 1.2: Password and key-based authentication methods
 
-use super::{AuthMethod, HostKeyCheck, SshConfig};
+use super::{AuthMethod, HostKeyCheck, SshConfig, Transport};
+use crate::connection::{
+    ConnectionHealth, ConnectionState, HealthConfig, HealthMonitor, HealthProbe,
+    NetworkChangeDetector, ReconnectionController, StateChangeEvent, StateManager,
+};
 use crate::error::{ConnectionError, SshError};
+use crate::events::{EventBus, RusshEvent};
+use crate::p2p::{LocalTunnel, P2PConnectionManager, SSH_SERVICE};
 use async_ssh2_tokio::client::{AuthMethod as SshAuthMethod, Client, ServerCheckMethod};
-use std::net::ToSocketAddrs;
+use async_trait::async_trait;
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::sync::Arc;
 
 use super::forward::ForwardHandle;
+use super::mux::MuxClient;
+use super::pool::PoolKey;
+use super::reconnect::{AutoReconnectConfig, ShellSpec};
 use std::collections::HashMap;
+use tokio::sync::broadcast;
 use tokio::sync::RwLock;
 use tokio::task::AbortHandle;
 use uuid::Uuid;
@@ -26,10 +37,28 @@ type ForwardsMap = HashMap<Uuid, (Arc<ForwardHandle>, AbortHandle)>;
 /// - Public key authentication
 /// - Command execution
 /// - Port forwarding
+/// - Connecting through a P2P peer ([`Transport::P2P`])
+/// - Running commands over a session shared by a [`MuxServer`](super::MuxServer)
 pub struct SshClient {
     client: Option<Client>,
     config: Option<SshConfig>,
     pub(crate) forwards: Arc<RwLock<ForwardsMap>>,
+    /// Connection state, including reconnection progress
+    pub(crate) state_manager: Arc<StateManager>,
+    /// Reconnection controller used by auto-reconnect
+    pub(crate) reconnection_controller: Arc<ReconnectionController>,
+    /// Auto-reconnect policy (disabled when `None`)
+    pub(crate) auto_reconnect: Option<AutoReconnectConfig>,
+    /// Parameters of the last interactive shell, used to re-open it
+    pub(crate) last_shell: Arc<std::sync::RwLock<Option<ShellSpec>>>,
+    /// Event bus for lifecycle events
+    event_bus: Option<EventBus>,
+    /// Task bridging state changes onto the event bus
+    event_bridge: Option<AbortHandle>,
+    /// P2P connection manager used for [`Transport::P2P`]
+    pub(crate) p2p: Option<Arc<P2PConnectionManager>>,
+    /// Multiplexer and session that commands run over instead
+    pub(crate) mux: Option<(MuxClient, PoolKey)>,
 }
 
 impl Default for SshClient {
@@ -45,28 +74,174 @@ impl SshClient {
             client: None,
             config: None,
             forwards: Arc::new(RwLock::new(HashMap::new())),
+            state_manager: Arc::new(StateManager::new()),
+            reconnection_controller: Arc::new(ReconnectionController::new()),
+            auto_reconnect: None,
+            last_shell: Arc::new(std::sync::RwLock::new(None)),
+            event_bus: None,
+            event_bridge: None,
+            p2p: None,
+            mux: None,
         }
     }
 
+    /// Use a P2P connection manager for configs with [`Transport::P2P`]
+    pub fn with_p2p(mut self, manager: Arc<P2PConnectionManager>) -> Self {
+        self.p2p = Some(manager);
+        self
+    }
+
+    /// Run commands over the multiplexer's session for `key` instead of
+    /// connecting
+    ///
+    /// The session must already be open in the multiplexer. Shells and
+    /// port forwards are not available on such a client.
+    pub fn with_mux(mut self, mux: MuxClient, key: PoolKey) -> Self {
+        self.mux = Some((mux, key));
+        self.state_manager.set_state(ConnectionState::Connected);
+        self
+    }
+
+    /// Check whether commands run over a multiplexer's session
+    pub fn is_muxed(&self) -> bool {
+        self.mux.is_some()
+    }
+
+    /// Publish lifecycle events to the given event bus
+    pub fn with_event_bus(mut self, bus: EventBus) -> Self {
+        self.event_bus = Some(bus);
+        self
+    }
+
+    /// Publish an event if an event bus is attached
+    pub(crate) fn emit(&self, event: RusshEvent) {
+        if let Some(bus) = &self.event_bus {
+            bus.publish(event);
+        }
+    }
+
+    /// Enable auto-reconnect with the given policy
+    pub fn with_auto_reconnect(mut self, config: AutoReconnectConfig) -> Self {
+        self.auto_reconnect = Some(config);
+        self
+    }
+
+    /// Enable or disable auto-reconnect on an existing client
+    pub fn set_auto_reconnect(&mut self, config: Option<AutoReconnectConfig>) {
+        self.auto_reconnect = config;
+    }
+
+    /// Get the auto-reconnect policy, if enabled
+    pub fn auto_reconnect(&self) -> Option<&AutoReconnectConfig> {
+        self.auto_reconnect.as_ref()
+    }
+
+    /// Get the current connection state
+    pub fn state(&self) -> ConnectionState {
+        self.state_manager.state()
+    }
+
+    /// Subscribe to connection state changes (including reconnection progress)
+    pub fn subscribe_state_changes(&self) -> broadcast::Receiver<StateChangeEvent> {
+        self.state_manager.subscribe()
+    }
+
+    /// Get the state manager for external state monitoring
+    pub fn state_manager(&self) -> Arc<StateManager> {
+        self.state_manager.clone()
+    }
+
+    /// Get the last measured connection health
+    pub fn health(&self) -> ConnectionHealth {
+        self.state_manager.health()
+    }
+
+    /// Create a health monitor publishing to this client's state manager
+    ///
+    /// Start it with [`HealthMonitor::spawn`], passing the client as probe.
+    pub fn health_monitor(&self, config: HealthConfig) -> Arc<HealthMonitor> {
+        Arc::new(HealthMonitor::new(config, self.state_manager.clone()))
+    }
+
+    /// Mark the session failed when the network changes
+    ///
+    /// The next [`ensure_connected`](Self::ensure_connected) then reconnects
+    /// immediately instead of waiting for the transport to time out.
+    pub fn watch_network(&self, detector: &NetworkChangeDetector) -> tokio::task::JoinHandle<()> {
+        detector.fail_on_change(self.state_manager.clone())
+    }
+
     /// Connect and authenticate to the remote host
     ///
     /// # Requirements Coverage
     /// - Requirement 1.2: Support password and key-based authentication methods
     pub async fn connect(&mut self, config: &SshConfig) -> Result<(), SshError> {
-        let addr = format!("{}:{}", config.host, config.port);
-        let socket_addr = addr
-            .to_socket_addrs()
-            .map_err(|e| ConnectionError::DnsResolution {
-                host: config.host.clone(),
-                reason: e.to_string(),
-            })?
-            .next()
-            .ok_or_else(|| ConnectionError::DnsResolution {
-                host: config.host.clone(),
-                reason: "No address found".to_string(),
-            })?;
+        if let Some(bus) = &self.event_bus {
+            if let Some(previous) = self.event_bridge.take() {
+                previous.abort();
+            }
+            let bridge = bus.bridge_state_changes(config.host.clone(), &self.state_manager);
+            self.event_bridge = Some(bridge.abort_handle());
+        }
 
-        tracing::info!("Connecting to SSH server at {}", addr);
+        self.state_manager.set_state(ConnectionState::Connecting);
+
+        match Self::establish(config, self.p2p.as_ref()).await {
+            Ok(client) => {
+                self.client = Some(client);
+                self.config = Some(config.clone());
+                self.state_manager.set_state(ConnectionState::Connected);
+                self.emit(RusshEvent::Auth {
+                    host: config.host.clone(),
+                    user: config.username.clone(),
+                    success: true,
+                    reason: None,
+                });
+                Ok(())
+            }
+            Err(e) => {
+                self.state_manager.set_state(ConnectionState::Failed {
+                    reason: e.to_string(),
+                });
+                if let SshError::AuthenticationFailed { reason, .. } = &e {
+                    self.emit(RusshEvent::Auth {
+                        host: config.host.clone(),
+                        user: config.username.clone(),
+                        success: false,
+                        reason: Some(reason.clone()),
+                    });
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Perform the TCP connect, SSH handshake and authentication
+    ///
+    /// With [`Transport::P2P`] the TCP stream goes through a loopback
+    /// tunnel to the peer's agent. The tunnel listener is dropped once the
+    /// handshake has connected; the piped connection lives until the SSH
+    /// session closes.
+    pub(crate) async fn establish(
+        config: &SshConfig,
+        p2p: Option<&Arc<P2PConnectionManager>>,
+    ) -> Result<Client, SshError> {
+        let (socket_addr, _tunnel) = match config.transport {
+            Transport::Tcp => (Self::resolve(config)?, None),
+            Transport::P2P(peer_id) => {
+                let manager = p2p.ok_or_else(|| {
+                    ConnectionError::InvalidConfig(
+                        "P2P transport requires a connection manager (SshClient::with_p2p)"
+                            .to_string(),
+                    )
+                })?;
+                tracing::info!(peer_id = %peer_id, "Tunneling SSH connection through peer");
+                let tunnel = LocalTunnel::bind(manager.clone(), peer_id, SSH_SERVICE)
+                    .await
+                    .map_err(|e| ConnectionError::Proxy(e.to_string()))?;
+                (tunnel.local_addr(), Some(tunnel))
+            }
+        };
 
         // Convert our AuthMethod to async-ssh2-tokio's AuthMethod
         let auth_method = match &config.auth {
@@ -118,8 +293,10 @@ impl SshClient {
             }
         };
 
-        let client = Client::connect(socket_addr, &config.username, auth_method, check_method)
+        let connecting = Client::connect(socket_addr, &config.username, auth_method, check_method);
+        let client = tokio::time::timeout(config.timeout, connecting)
             .await
+            .map_err(|_| ConnectionError::Timeout(config.timeout))?
             .map_err(|e| SshError::AuthenticationFailed {
                 user: config.username.clone(),
                 reason: e.to_string(),
@@ -127,17 +304,38 @@ impl SshClient {
 
         tracing::info!("SSH authentication successful for user {}", config.username);
 
-        self.client = Some(client);
-        self.config = Some(config.clone());
-        Ok(())
+        Ok(client)
+    }
+
+    /// Resolve the server address for a direct TCP connection
+    fn resolve(config: &SshConfig) -> Result<SocketAddr, SshError> {
+        let addr = format!("{}:{}", config.host, config.port);
+        let socket_addr = addr
+            .to_socket_addrs()
+            .map_err(|e| ConnectionError::DnsResolution {
+                host: config.host.clone(),
+                reason: e.to_string(),
+            })?
+            .next()
+            .ok_or_else(|| ConnectionError::DnsResolution {
+                host: config.host.clone(),
+                reason: "No address found".to_string(),
+            })?;
+
+        tracing::info!("Connecting to SSH server at {}", addr);
+        Ok(socket_addr)
     }
 
     /// Check if connected
+    ///
+    /// A client attached to a multiplexer counts as connected.
     pub fn is_connected(&self) -> bool {
-        self.client
-            .as_ref()
-            .map(|c| !c.is_closed())
-            .unwrap_or(false)
+        self.mux.is_some()
+            || self
+                .client
+                .as_ref()
+                .map(|c| !c.is_closed())
+                .unwrap_or(false)
     }
 
     /// Get the current configuration
@@ -168,6 +366,9 @@ impl SshClient {
             tracing::info!("Disconnected from SSH server");
         }
         self.config = None;
+        // The multiplexer keeps its session for the next process
+        self.mux = None;
+        self.state_manager.set_state(ConnectionState::Disconnected);
         Ok(())
     }
 
@@ -175,4 +376,20 @@ impl SshClient {
     pub(crate) fn inner(&self) -> Option<&Client> {
         self.client.as_ref()
     }
+
+    /// Replace the inner client after a successful reconnection
+    pub(crate) fn set_inner(&mut self, client: Client) {
+        self.client = Some(client);
+    }
+}
+
+#[async_trait]
+impl HealthProbe for SshClient {
+    /// Run a no-op command to measure the round trip over the session
+    async fn probe(&self) -> Result<(), ConnectionError> {
+        self.execute("true")
+            .await
+            .map(|_| ())
+            .map_err(|e| ConnectionError::ConnectionClosed(e.to_string()))
+    }
 }
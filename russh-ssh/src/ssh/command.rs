@@ -8,6 +8,7 @@
 //! - Requirement 9.3: Return exit code when command completes
 //! - Requirement 9.5: Command timeout handling
 
+use super::reconnect::ShellSpec;
 use super::SshClient;
 use crate::error::SshError;
 use std::time::Duration;
@@ -50,6 +51,10 @@ impl SshClient {
     /// - Requirement 9.1: Execute commands on remote host asynchronously
     /// - Requirement 9.3: Return exit code when command completes
     pub async fn execute(&self, command: &str) -> Result<CommandResult, SshError> {
+        if let Some((mux, key)) = &self.mux {
+            tracing::debug!("Executing command over {}: {}", key, command);
+            return mux.execute(key, command).await;
+        }
         let client = self.inner().ok_or(SshError::NotConnected)?;
 
         tracing::debug!("Executing command: {}", command);
@@ -291,15 +296,44 @@ impl SshClient {
     /// Note: The async-ssh2-tokio library handles PTY allocation internally
     /// when request_pty=true is passed to execute_io.
     pub async fn open_shell(&self, term: &str, cols: u32, rows: u32) -> Result<Shell, SshError> {
+        self.open_shell_with_command("/bin/sh", term, cols, rows)
+            .await
+    }
+
+    /// Open an interactive PTY session running a specific command
+    ///
+    /// Used to re-open a shell after reconnection, e.g. with a
+    /// `tmux new-session -A` command to reattach a persistent session.
+    /// The shell parameters are remembered so auto-reconnect can restore them.
+    pub async fn open_shell_with_command(
+        &self,
+        command: &str,
+        term: &str,
+        cols: u32,
+        rows: u32,
+    ) -> Result<Shell, SshError> {
         let client = self.inner().ok_or(SshError::NotConnected)?;
 
         tracing::debug!(
-            "Opening shell with PTY: term={}, cols={}, rows={}",
+            "Opening shell with PTY: command={}, term={}, cols={}, rows={}",
+            command,
             term,
             cols,
             rows
         );
 
+        {
+            let mut last_shell = self.last_shell.write().unwrap_or_else(|poisoned| {
+                tracing::warn!("Shell spec lock was poisoned, recovering");
+                poisoned.into_inner()
+            });
+            *last_shell = Some(ShellSpec {
+                term: term.to_string(),
+                cols,
+                rows,
+            });
+        }
+
         // Create channels for I/O
         let (stdin_tx, stdin_rx) = mpsc::channel::<Vec<u8>>(32);
         let (stdout_tx, stdout_rx) = mpsc::channel::<Vec<u8>>(32);
@@ -307,6 +341,7 @@ impl SshClient {
         // Clone client for the background task
         let client_clone = client.clone();
         let term_clone = term.to_string();
+        let command = command.to_string();
 
         // Spawn background task to run the shell
         tokio::spawn(async move {
@@ -314,7 +349,7 @@ impl SshClient {
             // The PTY flag enables pseudo-terminal allocation
             let result = client_clone
                 .execute_io(
-                    "/bin/sh", // Use sh as the shell command
+                    &command,
                     stdout_tx,
                     None, // stderr goes to stdout when PTY is enabled
                     Some(stdin_rx),
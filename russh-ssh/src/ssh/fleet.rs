@@ -0,0 +1,213 @@
+//! Running a command across many hosts
+//!
+//! [`FleetExecutor`] connects to every host, runs one command and
+//! disconnects, keeping at most `parallelism` sessions open at once.
+//! Results are delivered as hosts finish, so output can be shown without
+//! waiting for the slowest host. With a [`MuxClient`], sessions the
+//! multiplexer holds are reused and new ones are left open in it.
+//!
+//! # Requirements Coverage
+//! - Requirement 9: Command Execution
+
+use super::{CommandResult, MuxClient, SshClient, SshConfig};
+use crate::error::SshError;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Semaphore};
+
+/// A host to run the command on
+#[derive(Debug, Clone)]
+pub struct FleetHost {
+    /// Name shown with the host's output, e.g. a profile name
+    pub name: String,
+    /// How to reach the host
+    pub config: SshConfig,
+}
+
+impl FleetHost {
+    /// Create a host entry
+    pub fn new(name: impl Into<String>, config: SshConfig) -> Self {
+        Self {
+            name: name.into(),
+            config,
+        }
+    }
+}
+
+/// What running the command on one host produced
+#[derive(Debug)]
+pub struct HostResult {
+    /// Position of the host in the list given to the executor
+    pub index: usize,
+    /// Name of the host
+    pub name: String,
+    /// Command output, or why the host could not run it
+    pub result: Result<CommandResult, SshError>,
+    /// Time from connecting to disconnecting
+    pub elapsed: Duration,
+}
+
+impl HostResult {
+    /// Whether the command ran and exited with status 0
+    pub fn success(&self) -> bool {
+        self.result.as_ref().is_ok_and(CommandResult::success)
+    }
+}
+
+/// Runs a command on many hosts in parallel
+#[derive(Debug, Clone)]
+pub struct FleetExecutor {
+    parallelism: usize,
+    timeout: Option<Duration>,
+    mux: Option<MuxClient>,
+}
+
+impl Default for FleetExecutor {
+    fn default() -> Self {
+        Self::new(10)
+    }
+}
+
+impl FleetExecutor {
+    /// Create an executor running on up to `parallelism` hosts at once
+    pub fn new(parallelism: usize) -> Self {
+        Self {
+            parallelism: parallelism.max(1),
+            timeout: None,
+            mux: None,
+        }
+    }
+
+    /// Fail hosts whose command runs longer than `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Run the command over sessions shared by a multiplexer
+    pub fn with_mux(mut self, mux: MuxClient) -> Self {
+        self.mux = Some(mux);
+        self
+    }
+
+    /// Get the maximum number of hosts in flight
+    pub fn parallelism(&self) -> usize {
+        self.parallelism
+    }
+
+    /// Start running `command` on every host
+    ///
+    /// Results arrive on the returned channel in the order hosts finish;
+    /// it closes after the last one.
+    pub fn spawn(&self, hosts: Vec<FleetHost>, command: &str) -> mpsc::Receiver<HostResult> {
+        let (tx, rx) = mpsc::channel(hosts.len().max(1));
+        let permits = Arc::new(Semaphore::new(self.parallelism));
+        let command: Arc<str> = Arc::from(command);
+
+        for (index, host) in hosts.into_iter().enumerate() {
+            let tx = tx.clone();
+            let permits = permits.clone();
+            let command = command.clone();
+            let timeout = self.timeout;
+            let mux = self.mux.clone();
+            tokio::spawn(async move {
+                // The semaphore is never closed
+                let _permit = permits.acquire_owned().await;
+                let started = Instant::now();
+                let result = run_on_host(&host.config, &command, timeout, mux.as_ref()).await;
+                let _ = tx
+                    .send(HostResult {
+                        index,
+                        name: host.name,
+                        result,
+                        elapsed: started.elapsed(),
+                    })
+                    .await;
+            });
+        }
+        rx
+    }
+
+    /// Run `command` on every host and return the results in host order
+    pub async fn run(&self, hosts: Vec<FleetHost>, command: &str) -> Vec<HostResult> {
+        let mut rx = self.spawn(hosts, command);
+        let mut results = Vec::new();
+        while let Some(result) = rx.recv().await {
+            results.push(result);
+        }
+        results.sort_by_key(|result| result.index);
+        results
+    }
+}
+
+async fn run_on_host(
+    config: &SshConfig,
+    command: &str,
+    timeout: Option<Duration>,
+    mux: Option<&MuxClient>,
+) -> Result<CommandResult, SshError> {
+    let mut client = match mux {
+        Some(mux) => mux.attach(config).await?,
+        None => {
+            let mut client = SshClient::new();
+            client.connect(config).await?;
+            client
+        }
+    };
+    let result = match timeout {
+        Some(timeout) => client.execute_with_timeout(command, timeout).await,
+        None => client.execute(command).await,
+    };
+    if let Err(e) = client.disconnect().await {
+        tracing::debug!(host = %config.host, error = %e, "Disconnect after fleet command failed");
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh::{AuthMethod, HostKeyCheck, Transport};
+
+    fn unreachable_host(name: &str, port: u16) -> FleetHost {
+        FleetHost::new(
+            name,
+            SshConfig {
+                host: "127.0.0.1".to_string(),
+                port,
+                username: "user".to_string(),
+                auth: AuthMethod::Password("secret".to_string()),
+                timeout: Duration::from_secs(5),
+                known_hosts_path: None,
+                host_key_check: HostKeyCheck::None,
+                transport: Transport::Tcp,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn every_host_reports_in_host_order() {
+        // A port nothing listens on
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let hosts = vec![
+            unreachable_host("web-1", port),
+            unreachable_host("web-2", port),
+            unreachable_host("web-3", port),
+        ];
+
+        let results = FleetExecutor::new(2).run(hosts, "uptime").await;
+        let names: Vec<&str> = results.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["web-1", "web-2", "web-3"]);
+        assert!(results.iter().all(|r| r.result.is_err() && !r.success()));
+    }
+
+    #[test]
+    fn parallelism_is_at_least_one() {
+        assert_eq!(FleetExecutor::new(0).parallelism(), 1);
+        assert_eq!(FleetExecutor::default().parallelism(), 10);
+    }
+}
@@ -1,25 +1,31 @@
 //! SSH Port Forwarding
 //!
-//! Handles local, remote, and dynamic port forwarding.
+//! Handles local and dynamic port forwarding. Remote forwards are refused
+//! with [`ForwardError::Unsupported`]: the SSH library underneath cannot
+//! request `tcpip-forward` or accept the channels it opens.
 //!
 //! # Requirements Coverage
 //! - Requirement 10.1: Local port forwarding
-//! - Requirement 10.2: Remote port forwarding (limited support)
+//! - Requirement 10.2: Remote port forwarding (rejected as unsupported)
 //! - Requirement 10.3: Dynamic port forwarding (SOCKS5 proxy)
 //! - Requirement 10.4: Concurrent forward management
 //! - Requirement 10.5: Graceful failure handling
 
 use super::SshClient;
 use crate::error::{ForwardError, SshError};
+use crate::events::RusshEvent;
 use async_trait::async_trait;
+use std::io;
+use std::pin::Pin;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
 use tokio::net::{TcpListener, TcpStream};
 use uuid::Uuid;
 
 /// Port forward configuration
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum PortForward {
     /// Local port forwarding (Local -> Remote)
     Local {
@@ -57,6 +63,58 @@ impl ForwardHandle {
     pub fn inc_bytes(&self, bytes: u64) {
         self.bytes_transferred.fetch_add(bytes, Ordering::Relaxed);
     }
+
+    /// Bytes carried in both directions so far
+    pub fn bytes(&self) -> u64 {
+        self.bytes_transferred.load(Ordering::Relaxed)
+    }
+}
+
+/// Counts the bytes passing through a forwarded connection on its handle
+struct Metered<S> {
+    inner: S,
+    handle: Arc<ForwardHandle>,
+}
+
+impl<S> Metered<S> {
+    fn new(inner: S, handle: Arc<ForwardHandle>) -> Self {
+        Self { inner, handle }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Metered<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        self.handle.inc_bytes((buf.filled().len() - before) as u64);
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Metered<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, data);
+        if let Poll::Ready(Ok(written)) = &poll {
+            self.handle.inc_bytes(*written as u64);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
 }
 
 /// Port forwarder trait
@@ -78,6 +136,50 @@ impl PortForwarder for SshClient {
     async fn start_forward(
         &self,
         forward: PortForward,
+    ) -> Result<Arc<ForwardHandle>, ForwardError> {
+        match self.start_forward_inner(forward.clone()).await {
+            Ok(handle) => {
+                self.emit(RusshEvent::ForwardStarted {
+                    id: handle.id,
+                    forward,
+                });
+                Ok(handle)
+            }
+            Err(e) => {
+                self.emit(RusshEvent::ForwardFailed {
+                    forward,
+                    reason: e.to_string(),
+                });
+                Err(e)
+            }
+        }
+    }
+
+    async fn stop_forward(&self, id: Uuid) -> Result<(), ForwardError> {
+        let mut forwards = self.forwards.write().await;
+        if let Some((_, abort_handle)) = forwards.remove(&id) {
+            abort_handle.abort();
+            self.emit(RusshEvent::ForwardStopped { id });
+            Ok(())
+        } else {
+            Err(ForwardError::NotFound(id.to_string()))
+        }
+    }
+
+    async fn list_forwards(&self) -> Vec<Arc<ForwardHandle>> {
+        let forwards = self.forwards.read().await;
+        forwards
+            .values()
+            .map(|(handle, _)| handle.clone())
+            .collect()
+    }
+}
+
+impl SshClient {
+    /// Bind the forward and spawn its background task
+    async fn start_forward_inner(
+        &self,
+        forward: PortForward,
     ) -> Result<Arc<ForwardHandle>, ForwardError> {
         let client = self
             .inner()
@@ -106,13 +208,14 @@ impl PortForwarder for SshClient {
                 // Clone the client for use in the spawned task
                 // Note: async-ssh2-tokio Client should be Clone
                 let client_clone = client.clone();
+                let forward_handle = handle.clone();
 
                 let task = tokio::spawn(async move {
                     tracing::info!("Started local forward on port {}", local_port);
 
                     loop {
                         match listener.accept().await {
-                            Ok((mut local_stream, addr)) => {
+                            Ok((local_stream, addr)) => {
                                 tracing::debug!(
                                     "Accepted connection from {} for forward to {}:{}",
                                     addr,
@@ -123,6 +226,8 @@ impl PortForwarder for SshClient {
                                 let host = remote_host.clone();
                                 let port = remote_port;
                                 let client_for_conn = client_clone.clone();
+                                let mut local_stream =
+                                    Metered::new(local_stream, forward_handle.clone());
 
                                 tokio::spawn(async move {
                                     tracing::debug!(
@@ -178,62 +283,13 @@ impl PortForwarder for SshClient {
                 });
                 task.abort_handle()
             }
-            PortForward::Remote {
-                remote_port,
-                local_host,
-                local_port,
-            } => {
-                // Remote port forwarding: The SSH server listens on remote_port and
-                // forwards connections to local_host:local_port on the client side.
-                //
-                // Note: This requires the SSH server to support tcpip-forward requests.
-                // The async-ssh2-tokio library doesn't directly expose this functionality,
-                // so we implement it using SSH command execution to set up the forward.
-                //
-                // For full remote port forwarding support, consider using the russh
-                // library directly or an SSH server that supports reverse tunnels.
-
-                let remote_port = *remote_port;
-                let local_host = local_host.clone();
-                let local_port = *local_port;
-
-                // We'll use socat or netcat on the remote side if available
-                // This is a workaround since direct tcpip-forward isn't exposed
-                let client_clone = client.clone();
-
-                let task = tokio::spawn(async move {
-                    tracing::info!(
-                        "Starting remote forward: remote:{} -> {}:{}",
-                        remote_port,
-                        local_host,
-                        local_port
-                    );
-
-                    // Try to set up a reverse tunnel using SSH command
-                    // This requires socat or similar on the remote host
-                    let cmd = format!(
-                        "socat TCP-LISTEN:{},fork,reuseaddr TCP:{}:{} 2>/dev/null || \
-                         nc -l -p {} -c 'nc {} {}' 2>/dev/null || \
-                         echo 'Remote forwarding requires socat or netcat on remote host'",
-                        remote_port, local_host, local_port, remote_port, local_host, local_port
-                    );
-
-                    match client_clone.execute(&cmd).await {
-                        Ok(result) => {
-                            if result.exit_status != 0 {
-                                tracing::warn!(
-                                    "Remote forward command exited with status {}: {}",
-                                    result.exit_status,
-                                    result.stdout
-                                );
-                            }
-                        }
-                        Err(e) => {
-                            tracing::error!("Remote forward failed: {}", e);
-                        }
-                    }
-                });
-                task.abort_handle()
+            PortForward::Remote { .. } => {
+                // The server would have to open forwarded-tcpip channels back
+                // to us, which async-ssh2-tokio neither requests nor accepts
+                return Err(ForwardError::Unsupported(
+                    "remote port forwarding needs tcpip-forward, which this client does not implement"
+                        .to_string(),
+                ));
             }
             PortForward::Dynamic { local_port } => {
                 // Dynamic port forwarding: SOCKS5 proxy
@@ -248,6 +304,7 @@ impl PortForwarder for SshClient {
 
                 let local_port = *local_port;
                 let client_clone = client.clone();
+                let forward_handle = handle.clone();
 
                 let task = tokio::spawn(async move {
                     tracing::info!("Started SOCKS5 proxy on port {}", local_port);
@@ -257,10 +314,15 @@ impl PortForwarder for SshClient {
                             Ok((stream, addr)) => {
                                 tracing::debug!("SOCKS5: Accepted connection from {}", addr);
                                 let client_for_conn = client_clone.clone();
+                                let forward_handle = forward_handle.clone();
 
                                 tokio::spawn(async move {
-                                    if let Err(e) =
-                                        handle_socks5_connection(stream, client_for_conn).await
+                                    if let Err(e) = handle_socks5_connection(
+                                        stream,
+                                        client_for_conn,
+                                        forward_handle,
+                                    )
+                                    .await
                                     {
                                         tracing::debug!("SOCKS5 connection error: {}", e);
                                     }
@@ -282,24 +344,6 @@ impl PortForwarder for SshClient {
 
         Ok(handle)
     }
-
-    async fn stop_forward(&self, id: Uuid) -> Result<(), ForwardError> {
-        let mut forwards = self.forwards.write().await;
-        if let Some((_, abort_handle)) = forwards.remove(&id) {
-            abort_handle.abort();
-            Ok(())
-        } else {
-            Err(ForwardError::NotFound(id.to_string()))
-        }
-    }
-
-    async fn list_forwards(&self) -> Vec<Arc<ForwardHandle>> {
-        let forwards = self.forwards.read().await;
-        forwards
-            .values()
-            .map(|(handle, _)| handle.clone())
-            .collect()
-    }
 }
 
 /// Handle a SOCKS5 connection
@@ -309,6 +353,7 @@ impl PortForwarder for SshClient {
 async fn handle_socks5_connection(
     mut stream: TcpStream,
     client: async_ssh2_tokio::client::Client,
+    handle: Arc<ForwardHandle>,
 ) -> Result<(), ForwardError> {
     // SOCKS5 greeting
     let mut buf = [0u8; 2];
@@ -426,7 +471,8 @@ async fn handle_socks5_connection(
 
             // Bridge the streams
             let mut channel_stream = channel.into_stream();
-            match tokio::io::copy_bidirectional(&mut stream, &mut channel_stream).await {
+            let mut local_stream = Metered::new(&mut stream, handle);
+            match tokio::io::copy_bidirectional(&mut local_stream, &mut channel_stream).await {
                 Ok((sent, received)) => {
                     tracing::debug!(
                         "SOCKS5 connection to {}:{} closed. Sent: {}, Received: {}",
@@ -462,3 +508,26 @@ async fn handle_socks5_connection(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn metered_streams_count_both_directions() {
+        let handle = Arc::new(ForwardHandle::new(
+            Uuid::new_v4(),
+            PortForward::Dynamic { local_port: 1080 },
+        ));
+        let (local, mut remote) = tokio::io::duplex(64);
+        let mut metered = Metered::new(local, handle.clone());
+
+        metered.write_all(b"hello").await.unwrap();
+        remote.write_all(b"world!").await.unwrap();
+        let mut buf = [0u8; 6];
+        metered.read_exact(&mut buf).await.unwrap();
+
+        assert_eq!(&buf, b"world!");
+        assert_eq!(handle.bytes(), 11);
+    }
+}
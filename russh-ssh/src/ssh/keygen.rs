@@ -0,0 +1,292 @@
+//! SSH Key Generation
+//!
+//! Creates user keys in the formats OpenSSH reads: the private key in the
+//! `openssh-key-v1` format, optionally encrypted with a passphrase, and the
+//! public key as an `authorized_keys` line in a file next to it with a
+//! `.pub` suffix. Private key files are readable only by the owner on Unix.
+//!
+//! # Requirements Coverage
+//! - Requirement 1: Async SSH Connection Management (public key authentication)
+
+use crate::error::SshError;
+use rand::rngs::OsRng;
+use ssh_key::private::{KeypairData, RsaKeypair};
+use ssh_key::{Algorithm, EcdsaCurve, HashAlg, LineEnding, PrivateKey};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// RSA key size used when none is given
+pub const DEFAULT_RSA_BITS: usize = 3072;
+
+/// Smallest RSA key size accepted
+pub const MIN_RSA_BITS: usize = 2048;
+
+/// Kind of key to generate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    /// Ed25519, the recommended default
+    Ed25519,
+    /// RSA with the given modulus size
+    Rsa { bits: usize },
+    /// ECDSA over the NIST curve of the given size (256, 384 or 521)
+    Ecdsa { bits: usize },
+}
+
+impl KeyType {
+    /// Parse a type name as `ssh-keygen -t` takes it, with an optional size
+    pub fn parse(name: &str, bits: Option<usize>) -> Result<Self, SshError> {
+        let key_type = match name.to_ascii_lowercase().as_str() {
+            "ed25519" => Self::Ed25519,
+            "rsa" => Self::Rsa {
+                bits: bits.unwrap_or(DEFAULT_RSA_BITS),
+            },
+            "ecdsa" => Self::Ecdsa {
+                bits: bits.unwrap_or(256),
+            },
+            other => return Err(SshError::Key(format!("Unknown key type: {}", other))),
+        };
+        match key_type {
+            Self::Ed25519 if bits.is_some_and(|bits| bits != 256) => {
+                Err(SshError::Key("Ed25519 keys have a fixed size".to_string()))
+            }
+            Self::Rsa { bits } if !(MIN_RSA_BITS..=16384).contains(&bits) => Err(SshError::Key(
+                format!("RSA keys must be {} to 16384 bits", MIN_RSA_BITS),
+            )),
+            Self::Ecdsa { bits } if ![256, 384, 521].contains(&bits) => Err(SshError::Key(
+                "ECDSA keys must be 256, 384 or 521 bits".to_string(),
+            )),
+            key_type => Ok(key_type),
+        }
+    }
+
+    /// Get the type name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ed25519 => "ed25519",
+            Self::Rsa { .. } => "rsa",
+            Self::Ecdsa { .. } => "ecdsa",
+        }
+    }
+
+    /// File name OpenSSH uses for this kind of key, e.g. `id_ed25519`
+    pub fn default_file_name(&self) -> String {
+        format!("id_{}", self.name())
+    }
+}
+
+impl std::fmt::Display for KeyType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ed25519 => f.write_str("ed25519"),
+            Self::Rsa { bits } | Self::Ecdsa { bits } => write!(f, "{}-{}", self.name(), bits),
+        }
+    }
+}
+
+/// A user key pair in OpenSSH format
+#[derive(Clone)]
+pub struct SshKeyPair {
+    key: PrivateKey,
+}
+
+impl SshKeyPair {
+    /// Generate a new random key pair
+    ///
+    /// Large RSA keys can take several seconds.
+    pub fn generate(key_type: KeyType, comment: &str) -> Result<Self, SshError> {
+        let mut key = match key_type {
+            KeyType::Ed25519 => PrivateKey::random(&mut OsRng, Algorithm::Ed25519),
+            KeyType::Rsa { bits } => RsaKeypair::random(&mut OsRng, bits)
+                .and_then(|rsa| PrivateKey::new(KeypairData::from(rsa), comment)),
+            KeyType::Ecdsa { bits } => {
+                let curve = match bits {
+                    384 => EcdsaCurve::NistP384,
+                    521 => EcdsaCurve::NistP521,
+                    _ => EcdsaCurve::NistP256,
+                };
+                PrivateKey::random(&mut OsRng, Algorithm::Ecdsa { curve })
+            }
+        }
+        .map_err(key_error)?;
+        key.set_comment(comment);
+        Ok(Self { key })
+    }
+
+    pub(crate) fn from_private_key(key: PrivateKey) -> Self {
+        Self { key }
+    }
+
+    pub(crate) fn private_key(&self) -> &PrivateKey {
+        &self.key
+    }
+
+    /// Read a private key file, decrypting it with `passphrase` if needed
+    pub fn load(path: &Path, passphrase: Option<&str>) -> Result<Self, SshError> {
+        let pem = std::fs::read_to_string(path)
+            .map_err(|e| SshError::Key(format!("{}: {}", path.display(), e)))?;
+        Self::decode(&pem, passphrase, &path.display().to_string())
+    }
+
+    /// Parse a private key in OpenSSH format, e.g. pasted by the user
+    pub fn from_openssh(pem: &str, passphrase: Option<&str>) -> Result<Self, SshError> {
+        Self::decode(pem, passphrase, "The key")
+    }
+
+    fn decode(pem: &str, passphrase: Option<&str>, source: &str) -> Result<Self, SshError> {
+        let key = PrivateKey::from_openssh(pem.trim()).map_err(key_error)?;
+        if !key.is_encrypted() {
+            return Ok(Self { key });
+        }
+        let passphrase = passphrase
+            .ok_or_else(|| SshError::Key(format!("{} is protected by a passphrase", source)))?;
+        let key = key
+            .decrypt(passphrase)
+            .map_err(|_| SshError::Key("Incorrect passphrase".to_string()))?;
+        Ok(Self { key })
+    }
+
+    /// Check whether a private key file needs a passphrase to load
+    pub fn is_encrypted_file(path: &Path) -> Result<bool, SshError> {
+        let pem = std::fs::read_to_string(path)
+            .map_err(|e| SshError::Key(format!("{}: {}", path.display(), e)))?;
+        Ok(PrivateKey::from_openssh(pem)
+            .map_err(key_error)?
+            .is_encrypted())
+    }
+
+    /// Get the SSH algorithm name, e.g. `ssh-ed25519`
+    pub fn algorithm(&self) -> String {
+        self.key.algorithm().to_string()
+    }
+
+    /// Get the comment stored with the key
+    pub fn comment(&self) -> &str {
+        self.key.comment()
+    }
+
+    /// SHA-256 fingerprint as `ssh-keygen -l` prints it
+    pub fn fingerprint(&self) -> String {
+        self.key.fingerprint(HashAlg::Sha256).to_string()
+    }
+
+    /// Public key as an `authorized_keys` line
+    pub fn public_key(&self) -> Result<String, SshError> {
+        self.key.public_key().to_openssh().map_err(key_error)
+    }
+
+    /// Write the private key to `path` and the public key to `path.pub`
+    ///
+    /// With a passphrase the private key is encrypted. Returns the path of
+    /// the public key file.
+    pub fn save(&self, path: &Path, passphrase: Option<&str>) -> Result<PathBuf, SshError> {
+        let storage_error = |e: std::io::Error| SshError::Key(format!("{}: {}", path.display(), e));
+
+        let private = match passphrase.filter(|p| !p.is_empty()) {
+            Some(passphrase) => self
+                .key
+                .encrypt(&mut OsRng, passphrase)
+                .and_then(|key| key.to_openssh(LineEnding::LF)),
+            None => self.key.to_openssh(LineEnding::LF),
+        }
+        .map_err(key_error)?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(storage_error)?;
+        }
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600); // rw-------
+        }
+        let mut file = options.open(path).map_err(storage_error)?;
+        file.write_all(private.as_bytes()).map_err(storage_error)?;
+        file.sync_all().map_err(storage_error)?;
+
+        let mut public_path = path.as_os_str().to_owned();
+        public_path.push(".pub");
+        let public_path = PathBuf::from(public_path);
+        let mut public = self.public_key()?;
+        public.push('\n');
+        std::fs::write(&public_path, public)
+            .map_err(|e| SshError::Key(format!("{}: {}", public_path.display(), e)))?;
+        Ok(public_path)
+    }
+}
+
+impl std::fmt::Debug for SshKeyPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SshKeyPair")
+            .field("algorithm", &self.algorithm())
+            .field("fingerprint", &self.fingerprint())
+            .finish()
+    }
+}
+
+/// Fingerprint of a public key given as an `authorized_keys` line
+pub fn public_key_fingerprint(line: &str) -> Result<String, SshError> {
+    let key = ssh_key::PublicKey::from_openssh(line.trim()).map_err(key_error)?;
+    Ok(key.fingerprint(HashAlg::Sha256).to_string())
+}
+
+pub(crate) fn key_error(e: ssh_key::Error) -> SshError {
+    SshError::Key(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_types_parse_like_ssh_keygen() {
+        assert_eq!(KeyType::parse("ed25519", None).unwrap(), KeyType::Ed25519);
+        assert_eq!(
+            KeyType::parse("RSA", None).unwrap(),
+            KeyType::Rsa {
+                bits: DEFAULT_RSA_BITS
+            }
+        );
+        assert_eq!(
+            KeyType::parse("ecdsa", Some(384)).unwrap(),
+            KeyType::Ecdsa { bits: 384 }
+        );
+        assert!(KeyType::parse("ecdsa", Some(300)).is_err());
+        assert!(KeyType::parse("rsa", Some(1024)).is_err());
+        assert!(KeyType::parse("dsa", None).is_err());
+        assert_eq!(KeyType::Ed25519.default_file_name(), "id_ed25519");
+    }
+
+    #[test]
+    fn saved_keys_load_back_with_their_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys/id_ecdsa");
+        let key = SshKeyPair::generate(KeyType::Ecdsa { bits: 256 }, "me@laptop").unwrap();
+        let public_path = key.save(&path, Some("hunter2")).unwrap();
+
+        let public = std::fs::read_to_string(&public_path).unwrap();
+        assert!(public.starts_with("ecdsa-sha2-nistp256 "));
+        assert!(public.trim_end().ends_with(" me@laptop"));
+        assert!(key.fingerprint().starts_with("SHA256:"));
+
+        assert!(SshKeyPair::is_encrypted_file(&path).unwrap());
+        assert!(SshKeyPair::load(&path, None).is_err());
+        assert!(SshKeyPair::load(&path, Some("wrong")).is_err());
+        let loaded = SshKeyPair::load(&path, Some("hunter2")).unwrap();
+        assert_eq!(loaded.fingerprint(), key.fingerprint());
+        assert_eq!(loaded.comment(), "me@laptop");
+        assert_eq!(public_key_fingerprint(&public).unwrap(), key.fingerprint());
+
+        let pem = std::fs::read_to_string(&path).unwrap();
+        assert!(SshKeyPair::from_openssh(&pem, None).is_err());
+        let pasted = SshKeyPair::from_openssh(&pem, Some("hunter2")).unwrap();
+        assert_eq!(pasted.fingerprint(), key.fingerprint());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
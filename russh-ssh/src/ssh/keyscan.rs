@@ -0,0 +1,442 @@
+//! Fetching Host Keys
+//!
+//! Asks a server for its host keys the way `ssh-keyscan` does, so they can
+//! be pinned in known_hosts before the first connection. For each key type
+//! the server offers, one connection runs the key exchange up to the
+//! server's reply, which carries the host key and its signature over the
+//! exchange hash, then hangs up. The signature is checked, so a returned key
+//! is one the server holds the private half of; no user authentication
+//! takes place.
+//!
+//! Only `curve25519-sha256` key exchange is used, which every OpenSSH
+//! release since 6.5 offers.
+
+use super::keygen::key_error;
+use crate::error::{ConnectionError, SshError};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use ring::agreement::{self, EphemeralPrivateKey, UnparsedPublicKey, X25519};
+use ring::digest::{self, SHA256};
+use ring::rand::SystemRandom;
+use signature::Verifier;
+use ssh_encoding::Decode;
+use ssh_key::{PublicKey, Signature};
+use std::time::Duration;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt,
+};
+use tokio::net::TcpStream;
+
+const CLIENT_VERSION: &str = "SSH-2.0-russh_keyscan";
+
+const KEX_ALGORITHMS: &str = "curve25519-sha256,curve25519-sha256@libssh.org";
+const CIPHERS: &str = "chacha20-poly1305@openssh.com,aes128-gcm@openssh.com,\
+                       aes256-gcm@openssh.com,aes128-ctr,aes192-ctr,aes256-ctr";
+const MACS: &str = "hmac-sha2-256-etm@openssh.com,hmac-sha2-512-etm@openssh.com,\
+                    hmac-sha2-256,hmac-sha2-512";
+
+/// Host key algorithms asked for, one connection per group; a group lists
+/// signature algorithms for the same key type in order of preference
+const HOST_KEY_ALGORITHMS: &[&[&str]] = &[
+    &["ssh-ed25519"],
+    &["ecdsa-sha2-nistp256"],
+    &["ecdsa-sha2-nistp384"],
+    &["ecdsa-sha2-nistp521"],
+    &["rsa-sha2-512", "rsa-sha2-256"],
+];
+
+const SSH_MSG_DISCONNECT: u8 = 1;
+const SSH_MSG_IGNORE: u8 = 2;
+const SSH_MSG_DEBUG: u8 = 4;
+const SSH_MSG_KEXINIT: u8 = 20;
+const SSH_MSG_KEX_ECDH_INIT: u8 = 30;
+const SSH_MSG_KEX_ECDH_REPLY: u8 = 31;
+
+/// Longest version line accepted, banner lines included (RFC 4253 §4.2)
+const MAX_VERSION_LINE: u64 = 255;
+/// Lines accepted before the version line
+const MAX_BANNER_LINES: usize = 64;
+/// Largest packet accepted before keys are in use
+const MAX_PACKET: usize = 35000;
+
+/// Fetch the host keys `host` offers, as `type base64` lines
+///
+/// `timeout` applies to each connection. Fails if the server offers none
+/// of the supported key types or a signature does not verify.
+pub async fn scan_host_keys(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+) -> Result<Vec<String>, SshError> {
+    let mut keys = Vec::new();
+    let mut offered: Option<Vec<String>> = None;
+    for algorithms in HOST_KEY_ALGORITHMS {
+        if offered.as_ref().is_some_and(|offered| {
+            !algorithms
+                .iter()
+                .any(|algorithm| offered.iter().any(|o| o == algorithm))
+        }) {
+            continue;
+        }
+        let scan = async {
+            let stream = TcpStream::connect((host, port))
+                .await
+                .map_err(ConnectionError::from)?;
+            fetch_host_key(stream, algorithms).await
+        };
+        let (server_offers, key) = tokio::time::timeout(timeout, scan)
+            .await
+            .map_err(|_| ConnectionError::Timeout(timeout))??;
+        if let Some(key) = key {
+            keys.push(key.to_openssh().map_err(key_error)?);
+        }
+        offered = Some(server_offers);
+    }
+    if keys.is_empty() {
+        return Err(SshError::HostKey(format!(
+            "{}:{} offers none of the supported host key types",
+            host, port
+        )));
+    }
+    Ok(keys)
+}
+
+/// Run the key exchange on `stream` for the first of `algorithms` the
+/// server offers
+///
+/// Returns the host key algorithms the server offers and, unless it offers
+/// none of `algorithms`, its verified host key.
+async fn fetch_host_key<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    algorithms: &[&str],
+) -> Result<(Vec<String>, Option<PublicKey>), SshError> {
+    let mut stream = tokio::io::BufReader::new(stream);
+    stream
+        .write_all(format!("{}\r\n", CLIENT_VERSION).as_bytes())
+        .await
+        .map_err(io_error)?;
+    let server_version = read_version(&mut stream).await?;
+
+    let server_kexinit = read_packet(&mut stream).await?;
+    if server_kexinit.first() != Some(&SSH_MSG_KEXINIT) {
+        return Err(protocol_error("expected KEXINIT"));
+    }
+    let (kex, host_key_algorithms) = parse_kexinit(&server_kexinit)?;
+    if !kex
+        .iter()
+        .any(|name| KEX_ALGORITHMS.split(',').any(|ours| ours == name))
+    {
+        return Err(SshError::HostKey(
+            "the server does not support curve25519-sha256 key exchange".to_string(),
+        ));
+    }
+    let Some(algorithm) = algorithms
+        .iter()
+        .find(|algorithm| host_key_algorithms.iter().any(|o| o == *algorithm))
+    else {
+        return Ok((host_key_algorithms, None));
+    };
+
+    let client_kexinit = kexinit(KEX_ALGORITHMS, algorithm);
+    write_packet(&mut stream, &client_kexinit).await?;
+    let private = EphemeralPrivateKey::generate(&X25519, &SystemRandom::new())
+        .map_err(|_| SshError::HostKey("could not generate a key exchange key".to_string()))?;
+    let q_c = private
+        .compute_public_key()
+        .map_err(|_| SshError::HostKey("could not generate a key exchange key".to_string()))?;
+    let mut init = vec![SSH_MSG_KEX_ECDH_INIT];
+    put_string(&mut init, q_c.as_ref());
+    write_packet(&mut stream, &init).await?;
+
+    let reply = read_packet(&mut stream).await?;
+    let mut rest = match reply.split_first() {
+        Some((&SSH_MSG_KEX_ECDH_REPLY, rest)) => rest,
+        _ => return Err(protocol_error("expected KEX_ECDH_REPLY")),
+    };
+    let k_s = Vec::<u8>::decode(&mut rest).map_err(decode_error)?;
+    let q_s = Vec::<u8>::decode(&mut rest).map_err(decode_error)?;
+    let signature = Vec::<u8>::decode(&mut rest).map_err(decode_error)?;
+
+    let shared = agreement::agree_ephemeral(private, &UnparsedPublicKey::new(&X25519, &q_s), |k| {
+        k.to_vec()
+    })
+    .map_err(|_| protocol_error("invalid key exchange public key"))?;
+    let hash = exchange_hash(&ExchangeHashInput {
+        client_version: CLIENT_VERSION.as_bytes(),
+        server_version: server_version.as_bytes(),
+        client_kexinit: &client_kexinit,
+        server_kexinit: &server_kexinit,
+        host_key: &k_s,
+        client_public: q_c.as_ref(),
+        server_public: &q_s,
+        shared_secret: &shared,
+    });
+
+    let key = PublicKey::from_bytes(&k_s).map_err(key_error)?;
+    let signature = Signature::try_from(signature.as_slice()).map_err(key_error)?;
+    Verifier::verify(&key, &hash, &signature).map_err(|_| {
+        SshError::HostKey(format!(
+            "the {} host key signature does not verify",
+            algorithm
+        ))
+    })?;
+    Ok((host_key_algorithms, Some(key)))
+}
+
+/// Values hashed into the exchange hash H (RFC 8731 §3.1)
+struct ExchangeHashInput<'a> {
+    client_version: &'a [u8],
+    server_version: &'a [u8],
+    client_kexinit: &'a [u8],
+    server_kexinit: &'a [u8],
+    host_key: &'a [u8],
+    client_public: &'a [u8],
+    server_public: &'a [u8],
+    shared_secret: &'a [u8],
+}
+
+fn exchange_hash(input: &ExchangeHashInput<'_>) -> Vec<u8> {
+    let mut data = Vec::new();
+    for field in [
+        input.client_version,
+        input.server_version,
+        input.client_kexinit,
+        input.server_kexinit,
+        input.host_key,
+        input.client_public,
+        input.server_public,
+    ] {
+        put_string(&mut data, field);
+    }
+    put_mpint(&mut data, input.shared_secret);
+    digest::digest(&SHA256, &data).as_ref().to_vec()
+}
+
+/// Build a KEXINIT payload offering one key exchange list and host key
+/// algorithm
+fn kexinit(kex: &str, host_key_algorithm: &str) -> Vec<u8> {
+    let mut payload = vec![SSH_MSG_KEXINIT];
+    let mut cookie = [0u8; 16];
+    OsRng.fill_bytes(&mut cookie);
+    payload.extend_from_slice(&cookie);
+    for list in [
+        kex,
+        host_key_algorithm,
+        CIPHERS,
+        CIPHERS,
+        MACS,
+        MACS,
+        "none",
+        "none",
+        "",
+        "",
+    ] {
+        put_string(&mut payload, list.as_bytes());
+    }
+    // first_kex_packet_follows and the reserved field
+    payload.push(0);
+    payload.extend_from_slice(&0u32.to_be_bytes());
+    payload
+}
+
+/// Key exchange and host key algorithm lists of a KEXINIT payload
+fn parse_kexinit(payload: &[u8]) -> Result<(Vec<String>, Vec<String>), SshError> {
+    let mut rest = payload
+        .get(17..)
+        .ok_or_else(|| protocol_error("truncated KEXINIT"))?;
+    let kex = String::decode(&mut rest).map_err(decode_error)?;
+    let host_keys = String::decode(&mut rest).map_err(decode_error)?;
+    let list = |names: String| names.split(',').map(str::to_string).collect();
+    Ok((list(kex), list(host_keys)))
+}
+
+/// Read the server's version line, skipping any lines sent before it
+async fn read_version<R: AsyncBufRead + Unpin>(stream: &mut R) -> Result<String, SshError> {
+    for _ in 0..MAX_BANNER_LINES {
+        let mut line = Vec::new();
+        (&mut *stream)
+            .take(MAX_VERSION_LINE)
+            .read_until(b'\n', &mut line)
+            .await
+            .map_err(io_error)?;
+        if !line.ends_with(b"\n") {
+            return Err(protocol_error("no SSH version line"));
+        }
+        let line = String::from_utf8_lossy(&line)
+            .trim_end_matches(['\r', '\n'])
+            .to_string();
+        if line.starts_with("SSH-") {
+            if !line.starts_with("SSH-2.0-") && !line.starts_with("SSH-1.99-") {
+                return Err(SshError::HostKey(format!(
+                    "unsupported protocol version: {}",
+                    line
+                )));
+            }
+            return Ok(line);
+        }
+    }
+    Err(protocol_error("no SSH version line"))
+}
+
+/// Read the payload of the next unencrypted packet, skipping IGNORE and
+/// DEBUG messages
+async fn read_packet<R: AsyncRead + Unpin>(stream: &mut R) -> Result<Vec<u8>, SshError> {
+    loop {
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len).await.map_err(io_error)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if !(5..=MAX_PACKET).contains(&len) {
+            return Err(protocol_error("invalid packet length"));
+        }
+        let mut packet = vec![0u8; len];
+        stream.read_exact(&mut packet).await.map_err(io_error)?;
+        let padding = packet[0] as usize;
+        let Some(payload) = packet.get(1..len.saturating_sub(padding)) else {
+            return Err(protocol_error("invalid packet padding"));
+        };
+        match payload.first() {
+            Some(&SSH_MSG_IGNORE) | Some(&SSH_MSG_DEBUG) => continue,
+            Some(&SSH_MSG_DISCONNECT) => {
+                let mut rest = &payload[1..];
+                let reason = u32::decode(&mut rest)
+                    .and_then(|_| String::decode(&mut rest))
+                    .unwrap_or_default();
+                return Err(SshError::HostKey(format!(
+                    "the server disconnected: {}",
+                    reason
+                )));
+            }
+            Some(_) => return Ok(payload.to_vec()),
+            None => return Err(protocol_error("empty packet")),
+        }
+    }
+}
+
+/// Write `payload` as an unencrypted packet
+async fn write_packet<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    payload: &[u8],
+) -> Result<(), SshError> {
+    let mut padding = 8 - (5 + payload.len()) % 8;
+    if padding < 4 {
+        padding += 8;
+    }
+    let mut packet = Vec::with_capacity(5 + payload.len() + padding);
+    packet.extend_from_slice(&((1 + payload.len() + padding) as u32).to_be_bytes());
+    packet.push(padding as u8);
+    packet.extend_from_slice(payload);
+    let mut filler = vec![0u8; padding];
+    OsRng.fill_bytes(&mut filler);
+    packet.extend_from_slice(&filler);
+    stream.write_all(&packet).await.map_err(io_error)?;
+    stream.flush().await.map_err(io_error)
+}
+
+fn put_string(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(data);
+}
+
+/// Append an unsigned big-endian integer as an SSH mpint
+fn put_mpint(out: &mut Vec<u8>, value: &[u8]) {
+    let start = value.iter().position(|&b| b != 0).unwrap_or(value.len());
+    let value = &value[start..];
+    if value.first().is_some_and(|&b| b & 0x80 != 0) {
+        out.extend_from_slice(&(value.len() as u32 + 1).to_be_bytes());
+        out.push(0);
+        out.extend_from_slice(value);
+    } else {
+        put_string(out, value);
+    }
+}
+
+fn io_error(e: std::io::Error) -> SshError {
+    SshError::Connection(ConnectionError::Io(e))
+}
+
+fn decode_error(e: ssh_encoding::Error) -> SshError {
+    SshError::HostKey(format!("malformed packet from the server: {}", e))
+}
+
+fn protocol_error(message: &str) -> SshError {
+    SshError::HostKey(format!("key exchange failed: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use signature::Signer;
+    use ssh_key::{Algorithm, PrivateKey};
+    use tokio::net::TcpListener;
+
+    /// Answer one key exchange with `host_key`, signing the wrong hash when
+    /// `forge` is set
+    async fn serve(listener: TcpListener, host_key: PrivateKey, forge: bool) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut stream = tokio::io::BufReader::new(stream);
+        let server_version = "SSH-2.0-test";
+        stream
+            .write_all(format!("banner\r\n{}\r\n", server_version).as_bytes())
+            .await
+            .unwrap();
+        let client_version = read_version(&mut stream).await.unwrap();
+        let server_kexinit = kexinit("curve25519-sha256", "ssh-ed25519");
+        write_packet(&mut stream, &server_kexinit).await.unwrap();
+        let client_kexinit = read_packet(&mut stream).await.unwrap();
+        let init = read_packet(&mut stream).await.unwrap();
+        let q_c = Vec::<u8>::decode(&mut &init[1..]).unwrap();
+
+        let private = EphemeralPrivateKey::generate(&X25519, &SystemRandom::new()).unwrap();
+        let q_s = private.compute_public_key().unwrap();
+        let shared =
+            agreement::agree_ephemeral(private, &UnparsedPublicKey::new(&X25519, &q_c), |k| {
+                k.to_vec()
+            })
+            .unwrap();
+        let k_s = host_key.public_key().to_bytes().unwrap();
+        let mut hash = exchange_hash(&ExchangeHashInput {
+            client_version: client_version.as_bytes(),
+            server_version: server_version.as_bytes(),
+            client_kexinit: &client_kexinit,
+            server_kexinit: &server_kexinit,
+            host_key: &k_s,
+            client_public: &q_c,
+            server_public: q_s.as_ref(),
+            shared_secret: &shared,
+        });
+        if forge {
+            hash[0] ^= 1;
+        }
+        let signature: Signature = host_key.try_sign(&hash).unwrap();
+
+        let mut reply = vec![SSH_MSG_KEX_ECDH_REPLY];
+        put_string(&mut reply, &k_s);
+        put_string(&mut reply, q_s.as_ref());
+        put_string(&mut reply, &Vec::try_from(signature).unwrap());
+        write_packet(&mut stream, &reply).await.unwrap();
+    }
+
+    async fn scan(forge: bool) -> (PrivateKey, Result<Vec<String>, SshError>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let host_key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        tokio::spawn(serve(listener, host_key.clone(), forge));
+        let keys = scan_host_keys("127.0.0.1", port, Duration::from_secs(5)).await;
+        (host_key, keys)
+    }
+
+    #[tokio::test]
+    async fn scan_returns_the_verified_host_key() {
+        let (host_key, keys) = scan(false).await;
+        assert_eq!(
+            keys.unwrap(),
+            vec![host_key.public_key().to_openssh().unwrap()]
+        );
+    }
+
+    #[tokio::test]
+    async fn scan_rejects_a_bad_signature() {
+        let (_, keys) = scan(true).await;
+        assert!(matches!(keys, Err(SshError::HostKey(_))));
+    }
+}
@@ -0,0 +1,542 @@
+//! OpenSSH known_hosts Files
+//!
+//! Reads and edits `known_hosts` files in the format OpenSSH writes: one
+//! key per line for a comma-separated list of host patterns, optionally
+//! behind an `@cert-authority` or `@revoked` marker. Patterns may use `*`
+//! and `?` wildcards and `!` negation, and names hashed with
+//! `HashKnownHosts` (`|1|salt|hash`) are matched as OpenSSH does. Hosts on
+//! a port other than 22 are recorded as `[host]:port`.
+//!
+//! Comments, blank lines and lines that cannot be parsed are kept, and
+//! lines that are not changed are written back exactly as they were read.
+
+use super::keygen::{key_error, public_key_fingerprint};
+use crate::error::SshError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use ring::hmac;
+use ssh_key::PublicKey;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// File name of the known hosts file in the russh config directory
+pub const KNOWN_HOSTS_FILE_NAME: &str = "known_hosts";
+
+/// Prefix of a hashed host name
+const HASH_MAGIC: &str = "|1|";
+
+/// Marker in front of a known_hosts entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostMarker {
+    /// `@cert-authority`: the key signs host certificates for the hosts
+    CertAuthority,
+    /// `@revoked`: the key must never be accepted for the hosts
+    Revoked,
+}
+
+impl HostMarker {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::CertAuthority => "@cert-authority",
+            Self::Revoked => "@revoked",
+        }
+    }
+}
+
+/// One key line of a known_hosts file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KnownHost {
+    /// Marker in front of the line, if any
+    pub marker: Option<HostMarker>,
+    /// Host patterns or hashed names the key is for
+    pub hosts: Vec<String>,
+    /// Key algorithm, e.g. `ssh-ed25519`
+    pub key_type: String,
+    /// Base64 key blob
+    pub key: String,
+    /// Comment after the key
+    pub comment: Option<String>,
+}
+
+impl KnownHost {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let mut first = fields.next()?;
+        let marker = match first {
+            "@cert-authority" => Some(HostMarker::CertAuthority),
+            "@revoked" => Some(HostMarker::Revoked),
+            _ => None,
+        };
+        if marker.is_some() {
+            first = fields.next()?;
+        }
+        let hosts = first.split(',').map(str::to_string).collect();
+        let key_type = fields.next()?.to_string();
+        let key = fields.next()?.to_string();
+        let comment: Vec<&str> = fields.collect();
+        Some(Self {
+            marker,
+            hosts,
+            key_type,
+            key,
+            comment: (!comment.is_empty()).then(|| comment.join(" ")),
+        })
+    }
+
+    /// Whether every host name on the line is hashed
+    pub fn is_hashed(&self) -> bool {
+        self.hosts.iter().all(|host| host.starts_with(HASH_MAGIC))
+    }
+
+    /// Whether the line applies to `host` on `port`
+    ///
+    /// As with OpenSSH, a matching negated pattern rules the line out even
+    /// when another pattern matches.
+    pub fn matches(&self, host: &str, port: u16) -> bool {
+        let name = host_pattern(host, port).to_ascii_lowercase();
+        let mut matched = false;
+        for pattern in &self.hosts {
+            if let Some(negated) = pattern.strip_prefix('!') {
+                if glob_match(&negated.to_ascii_lowercase(), &name) {
+                    return false;
+                }
+            } else if pattern_matches(pattern, &name) {
+                matched = true;
+            }
+        }
+        matched
+    }
+
+    /// Public key as `type base64`, the way `authorized_keys` has it
+    pub fn public_key(&self) -> String {
+        format!("{} {}", self.key_type, self.key)
+    }
+
+    /// SHA256 fingerprint of the key, as `ssh-keygen -l` shows it
+    pub fn fingerprint(&self) -> Result<String, SshError> {
+        public_key_fingerprint(&self.public_key())
+    }
+
+    /// Remove the names that are exactly `host` on `port`, plain or hashed
+    ///
+    /// Wildcard patterns are left alone since they cover other hosts too.
+    /// Returns whether any name was removed.
+    fn remove_name(&mut self, host: &str, port: u16) -> bool {
+        let name = host_pattern(host, port).to_ascii_lowercase();
+        let before = self.hosts.len();
+        self.hosts.retain(|pattern| {
+            if pattern.starts_with(HASH_MAGIC) {
+                !hashed_matches(pattern, &name)
+            } else {
+                !pattern.eq_ignore_ascii_case(&name)
+            }
+        });
+        self.hosts.len() != before
+    }
+}
+
+impl std::fmt::Display for KnownHost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(marker) = self.marker {
+            write!(f, "{} ", marker.as_str())?;
+        }
+        write!(f, "{} {} {}", self.hosts.join(","), self.key_type, self.key)?;
+        if let Some(comment) = &self.comment {
+            write!(f, " {}", comment)?;
+        }
+        Ok(())
+    }
+}
+
+/// Name a host is recorded under: `host` on port 22, `[host]:port` otherwise
+pub fn host_pattern(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+/// Hash a host name the way `ssh-keygen -H` does
+pub fn hash_host(name: &str) -> String {
+    let mut salt = [0u8; 20];
+    OsRng.fill_bytes(&mut salt);
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &salt);
+    let tag = hmac::sign(&key, name.to_ascii_lowercase().as_bytes());
+    format!(
+        "{}{}|{}",
+        HASH_MAGIC,
+        STANDARD.encode(salt),
+        STANDARD.encode(tag.as_ref())
+    )
+}
+
+fn pattern_matches(pattern: &str, name: &str) -> bool {
+    if pattern.starts_with(HASH_MAGIC) {
+        hashed_matches(pattern, name)
+    } else {
+        glob_match(&pattern.to_ascii_lowercase(), name)
+    }
+}
+
+fn hashed_matches(pattern: &str, name: &str) -> bool {
+    let Some((salt, hash)) = pattern
+        .strip_prefix(HASH_MAGIC)
+        .and_then(|rest| rest.split_once('|'))
+    else {
+        return false;
+    };
+    let (Ok(salt), Ok(hash)) = (STANDARD.decode(salt), STANDARD.decode(hash)) else {
+        return false;
+    };
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, &salt);
+    hmac::verify(&key, name.as_bytes(), &hash).is_ok()
+}
+
+fn is_wildcard(pattern: &str) -> bool {
+    pattern.starts_with('!') || pattern.contains(['*', '?'])
+}
+
+/// Match `name` against a pattern with `*` and `?` wildcards
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// A line of the file: a parsed key or anything else, kept as it was
+#[derive(Debug, Clone)]
+enum Line {
+    /// A key line and, while it is unchanged, its original text
+    Entry(KnownHost, Option<String>),
+    Other(String),
+}
+
+/// A known_hosts file loaded for reading and editing
+#[derive(Debug, Clone)]
+pub struct KnownHosts {
+    path: PathBuf,
+    lines: Vec<Line>,
+}
+
+impl KnownHosts {
+    /// Load the file at `path`; a missing file is empty
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, SshError> {
+        let path = path.into();
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(SshError::HostKey(format!("{}: {}", path.display(), e))),
+        };
+        Ok(Self {
+            path,
+            lines: parse(&text),
+        })
+    }
+
+    /// Path the file is read from and saved to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every key line, in file order
+    pub fn entries(&self) -> impl Iterator<Item = &KnownHost> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Entry(entry, _) => Some(entry),
+            Line::Other(_) => None,
+        })
+    }
+
+    /// Key lines that apply to `host` on `port`
+    pub fn lookup(&self, host: &str, port: u16) -> Vec<&KnownHost> {
+        self.entries()
+            .filter(|entry| entry.matches(host, port))
+            .collect()
+    }
+
+    /// Key lines with a plain host pattern or comment containing `query`
+    ///
+    /// Hashed names cannot be searched by substring; they are found when
+    /// `query` is the full host name, or `[host]:port`.
+    pub fn search(&self, query: &str) -> Vec<&KnownHost> {
+        let needle = query.to_ascii_lowercase();
+        let (host, port) = split_host_port(query);
+        self.entries()
+            .filter(|entry| {
+                entry.hosts.iter().any(|pattern| {
+                    !pattern.starts_with(HASH_MAGIC)
+                        && pattern.to_ascii_lowercase().contains(&needle)
+                }) || entry
+                    .comment
+                    .as_ref()
+                    .is_some_and(|comment| comment.to_ascii_lowercase().contains(&needle))
+                    || entry.matches(host, port)
+            })
+            .collect()
+    }
+
+    /// Remove `host` on `port` from every line naming it
+    ///
+    /// Lines left without a host are dropped; wildcard patterns that also
+    /// match the host are kept. Returns how many lines were changed.
+    pub fn remove(&mut self, host: &str, port: u16) -> usize {
+        let mut changed = 0;
+        self.lines.retain_mut(|line| {
+            let Line::Entry(entry, original) = line else {
+                return true;
+            };
+            if !entry.remove_name(host, port) {
+                return true;
+            }
+            changed += 1;
+            *original = None;
+            !entry.hosts.is_empty()
+        });
+        changed
+    }
+
+    /// Record `public_key` (`type base64 [comment]`) for `host` on `port`
+    ///
+    /// Replaces the key of the same type already recorded for the host, so
+    /// pinning a key again after it changed leaves one line per type.
+    pub fn add(
+        &mut self,
+        host: &str,
+        port: u16,
+        public_key: &str,
+        hash: bool,
+    ) -> Result<KnownHost, SshError> {
+        let key = PublicKey::from_openssh(public_key).map_err(key_error)?;
+        let openssh = key.to_openssh().map_err(key_error)?;
+        let mut fields = openssh.split_whitespace();
+        let (Some(key_type), Some(blob)) = (fields.next(), fields.next()) else {
+            return Err(SshError::Key(format!("Invalid public key: {}", public_key)));
+        };
+
+        self.lines.retain_mut(|line| {
+            let Line::Entry(entry, original) = line else {
+                return true;
+            };
+            if entry.marker.is_some() || entry.key_type != key_type {
+                return true;
+            }
+            if entry.remove_name(host, port) {
+                *original = None;
+            }
+            !entry.hosts.is_empty()
+        });
+
+        let name = host_pattern(host, port);
+        let entry = KnownHost {
+            marker: None,
+            hosts: vec![if hash { hash_host(&name) } else { name }],
+            key_type: key_type.to_string(),
+            key: blob.to_string(),
+            comment: None,
+        };
+        self.lines.push(Line::Entry(entry.clone(), None));
+        Ok(entry)
+    }
+
+    /// Hash every plain host name, as `ssh-keygen -H` does
+    ///
+    /// Lines naming several hosts are split into one line per host. Lines
+    /// with wildcard or negated patterns cannot be hashed and are left as
+    /// they are. Returns how many names were hashed.
+    pub fn hash_hosts(&mut self) -> usize {
+        let mut hashed = 0;
+        let mut lines = Vec::with_capacity(self.lines.len());
+        for line in self.lines.drain(..) {
+            match line {
+                Line::Entry(entry, original)
+                    if entry.is_hashed() || entry.hosts.iter().any(|host| is_wildcard(host)) =>
+                {
+                    lines.push(Line::Entry(entry, original));
+                }
+                Line::Entry(entry, _) => {
+                    for host in &entry.hosts {
+                        let host = if host.starts_with(HASH_MAGIC) {
+                            host.clone()
+                        } else {
+                            hashed += 1;
+                            hash_host(host)
+                        };
+                        let split = KnownHost {
+                            hosts: vec![host],
+                            ..entry.clone()
+                        };
+                        lines.push(Line::Entry(split, None));
+                    }
+                }
+                other => lines.push(other),
+            }
+        }
+        self.lines = lines;
+        hashed
+    }
+
+    /// Write the file back, replacing it atomically
+    pub fn save(&self) -> Result<(), SshError> {
+        let storage_error =
+            |e: std::io::Error| SshError::HostKey(format!("{}: {}", self.path.display(), e));
+
+        let mut text = String::new();
+        for line in &self.lines {
+            match line {
+                Line::Entry(_, Some(original)) | Line::Other(original) => text.push_str(original),
+                Line::Entry(entry, None) => text.push_str(&entry.to_string()),
+            }
+            text.push('\n');
+        }
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(storage_error)?;
+        }
+
+        let tmp_path = self.path.with_extension("tmp");
+        let mut file = std::fs::File::create(&tmp_path).map_err(storage_error)?;
+        file.write_all(text.as_bytes()).map_err(storage_error)?;
+        file.sync_all().map_err(storage_error)?;
+        std::fs::rename(&tmp_path, &self.path).map_err(storage_error)
+    }
+}
+
+fn parse(text: &str) -> Vec<Line> {
+    text.lines()
+        .map(|line| {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                return Line::Other(line.to_string());
+            }
+            match KnownHost::parse(trimmed) {
+                Some(entry) => Line::Entry(entry, Some(line.to_string())),
+                None => Line::Other(line.to_string()),
+            }
+        })
+        .collect()
+}
+
+/// Split `[host]:port` into its parts; anything else is a host on port 22
+fn split_host_port(name: &str) -> (&str, u16) {
+    name.strip_prefix('[')
+        .and_then(|rest| rest.split_once("]:"))
+        .and_then(|(host, port)| Some((host, port.parse().ok()?)))
+        .unwrap_or((name, 22))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh::{KeyType, SshKeyPair};
+
+    /// A fresh public key as `type base64`, without a comment
+    fn public_key() -> String {
+        let line = SshKeyPair::generate(KeyType::Ed25519, "test")
+            .unwrap()
+            .public_key()
+            .unwrap();
+        line.split_whitespace()
+            .take(2)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn lookup_matches_patterns_and_hashed_names() {
+        let key = public_key();
+        let text = format!(
+            "# comment\n\
+             *.example.com,!bad.example.com {key}\n\
+             [alt.example.org]:2222 {key}\n\
+             {hashed} {key}\n",
+            key = key,
+            hashed = hash_host("hidden.example.net")
+        );
+        let lines = parse(&text);
+        let known = KnownHosts {
+            path: PathBuf::from("known_hosts"),
+            lines,
+        };
+
+        assert_eq!(known.lookup("web.EXAMPLE.com", 22).len(), 1);
+        assert!(known.lookup("bad.example.com", 22).is_empty());
+        assert_eq!(known.lookup("alt.example.org", 2222).len(), 1);
+        assert!(known.lookup("alt.example.org", 22).is_empty());
+        assert_eq!(known.lookup("hidden.example.net", 22).len(), 1);
+        assert_eq!(known.search("hidden.example.net").len(), 1);
+        assert_eq!(known.search("alt.").len(), 1);
+    }
+
+    #[test]
+    fn add_and_remove_keep_other_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("known_hosts");
+        let original = public_key();
+        std::fs::write(
+            &path,
+            format!("# pinned by hand\nhost.test,other.test  {}\n", original),
+        )
+        .unwrap();
+
+        let mut known = KnownHosts::open(&path).unwrap();
+        let replacement = public_key();
+        let entry = known.add("host.test", 22, &replacement, false).unwrap();
+        assert_eq!(entry.hosts, vec!["host.test"]);
+        known.save().unwrap();
+
+        let known = KnownHosts::open(&path).unwrap();
+        let host = known.lookup("host.test", 22);
+        assert_eq!(host.len(), 1);
+        assert_eq!(host[0].public_key(), replacement);
+        assert_eq!(known.lookup("other.test", 22)[0].public_key(), original);
+
+        let mut known = known;
+        assert_eq!(known.remove("other.test", 22), 1);
+        known.save().unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("# pinned by hand\n"));
+        assert_eq!(KnownHosts::open(&path).unwrap().entries().count(), 1);
+    }
+
+    #[test]
+    fn hash_hosts_splits_plain_names() {
+        let key = public_key();
+        let text = format!("a.test,[b.test]:2200 {key}\n*.test {key}\n", key = key);
+        let mut known = KnownHosts {
+            path: PathBuf::from("known_hosts"),
+            lines: parse(&text),
+        };
+
+        assert_eq!(known.hash_hosts(), 2);
+        let entries: Vec<_> = known.entries().collect();
+        assert_eq!(entries.len(), 3);
+        assert!(entries[0].is_hashed() && entries[1].is_hashed());
+        assert_eq!(entries[2].hosts, vec!["*.test"]);
+        assert!(entries[0].matches("a.test", 22));
+        assert!(entries[1].matches("b.test", 2200));
+        assert_eq!(known.hash_hosts(), 0);
+    }
+}
@@ -4,28 +4,58 @@
 //! - Connection management
 //! - Authentication (Password, Public Key, Agent)
 //! - Command execution
-//! - Interactive shell
+//! - Running a command across many hosts in parallel
+//! - Interactive shell, with local echo prediction for slow links
 //! - Port forwarding
+//! - Auto-reconnect with shell and forward restoration
+//! - Connection pooling with per-host limits
+//! - Sharing sessions between processes through a multiplexer daemon
 //! - SFTP file operations
+//! - Key generation in OpenSSH format
+//! - Reading and editing known_hosts files and fetching host keys
+//! - ssh-agent client and a built-in agent
+//! - Tunneling through a P2P peer to reach hosts behind NAT
 //!
 //! # Requirements Coverage
 //! - Requirement 1: Async SSH Connection Management
 //! - Requirement 9: Command Execution
 //! - Requirement 10: Port Forwarding
 
+pub mod agent;
 pub mod client;
 pub mod command;
+pub mod fleet;
 pub mod forward;
+pub mod keygen;
+pub mod keyscan;
+pub mod known_hosts;
+pub mod mux;
+pub mod pool;
+pub mod predict;
+pub mod reconnect;
 pub mod sftp;
 
+pub use agent::{AgentClient, AgentIdentity, KeyAgent};
 pub use client::SshClient;
 pub use command::{CommandResult, Shell};
-pub use forward::{PortForward, PortForwarder};
-pub use sftp::RemoteFileEntry;
+pub use fleet::{FleetExecutor, FleetHost, HostResult};
+pub use forward::{ForwardHandle, PortForward, PortForwarder};
+pub use keygen::{KeyType, SshKeyPair};
+pub use keyscan::scan_host_keys;
+pub use known_hosts::{HostMarker, KnownHost, KnownHosts, KNOWN_HOSTS_FILE_NAME};
+pub use mux::{
+    MuxClient, MuxServer, MuxSessionInfo, DEFAULT_MUX_IDLE_TIMEOUT, MUX_SOCKET_FILE_NAME,
+};
+pub use pool::{ClientPool, PoolConfig, PoolKey, PoolOverflow, PooledClient};
+pub use predict::EchoPredictor;
+pub use reconnect::{AutoReconnectConfig, RestoredSession, ShellSpec};
+pub use sftp::{RemoteFileEntry, RemoteFileWriter};
 
+use iroh::NodeId;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::time::Duration;
+use zeroize::Zeroize;
 
 /// SSH session configuration
 #[derive(Debug, Clone)]
@@ -44,6 +74,19 @@ pub struct SshConfig {
     pub known_hosts_path: Option<PathBuf>,
     /// Host key check policy
     pub host_key_check: HostKeyCheck,
+    /// How the TCP stream to the server is carried
+    pub transport: Transport,
+}
+
+/// Transport carrying the SSH connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Transport {
+    /// Direct TCP connection to `host:port`
+    #[default]
+    Tcp,
+    /// Tunnel through the P2P agent on the given peer, which forwards to
+    /// its local sshd; `host` and `port` are only used for display
+    P2P(NodeId),
 }
 
 /// Host key checking policy
@@ -59,7 +102,9 @@ pub enum HostKeyCheck {
 }
 
 /// SSH authentication method
-#[derive(Debug, Clone)]
+///
+/// Secrets are zeroed on drop and never shown by `Debug`.
+#[derive(Clone)]
 pub enum AuthMethod {
     /// Password authentication
     Password(String),
@@ -73,3 +118,30 @@ pub enum AuthMethod {
     /// SSH Agent authentication
     Agent,
 }
+
+impl std::fmt::Debug for AuthMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Password(_) => f.debug_tuple("Password").field(&"[REDACTED]").finish(),
+            Self::PublicKey {
+                key_path,
+                passphrase,
+            } => f
+                .debug_struct("PublicKey")
+                .field("key_path", key_path)
+                .field("passphrase", &passphrase.as_ref().map(|_| "[REDACTED]"))
+                .finish(),
+            Self::Agent => f.write_str("Agent"),
+        }
+    }
+}
+
+impl Drop for AuthMethod {
+    fn drop(&mut self) {
+        match self {
+            Self::Password(password) => password.zeroize(),
+            Self::PublicKey { passphrase, .. } => passphrase.zeroize(),
+            Self::Agent => {}
+        }
+    }
+}
@@ -0,0 +1,661 @@
+//! Sharing SSH sessions between processes
+//!
+//! [`MuxServer`] keeps connections open on behalf of short-lived processes,
+//! like OpenSSH's ControlMaster: it listens on a Unix socket and runs
+//! commands over the session for a `user@host:port`, which a client opens
+//! once. An [`SshClient`] given a [`MuxClient`] with [`SshClient::with_mux`]
+//! runs its commands, and so its SFTP operations, over the shared session
+//! without a handshake of its own.
+//!
+//! The protocol is one JSON request and one JSON response per line.
+//! Interactive shells and port forwards are not shared; they need a session
+//! of their own.
+
+use super::pool::PoolKey;
+use super::{AuthMethod, CommandResult, HostKeyCheck, SshClient, SshConfig, Transport};
+use crate::error::SshError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{Mutex, Notify, RwLock};
+use zeroize::Zeroize;
+
+/// File name of the control socket in the russh config directory
+pub const MUX_SOCKET_FILE_NAME: &str = "mux.sock";
+
+/// How long an unused session is kept open by default
+pub const DEFAULT_MUX_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// A session held by the server
+struct MuxSession {
+    client: Arc<RwLock<SshClient>>,
+    /// Used to reconnect when the session has dropped
+    config: SshConfig,
+    commands: u64,
+    last_used: Instant,
+}
+
+/// A shared session as reported by `status`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MuxSessionInfo {
+    /// Destination of the session
+    pub key: PoolKey,
+    /// Whether the connection is still up
+    pub connected: bool,
+    /// Commands run over the session
+    pub commands: u64,
+    /// Time since the last command
+    pub idle: Duration,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum MuxRequest {
+    Open { config: WireConfig },
+    Exec { key: PoolKey, command: String },
+    Status,
+    Close { key: PoolKey },
+    Stop,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum MuxResponse {
+    Ok,
+    Output {
+        stdout: String,
+        stderr: String,
+        exit_code: i32,
+    },
+    Sessions {
+        sessions: Vec<MuxSessionInfo>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// [`SshConfig`] as sent over the socket
+#[derive(Debug, Serialize, Deserialize)]
+struct WireConfig {
+    host: String,
+    port: u16,
+    username: String,
+    auth: WireAuth,
+    timeout_secs: u64,
+    known_hosts_path: Option<PathBuf>,
+    host_key_check: HostKeyCheck,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+enum WireAuth {
+    Password {
+        password: String,
+    },
+    PublicKey {
+        key_path: PathBuf,
+        passphrase: Option<String>,
+    },
+    Agent,
+}
+
+impl std::fmt::Debug for WireAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Password { .. } => "Password",
+            Self::PublicKey { .. } => "PublicKey",
+            Self::Agent => "Agent",
+        })
+    }
+}
+
+impl Drop for WireAuth {
+    fn drop(&mut self) {
+        match self {
+            Self::Password { password } => password.zeroize(),
+            Self::PublicKey { passphrase, .. } => passphrase.zeroize(),
+            Self::Agent => {}
+        }
+    }
+}
+
+impl WireConfig {
+    fn from_config(config: &SshConfig) -> Result<Self, SshError> {
+        if config.transport != Transport::Tcp {
+            return Err(SshError::Mux(
+                "sessions tunneled through a P2P peer cannot be shared".to_string(),
+            ));
+        }
+        let auth = match &config.auth {
+            AuthMethod::Password(password) => WireAuth::Password {
+                password: password.clone(),
+            },
+            AuthMethod::PublicKey {
+                key_path,
+                passphrase,
+            } => WireAuth::PublicKey {
+                key_path: key_path.clone(),
+                passphrase: passphrase.clone(),
+            },
+            AuthMethod::Agent => WireAuth::Agent,
+        };
+        Ok(Self {
+            host: config.host.clone(),
+            port: config.port,
+            username: config.username.clone(),
+            auth,
+            timeout_secs: config.timeout.as_secs(),
+            known_hosts_path: config.known_hosts_path.clone(),
+            host_key_check: config.host_key_check.clone(),
+        })
+    }
+
+    fn to_config(&self) -> SshConfig {
+        let auth = match &self.auth {
+            WireAuth::Password { password } => AuthMethod::Password(password.clone()),
+            WireAuth::PublicKey {
+                key_path,
+                passphrase,
+            } => AuthMethod::PublicKey {
+                key_path: key_path.clone(),
+                passphrase: passphrase.clone(),
+            },
+            WireAuth::Agent => AuthMethod::Agent,
+        };
+        SshConfig {
+            host: self.host.clone(),
+            port: self.port,
+            username: self.username.clone(),
+            auth,
+            timeout: Duration::from_secs(self.timeout_secs),
+            known_hosts_path: self.known_hosts_path.clone(),
+            host_key_check: self.host_key_check.clone(),
+            transport: Transport::Tcp,
+        }
+    }
+}
+
+/// Daemon holding SSH sessions for other processes
+#[derive(Clone)]
+pub struct MuxServer {
+    sessions: Arc<Mutex<HashMap<PoolKey, MuxSession>>>,
+    idle_timeout: Duration,
+    stop: Arc<Notify>,
+}
+
+impl Default for MuxServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MuxServer {
+    /// Create a server with no sessions
+    pub fn new() -> Self {
+        Self {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            idle_timeout: DEFAULT_MUX_IDLE_TIMEOUT,
+            stop: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Close sessions that have not run a command for `timeout`
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Listen on a Unix socket until a client asks the server to stop
+    ///
+    /// A stale socket left at `path` is replaced, and the socket is only
+    /// accessible by the current user. Sessions are closed on return.
+    #[cfg(unix)]
+    pub async fn serve(&self, path: &Path) -> Result<(), SshError> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let socket_error = |e: std::io::Error| SshError::Mux(format!("{}: {}", path.display(), e));
+        if MuxClient::new(path).is_running().await {
+            return Err(SshError::Mux(format!(
+                "{}: a multiplexer is already running",
+                path.display()
+            )));
+        }
+        if path.exists() {
+            std::fs::remove_file(path).map_err(socket_error)?;
+        }
+        let listener = tokio::net::UnixListener::bind(path).map_err(socket_error)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .map_err(socket_error)?;
+        tracing::info!(path = %path.display(), "SSH multiplexer listening");
+
+        let mut prune = tokio::time::interval(Duration::from_secs(60).min(self.idle_timeout));
+        let result = loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, _) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => break Err(socket_error(e)),
+                    };
+                    let server = self.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = server.handle(stream).await {
+                            tracing::debug!(error = %e, "Multiplexer client failed");
+                        }
+                    });
+                }
+                _ = prune.tick() => self.close_idle().await,
+                _ = self.stop.notified() => break Ok(()),
+            }
+        };
+
+        self.close_all().await;
+        let _ = std::fs::remove_file(path);
+        tracing::info!("SSH multiplexer stopped");
+        result
+    }
+
+    /// Answer requests on one connection until the client hangs up
+    pub async fn handle<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        stream: S,
+    ) -> Result<(), SshError> {
+        let mut stream = BufReader::new(stream);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if stream.read_line(&mut line).await.map_err(io_error)? == 0 {
+                return Ok(());
+            }
+            let response = match serde_json::from_str::<MuxRequest>(&line) {
+                Ok(request) => self.respond(request).await,
+                Err(e) => MuxResponse::Error {
+                    message: format!("invalid request: {}", e),
+                },
+            };
+            line.zeroize();
+            write_line(stream.get_mut(), &response).await?;
+        }
+    }
+
+    /// Get the shared sessions
+    pub async fn sessions(&self) -> Vec<MuxSessionInfo> {
+        let sessions = self.sessions.lock().await;
+        let mut infos = Vec::with_capacity(sessions.len());
+        for (key, session) in sessions.iter() {
+            infos.push(MuxSessionInfo {
+                key: key.clone(),
+                connected: session.client.read().await.is_connected(),
+                commands: session.commands,
+                idle: session.last_used.elapsed(),
+            });
+        }
+        infos.sort_by_key(|info| info.key.to_string());
+        infos
+    }
+
+    async fn respond(&self, request: MuxRequest) -> MuxResponse {
+        let result = match request {
+            MuxRequest::Open { config } => self.open(config.to_config()).await,
+            MuxRequest::Exec { key, command } => {
+                self.execute(&key, &command)
+                    .await
+                    .map(|output| MuxResponse::Output {
+                        stdout: STANDARD.encode(&output.stdout),
+                        stderr: STANDARD.encode(&output.stderr),
+                        exit_code: output.exit_code,
+                    })
+            }
+            MuxRequest::Status => Ok(MuxResponse::Sessions {
+                sessions: self.sessions().await,
+            }),
+            MuxRequest::Close { key } => self.close(&key).await,
+            MuxRequest::Stop => {
+                self.stop.notify_one();
+                Ok(MuxResponse::Ok)
+            }
+        };
+        result.unwrap_or_else(|e| MuxResponse::Error {
+            message: e.to_string(),
+        })
+    }
+
+    /// Open a session unless a live one is already shared
+    async fn open(&self, config: SshConfig) -> Result<MuxResponse, SshError> {
+        let key = PoolKey::from_config(&config);
+        let existing = self
+            .sessions
+            .lock()
+            .await
+            .get(&key)
+            .map(|s| s.client.clone());
+        if let Some(client) = existing {
+            if client.read().await.is_connected() {
+                return Ok(MuxResponse::Ok);
+            }
+        }
+
+        // Connect without holding the lock so other sessions stay usable
+        let mut client = SshClient::new();
+        client.connect(&config).await?;
+        tracing::info!(session = %key, "Sharing SSH session");
+        let session = MuxSession {
+            client: Arc::new(RwLock::new(client)),
+            config,
+            commands: 0,
+            last_used: Instant::now(),
+        };
+        if let Some(old) = self.sessions.lock().await.insert(key, session) {
+            disconnect(old).await;
+        }
+        Ok(MuxResponse::Ok)
+    }
+
+    /// Run a command over a shared session, reconnecting it if it dropped
+    async fn execute(&self, key: &PoolKey, command: &str) -> Result<CommandResult, SshError> {
+        let (client, config) = {
+            let mut sessions = self.sessions.lock().await;
+            let session = sessions
+                .get_mut(key)
+                .ok_or_else(|| SshError::Mux(format!("no session for {}", key)))?;
+            session.commands += 1;
+            session.last_used = Instant::now();
+            (session.client.clone(), session.config.clone())
+        };
+
+        if !client.read().await.is_connected() {
+            let mut client = client.write().await;
+            // Another request may have reconnected it meanwhile
+            if !client.is_connected() {
+                tracing::info!(session = %key, "Reconnecting shared SSH session");
+                client.connect(&config).await?;
+            }
+        }
+        let client = client.read().await;
+        client.execute(command).await
+    }
+
+    async fn close(&self, key: &PoolKey) -> Result<MuxResponse, SshError> {
+        let session = self.sessions.lock().await.remove(key);
+        match session {
+            Some(session) => {
+                disconnect(session).await;
+                Ok(MuxResponse::Ok)
+            }
+            None => Err(SshError::Mux(format!("no session for {}", key))),
+        }
+    }
+
+    async fn close_idle(&self) {
+        let idle: Vec<MuxSession> = {
+            let mut sessions = self.sessions.lock().await;
+            let keys: Vec<PoolKey> = sessions
+                .iter()
+                .filter(|(_, session)| session.last_used.elapsed() >= self.idle_timeout)
+                .map(|(key, _)| key.clone())
+                .collect();
+            keys.iter().filter_map(|key| sessions.remove(key)).collect()
+        };
+        for session in idle {
+            disconnect(session).await;
+        }
+    }
+
+    async fn close_all(&self) {
+        let sessions: Vec<MuxSession> =
+            self.sessions.lock().await.drain().map(|(_, s)| s).collect();
+        for session in sessions {
+            disconnect(session).await;
+        }
+    }
+}
+
+/// Disconnect a session once commands still running on it finish
+async fn disconnect(session: MuxSession) {
+    let key = PoolKey::from_config(&session.config);
+    if let Err(e) = session.client.write().await.disconnect().await {
+        tracing::debug!(session = %key, error = %e, "Closing shared session failed");
+    }
+    tracing::info!(session = %key, "Closed shared SSH session");
+}
+
+/// Client of a [`MuxServer`]
+///
+/// Every call is a separate connection to the socket, so the client is
+/// cheap to clone and holds nothing open.
+#[derive(Debug, Clone)]
+pub struct MuxClient {
+    path: PathBuf,
+}
+
+impl MuxClient {
+    /// Create a client for the socket at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Get the socket path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Check whether a server answers on the socket
+    pub async fn is_running(&self) -> bool {
+        self.status().await.is_ok()
+    }
+
+    /// Get the shared sessions
+    pub async fn status(&self) -> Result<Vec<MuxSessionInfo>, SshError> {
+        match self.request(&MuxRequest::Status).await? {
+            MuxResponse::Sessions { sessions } => Ok(sessions),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Check whether the server holds a session for `key`
+    ///
+    /// A session that has dropped still counts: the server reconnects it
+    /// with the credentials it was opened with.
+    pub async fn has_session(&self, key: &PoolKey) -> bool {
+        self.status()
+            .await
+            .is_ok_and(|sessions| sessions.iter().any(|s| &s.key == key))
+    }
+
+    /// Get a client running commands over the shared session for
+    /// `config`, having the server open it first if it holds none
+    pub async fn attach(&self, config: &SshConfig) -> Result<SshClient, SshError> {
+        let key = PoolKey::from_config(config);
+        if !self.has_session(&key).await {
+            self.open(config).await?;
+        }
+        Ok(SshClient::new().with_mux(self.clone(), key))
+    }
+
+    /// Have the server connect with `config` and share the session
+    pub async fn open(&self, config: &SshConfig) -> Result<PoolKey, SshError> {
+        let request = MuxRequest::Open {
+            config: WireConfig::from_config(config)?,
+        };
+        match self.request(&request).await? {
+            MuxResponse::Ok => Ok(PoolKey::from_config(config)),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Run a command over the shared session for `key`
+    pub async fn execute(&self, key: &PoolKey, command: &str) -> Result<CommandResult, SshError> {
+        let request = MuxRequest::Exec {
+            key: key.clone(),
+            command: command.to_string(),
+        };
+        match self.request(&request).await? {
+            MuxResponse::Output {
+                stdout,
+                stderr,
+                exit_code,
+            } => Ok(CommandResult {
+                stdout: decode(&stdout)?,
+                stderr: decode(&stderr)?,
+                exit_code,
+            }),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Disconnect the shared session for `key`
+    pub async fn close(&self, key: &PoolKey) -> Result<(), SshError> {
+        match self
+            .request(&MuxRequest::Close { key: key.clone() })
+            .await?
+        {
+            MuxResponse::Ok => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Ask the server to close every session and exit
+    pub async fn stop(&self) -> Result<(), SshError> {
+        match self.request(&MuxRequest::Stop).await? {
+            MuxResponse::Ok => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    #[cfg(unix)]
+    async fn request(&self, request: &MuxRequest) -> Result<MuxResponse, SshError> {
+        let stream = tokio::net::UnixStream::connect(&self.path)
+            .await
+            .map_err(|e| SshError::Mux(format!("{}: {}", self.path.display(), e)))?;
+        exchange(stream, request).await
+    }
+
+    #[cfg(not(unix))]
+    async fn request(&self, _request: &MuxRequest) -> Result<MuxResponse, SshError> {
+        Err(SshError::Mux(
+            "session multiplexing is only supported on Unix".to_string(),
+        ))
+    }
+}
+
+/// Send one request and read its response
+async fn exchange<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: S,
+    request: &MuxRequest,
+) -> Result<MuxResponse, SshError> {
+    let mut stream = BufReader::new(stream);
+    write_line(stream.get_mut(), request).await?;
+    let mut line = String::new();
+    if stream.read_line(&mut line).await.map_err(io_error)? == 0 {
+        return Err(SshError::Mux("the multiplexer hung up".to_string()));
+    }
+    serde_json::from_str(&line).map_err(|e| SshError::Mux(format!("invalid response: {}", e)))
+}
+
+async fn write_line<S: AsyncWrite + Unpin, T: Serialize>(
+    stream: &mut S,
+    message: &T,
+) -> Result<(), SshError> {
+    let mut line = serde_json::to_vec(message)
+        .map_err(|e| SshError::Mux(format!("encoding message: {}", e)))?;
+    line.push(b'\n');
+    stream.write_all(&line).await.map_err(io_error)?;
+    line.zeroize();
+    stream.flush().await.map_err(io_error)
+}
+
+fn decode(data: &str) -> Result<Vec<u8>, SshError> {
+    STANDARD
+        .decode(data)
+        .map_err(|e| SshError::Mux(format!("invalid output: {}", e)))
+}
+
+fn unexpected(response: MuxResponse) -> SshError {
+    match response {
+        MuxResponse::Error { message } => SshError::Mux(message),
+        _ => SshError::Mux("unexpected response".to_string()),
+    }
+}
+
+fn io_error(e: std::io::Error) -> SshError {
+    SshError::Mux(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn ask(server: &MuxServer, request: &MuxRequest) -> MuxResponse {
+        let (client, server_side) = tokio::io::duplex(4096);
+        let server = server.clone();
+        tokio::spawn(async move { server.handle(server_side).await });
+        exchange(client, request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn exec_needs_an_open_session() {
+        let server = MuxServer::new();
+        let response = ask(&server, &MuxRequest::Status).await;
+        assert!(matches!(response, MuxResponse::Sessions { sessions } if sessions.is_empty()));
+
+        let key = PoolKey {
+            host: "example.com".to_string(),
+            port: 22,
+            username: "deploy".to_string(),
+        };
+        let request = MuxRequest::Exec {
+            key,
+            command: "uptime".to_string(),
+        };
+        let response = ask(&server, &request).await;
+        assert!(
+            matches!(response, MuxResponse::Error { message } if message.contains("deploy@example.com:22"))
+        );
+    }
+
+    #[tokio::test]
+    async fn p2p_sessions_are_not_shared() {
+        let config = SshConfig {
+            host: "peer".to_string(),
+            port: 22,
+            username: "user".to_string(),
+            auth: AuthMethod::Agent,
+            timeout: Duration::from_secs(5),
+            known_hosts_path: None,
+            host_key_check: HostKeyCheck::Strict,
+            transport: Transport::P2P(iroh::SecretKey::generate(rand::rngs::OsRng).public()),
+        };
+        assert!(WireConfig::from_config(&config).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stop_ends_the_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(MUX_SOCKET_FILE_NAME);
+        let server = MuxServer::new();
+        let serving = {
+            let path = path.clone();
+            tokio::spawn(async move { server.serve(&path).await })
+        };
+
+        let client = MuxClient::new(&path);
+        for _ in 0..50 {
+            if client.is_running().await {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(client.status().await.unwrap().is_empty());
+
+        client.stop().await.unwrap();
+        serving.await.unwrap().unwrap();
+        assert!(!path.exists());
+        assert!(!client.is_running().await);
+    }
+}
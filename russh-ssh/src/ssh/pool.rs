@@ -0,0 +1,391 @@
+//! SSH Client Pool
+//!
+//! Caps the number of concurrent sessions per host and reuses idle
+//! connections. Excess acquires either wait in a queue (with a timeout)
+//! or are rejected immediately, so fan-out automation across many hosts
+//! cannot open an unbounded number of sockets.
+//!
+//! # Requirements Coverage
+//! - Requirement 1.1: Async SSH connection establishment
+
+use super::{SshClient, SshConfig};
+use crate::error::SshError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Behaviour when a host is at its concurrency limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolOverflow {
+    /// Wait for a free slot, failing after the timeout
+    Queue { timeout: Duration },
+    /// Fail immediately
+    Reject,
+}
+
+/// Client pool configuration
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Maximum concurrent sessions per host
+    pub max_per_host: usize,
+    /// What to do when a host is at its limit
+    pub overflow: PoolOverflow,
+    /// How long an idle connection is kept for reuse
+    pub idle_timeout: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_per_host: 4,
+            overflow: PoolOverflow::Queue {
+                timeout: Duration::from_secs(30),
+            },
+            idle_timeout: Duration::from_secs(300),
+        }
+    }
+}
+
+impl PoolConfig {
+    /// Create a pool configuration with default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the per-host concurrency limit
+    pub fn with_max_per_host(mut self, max: usize) -> Self {
+        self.max_per_host = max;
+        self
+    }
+
+    /// Set the overflow behaviour
+    pub fn with_overflow(mut self, overflow: PoolOverflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Set the idle connection timeout
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+}
+
+/// Key identifying connections that can be shared
+///
+/// Only the destination and user are part of the key: the authentication
+/// method, host key policy and transport of an [`SshConfig`] are ignored,
+/// so a lease reuses whichever session was opened first for that user and
+/// host. This lets a shared session be found before any credentials are
+/// gathered; callers that need different settings for the same destination
+/// should use separate pools.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PoolKey {
+    /// Remote host
+    pub host: String,
+    /// Remote port
+    pub port: u16,
+    /// Username
+    pub username: String,
+}
+
+impl PoolKey {
+    /// Build the pool key for an SSH configuration
+    pub fn from_config(config: &SshConfig) -> Self {
+        Self {
+            host: config.host.clone(),
+            port: config.port,
+            username: config.username.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for PoolKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}:{}", self.username, self.host, self.port)
+    }
+}
+
+/// An idle connection waiting for reuse
+struct IdleClient {
+    client: SshClient,
+    idle_since: Instant,
+}
+
+/// Per-host pool state
+struct HostPool {
+    /// Limits concurrent leases for the host
+    slots: Arc<Semaphore>,
+    /// Connected clients available for reuse
+    idle: Vec<IdleClient>,
+}
+
+type HostPools = Arc<Mutex<HashMap<PoolKey, HostPool>>>;
+
+/// Pool of SSH clients with per-host concurrency limits
+pub struct ClientPool {
+    /// Pool configuration
+    config: PoolConfig,
+    /// Per-host state
+    hosts: HostPools,
+}
+
+impl ClientPool {
+    /// Create a new client pool
+    pub fn new(config: PoolConfig) -> Self {
+        Self {
+            config,
+            hosts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Get the pool configuration
+    pub fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+
+    /// Acquire a connected client for the given configuration
+    ///
+    /// Reuses an idle connection when one is available, otherwise connects.
+    /// Connections are matched by [`PoolKey`] alone, so an idle client may
+    /// have been authenticated differently than `config` asks for.
+    pub async fn acquire(&self, config: &SshConfig) -> Result<PooledClient, SshError> {
+        let key = PoolKey::from_config(config);
+        let permit = self.acquire_slot(&key).await?;
+
+        if let Some(client) = self.take_idle(&key) {
+            tracing::debug!(key = %key, "Reusing idle pooled connection");
+            return Ok(PooledClient::new(client, key, self.hosts.clone(), permit));
+        }
+
+        let mut client = SshClient::new();
+        client.connect(config).await?;
+        tracing::debug!(key = %key, "Opened new pooled connection");
+        Ok(PooledClient::new(client, key, self.hosts.clone(), permit))
+    }
+
+    /// Number of sessions currently leased for a host
+    pub fn active(&self, key: &PoolKey) -> usize {
+        let hosts = lock(&self.hosts);
+        hosts
+            .get(key)
+            .map(|pool| self.config.max_per_host - pool.slots.available_permits())
+            .unwrap_or(0)
+    }
+
+    /// Number of idle connections across all hosts
+    pub fn idle_count(&self) -> usize {
+        lock(&self.hosts).values().map(|pool| pool.idle.len()).sum()
+    }
+
+    /// Drop idle connections that have exceeded the idle timeout
+    pub fn prune_idle(&self) -> usize {
+        let mut hosts = lock(&self.hosts);
+        let mut pruned = 0;
+        for pool in hosts.values_mut() {
+            let before = pool.idle.len();
+            pool.idle.retain(|idle| {
+                idle.idle_since.elapsed() < self.config.idle_timeout && idle.client.is_connected()
+            });
+            pruned += before - pool.idle.len();
+        }
+        pruned
+    }
+
+    /// Wait for (or reject) a free slot for the host
+    async fn acquire_slot(&self, key: &PoolKey) -> Result<OwnedSemaphorePermit, SshError> {
+        let slots = {
+            let mut hosts = lock(&self.hosts);
+            hosts
+                .entry(key.clone())
+                .or_insert_with(|| HostPool {
+                    slots: Arc::new(Semaphore::new(self.config.max_per_host)),
+                    idle: Vec::new(),
+                })
+                .slots
+                .clone()
+        };
+
+        let exhausted = || SshError::PoolExhausted {
+            host: key.to_string(),
+            limit: self.config.max_per_host,
+        };
+
+        match self.config.overflow {
+            PoolOverflow::Reject => slots.try_acquire_owned().map_err(|_| exhausted()),
+            PoolOverflow::Queue { timeout } => {
+                match tokio::time::timeout(timeout, slots.acquire_owned()).await {
+                    Ok(Ok(permit)) => Ok(permit),
+                    _ => Err(exhausted()),
+                }
+            }
+        }
+    }
+
+    /// Take a live, non-expired idle client for the host
+    fn take_idle(&self, key: &PoolKey) -> Option<SshClient> {
+        let mut hosts = lock(&self.hosts);
+        let pool = hosts.get_mut(key)?;
+        while let Some(idle) = pool.idle.pop() {
+            if idle.idle_since.elapsed() < self.config.idle_timeout && idle.client.is_connected() {
+                return Some(idle.client);
+            }
+        }
+        None
+    }
+}
+
+impl Default for ClientPool {
+    fn default() -> Self {
+        Self::new(PoolConfig::default())
+    }
+}
+
+/// Lock the host map, recovering from poisoning
+fn lock(hosts: &HostPools) -> std::sync::MutexGuard<'_, HashMap<PoolKey, HostPool>> {
+    hosts.lock().unwrap_or_else(|poisoned| {
+        tracing::warn!("Client pool lock was poisoned, recovering");
+        poisoned.into_inner()
+    })
+}
+
+/// A client leased from the pool
+///
+/// Dereferences to `SshClient`. On drop the client is returned to the pool
+/// for reuse if it is still connected, and its slot is released.
+pub struct PooledClient {
+    client: SshClient,
+    key: PoolKey,
+    hosts: HostPools,
+    detached: bool,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledClient {
+    fn new(
+        client: SshClient,
+        key: PoolKey,
+        hosts: HostPools,
+        permit: OwnedSemaphorePermit,
+    ) -> Self {
+        Self {
+            client,
+            key,
+            hosts,
+            detached: false,
+            _permit: permit,
+        }
+    }
+
+    /// Get the pool key of this client
+    pub fn key(&self) -> &PoolKey {
+        &self.key
+    }
+
+    /// Remove the client from the pool instead of returning it on drop
+    pub fn detach(mut self) -> SshClient {
+        self.detached = true;
+        std::mem::take(&mut self.client)
+    }
+}
+
+impl Deref for PooledClient {
+    type Target = SshClient;
+
+    fn deref(&self) -> &SshClient {
+        &self.client
+    }
+}
+
+impl DerefMut for PooledClient {
+    fn deref_mut(&mut self) -> &mut SshClient {
+        &mut self.client
+    }
+}
+
+impl Drop for PooledClient {
+    fn drop(&mut self) {
+        if self.detached || !self.client.is_connected() {
+            return;
+        }
+        let client = std::mem::take(&mut self.client);
+        let mut hosts = lock(&self.hosts);
+        if let Some(pool) = hosts.get_mut(&self.key) {
+            pool.idle.push(IdleClient {
+                client,
+                idle_since: Instant::now(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> PoolKey {
+        PoolKey {
+            host: "host.com".to_string(),
+            port: 22,
+            username: "user".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn reject_policy_fails_at_limit() {
+        let pool = ClientPool::new(
+            PoolConfig::new()
+                .with_max_per_host(1)
+                .with_overflow(PoolOverflow::Reject),
+        );
+
+        let first = pool.acquire_slot(&key()).await.unwrap();
+        assert_eq!(pool.active(&key()), 1);
+        assert!(matches!(
+            pool.acquire_slot(&key()).await,
+            Err(SshError::PoolExhausted { limit: 1, .. })
+        ));
+
+        drop(first);
+        assert_eq!(pool.active(&key()), 0);
+        assert!(pool.acquire_slot(&key()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn queue_policy_waits_for_free_slot() {
+        let pool = Arc::new(ClientPool::new(
+            PoolConfig::new()
+                .with_max_per_host(1)
+                .with_overflow(PoolOverflow::Queue {
+                    timeout: Duration::from_millis(50),
+                }),
+        ));
+
+        let first = pool.acquire_slot(&key()).await.unwrap();
+        assert!(pool.acquire_slot(&key()).await.is_err());
+
+        let waiter = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.acquire_slot(&key()).await.is_ok() })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(first);
+        assert!(waiter.await.unwrap());
+    }
+
+    #[test]
+    fn limits_are_per_host() {
+        let pool = ClientPool::default();
+        let other = PoolKey {
+            host: "other.com".to_string(),
+            ..key()
+        };
+        assert_eq!(pool.active(&key()), 0);
+        assert_eq!(pool.active(&other), 0);
+        assert_eq!(pool.idle_count(), 0);
+        assert_eq!(other.to_string(), "user@other.com:22");
+    }
+}
@@ -0,0 +1,265 @@
+//! Local Echo Prediction
+//!
+//! Draws typed characters before the server echoes them, as mosh does, so
+//! an interactive shell over a slow link still feels immediate. Each
+//! printable keystroke is expected to come back as the same character; when
+//! the server's output arrives, echoes that match are dropped since they are
+//! already on screen, and a mismatch erases what was drawn before the output
+//! is shown.
+//!
+//! Predictions are only drawn once echo has been seen to work: after Enter
+//! or any other control key, the next keystroke waits for its echo before
+//! later ones are drawn, so a password prompt with echo turned off never
+//! shows what is typed. Nothing is drawn while a full-screen program holds
+//! the alternate screen, or while echoes come back faster than the display
+//! threshold, where prediction would not be noticed.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Echo delay above which predictions are drawn by default
+pub const DEFAULT_PREDICTION_THRESHOLD: Duration = Duration::from_millis(30);
+
+/// How long a prediction may wait for its echo before it is erased
+const PREDICTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sequences switching the alternate screen on, then off
+const ALTERNATE_SCREEN: [(&[u8], &[u8]); 3] = [
+    (b"\x1b[?1049h", b"\x1b[?1049l"),
+    (b"\x1b[?1047h", b"\x1b[?1047l"),
+    (b"\x1b[?47h", b"\x1b[?47l"),
+];
+
+/// Longest alternate screen sequence, kept across output chunks
+const MAX_SEQUENCE: usize = 8;
+
+/// A keystroke waiting for its echo
+#[derive(Debug, Clone, Copy)]
+struct Prediction {
+    byte: u8,
+    /// Whether it was drawn
+    shown: bool,
+    at: Instant,
+}
+
+/// Predicts the echo of typed characters for an interactive shell
+///
+/// Feed keystrokes through [`input`](Self::input) and server output through
+/// [`output`](Self::output), writing what each returns to the terminal, and
+/// call [`expire`](Self::expire) now and then.
+#[derive(Debug, Clone)]
+pub struct EchoPredictor {
+    pending: VecDeque<Prediction>,
+    /// Whether an echo has been seen since the last control key
+    confirmed: bool,
+    alternate_screen: bool,
+    /// Smoothed echo delay
+    srtt: Option<Duration>,
+    threshold: Duration,
+    /// End of the previous output chunk, for sequences split across chunks
+    tail: Vec<u8>,
+}
+
+impl Default for EchoPredictor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EchoPredictor {
+    /// Create a predictor drawing predictions once echoes take longer than
+    /// [`DEFAULT_PREDICTION_THRESHOLD`]
+    pub fn new() -> Self {
+        Self {
+            pending: VecDeque::new(),
+            confirmed: false,
+            alternate_screen: false,
+            srtt: None,
+            threshold: DEFAULT_PREDICTION_THRESHOLD,
+            tail: Vec::new(),
+        }
+    }
+
+    /// Draw predictions once echoes take longer than `threshold`; zero
+    /// draws them whenever echo has been confirmed
+    pub fn with_threshold(mut self, threshold: Duration) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Smoothed delay between a keystroke and its echo, once measured
+    pub fn echo_delay(&self) -> Option<Duration> {
+        self.srtt
+    }
+
+    /// Record keystrokes sent to the server; returns what to draw now
+    pub fn input(&mut self, data: &[u8]) -> Vec<u8> {
+        let now = Instant::now();
+        let mut draw = Vec::new();
+        for &byte in data {
+            if self.alternate_screen || !(0x20..0x7f).contains(&byte) {
+                // The effect of control keys is left to the server
+                self.confirmed = false;
+                continue;
+            }
+            let shown = self.confirmed && self.srtt.is_some_and(|srtt| srtt >= self.threshold);
+            if shown {
+                draw.push(byte);
+            }
+            self.pending.push_back(Prediction {
+                byte,
+                shown,
+                at: now,
+            });
+        }
+        draw
+    }
+
+    /// Reconcile server output with the predictions; returns what to write
+    pub fn output(&mut self, data: &[u8]) -> Vec<u8> {
+        let now = Instant::now();
+        let mut out = Vec::with_capacity(data.len());
+        for (i, &byte) in data.iter().enumerate() {
+            let Some(prediction) = self.pending.front().copied() else {
+                out.extend_from_slice(&data[i..]);
+                break;
+            };
+            if prediction.byte == byte {
+                self.pending.pop_front();
+                self.confirmed = true;
+                self.sample(now.duration_since(prediction.at));
+                if !prediction.shown {
+                    out.push(byte);
+                }
+            } else {
+                out.extend_from_slice(&self.clear());
+                out.extend_from_slice(&data[i..]);
+                break;
+            }
+        }
+        if self.track_alternate_screen(data) {
+            self.pending.clear();
+            self.confirmed = false;
+        }
+        out
+    }
+
+    /// Erase predictions that have waited too long for their echo
+    pub fn expire(&mut self, now: Instant) -> Vec<u8> {
+        match self.pending.front() {
+            Some(prediction) if now.duration_since(prediction.at) >= PREDICTION_TIMEOUT => {
+                self.clear()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Forget all predictions, e.g. for a new shell after reconnecting
+    ///
+    /// The measured echo delay is kept.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.confirmed = false;
+        self.alternate_screen = false;
+        self.tail.clear();
+    }
+
+    /// Drop every prediction, returning what erases the drawn ones
+    fn clear(&mut self) -> Vec<u8> {
+        let shown = self.pending.iter().filter(|p| p.shown).count();
+        self.pending.clear();
+        self.confirmed = false;
+        if shown == 0 {
+            return Vec::new();
+        }
+        // Back over the drawn characters, then clear to the end of the line
+        format!("\x1b[{}D\x1b[K", shown).into_bytes()
+    }
+
+    fn sample(&mut self, delay: Duration) {
+        self.srtt = Some(match self.srtt {
+            Some(srtt) => (srtt * 7 + delay) / 8,
+            None => delay,
+        });
+    }
+
+    /// Follow alternate screen switches; returns whether it was entered
+    fn track_alternate_screen(&mut self, data: &[u8]) -> bool {
+        let mut window = std::mem::take(&mut self.tail);
+        window.extend_from_slice(data);
+        // The last switch in the window decides
+        let mut last: Option<(usize, bool)> = None;
+        for (enter, leave) in ALTERNATE_SCREEN {
+            for (sequence, on) in [(enter, true), (leave, false)] {
+                if let Some(at) = rfind(&window, sequence) {
+                    if !last.is_some_and(|(last_at, _)| last_at > at) {
+                        last = Some((at, on));
+                    }
+                }
+            }
+        }
+        let start = window.len().saturating_sub(MAX_SEQUENCE - 1);
+        self.tail = window.split_off(start);
+
+        let Some((_, on)) = last else {
+            return false;
+        };
+        let entered = on && !self.alternate_screen;
+        self.alternate_screen = on;
+        entered
+    }
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn confirmed() -> EchoPredictor {
+        let mut predictor = EchoPredictor::new().with_threshold(Duration::ZERO);
+        assert!(predictor.input(b"l").is_empty());
+        assert_eq!(predictor.output(b"l"), b"l");
+        predictor
+    }
+
+    #[test]
+    fn echo_is_drawn_once_confirmed() {
+        let mut predictor = confirmed();
+        assert_eq!(predictor.input(b"s -"), b"s -");
+        // Echoes already on screen are dropped, the rest passes through
+        assert_eq!(predictor.output(b"s"), b"");
+        assert_eq!(predictor.output(b" -la"), b"la");
+        assert!(predictor.echo_delay().is_some());
+
+        // After Enter the next key waits for its echo again
+        assert!(predictor.input(b"\r").is_empty());
+        assert!(predictor.input(b"x").is_empty());
+    }
+
+    #[test]
+    fn mismatch_erases_predictions() {
+        let mut predictor = confirmed();
+        assert_eq!(predictor.input(b"ab"), b"ab");
+        let out = predictor.output(b"\r\nmail\r\n");
+        assert_eq!(out, b"\x1b[2D\x1b[K\r\nmail\r\n");
+        // Echo arriving after the erase is shown as is
+        assert_eq!(predictor.output(b"ab"), b"ab");
+    }
+
+    #[test]
+    fn alternate_screen_turns_prediction_off() {
+        let mut predictor = confirmed();
+        predictor.output(b"\x1b[?10");
+        predictor.output(b"49h");
+        assert!(predictor.input(b"j").is_empty());
+        assert_eq!(predictor.output(b"\x1b[?1049l$ "), b"\x1b[?1049l$ ");
+        assert!(predictor.input(b"k").is_empty());
+        predictor.output(b"k");
+        assert_eq!(predictor.input(b"w"), b"w");
+    }
+}
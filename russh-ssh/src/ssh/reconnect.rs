@@ -0,0 +1,255 @@
+//! SSH Auto-Reconnect
+//!
+//! Re-establishes a dropped SSH session: re-runs the handshake and
+//! authentication, re-opens the interactive shell (optionally reattaching
+//! a tmux session) and rebinds the port forwards that were active.
+//! Progress is reported through the client's state manager.
+//!
+//! # Requirements Coverage
+//! - Requirement 2.1: Automatic reconnection with exponential backoff
+//! - Requirement 2.3: Session state restoration
+//! - Requirement 2.4: User notification of reconnection status
+
+use super::command::Shell;
+use super::forward::{ForwardHandle, PortForward, PortForwarder};
+use super::sftp::shell_escape;
+use super::SshClient;
+use crate::config::ReconnectionStrategy;
+use crate::connection::{ConnectionState, ReconnectionPolicy};
+use crate::error::SshError;
+use std::sync::Arc;
+
+/// Auto-reconnect policy for an SSH client
+#[derive(Debug, Clone)]
+pub struct AutoReconnectConfig {
+    /// Backoff policy for reconnection attempts
+    pub strategy: Arc<dyn ReconnectionPolicy>,
+    /// Re-open the interactive shell after reconnecting
+    pub restore_shell: bool,
+    /// tmux session to reattach instead of starting a fresh shell
+    pub tmux_session: Option<String>,
+    /// Rebind port forwards that were active before the drop
+    pub restore_forwards: bool,
+}
+
+impl Default for AutoReconnectConfig {
+    fn default() -> Self {
+        Self {
+            strategy: Arc::new(ReconnectionStrategy::default()),
+            restore_shell: true,
+            tmux_session: None,
+            restore_forwards: true,
+        }
+    }
+}
+
+impl AutoReconnectConfig {
+    /// Create a new auto-reconnect policy with default settings
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the backoff policy
+    pub fn with_strategy(mut self, strategy: impl ReconnectionPolicy + 'static) -> Self {
+        self.strategy = Arc::new(strategy);
+        self
+    }
+
+    /// Reattach the given tmux session when restoring the shell
+    pub fn with_tmux_session(mut self, session: impl Into<String>) -> Self {
+        self.tmux_session = Some(session.into());
+        self
+    }
+
+    /// Enable or disable shell restoration
+    pub fn with_restore_shell(mut self, enabled: bool) -> Self {
+        self.restore_shell = enabled;
+        self
+    }
+
+    /// Enable or disable port forward restoration
+    pub fn with_restore_forwards(mut self, enabled: bool) -> Self {
+        self.restore_forwards = enabled;
+        self
+    }
+
+    /// Command used to (re)open the interactive shell
+    pub fn shell_command(&self) -> String {
+        match &self.tmux_session {
+            Some(session) => format!("tmux new-session -A -s {}", shell_escape(session)),
+            None => "/bin/sh".to_string(),
+        }
+    }
+}
+
+/// Parameters of an interactive shell, remembered for restoration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShellSpec {
+    /// Terminal type
+    pub term: String,
+    /// Terminal width in columns
+    pub cols: u32,
+    /// Terminal height in rows
+    pub rows: u32,
+}
+
+/// What was restored after a successful reconnection
+pub struct RestoredSession {
+    /// Re-opened interactive shell, if one was active and restoration is enabled
+    pub shell: Option<Shell>,
+    /// Port forwards that were rebound
+    pub forwards: Vec<Arc<ForwardHandle>>,
+    /// Port forwards that could not be rebound
+    pub failed_forwards: Vec<PortForward>,
+}
+
+impl SshClient {
+    /// Reconnect the session if it has dropped and auto-reconnect is enabled
+    ///
+    /// A session marked failed (by the health monitor or a network change)
+    /// is reconnected even if the transport has not noticed yet.
+    /// Returns `Ok(None)` when the session is still alive.
+    pub async fn ensure_connected(&mut self) -> Result<Option<RestoredSession>, SshError> {
+        if self.is_connected() && !self.state().is_failed() {
+            return Ok(None);
+        }
+        if self.auto_reconnect.is_none() {
+            return Err(SshError::NotConnected);
+        }
+        self.reconnect().await.map(Some)
+    }
+
+    /// Re-establish the SSH session and restore shell and forwards
+    ///
+    /// Uses the auto-reconnect policy if set, otherwise the defaults.
+    /// Requires a previous successful `connect`.
+    pub async fn reconnect(&mut self) -> Result<RestoredSession, SshError> {
+        let config = self.config().cloned().ok_or(SshError::NotConnected)?;
+        let policy = self.auto_reconnect.clone().unwrap_or_default();
+
+        // Tear down forwards bound to the dead connection, remembering their config
+        let previous_forwards: Vec<PortForward> = {
+            let mut forwards = self.forwards.write().await;
+            forwards
+                .drain()
+                .map(|(_, (handle, abort_handle))| {
+                    abort_handle.abort();
+                    handle.config.clone()
+                })
+                .collect()
+        };
+
+        tracing::info!(
+            host = %config.host,
+            max_attempts = ?policy.strategy.max_attempts(),
+            "Starting SSH reconnection"
+        );
+
+        let state_manager = self.state_manager.clone();
+        let controller = self.reconnection_controller.clone();
+        let p2p = self.p2p.clone();
+        let result = controller
+            .reconnect(policy.strategy.as_ref(), || {
+                let attempt = controller.current_attempt();
+                state_manager.set_state(ConnectionState::Reconnecting { attempt });
+                tracing::info!(attempt = %attempt, host = %config.host, "SSH reconnection attempt");
+                Self::establish(&config, p2p.as_ref())
+            })
+            .await;
+
+        let client = match result {
+            Ok(client) => client,
+            Err(e) => {
+                self.state_manager.set_state(ConnectionState::Failed {
+                    reason: e.to_string(),
+                });
+                tracing::error!(host = %config.host, error = %e, "SSH reconnection failed");
+                return Err(e.into());
+            }
+        };
+        self.set_inner(client);
+        self.state_manager.set_state(ConnectionState::Connected);
+        tracing::info!(host = %config.host, "SSH reconnection successful");
+
+        let mut restored = RestoredSession {
+            shell: None,
+            forwards: Vec::new(),
+            failed_forwards: Vec::new(),
+        };
+
+        if policy.restore_forwards {
+            for forward in previous_forwards {
+                match self.start_forward(forward.clone()).await {
+                    Ok(handle) => restored.forwards.push(handle),
+                    Err(e) => {
+                        tracing::warn!(forward = ?forward, error = %e, "Failed to restore port forward");
+                        restored.failed_forwards.push(forward);
+                    }
+                }
+            }
+        }
+
+        let last_shell = if policy.restore_shell {
+            self.last_shell
+                .read()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .clone()
+        } else {
+            None
+        };
+        if let Some(spec) = last_shell {
+            let shell = self
+                .open_shell_with_command(&policy.shell_command(), &spec.term, spec.cols, spec.rows)
+                .await?;
+            restored.shell = Some(shell);
+        }
+
+        Ok(restored)
+    }
+
+    /// Cancel an in-progress reconnection
+    pub fn cancel_reconnection(&self) {
+        self.reconnection_controller.cancel_reconnection();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_policy_restores_everything() {
+        let config = AutoReconnectConfig::default();
+        assert!(config.restore_shell);
+        assert!(config.restore_forwards);
+        assert!(config.tmux_session.is_none());
+        assert_eq!(config.shell_command(), "/bin/sh");
+    }
+
+    #[test]
+    fn tmux_session_is_reattached() {
+        let config = AutoReconnectConfig::new().with_tmux_session("work");
+        assert_eq!(config.shell_command(), "tmux new-session -A -s 'work'");
+
+        let config = AutoReconnectConfig::new().with_tmux_session("it's");
+        assert_eq!(config.shell_command(), "tmux new-session -A -s 'it'\\''s'");
+    }
+
+    #[tokio::test]
+    async fn reconnect_requires_previous_connection() {
+        let mut client = SshClient::new().with_auto_reconnect(AutoReconnectConfig::default());
+        assert!(matches!(
+            client.reconnect().await,
+            Err(SshError::NotConnected)
+        ));
+    }
+
+    #[tokio::test]
+    async fn ensure_connected_without_policy_fails() {
+        let mut client = SshClient::new();
+        assert!(matches!(
+            client.ensure_connected().await,
+            Err(SshError::NotConnected)
+        ));
+    }
+}
@@ -4,8 +4,12 @@
 //! Uses command execution as a fallback when native SFTP is not available.
 
 use crate::error::SshError;
-use crate::ssh::SshClient;
+use crate::events::RusshEvent;
+use crate::ssh::{CommandResult, SshClient};
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use uuid::Uuid;
 
 /// File entry information from remote server
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -53,6 +57,14 @@ impl SshClient {
             )));
         }
 
+        let bytes = result.stdout.len() as u64;
+        self.emit(RusshEvent::Transfer {
+            transfer_id: Uuid::new_v4(),
+            path: path.to_string(),
+            bytes,
+            total: Some(bytes),
+            done: true,
+        });
         Ok(result.stdout)
     }
 
@@ -70,9 +82,120 @@ impl SshClient {
             )));
         }
 
+        self.emit(RusshEvent::Transfer {
+            transfer_id: Uuid::new_v4(),
+            path: path.to_string(),
+            bytes: data.len() as u64,
+            total: Some(data.len() as u64),
+            done: true,
+        });
         Ok(())
     }
 
+    /// Read `len` bytes of a file starting at `offset`
+    ///
+    /// Returns fewer bytes when the file ends first. The bytes come back
+    /// base64 encoded, since command output is read as text.
+    pub async fn read_file_range(
+        &self,
+        path: &str,
+        offset: u64,
+        len: u64,
+    ) -> Result<Vec<u8>, SshError> {
+        let cmd = format!(
+            "test -r {p} || exit 1; tail -c +{} {p} | head -c {} | base64",
+            offset + 1,
+            len,
+            p = shell_escape(path)
+        );
+        let result = self.execute(&cmd).await?;
+
+        if result.exit_code != 0 {
+            return Err(SshError::CommandExecution(format!(
+                "Failed to read file: {}",
+                result.stderr_string()
+            )));
+        }
+
+        decode_base64_output(&result.stdout)
+    }
+
+    /// Append data to a file, creating it if needed
+    ///
+    /// The data travels base64 encoded in the command line, so keep each
+    /// call well under the remote's argument size limit.
+    pub async fn append_file(&self, path: &str, data: &[u8]) -> Result<(), SshError> {
+        let encoded = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, data);
+        let cmd = format!("echo '{}' | base64 -d >> {}", encoded, shell_escape(path));
+
+        let result = self.execute(&cmd).await?;
+
+        if result.exit_code != 0 {
+            return Err(SshError::CommandExecution(format!(
+                "Failed to append to file: {}",
+                result.stderr_string()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Open a file for writing as a stream
+    ///
+    /// Everything written goes to the standard input of one `cat` on the
+    /// remote host, so a large file is a single command instead of one per
+    /// chunk. The file is truncated first unless `append` is set. Needs a
+    /// direct connection; a shared session only runs whole commands.
+    pub async fn open_file_writer(
+        &self,
+        path: &str,
+        append: bool,
+    ) -> Result<RemoteFileWriter, SshError> {
+        if self.mux.is_some() {
+            return Err(SshError::Mux(
+                "streaming writes need a direct connection".to_string(),
+            ));
+        }
+        let client = self.inner().ok_or(SshError::NotConnected)?.clone();
+        let cmd = format!(
+            "cat {} {}",
+            if append { ">>" } else { ">" },
+            shell_escape(path)
+        );
+        tracing::debug!("Opening remote file writer: {}", cmd);
+
+        let (stdin_tx, stdin_rx) = mpsc::channel::<Vec<u8>>(WRITER_QUEUE);
+        let task = tokio::spawn(async move {
+            // Stderr arrives here as well; `cat` prints nothing else
+            let (output_tx, mut output_rx) = mpsc::channel::<Vec<u8>>(WRITER_QUEUE);
+            let exec = client.execute_io(&cmd, output_tx, None, Some(stdin_rx), false, None);
+            tokio::pin!(exec);
+            let mut output = Vec::new();
+            let exit_status = loop {
+                tokio::select! {
+                    result = &mut exec => break result,
+                    Some(data) = output_rx.recv() => output.extend(data),
+                }
+            };
+            while let Ok(data) = output_rx.try_recv() {
+                output.extend(data);
+            }
+            let exit_status = exit_status.map_err(|e| SshError::CommandExecution(e.to_string()))?;
+            Ok(CommandResult {
+                stdout: Vec::new(),
+                stderr: output,
+                exit_code: exit_status as i32,
+            })
+        });
+
+        Ok(RemoteFileWriter {
+            path: path.to_string(),
+            stdin: stdin_tx,
+            task,
+            written: 0,
+        })
+    }
+
     /// Delete file or directory
     pub async fn delete_path(&self, path: &str, recursive: bool) -> Result<(), SshError> {
         let cmd = if recursive {
@@ -123,6 +246,36 @@ impl SshClient {
         Ok(())
     }
 
+    /// Remove an empty directory
+    pub async fn remove_directory(&self, path: &str) -> Result<(), SshError> {
+        let cmd = format!("rmdir {}", shell_escape(path));
+        let result = self.execute(&cmd).await?;
+
+        if result.exit_code != 0 {
+            return Err(SshError::CommandExecution(format!(
+                "Failed to remove directory: {}",
+                result.stderr_string()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Change the permission bits of a file or directory
+    pub async fn chmod(&self, path: &str, mode: u32) -> Result<(), SshError> {
+        let cmd = format!("chmod {:o} {}", mode & 0o7777, shell_escape(path));
+        let result = self.execute(&cmd).await?;
+
+        if result.exit_code != 0 {
+            return Err(SshError::CommandExecution(format!(
+                "Failed to change permissions: {}",
+                result.stderr_string()
+            )));
+        }
+
+        Ok(())
+    }
+
     /// Get file/directory info
     pub async fn stat_path(&self, path: &str) -> Result<RemoteFileEntry, SshError> {
         let cmd = format!(
@@ -175,11 +328,95 @@ impl SshClient {
     }
 }
 
+/// Chunks queued for a remote file writer before writes wait
+const WRITER_QUEUE: usize = 16;
+
+/// A remote file being written as a stream, see [`SshClient::open_file_writer`]
+///
+/// Call [`finish`](Self::finish) to learn whether everything arrived;
+/// dropping the writer abandons the command and leaves a partial file.
+pub struct RemoteFileWriter {
+    path: String,
+    stdin: mpsc::Sender<Vec<u8>>,
+    task: JoinHandle<Result<CommandResult, SshError>>,
+    written: u64,
+}
+
+impl RemoteFileWriter {
+    /// Queue data to be written
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), SshError> {
+        // An empty message would close the remote side's input
+        if data.is_empty() {
+            return Ok(());
+        }
+        if self.stdin.send(data.to_vec()).await.is_err() {
+            return Err(SshError::CommandExecution(format!(
+                "Writing {} stopped early",
+                self.path
+            )));
+        }
+        self.written += data.len() as u64;
+        Ok(())
+    }
+
+    /// Bytes queued so far
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Close the file and wait until the remote host has written it all
+    ///
+    /// Returns the number of bytes written.
+    pub async fn finish(mut self) -> Result<u64, SshError> {
+        // The sender stays alive until the command exits, since a closed
+        // channel would keep waking the task that feeds the remote side
+        self.stdin.send(Vec::new()).await.ok();
+        let result = (&mut self.task)
+            .await
+            .map_err(|e| SshError::CommandExecution(format!("Writer task failed: {}", e)))??;
+
+        if result.exit_code != 0 {
+            return Err(SshError::CommandExecution(format!(
+                "Failed to write {}: {}",
+                self.path,
+                result.stderr_string()
+            )));
+        }
+        Ok(self.written)
+    }
+}
+
+impl Drop for RemoteFileWriter {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl std::fmt::Debug for RemoteFileWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RemoteFileWriter")
+            .field("path", &self.path)
+            .field("written", &self.written)
+            .finish()
+    }
+}
+
 /// Escape shell special characters
-fn shell_escape(s: &str) -> String {
+pub(crate) fn shell_escape(s: &str) -> String {
     format!("'{}'", s.replace('\'', "'\\''"))
 }
 
+/// Decode the output of `base64`, which may be wrapped over several lines
+fn decode_base64_output(output: &[u8]) -> Result<Vec<u8>, SshError> {
+    let encoded: Vec<u8> = output
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    base64::Engine::decode(&base64::engine::general_purpose::STANDARD, encoded)
+        .map_err(|e| SshError::CommandExecution(format!("Failed to decode file contents: {}", e)))
+}
+
 /// Parse ls -la output into file entries
 fn parse_ls_output(output: &str, base_path: &str) -> Result<Vec<RemoteFileEntry>, SshError> {
     let mut entries = Vec::new();
@@ -282,3 +519,29 @@ fn get_parent_path(path: &str) -> String {
         None => "/".to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrapped_base64_output_round_trips_binary_data() {
+        // Invalid UTF-8 that a lossy text conversion would replace
+        let data: Vec<u8> = (0..=255u8)
+            .chain([0xff, 0xfe, 0xc3, 0x28])
+            .cycle()
+            .take(300)
+            .collect();
+        let encoded = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &data);
+        // GNU base64 wraps its output every 76 characters
+        let wrapped: Vec<u8> = encoded
+            .as_bytes()
+            .chunks(76)
+            .flat_map(|line| line.iter().copied().chain([b'\n']))
+            .collect();
+
+        assert_eq!(decode_base64_output(&wrapped).unwrap(), data);
+        assert!(decode_base64_output(b"").unwrap().is_empty());
+        assert!(decode_base64_output(b"not base64!").is_err());
+    }
+}
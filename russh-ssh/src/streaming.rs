@@ -1,18 +1,46 @@
 //! Stream Handler Implementation
 //!
-//! Provides adaptive buffering, seeking, and stream resumption for media streaming.
+//! Provides adaptive buffering, seeking, and stream resumption for media streaming,
+//! segment fetching for HLS and DASH sources, and watch- or listen-together
+//! rooms synchronized over P2P, with hosts serving their local media to
+//! room members in chunks. Each session records playback quality metrics,
+//! and rooms can be recorded into VDFS. A room can also broadcast the host's
+//! terminal to read-only viewers.
 //!
 //! # Requirements Coverage
 //! - Requirement 6.1: Seeking support
 //! - Requirement 6.2: Adaptive buffering
 //! - Requirement 6.5: Stream resumption
 
+pub mod access;
+pub mod audio;
 pub mod buffer;
 pub mod handler;
+pub mod live;
+pub mod manifest;
+pub mod metrics;
+pub mod quality;
+pub mod record;
+pub mod room;
+pub mod share;
+pub mod terminal;
 pub mod video;
 
+pub use access::{JoinCredentials, JoinRequest, RoomAccess, RoomInvite, ROOM_INVITE_PREFIX};
+pub use audio::{MediaKind, Track, TrackMetadata};
 pub use buffer::{AdaptiveBuffer, BufferConfig};
 pub use handler::{StreamHandler, StreamPosition, StreamState};
+pub use live::{Segment, SegmentStream};
+pub use manifest::{Manifest, ManifestKind, MediaPlaylist, MediaSegment, Variant};
+pub use metrics::{MetricsRecorder, StreamMetrics};
+pub use quality::{QualityConfig, QualityMode, QualitySelector, Rendition};
+pub use record::{Recording, RoomRecorder, TimelineEntry, TimelineEvent};
+pub use room::{RoomLink, RoomServer, APPROVAL_TIMEOUT, STREAM_ROOM_SERVICE};
+pub use share::{MediaReader, MediaServer, MEDIA_SERVICE};
+pub use terminal::{
+    TerminalBroadcast, TerminalFrame, TerminalServer, TerminalViewer, TERMINAL_SERVICE,
+};
 pub use video::{
-    HttpVideoStream, PlaybackState, StreamRoom, StreamSession, StreamSource, SyncEvent,
+    parse_share_link, HttpVideoStream, PlaybackState, StreamRoom, StreamSession, StreamSource,
+    SyncEvent, SHARE_LINK_PREFIX,
 };
@@ -0,0 +1,464 @@
+//! Room Access Control
+//!
+//! Decides who may join a hosted room.
+//!
+//! Each room's [`RoomAccess`] holds a random secret known only to the host.
+//! Invites are signed with it (keyed BLAKE3), name the room and the host,
+//! expire, and can be limited to a single use. A room can additionally
+//! require a password, accept invite holders only, or hold every other
+//! join request until the host approves it. Invite holders are admitted
+//! without a password or approval.
+
+use crate::error::StreamError;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use zeroize::Zeroize;
+
+/// Prefix of encoded room invites (includes the format version)
+pub const ROOM_INVITE_PREFIX: &str = "russhinv1";
+
+/// Size of an invite's signature
+const INVITE_MAC_SIZE: usize = 32;
+
+/// Invite flag: the invite is accepted once
+const FLAG_SINGLE_USE: u8 = 0x01;
+
+/// Domain separation for password hashes
+const PASSWORD_CONTEXT: &[u8] = b"russh-room-password";
+
+/// A signed invitation to a room
+#[derive(Clone, PartialEq, Eq)]
+pub struct RoomInvite {
+    room_id: String,
+    host_id: String,
+    /// Random identifier, remembered once a single-use invite is redeemed
+    token_id: [u8; 16],
+    expires_at: DateTime<Utc>,
+    single_use: bool,
+    mac: [u8; INVITE_MAC_SIZE],
+}
+
+impl RoomInvite {
+    /// Room the invite admits to
+    pub fn room_id(&self) -> &str {
+        &self.room_id
+    }
+
+    /// Node hosting the room
+    pub fn host_id(&self) -> &str {
+        &self.host_id
+    }
+
+    /// When the invite expires
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.expires_at
+    }
+
+    /// Whether the invite has expired
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.expires_at
+    }
+
+    /// Whether the invite is accepted only once
+    pub fn is_single_use(&self) -> bool {
+        self.single_use
+    }
+
+    /// Everything the signature covers
+    fn signed_bytes(&self) -> Vec<u8> {
+        let room = self.room_id.as_bytes();
+        let mut bytes = Vec::with_capacity(27 + room.len() + self.host_id.len());
+        bytes.extend_from_slice(&self.token_id);
+        bytes.extend_from_slice(&self.expires_at.timestamp().to_be_bytes());
+        bytes.push(if self.single_use { FLAG_SINGLE_USE } else { 0 });
+        // Room IDs are UUIDs; longer ones cannot be encoded
+        bytes.extend_from_slice(&(room.len().min(u16::MAX as usize) as u16).to_be_bytes());
+        bytes.extend_from_slice(room);
+        bytes.extend_from_slice(self.host_id.as_bytes());
+        bytes
+    }
+}
+
+impl fmt::Debug for RoomInvite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RoomInvite")
+            .field("room_id", &self.room_id)
+            .field("host_id", &self.host_id)
+            .field("expires_at", &self.expires_at)
+            .field("single_use", &self.single_use)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for RoomInvite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bytes = self.signed_bytes();
+        bytes.extend_from_slice(&self.mac);
+        write!(
+            f,
+            "{}{}",
+            ROOM_INVITE_PREFIX,
+            URL_SAFE_NO_PAD.encode(&bytes)
+        )
+    }
+}
+
+impl FromStr for RoomInvite {
+    type Err = StreamError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            |reason: &str| StreamError::AccessDenied(format!("invalid invite: {}", reason));
+        let encoded = s
+            .trim()
+            .strip_prefix(ROOM_INVITE_PREFIX)
+            .ok_or_else(|| invalid("not a room invite"))?;
+        let bytes = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|_| invalid("bad encoding"))?;
+        if bytes.len() < 27 + INVITE_MAC_SIZE {
+            return Err(invalid("truncated"));
+        }
+
+        let (body, mac_bytes) = bytes.split_at(bytes.len() - INVITE_MAC_SIZE);
+        let mut token_id = [0u8; 16];
+        token_id.copy_from_slice(&body[..16]);
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(&body[16..24]);
+        let flags = body[24];
+        let room_len = u16::from_be_bytes([body[25], body[26]]) as usize;
+        let rest = &body[27..];
+        if rest.len() < room_len {
+            return Err(invalid("truncated"));
+        }
+        let (room, host) = rest.split_at(room_len);
+        let text = |bytes: &[u8]| {
+            String::from_utf8(bytes.to_vec()).map_err(|_| invalid("identifier is not UTF-8"))
+        };
+        let mut mac = [0u8; INVITE_MAC_SIZE];
+        mac.copy_from_slice(mac_bytes);
+
+        Ok(Self {
+            room_id: text(room)?,
+            host_id: text(host)?,
+            token_id,
+            expires_at: DateTime::from_timestamp(i64::from_be_bytes(timestamp), 0)
+                .ok_or_else(|| invalid("bad expiry"))?,
+            single_use: flags & FLAG_SINGLE_USE != 0,
+            mac,
+        })
+    }
+}
+
+/// What a guest presents when joining
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct JoinCredentials {
+    /// Invite in its encoded form
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite: Option<String>,
+    /// Room password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+}
+
+impl JoinCredentials {
+    /// Join with an invite
+    pub fn with_invite(mut self, invite: &RoomInvite) -> Self {
+        self.invite = Some(invite.to_string());
+        self
+    }
+
+    /// Join with the room password
+    pub fn with_password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+}
+
+/// Outcome of checking a join request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Admission {
+    /// Let the guest in
+    Admit,
+    /// Ask the host first
+    NeedsApproval,
+    /// Turn the guest away
+    Deny(String),
+}
+
+/// Who may join a room
+pub struct RoomAccess {
+    secret: [u8; 32],
+    password: Option<blake3::Hash>,
+    invite_only: bool,
+    approval: bool,
+    /// Redeemed single-use invites
+    redeemed: HashSet<[u8; 16]>,
+    /// Peers admitted without credentials
+    members: HashSet<String>,
+}
+
+impl RoomAccess {
+    /// Admit anyone who knows the room ID
+    pub fn open() -> Self {
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        Self {
+            secret,
+            password: None,
+            invite_only: false,
+            approval: false,
+            redeemed: HashSet::new(),
+            members: HashSet::new(),
+        }
+    }
+
+    /// Require a password from guests without an invite
+    pub fn with_password(mut self, password: &str) -> Self {
+        self.password = Some(self.password_hash(password));
+        self
+    }
+
+    /// Admit invite holders only
+    pub fn invite_only(mut self) -> Self {
+        self.invite_only = true;
+        self
+    }
+
+    /// Hold guests without an invite until the host approves them
+    pub fn with_approval(mut self) -> Self {
+        self.approval = true;
+        self
+    }
+
+    /// Admit these peers without credentials or approval
+    ///
+    /// Used when taking over a room, so its members can follow the new host.
+    pub fn with_members<I, S>(mut self, peers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.members.extend(peers.into_iter().map(Into::into));
+        self
+    }
+
+    /// Check whether a peer is admitted without credentials
+    pub(crate) fn is_member(&self, peer_id: &str) -> bool {
+        self.members.contains(peer_id)
+    }
+
+    /// Whether guests without an invite wait for approval
+    pub fn requires_approval(&self) -> bool {
+        self.approval
+    }
+
+    /// Sign an invite to `room_id` hosted by `host_id`
+    pub fn create_invite(
+        &self,
+        room_id: &str,
+        host_id: &str,
+        ttl: Duration,
+        single_use: bool,
+    ) -> RoomInvite {
+        let mut token_id = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut token_id);
+        let expires_at = chrono::Duration::from_std(ttl)
+            .ok()
+            .and_then(|ttl| Utc::now().checked_add_signed(ttl))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        // Invites carry whole seconds
+        let expires_at = DateTime::from_timestamp(expires_at.timestamp(), 0).unwrap_or(expires_at);
+        let mut invite = RoomInvite {
+            room_id: room_id.to_string(),
+            host_id: host_id.to_string(),
+            token_id,
+            expires_at,
+            single_use,
+            mac: [0u8; INVITE_MAC_SIZE],
+        };
+        invite.mac = self.sign(&invite);
+        invite
+    }
+
+    /// Decide on a request to join `room_id`
+    ///
+    /// Redeems single-use invites.
+    pub(crate) fn check(&mut self, room_id: &str, credentials: &JoinCredentials) -> Admission {
+        if let Some(invite) = &credentials.invite {
+            return match self.redeem(room_id, invite) {
+                Ok(()) => Admission::Admit,
+                Err(reason) => Admission::Deny(reason),
+            };
+        }
+        if self.invite_only {
+            return Admission::Deny("an invite is required".to_string());
+        }
+        if let Some(expected) = &self.password {
+            let given = credentials
+                .password
+                .as_deref()
+                .map(|p| self.password_hash(p));
+            if given.as_ref() != Some(expected) {
+                return Admission::Deny("wrong password".to_string());
+            }
+        }
+        if self.approval {
+            Admission::NeedsApproval
+        } else {
+            Admission::Admit
+        }
+    }
+
+    fn redeem(&mut self, room_id: &str, invite: &str) -> Result<(), String> {
+        let invite: RoomInvite = invite.parse().map_err(|e: StreamError| e.to_string())?;
+        if blake3::Hash::from(self.sign(&invite)) != blake3::Hash::from(invite.mac) {
+            return Err("invite signature is not valid".to_string());
+        }
+        if invite.room_id != room_id {
+            return Err("invite is for another room".to_string());
+        }
+        if invite.is_expired() {
+            return Err("invite has expired".to_string());
+        }
+        if invite.single_use && !self.redeemed.insert(invite.token_id) {
+            return Err("invite has already been used".to_string());
+        }
+        Ok(())
+    }
+
+    fn sign(&self, invite: &RoomInvite) -> [u8; INVITE_MAC_SIZE] {
+        *blake3::keyed_hash(&self.secret, &invite.signed_bytes()).as_bytes()
+    }
+
+    fn password_hash(&self, password: &str) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new_keyed(&self.secret);
+        hasher.update(PASSWORD_CONTEXT);
+        hasher.update(password.as_bytes());
+        hasher.finalize()
+    }
+}
+
+impl Default for RoomAccess {
+    fn default() -> Self {
+        Self::open()
+    }
+}
+
+impl Drop for RoomAccess {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+impl fmt::Debug for RoomAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RoomAccess")
+            .field("password", &self.password.is_some())
+            .field("invite_only", &self.invite_only)
+            .field("approval", &self.approval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A guest waiting for the host's approval
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JoinRequest {
+    /// Room the guest wants to join
+    pub room_id: String,
+    /// Guest's node ID
+    pub peer_id: String,
+    /// When the request arrived
+    pub requested_at: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn with_invite(invite: &RoomInvite) -> JoinCredentials {
+        JoinCredentials::default().with_invite(invite)
+    }
+
+    #[test]
+    fn invites_are_signed_scoped_and_single_use() {
+        let mut access = RoomAccess::open().invite_only();
+        let invite = access.create_invite("room", "host-node", HOUR, true);
+        let parsed: RoomInvite = invite.to_string().parse().unwrap();
+        assert_eq!(parsed, invite);
+        assert_eq!(parsed.host_id(), "host-node");
+
+        assert_eq!(
+            access.check("other", &with_invite(&invite)),
+            Admission::Deny("invite is for another room".to_string())
+        );
+        assert_eq!(
+            access.check("room", &with_invite(&invite)),
+            Admission::Admit
+        );
+        assert!(matches!(
+            access.check("room", &with_invite(&invite)),
+            Admission::Deny(_)
+        ));
+
+        // Signed by another room's secret
+        let forged = RoomAccess::open().create_invite("room", "host-node", HOUR, false);
+        assert!(matches!(
+            access.check("room", &with_invite(&forged)),
+            Admission::Deny(_)
+        ));
+
+        let expired = access.create_invite("room", "host-node", Duration::ZERO, false);
+        assert_eq!(
+            access.check("room", &with_invite(&expired)),
+            Admission::Deny("invite has expired".to_string())
+        );
+        assert!(matches!(
+            access.check("room", &JoinCredentials::default()),
+            Admission::Deny(_)
+        ));
+        assert!("russhinv1AAAA".parse::<RoomInvite>().is_err());
+    }
+
+    #[test]
+    fn password_and_approval() {
+        let mut access = RoomAccess::open().with_password("popcorn").with_approval();
+        assert!(matches!(
+            access.check("room", &JoinCredentials::default()),
+            Admission::Deny(_)
+        ));
+        assert!(matches!(
+            access.check("room", &JoinCredentials::default().with_password("nachos")),
+            Admission::Deny(_)
+        ));
+        assert_eq!(
+            access.check("room", &JoinCredentials::default().with_password("popcorn")),
+            Admission::NeedsApproval
+        );
+
+        // Invite holders skip both
+        let invite = access.create_invite("room", "host", HOUR, false);
+        assert_eq!(
+            access.check("room", &with_invite(&invite)),
+            Admission::Admit
+        );
+        assert_eq!(
+            access.check("room", &with_invite(&invite)),
+            Admission::Admit
+        );
+
+        let mut open = RoomAccess::default();
+        assert_eq!(
+            open.check("room", &JoinCredentials::default()),
+            Admission::Admit
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Audio Streaming Module
+//!
+//! Lets rooms play music with the same sync machinery as video.
+//!
+//! An audio room's source is a [`StreamSource::Audio`] queue of [`Track`]s,
+//! each an ordinary URL, local or P2P source with its [`TrackMetadata`].
+//! Track changes are scheduled rather than applied on arrival: the host
+//! announces the next track together with the wall-clock time the current
+//! one ends, so every member can preload it and switch at the same instant
+//! without a gap.
+
+use super::video::{PlaybackState, StreamSource};
+use serde::{Deserialize, Serialize};
+
+/// File extensions treated as audio
+const AUDIO_EXTENSIONS: &[&str] = &[
+    "aac", "aiff", "alac", "flac", "m4a", "mp3", "oga", "ogg", "opus", "wav", "wma",
+];
+
+/// Kind of media a source carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    /// Video, possibly with sound
+    Video,
+    /// Sound only
+    Audio,
+    /// Terminal output
+    Terminal,
+}
+
+impl MediaKind {
+    /// Guess the kind from a file name or URL
+    pub fn from_path(path: &str) -> Self {
+        let path = path.split(['?', '#']).next().unwrap_or(path);
+        let name = path.rsplit('/').next().unwrap_or(path);
+        match name.rsplit_once('.') {
+            Some((_, ext)) if AUDIO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()) => {
+                Self::Audio
+            }
+            _ => Self::Video,
+        }
+    }
+}
+
+/// Descriptive information about a track
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackMetadata {
+    /// Track title
+    pub title: String,
+    /// Performing artist
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    /// Album the track belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    /// Length in seconds; needed to schedule gapless transitions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    /// Cover art location
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artwork_url: Option<String>,
+}
+
+impl TrackMetadata {
+    /// Create metadata with only a title
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Set the artist
+    pub fn with_artist(mut self, artist: impl Into<String>) -> Self {
+        self.artist = Some(artist.into());
+        self
+    }
+
+    /// Set the album
+    pub fn with_album(mut self, album: impl Into<String>) -> Self {
+        self.album = Some(album.into());
+        self
+    }
+
+    /// Set the length in seconds
+    pub fn with_duration(mut self, duration: f64) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Set the cover art location
+    pub fn with_artwork_url(mut self, url: impl Into<String>) -> Self {
+        self.artwork_url = Some(url.into());
+        self
+    }
+}
+
+/// One entry of an audio queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Track {
+    /// Where the audio is played from
+    pub source: StreamSource,
+    /// What is playing
+    pub metadata: TrackMetadata,
+}
+
+impl Track {
+    /// Create a track
+    pub fn new(source: StreamSource, metadata: TrackMetadata) -> Self {
+        Self { source, metadata }
+    }
+}
+
+impl StreamSource {
+    /// Kind of media this source plays
+    pub fn kind(&self) -> MediaKind {
+        match self {
+            Self::Url { url } => MediaKind::from_path(url),
+            Self::LocalFile { path, .. } => MediaKind::from_path(path),
+            Self::P2PFile { file_id, .. } => MediaKind::from_path(file_id),
+            Self::Audio { .. } => MediaKind::Audio,
+            Self::Hls { .. } | Self::Dash { .. } => MediaKind::Video,
+            Self::Terminal { .. } => MediaKind::Terminal,
+            Self::Renditions { renditions } => renditions
+                .first()
+                .map_or(MediaKind::Video, |rendition| rendition.source.kind()),
+        }
+    }
+
+    /// Track currently playing from an audio queue
+    pub fn current_track(&self) -> Option<&Track> {
+        match self {
+            Self::Audio { tracks, current } => tracks.get(*current),
+            _ => None,
+        }
+    }
+
+    /// Track after the current one, for preloading
+    pub fn next_track(&self) -> Option<&Track> {
+        match self {
+            Self::Audio { tracks, current } => tracks.get(current + 1),
+            _ => None,
+        }
+    }
+
+    /// Make `index` the current track; `false` if there is no such track
+    pub(crate) fn select_track(&mut self, index: usize) -> bool {
+        match self {
+            Self::Audio { tracks, current } if index < tracks.len() => {
+                *current = index;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl PlaybackState {
+    /// When a track of `duration` seconds finishes, in Unix ms
+    ///
+    /// `None` while paused, since the end is not known yet.
+    pub fn finishes_at(&self, duration: f64) -> Option<i64> {
+        if !self.playing || self.speed <= 0.0 {
+            return None;
+        }
+        let remaining_ms = (duration - self.position).max(0.0) / self.speed * 1000.0;
+        Some(self.sync_time + remaining_ms as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaming::video::{StreamSession, SyncEvent};
+
+    fn song(title: &str, duration: f64) -> Track {
+        Track::new(
+            StreamSource::Url {
+                url: format!("https://example.com/{}.flac", title),
+            },
+            TrackMetadata::new(title).with_duration(duration),
+        )
+    }
+
+    #[test]
+    fn media_kind_from_extension() {
+        assert_eq!(MediaKind::from_path("/music/Song.FLAC"), MediaKind::Audio);
+        assert_eq!(
+            MediaKind::from_path("https://example.com/a.mp3?token=x.mp4"),
+            MediaKind::Audio
+        );
+        assert_eq!(MediaKind::from_path("movie.mkv"), MediaKind::Video);
+        assert_eq!(
+            MediaKind::from_path("https://example.com/live"),
+            MediaKind::Video
+        );
+
+        let queue = StreamSource::Audio {
+            tracks: vec![song("one", 180.0), song("two", 200.0)],
+            current: 0,
+        };
+        assert_eq!(queue.kind(), MediaKind::Audio);
+        assert_eq!(queue.next_track().unwrap().metadata.title, "two");
+
+        let json = serde_json::to_string(&queue).unwrap();
+        let restored: StreamSource = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            restored.current_track().unwrap().metadata,
+            song("one", 180.0).metadata
+        );
+    }
+
+    #[tokio::test]
+    async fn next_track_starts_when_the_current_one_ends() {
+        let queue = StreamSource::Audio {
+            tracks: vec![song("one", 180.0), song("two", 200.0)],
+            current: 0,
+        };
+        let session = StreamSession::create_room("Party".to_string(), queue, "host".to_string());
+        let mut events = session.subscribe();
+
+        let started = chrono::Utc::now().timestamp_millis();
+        session
+            .handle_event(SyncEvent::StateSync {
+                state: PlaybackState {
+                    playing: true,
+                    position: 170.0,
+                    speed: 1.0,
+                    sync_time: started,
+                },
+            })
+            .await
+            .unwrap();
+        let _ = events.recv().await.unwrap();
+
+        assert!(session.advance_track().await.unwrap());
+        match events.recv().await.unwrap() {
+            SyncEvent::TrackChanged { index, start_time } => {
+                assert_eq!(index, 1);
+                assert_eq!(start_time, started + 10_000);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        let state = session.playback_state().await;
+        assert!(state.playing);
+        assert_eq!(state.position, 0.0);
+        assert!(session.expected_position().await < 0.0);
+        assert_eq!(
+            session
+                .room()
+                .await
+                .source
+                .current_track()
+                .unwrap()
+                .metadata
+                .title,
+            "two"
+        );
+
+        // End of the queue
+        assert!(!session.advance_track().await.unwrap());
+    }
+}
@@ -3,13 +3,39 @@
 //! Implements adaptive buffering for media streaming with configurable
 //! buffer sizes and duration tracking.
 //!
+//! Given the media bitrate, the buffer also follows the room's playback:
+//! [`follow_playback`](AdaptiveBuffer::follow_playback) and
+//! [`handle_sync_event`](AdaptiveBuffer::handle_sync_event) move the
+//! playhead, drop data far behind it and return the next byte range to
+//! fetch. The prefetch window covers `target_duration` of media and grows
+//! when the measured throughput is lower than the bitrate.
+//!
+//! A buffer given a [`MetricsRecorder`] reports its level and counts a
+//! rebuffer whenever a read finds no data before the end of the stream.
+//!
+//! Data is held as [`Bytes`] segments, so nothing is copied on the way
+//! through: added chunks are kept as they are, and reads and
+//! [`view`](AdaptiveBuffer::view)s hand out slices of them. Finding the
+//! segment for a position and evicting old ones only walk the segments
+//! involved, which keeps high-bitrate streams with many small chunks cheap
+//! (see `benches/adaptive_buffer.rs`).
+//!
 //! # Requirements Coverage
 //! - Requirement 6.2: Adaptive buffering
 
+use super::metrics::MetricsRecorder;
+use super::video::{PlaybackState, SyncEvent};
+use bytes::Bytes;
 use std::collections::BTreeMap;
 use std::ops::Range;
 use std::time::Duration;
 
+/// Weight of the newest sample in the throughput estimate
+const THROUGHPUT_SMOOTHING: f64 = 0.3;
+
+/// Largest factor the prefetch window grows by on a slow link
+const MAX_PREFETCH_SCALE: f64 = 4.0;
+
 /// Buffer configuration
 #[derive(Debug, Clone)]
 pub struct BufferConfig {
@@ -23,6 +49,8 @@ pub struct BufferConfig {
     pub low_watermark: usize,
     /// High watermark - stop buffering when above this
     pub high_watermark: usize,
+    /// Bytes kept behind the playhead for short rewinds
+    pub back_buffer: usize,
 }
 
 impl Default for BufferConfig {
@@ -33,6 +61,7 @@ impl Default for BufferConfig {
             target_duration: Duration::from_secs(10),
             low_watermark: 256 * 1024,       // 256 KB
             high_watermark: 8 * 1024 * 1024, // 8 MB
+            back_buffer: 2 * 1024 * 1024,    // 2 MB
         }
     }
 }
@@ -59,6 +88,12 @@ impl BufferConfig {
         self.high_watermark = high;
         self
     }
+
+    /// Set how much data is kept behind the playhead
+    pub fn with_back_buffer(mut self, bytes: usize) -> Self {
+        self.back_buffer = bytes;
+        self
+    }
 }
 
 /// A buffered range of data
@@ -67,7 +102,7 @@ struct BufferedRange {
     /// Start position in the stream
     start: u64,
     /// The buffered data
-    data: Vec<u8>,
+    data: Bytes,
 }
 
 impl BufferedRange {
@@ -104,6 +139,14 @@ pub struct AdaptiveBuffer {
     bytes_consumed: usize,
     /// Current adaptive buffer target
     adaptive_target: usize,
+    /// Media bitrate in bits per second (if known)
+    bitrate: Option<u64>,
+    /// Smoothed download rate in bytes per second
+    throughput: Option<f64>,
+    /// Byte offset of what is playing now
+    playhead: u64,
+    /// Where buffer level and rebuffers are reported
+    metrics: Option<MetricsRecorder>,
 }
 
 impl AdaptiveBuffer {
@@ -118,9 +161,25 @@ impl AdaptiveBuffer {
             read_position: 0,
             bytes_consumed: 0,
             adaptive_target,
+            bitrate: None,
+            throughput: None,
+            playhead: 0,
+            metrics: None,
         }
     }
 
+    /// Create with known media bitrate (bits per second)
+    pub fn with_bitrate(mut self, bitrate: u64) -> Self {
+        self.bitrate = Some(bitrate);
+        self
+    }
+
+    /// Report buffer level and rebuffers to a session's metrics
+    pub fn with_metrics(mut self, metrics: MetricsRecorder) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Create with known stream size
     pub fn with_stream_size(mut self, size: u64) -> Self {
         self.stream_size = Some(size);
@@ -159,14 +218,22 @@ impl AdaptiveBuffer {
 
     /// Add data to the buffer at a specific position
     ///
+    /// The data is kept without copying it, whether it comes as a `Vec<u8>`
+    /// or as [`Bytes`] received from the network. Data already buffered at
+    /// the same position is replaced.
+    ///
     /// Memory safety: Evicts old data BEFORE adding new data to prevent memory spikes.
     /// The buffer will never exceed `max_buffer_size` even temporarily.
-    pub fn add_data(&mut self, position: u64, data: Vec<u8>) {
+    pub fn add_data(&mut self, position: u64, data: impl Into<Bytes>) {
+        let mut data = data.into();
         if data.is_empty() {
             return;
         }
 
         let data_len = data.len();
+        if let Some(replaced) = self.ranges.remove(&position) {
+            self.total_buffered -= replaced.data.len();
+        }
 
         // CRITICAL: Evict old data BEFORE adding new data to prevent memory spikes
         // This ensures we never exceed max_buffer_size even temporarily
@@ -175,16 +242,14 @@ impl AdaptiveBuffer {
         }
 
         // If data is larger than max buffer size, truncate it
-        let data = if data_len > self.config.max_buffer_size {
+        if data_len > self.config.max_buffer_size {
             tracing::warn!(
                 "Data chunk ({} bytes) exceeds max buffer size ({} bytes), truncating",
                 data_len,
                 self.config.max_buffer_size
             );
-            data[..self.config.max_buffer_size].to_vec()
-        } else {
-            data
-        };
+            data.truncate(self.config.max_buffer_size);
+        }
         let actual_len = data.len();
 
         // Check if this overlaps with existing ranges
@@ -197,55 +262,79 @@ impl AdaptiveBuffer {
 
         self.ranges.insert(position, range);
         self.total_buffered += actual_len;
+        self.report_level();
     }
 
     /// Evict data to make room for new data
+    ///
+    /// Ranges the reader has moved past go first, then the oldest ones. A
+    /// single walk over the ranges before the read position finds all the
+    /// consumed ones needed.
     fn evict_to_make_room(&mut self, needed_space: usize) {
         let target_size = self.config.max_buffer_size.saturating_sub(needed_space);
+        let read_position = self.read_position;
 
-        while self.total_buffered > target_size {
-            // Remove the oldest range that's before the read position first
-            let to_remove = self
-                .ranges
-                .iter()
-                .filter(|(_, r)| r.end() <= self.read_position)
-                .map(|(k, r)| (*k, r.data.len()))
-                .next();
-
-            if let Some((key, size)) = to_remove {
-                self.ranges.remove(&key);
-                self.total_buffered -= size;
-            } else {
-                // No old ranges to remove, remove the oldest anyway
-                if let Some((&key, range)) = self.ranges.iter().next() {
-                    let size = range.data.len();
-                    self.ranges.remove(&key);
-                    self.total_buffered -= size;
-                } else {
-                    break;
-                }
+        let mut excess = self.total_buffered.saturating_sub(target_size);
+        let mut consumed = Vec::new();
+        for (start, range) in self.ranges.range(..read_position) {
+            if excess == 0 {
+                break;
+            }
+            if range.end() <= read_position {
+                excess = excess.saturating_sub(range.data.len());
+                consumed.push(*start);
+            }
+        }
+        for start in consumed {
+            if let Some(range) = self.ranges.remove(&start) {
+                self.total_buffered -= range.data.len();
             }
         }
+
+        // No old ranges left to remove, remove the oldest anyway
+        while self.total_buffered > target_size {
+            let Some((_, range)) = self.ranges.pop_first() else {
+                break;
+            };
+            self.total_buffered -= range.data.len();
+        }
+    }
+
+    /// The range holding `pos`
+    ///
+    /// Ranges may overlap, so this walks back from the last one starting at
+    /// or before `pos`; usually that one holds it.
+    fn range_at(&self, pos: u64) -> Option<&BufferedRange> {
+        self.ranges
+            .range(..=pos)
+            .rev()
+            .map(|(_, range)| range)
+            .find(|range| range.contains(pos))
     }
 
     /// Read data from the buffer
     ///
     /// Returns the data if available, or None if the position is not buffered.
-    pub fn read(&mut self, len: usize) -> Option<Vec<u8>> {
+    /// The data is a slice of the buffered chunk, not a copy, and reads stop
+    /// at the end of that chunk.
+    pub fn read(&mut self, len: usize) -> Option<Bytes> {
         let pos = self.read_position;
 
         // Find the range containing this position
-        let range = self.ranges.iter().find(|(_, r)| r.contains(pos))?;
-
-        let range_start = *range.0;
-        let range_data = &range.1.data;
+        let Some(range) = self.range_at(pos) else {
+            let at_end = self.stream_size.is_some_and(|size| pos >= size);
+            if let (Some(metrics), false) = (&self.metrics, at_end) {
+                metrics.rebuffer_started();
+            }
+            return None;
+        };
 
         // Calculate offset within the range
-        let offset = (pos - range_start) as usize;
-        let available = range_data.len() - offset;
+        let offset = (pos - range.start) as usize;
+        let available = range.data.len() - offset;
         let to_read = len.min(available);
 
-        let data = range_data[offset..offset + to_read].to_vec();
+        let data = range.data.slice(offset..offset + to_read);
 
         // Update position
         self.read_position += to_read as u64;
@@ -254,6 +343,11 @@ impl AdaptiveBuffer {
         // Adapt buffer size based on consumption
         self.adapt_buffer_size();
 
+        if let Some(metrics) = &self.metrics {
+            metrics.rebuffer_ended();
+        }
+        self.report_level();
+
         Some(data)
     }
 
@@ -271,7 +365,25 @@ impl AdaptiveBuffer {
         self.read_position = position;
 
         // Check if position is buffered
-        self.ranges.iter().any(|(_, r)| r.contains(position))
+        self.is_buffered(position)
+    }
+
+    /// Buffered data for `range`, as slices of the chunks holding it
+    ///
+    /// Neither copies the data nor moves the read position. The slices keep
+    /// their data alive even once the buffer evicts it. Returns `None`
+    /// unless all of `range` is buffered.
+    pub fn view(&self, range: Range<u64>) -> Option<Vec<Bytes>> {
+        let mut slices = Vec::new();
+        let mut cursor = range.start;
+        while cursor < range.end {
+            let chunk = self.range_at(cursor)?;
+            let end = chunk.end().min(range.end);
+            let offset = (cursor - chunk.start) as usize;
+            slices.push(chunk.data.slice(offset..(end - chunk.start) as usize));
+            cursor = end;
+        }
+        Some(slices)
     }
 
     /// Get buffered ranges
@@ -281,7 +393,7 @@ impl AdaptiveBuffer {
 
     /// Check if a position is buffered
     pub fn is_buffered(&self, position: u64) -> bool {
-        self.ranges.iter().any(|(_, r)| r.contains(position))
+        self.range_at(position).is_some()
     }
 
     /// Clear the buffer
@@ -289,6 +401,163 @@ impl AdaptiveBuffer {
         self.ranges.clear();
         self.total_buffered = 0;
         self.read_position = 0;
+        self.playhead = 0;
+        self.report_level();
+    }
+
+    fn report_level(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record_buffer_level(self.total_buffered as u64);
+        }
+    }
+
+    /// Change the media bitrate, e.g. after switching rendition
+    pub fn set_bitrate(&mut self, bitrate: u64) {
+        self.bitrate = Some(bitrate);
+    }
+
+    /// Feed a completed download of `bytes` that took `elapsed`
+    pub fn record_throughput(&mut self, bytes: usize, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if secs <= 0.0 {
+            return;
+        }
+        let sample = bytes as f64 / secs;
+        self.throughput = Some(match self.throughput {
+            Some(estimate) => {
+                THROUGHPUT_SMOOTHING * sample + (1.0 - THROUGHPUT_SMOOTHING) * estimate
+            }
+            None => sample,
+        });
+    }
+
+    /// Estimated download rate in bytes per second
+    pub fn throughput(&self) -> Option<u64> {
+        self.throughput.map(|rate| rate as u64)
+    }
+
+    /// Get the byte offset of what is playing now
+    pub fn playhead(&self) -> u64 {
+        self.playhead
+    }
+
+    /// Byte offset of a playback position in seconds
+    ///
+    /// Needs the bitrate; assumes it is constant.
+    pub fn byte_offset(&self, seconds: f64) -> Option<u64> {
+        let bitrate = self.bitrate?;
+        let offset = (seconds.max(0.0) * bitrate as f64 / 8.0) as u64;
+        Some(self.stream_size.map_or(offset, |size| offset.min(size)))
+    }
+
+    /// Bytes to keep buffered ahead of the playhead
+    ///
+    /// `target_duration` of media at the current bitrate, scaled up when
+    /// downloads are slower than playback, and kept between
+    /// `min_buffer_size` and `high_watermark`. Falls back to the adaptive
+    /// target while the bitrate is unknown.
+    pub fn prefetch_window(&self) -> usize {
+        let Some(bitrate) = self.bitrate else {
+            return self.adaptive_target;
+        };
+        let play_rate = bitrate as f64 / 8.0;
+        let scale = match self.throughput {
+            Some(rate) if rate > 0.0 => (play_rate / rate).clamp(1.0, MAX_PREFETCH_SCALE),
+            Some(_) => MAX_PREFETCH_SCALE,
+            None => 1.0,
+        };
+        let window = play_rate * self.config.target_duration.as_secs_f64() * scale;
+        let ceiling = self.config.high_watermark.max(self.config.min_buffer_size);
+        (window as usize).clamp(self.config.min_buffer_size, ceiling)
+    }
+
+    /// Next byte range to fetch to fill the prefetch window
+    ///
+    /// `None` when everything up to the window's end is buffered.
+    pub fn prefetch_range(&self) -> Option<Range<u64>> {
+        let mut end = self.playhead.saturating_add(self.prefetch_window() as u64);
+        if let Some(size) = self.stream_size {
+            end = end.min(size);
+        }
+
+        let mut cursor = self.playhead;
+        for range in self.ranges.values() {
+            if range.end() <= cursor {
+                continue;
+            }
+            if range.start > cursor {
+                end = end.min(range.start);
+                break;
+            }
+            cursor = range.end();
+        }
+        (cursor < end).then_some(cursor..end)
+    }
+
+    /// Move the playhead to where `state` says playback is now
+    ///
+    /// Drops data far behind the playhead and returns the next range to
+    /// fetch. Does nothing but return the range while the bitrate is
+    /// unknown.
+    pub fn follow_playback(&mut self, state: &PlaybackState) -> Option<Range<u64>> {
+        let now = chrono::Utc::now().timestamp_millis();
+        if let Some(offset) = self.byte_offset(state.position_at(now)) {
+            self.move_playhead(offset);
+        }
+        self.prefetch_range()
+    }
+
+    /// React to a room event
+    ///
+    /// A seek (or a play or pause that jumps) moves the playhead and read
+    /// position straight to the new spot, so fetching starts there instead
+    /// of continuing where playback was. A new source or track starts
+    /// over. Returns the next range to fetch.
+    pub fn handle_sync_event(&mut self, event: &SyncEvent) -> Option<Range<u64>> {
+        match event {
+            SyncEvent::Seek { position }
+            | SyncEvent::Play { position }
+            | SyncEvent::Pause { position } => {
+                let offset = self.byte_offset(*position)?;
+                if !self.is_buffered(offset) {
+                    tracing::debug!(
+                        offset,
+                        "Playhead jumped outside the buffer, prefetching there"
+                    );
+                }
+                self.seek(offset);
+                self.move_playhead(offset);
+            }
+            SyncEvent::StateSync { state } => return self.follow_playback(state),
+            SyncEvent::SourceChanged { .. } | SyncEvent::TrackChanged { .. } => self.clear(),
+            _ => {}
+        }
+        self.prefetch_range()
+    }
+
+    fn move_playhead(&mut self, offset: u64) {
+        self.playhead = offset;
+        self.drop_behind_playhead();
+    }
+
+    /// Drop ranges that end more than `back_buffer` before the playhead
+    fn drop_behind_playhead(&mut self) {
+        let keep_from = self
+            .playhead
+            .saturating_sub(self.config.back_buffer as u64)
+            .min(self.read_position);
+        let stale: Vec<u64> = self
+            .ranges
+            .range(..keep_from)
+            .map(|(_, range)| range)
+            .filter(|range| range.end() < keep_from)
+            .map(|range| range.start)
+            .collect();
+        for start in stale {
+            if let Some(range) = self.ranges.remove(&start) {
+                self.total_buffered -= range.data.len();
+            }
+        }
     }
 
     /// Adapt buffer size based on consumption patterns
@@ -375,4 +644,117 @@ mod tests {
         assert!(!buffer.is_buffered(150));
         assert!(buffer.is_buffered(250));
     }
+
+    #[test]
+    fn views_share_the_buffered_data() {
+        let config = BufferConfig::new(16, 100);
+        let mut buffer = AdaptiveBuffer::new(config);
+        let chunk = Bytes::from(vec![7u8; 60]);
+        buffer.add_data(0, chunk.clone());
+        buffer.add_data(60, vec![8u8; 30]);
+
+        let read = buffer.read(10).unwrap();
+        assert_eq!(read.as_ptr(), chunk.as_ptr());
+
+        let view = buffer.view(50..70).unwrap();
+        assert_eq!(view.len(), 2);
+        assert_eq!(view[0].as_ptr(), chunk[50..].as_ptr());
+        assert_eq!(view[1], vec![8u8; 10]);
+        assert!(buffer.view(80..100).is_none());
+        assert_eq!(buffer.position(), 10);
+
+        // Replacing a chunk does not count it twice
+        buffer.add_data(60, vec![9u8; 30]);
+        assert_eq!(buffer.buffered_bytes(), 90);
+
+        // The consumed chunk goes first; the view still holds its data
+        buffer.seek(60);
+        buffer.add_data(90, vec![0u8; 40]);
+        assert!(!buffer.is_buffered(0));
+        assert!(buffer.is_buffered(60));
+        assert_eq!(buffer.buffered_bytes(), 70);
+        assert_eq!(view[0], vec![7u8; 10]);
+    }
+
+    #[test]
+    fn prefetch_window_follows_bitrate_and_throughput() {
+        // 1 Mbit/s for 10 s is 1.25 MB
+        let config = BufferConfig::new(1024, 16 * 1024 * 1024);
+        let mut buffer = AdaptiveBuffer::new(config).with_bitrate(1_000_000);
+        assert_eq!(buffer.prefetch_window(), 1_250_000);
+        assert_eq!(buffer.prefetch_range(), Some(0..1_250_000));
+
+        // A link at half the playback rate needs twice the lead
+        buffer.record_throughput(62_500, Duration::from_secs(1));
+        assert_eq!(buffer.throughput(), Some(62_500));
+        assert_eq!(buffer.prefetch_window(), 2_500_000);
+
+        // A fast link does not shrink it below the target duration
+        buffer.set_bitrate(8_000);
+        assert_eq!(buffer.prefetch_window(), 10_000);
+
+        buffer.add_data(0, vec![0; 4_000]);
+        buffer.add_data(6_000, vec![0; 1_000]);
+        assert_eq!(buffer.prefetch_range(), Some(4_000..6_000));
+        buffer.add_data(4_000, vec![0; 2_000]);
+        assert_eq!(buffer.prefetch_range(), Some(7_000..10_000));
+    }
+
+    #[test]
+    fn seek_jumps_and_drops_data_behind() {
+        let config = BufferConfig::new(1024, 16 * 1024 * 1024).with_back_buffer(10_000);
+        // 80 kbit/s is 10 kB per second of media
+        let mut buffer = AdaptiveBuffer::new(config)
+            .with_stream_size(1_000_000)
+            .with_bitrate(80_000);
+        buffer.add_data(0, vec![0; 20_000]);
+        buffer.add_data(20_000, vec![0; 20_000]);
+
+        let next = buffer.handle_sync_event(&SyncEvent::Seek { position: 60.0 });
+        assert_eq!(buffer.playhead(), 600_000);
+        assert_eq!(buffer.position(), 600_000);
+        assert_eq!(next, Some(600_000..700_000));
+        assert_eq!(buffer.buffered_bytes(), 0);
+
+        // Near the end the window stops at the stream size
+        buffer.add_data(600_000, vec![0; 50_000]);
+        let state = PlaybackState {
+            playing: false,
+            position: 95.0,
+            ..PlaybackState::default()
+        };
+        assert_eq!(buffer.follow_playback(&state), Some(950_000..1_000_000));
+        // Data the reader has not consumed yet is kept
+        assert!(buffer.is_buffered(640_000));
+
+        buffer.handle_sync_event(&SyncEvent::TrackChanged {
+            index: 1,
+            start_time: 0,
+        });
+        assert_eq!(buffer.buffered_bytes(), 0);
+        assert_eq!(buffer.playhead(), 0);
+    }
+
+    #[test]
+    fn reads_past_the_buffer_count_as_rebuffers() {
+        let metrics = MetricsRecorder::new();
+        let mut buffer = AdaptiveBuffer::new(BufferConfig::new(16, 1024))
+            .with_stream_size(20)
+            .with_metrics(metrics.clone());
+        buffer.add_data(0, vec![0; 10]);
+        assert_eq!(metrics.snapshot().buffer_level, 10);
+
+        assert!(buffer.read(10).is_some());
+        assert!(buffer.read(5).is_none());
+        assert!(buffer.read(5).is_none());
+        assert!(metrics.snapshot().rebuffering);
+
+        buffer.add_data(10, vec![0; 10]);
+        assert!(buffer.read(10).is_some());
+        // The end of the stream is not a stall
+        assert!(buffer.read(5).is_none());
+        let snapshot = metrics.snapshot();
+        assert!(!snapshot.rebuffering);
+        assert_eq!(snapshot.rebuffer_count, 1);
+    }
 }
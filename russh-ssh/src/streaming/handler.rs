@@ -8,6 +8,7 @@
 
 use super::buffer::{AdaptiveBuffer, BufferConfig};
 use crate::error::StreamError;
+use bytes::Bytes;
 use std::time::Instant;
 
 /// Stream position information
@@ -161,12 +162,12 @@ impl StreamHandler {
     }
 
     /// Add data to the buffer
-    pub fn add_data(&mut self, position: u64, data: Vec<u8>) {
+    pub fn add_data(&mut self, position: u64, data: impl Into<Bytes>) {
         self.buffer.add_data(position, data);
     }
 
     /// Read data from the stream
-    pub fn read(&mut self, len: usize) -> Result<Vec<u8>, StreamError> {
+    pub fn read(&mut self, len: usize) -> Result<Bytes, StreamError> {
         if !self.active {
             return Err(StreamError::NotFound(self.stream_id.clone()));
         }
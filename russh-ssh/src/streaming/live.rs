@@ -0,0 +1,456 @@
+//! Segment Fetching for HLS and DASH Sources
+//!
+//! A [`SegmentStream`] plays a [`StreamSource::Hls`] or
+//! [`StreamSource::Dash`] source segment by segment. It fetches the
+//! manifest, picks a variant, and hands out segments in order, starting
+//! with the initialization segment. Live streams start near the live edge;
+//! when the stream runs out of listed segments it reloads the manifest,
+//! waiting a target duration between reloads, and skips ahead if it fell
+//! out of the window the server still lists.
+
+use super::manifest::{Manifest, ManifestKind, MediaPlaylist, MediaSegment, Variant};
+use super::metrics::MetricsRecorder;
+use super::quality::Rendition;
+use super::video::StreamSource;
+use crate::error::StreamError;
+use reqwest::Url;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Shortest wait between two manifest reloads
+const MIN_RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Largest manifest that is read
+const MAX_MANIFEST_SIZE: usize = 8 * 1024 * 1024;
+
+/// Largest segment that is read
+///
+/// Ten seconds of an 8K stream, with room to spare.
+const MAX_SEGMENT_SIZE: usize = 128 * 1024 * 1024;
+
+/// A fetched segment
+#[derive(Debug, Clone)]
+pub struct Segment {
+    /// Sequence number; `None` for an initialization segment
+    pub sequence: Option<u64>,
+    /// Length in seconds
+    pub duration: f64,
+    /// Segment bytes
+    pub data: Vec<u8>,
+}
+
+/// Plays an HLS or DASH source segment by segment
+#[derive(Debug)]
+pub struct SegmentStream {
+    client: reqwest::Client,
+    source: StreamSource,
+    kind: ManifestKind,
+    manifest_url: Url,
+    /// Variants by ascending bandwidth; empty for a single media playlist
+    variants: Vec<Variant>,
+    current: usize,
+    playlist: MediaPlaylist,
+    /// Sequence number of the next segment to hand out
+    next_sequence: Option<u64>,
+    /// Whether the initialization segment goes out next
+    needs_init: bool,
+    last_reload: Instant,
+    /// The last reload listed no new segments
+    stale: bool,
+    metrics: Option<MetricsRecorder>,
+}
+
+impl SegmentStream {
+    /// Fetch the manifest of a segmented source
+    ///
+    /// Starts on the lowest-bandwidth variant.
+    pub async fn open(source: &StreamSource) -> Result<Self, StreamError> {
+        let (kind, url) = source
+            .manifest()
+            .ok_or_else(|| StreamError::Manifest("source is not HLS or DASH".to_string()))?;
+        let manifest_url: Url = url
+            .parse()
+            .map_err(|e| StreamError::Manifest(format!("bad manifest URL: {}", e)))?;
+        let client = reqwest::Client::new();
+
+        let mut stream = Self {
+            client,
+            source: source.clone(),
+            kind,
+            manifest_url,
+            variants: Vec::new(),
+            current: 0,
+            playlist: MediaPlaylist {
+                target_duration: 0.0,
+                init: None,
+                segments: Vec::new(),
+                live: false,
+            },
+            next_sequence: None,
+            needs_init: true,
+            last_reload: Instant::now(),
+            stale: false,
+            metrics: None,
+        };
+
+        let manifest = stream.fetch_manifest(&stream.manifest_url).await?;
+        match manifest {
+            Manifest::Master(mut variants) => {
+                variants.sort_by_key(|variant| variant.bandwidth);
+                stream.variants = variants;
+                stream.playlist = stream.load_playlist().await?;
+            }
+            Manifest::Media(playlist) => stream.playlist = playlist,
+            Manifest::Dash(mut representations) => {
+                representations.sort_by_key(|(variant, _)| variant.bandwidth);
+                let (variants, mut playlists): (Vec<_>, Vec<_>) =
+                    representations.into_iter().unzip();
+                stream.variants = variants;
+                stream.playlist = playlists.swap_remove(0);
+            }
+        }
+        stream.last_reload = Instant::now();
+        Ok(stream)
+    }
+
+    /// Count fetched segment bytes in a session's metrics, under the
+    /// manifest URL
+    pub fn with_metrics(mut self, metrics: MetricsRecorder) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Variants by ascending bandwidth
+    pub fn variants(&self) -> &[Variant] {
+        &self.variants
+    }
+
+    /// Variant being played, if the source offers several
+    pub fn current_variant(&self) -> Option<&Variant> {
+        self.variants.get(self.current)
+    }
+
+    /// The variants as renditions of this source
+    ///
+    /// Indices match [`variants`](Self::variants), so the choice of a
+    /// [`QualitySelector`](super::quality::QualitySelector) built from them
+    /// can be passed straight to [`select_variant`](Self::select_variant).
+    pub fn renditions(&self) -> Vec<Rendition> {
+        self.variants
+            .iter()
+            .map(|variant| Rendition::new(variant.label(), variant.bandwidth, self.source.clone()))
+            .collect()
+    }
+
+    /// Switch to another variant, continuing at the same segment
+    pub async fn select_variant(&mut self, index: usize) -> Result<(), StreamError> {
+        if index >= self.variants.len() {
+            return Err(StreamError::NotFound(format!(
+                "Variant {} not found",
+                index
+            )));
+        }
+        if index == self.current {
+            return Ok(());
+        }
+        let previous = self.current;
+        self.current = index;
+        match self.load_playlist().await {
+            Ok(playlist) => {
+                self.playlist = playlist;
+                self.last_reload = Instant::now();
+                self.needs_init = true;
+                Ok(())
+            }
+            Err(e) => {
+                self.current = previous;
+                Err(e)
+            }
+        }
+    }
+
+    /// Segments of the variant being played, as last loaded
+    pub fn playlist(&self) -> &MediaPlaylist {
+        &self.playlist
+    }
+
+    /// Check whether the source is a live stream
+    pub fn is_live(&self) -> bool {
+        self.playlist.live
+    }
+
+    /// Continue from `position` seconds into on-demand media
+    ///
+    /// Returns `false` for live streams and positions past the end.
+    pub fn seek(&mut self, position: f64) -> bool {
+        if self.playlist.live {
+            return false;
+        }
+        match self.playlist.segment_at(position) {
+            Some(index) => {
+                self.next_sequence = Some(self.playlist.segments[index].sequence);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Fetch the next segment
+    ///
+    /// Waits for new segments of a live stream. Returns `None` at the end
+    /// of on-demand media or when a live stream ends.
+    pub async fn next_segment(&mut self) -> Result<Option<Segment>, StreamError> {
+        if self.needs_init {
+            self.needs_init = false;
+            if let Some(init) = self.playlist.init.clone() {
+                let data = self.fetch_segment(&init).await?;
+                return Ok(Some(Segment {
+                    sequence: None,
+                    duration: 0.0,
+                    data,
+                }));
+            }
+        }
+
+        loop {
+            if let Some(segment) = self.upcoming() {
+                self.next_sequence = Some(segment.sequence + 1);
+                let data = self.fetch_segment(&segment).await?;
+                return Ok(Some(Segment {
+                    sequence: Some(segment.sequence),
+                    duration: segment.duration,
+                    data,
+                }));
+            }
+            if !self.playlist.live {
+                return Ok(None);
+            }
+            self.wait_and_reload().await?;
+        }
+    }
+
+    /// Segment to hand out next, if the playlist lists it yet
+    fn upcoming(&self) -> Option<MediaSegment> {
+        let segments = &self.playlist.segments;
+        let index = match (self.next_sequence, segments.first()) {
+            (None, _) => self.playlist.live_edge(),
+            (Some(sequence), Some(first)) if sequence < first.sequence => {
+                tracing::warn!(
+                    sequence,
+                    first = first.sequence,
+                    "Fell behind the live window, skipping to the live edge"
+                );
+                self.playlist.live_edge()
+            }
+            (Some(sequence), _) => self.playlist.index_of(sequence)?,
+        };
+        segments.get(index).cloned()
+    }
+
+    /// Reload a live manifest once the server had time to add segments
+    ///
+    /// Waits a target duration after a reload that brought new segments
+    /// and half of one after a reload that did not.
+    async fn wait_and_reload(&mut self) -> Result<(), StreamError> {
+        let target = Duration::from_secs_f64(self.playlist.target_duration.max(0.0));
+        let interval = if self.stale { target / 2 } else { target };
+        tokio::time::sleep_until(self.last_reload + interval.max(MIN_RELOAD_INTERVAL)).await;
+
+        let last = self
+            .playlist
+            .segments
+            .last()
+            .map(|segment| segment.sequence);
+        self.playlist = self.load_playlist().await?;
+        self.last_reload = Instant::now();
+        self.stale = self
+            .playlist
+            .segments
+            .last()
+            .map(|segment| segment.sequence)
+            == last;
+        Ok(())
+    }
+
+    /// Load the segments of the current variant
+    async fn load_playlist(&self) -> Result<MediaPlaylist, StreamError> {
+        let variant = self.variants.get(self.current);
+        let url = match (self.kind, variant) {
+            (ManifestKind::Hls, Some(variant)) => variant
+                .id
+                .parse()
+                .map_err(|e| StreamError::Manifest(format!("bad playlist URL: {}", e)))?,
+            _ => self.manifest_url.clone(),
+        };
+        match self.fetch_manifest(&url).await? {
+            Manifest::Media(playlist) => Ok(playlist),
+            Manifest::Dash(representations) => representations
+                .into_iter()
+                .find(|(rep, _)| variant.map_or(true, |variant| variant.id == rep.id))
+                .map(|(_, playlist)| playlist)
+                .ok_or_else(|| StreamError::Manifest("representation disappeared".to_string())),
+            Manifest::Master(_) => Err(StreamError::Manifest(
+                "expected a media playlist".to_string(),
+            )),
+        }
+    }
+
+    async fn fetch_manifest(&self, url: &Url) -> Result<Manifest, StreamError> {
+        let response = self
+            .client
+            .get(url.clone())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| StreamError::NotFound(format!("Failed to fetch {}: {}", url, e)))?;
+        let body = read_body(response, MAX_MANIFEST_SIZE, url.as_str()).await?;
+        let text = String::from_utf8_lossy(&body);
+        Manifest::parse(self.kind, &text, url, chrono::Utc::now())
+    }
+
+    async fn fetch_segment(&self, segment: &MediaSegment) -> Result<Vec<u8>, StreamError> {
+        let mut request = self.client.get(&segment.url);
+        if let Some(range) = &segment.byte_range {
+            request = request.header(
+                reqwest::header::RANGE,
+                format!("bytes={}-{}", range.start, range.end.saturating_sub(1)),
+            );
+        }
+        let response = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| {
+                StreamError::NotFound(format!("Failed to fetch {}: {}", segment.url, e))
+            })?;
+        let body = read_body(response, MAX_SEGMENT_SIZE, &segment.url).await?;
+        if let Some(metrics) = &self.metrics {
+            metrics.record_fetch(self.manifest_url.as_str(), body.len() as u64);
+        }
+        Ok(body)
+    }
+}
+
+/// Read a response body of at most `limit` bytes
+async fn read_body(
+    mut response: reqwest::Response,
+    limit: usize,
+    url: &str,
+) -> Result<Vec<u8>, StreamError> {
+    let too_large = || StreamError::NotFound(format!("{} is larger than {} bytes", url, limit));
+    let declared = response.content_length().unwrap_or(0);
+    if declared > limit as u64 {
+        return Err(too_large());
+    }
+    let mut body = Vec::with_capacity(declared as usize);
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| StreamError::NotFound(format!("Failed to read {}: {}", url, e)))?
+    {
+        if body.len() + chunk.len() > limit {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live_stream(first: u64, count: u64) -> SegmentStream {
+        let source = StreamSource::from_url("https://live.example.com/ch1.m3u8");
+        let segments = (first..first + count)
+            .map(|sequence| MediaSegment {
+                url: format!("https://live.example.com/{}.ts", sequence),
+                sequence,
+                duration: 2.0,
+                byte_range: None,
+            })
+            .collect();
+        SegmentStream {
+            client: reqwest::Client::new(),
+            source,
+            kind: ManifestKind::Hls,
+            manifest_url: "https://live.example.com/ch1.m3u8".parse().unwrap(),
+            variants: Vec::new(),
+            current: 0,
+            playlist: MediaPlaylist {
+                target_duration: 2.0,
+                init: None,
+                segments,
+                live: true,
+            },
+            next_sequence: None,
+            needs_init: false,
+            last_reload: Instant::now(),
+            stale: false,
+            metrics: None,
+        }
+    }
+
+    #[test]
+    fn starts_at_the_live_edge_and_skips_ahead_when_behind() {
+        let mut stream = live_stream(50, 10);
+        assert_eq!(stream.upcoming().unwrap().sequence, 57);
+        assert!(!stream.seek(4.0));
+
+        stream.next_sequence = Some(55);
+        assert_eq!(stream.upcoming().unwrap().sequence, 55);
+        // Not listed yet: wait for a reload
+        stream.next_sequence = Some(60);
+        assert!(stream.upcoming().is_none());
+        // Dropped from the window already
+        stream.next_sequence = Some(40);
+        assert_eq!(stream.upcoming().unwrap().sequence, 57);
+
+        stream.playlist.live = false;
+        assert!(stream.seek(4.0));
+        assert_eq!(stream.upcoming().unwrap().sequence, 52);
+    }
+
+    /// Answer one HTTP request with `response` and close the connection
+    async fn serve_once(response: Vec<u8>) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = socket.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            socket.write_all(&response).await.ok();
+        });
+        url
+    }
+
+    async fn fetch(header: &str, body_len: usize) -> Result<Vec<u8>, StreamError> {
+        let mut response =
+            format!("HTTP/1.1 200 OK\r\n{}Connection: close\r\n\r\n", header).into_bytes();
+        response.resize(response.len() + body_len, b'x');
+        let url = serve_once(response).await;
+        let response = reqwest::get(&url).await.unwrap();
+        read_body(response, 1000, &url).await
+    }
+
+    #[tokio::test]
+    async fn bodies_are_read_up_to_the_limit() {
+        assert_eq!(
+            fetch("Content-Length: 1000\r\n", 1000).await.unwrap().len(),
+            1000
+        );
+        assert_eq!(fetch("", 1000).await.unwrap().len(), 1000);
+        // Declared too large, and too large without a declared length
+        assert!(fetch("Content-Length: 1001\r\n", 1001).await.is_err());
+        assert!(fetch("", 1001).await.is_err());
+    }
+}
@@ -0,0 +1,1029 @@
+//! Segmented Stream Manifests
+//!
+//! Parses HLS playlists and DASH manifests into one model: the
+//! [`Variant`]s a stream is offered in and, per variant, a
+//! [`MediaPlaylist`] of segments. Live manifests are marked as such and
+//! know where their live edge is; [`SegmentStream`](super::live::SegmentStream)
+//! reloads them as they grow.
+//!
+//! Supported are HLS master and media playlists (byte ranges, `EXT-X-MAP`,
+//! unencrypted segments only) and the single-period DASH profiles in common
+//! use: `SegmentTemplate` with a fixed duration or a `SegmentTimeline`,
+//! `SegmentList`, and one file per representation. DASH variants come from
+//! one adaptation set, video if there is one; separate audio sets are not
+//! played.
+
+use super::video::StreamSource;
+use crate::error::StreamError;
+use chrono::{DateTime, Utc};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use reqwest::Url;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Live HLS clients start this many target durations behind the end
+const LIVE_EDGE_TARGET_DURATIONS: f64 = 3.0;
+
+/// Time-shift window of live DASH streams that do not declare one
+const DEFAULT_TIME_SHIFT: f64 = 30.0;
+
+/// Most segments one playlist may expand to
+///
+/// A day of two-second segments. DASH templates and timelines describe
+/// segments by count, so a manifest could otherwise ask for billions.
+const MAX_SEGMENTS: u64 = 43_200;
+
+/// Kind of manifest a segmented source uses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestKind {
+    /// HTTP Live Streaming (`.m3u8`)
+    Hls,
+    /// MPEG-DASH (`.mpd`)
+    Dash,
+}
+
+impl ManifestKind {
+    /// Guess the kind from a URL's file extension
+    pub fn from_url(url: &str) -> Option<Self> {
+        let path = url.split(['?', '#']).next().unwrap_or(url);
+        let path = path.to_ascii_lowercase();
+        if path.ends_with(".m3u8") || path.ends_with(".m3u") {
+            Some(Self::Hls)
+        } else if path.ends_with(".mpd") {
+            Some(Self::Dash)
+        } else {
+            None
+        }
+    }
+}
+
+impl StreamSource {
+    /// Source for a URL, recognizing HLS and DASH manifests
+    pub fn from_url(url: impl Into<String>) -> Self {
+        let url = url.into();
+        match ManifestKind::from_url(&url) {
+            Some(ManifestKind::Hls) => Self::Hls { playlist_url: url },
+            Some(ManifestKind::Dash) => Self::Dash { manifest_url: url },
+            None => Self::Url { url },
+        }
+    }
+
+    /// Manifest of a segmented source
+    pub fn manifest(&self) -> Option<(ManifestKind, &str)> {
+        match self {
+            Self::Hls { playlist_url } => Some((ManifestKind::Hls, playlist_url)),
+            Self::Dash { manifest_url } => Some((ManifestKind::Dash, manifest_url)),
+            _ => None,
+        }
+    }
+}
+
+/// One quality level offered by a manifest
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    /// Media playlist URL (HLS) or representation ID (DASH)
+    pub id: String,
+    /// Peak bitrate in bits per second
+    pub bandwidth: u64,
+    /// Width and height in pixels, for video
+    pub resolution: Option<(u32, u32)>,
+    /// RFC 6381 codecs string
+    pub codecs: Option<String>,
+}
+
+impl Variant {
+    /// Label for the quality, like `720p` or `128 kbps`
+    pub fn label(&self) -> String {
+        match self.resolution {
+            Some((_, height)) => format!("{}p", height),
+            None => format!("{} kbps", self.bandwidth / 1000),
+        }
+    }
+}
+
+/// One segment of a media playlist
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaSegment {
+    /// Absolute segment URL
+    pub url: String,
+    /// Sequence number, unique within the stream
+    pub sequence: u64,
+    /// Length in seconds
+    pub duration: f64,
+    /// Part of the resource holding the segment
+    pub byte_range: Option<Range<u64>>,
+}
+
+/// Segments of one variant
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaPlaylist {
+    /// Longest segment length in seconds
+    pub target_duration: f64,
+    /// Initialization segment to play before the media segments
+    pub init: Option<MediaSegment>,
+    /// Segments in playback order
+    pub segments: Vec<MediaSegment>,
+    /// More segments will be appended (a live stream)
+    pub live: bool,
+}
+
+impl MediaPlaylist {
+    fn new() -> Self {
+        Self {
+            target_duration: 0.0,
+            init: None,
+            segments: Vec::new(),
+            live: true,
+        }
+    }
+
+    /// Length in seconds of the segments listed
+    pub fn duration(&self) -> f64 {
+        self.segments.iter().map(|segment| segment.duration).sum()
+    }
+
+    /// Index of the segment to start playing at
+    ///
+    /// Live streams start three target durations from the end, as HLS
+    /// recommends, to leave room for the next reload; on-demand media
+    /// starts at the beginning.
+    pub fn live_edge(&self) -> usize {
+        if !self.live {
+            return 0;
+        }
+        let hold_back = self.target_duration * LIVE_EDGE_TARGET_DURATIONS;
+        let mut behind = 0.0;
+        for (index, segment) in self.segments.iter().enumerate().rev() {
+            behind += segment.duration;
+            if behind >= hold_back {
+                return index;
+            }
+        }
+        0
+    }
+
+    /// Index of the segment containing `position` seconds from the start
+    pub fn segment_at(&self, position: f64) -> Option<usize> {
+        let mut end = 0.0;
+        self.segments.iter().position(|segment| {
+            end += segment.duration;
+            position < end
+        })
+    }
+
+    /// Index of the segment with a sequence number
+    pub fn index_of(&self, sequence: u64) -> Option<usize> {
+        let first = self.segments.first()?.sequence;
+        let index = usize::try_from(sequence.checked_sub(first)?).ok()?;
+        (self.segments.get(index)?.sequence == sequence).then_some(index)
+    }
+}
+
+/// A parsed manifest
+#[derive(Debug, Clone)]
+pub enum Manifest {
+    /// An HLS master playlist; each variant's playlist is fetched separately
+    Master(Vec<Variant>),
+    /// Segments of a single variant
+    Media(MediaPlaylist),
+    /// A DASH manifest with the segments of every representation
+    Dash(Vec<(Variant, MediaPlaylist)>),
+}
+
+impl Manifest {
+    /// Parse a manifest fetched from `base`
+    ///
+    /// `now` places the live edge of DASH streams that are described by a
+    /// segment template only.
+    pub fn parse(
+        kind: ManifestKind,
+        text: &str,
+        base: &Url,
+        now: DateTime<Utc>,
+    ) -> Result<Self, StreamError> {
+        match kind {
+            ManifestKind::Hls => parse_hls(text, base),
+            ManifestKind::Dash => parse_dash(text, base, now),
+        }
+    }
+}
+
+fn invalid(reason: impl Into<String>) -> StreamError {
+    StreamError::Manifest(reason.into())
+}
+
+fn resolve(base: &Url, reference: &str) -> Result<Url, StreamError> {
+    base.join(reference)
+        .map_err(|e| invalid(format!("bad URL {}: {}", reference, e)))
+}
+
+/// Fail if a playlist holding `len` segments would grow past [`MAX_SEGMENTS`] by `more`
+fn check_segment_count(len: usize, more: u64) -> Result<(), StreamError> {
+    match (len as u64).checked_add(more) {
+        Some(total) if total <= MAX_SEGMENTS => Ok(()),
+        _ => Err(invalid(format!("more than {} segments", MAX_SEGMENTS))),
+    }
+}
+
+fn parse_hls(text: &str, base: &Url) -> Result<Manifest, StreamError> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    if lines.next() != Some("#EXTM3U") {
+        return Err(invalid("not an HLS playlist"));
+    }
+
+    let mut variants = Vec::new();
+    let mut pending_variant = None;
+    let mut playlist = MediaPlaylist::new();
+    let mut sequence = 0u64;
+    let mut segment_duration = None;
+    let mut byte_range = None;
+    // Where a byte range without an offset continues from
+    let mut next_offset = 0u64;
+
+    for line in lines {
+        if let Some(list) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+            let attrs = attributes(list);
+            let bandwidth = attrs
+                .get("BANDWIDTH")
+                .and_then(|value| value.parse().ok())
+                .ok_or_else(|| invalid("variant without a bandwidth"))?;
+            let resolution = attrs.get("RESOLUTION").and_then(|value| {
+                let (width, height) = value.split_once('x')?;
+                Some((width.parse().ok()?, height.parse().ok()?))
+            });
+            pending_variant = Some(Variant {
+                id: String::new(),
+                bandwidth,
+                resolution,
+                codecs: attrs.get("CODECS").cloned(),
+            });
+        } else if let Some(value) = line.strip_prefix("#EXT-X-TARGETDURATION:") {
+            playlist.target_duration = value.parse().map_err(|_| invalid("bad target duration"))?;
+        } else if let Some(value) = line.strip_prefix("#EXT-X-MEDIA-SEQUENCE:") {
+            sequence = value.parse().map_err(|_| invalid("bad media sequence"))?;
+        } else if let Some(value) = line.strip_prefix("#EXTINF:") {
+            let duration = value.split(',').next().unwrap_or(value);
+            segment_duration = Some(
+                duration
+                    .trim()
+                    .parse()
+                    .map_err(|_| invalid("bad segment duration"))?,
+            );
+        } else if let Some(value) = line.strip_prefix("#EXT-X-BYTERANGE:") {
+            byte_range = Some(parse_byte_range(value, next_offset)?);
+        } else if let Some(list) = line.strip_prefix("#EXT-X-MAP:") {
+            let attrs = attributes(list);
+            let uri = attrs
+                .get("URI")
+                .ok_or_else(|| invalid("EXT-X-MAP without a URI"))?;
+            let byte_range = attrs
+                .get("BYTERANGE")
+                .map(|value| parse_byte_range(value, 0))
+                .transpose()?;
+            playlist.init = Some(MediaSegment {
+                url: resolve(base, uri)?.into(),
+                sequence,
+                duration: 0.0,
+                byte_range,
+            });
+        } else if let Some(list) = line.strip_prefix("#EXT-X-KEY:") {
+            let attrs = attributes(list);
+            if attrs.get("METHOD").is_some_and(|method| method != "NONE") {
+                return Err(invalid("encrypted HLS segments are not supported"));
+            }
+        } else if line == "#EXT-X-ENDLIST" {
+            playlist.live = false;
+        } else if line.starts_with('#') {
+            // Other tags do not affect playback
+        } else if let Some(mut variant) = pending_variant.take() {
+            variant.id = resolve(base, line)?.into();
+            variants.push(variant);
+        } else if let Some(duration) = segment_duration.take() {
+            let range = byte_range.take();
+            if let Some(range) = &range {
+                next_offset = range.end;
+            }
+            check_segment_count(playlist.segments.len(), 1)?;
+            playlist.segments.push(MediaSegment {
+                url: resolve(base, line)?.into(),
+                sequence,
+                duration,
+                byte_range: range,
+            });
+            sequence = sequence
+                .checked_add(1)
+                .ok_or_else(|| invalid("bad media sequence"))?;
+        }
+    }
+
+    if !variants.is_empty() {
+        return Ok(Manifest::Master(variants));
+    }
+    if playlist.target_duration <= 0.0 {
+        playlist.target_duration = playlist
+            .segments
+            .iter()
+            .map(|segment| segment.duration)
+            .fold(0.0, f64::max);
+    }
+    Ok(Manifest::Media(playlist))
+}
+
+/// Split an HLS attribute list, unquoting values
+fn attributes(list: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    let mut rest = list.trim();
+    while !rest.is_empty() {
+        let Some((name, after)) = rest.split_once('=') else {
+            break;
+        };
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => match quoted.split_once('"') {
+                Some((value, after)) => (value, after),
+                None => (quoted, ""),
+            },
+            None => after.split_once(',').unwrap_or((after, "")),
+        };
+        attrs.insert(name.trim().to_string(), value.to_string());
+        rest = after.trim_start_matches(',').trim_start();
+    }
+    attrs
+}
+
+/// Parse `length[@offset]`
+fn parse_byte_range(value: &str, default_offset: u64) -> Result<Range<u64>, StreamError> {
+    let (length, offset) = match value.split_once('@') {
+        Some((length, offset)) => (length, Some(offset)),
+        None => (value, None),
+    };
+    let length: u64 = length
+        .trim()
+        .parse()
+        .map_err(|_| invalid("bad byte range"))?;
+    let offset = match offset {
+        Some(offset) => offset
+            .trim()
+            .parse()
+            .map_err(|_| invalid("bad byte range"))?,
+        None => default_offset,
+    };
+    let end = offset
+        .checked_add(length)
+        .ok_or_else(|| invalid("bad byte range"))?;
+    Ok(offset..end)
+}
+
+/// A parsed XML element
+#[derive(Debug, Default)]
+struct Node {
+    name: String,
+    attrs: HashMap<String, String>,
+    children: Vec<Node>,
+    text: String,
+}
+
+impl Node {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs.get(name).map(String::as_str)
+    }
+
+    fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Node> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    /// Resolve this element's `BaseURL` against `base`
+    fn base_url(&self, base: &Url) -> Result<Url, StreamError> {
+        match self.child("BaseURL") {
+            Some(node) if !node.text.trim().is_empty() => resolve(base, node.text.trim()),
+            _ => Ok(base.clone()),
+        }
+    }
+}
+
+fn element(start: &BytesStart<'_>) -> Result<Node, StreamError> {
+    let mut node = Node {
+        name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+        ..Node::default()
+    };
+    for attr in start.attributes() {
+        let attr = attr.map_err(|e| invalid(e.to_string()))?;
+        let value = attr.unescape_value().map_err(|e| invalid(e.to_string()))?;
+        node.attrs.insert(
+            String::from_utf8_lossy(attr.key.local_name().as_ref()).into_owned(),
+            value.into_owned(),
+        );
+    }
+    Ok(node)
+}
+
+/// Parse a document into its root element
+fn parse_xml(text: &str) -> Result<Node, StreamError> {
+    let mut reader = Reader::from_str(text);
+    reader.trim_text(true);
+    let mut stack = vec![Node::default()];
+    loop {
+        match reader.read_event().map_err(|e| invalid(e.to_string()))? {
+            Event::Start(start) => stack.push(element(&start)?),
+            Event::Empty(start) => {
+                let node = element(&start)?;
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(node);
+                }
+            }
+            Event::Text(text) => {
+                if let Some(node) = stack.last_mut() {
+                    node.text
+                        .push_str(&text.unescape().map_err(|e| invalid(e.to_string()))?);
+                }
+            }
+            Event::End(_) => {
+                let node = stack.pop().ok_or_else(|| invalid("unbalanced XML"))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => return Err(invalid("unbalanced XML")),
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    stack
+        .pop()
+        .and_then(|document| document.children.into_iter().next())
+        .ok_or_else(|| invalid("empty manifest"))
+}
+
+/// Parse an ISO 8601 duration like `PT1H2M3.5S` into seconds
+fn parse_iso_duration(value: &str) -> Option<f64> {
+    let rest = value.trim().strip_prefix('P')?;
+    let (days, time) = match rest.split_once('T') {
+        Some((days, time)) => (days, time),
+        None => (rest, ""),
+    };
+    let mut seconds = 0.0;
+    if !days.is_empty() {
+        seconds += days.strip_suffix('D')?.parse::<f64>().ok()? * 86_400.0;
+    }
+    let mut number = String::new();
+    for c in time.chars() {
+        let unit = match c {
+            'H' => 3_600.0,
+            'M' => 60.0,
+            'S' => 1.0,
+            _ => {
+                number.push(c);
+                continue;
+            }
+        };
+        seconds += number.parse::<f64>().ok()? * unit;
+        number.clear();
+    }
+    number.is_empty().then_some(seconds)
+}
+
+/// Fill in a segment template's `$Identifier$`s
+fn expand_template(template: &str, id: &str, bandwidth: u64, number: u64, time: u64) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut parts = template.split('$');
+    if let Some(first) = parts.next() {
+        out.push_str(first);
+    }
+    let mut in_identifier = true;
+    for part in parts {
+        if in_identifier {
+            let (name, width) = match part.split_once("%0") {
+                Some((name, format)) => (
+                    name,
+                    format.trim_end_matches('d').parse::<usize>().unwrap_or(0),
+                ),
+                None => (part, 0),
+            };
+            match name {
+                "" => out.push('$'),
+                "RepresentationID" => out.push_str(id),
+                "Bandwidth" => out.push_str(&format!("{:0width$}", bandwidth, width = width)),
+                "Number" => out.push_str(&format!("{:0width$}", number, width = width)),
+                "Time" => out.push_str(&format!("{:0width$}", time, width = width)),
+                _ => {
+                    out.push('$');
+                    out.push_str(part);
+                    out.push('$');
+                }
+            }
+        } else {
+            out.push_str(part);
+        }
+        in_identifier = !in_identifier;
+    }
+    out
+}
+
+/// Timing of a DASH presentation
+struct Presentation {
+    live: bool,
+    /// Seconds since the period started, for live streams
+    elapsed: f64,
+    duration: Option<f64>,
+    time_shift: f64,
+}
+
+fn parse_dash(text: &str, base: &Url, now: DateTime<Utc>) -> Result<Manifest, StreamError> {
+    let mpd = parse_xml(text)?;
+    if mpd.name != "MPD" {
+        return Err(invalid("not a DASH manifest"));
+    }
+    let period = mpd
+        .child("Period")
+        .ok_or_else(|| invalid("manifest has no period"))?;
+
+    let live = mpd.attr("type") == Some("dynamic");
+    let period_start = period.attr("start").and_then(parse_iso_duration);
+    let elapsed = match mpd.attr("availabilityStartTime") {
+        Some(start) if live => {
+            let start = DateTime::parse_from_rfc3339(start)
+                .map_err(|_| invalid("bad availability start time"))?;
+            (now - start.with_timezone(&Utc)).num_milliseconds() as f64 / 1000.0
+                - period_start.unwrap_or(0.0)
+        }
+        _ => 0.0,
+    };
+    let presentation = Presentation {
+        live,
+        elapsed,
+        duration: period
+            .attr("duration")
+            .or_else(|| mpd.attr("mediaPresentationDuration"))
+            .and_then(parse_iso_duration),
+        time_shift: mpd
+            .attr("timeShiftBufferDepth")
+            .and_then(parse_iso_duration)
+            .unwrap_or(DEFAULT_TIME_SHIFT),
+    };
+
+    let base = period.base_url(&mpd.base_url(base)?)?;
+    let is_video = |node: &Node| {
+        let kind = node
+            .attr("contentType")
+            .or_else(|| node.attr("mimeType"))
+            .unwrap_or_default();
+        kind.starts_with("video")
+            || node
+                .children("Representation")
+                .any(|rep| rep.attr("mimeType").is_some_and(|m| m.starts_with("video")))
+    };
+    let adaptation = period
+        .children("AdaptationSet")
+        .find(|set| is_video(set))
+        .or_else(|| period.child("AdaptationSet"))
+        .ok_or_else(|| invalid("period has no adaptation set"))?;
+    let base = adaptation.base_url(&base)?;
+
+    let mut representations = Vec::new();
+    for rep in adaptation.children("Representation") {
+        let id = rep.attr("id").unwrap_or_default().to_string();
+        let bandwidth = rep
+            .attr("bandwidth")
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| invalid("representation without a bandwidth"))?;
+        let resolution = rep
+            .attr("width")
+            .zip(rep.attr("height"))
+            .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)));
+        let variant = Variant {
+            id,
+            bandwidth,
+            resolution,
+            codecs: rep
+                .attr("codecs")
+                .or_else(|| adaptation.attr("codecs"))
+                .map(str::to_string),
+        };
+        let base = rep.base_url(&base)?;
+        let playlist = representation_playlist(&presentation, adaptation, rep, &variant, &base)?;
+        representations.push((variant, playlist));
+    }
+    if representations.is_empty() {
+        return Err(invalid("adaptation set has no representations"));
+    }
+    Ok(Manifest::Dash(representations))
+}
+
+/// Build the segment list of one representation
+fn representation_playlist(
+    presentation: &Presentation,
+    adaptation: &Node,
+    rep: &Node,
+    variant: &Variant,
+    base: &Url,
+) -> Result<MediaPlaylist, StreamError> {
+    // Representation attributes override the adaptation set's
+    let template = match (
+        adaptation.child("SegmentTemplate"),
+        rep.child("SegmentTemplate"),
+    ) {
+        (Some(outer), Some(inner)) => {
+            let mut attrs = outer.attrs.clone();
+            attrs.extend(inner.attrs.clone());
+            let timeline = inner
+                .child("SegmentTimeline")
+                .or_else(|| outer.child("SegmentTimeline"));
+            Some((attrs, timeline))
+        }
+        (Some(node), None) | (None, Some(node)) => {
+            Some((node.attrs.clone(), node.child("SegmentTimeline")))
+        }
+        (None, None) => None,
+    };
+
+    let mut playlist = MediaPlaylist::new();
+    playlist.live = presentation.live;
+
+    if let Some((attrs, timeline)) = template {
+        let number = |key: &str, default: u64| {
+            attrs
+                .get(key)
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+        let timescale = number("timescale", 1).max(1) as f64;
+        let start_number = number("startNumber", 1);
+        let media = attrs
+            .get("media")
+            .ok_or_else(|| invalid("segment template without media"))?;
+        let url = |number: u64, time: u64| {
+            resolve(
+                base,
+                &expand_template(media, &variant.id, variant.bandwidth, number, time),
+            )
+        };
+        if let Some(init) = attrs.get("initialization") {
+            playlist.init = Some(MediaSegment {
+                url: resolve(
+                    base,
+                    &expand_template(init, &variant.id, variant.bandwidth, 0, 0),
+                )?
+                .into(),
+                sequence: start_number,
+                duration: 0.0,
+                byte_range: None,
+            });
+        }
+
+        if let Some(timeline) = timeline {
+            let mut time = 0u64;
+            let mut sequence = start_number;
+            for entry in timeline.children("S") {
+                let duration: u64 = entry
+                    .attr("d")
+                    .and_then(|value| value.parse().ok())
+                    .filter(|&duration| duration > 0)
+                    .ok_or_else(|| invalid("timeline entry without a duration"))?;
+                if let Some(start) = entry.attr("t").and_then(|value| value.parse().ok()) {
+                    time = start;
+                }
+                let repeat: i64 = entry
+                    .attr("r")
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(0);
+                // A negative repeat lasts until the end of the period
+                let repeat = if repeat < 0 {
+                    let end = if presentation.live {
+                        presentation.elapsed
+                    } else {
+                        presentation.duration.unwrap_or(0.0)
+                    };
+                    // Saturates for an end the timeline cannot reach
+                    ((end * timescale - time as f64) / duration as f64)
+                        .ceil()
+                        .max(1.0) as u64
+                        - 1
+                } else {
+                    repeat as u64
+                };
+                check_segment_count(playlist.segments.len(), repeat.saturating_add(1))?;
+                for _ in 0..=repeat {
+                    playlist.segments.push(MediaSegment {
+                        url: url(sequence, time)?.into(),
+                        sequence,
+                        duration: duration as f64 / timescale,
+                        byte_range: None,
+                    });
+                    time = time
+                        .checked_add(duration)
+                        .ok_or_else(|| invalid("segment timeline overflows"))?;
+                    sequence = sequence
+                        .checked_add(1)
+                        .ok_or_else(|| invalid("segment numbers overflow"))?;
+                }
+            }
+        } else {
+            let duration = number("duration", 0) as f64 / timescale;
+            if duration <= 0.0 {
+                return Err(invalid("segment template without a duration or timeline"));
+            }
+            let numbers = if presentation.live {
+                // Segments that are complete and still inside the time-shift window
+                let available = (presentation.elapsed / duration).floor().max(0.0) as u64;
+                let window = (presentation.time_shift / duration).ceil() as u64;
+                available.saturating_sub(window)..available
+            } else {
+                let total = presentation
+                    .duration
+                    .ok_or_else(|| invalid("on-demand manifest without a duration"))?;
+                0..(total / duration).ceil() as u64
+            };
+            check_segment_count(
+                playlist.segments.len(),
+                numbers.end.saturating_sub(numbers.start),
+            )?;
+            for index in numbers {
+                let sequence = start_number
+                    .checked_add(index)
+                    .ok_or_else(|| invalid("segment numbers overflow"))?;
+                let time = (index as f64 * duration * timescale) as u64;
+                playlist.segments.push(MediaSegment {
+                    url: url(sequence, time)?.into(),
+                    sequence,
+                    duration,
+                    byte_range: None,
+                });
+            }
+        }
+    } else if let Some(list) = rep
+        .child("SegmentList")
+        .or_else(|| adaptation.child("SegmentList"))
+    {
+        let timescale = list
+            .attr("timescale")
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(1)
+            .max(1) as f64;
+        let duration = list
+            .attr("duration")
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(0) as f64
+            / timescale;
+        let start_number = list
+            .attr("startNumber")
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(1);
+        if let Some(init) = list.child("Initialization") {
+            playlist.init = Some(MediaSegment {
+                url: resolve(base, init.attr("sourceURL").unwrap_or_default())?.into(),
+                sequence: start_number,
+                duration: 0.0,
+                byte_range: init.attr("range").map(parse_http_range).transpose()?,
+            });
+        }
+        for (index, entry) in list.children("SegmentURL").enumerate() {
+            check_segment_count(playlist.segments.len(), 1)?;
+            let sequence = start_number
+                .checked_add(index as u64)
+                .ok_or_else(|| invalid("segment numbers overflow"))?;
+            playlist.segments.push(MediaSegment {
+                url: resolve(base, entry.attr("media").unwrap_or_default())?.into(),
+                sequence,
+                duration,
+                byte_range: entry.attr("mediaRange").map(parse_http_range).transpose()?,
+            });
+        }
+    } else {
+        // The whole representation is one file
+        playlist.segments.push(MediaSegment {
+            url: base.to_string(),
+            sequence: 0,
+            duration: presentation.duration.unwrap_or(0.0),
+            byte_range: None,
+        });
+    }
+
+    playlist.target_duration = playlist
+        .segments
+        .iter()
+        .map(|segment| segment.duration)
+        .fold(0.0, f64::max);
+    Ok(playlist)
+}
+
+/// Parse an inclusive `first-last` byte range
+fn parse_http_range(value: &str) -> Result<Range<u64>, StreamError> {
+    let (first, last) = value
+        .split_once('-')
+        .ok_or_else(|| invalid("bad byte range"))?;
+    let first: u64 = first
+        .trim()
+        .parse()
+        .map_err(|_| invalid("bad byte range"))?;
+    let last: u64 = last.trim().parse().map_err(|_| invalid("bad byte range"))?;
+    let end = last
+        .checked_add(1)
+        .ok_or_else(|| invalid("bad byte range"))?;
+    Ok(first..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Url {
+        "https://cdn.example.com/live/stream.m3u8".parse().unwrap()
+    }
+
+    #[test]
+    fn hls_master_and_live_media_playlists() {
+        let master = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=800000,RESOLUTION=640x360,CODECS=\"avc1.4d401e,mp4a.40.2\"\n\
+            low/index.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=3000000,RESOLUTION=1280x720\n\
+            https://other.example.com/hi.m3u8\n";
+        let Manifest::Master(variants) = parse_hls(master, &base()).unwrap() else {
+            panic!("expected a master playlist");
+        };
+        assert_eq!(variants.len(), 2);
+        assert_eq!(
+            variants[0].id,
+            "https://cdn.example.com/live/low/index.m3u8"
+        );
+        assert_eq!(variants[0].codecs.as_deref(), Some("avc1.4d401e,mp4a.40.2"));
+        assert_eq!(variants[1].label(), "720p");
+
+        let media = "#EXTM3U\n\
+            #EXT-X-TARGETDURATION:4\n\
+            #EXT-X-MEDIA-SEQUENCE:100\n\
+            #EXT-X-MAP:URI=\"init.mp4\",BYTERANGE=\"700@0\"\n\
+            #EXTINF:4.0,\nseg100.m4s\n\
+            #EXTINF:4.0,\nseg101.m4s\n\
+            #EXTINF:4.0,\n#EXT-X-BYTERANGE:500@1000\nall.m4s\n\
+            #EXTINF:4.0,\n#EXT-X-BYTERANGE:300\nall.m4s\n\
+            #EXTINF:3.5,\nseg104.m4s\n";
+        let Manifest::Media(playlist) = parse_hls(media, &base()).unwrap() else {
+            panic!("expected a media playlist");
+        };
+        assert!(playlist.live);
+        assert_eq!(playlist.init.as_ref().unwrap().byte_range, Some(0..700));
+        assert_eq!(playlist.segments[2].byte_range, Some(1000..1500));
+        assert_eq!(playlist.segments[3].byte_range, Some(1500..1800));
+        assert_eq!(playlist.segments[4].sequence, 104);
+        assert_eq!(playlist.index_of(102), Some(2));
+        assert_eq!(playlist.index_of(99), None);
+        // Three target durations back from the end
+        assert_eq!(playlist.live_edge(), 1);
+
+        let ended = format!("{}#EXT-X-ENDLIST\n", media);
+        let Manifest::Media(playlist) = parse_hls(&ended, &base()).unwrap() else {
+            panic!("expected a media playlist");
+        };
+        assert!(!playlist.live);
+        assert_eq!(playlist.live_edge(), 0);
+        assert_eq!(playlist.segment_at(9.0), Some(2));
+        assert_eq!(playlist.segment_at(19.5), None);
+
+        let encrypted = "#EXTM3U\n#EXT-X-KEY:METHOD=AES-128,URI=\"key\"\n";
+        assert!(parse_hls(encrypted, &base()).is_err());
+    }
+
+    #[test]
+    fn dash_templates_lists_and_live_edge() {
+        let vod = r#"<?xml version="1.0"?>
+            <MPD xmlns="urn:mpeg:dash:schema:mpd:2011" type="static" mediaPresentationDuration="PT0M9.5S">
+              <Period>
+                <AdaptationSet contentType="audio">
+                  <Representation id="a" bandwidth="128000"/>
+                </AdaptationSet>
+                <AdaptationSet mimeType="video/mp4">
+                  <SegmentTemplate timescale="1000" duration="4000" startNumber="1"
+                                   initialization="$RepresentationID$/init.mp4"
+                                   media="$RepresentationID$/seg-$Number%05d$.m4s"/>
+                  <Representation id="720p" bandwidth="3000000" width="1280" height="720"/>
+                  <Representation id="360p" bandwidth="800000" width="640" height="360">
+                    <SegmentTemplate timescale="10" media="t/$Time$.m4s">
+                      <SegmentTimeline><S t="0" d="20" r="1"/><S d="15"/></SegmentTimeline>
+                    </SegmentTemplate>
+                  </Representation>
+                </AdaptationSet>
+              </Period>
+            </MPD>"#;
+        let base: Url = "https://cdn.example.com/vod/movie.mpd".parse().unwrap();
+        let Manifest::Dash(reps) = parse_dash(vod, &base, Utc::now()).unwrap() else {
+            panic!("expected a DASH manifest");
+        };
+        assert_eq!(reps.len(), 2);
+        let (variant, playlist) = &reps[0];
+        assert_eq!(variant.label(), "720p");
+        assert!(!playlist.live);
+        assert_eq!(
+            playlist.init.as_ref().unwrap().url,
+            "https://cdn.example.com/vod/720p/init.mp4"
+        );
+        assert_eq!(playlist.segments.len(), 3);
+        assert_eq!(
+            playlist.segments[2].url,
+            "https://cdn.example.com/vod/720p/seg-00003.m4s"
+        );
+        let (_, playlist) = &reps[1];
+        let times: Vec<_> = playlist.segments.iter().map(|s| s.url.as_str()).collect();
+        assert_eq!(
+            times,
+            [
+                "https://cdn.example.com/vod/t/0.m4s",
+                "https://cdn.example.com/vod/t/20.m4s",
+                "https://cdn.example.com/vod/t/40.m4s",
+            ]
+        );
+        assert_eq!(playlist.duration(), 5.5);
+
+        let live = r#"<MPD type="dynamic" availabilityStartTime="2024-01-01T00:00:00Z" timeShiftBufferDepth="PT10S">
+              <BaseURL>https://live.example.com/ch1/</BaseURL>
+              <Period start="PT0S">
+                <AdaptationSet mimeType="video/mp4">
+                  <Representation id="v" bandwidth="2000000">
+                    <SegmentTemplate duration="2" startNumber="10" media="$Number$.m4s"/>
+                  </Representation>
+                </AdaptationSet>
+              </Period>
+            </MPD>"#;
+        let now = "2024-01-01T00:01:00.5Z".parse().unwrap();
+        let Manifest::Dash(reps) = parse_dash(live, &base, now).unwrap() else {
+            panic!("expected a DASH manifest");
+        };
+        let (_, playlist) = &reps[0];
+        assert!(playlist.live);
+        // 30 segments are complete after 60.5 s; the last five are kept
+        let numbers: Vec<_> = playlist.segments.iter().map(|s| s.sequence).collect();
+        assert_eq!(numbers, [35, 36, 37, 38, 39]);
+        assert_eq!(
+            playlist.segments[4].url,
+            "https://live.example.com/ch1/39.m4s"
+        );
+        assert_eq!(playlist.live_edge(), 2);
+
+        assert_eq!(parse_iso_duration("PT1H2M3.5S"), Some(3723.5));
+        assert_eq!(parse_iso_duration("P1D"), Some(86_400.0));
+        assert_eq!(parse_iso_duration("PT5X"), None);
+        assert_eq!(
+            StreamSource::from_url("https://x.example.com/a.M3U8?token=1").manifest(),
+            Some((ManifestKind::Hls, "https://x.example.com/a.M3U8?token=1"))
+        );
+    }
+
+    #[test]
+    fn hostile_manifests_are_rejected() {
+        let base: Url = "https://cdn.example.com/vod/movie.mpd".parse().unwrap();
+        let dash = |kind: &str, template: &str| {
+            let mpd = format!(
+                r#"<MPD type="{}" availabilityStartTime="1970-01-01T00:00:00Z" mediaPresentationDuration="PT1H">
+                  <Period><AdaptationSet><Representation id="v" bandwidth="1">{}</Representation></AdaptationSet></Period>
+                </MPD>"#,
+                kind, template
+            );
+            parse_dash(&mpd, &base, Utc::now())
+        };
+        let timeline = |entries: &str| {
+            format!(
+                r#"<SegmentTemplate media="$Time$.m4s"><SegmentTimeline>{}</SegmentTimeline></SegmentTemplate>"#,
+                entries
+            )
+        };
+
+        // A zero duration would repeat until the end of time
+        assert!(dash("static", &timeline(r#"<S d="0" r="-1"/>"#)).is_err());
+        assert!(dash(
+            "static",
+            r#"<SegmentTemplate duration="0" media="$Number$.m4s"/>"#
+        )
+        .is_err());
+        // Too many segments
+        assert!(dash("static", &timeline(r#"<S d="1" r="4000000000"/>"#)).is_err());
+        assert!(dash("dynamic", &timeline(r#"<S d="1" r="-1"/>"#)).is_err());
+        assert!(dash(
+            "static",
+            r#"<SegmentTemplate timescale="1000" duration="1" media="$Number$.m4s"/>"#
+        )
+        .is_err());
+        // Times and numbers past u64::MAX
+        assert!(dash(
+            "static",
+            &timeline(r#"<S t="18446744073709551615" d="1" r="1"/>"#)
+        )
+        .is_err());
+        assert!(dash(
+            "static",
+            r#"<SegmentTemplate duration="1800" startNumber="18446744073709551615" media="$Number$.m4s"/>"#
+        )
+        .is_err());
+        // A timeline within the limit still expands
+        let Manifest::Dash(reps) = dash("static", &timeline(r#"<S d="2" r="-1"/>"#)).unwrap()
+        else {
+            panic!("expected a DASH manifest");
+        };
+        assert_eq!(reps[0].1.segments.len(), 1800);
+
+        assert!(parse_byte_range("10@18446744073709551615", 0).is_err());
+        assert!(parse_http_range("0-18446744073709551615").is_err());
+    }
+}
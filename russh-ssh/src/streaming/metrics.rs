@@ -0,0 +1,263 @@
+//! Streaming Quality Metrics
+//!
+//! A [`MetricsRecorder`] collects how well one session plays: how often and
+//! for how long playback stalled waiting for data, how full the buffer was
+//! on average, how far a guest drifted from the host's position, and how
+//! many bytes were fetched from each source. Recorders are cheap to clone;
+//! the session, its buffer and its fetchers share one and
+//! [`snapshot`](MetricsRecorder::snapshot) reads them for a stats overlay.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
+
+/// Snapshot of a session's metrics
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StreamMetrics {
+    /// Number of times playback stalled waiting for data
+    pub rebuffer_count: u64,
+    /// Total time spent stalled in seconds, including a stall in progress
+    pub rebuffer_secs: f64,
+    /// Whether playback is stalled right now
+    pub rebuffering: bool,
+    /// Latest buffer level in bytes
+    pub buffer_level: u64,
+    /// Buffer level in bytes averaged over time
+    pub avg_buffer_level: f64,
+    /// Latest position minus the host's, in seconds; positive when ahead
+    pub sync_drift: Option<f64>,
+    /// Mean absolute drift from the host in seconds
+    pub avg_sync_drift: Option<f64>,
+    /// Largest absolute drift from the host in seconds
+    pub max_sync_drift: f64,
+    /// Bytes fetched, by source
+    pub bytes_fetched: HashMap<String, u64>,
+    /// Seconds since recording started
+    pub elapsed_secs: f64,
+}
+
+impl StreamMetrics {
+    /// Total bytes fetched from all sources
+    pub fn total_bytes_fetched(&self) -> u64 {
+        self.bytes_fetched.values().sum()
+    }
+}
+
+#[derive(Debug)]
+struct MetricsState {
+    started: Instant,
+    rebuffer_count: u64,
+    rebuffer_time: f64,
+    /// When the stall in progress began
+    stalled_since: Option<Instant>,
+    buffer_level: u64,
+    /// Time-weighted sum of buffer levels, in byte-seconds
+    buffer_area: f64,
+    /// When the buffer level was first and last recorded
+    buffer_since: Option<(Instant, Instant)>,
+    sync_drift: Option<f64>,
+    drift_total: f64,
+    drift_samples: u64,
+    max_sync_drift: f64,
+    bytes_fetched: HashMap<String, u64>,
+}
+
+impl MetricsState {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            rebuffer_count: 0,
+            rebuffer_time: 0.0,
+            stalled_since: None,
+            buffer_level: 0,
+            buffer_area: 0.0,
+            buffer_since: None,
+            sync_drift: None,
+            drift_total: 0.0,
+            drift_samples: 0,
+            max_sync_drift: 0.0,
+            bytes_fetched: HashMap::new(),
+        }
+    }
+
+    /// Fold the current level into the average up to `now`
+    fn settle_buffer(&mut self, now: Instant) {
+        if let Some((first, last)) = self.buffer_since {
+            self.buffer_area += self.buffer_level as f64 * (now - last).as_secs_f64();
+            self.buffer_since = Some((first, now));
+        }
+    }
+}
+
+/// Collects the metrics of one streaming session
+#[derive(Debug, Clone)]
+pub struct MetricsRecorder {
+    state: Arc<Mutex<MetricsState>>,
+}
+
+impl MetricsRecorder {
+    /// Create a recorder with nothing recorded
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MetricsState::new())),
+        }
+    }
+
+    /// Record that playback stalled waiting for data
+    ///
+    /// Does nothing while a stall is already in progress.
+    pub fn rebuffer_started(&self) {
+        let mut state = self.lock();
+        if state.stalled_since.is_none() {
+            state.rebuffer_count += 1;
+            state.stalled_since = Some(Instant::now());
+        }
+    }
+
+    /// Record that playback resumed after a stall
+    pub fn rebuffer_ended(&self) {
+        let mut state = self.lock();
+        if let Some(since) = state.stalled_since.take() {
+            state.rebuffer_time += since.elapsed().as_secs_f64();
+        }
+    }
+
+    /// Record the number of bytes buffered now
+    pub fn record_buffer_level(&self, bytes: u64) {
+        let now = Instant::now();
+        let mut state = self.lock();
+        state.settle_buffer(now);
+        if state.buffer_since.is_none() {
+            state.buffer_since = Some((now, now));
+        }
+        state.buffer_level = bytes;
+    }
+
+    /// Record how far local playback is from the host's, in seconds
+    pub fn record_drift(&self, drift: f64) {
+        if !drift.is_finite() {
+            return;
+        }
+        let mut state = self.lock();
+        state.sync_drift = Some(drift);
+        state.drift_total += drift.abs();
+        state.drift_samples += 1;
+        state.max_sync_drift = state.max_sync_drift.max(drift.abs());
+    }
+
+    /// Record bytes fetched from a source
+    pub fn record_fetch(&self, source: &str, bytes: u64) {
+        let mut state = self.lock();
+        *state.bytes_fetched.entry(source.to_string()).or_default() += bytes;
+    }
+
+    /// Read the metrics recorded so far
+    pub fn snapshot(&self) -> StreamMetrics {
+        let now = Instant::now();
+        let mut state = self.lock();
+        state.settle_buffer(now);
+
+        let stalled = state
+            .stalled_since
+            .map_or(0.0, |since| (now - since).as_secs_f64());
+        let avg_buffer_level = match state.buffer_since {
+            Some((first, _)) if now > first => state.buffer_area / (now - first).as_secs_f64(),
+            _ => state.buffer_level as f64,
+        };
+
+        StreamMetrics {
+            rebuffer_count: state.rebuffer_count,
+            rebuffer_secs: state.rebuffer_time + stalled,
+            rebuffering: state.stalled_since.is_some(),
+            buffer_level: state.buffer_level,
+            avg_buffer_level,
+            sync_drift: state.sync_drift,
+            avg_sync_drift: (state.drift_samples > 0)
+                .then(|| state.drift_total / state.drift_samples as f64),
+            max_sync_drift: state.max_sync_drift,
+            bytes_fetched: state.bytes_fetched.clone(),
+            elapsed_secs: (now - state.started).as_secs_f64(),
+        }
+    }
+
+    /// Forget everything recorded so far
+    pub fn reset(&self) {
+        *self.lock() = MetricsState::new();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MetricsState> {
+        self.state.lock().unwrap_or_else(|poisoned| {
+            tracing::warn!("Stream metrics lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+}
+
+impl Default for MetricsRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn rebuffers_and_buffer_level_are_timed() {
+        let metrics = MetricsRecorder::new();
+        metrics.record_buffer_level(1000);
+        tokio::time::advance(Duration::from_secs(3)).await;
+        metrics.record_buffer_level(0);
+        metrics.rebuffer_started();
+        tokio::time::advance(Duration::from_secs(1)).await;
+        // Still the same stall
+        metrics.rebuffer_started();
+        let stalled = metrics.snapshot();
+        assert!(stalled.rebuffering);
+        assert_eq!(stalled.rebuffer_count, 1);
+        assert!((stalled.rebuffer_secs - 1.0).abs() < 1e-9);
+        assert!((stalled.avg_buffer_level - 750.0).abs() < 1e-9);
+
+        metrics.rebuffer_ended();
+        metrics.record_buffer_level(4000);
+        tokio::time::advance(Duration::from_secs(1)).await;
+        metrics.rebuffer_started();
+        tokio::time::advance(Duration::from_secs(2)).await;
+        metrics.rebuffer_ended();
+        let snapshot = metrics.snapshot();
+        assert!(!snapshot.rebuffering);
+        assert_eq!(snapshot.rebuffer_count, 2);
+        assert!((snapshot.rebuffer_secs - 3.0).abs() < 1e-9);
+        assert_eq!(snapshot.buffer_level, 4000);
+        // 3s at 1000, 1s empty and 3s at 4000
+        assert!((snapshot.avg_buffer_level - 15000.0 / 7.0).abs() < 1e-9);
+        assert!((snapshot.elapsed_secs - 7.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn drift_and_fetches_accumulate() {
+        let metrics = MetricsRecorder::new();
+        assert_eq!(metrics.snapshot().avg_sync_drift, None);
+        metrics.record_drift(0.5);
+        metrics.record_drift(-1.5);
+        metrics.record_drift(f64::NAN);
+        metrics.record_fetch("a.mkv", 100);
+        metrics.record_fetch("b.m3u8", 50);
+        metrics.record_fetch("a.mkv", 25);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.sync_drift, Some(-1.5));
+        assert_eq!(snapshot.avg_sync_drift, Some(1.0));
+        assert_eq!(snapshot.max_sync_drift, 1.5);
+        assert_eq!(snapshot.bytes_fetched["a.mkv"], 125);
+        assert_eq!(snapshot.total_bytes_fetched(), 175);
+
+        metrics.reset();
+        let snapshot = metrics.snapshot();
+        assert!(snapshot.bytes_fetched.is_empty());
+        assert_eq!(snapshot.sync_drift, None);
+    }
+}
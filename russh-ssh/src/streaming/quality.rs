@@ -0,0 +1,323 @@
+//! Adaptive Quality Selection
+//!
+//! Lets a source offer several renditions of the same media and picks one
+//! per room member, so a member on a slow link drops to a lower quality
+//! instead of stalling everyone else's playback.
+//!
+//! A [`QualitySelector`] keeps a smoothed estimate of the download rate
+//! from [`record_download`](QualitySelector::record_download) samples and
+//! is asked to [`update`](QualitySelector::update) with the amount of media
+//! buffered ahead. It steps down right away when the buffer runs low,
+//! steps up only once the buffer is healthy and the estimate leaves
+//! headroom, and never changes rendition while a manual choice is pinned.
+//!
+//! # Requirements Coverage
+//! - Requirement 6.2: Adaptive buffering
+
+use super::audio::MediaKind;
+use super::video::StreamSource;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// One quality level of a source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rendition {
+    /// Name shown to the user, such as `720p`
+    pub label: String,
+    /// Average bitrate in bits per second
+    pub bitrate: u64,
+    /// Where this rendition is played from
+    pub source: StreamSource,
+}
+
+impl Rendition {
+    /// Create a rendition
+    pub fn new(label: impl Into<String>, bitrate: u64, source: StreamSource) -> Self {
+        Self {
+            label: label.into(),
+            bitrate,
+            source,
+        }
+    }
+}
+
+impl StreamSource {
+    /// Renditions offered by a multi-quality source
+    pub fn renditions(&self) -> &[Rendition] {
+        match self {
+            Self::Renditions { renditions } => renditions,
+            _ => &[],
+        }
+    }
+}
+
+/// Tuning for [`QualitySelector`]
+#[derive(Debug, Clone)]
+pub struct QualityConfig {
+    /// Fraction of the measured bandwidth a rendition may use
+    pub safety_factor: f64,
+    /// Weight of the newest sample in the bandwidth estimate
+    pub smoothing: f64,
+    /// Step down immediately when less than this is buffered
+    pub low_buffer: Duration,
+    /// Only step up when at least this much is buffered
+    pub high_buffer: Duration,
+    /// Minimum time between two step-ups
+    pub min_upswitch_interval: Duration,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            safety_factor: 0.8,
+            smoothing: 0.3,
+            low_buffer: Duration::from_secs(5),
+            high_buffer: Duration::from_secs(15),
+            min_upswitch_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Whether the rendition is chosen automatically
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "mode", content = "index")]
+pub enum QualityMode {
+    /// Follow bandwidth and buffer health
+    Auto,
+    /// Stay on the rendition at this index
+    Manual(usize),
+}
+
+/// Picks a rendition for one room member
+#[derive(Debug)]
+pub struct QualitySelector {
+    config: QualityConfig,
+    /// Renditions ordered by ascending bitrate
+    renditions: Vec<Rendition>,
+    mode: QualityMode,
+    current: usize,
+    /// Smoothed download rate in bits per second
+    bandwidth: Option<f64>,
+    last_upswitch: Option<Instant>,
+}
+
+impl QualitySelector {
+    /// Create a selector starting at the lowest bitrate
+    ///
+    /// Returns `None` when there are no renditions.
+    pub fn new(mut renditions: Vec<Rendition>) -> Option<Self> {
+        if renditions.is_empty() {
+            return None;
+        }
+        renditions.sort_by_key(|rendition| rendition.bitrate);
+        Some(Self {
+            config: QualityConfig::default(),
+            renditions,
+            mode: QualityMode::Auto,
+            current: 0,
+            bandwidth: None,
+            last_upswitch: None,
+        })
+    }
+
+    /// Create a selector for a [`StreamSource::Renditions`] source
+    pub fn for_source(source: &StreamSource) -> Option<Self> {
+        Self::new(source.renditions().to_vec())
+    }
+
+    /// Use different tuning
+    pub fn with_config(mut self, config: QualityConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Renditions ordered by ascending bitrate
+    pub fn renditions(&self) -> &[Rendition] {
+        &self.renditions
+    }
+
+    /// Rendition to play now
+    pub fn current(&self) -> &Rendition {
+        &self.renditions[self.current]
+    }
+
+    /// Index of the rendition to play now
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    /// Kind of media the renditions carry
+    pub fn kind(&self) -> MediaKind {
+        self.current().source.kind()
+    }
+
+    /// Current selection mode
+    pub fn mode(&self) -> QualityMode {
+        self.mode
+    }
+
+    /// Pin a rendition, turning automatic switching off
+    ///
+    /// Returns `false` if there is no rendition at `index`.
+    pub fn set_manual(&mut self, index: usize) -> bool {
+        if index >= self.renditions.len() {
+            return false;
+        }
+        self.mode = QualityMode::Manual(index);
+        self.current = index;
+        true
+    }
+
+    /// Go back to automatic switching
+    pub fn set_auto(&mut self) {
+        self.mode = QualityMode::Auto;
+    }
+
+    /// Estimated download rate in bits per second
+    pub fn bandwidth(&self) -> Option<u64> {
+        self.bandwidth.map(|bps| bps as u64)
+    }
+
+    /// Feed a completed download of `bytes` that took `elapsed`
+    pub fn record_download(&mut self, bytes: u64, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if secs <= 0.0 {
+            return;
+        }
+        let sample = bytes as f64 * 8.0 / secs;
+        let alpha = self.config.smoothing;
+        self.bandwidth = Some(match self.bandwidth {
+            Some(estimate) => alpha * sample + (1.0 - alpha) * estimate,
+            None => sample,
+        });
+    }
+
+    /// Playback time held by `bytes` of the current rendition
+    pub fn buffered_duration(&self, bytes: usize) -> Duration {
+        let bitrate = self.current().bitrate.max(1) as f64;
+        Duration::from_secs_f64(bytes as f64 * 8.0 / bitrate)
+    }
+
+    /// Re-evaluate the choice given how much media is buffered ahead
+    ///
+    /// Returns the new rendition when it changed.
+    pub fn update(&mut self, buffered: Duration) -> Option<&Rendition> {
+        if self.mode != QualityMode::Auto {
+            return None;
+        }
+
+        let affordable = self.bandwidth.map(|bandwidth| {
+            let budget = bandwidth * self.config.safety_factor;
+            self.renditions
+                .iter()
+                .rposition(|rendition| rendition.bitrate as f64 <= budget)
+                .unwrap_or(0)
+        });
+
+        let target = if buffered < self.config.low_buffer {
+            // About to stall: at least one step down
+            let step_down = self.current.saturating_sub(1);
+            affordable.map_or(step_down, |index| index.min(step_down))
+        } else {
+            match affordable {
+                Some(index) if index < self.current && buffered < self.config.high_buffer => index,
+                Some(index) if index > self.current && self.may_step_up(buffered) => index,
+                _ => self.current,
+            }
+        };
+
+        if target == self.current {
+            return None;
+        }
+        if target > self.current {
+            self.last_upswitch = Some(Instant::now());
+        }
+        tracing::debug!(
+            from = %self.renditions[self.current].label,
+            to = %self.renditions[target].label,
+            buffered_secs = buffered.as_secs_f64(),
+            bandwidth = ?self.bandwidth(),
+            "Switching rendition"
+        );
+        self.current = target;
+        Some(&self.renditions[target])
+    }
+
+    fn may_step_up(&self, buffered: Duration) -> bool {
+        buffered >= self.config.high_buffer
+            && self
+                .last_upswitch
+                .map_or(true, |at| at.elapsed() >= self.config.min_upswitch_interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ladder() -> Vec<Rendition> {
+        [("1080p", 6_000_000), ("360p", 800_000), ("720p", 3_000_000)]
+            .into_iter()
+            .map(|(label, bitrate)| {
+                let url = format!("https://example.com/{}.mp4", label);
+                Rendition::new(label, bitrate, StreamSource::Url { url })
+            })
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn steps_up_with_bandwidth_and_healthy_buffer() {
+        let source = StreamSource::Renditions {
+            renditions: ladder(),
+        };
+        let mut selector = QualitySelector::for_source(&source).unwrap();
+        assert_eq!(selector.current().label, "360p");
+        assert_eq!(selector.kind(), MediaKind::Video);
+
+        // 5 Mbit/s leaves room for 720p, but not while the buffer is short
+        selector.record_download(5_000_000 / 8, Duration::from_secs(1));
+        assert_eq!(
+            selector.update(Duration::from_secs(8)).map(|r| r.bitrate),
+            None
+        );
+        let chosen = selector.update(Duration::from_secs(20)).unwrap();
+        assert_eq!(chosen.label, "720p");
+
+        // A faster link is not exploited until the interval has passed
+        selector.record_download(20_000_000 / 8, Duration::from_secs(1));
+        selector.record_download(20_000_000 / 8, Duration::from_secs(1));
+        assert!(selector.update(Duration::from_secs(20)).is_none());
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert_eq!(
+            selector.update(Duration::from_secs(20)).unwrap().label,
+            "1080p"
+        );
+    }
+
+    #[test]
+    fn low_buffer_steps_down_unless_pinned() {
+        let mut selector = QualitySelector::new(ladder()).unwrap();
+        assert!(selector.set_manual(2));
+        assert_eq!(selector.mode(), QualityMode::Manual(2));
+        assert!(selector.update(Duration::ZERO).is_none());
+        assert_eq!(selector.current().label, "1080p");
+        assert!(!selector.set_manual(3));
+
+        // Stalling with no estimate yet drops one step at a time
+        selector.set_auto();
+        assert_eq!(selector.update(Duration::ZERO).unwrap().label, "720p");
+        // A slow estimate drops straight to what the link can carry
+        selector.record_download(500_000 / 8, Duration::from_secs(1));
+        assert_eq!(
+            selector.update(Duration::from_secs(1)).unwrap().label,
+            "360p"
+        );
+        assert!(selector.update(Duration::ZERO).is_none());
+
+        assert_eq!(
+            selector.buffered_duration(800_000 / 8 * 3),
+            Duration::from_secs(3)
+        );
+    }
+}
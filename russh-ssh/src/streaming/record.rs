@@ -0,0 +1,359 @@
+//! Recording Stream Rooms
+//!
+//! A [`RoomRecorder`] saves what a room watches into VDFS while it plays.
+//! Recording is opt-in: nothing is written until a recorder is started for
+//! a session. A recording is a directory holding
+//!
+//! - `room.json`, the room as it was when recording started,
+//! - `media.<ext>`, the media bytes in the order the player fed them, and
+//! - `timeline.jsonl`, one [`TimelineEntry`] per sync or chat event.
+//!
+//! The media file is written through the chunk store with a
+//! [`VdfsWriter`], so whole chunks are committed as they arrive and the
+//! recording can be shared and synced before it ends.
+
+use super::video::{StreamSession, StreamSource, SyncEvent};
+use crate::error::StreamError;
+use crate::vdfs::{FileMetadata, VdfsWriter, VirtualFs};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch, Mutex};
+use tokio::task::JoinHandle;
+
+/// Something that happened in the room during a recording
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TimelineEvent {
+    /// A playback or membership event
+    Sync { event: SyncEvent },
+    /// A chat message
+    Chat { peer_id: String, text: String },
+}
+
+/// One line of a recording's timeline
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineEntry {
+    /// Milliseconds since the recording started
+    pub at: i64,
+    #[serde(flatten)]
+    pub event: TimelineEvent,
+}
+
+/// Files of a finished recording
+#[derive(Debug, Clone)]
+pub struct Recording {
+    /// Directory holding the recording
+    pub dir: PathBuf,
+    /// The recorded media
+    pub media: FileMetadata,
+    /// The event timeline
+    pub timeline: FileMetadata,
+}
+
+struct Writers {
+    media: VdfsWriter,
+    timeline: VdfsWriter,
+}
+
+/// Records a room's media and events into VDFS
+pub struct RoomRecorder {
+    dir: PathBuf,
+    started: i64,
+    writers: Arc<Mutex<Writers>>,
+    stop: watch::Sender<bool>,
+    events: JoinHandle<()>,
+}
+
+impl RoomRecorder {
+    /// Start recording a session into a new directory under `parent`
+    ///
+    /// The directory is named after the room and the start time.
+    pub async fn start(
+        fs: Arc<VirtualFs>,
+        session: &StreamSession,
+        parent: &Path,
+    ) -> Result<Self, StreamError> {
+        let room = session.room().await;
+        let started = chrono::Utc::now();
+        let dir = parent.join(format!(
+            "{}-{}",
+            sanitize(&room.name),
+            started.format("%Y%m%d-%H%M%S")
+        ));
+        fs.mkdir(&dir).await?;
+
+        let snapshot = serde_json::to_vec_pretty(&room)
+            .map_err(|e| StreamError::Io(std::io::Error::other(e)))?;
+        fs.write(&dir.join("room.json"), &snapshot).await?;
+
+        let media = VdfsWriter::create(
+            fs.clone(),
+            &dir.join(format!("media.{}", media_extension(&room.source))),
+        )
+        .await?;
+        let timeline = VdfsWriter::create(fs, &dir.join("timeline.jsonl")).await?;
+        let writers = Arc::new(Mutex::new(Writers { media, timeline }));
+
+        let started = started.timestamp_millis();
+        // Replay starts from the state the room was in
+        append_entry(
+            &writers,
+            started,
+            TimelineEvent::Sync {
+                event: SyncEvent::StateSync {
+                    state: room.playback,
+                },
+            },
+        )
+        .await?;
+
+        let (stop, stopped) = watch::channel(false);
+        let events = tokio::spawn(record_events(
+            session.subscribe(),
+            stopped,
+            writers.clone(),
+            started,
+        ));
+        tracing::info!(dir = %dir.display(), "Recording stream room");
+
+        Ok(Self {
+            dir,
+            started,
+            writers,
+            stop,
+            events,
+        })
+    }
+
+    /// Directory the recording is written to
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Bytes of media recorded so far
+    pub async fn media_len(&self) -> u64 {
+        self.writers.lock().await.media.len()
+    }
+
+    /// Append media bytes as the player receives them
+    pub async fn record_media(&self, data: &[u8]) -> Result<(), StreamError> {
+        self.writers.lock().await.media.write(data).await?;
+        Ok(())
+    }
+
+    /// Add a chat message to the timeline
+    pub async fn record_chat(&self, peer_id: &str, text: &str) -> Result<(), StreamError> {
+        append_entry(
+            &self.writers,
+            self.started,
+            TimelineEvent::Chat {
+                peer_id: peer_id.to_string(),
+                text: text.to_string(),
+            },
+        )
+        .await
+    }
+
+    /// Stop recording and commit everything written
+    ///
+    /// Events the session emitted before the call are still recorded.
+    pub async fn stop(self) -> Result<Recording, StreamError> {
+        let _ = self.stop.send(true);
+        let _ = self.events.await;
+
+        let writers = Arc::try_unwrap(self.writers)
+            .map_err(|_| StreamError::NotFound("Recording still in use".to_string()))?
+            .into_inner();
+        let media = writers.media.finish().await?;
+        let timeline = writers.timeline.finish().await?;
+        tracing::info!(dir = %self.dir.display(), size = media.size, "Recording finished");
+
+        Ok(Recording {
+            dir: self.dir,
+            media,
+            timeline,
+        })
+    }
+}
+
+impl std::fmt::Debug for RoomRecorder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RoomRecorder")
+            .field("dir", &self.dir)
+            .field("started", &self.started)
+            .finish()
+    }
+}
+
+/// Write the session's events to the timeline until the recording stops
+///
+/// On stop, events already queued are written before returning.
+async fn record_events(
+    mut events: broadcast::Receiver<SyncEvent>,
+    mut stopped: watch::Receiver<bool>,
+    writers: Arc<Mutex<Writers>>,
+    started: i64,
+) {
+    loop {
+        let event = tokio::select! {
+            received = events.recv() => match received {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "Recording fell behind, events were not recorded");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            _ = stopped.changed() => break,
+        };
+        record_event(&writers, started, event).await;
+    }
+    loop {
+        match events.try_recv() {
+            Ok(event) => record_event(&writers, started, event).await,
+            Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+            Err(_) => return,
+        }
+    }
+}
+
+async fn record_event(writers: &Mutex<Writers>, started: i64, event: SyncEvent) {
+    if let Err(e) = append_entry(writers, started, TimelineEvent::Sync { event }).await {
+        tracing::warn!(error = %e, "Failed to record room event");
+    }
+}
+
+async fn append_entry(
+    writers: &Mutex<Writers>,
+    started: i64,
+    event: TimelineEvent,
+) -> Result<(), StreamError> {
+    let entry = TimelineEntry {
+        at: chrono::Utc::now().timestamp_millis() - started,
+        event,
+    };
+    let mut line =
+        serde_json::to_vec(&entry).map_err(|e| StreamError::Io(std::io::Error::other(e)))?;
+    line.push(b'\n');
+    writers.lock().await.timeline.write(&line).await?;
+    Ok(())
+}
+
+/// Keep a room name usable as a file name
+fn sanitize(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim_matches('.');
+    if name.is_empty() {
+        "room".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// File extension for the media a source plays
+fn media_extension(source: &StreamSource) -> String {
+    let name = match source {
+        StreamSource::Url { url } => url.as_str(),
+        StreamSource::LocalFile { path, .. } => path.as_str(),
+        StreamSource::P2PFile { file_id, .. } => file_id.as_str(),
+        StreamSource::Hls { .. } => return "ts".to_string(),
+        StreamSource::Dash { .. } => return "mp4".to_string(),
+        StreamSource::Terminal { .. } => return "log".to_string(),
+        StreamSource::Audio { .. } => {
+            return source
+                .current_track()
+                .map_or_else(|| "bin".to_string(), |track| media_extension(&track.source))
+        }
+        StreamSource::Renditions { renditions } => {
+            return renditions
+                .first()
+                .map_or_else(|| "bin".to_string(), |r| media_extension(&r.source))
+        }
+    };
+    let name = name.split(['?', '#']).next().unwrap_or(name);
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    match name.rsplit_once('.') {
+        Some((_, ext)) if !ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric()) => {
+            ext.to_ascii_lowercase()
+        }
+        _ => "bin".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdfs::ChunkStore;
+
+    #[tokio::test]
+    async fn records_media_and_the_event_timeline() {
+        let fs = Arc::new(VirtualFs::with_chunk_store(
+            "node".to_string(),
+            PathBuf::from("/vdfs"),
+            ChunkStore::with_chunk_size(8),
+        ));
+        let session = StreamSession::create_room(
+            "Movie night!".to_string(),
+            StreamSource::Url {
+                url: "https://example.com/films/night.MKV?t=1".to_string(),
+            },
+            "host".to_string(),
+        );
+
+        let recorder = RoomRecorder::start(fs.clone(), &session, Path::new("/vdfs/rec"))
+            .await
+            .unwrap();
+        assert!(recorder
+            .dir()
+            .to_string_lossy()
+            .starts_with("/vdfs/rec/Movie_night_-"));
+
+        recorder.record_media(b"0123456789").await.unwrap();
+        session.play().await.unwrap();
+        recorder.record_chat("guest", "hi").await.unwrap();
+        tokio::task::yield_now().await;
+        session.seek(42.0).await.unwrap();
+        recorder.record_media(b"abc").await.unwrap();
+
+        let recording = recorder.stop().await.unwrap();
+        assert!(recording.media.path.ends_with("media.mkv"));
+        assert_eq!(
+            fs.read(&recording.media.path).await.unwrap(),
+            b"0123456789abc"
+        );
+
+        let timeline = fs.read(&recording.timeline.path).await.unwrap();
+        let entries: Vec<TimelineEntry> = timeline
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert!(matches!(
+            entries[0].event,
+            TimelineEvent::Sync {
+                event: SyncEvent::StateSync { .. }
+            }
+        ));
+        assert!(entries.iter().any(|entry| matches!(
+            &entry.event,
+            TimelineEvent::Chat { peer_id, text } if peer_id == "guest" && text == "hi"
+        )));
+        assert!(entries.iter().any(|entry| matches!(
+            entry.event,
+            TimelineEvent::Sync {
+                event: SyncEvent::Seek { position }
+            } if position == 42.0
+        )));
+        assert!(fs.exists(&recording.dir.join("room.json")).await);
+    }
+}
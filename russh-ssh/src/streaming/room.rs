@@ -0,0 +1,881 @@
+//! Watch-Together Rooms over P2P
+//!
+//! Carries [`SyncEvent`]s between the members of a [`StreamRoom`] over
+//! encrypted tunnel streams.
+//!
+//! A room is a star around its host. Each guest opens one long-lived
+//! stream to the host's `stream-room` service, asks to join a room and
+//! receives the current [`StreamRoom`] in reply. From then on the host is
+//! authoritative: guests send their play, pause, seek and speed changes to
+//! the host, which applies them and relays the result to every guest,
+//! including the sender. Guests cannot change the source or the member
+//! list; the host announces members as `PeerJoined`/`PeerLeft` when their
+//! stream opens and closes.
+//!
+//! Join requests are checked against the room's
+//! [`RoomAccess`](super::access::RoomAccess) rules before the guest sees
+//! anything of the room.
+//!
+//! A room outlives its host. The host can [hand it over](RoomServer::hand_over)
+//! to a member; if the host simply disappears, the longest-connected member
+//! (see [`StreamRoom::successor`]) takes over through its own
+//! [`RoomServer`] and the others reconnect to it. The new host carries on
+//! from the last playback state it saw and pauses the room when the media
+//! was only reachable through the old host.
+//!
+//! # Requirements Coverage
+//! - Requirement 3.4: Multiplexed bidirectional streams
+//! - Requirement 4.1: End-to-end encryption between peers
+
+use super::access::{Admission, JoinCredentials, JoinRequest, RoomAccess, RoomInvite};
+use super::video::{StreamRoom, StreamSession, SyncEvent};
+use crate::error::{P2PError, StreamError};
+use crate::p2p::acl::PeerCapability;
+use crate::p2p::connection::P2PConnectionManager;
+use crate::p2p::secure::{SecureReceiver, SecureSender, SecureStream};
+use crate::p2p::stream::BiStream;
+use crate::p2p::tunnel::{open_tunnel, StreamHandler, TunnelAgent};
+use async_trait::async_trait;
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinHandle;
+
+/// Service name of the room protocol
+pub const STREAM_ROOM_SERVICE: &str = "stream-room";
+
+/// Largest frame on a room stream
+const MAX_ROOM_FRAME: usize = 256 * 1024;
+
+/// How long a guest waits for the host's approval
+pub const APPROVAL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Capacity of the join request channel
+const JOIN_REQUESTS_CAPACITY: usize = 16;
+
+/// Attempts to reach a new host after the old one went away
+const RECONNECT_ATTEMPTS: u32 = 5;
+
+/// Delay before the first attempt, grown linearly per attempt
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Messages on a room stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum RoomFrame {
+    /// Guest asks to join a room
+    Join {
+        room_id: String,
+        #[serde(default)]
+        credentials: JoinCredentials,
+    },
+    /// Host is waiting for the user to approve the guest
+    Pending,
+    /// Host admits the guest, or resynchronizes a guest that fell behind
+    Welcome { room: StreamRoom },
+    /// Host refuses the join
+    Rejected { reason: String },
+    /// A sync event
+    Event { event: SyncEvent },
+}
+
+/// Events the host accepts from guests
+fn guest_may_send(event: &SyncEvent) -> bool {
+    matches!(
+        event,
+        SyncEvent::Play { .. }
+            | SyncEvent::Pause { .. }
+            | SyncEvent::Seek { .. }
+            | SyncEvent::Speed { .. }
+            | SyncEvent::RequestSync
+    )
+}
+
+/// A room being served and who may join it
+struct HostedRoom {
+    session: Arc<StreamSession>,
+    access: RoomAccess,
+}
+
+/// A join request waiting for the host
+struct PendingJoin {
+    request: JoinRequest,
+    decision: oneshot::Sender<bool>,
+}
+
+#[derive(Default)]
+struct ServerState {
+    rooms: HashMap<String, HostedRoom>,
+    pending: Vec<PendingJoin>,
+}
+
+/// Serves the rooms hosted on this node
+///
+/// Register it with the [`TunnelAgent`] to let peers with the
+/// [`PeerCapability::Streaming`] capability join. Each room is guarded by
+/// its [`RoomAccess`] rules; guests held for approval show up in
+/// [`subscribe_join_requests`](Self::subscribe_join_requests) until the
+/// host calls [`approve`](Self::approve) or [`deny`](Self::deny).
+#[derive(Clone)]
+pub struct RoomServer {
+    state: Arc<Mutex<ServerState>>,
+    requests: broadcast::Sender<JoinRequest>,
+}
+
+impl RoomServer {
+    /// Create a server with no rooms
+    pub fn new() -> Self {
+        let (requests, _) = broadcast::channel(JOIN_REQUESTS_CAPACITY);
+        Self {
+            state: Arc::new(Mutex::new(ServerState::default())),
+            requests,
+        }
+    }
+
+    /// Accept room members on the agent
+    pub fn register(&self, agent: TunnelAgent) -> TunnelAgent {
+        agent.with_handler(
+            STREAM_ROOM_SERVICE,
+            PeerCapability::Streaming,
+            Arc::new(self.clone()),
+        )
+    }
+
+    /// Open a room created with [`StreamSession::create_room`] to anyone
+    /// who knows its ID
+    pub fn host(&self, session: Arc<StreamSession>) -> Result<(), StreamError> {
+        self.host_with_access(session, RoomAccess::open())
+    }
+
+    /// Open a room to the guests `access` admits
+    pub fn host_with_access(
+        &self,
+        session: Arc<StreamSession>,
+        access: RoomAccess,
+    ) -> Result<(), StreamError> {
+        if !session.is_host() {
+            return Err(StreamError::NotFound(
+                "Only the host can serve a room".to_string(),
+            ));
+        }
+        self.lock()
+            .rooms
+            .insert(session.session_id.clone(), HostedRoom { session, access });
+        Ok(())
+    }
+
+    /// Stop accepting guests for a room
+    ///
+    /// Pending requests are denied. Guests already in the room stay
+    /// connected until they leave.
+    pub fn close(&self, room_id: &str) -> Option<Arc<StreamSession>> {
+        let mut state = self.lock();
+        state
+            .pending
+            .retain(|pending| pending.request.room_id != room_id);
+        state.rooms.remove(room_id).map(|room| room.session)
+    }
+
+    /// Make a member the host of a served room
+    ///
+    /// The room stops being served here; its guests reconnect to `peer_id`.
+    /// Returns the local session, which then follows the room no longer.
+    pub async fn hand_over(
+        &self,
+        room_id: &str,
+        peer_id: &str,
+    ) -> Result<Arc<StreamSession>, StreamError> {
+        let session = self
+            .session(room_id)
+            .ok_or_else(|| StreamError::NotFound(format!("Room not hosted: {}", room_id)))?;
+        session.hand_over(peer_id).await?;
+        self.close(room_id);
+        tracing::info!(room_id = %room_id, peer_id = %peer_id, "Handed room over");
+        Ok(session)
+    }
+
+    /// IDs of the rooms being served
+    pub fn rooms(&self) -> Vec<String> {
+        self.lock().rooms.keys().cloned().collect()
+    }
+
+    /// Sign an invite to a hosted room
+    pub async fn create_invite(
+        &self,
+        room_id: &str,
+        ttl: Duration,
+        single_use: bool,
+    ) -> Result<RoomInvite, StreamError> {
+        let session = self
+            .session(room_id)
+            .ok_or_else(|| StreamError::NotFound(format!("Room not hosted: {}", room_id)))?;
+        let host_id = session.room().await.host_id;
+        let state = self.lock();
+        let room = state
+            .rooms
+            .get(room_id)
+            .ok_or_else(|| StreamError::NotFound(format!("Room not hosted: {}", room_id)))?;
+        Ok(room
+            .access
+            .create_invite(room_id, &host_id, ttl, single_use))
+    }
+
+    /// Subscribe to join requests that need approval
+    pub fn subscribe_join_requests(&self) -> broadcast::Receiver<JoinRequest> {
+        self.requests.subscribe()
+    }
+
+    /// Join requests waiting for approval
+    pub fn pending_requests(&self) -> Vec<JoinRequest> {
+        self.lock()
+            .pending
+            .iter()
+            .map(|pending| pending.request.clone())
+            .collect()
+    }
+
+    /// Admit a waiting guest; `false` if there is no such request
+    pub fn approve(&self, room_id: &str, peer_id: &str) -> bool {
+        self.decide(room_id, peer_id, true)
+    }
+
+    /// Turn a waiting guest away; `false` if there is no such request
+    pub fn deny(&self, room_id: &str, peer_id: &str) -> bool {
+        self.decide(room_id, peer_id, false)
+    }
+
+    fn decide(&self, room_id: &str, peer_id: &str, admit: bool) -> bool {
+        let mut state = self.lock();
+        let Some(index) = state.pending.iter().position(|pending| {
+            pending.request.room_id == room_id && pending.request.peer_id == peer_id
+        }) else {
+            return false;
+        };
+        state.pending.remove(index).decision.send(admit).is_ok()
+    }
+
+    fn session(&self, room_id: &str) -> Option<Arc<StreamSession>> {
+        self.lock()
+            .rooms
+            .get(room_id)
+            .map(|room| room.session.clone())
+    }
+
+    /// Check a join request against the room's rules
+    fn admission(
+        &self,
+        room_id: &str,
+        peer_id: &str,
+        credentials: &JoinCredentials,
+    ) -> Option<(Arc<StreamSession>, Admission)> {
+        let mut state = self.lock();
+        let room = state.rooms.get_mut(room_id)?;
+        let admission = if room.access.is_member(peer_id) {
+            Admission::Admit
+        } else {
+            room.access.check(room_id, credentials)
+        };
+        Some((room.session.clone(), admission))
+    }
+
+    /// Hold a guest until the host decides or the request times out
+    async fn await_approval(&self, room_id: &str, peer_id: &str) -> bool {
+        let request = JoinRequest {
+            room_id: room_id.to_string(),
+            peer_id: peer_id.to_string(),
+            requested_at: chrono::Utc::now(),
+        };
+        let (decision, decided) = oneshot::channel();
+        {
+            let mut state = self.lock();
+            // A repeated request replaces the earlier one
+            state.pending.retain(|pending| {
+                pending.request.room_id != room_id || pending.request.peer_id != peer_id
+            });
+            state.pending.push(PendingJoin {
+                request: request.clone(),
+                decision,
+            });
+        }
+        let _ = self.requests.send(request);
+
+        match tokio::time::timeout(APPROVAL_TIMEOUT, decided).await {
+            Ok(decision) => decision.unwrap_or(false),
+            Err(_) => {
+                self.decide(room_id, peer_id, false);
+                false
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ServerState> {
+        self.state.lock().unwrap_or_else(|poisoned| {
+            tracing::warn!("Room server lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+}
+
+impl Default for RoomServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl StreamHandler for RoomServer {
+    async fn handle(&self, peer_id: NodeId, stream: BiStream) -> Result<(), P2PError> {
+        let mut stream = SecureStream::accept(stream).await?;
+        let (room_id, credentials) = match stream.recv_json(MAX_ROOM_FRAME).await? {
+            RoomFrame::Join {
+                room_id,
+                credentials,
+            } => (room_id, credentials),
+            _ => return Err(P2PError::Stream("Expected a room join request".to_string())),
+        };
+        let peer = peer_id.to_string();
+
+        let session = match self.admission(&room_id, &peer, &credentials) {
+            None => return reject(&mut stream, format!("No such room: {}", room_id)).await,
+            Some((session, Admission::Admit)) => session,
+            Some((_, Admission::Deny(reason))) => {
+                tracing::info!(room_id = %room_id, peer_id = %peer, reason = %reason, "Refused room join");
+                return reject(&mut stream, reason).await;
+            }
+            Some((session, Admission::NeedsApproval)) => {
+                stream.send_json(&RoomFrame::Pending).await?;
+                if !self.await_approval(&room_id, &peer).await {
+                    return reject(&mut stream, "The host declined the request".to_string()).await;
+                }
+                session
+            }
+        };
+
+        // Subscribe before taking the snapshot so nothing falls in between
+        let events = session.peer_events();
+        apply(
+            &session,
+            SyncEvent::PeerJoined {
+                peer_id: peer.clone(),
+            },
+        )
+        .await;
+        stream
+            .send_json(&RoomFrame::Welcome {
+                room: session.room().await,
+            })
+            .await?;
+        tracing::info!(room_id = %room_id, peer_id = %peer, "Peer joined room");
+
+        let (sender, mut receiver) = stream.split();
+        let inbound = async {
+            while let Some(event) = next_event(&mut receiver, &session).await {
+                if guest_may_send(&event) {
+                    apply(&session, event).await;
+                } else {
+                    tracing::debug!(peer_id = %peer, ?event, "Ignoring event only the host may send");
+                }
+            }
+        };
+        tokio::select! {
+            _ = inbound => {}
+            _ = forward(events, sender, Some(&session)) => {}
+        }
+
+        apply(
+            &session,
+            SyncEvent::PeerLeft {
+                peer_id: peer.clone(),
+            },
+        )
+        .await;
+        tracing::info!(room_id = %room_id, peer_id = %peer, "Peer left room");
+        Ok(())
+    }
+}
+
+/// Tell a guest why it was not admitted and close the stream
+async fn reject(stream: &mut SecureStream, reason: String) -> Result<(), P2PError> {
+    stream.send_json(&RoomFrame::Rejected { reason }).await?;
+    stream.finish().await
+}
+
+/// A guest's membership in a remote room
+///
+/// The local [`StreamSession`] follows the host; its actions are sent to
+/// the host. When the host hands the room over or goes away, the link
+/// follows the new host, or takes the room over if this member is next in
+/// line and has a [`RoomServer`] (see [`with_server`](Self::with_server)).
+/// Dropping the link leaves the room.
+pub struct RoomLink {
+    session: Arc<StreamSession>,
+    server: watch::Sender<Option<RoomServer>>,
+    task: JoinHandle<()>,
+}
+
+impl RoomLink {
+    /// Join an open room served by `host`
+    pub async fn join(
+        manager: Arc<P2PConnectionManager>,
+        host: NodeId,
+        room_id: &str,
+    ) -> Result<Self, StreamError> {
+        Self::join_with(manager, host, room_id, JoinCredentials::default()).await
+    }
+
+    /// Join the room an invite is for
+    pub async fn join_invite(
+        manager: Arc<P2PConnectionManager>,
+        invite: &RoomInvite,
+    ) -> Result<Self, StreamError> {
+        let host = invite
+            .host_id()
+            .parse()
+            .map_err(|_| StreamError::AccessDenied("invite names an invalid host".to_string()))?;
+        let credentials = JoinCredentials::default().with_invite(invite);
+        Self::join_with(manager, host, invite.room_id(), credentials).await
+    }
+
+    /// Join a room served by `host`, presenting an invite or password
+    ///
+    /// Waits while the host approves the request, if the room requires it.
+    pub async fn join_with(
+        manager: Arc<P2PConnectionManager>,
+        host: NodeId,
+        room_id: &str,
+        credentials: JoinCredentials,
+    ) -> Result<Self, StreamError> {
+        let (stream, room) = connect(&manager, host, room_id, credentials).await?;
+        let session = Arc::new(StreamSession::join_room(room).with_p2p(manager.clone()));
+        let (server, takeover) = watch::channel(None);
+        let task = tokio::spawn(follow(manager, session.clone(), stream, takeover));
+        Ok(Self {
+            session,
+            server,
+            task,
+        })
+    }
+
+    /// Take the room over through `server` when this member is next in line
+    /// after the host goes away
+    ///
+    /// Without a server the link only follows a new host.
+    pub fn with_server(self, server: RoomServer) -> Self {
+        self.server.send_replace(Some(server));
+        self
+    }
+
+    /// Get the session that mirrors the room
+    pub fn session(&self) -> &Arc<StreamSession> {
+        &self.session
+    }
+
+    /// Check whether the link to the host is still up
+    ///
+    /// Also `false` once this member took the room over; the session then
+    /// [is the host](StreamSession::is_host).
+    pub fn is_connected(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Leave the room; same as dropping the link
+    pub fn leave(self) {}
+}
+
+impl Drop for RoomLink {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Ask `host` to admit us to a room and wait for the snapshot
+async fn connect(
+    manager: &P2PConnectionManager,
+    host: NodeId,
+    room_id: &str,
+    credentials: JoinCredentials,
+) -> Result<(SecureStream, StreamRoom), StreamError> {
+    let stream = open_tunnel(manager, host, STREAM_ROOM_SERVICE).await?;
+    let mut stream = SecureStream::initiate(stream).await?;
+    stream
+        .send_json(&RoomFrame::Join {
+            room_id: room_id.to_string(),
+            credentials,
+        })
+        .await?;
+    loop {
+        match stream.recv_json(MAX_ROOM_FRAME).await? {
+            RoomFrame::Welcome { room } => return Ok((stream, room)),
+            RoomFrame::Pending => {
+                tracing::info!(room_id = %room_id, "Waiting for the host to approve the join")
+            }
+            RoomFrame::Rejected { reason } => return Err(StreamError::AccessDenied(reason)),
+            _ => return Err(P2PError::Stream("Unexpected reply to room join".to_string()).into()),
+        }
+    }
+}
+
+/// Follow the room's host for as long as the room lives
+async fn follow(
+    manager: Arc<P2PConnectionManager>,
+    session: Arc<StreamSession>,
+    mut stream: SecureStream,
+    takeover: watch::Receiver<Option<RoomServer>>,
+) {
+    let me = manager.endpoint().node_id().to_string();
+    loop {
+        let host = session.room().await.host_id;
+        let handed_over = relay(&session, stream).await;
+        tracing::info!(room_id = %session.session_id, handed_over, "Disconnected from room host");
+
+        // A hand-over names the new host; otherwise every member tries the
+        // same members in join order
+        let room = session.room().await;
+        let candidates = if handed_over {
+            vec![room.host_id]
+        } else {
+            room.peers
+        };
+        let mut next = None;
+        for candidate in candidates {
+            if candidate == me {
+                let server = takeover.borrow().clone();
+                match server {
+                    Some(server) => take_over(&server, &session, &me, &host).await,
+                    None => tracing::warn!(
+                        room_id = %session.session_id,
+                        "Next in line to host the room but no room server is set"
+                    ),
+                }
+                return;
+            }
+            if let Some(found) = rejoin(&manager, &session, &candidate).await {
+                next = Some(found);
+                break;
+            }
+        }
+        match next {
+            Some(found) => stream = found,
+            None => {
+                tracing::warn!(room_id = %session.session_id, "Could not reach a new room host");
+                return;
+            }
+        }
+    }
+}
+
+/// Exchange events with the host until the stream ends
+///
+/// Returns `true` if the host handed the room over.
+async fn relay(session: &StreamSession, stream: SecureStream) -> bool {
+    let events = session.peer_events();
+    let (sender, mut receiver) = stream.split();
+    let inbound = async {
+        while let Some(event) = next_event(&mut receiver, session).await {
+            let handed_over = matches!(event, SyncEvent::HostChanged { .. });
+            apply(session, event).await;
+            if handed_over {
+                return true;
+            }
+        }
+        false
+    };
+    tokio::select! {
+        handed_over = inbound => handed_over,
+        _ = forward(events, sender, None) => false,
+    }
+}
+
+/// Start serving a room whose host went away
+async fn take_over(server: &RoomServer, session: &Arc<StreamSession>, me: &str, previous: &str) {
+    let members = session.take_over(me, previous).await;
+    let access = RoomAccess::open().invite_only().with_members(members);
+    match server.host_with_access(session.clone(), access) {
+        Ok(()) => tracing::info!(room_id = %session.session_id, "Took over as room host"),
+        Err(e) => {
+            tracing::warn!(room_id = %session.session_id, error = %e, "Failed to take over room")
+        }
+    }
+}
+
+/// Reconnect to a member that may have become the host
+async fn rejoin(
+    manager: &P2PConnectionManager,
+    session: &StreamSession,
+    candidate: &str,
+) -> Option<SecureStream> {
+    let host: NodeId = candidate.parse().ok()?;
+    for attempt in 1..=RECONNECT_ATTEMPTS {
+        tokio::time::sleep(RECONNECT_DELAY * attempt).await;
+        match connect(
+            manager,
+            host,
+            &session.session_id,
+            JoinCredentials::default(),
+        )
+        .await
+        {
+            Ok((stream, room)) => {
+                tracing::info!(room_id = %session.session_id, host = %candidate, "Rejoined room under new host");
+                session.reset_room(room).await;
+                return Some(stream);
+            }
+            // The candidate may still be taking over, so refusals are retried too
+            Err(e) => {
+                tracing::debug!(host = %candidate, attempt, error = %e, "Candidate host not reachable")
+            }
+        }
+    }
+    None
+}
+
+/// Apply an event from the other side of a room stream
+async fn apply(session: &StreamSession, event: SyncEvent) {
+    if let Err(e) = session.handle_event(event).await {
+        tracing::warn!(room_id = %session.session_id, error = %e, "Failed to apply room event");
+    }
+}
+
+/// Read the next event from a room stream
+///
+/// Room snapshots are applied in place. Returns `None` once the stream
+/// closes or breaks.
+async fn next_event(receiver: &mut SecureReceiver, session: &StreamSession) -> Option<SyncEvent> {
+    loop {
+        match receiver.recv_json(MAX_ROOM_FRAME).await {
+            Ok(RoomFrame::Event { event }) => return Some(event),
+            Ok(RoomFrame::Welcome { room }) if !session.is_host() => session.reset_room(room).await,
+            Ok(frame) => tracing::debug!(?frame, "Ignoring unexpected room frame"),
+            Err(e) => {
+                tracing::debug!(room_id = %session.session_id, error = %e, "Room stream closed");
+                return None;
+            }
+        }
+    }
+}
+
+/// Send a session's outgoing events until either side goes away
+///
+/// A host whose guest fell behind sends it a fresh snapshot instead of the
+/// events it missed.
+async fn forward(
+    mut events: broadcast::Receiver<SyncEvent>,
+    mut sender: SecureSender,
+    host: Option<&StreamSession>,
+) {
+    loop {
+        let frame = match events.recv().await {
+            Ok(event) => RoomFrame::Event { event },
+            Err(RecvError::Lagged(missed)) => match host {
+                Some(session) => {
+                    tracing::warn!(missed, "Room member fell behind, resending room state");
+                    RoomFrame::Welcome {
+                        room: session.room().await,
+                    }
+                }
+                None => continue,
+            },
+            Err(RecvError::Closed) => break,
+        };
+        if sender.send_json(&frame).await.is_err() {
+            return;
+        }
+    }
+    let _ = sender.finish();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaming::video::StreamSource;
+
+    fn source() -> StreamSource {
+        StreamSource::Url {
+            url: "https://example.com/video.mp4".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn host_relays_what_it_applies() {
+        let host = StreamSession::create_room("Movie".to_string(), source(), "host".to_string());
+        let mut to_guests = host.peer_events();
+
+        host.handle_event(SyncEvent::PeerJoined {
+            peer_id: "guest".to_string(),
+        })
+        .await
+        .unwrap();
+        host.handle_event(SyncEvent::Seek { position: 42.0 })
+            .await
+            .unwrap();
+        host.handle_event(SyncEvent::RequestSync).await.unwrap();
+
+        assert!(matches!(
+            to_guests.try_recv().unwrap(),
+            SyncEvent::PeerJoined { .. }
+        ));
+        assert!(matches!(
+            to_guests.try_recv().unwrap(),
+            SyncEvent::Seek { position } if position == 42.0
+        ));
+        // A sync request is answered with the host's state, not relayed
+        assert!(matches!(
+            to_guests.try_recv().unwrap(),
+            SyncEvent::StateSync { state } if state.position == 42.0
+        ));
+        assert!(to_guests.try_recv().is_err());
+        assert_eq!(host.room().await.peers, vec!["guest".to_string()]);
+
+        // Guests only send their own actions to the host
+        let guest = StreamSession::join_room(host.room().await);
+        let mut to_host = guest.peer_events();
+        guest
+            .handle_event(SyncEvent::Pause { position: 42.0 })
+            .await
+            .unwrap();
+        assert!(to_host.try_recv().is_err());
+        guest.play().await.unwrap();
+        assert!(matches!(
+            to_host.try_recv().unwrap(),
+            SyncEvent::Play { .. }
+        ));
+        assert!(guest.change_source(source()).await.is_err());
+    }
+
+    #[test]
+    fn frames_roundtrip_and_filter_guest_events() {
+        let frame = RoomFrame::Event {
+            event: SyncEvent::Speed { speed: 1.5 },
+        };
+        let json = serde_json::to_string(&frame).unwrap();
+        assert!(json.contains("\"kind\":\"event\""));
+        match serde_json::from_str(&json).unwrap() {
+            RoomFrame::Event {
+                event: SyncEvent::Speed { speed },
+            } => assert_eq!(speed, 1.5),
+            other => panic!("unexpected frame: {:?}", other),
+        }
+
+        assert!(guest_may_send(&SyncEvent::RequestSync));
+        assert!(!guest_may_send(&SyncEvent::SourceChanged {
+            source: source()
+        }));
+        assert!(!guest_may_send(&SyncEvent::PeerLeft {
+            peer_id: "host".to_string()
+        }));
+    }
+
+    #[tokio::test]
+    async fn approval_holds_guests_until_the_host_decides() {
+        let server = RoomServer::new();
+        let session = Arc::new(StreamSession::create_room(
+            "Movie".to_string(),
+            source(),
+            "host".to_string(),
+        ));
+        let room_id = session.session_id.clone();
+        let guest = StreamSession::join_room(session.room().await);
+        assert!(server.host(Arc::new(guest)).is_err());
+        server
+            .host_with_access(session, RoomAccess::open().with_approval())
+            .unwrap();
+
+        let mut requests = server.subscribe_join_requests();
+        let waiting = tokio::spawn({
+            let server = server.clone();
+            let room_id = room_id.clone();
+            async move { server.await_approval(&room_id, "guest").await }
+        });
+        let request = requests.recv().await.unwrap();
+        assert_eq!(request.peer_id, "guest");
+        assert_eq!(server.pending_requests(), vec![request]);
+        assert!(!server.approve(&room_id, "someone-else"));
+        assert!(server.approve(&room_id, "guest"));
+        assert!(waiting.await.unwrap());
+        assert!(server.pending_requests().is_empty());
+
+        let waiting = tokio::spawn({
+            let server = server.clone();
+            let room_id = room_id.clone();
+            async move { server.await_approval(&room_id, "guest").await }
+        });
+        requests.recv().await.unwrap();
+        server.close(&room_id);
+        assert!(!waiting.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn hand_over_moves_authority_and_flags_host_only_media() {
+        let media = StreamSource::LocalFile {
+            path: "/home/host/movie.mkv".to_string(),
+            size: 1024,
+        };
+        let host = Arc::new(StreamSession::create_room(
+            "Movie".to_string(),
+            media,
+            "host".to_string(),
+        ));
+        let room_id = host.session_id.clone();
+        for peer in ["alice", "bob"] {
+            host.handle_event(SyncEvent::PeerJoined {
+                peer_id: peer.to_string(),
+            })
+            .await
+            .unwrap();
+        }
+        host.seek(42.0).await.unwrap();
+        host.play().await.unwrap();
+
+        let alice = Arc::new(StreamSession::join_room(host.room().await));
+        assert_eq!(alice.room().await.successor(), Some("alice"));
+
+        let server = RoomServer::new();
+        server.host(host.clone()).unwrap();
+        let mut to_guests = host.peer_events();
+        assert!(server.hand_over(&room_id, "carol").await.is_err());
+        server.hand_over(&room_id, "alice").await.unwrap();
+        assert!(!host.is_host());
+        assert!(server.rooms().is_empty());
+
+        // The new host gets the last state, then learns it is in charge
+        assert!(matches!(
+            to_guests.try_recv().unwrap(),
+            SyncEvent::StateSync { state } if state.playing && state.position == 42.0
+        ));
+        let handed_over = to_guests.try_recv().unwrap();
+        assert!(matches!(&handed_over, SyncEvent::HostChanged { host_id } if host_id == "alice"));
+        alice.handle_event(handed_over).await.unwrap();
+        assert_eq!(alice.room().await.peers, vec!["bob".to_string()]);
+
+        let mut events = alice.subscribe();
+        let successor = RoomServer::new();
+        take_over(&successor, &alice, "alice", "host").await;
+        assert!(alice.is_host());
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            SyncEvent::HostChanged { .. }
+        ));
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            SyncEvent::SourceUnavailable { .. }
+        ));
+        let state = alice.playback_state().await;
+        assert!(!state.playing);
+        assert!(state.position >= 42.0);
+
+        // Former members follow without credentials, strangers need an invite
+        let open = JoinCredentials::default();
+        assert!(matches!(
+            successor.admission(&room_id, "bob", &open),
+            Some((_, Admission::Admit))
+        ));
+        assert!(matches!(
+            successor.admission(&room_id, "eve", &open),
+            Some((_, Admission::Deny(_)))
+        ));
+    }
+}
@@ -0,0 +1,599 @@
+//! Serving Local Media to Room Members
+//!
+//! Turns a host's [`StreamSource::LocalFile`] into a
+//! [`StreamSource::P2PFile`] that peers can stream and seek in.
+//!
+//! The host registers a [`MediaServer`] on its tunnel agent and shares
+//! files with it. A peer opens one encrypted stream to the host's
+//! `stream-media` service, names the file, and then asks for byte ranges;
+//! the host answers with the fixed-size chunks that cover each range. Chunks
+//! read from disk are kept in a VDFS [`ChunkStore`], so members watching
+//! the same part of a file are served from memory. When the store is full
+//! chunks are read from disk each time.
+//!
+//! On the peer side a [`MediaReader`] reads ranges and can fill an
+//! [`AdaptiveBuffer`] from its prefetch window.
+//!
+//! # Requirements Coverage
+//! - Requirement 6.1: Seeking support
+//! - Requirement 6.2: Adaptive buffering
+
+use super::buffer::AdaptiveBuffer;
+use super::metrics::MetricsRecorder;
+use super::video::StreamSource;
+use crate::error::{P2PError, StreamError};
+use crate::p2p::acl::PeerCapability;
+use crate::p2p::connection::P2PConnectionManager;
+use crate::p2p::secure::SecureStream;
+use crate::p2p::stream::BiStream;
+use crate::p2p::tunnel::{open_tunnel, StreamHandler, TunnelAgent};
+use crate::vdfs::chunk::{Chunk, ChunkId, ChunkStore};
+use async_trait::async_trait;
+use bytes::Bytes;
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Service name of the media protocol
+pub const MEDIA_SERVICE: &str = "stream-media";
+
+/// Default chunk size of shared media
+pub const DEFAULT_MEDIA_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Largest chunk size a reader accepts
+const MAX_MEDIA_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Most bytes one read request may ask for
+pub const MAX_MEDIA_READ: u64 = 8 * 1024 * 1024;
+
+/// Default limit of the chunk cache
+const DEFAULT_CACHE_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Largest control frame
+const MAX_CONTROL_FRAME: usize = 64 * 1024;
+
+/// Room for frame encoding around a chunk
+const FRAME_OVERHEAD: usize = 1024;
+
+/// Requests from a peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum MediaRequest {
+    /// Start reading a shared file
+    Open { file_id: String },
+    /// Send the chunks covering `len` bytes from `offset`
+    Read { offset: u64, len: u64 },
+}
+
+/// Replies from the host
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum MediaReply {
+    /// The file is available
+    Opened { size: u64, chunk_size: usize },
+    /// `count` chunks starting at chunk `first` follow as binary frames
+    Chunks { first: u64, count: u64 },
+    /// The request failed; the stream stays usable
+    Error { reason: String },
+}
+
+/// A file the host shares
+#[derive(Debug, Clone)]
+struct SharedFile {
+    path: PathBuf,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+/// Serves shared local files to peers
+///
+/// Register it with the [`TunnelAgent`] to let peers with the
+/// [`PeerCapability::Streaming`] capability read shared files.
+#[derive(Clone)]
+pub struct MediaServer {
+    files: Arc<RwLock<HashMap<String, SharedFile>>>,
+    store: Arc<ChunkStore>,
+    /// Where each (file, chunk index) lives in the store
+    index: Arc<RwLock<HashMap<(String, u64), ChunkId>>>,
+    chunk_size: usize,
+}
+
+impl MediaServer {
+    /// Create a server with its own bounded chunk cache
+    pub fn new() -> Self {
+        let store = ChunkStore::with_chunk_size(DEFAULT_MEDIA_CHUNK_SIZE)
+            .with_max_bytes(DEFAULT_CACHE_BYTES);
+        Self::with_store(Arc::new(store))
+    }
+
+    /// Cache chunks in an existing store, using its chunk size
+    pub fn with_store(store: Arc<ChunkStore>) -> Self {
+        let chunk_size = store.chunk_size().clamp(1, MAX_MEDIA_CHUNK_SIZE);
+        Self {
+            files: Arc::new(RwLock::new(HashMap::new())),
+            store,
+            index: Arc::new(RwLock::new(HashMap::new())),
+            chunk_size,
+        }
+    }
+
+    /// Serve shared files to peers
+    pub fn register(&self, agent: TunnelAgent) -> TunnelAgent {
+        agent.with_handler(
+            MEDIA_SERVICE,
+            PeerCapability::Streaming,
+            Arc::new(self.clone()),
+        )
+    }
+
+    /// Get the size of the chunks files are served in
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Share a local file, returning the source peers play it from
+    ///
+    /// `host_id` is this node's ID. The file ID keeps the file name so
+    /// peers can tell audio from video.
+    pub async fn share(
+        &self,
+        path: impl AsRef<Path>,
+        host_id: &str,
+    ) -> Result<StreamSource, StreamError> {
+        let path = tokio::fs::canonicalize(path.as_ref()).await?;
+        let metadata = tokio::fs::metadata(&path).await?;
+        if !metadata.is_file() {
+            return Err(StreamError::NotFound(format!(
+                "Not a file: {}",
+                path.display()
+            )));
+        }
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let file_id = format!("{}/{}", Uuid::new_v4().simple(), name);
+        let size = metadata.len();
+
+        self.files.write().await.insert(
+            file_id.clone(),
+            SharedFile {
+                path,
+                size,
+                modified: metadata.modified().ok(),
+            },
+        );
+        tracing::info!(file_id = %file_id, size, "Sharing media file");
+        Ok(StreamSource::P2PFile {
+            host_id: host_id.to_string(),
+            file_id,
+            size,
+        })
+    }
+
+    /// Share every local file a source plays
+    ///
+    /// Local files, including audio tracks and renditions, become P2P
+    /// files served by this node; other sources are returned unchanged.
+    pub async fn share_source(
+        &self,
+        source: &StreamSource,
+        host_id: &str,
+    ) -> Result<StreamSource, StreamError> {
+        let mut source = source.clone();
+        match &mut source {
+            StreamSource::LocalFile { path, .. } => {
+                return self.share(path.as_str(), host_id).await
+            }
+            StreamSource::Audio { tracks, .. } => {
+                for track in tracks {
+                    if let StreamSource::LocalFile { path, .. } = &track.source {
+                        track.source = self.share(path.as_str(), host_id).await?;
+                    }
+                }
+            }
+            StreamSource::Renditions { renditions } => {
+                for rendition in renditions {
+                    if let StreamSource::LocalFile { path, .. } = &rendition.source {
+                        rendition.source = self.share(path.as_str(), host_id).await?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(source)
+    }
+
+    /// Stop sharing a file; `false` if it was not shared
+    pub async fn unshare(&self, file_id: &str) -> bool {
+        let removed = self.files.write().await.remove(file_id).is_some();
+        self.forget_chunks(file_id).await;
+        removed
+    }
+
+    /// IDs of the files being shared
+    pub async fn shared_files(&self) -> Vec<String> {
+        self.files.read().await.keys().cloned().collect()
+    }
+
+    /// Look a file up, noticing when it changed on disk since it was shared
+    async fn open(&self, file_id: &str) -> Result<SharedFile, StreamError> {
+        let shared = self
+            .files
+            .read()
+            .await
+            .get(file_id)
+            .cloned()
+            .ok_or_else(|| StreamError::NotFound(format!("File not shared: {}", file_id)))?;
+
+        let metadata = tokio::fs::metadata(&shared.path).await?;
+        let modified = metadata.modified().ok();
+        if metadata.len() == shared.size && modified == shared.modified {
+            return Ok(shared);
+        }
+
+        tracing::info!(file_id = %file_id, "Shared file changed on disk, dropping cached chunks");
+        self.forget_chunks(file_id).await;
+        let updated = SharedFile {
+            path: shared.path,
+            size: metadata.len(),
+            modified,
+        };
+        if let Some(entry) = self.files.write().await.get_mut(file_id) {
+            *entry = updated.clone();
+        }
+        Ok(updated)
+    }
+
+    /// Get one chunk of a file, from the store or from disk
+    async fn read_chunk(
+        &self,
+        file_id: &str,
+        file: &mut tokio::fs::File,
+        size: u64,
+        index: u64,
+    ) -> Result<Vec<u8>, StreamError> {
+        let key = (file_id.to_string(), index);
+        let cached = self.index.read().await.get(&key).copied();
+        if let Some(id) = cached {
+            if let Ok(chunk) = self.store.get(&id).await {
+                return Ok(chunk.data);
+            }
+        }
+
+        let chunk_size = self.chunk_size as u64;
+        let offset = index * chunk_size;
+        let len = size.saturating_sub(offset).min(chunk_size) as usize;
+        let mut data = vec![0u8; len];
+        file.seek(SeekFrom::Start(offset)).await?;
+        file.read_exact(&mut data).await?;
+
+        let chunk = Chunk::new(data);
+        let id = chunk.id;
+        match self.store.store(chunk.clone()).await {
+            Ok(_) => {
+                self.index.write().await.insert(key, id);
+            }
+            Err(e) => tracing::debug!(error = %e, "Not caching media chunk"),
+        }
+        Ok(chunk.data)
+    }
+
+    /// Drop a file's chunks from the index
+    ///
+    /// The chunks themselves stay in the store; other files may share them.
+    async fn forget_chunks(&self, file_id: &str) {
+        self.index.write().await.retain(|(id, _), _| id != file_id);
+    }
+}
+
+impl Default for MediaServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl StreamHandler for MediaServer {
+    async fn handle(&self, peer_id: NodeId, stream: BiStream) -> Result<(), P2PError> {
+        let mut stream = SecureStream::accept(stream).await?;
+        let mut open: Option<(String, SharedFile, tokio::fs::File)> = None;
+
+        loop {
+            let request = match stream.recv_json(MAX_CONTROL_FRAME).await {
+                Ok(request) => request,
+                // The peer is done reading
+                Err(_) => return Ok(()),
+            };
+            match request {
+                MediaRequest::Open { file_id } => {
+                    let opened = match self.open(&file_id).await {
+                        Ok(shared) => tokio::fs::File::open(&shared.path)
+                            .await
+                            .map(|file| (shared, file))
+                            .map_err(StreamError::from),
+                        Err(e) => Err(e),
+                    };
+                    match opened {
+                        Ok((shared, file)) => {
+                            tracing::debug!(peer_id = %peer_id, file_id = %file_id, "Peer opened shared media");
+                            stream
+                                .send_json(&MediaReply::Opened {
+                                    size: shared.size,
+                                    chunk_size: self.chunk_size,
+                                })
+                                .await?;
+                            open = Some((file_id, shared, file));
+                        }
+                        Err(e) => {
+                            open = None;
+                            stream
+                                .send_json(&MediaReply::Error {
+                                    reason: e.to_string(),
+                                })
+                                .await?;
+                        }
+                    }
+                }
+                MediaRequest::Read { offset, len } => {
+                    let Some((file_id, shared, file)) = open.as_mut() else {
+                        stream
+                            .send_json(&MediaReply::Error {
+                                reason: "no file open".to_string(),
+                            })
+                            .await?;
+                        continue;
+                    };
+                    let Some(chunks) = chunk_span(offset, len, shared.size, self.chunk_size) else {
+                        stream
+                            .send_json(&MediaReply::Error {
+                                reason: format!(
+                                    "range {}+{} outside file of {} bytes",
+                                    offset, len, shared.size
+                                ),
+                            })
+                            .await?;
+                        continue;
+                    };
+                    stream
+                        .send_json(&MediaReply::Chunks {
+                            first: chunks.start,
+                            count: chunks.end - chunks.start,
+                        })
+                        .await?;
+                    for index in chunks {
+                        let data = self
+                            .read_chunk(file_id, file, shared.size, index)
+                            .await
+                            .map_err(|e| P2PError::Stream(e.to_string()))?;
+                        stream.send(&data).await?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Chunk indices covering a byte range, or `None` if it is not in the file
+fn chunk_span(offset: u64, len: u64, size: u64, chunk_size: usize) -> Option<std::ops::Range<u64>> {
+    if len == 0 || len > MAX_MEDIA_READ || offset >= size {
+        return None;
+    }
+    let chunk_size = chunk_size as u64;
+    let end = offset.saturating_add(len).min(size);
+    Some(offset / chunk_size..end.div_ceil(chunk_size))
+}
+
+/// Reads a host's shared file
+pub struct MediaReader {
+    stream: SecureStream,
+    file_id: String,
+    size: u64,
+    chunk_size: usize,
+    metrics: Option<MetricsRecorder>,
+}
+
+impl MediaReader {
+    /// Open a [`StreamSource::P2PFile`] on its host
+    pub async fn open(
+        manager: &P2PConnectionManager,
+        source: &StreamSource,
+    ) -> Result<Self, StreamError> {
+        let StreamSource::P2PFile {
+            host_id, file_id, ..
+        } = source
+        else {
+            return Err(StreamError::NotFound(
+                "Source is not a P2P file".to_string(),
+            ));
+        };
+        let host: NodeId = host_id
+            .parse()
+            .map_err(|_| StreamError::NotFound(format!("Invalid host ID: {}", host_id)))?;
+
+        let stream = open_tunnel(manager, host, MEDIA_SERVICE).await?;
+        let mut stream = SecureStream::initiate(stream).await?;
+        stream
+            .send_json(&MediaRequest::Open {
+                file_id: file_id.clone(),
+            })
+            .await?;
+        match stream.recv_json(MAX_CONTROL_FRAME).await? {
+            MediaReply::Opened { size, chunk_size }
+                if (1..=MAX_MEDIA_CHUNK_SIZE).contains(&chunk_size) =>
+            {
+                Ok(Self {
+                    stream,
+                    file_id: file_id.clone(),
+                    size,
+                    chunk_size,
+                    metrics: None,
+                })
+            }
+            MediaReply::Error { reason } => Err(StreamError::NotFound(reason)),
+            other => Err(P2PError::Stream(format!("Unexpected media reply: {:?}", other)).into()),
+        }
+    }
+
+    /// Count the bytes read in a session's metrics, under the file ID
+    pub fn with_metrics(mut self, metrics: MetricsRecorder) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Get the ID of the file being read
+    pub fn file_id(&self) -> &str {
+        &self.file_id
+    }
+
+    /// Get the file size
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Read up to `len` bytes at `offset`
+    ///
+    /// Returns fewer bytes at the end of the file and an error past it.
+    /// Reads longer than [`MAX_MEDIA_READ`] are cut short.
+    pub async fn read_at(&mut self, offset: u64, len: u64) -> Result<Bytes, StreamError> {
+        if offset >= self.size {
+            return Err(StreamError::SeekOutOfBounds {
+                position: offset,
+                size: self.size,
+            });
+        }
+        let len = len.min(MAX_MEDIA_READ).min(self.size - offset);
+        self.stream
+            .send_json(&MediaRequest::Read { offset, len })
+            .await?;
+        let (first, count) = match self.stream.recv_json(MAX_CONTROL_FRAME).await? {
+            MediaReply::Chunks { first, count } => (first, count),
+            MediaReply::Error { reason } => return Err(StreamError::NotFound(reason)),
+            other => {
+                return Err(P2PError::Stream(format!("Unexpected media reply: {:?}", other)).into())
+            }
+        };
+
+        let chunk_size = self.chunk_size as u64;
+        let skip = offset
+            .checked_sub(first * chunk_size)
+            .filter(|skip| *skip < chunk_size && count <= (skip + len).div_ceil(chunk_size))
+            .ok_or_else(|| P2PError::Stream("Host sent the wrong chunks".to_string()))?;
+        let mut data = Vec::with_capacity((count * chunk_size) as usize);
+        for _ in 0..count {
+            let chunk = self.stream.recv(self.chunk_size + FRAME_OVERHEAD).await?;
+            data.extend_from_slice(&chunk);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.record_fetch(&self.file_id, data.len() as u64);
+        }
+        let start = (skip as usize).min(data.len());
+        let end = (start + len as usize).min(data.len());
+        Ok(Bytes::from(data).slice(start..end))
+    }
+
+    /// Fetch the next range a buffer wants to prefetch
+    ///
+    /// Returns the number of bytes added, 0 when the buffer's prefetch
+    /// window is already full.
+    pub async fn fill(&mut self, buffer: &mut AdaptiveBuffer) -> Result<usize, StreamError> {
+        let Some(range) = buffer.prefetch_range() else {
+            return Ok(0);
+        };
+        if range.start >= self.size {
+            return Ok(0);
+        }
+        let data = self.read_at(range.start, range.end - range.start).await?;
+        let len = data.len();
+        buffer.add_data(range.start, data);
+        Ok(len)
+    }
+
+    /// Stop reading
+    pub async fn close(mut self) -> Result<(), StreamError> {
+        self.stream.finish().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streaming::audio::{Track, TrackMetadata};
+
+    #[test]
+    fn ranges_map_to_whole_chunks() {
+        assert_eq!(chunk_span(0, 10, 100, 16), Some(0..1));
+        assert_eq!(chunk_span(15, 2, 100, 16), Some(0..2));
+        assert_eq!(chunk_span(90, 50, 100, 16), Some(5..7));
+        assert_eq!(chunk_span(100, 1, 100, 16), None);
+        assert_eq!(chunk_span(0, 0, 100, 16), None);
+        assert_eq!(chunk_span(0, MAX_MEDIA_READ + 1, u64::MAX, 16), None);
+    }
+
+    #[tokio::test]
+    async fn shared_files_are_served_and_cached_by_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let movie = dir.path().join("movie.mkv");
+        let content: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&movie, &content).unwrap();
+        let song = dir.path().join("song.flac");
+        std::fs::write(&song, b"flac").unwrap();
+
+        let server = MediaServer::with_store(Arc::new(ChunkStore::with_chunk_size(64)));
+        let source = server.share(&movie, "host").await.unwrap();
+        let StreamSource::P2PFile { file_id, size, .. } = &source else {
+            panic!("expected a P2P file");
+        };
+        assert_eq!(*size, 1000);
+        assert!(file_id.ends_with("/movie.mkv"));
+
+        let shared = server.open(file_id).await.unwrap();
+        let mut file = tokio::fs::File::open(&shared.path).await.unwrap();
+        let last = server
+            .read_chunk(file_id, &mut file, 1000, 15)
+            .await
+            .unwrap();
+        assert_eq!(last, content[960..]);
+        assert_eq!(server.store.len().await, 1);
+        // Served from the store the second time, even with the file gone
+        drop(file);
+        let mut other = tokio::fs::File::open(&song).await.unwrap();
+        let again = server
+            .read_chunk(file_id, &mut other, 1000, 15)
+            .await
+            .unwrap();
+        assert_eq!(again, last);
+
+        // Rewriting the file invalidates what was cached
+        std::fs::write(&movie, vec![7u8; 2000]).unwrap();
+        assert_eq!(server.open(file_id).await.unwrap().size, 2000);
+        assert!(server.index.read().await.is_empty());
+
+        let queue = StreamSource::Audio {
+            tracks: vec![Track::new(
+                StreamSource::LocalFile {
+                    path: song.to_string_lossy().into_owned(),
+                    size: 4,
+                },
+                TrackMetadata::new("song"),
+            )],
+            current: 0,
+        };
+        let shared_queue = server.share_source(&queue, "host").await.unwrap();
+        assert!(matches!(
+            &shared_queue.current_track().unwrap().source,
+            StreamSource::P2PFile { host_id, .. } if host_id == "host"
+        ));
+        assert_eq!(server.shared_files().await.len(), 2);
+        assert!(server.unshare(file_id).await);
+        assert!(!server.unshare(file_id).await);
+    }
+}
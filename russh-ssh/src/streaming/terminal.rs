@@ -0,0 +1,573 @@
+//! Terminal Broadcasts
+//!
+//! Streams a terminal session to the members of a room, for teaching and
+//! demos. The room is an ordinary [`StreamSession`] whose source is
+//! [`StreamSource::Terminal`]; it is served by a
+//! [`RoomServer`](super::room::RoomServer) as usual, so invites, passwords
+//! and host approval decide who may watch.
+//!
+//! The terminal output itself travels on the separate `stream-terminal`
+//! service. A member who joined the room opens a stream to the host and
+//! receives the recent output, so its screen starts out populated, followed
+//! by every new output and resize as it happens. Viewers are read-only: the
+//! service accepts nothing from them. A [`TerminalViewer`] can hold frames
+//! back by a fixed delay, e.g. to keep answers off screen for a moment.
+//!
+//! # Requirements Coverage
+//! - Requirement 4.1: End-to-end encryption between peers
+//! - Requirement 9.4: Interactive shell sessions with PTY allocation
+
+use super::video::{StreamSession, StreamSource};
+use crate::error::{P2PError, StreamError};
+use crate::p2p::acl::PeerCapability;
+use crate::p2p::connection::P2PConnectionManager;
+use crate::p2p::secure::SecureStream;
+use crate::p2p::stream::BiStream;
+use crate::p2p::tunnel::{open_tunnel, StreamHandler, TunnelAgent};
+use crate::ssh::Shell;
+use async_trait::async_trait;
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// Service name of the terminal protocol
+pub const TERMINAL_SERVICE: &str = "stream-terminal";
+
+/// Output kept for viewers who join late
+pub const HISTORY_BYTES: usize = 256 * 1024;
+
+/// Frames buffered per viewer before it is considered behind
+const FRAME_CAPACITY: usize = 1024;
+
+/// Largest control frame
+const MAX_CONTROL_FRAME: usize = 64 * 1024;
+
+/// Largest output frame
+const MAX_OUTPUT_FRAME: usize = 1024 * 1024;
+
+/// Something the terminal did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TerminalFrame {
+    /// Bytes the terminal printed
+    Output {
+        /// Milliseconds since the broadcast started
+        at: u64,
+        data: Vec<u8>,
+    },
+    /// The terminal changed size
+    Resize { at: u64, cols: u32, rows: u32 },
+}
+
+impl TerminalFrame {
+    /// Milliseconds since the broadcast started
+    pub fn at(&self) -> u64 {
+        match self {
+            Self::Output { at, .. } | Self::Resize { at, .. } => *at,
+        }
+    }
+}
+
+/// Messages on a terminal stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum TerminalMessage {
+    /// Viewer asks for a room's terminal
+    Watch { room_id: String },
+    /// Host accepts; `now` is the broadcast clock in milliseconds
+    Watching {
+        term: String,
+        cols: u32,
+        rows: u32,
+        now: u64,
+    },
+    /// Host refuses
+    Rejected { reason: String },
+    /// Output follows as one binary frame
+    Output { at: u64 },
+    /// The terminal changed size
+    Resize { at: u64, cols: u32, rows: u32 },
+}
+
+/// Recent frames for viewers who join late
+#[derive(Debug)]
+struct History {
+    frames: VecDeque<TerminalFrame>,
+    bytes: usize,
+    term: String,
+    /// Terminal size before the oldest kept frame
+    base_size: (u32, u32),
+}
+
+impl History {
+    fn push(&mut self, frame: TerminalFrame) {
+        if let TerminalFrame::Output { data, .. } = &frame {
+            self.bytes += data.len();
+        }
+        self.frames.push_back(frame);
+        while self.bytes > HISTORY_BYTES && self.frames.len() > 1 {
+            match self.frames.pop_front() {
+                Some(TerminalFrame::Output { data, .. }) => self.bytes -= data.len(),
+                Some(TerminalFrame::Resize { cols, rows, .. }) => self.base_size = (cols, rows),
+                None => break,
+            }
+        }
+    }
+
+    /// Current terminal size
+    fn size(&self) -> (u32, u32) {
+        self.frames
+            .iter()
+            .rev()
+            .find_map(|frame| match frame {
+                TerminalFrame::Resize { cols, rows, .. } => Some((*cols, *rows)),
+                TerminalFrame::Output { .. } => None,
+            })
+            .unwrap_or(self.base_size)
+    }
+}
+
+/// A terminal being broadcast to a room
+#[derive(Clone)]
+pub struct TerminalBroadcast {
+    session: Arc<StreamSession>,
+    history: Arc<Mutex<History>>,
+    frames: broadcast::Sender<TerminalFrame>,
+    ended: Arc<watch::Sender<bool>>,
+    started: Instant,
+}
+
+impl TerminalBroadcast {
+    fn new(session: Arc<StreamSession>, term: String, cols: u32, rows: u32) -> Self {
+        let (frames, _) = broadcast::channel(FRAME_CAPACITY);
+        Self {
+            session,
+            history: Arc::new(Mutex::new(History {
+                frames: VecDeque::new(),
+                bytes: 0,
+                term,
+                base_size: (cols, rows),
+            })),
+            frames,
+            ended: Arc::new(watch::channel(false).0),
+            started: Instant::now(),
+        }
+    }
+
+    /// Get the room the terminal is shown in
+    pub fn session(&self) -> &Arc<StreamSession> {
+        &self.session
+    }
+
+    /// Show output to the viewers
+    pub fn send_output(&self, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        self.publish(TerminalFrame::Output {
+            at: self.now(),
+            data: data.to_vec(),
+        });
+    }
+
+    /// Tell the viewers the terminal changed size
+    pub fn resize(&self, cols: u32, rows: u32) {
+        self.publish(TerminalFrame::Resize {
+            at: self.now(),
+            cols,
+            rows,
+        });
+    }
+
+    /// Read a shell's output and show it to the viewers as well
+    ///
+    /// Use in place of [`Shell::read`]; returns the same data.
+    pub async fn read_shell(&self, shell: &mut Shell) -> Option<Vec<u8>> {
+        let data = shell.read().await?;
+        self.send_output(&data);
+        Some(data)
+    }
+
+    /// Number of viewers watching
+    pub fn viewers(&self) -> usize {
+        self.frames.receiver_count()
+    }
+
+    /// Get the current terminal size
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.lock().size()
+    }
+
+    /// End the broadcast, disconnecting every viewer
+    pub fn end(&self) {
+        self.ended.send_replace(true);
+    }
+
+    fn publish(&self, frame: TerminalFrame) {
+        let mut history = self.lock();
+        history.push(frame.clone());
+        // Sent under the lock so a new viewer sees each frame exactly once
+        let _ = self.frames.send(frame);
+    }
+
+    fn now(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, History> {
+        self.history.lock().unwrap_or_else(|poisoned| {
+            tracing::warn!("Terminal history lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+}
+
+/// Serves the terminals broadcast from this node
+///
+/// Register it with the [`TunnelAgent`] next to the room server. Only peers
+/// that are members of a room can watch its terminal.
+#[derive(Clone, Default)]
+pub struct TerminalServer {
+    broadcasts: Arc<Mutex<HashMap<String, TerminalBroadcast>>>,
+}
+
+impl TerminalServer {
+    /// Create a server with no broadcasts
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve terminals to peers
+    pub fn register(&self, agent: TunnelAgent) -> TunnelAgent {
+        agent.with_handler(
+            TERMINAL_SERVICE,
+            PeerCapability::Streaming,
+            Arc::new(self.clone()),
+        )
+    }
+
+    /// Start broadcasting the terminal of a room this node hosts
+    ///
+    /// The room's source must be [`StreamSource::Terminal`].
+    pub async fn broadcast(
+        &self,
+        session: Arc<StreamSession>,
+    ) -> Result<TerminalBroadcast, StreamError> {
+        if !session.is_host() {
+            return Err(StreamError::NotFound(
+                "Only the host can broadcast a terminal".to_string(),
+            ));
+        }
+        let StreamSource::Terminal { term, cols, rows } = session.room().await.source else {
+            return Err(StreamError::NotFound(
+                "Room source is not a terminal".to_string(),
+            ));
+        };
+        let broadcast = TerminalBroadcast::new(session.clone(), term, cols, rows);
+        self.lock()
+            .insert(session.session_id.clone(), broadcast.clone());
+        tracing::info!(room_id = %session.session_id, "Broadcasting terminal");
+        Ok(broadcast)
+    }
+
+    /// Stop broadcasting a room's terminal; viewers are disconnected
+    pub fn stop(&self, room_id: &str) -> bool {
+        let removed = self.lock().remove(room_id);
+        removed.map(|broadcast| broadcast.end()).is_some()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, TerminalBroadcast>> {
+        self.broadcasts.lock().unwrap_or_else(|poisoned| {
+            tracing::warn!("Terminal server lock poisoned, recovering");
+            poisoned.into_inner()
+        })
+    }
+}
+
+#[async_trait]
+impl StreamHandler for TerminalServer {
+    async fn handle(&self, peer_id: NodeId, stream: BiStream) -> Result<(), P2PError> {
+        let mut stream = SecureStream::accept(stream).await?;
+        let TerminalMessage::Watch { room_id } = stream.recv_json(MAX_CONTROL_FRAME).await? else {
+            return Err(P2PError::Stream("Expected a watch request".to_string()));
+        };
+        let peer = peer_id.to_string();
+
+        let broadcast = self.lock().get(&room_id).cloned();
+        let Some(broadcast) = broadcast else {
+            return reject(&mut stream, format!("No terminal in room {}", room_id)).await;
+        };
+        if !broadcast.session.room().await.peers.contains(&peer) {
+            tracing::info!(room_id = %room_id, peer_id = %peer, "Refused terminal viewer outside the room");
+            return reject(&mut stream, "Join the room first".to_string()).await;
+        }
+
+        let (frames, backlog, welcome) = {
+            let history = broadcast.lock();
+            let (cols, rows) = history.base_size;
+            let welcome = TerminalMessage::Watching {
+                term: history.term.clone(),
+                cols,
+                rows,
+                now: broadcast.now(),
+            };
+            (
+                broadcast.frames.subscribe(),
+                history.frames.clone(),
+                welcome,
+            )
+        };
+        let mut ended = broadcast.ended.subscribe();
+
+        stream.send_json(&welcome).await?;
+        tracing::info!(room_id = %room_id, peer_id = %peer, "Viewer watching terminal");
+
+        let (mut sender, mut receiver) = stream.split();
+        let send = async {
+            for frame in backlog {
+                send_frame(&mut sender, frame).await?;
+            }
+            let mut frames = frames;
+            loop {
+                match frames.recv().await {
+                    Ok(frame) => send_frame(&mut sender, frame).await?,
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(peer_id = %peer, skipped, "Terminal viewer fell behind");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+            sender.finish()
+        };
+        // Viewers send nothing; a read returns when they hang up
+        let hang_up = receiver.recv(MAX_CONTROL_FRAME);
+        tokio::select! {
+            result = send => result?,
+            _ = hang_up => {}
+            _ = ended.wait_for(|ended| *ended) => {}
+        }
+        tracing::info!(room_id = %room_id, peer_id = %peer, "Viewer stopped watching terminal");
+        Ok(())
+    }
+}
+
+async fn send_frame(
+    sender: &mut crate::p2p::secure::SecureSender,
+    frame: TerminalFrame,
+) -> Result<(), P2PError> {
+    match frame {
+        TerminalFrame::Output { at, data } => {
+            sender.send_json(&TerminalMessage::Output { at }).await?;
+            sender.send(&data).await
+        }
+        TerminalFrame::Resize { at, cols, rows } => {
+            sender
+                .send_json(&TerminalMessage::Resize { at, cols, rows })
+                .await
+        }
+    }
+}
+
+async fn reject(stream: &mut SecureStream, reason: String) -> Result<(), P2PError> {
+    stream
+        .send_json(&TerminalMessage::Rejected { reason })
+        .await?;
+    stream.finish().await
+}
+
+/// Watches a terminal broadcast
+pub struct TerminalViewer {
+    stream: SecureStream,
+    term: String,
+    cols: u32,
+    rows: u32,
+    /// Local time matching the host's clock reading in the welcome
+    joined: Instant,
+    host_now: u64,
+    delay: Duration,
+}
+
+impl TerminalViewer {
+    /// Watch the terminal of a room this node has joined
+    pub async fn watch(
+        manager: &P2PConnectionManager,
+        host: NodeId,
+        room_id: &str,
+    ) -> Result<Self, StreamError> {
+        let stream = open_tunnel(manager, host, TERMINAL_SERVICE).await?;
+        let mut stream = SecureStream::initiate(stream).await?;
+        stream
+            .send_json(&TerminalMessage::Watch {
+                room_id: room_id.to_string(),
+            })
+            .await?;
+        match stream.recv_json(MAX_CONTROL_FRAME).await? {
+            TerminalMessage::Watching {
+                term,
+                cols,
+                rows,
+                now,
+            } => Ok(Self {
+                stream,
+                term,
+                cols,
+                rows,
+                joined: Instant::now(),
+                host_now: now,
+                delay: Duration::ZERO,
+            }),
+            TerminalMessage::Rejected { reason } => Err(StreamError::AccessDenied(reason)),
+            _ => Err(P2PError::Stream("Unexpected reply to watch request".to_string()).into()),
+        }
+    }
+
+    /// Show every frame `delay` after the host produced it
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Terminal type of the host
+    pub fn term(&self) -> &str {
+        &self.term
+    }
+
+    /// Terminal size as last reported by [`next_frame`](Self::next_frame)
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.cols, self.rows)
+    }
+
+    /// Wait for the next frame to show
+    ///
+    /// Returns `None` when the broadcast ends.
+    pub async fn next_frame(&mut self) -> Option<TerminalFrame> {
+        let frame = match self.stream.recv_json(MAX_CONTROL_FRAME).await.ok()? {
+            TerminalMessage::Output { at } => TerminalFrame::Output {
+                at,
+                data: self.stream.recv(MAX_OUTPUT_FRAME).await.ok()?,
+            },
+            TerminalMessage::Resize { at, cols, rows } => {
+                self.cols = cols;
+                self.rows = rows;
+                TerminalFrame::Resize { at, cols, rows }
+            }
+            other => {
+                tracing::warn!(?other, "Unexpected terminal frame");
+                return None;
+            }
+        };
+        tokio::time::sleep_until(release_at(
+            self.joined,
+            self.host_now,
+            frame.at(),
+            self.delay,
+        ))
+        .await;
+        Some(frame)
+    }
+
+    /// Stop watching
+    pub async fn close(mut self) -> Result<(), StreamError> {
+        self.stream.finish().await?;
+        Ok(())
+    }
+}
+
+/// When to show a frame produced at `at` on the host's clock
+///
+/// `joined` is the local time the host's clock read `host_now`. Frames from
+/// before that are shown as soon as their delay has passed, so history
+/// older than the delay appears at once.
+fn release_at(joined: Instant, host_now: u64, at: u64, delay: Duration) -> Instant {
+    let age = Duration::from_millis(host_now.saturating_sub(at));
+    let ahead = Duration::from_millis(at.saturating_sub(host_now));
+    (joined + ahead + delay)
+        .checked_sub(age)
+        .unwrap_or(joined)
+        .max(joined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(at: u64, len: usize) -> TerminalFrame {
+        TerminalFrame::Output {
+            at,
+            data: vec![b'x'; len],
+        }
+    }
+
+    #[tokio::test]
+    async fn history_keeps_recent_output_and_the_latest_size() {
+        let session = Arc::new(StreamSession::create_room(
+            "demo".to_string(),
+            StreamSource::Terminal {
+                term: "xterm-256color".to_string(),
+                cols: 80,
+                rows: 24,
+            },
+            "host".to_string(),
+        ));
+        let server = TerminalServer::new();
+        let broadcast = server.broadcast(session).await.unwrap();
+
+        let mut viewer = broadcast.frames.subscribe();
+        broadcast.send_output(b"");
+        broadcast.send_output(&[b'a'; HISTORY_BYTES / 2]);
+        broadcast.resize(120, 40);
+        broadcast.send_output(&[b'b'; HISTORY_BYTES / 2]);
+        broadcast.send_output(b"$ ");
+
+        let frame = viewer.recv().await.unwrap();
+        assert!(matches!(frame, TerminalFrame::Output { data, .. } if data[0] == b'a'));
+
+        {
+            let history = broadcast.lock();
+            // The oldest output was dropped to stay within the limit
+            assert!(history.bytes <= HISTORY_BYTES);
+            assert_eq!(history.frames.len(), 3);
+            assert!(matches!(
+                history.frames.front(),
+                Some(TerminalFrame::Resize { cols: 120, .. })
+            ));
+            assert_eq!(history.base_size, (80, 24));
+        }
+        assert_eq!(broadcast.dimensions(), (120, 40));
+
+        assert!(server.stop(&broadcast.session().session_id));
+        assert!(!server.stop(&broadcast.session().session_id));
+
+        let video = Arc::new(StreamSession::create_room(
+            "movie".to_string(),
+            StreamSource::Url {
+                url: "https://example.com/a.mp4".to_string(),
+            },
+            "host".to_string(),
+        ));
+        assert!(server.broadcast(video).await.is_err());
+    }
+
+    #[test]
+    fn delayed_frames_keep_their_spacing() {
+        let joined = Instant::now();
+        let delay = Duration::from_secs(5);
+        // Joined when the host's clock read 10s
+        assert_eq!(
+            release_at(joined, 10_000, 12_000, delay),
+            joined + Duration::from_secs(7)
+        );
+        assert_eq!(
+            release_at(joined, 10_000, 8_000, delay),
+            joined + Duration::from_secs(3)
+        );
+        // History older than the delay shows at once
+        assert_eq!(release_at(joined, 10_000, 1_000, delay), joined);
+        assert_eq!(release_at(joined, 10_000, 10_000, Duration::ZERO), joined);
+        assert_eq!(output(3, 2).at(), 3);
+    }
+}
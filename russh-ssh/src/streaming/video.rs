@@ -3,14 +3,21 @@
 //! Provides synchronized video streaming over P2P connections.
 //! Uses stream-download-rs for efficient streaming with seeking support.
 
+use super::audio::Track;
+use super::metrics::MetricsRecorder;
+use super::quality::Rendition;
 use crate::error::StreamError;
 use crate::p2p::P2PConnectionManager;
 use serde::{Deserialize, Serialize};
 use std::io::{Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::{broadcast, RwLock};
 use uuid::Uuid;
 
+/// Start of a room's share link, see [`StreamSession::share_link`]
+pub const SHARE_LINK_PREFIX: &str = "russh://stream/";
+
 /// Stream room for synchronized playback
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamRoom {
@@ -30,6 +37,18 @@ pub struct StreamRoom {
     pub created_at: i64,
 }
 
+impl StreamRoom {
+    /// Member next in line to host if the host goes away
+    ///
+    /// Peers are kept in join order, so this is the longest-connected one.
+    pub fn successor(&self) -> Option<&str> {
+        self.peers
+            .iter()
+            .find(|peer| **peer != self.host_id)
+            .map(String::as_str)
+    }
+}
+
 /// Stream source types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -44,6 +63,37 @@ pub enum StreamSource {
         file_id: String,
         size: u64,
     },
+    /// Audio-only queue of tracks played back to back
+    Audio {
+        tracks: Vec<Track>,
+        /// Index of the track playing
+        current: usize,
+    },
+    /// The same media in several qualities; each member picks one
+    Renditions { renditions: Vec<Rendition> },
+    /// HLS playlist, live or on demand
+    Hls { playlist_url: String },
+    /// MPEG-DASH manifest, live or on demand
+    Dash { manifest_url: String },
+    /// The host's terminal, broadcast read-only to the room
+    Terminal { term: String, cols: u32, rows: u32 },
+}
+
+impl StreamSource {
+    /// Check whether the media can only be played through `host_id`
+    pub fn depends_on_host(&self, host_id: &str) -> bool {
+        match self {
+            Self::Url { .. } | Self::Hls { .. } | Self::Dash { .. } => false,
+            Self::LocalFile { .. } | Self::Terminal { .. } => true,
+            Self::P2PFile { host_id: owner, .. } => owner == host_id,
+            Self::Audio { .. } => self
+                .current_track()
+                .is_some_and(|track| track.source.depends_on_host(host_id)),
+            Self::Renditions { renditions } => renditions
+                .iter()
+                .all(|rendition| rendition.source.depends_on_host(host_id)),
+        }
+    }
 }
 
 /// Playback state for synchronization
@@ -59,6 +109,17 @@ pub struct PlaybackState {
     pub sync_time: i64,
 }
 
+impl PlaybackState {
+    /// Position at `now` (Unix ms), extrapolated while playing
+    pub fn position_at(&self, now: i64) -> f64 {
+        if !self.playing {
+            return self.position;
+        }
+        let elapsed_secs = (now - self.sync_time) as f64 / 1000.0;
+        self.position + elapsed_secs * self.speed
+    }
+}
+
 impl Default for PlaybackState {
     fn default() -> Self {
         Self {
@@ -92,6 +153,12 @@ pub enum SyncEvent {
     RequestSync,
     /// Full state sync (from host)
     StateSync { state: PlaybackState },
+    /// Switch to another track of an audio queue at `start_time` (Unix ms)
+    TrackChanged { index: usize, start_time: i64 },
+    /// Another member now hosts the room
+    HostChanged { host_id: String },
+    /// The new host cannot play the source; playback is paused
+    SourceUnavailable { reason: String },
 }
 
 /// Stream session manager
@@ -100,12 +167,16 @@ pub struct StreamSession {
     pub session_id: String,
     /// Room info
     room: Arc<RwLock<StreamRoom>>,
-    /// Is host
-    is_host: bool,
+    /// Is host; changes when the room migrates
+    is_host: AtomicBool,
     /// Event sender
     event_tx: broadcast::Sender<SyncEvent>,
+    /// Events for remote room members
+    peer_tx: broadcast::Sender<SyncEvent>,
     /// P2P connection manager
     p2p_manager: Option<Arc<P2PConnectionManager>>,
+    /// Playback quality of this session
+    metrics: MetricsRecorder,
 }
 
 impl StreamSession {
@@ -113,6 +184,7 @@ impl StreamSession {
     pub fn create_room(name: String, source: StreamSource, host_id: String) -> Self {
         let room_id = Uuid::new_v4().to_string();
         let (event_tx, _) = broadcast::channel(100);
+        let (peer_tx, _) = broadcast::channel(100);
 
         let room = StreamRoom {
             room_id: room_id.clone(),
@@ -127,23 +199,28 @@ impl StreamSession {
         Self {
             session_id: room_id,
             room: Arc::new(RwLock::new(room)),
-            is_host: true,
+            is_host: AtomicBool::new(true),
             event_tx,
+            peer_tx,
             p2p_manager: None,
+            metrics: MetricsRecorder::new(),
         }
     }
 
     /// Join an existing room
     pub fn join_room(room: StreamRoom) -> Self {
         let (event_tx, _) = broadcast::channel(100);
+        let (peer_tx, _) = broadcast::channel(100);
         let session_id = room.room_id.clone();
 
         Self {
             session_id,
             room: Arc::new(RwLock::new(room)),
-            is_host: false,
+            is_host: AtomicBool::new(false),
             event_tx,
+            peer_tx,
             p2p_manager: None,
+            metrics: MetricsRecorder::new(),
         }
     }
 
@@ -153,15 +230,43 @@ impl StreamSession {
         self
     }
 
+    /// Get the P2P manager the session talks to peers through
+    pub fn p2p_manager(&self) -> Option<&Arc<P2PConnectionManager>> {
+        self.p2p_manager.as_ref()
+    }
+
+    /// Get the session's metrics recorder
+    ///
+    /// Hand it to the buffer and fetchers playing the room's source with
+    /// their `with_metrics` builders.
+    pub fn metrics(&self) -> &MetricsRecorder {
+        &self.metrics
+    }
+
+    /// Check whether this session hosts the room
+    pub fn is_host(&self) -> bool {
+        self.is_host.load(Ordering::SeqCst)
+    }
+
     /// Get room info
     pub async fn room(&self) -> StreamRoom {
         self.room.read().await.clone()
     }
 
+    /// Replace the room with a snapshot from the host
+    pub(crate) async fn reset_room(&self, room: StreamRoom) {
+        let state = room.playback.clone();
+        *self.room.write().await = room;
+        let _ = self.event_tx.send(SyncEvent::StateSync { state });
+    }
+
     /// Get share link
     pub async fn share_link(&self) -> String {
         let room = self.room.read().await;
-        format!("russh://stream/{}?host={}", room.room_id, room.host_id)
+        format!(
+            "{}{}?host={}",
+            SHARE_LINK_PREFIX, room.room_id, room.host_id
+        )
     }
 
     /// Subscribe to sync events
@@ -169,6 +274,14 @@ impl StreamSession {
         self.event_tx.subscribe()
     }
 
+    /// Subscribe to events that must reach remote room members
+    ///
+    /// Carries local actions and, on the host, every event it applied so
+    /// guests follow the host's state.
+    pub(crate) fn peer_events(&self) -> broadcast::Receiver<SyncEvent> {
+        self.peer_tx.subscribe()
+    }
+
     /// Play
     pub async fn play(&self) -> Result<(), StreamError> {
         let mut room = self.room.write().await;
@@ -204,9 +317,21 @@ impl StreamSession {
     }
 
     /// Update position (called periodically during playback)
+    ///
+    /// The host re-anchors the room's playback at `position`. Guests keep
+    /// the host's state and record how far they drifted from it.
     pub async fn update_position(&self, position: f64) {
+        let now = chrono::Utc::now().timestamp_millis();
         let mut room = self.room.write().await;
+        if !self.is_host() {
+            let expected = room.playback.position_at(now);
+            if room.playback.playing && expected >= 0.0 {
+                self.metrics.record_drift(position - expected);
+            }
+            return;
+        }
         room.playback.position = position;
+        room.playback.sync_time = now;
     }
 
     /// Set playback speed
@@ -221,7 +346,7 @@ impl StreamSession {
 
     /// Change source
     pub async fn change_source(&self, source: StreamSource) -> Result<(), StreamError> {
-        if !self.is_host {
+        if !self.is_host() {
             return Err(StreamError::NotFound(
                 "Only host can change source".to_string(),
             ));
@@ -235,7 +360,126 @@ impl StreamSession {
         self.broadcast_event(event).await
     }
 
+    /// Jump to a track of the audio queue now (host only)
+    pub async fn play_track(&self, index: usize) -> Result<(), StreamError> {
+        self.change_track(index, None).await
+    }
+
+    /// Schedule the next track to start the moment the current one ends
+    /// (host only)
+    ///
+    /// Call it while the current track is still playing so members have
+    /// time to preload the next one. Starts it immediately when the current
+    /// track's duration is unknown or playback is paused. Returns `false`
+    /// at the end of the queue.
+    pub async fn advance_track(&self) -> Result<bool, StreamError> {
+        let (next, start_time) = {
+            let room = self.room.read().await;
+            let StreamSource::Audio { tracks, current } = &room.source else {
+                return Err(StreamError::NotFound(
+                    "Room is not playing audio".to_string(),
+                ));
+            };
+            if current + 1 >= tracks.len() {
+                return Ok(false);
+            }
+            let start_time = tracks[*current]
+                .metadata
+                .duration
+                .and_then(|duration| room.playback.finishes_at(duration));
+            (current + 1, start_time)
+        };
+        self.change_track(next, start_time).await?;
+        Ok(true)
+    }
+
+    async fn change_track(&self, index: usize, start_time: Option<i64>) -> Result<(), StreamError> {
+        if !self.is_host() {
+            return Err(StreamError::NotFound(
+                "Only host can change tracks".to_string(),
+            ));
+        }
+
+        let mut room = self.room.write().await;
+        if !room.source.select_track(index) {
+            return Err(StreamError::NotFound(format!("Track {} not found", index)));
+        }
+        let now = chrono::Utc::now().timestamp_millis();
+        let start_time = start_time.map_or(now, |start| start.max(now));
+        room.playback.position = 0.0;
+        room.playback.sync_time = start_time;
+
+        let event = SyncEvent::TrackChanged { index, start_time };
+        self.broadcast_event(event).await
+    }
+
+    /// Hand the room over to a member (host only)
+    ///
+    /// Sends the current playback state, then tells the guests to
+    /// reconnect to `peer_id`, which carries on from that state. The
+    /// session stops being authoritative and should serve no more guests.
+    pub async fn hand_over(&self, peer_id: &str) -> Result<(), StreamError> {
+        if !self.is_host() {
+            return Err(StreamError::NotFound(
+                "Only host can hand the room over".to_string(),
+            ));
+        }
+
+        let mut room = self.room.write().await;
+        if !room.peers.iter().any(|p| p == peer_id) {
+            return Err(StreamError::NotFound(format!(
+                "Peer not in room: {}",
+                peer_id
+            )));
+        }
+        let state = room.playback.clone();
+        self.is_host.store(false, Ordering::SeqCst);
+        room.host_id = peer_id.to_string();
+        room.peers.retain(|p| p != peer_id);
+
+        self.broadcast_event(SyncEvent::StateSync { state }).await?;
+        self.broadcast_event(SyncEvent::HostChanged {
+            host_id: peer_id.to_string(),
+        })
+        .await
+    }
+
+    /// Become the host of a room whose host went away or handed it over
+    ///
+    /// Returns the other members, who are expected to reconnect. If the
+    /// source was only reachable through `previous_host`, playback pauses
+    /// and members are told the source is unavailable.
+    pub(crate) async fn take_over(&self, host_id: &str, previous_host: &str) -> Vec<String> {
+        let (members, unavailable) = {
+            let mut room = self.room.write().await;
+            room.host_id = host_id.to_string();
+            let members: Vec<String> = std::mem::take(&mut room.peers)
+                .into_iter()
+                .filter(|peer| peer != host_id && peer != previous_host)
+                .collect();
+            self.is_host.store(true, Ordering::SeqCst);
+            (members, room.source.depends_on_host(previous_host))
+        };
+
+        let _ = self
+            .handle_event(SyncEvent::HostChanged {
+                host_id: host_id.to_string(),
+            })
+            .await;
+        if unavailable {
+            tracing::warn!(room_id = %self.session_id, "Source was only available from the previous host");
+            let _ = self
+                .handle_event(SyncEvent::SourceUnavailable {
+                    reason: "The media was only available from the previous host".to_string(),
+                })
+                .await;
+        }
+        members
+    }
+
     /// Handle incoming sync event
+    ///
+    /// The host relays what it applied to the guests; guests only apply.
     pub async fn handle_event(&self, event: SyncEvent) -> Result<(), StreamError> {
         match &event {
             SyncEvent::Play { position } => {
@@ -275,7 +519,7 @@ impl StreamSession {
                 room.playback = PlaybackState::default();
             }
             SyncEvent::RequestSync => {
-                if self.is_host {
+                if self.is_host() {
                     let room = self.room.read().await;
                     let sync_event = SyncEvent::StateSync {
                         state: room.playback.clone(),
@@ -287,6 +531,29 @@ impl StreamSession {
                 let mut room = self.room.write().await;
                 room.playback = state.clone();
             }
+            SyncEvent::TrackChanged { index, start_time } => {
+                let mut room = self.room.write().await;
+                if room.source.select_track(*index) {
+                    room.playback.position = 0.0;
+                    room.playback.sync_time = *start_time;
+                }
+            }
+            SyncEvent::HostChanged { host_id } => {
+                let mut room = self.room.write().await;
+                room.host_id = host_id.clone();
+                room.peers.retain(|p| p != host_id);
+            }
+            SyncEvent::SourceUnavailable { .. } => {
+                let mut room = self.room.write().await;
+                let now = chrono::Utc::now().timestamp_millis();
+                room.playback.position = room.playback.position_at(now);
+                room.playback.playing = false;
+                room.playback.sync_time = now;
+            }
+        }
+
+        if self.is_host() && !matches!(event, SyncEvent::RequestSync) {
+            let _ = self.peer_tx.send(event.clone());
         }
 
         // Re-broadcast to local subscribers
@@ -295,13 +562,13 @@ impl StreamSession {
     }
 
     /// Broadcast event to all peers
+    ///
+    /// Remote members are reached through the room links in
+    /// [`room`](super::room), which forward [`peer_events`](Self::peer_events).
     async fn broadcast_event(&self, event: SyncEvent) -> Result<(), StreamError> {
         // Send to local subscribers
         let _ = self.event_tx.send(event.clone());
-
-        // TODO: Send to P2P peers via connection manager
-        // This would serialize the event and send over QUIC streams
-
+        let _ = self.peer_tx.send(event);
         Ok(())
     }
 
@@ -311,18 +578,28 @@ impl StreamSession {
     }
 
     /// Calculate expected position based on sync time
+    ///
+    /// Negative while a scheduled track change is still ahead: the current
+    /// track starts that many seconds from now.
     pub async fn expected_position(&self) -> f64 {
-        let room = self.room.read().await;
-        if !room.playback.playing {
-            return room.playback.position;
-        }
-
         let now = chrono::Utc::now().timestamp_millis();
-        let elapsed_ms = (now - room.playback.sync_time) as f64;
-        let elapsed_secs = elapsed_ms / 1000.0;
+        self.room.read().await.playback.position_at(now)
+    }
+}
 
-        room.playback.position + (elapsed_secs * room.playback.speed)
+/// Room and host IDs of a share link, if `link` is one
+pub fn parse_share_link(link: &str) -> Option<(String, String)> {
+    let (room_id, query) = link
+        .trim()
+        .strip_prefix(SHARE_LINK_PREFIX)?
+        .split_once('?')?;
+    let host_id = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("host="))?;
+    if room_id.is_empty() || host_id.is_empty() {
+        return None;
     }
+    Some((room_id.to_string(), host_id.to_string()))
 }
 
 /// HTTP video stream using stream-download
@@ -400,10 +677,23 @@ mod tests {
         let session =
             StreamSession::create_room("Test Room".to_string(), source, "host123".to_string());
 
-        assert!(session.is_host);
+        assert!(session.is_host());
         assert!(!session.session_id.is_empty());
     }
 
+    #[tokio::test]
+    async fn share_link_roundtrip() {
+        let source = StreamSource::from_url("https://example.com/video.mp4");
+        let session = StreamSession::create_room("Test".to_string(), source, "host".to_string());
+
+        let link = session.share_link().await;
+        let (room_id, host_id) = parse_share_link(&link).unwrap();
+        assert_eq!(room_id, session.session_id);
+        assert_eq!(host_id, "host");
+        assert!(parse_share_link("russh://stream/room").is_none());
+        assert!(parse_share_link("https://example.com/?host=x").is_none());
+    }
+
     #[tokio::test]
     async fn stream_session_playback() {
         let source = StreamSource::Url {
@@ -412,7 +702,7 @@ mod tests {
         let session = StreamSession::create_room("Test".to_string(), source, "host".to_string());
 
         // Subscribe before events
-        let mut rx = session.subscribe();
+        let _rx = session.subscribe();
 
         session.play().await.unwrap();
         let state = session.playback_state().await;
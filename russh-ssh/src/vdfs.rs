@@ -9,12 +9,55 @@
 //! - Requirement 5.4: Deterministic chunking
 //! - Requirement 5.5: File metadata serialization
 
+pub mod archive;
 pub mod chunk;
+pub mod clock;
+pub mod crypto;
 pub mod filesystem;
+pub mod folder;
+#[cfg(feature = "fuse")]
+pub mod fuse;
+pub mod gc;
+pub mod history;
+pub mod lock;
+pub mod merkle;
 pub mod metadata;
+pub mod quota;
+pub mod reader;
+pub mod replication;
+pub mod resolver;
+pub mod scrub;
 pub mod sync;
+pub mod watch;
+pub mod writer;
 
-pub use chunk::{chunk_data, reassemble_chunks, Chunk, ChunkId, ChunkStore};
+pub use archive::{NamespaceArchive, ARCHIVE_MAGIC};
+pub use chunk::{
+    chunk_data, reassemble_chunks, Chunk, ChunkId, ChunkPipeline, ChunkStore, ChunkStream,
+    ChunkingStats,
+};
+pub use clock::{Causality, VectorClock};
+pub use crypto::{ChunkCipher, ChunkKeyMode, SealedChunk};
 pub use filesystem::VirtualFs;
+pub use folder::{is_valid_folder_id, FolderSync, PullReport, SyncedFolder, FOLDER_SYNC_SERVICE};
+#[cfg(feature = "fuse")]
+pub use fuse::{VdfsFuse, VdfsMount};
+pub use gc::{ChunkRefs, GcReport};
+pub use history::{FileVersion, Snapshot, VersionHistory};
+pub use lock::{FileLock, LockTable};
+pub use merkle::{Difference, MerkleTree, Negotiation, NodeSummary};
 pub use metadata::FileMetadata;
-pub use sync::{SyncEngine, SyncState};
+pub use quota::{Quota, QuotaUsage};
+pub use reader::{ChunkSource, VdfsReader};
+pub use replication::{
+    ChunkLocations, ChunkTransfer, FileReplication, PlacementEngine, ReplicationPlan,
+    ReplicationPolicy,
+};
+pub use resolver::{
+    CallbackResolver, ConflictCopy, ConflictResolver, LastWriterWins, PreferLocal, PreferPeer,
+    Resolution,
+};
+pub use scrub::ScrubReport;
+pub use sync::{Conflict, SyncEngine, SyncState};
+pub use watch::{DirWatcher, WatchChange, WatchConfig, WatchHandle};
+pub use writer::{VdfsWriter, DEFAULT_COMMIT_THRESHOLD};
@@ -0,0 +1,289 @@
+//! Namespace Archives
+//!
+//! Packs a namespace (or a subtree of it) into a single file for backups
+//! and for seeding a new device without a full network sync.
+//!
+//! An archive holds the file metadata and every chunk those files
+//! reference. Its encoding is [`ARCHIVE_MAGIC`], a flags byte, then the
+//! body: a length-prefixed JSON header with the metadata followed by chunk
+//! records (32-byte ID, big-endian `u32` length, data). With a key, the body
+//! is sealed as one AEAD message bound to the magic and flags.
+//!
+//! Importing merges the metadata like a sync from a peer, so an archive can
+//! be applied to a namespace that already has data.
+//!
+//! # Requirements Coverage
+//! - Requirement 5.1: Content-addressed storage using BLAKE3
+//! - Requirement 5.5: File metadata serialization
+
+use super::chunk::Chunk;
+use super::metadata::FileMetadata;
+use crate::encryption::cipher::{
+    decrypt_with_aad, encrypt_with_aad, CipherSuite, EncryptionKey, SealedMessage, NONCE_SIZE,
+};
+use crate::encryption::hash::{hash_data, ContentHash, HASH_SIZE};
+use crate::error::VdfsError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// Leading bytes of every archive
+pub const ARCHIVE_MAGIC: &[u8; 8] = b"RVDFSAR1";
+
+/// Flag set when the archive body is encrypted
+const FLAG_ENCRYPTED: u8 = 0x01;
+
+/// Metadata part of an archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ArchiveHeader {
+    node_id: String,
+    created_at: DateTime<Utc>,
+    root: PathBuf,
+    files: Vec<FileMetadata>,
+}
+
+/// Exported contents of a namespace
+#[derive(Debug, Clone)]
+pub struct NamespaceArchive {
+    /// Node that made the export
+    pub node_id: String,
+    /// When the export was made
+    pub created_at: DateTime<Utc>,
+    /// Exported path; `files` all live under it
+    pub root: PathBuf,
+    /// Entries under `root`
+    pub files: Vec<FileMetadata>,
+    /// Every chunk referenced by `files`, each once
+    pub chunks: Vec<Chunk>,
+}
+
+impl NamespaceArchive {
+    /// Total bytes of chunk data
+    pub fn chunk_bytes(&self) -> u64 {
+        self.chunks
+            .iter()
+            .map(|chunk| chunk.data.len() as u64)
+            .sum()
+    }
+
+    /// Check that every chunk matches its ID and every referenced chunk is
+    /// present
+    pub fn verify(&self) -> Result<(), VdfsError> {
+        let mut present = HashSet::with_capacity(self.chunks.len());
+        for chunk in &self.chunks {
+            if !chunk.verify() {
+                return Err(VdfsError::HashMismatch {
+                    expected: chunk.id.to_hex(),
+                    actual: hash_data(&chunk.data).to_hex(),
+                });
+            }
+            present.insert(chunk.id);
+        }
+        match self
+            .files
+            .iter()
+            .flat_map(|file| &file.chunks)
+            .find(|id| !present.contains(*id))
+        {
+            Some(id) => Err(VdfsError::ChunkNotFound(id.to_hex())),
+            None => Ok(()),
+        }
+    }
+
+    /// Encode the archive, sealing the body when a key is given
+    pub fn to_bytes(&self, key: Option<&EncryptionKey>) -> Result<Vec<u8>, VdfsError> {
+        let header = serde_json::to_vec(&ArchiveHeader {
+            node_id: self.node_id.clone(),
+            created_at: self.created_at,
+            root: self.root.clone(),
+            files: self.files.clone(),
+        })
+        .map_err(|e| VdfsError::Serialization(e.to_string()))?;
+        let header_len = u32::try_from(header.len())
+            .map_err(|_| VdfsError::InvalidArchive("metadata too large".into()))?;
+
+        let mut body = Vec::with_capacity(4 + header.len() + self.chunk_bytes() as usize);
+        body.extend_from_slice(&header_len.to_be_bytes());
+        body.extend_from_slice(&header);
+        for chunk in &self.chunks {
+            let len = u32::try_from(chunk.data.len())
+                .map_err(|_| VdfsError::InvalidArchive("chunk too large".into()))?;
+            body.extend_from_slice(chunk.id.as_bytes());
+            body.extend_from_slice(&len.to_be_bytes());
+            body.extend_from_slice(&chunk.data);
+        }
+
+        let flags = if key.is_some() { FLAG_ENCRYPTED } else { 0 };
+        let mut out = Vec::with_capacity(ARCHIVE_MAGIC.len() + 1 + body.len());
+        out.extend_from_slice(ARCHIVE_MAGIC);
+        out.push(flags);
+        match key {
+            Some(key) => {
+                let sealed = encrypt_with_aad(CipherSuite::Aes256Gcm, key, &body, &out[..])?;
+                out.extend_from_slice(&sealed.nonce);
+                out.extend_from_slice(&sealed.ciphertext);
+            }
+            None => out.extend_from_slice(&body),
+        }
+        Ok(out)
+    }
+
+    /// Decode an archive
+    ///
+    /// Encrypted archives need the key they were sealed with. Chunks are
+    /// checked against their IDs.
+    pub fn from_bytes(bytes: &[u8], key: Option<&EncryptionKey>) -> Result<Self, VdfsError> {
+        let prefix_len = ARCHIVE_MAGIC.len() + 1;
+        if bytes.len() < prefix_len || &bytes[..ARCHIVE_MAGIC.len()] != ARCHIVE_MAGIC {
+            return Err(VdfsError::InvalidArchive("not a VDFS archive".into()));
+        }
+        let (prefix, rest) = bytes.split_at(prefix_len);
+        let flags = prefix[ARCHIVE_MAGIC.len()];
+
+        let opened;
+        let body = match (flags & FLAG_ENCRYPTED != 0, key) {
+            (false, _) => rest,
+            (true, None) => {
+                return Err(VdfsError::InvalidArchive(
+                    "archive is encrypted and no key was given".into(),
+                ))
+            }
+            (true, Some(key)) => {
+                if rest.len() < NONCE_SIZE {
+                    return Err(VdfsError::InvalidArchive("truncated archive".into()));
+                }
+                let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+                let mut message = SealedMessage {
+                    ciphertext: ciphertext.to_vec(),
+                    nonce: [0u8; NONCE_SIZE],
+                };
+                message.nonce.copy_from_slice(nonce);
+                opened = decrypt_with_aad(CipherSuite::Aes256Gcm, key, &message, prefix)?;
+                &opened[..]
+            }
+        };
+
+        let mut reader = Reader(body);
+        let header_len = reader.u32()? as usize;
+        let header: ArchiveHeader = serde_json::from_slice(reader.take(header_len)?)
+            .map_err(|e| VdfsError::Serialization(e.to_string()))?;
+
+        let mut chunks = Vec::new();
+        while !reader.0.is_empty() {
+            let mut id = [0u8; HASH_SIZE];
+            id.copy_from_slice(reader.take(HASH_SIZE)?);
+            let len = reader.u32()? as usize;
+            chunks.push(Chunk {
+                id: ContentHash::from_bytes(id),
+                data: reader.take(len)?.to_vec(),
+            });
+        }
+
+        let archive = Self {
+            node_id: header.node_id,
+            created_at: header.created_at,
+            root: header.root,
+            files: header.files,
+            chunks,
+        };
+        archive.verify()?;
+        Ok(archive)
+    }
+}
+
+/// Cursor over the archive body
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], VdfsError> {
+        if self.0.len() < len {
+            return Err(VdfsError::InvalidArchive("truncated archive".into()));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, VdfsError> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdfs::VirtualFs;
+    use std::path::Path;
+
+    #[tokio::test]
+    async fn export_import_round_trip() {
+        let source = VirtualFs::with_chunk_size("laptop".to_string(), PathBuf::from("/vfs"), 4);
+        source.mkdir(Path::new("docs")).await.unwrap();
+        source
+            .write(Path::new("docs/a.txt"), b"alpha beta")
+            .await
+            .unwrap();
+        source.write(Path::new("other.txt"), b"skip").await.unwrap();
+
+        let archive = source.export(Path::new("docs")).await.unwrap();
+        assert_eq!(archive.files.len(), 2);
+        let key = EncryptionKey::generate().unwrap();
+        let bytes = archive.to_bytes(Some(&key)).unwrap();
+
+        assert!(NamespaceArchive::from_bytes(&bytes, None).is_err());
+        let wrong = EncryptionKey::generate().unwrap();
+        assert!(NamespaceArchive::from_bytes(&bytes, Some(&wrong)).is_err());
+
+        let restored = NamespaceArchive::from_bytes(&bytes, Some(&key)).unwrap();
+        let target = VirtualFs::new("phone".to_string(), PathBuf::from("/vfs"));
+        assert_eq!(target.import(&restored).await.unwrap(), 2);
+        assert_eq!(
+            target.read(Path::new("docs/a.txt")).await.unwrap(),
+            b"alpha beta"
+        );
+        assert!(target.stat(Path::new("other.txt")).await.is_err());
+
+        // Importing again changes nothing
+        assert_eq!(target.import(&restored).await.unwrap(), 0);
+    }
+
+    #[test]
+    fn damaged_archives_are_rejected() {
+        let chunk = Chunk::new(b"data".to_vec());
+        let archive = NamespaceArchive {
+            node_id: "node".to_string(),
+            created_at: Utc::now(),
+            root: PathBuf::from("/vfs"),
+            files: vec![FileMetadata::new_file(
+                PathBuf::from("/vfs/f"),
+                4,
+                chunk.id,
+                vec![chunk.id],
+            )],
+            chunks: vec![chunk],
+        };
+        let mut bytes = archive.to_bytes(None).unwrap();
+        assert_eq!(
+            NamespaceArchive::from_bytes(&bytes, None)
+                .unwrap()
+                .chunk_bytes(),
+            4
+        );
+
+        // Flip a byte of chunk data
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        assert!(matches!(
+            NamespaceArchive::from_bytes(&bytes, None),
+            Err(VdfsError::HashMismatch { .. })
+        ));
+        assert!(matches!(
+            NamespaceArchive::from_bytes(&bytes[..bytes.len() - 2], None),
+            Err(VdfsError::InvalidArchive(_))
+        ));
+        assert!(NamespaceArchive::from_bytes(b"not an archive", None).is_err());
+    }
+}
@@ -3,19 +3,36 @@
 //! Stores file data as BLAKE3-addressed chunks for deduplication
 //! and efficient synchronization.
 //!
+//! Large files are best chunked with a [`ChunkPipeline`], which reads,
+//! chunks and hashes on several threads at once with bounded memory.
+//!
 //! # Requirements Coverage
 //! - Requirement 5.1: Content-addressed storage using BLAKE3
 //! - Requirement 5.4: Deterministic chunking
 
+use super::crypto::{ChunkCipher, SealedChunk};
+use super::quota;
 use crate::encryption::hash::{hash_data, ContentHash};
 use crate::error::VdfsError;
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::io::{self, Read};
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, RwLock};
 
 /// Default chunk size (64 KB)
 pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
 
+/// Chunks a [`ChunkPipeline`] reads ahead per worker by default
+const IN_FLIGHT_PER_WORKER: usize = 4;
+
+/// Bytes a [`ChunkPipeline`] reads at a time, rounded up to whole chunks;
+/// BLAKE3 only spreads hashing over several cores for large inputs
+const PIPELINE_READ_SIZE: usize = 1024 * 1024;
+
 /// Chunk identifier (BLAKE3 hash)
 pub type ChunkId = ContentHash;
 
@@ -51,22 +68,53 @@ impl Chunk {
     }
 }
 
+/// A chunk as held by the store
+#[derive(Debug, Clone)]
+enum StoredChunk {
+    /// Plaintext chunk (no cipher configured)
+    Plain(Chunk),
+    /// Chunk sealed with the store's cipher
+    Sealed(SealedChunk),
+}
+
+impl StoredChunk {
+    /// Bytes occupied in the store
+    fn size(&self) -> usize {
+        match self {
+            StoredChunk::Plain(chunk) => chunk.size(),
+            StoredChunk::Sealed(sealed) => sealed.size(),
+        }
+    }
+}
+
 /// In-memory chunk store
 ///
 /// Provides content-addressed storage for chunks with deduplication.
-#[derive(Debug, Default)]
+/// With a [`ChunkCipher`] configured, chunk payloads are only ever held
+/// sealed and are decrypted on retrieval.
+///
+/// An optional byte limit caps the stored size; `used_bytes` and the limit
+/// are only changed while the chunk map is write-locked.
+#[derive(Debug)]
 pub struct ChunkStore {
-    chunks: Arc<RwLock<HashMap<ChunkId, Chunk>>>,
+    chunks: Arc<RwLock<HashMap<ChunkId, StoredChunk>>>,
     chunk_size: usize,
+    cipher: Option<Arc<ChunkCipher>>,
+    used_bytes: AtomicU64,
+    /// Byte limit, `u64::MAX` when unlimited
+    max_bytes: AtomicU64,
+}
+
+impl Default for ChunkStore {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ChunkStore {
     /// Create a new chunk store with default chunk size
     pub fn new() -> Self {
-        Self {
-            chunks: Arc::new(RwLock::new(HashMap::new())),
-            chunk_size: DEFAULT_CHUNK_SIZE,
-        }
+        Self::with_chunk_size(DEFAULT_CHUNK_SIZE)
     }
 
     /// Create a chunk store with custom chunk size
@@ -74,9 +122,75 @@ impl ChunkStore {
         Self {
             chunks: Arc::new(RwLock::new(HashMap::new())),
             chunk_size,
+            cipher: None,
+            used_bytes: AtomicU64::new(0),
+            max_bytes: AtomicU64::new(u64::MAX),
         }
     }
 
+    /// Limit the total stored size to `max_bytes`
+    pub fn with_max_bytes(self, max_bytes: u64) -> Self {
+        self.max_bytes.store(max_bytes, Ordering::Relaxed);
+        self
+    }
+
+    /// Change the byte limit, `None` removing it
+    ///
+    /// Lowering the limit below current usage only blocks new chunks;
+    /// nothing already stored is evicted.
+    pub fn set_max_bytes(&self, max_bytes: Option<u64>) {
+        self.max_bytes
+            .store(max_bytes.unwrap_or(u64::MAX), Ordering::Relaxed);
+    }
+
+    /// Get the byte limit
+    pub fn max_bytes(&self) -> Option<u64> {
+        match self.max_bytes.load(Ordering::Relaxed) {
+            u64::MAX => None,
+            max => Some(max),
+        }
+    }
+
+    /// Get the bytes currently held in the store
+    pub fn used_bytes(&self) -> u64 {
+        self.used_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Insert a chunk unless present, enforcing the byte limit
+    ///
+    /// Must be called with the chunk map write-locked.
+    fn insert(
+        &self,
+        chunks: &mut HashMap<ChunkId, StoredChunk>,
+        id: ChunkId,
+        stored: StoredChunk,
+    ) -> Result<(), VdfsError> {
+        if chunks.contains_key(&id) {
+            return Ok(());
+        }
+        let size = stored.size() as u64;
+        quota::check("bytes", self.used_bytes(), size, self.max_bytes())?;
+        chunks.insert(id, stored);
+        self.used_bytes.fetch_add(size, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Account for chunks leaving the store
+    fn release(&self, bytes: usize) {
+        self.used_bytes.fetch_sub(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Encrypt chunk payloads at rest with the given cipher
+    pub fn with_encryption(mut self, cipher: ChunkCipher) -> Self {
+        self.cipher = Some(Arc::new(cipher));
+        self
+    }
+
+    /// Check whether chunks are stored encrypted
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
     /// Get the configured chunk size
     pub fn chunk_size(&self) -> usize {
         self.chunk_size
@@ -85,29 +199,89 @@ impl ChunkStore {
     /// Store a chunk
     ///
     /// Returns the chunk ID. If a chunk with the same content already exists,
-    /// it won't be duplicated (content-addressed deduplication).
-    pub async fn store(&self, chunk: Chunk) -> ChunkId {
+    /// it won't be duplicated (content-addressed deduplication). Fails with
+    /// [`VdfsError::QuotaExceeded`] if a new chunk would exceed the byte limit.
+    pub async fn store(&self, chunk: Chunk) -> Result<ChunkId, VdfsError> {
         let id = chunk.id;
+        if self.contains(&id).await {
+            return Ok(id);
+        }
+
+        let stored = match &self.cipher {
+            Some(cipher) => StoredChunk::Sealed(cipher.seal(&chunk)?),
+            None => StoredChunk::Plain(chunk),
+        };
         let mut chunks = self.chunks.write().await;
-        chunks.entry(id).or_insert(chunk);
-        id
+        self.insert(&mut chunks, id, stored)?;
+        Ok(id)
     }
 
     /// Store raw data as a chunk
     ///
     /// Creates a chunk from the data and stores it.
-    pub async fn store_data(&self, data: Vec<u8>) -> ChunkId {
+    pub async fn store_data(&self, data: Vec<u8>) -> Result<ChunkId, VdfsError> {
         let chunk = Chunk::new(data);
         self.store(chunk).await
     }
 
+    /// Store a sealed chunk received from a peer
+    ///
+    /// The chunk is opened to verify it matches `id` before being kept.
+    pub async fn store_sealed(&self, id: ChunkId, sealed: SealedChunk) -> Result<(), VdfsError> {
+        let chunk = self.open_sealed(&id, &sealed)?;
+        let stored = match &self.cipher {
+            Some(_) => StoredChunk::Sealed(sealed),
+            None => StoredChunk::Plain(chunk),
+        };
+        let mut chunks = self.chunks.write().await;
+        self.insert(&mut chunks, id, stored)
+    }
+
     /// Retrieve a chunk by ID
     pub async fn get(&self, id: &ChunkId) -> Result<Chunk, VdfsError> {
+        let stored = {
+            let chunks = self.chunks.read().await;
+            chunks.get(id).cloned()
+        }
+        .ok_or_else(|| VdfsError::ChunkNotFound(id.to_hex()))?;
+
+        match stored {
+            StoredChunk::Plain(chunk) => Ok(chunk),
+            StoredChunk::Sealed(sealed) => self.open_sealed(id, &sealed),
+        }
+    }
+
+    /// Retrieve a chunk in sealed form, for shipping to peers or relays
+    pub async fn get_sealed(&self, id: &ChunkId) -> Result<SealedChunk, VdfsError> {
+        let cipher = self.cipher()?;
+        let stored = {
+            let chunks = self.chunks.read().await;
+            chunks.get(id).cloned()
+        }
+        .ok_or_else(|| VdfsError::ChunkNotFound(id.to_hex()))?;
+
+        match stored {
+            StoredChunk::Plain(chunk) => cipher.seal(&chunk),
+            StoredChunk::Sealed(sealed) => Ok(sealed),
+        }
+    }
+
+    /// Get the size of a chunk as held in the store
+    pub async fn stored_size(&self, id: &ChunkId) -> Option<usize> {
         let chunks = self.chunks.read().await;
-        chunks
-            .get(id)
-            .cloned()
-            .ok_or_else(|| VdfsError::ChunkNotFound(id.to_hex()))
+        chunks.get(id).map(StoredChunk::size)
+    }
+
+    fn cipher(&self) -> Result<&ChunkCipher, VdfsError> {
+        self.cipher.as_deref().ok_or_else(|| {
+            VdfsError::Encryption(crate::error::EncryptionError::Encryption(
+                "chunk store has no encryption key".into(),
+            ))
+        })
+    }
+
+    fn open_sealed(&self, id: &ChunkId, sealed: &SealedChunk) -> Result<Chunk, VdfsError> {
+        self.cipher()?.open(id, sealed)
     }
 
     /// Check if a chunk exists
@@ -117,9 +291,13 @@ impl ChunkStore {
     }
 
     /// Remove a chunk
-    pub async fn remove(&self, id: &ChunkId) -> Option<Chunk> {
+    ///
+    /// Returns the number of bytes freed, or `None` if the chunk was absent.
+    pub async fn remove(&self, id: &ChunkId) -> Option<usize> {
         let mut chunks = self.chunks.write().await;
-        chunks.remove(id)
+        let size = chunks.remove(id).map(|stored| stored.size())?;
+        self.release(size);
+        Some(size)
     }
 
     /// Get the number of stored chunks
@@ -141,8 +319,7 @@ impl ChunkStore {
 
     /// Get total storage size in bytes
     pub async fn total_size(&self) -> usize {
-        let chunks = self.chunks.read().await;
-        chunks.values().map(|c| c.size()).sum()
+        self.used_bytes() as usize
     }
 
     /// Garbage collect unreferenced chunks
@@ -169,6 +346,7 @@ impl ChunkStore {
                 false
             }
         });
+        self.release(freed_bytes);
 
         (removed_count, freed_bytes)
     }
@@ -181,6 +359,7 @@ impl ChunkStore {
         let count = chunks.len();
         let bytes: usize = chunks.values().map(|c| c.size()).sum();
         chunks.clear();
+        self.release(bytes);
         (count, bytes)
     }
 }
@@ -200,6 +379,263 @@ pub fn reassemble_chunks(chunks: &[Chunk]) -> Vec<u8> {
     chunks.iter().flat_map(|c| c.data.iter().cloned()).collect()
 }
 
+/// Splits a reader into chunks, hashing them on several threads
+///
+/// One thread reads the input a megabyte at a time and hashes the whole
+/// content on every core, while a pool of workers computes the chunk IDs and
+/// [`ChunkStream::next`] hands the chunks over in order. At most
+/// `max_in_flight` chunks wait between being read and being handed over, so
+/// memory stays bounded however large the input. The chunks are the same as
+/// [`chunk_data`] makes.
+#[derive(Debug, Clone)]
+pub struct ChunkPipeline {
+    chunk_size: usize,
+    workers: usize,
+    max_in_flight: usize,
+}
+
+impl ChunkPipeline {
+    /// Create a pipeline with a worker per CPU
+    pub fn new(chunk_size: usize) -> Self {
+        let workers = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        Self {
+            chunk_size: chunk_size.max(1),
+            workers,
+            max_in_flight: workers * IN_FLIGHT_PER_WORKER,
+        }
+    }
+
+    /// Hash chunks on `workers` threads
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Read at most `chunks` chunks ahead of the consumer
+    pub fn with_max_in_flight(mut self, chunks: usize) -> Self {
+        self.max_in_flight = chunks.max(1);
+        self
+    }
+
+    /// Chunk a local file
+    pub fn start_file(self, path: &Path) -> Result<ChunkStream, VdfsError> {
+        let file = std::fs::File::open(path)?;
+        Ok(self.start(file))
+    }
+
+    /// Start chunking everything `reader` yields
+    pub fn start<R: Read + Send + 'static>(self, mut reader: R) -> ChunkStream {
+        let counters = Arc::new(PipelineCounters::default());
+        let (pieces_tx, pieces) = mpsc::channel(self.max_in_flight);
+        let (work_tx, work_rx) = std::sync::mpsc::channel::<(Vec<u8>, oneshot::Sender<Chunk>)>();
+        let work_rx = Arc::new(Mutex::new(work_rx));
+
+        for _ in 0..self.workers {
+            let work_rx = work_rx.clone();
+            let counters = counters.clone();
+            std::thread::spawn(move || loop {
+                let job = match work_rx.lock() {
+                    Ok(work) => work.recv(),
+                    Err(_) => break,
+                };
+                let Ok((data, reply)) = job else {
+                    break;
+                };
+                let started = Instant::now();
+                let chunk = Chunk::new(data);
+                counters.hashed(chunk.size(), started.elapsed());
+                // The consumer may have stopped listening
+                reply.send(chunk).ok();
+            });
+        }
+
+        let chunk_size = self.chunk_size;
+        let read_size = PIPELINE_READ_SIZE.div_ceil(chunk_size) * chunk_size;
+        let reader_counters = counters.clone();
+        std::thread::spawn(move || {
+            let counters = reader_counters;
+            let mut hasher = blake3::Hasher::new();
+            'read: loop {
+                let started = Instant::now();
+                let mut data = Vec::with_capacity(read_size);
+                let read = reader
+                    .by_ref()
+                    .take(read_size as u64)
+                    .read_to_end(&mut data);
+                counters.read(data.len(), started.elapsed());
+
+                let last = match read {
+                    Ok(0) => Piece::Done(hasher.finalize().into()),
+                    Ok(_) => {
+                        // The whole-input hash runs on all cores, so it keeps
+                        // up with the workers hashing the chunks
+                        hasher.update_rayon(&data);
+                        for piece in data.chunks(chunk_size) {
+                            let (reply, chunk) = oneshot::channel();
+                            if work_tx.send((piece.to_vec(), reply)).is_err() {
+                                break 'read;
+                            }
+                            // Waits while `max_in_flight` chunks are queued
+                            if pieces_tx.blocking_send(Piece::Chunk(chunk)).is_err() {
+                                break 'read;
+                            }
+                        }
+                        continue;
+                    }
+                    Err(e) => Piece::Failed(e),
+                };
+                pieces_tx.blocking_send(last).ok();
+                break;
+            }
+        });
+
+        ChunkStream {
+            pieces,
+            content_hash: None,
+            counters,
+            started: Instant::now(),
+            workers: self.workers,
+        }
+    }
+}
+
+/// What the reading thread of a [`ChunkPipeline`] passes on, in order
+#[derive(Debug)]
+enum Piece {
+    /// A chunk being hashed
+    Chunk(oneshot::Receiver<Chunk>),
+    /// The input ended; hash of all of it
+    Done(ContentHash),
+    Failed(io::Error),
+}
+
+#[derive(Debug, Default)]
+struct PipelineCounters {
+    bytes_read: AtomicU64,
+    chunks_hashed: AtomicU64,
+    bytes_hashed: AtomicU64,
+    read_nanos: AtomicU64,
+    hash_nanos: AtomicU64,
+}
+
+impl PipelineCounters {
+    fn read(&self, bytes: usize, took: Duration) {
+        self.bytes_read.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.read_nanos
+            .fetch_add(took.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn hashed(&self, bytes: usize, took: Duration) {
+        self.chunks_hashed.fetch_add(1, Ordering::Relaxed);
+        self.bytes_hashed.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.hash_nanos
+            .fetch_add(took.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Chunks coming out of a [`ChunkPipeline`], in order
+///
+/// Dropping it stops the pipeline.
+#[derive(Debug)]
+pub struct ChunkStream {
+    pieces: mpsc::Receiver<Piece>,
+    content_hash: Option<ContentHash>,
+    counters: Arc<PipelineCounters>,
+    started: Instant,
+    workers: usize,
+}
+
+impl ChunkStream {
+    /// Get the next chunk, or `None` once the input is used up
+    pub async fn next(&mut self) -> Option<Result<Chunk, VdfsError>> {
+        match self.pieces.recv().await? {
+            Piece::Chunk(chunk) => Some(
+                chunk
+                    .await
+                    .map_err(|_| io::Error::other("chunk hashing worker stopped").into()),
+            ),
+            Piece::Done(hash) => {
+                self.content_hash = Some(hash);
+                None
+            }
+            Piece::Failed(e) => Some(Err(e.into())),
+        }
+    }
+
+    /// BLAKE3 hash of the whole input, known once [`next`](Self::next)
+    /// returned `None`
+    pub fn content_hash(&self) -> Option<ContentHash> {
+        self.content_hash
+    }
+
+    /// Wait for every chunk, returning them with the hash of the whole input
+    pub async fn collect(mut self) -> Result<(Vec<Chunk>, ContentHash), VdfsError> {
+        let mut chunks = Vec::new();
+        while let Some(chunk) = self.next().await {
+            chunks.push(chunk?);
+        }
+        let content_hash = self
+            .content_hash
+            .ok_or_else(|| io::Error::other("chunking stopped before the end of the input"))?;
+        Ok((chunks, content_hash))
+    }
+
+    /// Throughput so far
+    pub fn stats(&self) -> ChunkingStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        ChunkingStats {
+            bytes_read: load(&self.counters.bytes_read),
+            chunks_hashed: load(&self.counters.chunks_hashed),
+            bytes_hashed: load(&self.counters.bytes_hashed),
+            elapsed: self.started.elapsed(),
+            read_time: Duration::from_nanos(load(&self.counters.read_nanos)),
+            hash_time: Duration::from_nanos(load(&self.counters.hash_nanos)),
+            workers: self.workers,
+        }
+    }
+}
+
+/// Throughput of a [`ChunkPipeline`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkingStats {
+    /// Bytes read from the input
+    pub bytes_read: u64,
+    /// Chunks whose ID is computed
+    pub chunks_hashed: u64,
+    /// Bytes in those chunks
+    pub bytes_hashed: u64,
+    /// Time since the pipeline started
+    pub elapsed: Duration,
+    /// Time spent waiting on the input
+    pub read_time: Duration,
+    /// Time spent hashing chunks, summed over the workers
+    pub hash_time: Duration,
+    /// Threads computing chunk IDs
+    pub workers: usize,
+}
+
+impl ChunkingStats {
+    /// Bytes chunked per second since the start
+    pub fn throughput(&self) -> f64 {
+        per_second(self.bytes_hashed, self.elapsed)
+    }
+
+    /// Bytes a single worker hashes per second while busy
+    ///
+    /// Well above [`throughput`](Self::throughput) divided by the workers,
+    /// the pipeline is waiting on the input rather than on hashing.
+    pub fn hash_rate(&self) -> f64 {
+        per_second(self.bytes_hashed, self.hash_time)
+    }
+}
+
+fn per_second(bytes: u64, time: Duration) -> f64 {
+    match time.as_secs_f64() {
+        secs if secs > 0.0 => bytes as f64 / secs,
+        _ => 0.0,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -228,7 +664,7 @@ mod tests {
         let store = ChunkStore::new();
 
         let data = b"Test data".to_vec();
-        let id = store.store_data(data.clone()).await;
+        let id = store.store_data(data.clone()).await.unwrap();
 
         assert!(store.contains(&id).await);
 
@@ -241,8 +677,8 @@ mod tests {
         let store = ChunkStore::new();
 
         let data = b"Duplicate data".to_vec();
-        let id1 = store.store_data(data.clone()).await;
-        let id2 = store.store_data(data).await;
+        let id1 = store.store_data(data.clone()).await.unwrap();
+        let id2 = store.store_data(data).await.unwrap();
 
         assert_eq!(id1, id2);
         assert_eq!(store.len().await, 1);
@@ -259,14 +695,54 @@ mod tests {
         assert_eq!(reassembled, data);
     }
 
+    #[tokio::test]
+    async fn pipeline_matches_sequential_chunking() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i * 7) as u8).collect();
+        let mut stream = ChunkPipeline::new(256)
+            .with_workers(3)
+            .with_max_in_flight(2)
+            .start(io::Cursor::new(data.clone()));
+
+        let mut chunks = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            chunks.push(chunk.unwrap());
+        }
+        let expected = chunk_data(&data, 256);
+        assert_eq!(chunks.len(), expected.len());
+        assert!(chunks.iter().zip(&expected).all(|(a, b)| a.id == b.id));
+        assert_eq!(reassemble_chunks(&chunks), data);
+        assert_eq!(stream.content_hash(), Some(hash_data(&data)));
+
+        let stats = stream.stats();
+        assert_eq!(stats.bytes_read, 10_000);
+        assert_eq!(stats.bytes_hashed, 10_000);
+        assert_eq!(stats.chunks_hashed, expected.len() as u64);
+        assert_eq!(stats.workers, 3);
+    }
+
+    #[tokio::test]
+    async fn pipeline_reports_read_errors() {
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk gone"))
+            }
+        }
+
+        let mut stream = ChunkPipeline::new(64).start(Failing);
+        assert!(matches!(stream.next().await, Some(Err(VdfsError::Io(_)))));
+        assert!(stream.next().await.is_none());
+        assert_eq!(stream.content_hash(), None);
+    }
+
     #[tokio::test]
     async fn chunk_store_garbage_collection() {
         let store = ChunkStore::new();
 
         // Store some chunks
-        let id1 = store.store_data(b"chunk 1".to_vec()).await;
-        let id2 = store.store_data(b"chunk 2".to_vec()).await;
-        let id3 = store.store_data(b"chunk 3".to_vec()).await;
+        let id1 = store.store_data(b"chunk 1".to_vec()).await.unwrap();
+        let id2 = store.store_data(b"chunk 2".to_vec()).await.unwrap();
+        let id3 = store.store_data(b"chunk 3".to_vec()).await.unwrap();
 
         assert_eq!(store.len().await, 3);
 
@@ -285,12 +761,59 @@ mod tests {
         assert!(store.contains(&id3).await);
     }
 
+    #[tokio::test]
+    async fn encrypted_store_seals_at_rest() {
+        use crate::encryption::cipher::EncryptionKey;
+
+        let cipher = ChunkCipher::new(&EncryptionKey::from_bytes([1u8; 32]));
+        let store = ChunkStore::new().with_encryption(cipher);
+        let id = store.store_data(b"plaintext".to_vec()).await.unwrap();
+
+        let sealed = store.get_sealed(&id).await.unwrap();
+        assert_ne!(sealed.sealed.ciphertext, b"plaintext");
+        assert_eq!(store.get(&id).await.unwrap().data, b"plaintext");
+
+        // A peer sharing the namespace key can import the sealed form
+        let peer = ChunkStore::new()
+            .with_encryption(ChunkCipher::new(&EncryptionKey::from_bytes([1u8; 32])));
+        peer.store_sealed(id, sealed.clone()).await.unwrap();
+        assert_eq!(peer.get(&id).await.unwrap().data, b"plaintext");
+
+        // Without the key, neither importing nor reading works
+        assert!(ChunkStore::new().store_sealed(id, sealed).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn byte_limit_rejects_new_chunks_only() {
+        let store = ChunkStore::new().with_max_bytes(10);
+
+        let id = store.store_data(b"12345678".to_vec()).await.unwrap();
+        // Deduplicated chunks cost nothing
+        store.store_data(b"12345678".to_vec()).await.unwrap();
+        assert!(matches!(
+            store.store_data(b"abc".to_vec()).await,
+            Err(VdfsError::QuotaExceeded {
+                limit: 10,
+                requested: 11,
+                ..
+            })
+        ));
+        assert_eq!(store.used_bytes(), 8);
+
+        store.remove(&id).await.unwrap();
+        store.store_data(b"abc".to_vec()).await.unwrap();
+        assert_eq!(store.used_bytes(), 3);
+
+        store.set_max_bytes(None);
+        assert_eq!(store.max_bytes(), None);
+    }
+
     #[tokio::test]
     async fn chunk_store_clear() {
         let store = ChunkStore::new();
 
-        store.store_data(b"chunk 1".to_vec()).await;
-        store.store_data(b"chunk 2".to_vec()).await;
+        store.store_data(b"chunk 1".to_vec()).await.unwrap();
+        store.store_data(b"chunk 2".to_vec()).await.unwrap();
 
         assert_eq!(store.len().await, 2);
 
@@ -0,0 +1,132 @@
+//! Vector Clocks
+//!
+//! Per-file causality tracking for conflict detection.
+//!
+//! A single logical clock can only order edits, not tell whether one edit
+//! saw the other. Each file therefore carries a vector clock with one
+//! counter per node that modified it: if neither clock dominates the other,
+//! the edits were concurrent and the sync engine reports a conflict instead
+//! of silently dropping one side.
+//!
+//! # Requirements Coverage
+//! - Requirement 5.2: CRDT-based sync for conflict resolution
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Causal relationship between two clocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Causality {
+    /// Both clocks describe the same history
+    Equal,
+    /// The first clock happened before the second
+    Before,
+    /// The first clock happened after the second
+    After,
+    /// Neither clock saw the other's changes
+    Concurrent,
+}
+
+/// Vector clock mapping node IDs to event counters
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VectorClock(BTreeMap<String, u64>);
+
+impl VectorClock {
+    /// Create an empty clock
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the counter for a node
+    pub fn get(&self, node_id: &str) -> u64 {
+        self.0.get(node_id).copied().unwrap_or(0)
+    }
+
+    /// Record an event on `node_id`, returning its new counter
+    pub fn increment(&mut self, node_id: &str) -> u64 {
+        let counter = self.0.entry(node_id.to_string()).or_insert(0);
+        *counter += 1;
+        *counter
+    }
+
+    /// Take the element-wise maximum with another clock
+    pub fn merge(&mut self, other: &VectorClock) {
+        for (node, counter) in &other.0 {
+            let entry = self.0.entry(node.clone()).or_insert(0);
+            *entry = (*entry).max(*counter);
+        }
+    }
+
+    /// Compare causality with another clock
+    pub fn compare(&self, other: &VectorClock) -> Causality {
+        let mut less = false;
+        let mut greater = false;
+        for node in self.0.keys().chain(other.0.keys()) {
+            match self.get(node).cmp(&other.get(node)) {
+                std::cmp::Ordering::Less => less = true,
+                std::cmp::Ordering::Greater => greater = true,
+                std::cmp::Ordering::Equal => {}
+            }
+        }
+
+        match (less, greater) {
+            (false, false) => Causality::Equal,
+            (true, false) => Causality::Before,
+            (false, true) => Causality::After,
+            (true, true) => Causality::Concurrent,
+        }
+    }
+
+    /// Check whether the clocks are concurrent
+    pub fn is_concurrent(&self, other: &VectorClock) -> bool {
+        self.compare(other) == Causality::Concurrent
+    }
+
+    /// Check if no events have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterate over `(node, counter)` pairs in node order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.0
+            .iter()
+            .map(|(node, counter)| (node.as_str(), *counter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_orders_causal_histories() {
+        let mut a = VectorClock::new();
+        a.increment("a");
+        let mut b = a.clone();
+        b.increment("b");
+
+        assert_eq!(a.compare(&a), Causality::Equal);
+        assert_eq!(a.compare(&b), Causality::Before);
+        assert_eq!(b.compare(&a), Causality::After);
+        assert_eq!(VectorClock::new().compare(&a), Causality::Before);
+    }
+
+    #[test]
+    fn divergent_edits_are_concurrent_until_merged() {
+        let mut base = VectorClock::new();
+        base.increment("a");
+        let mut left = base.clone();
+        left.increment("a");
+        let mut right = base.clone();
+        right.increment("b");
+
+        assert!(left.is_concurrent(&right));
+
+        let mut merged = left.clone();
+        merged.merge(&right);
+        assert_eq!(merged.compare(&left), Causality::After);
+        assert_eq!(merged.compare(&right), Causality::After);
+    }
+}
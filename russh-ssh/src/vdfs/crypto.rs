@@ -0,0 +1,242 @@
+//! Chunk Encryption at Rest
+//!
+//! Seals chunk payloads with a per-namespace key so a compromised disk or
+//! relay only ever sees ciphertext.
+//!
+//! Chunks keep their plaintext BLAKE3 [`ChunkId`] as the logical address used
+//! by [`FileMetadata`](super::metadata::FileMetadata), so deduplication and
+//! content addressing work unchanged. A sealed chunk is published under a
+//! *locator* instead, a keyed hash of the chunk ID that reveals nothing about
+//! the content to anyone without the namespace key.
+//!
+//! Two key modes are supported:
+//! - [`ChunkKeyMode::Random`]: one namespace data key with a fresh random
+//!   nonce per seal. Identical chunks produce different ciphertext.
+//! - [`ChunkKeyMode::Convergent`]: a per-chunk key derived from the namespace
+//!   key and the chunk ID. Identical chunks produce identical ciphertext, so
+//!   peers and relays can deduplicate sealed data, at the cost of revealing
+//!   which chunks are equal within the namespace.
+//!
+//! # Requirements Coverage
+//! - Requirement 5.1: Content-addressed storage using BLAKE3
+
+use super::chunk::{Chunk, ChunkId};
+use crate::encryption::cipher::{
+    decrypt_with_aad, encrypt_with_aad, encrypt_with_nonce, CipherSuite, EncryptionKey,
+    SealedMessage, NONCE_SIZE,
+};
+use crate::encryption::hash::ContentHash;
+use crate::error::VdfsError;
+use serde::{Deserialize, Serialize};
+
+/// How per-chunk encryption keys are chosen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChunkKeyMode {
+    /// Namespace data key with a random nonce per chunk
+    #[default]
+    Random,
+    /// Key derived from the namespace key and the chunk ID
+    Convergent,
+}
+
+/// An encrypted chunk as stored on disk or shipped to peers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedChunk {
+    /// Keyed hash of the chunk ID, safe to expose to relays
+    pub locator: ContentHash,
+    /// Key mode the chunk was sealed with
+    pub mode: ChunkKeyMode,
+    /// Cipher suite used for sealing
+    pub cipher_suite: CipherSuite,
+    /// Nonce and ciphertext (including tag)
+    pub sealed: SealedMessage,
+}
+
+impl SealedChunk {
+    /// Size of the sealed payload in bytes
+    pub fn size(&self) -> usize {
+        self.sealed.size()
+    }
+}
+
+/// Seals and opens chunks with a namespace key
+///
+/// Sub-keys for data, locators and convergent keys are derived from the
+/// namespace key, so only that one secret has to be distributed to the
+/// devices sharing a namespace.
+#[derive(Clone)]
+pub struct ChunkCipher {
+    data_key: EncryptionKey,
+    locator_key: EncryptionKey,
+    convergent_key: EncryptionKey,
+    mode: ChunkKeyMode,
+    suite: CipherSuite,
+}
+
+impl ChunkCipher {
+    /// Create a cipher from a namespace key
+    ///
+    /// Uses [`ChunkKeyMode::Random`] and the fastest cipher suite for this CPU.
+    pub fn new(namespace_key: &EncryptionKey) -> Self {
+        let secret = namespace_key.as_bytes();
+        Self {
+            data_key: EncryptionKey::from_high_entropy_secret(secret, b"vdfs chunk data"),
+            locator_key: EncryptionKey::from_high_entropy_secret(secret, b"vdfs chunk locator"),
+            convergent_key: EncryptionKey::from_high_entropy_secret(
+                secret,
+                b"vdfs chunk convergent",
+            ),
+            mode: ChunkKeyMode::default(),
+            suite: CipherSuite::preferred(),
+        }
+    }
+
+    /// Derive a namespace key from a master key and namespace name
+    pub fn for_namespace(master_key: &EncryptionKey, namespace: &str) -> Self {
+        let namespace_key =
+            EncryptionKey::from_high_entropy_secret(master_key.as_bytes(), namespace.as_bytes());
+        Self::new(&namespace_key)
+    }
+
+    /// Set the key mode used for sealing
+    pub fn with_mode(mut self, mode: ChunkKeyMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set the cipher suite used for sealing
+    pub fn with_cipher_suite(mut self, suite: CipherSuite) -> Self {
+        self.suite = suite;
+        self
+    }
+
+    /// Get the key mode used for sealing
+    pub fn mode(&self) -> ChunkKeyMode {
+        self.mode
+    }
+
+    /// Get the cipher suite used for sealing
+    pub fn cipher_suite(&self) -> CipherSuite {
+        self.suite
+    }
+
+    /// Compute the public locator for a chunk ID
+    pub fn locator(&self, id: &ChunkId) -> ContentHash {
+        blake3::keyed_hash(self.locator_key.as_bytes(), id.as_bytes()).into()
+    }
+
+    /// Encrypt a chunk
+    pub fn seal(&self, chunk: &Chunk) -> Result<SealedChunk, VdfsError> {
+        let locator = self.locator(&chunk.id);
+        let sealed = match self.mode {
+            ChunkKeyMode::Random => {
+                encrypt_with_aad(self.suite, &self.data_key, &chunk.data, locator.as_bytes())?
+            }
+            // The key is unique per chunk content, so a fixed nonce never
+            // encrypts two different plaintexts under the same key.
+            ChunkKeyMode::Convergent => encrypt_with_nonce(
+                self.suite,
+                &self.convergent_chunk_key(&chunk.id),
+                [0u8; NONCE_SIZE],
+                &chunk.data,
+                locator.as_bytes(),
+            )?,
+        };
+
+        Ok(SealedChunk {
+            locator,
+            mode: self.mode,
+            cipher_suite: self.suite,
+            sealed,
+        })
+    }
+
+    /// Decrypt a sealed chunk, checking it really is chunk `id`
+    pub fn open(&self, id: &ChunkId, sealed: &SealedChunk) -> Result<Chunk, VdfsError> {
+        if sealed.locator != self.locator(id) {
+            return Err(VdfsError::ChunkNotFound(id.to_hex()));
+        }
+
+        let key = match sealed.mode {
+            ChunkKeyMode::Random => self.data_key.clone(),
+            ChunkKeyMode::Convergent => self.convergent_chunk_key(id),
+        };
+        let data = decrypt_with_aad(
+            sealed.cipher_suite,
+            &key,
+            &sealed.sealed,
+            sealed.locator.as_bytes(),
+        )?;
+
+        let chunk = Chunk::new(data);
+        if chunk.id != *id {
+            return Err(VdfsError::HashMismatch {
+                expected: id.to_hex(),
+                actual: chunk.id.to_hex(),
+            });
+        }
+        Ok(chunk)
+    }
+
+    fn convergent_chunk_key(&self, id: &ChunkId) -> EncryptionKey {
+        EncryptionKey::from_high_entropy_secret(self.convergent_key.as_bytes(), id.as_bytes())
+    }
+}
+
+impl std::fmt::Debug for ChunkCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkCipher")
+            .field("mode", &self.mode)
+            .field("suite", &self.suite)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher() -> ChunkCipher {
+        ChunkCipher::new(&EncryptionKey::from_bytes([7u8; 32]))
+    }
+
+    #[test]
+    fn seal_open_roundtrip_hides_content() {
+        let chunk = Chunk::new(b"secret chunk contents".to_vec());
+
+        for mode in [ChunkKeyMode::Random, ChunkKeyMode::Convergent] {
+            let cipher = cipher().with_mode(mode);
+            let sealed = cipher.seal(&chunk).unwrap();
+
+            assert_ne!(sealed.locator, chunk.id);
+            assert!(!sealed.sealed.ciphertext.windows(6).any(|w| w == b"secret"));
+            assert_eq!(cipher.open(&chunk.id, &sealed).unwrap().data, chunk.data);
+        }
+    }
+
+    #[test]
+    fn convergent_mode_is_deterministic() {
+        let chunk = Chunk::new(b"same content".to_vec());
+
+        let convergent = cipher().with_mode(ChunkKeyMode::Convergent);
+        assert_eq!(
+            convergent.seal(&chunk).unwrap(),
+            convergent.seal(&chunk).unwrap()
+        );
+
+        let random = cipher();
+        assert_ne!(random.seal(&chunk).unwrap(), random.seal(&chunk).unwrap());
+    }
+
+    #[test]
+    fn open_rejects_wrong_key_or_chunk() {
+        let chunk = Chunk::new(b"payload".to_vec());
+        let other = Chunk::new(b"other".to_vec());
+        let sealed = cipher().seal(&chunk).unwrap();
+
+        let stranger = ChunkCipher::new(&EncryptionKey::from_bytes([9u8; 32]));
+        assert!(stranger.open(&chunk.id, &sealed).is_err());
+        assert!(cipher().open(&other.id, &sealed).is_err());
+    }
+}
@@ -5,14 +5,28 @@
 //! # Requirements Coverage
 //! - Requirement 5.3: Virtual filesystem interface
 
-use super::chunk::{chunk_data, reassemble_chunks, ChunkStore};
-use super::metadata::FileMetadata;
-use super::sync::{SyncEngine, SyncStatus};
-use crate::encryption::hash::hash_data;
+use super::archive::NamespaceArchive;
+use super::chunk::{chunk_data, reassemble_chunks, Chunk, ChunkId, ChunkStore};
+use super::gc::{ChunkRefs, GcReport};
+use super::history::{FileVersion, Snapshot, VersionHistory};
+use super::lock::FileLock;
+use super::metadata::{FileMetadata, Permissions};
+use super::quota::{self, Quota, QuotaUsage};
+use super::reader::{chunk_index, ChunkSource};
+use super::replication::{ChunkLocations, PlacementEngine, ReplicationPlan, ReplicationPolicy};
+use super::resolver::ConflictResolver;
+use super::scrub::ScrubReport;
+use super::sync::{Conflict, SyncEngine, SyncState, SyncStatus};
+use crate::encryption::hash::{hash_data, ContentHash};
 use crate::error::VdfsError;
+use crate::scheduler::{TransferClass, TransferScheduler};
+use chrono::Utc;
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
+use uuid::Uuid;
 
 /// Virtual Distributed File System
 ///
@@ -22,6 +36,21 @@ pub struct VirtualFs {
     chunks: Arc<ChunkStore>,
     /// Sync engine
     sync: Arc<RwLock<SyncEngine>>,
+    /// Chunk reference counts for garbage collection
+    refs: RwLock<ChunkRefs>,
+    /// Prior file versions and snapshots
+    history: RwLock<VersionHistory>,
+    /// Storage limits for this namespace
+    quota: RwLock<Quota>,
+    /// Replication requirements for this namespace
+    replication: RwLock<ReplicationPolicy>,
+    /// Where to fetch chunks missing from the local store
+    chunk_source: Option<Arc<dyn ChunkSource>>,
+    /// Paces chunk fetches from the source
+    scheduler: Option<TransferScheduler>,
+    /// Held shared by writers and exclusively by GC, so a sweep never
+    /// sees chunks whose metadata has not been recorded yet
+    gc_guard: RwLock<()>,
     /// Mount point (virtual root)
     mount_point: PathBuf,
 }
@@ -32,6 +61,13 @@ impl VirtualFs {
         Self {
             chunks: Arc::new(ChunkStore::new()),
             sync: Arc::new(RwLock::new(SyncEngine::new(node_id))),
+            refs: RwLock::new(ChunkRefs::new()),
+            history: RwLock::new(VersionHistory::default()),
+            quota: RwLock::new(Quota::unlimited()),
+            replication: RwLock::new(ReplicationPolicy::default()),
+            chunk_source: None,
+            scheduler: None,
+            gc_guard: RwLock::new(()),
             mount_point,
         }
     }
@@ -41,10 +77,134 @@ impl VirtualFs {
         Self {
             chunks: Arc::new(ChunkStore::with_chunk_size(chunk_size)),
             sync: Arc::new(RwLock::new(SyncEngine::new(node_id))),
+            refs: RwLock::new(ChunkRefs::new()),
+            history: RwLock::new(VersionHistory::default()),
+            quota: RwLock::new(Quota::unlimited()),
+            replication: RwLock::new(ReplicationPolicy::default()),
+            chunk_source: None,
+            scheduler: None,
+            gc_guard: RwLock::new(()),
             mount_point,
         }
     }
 
+    /// Create on top of an existing chunk store
+    ///
+    /// Use this to plug in a store configured with
+    /// [`ChunkStore::with_encryption`].
+    pub fn with_chunk_store(node_id: String, mount_point: PathBuf, chunks: ChunkStore) -> Self {
+        Self {
+            chunks: Arc::new(chunks),
+            sync: Arc::new(RwLock::new(SyncEngine::new(node_id))),
+            refs: RwLock::new(ChunkRefs::new()),
+            history: RwLock::new(VersionHistory::default()),
+            quota: RwLock::new(Quota::unlimited()),
+            replication: RwLock::new(ReplicationPolicy::default()),
+            chunk_source: None,
+            scheduler: None,
+            gc_guard: RwLock::new(()),
+            mount_point,
+        }
+    }
+
+    /// Set how many prior versions are kept per path
+    ///
+    /// Zero disables version history.
+    pub fn with_max_versions(mut self, max_versions: usize) -> Self {
+        self.history = RwLock::new(VersionHistory::new(max_versions));
+        self
+    }
+
+    /// Fetch chunks missing from the local store from `source`
+    pub fn with_chunk_source(mut self, source: Arc<dyn ChunkSource>) -> Self {
+        self.chunk_source = Some(source);
+        self
+    }
+
+    /// Pace chunk fetches from the chunk source as sync traffic
+    pub fn with_scheduler(mut self, scheduler: TransferScheduler) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    /// Enforce storage limits on this namespace
+    ///
+    /// The byte limit is applied to the chunk store.
+    pub fn with_quota(mut self, quota: Quota) -> Self {
+        self.chunks.set_max_bytes(quota.max_bytes);
+        self.quota = RwLock::new(quota);
+        self
+    }
+
+    /// Change the storage limits
+    ///
+    /// Lowering a limit below current usage does not remove anything; it
+    /// only rejects further growth.
+    pub async fn set_quota(&self, quota: Quota) {
+        let mut current = self.quota.write().await;
+        self.chunks.set_max_bytes(quota.max_bytes);
+        *current = quota;
+    }
+
+    /// Get the storage limits
+    pub async fn quota(&self) -> Quota {
+        *self.quota.read().await
+    }
+
+    /// Get current usage against the quota
+    pub async fn usage(&self) -> QuotaUsage {
+        let files = self.sync.read().await.state().len() as u64;
+        QuotaUsage {
+            bytes: self.chunks.used_bytes(),
+            files,
+            quota: self.quota().await,
+        }
+    }
+
+    /// Set the replication policy for this namespace
+    pub fn with_replication_policy(mut self, policy: ReplicationPolicy) -> Self {
+        self.replication = RwLock::new(policy);
+        self
+    }
+
+    /// Change the replication policy
+    pub async fn set_replication_policy(&self, policy: ReplicationPolicy) {
+        *self.replication.write().await = policy;
+    }
+
+    /// Get the replication policy
+    pub async fn replication_policy(&self) -> ReplicationPolicy {
+        self.replication.read().await.clone()
+    }
+
+    /// Plan chunk transfers that satisfy the replication policy
+    ///
+    /// `locations` describes what peers hold; this node's own chunks and
+    /// its being online are added automatically.
+    pub async fn replication_plan(
+        &self,
+        locations: &ChunkLocations,
+        online: &BTreeSet<String>,
+    ) -> ReplicationPlan {
+        let node_id = self.sync.read().await.state().node_id().to_string();
+        let mut locations = locations.clone();
+        locations.add_store(&node_id, &self.chunks).await;
+        let mut online = online.clone();
+        online.insert(node_id);
+
+        let files: Vec<FileMetadata> = {
+            let sync = self.sync.read().await;
+            sync.state().list_files().into_iter().cloned().collect()
+        };
+        PlacementEngine::new(self.replication_policy().await).plan(&files, &locations, &online)
+    }
+
+    /// Fail if `additional` new entries would exceed the entry limit
+    async fn check_new_entries(&self, state: &SyncState, additional: u64) -> Result<(), VdfsError> {
+        let max_files = self.quota.read().await.max_files;
+        quota::check("files", state.len() as u64, additional, max_files)
+    }
+
     /// Get the mount point
     pub fn mount_point(&self) -> &Path {
         &self.mount_point
@@ -63,38 +223,112 @@ impl VirtualFs {
     ///
     /// Chunks the data, stores chunks, and creates metadata.
     pub async fn write(&self, path: &Path, data: &[u8]) -> Result<FileMetadata, VdfsError> {
+        let chunks = chunk_data(data, self.chunks.chunk_size());
+        self.write_chunks(path, &[], chunks, data.len() as u64, hash_data(data))
+            .await
+    }
+
+    /// Store `chunks` and record the file as `committed` followed by them
+    ///
+    /// Used by [`VdfsWriter`](super::writer::VdfsWriter), which tracks the
+    /// chunk list, size and hash of what it wrote so far. Storing and
+    /// committing happen under the GC guard, so the new chunks are never
+    /// collected before the metadata refers to them.
+    pub(crate) async fn write_chunks(
+        &self,
+        path: &Path,
+        committed: &[ChunkId],
+        chunks: Vec<Chunk>,
+        size: u64,
+        content_hash: ContentHash,
+    ) -> Result<FileMetadata, VdfsError> {
         let normalized = self.normalize_path(path);
+        let _guard = self.gc_guard.read().await;
 
-        // Chunk the data
-        let chunks = chunk_data(data, self.chunks.chunk_size());
+        // Overwriting content keeps the file's attributes; a new file fails
+        // early rather than storing chunks it can't be added with
+        let xattrs = {
+            let sync = self.sync.read().await;
+            match sync.state().get(&normalized) {
+                Some(existing) => existing.xattrs.clone(),
+                None => {
+                    self.check_new_entries(sync.state(), 1).await?;
+                    BTreeMap::new()
+                }
+            }
+        };
 
-        // Store chunks and collect IDs
-        let mut chunk_ids = Vec::with_capacity(chunks.len());
+        let mut chunk_ids = Vec::with_capacity(committed.len() + chunks.len());
+        chunk_ids.extend_from_slice(committed);
         for chunk in chunks {
-            let id = self.chunks.store(chunk).await;
-            chunk_ids.push(id);
+            chunk_ids.push(self.chunks.store(chunk).await?);
         }
 
-        // Compute content hash
-        let content_hash = hash_data(data);
+        let mut metadata = FileMetadata::new_file(normalized, size, content_hash, chunk_ids);
+        metadata.chunk_size = self.chunks.chunk_size() as u64;
+        metadata.xattrs = xattrs;
 
-        // Create metadata
-        let metadata = FileMetadata::new_file(
-            normalized.clone(),
-            data.len() as u64,
-            content_hash,
-            chunk_ids,
-        );
+        self.commit(metadata, true).await
+    }
 
-        // Update sync state
-        {
+    /// Record new metadata for a path, keeping the replaced version
+    ///
+    /// Assigns the next version number for the path, so versions keep
+    /// increasing across deletes and restores. With `check_quota` set, a
+    /// new path fails if it would exceed the entry limit.
+    async fn commit(
+        &self,
+        mut metadata: FileMetadata,
+        check_quota: bool,
+    ) -> Result<FileMetadata, VdfsError> {
+        let previous = {
             let mut sync = self.sync.write().await;
-            sync.create_file(metadata.clone());
-        }
+            let history = self.history.read().await;
+            let previous = sync.state().get(&metadata.path).cloned();
+            if check_quota && previous.is_none() {
+                self.check_new_entries(sync.state(), 1).await?;
+            }
+            let latest = previous
+                .as_ref()
+                .map(|p| p.version)
+                .max(history.latest_version(&metadata.path));
+
+            metadata.version = latest.map_or(1, |v| v + 1);
+            match &previous {
+                Some(previous) => {
+                    metadata.created = previous.created;
+                    sync.update_file(metadata.clone());
+                }
+                None => {
+                    // A recreated path continues the deleted file's history
+                    if let Some(deleted) = history.versions(&metadata.path).first() {
+                        metadata.clock.merge(&deleted.metadata.clock);
+                    }
+                    sync.create_file(metadata.clone());
+                }
+            }
+            if let Some(stored) = sync.state().get(&metadata.path) {
+                metadata.clock = stored.clock.clone();
+            }
+            previous
+        };
 
+        self.refs.write().await.add(&metadata);
+        if let Some(previous) = previous {
+            self.retire(previous, false).await;
+        }
         Ok(metadata)
     }
 
+    /// Move replaced or deleted metadata into the history
+    async fn retire(&self, metadata: FileMetadata, deleted: bool) {
+        let evicted = self.history.write().await.record(metadata, deleted);
+        let mut refs = self.refs.write().await;
+        for metadata in &evicted {
+            refs.release(metadata);
+        }
+    }
+
     /// Read a file
     ///
     /// Retrieves chunks and reassembles the file.
@@ -115,7 +349,7 @@ impl VirtualFs {
         // Retrieve chunks
         let mut chunks = Vec::with_capacity(metadata.chunks.len());
         for chunk_id in &metadata.chunks {
-            let chunk = self.chunks.get(chunk_id).await?;
+            let chunk = self.fetch_chunk(chunk_id).await?;
             chunks.push(chunk);
         }
 
@@ -136,7 +370,103 @@ impl VirtualFs {
         Ok(data)
     }
 
+    /// Read `len` bytes of a file starting at `offset`
+    ///
+    /// Only the chunks covering the range are fetched. The range is clipped
+    /// to the end of the file.
+    pub async fn read_range(
+        &self,
+        path: &Path,
+        offset: u64,
+        len: u64,
+    ) -> Result<Vec<u8>, VdfsError> {
+        let metadata = self.stat(path).await?;
+        if !metadata.is_file() {
+            return Err(VdfsError::NotFound(metadata.path));
+        }
+
+        let end = offset.saturating_add(len).min(metadata.size);
+        if offset >= end {
+            return Ok(Vec::new());
+        }
+
+        let offsets = self.chunk_offsets(&metadata).await?;
+        let first = chunk_index(&offsets, offset);
+        let last = chunk_index(&offsets, end - 1);
+
+        let mut data = Vec::with_capacity((end - offset) as usize);
+        for (chunk_id, start) in metadata.chunks[first..=last]
+            .iter()
+            .zip(&offsets[first..=last])
+        {
+            let chunk = self.fetch_chunk(chunk_id).await?;
+            let to = ((end - start) as usize).min(chunk.data.len());
+            let from = (offset.saturating_sub(*start) as usize).min(to);
+            data.extend_from_slice(&chunk.data[from..to]);
+        }
+        Ok(data)
+    }
+
+    /// Get a chunk from the local store, or from the chunk source
+    ///
+    /// Fetched chunks are verified against their ID and cached locally
+    /// when the quota allows. Fetches wait for the scheduler set with
+    /// [`with_scheduler`](Self::with_scheduler).
+    pub async fn fetch_chunk(&self, id: &ChunkId) -> Result<Chunk, VdfsError> {
+        let source = match (self.chunks.get(id).await, &self.chunk_source) {
+            (Ok(chunk), _) => return Ok(chunk),
+            (Err(VdfsError::ChunkNotFound(_)), Some(source)) => source,
+            (Err(e), _) => return Err(e),
+        };
+
+        if let Some(scheduler) = &self.scheduler {
+            // The real size is only known after the fetch; a full chunk is
+            // the estimate
+            scheduler
+                .acquire(TransferClass::Sync, self.chunks.chunk_size() as u64)
+                .await;
+        }
+        let chunk = source.fetch(id).await?;
+        if chunk.id != *id || !chunk.verify() {
+            return Err(VdfsError::HashMismatch {
+                expected: id.to_hex(),
+                actual: hash_data(&chunk.data).to_hex(),
+            });
+        }
+        match self.chunks.store(chunk.clone()).await {
+            Ok(_) | Err(VdfsError::QuotaExceeded { .. }) => Ok(chunk),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Start offset of each chunk of a file
+    ///
+    /// Metadata without a recorded chunk size needs every chunk fetched to
+    /// learn the lengths.
+    pub(crate) async fn chunk_offsets(
+        &self,
+        metadata: &FileMetadata,
+    ) -> Result<Vec<u64>, VdfsError> {
+        if metadata.chunk_size > 0 {
+            return Ok((0..metadata.chunks.len() as u64)
+                .map(|i| i * metadata.chunk_size)
+                .collect());
+        }
+
+        let mut offsets = Vec::with_capacity(metadata.chunks.len());
+        let mut start = 0;
+        for chunk_id in &metadata.chunks {
+            offsets.push(start);
+            start += self.fetch_chunk(chunk_id).await?.size() as u64;
+        }
+        Ok(offsets)
+    }
+
     /// Delete a file
+    ///
+    /// The deleted version is kept in the history and can be brought back
+    /// with [`VirtualFs::restore`]. Its chunks are reclaimed by
+    /// [`VirtualFs::gc`] once the version is evicted or pruned.
     pub async fn delete(&self, path: &Path) -> Result<(), VdfsError> {
         let normalized = self.normalize_path(path);
 
@@ -147,20 +477,102 @@ impl VirtualFs {
         }
         .ok_or_else(|| VdfsError::NotFound(normalized.clone()))?;
 
-        // Remove chunks (if not referenced elsewhere - simplified: always remove)
-        for chunk_id in &metadata.chunks {
-            self.chunks.remove(chunk_id).await;
-        }
-
         // Update sync state
         {
             let mut sync = self.sync.write().await;
             sync.delete_file(normalized);
         }
 
+        self.retire(metadata, true).await;
+
+        Ok(())
+    }
+
+    /// Rename a file or directory
+    ///
+    /// A directory is moved together with everything below it. Moved
+    /// entries keep their chunks, so no data is re-stored.
+    pub async fn rename(&self, from: &Path, to: &Path) -> Result<(), VdfsError> {
+        let from = self.normalize_path(from);
+        let to = self.normalize_path(to);
+        let _guard = self.gc_guard.read().await;
+
+        let entries: Vec<FileMetadata> = {
+            let sync = self.sync.read().await;
+            sync.state()
+                .list_files()
+                .into_iter()
+                .filter(|f| f.path.starts_with(&from))
+                .cloned()
+                .collect()
+        };
+        if entries.is_empty() {
+            return Err(VdfsError::NotFound(from));
+        }
+
+        for entry in entries {
+            let mut moved = entry.clone();
+            moved.path = match entry.path.strip_prefix(&from) {
+                Ok(rest) if !rest.as_os_str().is_empty() => to.join(rest),
+                _ => to.clone(),
+            };
+            moved.modified = Utc::now();
+            // The source is deleted right after, so the entry count is unchanged
+            self.commit(moved, false).await?;
+            self.delete(&entry.path).await?;
+        }
+
         Ok(())
     }
 
+    /// Change the permissions of a file or directory
+    pub async fn set_permissions(
+        &self,
+        path: &Path,
+        permissions: Permissions,
+    ) -> Result<FileMetadata, VdfsError> {
+        let mut metadata = self.stat(path).await?;
+        metadata.permissions = permissions;
+        metadata.modified = Utc::now();
+        self.commit(metadata, true).await
+    }
+
+    /// Set an extended attribute on a file or directory
+    pub async fn set_xattr(
+        &self,
+        path: &Path,
+        name: &str,
+        value: &[u8],
+    ) -> Result<FileMetadata, VdfsError> {
+        let mut metadata = self.stat(path).await?;
+        metadata.set_xattr(name, value.to_vec())?;
+        metadata.modified = Utc::now();
+        self.commit(metadata, true).await
+    }
+
+    /// Get an extended attribute
+    pub async fn get_xattr(&self, path: &Path, name: &str) -> Result<Option<Vec<u8>>, VdfsError> {
+        Ok(self.stat(path).await?.xattr(name).map(<[u8]>::to_vec))
+    }
+
+    /// Get all extended attributes of a path
+    pub async fn xattrs(&self, path: &Path) -> Result<BTreeMap<String, Vec<u8>>, VdfsError> {
+        Ok(self.stat(path).await?.xattrs)
+    }
+
+    /// Remove an extended attribute
+    ///
+    /// Returns whether the attribute existed; nothing is recorded if not.
+    pub async fn remove_xattr(&self, path: &Path, name: &str) -> Result<bool, VdfsError> {
+        let mut metadata = self.stat(path).await?;
+        if metadata.remove_xattr(name).is_none() {
+            return Ok(false);
+        }
+        metadata.modified = Utc::now();
+        self.commit(metadata, true).await?;
+        Ok(true)
+    }
+
     /// Create a directory
     pub async fn mkdir(&self, path: &Path) -> Result<FileMetadata, VdfsError> {
         let normalized = self.normalize_path(path);
@@ -169,6 +581,9 @@ impl VirtualFs {
 
         {
             let mut sync = self.sync.write().await;
+            if sync.state().get(&normalized).is_none() {
+                self.check_new_entries(sync.state(), 1).await?;
+            }
             sync.create_file(metadata.clone());
         }
 
@@ -219,6 +634,171 @@ impl VirtualFs {
         sync.state().get_status(&normalized)
     }
 
+    /// Concurrent edits detected while syncing with peers
+    pub async fn conflicts(&self) -> Vec<Conflict> {
+        let sync = self.sync.read().await;
+        sync.state().conflicts().into_iter().cloned().collect()
+    }
+
+    /// Change how concurrent edits from peers are resolved
+    pub async fn set_conflict_resolver(&self, resolver: Arc<dyn ConflictResolver>) {
+        self.sync.write().await.set_resolver(resolver);
+    }
+
+    /// Mark a conflict as handled, keeping the current version
+    pub async fn resolve_conflict(&self, path: &Path) -> Option<Conflict> {
+        let normalized = self.normalize_path(path);
+        let mut sync = self.sync.write().await;
+        sync.state_mut().resolve_conflict(&normalized)
+    }
+
+    /// Take an advisory lock on a path
+    ///
+    /// Calling this again while holding the lock renews the lease. Other
+    /// peers see the lock after the next sync; writes are not blocked.
+    pub async fn lock(&self, path: &Path, lease: Duration) -> Result<FileLock, VdfsError> {
+        let normalized = self.normalize_path(path);
+        let mut sync = self.sync.write().await;
+        sync.state_mut()
+            .lock(&normalized, lease)
+            .map_err(|holder| VdfsError::Locked {
+                path: holder.path,
+                owner: holder.owner,
+                expires_at: holder.expires_at,
+            })
+    }
+
+    /// Release this node's lock on a path
+    ///
+    /// Returns whether a lock was released.
+    pub async fn unlock(&self, path: &Path) -> bool {
+        let normalized = self.normalize_path(path);
+        let mut sync = self.sync.write().await;
+        sync.state_mut().unlock(&normalized)
+    }
+
+    /// Get the live lock on a path, whoever holds it
+    pub async fn lock_holder(&self, path: &Path) -> Option<FileLock> {
+        let normalized = self.normalize_path(path);
+        let sync = self.sync.read().await;
+        sync.state().lock_on(&normalized).cloned()
+    }
+
+    /// All live locks in the namespace
+    pub async fn locks(&self) -> Vec<FileLock> {
+        let sync = self.sync.read().await;
+        sync.state().locks().into_iter().cloned().collect()
+    }
+
+    /// Merge file entries received from a peer
+    ///
+    /// Nothing is applied if the new paths would exceed the entry limit.
+    /// Versions replaced by the merge are kept in the history. Returns the
+    /// number of paths that changed.
+    pub async fn merge_remote(
+        &self,
+        files: impl IntoIterator<Item = FileMetadata>,
+    ) -> Result<usize, VdfsError> {
+        let _guard = self.gc_guard.read().await;
+        self.apply_remote(files.into_iter().collect()).await
+    }
+
+    /// Merge remote entries; the caller holds the GC guard
+    async fn apply_remote(&self, files: Vec<FileMetadata>) -> Result<usize, VdfsError> {
+        let changes = {
+            let mut sync = self.sync.write().await;
+            let mut paths: Vec<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
+            paths.sort();
+            paths.dedup();
+
+            let previous: Vec<Option<FileMetadata>> = paths
+                .iter()
+                .map(|path| sync.state().get(path).cloned())
+                .collect();
+            let new_paths = previous.iter().filter(|p| p.is_none()).count() as u64;
+            if new_paths > 0 {
+                self.check_new_entries(sync.state(), new_paths).await?;
+            }
+
+            let copies = sync.state_mut().merge_files(files);
+
+            paths
+                .iter()
+                .zip(previous)
+                .chain(copies.iter().map(|copy| (copy, None)))
+                .filter_map(|(path, previous)| {
+                    let current = sync.state().get(path).cloned()?;
+                    (previous.as_ref() != Some(&current)).then_some((current, previous))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (current, previous) in &changes {
+            self.refs.write().await.add(current);
+            if let Some(previous) = previous {
+                self.retire(previous.clone(), false).await;
+            }
+        }
+        Ok(changes.len())
+    }
+
+    /// Export `path` and everything below it, with the chunks it references
+    ///
+    /// Chunks missing locally are fetched from the chunk source.
+    pub async fn export(&self, path: &Path) -> Result<NamespaceArchive, VdfsError> {
+        let root = self.normalize_path(path);
+        let _guard = self.gc_guard.read().await;
+
+        let (node_id, mut files) = {
+            let sync = self.sync.read().await;
+            let files: Vec<FileMetadata> = sync
+                .state()
+                .list_files()
+                .into_iter()
+                .filter(|file| file.path.starts_with(&root))
+                .cloned()
+                .collect();
+            (sync.state().node_id().to_string(), files)
+        };
+        if files.is_empty() && root != self.mount_point {
+            return Err(VdfsError::NotFound(root));
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut ids: Vec<ChunkId> = files
+            .iter()
+            .flat_map(|f| f.chunks.iter().copied())
+            .collect();
+        ids.sort_by_key(|id| *id.as_bytes());
+        ids.dedup();
+        let mut chunks = Vec::with_capacity(ids.len());
+        for id in &ids {
+            chunks.push(self.fetch_chunk(id).await?);
+        }
+
+        Ok(NamespaceArchive {
+            node_id,
+            created_at: Utc::now(),
+            root,
+            files,
+            chunks,
+        })
+    }
+
+    /// Import an archive made by [`export`](Self::export)
+    ///
+    /// Entries are merged as if they came from a peer, so newer local
+    /// versions win and concurrent edits are recorded as conflicts. Returns
+    /// the number of paths that changed.
+    pub async fn import(&self, archive: &NamespaceArchive) -> Result<usize, VdfsError> {
+        archive.verify()?;
+        let _guard = self.gc_guard.read().await;
+        for chunk in &archive.chunks {
+            self.chunks.store(chunk.clone()).await?;
+        }
+        self.apply_remote(archive.files.clone()).await
+    }
+
     /// Get the chunk store
     pub fn chunk_store(&self) -> &ChunkStore {
         &self.chunks
@@ -229,6 +809,276 @@ impl VirtualFs {
         &self.sync
     }
 
+    /// Run a garbage collection pass over the chunk store
+    ///
+    /// Reference counts are rebuilt from the current sync state, version
+    /// history and snapshots first, so files added or removed by a remote
+    /// merge are accounted for. With
+    /// `dry_run` set, nothing is removed and the report only describes
+    /// what would be reclaimed.
+    pub async fn gc(&self, dry_run: bool) -> GcReport {
+        let _guard = self.gc_guard.write().await;
+
+        let refs = {
+            let sync = self.sync.read().await;
+            let history = self.history.read().await;
+            ChunkRefs::from_metadata(
+                sync.state()
+                    .list_files()
+                    .into_iter()
+                    .chain(history.retained_metadata()),
+            )
+        };
+        let report = self.chunks.sweep(&refs, dry_run).await;
+        *self.refs.write().await = refs;
+
+        report
+    }
+
+    /// Bytes held by chunks that no file references
+    pub async fn reclaimable_bytes(&self) -> usize {
+        self.gc(true).await.reclaimable_bytes
+    }
+
+    /// Check the chunk store for corrupt and missing chunks
+    ///
+    /// Every stored chunk is re-hashed and every chunk referenced by a
+    /// current file, a retained version or a snapshot is checked for
+    /// presence. With `repair` set, corrupt chunks are removed and damaged
+    /// referenced chunks are fetched again from the chunk source; without a
+    /// source they are reported as unrepaired.
+    pub async fn scrub(&self, repair: bool) -> ScrubReport {
+        // Keeps GC from sweeping chunks while they are checked
+        let _guard = self.gc_guard.read().await;
+        let (referenced, files) = {
+            let sync = self.sync.read().await;
+            let history = self.history.read().await;
+            let files: Vec<FileMetadata> = sync.state().list_files().into_iter().cloned().collect();
+            let refs = ChunkRefs::from_metadata(files.iter().chain(history.retained_metadata()));
+            (refs.referenced(), files)
+        };
+
+        let (scanned_chunks, corrupt) = self.chunks.find_corrupt().await;
+        let mut missing = Vec::new();
+        for id in &referenced {
+            if !self.chunks.contains(id).await {
+                missing.push(*id);
+            }
+        }
+        missing.sort_by_key(|id| *id.as_bytes());
+
+        let mut damaged: Vec<ChunkId> = corrupt
+            .iter()
+            .filter(|id| referenced.contains(id))
+            .chain(&missing)
+            .copied()
+            .collect();
+        damaged.sort_by_key(|id| *id.as_bytes());
+
+        let mut repaired = Vec::new();
+        let mut unrepaired = Vec::new();
+        if repair {
+            for id in &corrupt {
+                self.chunks.remove(id).await;
+            }
+            for id in damaged {
+                if self.chunk_source.is_some()
+                    && self.fetch_chunk(&id).await.is_ok()
+                    && self.chunks.contains(&id).await
+                {
+                    repaired.push(id);
+                } else {
+                    unrepaired.push(id);
+                }
+            }
+        } else {
+            unrepaired = damaged;
+        }
+
+        let mut affected_files: Vec<PathBuf> = files
+            .into_iter()
+            .filter(|file| file.chunks.iter().any(|id| unrepaired.contains(id)))
+            .map(|file| file.path)
+            .collect();
+        affected_files.sort();
+
+        if !corrupt.is_empty() || !missing.is_empty() {
+            tracing::warn!(
+                corrupt = corrupt.len(),
+                missing = missing.len(),
+                repaired = repaired.len(),
+                unrepaired = unrepaired.len(),
+                "Scrub found damaged chunks"
+            );
+        }
+
+        ScrubReport {
+            repair,
+            scanned_chunks,
+            corrupt,
+            missing,
+            repaired,
+            unrepaired,
+            affected_files,
+        }
+    }
+
+    /// Get the reference count of a chunk as of the last write or GC pass
+    pub async fn chunk_ref_count(&self, id: &ChunkId) -> usize {
+        self.refs.read().await.count(id)
+    }
+
+    /// Get the versions of a path, newest first
+    ///
+    /// The current version (if the path exists) comes first, followed by
+    /// replaced and deleted versions from the history.
+    pub async fn history(&self, path: &Path) -> Vec<FileVersion> {
+        let normalized = self.normalize_path(path);
+        let current = {
+            let sync = self.sync.read().await;
+            sync.state().get(&normalized).cloned()
+        };
+
+        let mut versions: Vec<FileVersion> = current
+            .map(|metadata| FileVersion {
+                metadata,
+                replaced_at: None,
+                deleted: false,
+            })
+            .into_iter()
+            .collect();
+        versions.extend(self.history.read().await.versions(&normalized));
+        versions
+    }
+
+    /// Restore a prior version of a path
+    ///
+    /// The restored content becomes a new version; the version it replaces
+    /// is kept in the history, so a restore can itself be undone.
+    pub async fn restore(&self, path: &Path, version: u64) -> Result<FileMetadata, VdfsError> {
+        let normalized = self.normalize_path(path);
+        let _guard = self.gc_guard.read().await;
+
+        let target = self
+            .history
+            .read()
+            .await
+            .find(&normalized, version)
+            .map(|v| v.metadata.clone())
+            .ok_or_else(|| VdfsError::VersionNotFound {
+                path: normalized.clone(),
+                version,
+            })?;
+
+        self.restore_metadata(target).await
+    }
+
+    /// Re-commit old metadata as the current version
+    async fn restore_metadata(
+        &self,
+        mut metadata: FileMetadata,
+    ) -> Result<FileMetadata, VdfsError> {
+        for chunk_id in &metadata.chunks {
+            if !self.chunks.contains(chunk_id).await {
+                return Err(VdfsError::ChunkNotFound(chunk_id.to_hex()));
+            }
+        }
+
+        metadata.modified = Utc::now();
+        self.commit(metadata, true).await
+    }
+
+    /// Take a snapshot of every file in the namespace
+    pub async fn snapshot(&self, name: impl Into<String>) -> Snapshot {
+        let files: Vec<FileMetadata> = {
+            let sync = self.sync.read().await;
+            sync.state().list_files().into_iter().cloned().collect()
+        };
+
+        let snapshot = Snapshot {
+            id: Uuid::new_v4(),
+            name: name.into(),
+            created_at: Utc::now(),
+            files,
+        };
+
+        {
+            let mut refs = self.refs.write().await;
+            for metadata in &snapshot.files {
+                refs.add(metadata);
+            }
+        }
+        self.history.write().await.add_snapshot(snapshot.clone());
+        snapshot
+    }
+
+    /// List snapshots, oldest first
+    pub async fn snapshots(&self) -> Vec<Snapshot> {
+        self.history.read().await.snapshots().to_vec()
+    }
+
+    /// Delete a snapshot
+    ///
+    /// Chunks only it referenced are reclaimed by the next GC pass.
+    pub async fn delete_snapshot(&self, id: &Uuid) -> Result<(), VdfsError> {
+        let snapshot = self
+            .history
+            .write()
+            .await
+            .remove_snapshot(id)
+            .ok_or_else(|| VdfsError::SnapshotNotFound(id.to_string()))?;
+
+        let mut refs = self.refs.write().await;
+        for metadata in &snapshot.files {
+            refs.release(metadata);
+        }
+        Ok(())
+    }
+
+    /// Roll the whole namespace back to a snapshot
+    ///
+    /// Files that changed since the snapshot are restored, files created
+    /// since are deleted. Every replaced version stays in the history.
+    /// Returns the number of paths changed.
+    pub async fn restore_snapshot(&self, id: &Uuid) -> Result<usize, VdfsError> {
+        let _guard = self.gc_guard.read().await;
+
+        let snapshot = self
+            .history
+            .read()
+            .await
+            .snapshot(id)
+            .cloned()
+            .ok_or_else(|| VdfsError::SnapshotNotFound(id.to_string()))?;
+        let current: Vec<FileMetadata> = {
+            let sync = self.sync.read().await;
+            sync.state().list_files().into_iter().cloned().collect()
+        };
+
+        let mut changed = 0;
+        for existing in &current {
+            if !snapshot.files.iter().any(|f| f.path == existing.path) {
+                self.delete(&existing.path).await?;
+                changed += 1;
+            }
+        }
+
+        for metadata in snapshot.files {
+            let unchanged = current.iter().any(|c| {
+                c.path == metadata.path
+                    && c.file_type == metadata.file_type
+                    && c.content_hash == metadata.content_hash
+                    && c.permissions == metadata.permissions
+            });
+            if !unchanged {
+                self.restore_metadata(metadata).await?;
+                changed += 1;
+            }
+        }
+
+        Ok(changed)
+    }
+
     /// Get storage statistics
     pub async fn stats(&self) -> FsStats {
         let sync = self.sync.read().await;
@@ -294,6 +1144,173 @@ mod tests {
         assert!(!fs.exists(Path::new("to_delete.txt")).await);
     }
 
+    #[tokio::test]
+    async fn gc_reclaims_only_orphaned_chunks() {
+        let fs = VirtualFs::with_chunk_size("test-node".to_string(), PathBuf::from("/vfs"), 4)
+            .with_max_versions(0);
+
+        fs.write(Path::new("a.txt"), b"sharedAAAA").await.unwrap();
+        fs.write(Path::new("b.txt"), b"sharedBBBB").await.unwrap();
+        fs.write(Path::new("a.txt"), b"sharedCCCC").await.unwrap();
+        fs.delete(Path::new("b.txt")).await.unwrap();
+
+        let report = fs.gc(true).await;
+        assert_eq!(report.orphaned.len(), 4);
+        assert_eq!(fs.reclaimable_bytes().await, 12);
+
+        let report = fs.gc(false).await;
+        assert_eq!(report.reclaimed_bytes(), 12);
+        assert_eq!(fs.reclaimable_bytes().await, 0);
+        assert_eq!(fs.read(Path::new("a.txt")).await.unwrap(), b"sharedCCCC");
+    }
+
+    #[tokio::test]
+    async fn history_and_restore() {
+        let fs = VirtualFs::new("test-node".to_string(), PathBuf::from("/vfs"));
+        let path = Path::new("notes.txt");
+
+        fs.write(path, b"first").await.unwrap();
+        fs.write(path, b"second").await.unwrap();
+        fs.delete(path).await.unwrap();
+
+        // Old versions keep their chunks alive through GC
+        assert_eq!(fs.gc(false).await.removed_chunks(), 0);
+
+        let versions: Vec<u64> = fs.history(path).await.iter().map(|v| v.version()).collect();
+        assert_eq!(versions, vec![2, 1]);
+
+        let restored = fs.restore(path, 1).await.unwrap();
+        assert_eq!(restored.version, 3);
+        assert_eq!(fs.read(path).await.unwrap(), b"first");
+        assert!(matches!(
+            fs.restore(path, 9).await,
+            Err(VdfsError::VersionNotFound { version: 9, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn snapshot_restore_rolls_back_namespace() {
+        let fs = VirtualFs::new("test-node".to_string(), PathBuf::from("/vfs"));
+        fs.write(Path::new("a.txt"), b"a1").await.unwrap();
+        fs.write(Path::new("b.txt"), b"b1").await.unwrap();
+        let snapshot = fs.snapshot("before").await;
+
+        fs.write(Path::new("a.txt"), b"a2").await.unwrap();
+        fs.delete(Path::new("b.txt")).await.unwrap();
+        fs.write(Path::new("c.txt"), b"c1").await.unwrap();
+
+        assert_eq!(fs.restore_snapshot(&snapshot.id).await.unwrap(), 3);
+        assert_eq!(fs.read(Path::new("a.txt")).await.unwrap(), b"a1");
+        assert_eq!(fs.read(Path::new("b.txt")).await.unwrap(), b"b1");
+        assert!(!fs.exists(Path::new("c.txt")).await);
+
+        fs.delete_snapshot(&snapshot.id).await.unwrap();
+        assert!(fs.snapshots().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rename_moves_directory_tree() {
+        let fs = VirtualFs::new("test-node".to_string(), PathBuf::from("/vfs"));
+        fs.mkdir(Path::new("old")).await.unwrap();
+        fs.write(Path::new("old/a.txt"), b"content").await.unwrap();
+
+        fs.rename(Path::new("old"), Path::new("new")).await.unwrap();
+
+        assert!(!fs.exists(Path::new("old")).await);
+        assert!(!fs.exists(Path::new("old/a.txt")).await);
+        assert!(fs.stat(Path::new("new")).await.unwrap().is_directory());
+        assert_eq!(fs.read(Path::new("new/a.txt")).await.unwrap(), b"content");
+    }
+
+    #[tokio::test]
+    async fn quota_limits_local_writes_and_remote_merges() {
+        let fs = VirtualFs::new("test-node".to_string(), PathBuf::from("/vfs"))
+            .with_quota(Quota::unlimited().with_max_files(2).with_max_bytes(16));
+
+        fs.write(Path::new("a.txt"), b"0123456789").await.unwrap();
+        assert!(matches!(
+            fs.write(Path::new("b.txt"), b"0123456789abcdef").await,
+            Err(VdfsError::QuotaExceeded {
+                resource: "bytes",
+                ..
+            })
+        ));
+        fs.write(Path::new("b.txt"), b"tiny").await.unwrap();
+        assert!(matches!(
+            fs.mkdir(Path::new("dir")).await,
+            Err(VdfsError::QuotaExceeded {
+                resource: "files",
+                ..
+            })
+        ));
+
+        // Overwriting an existing path doesn't add an entry
+        fs.write(Path::new("b.txt"), b"tiny").await.unwrap();
+
+        let peer = VirtualFs::new("peer".to_string(), PathBuf::from("/vfs"));
+        peer.write(Path::new("c.txt"), b"remote").await.unwrap();
+        let remote = peer.stat(Path::new("c.txt")).await.unwrap();
+        assert!(fs.merge_remote([remote.clone()]).await.is_err());
+        assert!(!fs.exists(Path::new("c.txt")).await);
+
+        // Chunks fetched from the peer count against the byte limit too
+        let chunk = peer.chunk_store().get(&remote.chunks[0]).await.unwrap();
+        assert!(fs.chunk_store().store(chunk.clone()).await.is_err());
+
+        fs.set_quota(Quota::unlimited()).await;
+        fs.chunk_store().store(chunk).await.unwrap();
+        assert_eq!(fs.merge_remote([remote]).await.unwrap(), 1);
+        assert_eq!(fs.read(Path::new("c.txt")).await.unwrap(), b"remote");
+
+        let usage = fs.usage().await;
+        assert_eq!(usage.files, 3);
+        assert_eq!(usage.bytes, 20);
+        assert_eq!(usage.remaining_bytes(), None);
+    }
+
+    #[tokio::test]
+    async fn xattrs_are_versioned_with_the_file() {
+        let fs = VirtualFs::new("test-node".to_string(), PathBuf::from("/vfs"));
+        let path = Path::new("photo.jpg");
+        fs.write(path, b"v1").await.unwrap();
+
+        fs.set_xattr(path, "user.tag", b"holiday").await.unwrap();
+        // Rewriting the content keeps the attributes
+        fs.write(path, b"v2").await.unwrap();
+        assert_eq!(
+            fs.get_xattr(path, "user.tag").await.unwrap(),
+            Some(b"holiday".to_vec())
+        );
+
+        assert!(fs.remove_xattr(path, "user.tag").await.unwrap());
+        assert!(!fs.remove_xattr(path, "user.tag").await.unwrap());
+        assert!(fs.xattrs(path).await.unwrap().is_empty());
+
+        // Older versions still carry the attribute
+        let tagged = fs.history(path).await[1].version();
+        fs.restore(path, tagged).await.unwrap();
+        assert_eq!(fs.xattrs(path).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn read_range_spans_chunk_boundaries() {
+        let fs = VirtualFs::with_chunk_size("test-node".to_string(), PathBuf::from("/vfs"), 4);
+        fs.write(Path::new("data.bin"), b"0123456789")
+            .await
+            .unwrap();
+        let path = Path::new("data.bin");
+
+        assert_eq!(fs.read_range(path, 0, 4).await.unwrap(), b"0123");
+        assert_eq!(fs.read_range(path, 3, 6).await.unwrap(), b"345678");
+        assert_eq!(fs.read_range(path, 8, 100).await.unwrap(), b"89");
+        assert!(fs.read_range(path, 10, 1).await.unwrap().is_empty());
+
+        // Metadata without a chunk size falls back to measuring chunks
+        let mut legacy = fs.stat(path).await.unwrap();
+        legacy.chunk_size = 0;
+        assert_eq!(fs.chunk_offsets(&legacy).await.unwrap(), vec![0, 4, 8]);
+    }
+
     #[tokio::test]
     async fn create_directory() {
         let fs = VirtualFs::new("test-node".to_string(), PathBuf::from("/vfs"));
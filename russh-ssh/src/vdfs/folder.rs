@@ -0,0 +1,843 @@
+//! Folder Sync Between Peers
+//!
+//! Keeps a local directory in step with the same folder on paired peers,
+//! in the manner of Syncthing. A [`SyncedFolder`] mirrors its directory
+//! into a [`VirtualFs`] through a [`DirWatcher`] and writes what it
+//! receives from peers back to disk.
+//!
+//! Folders are offered by a [`FolderSync`] registered on the tunnel agent,
+//! each to the peers it is shared with. Syncing is pull-based: a peer opens
+//! one encrypted stream to the `vdfs-sync` service and
+//! 1. compares Merkle roots, narrowing down the differing paths with a
+//!    [`Negotiation`],
+//! 2. fetches the metadata of those paths and the chunks it lacks,
+//! 3. asks for the files deleted since its last pull.
+//!
+//! Received entries are merged like any remote state, so concurrent edits
+//! are recorded as conflicts; the same content added on both sides before
+//! they first synced is not one. A delete applies only where the local
+//! version is one the deleting peer had seen, so a file edited while it
+//! was deleted elsewhere survives. Each side pulls from the other to sync
+//! both ways.
+//!
+//! A file changed on disk since it was last synced is never overwritten or
+//! removed; the watcher picks the change up and it syncs as a new version.
+//!
+//! # Requirements Coverage
+//! - Requirement 5.2: CRDT-based sync for conflict resolution
+
+use super::chunk::{chunk_data, Chunk, ChunkId};
+use super::clock::Causality;
+use super::filesystem::VirtualFs;
+use super::merkle::{MerkleTree, Negotiation, NodeSummary, Prefix};
+use super::metadata::FileMetadata;
+use super::sync::FileOperation;
+use super::watch::DirWatcher;
+use crate::encryption::hash::{hash_data, ContentHash};
+use crate::error::{P2PError, VdfsError};
+use crate::p2p::acl::PeerCapability;
+use crate::p2p::connection::P2PConnectionManager;
+use crate::p2p::secure::SecureStream;
+use crate::p2p::stream::BiStream;
+use crate::p2p::tunnel::{open_tunnel, StreamHandler, TunnelAgent};
+use async_trait::async_trait;
+use iroh::NodeId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// Service name of the folder sync protocol
+pub const FOLDER_SYNC_SERVICE: &str = "vdfs-sync";
+
+/// Suffix of files being written into place
+///
+/// They are also hidden, so the watcher does not pick them up.
+pub const PARTIAL_SUFFIX: &str = ".russh-sync";
+
+/// Most paths or chunks asked for in one request
+const BATCH_SIZE: usize = 256;
+
+/// Largest control frame; metadata batches can be sizeable
+const MAX_CONTROL_FRAME: usize = 16 * 1024 * 1024;
+
+/// Largest chunk frame
+const MAX_CHUNK_FRAME: usize = 16 * 1024 * 1024 + 1024;
+
+/// Requests from a pulling peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum SyncRequest {
+    /// Start syncing a folder
+    Open { folder: String },
+    /// Summarize trie nodes of the folder
+    Summarize { prefixes: Vec<Prefix> },
+    /// Send the current metadata of paths
+    Files { paths: Vec<PathBuf> },
+    /// Send the deleted versions of files removed after `since`
+    Deleted { since: u64 },
+    /// Send chunks
+    Chunks { ids: Vec<ChunkId> },
+}
+
+/// Replies from the serving peer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum SyncReply {
+    /// The folder is open; `clock` counts its operations
+    Opened { root: ContentHash, clock: u64 },
+    /// Answers to a [`SyncRequest::Summarize`]
+    Summaries { summaries: Vec<NodeSummary> },
+    /// Metadata of current or deleted files
+    Files { files: Vec<FileMetadata> },
+    /// The chunks among those asked for that follow as binary frames
+    Chunks { ids: Vec<ChunkId> },
+    /// The request failed; the stream stays usable
+    Error { reason: String },
+}
+
+/// What a pull changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PullReport {
+    /// The peer's operation clock; pass it as `since` to the next pull
+    pub clock: u64,
+    /// Files and directories written, relative to the folder root
+    pub updated: Vec<PathBuf>,
+    /// Files and directories removed, relative to the folder root
+    pub removed: Vec<PathBuf>,
+    /// Paths with a new conflict, relative to the folder root
+    pub conflicts: Vec<PathBuf>,
+    /// Chunk bytes received
+    pub bytes: u64,
+}
+
+impl PullReport {
+    /// Whether the pull changed nothing
+    pub fn is_empty(&self) -> bool {
+        self.updated.is_empty() && self.removed.is_empty() && self.conflicts.is_empty()
+    }
+}
+
+/// Whether `id` can name a synced folder
+///
+/// IDs are non-empty and hold only letters, digits, `-` and `_`.
+pub fn is_valid_folder_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// A local directory synced with peers
+pub struct SyncedFolder {
+    id: String,
+    root: PathBuf,
+    fs: Arc<VirtualFs>,
+}
+
+impl SyncedFolder {
+    /// Create a folder for the directory at `root`
+    ///
+    /// `id` names the folder on every peer that syncs it; see
+    /// [`is_valid_folder_id`]. `node_id` identifies this machine in
+    /// the folder's version clocks and should stay the same across runs.
+    pub fn new(
+        id: impl Into<String>,
+        root: impl Into<PathBuf>,
+        node_id: impl Into<String>,
+    ) -> Result<Self, VdfsError> {
+        let id = id.into();
+        if !is_valid_folder_id(&id) {
+            return Err(VdfsError::InvalidFolder(id));
+        }
+        let fs = VirtualFs::new(node_id.into(), Path::new("/").join(&id));
+        Ok(Self {
+            id,
+            root: root.into(),
+            fs: Arc::new(fs),
+        })
+    }
+
+    /// Get the folder ID
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get the local directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Get the virtual filesystem mirroring the directory
+    pub fn fs(&self) -> &Arc<VirtualFs> {
+        &self.fs
+    }
+
+    /// Create a watcher mirroring changes in the directory
+    pub fn watcher(&self) -> DirWatcher {
+        DirWatcher::new(self.fs.clone(), &self.root)
+    }
+
+    /// Get the number of operations recorded so far
+    pub async fn clock(&self) -> u64 {
+        self.fs.sync_engine().read().await.state().clock()
+    }
+
+    /// Get every current entry, sorted by path
+    pub async fn entries(&self) -> Vec<FileMetadata> {
+        let mut entries: Vec<FileMetadata> = {
+            let sync = self.fs.sync_engine().read().await;
+            sync.state().list_files().into_iter().cloned().collect()
+        };
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        entries
+    }
+
+    /// Get the deleted versions of entries removed after operation `since`
+    ///
+    /// Paths that exist again are left out.
+    pub async fn tombstones(&self, since: u64) -> Vec<FileMetadata> {
+        let mut paths: Vec<PathBuf> = {
+            let sync = self.fs.sync_engine().read().await;
+            let state = sync.state();
+            state
+                .operations_since(since)
+                .into_iter()
+                .filter_map(|op| match &op.op {
+                    FileOperation::Delete { path } | FileOperation::Move { from: path, .. } => {
+                        Some(path.clone())
+                    }
+                    _ => None,
+                })
+                .filter(|path| state.get(path).is_none())
+                .collect()
+        };
+        paths.sort();
+        paths.dedup();
+
+        let mut tombstones = Vec::with_capacity(paths.len());
+        for path in paths {
+            let deleted = self.fs.history(&path).await.into_iter().find(|v| v.deleted);
+            if let Some(version) = deleted {
+                tombstones.push(version.metadata);
+            }
+        }
+        tombstones
+    }
+
+    /// Restore entries and tombstones saved by an earlier run
+    ///
+    /// Chunks are read back from the directory. Entries whose file was
+    /// removed since are deleted; those whose file changed are left for the
+    /// watcher's initial scan. Returns the number of entries restored.
+    pub async fn restore(
+        &self,
+        entries: Vec<FileMetadata>,
+        tombstones: Vec<FileMetadata>,
+    ) -> Result<usize, VdfsError> {
+        let current: HashSet<&Path> = entries.iter().map(|e| e.path.as_path()).collect();
+        let tombstones: Vec<FileMetadata> = tombstones
+            .into_iter()
+            .filter(|t| !current.contains(t.path.as_path()) && self.local_path(&t.path).is_some())
+            .collect();
+        for tombstone in tombstones {
+            let path = tombstone.path.clone();
+            self.fs.merge_remote([tombstone]).await?;
+            self.fs.delete(&path).await?;
+        }
+
+        let mut entries: Vec<FileMetadata> = entries
+            .into_iter()
+            .filter(|e| self.local_path(&e.path).is_some())
+            .collect();
+        // Children before parents, so removed directories go last
+        entries.sort_by(|a, b| b.path.cmp(&a.path));
+        self.fs.merge_remote(entries.clone()).await?;
+
+        let mut restored = 0;
+        for entry in &entries {
+            let Some(local) = self.local_path(&entry.path) else {
+                continue;
+            };
+            let on_disk = match tokio::fs::symlink_metadata(&local).await {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    self.fs.delete(&entry.path).await?;
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if entry.is_file() && on_disk.is_file() {
+                let data = tokio::fs::read(&local).await?;
+                if entry.content_hash == Some(hash_data(&data)) {
+                    let chunk_size = match entry.chunk_size {
+                        0 => self.fs.chunk_store().chunk_size(),
+                        size => size as usize,
+                    };
+                    for chunk in chunk_data(&data, chunk_size) {
+                        self.fs.chunk_store().store(chunk).await?;
+                    }
+                }
+            }
+            restored += 1;
+        }
+        Ok(restored)
+    }
+
+    /// Pull changes from a peer sharing this folder
+    ///
+    /// `since` is the [`PullReport::clock`] of the last pull from the peer,
+    /// or 0; deletes recorded after it are applied.
+    pub async fn pull(
+        &self,
+        manager: &P2PConnectionManager,
+        peer: NodeId,
+        since: u64,
+    ) -> Result<PullReport, VdfsError> {
+        let stream = open_tunnel(manager, peer, FOLDER_SYNC_SERVICE).await?;
+        let mut stream = SecureStream::initiate(stream).await?;
+
+        let (root, clock) = match request(
+            &mut stream,
+            &SyncRequest::Open {
+                folder: self.id.clone(),
+            },
+        )
+        .await?
+        {
+            SyncReply::Opened { root, clock } => (root, clock),
+            other => return Err(unexpected(other)),
+        };
+        // A peer that restarted counts its operations from zero again
+        let since = if clock < since { 0 } else { since };
+
+        let tree = {
+            let sync = self.fs.sync_engine().read().await;
+            MerkleTree::from_state(sync.state())
+        };
+        let mut negotiation = Negotiation::new(&tree, root);
+        while !negotiation.is_done() {
+            let prefixes = negotiation.next_request();
+            match request(&mut stream, &SyncRequest::Summarize { prefixes }).await? {
+                SyncReply::Summaries { summaries } => negotiation.receive(summaries),
+                other => return Err(unexpected(other)),
+            }
+        }
+        // Paths only this side has are the peer's to pull
+        let wanted: Vec<PathBuf> = negotiation
+            .into_differences()
+            .into_iter()
+            .filter(|d| d.remote.is_some())
+            .map(|d| d.path)
+            .collect();
+
+        let mut files = Vec::with_capacity(wanted.len());
+        for paths in wanted.chunks(BATCH_SIZE) {
+            let paths = paths.to_vec();
+            match request(&mut stream, &SyncRequest::Files { paths }).await? {
+                SyncReply::Files { files: batch } => files.extend(batch),
+                other => return Err(unexpected(other)),
+            }
+        }
+        let tombstones = if clock > since {
+            match request(&mut stream, &SyncRequest::Deleted { since }).await? {
+                SyncReply::Files { files } => files,
+                other => return Err(unexpected(other)),
+            }
+        } else {
+            Vec::new()
+        };
+
+        let mut seen = HashSet::new();
+        let mut missing = Vec::new();
+        for id in files.iter().flat_map(|f| f.chunks.iter()) {
+            if seen.insert(*id) && !self.fs.chunk_store().contains(id).await {
+                missing.push(*id);
+            }
+        }
+        let mut bytes = 0;
+        for ids in missing.chunks(BATCH_SIZE) {
+            let ids = ids.to_vec();
+            let sent = match request(&mut stream, &SyncRequest::Chunks { ids }).await? {
+                SyncReply::Chunks { ids } => ids,
+                other => return Err(unexpected(other)),
+            };
+            for id in sent {
+                let chunk = Chunk::new(stream.recv(MAX_CHUNK_FRAME).await?);
+                if chunk.id != id {
+                    return Err(VdfsError::HashMismatch {
+                        expected: id.to_hex(),
+                        actual: chunk.id.to_hex(),
+                    });
+                }
+                bytes += chunk.size() as u64;
+                self.fs.chunk_store().store(chunk).await?;
+            }
+        }
+        let _ = stream.finish().await;
+
+        let mut report = self.apply(files, tombstones).await?;
+        report.clock = clock;
+        report.bytes = bytes;
+        Ok(report)
+    }
+
+    /// Merge a peer's entries and deletes, then bring the directory in line
+    ///
+    /// The chunks of the entries must be in the local store; entries with
+    /// missing chunks are skipped.
+    async fn apply(
+        &self,
+        files: Vec<FileMetadata>,
+        tombstones: Vec<FileMetadata>,
+    ) -> Result<PullReport, VdfsError> {
+        let before: HashMap<PathBuf, FileMetadata> = self
+            .entries()
+            .await
+            .into_iter()
+            .map(|e| (e.path.clone(), e))
+            .collect();
+        let conflicts_before: HashSet<PathBuf> = self
+            .fs
+            .conflicts()
+            .await
+            .into_iter()
+            .map(|c| c.path)
+            .collect();
+
+        let mut incoming = Vec::with_capacity(files.len());
+        'files: for mut file in files {
+            if self.local_path(&file.path).is_none() {
+                continue;
+            }
+            for id in &file.chunks {
+                if !self.fs.chunk_store().contains(id).await {
+                    tracing::warn!(path = %file.path.display(), "Skipping entry with missing chunks");
+                    continue 'files;
+                }
+            }
+            if let Some(local) = before.get(&file.path) {
+                let same = local.file_type == file.file_type
+                    && local.content_hash == file.content_hash
+                    && local.symlink_target == file.symlink_target;
+                if same && local.clock.is_concurrent(&file.clock) {
+                    // Added on both sides: the peer's entry supersedes ours
+                    file.clock.merge(&local.clock);
+                }
+            }
+            incoming.push(file);
+        }
+        self.fs.merge_remote(incoming).await?;
+
+        let mut tombstones = tombstones;
+        tombstones.sort_by(|a, b| b.path.cmp(&a.path));
+        for tombstone in tombstones {
+            let Ok(local) = self.fs.stat(&tombstone.path).await else {
+                continue;
+            };
+            let seen = matches!(
+                local.clock.compare(&tombstone.clock),
+                Causality::Before | Causality::Equal
+            );
+            if seen {
+                self.fs.delete(&tombstone.path).await?;
+            }
+        }
+
+        let mut report = PullReport::default();
+        let after = self.entries().await;
+        let present: HashSet<&Path> = after.iter().map(|e| e.path.as_path()).collect();
+        // Parents before children
+        for entry in &after {
+            let previous = before.get(&entry.path);
+            if previous == Some(entry) {
+                continue;
+            }
+            match self.write_out(entry, previous).await {
+                Ok(true) => report.updated.push(self.relative(&entry.path)),
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::warn!(path = %entry.path.display(), error = %e, "Failed to write synced entry")
+                }
+            }
+        }
+        let mut gone: Vec<&FileMetadata> = before
+            .values()
+            .filter(|e| !present.contains(e.path.as_path()))
+            .collect();
+        // Children before parents
+        gone.sort_by(|a, b| b.path.cmp(&a.path));
+        for entry in gone {
+            match self.remove_out(entry).await {
+                Ok(true) => report.removed.push(self.relative(&entry.path)),
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::warn!(path = %entry.path.display(), error = %e, "Failed to remove synced entry")
+                }
+            }
+        }
+
+        let mut conflicts: Vec<PathBuf> = self
+            .fs
+            .conflicts()
+            .await
+            .into_iter()
+            .filter(|c| !conflicts_before.contains(&c.path))
+            .map(|c| self.relative(&c.path))
+            .collect();
+        conflicts.sort();
+        report.conflicts = conflicts;
+        Ok(report)
+    }
+
+    /// Write an entry to disk; returns whether anything was written
+    async fn write_out(
+        &self,
+        entry: &FileMetadata,
+        previous: Option<&FileMetadata>,
+    ) -> Result<bool, VdfsError> {
+        let Some(local) = self.local_path(&entry.path) else {
+            return Ok(false);
+        };
+        if entry.is_directory() {
+            if tokio::fs::metadata(&local).await.is_ok_and(|m| m.is_dir()) {
+                return Ok(false);
+            }
+            tokio::fs::create_dir_all(&local).await?;
+            return Ok(true);
+        }
+        // Symlinks are not mirrored
+        if !entry.is_file() {
+            return Ok(false);
+        }
+
+        match tokio::fs::read(&local).await {
+            Ok(data) => {
+                let on_disk = Some(hash_data(&data));
+                if on_disk == entry.content_hash {
+                    return Ok(false);
+                }
+                if on_disk != previous.and_then(|p| p.content_hash) {
+                    // Edited locally and not picked up yet
+                    return Ok(false);
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let data = self.fs.read(&entry.path).await?;
+        let parent = local.parent().unwrap_or(&self.root);
+        tokio::fs::create_dir_all(parent).await?;
+        let name = local
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let partial = parent.join(format!(".{}{}", name, PARTIAL_SUFFIX));
+        tokio::fs::write(&partial, &data).await?;
+        tokio::fs::rename(&partial, &local).await?;
+        Ok(true)
+    }
+
+    /// Remove a deleted entry from disk; returns whether it was removed
+    async fn remove_out(&self, entry: &FileMetadata) -> Result<bool, VdfsError> {
+        let Some(local) = self.local_path(&entry.path) else {
+            return Ok(false);
+        };
+        let result = if entry.is_directory() {
+            // Anything still inside was added locally
+            tokio::fs::remove_dir(&local).await
+        } else if entry.is_file() {
+            match tokio::fs::read(&local).await {
+                Ok(data) if Some(hash_data(&data)) == entry.content_hash => {
+                    tokio::fs::remove_file(&local).await
+                }
+                Ok(_) => return Ok(false),
+                Err(e) => Err(e),
+            }
+        } else {
+            return Ok(false);
+        };
+        match result {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(_) if entry.is_directory() => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Map a virtual path to the local directory
+    ///
+    /// `None` for paths outside the folder or that would escape it.
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        let relative = path.strip_prefix(self.fs.mount_point()).ok()?;
+        let normal = relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+        if relative.as_os_str().is_empty() || !normal {
+            return None;
+        }
+        Some(self.root.join(relative))
+    }
+
+    /// Path relative to the folder root, for reports
+    fn relative(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(self.fs.mount_point())
+            .unwrap_or(path)
+            .to_path_buf()
+    }
+}
+
+/// A folder and the peers it is shared with
+struct SharedFolder {
+    folder: Arc<SyncedFolder>,
+    peers: HashSet<NodeId>,
+}
+
+/// Serves synced folders to peers
+///
+/// Register it with the [`TunnelAgent`] to let peers with the
+/// [`PeerCapability::FileSync`] capability pull the folders shared with
+/// them.
+#[derive(Clone, Default)]
+pub struct FolderSync {
+    folders: Arc<RwLock<HashMap<String, SharedFolder>>>,
+}
+
+impl FolderSync {
+    /// Create a server with no folders
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve folders to peers
+    pub fn register(&self, agent: TunnelAgent) -> TunnelAgent {
+        agent.with_handler(
+            FOLDER_SYNC_SERVICE,
+            PeerCapability::FileSync,
+            Arc::new(self.clone()),
+        )
+    }
+
+    /// Share a folder with peers, replacing an earlier share of its ID
+    pub async fn share(&self, folder: Arc<SyncedFolder>, peers: impl IntoIterator<Item = NodeId>) {
+        let shared = SharedFolder {
+            folder,
+            peers: peers.into_iter().collect(),
+        };
+        self.folders
+            .write()
+            .await
+            .insert(shared.folder.id().to_string(), shared);
+    }
+
+    /// Stop sharing a folder
+    pub async fn unshare(&self, id: &str) -> bool {
+        self.folders.write().await.remove(id).is_some()
+    }
+
+    /// Get a folder if it is shared with a peer
+    async fn folder_for(&self, peer: &NodeId, id: &str) -> Option<Arc<SyncedFolder>> {
+        self.folders
+            .read()
+            .await
+            .get(id)
+            .filter(|shared| shared.peers.contains(peer))
+            .map(|shared| shared.folder.clone())
+    }
+}
+
+#[async_trait]
+impl StreamHandler for FolderSync {
+    async fn handle(&self, peer_id: NodeId, stream: BiStream) -> Result<(), P2PError> {
+        let mut stream = SecureStream::accept(stream).await?;
+        let mut open: Option<(Arc<SyncedFolder>, MerkleTree)> = None;
+
+        loop {
+            let request = match stream.recv_json(MAX_CONTROL_FRAME).await {
+                Ok(request) => request,
+                // The peer is done pulling
+                Err(_) => return Ok(()),
+            };
+            let reply = match (request, &open) {
+                (SyncRequest::Open { folder }, _) => match self.folder_for(&peer_id, &folder).await
+                {
+                    Some(folder) => {
+                        tracing::debug!(peer_id = %peer_id, folder = %folder.id(), "Peer is pulling folder");
+                        let tree = {
+                            let sync = folder.fs.sync_engine().read().await;
+                            MerkleTree::from_state(sync.state())
+                        };
+                        let reply = SyncReply::Opened {
+                            root: tree.root_hash(),
+                            clock: folder.clock().await,
+                        };
+                        open = Some((folder, tree));
+                        reply
+                    }
+                    None => SyncReply::Error {
+                        reason: format!("folder {} is not shared with this peer", folder),
+                    },
+                },
+                (_, None) => SyncReply::Error {
+                    reason: "no folder open".to_string(),
+                },
+                (SyncRequest::Summarize { prefixes }, Some((_, tree))) => SyncReply::Summaries {
+                    summaries: tree.summarize_all(&prefixes),
+                },
+                (SyncRequest::Files { paths }, Some((folder, _))) => {
+                    let sync = folder.fs.sync_engine().read().await;
+                    let files = paths
+                        .iter()
+                        .take(BATCH_SIZE)
+                        .filter_map(|path| sync.state().get(path).cloned())
+                        .collect();
+                    SyncReply::Files { files }
+                }
+                (SyncRequest::Deleted { since }, Some((folder, _))) => SyncReply::Files {
+                    files: folder.tombstones(since).await,
+                },
+                (SyncRequest::Chunks { ids }, Some((folder, _))) => {
+                    let mut chunks = Vec::new();
+                    for id in ids.iter().take(BATCH_SIZE) {
+                        if let Ok(chunk) = folder.fs.fetch_chunk(id).await {
+                            chunks.push(chunk);
+                        }
+                    }
+                    stream
+                        .send_json(&SyncReply::Chunks {
+                            ids: chunks.iter().map(|c| c.id).collect(),
+                        })
+                        .await?;
+                    for chunk in chunks {
+                        stream.send(&chunk.data).await?;
+                    }
+                    continue;
+                }
+            };
+            stream.send_json(&reply).await?;
+        }
+    }
+}
+
+/// Send a request and wait for its reply
+async fn request(stream: &mut SecureStream, request: &SyncRequest) -> Result<SyncReply, VdfsError> {
+    stream.send_json(request).await?;
+    Ok(stream.recv_json(MAX_CONTROL_FRAME).await?)
+}
+
+fn unexpected(reply: SyncReply) -> VdfsError {
+    match reply {
+        SyncReply::Error { reason } => P2PError::Stream(reason).into(),
+        other => P2PError::Stream(format!("Unexpected sync reply: {:?}", other)).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(dir: &Path, node: &str) -> SyncedFolder {
+        SyncedFolder::new("docs", dir, node).unwrap()
+    }
+
+    /// Pull from `from` into `to` without a network in between
+    async fn pull_local(from: &SyncedFolder, to: &SyncedFolder) -> PullReport {
+        let files = from.entries().await;
+        for id in files.iter().flat_map(|f| f.chunks.iter()) {
+            let chunk = from.fs().fetch_chunk(id).await.unwrap();
+            to.fs().chunk_store().store(chunk).await.unwrap();
+        }
+        to.apply(files, from.tombstones(0).await).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn changes_and_deletes_reach_the_other_folder() {
+        let (dir_a, dir_b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (a, b) = (folder(dir_a.path(), "a"), folder(dir_b.path(), "b"));
+
+        a.fs().mkdir(Path::new("notes")).await.unwrap();
+        a.fs()
+            .write(Path::new("notes/todo.txt"), b"milk")
+            .await
+            .unwrap();
+        a.fs().write(Path::new("plan.txt"), b"v1").await.unwrap();
+        let report = pull_local(&a, &b).await;
+        assert_eq!(report.updated.len(), 3);
+        assert_eq!(
+            std::fs::read(dir_b.path().join("notes/todo.txt")).unwrap(),
+            b"milk"
+        );
+
+        // b edits the plan while a deletes it; the edit survives
+        b.fs().write(Path::new("plan.txt"), b"v2").await.unwrap();
+        std::fs::write(dir_b.path().join("plan.txt"), b"v2").unwrap();
+        a.fs().delete(Path::new("plan.txt")).await.unwrap();
+        a.fs().delete(Path::new("notes/todo.txt")).await.unwrap();
+        let report = pull_local(&a, &b).await;
+        assert_eq!(report.removed, vec![PathBuf::from("notes/todo.txt")]);
+        assert!(!dir_b.path().join("notes/todo.txt").exists());
+        assert_eq!(std::fs::read(dir_b.path().join("plan.txt")).unwrap(), b"v2");
+
+        // Pulling back brings the edit to a
+        let report = pull_local(&b, &a).await;
+        assert_eq!(report.updated, vec![PathBuf::from("plan.txt")]);
+        assert_eq!(a.fs().read(Path::new("plan.txt")).await.unwrap(), b"v2");
+    }
+
+    #[tokio::test]
+    async fn same_content_on_both_sides_is_not_a_conflict() {
+        let (dir_a, dir_b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (a, b) = (folder(dir_a.path(), "a"), folder(dir_b.path(), "b"));
+        a.fs().write(Path::new("same.txt"), b"hello").await.unwrap();
+        b.fs().write(Path::new("same.txt"), b"hello").await.unwrap();
+        a.fs().write(Path::new("diff.txt"), b"mine").await.unwrap();
+        b.fs().write(Path::new("diff.txt"), b"yours").await.unwrap();
+
+        let report = pull_local(&a, &b).await;
+        assert_eq!(report.conflicts, vec![PathBuf::from("diff.txt")]);
+        pull_local(&b, &a).await;
+        assert_eq!(a.entries().await, b.entries().await);
+    }
+
+    #[tokio::test]
+    async fn restore_rereads_chunks_and_drops_removed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = folder(dir.path(), "a");
+        for (name, data) in [("kept.txt", b"kept"), ("gone.txt", b"gone")] {
+            first.fs().write(Path::new(name), data).await.unwrap();
+            std::fs::write(dir.path().join(name), data).unwrap();
+        }
+        first
+            .fs()
+            .write(Path::new("old.txt"), b"old")
+            .await
+            .unwrap();
+        first.fs().delete(Path::new("old.txt")).await.unwrap();
+        let (entries, tombstones) = (first.entries().await, first.tombstones(0).await);
+        assert_eq!(tombstones.len(), 1);
+
+        std::fs::remove_file(dir.path().join("gone.txt")).unwrap();
+        let second = folder(dir.path(), "a");
+        assert_eq!(second.restore(entries, tombstones).await.unwrap(), 1);
+        assert_eq!(
+            second.fs().read(Path::new("kept.txt")).await.unwrap(),
+            b"kept"
+        );
+        assert!(!second.fs().exists(Path::new("gone.txt")).await);
+        let deleted: Vec<PathBuf> = second
+            .tombstones(0)
+            .await
+            .into_iter()
+            .map(|t| t.path)
+            .collect();
+        assert_eq!(
+            deleted,
+            vec![
+                PathBuf::from("/docs/gone.txt"),
+                PathBuf::from("/docs/old.txt")
+            ]
+        );
+    }
+}
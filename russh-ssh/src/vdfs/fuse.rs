@@ -0,0 +1,784 @@
+//! FUSE Mount
+//!
+//! Mounts a [`VirtualFs`] as a local filesystem so ordinary tools can work
+//! on distributed files directly. Only built with the `fuse` feature.
+//!
+//! Reads are served from the chunk store. Writes are buffered per open
+//! file handle and committed when the handle is flushed or released, so a
+//! save goes through chunking, the sync engine and version history exactly
+//! like [`VirtualFs::write`].
+//!
+//! Directories that only exist implicitly (a file was written at `a/b.txt`
+//! without creating `a`) are shown as regular directories.
+//!
+//! Extended attributes map onto [`FileMetadata::xattrs`] by name, so tags
+//! set with `setfattr`/`xattr` sync along with the file.
+//!
+//! # Requirements Coverage
+//! - Requirement 5.3: Virtual filesystem interface
+
+use super::filesystem::VirtualFs;
+use super::metadata::{FileMetadata, Permissions};
+use crate::error::VdfsError;
+use fuser::{
+    BackgroundSession, FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyCreate,
+    ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, ReplyWrite, ReplyXattr, Request,
+    TimeOrNow, FUSE_ROOT_ID,
+};
+use libc::c_int;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::runtime::Handle;
+
+/// How long the kernel may cache attributes and entries
+const TTL: Duration = Duration::from_secs(1);
+
+/// Block size reported to the kernel
+const BLOCK_SIZE: u32 = 4096;
+
+/// Filesystem name shown in the mount table
+const FS_NAME: &str = "russh-vdfs";
+
+/// Error for a missing extended attribute
+#[cfg(target_os = "linux")]
+const NO_XATTR: c_int = libc::ENODATA;
+#[cfg(not(target_os = "linux"))]
+const NO_XATTR: c_int = libc::ENOATTR;
+
+/// An open file handle
+struct OpenFile {
+    ino: u64,
+    /// Whole-file write buffer, loaded on the first write
+    buffer: Option<Vec<u8>>,
+    dirty: bool,
+}
+
+/// What a path resolves to
+enum Entry {
+    /// Path has metadata of its own
+    Explicit(Box<FileMetadata>),
+    /// Directory implied by entries below it
+    ImplicitDir,
+}
+
+/// FUSE adapter for a [`VirtualFs`]
+pub struct VdfsFuse {
+    fs: Arc<VirtualFs>,
+    runtime: Handle,
+    paths: HashMap<u64, PathBuf>,
+    inodes: HashMap<PathBuf, u64>,
+    next_ino: u64,
+    handles: HashMap<u64, OpenFile>,
+    next_fh: u64,
+}
+
+/// A filesystem mounted in the background
+///
+/// The filesystem is unmounted when this is dropped.
+pub struct VdfsMount {
+    session: BackgroundSession,
+}
+
+impl VdfsMount {
+    /// Unmount and wait for the session to finish
+    pub fn unmount(self) {
+        self.session.join();
+    }
+}
+
+impl VdfsFuse {
+    /// Create an adapter; `runtime` drives the async filesystem calls
+    pub fn new(fs: Arc<VirtualFs>, runtime: Handle) -> Self {
+        let root = fs.mount_point().to_path_buf();
+        Self {
+            fs,
+            runtime,
+            paths: HashMap::from([(FUSE_ROOT_ID, root.clone())]),
+            inodes: HashMap::from([(root, FUSE_ROOT_ID)]),
+            next_ino: FUSE_ROOT_ID + 1,
+            handles: HashMap::new(),
+            next_fh: 1,
+        }
+    }
+
+    /// Mount at `mountpoint` and serve requests until unmounted
+    ///
+    /// Blocks the calling thread; do not call from an async task.
+    pub fn mount(self, mountpoint: &Path) -> Result<(), VdfsError> {
+        fuser::mount2(self, mountpoint, &mount_options())?;
+        Ok(())
+    }
+
+    /// Mount at `mountpoint` and serve requests on a background thread
+    pub fn spawn_mount(self, mountpoint: &Path) -> Result<VdfsMount, VdfsError> {
+        let session = fuser::spawn_mount2(self, mountpoint, &mount_options())?;
+        Ok(VdfsMount { session })
+    }
+
+    fn ino_for(&mut self, path: &Path) -> u64 {
+        if let Some(ino) = self.inodes.get(path) {
+            return *ino;
+        }
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        self.inodes.insert(path.to_path_buf(), ino);
+        self.paths.insert(ino, path.to_path_buf());
+        ino
+    }
+
+    fn path_of(&self, ino: u64) -> Result<PathBuf, c_int> {
+        self.paths.get(&ino).cloned().ok_or(libc::ENOENT)
+    }
+
+    fn child_path(&self, parent: u64, name: &OsStr) -> Result<PathBuf, c_int> {
+        Ok(self.path_of(parent)?.join(name))
+    }
+
+    fn forget_path(&mut self, path: &Path) {
+        if let Some(ino) = self.inodes.remove(path) {
+            self.paths.remove(&ino);
+        }
+    }
+
+    /// Children of a directory with their file types
+    fn children(&self, dir: &Path) -> BTreeMap<OsString, FileType> {
+        let files: Vec<FileMetadata> = self.runtime.block_on(async {
+            let sync = self.fs.sync_engine().read().await;
+            sync.state().list_files().into_iter().cloned().collect()
+        });
+
+        let mut children = BTreeMap::new();
+        for file in files {
+            let Ok(rest) = file.path.strip_prefix(dir) else {
+                continue;
+            };
+            let mut components = rest.components();
+            let Some(first) = components.next() else {
+                continue;
+            };
+            let kind = if components.next().is_some() {
+                FileType::Directory
+            } else {
+                file_type(&file)
+            };
+            children.insert(first.as_os_str().to_os_string(), kind);
+        }
+        children
+    }
+
+    fn resolve(&self, path: &Path) -> Option<Entry> {
+        if let Ok(metadata) = self.runtime.block_on(self.fs.stat(path)) {
+            return Some(Entry::Explicit(Box::new(metadata)));
+        }
+        if path == self.fs.mount_point() || !self.children(path).is_empty() {
+            return Some(Entry::ImplicitDir);
+        }
+        None
+    }
+
+    fn attr(&mut self, req: &Request<'_>, path: &Path, entry: &Entry) -> FileAttr {
+        let ino = self.ino_for(path);
+        let (kind, size, perm, mtime, crtime) = match entry {
+            Entry::Explicit(metadata) => (
+                file_type(metadata),
+                self.buffered_size(ino).unwrap_or(metadata.size),
+                metadata.permissions.to_mode() as u16,
+                SystemTime::from(metadata.modified),
+                SystemTime::from(metadata.created),
+            ),
+            Entry::ImplicitDir => (
+                FileType::Directory,
+                0,
+                0o755,
+                SystemTime::now(),
+                SystemTime::now(),
+            ),
+        };
+
+        FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(u64::from(BLOCK_SIZE)),
+            atime: mtime,
+            mtime,
+            ctime: mtime,
+            crtime,
+            kind,
+            perm,
+            nlink: if kind == FileType::Directory { 2 } else { 1 },
+            uid: req.uid(),
+            gid: req.gid(),
+            rdev: 0,
+            blksize: BLOCK_SIZE,
+            flags: 0,
+        }
+    }
+
+    /// Size of unsaved data in an open handle for `ino`, if any
+    fn buffered_size(&self, ino: u64) -> Option<u64> {
+        self.handles
+            .values()
+            .filter(|h| h.ino == ino && h.dirty)
+            .find_map(|h| h.buffer.as_ref().map(|b| b.len() as u64))
+    }
+
+    fn attr_for(&mut self, req: &Request<'_>, path: &Path) -> Result<FileAttr, c_int> {
+        let entry = self.resolve(path).ok_or(libc::ENOENT)?;
+        Ok(self.attr(req, path, &entry))
+    }
+
+    /// Load the write buffer of a handle from the stored file
+    fn load_buffer(&mut self, fh: u64) -> Result<&mut OpenFile, c_int> {
+        let handle = self.handles.get(&fh).ok_or(libc::EBADF)?;
+        if handle.buffer.is_none() {
+            let path = self.path_of(handle.ino)?;
+            let data = match self.runtime.block_on(self.fs.read(&path)) {
+                Ok(data) => data,
+                Err(VdfsError::NotFound(_)) => Vec::new(),
+                Err(e) => return Err(errno(&e)),
+            };
+            if let Some(handle) = self.handles.get_mut(&fh) {
+                handle.buffer = Some(data);
+            }
+        }
+        self.handles.get_mut(&fh).ok_or(libc::EBADF)
+    }
+
+    /// Commit a handle's buffered writes as a new file version
+    fn commit(&mut self, fh: u64) -> Result<(), c_int> {
+        let Some(handle) = self.handles.get(&fh) else {
+            return Ok(());
+        };
+        if !handle.dirty {
+            return Ok(());
+        }
+        let path = self.path_of(handle.ino)?;
+        let data = handle.buffer.clone().unwrap_or_default();
+
+        self.runtime
+            .block_on(self.fs.write(&path, &data))
+            .map_err(|e| errno(&e))?;
+        if let Some(handle) = self.handles.get_mut(&fh) {
+            handle.dirty = false;
+        }
+        Ok(())
+    }
+
+    fn open_handle(&mut self, ino: u64, buffer: Option<Vec<u8>>) -> u64 {
+        let fh = self.next_fh;
+        self.next_fh += 1;
+        self.handles.insert(
+            fh,
+            OpenFile {
+                ino,
+                buffer,
+                dirty: false,
+            },
+        );
+        fh
+    }
+
+    fn truncate(&mut self, path: &Path, fh: Option<u64>, size: u64) -> Result<(), c_int> {
+        let size = usize::try_from(size).map_err(|_| libc::EFBIG)?;
+        if let Some(fh) = fh.filter(|fh| self.handles.contains_key(fh)) {
+            let handle = self.load_buffer(fh)?;
+            if let Some(buffer) = handle.buffer.as_mut() {
+                buffer.resize(size, 0);
+            }
+            handle.dirty = true;
+            return Ok(());
+        }
+
+        let fs = self.fs.clone();
+        self.runtime
+            .block_on(async move {
+                let mut data = fs.read(path).await?;
+                data.resize(size, 0);
+                fs.write(path, &data).await
+            })
+            .map(|_| ())
+            .map_err(|e| errno(&e))
+    }
+}
+
+impl Filesystem for VdfsFuse {
+    fn lookup(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match self
+            .child_path(parent, name)
+            .and_then(|path| self.attr_for(req, &path))
+        {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn getattr(&mut self, req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.path_of(ino).and_then(|path| self.attr_for(req, &path)) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn setattr(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        size: Option<u64>,
+        _atime: Option<TimeOrNow>,
+        _mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        let result = self.path_of(ino).and_then(|path| {
+            if let Some(size) = size {
+                self.truncate(&path, fh, size)?;
+            }
+            if let Some(mode) = mode {
+                let permissions = Permissions::from_mode(mode & 0o777);
+                self.runtime
+                    .block_on(self.fs.set_permissions(&path, permissions))
+                    .map_err(|e| errno(&e))?;
+            }
+            self.attr_for(req, &path)
+        });
+
+        match result {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn mkdir(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        reply: ReplyEntry,
+    ) {
+        let result = self.child_path(parent, name).and_then(|path| {
+            let permissions = Permissions::from_mode(mode & !umask & 0o777);
+            self.runtime
+                .block_on(async {
+                    self.fs.mkdir(&path).await?;
+                    self.fs.set_permissions(&path, permissions).await
+                })
+                .map_err(|e| errno(&e))?;
+            self.attr_for(req, &path)
+        });
+
+        match result {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        let result = self.child_path(parent, name).and_then(|path| {
+            self.runtime
+                .block_on(self.fs.delete(&path))
+                .map_err(|e| errno(&e))?;
+            self.forget_path(&path);
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => reply.ok(),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        let result = self.child_path(parent, name).and_then(|path| {
+            if !self.children(&path).is_empty() {
+                return Err(libc::ENOTEMPTY);
+            }
+            match self.runtime.block_on(self.fs.delete(&path)) {
+                Ok(()) | Err(VdfsError::NotFound(_)) => {}
+                Err(e) => return Err(errno(&e)),
+            }
+            self.forget_path(&path);
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => reply.ok(),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        _flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        let result = self.child_path(parent, name).and_then(|from| {
+            let to = self.child_path(newparent, newname)?;
+            self.runtime
+                .block_on(self.fs.rename(&from, &to))
+                .map_err(|e| errno(&e))?;
+
+            // Keep inode numbers stable for open handles on moved paths
+            self.forget_path(&to);
+            let moved: Vec<(PathBuf, u64)> = self
+                .inodes
+                .iter()
+                .filter(|(path, _)| path.starts_with(&from))
+                .map(|(path, ino)| (path.clone(), *ino))
+                .collect();
+            for (old, ino) in moved {
+                let new = match old.strip_prefix(&from) {
+                    Ok(rest) if !rest.as_os_str().is_empty() => to.join(rest),
+                    _ => to.clone(),
+                };
+                self.inodes.remove(&old);
+                self.inodes.insert(new.clone(), ino);
+                self.paths.insert(ino, new);
+            }
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => reply.ok(),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        if let Err(code) = self.path_of(ino) {
+            reply.error(code);
+            return;
+        }
+        let buffer = (flags & libc::O_TRUNC != 0).then(Vec::new);
+        let truncating = buffer.is_some();
+        let fh = self.open_handle(ino, buffer);
+        if let Some(handle) = self.handles.get_mut(&fh) {
+            handle.dirty = truncating;
+        }
+        reply.opened(fh, 0);
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let buffered = self
+            .handles
+            .get(&fh)
+            .and_then(|handle| handle.buffer.clone());
+        let data = match buffered {
+            Some(data) => Ok(data),
+            None => self.path_of(ino).and_then(|path| {
+                self.runtime
+                    .block_on(self.fs.read(&path))
+                    .map_err(|e| errno(&e))
+            }),
+        };
+
+        match data {
+            Ok(data) => {
+                let start = (offset.max(0) as usize).min(data.len());
+                let end = start.saturating_add(size as usize).min(data.len());
+                reply.data(&data[start..end]);
+            }
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        let result = self.load_buffer(fh).and_then(|handle| {
+            let offset = usize::try_from(offset).map_err(|_| libc::EINVAL)?;
+            let buffer = handle.buffer.get_or_insert_with(Vec::new);
+            let end = offset.checked_add(data.len()).ok_or(libc::EFBIG)?;
+            if buffer.len() < end {
+                buffer.resize(end, 0);
+            }
+            buffer[offset..end].copy_from_slice(data);
+            handle.dirty = true;
+            Ok(data.len() as u32)
+        });
+
+        match result {
+            Ok(written) => reply.written(written),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn flush(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _lock_owner: u64,
+        reply: ReplyEmpty,
+    ) {
+        match self.commit(fh) {
+            Ok(()) => reply.ok(),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn fsync(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _datasync: bool,
+        reply: ReplyEmpty,
+    ) {
+        match self.commit(fh) {
+            Ok(()) => reply.ok(),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        let result = self.commit(fh);
+        self.handles.remove(&fh);
+        match result {
+            Ok(()) => reply.ok(),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn create(
+        &mut self,
+        req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        _flags: i32,
+        reply: ReplyCreate,
+    ) {
+        let result = self.child_path(parent, name).and_then(|path| {
+            let permissions = Permissions::from_mode(mode & !umask & 0o777);
+            self.runtime
+                .block_on(async {
+                    self.fs.write(&path, &[]).await?;
+                    self.fs.set_permissions(&path, permissions).await
+                })
+                .map_err(|e| errno(&e))?;
+            let attr = self.attr_for(req, &path)?;
+            let fh = self.open_handle(attr.ino, Some(Vec::new()));
+            Ok((attr, fh))
+        });
+
+        match result {
+            Ok((attr, fh)) => reply.created(&TTL, &attr, 0, fh, 0),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn setxattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        value: &[u8],
+        _flags: i32,
+        _position: u32,
+        reply: ReplyEmpty,
+    ) {
+        let result = self.path_of(ino).and_then(|path| {
+            let name = xattr_name(name)?;
+            self.runtime
+                .block_on(self.fs.set_xattr(&path, name, value))
+                .map_err(|e| errno(&e))
+        });
+
+        match result {
+            Ok(_) => reply.ok(),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn getxattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        size: u32,
+        reply: ReplyXattr,
+    ) {
+        let result = self.path_of(ino).and_then(|path| {
+            let name = xattr_name(name)?;
+            match self.runtime.block_on(self.fs.get_xattr(&path, name)) {
+                Ok(Some(value)) => Ok(value),
+                Ok(None) | Err(VdfsError::NotFound(_)) => Err(NO_XATTR),
+                Err(e) => Err(errno(&e)),
+            }
+        });
+
+        match result {
+            Ok(value) => reply_xattr(reply, size, &value),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn listxattr(&mut self, _req: &Request<'_>, ino: u64, size: u32, reply: ReplyXattr) {
+        let result = self.path_of(ino).and_then(|path| {
+            match self.runtime.block_on(self.fs.xattrs(&path)) {
+                Ok(xattrs) => Ok(xattrs),
+                // Implicit directories have no attributes
+                Err(VdfsError::NotFound(_)) => Ok(BTreeMap::new()),
+                Err(e) => Err(errno(&e)),
+            }
+        });
+
+        match result {
+            Ok(xattrs) => {
+                // NUL-terminated names, back to back
+                let mut names = Vec::new();
+                for name in xattrs.keys() {
+                    names.extend_from_slice(name.as_bytes());
+                    names.push(0);
+                }
+                reply_xattr(reply, size, &names);
+            }
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn removexattr(&mut self, _req: &Request<'_>, ino: u64, name: &OsStr, reply: ReplyEmpty) {
+        let result = self.path_of(ino).and_then(|path| {
+            let name = xattr_name(name)?;
+            match self.runtime.block_on(self.fs.remove_xattr(&path, name)) {
+                Ok(true) => Ok(()),
+                Ok(false) | Err(VdfsError::NotFound(_)) => Err(NO_XATTR),
+                Err(e) => Err(errno(&e)),
+            }
+        });
+
+        match result {
+            Ok(()) => reply.ok(),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let dir = match self.path_of(ino) {
+            Ok(dir) => dir,
+            Err(code) => {
+                reply.error(code);
+                return;
+            }
+        };
+        let parent_ino = dir
+            .parent()
+            .and_then(|parent| self.inodes.get(parent).copied())
+            .unwrap_or(FUSE_ROOT_ID);
+
+        let mut entries = vec![
+            (ino, FileType::Directory, OsString::from(".")),
+            (parent_ino, FileType::Directory, OsString::from("..")),
+        ];
+        for (name, kind) in self.children(&dir) {
+            let child_ino = self.ino_for(&dir.join(&name));
+            entries.push((child_ino, kind, name));
+        }
+
+        for (index, (ino, kind, name)) in
+            entries.into_iter().enumerate().skip(offset.max(0) as usize)
+        {
+            // `add` returns true once the reply buffer is full
+            if reply.add(ino, (index + 1) as i64, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+fn mount_options() -> Vec<MountOption> {
+    vec![
+        MountOption::FSName(FS_NAME.to_string()),
+        MountOption::DefaultPermissions,
+    ]
+}
+
+fn file_type(metadata: &FileMetadata) -> FileType {
+    if metadata.is_directory() {
+        FileType::Directory
+    } else if metadata.is_symlink() {
+        FileType::Symlink
+    } else {
+        FileType::RegularFile
+    }
+}
+
+fn xattr_name(name: &OsStr) -> Result<&str, c_int> {
+    name.to_str().ok_or(libc::EINVAL)
+}
+
+/// Answer a size probe (`size == 0`) or return the value if it fits
+fn reply_xattr(reply: ReplyXattr, size: u32, value: &[u8]) {
+    if size == 0 {
+        reply.size(value.len() as u32);
+    } else if value.len() > size as usize {
+        reply.error(libc::ERANGE);
+    } else {
+        reply.data(value);
+    }
+}
+
+fn errno(error: &VdfsError) -> c_int {
+    match error {
+        VdfsError::NotFound(_) | VdfsError::VersionNotFound { .. } => libc::ENOENT,
+        VdfsError::PermissionDenied(_) => libc::EACCES,
+        VdfsError::InvalidAttribute(_) => libc::EINVAL,
+        VdfsError::Io(e) => e.raw_os_error().unwrap_or(libc::EIO),
+        _ => libc::EIO,
+    }
+}
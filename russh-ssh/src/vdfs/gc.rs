@@ -0,0 +1,205 @@
+//! Chunk Garbage Collection
+//!
+//! Tracks which chunks are referenced by file metadata and reclaims
+//! chunks that no file points at any more.
+//!
+//! Deleting or overwriting a file only drops its references; the chunk
+//! data stays in the store until a GC pass sweeps it. This keeps
+//! deduplicated chunks shared between files safe, and lets callers ask
+//! how much space a pass would free before running it.
+//!
+//! # Requirements Coverage
+//! - Requirement 5.1: Content-addressed storage using BLAKE3
+
+use super::chunk::{ChunkId, ChunkStore};
+use super::metadata::FileMetadata;
+use std::collections::{HashMap, HashSet};
+
+/// Reference counts for chunks, derived from file metadata
+///
+/// A chunk that appears several times in one file counts once per
+/// occurrence, so adding and releasing the same metadata is symmetric.
+#[derive(Debug, Clone, Default)]
+pub struct ChunkRefs {
+    counts: HashMap<ChunkId, usize>,
+}
+
+impl ChunkRefs {
+    /// Create an empty reference table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a reference table from a set of files (the mark phase)
+    pub fn from_metadata<'a>(files: impl IntoIterator<Item = &'a FileMetadata>) -> Self {
+        let mut refs = Self::new();
+        for metadata in files {
+            refs.add(metadata);
+        }
+        refs
+    }
+
+    /// Add the chunk references held by a file
+    pub fn add(&mut self, metadata: &FileMetadata) {
+        for id in &metadata.chunks {
+            *self.counts.entry(*id).or_insert(0) += 1;
+        }
+    }
+
+    /// Drop the chunk references held by a file
+    ///
+    /// Returns the chunks whose reference count reached zero.
+    pub fn release(&mut self, metadata: &FileMetadata) -> Vec<ChunkId> {
+        let mut orphaned = Vec::new();
+        for id in &metadata.chunks {
+            if let Some(count) = self.counts.get_mut(id) {
+                *count -= 1;
+                if *count == 0 {
+                    self.counts.remove(id);
+                    orphaned.push(*id);
+                }
+            }
+        }
+        orphaned
+    }
+
+    /// Get the reference count of a chunk
+    pub fn count(&self, id: &ChunkId) -> usize {
+        self.counts.get(id).copied().unwrap_or(0)
+    }
+
+    /// Check whether any file references a chunk
+    pub fn is_referenced(&self, id: &ChunkId) -> bool {
+        self.counts.contains_key(id)
+    }
+
+    /// Number of distinct referenced chunks
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Check if no chunks are referenced
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Set of all referenced chunk IDs
+    pub fn referenced(&self) -> HashSet<ChunkId> {
+        self.counts.keys().copied().collect()
+    }
+}
+
+/// Result of a garbage collection pass
+#[derive(Debug, Clone, Default)]
+pub struct GcReport {
+    /// Whether the pass only reported without removing anything
+    pub dry_run: bool,
+    /// Number of chunks examined
+    pub scanned_chunks: usize,
+    /// Number of chunks still referenced by a file
+    pub live_chunks: usize,
+    /// Chunks not referenced by any file
+    pub orphaned: Vec<ChunkId>,
+    /// Bytes held by the orphaned chunks
+    pub reclaimable_bytes: usize,
+}
+
+impl GcReport {
+    /// Number of chunks actually removed by this pass
+    pub fn removed_chunks(&self) -> usize {
+        if self.dry_run {
+            0
+        } else {
+            self.orphaned.len()
+        }
+    }
+
+    /// Bytes actually freed by this pass
+    pub fn reclaimed_bytes(&self) -> usize {
+        if self.dry_run {
+            0
+        } else {
+            self.reclaimable_bytes
+        }
+    }
+}
+
+impl ChunkStore {
+    /// Sweep chunks that are not referenced in `refs`
+    ///
+    /// With `dry_run` set, the store is left untouched and the report
+    /// only describes what would be reclaimed.
+    pub async fn sweep(&self, refs: &ChunkRefs, dry_run: bool) -> GcReport {
+        let mut report = GcReport {
+            dry_run,
+            ..GcReport::default()
+        };
+
+        for id in self.list_ids().await {
+            report.scanned_chunks += 1;
+            if refs.is_referenced(&id) {
+                report.live_chunks += 1;
+                continue;
+            }
+
+            let size = if dry_run {
+                self.stored_size(&id).await
+            } else {
+                self.remove(&id).await
+            };
+            if let Some(size) = size {
+                report.reclaimable_bytes += size;
+                report.orphaned.push(id);
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::hash::hash_data;
+    use std::path::PathBuf;
+
+    fn file(path: &str, chunks: Vec<ChunkId>) -> FileMetadata {
+        FileMetadata::new_file(PathBuf::from(path), 0, hash_data(path.as_bytes()), chunks)
+    }
+
+    #[test]
+    fn refs_count_shared_chunks() {
+        let shared = hash_data(b"shared");
+        let own = hash_data(b"own");
+        let a = file("/a", vec![shared, own]);
+        let b = file("/b", vec![shared]);
+
+        let mut refs = ChunkRefs::from_metadata([&a, &b]);
+        assert_eq!(refs.count(&shared), 2);
+
+        assert_eq!(refs.release(&a), vec![own]);
+        assert!(refs.is_referenced(&shared));
+        assert_eq!(refs.release(&b), vec![shared]);
+        assert!(refs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sweep_dry_run_leaves_store_untouched() {
+        let store = ChunkStore::new();
+        let live = store.store_data(b"live".to_vec()).await.unwrap();
+        let dead = store.store_data(b"dead chunk".to_vec()).await.unwrap();
+        let refs = ChunkRefs::from_metadata([&file("/live", vec![live])]);
+
+        let report = store.sweep(&refs, true).await;
+        assert_eq!(report.orphaned, vec![dead]);
+        assert_eq!(report.reclaimable_bytes, 10);
+        assert_eq!(report.removed_chunks(), 0);
+        assert!(store.contains(&dead).await);
+
+        let report = store.sweep(&refs, false).await;
+        assert_eq!(report.reclaimed_bytes(), 10);
+        assert_eq!(report.live_chunks, 1);
+        assert!(!store.contains(&dead).await);
+        assert!(store.contains(&live).await);
+    }
+}
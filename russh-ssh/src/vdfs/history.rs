@@ -0,0 +1,229 @@
+//! File Version History
+//!
+//! Keeps prior [`FileMetadata`] versions and namespace-level snapshots.
+//!
+//! Because chunks are content-addressed, an old version is just its
+//! metadata: restoring it only needs the chunks it lists, which garbage
+//! collection keeps alive for as long as the version or snapshot exists.
+//!
+//! # Requirements Coverage
+//! - Requirement 5.1: Content-addressed storage using BLAKE3
+//! - Requirement 5.5: File metadata serialization
+
+use super::metadata::FileMetadata;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Default number of prior versions kept per path
+pub const DEFAULT_MAX_VERSIONS: usize = 32;
+
+/// One version of a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileVersion {
+    /// Metadata of the file at this version
+    pub metadata: FileMetadata,
+    /// When this version was replaced or deleted (`None` for the current one)
+    pub replaced_at: Option<DateTime<Utc>>,
+    /// Whether this version was removed by a delete
+    pub deleted: bool,
+}
+
+impl FileVersion {
+    /// Version number
+    pub fn version(&self) -> u64 {
+        self.metadata.version
+    }
+}
+
+/// A point-in-time copy of every file's metadata in the namespace
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Snapshot identifier
+    pub id: Uuid,
+    /// Human-readable name
+    pub name: String,
+    /// When the snapshot was taken
+    pub created_at: DateTime<Utc>,
+    /// Metadata of every file and directory at that time
+    pub files: Vec<FileMetadata>,
+}
+
+impl Snapshot {
+    /// Number of entries in the snapshot
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Total logical size of the files in the snapshot
+    pub fn total_size(&self) -> u64 {
+        self.files.iter().map(|f| f.size).sum()
+    }
+}
+
+/// Prior versions per path plus named snapshots
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionHistory {
+    versions: HashMap<PathBuf, VecDeque<FileVersion>>,
+    snapshots: Vec<Snapshot>,
+    max_versions: usize,
+}
+
+impl Default for VersionHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_VERSIONS)
+    }
+}
+
+impl VersionHistory {
+    /// Create a history keeping up to `max_versions` prior versions per path
+    pub fn new(max_versions: usize) -> Self {
+        Self {
+            versions: HashMap::new(),
+            snapshots: Vec::new(),
+            max_versions,
+        }
+    }
+
+    /// Get the number of prior versions kept per path
+    pub fn max_versions(&self) -> usize {
+        self.max_versions
+    }
+
+    /// Record a version that was just replaced or deleted
+    ///
+    /// Returns versions evicted to stay within the limit, whose chunk
+    /// references the caller should release.
+    pub fn record(&mut self, metadata: FileMetadata, deleted: bool) -> Vec<FileMetadata> {
+        let path = metadata.path.clone();
+        let versions = self.versions.entry(path.clone()).or_default();
+        versions.push_front(FileVersion {
+            metadata,
+            replaced_at: Some(Utc::now()),
+            deleted,
+        });
+
+        let evicted = versions
+            .drain(self.max_versions.min(versions.len())..)
+            .map(|v| v.metadata)
+            .collect();
+        if versions.is_empty() {
+            self.versions.remove(&path);
+        }
+        evicted
+    }
+
+    /// Prior versions of a path, newest first
+    pub fn versions(&self, path: &Path) -> Vec<FileVersion> {
+        self.versions
+            .get(path)
+            .map(|v| v.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Find a prior version by number
+    pub fn find(&self, path: &Path, version: u64) -> Option<&FileVersion> {
+        self.versions
+            .get(path)?
+            .iter()
+            .find(|v| v.version() == version)
+    }
+
+    /// Highest version number recorded for a path
+    pub fn latest_version(&self, path: &Path) -> Option<u64> {
+        self.versions
+            .get(path)?
+            .iter()
+            .map(FileVersion::version)
+            .max()
+    }
+
+    /// Drop all but the newest `keep` prior versions of every path
+    ///
+    /// Returns the dropped versions.
+    pub fn prune(&mut self, keep: usize) -> Vec<FileMetadata> {
+        let mut dropped = Vec::new();
+        for versions in self.versions.values_mut() {
+            dropped.extend(
+                versions
+                    .drain(keep.min(versions.len())..)
+                    .map(|v| v.metadata),
+            );
+        }
+        self.versions.retain(|_, v| !v.is_empty());
+        dropped
+    }
+
+    /// Store a snapshot
+    pub fn add_snapshot(&mut self, snapshot: Snapshot) {
+        self.snapshots.push(snapshot);
+    }
+
+    /// Get a snapshot by ID
+    pub fn snapshot(&self, id: &Uuid) -> Option<&Snapshot> {
+        self.snapshots.iter().find(|s| s.id == *id)
+    }
+
+    /// All snapshots, oldest first
+    pub fn snapshots(&self) -> &[Snapshot] {
+        &self.snapshots
+    }
+
+    /// Remove a snapshot
+    pub fn remove_snapshot(&mut self, id: &Uuid) -> Option<Snapshot> {
+        let index = self.snapshots.iter().position(|s| s.id == *id)?;
+        Some(self.snapshots.remove(index))
+    }
+
+    /// Every metadata entry kept by versions and snapshots
+    ///
+    /// Used as additional roots by garbage collection.
+    pub fn retained_metadata(&self) -> impl Iterator<Item = &FileMetadata> {
+        self.versions
+            .values()
+            .flat_map(|v| v.iter().map(|v| &v.metadata))
+            .chain(self.snapshots.iter().flat_map(|s| s.files.iter()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::hash::hash_data;
+
+    fn version(n: u64) -> FileMetadata {
+        let hash = hash_data(&n.to_le_bytes());
+        let mut metadata = FileMetadata::new_file(PathBuf::from("/f"), 8, hash, vec![hash]);
+        metadata.version = n;
+        metadata
+    }
+
+    #[test]
+    fn record_keeps_newest_versions() {
+        let mut history = VersionHistory::new(2);
+        assert!(history.record(version(1), false).is_empty());
+        assert!(history.record(version(2), false).is_empty());
+        let evicted = history.record(version(3), true);
+
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].version, 1);
+        let kept: Vec<u64> = history
+            .versions(Path::new("/f"))
+            .iter()
+            .map(FileVersion::version)
+            .collect();
+        assert_eq!(kept, vec![3, 2]);
+        assert!(history.find(Path::new("/f"), 3).unwrap().deleted);
+        assert_eq!(history.latest_version(Path::new("/f")), Some(3));
+    }
+
+    #[test]
+    fn zero_limit_evicts_immediately() {
+        let mut history = VersionHistory::new(0);
+        assert_eq!(history.record(version(1), false).len(), 1);
+        assert!(history.versions(Path::new("/f")).is_empty());
+        assert_eq!(history.retained_metadata().count(), 0);
+    }
+}
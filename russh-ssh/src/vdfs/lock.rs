@@ -0,0 +1,225 @@
+//! Advisory File Locks
+//!
+//! Lease-based locks that peers use to avoid editing the same file at once.
+//!
+//! Locks are advisory: writes are never refused, but a path locked by
+//! another node reports [`SyncStatus::Locked`](super::sync::SyncStatus)
+//! so clients can warn before editing. Lock tables travel with the
+//! [`SyncState`](super::SyncState) and merge deterministically; when two
+//! nodes take the same path concurrently, the earlier acquisition wins and
+//! the other node sees it after the next sync. Leases expire on their own,
+//! so a lock held by a peer that went away does not block anyone for long.
+//!
+//! # Requirements Coverage
+//! - Requirement 5.2: CRDT-based sync for conflict resolution
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Default lease length for a lock
+pub const DEFAULT_LEASE: Duration = Duration::from_secs(5 * 60);
+
+/// A lease on a path held by one node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileLock {
+    /// Locked path
+    pub path: PathBuf,
+    /// Node holding the lock
+    pub owner: String,
+    /// Identifies this acquisition, so renewals and releases of an older
+    /// lease on the same path don't affect a newer one
+    pub lease_id: Uuid,
+    /// When the lock was acquired
+    pub acquired_at: DateTime<Utc>,
+    /// When the lease runs out unless renewed
+    pub expires_at: DateTime<Utc>,
+    /// Whether the owner released the lock
+    pub released: bool,
+}
+
+impl FileLock {
+    /// Check whether the lock is held at `now`
+    pub fn is_live(&self, now: DateTime<Utc>) -> bool {
+        !self.released && self.expires_at > now
+    }
+
+    /// Check whether `node_id` holds the lock
+    pub fn is_owned_by(&self, node_id: &str) -> bool {
+        self.owner == node_id
+    }
+
+    /// Ordering used to pick a winner between concurrent live leases
+    fn precedence(&self) -> (DateTime<Utc>, &str, Uuid) {
+        (self.acquired_at, &self.owner, self.lease_id)
+    }
+}
+
+/// Lock state for a namespace
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockTable {
+    locks: HashMap<PathBuf, FileLock>,
+}
+
+impl LockTable {
+    /// Create an empty lock table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Acquire or renew a lock on `path` for `owner`
+    ///
+    /// Returns the current holder as the error if another node holds a
+    /// live lease.
+    pub fn acquire(
+        &mut self,
+        path: &Path,
+        owner: &str,
+        lease: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<FileLock, FileLock> {
+        let expires_at = chrono::Duration::from_std(lease)
+            .ok()
+            .and_then(|lease| now.checked_add_signed(lease))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+
+        if let Some(existing) = self.locks.get_mut(path) {
+            if existing.is_live(now) {
+                if !existing.is_owned_by(owner) {
+                    return Err(existing.clone());
+                }
+                existing.expires_at = existing.expires_at.max(expires_at);
+                return Ok(existing.clone());
+            }
+        }
+
+        let lock = FileLock {
+            path: path.to_path_buf(),
+            owner: owner.to_string(),
+            lease_id: Uuid::new_v4(),
+            acquired_at: now,
+            expires_at,
+            released: false,
+        };
+        self.locks.insert(path.to_path_buf(), lock.clone());
+        Ok(lock)
+    }
+
+    /// Release `owner`'s lock on `path`
+    ///
+    /// Returns whether a live lock was released.
+    pub fn release(&mut self, path: &Path, owner: &str, now: DateTime<Utc>) -> bool {
+        match self.locks.get_mut(path) {
+            Some(lock) if lock.is_live(now) && lock.is_owned_by(owner) => {
+                lock.released = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Get the live lock on a path
+    pub fn get(&self, path: &Path, now: DateTime<Utc>) -> Option<&FileLock> {
+        self.locks.get(path).filter(|lock| lock.is_live(now))
+    }
+
+    /// All live locks
+    pub fn live(&self, now: DateTime<Utc>) -> Vec<&FileLock> {
+        self.locks
+            .values()
+            .filter(|lock| lock.is_live(now))
+            .collect()
+    }
+
+    /// Merge a peer's lock table
+    ///
+    /// Commutative and idempotent: for the same lease, a release wins and
+    /// the later expiry is kept; between different leases, a live one beats
+    /// a dead one and the earlier acquisition wins among live ones.
+    pub fn merge(&mut self, other: &LockTable, now: DateTime<Utc>) {
+        for (path, theirs) in &other.locks {
+            let merged = match self.locks.get(path) {
+                None => theirs.clone(),
+                Some(ours) if ours.lease_id == theirs.lease_id => FileLock {
+                    expires_at: ours.expires_at.max(theirs.expires_at),
+                    released: ours.released || theirs.released,
+                    ..ours.clone()
+                },
+                Some(ours) => match (ours.is_live(now), theirs.is_live(now)) {
+                    (true, false) => continue,
+                    (false, true) => theirs.clone(),
+                    _ if theirs.precedence() < ours.precedence() => theirs.clone(),
+                    _ => continue,
+                },
+            };
+            self.locks.insert(path.clone(), merged);
+        }
+    }
+
+    /// Drop expired and released locks
+    ///
+    /// A released lock should only be pruned once every peer has seen the
+    /// release, or a peer still holding the old entry will revive it until
+    /// its lease runs out.
+    pub fn prune(&mut self, now: DateTime<Utc>) -> usize {
+        let before = self.locks.len();
+        self.locks.retain(|_, lock| lock.is_live(now));
+        before - self.locks.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lease_excludes_other_nodes_until_released_or_expired() {
+        let path = Path::new("/doc.bin");
+        let now = Utc::now();
+        let mut table = LockTable::new();
+
+        let lock = table.acquire(path, "a", DEFAULT_LEASE, now).unwrap();
+        assert_eq!(
+            table.acquire(path, "b", DEFAULT_LEASE, now).unwrap_err(),
+            lock
+        );
+        // The owner renews the same lease
+        let renewed = table.acquire(path, "a", DEFAULT_LEASE * 2, now).unwrap();
+        assert_eq!(renewed.lease_id, lock.lease_id);
+
+        assert!(!table.release(path, "b", now));
+        assert!(table.release(path, "a", now));
+        assert!(table.get(path, now).is_none());
+        assert!(table.acquire(path, "b", DEFAULT_LEASE, now).is_ok());
+
+        let later = now + chrono::Duration::hours(1);
+        assert!(table.get(path, later).is_none());
+        assert_eq!(table.prune(later), 1);
+    }
+
+    #[test]
+    fn concurrent_acquisitions_merge_to_the_earliest() {
+        let path = Path::new("/doc.bin");
+        let now = Utc::now();
+        let mut a = LockTable::new();
+        let mut b = LockTable::new();
+        let first = a.acquire(path, "a", DEFAULT_LEASE, now).unwrap();
+        b.acquire(path, "b", DEFAULT_LEASE, now + chrono::Duration::seconds(1))
+            .unwrap();
+
+        let mut ab = a.clone();
+        ab.merge(&b, now);
+        let mut ba = b.clone();
+        ba.merge(&a, now);
+        assert_eq!(ab, ba);
+        assert_eq!(ab.get(path, now), Some(&first));
+
+        // A release propagates to peers that still hold the lease
+        a.release(path, "a", now);
+        ba.merge(&a, now);
+        assert!(ba.get(path, now).is_none());
+    }
+}
@@ -0,0 +1,426 @@
+//! Merkle Sync Negotiation
+//!
+//! Summarises a namespace as a hash-prefix trie so two peers can find the
+//! files they disagree on without exchanging whole operation logs.
+//!
+//! Every file is a leaf keyed by the BLAKE3 hash of its path, which spreads
+//! paths evenly over a trie with a fanout of 16 (one hex nibble per level).
+//! A node's hash covers everything below it, so equal hashes prune whole
+//! subtrees and only differing branches are expanded, one level per round
+//! trip. Small subtrees are sent as their leaf list directly.
+//!
+//! The exchange is transport-agnostic:
+//! 1. Peers swap [`MerkleTree::root_hash`]. Equal roots mean nothing to do.
+//! 2. The initiator creates a [`Negotiation`] and sends
+//!    [`Negotiation::next_request`] to the peer.
+//! 3. The peer answers with [`MerkleTree::summarize_all`].
+//! 4. The initiator feeds the answer to [`Negotiation::receive`] and repeats
+//!    until [`Negotiation::is_done`], then exchanges metadata and chunks for
+//!    the reported [`Difference`]s.
+//!
+//! # Requirements Coverage
+//! - Requirement 5.2: CRDT-based sync for conflict resolution
+
+use super::metadata::FileMetadata;
+use super::sync::SyncState;
+use crate::encryption::hash::ContentHash;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Children per trie node (one hex nibble)
+pub const FANOUT: usize = 16;
+
+/// Subtrees with at most this many files are sent as a leaf list
+pub const LEAF_THRESHOLD: usize = 16;
+
+/// Deepest possible prefix, in nibbles
+const MAX_DEPTH: usize = 64;
+
+/// Trie prefix as a sequence of nibbles (each `0..16`)
+pub type Prefix = Vec<u8>;
+
+/// A file as seen by the trie
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeafEntry {
+    /// File path
+    pub path: PathBuf,
+    /// Hash of the file's metadata
+    pub hash: ContentHash,
+}
+
+/// Answer for one requested trie node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NodeSummary {
+    /// Large subtree: hashes of its [`FANOUT`] children
+    Branch {
+        prefix: Prefix,
+        children: Vec<ContentHash>,
+    },
+    /// Small subtree: all of its files
+    Leaves {
+        prefix: Prefix,
+        entries: Vec<LeafEntry>,
+    },
+}
+
+/// A path the two peers disagree on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Difference {
+    /// File path
+    pub path: PathBuf,
+    /// Local metadata hash, `None` if the file is only on the peer
+    pub local: Option<ContentHash>,
+    /// Peer metadata hash, `None` if the file is only local
+    pub remote: Option<ContentHash>,
+}
+
+#[derive(Debug, Clone)]
+struct Leaf {
+    key: [u8; 32],
+    hash: ContentHash,
+    path: PathBuf,
+}
+
+/// Hash-prefix trie over a namespace's file metadata
+#[derive(Debug, Clone, Default)]
+pub struct MerkleTree {
+    /// Leaves sorted by key
+    leaves: Vec<Leaf>,
+}
+
+impl MerkleTree {
+    /// Build a tree from file metadata
+    pub fn from_files<'a>(files: impl IntoIterator<Item = &'a FileMetadata>) -> Self {
+        let mut leaves: Vec<Leaf> = files
+            .into_iter()
+            .map(|metadata| Leaf {
+                key: *blake3::hash(metadata.path.to_string_lossy().as_bytes()).as_bytes(),
+                hash: leaf_hash(metadata),
+                path: metadata.path.clone(),
+            })
+            .collect();
+        leaves.sort_by_key(|l| l.key);
+        Self { leaves }
+    }
+
+    /// Build a tree from a sync state's current files
+    pub fn from_state(state: &SyncState) -> Self {
+        Self::from_files(state.list_files())
+    }
+
+    /// Number of files in the tree
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Check if the tree is empty
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Hash covering the whole namespace
+    pub fn root_hash(&self) -> ContentHash {
+        self.node_hash(&[])
+    }
+
+    /// Summarise the node at `prefix`
+    pub fn summarize(&self, prefix: &[u8]) -> NodeSummary {
+        let leaves = self.range(prefix);
+        if is_leaf_node(prefix, leaves.len()) {
+            NodeSummary::Leaves {
+                prefix: prefix.to_vec(),
+                entries: leaves
+                    .iter()
+                    .map(|l| LeafEntry {
+                        path: l.path.clone(),
+                        hash: l.hash,
+                    })
+                    .collect(),
+            }
+        } else {
+            NodeSummary::Branch {
+                prefix: prefix.to_vec(),
+                children: self.child_hashes(prefix),
+            }
+        }
+    }
+
+    /// Summarise several nodes, answering a peer's [`Negotiation::next_request`]
+    pub fn summarize_all(&self, prefixes: &[Prefix]) -> Vec<NodeSummary> {
+        prefixes.iter().map(|p| self.summarize(p)).collect()
+    }
+
+    /// Compare against another tree held locally
+    pub fn diff(&self, other: &MerkleTree) -> Vec<Difference> {
+        let mut negotiation = Negotiation::new(self, other.root_hash());
+        while !negotiation.is_done() {
+            let request = negotiation.next_request();
+            negotiation.receive(other.summarize_all(&request));
+        }
+        negotiation.into_differences()
+    }
+
+    /// Leaves whose key starts with `prefix`
+    fn range(&self, prefix: &[u8]) -> &[Leaf] {
+        let start = self
+            .leaves
+            .partition_point(|l| compare_prefix(&l.key, prefix) == Ordering::Less);
+        let end = self
+            .leaves
+            .partition_point(|l| compare_prefix(&l.key, prefix) != Ordering::Greater);
+        &self.leaves[start..end]
+    }
+
+    fn node_hash(&self, prefix: &[u8]) -> ContentHash {
+        let leaves = self.range(prefix);
+        if leaves.is_empty() {
+            return ContentHash::from_bytes([0u8; 32]);
+        }
+
+        let mut hasher = blake3::Hasher::new();
+        if is_leaf_node(prefix, leaves.len()) {
+            hasher.update(b"leaves");
+            for leaf in leaves {
+                hasher.update(&leaf.key);
+                hasher.update(leaf.hash.as_bytes());
+            }
+        } else {
+            hasher.update(b"branch");
+            for child in self.child_hashes(prefix) {
+                hasher.update(child.as_bytes());
+            }
+        }
+        hasher.finalize().into()
+    }
+
+    fn child_hashes(&self, prefix: &[u8]) -> Vec<ContentHash> {
+        (0..FANOUT as u8)
+            .map(|nibble| self.node_hash(&child_prefix(prefix, nibble)))
+            .collect()
+    }
+}
+
+/// Initiator side of a sync negotiation
+#[derive(Debug)]
+pub struct Negotiation<'a> {
+    local: &'a MerkleTree,
+    pending: Vec<Prefix>,
+    differences: BTreeMap<PathBuf, Difference>,
+    rounds: usize,
+}
+
+impl<'a> Negotiation<'a> {
+    /// Start negotiating against a peer whose root hash is `remote_root`
+    pub fn new(local: &'a MerkleTree, remote_root: ContentHash) -> Self {
+        let pending = if local.root_hash() == remote_root {
+            Vec::new()
+        } else {
+            vec![Vec::new()]
+        };
+        Self {
+            local,
+            pending,
+            differences: BTreeMap::new(),
+            rounds: 0,
+        }
+    }
+
+    /// Check whether all differing subtrees have been resolved
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Prefixes to ask the peer about next
+    pub fn next_request(&self) -> Vec<Prefix> {
+        self.pending.clone()
+    }
+
+    /// Number of round trips so far
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    /// Process the peer's answer to [`Negotiation::next_request`]
+    pub fn receive(&mut self, summaries: Vec<NodeSummary>) {
+        let requested = std::mem::take(&mut self.pending);
+        self.rounds += 1;
+
+        for summary in summaries {
+            match summary {
+                NodeSummary::Branch { prefix, children } => {
+                    if !requested.contains(&prefix) || prefix.len() >= MAX_DEPTH {
+                        continue;
+                    }
+                    for (nibble, remote) in (0..FANOUT as u8).zip(children) {
+                        let child = child_prefix(&prefix, nibble);
+                        if self.local.node_hash(&child) != remote {
+                            self.pending.push(child);
+                        }
+                    }
+                }
+                NodeSummary::Leaves { prefix, entries } => {
+                    if requested.contains(&prefix) {
+                        self.compare_leaves(&prefix, entries);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Paths the peers disagree on, sorted by path
+    pub fn differences(&self) -> Vec<Difference> {
+        self.differences.values().cloned().collect()
+    }
+
+    /// Finish and return the differing paths, sorted by path
+    pub fn into_differences(self) -> Vec<Difference> {
+        self.differences.into_values().collect()
+    }
+
+    fn compare_leaves(&mut self, prefix: &[u8], remote: Vec<LeafEntry>) {
+        let mut paths: BTreeMap<PathBuf, (Option<ContentHash>, Option<ContentHash>)> =
+            BTreeMap::new();
+        for leaf in self.local.range(prefix) {
+            paths.entry(leaf.path.clone()).or_default().0 = Some(leaf.hash);
+        }
+        for entry in remote {
+            paths.entry(entry.path).or_default().1 = Some(entry.hash);
+        }
+
+        for (path, (local, remote)) in paths {
+            if local != remote {
+                self.differences.insert(
+                    path.clone(),
+                    Difference {
+                        path,
+                        local,
+                        remote,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Hash of the metadata fields that matter for sync
+fn leaf_hash(metadata: &FileMetadata) -> ContentHash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(metadata.path.to_string_lossy().as_bytes());
+    hasher.update(&[metadata.file_type as u8]);
+    hasher.update(&metadata.size.to_le_bytes());
+    if let Some(content_hash) = &metadata.content_hash {
+        hasher.update(content_hash.as_bytes());
+    }
+    hasher.update(&metadata.permissions.to_mode().to_le_bytes());
+    hasher.update(&metadata.version.to_le_bytes());
+    hasher.update(
+        &metadata
+            .modified
+            .timestamp_nanos_opt()
+            .unwrap_or_default()
+            .to_le_bytes(),
+    );
+    for (node, counter) in metadata.clock.iter() {
+        hasher.update(node.as_bytes());
+        hasher.update(&counter.to_le_bytes());
+    }
+    for (name, value) in &metadata.xattrs {
+        hasher.update(&(name.len() as u64).to_le_bytes());
+        hasher.update(name.as_bytes());
+        hasher.update(&(value.len() as u64).to_le_bytes());
+        hasher.update(value);
+    }
+    hasher.finalize().into()
+}
+
+fn is_leaf_node(prefix: &[u8], count: usize) -> bool {
+    count <= LEAF_THRESHOLD || prefix.len() >= MAX_DEPTH
+}
+
+fn child_prefix(prefix: &[u8], nibble: u8) -> Prefix {
+    let mut child = prefix.to_vec();
+    child.push(nibble);
+    child
+}
+
+fn nibble(key: &[u8; 32], index: usize) -> u8 {
+    let byte = key[index / 2];
+    if index % 2 == 0 {
+        byte >> 4
+    } else {
+        byte & 0x0f
+    }
+}
+
+/// Compare the first `prefix.len()` nibbles of `key` with `prefix`
+fn compare_prefix(key: &[u8; 32], prefix: &[u8]) -> Ordering {
+    prefix
+        .iter()
+        .enumerate()
+        .map(|(i, p)| nibble(key, i).cmp(p))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::hash::hash_data;
+
+    fn files(count: usize) -> Vec<FileMetadata> {
+        (0..count)
+            .map(|i| {
+                let path = format!("/dir/file-{i}.txt");
+                let hash = hash_data(path.as_bytes());
+                FileMetadata::new_file(PathBuf::from(path), 1, hash, vec![hash])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_trees_need_no_rounds() {
+        let files = files(100);
+        let a = MerkleTree::from_files(&files);
+        let b = MerkleTree::from_files(files.iter().rev());
+
+        assert_eq!(a.root_hash(), b.root_hash());
+        assert!(Negotiation::new(&a, b.root_hash()).is_done());
+        assert!(a.diff(&b).is_empty());
+    }
+
+    #[test]
+    fn negotiation_finds_changed_added_and_missing_files() {
+        let base = files(1000);
+        let mut remote = base.clone();
+        remote[10].version += 1;
+        remote.remove(500);
+        let extra = FileMetadata::new_directory(PathBuf::from("/only-remote"));
+        remote.push(extra);
+
+        let local_tree = MerkleTree::from_files(&base);
+        let remote_tree = MerkleTree::from_files(&remote);
+
+        let mut negotiation = Negotiation::new(&local_tree, remote_tree.root_hash());
+        while !negotiation.is_done() {
+            let request = negotiation.next_request();
+            negotiation.receive(remote_tree.summarize_all(&request));
+        }
+        // 1000 files need only a couple of levels
+        assert!(negotiation.rounds() <= 4);
+
+        let differences = negotiation.into_differences();
+        let paths: Vec<&PathBuf> = differences.iter().map(|d| &d.path).collect();
+        assert_eq!(differences.len(), 3);
+        assert!(paths.contains(&&base[10].path));
+        assert!(paths.contains(&&base[500].path));
+        assert!(paths.contains(&&PathBuf::from("/only-remote")));
+
+        let missing = differences
+            .iter()
+            .find(|d| d.path == base[500].path)
+            .unwrap();
+        assert!(missing.local.is_some() && missing.remote.is_none());
+    }
+}
@@ -5,11 +5,20 @@
 //! # Requirements Coverage
 //! - Requirement 5.5: File metadata serialization
 
+use super::clock::VectorClock;
 use crate::encryption::hash::ContentHash;
+use crate::error::VdfsError;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+/// Maximum length of an extended attribute name in bytes
+pub const MAX_XATTR_NAME_LEN: usize = 255;
+
+/// Maximum size of an extended attribute value in bytes
+pub const MAX_XATTR_VALUE_LEN: usize = 64 * 1024;
+
 /// File type in the virtual filesystem
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FileType {
@@ -127,6 +136,10 @@ pub struct FileMetadata {
     pub content_hash: Option<ContentHash>,
     /// List of chunk IDs that make up the file
     pub chunks: Vec<ContentHash>,
+    /// Size of every chunk but the last, for mapping offsets to chunks
+    /// (0 if unknown)
+    #[serde(default)]
+    pub chunk_size: u64,
     /// File permissions
     pub permissions: Permissions,
     /// Creation time
@@ -141,6 +154,15 @@ pub struct FileMetadata {
     pub version: u64,
     /// Node ID that last modified this file
     pub modified_by: Option<String>,
+    /// Causal history of edits, used to detect concurrent changes
+    #[serde(default)]
+    pub clock: VectorClock,
+    /// User-defined extended attributes
+    ///
+    /// Versioned together with the rest of the metadata, so they sync,
+    /// merge and restore with the file.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub xattrs: BTreeMap<String, Vec<u8>>,
 }
 
 impl FileMetadata {
@@ -158,6 +180,7 @@ impl FileMetadata {
             size,
             content_hash: Some(content_hash),
             chunks,
+            chunk_size: 0,
             permissions: Permissions::default(),
             created: now,
             modified: now,
@@ -165,6 +188,8 @@ impl FileMetadata {
             symlink_target: None,
             version: 1,
             modified_by: None,
+            clock: VectorClock::new(),
+            xattrs: BTreeMap::new(),
         }
     }
 
@@ -177,6 +202,7 @@ impl FileMetadata {
             size: 0,
             content_hash: None,
             chunks: Vec::new(),
+            chunk_size: 0,
             permissions: Permissions::from_mode(0o755),
             created: now,
             modified: now,
@@ -184,6 +210,8 @@ impl FileMetadata {
             symlink_target: None,
             version: 1,
             modified_by: None,
+            clock: VectorClock::new(),
+            xattrs: BTreeMap::new(),
         }
     }
 
@@ -196,6 +224,7 @@ impl FileMetadata {
             size: 0,
             content_hash: None,
             chunks: Vec::new(),
+            chunk_size: 0,
             permissions: Permissions::from_mode(0o777),
             created: now,
             modified: now,
@@ -203,6 +232,8 @@ impl FileMetadata {
             symlink_target: Some(target),
             version: 1,
             modified_by: None,
+            clock: VectorClock::new(),
+            xattrs: BTreeMap::new(),
         }
     }
 
@@ -227,6 +258,32 @@ impl FileMetadata {
         self.version += 1;
     }
 
+    /// Get an extended attribute
+    pub fn xattr(&self, name: &str) -> Option<&[u8]> {
+        self.xattrs.get(name).map(Vec::as_slice)
+    }
+
+    /// Set an extended attribute, returning the previous value
+    pub fn set_xattr(&mut self, name: &str, value: Vec<u8>) -> Result<Option<Vec<u8>>, VdfsError> {
+        if name.is_empty() || name.len() > MAX_XATTR_NAME_LEN || name.contains('\0') {
+            return Err(VdfsError::InvalidAttribute(format!(
+                "invalid attribute name {name:?}"
+            )));
+        }
+        if value.len() > MAX_XATTR_VALUE_LEN {
+            return Err(VdfsError::InvalidAttribute(format!(
+                "value of {name} is {} bytes, limit is {MAX_XATTR_VALUE_LEN}",
+                value.len()
+            )));
+        }
+        Ok(self.xattrs.insert(name.to_string(), value))
+    }
+
+    /// Remove an extended attribute, returning its value
+    pub fn remove_xattr(&mut self, name: &str) -> Option<Vec<u8>> {
+        self.xattrs.remove(name)
+    }
+
     /// Serialize to JSON
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
         serde_json::to_string(self)
@@ -274,6 +331,28 @@ mod tests {
         assert_eq!(restored.chunks.len(), metadata.chunks.len());
     }
 
+    #[test]
+    fn xattrs_survive_serialization() {
+        use crate::encryption::hash::hash_data;
+
+        let hash = hash_data(b"data");
+        let mut metadata = FileMetadata::new_file(PathBuf::from("/f"), 4, hash, vec![hash]);
+        // Files without attributes serialize as before
+        assert!(!metadata.to_json().unwrap().contains("xattrs"));
+
+        metadata
+            .set_xattr("user.tag", b"important".to_vec())
+            .unwrap();
+        assert!(metadata.set_xattr("", Vec::new()).is_err());
+        assert!(metadata
+            .set_xattr("user.big", vec![0; MAX_XATTR_VALUE_LEN + 1])
+            .is_err());
+
+        let restored = FileMetadata::from_json(&metadata.to_json().unwrap()).unwrap();
+        assert_eq!(restored.xattr("user.tag"), Some(&b"important"[..]));
+        assert_eq!(restored.xattrs.len(), 1);
+    }
+
     #[test]
     fn directory_metadata() {
         let metadata = FileMetadata::new_directory(PathBuf::from("/test/dir"));
@@ -0,0 +1,124 @@
+//! Storage Quotas
+//!
+//! Limits on how much a namespace may hold.
+//!
+//! Byte limits are enforced by the [`ChunkStore`](super::ChunkStore) on the
+//! stored size of chunks, so deduplicated data only counts once and sealed
+//! chunks count with their encryption overhead. Entry limits are enforced by
+//! the [`VirtualFs`](super::VirtualFs) whenever a new path appears, whether
+//! it was written locally or merged from a peer.
+//!
+//! # Requirements Coverage
+//! - Requirement 5.3: Virtual filesystem interface
+
+use crate::error::VdfsError;
+use serde::{Deserialize, Serialize};
+
+/// Storage limits for a namespace
+///
+/// `None` leaves the resource unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    /// Maximum bytes of chunk storage
+    pub max_bytes: Option<u64>,
+    /// Maximum number of entries (files, directories and symlinks)
+    pub max_files: Option<u64>,
+}
+
+impl Quota {
+    /// A quota without limits
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Limit chunk storage to `max_bytes`
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Limit the number of entries to `max_files`
+    pub fn with_max_files(mut self, max_files: u64) -> Self {
+        self.max_files = Some(max_files);
+        self
+    }
+
+    /// Check if no limit is set
+    pub fn is_unlimited(&self) -> bool {
+        self.max_bytes.is_none() && self.max_files.is_none()
+    }
+}
+
+/// Current usage of a namespace against its quota
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    /// Bytes of chunk storage in use
+    pub bytes: u64,
+    /// Number of entries
+    pub files: u64,
+    /// Configured limits
+    pub quota: Quota,
+}
+
+impl QuotaUsage {
+    /// Bytes left before the byte limit, if any
+    pub fn remaining_bytes(&self) -> Option<u64> {
+        self.quota
+            .max_bytes
+            .map(|max| max.saturating_sub(self.bytes))
+    }
+
+    /// Entries left before the entry limit, if any
+    pub fn remaining_files(&self) -> Option<u64> {
+        self.quota
+            .max_files
+            .map(|max| max.saturating_sub(self.files))
+    }
+
+    /// Check if either limit has been reached
+    pub fn is_full(&self) -> bool {
+        self.remaining_bytes() == Some(0) || self.remaining_files() == Some(0)
+    }
+}
+
+/// Fail with [`VdfsError::QuotaExceeded`] if `used + additional` exceeds `limit`
+pub(crate) fn check(
+    resource: &'static str,
+    used: u64,
+    additional: u64,
+    limit: Option<u64>,
+) -> Result<(), VdfsError> {
+    let requested = used.saturating_add(additional);
+    match limit {
+        Some(limit) if requested > limit => Err(VdfsError::QuotaExceeded {
+            resource,
+            limit,
+            requested,
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_reports_remaining_capacity() {
+        let usage = QuotaUsage {
+            bytes: 60,
+            files: 3,
+            quota: Quota::unlimited().with_max_bytes(100).with_max_files(3),
+        };
+
+        assert_eq!(usage.remaining_bytes(), Some(40));
+        assert_eq!(usage.remaining_files(), Some(0));
+        assert!(usage.is_full());
+        assert!(check("bytes", usage.bytes, 40, usage.quota.max_bytes).is_ok());
+        assert!(matches!(
+            check("bytes", usage.bytes, 41, usage.quota.max_bytes),
+            Err(VdfsError::QuotaExceeded { requested: 101, .. })
+        ));
+        assert!(check("files", u64::MAX, 1, None).is_ok());
+    }
+}
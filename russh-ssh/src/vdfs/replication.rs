@@ -0,0 +1,368 @@
+//! Replication Policies
+//!
+//! Decides which peers should hold which chunks.
+//!
+//! A [`ReplicationPolicy`] states how many copies of every chunk a
+//! namespace wants and which peers must hold a full copy. Given where
+//! chunks currently live ([`ChunkLocations`]) and which peers are online,
+//! the [`PlacementEngine`] produces a [`ReplicationPlan`]: the chunk
+//! transfers that bring the namespace in line with its policy, plus the
+//! files that are currently under-replicated.
+//!
+//! Copies on offline peers still count, since the data exists; transfers
+//! are only planned between online peers.
+//!
+//! # Requirements Coverage
+//! - Requirement 5.1: Content-addressed storage using BLAKE3
+//! - Requirement 5.2: CRDT-based sync for conflict resolution
+
+use super::chunk::{ChunkId, ChunkStore};
+use super::metadata::FileMetadata;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::PathBuf;
+
+/// Replication requirements for a namespace
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicationPolicy {
+    /// Minimum number of peers holding each chunk
+    pub min_copies: usize,
+    /// Peers that must hold every chunk
+    #[serde(default)]
+    pub pinned: BTreeSet<String>,
+}
+
+impl Default for ReplicationPolicy {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl ReplicationPolicy {
+    /// Keep at least `min_copies` copies of each chunk
+    pub fn new(min_copies: usize) -> Self {
+        Self {
+            min_copies,
+            pinned: BTreeSet::new(),
+        }
+    }
+
+    /// Require `peer` to hold a full copy of the namespace
+    pub fn with_pin(mut self, peer: impl Into<String>) -> Self {
+        self.pinned.insert(peer.into());
+        self
+    }
+
+    /// Number of copies the policy needs, counting pinned peers
+    pub fn required_copies(&self) -> usize {
+        self.min_copies.max(self.pinned.len())
+    }
+}
+
+/// Which peers hold which chunks
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChunkLocations {
+    holders: HashMap<ChunkId, BTreeSet<String>>,
+}
+
+impl ChunkLocations {
+    /// Create an empty location map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `peer` holds a chunk
+    pub fn add(&mut self, peer: &str, id: ChunkId) {
+        self.holders.entry(id).or_default().insert(peer.to_string());
+    }
+
+    /// Record that `peer` holds all of `ids`
+    pub fn add_all(&mut self, peer: &str, ids: impl IntoIterator<Item = ChunkId>) {
+        for id in ids {
+            self.add(peer, id);
+        }
+    }
+
+    /// Record every chunk in a local store as held by `peer`
+    pub async fn add_store(&mut self, peer: &str, store: &ChunkStore) {
+        self.add_all(peer, store.list_ids().await);
+    }
+
+    /// Record that `peer` no longer holds a chunk
+    pub fn remove(&mut self, peer: &str, id: &ChunkId) {
+        if let Some(holders) = self.holders.get_mut(id) {
+            holders.remove(peer);
+            if holders.is_empty() {
+                self.holders.remove(id);
+            }
+        }
+    }
+
+    /// Forget everything held by `peer`
+    pub fn forget_peer(&mut self, peer: &str) {
+        self.holders.retain(|_, holders| {
+            holders.remove(peer);
+            !holders.is_empty()
+        });
+    }
+
+    /// Peers holding a chunk
+    pub fn holders(&self, id: &ChunkId) -> impl Iterator<Item = &str> {
+        self.holders
+            .get(id)
+            .into_iter()
+            .flat_map(|holders| holders.iter().map(String::as_str))
+    }
+
+    /// Number of peers holding a chunk
+    pub fn copies(&self, id: &ChunkId) -> usize {
+        self.holders.get(id).map_or(0, BTreeSet::len)
+    }
+
+    fn holds(&self, peer: &str, id: &ChunkId) -> bool {
+        self.holders
+            .get(id)
+            .is_some_and(|holders| holders.contains(peer))
+    }
+}
+
+/// A chunk copy to make from one peer to another
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChunkTransfer {
+    /// Chunk to copy
+    pub chunk: ChunkId,
+    /// Online peer holding the chunk
+    pub from: String,
+    /// Peer that should receive it
+    pub to: String,
+}
+
+/// Replication state of one file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileReplication {
+    /// File path
+    pub path: PathBuf,
+    /// Copies of the least-replicated chunk of the file
+    pub copies: usize,
+    /// Copies required by the policy
+    pub required: usize,
+    /// Pinned peers missing at least one chunk of the file
+    pub missing_pins: Vec<String>,
+}
+
+impl FileReplication {
+    /// Check whether the file meets the policy
+    pub fn is_satisfied(&self) -> bool {
+        self.copies >= self.required && self.missing_pins.is_empty()
+    }
+}
+
+/// Output of a placement pass
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplicationPlan {
+    /// Transfers that move the namespace towards its policy
+    pub transfers: Vec<ChunkTransfer>,
+    /// Files that do not meet the policy right now
+    pub under_replicated: Vec<FileReplication>,
+    /// Chunks no online peer can provide
+    pub unavailable: Vec<ChunkId>,
+}
+
+impl ReplicationPlan {
+    /// Check whether every file meets the policy
+    pub fn is_satisfied(&self) -> bool {
+        self.under_replicated.is_empty()
+    }
+}
+
+/// Plans chunk transfers that satisfy a replication policy
+#[derive(Debug, Clone, Default)]
+pub struct PlacementEngine {
+    policy: ReplicationPolicy,
+}
+
+impl PlacementEngine {
+    /// Create an engine for a policy
+    pub fn new(policy: ReplicationPolicy) -> Self {
+        Self { policy }
+    }
+
+    /// Get the policy
+    pub fn policy(&self) -> &ReplicationPolicy {
+        &self.policy
+    }
+
+    /// Plan transfers for `files` given current chunk locations
+    ///
+    /// Only peers in `online` send or receive. New copies go to pinned
+    /// peers first, then to the online peers holding the fewest chunks, so
+    /// load spreads evenly. The plan is deterministic for the same inputs.
+    pub fn plan<'a>(
+        &self,
+        files: impl IntoIterator<Item = &'a FileMetadata>,
+        locations: &ChunkLocations,
+        online: &BTreeSet<String>,
+    ) -> ReplicationPlan {
+        let files: Vec<&FileMetadata> = files.into_iter().filter(|f| f.is_file()).collect();
+        let required = self.policy.required_copies();
+        let mut plan = ReplicationPlan::default();
+
+        for file in &files {
+            let copies = file
+                .chunks
+                .iter()
+                .map(|id| locations.copies(id))
+                .min()
+                .unwrap_or(required);
+            let missing_pins: Vec<String> = self
+                .policy
+                .pinned
+                .iter()
+                .filter(|peer| !file.chunks.iter().all(|id| locations.holds(peer, id)))
+                .cloned()
+                .collect();
+            let status = FileReplication {
+                path: file.path.clone(),
+                copies,
+                required,
+                missing_pins,
+            };
+            if !status.is_satisfied() {
+                plan.under_replicated.push(status);
+            }
+        }
+        plan.under_replicated.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut chunks: Vec<ChunkId> = files
+            .iter()
+            .flat_map(|f| f.chunks.iter().copied())
+            .collect();
+        chunks.sort_by_key(|id| *id.as_bytes());
+        chunks.dedup();
+
+        // Chunks held (or about to be) and sent per peer, for balancing
+        let mut held: BTreeMap<&str, usize> = online.iter().map(|p| (p.as_str(), 0)).collect();
+        for id in &chunks {
+            for peer in locations.holders(id) {
+                if let Some(count) = held.get_mut(peer) {
+                    *count += 1;
+                }
+            }
+        }
+        let mut sent: BTreeMap<&str, usize> = BTreeMap::new();
+
+        for id in chunks {
+            let sources: Vec<&str> = locations
+                .holders(&id)
+                .filter(|peer| online.contains(*peer))
+                .collect();
+            let mut copies = locations.copies(&id);
+            let mut targets: Vec<&str> = self
+                .policy
+                .pinned
+                .iter()
+                .map(String::as_str)
+                .filter(|peer| online.contains(*peer) && !locations.holds(peer, &id))
+                .collect();
+            copies += targets.len();
+
+            if copies < required {
+                let mut candidates: Vec<&str> = held
+                    .keys()
+                    .copied()
+                    .filter(|peer| !locations.holds(peer, &id) && !targets.contains(peer))
+                    .collect();
+                candidates.sort_by_key(|peer| (held.get(peer).copied().unwrap_or(0), *peer));
+                targets.extend(candidates.into_iter().take(required - copies));
+            }
+
+            if targets.is_empty() {
+                continue;
+            }
+            if sources.is_empty() {
+                plan.unavailable.push(id);
+                continue;
+            }
+
+            for to in targets {
+                let Some(from) = sources
+                    .iter()
+                    .copied()
+                    .min_by_key(|peer| (sent.get(peer).copied().unwrap_or(0), *peer))
+                else {
+                    break;
+                };
+                *sent.entry(from).or_default() += 1;
+                *held.entry(to).or_default() += 1;
+                plan.transfers.push(ChunkTransfer {
+                    chunk: id,
+                    from: from.to_string(),
+                    to: to.to_string(),
+                });
+            }
+        }
+
+        plan
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::hash::hash_data;
+
+    fn file(path: &str, chunks: &[&[u8]]) -> FileMetadata {
+        let ids: Vec<ChunkId> = chunks.iter().map(|c| hash_data(c)).collect();
+        FileMetadata::new_file(PathBuf::from(path), 0, hash_data(path.as_bytes()), ids)
+    }
+
+    fn peers(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn plan_fills_pins_then_spreads_copies() {
+        let doc = file("/doc", &[b"a", b"b"]);
+        let mut locations = ChunkLocations::new();
+        locations.add_all("laptop", doc.chunks.iter().copied());
+        locations.add("phone", doc.chunks[0]);
+
+        let engine = PlacementEngine::new(ReplicationPolicy::new(2).with_pin("nas"));
+        let plan = engine.plan([&doc], &locations, &peers(&["laptop", "phone", "nas"]));
+
+        assert_eq!(plan.under_replicated.len(), 1);
+        assert_eq!(plan.under_replicated[0].copies, 1);
+        assert_eq!(plan.under_replicated[0].missing_pins, vec!["nas"]);
+
+        // Both chunks go to the pin; chunk b already reaches two copies that way
+        let to_nas = plan.transfers.iter().filter(|t| t.to == "nas").count();
+        assert_eq!(to_nas, 2);
+        assert_eq!(plan.transfers.len(), 2);
+        assert!(plan.unavailable.is_empty());
+
+        // Once applied, nothing is left to do
+        for transfer in &plan.transfers {
+            locations.add(&transfer.to, transfer.chunk);
+        }
+        let plan = engine.plan([&doc], &locations, &peers(&["laptop", "phone", "nas"]));
+        assert!(plan.is_satisfied());
+        assert!(plan.transfers.is_empty());
+    }
+
+    #[test]
+    fn offline_holders_count_but_cannot_send() {
+        let doc = file("/doc", &[b"a"]);
+        let mut locations = ChunkLocations::new();
+        locations.add("laptop", doc.chunks[0]);
+
+        let engine = PlacementEngine::new(ReplicationPolicy::new(2));
+        let plan = engine.plan([&doc], &locations, &peers(&["phone"]));
+
+        assert_eq!(plan.unavailable, vec![doc.chunks[0]]);
+        assert!(plan.transfers.is_empty());
+        assert_eq!(plan.under_replicated[0].copies, 1);
+
+        locations.forget_peer("laptop");
+        assert_eq!(locations.copies(&doc.chunks[0]), 0);
+    }
+}
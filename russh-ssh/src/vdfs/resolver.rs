@@ -0,0 +1,237 @@
+//! Conflict Resolution Strategies
+//!
+//! Decides what happens when two peers edited the same path concurrently.
+//!
+//! The [`SyncState`](super::SyncState) asks its [`ConflictResolver`] only
+//! for edits whose vector clocks are concurrent; causally ordered edits
+//! never reach it. Whatever the resolver picks is stored with the merged
+//! clock of both versions, so it supersedes both and is not reported as a
+//! conflict again. Built-in strategies:
+//!
+//! - [`LastWriterWins`] (the default): keep the newer version and record a
+//!   [`Conflict`](super::Conflict) so the user can review the other one.
+//! - [`ConflictCopy`]: keep the newer version and save the other one next
+//!   to it as `name (conflict <hash>).ext`.
+//! - [`PreferLocal`] and [`PreferPeer`]: always keep one side.
+//! - [`CallbackResolver`]: hand the decision to the application, e.g. to
+//!   merge text notes.
+//!
+//! # Requirements Coverage
+//! - Requirement 5.2: CRDT-based sync for conflict resolution
+
+use super::metadata::FileMetadata;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Outcome chosen by a [`ConflictResolver`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keep the Last-Writer-Wins version and record a conflict
+    LastWriterWins,
+    /// Keep the local version
+    KeepLocal,
+    /// Take the peer's version
+    TakeRemote,
+    /// Keep the Last-Writer-Wins version and save the other beside it
+    KeepBoth,
+    /// Replace both with a merged version at the same path
+    ///
+    /// Any chunks it references must already be in the chunk store.
+    Merged(Box<FileMetadata>),
+}
+
+/// Strategy for concurrent edits of the same path
+///
+/// Resolvers run while the sync state is being merged, so they must not
+/// block. Peers using different strategies still converge: when the
+/// chosen versions differ, the next merge breaks the tie by
+/// Last-Writer-Wins.
+pub trait ConflictResolver: Send + Sync + std::fmt::Debug {
+    /// Decide between the local version and a concurrent peer version
+    fn resolve(&self, local: &FileMetadata, remote: &FileMetadata) -> Resolution;
+}
+
+/// Keep the newer version and record the conflict
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LastWriterWins;
+
+impl ConflictResolver for LastWriterWins {
+    fn resolve(&self, _local: &FileMetadata, _remote: &FileMetadata) -> Resolution {
+        Resolution::LastWriterWins
+    }
+}
+
+/// Keep the newer version and save the other as a conflict copy
+///
+/// Directories and symlinks fall back to [`LastWriterWins`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConflictCopy;
+
+impl ConflictResolver for ConflictCopy {
+    fn resolve(&self, local: &FileMetadata, remote: &FileMetadata) -> Resolution {
+        if local.is_file() && remote.is_file() {
+            Resolution::KeepBoth
+        } else {
+            Resolution::LastWriterWins
+        }
+    }
+}
+
+/// Always keep the local version
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PreferLocal;
+
+impl ConflictResolver for PreferLocal {
+    fn resolve(&self, _local: &FileMetadata, _remote: &FileMetadata) -> Resolution {
+        Resolution::KeepLocal
+    }
+}
+
+/// Always take the peer's version
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PreferPeer;
+
+impl ConflictResolver for PreferPeer {
+    fn resolve(&self, _local: &FileMetadata, _remote: &FileMetadata) -> Resolution {
+        Resolution::TakeRemote
+    }
+}
+
+type ResolveFn = dyn Fn(&FileMetadata, &FileMetadata) -> Resolution + Send + Sync;
+
+/// Resolve conflicts with an application-provided function
+#[derive(Clone)]
+pub struct CallbackResolver {
+    callback: Arc<ResolveFn>,
+}
+
+impl CallbackResolver {
+    /// Call `callback` with the local and peer versions of each conflict
+    pub fn new(
+        callback: impl Fn(&FileMetadata, &FileMetadata) -> Resolution + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            callback: Arc::new(callback),
+        }
+    }
+}
+
+impl std::fmt::Debug for CallbackResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CallbackResolver").finish_non_exhaustive()
+    }
+}
+
+impl ConflictResolver for CallbackResolver {
+    fn resolve(&self, local: &FileMetadata, remote: &FileMetadata) -> Resolution {
+        (self.callback)(local, remote)
+    }
+}
+
+/// Path for a conflict copy of `metadata`
+///
+/// Derived from the content hash, so every peer picks the same name for
+/// the same version.
+pub fn conflict_copy_path(metadata: &FileMetadata) -> PathBuf {
+    let tag = metadata
+        .content_hash
+        .map(|hash| hash.to_hex()[..8].to_string())
+        .unwrap_or_else(|| format!("v{}", metadata.version));
+    let path = &metadata.path;
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{} (conflict {}).{}", stem, tag, ext.to_string_lossy()),
+        None => format!("{} (conflict {})", stem, tag),
+    };
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::hash::hash_data;
+    use crate::vdfs::sync::{FileOperation, SyncState};
+
+    fn file(path: &str, content: &[u8]) -> FileMetadata {
+        let hash = hash_data(content);
+        FileMetadata::new_file(PathBuf::from(path), content.len() as u64, hash, vec![hash])
+    }
+
+    /// Two nodes that created `/doc.txt` independently
+    fn diverged() -> (SyncState, SyncState) {
+        let mut local = SyncState::new("laptop".to_string());
+        let mut peer = SyncState::new("phone".to_string());
+        for (state, content, version) in [(&mut local, &b"mine"[..], 1), (&mut peer, b"theirs", 2)]
+        {
+            let mut metadata = file("/doc.txt", content);
+            metadata.version = version;
+            state.apply_local(FileOperation::Create {
+                path: metadata.path.clone(),
+                metadata: Box::new(metadata),
+            });
+        }
+        (local, peer)
+    }
+
+    #[test]
+    fn conflict_copies_get_stable_names() {
+        let hash = hash_data(b"draft");
+        let file = FileMetadata::new_file(PathBuf::from("/notes/todo.md"), 5, hash, vec![hash]);
+        let copy = conflict_copy_path(&file);
+
+        assert_eq!(
+            copy,
+            PathBuf::from(format!("/notes/todo (conflict {}).md", &hash.to_hex()[..8]))
+        );
+        assert_eq!(conflict_copy_path(&file), copy);
+
+        let dir = FileMetadata::new_directory(PathBuf::from("/notes"));
+        assert_eq!(
+            ConflictCopy.resolve(&dir, &dir.clone()),
+            Resolution::LastWriterWins
+        );
+    }
+
+    #[test]
+    fn strategies_shape_the_merge() {
+        let doc = PathBuf::from("/doc.txt");
+        let theirs = hash_data(b"theirs");
+
+        let (mut local, peer) = diverged();
+        local.set_resolver(Arc::new(ConflictCopy));
+        let copies = local.merge_files(peer.list_files().into_iter().cloned());
+        assert_eq!(local.get(&doc).unwrap().content_hash, Some(theirs));
+        assert_eq!(copies.len(), 1);
+        assert_eq!(
+            local.get(&copies[0]).unwrap().content_hash,
+            Some(hash_data(b"mine"))
+        );
+        assert!(local.conflicts().is_empty());
+
+        let (mut local, peer) = diverged();
+        local.set_resolver(Arc::new(PreferLocal));
+        local.merge(&peer);
+        assert_eq!(
+            local.get(&doc).unwrap().content_hash,
+            Some(hash_data(b"mine"))
+        );
+        // The kept version now dominates the peer's, so the peer takes it
+        let mut peer = peer;
+        peer.merge(&local);
+        assert_eq!(peer.get(&doc), local.get(&doc));
+
+        let merged = file("/ignored", b"mine+theirs");
+        let (mut local, peer) = diverged();
+        local.set_resolver(Arc::new(CallbackResolver::new(move |_, _| {
+            Resolution::Merged(Box::new(merged.clone()))
+        })));
+        local.merge(&peer);
+        let kept = local.get(&doc).unwrap();
+        assert_eq!(kept.path, doc);
+        assert_eq!(kept.content_hash, Some(hash_data(b"mine+theirs")));
+        assert!(local.conflicts().is_empty());
+    }
+}
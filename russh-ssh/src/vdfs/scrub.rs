@@ -0,0 +1,159 @@
+//! Integrity Scrubbing
+//!
+//! Finds chunks that went bad at rest before a read trips over them.
+//!
+//! A scrub pass re-hashes every chunk in the store and checks that every
+//! chunk referenced by file metadata is present. With repair enabled,
+//! corrupt chunks are dropped and damaged chunks are fetched again from the
+//! [`ChunkSource`](super::ChunkSource), which verifies them before they are
+//! stored. The [`ScrubReport`] lists what was found, what was repaired and
+//! which files are still unreadable.
+//!
+//! # Requirements Coverage
+//! - Requirement 5.1: Content-addressed storage using BLAKE3
+
+use super::chunk::{ChunkId, ChunkStore};
+use crate::error::VdfsError;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Result of a scrub pass
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrubReport {
+    /// Whether damaged chunks were repaired
+    pub repair: bool,
+    /// Number of chunks re-hashed
+    pub scanned_chunks: usize,
+    /// Stored chunks whose content no longer matches their ID
+    pub corrupt: Vec<ChunkId>,
+    /// Chunks referenced by metadata but absent from the store
+    pub missing: Vec<ChunkId>,
+    /// Damaged chunks that were fetched again and verified
+    pub repaired: Vec<ChunkId>,
+    /// Referenced chunks that are still corrupt or missing
+    pub unrepaired: Vec<ChunkId>,
+    /// Current files that reference an unrepaired chunk
+    pub affected_files: Vec<PathBuf>,
+}
+
+impl ScrubReport {
+    /// Check whether no damage was found
+    pub fn is_clean(&self) -> bool {
+        self.corrupt.is_empty() && self.missing.is_empty()
+    }
+
+    /// Check whether every referenced chunk is intact after the pass
+    pub fn is_healthy(&self) -> bool {
+        self.unrepaired.is_empty()
+    }
+}
+
+impl ChunkStore {
+    /// Re-hash every stored chunk
+    ///
+    /// Returns the number of chunks checked and the IDs of those that fail
+    /// verification, including sealed chunks that no longer open.
+    pub async fn find_corrupt(&self) -> (usize, Vec<ChunkId>) {
+        let ids = self.list_ids().await;
+        let mut corrupt = Vec::new();
+        for id in &ids {
+            match self.get(id).await {
+                Ok(chunk) if chunk.verify() => {}
+                // Removed by a concurrent GC pass
+                Err(VdfsError::ChunkNotFound(_)) => {}
+                _ => corrupt.push(*id),
+            }
+        }
+        corrupt.sort_by_key(|id| *id.as_bytes());
+        (ids.len(), corrupt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdfs::chunk::Chunk;
+    use crate::vdfs::{ChunkSource, VirtualFs};
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    /// A peer holding good copies of some chunks
+    #[derive(Debug)]
+    struct Replica(HashMap<ChunkId, Chunk>);
+
+    #[async_trait]
+    impl ChunkSource for Replica {
+        async fn fetch(&self, id: &ChunkId) -> Result<Chunk, VdfsError> {
+            self.0
+                .get(id)
+                .cloned()
+                .ok_or_else(|| VdfsError::ChunkNotFound(id.to_hex()))
+        }
+    }
+
+    async fn rot(store: &ChunkStore, id: ChunkId) {
+        store.remove(&id).await;
+        store
+            .store(Chunk {
+                id,
+                data: b"bit rot".to_vec(),
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn rehash_finds_rotted_chunks() {
+        let store = ChunkStore::new();
+        let good = store.store_data(b"good".to_vec()).await.unwrap();
+        let bad = store.store_data(b"bad".to_vec()).await.unwrap();
+
+        rot(&store, bad).await;
+
+        let (scanned, corrupt) = store.find_corrupt().await;
+        assert_eq!(scanned, 2);
+        assert_eq!(corrupt, vec![bad]);
+        assert!(!corrupt.contains(&good));
+    }
+
+    #[tokio::test]
+    async fn scrub_repairs_damaged_chunks_from_a_replica() {
+        let fs = VirtualFs::with_chunk_size("test-node".to_string(), PathBuf::from("/vfs"), 4);
+        let doc = fs
+            .write(Path::new("doc.txt"), b"aaaabbbbcccc")
+            .await
+            .unwrap();
+        let good: HashMap<ChunkId, Chunk> = [&b"aaaa"[..], b"bbbb"]
+            .into_iter()
+            .map(|data| Chunk::new(data.to_vec()))
+            .map(|chunk| (chunk.id, chunk))
+            .collect();
+
+        rot(fs.chunk_store(), doc.chunks[0]).await;
+        fs.chunk_store().remove(&doc.chunks[1]).await;
+        fs.chunk_store().remove(&doc.chunks[2]).await;
+
+        let report = fs.scrub(false).await;
+        assert_eq!(report.corrupt, vec![doc.chunks[0]]);
+        assert_eq!(report.missing.len(), 2);
+        assert_eq!(report.affected_files, vec![doc.path.clone()]);
+        assert!(!report.is_clean());
+
+        // The replica lacks the third chunk, so the file stays damaged
+        let fs = fs.with_chunk_source(Arc::new(Replica(good)));
+        let report = fs.scrub(true).await;
+        assert_eq!(report.repaired.len(), 2);
+        assert_eq!(report.unrepaired, vec![doc.chunks[2]]);
+        assert!(!report.is_healthy());
+        assert_eq!(
+            fs.read_range(Path::new("doc.txt"), 0, 8).await.unwrap(),
+            b"aaaabbbb"
+        );
+
+        let report = fs.scrub(false).await;
+        assert!(report.corrupt.is_empty());
+        assert_eq!(report.missing, vec![doc.chunks[2]]);
+    }
+}
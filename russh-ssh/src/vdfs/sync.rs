@@ -2,14 +2,26 @@
 //!
 //! Implements conflict-free replicated data types for file synchronization.
 //!
+//! Versions are ordered by their
+//! [`VectorClock`](super::clock::VectorClock)s. When neither side's
+//! clock dominates, the edits were concurrent and the configured
+//! [`ConflictResolver`] decides. The default keeps the Last-Writer-Wins
+//! choice and records a [`Conflict`] so the losing version can be surfaced
+//! instead of silently discarded.
+//!
 //! # Requirements Coverage
 //! - Requirement 5.2: CRDT-based sync for conflict resolution
 
+use super::clock::Causality;
+use super::lock::{FileLock, LockTable};
 use super::metadata::FileMetadata;
+use super::resolver::{conflict_copy_path, ConflictResolver, Resolution};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Sync status for a file
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -24,6 +36,8 @@ pub enum SyncStatus {
     Conflict,
     /// File is being synced
     Syncing,
+    /// File is locked by another peer
+    Locked,
 }
 
 /// A file operation in the CRDT
@@ -82,10 +96,24 @@ impl TimestampedOp {
     }
 }
 
+/// Concurrent edits of the same path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Conflict {
+    /// Path of the conflicting file
+    pub path: PathBuf,
+    /// Version kept by Last-Writer-Wins
+    pub winner: FileMetadata,
+    /// Concurrent version that lost
+    pub loser: FileMetadata,
+    /// When the conflict was detected
+    pub detected_at: DateTime<Utc>,
+}
+
 /// CRDT state for file synchronization
 ///
-/// Uses a Last-Writer-Wins (LWW) strategy with logical clocks
-/// for conflict resolution.
+/// Orders versions by vector clock and hands concurrent edits to a
+/// [`ConflictResolver`]; by default they are settled by Last-Writer-Wins
+/// (LWW) and recorded as conflicts.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SyncState {
     /// Current file states (path -> metadata)
@@ -98,6 +126,15 @@ pub struct SyncState {
     node_id: String,
     /// Sync status per file
     status: HashMap<PathBuf, SyncStatus>,
+    /// Unresolved concurrent edits per file
+    #[serde(default)]
+    conflicts: HashMap<PathBuf, Conflict>,
+    /// Advisory locks held by this node and its peers
+    #[serde(default)]
+    locks: LockTable,
+    /// Strategy for concurrent edits; LWW with recorded conflicts if unset
+    #[serde(skip)]
+    resolver: Option<Arc<dyn ConflictResolver>>,
 }
 
 impl SyncState {
@@ -109,9 +146,17 @@ impl SyncState {
             clock: 0,
             node_id,
             status: HashMap::new(),
+            conflicts: HashMap::new(),
+            locks: LockTable::new(),
+            resolver: None,
         }
     }
 
+    /// Set the strategy for concurrent edits
+    pub fn set_resolver(&mut self, resolver: Arc<dyn ConflictResolver>) {
+        self.resolver = Some(resolver);
+    }
+
     /// Get the current logical clock value
     pub fn clock(&self) -> u64 {
         self.clock
@@ -123,13 +168,40 @@ impl SyncState {
     }
 
     /// Apply a local operation
+    ///
+    /// Created and updated files get the current clock of the path
+    /// advanced by this node, so they supersede every version seen so far.
     pub fn apply_local(&mut self, op: FileOperation) {
         self.clock += 1;
-        let timestamped = TimestampedOp::new(op.clone(), self.node_id.clone(), self.clock);
+        let op = match op {
+            FileOperation::Create { path, metadata } => FileOperation::Create {
+                metadata: self.advance_clock(&path, metadata),
+                path,
+            },
+            FileOperation::Update { path, metadata } => FileOperation::Update {
+                metadata: self.advance_clock(&path, metadata),
+                path,
+            },
+            other => other,
+        };
+        let timestamped = TimestampedOp::new(op, self.node_id.clone(), self.clock);
         self.apply_operation(&timestamped);
         self.operations.push(timestamped);
     }
 
+    /// Stamp metadata with the path's clock plus a local event
+    fn advance_clock(&self, path: &PathBuf, mut metadata: Box<FileMetadata>) -> Box<FileMetadata> {
+        let mut clock = self
+            .files
+            .get(path)
+            .map(|existing| existing.clock.clone())
+            .unwrap_or_default();
+        clock.merge(&metadata.clock);
+        clock.increment(&self.node_id);
+        metadata.clock = clock;
+        metadata
+    }
+
     /// Apply a remote operation
     pub fn apply_remote(&mut self, op: TimestampedOp) {
         // Update clock to be at least as high as the remote clock
@@ -141,27 +213,15 @@ impl SyncState {
     /// Apply an operation to the state
     fn apply_operation(&mut self, op: &TimestampedOp) {
         match &op.op {
-            FileOperation::Create { path, metadata } => {
-                self.files.insert(path.clone(), *metadata.clone());
-                self.status.insert(path.clone(), SyncStatus::Synced);
-            }
-            FileOperation::Update { path, metadata } => {
-                if let Some(existing) = self.files.get(path) {
-                    // LWW: Only apply if newer
-                    if metadata.version > existing.version
-                        || (metadata.version == existing.version
-                            && op.timestamp > existing.modified)
-                    {
-                        self.files.insert(path.clone(), *metadata.clone());
-                    }
-                } else {
-                    self.files.insert(path.clone(), *metadata.clone());
+            FileOperation::Create { path, metadata } | FileOperation::Update { path, metadata } => {
+                if !self.merge_file(*metadata.clone(), &mut Vec::new()) {
+                    self.status.insert(path.clone(), SyncStatus::Synced);
                 }
-                self.status.insert(path.clone(), SyncStatus::Synced);
             }
             FileOperation::Delete { path } => {
                 self.files.remove(path);
                 self.status.remove(path);
+                self.conflicts.remove(path);
             }
             FileOperation::Move { from, to } => {
                 if let Some(metadata) = self.files.remove(from) {
@@ -186,22 +246,8 @@ impl SyncState {
         self.clock = self.clock.max(other.clock) + 1;
 
         // Merge files using LWW
-        for (path, other_meta) in &other.files {
-            match self.files.get(path) {
-                Some(self_meta) => {
-                    // LWW: Keep the one with higher version, or later timestamp if same version
-                    if other_meta.version > self_meta.version
-                        || (other_meta.version == self_meta.version
-                            && other_meta.modified > self_meta.modified)
-                    {
-                        self.files.insert(path.clone(), other_meta.clone());
-                    }
-                }
-                None => {
-                    self.files.insert(path.clone(), other_meta.clone());
-                }
-            }
-        }
+        self.merge_files(other.files.values().cloned());
+        self.locks.merge(&other.locks, Utc::now());
 
         // Merge operations (deduplicate by timestamp + node_id)
         for op in &other.operations {
@@ -222,6 +268,117 @@ impl SyncState {
         });
     }
 
+    /// Merge individual file entries
+    ///
+    /// Used after a [`Negotiation`](super::merkle::Negotiation) has narrowed
+    /// the exchange down to the files that differ. Returns the paths of
+    /// conflict copies created while resolving concurrent edits.
+    pub fn merge_files(&mut self, files: impl IntoIterator<Item = FileMetadata>) -> Vec<PathBuf> {
+        let mut copies = Vec::new();
+        for other_meta in files {
+            self.merge_file(other_meta, &mut copies);
+        }
+        copies
+    }
+
+    /// Merge one file entry, returning whether it conflicted
+    fn merge_file(&mut self, other_meta: FileMetadata, copies: &mut Vec<PathBuf>) -> bool {
+        let Some(self_meta) = self.files.get(&other_meta.path) else {
+            self.files.insert(other_meta.path.clone(), other_meta);
+            return false;
+        };
+
+        match self_meta.clock.compare(&other_meta.clock) {
+            Causality::Before => {
+                self.files.insert(other_meta.path.clone(), other_meta);
+                false
+            }
+            Causality::After => false,
+            // Same history (or no clocks at all): plain LWW
+            Causality::Equal => {
+                if lww_newer(&other_meta, self_meta) {
+                    self.files.insert(other_meta.path.clone(), other_meta);
+                }
+                false
+            }
+            Causality::Concurrent => {
+                let local = self_meta.clone();
+                let remote = other_meta;
+                let resolution = match &self.resolver {
+                    Some(resolver) => resolver.resolve(&local, &remote),
+                    None => Resolution::LastWriterWins,
+                };
+                // Whatever is kept supersedes both versions
+                let mut clock = local.clock.clone();
+                clock.merge(&remote.clock);
+                let path = local.path.clone();
+                let lww = |local: FileMetadata, remote: FileMetadata| {
+                    if lww_newer(&remote, &local) {
+                        (remote, local)
+                    } else {
+                        (local, remote)
+                    }
+                };
+
+                let mut kept = match resolution {
+                    Resolution::LastWriterWins => {
+                        let (mut winner, loser) = lww(local, remote);
+                        winner.clock = clock;
+                        self.conflicts.insert(
+                            path.clone(),
+                            Conflict {
+                                path: path.clone(),
+                                winner: winner.clone(),
+                                loser,
+                                detected_at: Utc::now(),
+                            },
+                        );
+                        self.status.insert(path.clone(), SyncStatus::Conflict);
+                        self.files.insert(path, winner);
+                        return true;
+                    }
+                    Resolution::KeepLocal => local,
+                    Resolution::TakeRemote => remote,
+                    Resolution::KeepBoth => {
+                        let (winner, mut loser) = lww(local, remote);
+                        loser.path = conflict_copy_path(&loser);
+                        if !self.files.contains_key(&loser.path) {
+                            copies.push(loser.path.clone());
+                        }
+                        self.merge_file(loser, copies);
+                        winner
+                    }
+                    Resolution::Merged(merged) => FileMetadata {
+                        path: path.clone(),
+                        ..*merged
+                    },
+                };
+                kept.clock = clock;
+                self.files.insert(path, kept);
+                false
+            }
+        }
+    }
+
+    /// Unresolved conflicts
+    pub fn conflicts(&self) -> Vec<&Conflict> {
+        self.conflicts.values().collect()
+    }
+
+    /// Get the conflict recorded for a path
+    pub fn conflict(&self, path: &PathBuf) -> Option<&Conflict> {
+        self.conflicts.get(path)
+    }
+
+    /// Mark a conflict as resolved, returning it
+    pub fn resolve_conflict(&mut self, path: &PathBuf) -> Option<Conflict> {
+        let conflict = self.conflicts.remove(path)?;
+        if self.files.contains_key(path) {
+            self.status.insert(path.clone(), SyncStatus::Synced);
+        }
+        Some(conflict)
+    }
+
     /// Get file metadata by path
     pub fn get(&self, path: &PathBuf) -> Option<&FileMetadata> {
         self.files.get(path)
@@ -232,9 +389,50 @@ impl SyncState {
         self.files.values().collect()
     }
 
+    /// Get the number of entries
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Check if there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
     /// Get sync status for a file
+    ///
+    /// A live lock held by another node is reported as
+    /// [`SyncStatus::Locked`] unless the file is in conflict.
     pub fn get_status(&self, path: &PathBuf) -> SyncStatus {
-        self.status.get(path).copied().unwrap_or(SyncStatus::Synced)
+        let status = self.status.get(path).copied().unwrap_or(SyncStatus::Synced);
+        match self.locks.get(path, Utc::now()) {
+            Some(lock) if status != SyncStatus::Conflict && !lock.is_owned_by(&self.node_id) => {
+                SyncStatus::Locked
+            }
+            _ => status,
+        }
+    }
+
+    /// Take or renew an advisory lock on a path for this node
+    ///
+    /// Returns the current holder as the error if another node holds it.
+    pub fn lock(&mut self, path: &Path, lease: Duration) -> Result<FileLock, FileLock> {
+        self.locks.acquire(path, &self.node_id, lease, Utc::now())
+    }
+
+    /// Release this node's lock on a path
+    pub fn unlock(&mut self, path: &Path) -> bool {
+        self.locks.release(path, &self.node_id, Utc::now())
+    }
+
+    /// Get the live lock on a path
+    pub fn lock_on(&self, path: &Path) -> Option<&FileLock> {
+        self.locks.get(path, Utc::now())
+    }
+
+    /// All live locks
+    pub fn locks(&self) -> Vec<&FileLock> {
+        self.locks.live(Utc::now())
     }
 
     /// Set sync status for a file
@@ -251,6 +449,11 @@ impl SyncState {
     }
 }
 
+/// Whether `a` wins over `b` under Last-Writer-Wins
+fn lww_newer(a: &FileMetadata, b: &FileMetadata) -> bool {
+    a.version > b.version || (a.version == b.version && a.modified > b.modified)
+}
+
 /// Sync engine for coordinating synchronization
 pub struct SyncEngine {
     state: SyncState,
@@ -264,6 +467,17 @@ impl SyncEngine {
         }
     }
 
+    /// Resolve concurrent edits with `resolver`
+    pub fn with_resolver(mut self, resolver: Arc<dyn ConflictResolver>) -> Self {
+        self.state.set_resolver(resolver);
+        self
+    }
+
+    /// Change the strategy for concurrent edits
+    pub fn set_resolver(&mut self, resolver: Arc<dyn ConflictResolver>) {
+        self.state.set_resolver(resolver);
+    }
+
     /// Get the current state
     pub fn state(&self) -> &SyncState {
         &self.state
@@ -381,6 +595,88 @@ mod tests {
         assert_eq!(state.list_files().len(), 1);
     }
 
+    #[test]
+    fn sequential_edits_do_not_conflict() {
+        let mut state1 = SyncState::new("node1".to_string());
+        state1.apply_local(FileOperation::Create {
+            path: PathBuf::from("/doc.txt"),
+            metadata: Box::new(create_test_metadata("/doc.txt")),
+        });
+
+        let mut state2 = SyncState::new("node2".to_string());
+        state2.merge(&state1);
+        let mut edited = create_test_metadata("/doc.txt");
+        edited.size = 200;
+        state2.apply_local(FileOperation::Update {
+            path: PathBuf::from("/doc.txt"),
+            metadata: Box::new(edited),
+        });
+
+        // node2's edit saw node1's, so it supersedes it despite equal versions
+        state1.merge(&state2);
+        let doc = PathBuf::from("/doc.txt");
+        assert_eq!(state1.get(&doc).unwrap().size, 200);
+        assert!(state1.conflicts().is_empty());
+        assert_eq!(state1.get_status(&doc), SyncStatus::Synced);
+    }
+
+    #[test]
+    fn concurrent_edits_are_recorded_as_conflicts() {
+        let doc = PathBuf::from("/doc.txt");
+        let mut state1 = SyncState::new("node1".to_string());
+        state1.apply_local(FileOperation::Create {
+            path: doc.clone(),
+            metadata: Box::new(create_test_metadata("/doc.txt")),
+        });
+        let mut state2 = SyncState::new("node2".to_string());
+        let mut other = create_test_metadata("/doc.txt");
+        other.version = 2;
+        state2.apply_local(FileOperation::Create {
+            path: doc.clone(),
+            metadata: Box::new(other),
+        });
+
+        state1.merge(&state2);
+
+        let conflict = state1.conflict(&doc).unwrap();
+        assert_eq!(conflict.winner.version, 2);
+        assert_eq!(conflict.loser.version, 1);
+        assert_eq!(state1.get(&doc).unwrap().version, 2);
+        assert_eq!(state1.get_status(&doc), SyncStatus::Conflict);
+
+        // The merged clock dominates both sides, so re-merging is quiet
+        let resolved = state1.resolve_conflict(&doc).unwrap();
+        assert_eq!(resolved.path, doc);
+        state1.merge(&state2);
+        assert!(state1.conflicts().is_empty());
+        assert_eq!(state1.get_status(&doc), SyncStatus::Synced);
+    }
+
+    #[test]
+    fn peer_locks_surface_in_status() {
+        let doc = PathBuf::from("/doc.bin");
+        let mut state1 = SyncState::new("node1".to_string());
+        let mut state2 = SyncState::new("node2".to_string());
+
+        state1.lock(&doc, Duration::from_secs(60)).unwrap();
+        assert_eq!(state1.get_status(&doc), SyncStatus::Synced);
+
+        state2.merge(&state1);
+        assert_eq!(state2.get_status(&doc), SyncStatus::Locked);
+        assert_eq!(
+            state2
+                .lock(&doc, Duration::from_secs(60))
+                .unwrap_err()
+                .owner,
+            "node1"
+        );
+
+        state1.unlock(&doc);
+        state2.merge(&state1);
+        assert_eq!(state2.get_status(&doc), SyncStatus::Synced);
+        assert!(state2.lock(&doc, Duration::from_secs(60)).is_ok());
+    }
+
     #[test]
     fn sync_engine_workflow() {
         let mut engine = SyncEngine::new("node1".to_string());
@@ -0,0 +1,459 @@
+//! Filesystem Watcher Driven Sync
+//!
+//! Observes a local directory with `notify` and mirrors changes into a
+//! [`VirtualFs`], so edits made by ordinary tools become sync operations
+//! without manual calls.
+//!
+//! Raw events are only used as hints: bursts are debounced, and when a batch
+//! is applied each touched path is reconciled against what is on disk now.
+//! Editors that save via rename-and-replace therefore produce one update
+//! rather than a create/delete/create sequence.
+//!
+//! # Requirements Coverage
+//! - Requirement 5.2: CRDT-based sync for conflict resolution
+//! - Requirement 5.3: Virtual filesystem interface
+
+use super::chunk::{chunk_data, Chunk, ChunkPipeline};
+use super::filesystem::VirtualFs;
+use crate::encryption::hash::{hash_data, ContentHash};
+use crate::error::VdfsError;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Default quiet period before a burst of events is applied
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Default upper bound on how long a continuous burst can delay a batch
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Capacity of the change subscriber channel
+const CHANGE_CAPACITY: usize = 64;
+
+/// Files at least this large are chunked on a [`ChunkPipeline`]
+const PIPELINE_MIN_SIZE: u64 = 8 * 1024 * 1024;
+
+/// A change applied to the virtual filesystem
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchChange {
+    /// File content was written (created or modified)
+    Written(PathBuf),
+    /// Directory was created
+    DirectoryCreated(PathBuf),
+    /// File or directory was removed
+    Removed(PathBuf),
+}
+
+impl WatchChange {
+    /// Virtual path affected by the change
+    pub fn path(&self) -> &Path {
+        match self {
+            WatchChange::Written(path)
+            | WatchChange::DirectoryCreated(path)
+            | WatchChange::Removed(path) => path,
+        }
+    }
+}
+
+/// Watcher configuration
+#[derive(Debug, Clone)]
+pub struct WatchConfig {
+    /// Quiet period before a burst of events is applied
+    pub debounce: Duration,
+    /// Upper bound on how long a continuous burst can delay a batch
+    pub max_delay: Duration,
+    /// Import the directory's current contents when the watcher starts
+    pub initial_scan: bool,
+    /// Skip files and directories whose name starts with a dot
+    pub ignore_hidden: bool,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            debounce: DEFAULT_DEBOUNCE,
+            max_delay: DEFAULT_MAX_DELAY,
+            initial_scan: true,
+            ignore_hidden: true,
+        }
+    }
+}
+
+/// Mirrors a local directory into a virtual filesystem
+pub struct DirWatcher {
+    fs: Arc<VirtualFs>,
+    root: PathBuf,
+    config: WatchConfig,
+}
+
+impl DirWatcher {
+    /// Create a watcher for `root` feeding `fs`
+    pub fn new(fs: Arc<VirtualFs>, root: impl Into<PathBuf>) -> Self {
+        Self {
+            fs,
+            root: root.into(),
+            config: WatchConfig::default(),
+        }
+    }
+
+    /// Set the watcher configuration
+    pub fn with_config(mut self, config: WatchConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Start watching
+    ///
+    /// Watching stops when the returned handle is dropped or
+    /// [`WatchHandle::stop`] is called.
+    pub async fn start(self) -> Result<WatchHandle, VdfsError> {
+        let root = tokio::fs::canonicalize(&self.root).await?;
+        let (raw_tx, raw_rx) = mpsc::unbounded_channel();
+
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // The receiver only goes away once the handle is dropped
+            let _ = raw_tx.send(event);
+        })
+        .map_err(|e| VdfsError::Watch(e.to_string()))?;
+        watcher
+            .watch(&root, RecursiveMode::Recursive)
+            .map_err(|e| VdfsError::Watch(e.to_string()))?;
+
+        let (changes, _) = broadcast::channel(CHANGE_CAPACITY);
+        let sync = Reconciler {
+            fs: self.fs,
+            root,
+            ignore_hidden: self.config.ignore_hidden,
+            changes: changes.clone(),
+        };
+        let task = tokio::spawn(sync.run(raw_rx, self.config));
+
+        Ok(WatchHandle {
+            _watcher: watcher,
+            task,
+            changes,
+        })
+    }
+
+    /// Mirror the directory's current contents once, without watching
+    ///
+    /// Returns the changes applied.
+    pub async fn scan(&self) -> Result<Vec<WatchChange>, VdfsError> {
+        let root = tokio::fs::canonicalize(&self.root).await?;
+        let (changes, _) = broadcast::channel(1);
+        let sync = Reconciler {
+            fs: self.fs.clone(),
+            root,
+            ignore_hidden: self.config.ignore_hidden,
+            changes,
+        };
+        let paths = sync.scan().await;
+        Ok(sync.apply(paths).await)
+    }
+}
+
+/// Handle to a running watcher
+pub struct WatchHandle {
+    _watcher: RecommendedWatcher,
+    task: JoinHandle<()>,
+    changes: broadcast::Sender<Vec<WatchChange>>,
+}
+
+impl WatchHandle {
+    /// Subscribe to batches of applied changes
+    ///
+    /// Each batch is sent after its operations have been recorded on the
+    /// sync engine and its chunks stored, so a subscriber can push the new
+    /// operations and chunks to peers straight away.
+    pub fn subscribe(&self) -> broadcast::Receiver<Vec<WatchChange>> {
+        self.changes.subscribe()
+    }
+
+    /// Stop watching
+    pub fn stop(self) {
+        drop(self);
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Applies debounced batches of touched paths to the virtual filesystem
+struct Reconciler {
+    fs: Arc<VirtualFs>,
+    root: PathBuf,
+    ignore_hidden: bool,
+    changes: broadcast::Sender<Vec<WatchChange>>,
+}
+
+impl Reconciler {
+    async fn run(
+        self,
+        mut events: mpsc::UnboundedReceiver<notify::Result<notify::Event>>,
+        config: WatchConfig,
+    ) {
+        if config.initial_scan {
+            let paths = self.scan().await;
+            self.publish(self.apply(paths).await);
+        }
+
+        let mut pending: HashSet<PathBuf> = HashSet::new();
+        let mut batch_started = Instant::now();
+
+        loop {
+            let event = if pending.is_empty() {
+                events.recv().await
+            } else {
+                let deadline =
+                    (Instant::now() + config.debounce).min(batch_started + config.max_delay);
+                match tokio::time::timeout_at(deadline, events.recv()).await {
+                    Ok(event) => event,
+                    Err(_) => {
+                        self.publish(self.apply(std::mem::take(&mut pending)).await);
+                        continue;
+                    }
+                }
+            };
+
+            match event {
+                Some(Ok(event)) => {
+                    if pending.is_empty() {
+                        batch_started = Instant::now();
+                    }
+                    pending.extend(event.paths);
+                }
+                Some(Err(e)) => tracing::warn!("VDFS watcher error: {}", e),
+                None => break,
+            }
+        }
+
+        if !pending.is_empty() {
+            self.publish(self.apply(pending).await);
+        }
+    }
+
+    /// List every path under the root
+    async fn scan(&self) -> HashSet<PathBuf> {
+        let mut found = HashSet::new();
+        let mut dirs = vec![self.root.clone()];
+        while let Some(dir) = dirs.pop() {
+            let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+                continue;
+            };
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let path = entry.path();
+                if entry.file_type().await.map(|t| t.is_dir()).unwrap_or(false) {
+                    dirs.push(path.clone());
+                }
+                found.insert(path);
+            }
+        }
+        found
+    }
+
+    async fn apply(&self, paths: HashSet<PathBuf>) -> Vec<WatchChange> {
+        // Parents sort before their children
+        let mut paths: Vec<PathBuf> = paths.into_iter().collect();
+        paths.sort();
+
+        let mut applied = Vec::new();
+        for local in paths {
+            let Some(relative) = self.relative(&local) else {
+                continue;
+            };
+            match self.reconcile(&local, &relative).await {
+                Ok(changes) => applied.extend(changes),
+                Err(e) => tracing::warn!("Failed to sync {}: {}", local.display(), e),
+            }
+        }
+        applied
+    }
+
+    fn publish(&self, applied: Vec<WatchChange>) {
+        if !applied.is_empty() {
+            // No subscribers is fine
+            let _ = self.changes.send(applied);
+        }
+    }
+
+    /// Map a local path to a path relative to the watched root
+    fn relative(&self, local: &Path) -> Option<PathBuf> {
+        let relative = local.strip_prefix(&self.root).ok()?;
+        if relative.as_os_str().is_empty() {
+            return None;
+        }
+        let hidden = relative
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+        if self.ignore_hidden && hidden {
+            return None;
+        }
+        Some(relative.to_path_buf())
+    }
+
+    /// Bring one virtual path in line with the local disk
+    async fn reconcile(
+        &self,
+        local: &Path,
+        relative: &Path,
+    ) -> Result<Vec<WatchChange>, VdfsError> {
+        let virtual_path = self.fs.mount_point().join(relative);
+        let on_disk = match tokio::fs::symlink_metadata(local).await {
+            Ok(metadata) => Some(metadata),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        let current = self.fs.stat(relative).await.ok();
+
+        match on_disk {
+            Some(metadata) if metadata.is_file() => {
+                let chunk_size = self.fs.chunk_store().chunk_size();
+                let (chunks, content_hash) =
+                    match chunk_local_file(local, metadata.len(), chunk_size).await {
+                        Ok(chunked) => chunked,
+                        // Removed again before we got to it; a later event covers it
+                        Err(VdfsError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                            return Ok(Vec::new())
+                        }
+                        Err(e) => return Err(e),
+                    };
+                let unchanged = current
+                    .as_ref()
+                    .is_some_and(|m| m.is_file() && m.content_hash == Some(content_hash));
+                if unchanged {
+                    return Ok(Vec::new());
+                }
+                let size = chunks.iter().map(|c| c.size() as u64).sum();
+                self.fs
+                    .write_chunks(relative, &[], chunks, size, content_hash)
+                    .await?;
+                Ok(vec![WatchChange::Written(virtual_path)])
+            }
+            Some(metadata) if metadata.is_dir() => {
+                if current.is_some_and(|m| m.is_directory()) {
+                    return Ok(Vec::new());
+                }
+                self.fs.mkdir(relative).await?;
+                Ok(vec![WatchChange::DirectoryCreated(virtual_path)])
+            }
+            // Symlinks and special files are not mirrored
+            Some(_) => Ok(Vec::new()),
+            None => self.remove_tree(&virtual_path).await,
+        }
+    }
+
+    /// Remove a virtual path and everything below it
+    async fn remove_tree(&self, virtual_path: &Path) -> Result<Vec<WatchChange>, VdfsError> {
+        let mut doomed: Vec<PathBuf> = {
+            let sync = self.fs.sync_engine().read().await;
+            sync.state()
+                .list_files()
+                .into_iter()
+                .map(|m| m.path.clone())
+                .filter(|p| p.starts_with(virtual_path))
+                .collect()
+        };
+        // Children before parents
+        doomed.sort_by(|a, b| b.cmp(a));
+
+        let mut removed = Vec::with_capacity(doomed.len());
+        for path in doomed {
+            self.fs.delete(&path).await?;
+            removed.push(WatchChange::Removed(path));
+        }
+        Ok(removed)
+    }
+}
+
+/// Chunk and hash a local file, on a [`ChunkPipeline`] when it is large
+async fn chunk_local_file(
+    local: &Path,
+    size: u64,
+    chunk_size: usize,
+) -> Result<(Vec<Chunk>, ContentHash), VdfsError> {
+    if size >= PIPELINE_MIN_SIZE {
+        return ChunkPipeline::new(chunk_size)
+            .start_file(local)?
+            .collect()
+            .await;
+    }
+    let data = tokio::fs::read(local).await?;
+    Ok((chunk_data(&data, chunk_size), hash_data(&data)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quick() -> WatchConfig {
+        WatchConfig {
+            debounce: Duration::from_millis(50),
+            ..WatchConfig::default()
+        }
+    }
+
+    async fn next_batch(rx: &mut broadcast::Receiver<Vec<WatchChange>>) -> Vec<WatchChange> {
+        tokio::time::timeout(Duration::from_secs(10), rx.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn initial_scan_imports_existing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("docs")).unwrap();
+        std::fs::write(dir.path().join("docs/a.txt"), b"alpha").unwrap();
+        std::fs::write(dir.path().join(".hidden"), b"skip").unwrap();
+
+        let fs = Arc::new(VirtualFs::new("node".into(), PathBuf::from("/vfs")));
+        let handle = DirWatcher::new(fs.clone(), dir.path())
+            .with_config(quick())
+            .start()
+            .await
+            .unwrap();
+        let mut rx = handle.subscribe();
+
+        // The scan may already have run before we subscribed
+        if !fs.exists(Path::new("docs/a.txt")).await {
+            next_batch(&mut rx).await;
+        }
+        assert_eq!(fs.read(Path::new("docs/a.txt")).await.unwrap(), b"alpha");
+        assert!(fs.stat(Path::new("docs")).await.unwrap().is_directory());
+        assert!(!fs.exists(Path::new(".hidden")).await);
+    }
+
+    #[tokio::test]
+    async fn changes_on_disk_become_operations() {
+        let dir = tempfile::tempdir().unwrap();
+        let fs = Arc::new(VirtualFs::new("node".into(), PathBuf::from("/vfs")));
+        let handle = DirWatcher::new(fs.clone(), dir.path())
+            .with_config(quick())
+            .start()
+            .await
+            .unwrap();
+        let mut rx = handle.subscribe();
+
+        std::fs::write(dir.path().join("note.txt"), b"v1").unwrap();
+        let mut batch = next_batch(&mut rx).await;
+        while !batch.contains(&WatchChange::Written(PathBuf::from("/vfs/note.txt"))) {
+            batch = next_batch(&mut rx).await;
+        }
+        assert_eq!(fs.read(Path::new("note.txt")).await.unwrap(), b"v1");
+
+        std::fs::remove_file(dir.path().join("note.txt")).unwrap();
+        let mut batch = next_batch(&mut rx).await;
+        while !batch.contains(&WatchChange::Removed(PathBuf::from("/vfs/note.txt"))) {
+            batch = next_batch(&mut rx).await;
+        }
+        assert!(!fs.exists(Path::new("note.txt")).await);
+    }
+}
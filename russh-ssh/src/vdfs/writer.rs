@@ -0,0 +1,190 @@
+//! Incremental Writes
+//!
+//! [`VdfsWriter`] builds a file from data that arrives over time, such as a
+//! recording. Data is cut into chunks as it comes in and whole chunks are
+//! committed to the file every [`DEFAULT_COMMIT_THRESHOLD`] bytes, so the
+//! file can be read and synced while it grows. Each commit is a new version
+//! of the file that shares the earlier chunks; only the partial last chunk
+//! waits in memory until [`finish`](VdfsWriter::finish).
+//!
+//! # Requirements Coverage
+//! - Requirement 5.3: Virtual filesystem interface
+//! - Requirement 5.4: Deterministic chunking
+
+use super::chunk::{Chunk, ChunkId};
+use super::filesystem::VirtualFs;
+use super::metadata::FileMetadata;
+use crate::error::VdfsError;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Default number of bytes buffered before whole chunks are committed
+pub const DEFAULT_COMMIT_THRESHOLD: usize = 1024 * 1024;
+
+/// Writes a VDFS file piece by piece
+pub struct VdfsWriter {
+    fs: Arc<VirtualFs>,
+    path: PathBuf,
+    chunk_size: usize,
+    commit_threshold: usize,
+    /// Chunks of the committed part of the file
+    committed: Vec<ChunkId>,
+    /// Hash state of the committed part
+    hasher: blake3::Hasher,
+    /// Size of the committed part
+    size: u64,
+    /// Data not committed yet
+    pending: Vec<u8>,
+    metadata: FileMetadata,
+}
+
+impl VdfsWriter {
+    /// Create or truncate a file and start writing to it
+    pub async fn create(fs: Arc<VirtualFs>, path: &Path) -> Result<Self, VdfsError> {
+        let metadata = fs.write(path, &[]).await?;
+        let chunk_size = fs.chunk_store().chunk_size();
+        Ok(Self {
+            fs,
+            path: metadata.path.clone(),
+            chunk_size,
+            commit_threshold: DEFAULT_COMMIT_THRESHOLD.max(chunk_size),
+            committed: Vec::new(),
+            hasher: blake3::Hasher::new(),
+            size: 0,
+            pending: Vec::new(),
+            metadata,
+        })
+    }
+
+    /// Commit whole chunks once `bytes` are buffered
+    ///
+    /// Rounded up to a whole chunk.
+    pub fn with_commit_threshold(mut self, bytes: usize) -> Self {
+        self.commit_threshold = bytes.max(self.chunk_size);
+        self
+    }
+
+    /// Path of the file being written
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Bytes written so far, committed or not
+    pub fn len(&self) -> u64 {
+        self.size + self.pending.len() as u64
+    }
+
+    /// Check if nothing was written yet
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Metadata of the last committed version
+    pub fn metadata(&self) -> &FileMetadata {
+        &self.metadata
+    }
+
+    /// Append data, committing whole chunks past the threshold
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), VdfsError> {
+        self.pending.extend_from_slice(data);
+        if self.pending.len() >= self.commit_threshold {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Commit every whole chunk written so far
+    ///
+    /// Less than a chunk stays buffered.
+    pub async fn flush(&mut self) -> Result<(), VdfsError> {
+        let whole = self.pending.len() / self.chunk_size * self.chunk_size;
+        if whole == 0 {
+            return Ok(());
+        }
+        let rest = self.pending.split_off(whole);
+        let data = std::mem::replace(&mut self.pending, rest);
+        self.commit(data).await
+    }
+
+    /// Commit everything written and close the file
+    pub async fn finish(mut self) -> Result<FileMetadata, VdfsError> {
+        let data = std::mem::take(&mut self.pending);
+        if !data.is_empty() {
+            self.commit(data).await?;
+        }
+        Ok(self.metadata)
+    }
+
+    async fn commit(&mut self, data: Vec<u8>) -> Result<(), VdfsError> {
+        let chunks: Vec<Chunk> = data
+            .chunks(self.chunk_size)
+            .map(|piece| Chunk::new(piece.to_vec()))
+            .collect();
+        let ids: Vec<ChunkId> = chunks.iter().map(|chunk| chunk.id).collect();
+
+        let mut hasher = self.hasher.clone();
+        hasher.update(&data);
+        let size = self.size + data.len() as u64;
+        let metadata = self
+            .fs
+            .write_chunks(
+                &self.path,
+                &self.committed,
+                chunks,
+                size,
+                hasher.finalize().into(),
+            )
+            .await?;
+
+        self.committed.extend(ids);
+        self.hasher = hasher;
+        self.size = size;
+        self.metadata = metadata;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for VdfsWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VdfsWriter")
+            .field("path", &self.path)
+            .field("committed", &self.size)
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vdfs::chunk::ChunkStore;
+
+    #[tokio::test]
+    async fn grows_the_file_chunk_by_chunk() {
+        let fs = Arc::new(VirtualFs::with_chunk_store(
+            "node".to_string(),
+            PathBuf::from("/vdfs"),
+            ChunkStore::with_chunk_size(4),
+        ));
+        let path = Path::new("/vdfs/rec.bin");
+        let mut writer = VdfsWriter::create(fs.clone(), path)
+            .await
+            .unwrap()
+            .with_commit_threshold(8);
+        assert_eq!(fs.stat(path).await.unwrap().size, 0);
+
+        writer.write(b"abcde").await.unwrap();
+        assert_eq!(fs.stat(path).await.unwrap().size, 0);
+        writer.write(b"fghij").await.unwrap();
+        // Two whole chunks are visible, the rest is buffered
+        assert_eq!(fs.read(path).await.unwrap(), b"abcdefgh");
+        assert_eq!(writer.len(), 10);
+
+        writer.write(b"k").await.unwrap();
+        let metadata = writer.finish().await.unwrap();
+        assert_eq!(metadata.size, 11);
+        assert_eq!(metadata.chunks.len(), 3);
+        // The content hash covers the whole file, so full reads verify
+        assert_eq!(fs.read(path).await.unwrap(), b"abcdefghijk");
+    }
+}
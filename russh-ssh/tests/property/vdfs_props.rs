@@ -28,8 +28,8 @@ proptest! {
             let store = ChunkStore::new();
 
             // Store the same data twice
-            let id1 = store.store_data(data.clone()).await;
-            let id2 = store.store_data(data.clone()).await;
+            let id1 = store.store_data(data.clone()).await.unwrap();
+            let id2 = store.store_data(data.clone()).await.unwrap();
 
             // IDs should be identical (deterministic)
             prop_assert_eq!(
@@ -93,8 +93,8 @@ proptest! {
         rt.block_on(async {
             let store = ChunkStore::new();
 
-            let id1 = store.store_data(data1).await;
-            let id2 = store.store_data(data2).await;
+            let id1 = store.store_data(data1).await.unwrap();
+            let id2 = store.store_data(data2).await.unwrap();
 
             prop_assert_ne!(
                 id1, id2,